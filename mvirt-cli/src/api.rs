@@ -0,0 +1,208 @@
+//! Minimal REST client for the mvirt API server.
+//!
+//! Everything else in the CLI talks gRPC to the node-local daemons; this is
+//! only used for cluster-level resources that live in the API server
+//! (flavors, project SSH keys) and for consoles of VMs on other nodes.
+
+use serde::{Deserialize, Serialize};
+
+pub struct ApiClient {
+    base: String,
+    token: Option<String>,
+    http: reqwest::Client,
+}
+
+/// Flavor as served by `GET /v1/flavors/{name}`.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Flavor {
+    pub name: String,
+    pub vcpus: u32,
+    pub memory_mb: u64,
+    #[serde(default)]
+    pub network: Option<String>,
+    #[serde(default)]
+    pub user_data: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct FlavorList {
+    flavors: Vec<Flavor>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SshKey {
+    pub id: String,
+    pub name: String,
+    pub fingerprint: String,
+    pub project_default: bool,
+    pub created_at: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SshKeyList {
+    ssh_keys: Vec<SshKey>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CreateSshKey<'a> {
+    name: &'a str,
+    public_key: &'a str,
+    project_default: bool,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    error: String,
+}
+
+impl ApiClient {
+    /// Build a client from `--api-server`/`--api-token`, falling back to
+    /// `$MVIRT_API_SERVER`/`$MVIRT_API_TOKEN`. `what` names the feature
+    /// that needs the API for the error message.
+    pub fn from_args(
+        server: Option<String>,
+        token: Option<String>,
+        what: &str,
+    ) -> Result<Self, String> {
+        let base = server
+            .or_else(|| std::env::var("MVIRT_API_SERVER").ok())
+            .ok_or_else(|| format!("{} requires --api-server (or MVIRT_API_SERVER)", what))?;
+        Ok(Self {
+            base: base.trim_end_matches('/').to_string(),
+            token: token.or_else(|| std::env::var("MVIRT_API_TOKEN").ok()),
+            http: reqwest::Client::new(),
+        })
+    }
+
+    pub async fn get_flavor(&self, name: &str) -> Result<Flavor, String> {
+        let body = self
+            .send(self.http.get(self.url(&format!("/flavors/{}", name))))
+            .await
+            .map_err(|e| format!("Failed to fetch flavor '{}': {}", name, e))?;
+        parse(&body)
+    }
+
+    pub async fn list_flavors(&self) -> Result<Vec<Flavor>, String> {
+        let body = self.send(self.http.get(self.url("/flavors"))).await?;
+        parse::<FlavorList>(&body).map(|l| l.flavors)
+    }
+
+    pub async fn list_ssh_keys(&self, project: &str) -> Result<Vec<SshKey>, String> {
+        let body = self
+            .send(
+                self.http
+                    .get(self.url(&format!("/projects/{}/ssh-keys", project))),
+            )
+            .await?;
+        parse::<SshKeyList>(&body).map(|l| l.ssh_keys)
+    }
+
+    pub async fn create_ssh_key(
+        &self,
+        project: &str,
+        name: &str,
+        public_key: &str,
+        project_default: bool,
+    ) -> Result<SshKey, String> {
+        let payload = serde_json::to_string(&CreateSshKey {
+            name,
+            public_key,
+            project_default,
+        })
+        .map_err(|e| e.to_string())?;
+        let body = self
+            .send(
+                self.http
+                    .post(self.url(&format!("/projects/{}/ssh-keys", project)))
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(payload),
+            )
+            .await?;
+        parse(&body)
+    }
+
+    pub async fn delete_ssh_key(&self, id: &str) -> Result<(), String> {
+        self.send(self.http.delete(self.url(&format!("/ssh-keys/{}", id))))
+            .await
+            .map(|_| ())
+    }
+
+    /// WebSocket handshake for `path`, with the same credentials as REST
+    /// calls.
+    pub fn websocket_request(
+        &self,
+        path: &str,
+    ) -> Result<tokio_tungstenite::tungstenite::handshake::client::Request, String> {
+        use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+        use tokio_tungstenite::tungstenite::http::HeaderValue;
+
+        let url = self.url(path);
+        let url = match url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}", rest),
+            Some(("http", rest)) => format!("ws://{}", rest),
+            _ => url,
+        };
+        let mut request = url
+            .into_client_request()
+            .map_err(|e| format!("Invalid API server address {}: {}", self.base, e))?;
+        let headers = request.headers_mut();
+        if let Some(token) = &self.token
+            && let Ok(value) = HeaderValue::from_str(&format!("Bearer {}", token))
+        {
+            headers.insert("authorization", value);
+        }
+        if let Some(id) = mvirt_common::request_id::current()
+            && let Ok(value) = HeaderValue::from_str(&id)
+        {
+            headers.insert(mvirt_common::request_id::HEADER, value);
+        }
+        Ok(request)
+    }
+
+    /// The error message of a failed request's body, if it has one.
+    pub fn error_message(body: &[u8]) -> Option<String> {
+        serde_json::from_slice::<ApiError>(body)
+            .ok()
+            .map(|e| e.error)
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/v1{}", self.base, path)
+    }
+
+    /// Send a request and return the body, turning non-2xx responses into
+    /// the API's `{"error": ...}` message where there is one.
+    async fn send(&self, mut request: reqwest::RequestBuilder) -> Result<String, String> {
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(id) = mvirt_common::request_id::current() {
+            request = request.header(mvirt_common::request_id::HEADER, id);
+        }
+        let resp = request
+            .send()
+            .await
+            .map_err(|e| format!("Failed to reach mvirt API at {}: {}", self.base, e))?;
+        let status = resp.status();
+        let body = resp
+            .text()
+            .await
+            .map_err(|e| format!("Failed to read API response: {}", e))?;
+        if status.is_success() {
+            Ok(body)
+        } else {
+            match serde_json::from_str::<ApiError>(&body) {
+                Ok(e) => Err(e.error),
+                Err(_) => Err(format!("HTTP {}", status)),
+            }
+        }
+    }
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(body: &str) -> Result<T, String> {
+    serde_json::from_str(body).map_err(|e| format!("Invalid API response: {}", e))
+}
@@ -0,0 +1,123 @@
+//! `mvirt console --cluster`: consoles of VMs on any node, through the mvirt
+//! API server's console proxy (`GET /v1/vms/{id}/console`).
+//!
+//! Keys work as with a local console. Console bytes travel as binary
+//! WebSocket frames; text frames are JSON control messages, the session
+//! status from the server and takeover requests from us.
+
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use tokio::io::AsyncWriteExt;
+use tokio_tungstenite::tungstenite::{self, Message};
+
+use crate::api::ApiClient;
+use crate::proto::{ConsoleAccess, ConsoleInput};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Session status sent by the API server.
+#[derive(Deserialize)]
+struct SessionStatus {
+    #[serde(default)]
+    notice: String,
+}
+
+/// The `access` query value for `access`.
+fn access_param(access: ConsoleAccess) -> &'static str {
+    match access {
+        ConsoleAccess::ReadOnly => "read-only",
+        ConsoleAccess::Exclusive => "exclusive",
+        ConsoleAccess::Takeover => "takeover",
+        ConsoleAccess::Shared | ConsoleAccess::Unspecified => "shared",
+    }
+}
+
+pub async fn run(
+    api: &ApiClient,
+    vm: &str,
+    access: ConsoleAccess,
+    history_bytes: u32,
+) -> Result<(), Error> {
+    let request = api.websocket_request(&format!(
+        "/vms/{}/console?access={}&scrollback={}&client={}",
+        vm,
+        access_param(access),
+        history_bytes,
+        crate::console_client_name()
+    ))?;
+
+    println!("Connecting to console... (press Ctrl+a t to exit, Ctrl+a o to take over input)");
+    let socket = match tokio_tungstenite::connect_async(request).await {
+        Ok((socket, _)) => socket,
+        Err(tungstenite::Error::Http(response)) => {
+            let message = response
+                .body()
+                .as_deref()
+                .and_then(ApiClient::error_message)
+                .unwrap_or_else(|| format!("HTTP {}", response.status()));
+            return Err(message.into());
+        }
+        Err(e) => return Err(format!("Failed to reach mvirt API: {}", e).into()),
+    };
+    let (mut sink, mut stream) = socket.split();
+
+    crossterm::terminal::enable_raw_mode()?;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<ConsoleInput>(32);
+    let stdin_task = crate::spawn_console_stdin(tx);
+
+    let input_task = tokio::spawn(async move {
+        while let Some(input) = rx.recv().await {
+            let message = if input.access == ConsoleAccess::Takeover as i32 {
+                Message::Text(r#"{"access":"takeover"}"#.into())
+            } else {
+                Message::Binary(input.data.into())
+            };
+            if sink.send(message).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    let output_task = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(message) = stream.next().await {
+            match message {
+                Ok(Message::Binary(data)) => {
+                    if stdout.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+                Ok(Message::Text(text)) => {
+                    if let Ok(status) = serde_json::from_str::<SessionStatus>(text.as_str())
+                        && !status.notice.is_empty()
+                    {
+                        let line = format!("\r\n[mvirt: {}]\r\n", status.notice);
+                        let _ = stdout.write_all(line.as_bytes()).await;
+                    }
+                }
+                Ok(Message::Close(_)) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    let line = format!("\r\n[mvirt: {}]\r\n", e);
+                    let _ = stdout.write_all(line.as_bytes()).await;
+                    break;
+                }
+            }
+            let _ = stdout.flush().await;
+        }
+        let _ = stdout.flush().await;
+    });
+
+    tokio::select! {
+        _ = stdin_task => {}
+        _ = output_task => {}
+    }
+    input_task.abort();
+
+    crossterm::terminal::disable_raw_mode()?;
+    println!("\nDisconnected from console.");
+
+    Ok(())
+}
@@ -0,0 +1,164 @@
+//! Selectable VM columns, shared by `mvirt list -o custom-columns=` and the
+//! TUI's VM table.
+//!
+//! A column spec is `HEADER:FIELD[,HEADER:FIELD...]`, where FIELD is one of
+//! `.id`, `.name`, `.state`, `.vcpus`, `.memory` or `.labels.<key>`, e.g.
+//! `NAME:.name,OWNER:.labels.owner`.
+
+use std::cmp::Ordering;
+
+use crate::format_state;
+use crate::proto::Vm;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Field {
+    Id,
+    Name,
+    State,
+    Vcpus,
+    Memory,
+    Label(String),
+}
+
+impl Field {
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let path = path.strip_prefix('.').unwrap_or(path);
+        match path {
+            "id" => Ok(Field::Id),
+            "name" => Ok(Field::Name),
+            "state" => Ok(Field::State),
+            "vcpus" | "config.vcpus" => Ok(Field::Vcpus),
+            "memory" | "memory_mb" | "config.memory_mb" => Ok(Field::Memory),
+            _ => match path
+                .strip_prefix("labels.")
+                .or_else(|| path.strip_prefix("config.labels."))
+            {
+                Some(key) if !key.is_empty() => Ok(Field::Label(key.to_string())),
+                _ => Err(format!(
+                    "unknown field '.{}' (expected .id, .name, .state, .vcpus, .memory or .labels.<key>)",
+                    path
+                )),
+            },
+        }
+    }
+
+    /// Display value; `-` when unset.
+    pub fn value(&self, vm: &Vm) -> String {
+        let config = vm.config.as_ref();
+        let value = match self {
+            Field::Id => Some(vm.id.clone()),
+            Field::Name => vm.name.clone(),
+            Field::State => Some(format_state(vm.state())),
+            Field::Vcpus => config.map(|c| c.vcpus.to_string()),
+            Field::Memory => config.map(|c| format!("{} MB", c.memory_mb)),
+            Field::Label(key) => config.and_then(|c| c.labels.get(key).cloned()),
+        };
+        value
+            .filter(|v| !v.is_empty())
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    /// Order VMs by this field; numbers compare numerically, unset labels
+    /// sort last.
+    pub fn compare(&self, a: &Vm, b: &Vm) -> Ordering {
+        let num = |vm: &Vm| {
+            vm.config.as_ref().map(|c| match self {
+                Field::Vcpus => c.vcpus as u64,
+                _ => c.memory_mb,
+            })
+        };
+        match self {
+            Field::Vcpus | Field::Memory => num(a).cmp(&num(b)),
+            Field::Label(key) => {
+                let label = |vm: &Vm| vm.config.as_ref().and_then(|c| c.labels.get(key).cloned());
+                match (label(a), label(b)) {
+                    (Some(x), Some(y)) => x.cmp(&y),
+                    (Some(_), None) => Ordering::Less,
+                    (None, Some(_)) => Ordering::Greater,
+                    (None, None) => Ordering::Equal,
+                }
+            }
+            _ => self.value(a).cmp(&self.value(b)),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Column {
+    pub header: String,
+    pub field: Field,
+}
+
+/// Parse a `HEADER:FIELD,...` spec.
+pub fn parse(spec: &str) -> Result<Vec<Column>, String> {
+    let columns: Vec<Column> = spec
+        .split(',')
+        .filter(|c| !c.trim().is_empty())
+        .map(|c| {
+            let (header, path) = c
+                .split_once(':')
+                .ok_or_else(|| format!("column '{}' must be HEADER:FIELD", c))?;
+            Ok(Column {
+                header: header.trim().to_string(),
+                field: Field::parse(path.trim())?,
+            })
+        })
+        .collect::<Result<_, String>>()?;
+    if columns.is_empty() {
+        return Err("custom-columns needs at least one column".to_string());
+    }
+    Ok(columns)
+}
+
+/// Plain aligned table, one line per VM.
+pub fn render(columns: &[Column], vms: &[Vm]) -> String {
+    let rows: Vec<Vec<String>> = vms
+        .iter()
+        .map(|vm| columns.iter().map(|c| c.field.value(vm)).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, c)| {
+            rows.iter()
+                .map(|r| r[i].chars().count())
+                .chain(std::iter::once(c.header.chars().count()))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let mut out = String::new();
+    let headers = columns.iter().map(|c| c.header.clone()).collect();
+    for row in std::iter::once(headers).chain(rows) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(&widths)
+            .map(|(v, w)| format!("{:<width$}", v, width = w))
+            .collect();
+        out.push_str(line.join("   ").trim_end());
+        out.push('\n');
+    }
+    out
+}
+
+/// Whether a VM matches a filter: comma-separated terms that must all
+/// match. `key=value` matches a label, `key` alone a label's presence,
+/// anything else is a substring of the name or ID.
+pub fn matches(vm: &Vm, filter: &str) -> bool {
+    let labels = vm.config.as_ref().map(|c| &c.labels);
+    filter
+        .split(',')
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .all(|term| match term.split_once('=') {
+            Some((key, value)) => {
+                labels.and_then(|l| l.get(key.trim())).map(String::as_str) == Some(value.trim())
+            }
+            None => {
+                labels.is_some_and(|l| l.contains_key(term))
+                    || vm.id.starts_with(term)
+                    || vm.name.as_deref().is_some_and(|n| n.contains(term))
+            }
+        })
+}
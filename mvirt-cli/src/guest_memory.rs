@@ -0,0 +1,121 @@
+//! `mvirt admin dump-memory` / `memory-info`: guest memory of a running VM
+//! for debugging. Both need mvirt-vmm started with `--allow-memory-dump`, and
+//! a local user it allows with `--memory-dump-uid` (root by default).
+//!
+//! A full dump is written as the ELF core file cloud-hypervisor produces.
+//! A range dump is written as raw memory, byte `n` of the file being
+//! guest-physical address `start + n`; addresses without memory stay holes.
+
+use std::io::{Seek, SeekFrom, Write};
+use std::path::Path;
+
+use mvirt_common::request_id::RequestIdChannel;
+use tokio_stream::StreamExt;
+
+use crate::format_bytes;
+use crate::proto::vm_service_client::VmServiceClient;
+use crate::proto::{DumpGuestMemoryRequest, GetGuestMemoryInfoRequest};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Parse a guest-physical address, hex with `0x` or decimal.
+pub fn parse_address(s: &str) -> Result<u64, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16),
+        None => s.parse(),
+    };
+    parsed.map_err(|_| format!("invalid address '{}'", s))
+}
+
+pub async fn dump(
+    client: &mut VmServiceClient<RequestIdChannel>,
+    vm_id: String,
+    output: &Path,
+    range: Option<(u64, u64)>,
+) -> Result<(), Error> {
+    if output.exists() {
+        return Err(format!("{} already exists", output.display()).into());
+    }
+    let (start, length) = range.unwrap_or((0, 0));
+
+    eprintln!("Dumping guest memory (the VM is paused meanwhile)...");
+    let mut stream = client
+        .dump_guest_memory(DumpGuestMemoryRequest {
+            vm_id,
+            start,
+            length,
+        })
+        .await?
+        .into_inner();
+
+    let mut file = std::fs::File::create(output)
+        .map_err(|e| format!("Failed to create {}: {}", output.display(), e))?;
+    let mut written = 0u64;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.seek(SeekFrom::Start(chunk.offset - start))?;
+        file.write_all(&chunk.data)?;
+        written += chunk.data.len() as u64;
+        eprint!("\rWritten {}", format_bytes(written));
+    }
+    eprintln!();
+    if range.is_some() {
+        // Holes at the end of the range still count
+        file.set_len(length)?;
+    }
+    file.sync_all()?;
+
+    println!(
+        "Dumped {} of guest memory to {}",
+        format_bytes(written),
+        output.display()
+    );
+    Ok(())
+}
+
+pub async fn info(
+    client: &mut VmServiceClient<RequestIdChannel>,
+    vm_id: String,
+) -> Result<(), Error> {
+    let info = client
+        .get_guest_memory_info(GetGuestMemoryInfoRequest { vm_id })
+        .await?
+        .into_inner();
+
+    println!("Configured: {}", format_bytes(info.configured_bytes));
+    println!("Actual:     {}", format_bytes(info.actual_bytes));
+    match info.balloon_bytes {
+        Some(size) => println!(
+            "Balloon:    {} (deflate on OOM: {}, free page reporting: {})",
+            format_bytes(size),
+            info.balloon_deflate_on_oom,
+            info.balloon_free_page_reporting
+        ),
+        None => println!("Balloon:    none"),
+    }
+    println!("Regions:");
+    for region in &info.regions {
+        let mut flags = Vec::new();
+        if region.hugepages {
+            flags.push("hugepages".to_string());
+        }
+        if region.shared {
+            flags.push("shared".to_string());
+        }
+        if let Some(node) = region.host_numa_node {
+            flags.push(format!("numa node {}", node));
+        }
+        println!(
+            "  - {}: {} (+{} hotplugged){}",
+            region.id,
+            format_bytes(region.size_bytes),
+            format_bytes(region.hotplugged_bytes),
+            if flags.is_empty() {
+                String::new()
+            } else {
+                format!(" [{}]", flags.join(", "))
+            }
+        );
+    }
+    Ok(())
+}
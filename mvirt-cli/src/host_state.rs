@@ -0,0 +1,229 @@
+//! Host state archives for `mvirt admin export-state` / `import-state`.
+//!
+//! An archive is a single JSON file bundling the state snapshot of each
+//! daemon: VM definitions from mvirt-vmm, networks and NIC allocations
+//! from mvirt-net, volume and template metadata from mvirt-zfs. The
+//! daemons own their snapshot contents; the CLI only bundles them and
+//! restores them in dependency order (storage, then network, then VMs).
+//!
+//! Zvols themselves are not part of the archive. They move with
+//! `zfs send | zfs recv`, and the import checks they arrived.
+
+use mvirt_common::request_id::RequestIdChannel;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::net_proto;
+use crate::net_proto::net_service_client::NetServiceClient;
+use crate::proto;
+use crate::proto::vm_service_client::VmServiceClient;
+use crate::zfs_proto;
+use crate::zfs_proto::zfs_service_client::ZfsServiceClient;
+
+type Error = Box<dyn std::error::Error>;
+
+/// Format version of the archive wrapper.
+const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Archive {
+    version: u32,
+    created_at: String,
+    zfs: Snapshot,
+    net: Snapshot,
+    vmm: Snapshot,
+}
+
+/// One daemon's snapshot. The data is kept as JSON rather than bytes so
+/// the archive stays readable.
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    data: serde_json::Value,
+}
+
+impl Snapshot {
+    fn new(version: u32, data: &[u8]) -> Result<Self, Error> {
+        Ok(Self {
+            version,
+            data: serde_json::from_slice(data)?,
+        })
+    }
+
+    fn bytes(&self) -> Result<Vec<u8>, Error> {
+        Ok(serde_json::to_vec(&self.data)?)
+    }
+
+    /// Rows across all tables, for the summary.
+    fn records(&self) -> usize {
+        self.data
+            .as_object()
+            .map(|tables| {
+                tables
+                    .values()
+                    .filter_map(|rows| rows.as_array())
+                    .map(Vec::len)
+                    .sum()
+            })
+            .unwrap_or(0)
+    }
+}
+
+pub struct Clients {
+    pub vmm: VmServiceClient<RequestIdChannel>,
+    pub zfs: ZfsServiceClient<RequestIdChannel>,
+    pub net: NetServiceClient<RequestIdChannel>,
+}
+
+/// What one daemon reported for an import.
+struct Imported {
+    daemon: &'static str,
+    records: u32,
+    missing: Vec<String>,
+}
+
+/// Write every daemon's state to `path`.
+pub async fn export(clients: &mut Clients, path: &Path) -> Result<(), Error> {
+    let zfs = clients
+        .zfs
+        .export_state(zfs_proto::ExportStateRequest {})
+        .await?
+        .into_inner();
+    let net = clients
+        .net
+        .export_state(net_proto::ExportStateRequest {})
+        .await?
+        .into_inner();
+    let vmm = clients
+        .vmm
+        .export_state(proto::ExportStateRequest {})
+        .await?
+        .into_inner();
+
+    let archive = Archive {
+        version: ARCHIVE_VERSION,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        zfs: Snapshot::new(zfs.version, &zfs.data)?,
+        net: Snapshot::new(net.version, &net.data)?,
+        vmm: Snapshot::new(vmm.version, &vmm.data)?,
+    };
+    std::fs::write(path, serde_json::to_vec_pretty(&archive)?)
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    println!("Exported host state to {}", path.display());
+    for (daemon, snapshot) in [
+        ("zfs", &archive.zfs),
+        ("net", &archive.net),
+        ("vmm", &archive.vmm),
+    ] {
+        println!("  {}: {} records", daemon, snapshot.records());
+    }
+    Ok(())
+}
+
+/// Restore the archive at `path` into this host's (empty) daemons.
+///
+/// Every daemon validates first. Zvols or disks missing on this host
+/// abort the import unless `force` is set; nothing is written then.
+pub async fn import(clients: &mut Clients, path: &Path, force: bool) -> Result<(), Error> {
+    let contents =
+        std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let archive: Archive = serde_json::from_slice(&contents)
+        .map_err(|e| format!("{} is not a host state archive: {}", path.display(), e))?;
+    if archive.version != ARCHIVE_VERSION {
+        return Err(format!(
+            "Unsupported archive version {} (expected {})",
+            archive.version, ARCHIVE_VERSION
+        )
+        .into());
+    }
+
+    let checked = import_all(clients, &archive, true).await?;
+    let missing: Vec<_> = checked.iter().flat_map(|i| &i.missing).collect();
+    if !missing.is_empty() {
+        eprintln!("Missing on this host:");
+        for m in &missing {
+            eprintln!("  {}", m);
+        }
+        if !force {
+            return Err("Nothing imported; restore the above or re-run with --force".into());
+        }
+    }
+
+    let imported = import_all(clients, &archive, false).await?;
+    println!(
+        "Imported host state from {} (exported {})",
+        path.display(),
+        archive.created_at
+    );
+    for i in &imported {
+        println!("  {}: {} records", i.daemon, i.records);
+        for m in &i.missing {
+            println!("    missing: {}", m);
+        }
+    }
+    Ok(())
+}
+
+/// Import into zfs, net and vmm in that order; VMs refer to volumes and
+/// NIC sockets, which must be in place before they are checked.
+async fn import_all(
+    clients: &mut Clients,
+    archive: &Archive,
+    dry_run: bool,
+) -> Result<Vec<Imported>, Error> {
+    let zfs = clients
+        .zfs
+        .import_state(zfs_proto::ImportStateRequest {
+            snapshot: Some(zfs_proto::StateSnapshot {
+                version: archive.zfs.version,
+                data: archive.zfs.bytes()?,
+            }),
+            dry_run,
+        })
+        .await
+        .map_err(|s| format!("mvirt-zfs: {}", s.message()))?
+        .into_inner();
+    let net = clients
+        .net
+        .import_state(net_proto::ImportStateRequest {
+            snapshot: Some(net_proto::StateSnapshot {
+                version: archive.net.version,
+                data: archive.net.bytes()?,
+            }),
+            dry_run,
+        })
+        .await
+        .map_err(|s| format!("mvirt-net: {}", s.message()))?
+        .into_inner();
+    let vmm = clients
+        .vmm
+        .import_state(proto::ImportStateRequest {
+            snapshot: Some(proto::StateSnapshot {
+                version: archive.vmm.version,
+                data: archive.vmm.bytes()?,
+            }),
+            dry_run,
+        })
+        .await
+        .map_err(|s| format!("mvirt-vmm: {}", s.message()))?
+        .into_inner();
+
+    Ok(vec![
+        Imported {
+            daemon: "zfs",
+            records: zfs.records,
+            missing: zfs.missing,
+        },
+        Imported {
+            daemon: "net",
+            records: net.records,
+            missing: net.missing,
+        },
+        Imported {
+            daemon: "vmm",
+            records: vmm.records,
+            missing: vmm.missing,
+        },
+    ])
+}
@@ -0,0 +1,249 @@
+//! `mvirt jobs`: long-running operations of this host's daemons (image
+//! imports, volume transfers, memory dumps) in one list.
+//!
+//! Each daemon keeps its own jobs; the list asks every reachable one and
+//! `get` / `cancel` try them in turn until one knows the ID.
+
+use mvirt_common::request_id::RequestIdChannel;
+use tabled::{Table, Tabled};
+use tonic::Code;
+
+use crate::format_bytes;
+use crate::proto;
+use crate::proto::vm_service_client::VmServiceClient;
+use crate::zfs_proto;
+use crate::zfs_proto::zfs_service_client::ZfsServiceClient;
+
+type Error = Box<dyn std::error::Error>;
+
+pub struct Clients {
+    pub vmm: Option<VmServiceClient<RequestIdChannel>>,
+    pub zfs: Option<ZfsServiceClient<RequestIdChannel>>,
+}
+
+/// A job as either daemon reports it.
+struct Job {
+    source: &'static str,
+    id: String,
+    kind: String,
+    target: String,
+    state: &'static str,
+    phase: String,
+    done: u64,
+    total: u64,
+    error: Option<String>,
+    cancellable: bool,
+    cancel_requested: bool,
+    started_at: String,
+    finished_at: Option<String>,
+}
+
+impl Job {
+    fn progress(&self) -> String {
+        if self.total > 0 {
+            format!(
+                "{} / {} ({:.0}%)",
+                format_bytes(self.done),
+                format_bytes(self.total),
+                (self.done as f64 / self.total as f64 * 100.0).min(100.0)
+            )
+        } else if self.done > 0 {
+            format_bytes(self.done)
+        } else {
+            "-".to_string()
+        }
+    }
+
+    fn state(&self) -> String {
+        if self.cancel_requested && self.state == "running" {
+            "cancelling".to_string()
+        } else {
+            self.state.to_string()
+        }
+    }
+}
+
+impl From<proto::Job> for Job {
+    fn from(job: proto::Job) -> Self {
+        let state = match job.state() {
+            proto::JobState::Running => "running",
+            proto::JobState::Completed => "completed",
+            proto::JobState::Failed => "failed",
+            proto::JobState::Cancelled => "cancelled",
+            proto::JobState::Unspecified => "unknown",
+        };
+        Self {
+            source: "vmm",
+            id: job.id,
+            kind: job.kind,
+            target: job.target,
+            state,
+            phase: job.phase,
+            done: job.done,
+            total: job.total,
+            error: job.error,
+            cancellable: job.cancellable,
+            cancel_requested: job.cancel_requested,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+        }
+    }
+}
+
+impl From<zfs_proto::Job> for Job {
+    fn from(job: zfs_proto::Job) -> Self {
+        let state = match job.state() {
+            zfs_proto::JobState::Running => "running",
+            zfs_proto::JobState::Completed => "completed",
+            zfs_proto::JobState::Failed => "failed",
+            zfs_proto::JobState::Cancelled => "cancelled",
+            zfs_proto::JobState::Unspecified => "unknown",
+        };
+        Self {
+            source: "zfs",
+            id: job.id,
+            kind: job.kind,
+            target: job.target,
+            state,
+            phase: job.phase,
+            done: job.done,
+            total: job.total,
+            error: job.error,
+            cancellable: job.cancellable,
+            cancel_requested: job.cancel_requested,
+            started_at: job.started_at,
+            finished_at: job.finished_at,
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct JobRow {
+    #[tabled(rename = "SOURCE")]
+    source: &'static str,
+    #[tabled(rename = "ID")]
+    id: String,
+    #[tabled(rename = "KIND")]
+    kind: String,
+    #[tabled(rename = "TARGET")]
+    target: String,
+    #[tabled(rename = "STATE")]
+    state: String,
+    #[tabled(rename = "PHASE")]
+    phase: String,
+    #[tabled(rename = "PROGRESS")]
+    progress: String,
+    #[tabled(rename = "STARTED")]
+    started: String,
+}
+
+impl From<&Job> for JobRow {
+    fn from(job: &Job) -> Self {
+        Self {
+            source: job.source,
+            id: job.id.clone(),
+            kind: job.kind.clone(),
+            target: job.target.clone(),
+            state: job.state(),
+            phase: job.phase.clone(),
+            progress: job.progress(),
+            started: job.started_at.clone(),
+        }
+    }
+}
+
+/// Print the jobs of every reachable daemon; running ones only unless
+/// `all`.
+pub async fn list(clients: &mut Clients, all: bool) -> Result<(), Error> {
+    if clients.vmm.is_none() && clients.zfs.is_none() {
+        return Err("Neither mvirt-vmm nor mvirt-zfs is reachable".into());
+    }
+    let mut jobs: Vec<Job> = Vec::new();
+    if let Some(vmm) = clients.vmm.as_mut() {
+        let resp = vmm
+            .list_jobs(proto::ListJobsRequest {
+                include_finished: all,
+            })
+            .await?
+            .into_inner();
+        jobs.extend(resp.jobs.into_iter().map(Job::from));
+    }
+    if let Some(zfs) = clients.zfs.as_mut() {
+        let resp = zfs
+            .list_jobs(zfs_proto::ListJobsRequest {
+                include_finished: all,
+            })
+            .await?
+            .into_inner();
+        jobs.extend(resp.jobs.into_iter().map(Job::from));
+    }
+
+    if jobs.is_empty() {
+        println!("No jobs");
+        return Ok(());
+    }
+    jobs.sort_by(|a, b| a.started_at.cmp(&b.started_at));
+    let rows: Vec<JobRow> = jobs.iter().map(JobRow::from).collect();
+    println!("{}", Table::new(rows));
+    Ok(())
+}
+
+pub async fn get(clients: &mut Clients, id: &str) -> Result<(), Error> {
+    let job = find(clients, id, false).await?;
+    println!("ID:          {}", job.id);
+    println!("Source:      {}", job.source);
+    println!("Kind:        {}", job.kind);
+    println!("Target:      {}", job.target);
+    println!("State:       {}", job.state());
+    println!("Phase:       {}", job.phase);
+    println!("Progress:    {}", job.progress());
+    println!(
+        "Cancellable: {}",
+        if job.cancellable { "yes" } else { "no" }
+    );
+    println!("Started:     {}", job.started_at);
+    if let Some(finished_at) = &job.finished_at {
+        println!("Finished:    {}", finished_at);
+    }
+    if let Some(error) = &job.error {
+        println!("Error:       {}", error);
+    }
+    Ok(())
+}
+
+pub async fn cancel(clients: &mut Clients, id: &str) -> Result<(), Error> {
+    let job = find(clients, id, true).await?;
+    println!("Cancelling {} job {} of {}", job.kind, job.id, job.target);
+    Ok(())
+}
+
+/// Get (or cancel) the job with `id` from whichever daemon has it.
+async fn find(clients: &mut Clients, id: &str, cancel: bool) -> Result<Job, Error> {
+    if let Some(vmm) = clients.vmm.as_mut() {
+        let id = id.to_string();
+        let resp = if cancel {
+            vmm.cancel_job(proto::CancelJobRequest { id }).await
+        } else {
+            vmm.get_job(proto::GetJobRequest { id }).await
+        };
+        match resp {
+            Ok(job) => return Ok(job.into_inner().into()),
+            Err(s) if s.code() == Code::NotFound => {}
+            Err(s) => return Err(s.message().into()),
+        }
+    }
+    if let Some(zfs) = clients.zfs.as_mut() {
+        let id = id.to_string();
+        let resp = if cancel {
+            zfs.cancel_job(zfs_proto::CancelJobRequest { id }).await
+        } else {
+            zfs.get_job(zfs_proto::GetJobRequest { id }).await
+        };
+        match resp {
+            Ok(job) => return Ok(job.into_inner().into()),
+            Err(s) if s.code() == Code::NotFound => {}
+            Err(s) => return Err(s.message().into()),
+        }
+    }
+    Err(format!("Job '{}' not found", id).into())
+}
@@ -0,0 +1,140 @@
+//! `mvirt logs export`: bulk download of log entries for offline analysis.
+//!
+//! mvirt-log streams the export in self-contained chunks (whole NDJSON
+//! records, each a complete gzip member when compressed), so chunks are
+//! appended to the output file as they arrive. Next to the file a
+//! `<output>.cursor` sidecar records the request and the cursor of the
+//! last chunk written; `--resume` picks up from there after an
+//! interruption. The sidecar is removed once the export completes.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use mvirt_common::request_id::RequestIdChannel;
+use mvirt_log::{ExportFormat, ExportRequest, LogServiceClient};
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt;
+
+type Error = Box<dyn std::error::Error>;
+
+/// What to export, as given on the command line.
+pub struct Options {
+    pub since: Option<String>,
+    pub until: Option<String>,
+    pub object: Option<String>,
+    pub plain: bool,
+    pub output: PathBuf,
+    pub resume: bool,
+}
+
+/// Contents of the cursor sidecar. The time range is stored absolute so a
+/// resumed `--since 7d` doesn't shift.
+#[derive(Serialize, Deserialize)]
+struct Progress {
+    object_id: Option<String>,
+    start_time_ns: Option<i64>,
+    end_time_ns: Option<i64>,
+    plain: bool,
+    cursor: String,
+    entries: u64,
+}
+
+fn cursor_path(output: &Path) -> PathBuf {
+    let mut name = output.as_os_str().to_owned();
+    name.push(".cursor");
+    PathBuf::from(name)
+}
+
+/// Parse an age like `90s`, `30m`, `12h` or `7d` into nanoseconds before now.
+fn ago_ns(age: &str) -> Result<i64, Error> {
+    let age = age.trim();
+    let (num, unit) = age.split_at(age.len().saturating_sub(1));
+    let secs: i64 = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86_400,
+        _ => return Err(format!("invalid age '{}': expected e.g. 30m, 12h or 7d", age).into()),
+    };
+    let num: i64 = num
+        .parse()
+        .map_err(|_| format!("invalid age '{}': expected e.g. 30m, 12h or 7d", age))?;
+    let now = chrono::Utc::now()
+        .timestamp_nanos_opt()
+        .ok_or("clock out of range")?;
+    Ok(now - num * secs * 1_000_000_000)
+}
+
+pub async fn export(
+    client: &mut LogServiceClient<RequestIdChannel>,
+    opts: Options,
+) -> Result<(), Error> {
+    let sidecar = cursor_path(&opts.output);
+    let mut progress = if opts.resume {
+        let bytes = std::fs::read(&sidecar).map_err(|e| {
+            format!(
+                "Nothing to resume: failed to read {}: {}",
+                sidecar.display(),
+                e
+            )
+        })?;
+        serde_json::from_slice::<Progress>(&bytes)?
+    } else {
+        if opts.output.exists() {
+            return Err(format!(
+                "{} already exists; remove it or pass --resume",
+                opts.output.display()
+            )
+            .into());
+        }
+        Progress {
+            object_id: opts.object,
+            start_time_ns: opts.since.as_deref().map(ago_ns).transpose()?,
+            end_time_ns: opts.until.as_deref().map(ago_ns).transpose()?,
+            plain: opts.plain,
+            cursor: String::new(),
+            entries: 0,
+        }
+    };
+
+    let format = if progress.plain {
+        ExportFormat::Ndjson
+    } else {
+        ExportFormat::NdjsonGzip
+    };
+    let mut stream = client
+        .export(ExportRequest {
+            object_id: progress.object_id.clone(),
+            start_time_ns: progress.start_time_ns,
+            end_time_ns: progress.end_time_ns,
+            format: format as i32,
+            cursor: progress.cursor.clone(),
+        })
+        .await?
+        .into_inner();
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&opts.output)
+        .map_err(|e| format!("Failed to open {}: {}", opts.output.display(), e))?;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk.data)?;
+        file.sync_data()?;
+        // Only advance the cursor once the chunk is on disk
+        progress.cursor = chunk.cursor;
+        progress.entries += u64::from(chunk.entries);
+        std::fs::write(&sidecar, serde_json::to_vec(&progress)?)?;
+        eprint!("\rExported {} entries", progress.entries);
+    }
+    eprintln!();
+
+    let _ = std::fs::remove_file(&sidecar);
+    println!(
+        "Exported {} entries to {}",
+        progress.entries,
+        opts.output.display()
+    );
+    Ok(())
+}
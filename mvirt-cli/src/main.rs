@@ -3,7 +3,6 @@ use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
 use tabled::{Table, Tabled};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio_stream::StreamExt;
-use tonic::transport::Channel;
 
 pub mod proto {
     tonic::include_proto!("mvirt");
@@ -17,8 +16,23 @@ pub mod net_proto {
     tonic::include_proto!("mvirt.net");
 }
 
+mod api;
+mod cluster_console;
+mod columns;
+mod guest_memory;
+mod host_state;
+mod jobs;
+mod log_export;
+mod migrate;
+mod packet_capture;
+mod report;
+mod snapshot_hooks;
+mod start_stats;
 mod tui;
+mod wait;
 
+use api::ApiClient;
+use mvirt_common::request_id::{self, RequestIdChannel};
 use mvirt_log::LogServiceClient;
 use net_proto::net_service_client::NetServiceClient;
 use proto::pod_service_client::PodServiceClient;
@@ -46,6 +60,15 @@ struct Cli {
     #[arg(long, default_value = "http://[::1]:50054")]
     net_server: String,
 
+    /// REST address of the mvirt API server (used for flavors and `keys`,
+    /// defaults to $MVIRT_API_SERVER)
+    #[arg(long)]
+    api_server: Option<String>,
+
+    /// Bearer token for the mvirt API server (defaults to $MVIRT_API_TOKEN)
+    #[arg(long)]
+    api_token: Option<String>,
+
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -58,15 +81,28 @@ enum Commands {
         #[arg(short, long)]
         name: Option<String>,
 
-        /// Number of vCPUs
-        #[arg(long, default_value = "1")]
-        vcpus: u32,
+        /// Number of vCPUs (default: 1, or the flavor's)
+        #[arg(long)]
+        vcpus: Option<u32>,
 
-        /// Memory in MB
-        #[arg(long, default_value = "512")]
-        memory: u64,
+        /// Memory in MB (default: 512, or the flavor's)
+        #[arg(long)]
+        memory: Option<u64>,
+
+        /// vCPUs the running VM can grow to with `mvirt vm resize`
+        #[arg(long)]
+        max_vcpus: Option<u32>,
 
-        /// Boot mode: disk (default) or kernel
+        /// Memory in MB the running VM can grow to with `mvirt vm resize`
+        #[arg(long)]
+        max_memory: Option<u64>,
+
+        /// Flavor from the mvirt API (e.g. m1.small): sets vCPUs, memory,
+        /// default network and cloud-init user-data
+        #[arg(long)]
+        flavor: Option<String>,
+
+        /// Boot mode: disk (default), kernel or network (PXE / HTTP boot)
         #[arg(long, default_value = "disk")]
         boot: String,
 
@@ -82,10 +118,15 @@ enum Commands {
         #[arg(long)]
         cmdline: Option<String>,
 
-        /// Path to disk image (required for disk boot)
+        /// Path to disk image, or an nvme+tcp:// or iscsi:// target URL
+        /// (required for disk boot)
         #[arg(long)]
         disk: Option<String>,
 
+        /// Secret on the host with the credentials of a remote --disk
+        #[arg(long, requires = "disk")]
+        disk_secret: Option<String>,
+
         /// Path to cloud-init user-data file
         #[arg(long)]
         user_data: Option<std::path::PathBuf>,
@@ -97,10 +138,29 @@ enum Commands {
         /// NIC socket path (from mvirt nic create, e.g., "tap:tap_abc1234")
         #[arg(long)]
         nic: Option<String>,
+
+        /// Label as KEY=VALUE (repeatable), e.g. --label owner=alice
+        #[arg(long = "label", value_parser = parse_label)]
+        labels: Vec<(String, String)>,
     },
 
     /// List all VMs
-    List,
+    List {
+        /// Output format: table (default) or custom-columns=HEADER:FIELD,...
+        /// with FIELD one of .id .name .state .vcpus .memory .labels.<key>
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Only VMs matching all comma-separated terms: KEY=VALUE matches a
+        /// label, anything else a label key, part of the name or an ID prefix
+        #[arg(short, long)]
+        filter: Option<String>,
+
+        /// After listing, keep printing VMs as they are created, started,
+        /// stopped or deleted
+        #[arg(short, long)]
+        watch: bool,
+    },
 
     /// Get VM details
     Get {
@@ -112,6 +172,25 @@ enum Commands {
     Delete {
         /// VM ID
         id: String,
+
+        /// Cancel an in-flight start, or kill the VM if it is running
+        #[arg(short, long)]
+        force: bool,
+    },
+
+    /// Wait until a VM or pod reaches a state, e.g.
+    /// `mvirt wait vm/web --for=state=running --timeout=60s`
+    Wait {
+        /// vm/<name or id>, pod/<name or id>, or a VM name or ID
+        target: String,
+
+        /// state=<state>, or delete to wait until it is gone
+        #[arg(long = "for")]
+        condition: String,
+
+        /// Give up after this long (e.g. 30s, 5m); exits non-zero
+        #[arg(long, default_value = "30s")]
+        timeout: String,
     },
 
     /// Start a VM
@@ -136,10 +215,67 @@ enum Commands {
         id: String,
     },
 
-    /// Connect to VM console (exit with Ctrl+a t)
+    /// Live-migrate a running VM to another host. Its disks must be
+    /// network-attached and its NICs created on the target first
+    Migrate {
+        /// VM ID
+        id: String,
+
+        /// gRPC address of the target host's mvirt-vmm
+        #[arg(long)]
+        to: String,
+    },
+
+    /// Connect to VM console (exit with Ctrl+a t, take over with Ctrl+a o)
     Console {
         /// VM ID
         id: String,
+
+        /// View only, never send input
+        #[arg(long, conflicts_with_all = ["exclusive", "takeover"])]
+        read_only: bool,
+
+        /// Be the only session allowed to type (read-only if already taken)
+        #[arg(long, conflicts_with = "takeover")]
+        exclusive: bool,
+
+        /// Take exclusive write access from whoever holds it
+        #[arg(long)]
+        takeover: bool,
+
+        /// Recent output to show on attach, in KiB (0 = none)
+        #[arg(long, default_value = "4")]
+        scrollback: u32,
+
+        /// Attach through the mvirt API server, for a VM on any node
+        /// (ID or name as the API knows it)
+        #[arg(long)]
+        cluster: bool,
+    },
+
+    /// Print a VM's recent console output (works without attaching)
+    ConsoleLog {
+        /// VM ID
+        id: String,
+
+        /// Maximum bytes to print (0 = everything retained)
+        #[arg(short, long, default_value = "0")]
+        bytes: u32,
+    },
+
+    /// List sessions attached to VM consoles
+    ConsoleSessions {
+        /// VM ID (all VMs if omitted)
+        id: Option<String>,
+    },
+
+    /// Forcibly disconnect a console session
+    ConsoleKick {
+        /// VM ID
+        id: String,
+
+        /// Session ID (from console-sessions)
+        session: String,
     },
 
     /// Import a template from URL or file
@@ -151,8 +287,15 @@ enum Commands {
         source: String,
     },
 
-    /// Storage pool operations
-    Pool,
+    /// Storage pool operations (shows pool stats without a subcommand)
+    Pool {
+        #[command(subcommand)]
+        cmd: Option<PoolCommands>,
+    },
+
+    /// VM snapshots, hot-plug of disks and NICs, and resizing
+    #[command(subcommand)]
+    Vm(VmCommands),
 
     /// Volume operations
     #[command(subcommand)]
@@ -174,9 +317,312 @@ enum Commands {
     #[command(subcommand)]
     Nic(NicCommands),
 
+    /// Floating IP operations
+    #[command(subcommand)]
+    Fip(FipCommands),
+
     /// Pod operations (container pods in MicroVMs)
     #[command(subcommand)]
     Pod(PodCommands),
+
+    /// Project SSH key operations (via the mvirt API)
+    #[command(subcommand)]
+    Keys(KeysCommands),
+
+    /// Host administration
+    #[command(subcommand)]
+    Admin(AdminCommands),
+
+    /// Inventory of the host's VMs, pods, networks, storage and daemons
+    Report {
+        /// Output format
+        #[arg(long, value_parser = ["json", "html"], default_value = "json")]
+        format: String,
+
+        /// File to write (stdout if omitted), or the bundle directory
+        #[arg(short, long)]
+        output: Option<std::path::PathBuf>,
+
+        /// Write a support bundle: a directory with the report as JSON and
+        /// HTML plus recent logs
+        #[arg(long)]
+        support_bundle: bool,
+
+        /// Oldest log entries in the support bundle, e.g. 12h or 7d
+        #[arg(long, default_value = "24h")]
+        logs_since: String,
+    },
+
+    /// Log operations (via mvirt-log)
+    #[command(subcommand)]
+    Logs(LogsCommands),
+
+    /// Long-running operations of mvirt-vmm and mvirt-zfs
+    #[command(subcommand)]
+    Jobs(JobsCommands),
+}
+
+#[derive(Subcommand)]
+enum JobsCommands {
+    /// List running jobs
+    List {
+        /// Include completed, failed and cancelled jobs
+        #[arg(short, long)]
+        all: bool,
+    },
+
+    /// Show a job
+    Get {
+        /// Job ID
+        id: String,
+    },
+
+    /// Ask a job to stop
+    Cancel {
+        /// Job ID
+        id: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum LogsCommands {
+    /// Export log entries as NDJSON for offline analysis (gzip-compressed
+    /// when the output ends in .gz)
+    Export {
+        /// Only entries newer than this age, e.g. 30m, 12h or 7d
+        #[arg(long)]
+        since: Option<String>,
+
+        /// Only entries older than this age
+        #[arg(long)]
+        until: Option<String>,
+
+        /// Only entries related to this object ID
+        #[arg(long)]
+        object: Option<String>,
+
+        /// File to write
+        #[arg(short, long)]
+        output: std::path::PathBuf,
+
+        /// Continue an interrupted export into the same file
+        #[arg(long)]
+        resume: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum AdminCommands {
+    /// Export VMs, networks, NICs, volumes and templates to a state archive
+    ExportState {
+        /// Archive file to write
+        #[arg(short, long, default_value = "mvirt-state.json")]
+        output: std::path::PathBuf,
+    },
+
+    /// Restore a state archive on a freshly installed host
+    ImportState {
+        /// Archive file written by export-state
+        input: std::path::PathBuf,
+
+        /// Import even if referenced zvols or disks are missing
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Dump the memory of a running VM (needs mvirt-vmm --allow-memory-dump)
+    DumpMemory {
+        /// VM name or ID
+        vm: String,
+
+        /// File to write: an ELF core, or raw memory with --length
+        #[arg(short, long)]
+        output: std::path::PathBuf,
+
+        /// Guest-physical start address of a range dump (e.g. 0x100000)
+        #[arg(long, requires = "length")]
+        start: Option<String>,
+
+        /// Dump only this many bytes from --start (e.g. 16M)
+        #[arg(long)]
+        length: Option<String>,
+    },
+
+    /// Show the memory layout and balloon state of a running VM
+    MemoryInfo {
+        /// VM name or ID
+        vm: String,
+    },
+
+    /// Show start latency percentiles per stage over recent VM and pod starts
+    StartStats {
+        /// Only starts of this kind: vm or pod
+        #[arg(long)]
+        kind: Option<String>,
+
+        /// Only the most recent N starts (0 = all the VMM keeps)
+        #[arg(long, default_value_t = 0)]
+        last: u32,
+    },
+}
+
+#[derive(Subcommand)]
+enum KeysCommands {
+    /// List SSH keys in a project
+    List {
+        /// Project slug
+        #[arg(short, long)]
+        project: String,
+    },
+
+    /// Add an SSH public key to a project
+    Add {
+        /// Key name
+        name: String,
+
+        /// Public key file (e.g. ~/.ssh/id_ed25519.pub)
+        #[arg(short, long)]
+        file: std::path::PathBuf,
+
+        /// Project slug
+        #[arg(short, long)]
+        project: String,
+
+        /// Inject into every VM created in the project
+        #[arg(long)]
+        default: bool,
+    },
+
+    /// Remove an SSH key from a project
+    Remove {
+        /// Key ID or name
+        key: String,
+
+        /// Project slug
+        #[arg(short, long)]
+        project: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum PoolCommands {
+    /// Put mvirt-zfs into read-only mode for pool maintenance
+    Maintenance {
+        /// Shown to callers whose changes are refused
+        #[arg(short, long)]
+        reason: String,
+    },
+
+    /// Leave maintenance mode
+    Resume,
+}
+
+#[derive(Subcommand)]
+enum VmCommands {
+    /// Snapshot a running VM with its volumes; it is paused meanwhile
+    Snapshot {
+        /// VM ID
+        id: String,
+
+        /// Snapshot name
+        name: String,
+    },
+
+    /// Boot a stopped VM into a snapshot. Later snapshots of the VM are
+    /// deleted, their volume snapshots are gone with the rollback
+    Restore {
+        /// VM ID
+        id: String,
+
+        /// Snapshot name
+        name: String,
+    },
+
+    /// List snapshots of a VM
+    Snapshots {
+        /// VM ID
+        id: String,
+    },
+
+    /// Delete a snapshot of a VM with its volume snapshots
+    DeleteSnapshot {
+        /// VM ID
+        id: String,
+
+        /// Snapshot name
+        name: String,
+    },
+
+    /// Attach a disk; a running VM sees it right away
+    AttachDisk {
+        /// VM ID
+        id: String,
+
+        /// Disk path or remote disk URL (iscsi://, nvme-tcp://)
+        path: String,
+
+        /// Attach read-only
+        #[arg(long)]
+        readonly: bool,
+
+        /// Secret on the host with the credentials of a remote disk
+        #[arg(long)]
+        secret: Option<String>,
+    },
+
+    /// Detach a disk
+    DetachDisk {
+        /// VM ID
+        id: String,
+
+        /// Disk path as attached
+        path: String,
+    },
+
+    /// Create a NIC in mvirt-net and attach it; a running VM sees it right
+    /// away. vhost-user NICs can only be hot-plugged into a VM that booted
+    /// with one
+    AttachNic {
+        /// VM ID
+        id: String,
+
+        /// Network name or ID
+        #[arg(long)]
+        network: String,
+
+        /// NIC name
+        #[arg(long)]
+        name: Option<String>,
+
+        /// MAC address (auto-generated if not specified)
+        #[arg(long)]
+        mac: Option<String>,
+    },
+
+    /// Detach a NIC and delete it in mvirt-net
+    DetachNic {
+        /// VM ID
+        id: String,
+
+        /// NIC ID in mvirt-net
+        nic: String,
+    },
+
+    /// Change vCPUs and memory. A running VM only grows, up to the
+    /// --max-vcpus / --max-memory it was created with; a stopped VM takes
+    /// any size on its next start
+    Resize {
+        /// VM ID
+        id: String,
+
+        /// Number of vCPUs
+        #[arg(long)]
+        vcpus: Option<u32>,
+
+        /// Memory in MB
+        #[arg(long)]
+        memory: Option<u64>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -209,6 +655,22 @@ enum VolumeCommands {
         #[arg(short, long)]
         size: u64,
     },
+
+    /// Copy a volume onto another template, keeping its changes
+    Rebase {
+        /// Volume name
+        name: String,
+
+        /// Template to rebase onto
+        template: String,
+
+        /// Name of the rebased volume; the original is kept
+        new_name: String,
+
+        /// Only check whether the rebase is possible
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[derive(Subcommand)]
@@ -226,6 +688,32 @@ enum SnapshotCommands {
 
         /// Snapshot name
         name: String,
+
+        /// Pod (name or ID) to run --freeze and --thaw in around the
+        /// snapshot, for an application-consistent one
+        #[arg(long, requires = "freeze")]
+        pod: Option<String>,
+
+        /// Container of --pod to run the hooks in (default: its first)
+        #[arg(long, requires = "pod")]
+        container: Option<String>,
+
+        /// Shell command quiescing the application, e.g. "fsfreeze -f /data"
+        #[arg(long, requires = "pod")]
+        freeze: Option<String>,
+
+        /// Shell command resuming it afterwards, e.g. "fsfreeze -u /data"
+        #[arg(long, requires = "freeze")]
+        thaw: Option<String>,
+
+        /// Seconds each hook may run before it is killed
+        #[arg(long, default_value = "30")]
+        hook_timeout: u32,
+
+        /// If the freeze fails or times out: abort, or skip the freeze and
+        /// take a crash-consistent snapshot
+        #[arg(long, default_value = "abort", value_parser = ["abort", "skip"])]
+        on_hook_failure: String,
     },
 
     /// Delete a snapshot
@@ -282,6 +770,17 @@ enum TemplateCommands {
         #[arg(short, long)]
         size: Option<u64>,
     },
+
+    /// Show the volumes cloned from each template, including deleted
+    /// templates volumes still depend on
+    Clones,
+
+    /// Read a template into the ZFS cache ahead of cloning and booting
+    /// many VMs from it
+    Prewarm {
+        /// Template name
+        name: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -326,6 +825,15 @@ enum NetworkCommands {
         #[arg(short, long)]
         force: bool,
     },
+
+    /// Capture the frames of a network's NICs on this host to a pcap file
+    Capture {
+        /// Network ID or name
+        id: String,
+
+        #[command(flatten)]
+        args: packet_capture::CaptureArgs,
+    },
 }
 
 #[derive(Subcommand)]
@@ -357,6 +865,10 @@ enum NicCommands {
         /// IPv6 address (auto-allocated if not specified)
         #[arg(long)]
         ipv6: Option<String>,
+
+        /// Delegate an IPv6 prefix of this length (e.g. 64) to the NIC over DHCPv6
+        #[arg(long, value_name = "LEN")]
+        delegate_prefix: Option<u32>,
     },
 
     /// Get NIC details
@@ -376,6 +888,84 @@ enum NicCommands {
         /// NIC ID
         id: String,
     },
+
+    /// Bring a NIC's link up or down; a down link cuts the VM off the network
+    Link {
+        /// NIC ID
+        id: String,
+
+        /// Link state
+        #[arg(value_parser = ["up", "down"])]
+        state: String,
+    },
+
+    /// Show traffic counters and rates of a NIC, or of all NICs
+    Stats {
+        /// NIC ID
+        id: Option<String>,
+
+        /// Milliseconds to measure the rates over
+        #[arg(long, default_value_t = 1000)]
+        interval: u32,
+    },
+
+    /// Capture a NIC's frames to a pcap file
+    Capture {
+        /// NIC ID
+        id: String,
+
+        #[command(flatten)]
+        args: packet_capture::CaptureArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum FipCommands {
+    /// List floating IPs
+    List {
+        /// Only the floating IP of this NIC (ID)
+        #[arg(long)]
+        nic: Option<String>,
+    },
+
+    /// Allocate a floating IP from the daemon's pool
+    Create {
+        /// Address from the pool (next free one if not specified)
+        #[arg(long)]
+        address: Option<String>,
+
+        /// NIC ID to associate it with
+        #[arg(long)]
+        nic: Option<String>,
+    },
+
+    /// Get floating IP details
+    Get {
+        /// Floating IP ID or address
+        id: String,
+    },
+
+    /// Associate a floating IP with a NIC, moving it from its current one
+    Associate {
+        /// Floating IP ID or address
+        id: String,
+
+        /// NIC ID
+        #[arg(long)]
+        nic: String,
+    },
+
+    /// Detach a floating IP from its NIC, keeping the address
+    Disassociate {
+        /// Floating IP ID or address
+        id: String,
+    },
+
+    /// Release a floating IP back to the pool
+    Delete {
+        /// Floating IP ID or address
+        id: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -406,7 +996,26 @@ enum PodCommands {
         #[arg(long)]
         net: Option<String>,
 
-        /// Container image
+        /// Guest image to boot (see `mvirt pod images`; default: host default)
+        #[arg(long)]
+        guest_image: Option<String>,
+
+        /// Signal that stops the container (default: the image's, or SIGTERM)
+        #[arg(long)]
+        stop_signal: Option<String>,
+
+        /// Seconds the container gets to exit on stop before it is killed
+        #[arg(long, value_name = "SECONDS")]
+        stop_grace: Option<u32>,
+
+        /// Init container to run to completion before the container, e.g.
+        /// --init "app:1.4 ./migrate up" (repeatable, run in order; gets
+        /// the same --env). The pod fails if one exits non-zero
+        #[arg(long = "init", value_name = "IMAGE [COMMAND...]")]
+        init: Vec<String>,
+
+        /// Container image: a registry reference, or `oci-archive:<path>` of
+        /// an OCI archive on this host for hosts without registry access
         image: String,
 
         /// Command and arguments
@@ -417,6 +1026,15 @@ enum PodCommands {
     /// List pods
     Ps,
 
+    /// List guest images (kernel/initramfs/rootfs) pods can boot
+    Images,
+
+    /// Show CPU and memory usage of running pods
+    Top {
+        /// Pod name or ID (shows per-container usage)
+        name_or_id: Option<String>,
+    },
+
     /// Stop a pod
     Stop {
         /// Pod name or ID
@@ -427,14 +1045,30 @@ enum PodCommands {
         timeout: u32,
     },
 
+    /// Pause a pod (freeze its MicroVM)
+    Pause {
+        /// Pod name or ID
+        name_or_id: String,
+    },
+
+    /// Resume a paused pod
+    Resume {
+        /// Pod name or ID
+        name_or_id: String,
+    },
+
     /// Remove a pod (and its volume/nic)
     Rm {
         /// Pod name or ID
         name_or_id: String,
 
-        /// Force remove running pod
+        /// Force remove a running pod, or cancel its start and remove it
         #[arg(short, long)]
         force: bool,
+
+        /// Fail if the pod still owns volumes or NICs instead of removing them
+        #[arg(long)]
+        no_cascade: bool,
     },
 
     /// Attach to pod console
@@ -460,18 +1094,139 @@ struct VmRow {
 
 impl From<Vm> for VmRow {
     fn from(vm: Vm) -> Self {
-        let state = format_state(vm.state());
+        let state = match vm.start_queue_position {
+            Some(position) => format!("{} (queued #{})", format_state(vm.state()), position),
+            None => format_state(vm.state()),
+        };
         let config = vm.config.unwrap_or_default();
         Self {
-            id: vm.id,
-            name: vm.name.unwrap_or_else(|| "-".to_string()),
-            state,
-            vcpus: config.vcpus,
-            memory: format!("{}MB", config.memory_mb),
+            id: vm.id,
+            name: vm.name.unwrap_or_else(|| "-".to_string()),
+            state,
+            vcpus: config.vcpus,
+            memory: format!("{}MB", config.memory_mb),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct ConsoleSessionRow {
+    #[tabled(rename = "SESSION")]
+    id: String,
+    #[tabled(rename = "VM")]
+    vm_id: String,
+    #[tabled(rename = "CLIENT")]
+    client: String,
+    #[tabled(rename = "WRITABLE")]
+    writable: String,
+    #[tabled(rename = "ATTACHED")]
+    attached_at: String,
+}
+
+impl From<ConsoleSession> for ConsoleSessionRow {
+    fn from(s: ConsoleSession) -> Self {
+        Self {
+            id: s.id,
+            vm_id: s.vm_id,
+            client: s.client,
+            writable: if s.writable { "yes" } else { "no" }.to_string(),
+            attached_at: chrono::DateTime::from_timestamp(s.attached_at, 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "-".to_string()),
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct VmSnapshotRow {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "CREATED")]
+    created_at: String,
+    #[tabled(rename = "STATE")]
+    state_bytes: String,
+    #[tabled(rename = "VOLUMES")]
+    volumes: String,
+}
+
+impl From<VmSnapshot> for VmSnapshotRow {
+    fn from(s: VmSnapshot) -> Self {
+        Self {
+            name: s.name,
+            created_at: chrono::DateTime::from_timestamp(s.created_at, 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            state_bytes: format_bytes(s.state_bytes),
+            volumes: if s.volumes.is_empty() {
+                "-".to_string()
+            } else {
+                s.volumes.join(", ")
+            },
+        }
+    }
+}
+
+#[derive(Tabled)]
+struct GuestImageRow {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "DEFAULT")]
+    default: String,
+    #[tabled(rename = "KERNEL")]
+    kernel: String,
+    #[tabled(rename = "INITRAMFS")]
+    initramfs: String,
+    #[tabled(rename = "ROOTFS")]
+    rootfs: String,
+}
+
+#[derive(Tabled)]
+struct PodStatsRow {
+    #[tabled(rename = "NAME")]
+    name: String,
+    #[tabled(rename = "CPU %")]
+    cpu: String,
+    #[tabled(rename = "MEMORY")]
+    memory: String,
+    #[tabled(rename = "LIMIT")]
+    limit: String,
+    #[tabled(rename = "PIDS")]
+    pids: String,
+}
+
+impl From<ContainerStats> for PodStatsRow {
+    fn from(c: ContainerStats) -> Self {
+        Self {
+            name: c.name,
+            cpu: format!("{:.1}", c.cpu_percent),
+            memory: format_bytes(c.memory_bytes),
+            limit: if c.memory_limit_bytes == 0 {
+                "-".to_string()
+            } else {
+                format_bytes(c.memory_limit_bytes)
+            },
+            pids: c.pids.to_string(),
         }
     }
 }
 
+/// `OOMKilled`/`Exit(N)` for a container that has exited, else `None`.
+fn format_container_exit(c: &Container) -> Option<String> {
+    let stopped = matches!(
+        ContainerState::try_from(c.state),
+        Ok(ContainerState::Stopped | ContainerState::Failed)
+    );
+    if !stopped {
+        None
+    } else if c.oom_killed {
+        Some("OOMKilled".to_string())
+    } else if c.stop_path() == StopPath::Killed {
+        Some(format!("Killed({})", c.exit_code))
+    } else {
+        Some(format!("Exit({})", c.exit_code))
+    }
+}
+
 fn format_state(state: VmState) -> String {
     match state {
         VmState::Unspecified => "unknown".to_string(),
@@ -510,10 +1265,40 @@ fn format_pod_state(state: PodState) -> String {
         PodState::Stopping => "stopping".to_string(),
         PodState::Stopped => "stopped".to_string(),
         PodState::Failed => "failed".to_string(),
+        PodState::Pulling => "pulling".to_string(),
+        PodState::Paused => "paused".to_string(),
+    }
+}
+
+/// `pulling 42%` from the pod's image pull progress.
+fn format_pull_progress(p: &PullProgress) -> String {
+    if p.total_bytes == 0 {
+        return "pulling".to_string();
+    }
+    let percent = (p.downloaded_bytes * 100 / p.total_bytes).min(100);
+    format!("pulling {}%", percent)
+}
+
+/// An image with the path of an `oci-archive:` made absolute: mvirt-vmm
+/// reads archives, not from our working directory.
+fn absolute_archive_image(image: &str) -> Result<String, Box<dyn std::error::Error>> {
+    match image.strip_prefix("oci-archive:") {
+        Some(path) => {
+            let path = std::fs::canonicalize(path)
+                .map_err(|e| format!("Image archive {}: {}", path, e))?;
+            Ok(format!("oci-archive:{}", path.display()))
+        }
+        None => Ok(image.to_string()),
     }
 }
 
 /// Parse size string like "4G", "256M", "1024K" to bytes
+fn parse_label(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("invalid label '{}': expected KEY=VALUE", s))
+}
+
 fn parse_size(s: &str) -> Result<u64, Box<dyn std::error::Error>> {
     let s = s.trim().to_uppercase();
     let (num_str, multiplier) = if s.ends_with('G') {
@@ -537,7 +1322,7 @@ fn parse_memory_mb(s: &str) -> Result<u64, Box<dyn std::error::Error>> {
 
 /// Resolve pod name or ID to pod ID
 async fn resolve_pod_id(
-    client: &mut PodServiceClient<Channel>,
+    client: &mut PodServiceClient<RequestIdChannel>,
     name_or_id: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
     // First try to get by ID
@@ -565,9 +1350,45 @@ async fn resolve_pod_id(
     Err(format!("Pod '{}' not found", name_or_id).into())
 }
 
+/// Run `admin dump-memory`, `admin memory-info` or `admin start-stats`
+async fn vmm_admin_command(
+    client: &mut VmServiceClient<RequestIdChannel>,
+    cmd: &AdminCommands,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match cmd {
+        AdminCommands::DumpMemory {
+            vm,
+            output,
+            start,
+            length,
+        } => {
+            let vm_id = resolve_vm_id(client, vm).await?;
+            let range = match length {
+                Some(length) => {
+                    let start = match start {
+                        Some(start) => guest_memory::parse_address(start)?,
+                        None => 0,
+                    };
+                    Some((start, parse_size(length)?))
+                }
+                None => None,
+            };
+            guest_memory::dump(client, vm_id, output, range).await
+        }
+        AdminCommands::MemoryInfo { vm } => {
+            let vm_id = resolve_vm_id(client, vm).await?;
+            guest_memory::info(client, vm_id).await
+        }
+        AdminCommands::StartStats { kind, last } => {
+            start_stats::show(client, kind.as_deref(), *last).await
+        }
+        _ => unreachable!(),
+    }
+}
+
 /// Resolve VM name or ID to VM ID
 async fn resolve_vm_id(
-    client: &mut VmServiceClient<Channel>,
+    client: &mut VmServiceClient<RequestIdChannel>,
     name_or_id: &str,
 ) -> Result<String, Box<dyn std::error::Error>> {
     // First try to get by ID
@@ -591,11 +1412,31 @@ async fn resolve_vm_id(
     Err(format!("VM '{}' not found", name_or_id).into())
 }
 
+/// Identifies this CLI in the VMM's console audit log.
+fn console_client_name() -> String {
+    let user = std::env::var("USER").unwrap_or_else(|_| "unknown".to_string());
+    format!("{}@mvirt-cli", user)
+}
+
+fn console_access(read_only: bool, exclusive: bool, takeover: bool) -> ConsoleAccess {
+    if read_only {
+        ConsoleAccess::ReadOnly
+    } else if exclusive {
+        ConsoleAccess::Exclusive
+    } else if takeover {
+        ConsoleAccess::Takeover
+    } else {
+        ConsoleAccess::Shared
+    }
+}
+
 async fn run_console(
-    client: &mut VmServiceClient<Channel>,
+    client: &mut VmServiceClient<RequestIdChannel>,
     vm_id: String,
+    access: ConsoleAccess,
+    history_bytes: u32,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Connecting to console... (press Ctrl+a t to exit)");
+    println!("Connecting to console... (press Ctrl+a t to exit, Ctrl+a o to take over input)");
 
     // Create channel for input
     let (tx, rx) = tokio::sync::mpsc::channel::<ConsoleInput>(32);
@@ -604,6 +1445,9 @@ async fn run_console(
     tx.send(ConsoleInput {
         vm_id: vm_id.clone(),
         data: vec![],
+        access: access as i32,
+        client: console_client_name(),
+        history_bytes,
     })
     .await?;
 
@@ -615,9 +1459,51 @@ async fn run_console(
     // Enable raw mode
     enable_raw_mode()?;
 
-    // Spawn task to read from stdin
-    let tx_clone = tx.clone();
-    let stdin_task = tokio::spawn(async move {
+    let stdin_task = spawn_console_stdin(tx);
+
+    // Read from output stream and write to stdout
+    let output_task = tokio::spawn(async move {
+        let mut stdout = tokio::io::stdout();
+        while let Some(result) = output_stream.next().await {
+            match result {
+                Ok(output) => {
+                    // Status messages from the VMM (session/write access changes)
+                    if !output.notice.is_empty() {
+                        let line = format!("\r\n[mvirt: {}]\r\n", output.notice);
+                        let _ = stdout.write_all(line.as_bytes()).await;
+                    }
+                    if stdout.write_all(&output.data).await.is_err() {
+                        break;
+                    }
+                    let _ = stdout.flush().await;
+                }
+                Err(e) => {
+                    let line = format!("\r\n[mvirt: {}]\r\n", e.message());
+                    let _ = stdout.write_all(line.as_bytes()).await;
+                    let _ = stdout.flush().await;
+                    break;
+                }
+            }
+        }
+    });
+
+    // Wait for either task to complete
+    tokio::select! {
+        _ = stdin_task => {}
+        _ = output_task => {}
+    }
+
+    // Disable raw mode
+    disable_raw_mode()?;
+    println!("\nDisconnected from console.");
+
+    Ok(())
+}
+
+/// Read console keystrokes from stdin (in raw mode) until Ctrl+a t or EOF.
+/// Ctrl+a o sends a takeover request instead of input.
+fn spawn_console_stdin(tx: tokio::sync::mpsc::Sender<ConsoleInput>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
         let mut stdin = tokio::io::stdin();
         let mut buf = [0u8; 1];
         let mut saw_ctrl_a = false;
@@ -637,11 +1523,22 @@ async fn run_console(
                         if buf[0] == b't' {
                             break;
                         }
+                        // Ctrl+a o grabs exclusive write access
+                        if buf[0] == b'o' {
+                            let takeover = ConsoleInput {
+                                access: ConsoleAccess::Takeover as i32,
+                                ..Default::default()
+                            };
+                            if tx.send(takeover).await.is_err() {
+                                break;
+                            }
+                            continue;
+                        }
                         // Send the Ctrl+a we held back, then continue with current char
-                        if tx_clone
+                        if tx
                             .send(ConsoleInput {
-                                vm_id: String::new(),
                                 data: vec![0x01],
+                                ..Default::default()
                             })
                             .await
                             .is_err()
@@ -650,10 +1547,10 @@ async fn run_console(
                         }
                     }
 
-                    if tx_clone
+                    if tx
                         .send(ConsoleInput {
-                            vm_id: String::new(),
                             data: buf.to_vec(),
+                            ..Default::default()
                         })
                         .await
                         .is_err()
@@ -664,66 +1561,320 @@ async fn run_console(
                 Err(_) => break,
             }
         }
-    });
+    })
+}
 
-    // Read from output stream and write to stdout
-    let output_task = tokio::spawn(async move {
-        let mut stdout = tokio::io::stdout();
-        while let Some(result) = output_stream.next().await {
-            match result {
-                Ok(output) => {
-                    if stdout.write_all(&output.data).await.is_err() {
-                        break;
-                    }
-                    let _ = stdout.flush().await;
-                }
-                Err(_) => break,
-            }
+/// Explicit flag, else flavor value, else default. Flags contradicting
+/// the flavor are rejected, matching the API server's behaviour.
+fn resolve_flavor_size<T: Copy + PartialEq + std::fmt::Display>(
+    flag: &str,
+    explicit: Option<T>,
+    from_flavor: Option<T>,
+    default: T,
+) -> T {
+    match (explicit, from_flavor) {
+        (Some(e), Some(f)) if e != f => {
+            eprintln!("Error: {} {} conflicts with the flavor ({})", flag, e, f);
+            exit_failed();
         }
-    });
-
-    // Wait for either task to complete
-    tokio::select! {
-        _ = stdin_task => {}
-        _ = output_task => {}
+        (Some(v), _) | (None, Some(v)) => v,
+        (None, None) => default,
     }
+}
 
-    // Disable raw mode
-    disable_raw_mode()?;
-    println!("\nDisconnected from console.");
-
-    Ok(())
+/// Exit after a failed command, naming the request ID the daemons logged
+/// it under.
+fn exit_failed() -> ! {
+    if let Some(id) = request_id::current() {
+        eprintln!("Request ID: {}", id);
+    }
+    std::process::exit(1);
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    if cli.command.is_none() {
+        return run(cli).await;
+    }
+
+    // Every call of a command goes out under one request ID
+    request_id::scope(request_id::generate(), async {
+        if let Err(e) = run(cli).await {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+    })
+    .await;
+    Ok(())
+}
 
+async fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
     // Try to connect to mvirt-vmm (optional for TUI - required for subcommands)
-    let vm_client = VmServiceClient::connect(cli.server.clone()).await.ok();
+    let vm_client = RequestIdChannel::connect(cli.server.clone())
+        .await
+        .map(VmServiceClient::new)
+        .ok();
 
     // Try to connect to mvirt-zfs (optional - doesn't fail if unavailable)
-    let zfs_client = ZfsServiceClient::connect(cli.zfs_server.clone()).await.ok();
+    let zfs_client = RequestIdChannel::connect(cli.zfs_server.clone())
+        .await
+        .map(ZfsServiceClient::new)
+        .ok();
 
     // Try to connect to mvirt-log (optional - doesn't fail if unavailable)
-    let log_client = LogServiceClient::connect(cli.log_server.clone()).await.ok();
+    let log_client = RequestIdChannel::connect(cli.log_server.clone())
+        .await
+        .map(LogServiceClient::new)
+        .ok();
 
     // Try to connect to mvirt-net (optional - doesn't fail if unavailable)
-    let mut net_client = NetServiceClient::connect(cli.net_server.clone()).await.ok();
+    let mut net_client = RequestIdChannel::connect(cli.net_server.clone())
+        .await
+        .map(NetServiceClient::new)
+        .ok();
 
     let Some(command) = cli.command else {
-        // No subcommand: start TUI (works even without connections)
-        tui::run(vm_client, zfs_client, log_client, net_client).await?;
+        // No subcommand: start TUI (works even without connections).
+        // The API server is only needed for the flavor list.
+        let api = ApiClient::from_args(cli.api_server, cli.api_token, "flavors").ok();
+        tui::run(vm_client, zfs_client, log_client, net_client, api).await?;
         return Ok(());
     };
 
+    // Handle SSH key commands (talk to the mvirt API, not the local daemons)
+    if let Commands::Keys(cmd) = &command {
+        let api = match ApiClient::from_args(cli.api_server.clone(), cli.api_token.clone(), "keys")
+        {
+            Ok(api) => api,
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                exit_failed();
+            }
+        };
+        let result = match cmd {
+            KeysCommands::List { project } => api.list_ssh_keys(project).await.map(|keys| {
+                if keys.is_empty() {
+                    println!("No SSH keys found");
+                    return;
+                }
+                println!(
+                    "{:<36} {:<20} {:<51} {:<7} {:<20}",
+                    "ID", "NAME", "FINGERPRINT", "DEFAULT", "CREATED"
+                );
+                for key in keys {
+                    println!(
+                        "{:<36} {:<20} {:<51} {:<7} {:<20}",
+                        key.id,
+                        key.name,
+                        key.fingerprint,
+                        if key.project_default { "yes" } else { "no" },
+                        key.created_at
+                    );
+                }
+            }),
+            KeysCommands::Add {
+                name,
+                file,
+                project,
+                default,
+            } => match std::fs::read_to_string(file) {
+                Ok(public_key) => api
+                    .create_ssh_key(project, name, public_key.trim(), *default)
+                    .await
+                    .map(|key| println!("Added SSH key: {} ({})", key.name, key.fingerprint)),
+                Err(e) => Err(format!("Failed to read {}: {}", file.display(), e)),
+            },
+            KeysCommands::Remove { key, project } => match api.list_ssh_keys(project).await {
+                Ok(keys) => match keys.iter().find(|k| &k.id == key || &k.name == key) {
+                    Some(k) => api
+                        .delete_ssh_key(&k.id)
+                        .await
+                        .map(|_| println!("Removed SSH key: {}", k.name)),
+                    None => Err(format!(
+                        "SSH key '{}' not found in project '{}'",
+                        key, project
+                    )),
+                },
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+        return Ok(());
+    }
+
+    // Consoles of VMs on any node go through the mvirt API's console proxy
+    if let Commands::Console {
+        id,
+        read_only,
+        exclusive,
+        takeover,
+        scrollback,
+        cluster: true,
+    } = &command
+    {
+        let result = match ApiClient::from_args(
+            cli.api_server.clone(),
+            cli.api_token.clone(),
+            "console --cluster",
+        ) {
+            Ok(api) => {
+                let access = console_access(*read_only, *exclusive, *takeover);
+                cluster_console::run(&api, id, access, scrollback * 1024).await
+            }
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+        return Ok(());
+    }
+
+    // Jobs come from whichever of mvirt-vmm and mvirt-zfs are reachable
+    if let Commands::Jobs(cmd) = &command {
+        let mut clients = jobs::Clients {
+            vmm: vm_client.clone(),
+            zfs: zfs_client.clone(),
+        };
+        let result = match cmd {
+            JobsCommands::List { all } => jobs::list(&mut clients, *all).await,
+            JobsCommands::Get { id } => jobs::get(&mut clients, id).await,
+            JobsCommands::Cancel { id } => jobs::cancel(&mut clients, id).await,
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+        return Ok(());
+    }
+
+    // The report covers whichever daemons are reachable
+    if let Commands::Report {
+        format,
+        output,
+        support_bundle,
+        logs_since,
+    } = &command
+    {
+        let output = if *support_bundle {
+            report::Output::SupportBundle {
+                dir: output.clone(),
+                logs_since: logs_since.clone(),
+            }
+        } else if format == "html" {
+            report::Output::Html(output.clone())
+        } else {
+            report::Output::Json(output.clone())
+        };
+        let pod = RequestIdChannel::connect(cli.server.clone())
+            .await
+            .map(PodServiceClient::new)
+            .ok();
+        let mut clients = report::Clients {
+            vmm: vm_client,
+            pod,
+            zfs: zfs_client,
+            net: net_client,
+            log: log_client,
+        };
+        if let Err(e) = report::run(&mut clients, output).await {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+        return Ok(());
+    }
+
+    // Handle log commands (require log_client)
+    if let Commands::Logs(cmd) = &command {
+        let Some(mut log_client) = log_client else {
+            eprintln!("Error: Cannot connect to mvirt-log at {}", cli.log_server);
+            exit_failed();
+        };
+        let result = match cmd {
+            LogsCommands::Export {
+                since,
+                until,
+                object,
+                output,
+                resume,
+            } => {
+                let opts = log_export::Options {
+                    since: since.clone(),
+                    until: until.clone(),
+                    object: object.clone(),
+                    plain: output.extension().is_none_or(|ext| ext != "gz"),
+                    output: output.clone(),
+                    resume: *resume,
+                };
+                log_export::export(&mut log_client, opts).await
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+        return Ok(());
+    }
+
+    // Handle guest memory debugging and start stats (require vm_client only)
+    if let Commands::Admin(
+        cmd @ (AdminCommands::DumpMemory { .. }
+        | AdminCommands::MemoryInfo { .. }
+        | AdminCommands::StartStats { .. }),
+    ) = &command
+    {
+        let Some(mut client) = vm_client else {
+            eprintln!("Error: Cannot connect to mvirt-vmm at {}", cli.server);
+            exit_failed();
+        };
+        let result = vmm_admin_command(&mut client, cmd).await;
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+        return Ok(());
+    }
+
+    // Handle admin commands (require all daemons)
+    if let Commands::Admin(cmd) = &command {
+        let (Some(vmm), Some(zfs), Some(net)) = (vm_client, zfs_client, net_client) else {
+            eprintln!(
+                "Error: admin commands need mvirt-vmm ({}), mvirt-zfs ({}) and mvirt-net ({})",
+                cli.server, cli.zfs_server, cli.net_server
+            );
+            exit_failed();
+        };
+        let mut clients = host_state::Clients { vmm, zfs, net };
+        let result = match cmd {
+            AdminCommands::ExportState { output } => host_state::export(&mut clients, output).await,
+            AdminCommands::ImportState { input, force } => {
+                host_state::import(&mut clients, input, *force).await
+            }
+            AdminCommands::DumpMemory { .. }
+            | AdminCommands::MemoryInfo { .. }
+            | AdminCommands::StartStats { .. } => unreachable!(),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+            exit_failed();
+        }
+        return Ok(());
+    }
+
     // Handle network commands (require net_client)
-    let is_network_command = matches!(&command, Commands::Network(_) | Commands::Nic(_));
+    let is_network_command = matches!(
+        &command,
+        Commands::Network(_) | Commands::Nic(_) | Commands::Fip(_)
+    );
 
     if is_network_command {
         let Some(mut net_client) = net_client else {
             eprintln!("Error: Cannot connect to mvirt-net at {}", cli.net_server);
-            std::process::exit(1);
+            exit_failed();
         };
 
         match &command {
@@ -773,7 +1924,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         eprintln!(
                             "Error: At least one of --ipv4-subnet or --ipv6-prefix is required"
                         );
-                        std::process::exit(1);
+                        exit_failed();
                     }
 
                     let dns_servers: Vec<String> = dns
@@ -840,6 +1991,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
                 }
+                NetworkCommands::Capture { id, args } => {
+                    let target = packet_capture::Target::Network(id.clone());
+                    packet_capture::run(&mut net_client, target, args).await?;
+                }
             },
 
             Commands::Nic(cmd) => match cmd {
@@ -885,6 +2040,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     mac,
                     ipv4,
                     ipv6,
+                    delegate_prefix,
                 } => {
                     let response = net_client
                         .create_nic(net_proto::CreateNicRequest {
@@ -895,6 +2051,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             ipv6_address: ipv6.clone().unwrap_or_default(),
                             routed_ipv4_prefixes: vec![],
                             routed_ipv6_prefixes: vec![],
+                            owner: None,
+                            delegated_ipv6_prefix_len: delegate_prefix.unwrap_or(0),
                         })
                         .await?;
                     let nic = response.into_inner();
@@ -906,6 +2064,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     if !nic.ipv6_address.is_empty() {
                         println!("  IPv6:   {}", nic.ipv6_address);
                     }
+                    if !nic.delegated_ipv6_prefix.is_empty() {
+                        println!("  Prefix: {}", nic.delegated_ipv6_prefix);
+                    }
                 }
                 NicCommands::Get { id } => {
                     let response = net_client
@@ -930,6 +2091,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!("Network:  {}", nic.network_id);
                     println!("MAC:      {}", nic.mac_address);
                     println!("State:    {}", state);
+                    println!(
+                        "Link:     {}",
+                        if nic.link_state == net_proto::NicLinkState::Down as i32 {
+                            "down"
+                        } else {
+                            "up"
+                        }
+                    );
                     println!("Socket:   {}", nic.socket_path);
                     if !nic.ipv4_address.is_empty() {
                         println!("IPv4:     {}", nic.ipv4_address);
@@ -937,6 +2106,9 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     if !nic.ipv6_address.is_empty() {
                         println!("IPv6:     {}", nic.ipv6_address);
                     }
+                    if !nic.delegated_ipv6_prefix.is_empty() {
+                        println!("Prefix:   {} (delegated)", nic.delegated_ipv6_prefix);
+                    }
                     println!("Created:  {}", nic.created_at);
                 }
                 NicCommands::Delete { id } => {
@@ -956,6 +2128,150 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         println!("Failed to attach NIC: {} - {}", id, result.message);
                     }
                 }
+                NicCommands::Link { id, state } => {
+                    let link_state = if state == "down" {
+                        net_proto::NicLinkState::Down
+                    } else {
+                        net_proto::NicLinkState::Up
+                    };
+                    net_client
+                        .set_nic_link_state(net_proto::SetNicLinkStateRequest {
+                            id: id.clone(),
+                            link_state: link_state as i32,
+                        })
+                        .await?;
+                    println!("NIC {} link {}", id, state);
+                }
+                NicCommands::Stats { id, interval } => {
+                    let stats = net_client
+                        .get_nic_stats(net_proto::GetNicStatsRequest {
+                            nic_id: id.clone().unwrap_or_default(),
+                            interval_ms: *interval,
+                        })
+                        .await?
+                        .into_inner();
+                    if stats.nics.is_empty() {
+                        println!("No NICs with a reactor on this host");
+                    } else {
+                        println!(
+                            "{:<36} {:>12} {:>12} {:>10} {:>12} {:>12} {:>10}",
+                            "ID", "RX/s", "RX PPS", "RX DROP", "TX/s", "TX PPS", "TX DROP"
+                        );
+                        for nic in stats.nics {
+                            println!(
+                                "{:<36} {:>12} {:>12.0} {:>10} {:>12} {:>12.0} {:>10}",
+                                nic.nic_id,
+                                format!("{}/s", format_bytes(nic.rx_bytes_per_sec as u64)),
+                                nic.rx_packets_per_sec,
+                                nic.rx_dropped,
+                                format!("{}/s", format_bytes(nic.tx_bytes_per_sec as u64)),
+                                nic.tx_packets_per_sec,
+                                nic.tx_dropped,
+                            );
+                        }
+                        println!(
+                            "Rates over {} ms; RX is traffic to the guest, TX from it",
+                            stats.interval_ms
+                        );
+                    }
+                }
+                NicCommands::Capture { id, args } => {
+                    let target = packet_capture::Target::Nic(id.clone());
+                    packet_capture::run(&mut net_client, target, args).await?;
+                }
+            },
+
+            Commands::Fip(cmd) => match cmd {
+                FipCommands::List { nic } => {
+                    let response = net_client
+                        .list_floating_ips(net_proto::ListFloatingIpsRequest {
+                            nic_id: nic.clone().unwrap_or_default(),
+                        })
+                        .await?;
+                    let fips = response.into_inner().floating_ips;
+                    if fips.is_empty() {
+                        println!("No floating IPs found");
+                    } else {
+                        println!(
+                            "{:<36} {:<15} {:<36} {:<15}",
+                            "ID", "ADDRESS", "NIC", "NIC IPv4"
+                        );
+                        for fip in fips {
+                            println!(
+                                "{:<36} {:<15} {:<36} {:<15}",
+                                fip.id,
+                                fip.address,
+                                if fip.nic_id.is_empty() {
+                                    "-"
+                                } else {
+                                    &fip.nic_id
+                                },
+                                if fip.nic_ipv4_address.is_empty() {
+                                    "-"
+                                } else {
+                                    &fip.nic_ipv4_address
+                                },
+                            );
+                        }
+                    }
+                }
+                FipCommands::Create { address, nic } => {
+                    let fip = net_client
+                        .create_floating_ip(net_proto::CreateFloatingIpRequest {
+                            address: address.clone().unwrap_or_default(),
+                            nic_id: nic.clone().unwrap_or_default(),
+                        })
+                        .await?
+                        .into_inner();
+                    println!("Created floating IP: {} ({})", fip.address, fip.id);
+                    if !fip.nic_id.is_empty() {
+                        println!("  NIC:    {} ({})", fip.nic_id, fip.nic_ipv4_address);
+                    }
+                }
+                FipCommands::Get { id } => {
+                    let fip = net_client
+                        .get_floating_ip(net_proto::GetFloatingIpRequest { id: id.clone() })
+                        .await?
+                        .into_inner();
+                    println!("ID:       {}", fip.id);
+                    println!("Address:  {}", fip.address);
+                    if fip.nic_id.is_empty() {
+                        println!("NIC:      -");
+                    } else {
+                        println!("NIC:      {}", fip.nic_id);
+                        println!("NIC IPv4: {}", fip.nic_ipv4_address);
+                    }
+                    println!("Created:  {}", fip.created_at);
+                    println!("Updated:  {}", fip.updated_at);
+                }
+                FipCommands::Associate { id, nic } => {
+                    let fip = net_client
+                        .associate_floating_ip(net_proto::AssociateFloatingIpRequest {
+                            id: id.clone(),
+                            nic_id: nic.clone(),
+                        })
+                        .await?
+                        .into_inner();
+                    println!(
+                        "Associated floating IP {} with NIC {} ({})",
+                        fip.address, fip.nic_id, fip.nic_ipv4_address
+                    );
+                }
+                FipCommands::Disassociate { id } => {
+                    let fip = net_client
+                        .disassociate_floating_ip(net_proto::DisassociateFloatingIpRequest {
+                            id: id.clone(),
+                        })
+                        .await?
+                        .into_inner();
+                    println!("Disassociated floating IP {}", fip.address);
+                }
+                FipCommands::Delete { id } => {
+                    net_client
+                        .delete_floating_ip(net_proto::DeleteFloatingIpRequest { id: id.clone() })
+                        .await?;
+                    println!("Deleted floating IP: {}", id);
+                }
             },
 
             _ => unreachable!(),
@@ -968,11 +2284,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if let Commands::Pod(ref pod_cmd) = command {
         let Some(mut zfs_client) = zfs_client else {
             eprintln!("Error: Cannot connect to mvirt-zfs at {}", cli.zfs_server);
-            std::process::exit(1);
+            exit_failed();
         };
 
-        let mut pod_client = PodServiceClient::connect(cli.server.clone())
+        let mut pod_client = RequestIdChannel::connect(cli.server.clone())
             .await
+            .map(PodServiceClient::new)
             .map_err(|e| format!("Cannot connect to mvirt-vmm: {}", e))?;
 
         match pod_cmd {
@@ -983,27 +2300,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 disk,
                 env,
                 net,
+                guest_image,
+                stop_signal,
+                stop_grace,
+                init,
                 image,
                 command: cmd_args,
             } => {
+                // The pod id is chosen up front so the volume and NIC can
+                // name the pod as owner and go away with it on `pod rm`
+                let pod_id = uuid::Uuid::new_v4().to_string();
                 let pod_name = name
                     .clone()
-                    .unwrap_or_else(|| format!("pod-{}", &uuid::Uuid::new_v4().to_string()[..8]));
+                    .unwrap_or_else(|| format!("pod-{}", &pod_id[..8]));
 
                 // 1. Parse sizes
                 let disk_bytes = parse_size(disk)?;
                 let memory_mb = parse_memory_mb(memory)?;
 
-                // 2. Create ZFS volume
+                let image = absolute_archive_image(image)?;
+                let mut init_containers = Vec::new();
+                for (i, init) in init.iter().enumerate() {
+                    let mut words = init.split_whitespace().map(String::from);
+                    let Some(init_image) = words.next() else {
+                        eprintln!("Error: --init needs an image");
+                        exit_failed();
+                    };
+                    let command: Vec<String> = words.collect();
+                    init_containers.push(ContainerSpec {
+                        id: String::new(),
+                        name: format!("init-{}", i + 1),
+                        image: absolute_archive_image(&init_image)?,
+                        command: command.iter().take(1).cloned().collect(),
+                        args: command.iter().skip(1).cloned().collect(),
+                        env: env.clone(),
+                        working_dir: String::new(),
+                        stop_signal: String::new(),
+                        stop_grace_seconds: 0,
+                    });
+                }
+
+                // 2. Create ZFS volume; it and the NIC count into the
+                // pod's start timing
+                let mut prepare_stages = Vec::new();
+                let volume_started = std::time::SystemTime::now();
                 let volume_name = format!("{}-root", pod_name);
                 let volume = zfs_client
                     .create_volume(zfs_proto::CreateVolumeRequest {
                         name: volume_name.clone(),
                         size_bytes: disk_bytes,
                         volblocksize: None,
+                        owner: Some(zfs_proto::OwnerReference {
+                            kind: "pod".to_string(),
+                            id: pod_id.clone(),
+                        }),
                     })
                     .await?;
                 let volume_path = volume.into_inner().path;
+                prepare_stages.push(start_stats::stage_since("volume_create", volume_started));
 
                 // 3. Create NIC if network specified
                 let (nic_socket_path, nic_id) = if let Some(net_name) = net {
@@ -1014,7 +2368,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 name: volume_name.clone(),
                             })
                             .await;
-                        std::process::exit(1);
+                        exit_failed();
                     };
 
                     // Lookup network by name
@@ -1030,7 +2384,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     name: volume_name.clone(),
                                 })
                                 .await;
-                            std::process::exit(1);
+                            exit_failed();
                         }
                     };
                     let network = match networks
@@ -1045,11 +2399,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     name: volume_name.clone(),
                                 })
                                 .await;
-                            std::process::exit(1);
+                            exit_failed();
                         }
                     };
 
                     // Create NIC
+                    let nic_started = std::time::SystemTime::now();
                     let nic = match net_client
                         .create_nic(net_proto::CreateNicRequest {
                             network_id: network.id.clone(),
@@ -1059,6 +2414,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             ipv6_address: String::new(),
                             routed_ipv4_prefixes: vec![],
                             routed_ipv6_prefixes: vec![],
+                            owner: Some(net_proto::OwnerReference {
+                                kind: "pod".to_string(),
+                                id: pod_id.clone(),
+                            }),
+                            delegated_ipv6_prefix_len: 0,
                         })
                         .await
                     {
@@ -1070,10 +2430,11 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                     name: volume_name.clone(),
                                 })
                                 .await;
-                            std::process::exit(1);
+                            exit_failed();
                         }
                     };
 
+                    prepare_stages.push(start_stats::stage_since("nic_create", nic_started));
                     (Some(nic.socket_path), Some(nic.id))
                 } else {
                     (None, None)
@@ -1092,6 +2453,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     args: cmd_args.iter().skip(1).cloned().collect(),
                     env: env.clone(),
                     working_dir: String::new(),
+                    stop_signal: stop_signal.clone().unwrap_or_default(),
+                    stop_grace_seconds: stop_grace.unwrap_or(0),
                 };
 
                 // 5. Create pod
@@ -1106,6 +2469,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }),
                         root_disk_path: Some(volume_path),
                         nic_socket_path,
+                        guest_image: guest_image.clone(),
+                        id: Some(pod_id.clone()),
+                        owner: None,
+                        init_containers,
                     })
                     .await
                 {
@@ -1122,36 +2489,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                                 .delete_nic(net_proto::DeleteNicRequest { id })
                                 .await;
                         }
-                        std::process::exit(1);
+                        exit_failed();
                     }
                 };
 
                 // 6. Start pod
                 let pod = match pod_client
-                    .start_pod(StartPodRequest { id: pod.id.clone() })
+                    .start_pod(StartPodRequest {
+                        id: pod.id.clone(),
+                        prepare_stages,
+                    })
                     .await
                 {
                     Ok(resp) => resp.into_inner(),
                     Err(e) => {
                         eprintln!("Error: Failed to start pod: {}", e);
-                        // Delete the created pod
+                        // Takes the pod's volume and NIC with it
                         let _ = pod_client
                             .delete_pod(DeletePodRequest {
                                 id: pod.id.clone(),
                                 force: true,
+                                propagation: DeletePropagation::Cascade as i32,
                             })
                             .await;
-                        let _ = zfs_client
-                            .delete_volume(zfs_proto::DeleteVolumeRequest {
-                                name: volume_name.clone(),
-                            })
-                            .await;
-                        if let (Some(net_client), Some(id)) = (&mut net_client, nic_id) {
-                            let _ = net_client
-                                .delete_nic(net_proto::DeleteNicRequest { id })
-                                .await;
-                        }
-                        std::process::exit(1);
+                        exit_failed();
                     }
                 };
 
@@ -1167,20 +2528,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     println!("No pods found");
                 } else {
                     println!(
-                        "{:<36} {:<15} {:<10} {:<15} {:<20}",
+                        "{:<36} {:<15} {:<12} {:<15} {:<20}",
                         "ID", "NAME", "STATE", "IP", "IMAGE"
                     );
                     for pod in pods {
                         let state = format_pod_state(
                             PodState::try_from(pod.state).unwrap_or(PodState::Unspecified),
                         );
+                        // Show how containers ended, e.g. Exit(137); abnormal
+                        // exits win, clean ones only once the pod has stopped.
+                        let exits: Vec<String> = pod
+                            .containers
+                            .iter()
+                            .filter_map(format_container_exit)
+                            .collect();
+                        let state = exits
+                            .iter()
+                            .find(|e| *e != "Exit(0)")
+                            .or(exits.first().filter(|_| state == "stopped"))
+                            .cloned()
+                            .unwrap_or(state);
+                        let state = pod
+                            .pull_progress
+                            .as_ref()
+                            .map(format_pull_progress)
+                            .unwrap_or(state);
                         let image = pod
                             .containers
                             .first()
                             .map(|c| c.image.as_str())
                             .unwrap_or("-");
                         println!(
-                            "{:<36} {:<15} {:<10} {:<15} {:<20}",
+                            "{:<36} {:<15} {:<12} {:<15} {:<20}",
                             pod.id,
                             pod.name,
                             state,
@@ -1195,6 +2574,87 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
 
+            PodCommands::Images => {
+                let response = pod_client
+                    .list_guest_images(ListGuestImagesRequest {})
+                    .await?
+                    .into_inner();
+                if response.images.is_empty() {
+                    println!("No guest images installed");
+                } else {
+                    let rows: Vec<GuestImageRow> = response
+                        .images
+                        .into_iter()
+                        .map(|image| GuestImageRow {
+                            default: if image.name == response.default_image {
+                                "*"
+                            } else {
+                                ""
+                            }
+                            .to_string(),
+                            name: image.name,
+                            kernel: image.kernel,
+                            initramfs: image.initramfs.unwrap_or_else(|| "-".to_string()),
+                            rootfs: image.rootfs,
+                        })
+                        .collect();
+                    println!("{}", Table::new(rows));
+                }
+            }
+
+            PodCommands::Top { name_or_id } => {
+                if let Some(name_or_id) = name_or_id {
+                    let pod_id = resolve_pod_id(&mut pod_client, name_or_id).await?;
+                    let stats = pod_client
+                        .get_pod_stats(GetPodStatsRequest { pod_id })
+                        .await?
+                        .into_inner();
+                    let rows: Vec<PodStatsRow> = stats
+                        .containers
+                        .into_iter()
+                        .map(PodStatsRow::from)
+                        .collect();
+                    println!("{}", Table::new(rows));
+                } else {
+                    let pods = pod_client
+                        .list_pods(ListPodsRequest {})
+                        .await?
+                        .into_inner()
+                        .pods;
+                    let mut rows = Vec::new();
+                    for pod in pods
+                        .into_iter()
+                        .filter(|p| p.state == PodState::Running as i32)
+                    {
+                        // A pod may stop between listing and sampling; skip it.
+                        let Ok(stats) = pod_client
+                            .get_pod_stats(GetPodStatsRequest { pod_id: pod.id })
+                            .await
+                        else {
+                            continue;
+                        };
+                        let stats = stats.into_inner();
+                        rows.push(PodStatsRow {
+                            name: pod.name,
+                            cpu: format!("{:.1}", stats.cpu_percent),
+                            memory: format_bytes(stats.memory_bytes),
+                            limit: format_bytes(stats.memory_limit_bytes),
+                            pids: stats
+                                .containers
+                                .iter()
+                                .map(|c| c.pids)
+                                .sum::<u64>()
+                                .to_string(),
+                        });
+                    }
+                    if rows.is_empty() {
+                        println!("No running pods");
+                    } else {
+                        println!("{}", Table::new(rows));
+                    }
+                }
+            }
+
             PodCommands::Stop {
                 name_or_id,
                 timeout,
@@ -1210,7 +2670,27 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 println!("Stopped pod: {}", pod_id);
             }
 
-            PodCommands::Rm { name_or_id, force } => {
+            PodCommands::Pause { name_or_id } => {
+                let pod_id = resolve_pod_id(&mut pod_client, name_or_id).await?;
+                pod_client
+                    .pause_pod(PausePodRequest { id: pod_id.clone() })
+                    .await?;
+                println!("Paused pod: {}", pod_id);
+            }
+
+            PodCommands::Resume { name_or_id } => {
+                let pod_id = resolve_pod_id(&mut pod_client, name_or_id).await?;
+                pod_client
+                    .resume_pod(ResumePodRequest { id: pod_id.clone() })
+                    .await?;
+                println!("Resumed pod: {}", pod_id);
+            }
+
+            PodCommands::Rm {
+                name_or_id,
+                force,
+                no_cascade,
+            } => {
                 // Find pod by name or ID
                 let pod_id = resolve_pod_id(&mut pod_client, name_or_id).await?;
 
@@ -1220,27 +2700,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .await?
                     .into_inner();
 
-                // Delete pod from VMM
+                // The vmm deletes the volume and NIC the pod owns
+                let propagation = if *no_cascade {
+                    DeletePropagation::Block
+                } else {
+                    DeletePropagation::Cascade
+                };
                 pod_client
                     .delete_pod(DeletePodRequest {
                         id: pod_id.clone(),
                         force: *force,
+                        propagation: propagation as i32,
                     })
                     .await?;
 
-                // Delete ZFS volume (volume name is pod-name-root)
+                // Pods created before owner references have a root volume
+                // (pod-name-root) the cascade doesn't know about
                 let volume_name = format!("{}-root", pod.name);
-                if let Err(e) = zfs_client
-                    .delete_volume(zfs_proto::DeleteVolumeRequest {
-                        name: volume_name.clone(),
-                    })
-                    .await
+                if !*no_cascade
+                    && let Ok(volume) = zfs_client
+                        .get_volume(zfs_proto::GetVolumeRequest {
+                            name: volume_name.clone(),
+                        })
+                        .await
+                    && volume.into_inner().owner.is_none()
+                    && let Err(e) = zfs_client
+                        .delete_volume(zfs_proto::DeleteVolumeRequest {
+                            name: volume_name.clone(),
+                        })
+                        .await
                 {
                     eprintln!("Warning: Failed to delete volume {}: {}", volume_name, e);
                 }
 
-                // TODO: Delete NIC if exists (need to track NIC ID in pod)
-
                 println!("Removed pod: {}", pod_id);
             }
 
@@ -1256,12 +2748,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
                 if pod.vm_id.is_empty() {
                     eprintln!("Error: Pod is not running");
-                    std::process::exit(1);
+                    exit_failed();
                 }
 
                 // Connect to VM console
-                let mut vm_client = VmServiceClient::connect(cli.server.clone()).await?;
-                run_console(&mut vm_client, pod.vm_id).await?;
+                let mut vm_client =
+                    VmServiceClient::new(RequestIdChannel::connect(cli.server.clone()).await?);
+                run_console(&mut vm_client, pod.vm_id, ConsoleAccess::Shared, 4 * 1024).await?;
             }
         }
 
@@ -1272,7 +2765,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let is_storage_command = matches!(
         &command,
         Commands::Import { .. }
-            | Commands::Pool
+            | Commands::Pool { .. }
             | Commands::Volume(_)
             | Commands::Snapshot(_)
             | Commands::Template(_)
@@ -1281,7 +2774,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     if is_storage_command {
         let Some(mut zfs_client) = zfs_client else {
             eprintln!("Error: Cannot connect to mvirt-zfs at {}", cli.zfs_server);
-            std::process::exit(1);
+            exit_failed();
         };
 
         match &command {
@@ -1332,18 +2825,42 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                         zfs_proto::ImportJobState::Failed => {
                             eprintln!("Import failed: {}", status.error.unwrap_or_default());
-                            std::process::exit(1);
+                            exit_failed();
                         }
                         zfs_proto::ImportJobState::Cancelled => {
                             eprintln!("Import cancelled");
-                            std::process::exit(1);
+                            exit_failed();
                         }
                         _ => continue,
                     }
                 }
             }
 
-            Commands::Pool => {
+            Commands::Pool {
+                cmd: Some(PoolCommands::Maintenance { reason }),
+            } => {
+                zfs_client
+                    .set_maintenance(zfs_proto::SetMaintenanceRequest {
+                        enabled: true,
+                        reason: reason.clone(),
+                    })
+                    .await?;
+                println!("mvirt-zfs is read-only: {}", reason);
+            }
+
+            Commands::Pool {
+                cmd: Some(PoolCommands::Resume),
+            } => {
+                zfs_client
+                    .set_maintenance(zfs_proto::SetMaintenanceRequest {
+                        enabled: false,
+                        reason: String::new(),
+                    })
+                    .await?;
+                println!("Maintenance mode disabled");
+            }
+
+            Commands::Pool { cmd: None } => {
                 let stats = zfs_client
                     .get_pool_stats(zfs_proto::GetPoolStatsRequest {})
                     .await?
@@ -1359,6 +2876,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 if stats.compression_ratio > 1.0 {
                     println!("  Compression: {:.2}x", stats.compression_ratio);
                 }
+                let maintenance = zfs_client
+                    .get_maintenance(zfs_proto::GetMaintenanceRequest {})
+                    .await?
+                    .into_inner();
+                if maintenance.enabled {
+                    println!("  Maintenance: {} (read-only)", maintenance.reason);
+                }
             }
 
             Commands::Volume(cmd) => match cmd {
@@ -1389,6 +2913,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                             name: name.clone(),
                             size_bytes,
                             volblocksize: None,
+                            owner: None,
                         })
                         .await?;
                     let vol = response.into_inner();
@@ -1415,6 +2940,49 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         format_bytes(vol.volsize_bytes)
                     );
                 }
+                VolumeCommands::Rebase {
+                    name,
+                    template,
+                    new_name,
+                    dry_run,
+                } => {
+                    let resp = zfs_client
+                        .rebase_volume(zfs_proto::RebaseVolumeRequest {
+                            volume_name: name.clone(),
+                            template_name: template.clone(),
+                            new_volume_name: new_name,
+                            dry_run,
+                        })
+                        .await?
+                        .into_inner();
+                    if !resp.feasible {
+                        return Err(format!(
+                            "Cannot rebase {} onto {}: {} ({} changed, {} conflicting)",
+                            name,
+                            template,
+                            resp.reason,
+                            format_bytes(resp.changed_bytes),
+                            format_bytes(resp.conflict_bytes)
+                        )
+                        .into());
+                    }
+                    match resp.volume {
+                        Some(vol) => println!(
+                            "Rebased {} onto {} as {} ({}, {} replayed)",
+                            name,
+                            template,
+                            vol.name,
+                            vol.id,
+                            format_bytes(resp.changed_bytes)
+                        ),
+                        None => println!(
+                            "{} can be rebased onto {} ({} to replay)",
+                            name,
+                            template,
+                            format_bytes(resp.changed_bytes)
+                        ),
+                    }
+                }
             },
 
             Commands::Snapshot(cmd) => match cmd {
@@ -1439,7 +3007,39 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         }
                     }
                 }
-                SnapshotCommands::Create { volume, name } => {
+                SnapshotCommands::Create {
+                    volume,
+                    name,
+                    pod: Some(pod),
+                    container,
+                    freeze: Some(freeze),
+                    thaw,
+                    hook_timeout,
+                    on_hook_failure,
+                } => {
+                    let mut pod_client = RequestIdChannel::connect(cli.server.clone())
+                        .await
+                        .map(PodServiceClient::new)
+                        .map_err(|e| format!("Cannot connect to mvirt-vmm: {}", e))?;
+                    let hooks = snapshot_hooks::Hooks {
+                        pod_id: resolve_pod_id(&mut pod_client, pod).await?,
+                        container: container.clone().unwrap_or_default(),
+                        freeze: freeze.clone(),
+                        thaw: thaw.clone(),
+                        timeout: std::time::Duration::from_secs((*hook_timeout).into()),
+                        skip_on_failure: on_hook_failure == "skip",
+                    };
+                    let snap = snapshot_hooks::snapshot(
+                        &mut zfs_client,
+                        &mut pod_client,
+                        volume,
+                        name,
+                        &hooks,
+                    )
+                    .await?;
+                    println!("Created snapshot: {}@{} ({})", volume, snap.name, snap.id);
+                }
+                SnapshotCommands::Create { volume, name, .. } => {
                     let response = zfs_client
                         .create_snapshot(zfs_proto::CreateSnapshotRequest {
                             volume_name: volume.clone(),
@@ -1533,6 +3133,60 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                         template, vol.name, vol.id
                     );
                 }
+                TemplateCommands::Clones => {
+                    let templates = zfs_client
+                        .list_template_clones(zfs_proto::ListTemplateClonesRequest {})
+                        .await?
+                        .into_inner()
+                        .templates;
+                    println!(
+                        "{:<36} {:<20} {:<20} {:>10}",
+                        "TEMPLATE", "NAME", "VOLUME", "USED"
+                    );
+                    for tpl in templates {
+                        let name = if tpl.deleted {
+                            "(deleted)".to_string()
+                        } else {
+                            tpl.template_name
+                        };
+                        if tpl.clones.is_empty() {
+                            println!(
+                                "{:<36} {:<20} {:<20} {:>10}",
+                                tpl.template_id, name, "-", "-"
+                            );
+                        }
+                        for clone in tpl.clones {
+                            println!(
+                                "{:<36} {:<20} {:<20} {:>10}",
+                                tpl.template_id,
+                                name,
+                                clone.volume_name,
+                                format_bytes(clone.used_bytes)
+                            );
+                        }
+                    }
+                }
+                TemplateCommands::Prewarm { name } => {
+                    let resp = zfs_client
+                        .prewarm_template(zfs_proto::PrewarmTemplateRequest {
+                            template_name: name.clone(),
+                        })
+                        .await?
+                        .into_inner();
+                    println!(
+                        "Prewarmed template {}: {} read in {:.1}s",
+                        name,
+                        format_bytes(resp.bytes_read),
+                        resp.duration_ms as f64 / 1000.0
+                    );
+                    if resp.arc_max_bytes > 0 && resp.referenced_bytes > resp.arc_max_bytes {
+                        eprintln!(
+                            "Warning: template holds {}, more than the ARC's {}; only part of it stays cached",
+                            format_bytes(resp.referenced_bytes),
+                            format_bytes(resp.arc_max_bytes)
+                        );
+                    }
+                }
             },
 
             _ => unreachable!(),
@@ -1544,7 +3198,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Other subcommands require a connection to the VMM
     let Some(mut client) = vm_client else {
         eprintln!("Error: Cannot connect to mvirt daemon at {}", cli.server);
-        std::process::exit(1);
+        exit_failed();
     };
 
     match command {
@@ -1552,36 +3206,70 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             name,
             vcpus,
             memory,
+            max_vcpus,
+            max_memory,
             boot,
             kernel,
             initramfs,
             cmdline,
             disk,
+            disk_secret,
             user_data,
             nested_virt,
-            nic,
+            mut nic,
+            flavor,
+            labels,
         } => {
+            let flavor = match flavor {
+                Some(name) => {
+                    let flavor = match ApiClient::from_args(
+                        cli.api_server.clone(),
+                        cli.api_token.clone(),
+                        "--flavor",
+                    ) {
+                        Ok(api) => api.get_flavor(&name).await,
+                        Err(e) => Err(e),
+                    };
+                    match flavor {
+                        Ok(flavor) => Some(flavor),
+                        Err(e) => {
+                            eprintln!("Error: {}", e);
+                            exit_failed();
+                        }
+                    }
+                }
+                None => None,
+            };
+            let vcpus = resolve_flavor_size("--vcpus", vcpus, flavor.as_ref().map(|f| f.vcpus), 1);
+            let memory = resolve_flavor_size(
+                "--memory",
+                memory,
+                flavor.as_ref().map(|f| f.memory_mb),
+                512,
+            );
+
             // Parse boot mode
             let boot_mode = match boot.to_lowercase().as_str() {
                 "disk" => BootMode::Disk,
                 "kernel" => BootMode::Kernel,
+                "network" => BootMode::Network,
                 _ => {
                     eprintln!(
-                        "Error: Invalid boot mode '{}'. Use 'disk' or 'kernel'.",
+                        "Error: Invalid boot mode '{}'. Use 'disk', 'kernel' or 'network'.",
                         boot
                     );
-                    std::process::exit(1);
+                    exit_failed();
                 }
             };
 
             // Validate boot mode requirements
             if boot_mode == BootMode::Kernel && kernel.is_none() {
                 eprintln!("Error: Kernel boot mode requires --kernel");
-                std::process::exit(1);
+                exit_failed();
             }
             if boot_mode == BootMode::Disk && disk.is_none() {
                 eprintln!("Error: Disk boot mode requires --disk");
-                std::process::exit(1);
+                exit_failed();
             }
 
             let disks = disk
@@ -1589,18 +3277,61 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     vec![DiskConfig {
                         path,
                         readonly: false,
+                        secret: disk_secret,
                     }]
                 })
                 .unwrap_or_default();
 
-            // Read user-data file if provided
+            // Read user-data file if provided, else fall back to the flavor's
             let user_data_content = match user_data {
                 Some(path) => Some(std::fs::read_to_string(&path).map_err(|e| {
                     format!("Failed to read user-data file {}: {}", path.display(), e)
                 })?),
-                None => None,
+                None => flavor.as_ref().and_then(|f| f.user_data.clone()),
             };
 
+            // Flavor default network: create a NIC there unless one was given,
+            // and delete it again if the VM isn't created
+            let mut created_nic = None;
+            if nic.is_none()
+                && let Some(network) = flavor.as_ref().and_then(|f| f.network.as_deref())
+            {
+                let Some(ref mut net_client) = net_client else {
+                    eprintln!("Error: Cannot connect to mvirt-net at {}", cli.net_server);
+                    exit_failed();
+                };
+                let created = net_client
+                    .create_nic(net_proto::CreateNicRequest {
+                        network_id: network.to_string(),
+                        name: name
+                            .as_deref()
+                            .map(|n| format!("{}-nic", n))
+                            .unwrap_or_default(),
+                        mac_address: String::new(),
+                        ipv4_address: String::new(),
+                        ipv6_address: String::new(),
+                        routed_ipv4_prefixes: vec![],
+                        routed_ipv6_prefixes: vec![],
+                        owner: None,
+                        delegated_ipv6_prefix_len: 0,
+                    })
+                    .await?
+                    .into_inner();
+                println!("Created NIC: {} ({})", created.id, created.mac_address);
+                created_nic = Some(created.id.clone());
+                // TAP NICs need the MAC passed along so the guest matches the lease
+                nic = Some(if created.socket_path.starts_with("tap:") {
+                    format!("{},mac={}", created.socket_path, created.mac_address)
+                } else {
+                    created.socket_path
+                });
+            }
+
+            if boot_mode == BootMode::Network && nic.is_none() {
+                eprintln!("Error: Network boot mode requires --nic");
+                exit_failed();
+            }
+
             // Parse NIC socket path (format: "tap:name,mac=XX:XX:XX:XX:XX:XX" or "vhost-user:path")
             let nics = match nic {
                 Some(socket) => {
@@ -1644,25 +3375,90 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     nics,
                     user_data: user_data_content,
                     nested_virt,
+                    labels: labels.into_iter().collect(),
+                    max_vcpus: max_vcpus.unwrap_or(0),
+                    max_memory_mb: max_memory.unwrap_or(0),
                 }),
             };
 
-            let response = client.create_vm(request).await?;
-            let vm = response.into_inner();
+            let vm = match client.create_vm(request).await {
+                Ok(response) => response.into_inner(),
+                Err(e) => {
+                    eprintln!("Error: Failed to create VM: {}", e);
+                    if let (Some(net_client), Some(id)) = (&mut net_client, created_nic) {
+                        let _ = net_client
+                            .delete_nic(net_proto::DeleteNicRequest { id })
+                            .await;
+                    }
+                    exit_failed();
+                }
+            };
             println!("Created VM: {}", vm.id);
         }
 
-        Commands::List => {
+        Commands::List {
+            output,
+            filter,
+            watch,
+        } => {
+            let custom = match output.as_deref() {
+                None | Some("table") => None,
+                Some(o) => match o.strip_prefix("custom-columns=").map(columns::parse) {
+                    Some(Ok(columns)) => Some(columns),
+                    Some(Err(e)) => {
+                        eprintln!("Error: {}", e);
+                        exit_failed();
+                    }
+                    None => {
+                        eprintln!(
+                            "Error: Invalid output format '{}'. Use 'table' or 'custom-columns=...'.",
+                            o
+                        );
+                        exit_failed();
+                    }
+                },
+            };
+
             let response = client.list_vms(ListVmsRequest {}).await?;
-            let vms = response.into_inner().vms;
+            let mut vms = response.into_inner().vms;
+            if let Some(filter) = &filter {
+                vms.retain(|vm| columns::matches(vm, filter));
+            }
 
             if vms.is_empty() {
                 println!("No VMs found");
+            } else if let Some(columns) = &custom {
+                print!("{}", columns::render(columns, &vms));
             } else {
                 let rows: Vec<VmRow> = vms.into_iter().map(VmRow::from).collect();
                 let table = Table::new(rows);
                 println!("{}", table);
             }
+
+            if watch {
+                println!();
+                wait::watch_vms(&mut client, filter.as_deref(), custom.as_deref()).await?;
+            }
+        }
+
+        Commands::Wait {
+            target,
+            condition,
+            timeout,
+        } => {
+            let (kind, name) = wait::parse_target(&target)?;
+            let condition = wait::parse_condition(kind, &condition)?;
+            let timeout = wait::parse_timeout(&timeout)?;
+            match kind {
+                wait::Kind::Vm => wait::wait_vm(&mut client, &name, &condition, timeout).await?,
+                wait::Kind::Pod => {
+                    let mut pod_client = RequestIdChannel::connect(cli.server.clone())
+                        .await
+                        .map(PodServiceClient::new)
+                        .map_err(|e| format!("Cannot connect to mvirt-vmm: {}", e))?;
+                    wait::wait_pod(&mut pod_client, &name, &condition, timeout).await?
+                }
+            }
         }
 
         Commands::Get { id } => {
@@ -1673,13 +3469,38 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("ID:      {}", vm.id);
             println!("Name:    {}", vm.name.as_deref().unwrap_or("-"));
             println!("State:   {}", format_state(vm.state()));
-            println!("vCPUs:   {}", config.vcpus);
-            println!("Memory:  {}MB", config.memory_mb);
+            if let Some(position) = vm.start_queue_position {
+                println!("Queued:  #{} to boot", position);
+            }
+            if let Some(timing) = &vm.start_timing {
+                println!("Start:   {}", start_stats::format_timing(timing));
+            }
+            if config.max_vcpus > config.vcpus {
+                println!("vCPUs:   {} (max {})", config.vcpus, config.max_vcpus);
+            } else {
+                println!("vCPUs:   {}", config.vcpus);
+            }
+            if config.max_memory_mb > config.memory_mb {
+                println!(
+                    "Memory:  {}MB (max {}MB)",
+                    config.memory_mb, config.max_memory_mb
+                );
+            } else {
+                println!("Memory:  {}MB", config.memory_mb);
+            }
             let boot_mode_str = match BootMode::try_from(config.boot_mode) {
                 Ok(BootMode::Disk) | Ok(BootMode::Unspecified) | Err(_) => "disk",
                 Ok(BootMode::Kernel) => "kernel",
+                Ok(BootMode::Network) => "network",
             };
             println!("Boot:    {}", boot_mode_str);
+            if !config.labels.is_empty() {
+                let mut labels: Vec<_> = config.labels.iter().collect();
+                labels.sort();
+                let labels: Vec<String> =
+                    labels.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+                println!("Labels:  {}", labels.join(", "));
+            }
             if let Some(kernel) = &config.kernel {
                 println!("Kernel:  {}", kernel);
             }
@@ -1697,17 +3518,26 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
         }
 
-        Commands::Delete { id } => {
+        Commands::Delete { id, force } => {
             let vm_id = resolve_vm_id(&mut client, &id).await?;
             client
-                .delete_vm(DeleteVmRequest { id: vm_id.clone() })
+                .delete_vm(DeleteVmRequest {
+                    id: vm_id.clone(),
+                    propagation: DeletePropagation::Cascade as i32,
+                    force,
+                })
                 .await?;
             println!("Deleted VM: {}", vm_id);
         }
 
         Commands::Start { id } => {
             let vm_id = resolve_vm_id(&mut client, &id).await?;
-            let response = client.start_vm(StartVmRequest { id: vm_id }).await?;
+            let response = client
+                .start_vm(StartVmRequest {
+                    id: vm_id,
+                    prepare_stages: vec![],
+                })
+                .await?;
             let vm = response.into_inner();
             println!(
                 "Started VM: {} (state: {})",
@@ -1739,19 +3569,305 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             println!("Killed VM: {} (state: {})", vm.id, format_state(vm.state()));
         }
 
-        Commands::Console { id } => {
+        Commands::Migrate { id, to } => {
+            let vm_id = resolve_vm_id(&mut client, &id).await?;
+            if let Err(e) = migrate::migrate(&mut client, &vm_id, &to).await {
+                eprintln!("Error: {}", e);
+                exit_failed();
+            }
+        }
+
+        Commands::Vm(cmd) => match cmd {
+            VmCommands::Snapshot { id, name } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let snapshot = client
+                    .snapshot_vm(SnapshotVmRequest { vm_id, name })
+                    .await?
+                    .into_inner();
+                println!(
+                    "Snapshotted VM {} as {} ({} state, {} volumes)",
+                    snapshot.vm_id,
+                    snapshot.name,
+                    format_bytes(snapshot.state_bytes),
+                    snapshot.volumes.len()
+                );
+            }
+            VmCommands::Restore { id, name } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let vm = client
+                    .restore_vm(RestoreVmRequest {
+                        vm_id,
+                        name: name.clone(),
+                    })
+                    .await?
+                    .into_inner();
+                println!(
+                    "Restored VM {} from snapshot {} (state: {})",
+                    vm.id,
+                    name,
+                    format_state(vm.state())
+                );
+            }
+            VmCommands::Snapshots { id } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let snapshots = client
+                    .list_vm_snapshots(ListVmSnapshotsRequest { vm_id })
+                    .await?
+                    .into_inner()
+                    .snapshots;
+                if snapshots.is_empty() {
+                    println!("No snapshots found for VM: {}", id);
+                } else {
+                    let rows: Vec<VmSnapshotRow> = snapshots.into_iter().map(Into::into).collect();
+                    println!("{}", Table::new(rows));
+                }
+            }
+            VmCommands::DeleteSnapshot { id, name } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                client
+                    .delete_vm_snapshot(DeleteVmSnapshotRequest {
+                        vm_id: vm_id.clone(),
+                        name: name.clone(),
+                    })
+                    .await?;
+                println!("Deleted snapshot {} of VM {}", name, vm_id);
+            }
+            VmCommands::AttachDisk {
+                id,
+                path,
+                readonly,
+                secret,
+            } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let vm = client
+                    .attach_disk(AttachDiskRequest {
+                        vm_id,
+                        disk: Some(DiskConfig {
+                            path: path.clone(),
+                            readonly,
+                            secret,
+                        }),
+                    })
+                    .await?
+                    .into_inner();
+                println!("Attached disk {} to VM {}", path, vm.id);
+            }
+            VmCommands::DetachDisk { id, path } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let vm = client
+                    .detach_disk(DetachDiskRequest {
+                        vm_id,
+                        path: path.clone(),
+                    })
+                    .await?
+                    .into_inner();
+                println!("Detached disk {} from VM {}", path, vm.id);
+            }
+            VmCommands::AttachNic {
+                id,
+                network,
+                name,
+                mac,
+            } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let Some(ref mut net_client) = net_client else {
+                    eprintln!("Error: Cannot connect to mvirt-net at {}", cli.net_server);
+                    exit_failed();
+                };
+                let networks = net_client
+                    .list_networks(net_proto::ListNetworksRequest {})
+                    .await?
+                    .into_inner()
+                    .networks;
+                let Some(network) = networks
+                    .iter()
+                    .find(|n| n.name == network || n.id == network)
+                else {
+                    eprintln!("Error: Network '{}' not found", network);
+                    exit_failed();
+                };
+                let created = net_client
+                    .create_nic(net_proto::CreateNicRequest {
+                        network_id: network.id.clone(),
+                        name: name.unwrap_or_default(),
+                        mac_address: mac.unwrap_or_default(),
+                        ipv4_address: String::new(),
+                        ipv6_address: String::new(),
+                        routed_ipv4_prefixes: vec![],
+                        routed_ipv6_prefixes: vec![],
+                        owner: Some(net_proto::OwnerReference {
+                            kind: "vm".to_string(),
+                            id: vm_id.clone(),
+                        }),
+                        delegated_ipv6_prefix_len: 0,
+                    })
+                    .await?
+                    .into_inner();
+
+                // The guest gets the MAC mvirt-net leases addresses to
+                let nic = match created.socket_path.strip_prefix("tap:") {
+                    Some(tap) => NicConfig {
+                        tap: Some(tap.to_string()),
+                        mac: Some(created.mac_address.clone()),
+                        vhost_socket: None,
+                    },
+                    None => NicConfig {
+                        tap: None,
+                        mac: Some(created.mac_address.clone()),
+                        vhost_socket: Some(created.socket_path.clone()),
+                    },
+                };
+                if let Err(e) = client
+                    .attach_nic(AttachNicRequest {
+                        vm_id: vm_id.clone(),
+                        nic: Some(nic),
+                    })
+                    .await
+                {
+                    let _ = net_client
+                        .delete_nic(net_proto::DeleteNicRequest {
+                            id: created.id.clone(),
+                        })
+                        .await;
+                    return Err(e.into());
+                }
+                println!(
+                    "Attached NIC {} ({}) to VM {}",
+                    created.id, created.mac_address, vm_id
+                );
+                if !created.ipv4_address.is_empty() {
+                    println!("  IPv4:   {}", created.ipv4_address);
+                }
+                if !created.ipv6_address.is_empty() {
+                    println!("  IPv6:   {}", created.ipv6_address);
+                }
+            }
+            VmCommands::DetachNic { id, nic } => {
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let Some(ref mut net_client) = net_client else {
+                    eprintln!("Error: Cannot connect to mvirt-net at {}", cli.net_server);
+                    exit_failed();
+                };
+                let found = net_client
+                    .get_nic(net_proto::GetNicRequest {
+                        identifier: Some(net_proto::get_nic_request::Identifier::Id(nic)),
+                    })
+                    .await?
+                    .into_inner();
+                client
+                    .detach_nic(DetachNicRequest {
+                        vm_id: vm_id.clone(),
+                        mac: found.mac_address.clone(),
+                        socket: found.socket_path.clone(),
+                    })
+                    .await?;
+                net_client
+                    .delete_nic(net_proto::DeleteNicRequest {
+                        id: found.id.clone(),
+                    })
+                    .await?;
+                println!(
+                    "Detached NIC {} ({}) from VM {}",
+                    found.id, found.mac_address, vm_id
+                );
+            }
+            VmCommands::Resize { id, vcpus, memory } => {
+                if vcpus.is_none() && memory.is_none() {
+                    eprintln!("Error: At least one of --vcpus or --memory is required");
+                    exit_failed();
+                }
+                let vm_id = resolve_vm_id(&mut client, &id).await?;
+                let vm = client
+                    .update_vm_resources(UpdateVmResourcesRequest {
+                        vm_id,
+                        vcpus,
+                        memory_mb: memory,
+                    })
+                    .await?
+                    .into_inner();
+                let config = vm.config.unwrap_or_default();
+                println!(
+                    "Resized VM {} to {} vCPUs, {} MB",
+                    vm.id, config.vcpus, config.memory_mb
+                );
+            }
+        },
+
+        Commands::Console {
+            id,
+            read_only,
+            exclusive,
+            takeover,
+            scrollback,
+            ..
+        } => {
+            let vm_id = resolve_vm_id(&mut client, &id).await?;
+            let access = console_access(read_only, exclusive, takeover);
+            run_console(&mut client, vm_id, access, scrollback * 1024).await?;
+        }
+
+        Commands::ConsoleLog { id, bytes } => {
+            let vm_id = resolve_vm_id(&mut client, &id).await?;
+            let buffer = client
+                .get_console_buffer(GetConsoleBufferRequest {
+                    vm_id,
+                    max_bytes: bytes,
+                })
+                .await?
+                .into_inner();
+            if buffer.truncated {
+                eprintln!("[older output truncated]");
+            }
+            let mut stdout = tokio::io::stdout();
+            stdout.write_all(&buffer.data).await?;
+            stdout.flush().await?;
+        }
+
+        Commands::ConsoleSessions { id } => {
+            let vm_id = match id {
+                Some(id) => resolve_vm_id(&mut client, &id).await?,
+                None => String::new(),
+            };
+            let sessions = client
+                .list_console_sessions(ListConsoleSessionsRequest { vm_id })
+                .await?
+                .into_inner()
+                .sessions;
+            if sessions.is_empty() {
+                println!("No console sessions");
+            } else {
+                let rows: Vec<ConsoleSessionRow> =
+                    sessions.into_iter().map(ConsoleSessionRow::from).collect();
+                println!("{}", Table::new(rows));
+            }
+        }
+
+        Commands::ConsoleKick { id, session } => {
             let vm_id = resolve_vm_id(&mut client, &id).await?;
-            run_console(&mut client, vm_id).await?;
+            client
+                .disconnect_console_session(DisconnectConsoleSessionRequest {
+                    vm_id,
+                    session_id: session.clone(),
+                    reason: format!("disconnected by {}", console_client_name()),
+                })
+                .await?;
+            println!("Disconnected console session {}", session);
         }
 
         Commands::Import { .. }
-        | Commands::Pool
+        | Commands::Pool { .. }
         | Commands::Volume(_)
         | Commands::Snapshot(_)
         | Commands::Template(_)
         | Commands::Network(_)
         | Commands::Nic(_)
-        | Commands::Pod(_) => {
+        | Commands::Fip(_)
+        | Commands::Pod(_)
+        | Commands::Keys(_)
+        | Commands::Admin(_)
+        | Commands::Report { .. }
+        | Commands::Logs(_)
+        | Commands::Jobs(_) => {
             // Handled above
             unreachable!()
         }
@@ -0,0 +1,91 @@
+//! `mvirt migrate`: live-migrate a running VM to the mvirt-vmm of another
+//! host.
+//!
+//! The daemons don't talk to each other. This command opens MigrateVm on
+//! the source and ReceiveMigration on the target and passes the messages
+//! between them until both sides are done.
+
+use mvirt_common::request_id::RequestIdChannel;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+
+use crate::format_bytes;
+use crate::proto::migration_message::Message;
+use crate::proto::vm_service_client::VmServiceClient;
+use crate::proto::{MigrationHeader, MigrationMessage};
+
+type Error = Box<dyn std::error::Error>;
+
+pub async fn migrate(
+    source: &mut VmServiceClient<RequestIdChannel>,
+    vm_id: &str,
+    target: &str,
+) -> Result<(), Error> {
+    let mut target_client = VmServiceClient::new(
+        RequestIdChannel::connect(target.to_string())
+            .await
+            .map_err(|e| format!("Cannot connect to mvirt-vmm at {}: {}", target, e))?,
+    );
+
+    let (to_source, source_rx) = mpsc::channel(16);
+    let (to_target, target_rx) = mpsc::channel(16);
+    let _ = to_source
+        .send(MigrationMessage {
+            message: Some(Message::Header(MigrationHeader {
+                vm_id: vm_id.to_string(),
+                ..Default::default()
+            })),
+        })
+        .await;
+    let mut from_source = source
+        .migrate_vm(ReceiverStream::new(source_rx))
+        .await
+        .map_err(|s| s.message().to_string())?
+        .into_inner();
+
+    // The source describes the VM; the target checks it can run it
+    let header = from_source
+        .message()
+        .await
+        .map_err(|s| s.message().to_string())?
+        .ok_or("source ended the migration")?;
+    let _ = to_target.send(header).await;
+    let mut from_target = target_client
+        .receive_migration(ReceiverStream::new(target_rx))
+        .await
+        .map_err(|s| format!("{}: {}", target, s.message()))?
+        .into_inner();
+
+    eprintln!("Migrating VM {} to {}...", vm_id, target);
+    let source_to_target = async move {
+        let mut sent = 0u64;
+        while let Some(message) = from_source.message().await? {
+            if let Some(Message::Data(data)) = &message.message {
+                sent += data.len() as u64;
+            }
+            if to_target.send(message).await.is_err() {
+                break;
+            }
+        }
+        Ok::<u64, Status>(sent)
+    };
+    let target_to_source = async move {
+        while let Some(message) = from_target.message().await? {
+            if to_source.send(message).await.is_err() {
+                break;
+            }
+        }
+        Ok::<(), Status>(())
+    };
+    let (sent, ()) = tokio::try_join!(source_to_target, target_to_source)
+        .map_err(|s| s.message().to_string())?;
+
+    println!(
+        "Migrated VM {} to {} ({} transferred)",
+        vm_id,
+        target,
+        format_bytes(sent)
+    );
+    Ok(())
+}
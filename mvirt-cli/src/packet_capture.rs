@@ -0,0 +1,140 @@
+//! `mvirt nic capture` / `mvirt network capture`: tap a NIC's frames, or
+//! those of every NIC of a network, on mvirt-net and write them as a pcap
+//! file for Wireshark or `tcpdump -r`.
+//!
+//! The capture runs until its frame count or duration is reached, or until
+//! Ctrl+C. Frames mvirt-net buffered by then are still written.
+
+use mvirt_common::request_id::RequestIdChannel;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+use crate::net_proto;
+use crate::net_proto::net_service_client::NetServiceClient;
+
+type Error = Box<dyn std::error::Error>;
+
+#[derive(clap::Args)]
+pub struct CaptureArgs {
+    /// pcap file to write, `-` for stdout
+    #[arg(short, long, default_value = "-")]
+    write: String,
+
+    /// Only frames the guest sends or the ones it receives
+    #[arg(long, value_parser = ["both", "from-guest", "to-guest"], default_value = "both")]
+    direction: String,
+
+    /// Bytes kept per frame (default 65535)
+    #[arg(short, long)]
+    snaplen: Option<u32>,
+
+    /// Keep every Nth frame
+    #[arg(long)]
+    sample: Option<u32>,
+
+    /// Stop after this many frames
+    #[arg(short, long)]
+    count: Option<u64>,
+
+    /// Stop after this many seconds
+    #[arg(long)]
+    duration: Option<u32>,
+
+    /// Frames mvirt-net buffers before dropping (default 4096)
+    #[arg(long)]
+    buffer: Option<u32>,
+}
+
+/// What to capture: a NIC ID, or a network ID or name.
+pub enum Target {
+    Nic(String),
+    Network(String),
+}
+
+pub async fn run(
+    client: &mut NetServiceClient<RequestIdChannel>,
+    target: Target,
+    args: &CaptureArgs,
+) -> Result<(), Error> {
+    let direction = match args.direction.as_str() {
+        "from-guest" => net_proto::CaptureDirection::FromGuest,
+        "to-guest" => net_proto::CaptureDirection::ToGuest,
+        _ => net_proto::CaptureDirection::Both,
+    };
+    let (nic_id, network_id) = match target {
+        Target::Nic(id) => (id, String::new()),
+        Target::Network(id) => (String::new(), id),
+    };
+
+    let capture = client
+        .start_capture(net_proto::StartCaptureRequest {
+            nic_id,
+            network_id,
+            direction: direction as i32,
+            snaplen: args.snaplen.unwrap_or_default(),
+            sample_every: args.sample.unwrap_or_default(),
+            buffer_frames: args.buffer.unwrap_or_default(),
+            max_frames: args.count.unwrap_or_default(),
+            duration_seconds: args.duration.unwrap_or_default(),
+        })
+        .await?
+        .into_inner();
+    let mut stream = match client
+        .stream_capture(net_proto::StreamCaptureRequest {
+            capture_id: capture.id.clone(),
+        })
+        .await
+    {
+        Ok(response) => response.into_inner(),
+        Err(e) => {
+            let _ = client
+                .stop_capture(net_proto::StopCaptureRequest {
+                    capture_id: capture.id,
+                })
+                .await;
+            return Err(e.into());
+        }
+    };
+
+    // Status goes to stderr so stdout can carry the pcap
+    let mut out: Box<dyn AsyncWrite + Unpin> = if args.write == "-" {
+        Box::new(tokio::io::stdout())
+    } else {
+        Box::new(tokio::fs::File::create(&args.write).await?)
+    };
+    eprintln!(
+        "Capturing on {} NIC(s), press Ctrl+C to stop",
+        capture.nic_ids.len()
+    );
+
+    let mut stopped = None;
+    loop {
+        tokio::select! {
+            chunk = stream.message() => {
+                match chunk? {
+                    Some(chunk) => out.write_all(&chunk.data).await?,
+                    None => break,
+                }
+            }
+            _ = tokio::signal::ctrl_c(), if stopped.is_none() => {
+                // Keep reading: the stream ends after the buffered frames
+                let stats = client
+                    .stop_capture(net_proto::StopCaptureRequest {
+                        capture_id: capture.id.clone(),
+                    })
+                    .await?
+                    .into_inner();
+                stopped = Some(stats);
+            }
+        }
+    }
+    out.flush().await?;
+
+    match stopped {
+        Some(stats) => eprintln!(
+            "{} frames captured, {} dropped ({} seen)",
+            stats.captured, stats.dropped, stats.seen
+        ),
+        None => eprintln!("Capture ended"),
+    }
+    Ok(())
+}
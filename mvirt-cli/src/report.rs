@@ -0,0 +1,686 @@
+//! `mvirt report`: an inventory of the host across all daemons, for
+//! capacity reviews and support requests.
+//!
+//! The report lists the host, daemon versions, VMs, pods, networks, NICs,
+//! the storage pool, volumes and templates, as JSON or as a standalone HTML
+//! page. A daemon that can't be reached leaves its sections empty and is
+//! noted under `errors` rather than failing the report.
+//!
+//! `--support-bundle` writes a directory instead: the report in both
+//! formats plus the recent entries of mvirt-log.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use mvirt_common::request_id::RequestIdChannel;
+use mvirt_log::LogServiceClient;
+use serde::Serialize;
+
+use crate::net_proto;
+use crate::net_proto::net_service_client::NetServiceClient;
+use crate::proto;
+use crate::proto::pod_service_client::PodServiceClient;
+use crate::proto::vm_service_client::VmServiceClient;
+use crate::zfs_proto;
+use crate::zfs_proto::zfs_service_client::ZfsServiceClient;
+use crate::{format_bytes, format_pod_state, format_state, log_export};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Daemon connections; `None` for daemons that couldn't be reached.
+pub struct Clients {
+    pub vmm: Option<VmServiceClient<RequestIdChannel>>,
+    pub pod: Option<PodServiceClient<RequestIdChannel>>,
+    pub zfs: Option<ZfsServiceClient<RequestIdChannel>>,
+    pub net: Option<NetServiceClient<RequestIdChannel>>,
+    pub log: Option<LogServiceClient<RequestIdChannel>>,
+}
+
+#[derive(Serialize, Default)]
+struct Report {
+    generated_at: String,
+    host: Option<Host>,
+    daemons: Vec<Daemon>,
+    vms: Vec<Vm>,
+    pods: Vec<Pod>,
+    networks: Vec<Network>,
+    nics: Vec<Nic>,
+    pool: Option<Pool>,
+    volumes: Vec<Volume>,
+    templates: Vec<Template>,
+    /// What couldn't be gathered
+    errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Host {
+    hostname: String,
+    kernel_version: String,
+    uptime_seconds: u64,
+    cpu_model: String,
+    cpus: u32,
+    memory_mb: u64,
+    allocated_cpus: u32,
+    allocated_memory_mb: u64,
+}
+
+#[derive(Serialize)]
+struct Daemon {
+    name: &'static str,
+    /// Unset when the daemon couldn't be reached
+    version: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Vm {
+    id: String,
+    name: Option<String>,
+    state: String,
+    vcpus: u32,
+    memory_mb: u64,
+    disks: Vec<String>,
+    nics: usize,
+    labels: BTreeMap<String, String>,
+    created_at: String,
+}
+
+#[derive(Serialize)]
+struct Pod {
+    id: String,
+    name: String,
+    state: String,
+    vm_id: String,
+    ip_address: String,
+    guest_image: String,
+    images: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Network {
+    id: String,
+    name: String,
+    ipv4_subnet: String,
+    ipv6_prefix: String,
+    public: bool,
+    nic_count: u32,
+}
+
+#[derive(Serialize)]
+struct Nic {
+    id: String,
+    name: String,
+    network_id: String,
+    mac_address: String,
+    ipv4_address: String,
+    ipv6_address: String,
+    state: String,
+    link: String,
+}
+
+#[derive(Serialize)]
+struct Pool {
+    name: String,
+    total_bytes: u64,
+    used_bytes: u64,
+    available_bytes: u64,
+    provisioned_bytes: u64,
+    compression_ratio: f64,
+}
+
+#[derive(Serialize)]
+struct Volume {
+    id: String,
+    name: String,
+    volsize_bytes: u64,
+    used_bytes: u64,
+    snapshots: usize,
+    /// `kind/id` of the owning VM or pod
+    owner: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Template {
+    id: String,
+    name: String,
+    size_bytes: u64,
+    clone_count: u32,
+}
+
+/// Output of `mvirt report`.
+pub enum Output {
+    Json(Option<PathBuf>),
+    Html(Option<PathBuf>),
+    /// Directory with report.json, report.html and logs.ndjson.gz
+    SupportBundle {
+        dir: Option<PathBuf>,
+        logs_since: String,
+    },
+}
+
+/// Keep the value of a call, or note what failed.
+fn note<T>(
+    errors: &mut Vec<String>,
+    what: &str,
+    result: Result<tonic::Response<T>, tonic::Status>,
+) -> Option<T> {
+    match result {
+        Ok(response) => Some(response.into_inner()),
+        Err(status) => {
+            errors.push(format!("{}: {}", what, status.message()));
+            None
+        }
+    }
+}
+
+fn unreachable(daemons: &mut Vec<Daemon>, errors: &mut Vec<String>, name: &'static str) {
+    daemons.push(Daemon {
+        name,
+        version: None,
+    });
+    errors.push(format!("{}: not reachable", name));
+}
+
+async fn gather(clients: &mut Clients) -> Report {
+    let mut report = Report {
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        ..Default::default()
+    };
+    let mut errors = Vec::new();
+
+    match clients.vmm.as_mut() {
+        Some(vmm) => {
+            let version = note(
+                &mut errors,
+                "mvirt-vmm version",
+                vmm.get_version(proto::GetVersionRequest {}).await,
+            );
+            report.daemons.push(Daemon {
+                name: "mvirt-vmm",
+                version: version.map(|v| v.version),
+            });
+            if let Some(info) = note(
+                &mut errors,
+                "system info",
+                vmm.get_system_info(proto::GetSystemInfoRequest {}).await,
+            ) {
+                let host = info.host.unwrap_or_default();
+                report.host = Some(Host {
+                    hostname: host.hostname,
+                    kernel_version: host.kernel_version,
+                    uptime_seconds: host.uptime_seconds,
+                    cpu_model: info.cpu.map(|c| c.model).unwrap_or_default(),
+                    cpus: info.total_cpus,
+                    memory_mb: info.total_memory_mb,
+                    allocated_cpus: info.allocated_cpus,
+                    allocated_memory_mb: info.allocated_memory_mb,
+                });
+            }
+            if let Some(list) = note(
+                &mut errors,
+                "VMs",
+                vmm.list_vms(proto::ListVmsRequest {}).await,
+            ) {
+                report.vms = list.vms.into_iter().map(vm_entry).collect();
+            }
+        }
+        None => unreachable(&mut report.daemons, &mut errors, "mvirt-vmm"),
+    }
+
+    if let Some(pod) = clients.pod.as_mut()
+        && let Some(list) = note(
+            &mut errors,
+            "pods",
+            pod.list_pods(proto::ListPodsRequest {}).await,
+        )
+    {
+        report.pods = list
+            .pods
+            .into_iter()
+            .map(|p| Pod {
+                state: format_pod_state(p.state()),
+                id: p.id,
+                name: p.name,
+                vm_id: p.vm_id,
+                ip_address: p.ip_address,
+                guest_image: p.guest_image,
+                images: p.containers.into_iter().map(|c| c.image).collect(),
+            })
+            .collect();
+    }
+
+    match clients.net.as_mut() {
+        Some(net) => {
+            let version = note(
+                &mut errors,
+                "mvirt-net version",
+                net.get_version(net_proto::GetVersionRequest {}).await,
+            );
+            report.daemons.push(Daemon {
+                name: "mvirt-net",
+                version: version.map(|v| v.version),
+            });
+            if let Some(list) = note(
+                &mut errors,
+                "networks",
+                net.list_networks(net_proto::ListNetworksRequest {}).await,
+            ) {
+                report.networks = list
+                    .networks
+                    .into_iter()
+                    .map(|n| Network {
+                        id: n.id,
+                        name: n.name,
+                        ipv4_subnet: n.ipv4_subnet,
+                        ipv6_prefix: n.ipv6_prefix,
+                        public: n.is_public,
+                        nic_count: n.nic_count,
+                    })
+                    .collect();
+            }
+            if let Some(list) = note(
+                &mut errors,
+                "NICs",
+                net.list_nics(net_proto::ListNicsRequest::default()).await,
+            ) {
+                report.nics = list.nics.into_iter().map(nic_entry).collect();
+            }
+        }
+        None => unreachable(&mut report.daemons, &mut errors, "mvirt-net"),
+    }
+
+    match clients.zfs.as_mut() {
+        Some(zfs) => {
+            let version = note(
+                &mut errors,
+                "mvirt-zfs version",
+                zfs.get_version(zfs_proto::GetVersionRequest {}).await,
+            );
+            report.daemons.push(Daemon {
+                name: "mvirt-zfs",
+                version: version.map(|v| v.version),
+            });
+            if let Some(pool) = note(
+                &mut errors,
+                "pool",
+                zfs.get_pool_stats(zfs_proto::GetPoolStatsRequest {}).await,
+            ) {
+                report.pool = Some(Pool {
+                    name: pool.name,
+                    total_bytes: pool.total_bytes,
+                    used_bytes: pool.used_bytes,
+                    available_bytes: pool.available_bytes,
+                    provisioned_bytes: pool.provisioned_bytes,
+                    compression_ratio: pool.compression_ratio,
+                });
+            }
+            if let Some(list) = note(
+                &mut errors,
+                "volumes",
+                zfs.list_volumes(zfs_proto::ListVolumesRequest {}).await,
+            ) {
+                report.volumes = list
+                    .volumes
+                    .into_iter()
+                    .map(|v| Volume {
+                        id: v.id,
+                        name: v.name,
+                        volsize_bytes: v.volsize_bytes,
+                        used_bytes: v.used_bytes,
+                        snapshots: v.snapshots.len(),
+                        owner: v.owner.map(|o| format!("{}/{}", o.kind, o.id)),
+                    })
+                    .collect();
+            }
+            if let Some(list) = note(
+                &mut errors,
+                "templates",
+                zfs.list_templates(zfs_proto::ListTemplatesRequest {}).await,
+            ) {
+                report.templates = list
+                    .templates
+                    .into_iter()
+                    .map(|t| Template {
+                        id: t.id,
+                        name: t.name,
+                        size_bytes: t.size_bytes,
+                        clone_count: t.clone_count,
+                    })
+                    .collect();
+            }
+        }
+        None => unreachable(&mut report.daemons, &mut errors, "mvirt-zfs"),
+    }
+
+    report.errors = errors;
+    report
+}
+
+fn vm_entry(vm: proto::Vm) -> Vm {
+    let config = vm.config.unwrap_or_default();
+    Vm {
+        state: format_state(vm.state()),
+        id: vm.id,
+        name: vm.name,
+        vcpus: config.vcpus,
+        memory_mb: config.memory_mb,
+        disks: config.disks.into_iter().map(|d| d.path).collect(),
+        nics: config.nics.len(),
+        labels: config.labels.into_iter().collect(),
+        created_at: chrono::DateTime::from_timestamp(vm.created_at, 0)
+            .map(|t| t.to_rfc3339())
+            .unwrap_or_default(),
+    }
+}
+
+fn nic_entry(nic: net_proto::Nic) -> Nic {
+    let state = match nic.state() {
+        net_proto::NicState::Created => "created",
+        net_proto::NicState::Active => "active",
+        net_proto::NicState::Error => "error",
+        net_proto::NicState::Unspecified => "unknown",
+    };
+    let link = if nic.link_state() == net_proto::NicLinkState::Down {
+        "down"
+    } else {
+        "up"
+    };
+    Nic {
+        id: nic.id,
+        name: nic.name,
+        network_id: nic.network_id,
+        mac_address: nic.mac_address,
+        ipv4_address: nic.ipv4_address,
+        ipv6_address: nic.ipv6_address,
+        state: state.to_string(),
+        link: link.to_string(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn html_table(out: &mut String, title: &str, headers: &[&str], rows: Vec<Vec<String>>) {
+    let _ = write!(out, "<h2>{} ({})</h2>\n<table>\n<tr>", title, rows.len());
+    for header in headers {
+        let _ = write!(out, "<th>{}</th>", header);
+    }
+    out.push_str("</tr>\n");
+    for row in rows {
+        out.push_str("<tr>");
+        for cell in row {
+            let _ = write!(out, "<td>{}</td>", escape(&cell));
+        }
+        out.push_str("</tr>\n");
+    }
+    out.push_str("</table>\n");
+}
+
+fn to_html(report: &Report) -> String {
+    let hostname = report
+        .host
+        .as_ref()
+        .map_or("unknown host", |h| h.hostname.as_str());
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>mvirt report: {host}</title>\n\
+         <style>body{{font-family:sans-serif;margin:2em}}table{{border-collapse:collapse;margin-bottom:1em}}\
+         th,td{{border:1px solid #ccc;padding:.25em .5em;text-align:left}}th{{background:#eee}}\
+         .error{{color:#b00}}</style></head><body>\n\
+         <h1>mvirt report: {host}</h1>\n<p>Generated {at}</p>\n",
+        host = escape(hostname),
+        at = escape(&report.generated_at),
+    );
+
+    if !report.errors.is_empty() {
+        out.push_str("<h2>Errors</h2>\n<ul class=\"error\">\n");
+        for error in &report.errors {
+            let _ = writeln!(out, "<li>{}</li>", escape(error));
+        }
+        out.push_str("</ul>\n");
+    }
+    if let Some(host) = &report.host {
+        html_table(
+            &mut out,
+            "Host",
+            &[
+                "Kernel",
+                "Uptime",
+                "CPU",
+                "CPUs (allocated)",
+                "Memory (allocated)",
+            ],
+            vec![vec![
+                host.kernel_version.clone(),
+                format!("{}h", host.uptime_seconds / 3600),
+                host.cpu_model.clone(),
+                format!("{} ({})", host.cpus, host.allocated_cpus),
+                format!("{} MiB ({} MiB)", host.memory_mb, host.allocated_memory_mb),
+            ]],
+        );
+    }
+    html_table(
+        &mut out,
+        "Daemons",
+        &["Name", "Version"],
+        report
+            .daemons
+            .iter()
+            .map(|d| {
+                let version = d.version.as_deref().unwrap_or("not reachable");
+                vec![d.name.to_string(), version.to_string()]
+            })
+            .collect(),
+    );
+    html_table(
+        &mut out,
+        "VMs",
+        &["ID", "Name", "State", "vCPUs", "Memory", "Disks", "NICs"],
+        report
+            .vms
+            .iter()
+            .map(|vm| {
+                vec![
+                    vm.id.clone(),
+                    vm.name.clone().unwrap_or_default(),
+                    vm.state.clone(),
+                    vm.vcpus.to_string(),
+                    format!("{} MiB", vm.memory_mb),
+                    vm.disks.join(", "),
+                    vm.nics.to_string(),
+                ]
+            })
+            .collect(),
+    );
+    html_table(
+        &mut out,
+        "Pods",
+        &["ID", "Name", "State", "VM", "IP", "Images"],
+        report
+            .pods
+            .iter()
+            .map(|pod| {
+                vec![
+                    pod.id.clone(),
+                    pod.name.clone(),
+                    pod.state.clone(),
+                    pod.vm_id.clone(),
+                    pod.ip_address.clone(),
+                    pod.images.join(", "),
+                ]
+            })
+            .collect(),
+    );
+    html_table(
+        &mut out,
+        "Networks",
+        &["ID", "Name", "IPv4", "IPv6", "Public", "NICs"],
+        report
+            .networks
+            .iter()
+            .map(|n| {
+                vec![
+                    n.id.clone(),
+                    n.name.clone(),
+                    n.ipv4_subnet.clone(),
+                    n.ipv6_prefix.clone(),
+                    if n.public { "yes" } else { "no" }.to_string(),
+                    n.nic_count.to_string(),
+                ]
+            })
+            .collect(),
+    );
+    html_table(
+        &mut out,
+        "NICs",
+        &[
+            "ID", "Name", "Network", "MAC", "IPv4", "IPv6", "State", "Link",
+        ],
+        report
+            .nics
+            .iter()
+            .map(|n| {
+                vec![
+                    n.id.clone(),
+                    n.name.clone(),
+                    n.network_id.clone(),
+                    n.mac_address.clone(),
+                    n.ipv4_address.clone(),
+                    n.ipv6_address.clone(),
+                    n.state.clone(),
+                    n.link.clone(),
+                ]
+            })
+            .collect(),
+    );
+    if let Some(pool) = &report.pool {
+        html_table(
+            &mut out,
+            "Storage pool",
+            &[
+                "Name",
+                "Size",
+                "Used",
+                "Available",
+                "Provisioned",
+                "Compression",
+            ],
+            vec![vec![
+                pool.name.clone(),
+                format_bytes(pool.total_bytes),
+                format_bytes(pool.used_bytes),
+                format_bytes(pool.available_bytes),
+                format_bytes(pool.provisioned_bytes),
+                format!("{:.2}x", pool.compression_ratio),
+            ]],
+        );
+    }
+    html_table(
+        &mut out,
+        "Volumes",
+        &["ID", "Name", "Size", "Used", "Snapshots", "Owner"],
+        report
+            .volumes
+            .iter()
+            .map(|v| {
+                vec![
+                    v.id.clone(),
+                    v.name.clone(),
+                    format_bytes(v.volsize_bytes),
+                    format_bytes(v.used_bytes),
+                    v.snapshots.to_string(),
+                    v.owner.clone().unwrap_or_default(),
+                ]
+            })
+            .collect(),
+    );
+    html_table(
+        &mut out,
+        "Templates",
+        &["ID", "Name", "Size", "Clones"],
+        report
+            .templates
+            .iter()
+            .map(|t| {
+                vec![
+                    t.id.clone(),
+                    t.name.clone(),
+                    format_bytes(t.size_bytes),
+                    t.clone_count.to_string(),
+                ]
+            })
+            .collect(),
+    );
+
+    out.push_str("</body></html>\n");
+    out
+}
+
+fn write_output(path: Option<&Path>, contents: &str) -> Result<(), Error> {
+    match path {
+        Some(path) => std::fs::write(path, contents)
+            .map_err(|e| format!("Failed to write {}: {}", path.display(), e).into()),
+        None => {
+            print!("{}", contents);
+            Ok(())
+        }
+    }
+}
+
+pub async fn run(clients: &mut Clients, output: Output) -> Result<(), Error> {
+    let mut report = gather(clients).await;
+
+    let (dir, logs_since) = match output {
+        Output::Json(path) => {
+            let json = serde_json::to_string_pretty(&report)? + "\n";
+            return write_output(path.as_deref(), &json);
+        }
+        Output::Html(path) => return write_output(path.as_deref(), &to_html(&report)),
+        Output::SupportBundle { dir, logs_since } => (dir, logs_since),
+    };
+
+    let dir = dir.unwrap_or_else(|| {
+        let host = report
+            .host
+            .as_ref()
+            .map_or("unknown", |h| h.hostname.as_str());
+        let at = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+        PathBuf::from(format!("mvirt-support-{}-{}", host, at))
+    });
+    std::fs::create_dir(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    // Failed log exports are noted in the report, which is written last
+    match clients.log.as_mut() {
+        Some(log) => {
+            let opts = log_export::Options {
+                since: Some(logs_since),
+                until: None,
+                object: None,
+                plain: false,
+                output: dir.join("logs.ndjson.gz"),
+                resume: false,
+            };
+            if let Err(e) = log_export::export(log, opts).await {
+                report.errors.push(format!("logs: {}", e));
+            }
+        }
+        None => report.errors.push("mvirt-log: not reachable".to_string()),
+    }
+
+    let json = serde_json::to_string_pretty(&report)? + "\n";
+    write_output(Some(&dir.join("report.json")), &json)?;
+    write_output(Some(&dir.join("report.html")), &to_html(&report))?;
+    println!("Wrote support bundle to {}", dir.display());
+    if !report.errors.is_empty() {
+        eprintln!(
+            "{} part(s) could not be gathered, see report.json",
+            report.errors.len()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,124 @@
+//! `mvirt snapshot create --pod`: application-consistent snapshots of a
+//! volume a pod writes to.
+//!
+//! A plain snapshot is crash-consistent, holding what a power cut would
+//! leave on disk. With hooks, a freeze command runs in one of the pod's
+//! containers first (flushing a database and blocking its writes, or
+//! `fsfreeze -f`), and a thaw command runs after the snapshot, whether it
+//! was taken or not. Both run through mvirt-one as `sh -c <command>` and
+//! are killed after the hook timeout.
+//!
+//! A freeze that fails or times out aborts the snapshot, or with
+//! `--on-hook-failure skip` leaves a crash-consistent one. A failed thaw is
+//! an error either way, since the application may still be frozen.
+
+use std::time::Duration;
+
+use mvirt_common::request_id::RequestIdChannel;
+
+use crate::proto::RunPodCommandRequest;
+use crate::proto::pod_service_client::PodServiceClient;
+use crate::zfs_proto::{self, zfs_service_client::ZfsServiceClient};
+
+type Error = Box<dyn std::error::Error>;
+
+/// Guest hooks around a snapshot.
+pub struct Hooks {
+    pub pod_id: String,
+    /// Container ID or name; empty for the pod's first container
+    pub container: String,
+    pub freeze: String,
+    pub thaw: Option<String>,
+    pub timeout: Duration,
+    /// Take a crash-consistent snapshot if the freeze fails
+    pub skip_on_failure: bool,
+}
+
+/// Run `command` in the hooks' container.
+async fn run_hook(
+    client: &mut PodServiceClient<RequestIdChannel>,
+    hooks: &Hooks,
+    what: &str,
+    command: &str,
+) -> Result<(), String> {
+    let response = client
+        .run_pod_command(RunPodCommandRequest {
+            pod_id: hooks.pod_id.clone(),
+            container: hooks.container.clone(),
+            command: vec!["sh".to_string(), "-c".to_string(), command.to_string()],
+            timeout_seconds: hooks.timeout.as_secs() as u32,
+        })
+        .await
+        .map_err(|s| format!("{} hook failed: {}", what, s.message()))?
+        .into_inner();
+
+    if response.timed_out {
+        return Err(format!(
+            "{} hook timed out after {}s",
+            what,
+            hooks.timeout.as_secs()
+        ));
+    }
+    if response.exit_code != 0 {
+        let stderr = response.stderr.trim();
+        return Err(match stderr {
+            "" => format!("{} hook exited with {}", what, response.exit_code),
+            _ => format!(
+                "{} hook exited with {}: {}",
+                what, response.exit_code, stderr
+            ),
+        });
+    }
+    Ok(())
+}
+
+async fn thaw(
+    client: &mut PodServiceClient<RequestIdChannel>,
+    hooks: &Hooks,
+) -> Result<(), String> {
+    match &hooks.thaw {
+        Some(command) => {
+            eprintln!("Thawing...");
+            run_hook(client, hooks, "thaw", command).await
+        }
+        None => Ok(()),
+    }
+}
+
+/// Snapshot `volume` as `name` between the freeze and thaw hooks.
+pub async fn snapshot(
+    zfs: &mut ZfsServiceClient<RequestIdChannel>,
+    pods: &mut PodServiceClient<RequestIdChannel>,
+    volume: &str,
+    name: &str,
+    hooks: &Hooks,
+) -> Result<zfs_proto::Snapshot, Error> {
+    eprintln!("Freezing...");
+    if let Err(e) = run_hook(pods, hooks, "freeze", &hooks.freeze).await {
+        if !hooks.skip_on_failure {
+            // The freeze may have got partway
+            if let Err(thaw_err) = thaw(pods, hooks).await {
+                eprintln!("Error: {}", thaw_err);
+            }
+            return Err(format!("{}; no snapshot taken", e).into());
+        }
+        eprintln!("Warning: {}; taking a crash-consistent snapshot", e);
+    }
+
+    let snapshot = zfs
+        .create_snapshot(zfs_proto::CreateSnapshotRequest {
+            volume_name: volume.to_string(),
+            snapshot_name: name.to_string(),
+        })
+        .await;
+    let thawed = thaw(pods, hooks).await;
+    let snapshot = snapshot?.into_inner();
+    if let Err(e) = thawed {
+        return Err(format!(
+            "snapshot {}@{} taken, but {}; the application may still be frozen",
+            volume, snapshot.name, e
+        )
+        .into());
+    }
+    Ok(snapshot)
+}
@@ -0,0 +1,108 @@
+//! `mvirt admin start-stats`: start latency percentiles per stage, and the
+//! stage breakdown of single starts for `mvirt get` and `pod run`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mvirt_common::request_id::RequestIdChannel;
+use tabled::{Table, Tabled};
+
+use crate::proto::vm_service_client::VmServiceClient;
+use crate::proto::{GetStartStatsRequest, StageStats, StartKind, StartStage, StartTiming};
+
+type Error = Box<dyn std::error::Error>;
+
+/// A stage this CLI ran before StartVm/StartPod, from `started_at` until now.
+pub fn stage_since(name: &str, started_at: SystemTime) -> StartStage {
+    StartStage {
+        name: name.to_string(),
+        started_at_ms: started_at
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64,
+        duration_ms: started_at.elapsed().unwrap_or_default().as_millis() as u64,
+    }
+}
+
+/// Milliseconds as `850ms` or `1.2s`.
+pub fn format_ms(ms: u64) -> String {
+    if ms < 1000 {
+        format!("{}ms", ms)
+    } else {
+        format!("{:.1}s", ms as f64 / 1000.0)
+    }
+}
+
+/// A start as `1.8s (queued 0ms, spawn 1.2s, ...)`.
+pub fn format_timing(timing: &StartTiming) -> String {
+    let stages: Vec<String> = timing
+        .stages
+        .iter()
+        .map(|s| format!("{} {}", s.name, format_ms(s.duration_ms)))
+        .collect();
+    format!("{} ({})", format_ms(timing.total_ms), stages.join(", "))
+}
+
+#[derive(Tabled)]
+struct StageRow {
+    #[tabled(rename = "STAGE")]
+    name: String,
+    #[tabled(rename = "COUNT")]
+    count: u32,
+    #[tabled(rename = "P50")]
+    p50: String,
+    #[tabled(rename = "P90")]
+    p90: String,
+    #[tabled(rename = "P99")]
+    p99: String,
+    #[tabled(rename = "MAX")]
+    max: String,
+}
+
+impl From<StageStats> for StageRow {
+    fn from(s: StageStats) -> Self {
+        Self {
+            name: s.name,
+            count: s.count,
+            p50: format_ms(s.p50_ms),
+            p90: format_ms(s.p90_ms),
+            p99: format_ms(s.p99_ms),
+            max: format_ms(s.max_ms),
+        }
+    }
+}
+
+/// Print the percentiles of the most recent starts of `kind` (vm, pod,
+/// or both if `None`); `limit` 0 takes all the VMM keeps.
+pub async fn show(
+    client: &mut VmServiceClient<RequestIdChannel>,
+    kind: Option<&str>,
+    limit: u32,
+) -> Result<(), Error> {
+    let kind = match kind {
+        None => StartKind::Unspecified,
+        Some("vm") => StartKind::Vm,
+        Some("pod") => StartKind::Pod,
+        Some(other) => return Err(format!("unknown kind '{}', use vm or pod", other).into()),
+    };
+    let stats = client
+        .get_start_stats(GetStartStatsRequest {
+            kind: kind.into(),
+            limit,
+        })
+        .await?
+        .into_inner();
+
+    if stats.count == 0 {
+        println!("No starts recorded");
+        return Ok(());
+    }
+    println!("Starts: {}", stats.count);
+    let rows: Vec<StageRow> = stats
+        .stages
+        .into_iter()
+        .chain(stats.total)
+        .map(StageRow::from)
+        .collect();
+    println!("{}", Table::new(rows));
+    Ok(())
+}
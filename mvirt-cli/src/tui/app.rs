@@ -6,10 +6,12 @@ use chrono::Local;
 use ratatui::widgets::TableState;
 use tokio::sync::mpsc;
 
+use crate::columns::{self, Field};
 use crate::proto::{SystemInfo, Vm, VmState};
-use crate::tui::modals::network_create::NetworkCreateModal;
+use crate::tui::modals::network_create::{NetworkCreateModal, NetworkSubmit};
 use crate::tui::modals::nic_create::NicCreateModal;
 use crate::tui::modals::vm_create::CreateModal;
+use crate::tui::modals::vm_view::{VmViewPrompt, VmViewPromptKind};
 use crate::tui::modals::volume_clone::VolumeCloneModal;
 use crate::tui::modals::volume_create::VolumeCreateModal;
 use crate::tui::modals::volume_import::VolumeImportModal;
@@ -27,9 +29,16 @@ use mvirt_log::LogEntry;
 
 pub struct App {
     // VM state
+    /// VMs as shown: `all_vms` filtered by `vm_filter` and sorted by `vm_sort`
     pub vms: Vec<Vm>,
+    pub all_vms: Vec<Vm>,
     pub system_info: Option<SystemInfo>,
     pub table_state: TableState,
+    /// Label keys shown as extra VM table columns
+    pub vm_label_columns: Vec<String>,
+    pub vm_filter: String,
+    pub vm_sort: Option<Field>,
+    pub vm_view_prompt: Option<VmViewPrompt>,
 
     // Common state
     pub should_quit: bool,
@@ -115,8 +124,13 @@ impl App {
         Self {
             // VM state
             vms: Vec::new(),
+            all_vms: Vec::new(),
             system_info: None,
             table_state: TableState::default(),
+            vm_label_columns: Vec::new(),
+            vm_filter: String::new(),
+            vm_sort: None,
+            vm_view_prompt: None,
 
             // Common state
             should_quit: false,
@@ -219,19 +233,10 @@ impl App {
         self.busy = false;
         match result {
             ActionResult::Refreshed(Ok(vms)) => {
-                self.vms = vms;
+                self.all_vms = vms;
                 self.clear_status_if_expired();
                 self.last_refresh = Some(Local::now());
-                if self.vms.is_empty() {
-                    self.table_state.select(None);
-                } else if self.table_state.selected().is_none() {
-                    self.table_state.select(Some(0));
-                } else if let Some(selected) = self.table_state.selected()
-                    && selected >= self.vms.len()
-                {
-                    self.table_state
-                        .select(Some(self.vms.len().saturating_sub(1)));
-                }
+                self.apply_vm_view();
             }
             ActionResult::Refreshed(Err(e)) => {
                 self.set_status(format!("Error: {}", e));
@@ -438,8 +443,9 @@ impl App {
                 templates,
                 volumes,
                 networks,
+                flavors,
             } => {
-                self.open_create_modal_with_data(templates, volumes, networks);
+                self.open_create_modal_with_data(templates, volumes, networks, flavors);
             }
             ActionResult::VmDetailModalReady { vm_id, logs } => {
                 self.detail_view = Some(vm_id);
@@ -490,6 +496,13 @@ impl App {
             ActionResult::NetworkCreated(Err(e)) => {
                 self.set_status(format!("Error: {}", e));
             }
+            ActionResult::NetworkUpdated(Ok(net)) => {
+                self.set_status(format!("Network {} updated", net.name));
+                self.refresh_networks();
+            }
+            ActionResult::NetworkUpdated(Err(e)) => {
+                self.set_status(format!("Error: {}", e));
+            }
             ActionResult::NetworkDeleted(Ok(())) => {
                 self.set_status("Network deleted".to_string());
                 self.refresh_networks();
@@ -623,6 +636,7 @@ impl App {
         templates: Vec<crate::zfs_proto::Template>,
         volumes: Vec<crate::zfs_proto::Volume>,
         networks: Vec<crate::net_proto::Network>,
+        flavors: Vec<crate::api::Flavor>,
     ) {
         // Convert networks to NetworkItems
         let network_items: Vec<NetworkItem> = networks
@@ -634,11 +648,10 @@ impl App {
             .collect();
 
         // Pass storage and network data
-        self.create_modal = Some(CreateModal::with_storage_and_networks(
-            &templates,
-            &volumes,
-            &network_items,
-        ));
+        let mut modal =
+            CreateModal::with_storage_and_networks(&templates, &volumes, &network_items);
+        modal.flavors = flavors;
+        self.create_modal = Some(modal);
         self.status_message = None;
         self.status_message_time = None;
     }
@@ -648,7 +661,7 @@ impl App {
     }
 
     pub fn submit_create(&mut self) {
-        if let Some(modal) = &self.create_modal {
+        if let Some(modal) = &mut self.create_modal {
             match modal.validate() {
                 Ok(params) => {
                     self.set_status("Creating VM...".to_string());
@@ -656,7 +669,7 @@ impl App {
                     self.create_modal = None;
                 }
                 Err(e) => {
-                    self.set_status(format!("Error: {}", e));
+                    modal.error = Some(e.to_string());
                 }
             }
         }
@@ -745,7 +758,94 @@ impl App {
     }
 
     pub fn get_vm_by_id(&self, id: &str) -> Option<&Vm> {
-        self.vms.iter().find(|vm| vm.id == id)
+        self.all_vms.iter().find(|vm| vm.id == id)
+    }
+
+    // === VM table columns, filter and sort ===
+
+    /// Rebuild `vms` from `all_vms`, keeping the selected VM selected.
+    fn apply_vm_view(&mut self) {
+        let selected_id = self.selected_vm().map(|vm| vm.id.clone());
+        self.vms = self
+            .all_vms
+            .iter()
+            .filter(|vm| columns::matches(vm, &self.vm_filter))
+            .cloned()
+            .collect();
+        if let Some(field) = &self.vm_sort {
+            self.vms.sort_by(|a, b| field.compare(a, b));
+        }
+
+        let position = selected_id.and_then(|id| self.vms.iter().position(|vm| vm.id == id));
+        if self.vms.is_empty() {
+            self.table_state.select(None);
+        } else if position.is_some() {
+            self.table_state.select(position);
+        } else if self.table_state.selected().is_none() {
+            self.table_state.select(Some(0));
+        } else if let Some(selected) = self.table_state.selected()
+            && selected >= self.vms.len()
+        {
+            self.table_state
+                .select(Some(self.vms.len().saturating_sub(1)));
+        }
+    }
+
+    /// Sortable fields in the order `o` cycles through them.
+    fn vm_sort_fields(&self) -> Vec<Field> {
+        let mut fields = vec![Field::Name, Field::State, Field::Vcpus, Field::Memory];
+        fields.extend(self.vm_label_columns.iter().cloned().map(Field::Label));
+        fields
+    }
+
+    pub fn cycle_vm_sort(&mut self) {
+        let fields = self.vm_sort_fields();
+        let next = match &self.vm_sort {
+            None => fields.first(),
+            Some(current) => fields
+                .iter()
+                .position(|f| f == current)
+                .and_then(|i| fields.get(i + 1)),
+        };
+        self.vm_sort = next.cloned();
+        self.apply_vm_view();
+    }
+
+    pub fn open_vm_view_prompt(&mut self, kind: VmViewPromptKind) {
+        let input = match kind {
+            VmViewPromptKind::Columns => self.vm_label_columns.join(","),
+            VmViewPromptKind::Filter => self.vm_filter.clone(),
+        };
+        self.vm_view_prompt = Some(VmViewPrompt::new(kind, input));
+    }
+
+    pub fn close_vm_view_prompt(&mut self) {
+        self.vm_view_prompt = None;
+    }
+
+    pub fn submit_vm_view_prompt(&mut self) {
+        let Some(prompt) = self.vm_view_prompt.take() else {
+            return;
+        };
+        match prompt.kind {
+            VmViewPromptKind::Columns => {
+                self.vm_label_columns = prompt
+                    .input
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|k| !k.is_empty())
+                    .map(String::from)
+                    .collect();
+                // Drop a sort on a label column that is no longer shown
+                if let Some(Field::Label(key)) = &self.vm_sort
+                    && !self.vm_label_columns.contains(key)
+                {
+                    self.vm_sort = None;
+                }
+            }
+            VmViewPromptKind::Filter => self.vm_filter = prompt.input.trim().to_string(),
+        }
+        self.apply_vm_view();
     }
 
     // === View Navigation ===
@@ -1574,22 +1674,54 @@ impl App {
         self.network_create_modal = None;
     }
 
+    pub fn open_network_edit_modal(&mut self) {
+        if let Some(net) = self
+            .networks_table_state
+            .selected()
+            .and_then(|i| self.network.networks.get(i))
+        {
+            self.network_create_modal = Some(NetworkCreateModal::edit(net));
+        }
+    }
+
     pub fn submit_network_create(&mut self) {
-        if let Some(modal) = &self.network_create_modal {
+        if let Some(modal) = &mut self.network_create_modal {
             match modal.validate() {
-                Ok((name, ipv4_subnet, ipv6_prefix, dns_servers, is_public)) => {
+                Ok(NetworkSubmit::Create {
+                    name,
+                    ipv4_subnet,
+                    ipv6_prefix,
+                    dns_servers,
+                    ntp_servers,
+                    is_public,
+                }) => {
                     self.set_status(format!("Creating network {}...", name));
                     self.send_action(Action::CreateNetwork {
                         name,
                         ipv4_subnet,
                         ipv6_prefix,
                         dns_servers,
+                        ntp_servers,
                         is_public,
                     });
                     self.network_create_modal = None;
                 }
+                Ok(NetworkSubmit::Update {
+                    id,
+                    name,
+                    dns_servers,
+                    ntp_servers,
+                }) => {
+                    self.set_status(format!("Updating network {}...", name));
+                    self.send_action(Action::UpdateNetwork {
+                        id,
+                        dns_servers,
+                        ntp_servers,
+                    });
+                    self.network_create_modal = None;
+                }
                 Err(e) => {
-                    self.set_status(format!("Error: {}", e));
+                    modal.error = Some(e);
                 }
             }
         }
@@ -6,10 +6,11 @@ use crossterm::event::{self, Event, KeyCode, KeyEventKind};
 use crossterm::terminal::{
     EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode,
 };
+use mvirt_common::request_id::RequestIdChannel;
 use ratatui::prelude::*;
 use tokio::sync::mpsc;
-use tonic::transport::Channel;
 
+use crate::api::ApiClient;
 use crate::net_proto::net_service_client::NetServiceClient;
 use crate::proto::vm_service_client::VmServiceClient;
 use crate::zfs_proto::zfs_service_client::ZfsServiceClient;
@@ -23,6 +24,7 @@ pub mod widgets;
 mod worker;
 
 use app::App;
+use modals::vm_view::VmViewPromptKind;
 use types::{Action, NetworkFocus, StorageFocus, View};
 
 fn draw(frame: &mut Frame, app: &mut App) {
@@ -33,6 +35,9 @@ fn draw(frame: &mut Frame, app: &mut App) {
                 frame,
                 &app.vms,
                 &mut app.table_state,
+                &app.vm_label_columns,
+                app.vm_sort.as_ref(),
+                &app.vm_filter,
                 app.system_info.as_ref(),
                 app.status_message.as_deref(),
                 app.confirm_delete.as_deref(),
@@ -109,6 +114,11 @@ fn draw(frame: &mut Frame, app: &mut App) {
         modals::vm_create::draw(frame, modal);
     }
 
+    // VM table column/filter prompt
+    if let Some(prompt) = &app.vm_view_prompt {
+        modals::vm_view::draw(frame, prompt);
+    }
+
     // File Picker overlay (on top of modal)
     if let Some(picker) = &app.file_picker {
         widgets::file_picker::draw(frame, picker);
@@ -159,10 +169,11 @@ fn draw(frame: &mut Frame, app: &mut App) {
 }
 
 pub async fn run(
-    vm_client: Option<VmServiceClient<Channel>>,
-    zfs_client: Option<ZfsServiceClient<Channel>>,
-    log_client: Option<LogServiceClient<Channel>>,
-    net_client: Option<NetServiceClient<Channel>>,
+    vm_client: Option<VmServiceClient<RequestIdChannel>>,
+    zfs_client: Option<ZfsServiceClient<RequestIdChannel>>,
+    log_client: Option<LogServiceClient<RequestIdChannel>>,
+    net_client: Option<NetServiceClient<RequestIdChannel>>,
+    api: Option<ApiClient>,
 ) -> io::Result<()> {
     let (action_tx, action_rx) = mpsc::unbounded_channel();
     let (result_tx, result_rx) = mpsc::unbounded_channel();
@@ -172,7 +183,7 @@ pub async fn run(
     let log_available = log_client.is_some();
     let net_available = net_client.is_some();
     tokio::spawn(worker::action_worker(
-        vm_client, zfs_client, log_client, net_client, action_rx, result_tx,
+        vm_client, zfs_client, log_client, net_client, api, action_rx, result_tx,
     ));
 
     enable_raw_mode()?;
@@ -269,6 +280,8 @@ pub async fn run(
                 handle_detail_view_input(&mut app, key.code);
             } else if app.create_modal.is_some() {
                 handle_create_modal_input(&mut app, key.code);
+            } else if app.vm_view_prompt.is_some() {
+                handle_vm_view_prompt_input(&mut app, key.code);
             } else if app.confirm_kill.is_some() {
                 handle_confirm_kill_input(&mut app, key.code);
             } else if app.confirm_delete.is_some() {
@@ -346,6 +359,10 @@ fn handle_detail_view_input(app: &mut App, key_code: KeyCode) {
 }
 
 fn handle_create_modal_input(app: &mut App, key_code: KeyCode) {
+    if let Some(modal) = &mut app.create_modal {
+        modal.error = None;
+    }
+
     // Check if we're in "adding data disk" mode
     let adding_data_disk = app
         .create_modal
@@ -374,7 +391,7 @@ fn handle_create_modal_input(app: &mut App, key_code: KeyCode) {
                 if let Some(modal) = &mut app.create_modal
                     && let Err(e) = modal.confirm_add_data_disk()
                 {
-                    app.status_message = Some(e.to_string());
+                    modal.error = Some(e.to_string());
                 }
             }
             KeyCode::Backspace => {
@@ -420,7 +437,9 @@ fn handle_create_modal_input(app: &mut App, key_code: KeyCode) {
         }
         KeyCode::Down => {
             if let Some(modal) = &mut app.create_modal {
-                if modal.is_disk_field() {
+                if modal.is_flavor_field() {
+                    modal.flavor_select_next();
+                } else if modal.is_disk_field() {
                     modal.disk_select_next();
                 } else if modal.is_network_field() {
                     modal.network_select_next();
@@ -435,7 +454,9 @@ fn handle_create_modal_input(app: &mut App, key_code: KeyCode) {
         }
         KeyCode::Up => {
             if let Some(modal) = &mut app.create_modal {
-                if modal.is_disk_field() {
+                if modal.is_flavor_field() {
+                    modal.flavor_select_prev();
+                } else if modal.is_disk_field() {
                     modal.disk_select_prev();
                 } else if modal.is_network_field() {
                     modal.network_select_prev();
@@ -514,6 +535,24 @@ fn handle_create_modal_input(app: &mut App, key_code: KeyCode) {
     }
 }
 
+fn handle_vm_view_prompt_input(app: &mut App, key_code: KeyCode) {
+    match key_code {
+        KeyCode::Esc => app.close_vm_view_prompt(),
+        KeyCode::Enter => app.submit_vm_view_prompt(),
+        KeyCode::Backspace => {
+            if let Some(prompt) = &mut app.vm_view_prompt {
+                prompt.input.pop();
+            }
+        }
+        KeyCode::Char(c) => {
+            if let Some(prompt) = &mut app.vm_view_prompt {
+                prompt.input.push(c);
+            }
+        }
+        _ => {}
+    }
+}
+
 fn handle_confirm_kill_input(app: &mut App, key_code: KeyCode) {
     match key_code {
         KeyCode::Char('y') | KeyCode::Char('Y') => app.confirm_kill(),
@@ -585,6 +624,9 @@ fn handle_vm_input(app: &mut App, key_code: KeyCode) {
         KeyCode::Char('S') => app.stop_selected(),
         KeyCode::Char('k') => app.kill_selected(),
         KeyCode::Char('d') => app.delete_selected(),
+        KeyCode::Char('L') => app.open_vm_view_prompt(VmViewPromptKind::Columns),
+        KeyCode::Char('/') => app.open_vm_view_prompt(VmViewPromptKind::Filter),
+        KeyCode::Char('o') => app.cycle_vm_sort(),
         _ => {}
     }
 }
@@ -1009,6 +1051,11 @@ fn handle_network_input(app: &mut App, key_code: KeyCode) {
                 app.open_network_create_modal();
             }
         }
+        KeyCode::Char('e') => {
+            if app.network_focus == NetworkFocus::Networks {
+                app.open_network_edit_modal();
+            }
+        }
         KeyCode::Char('c') => {
             if app.network_focus == NetworkFocus::Nics {
                 app.open_nic_create_modal();
@@ -1034,6 +1081,10 @@ fn handle_system_input(app: &mut App, key_code: KeyCode) {
 }
 
 fn handle_network_create_modal_input(app: &mut App, key_code: KeyCode) {
+    // The error stays up until the form is touched again
+    if let Some(modal) = &mut app.network_create_modal {
+        modal.error = None;
+    }
     match key_code {
         KeyCode::Esc => app.close_network_create_modal(),
         KeyCode::Tab | KeyCode::Down => {
@@ -1072,13 +1123,14 @@ fn handle_network_create_modal_input(app: &mut App, key_code: KeyCode) {
                 // Toggle checkbox on Space when focused
                 if modal.is_checkbox_field() && c == ' ' {
                     modal.toggle_checkbox();
-                } else if modal.is_name_field()
-                    && (c.is_ascii_alphanumeric() || c == '-' || c == '_')
-                    && let Some(input) = modal.current_input()
-                {
-                    input.push(c);
+                } else if modal.is_name_field() {
+                    if (c.is_ascii_alphanumeric() || c == '-' || c == '_')
+                        && let Some(input) = modal.current_input()
+                    {
+                        input.push(c);
+                    }
                 } else if let Some(input) = modal.current_input() {
-                    // IPv4/IPv6 fields accept more characters
+                    // Address fields accept more characters
                     input.push(c);
                 }
             }
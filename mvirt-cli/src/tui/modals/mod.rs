@@ -1,6 +1,7 @@
 pub mod log_detail;
 pub mod vm_create;
 pub mod vm_detail;
+pub mod vm_view;
 
 // Storage modals
 pub mod volume_clone;
@@ -1,16 +1,58 @@
-//! Modal for creating a new network
+//! Modal for creating a network or editing an existing one
+//!
+//! Editing reuses the create form: only the DHCP-announced servers can be
+//! changed on an existing network, so the other fields are shown read-only.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
 
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Clear, Paragraph};
 
+use crate::net_proto::Network;
+
+const NAME: usize = 0;
+const IPV4: usize = 1;
+const IPV6: usize = 2;
+const DNS: usize = 3;
+const NTP: usize = 4;
+const PUBLIC: usize = 5;
+const SUBMIT: usize = 6;
+
+/// Fields that can be changed on an existing network
+const EDITABLE: [usize; 3] = [DNS, NTP, SUBMIT];
+
 #[derive(Default)]
 pub struct NetworkCreateModal {
+    /// ID of the network being edited, `None` when creating
+    pub editing: Option<String>,
     pub name: String,
     pub ipv4_subnet: String,
     pub ipv6_prefix: String,
     pub dns_servers: String,
+    pub ntp_servers: String,
     pub is_public: bool,
     pub focused_field: usize,
+    /// Validation error from the last submit attempt
+    pub error: Option<String>,
+}
+
+/// Validated form contents
+pub enum NetworkSubmit {
+    Create {
+        name: String,
+        ipv4_subnet: Option<String>,
+        ipv6_prefix: Option<String>,
+        dns_servers: Vec<String>,
+        ntp_servers: Vec<String>,
+        is_public: bool,
+    },
+    Update {
+        id: String,
+        name: String,
+        dns_servers: Vec<String>,
+        ntp_servers: Vec<String>,
+    },
 }
 
 impl NetworkCreateModal {
@@ -21,55 +63,96 @@ impl NetworkCreateModal {
         }
     }
 
+    /// Form prefilled with an existing network, focused on its DNS servers
+    pub fn edit(network: &Network) -> Self {
+        Self {
+            editing: Some(network.id.clone()),
+            name: network.name.clone(),
+            ipv4_subnet: network.ipv4_subnet.clone(),
+            ipv6_prefix: network.ipv6_prefix.clone(),
+            dns_servers: network.dns_servers.join(", "),
+            ntp_servers: network.ntp_servers.join(", "),
+            is_public: network.is_public,
+            focused_field: DNS,
+            error: None,
+        }
+    }
+
     pub fn field_count() -> usize {
-        6 // name, ipv4_subnet, ipv6_prefix, dns_servers, is_public, submit
+        7 // name, ipv4_subnet, ipv6_prefix, dns_servers, ntp_servers, is_public, submit
+    }
+
+    fn is_editable(&self, field: usize) -> bool {
+        self.editing.is_none() || EDITABLE.contains(&field)
     }
 
     pub fn focus_next(&mut self) {
-        self.focused_field = (self.focused_field + 1) % Self::field_count();
+        loop {
+            self.focused_field = (self.focused_field + 1) % Self::field_count();
+            if self.is_editable(self.focused_field) {
+                break;
+            }
+        }
     }
 
     pub fn focus_prev(&mut self) {
-        self.focused_field = if self.focused_field == 0 {
-            Self::field_count() - 1
-        } else {
-            self.focused_field - 1
-        };
+        loop {
+            self.focused_field = if self.focused_field == 0 {
+                Self::field_count() - 1
+            } else {
+                self.focused_field - 1
+            };
+            if self.is_editable(self.focused_field) {
+                break;
+            }
+        }
     }
 
     pub fn current_input(&mut self) -> Option<&mut String> {
+        if !self.is_editable(self.focused_field) {
+            return None;
+        }
         match self.focused_field {
-            0 => Some(&mut self.name),
-            1 => Some(&mut self.ipv4_subnet),
-            2 => Some(&mut self.ipv6_prefix),
-            3 => Some(&mut self.dns_servers),
+            NAME => Some(&mut self.name),
+            IPV4 => Some(&mut self.ipv4_subnet),
+            IPV6 => Some(&mut self.ipv6_prefix),
+            DNS => Some(&mut self.dns_servers),
+            NTP => Some(&mut self.ntp_servers),
             _ => None,
         }
     }
 
     pub fn is_name_field(&self) -> bool {
-        self.focused_field == 0
+        self.focused_field == NAME
     }
 
     pub fn is_checkbox_field(&self) -> bool {
-        self.focused_field == 4
+        self.focused_field == PUBLIC
     }
 
     pub fn toggle_checkbox(&mut self) {
-        if self.focused_field == 4 {
+        if self.focused_field == PUBLIC && self.editing.is_none() {
             self.is_public = !self.is_public;
         }
     }
 
     pub fn is_submit_field(&self) -> bool {
-        self.focused_field == 5
+        self.focused_field == SUBMIT
     }
 
-    /// Returns (name, ipv4_subnet or None, ipv6_prefix or None, dns_servers, is_public)
-    #[allow(clippy::type_complexity)]
-    pub fn validate(
-        &self,
-    ) -> Result<(String, Option<String>, Option<String>, Vec<String>, bool), String> {
+    pub fn validate(&self) -> Result<NetworkSubmit, String> {
+        let dns_servers = parse_servers(&self.dns_servers, "DNS server")?;
+        let ntp_servers = parse_servers(&self.ntp_servers, "NTP server")?;
+
+        if let Some(id) = &self.editing {
+            return Ok(NetworkSubmit::Update {
+                id: id.clone(),
+                name: self.name.clone(),
+                dns_servers,
+                ntp_servers,
+            });
+        }
+
         if self.name.is_empty() {
             return Err("Name is required".to_string());
         }
@@ -84,20 +167,16 @@ impl NetworkCreateModal {
         let ipv4 = if self.ipv4_subnet.is_empty() {
             None
         } else {
-            // Basic CIDR validation
-            if !self.ipv4_subnet.contains('/') {
-                return Err("IPv4 subnet must be in CIDR format (e.g., 10.0.0.0/24)".to_string());
-            }
+            check_cidr::<Ipv4Addr>(&self.ipv4_subnet, 32)
+                .map_err(|e| format!("IPv4 subnet {} (e.g., 10.0.0.0/24)", e))?;
             Some(self.ipv4_subnet.clone())
         };
 
         let ipv6 = if self.ipv6_prefix.is_empty() {
             None
         } else {
-            // Basic CIDR validation
-            if !self.ipv6_prefix.contains('/') {
-                return Err("IPv6 prefix must be in CIDR format (e.g., 2001:db8::/64)".to_string());
-            }
+            check_cidr::<Ipv6Addr>(&self.ipv6_prefix, 128)
+                .map_err(|e| format!("IPv6 prefix {} (e.g., 2001:db8::/64)", e))?;
             Some(self.ipv6_prefix.clone())
         };
 
@@ -105,24 +184,53 @@ impl NetworkCreateModal {
             return Err("At least one of IPv4 or IPv6 must be configured".to_string());
         }
 
-        // Parse DNS servers (comma or space separated)
-        let dns_servers: Vec<String> = self
-            .dns_servers
-            .split([',', ' '])
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
+        Ok(NetworkSubmit::Create {
+            name: self.name.clone(),
+            ipv4_subnet: ipv4,
+            ipv6_prefix: ipv6,
+            dns_servers,
+            ntp_servers,
+            is_public: self.is_public,
+        })
+    }
+}
 
-        Ok((self.name.clone(), ipv4, ipv6, dns_servers, self.is_public))
+/// Check `addr/len` notation; the error completes "IPv4 subnet ...".
+fn check_cidr<A: FromStr>(s: &str, max_len: u8) -> Result<(), &'static str> {
+    let (addr, len) = s.split_once('/').ok_or("must be in CIDR format")?;
+    addr.trim()
+        .parse::<A>()
+        .map_err(|_| "has an invalid address")?;
+    match len.trim().parse::<u8>() {
+        Ok(len) if len <= max_len => Ok(()),
+        _ => Err("has an invalid prefix length"),
     }
 }
 
+/// Parse a comma or space separated list of IP addresses.
+fn parse_servers(s: &str, what: &str) -> Result<Vec<String>, String> {
+    s.split([',', ' '])
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| {
+            s.parse::<IpAddr>()
+                .map(|_| s.to_string())
+                .map_err(|_| format!("Invalid {} '{}'", what, s))
+        })
+        .collect()
+}
+
 pub fn draw(frame: &mut Frame, modal: &NetworkCreateModal) {
-    let area = centered_rect(60, 18, frame.area());
+    let area = centered_rect(60, 22, frame.area());
     frame.render_widget(Clear, area);
 
+    let title = if modal.editing.is_some() {
+        " Edit Network "
+    } else {
+        " Create Network "
+    };
     let block = Block::default()
-        .title(" Create Network ")
+        .title(title)
         .borders(Borders::ALL)
         .border_style(Style::default().fg(Color::Cyan));
     frame.render_widget(block.clone(), area);
@@ -136,98 +244,42 @@ pub fn draw(frame: &mut Frame, modal: &NetworkCreateModal) {
             Constraint::Length(2), // IPv4 Subnet
             Constraint::Length(2), // IPv6 Prefix
             Constraint::Length(2), // DNS Servers
+            Constraint::Length(2), // NTP Servers
             Constraint::Length(2), // Public checkbox
-            Constraint::Length(1), // Spacing
+            Constraint::Length(1), // Validation error
             Constraint::Length(2), // Submit
         ])
         .split(inner);
 
-    // Name field
-    let name_style = if modal.focused_field == 0 {
-        Style::default().fg(Color::Yellow)
-    } else {
-        Style::default().fg(Color::White)
-    };
-    let name_line = Line::from(vec![
-        Span::styled(" Name: ", Style::default().fg(Color::Cyan)),
-        Span::styled(&modal.name, name_style),
-        if modal.focused_field == 0 {
-            Span::styled("_", Style::default().fg(Color::Yellow))
-        } else {
-            Span::raw("")
-        },
-    ]);
-    frame.render_widget(Paragraph::new(name_line), chunks[0]);
-
-    // IPv4 Subnet field
-    let ipv4_style = if modal.focused_field == 1 {
-        Style::default().fg(Color::Yellow)
-    } else {
-        Style::default().fg(Color::White)
-    };
-    let ipv4_line = Line::from(vec![
-        Span::styled(" IPv4 Subnet: ", Style::default().fg(Color::Cyan)),
-        Span::styled(&modal.ipv4_subnet, ipv4_style),
-        if modal.focused_field == 1 {
-            Span::styled("_", Style::default().fg(Color::Yellow))
-        } else {
-            Span::raw("")
-        },
-        if modal.ipv4_subnet.is_empty() && modal.focused_field != 1 {
-            Span::styled(" (e.g. 10.0.0.0/24)", Style::default().fg(Color::DarkGray))
-        } else {
-            Span::raw("")
-        },
-    ]);
-    frame.render_widget(Paragraph::new(ipv4_line), chunks[1]);
-
-    // IPv6 Prefix field
-    let ipv6_style = if modal.focused_field == 2 {
-        Style::default().fg(Color::Yellow)
-    } else {
-        Style::default().fg(Color::White)
-    };
-    let ipv6_line = Line::from(vec![
-        Span::styled(" IPv6 Prefix: ", Style::default().fg(Color::Cyan)),
-        Span::styled(&modal.ipv6_prefix, ipv6_style),
-        if modal.focused_field == 2 {
-            Span::styled("_", Style::default().fg(Color::Yellow))
-        } else {
-            Span::raw("")
-        },
-        if modal.ipv6_prefix.is_empty() && modal.focused_field != 2 {
-            Span::styled(
-                " (e.g. 2001:db8::/64)",
-                Style::default().fg(Color::DarkGray),
-            )
-        } else {
-            Span::raw("")
-        },
-    ]);
-    frame.render_widget(Paragraph::new(ipv6_line), chunks[2]);
-
-    // DNS Servers field
-    let dns_style = if modal.focused_field == 3 {
-        Style::default().fg(Color::Yellow)
-    } else {
-        Style::default().fg(Color::White)
-    };
-    let dns_line = Line::from(vec![
-        Span::styled(" DNS Servers: ", Style::default().fg(Color::Cyan)),
-        Span::styled(&modal.dns_servers, dns_style),
-        if modal.focused_field == 3 {
-            Span::styled("_", Style::default().fg(Color::Yellow))
-        } else {
-            Span::raw("")
-        },
-    ]);
-    frame.render_widget(Paragraph::new(dns_line), chunks[3]);
+    let fields = [
+        (NAME, " Name: ", &modal.name, ""),
+        (
+            IPV4,
+            " IPv4 Subnet: ",
+            &modal.ipv4_subnet,
+            " (e.g. 10.0.0.0/24)",
+        ),
+        (
+            IPV6,
+            " IPv6 Prefix: ",
+            &modal.ipv6_prefix,
+            " (e.g. 2001:db8::/64)",
+        ),
+        (DNS, " DNS Servers: ", &modal.dns_servers, ""),
+        (NTP, " NTP Servers: ", &modal.ntp_servers, " (optional)"),
+    ];
+    for (field, label, value, hint) in fields {
+        let line = text_line(modal, field, label, value, hint);
+        frame.render_widget(Paragraph::new(line), chunks[field]);
+    }
 
     // Public checkbox
-    let public_style = if modal.focused_field == 4 {
+    let public_style = if modal.focused_field == PUBLIC {
         Style::default().fg(Color::Yellow)
-    } else {
+    } else if modal.is_editable(PUBLIC) {
         Style::default().fg(Color::White)
+    } else {
+        Style::default().fg(Color::DarkGray)
     };
     let checkbox = if modal.is_public { "[x]" } else { "[ ]" };
     let public_line = Line::from(vec![
@@ -238,20 +290,68 @@ pub fn draw(frame: &mut Frame, modal: &NetworkCreateModal) {
             Style::default().fg(Color::DarkGray),
         ),
     ]);
-    frame.render_widget(Paragraph::new(public_line), chunks[4]);
+    frame.render_widget(Paragraph::new(public_line), chunks[PUBLIC]);
+
+    if let Some(error) = &modal.error {
+        frame.render_widget(
+            Paragraph::new(Span::styled(
+                format!(" {}", error),
+                Style::default().fg(Color::Red),
+            )),
+            chunks[6],
+        );
+    }
 
     // Submit button
-    let submit_style = if modal.focused_field == 5 {
+    let submit_style = if modal.focused_field == SUBMIT {
         Style::default().fg(Color::Black).bg(Color::Cyan)
     } else {
         Style::default().fg(Color::Cyan)
     };
+    let submit = if modal.editing.is_some() {
+        " [ Save ] "
+    } else {
+        " [ Create ] "
+    };
     frame.render_widget(
-        Paragraph::new(Span::styled(" [ Create ] ", submit_style)).alignment(Alignment::Center),
-        chunks[6],
+        Paragraph::new(Span::styled(submit, submit_style)).alignment(Alignment::Center),
+        chunks[7],
     );
 }
 
+/// One text field: label, value with cursor when focused, and a hint while
+/// empty. Read-only fields are greyed out.
+fn text_line<'a>(
+    modal: &NetworkCreateModal,
+    field: usize,
+    label: &'a str,
+    value: &'a str,
+    hint: &'a str,
+) -> Line<'a> {
+    let focused = modal.focused_field == field;
+    let value_style = if focused {
+        Style::default().fg(Color::Yellow)
+    } else if modal.is_editable(field) {
+        Style::default().fg(Color::White)
+    } else {
+        Style::default().fg(Color::DarkGray)
+    };
+    Line::from(vec![
+        Span::styled(label, Style::default().fg(Color::Cyan)),
+        Span::styled(value, value_style),
+        if focused {
+            Span::styled("_", Style::default().fg(Color::Yellow))
+        } else {
+            Span::raw("")
+        },
+        if value.is_empty() && !focused {
+            Span::styled(hint, Style::default().fg(Color::DarkGray))
+        } else {
+            Span::raw("")
+        },
+    ])
+}
+
 fn centered_rect(percent_x: u16, height: u16, r: Rect) -> Rect {
     let popup_layout = Layout::default()
         .direction(Direction::Vertical)
@@ -1,6 +1,9 @@
+use std::collections::HashMap;
+
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Clear, Paragraph};
 
+use crate::api::Flavor;
 use crate::tui::types::{
     CreateVmParams, CreateVmTab, DataDisk, DiskSourceType, NetworkItem, SshKeySource,
     SshKeysConfig, UserDataMode,
@@ -22,9 +25,12 @@ pub struct CreateModal {
 
     // General tab fields
     pub name: String,
+    pub flavors: Vec<Flavor>,
+    pub selected_flavor: Option<usize>, // None = sizes entered by hand
     pub vcpus: String,
     pub memory_mb: String,
     pub nested_virt: bool,
+    pub labels: String, // KEY=VALUE, comma separated
 
     // Storage tab fields
     pub disk_source_type: DiskSourceType,
@@ -51,6 +57,9 @@ pub struct CreateModal {
     pub ssh_github_user: String,
     pub ssh_local_path: String,
     pub ssh_password: String,
+
+    /// Validation error from the last submit attempt
+    pub error: Option<String>,
 }
 
 impl Default for CreateModal {
@@ -69,9 +78,12 @@ impl CreateModal {
             current_tab: CreateVmTab::General,
             focused_field: 0,
             name: String::new(),
+            flavors: Vec::new(),
+            selected_flavor: None,
             vcpus: "1".to_string(),
             memory_mb: "512".to_string(),
             nested_virt: false,
+            labels: String::new(),
             disk_source_type: DiskSourceType::Template,
             disk_items: Vec::new(),
             selected_disk: 0,
@@ -90,6 +102,7 @@ impl CreateModal {
             ssh_github_user: String::new(),
             ssh_local_path: default_ssh_path,
             ssh_password: String::new(),
+            error: None,
         }
     }
 
@@ -134,7 +147,7 @@ impl CreateModal {
     /// Number of fields in each tab (dynamic based on mode)
     fn field_count_for_tab(&self) -> usize {
         match self.current_tab {
-            CreateVmTab::General => 4, // name, vcpus, memory, nested_virt
+            CreateVmTab::General => 6, // name, flavor, vcpus, memory, nested_virt, labels
             CreateVmTab::Storage => {
                 if self.adding_data_disk {
                     4 // disk, volume_size, new_disk_name, new_disk_size
@@ -187,8 +200,9 @@ impl CreateModal {
         match self.current_tab {
             CreateVmTab::General => match self.focused_field {
                 0 => Some(&mut self.name),
-                1 => Some(&mut self.vcpus),
-                2 => Some(&mut self.memory_mb),
+                2 => Some(&mut self.vcpus),
+                3 => Some(&mut self.memory_mb),
+                5 => Some(&mut self.labels),
                 _ => None,
             },
             CreateVmTab::Storage => {
@@ -232,7 +246,7 @@ impl CreateModal {
 
     pub fn is_numeric_field(&self) -> bool {
         match self.current_tab {
-            CreateVmTab::General => matches!(self.focused_field, 1 | 2), // vcpus, memory
+            CreateVmTab::General => matches!(self.focused_field, 2 | 3), // vcpus, memory
             CreateVmTab::Storage => {
                 if self.adding_data_disk {
                     matches!(self.focused_field, 1 | 3) // volume_size, new_disk_size
@@ -245,13 +259,56 @@ impl CreateModal {
     }
 
     pub fn is_nested_virt_field(&self) -> bool {
-        self.current_tab == CreateVmTab::General && self.focused_field == 3
+        self.current_tab == CreateVmTab::General && self.focused_field == 4
     }
 
     pub fn toggle_nested_virt(&mut self) {
         self.nested_virt = !self.nested_virt;
     }
 
+    pub fn is_flavor_field(&self) -> bool {
+        self.current_tab == CreateVmTab::General && self.focused_field == 1
+    }
+
+    pub fn flavor_select_next(&mut self) {
+        if self.flavors.is_empty() {
+            return;
+        }
+        self.selected_flavor = match self.selected_flavor {
+            None => Some(0),
+            Some(idx) if idx >= self.flavors.len() - 1 => None,
+            Some(idx) => Some(idx + 1),
+        };
+        self.apply_flavor();
+    }
+
+    pub fn flavor_select_prev(&mut self) {
+        if self.flavors.is_empty() {
+            return;
+        }
+        self.selected_flavor = match self.selected_flavor {
+            None => Some(self.flavors.len() - 1),
+            Some(0) => None,
+            Some(idx) => Some(idx - 1),
+        };
+        self.apply_flavor();
+    }
+
+    /// Fill in the selected flavor's sizes and default network. They stay
+    /// editable; the flavor only provides starting values.
+    fn apply_flavor(&mut self) {
+        let Some(flavor) = self.selected_flavor.and_then(|idx| self.flavors.get(idx)) else {
+            return;
+        };
+        self.vcpus = flavor.vcpus.to_string();
+        self.memory_mb = flavor.memory_mb.to_string();
+        if let Some(network) = &flavor.network
+            && let Some(idx) = self.network_items.iter().position(|n| &n.id == network)
+        {
+            self.selected_network = Some(idx);
+        }
+    }
+
     pub fn is_disk_field(&self) -> bool {
         self.current_tab == CreateVmTab::Storage && self.focused_field == 0
     }
@@ -475,7 +532,13 @@ impl CreateModal {
         }
 
         let vcpus: u32 = self.vcpus.parse().map_err(|_| "Invalid vcpus")?;
+        if vcpus == 0 {
+            return Err("VCPUs must be at least 1");
+        }
         let memory_mb: u64 = self.memory_mb.parse().map_err(|_| "Invalid memory")?;
+        if memory_mb == 0 {
+            return Err("Memory must be greater than 0");
+        }
 
         // Parse volume size (only used when cloning from template)
         let volume_size_bytes = if disk_item.source_type == DiskSourceType::Template
@@ -494,6 +557,21 @@ impl CreateModal {
             None
         };
 
+        let mut labels = HashMap::new();
+        for item in self
+            .labels
+            .split(',')
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+        {
+            match item.split_once('=') {
+                Some((key, value)) if !key.trim().is_empty() => {
+                    labels.insert(key.trim().to_string(), value.trim().to_string());
+                }
+                _ => return Err("Labels must be KEY=VALUE, separated by commas"),
+            }
+        }
+
         // Get selected network ID if any
         let network_id = self
             .selected_network
@@ -534,6 +612,11 @@ impl CreateModal {
             ssh_keys_config,
             network_id,
             data_disks: self.data_disks.clone(),
+            labels,
+            flavor_user_data: self
+                .selected_flavor
+                .and_then(|idx| self.flavors.get(idx))
+                .and_then(|f| f.user_data.clone()),
         })
     }
 }
@@ -553,7 +636,7 @@ fn format_size(bytes: u64) -> String {
 pub fn draw(frame: &mut Frame, modal: &CreateModal) {
     let area = frame.area();
     let modal_width = 70.min(area.width.saturating_sub(4));
-    let modal_height = 18.min(area.height.saturating_sub(4));
+    let modal_height = 19.min(area.height.saturating_sub(4));
 
     let modal_area = Rect {
         x: (area.width - modal_width) / 2,
@@ -602,12 +685,16 @@ pub fn draw(frame: &mut Frame, modal: &CreateModal) {
         CreateVmTab::CloudInit => draw_cloud_init_tab(frame, main_chunks[1], modal),
     }
 
-    // Create button (always visible)
+    // Validation error above the create button (always visible)
+    let error_line = match &modal.error {
+        Some(e) => Line::from(Span::styled(e.as_str(), Style::default().fg(Color::Red))),
+        None => Line::raw(""),
+    };
     let submit_style = Style::default().fg(Color::Green);
-    let submit = Paragraph::new(Line::from(vec![Span::styled(
-        "  \u{25b6} Create VM  ",
-        submit_style,
-    )]))
+    let submit = Paragraph::new(vec![
+        error_line,
+        Line::from(vec![Span::styled("  \u{25b6} Create VM  ", submit_style)]),
+    ])
     .alignment(Alignment::Center);
     frame.render_widget(submit, main_chunks[2]);
 }
@@ -654,9 +741,11 @@ fn draw_general_tab(frame: &mut Frame, area: Rect, modal: &CreateModal) {
         .constraints([
             Constraint::Length(1), // Padding
             Constraint::Length(2), // Name
+            Constraint::Length(2), // Flavor
             Constraint::Length(2), // VCPUs
             Constraint::Length(2), // Memory
             Constraint::Length(2), // Nested virt
+            Constraint::Length(2), // Labels
             Constraint::Min(0),    // Spacer
         ])
         .split(area);
@@ -691,8 +780,42 @@ fn draw_general_tab(frame: &mut Frame, area: Rect, modal: &CreateModal) {
     };
     frame.render_widget(Paragraph::new(name_line), chunks[1]);
 
-    // VCPUs (field 1)
-    let vcpus_focused = modal.current_tab == CreateVmTab::General && modal.focused_field == 1;
+    // Flavor (field 1)
+    let flavor_focused = modal.is_flavor_field();
+    let flavor_value = match modal.selected_flavor.and_then(|idx| modal.flavors.get(idx)) {
+        Some(f) => Span::styled(
+            format!("{} ({} vCPU, {} MB)", f.name, f.vcpus, f.memory_mb),
+            if flavor_focused {
+                value_focused
+            } else {
+                value_normal
+            },
+        ),
+        None if modal.flavors.is_empty() => {
+            Span::styled("none available", Style::default().fg(Color::DarkGray))
+        }
+        None => Span::styled("custom", Style::default().fg(Color::DarkGray)),
+    };
+    let flavor_line = Line::from(vec![
+        Span::styled(
+            " Flavor:     ",
+            if flavor_focused {
+                label_focused
+            } else {
+                label_normal
+            },
+        ),
+        flavor_value,
+        if flavor_focused && !modal.flavors.is_empty() {
+            Span::styled(" [↑↓: select]", Style::default().fg(Color::Yellow))
+        } else {
+            Span::raw("")
+        },
+    ]);
+    frame.render_widget(Paragraph::new(flavor_line), chunks[2]);
+
+    // VCPUs (field 2)
+    let vcpus_focused = modal.current_tab == CreateVmTab::General && modal.focused_field == 2;
     let vcpus_cursor = if vcpus_focused { "\u{258c}" } else { "" };
     let vcpus_line = Line::from(vec![
         Span::styled(
@@ -712,10 +835,10 @@ fn draw_general_tab(frame: &mut Frame, area: Rect, modal: &CreateModal) {
             },
         ),
     ]);
-    frame.render_widget(Paragraph::new(vcpus_line), chunks[2]);
+    frame.render_widget(Paragraph::new(vcpus_line), chunks[3]);
 
-    // Memory (field 2)
-    let memory_focused = modal.current_tab == CreateVmTab::General && modal.focused_field == 2;
+    // Memory (field 3)
+    let memory_focused = modal.current_tab == CreateVmTab::General && modal.focused_field == 3;
     let memory_cursor = if memory_focused { "\u{258c}" } else { "" };
     let memory_line = Line::from(vec![
         Span::styled(
@@ -736,10 +859,10 @@ fn draw_general_tab(frame: &mut Frame, area: Rect, modal: &CreateModal) {
         ),
         Span::styled(" MB", Style::default().fg(Color::DarkGray)),
     ]);
-    frame.render_widget(Paragraph::new(memory_line), chunks[3]);
+    frame.render_widget(Paragraph::new(memory_line), chunks[4]);
 
-    // Nested Virt (field 3)
-    let nested_focused = modal.current_tab == CreateVmTab::General && modal.focused_field == 3;
+    // Nested Virt (field 4)
+    let nested_focused = modal.current_tab == CreateVmTab::General && modal.focused_field == 4;
     let nested_str = if modal.nested_virt {
         "[x] Enabled"
     } else {
@@ -768,7 +891,43 @@ fn draw_general_tab(frame: &mut Frame, area: Rect, modal: &CreateModal) {
             Span::raw("")
         },
     ]);
-    frame.render_widget(Paragraph::new(nested_line), chunks[4]);
+    frame.render_widget(Paragraph::new(nested_line), chunks[5]);
+
+    // Labels (field 5)
+    let labels_focused = modal.current_tab == CreateVmTab::General && modal.focused_field == 5;
+    let labels_line = if modal.labels.is_empty() && !labels_focused {
+        Line::from(vec![
+            Span::styled(" Labels:     ", label_normal),
+            Span::styled(
+                "optional, e.g. owner=alice,env=dev",
+                Style::default().fg(Color::DarkGray),
+            ),
+        ])
+    } else {
+        Line::from(vec![
+            Span::styled(
+                " Labels:     ",
+                if labels_focused {
+                    label_focused
+                } else {
+                    label_normal
+                },
+            ),
+            Span::styled(
+                format!(
+                    "{}{}",
+                    modal.labels,
+                    if labels_focused { "\u{258c}" } else { "" }
+                ),
+                if labels_focused {
+                    value_focused
+                } else {
+                    value_normal
+                },
+            ),
+        ])
+    };
+    frame.render_widget(Paragraph::new(labels_line), chunks[6]);
 }
 
 fn draw_storage_tab(frame: &mut Frame, area: Rect, modal: &CreateModal) {
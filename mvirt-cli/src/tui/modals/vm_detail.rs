@@ -116,6 +116,7 @@ pub fn draw(frame: &mut Frame, vm: &Vm, logs: &[LogEntry]) {
         let boot_mode_str = match BootMode::try_from(cfg.boot_mode) {
             Ok(BootMode::Disk) | Ok(BootMode::Unspecified) | Err(_) => "Disk (UEFI)",
             Ok(BootMode::Kernel) => "Kernel (Direct)",
+            Ok(BootMode::Network) => "Network (PXE/HTTP)",
         };
         lines.push(Line::from(vec![
             Span::styled(" Boot Mode:   ", label_style),
@@ -0,0 +1,66 @@
+//! One-line prompt for the VM table's label columns and filter
+
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Clear, Paragraph};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VmViewPromptKind {
+    /// Comma-separated label keys shown as extra columns
+    Columns,
+    /// Filter expression, see [`crate::columns::matches`]
+    Filter,
+}
+
+pub struct VmViewPrompt {
+    pub kind: VmViewPromptKind,
+    pub input: String,
+}
+
+impl VmViewPrompt {
+    pub fn new(kind: VmViewPromptKind, input: String) -> Self {
+        Self { kind, input }
+    }
+}
+
+pub fn draw(frame: &mut Frame, prompt: &VmViewPrompt) {
+    let (title, hint) = match prompt.kind {
+        VmViewPromptKind::Columns => (" Label Columns ", " Label keys, e.g. owner,env"),
+        VmViewPromptKind::Filter => (
+            " Filter VMs ",
+            " env=prod, a label key, or part of a name/ID; empty clears",
+        ),
+    };
+
+    let area = frame.area();
+    let width = area.width.saturating_sub(4).min(70);
+    let area = Rect {
+        x: area.x + (area.width.saturating_sub(width)) / 2,
+        y: area.y + area.height.saturating_sub(6) / 2,
+        width,
+        height: 6.min(area.height),
+    };
+    frame.render_widget(Clear, area);
+
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::Cyan));
+    frame.render_widget(block.clone(), area);
+
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(1)
+        .constraints([Constraint::Length(1), Constraint::Length(1)])
+        .split(block.inner(area));
+
+    let input = Line::from(vec![
+        Span::styled(" > ", Style::default().fg(Color::Cyan)),
+        Span::styled(&prompt.input, Style::default().fg(Color::Yellow)),
+        Span::styled("_", Style::default().fg(Color::Yellow)),
+    ]);
+    frame.render_widget(Paragraph::new(input), chunks[0]);
+    frame.render_widget(
+        Paragraph::new(Span::styled(hint, Style::default().fg(Color::DarkGray))),
+        chunks[1],
+    );
+}
@@ -1,5 +1,8 @@
+use std::collections::HashMap;
+
 use tokio::sync::mpsc;
 
+use crate::api::Flavor;
 use crate::net_proto::{Network, Nic};
 use crate::proto::{SystemInfo, Vm};
 use crate::zfs_proto::{ImportJob, PoolStats, Template, Volume};
@@ -32,7 +35,7 @@ pub enum UserDataMode {
 #[derive(Clone, Copy, PartialEq, Default)]
 pub enum CreateVmTab {
     #[default]
-    General, // Name, VCPUs, Memory, Nested Virt
+    General, // Name, Flavor, VCPUs, Memory, Nested Virt
     Storage,   // Boot Disk, Vol Size
     Network,   // Network selector
     CloudInit, // User-Data mode
@@ -75,6 +78,8 @@ pub struct CreateVmParams {
     pub ssh_keys_config: Option<SshKeysConfig>,
     pub network_id: Option<String>, // Network to join (creates vNIC automatically)
     pub data_disks: Vec<DataDisk>,  // Additional data disks (new volumes)
+    pub labels: HashMap<String, String>,
+    pub flavor_user_data: Option<String>, // Used when no user-data is configured
 }
 
 /// Network item for selection in VM create modal
@@ -234,8 +239,14 @@ pub enum Action {
         ipv4_subnet: Option<String>,
         ipv6_prefix: Option<String>,
         dns_servers: Vec<String>,
+        ntp_servers: Vec<String>,
         is_public: bool,
     },
+    UpdateNetwork {
+        id: String,
+        dns_servers: Vec<String>,
+        ntp_servers: Vec<String>,
+    },
     DeleteNetwork {
         id: String,
     },
@@ -290,6 +301,7 @@ pub enum ActionResult {
         templates: Vec<Template>,
         volumes: Vec<Volume>,
         networks: Vec<Network>,
+        flavors: Vec<Flavor>,
     },
     VmDetailModalReady {
         vm_id: String,
@@ -306,6 +318,7 @@ pub enum ActionResult {
     // Network results
     NetworksRefreshed(Result<Vec<Network>, String>),
     NetworkCreated(Result<Network, String>),
+    NetworkUpdated(Result<Network, String>),
     NetworkDeleted(Result<(), String>),
     NicsLoaded(Result<Vec<Nic>, String>),
     NicCreated(Result<Nic, String>),
@@ -58,6 +58,8 @@ pub fn draw(
         NetworkFocus::Networks => Line::from(vec![
             Span::styled(" n", Style::default().fg(Color::Green).bold()),
             Span::styled(":new ", Style::default().fg(Color::DarkGray)),
+            Span::styled("e", Style::default().fg(Color::Yellow).bold()),
+            Span::styled(":edit ", Style::default().fg(Color::DarkGray)),
             Span::styled("d", Style::default().fg(Color::Red).bold()),
             Span::styled(":delete ", Style::default().fg(Color::DarkGray)),
             Span::styled("Enter", Style::default().fg(Color::Cyan).bold()),
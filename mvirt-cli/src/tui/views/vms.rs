@@ -1,6 +1,7 @@
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
 
+use crate::columns::Field;
 use crate::proto::{SystemInfo, Vm};
 use crate::tui::modals::vm_detail::{format_state, state_style};
 
@@ -9,6 +10,9 @@ pub fn draw(
     frame: &mut Frame,
     vms: &[Vm],
     table_state: &mut TableState,
+    label_columns: &[String],
+    sort: Option<&Field>,
+    filter: &str,
     system_info: Option<&SystemInfo>,
     status_message: Option<&str>,
     confirm_delete: Option<&str>,
@@ -122,16 +126,31 @@ pub fn draw(
         title_chunks[1],
     );
 
-    // VM Table
-    let header = Row::new(vec![
-        Cell::from("ID").style(Style::default().fg(Color::Cyan)),
-        Cell::from("NAME").style(Style::default().fg(Color::Cyan)),
-        Cell::from("STATE").style(Style::default().fg(Color::Cyan)),
-        Cell::from("CPU").style(Style::default().fg(Color::Cyan)),
-        Cell::from("MEM").style(Style::default().fg(Color::Cyan)),
-    ])
-    .style(Style::default().bold())
-    .bottom_margin(1);
+    // VM Table; label columns follow the fixed ones, the sorted column is
+    // marked with an arrow
+    let header_cell = |title: String, field: Option<Field>| {
+        let title = if field.is_some() && field.as_ref() == sort {
+            format!("{} \u{25b4}", title)
+        } else {
+            title
+        };
+        Cell::from(title).style(Style::default().fg(Color::Cyan))
+    };
+    let mut header_cells = vec![
+        header_cell("ID".to_string(), None),
+        header_cell("NAME".to_string(), Some(Field::Name)),
+        header_cell("STATE".to_string(), Some(Field::State)),
+        header_cell("CPU".to_string(), Some(Field::Vcpus)),
+        header_cell("MEM".to_string(), Some(Field::Memory)),
+    ];
+    header_cells.extend(
+        label_columns
+            .iter()
+            .map(|key| header_cell(key.to_uppercase(), Some(Field::Label(key.clone())))),
+    );
+    let header = Row::new(header_cells)
+        .style(Style::default().bold())
+        .bottom_margin(1);
 
     let selected_idx = table_state.selected();
     let rows: Vec<Row> = vms
@@ -147,65 +166,65 @@ pub fn draw(
                 Color::Reset
             };
 
-            Row::new(vec![
+            let fg = if is_selected {
+                Color::White
+            } else {
+                Color::Reset
+            };
+            let mut cells = vec![
                 Cell::from(Span::styled(
                     format!("{}\u{2026}", &vm.id[..8]),
                     Style::default().fg(Color::DarkGray).bg(bg),
                 )),
                 Cell::from(Span::styled(
                     vm.name.clone().unwrap_or_else(|| "-".to_string()),
-                    Style::default()
-                        .fg(if is_selected {
-                            Color::White
-                        } else {
-                            Color::Reset
-                        })
-                        .bg(bg),
+                    Style::default().fg(fg).bg(bg),
                 )),
                 Cell::from(Span::styled(format_state(state), state_style(state).bg(bg))),
                 Cell::from(Span::styled(
                     config.map(|c| c.vcpus.to_string()).unwrap_or_default(),
-                    Style::default()
-                        .fg(if is_selected {
-                            Color::White
-                        } else {
-                            Color::Reset
-                        })
-                        .bg(bg),
+                    Style::default().fg(fg).bg(bg),
                 )),
                 Cell::from(Span::styled(
                     config
                         .map(|c| format!("{} MB", c.memory_mb))
                         .unwrap_or_default(),
-                    Style::default()
-                        .fg(if is_selected {
-                            Color::White
-                        } else {
-                            Color::Reset
-                        })
-                        .bg(bg),
+                    Style::default().fg(fg).bg(bg),
                 )),
-            ])
+            ];
+            cells.extend(label_columns.iter().map(|key| {
+                Cell::from(Span::styled(
+                    Field::Label(key.clone()).value(vm),
+                    Style::default().fg(fg).bg(bg),
+                ))
+            }));
+            Row::new(cells)
         })
         .collect();
 
-    let table = Table::new(
-        rows,
-        [
-            Constraint::Length(11),
-            Constraint::Min(15),
-            Constraint::Length(12),
-            Constraint::Length(5),
-            Constraint::Length(10),
-        ],
-    )
-    .header(header)
-    .block(
-        Block::default()
-            .borders(Borders::ALL)
-            .border_style(Style::default().fg(Color::DarkGray)),
-    )
-    .row_highlight_style(Style::default().bg(Color::Indexed(236)));
+    let mut widths = vec![
+        Constraint::Length(11),
+        Constraint::Min(15),
+        Constraint::Length(12),
+        Constraint::Length(7),
+        Constraint::Length(10),
+    ];
+    widths.extend(label_columns.iter().map(|_| Constraint::Min(10)));
+
+    let mut table_block = Block::default()
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::DarkGray));
+    if !filter.is_empty() {
+        table_block = table_block.title(Span::styled(
+            format!(" Filter: {} ", filter),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(table_block)
+        .row_highlight_style(Style::default().bg(Color::Indexed(236)));
 
     frame.render_stateful_widget(table, chunks[1], table_state);
 
@@ -230,6 +249,12 @@ pub fn draw(
         Span::styled(" Kill ", Style::default().fg(Color::DarkGray)),
         Span::styled("d", Style::default().fg(Color::Red).bold()),
         Span::styled(" Delete ", Style::default().fg(Color::DarkGray)),
+        Span::styled("/", Style::default().fg(Color::Cyan).bold()),
+        Span::styled(" Filter ", Style::default().fg(Color::DarkGray)),
+        Span::styled("o", Style::default().fg(Color::Cyan).bold()),
+        Span::styled(" Sort ", Style::default().fg(Color::DarkGray)),
+        Span::styled("L", Style::default().fg(Color::Cyan).bold()),
+        Span::styled(" Labels ", Style::default().fg(Color::DarkGray)),
         Span::styled("q", Style::default().fg(Color::Magenta).bold()),
         Span::styled(" Quit", Style::default().fg(Color::DarkGray)),
     ]);
@@ -1,12 +1,13 @@
+use mvirt_common::request_id::RequestIdChannel;
 use tokio::sync::mpsc;
 use tokio_stream::StreamExt;
 use tokio_stream::wrappers::UnboundedReceiverStream;
-use tonic::transport::Channel;
 
+use crate::api::ApiClient;
 use crate::net_proto::net_service_client::NetServiceClient;
 use crate::net_proto::{
     CreateNetworkRequest, CreateNicRequest, DeleteNetworkRequest, DeleteNicRequest,
-    ListNetworksRequest, ListNicsRequest,
+    ListNetworksRequest, ListNicsRequest, UpdateNetworkRequest,
 };
 use crate::proto::vm_service_client::VmServiceClient;
 use crate::proto::*;
@@ -23,7 +24,7 @@ async fn generate_user_data(
     result_tx: &mpsc::UnboundedSender<ActionResult>,
 ) -> Option<String> {
     match params.user_data_mode {
-        UserDataMode::None => None,
+        UserDataMode::None => params.flavor_user_data.clone(),
         UserDataMode::File => {
             if let Some(path) = &params.user_data_file {
                 match tokio::fs::read_to_string(path).await {
@@ -141,7 +142,7 @@ fn generate_cloud_init_yaml(cfg: &SshKeysConfig, ssh_keys: &[String]) -> String
 }
 
 async fn refresh_storage(
-    zfs_client: &mut ZfsServiceClient<Channel>,
+    zfs_client: &mut ZfsServiceClient<RequestIdChannel>,
 ) -> Result<StorageState, String> {
     // Fetch pool stats
     let pool = match zfs_client.get_pool_stats(GetPoolStatsRequest {}).await {
@@ -181,10 +182,11 @@ async fn refresh_storage(
 }
 
 pub async fn action_worker(
-    mut vm_client: Option<VmServiceClient<Channel>>,
-    mut zfs_client: Option<ZfsServiceClient<Channel>>,
-    mut log_client: Option<LogServiceClient<Channel>>,
-    mut net_client: Option<NetServiceClient<Channel>>,
+    mut vm_client: Option<VmServiceClient<RequestIdChannel>>,
+    mut zfs_client: Option<ZfsServiceClient<RequestIdChannel>>,
+    mut log_client: Option<LogServiceClient<RequestIdChannel>>,
+    mut net_client: Option<NetServiceClient<RequestIdChannel>>,
+    api: Option<ApiClient>,
     mut action_rx: mpsc::UnboundedReceiver<Action>,
     result_tx: mpsc::UnboundedSender<ActionResult>,
 ) {
@@ -215,7 +217,13 @@ pub async fn action_worker(
             }
             Action::Start(id) => {
                 if let Some(ref mut client) = vm_client {
-                    match client.start_vm(StartVmRequest { id: id.clone() }).await {
+                    match client
+                        .start_vm(StartVmRequest {
+                            id: id.clone(),
+                            prepare_stages: vec![],
+                        })
+                        .await
+                    {
                         Ok(_) => ActionResult::Started(id, Ok(())),
                         Err(e) => ActionResult::Started(id, Err(e.message().to_string())),
                     }
@@ -251,7 +259,14 @@ pub async fn action_worker(
             }
             Action::Delete(id) => {
                 if let Some(ref mut client) = vm_client {
-                    match client.delete_vm(DeleteVmRequest { id: id.clone() }).await {
+                    match client
+                        .delete_vm(DeleteVmRequest {
+                            id: id.clone(),
+                            propagation: DeletePropagation::Cascade as i32,
+                            force: false,
+                        })
+                        .await
+                    {
                         Ok(_) => ActionResult::Deleted(id, Ok(())),
                         Err(e) => ActionResult::Deleted(id, Err(e.message().to_string())),
                     }
@@ -329,6 +344,7 @@ pub async fn action_worker(
                     let mut disks = vec![DiskConfig {
                         path: disk_path,
                         readonly: false,
+                        secret: None,
                     }];
 
                     // Create data disks (additional volumes)
@@ -340,6 +356,7 @@ pub async fn action_worker(
                                     name: data_disk.name.clone(),
                                     size_bytes,
                                     volblocksize: None,
+                                    owner: None,
                                 })
                                 .await
                             {
@@ -348,6 +365,7 @@ pub async fn action_worker(
                                     disks.push(DiskConfig {
                                         path: vol.path,
                                         readonly: false,
+                                        secret: None,
                                     });
                                 }
                                 Err(e) => {
@@ -379,6 +397,8 @@ pub async fn action_worker(
                                     ipv6_address: String::new(),
                                     routed_ipv4_prefixes: vec![],
                                     routed_ipv6_prefixes: vec![],
+                                    owner: None,
+                                    delegated_ipv6_prefix_len: 0,
                                 })
                                 .await
                             {
@@ -428,6 +448,9 @@ pub async fn action_worker(
                         nics: vec![nic_config],
                         user_data: user_data_content,
                         nested_virt: params.nested_virt,
+                        labels: params.labels,
+                        max_vcpus: 0,
+                        max_memory_mb: 0,
                     };
                     match client
                         .create_vm(CreateVmRequest {
@@ -450,13 +473,17 @@ pub async fn action_worker(
                     let vm_id_clone = vm_id.clone();
                     let input_stream =
                         UnboundedReceiverStream::new(input_rx).map(move |data| ConsoleInput {
-                            vm_id: String::new(),
                             data,
+                            ..Default::default()
                         });
 
                     let initial_msg = ConsoleInput {
                         vm_id: vm_id_clone,
-                        data: vec![],
+                        client: format!(
+                            "{}@mvirt-tui",
+                            std::env::var("USER").unwrap_or_else(|_| "unknown".to_string())
+                        ),
+                        ..Default::default()
                     };
                     let full_stream = tokio_stream::once(initial_msg).chain(input_stream);
 
@@ -476,8 +503,17 @@ pub async fn action_worker(
                                 while let Some(result) = output_stream.next().await {
                                     match result {
                                         Ok(output) => {
+                                            let data = if output.notice.is_empty() {
+                                                output.data
+                                            } else {
+                                                let mut data =
+                                                    format!("\r\n[mvirt: {}]\r\n", output.notice)
+                                                        .into_bytes();
+                                                data.extend_from_slice(&output.data);
+                                                data
+                                            };
                                             if result_tx_clone
-                                                .send(ActionResult::ConsoleOutput(output.data))
+                                                .send(ActionResult::ConsoleOutput(data))
                                                 .is_err()
                                             {
                                                 break;
@@ -523,6 +559,7 @@ pub async fn action_worker(
                             name,
                             size_bytes,
                             volblocksize: None,
+                            owner: None,
                         })
                         .await
                     {
@@ -849,10 +886,18 @@ pub async fn action_worker(
                     vec![]
                 };
 
+                // Flavors are optional: without an API server the sizes are
+                // entered by hand
+                let flavors = match &api {
+                    Some(api) => api.list_flavors().await.unwrap_or_default(),
+                    None => vec![],
+                };
+
                 ActionResult::CreateVmModalReady {
                     templates,
                     volumes,
                     networks,
+                    flavors,
                 }
             }
 
@@ -918,6 +963,7 @@ pub async fn action_worker(
                 ipv4_subnet,
                 ipv6_prefix,
                 dns_servers,
+                ntp_servers,
                 is_public,
             } => {
                 if let Some(ref mut client) = net_client {
@@ -929,7 +975,7 @@ pub async fn action_worker(
                         ipv6_enabled: ipv6_prefix.is_some(),
                         ipv6_prefix: ipv6_prefix.unwrap_or_default(),
                         dns_servers,
-                        ntp_servers: vec![],
+                        ntp_servers,
                         is_public,
                     };
                     match client.create_network(req).await {
@@ -940,6 +986,25 @@ pub async fn action_worker(
                     ActionResult::NetworkCreated(Err("Network service not available".to_string()))
                 }
             }
+            Action::UpdateNetwork {
+                id,
+                dns_servers,
+                ntp_servers,
+            } => {
+                if let Some(ref mut client) = net_client {
+                    let req = UpdateNetworkRequest {
+                        id,
+                        dns_servers,
+                        ntp_servers,
+                    };
+                    match client.update_network(req).await {
+                        Ok(response) => ActionResult::NetworkUpdated(Ok(response.into_inner())),
+                        Err(e) => ActionResult::NetworkUpdated(Err(e.message().to_string())),
+                    }
+                } else {
+                    ActionResult::NetworkUpdated(Err("Network service not available".to_string()))
+                }
+            }
             Action::DeleteNetwork { id } => {
                 if let Some(ref mut client) = net_client {
                     match client
@@ -973,6 +1038,8 @@ pub async fn action_worker(
                         ipv6_address: String::new(),
                         routed_ipv4_prefixes: vec![],
                         routed_ipv6_prefixes: vec![],
+                        owner: None,
+                        delegated_ipv6_prefix_len: 0,
                     };
                     match client.create_nic(req).await {
                         Ok(response) => ActionResult::NicCreated(Ok(response.into_inner())),
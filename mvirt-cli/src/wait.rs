@@ -0,0 +1,349 @@
+//! `mvirt wait` and `mvirt list --watch`: follow VMs and pods so scripts
+//! don't need their own polling loops around create/start/stop.
+//!
+//! VMs are followed through the VMM's `WatchVms` stream; if the stream
+//! drops, waiting falls back to polling. Pods have no stream yet and are
+//! always polled, quickly at first and backing off to a few seconds.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use mvirt_common::request_id::RequestIdChannel;
+use tokio::time::Instant;
+use tokio_stream::StreamExt;
+use tonic::Code;
+
+use crate::columns::{self, Column};
+use crate::proto::pod_service_client::PodServiceClient;
+use crate::proto::vm_service_client::VmServiceClient;
+use crate::proto::{
+    GetPodRequest, GetVmRequest, ListVmsRequest, Pod, PodState, Vm, VmEventType, WatchVmsRequest,
+};
+use crate::{format_pod_state, format_state, resolve_pod_id, resolve_vm_id};
+
+type Error = Box<dyn std::error::Error>;
+
+const VM_STATES: &[&str] = &["stopped", "starting", "running", "stopping"];
+const POD_STATES: &[&str] = &[
+    "created", "pulling", "starting", "running", "paused", "stopping", "stopped", "failed",
+];
+
+/// First and longest delay between polls.
+const POLL_MIN: Duration = Duration::from_millis(250);
+const POLL_MAX: Duration = Duration::from_secs(3);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Vm,
+    Pod,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Vm => "VM",
+            Kind::Pod => "Pod",
+        }
+    }
+}
+
+/// `vm/<name or id>`, `pod/<name or id>`, or a bare VM name or ID.
+pub fn parse_target(target: &str) -> Result<(Kind, String), String> {
+    let (kind, name) = match target.split_once('/') {
+        Some(("vm", name)) => (Kind::Vm, name),
+        Some(("pod", name)) => (Kind::Pod, name),
+        Some((kind, _)) => {
+            return Err(format!(
+                "unknown resource type '{}' (expected vm or pod)",
+                kind
+            ));
+        }
+        None => (Kind::Vm, target),
+    };
+    if name.is_empty() {
+        return Err(format!("missing name in '{}'", target));
+    }
+    Ok((kind, name.to_string()))
+}
+
+pub enum Condition {
+    State(String),
+    Delete,
+}
+
+/// `state=<state>` or `delete`.
+pub fn parse_condition(kind: Kind, cond: &str) -> Result<Condition, String> {
+    if cond == "delete" {
+        return Ok(Condition::Delete);
+    }
+    let Some(state) = cond.strip_prefix("state=") else {
+        return Err(format!(
+            "invalid condition '{}' (expected state=<state> or delete)",
+            cond
+        ));
+    };
+    let states = match kind {
+        Kind::Vm => VM_STATES,
+        Kind::Pod => POD_STATES,
+    };
+    let state = state.to_ascii_lowercase();
+    if !states.contains(&state.as_str()) {
+        return Err(format!(
+            "unknown {} state '{}' (expected one of: {})",
+            kind.name(),
+            state,
+            states.join(", ")
+        ));
+    }
+    Ok(Condition::State(state))
+}
+
+/// `90`, `90s`, `5m` or `1h`.
+pub fn parse_timeout(timeout: &str) -> Result<Duration, String> {
+    let timeout = timeout.trim();
+    let (num, secs) = match timeout.char_indices().last() {
+        Some((i, 's')) => (&timeout[..i], 1),
+        Some((i, 'm')) => (&timeout[..i], 60),
+        Some((i, 'h')) => (&timeout[..i], 3600),
+        _ => (timeout, 1),
+    };
+    num.parse::<u64>()
+        .map(|n| Duration::from_secs(n * secs))
+        .map_err(|_| format!("invalid timeout '{}': expected e.g. 30s, 5m or 1h", timeout))
+}
+
+fn vm_state_matches(cond: &Condition, vm: Option<&Vm>) -> bool {
+    match cond {
+        Condition::Delete => vm.is_none(),
+        Condition::State(state) => vm.is_some_and(|vm| &format_state(vm.state()) == state),
+    }
+}
+
+/// `Ok(None)` once the VM is gone.
+async fn get_vm(
+    client: &mut VmServiceClient<RequestIdChannel>,
+    id: &str,
+) -> Result<Option<Vm>, Error> {
+    match client.get_vm(GetVmRequest { id: id.to_string() }).await {
+        Ok(resp) => Ok(Some(resp.into_inner())),
+        Err(status) if status.code() == Code::NotFound => Ok(None),
+        Err(status) => Err(status.into()),
+    }
+}
+
+async fn get_pod(
+    client: &mut PodServiceClient<RequestIdChannel>,
+    id: &str,
+) -> Result<Option<Pod>, Error> {
+    match client.get_pod(GetPodRequest { id: id.to_string() }).await {
+        Ok(resp) => Ok(Some(resp.into_inner())),
+        Err(status) if status.code() == Code::NotFound => Ok(None),
+        Err(status) => Err(status.into()),
+    }
+}
+
+fn timed_out(kind: Kind, name: &str, cond: &Condition) -> Error {
+    let what = match cond {
+        Condition::Delete => "deletion".to_string(),
+        Condition::State(state) => format!("state={}", state),
+    };
+    format!("timed out waiting for {} {} ({})", kind.name(), name, what).into()
+}
+
+/// Block until the VM meets `cond`, or fail after `timeout`.
+pub async fn wait_vm(
+    client: &mut VmServiceClient<RequestIdChannel>,
+    name: &str,
+    cond: &Condition,
+    timeout: Duration,
+) -> Result<(), Error> {
+    let deadline = Instant::now() + timeout;
+    let id = match resolve_vm_id(client, name).await {
+        Ok(id) => id,
+        Err(_) if matches!(cond, Condition::Delete) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    // Subscribe before the first look so no transition falls in between.
+    let stream = client
+        .watch_vms(WatchVmsRequest {
+            vm_id: Some(id.clone()),
+        })
+        .await;
+    if vm_state_matches(cond, get_vm(client, &id).await?.as_ref()) {
+        return Ok(());
+    }
+
+    if let Ok(resp) = stream {
+        let mut stream = resp.into_inner();
+        loop {
+            // Only some transitions are streamed (not starting/stopping),
+            // so look again now and then.
+            let wake = (Instant::now() + POLL_MAX).min(deadline);
+            let vm = match tokio::time::timeout_at(wake, stream.next()).await {
+                Ok(Some(Ok(event))) if event.r#type == VmEventType::VmEventDeleted as i32 => None,
+                Ok(Some(Ok(event))) => match event.vm {
+                    Some(vm) => Some(vm),
+                    None => get_vm(client, &id).await?,
+                },
+                // Stream gone; poll for the rest of the time
+                Ok(_) => break,
+                Err(_) if Instant::now() >= deadline => {
+                    return Err(timed_out(Kind::Vm, name, cond));
+                }
+                Err(_) => get_vm(client, &id).await?,
+            };
+            if vm_state_matches(cond, vm.as_ref()) {
+                return Ok(());
+            }
+            if vm.is_none() {
+                return Err(format!("VM {} was deleted", name).into());
+            }
+        }
+    }
+
+    let mut delay = POLL_MIN;
+    loop {
+        let vm = get_vm(client, &id).await?;
+        if vm_state_matches(cond, vm.as_ref()) {
+            return Ok(());
+        }
+        if vm.is_none() {
+            return Err(format!("VM {} was deleted", name).into());
+        }
+        if Instant::now() + delay > deadline {
+            return Err(timed_out(Kind::Vm, name, cond));
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(POLL_MAX);
+    }
+}
+
+/// Block until the pod meets `cond`, or fail after `timeout`. A pod that
+/// fails while waiting for another state ends the wait early.
+pub async fn wait_pod(
+    client: &mut PodServiceClient<RequestIdChannel>,
+    name: &str,
+    cond: &Condition,
+    timeout: Duration,
+) -> Result<(), Error> {
+    let deadline = Instant::now() + timeout;
+    let id = match resolve_pod_id(client, name).await {
+        Ok(id) => id,
+        Err(_) if matches!(cond, Condition::Delete) => return Ok(()),
+        Err(e) => return Err(e),
+    };
+
+    let mut delay = POLL_MIN;
+    loop {
+        let pod = get_pod(client, &id).await?;
+        let state = pod.as_ref().map(|p| {
+            format_pod_state(PodState::try_from(p.state).unwrap_or(PodState::Unspecified))
+        });
+        match (cond, &pod, state) {
+            (Condition::Delete, None, _) => return Ok(()),
+            (Condition::State(want), Some(_), Some(state)) if &state == want => return Ok(()),
+            (Condition::State(_), None, _) => {
+                return Err(format!("Pod {} was deleted", name).into());
+            }
+            (Condition::State(_), Some(pod), Some(state)) if state == "failed" => {
+                return Err(format!(
+                    "Pod {} failed: {}",
+                    name,
+                    pod.error_message.as_deref().unwrap_or("unknown error")
+                )
+                .into());
+            }
+            _ => {}
+        }
+        if Instant::now() + delay > deadline {
+            return Err(timed_out(Kind::Pod, name, cond));
+        }
+        tokio::time::sleep(delay).await;
+        delay = (delay * 2).min(POLL_MAX);
+    }
+}
+
+fn event_name(ty: i32) -> &'static str {
+    match VmEventType::try_from(ty) {
+        Ok(VmEventType::VmEventCreated) => "CREATED",
+        Ok(VmEventType::VmEventStarted) => "STARTED",
+        Ok(VmEventType::VmEventStopped) => "STOPPED",
+        Ok(VmEventType::VmEventDeleted) => "DELETED",
+        _ => "UPDATED",
+    }
+}
+
+/// Print a line per VM change until interrupted. `filter` and `columns`
+/// work as for `mvirt list`; a deleted VM is shown if it matched before.
+pub async fn watch_vms(
+    client: &mut VmServiceClient<RequestIdChannel>,
+    filter: Option<&str>,
+    columns: Option<&[Column]>,
+) -> Result<(), Error> {
+    let mut stream = client
+        .watch_vms(WatchVmsRequest { vm_id: None })
+        .await?
+        .into_inner();
+
+    // VMs currently listed, to know which deletions to show
+    let mut shown: HashSet<String> = client
+        .list_vms(ListVmsRequest {})
+        .await?
+        .into_inner()
+        .vms
+        .into_iter()
+        .filter(|vm| filter.is_none_or(|f| columns::matches(vm, f)))
+        .map(|vm| vm.id)
+        .collect();
+
+    match columns {
+        Some(columns) => {
+            let headers: Vec<&str> = columns.iter().map(|c| c.header.as_str()).collect();
+            println!("{:<8}   {}", "EVENT", headers.join("   "));
+        }
+        None => println!(
+            "{:<8}   {:<36}   {:<20}   {}",
+            "EVENT", "ID", "NAME", "STATE"
+        ),
+    }
+
+    while let Some(event) = stream.next().await {
+        let event = event?;
+        let kind = event_name(event.r#type);
+        let vm = match event.vm {
+            Some(vm) if filter.is_none_or(|f| columns::matches(&vm, f)) => {
+                shown.insert(vm.id.clone());
+                vm
+            }
+            Some(vm) => {
+                shown.remove(&vm.id);
+                continue;
+            }
+            None if shown.remove(&event.vm_id) => Vm {
+                id: event.vm_id,
+                ..Default::default()
+            },
+            None => continue,
+        };
+        let deleted = event.r#type == VmEventType::VmEventDeleted as i32;
+        match columns {
+            Some(columns) => {
+                let values: Vec<String> = columns.iter().map(|c| c.field.value(&vm)).collect();
+                println!("{:<8}   {}", kind, values.join("   "));
+            }
+            None => println!(
+                "{:<8}   {:<36}   {:<20}   {}",
+                kind,
+                vm.id,
+                vm.name.as_deref().unwrap_or("-"),
+                if deleted {
+                    "-".to_string()
+                } else {
+                    format_state(vm.state())
+                }
+            ),
+        }
+    }
+    Err("VM watch stream closed by mvirt-vmm".into())
+}
@@ -0,0 +1,454 @@
+//! Event bus between the daemons of a host.
+//!
+//! Daemons publish what happened to their resources (a NIC deleted, a
+//! volume resized, a VM stopped) so the others can react right away
+//! instead of polling each other or waiting for the cplane: mvirt-vmm
+//! unplugs a deleted NIC from its VMs, mvirt-node samples the host again
+//! when capacity was freed.
+//!
+//! The bus is a directory of unix datagram sockets, [`EVENTS_DIR`] unless
+//! `MVIRT_EVENTS_DIR` names another. Every subscriber binds one socket,
+//! named after its daemon, and a publisher sends each event as a JSON
+//! datagram to all sockets but its own. No daemon hosts the bus, so any of
+//! them can run without the others and in any order.
+//!
+//! The directory is only open to its owner, and subscribers take events
+//! only from processes of root or their own user, as the kernel reports
+//! them (`SO_PASSCRED`); the source an envelope names is the publisher's
+//! own claim.
+//!
+//! Delivery is best effort: a daemon misses the events published while it
+//! is down or while its socket buffer is full. Reactions must be safe to
+//! miss; the reconcilers and Watch* streams remain the source of truth.
+
+use std::fs::{self, DirBuilder, Permissions};
+use std::io;
+use std::mem;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tokio::io::Interest;
+use tracing::{debug, warn};
+
+/// Directory of the subscribers' sockets.
+pub const EVENTS_DIR: &str = "/run/mvirt/events";
+
+/// File extension of a subscriber's socket.
+const SOCKET_EXT: &str = "sock";
+
+/// Largest event accepted; events are far smaller.
+const MAX_EVENT_LEN: usize = 64 * 1024;
+
+/// Mode of the bus directory and of the subscribers' sockets.
+const DIR_MODE: u32 = 0o700;
+const SOCKET_MODE: u32 = 0o600;
+
+/// Something that happened to a resource of the publishing daemon.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    /// A NIC was deleted. `socket` is the backend VMs attach it by: the
+    /// vhost-user socket path, or `tap:<name>`.
+    NicDeleted {
+        nic_id: String,
+        mac: String,
+        socket: String,
+    },
+    /// A volume was resized; `path` is its block device.
+    VolumeResized {
+        volume_id: String,
+        name: String,
+        path: String,
+        size_bytes: u64,
+    },
+    /// A VM's process exited, after a stop, a kill or a crash.
+    VmStopped { vm_id: String },
+}
+
+/// An event as received, with the daemon that published it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// Name of the publishing daemon, e.g. "net"
+    pub source: String,
+    /// Unix time of publishing, in nanoseconds
+    pub timestamp_ns: i64,
+    #[serde(flatten)]
+    pub event: Event,
+    /// Process that sent the event, as the kernel reports it; set on
+    /// receipt, not sent
+    #[serde(skip)]
+    pub sender: Option<Sender>,
+}
+
+/// Credentials of the process that sent an event.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sender {
+    pub pid: u32,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+impl Sender {
+    /// Whether events of this sender are taken: root's and those of the
+    /// subscriber's own user.
+    fn is_trusted(&self) -> bool {
+        self.uid == 0 || self.uid == unsafe { libc::geteuid() }
+    }
+}
+
+/// A daemon's end of the bus.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    dir: PathBuf,
+    source: String,
+    socket: Arc<UnixDatagram>,
+}
+
+impl EventBus {
+    /// The bus of this host, for the daemon named `source`.
+    pub fn new(source: &str) -> io::Result<Self> {
+        let dir = std::env::var_os("MVIRT_EVENTS_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from(EVENTS_DIR));
+        Self::in_dir(dir, source)
+    }
+
+    /// The bus whose sockets are in `dir`.
+    pub fn in_dir(dir: impl Into<PathBuf>, source: &str) -> io::Result<Self> {
+        let socket = UnixDatagram::unbound()?;
+        // A subscriber that doesn't keep up loses events, rather than
+        // stalling the publisher
+        socket.set_nonblocking(true)?;
+        Ok(Self {
+            dir: dir.into(),
+            source: source.to_string(),
+            socket: Arc::new(socket),
+        })
+    }
+
+    /// Name of the daemon this end belongs to.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Send `event` to every subscriber but this daemon. Failures are
+    /// logged, not returned: nobody waits for the event.
+    pub fn publish(&self, event: Event) {
+        let envelope = Envelope {
+            source: self.source.clone(),
+            timestamp_ns: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as i64)
+                .unwrap_or_default(),
+            event,
+            sender: None,
+        };
+        let message = match serde_json::to_vec(&envelope) {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(error = %e, "Failed to encode event");
+                return;
+            }
+        };
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            // No subscriber has come up yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return,
+            Err(e) => {
+                warn!(dir = %self.dir.display(), error = %e, "Failed to list event subscribers");
+                return;
+            }
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            let Some(name) = subscriber_name(&path) else {
+                continue;
+            };
+            if name == self.source {
+                continue;
+            }
+            match self.socket.send_to(&message, &path) {
+                Ok(_) => {}
+                // Left behind by a subscriber that didn't exit cleanly
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+                    debug!(subscriber = %name, "Removing stale event socket");
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    warn!(subscriber = %name, event = ?envelope.event, "Event subscriber is not keeping up; event dropped");
+                }
+                Err(e) => {
+                    warn!(subscriber = %name, error = %e, "Failed to publish event");
+                }
+            }
+        }
+    }
+
+    /// Receive the events of the other daemons. The socket is named after
+    /// this daemon and replaces one left by an earlier run.
+    pub fn subscribe(&self) -> io::Result<Subscription> {
+        DirBuilder::new()
+            .recursive(true)
+            .mode(DIR_MODE)
+            .create(&self.dir)?;
+        // Left open by an earlier version
+        let metadata = fs::metadata(&self.dir)?;
+        if metadata.mode() & 0o777 != DIR_MODE {
+            fs::set_permissions(&self.dir, Permissions::from_mode(DIR_MODE))?;
+        }
+        let path = self.dir.join(format!("{}.{}", self.source, SOCKET_EXT));
+        match fs::remove_file(&path) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        let socket = tokio::net::UnixDatagram::bind(&path)?;
+        fs::set_permissions(&path, Permissions::from_mode(SOCKET_MODE))?;
+        pass_credentials(socket.as_raw_fd())?;
+        Ok(Subscription {
+            socket,
+            path,
+            buf: vec![0; MAX_EVENT_LEN],
+        })
+    }
+}
+
+/// A subscriber's socket; removed on drop.
+#[derive(Debug)]
+pub struct Subscription {
+    socket: tokio::net::UnixDatagram,
+    path: PathBuf,
+    buf: Vec<u8>,
+}
+
+impl Subscription {
+    /// The next event. Datagrams that aren't events (e.g. of a newer
+    /// daemon) are skipped, and so are events of untrusted senders.
+    pub async fn recv(&mut self) -> io::Result<Envelope> {
+        let Self { socket, buf, .. } = self;
+        loop {
+            let (len, sender) = socket
+                .async_io(Interest::READABLE, || {
+                    recv_with_sender(socket.as_raw_fd(), buf)
+                })
+                .await?;
+            let Some(sender) = sender.filter(Sender::is_trusted) else {
+                warn!(sender = ?sender, "Dropping event of an untrusted sender");
+                continue;
+            };
+            match serde_json::from_slice::<Envelope>(&buf[..len]) {
+                Ok(envelope) => {
+                    return Ok(Envelope {
+                        sender: Some(sender),
+                        ..envelope
+                    })
+                }
+                Err(e) => debug!(error = %e, "Skipping unknown event"),
+            }
+        }
+    }
+}
+
+/// Have the kernel attach the sender's credentials to every datagram
+/// received on `fd`.
+fn pass_credentials(fd: RawFd) -> io::Result<()> {
+    let on: libc::c_int = 1;
+    let ret = unsafe {
+        libc::setsockopt(
+            fd,
+            libc::SOL_SOCKET,
+            libc::SO_PASSCRED,
+            (&on as *const libc::c_int).cast(),
+            mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Receive a datagram into `buf`, with the credentials of its sender.
+fn recv_with_sender(fd: RawFd, buf: &mut [u8]) -> io::Result<(usize, Option<Sender>)> {
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr().cast(),
+        iov_len: buf.len(),
+    };
+    // Room for one SCM_CREDENTIALS message, aligned for cmsghdr
+    let mut control = [0u64; 8];
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr().cast();
+    msg.msg_controllen = mem::size_of_val(&control) as _;
+
+    let len = unsafe { libc::recvmsg(fd, &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if len < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut sender = None;
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_CREDENTIALS
+            {
+                let cred: libc::ucred = std::ptr::read_unaligned(libc::CMSG_DATA(cmsg).cast());
+                sender = Some(Sender {
+                    pid: cred.pid as u32,
+                    uid: cred.uid,
+                    gid: cred.gid,
+                });
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    Ok((len as usize, sender))
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// The daemon a socket in the bus directory belongs to.
+fn subscriber_name(path: &Path) -> Option<&str> {
+    if path.extension()? != SOCKET_EXT {
+        return None;
+    }
+    path.file_stem()?.to_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn next(subscription: &mut Subscription) -> Envelope {
+        tokio::time::timeout(Duration::from_secs(5), subscription.recv())
+            .await
+            .expect("no event")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_other_daemons() {
+        let dir = tempfile::tempdir().unwrap();
+        let net = EventBus::in_dir(dir.path(), "net").unwrap();
+        let vmm = EventBus::in_dir(dir.path(), "vmm").unwrap();
+        let node = EventBus::in_dir(dir.path(), "node").unwrap();
+        let mut net_events = net.subscribe().unwrap();
+        let mut vmm_events = vmm.subscribe().unwrap();
+        let mut node_events = node.subscribe().unwrap();
+
+        let deleted = Event::NicDeleted {
+            nic_id: "nic-1".to_string(),
+            mac: "02:00:00:00:00:01".to_string(),
+            socket: "/run/mvirt/net/nic-1.sock".to_string(),
+        };
+        net.publish(deleted.clone());
+        for events in [&mut vmm_events, &mut node_events] {
+            let envelope = next(events).await;
+            assert_eq!(envelope.source, "net");
+            assert_eq!(envelope.event, deleted);
+            let sender = envelope.sender.unwrap();
+            assert_eq!(sender.pid, std::process::id());
+            assert_eq!(sender.uid, unsafe { libc::geteuid() });
+        }
+
+        // The publisher doesn't hear itself
+        let stopped = Event::VmStopped {
+            vm_id: "vm-1".to_string(),
+        };
+        vmm.publish(stopped.clone());
+        assert_eq!(next(&mut net_events).await.event, stopped);
+        assert_eq!(next(&mut node_events).await.event, stopped);
+        assert!(
+            tokio::time::timeout(Duration::from_millis(100), vmm_events.recv())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_and_unknown() {
+        let dir = tempfile::tempdir().unwrap();
+        // Nobody subscribed, not even the directory exists
+        let zfs = EventBus::in_dir(dir.path().join("events"), "zfs").unwrap();
+        zfs.publish(Event::VmStopped {
+            vm_id: "vm-1".to_string(),
+        });
+
+        // A subscriber that went away without removing its socket
+        let path = dir.path().join("events/vmm.sock");
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        drop(UnixDatagram::bind(&path).unwrap());
+        let node = EventBus::in_dir(dir.path().join("events"), "node").unwrap();
+        let mut node_events = node.subscribe().unwrap();
+
+        // Not an event
+        UnixDatagram::unbound()
+            .unwrap()
+            .send_to(br#"{"type":"disk_exploded"}"#, &node_events.path)
+            .unwrap();
+        let resized = Event::VolumeResized {
+            volume_id: "vol-1".to_string(),
+            name: "data".to_string(),
+            path: "/dev/zvol/mvirt/vol-1".to_string(),
+            size_bytes: 10 << 30,
+        };
+        zfs.publish(resized.clone());
+        assert_eq!(next(&mut node_events).await.event, resized);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_envelope_format() {
+        let envelope = Envelope {
+            source: "vmm".to_string(),
+            timestamp_ns: 1,
+            event: Event::VmStopped {
+                vm_id: "vm-1".to_string(),
+            },
+            sender: None,
+        };
+        let json = serde_json::to_string(&envelope).unwrap();
+        assert_eq!(
+            json,
+            r#"{"source":"vmm","timestamp_ns":1,"type":"vm_stopped","vm_id":"vm-1"}"#
+        );
+        assert_eq!(serde_json::from_str::<Envelope>(&json).unwrap(), envelope);
+    }
+
+    #[tokio::test]
+    async fn test_bus_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let events = dir.path().join("events");
+        fs::create_dir(&events).unwrap();
+        fs::set_permissions(&events, Permissions::from_mode(0o777)).unwrap();
+
+        let vmm = EventBus::in_dir(&events, "vmm").unwrap();
+        let subscription = vmm.subscribe().unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().mode() & 0o777;
+        assert_eq!(mode(&events), DIR_MODE);
+        assert_eq!(mode(&subscription.path), SOCKET_MODE);
+    }
+
+    #[test]
+    fn test_trusted_senders() {
+        let euid = unsafe { libc::geteuid() };
+        let sender = |uid| Sender {
+            pid: 1,
+            uid,
+            gid: 0,
+        };
+        assert!(sender(0).is_trusted());
+        assert!(sender(euid).is_trusted());
+        assert!(!sender(euid.wrapping_add(1).max(1)).is_trusted());
+    }
+}
@@ -0,0 +1,453 @@
+//! Progress of long-running operations.
+//!
+//! An operation that outlives its RPC (an image import, a volume copy, a
+//! memory dump) registers a job in its daemon's [`JobRegistry`] and reports
+//! its phase and progress through the returned [`JobHandle`]. Each daemon
+//! serves the registry as ListJobs/GetJob/CancelJob, so clients render every
+//! kind of operation the same way.
+//!
+//! Cancelling only asks: the operation checks [`JobHandle::is_cancelled`] or
+//! awaits [`JobHandle::wait_cancelled`] and ends the job itself. The first
+//! call that ends a job decides its state; a handle dropped before the job
+//! was ended marks it failed (or cancelled, if that was requested). Finished jobs stay listed until the registry holds more
+//! than its retention limit of them.
+
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use tokio::sync::watch;
+
+/// Finished jobs kept by [`JobRegistry::default`].
+pub const DEFAULT_RETENTION: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Running => "running",
+            JobState::Completed => "completed",
+            JobState::Failed => "failed",
+            JobState::Cancelled => "cancelled",
+        }
+    }
+}
+
+/// A point-in-time view of a job.
+#[derive(Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    /// What the operation does, e.g. `import` or `memory-dump`
+    pub kind: String,
+    /// Name or ID of the object it works on
+    pub target: String,
+    pub state: JobState,
+    /// Operation-specific step, e.g. `downloading`
+    pub phase: String,
+    /// Units of work done, usually bytes
+    pub done: u64,
+    /// Units of work in total, if known
+    pub total: Option<u64>,
+    pub error: Option<String>,
+    pub cancellable: bool,
+    pub cancel_requested: bool,
+    pub started_at: SystemTime,
+    pub finished_at: Option<SystemTime>,
+}
+
+impl Job {
+    pub fn is_finished(&self) -> bool {
+        self.state != JobState::Running
+    }
+
+    /// Progress in percent, if the total is known.
+    pub fn percent(&self) -> Option<f64> {
+        match self.total {
+            Some(total) if total > 0 => Some((self.done as f64 / total as f64 * 100.0).min(100.0)),
+            _ => None,
+        }
+    }
+}
+
+/// Why a job could not be cancelled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CancelError {
+    NotFound,
+    NotCancellable,
+    Finished,
+}
+
+impl fmt::Display for CancelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CancelError::NotFound => write!(f, "job not found"),
+            CancelError::NotCancellable => write!(f, "job cannot be cancelled"),
+            CancelError::Finished => write!(f, "job has already finished"),
+        }
+    }
+}
+
+impl std::error::Error for CancelError {}
+
+impl From<CancelError> for tonic::Status {
+    fn from(e: CancelError) -> Self {
+        match e {
+            CancelError::NotFound => tonic::Status::not_found(e.to_string()),
+            CancelError::NotCancellable | CancelError::Finished => {
+                tonic::Status::failed_precondition(e.to_string())
+            }
+        }
+    }
+}
+
+struct Entry {
+    job: Job,
+    cancel: watch::Sender<bool>,
+    done: watch::Sender<bool>,
+}
+
+struct Inner {
+    jobs: HashMap<String, Entry>,
+    /// IDs of finished jobs, oldest first
+    finished: VecDeque<String>,
+    retention: usize,
+}
+
+/// Jobs of one daemon. Cheap to clone; clones share the jobs.
+#[derive(Clone)]
+pub struct JobRegistry {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for JobRegistry {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETENTION)
+    }
+}
+
+impl JobRegistry {
+    /// A registry keeping up to `retention` finished jobs.
+    pub fn new(retention: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                jobs: HashMap::new(),
+                finished: VecDeque::new(),
+                retention,
+            })),
+        }
+    }
+
+    /// Register a running job with a new ID.
+    pub fn start(&self, kind: &str, target: &str, cancellable: bool) -> JobHandle {
+        let id = ulid::Ulid::new().to_string().to_lowercase();
+        self.start_with_id(id, kind, target, cancellable)
+    }
+
+    /// Register a running job under an ID the operation already has, e.g.
+    /// one it persists. Replaces a finished job with the same ID.
+    pub fn start_with_id(
+        &self,
+        id: String,
+        kind: &str,
+        target: &str,
+        cancellable: bool,
+    ) -> JobHandle {
+        let (cancel, cancel_rx) = watch::channel(false);
+        let (done, _) = watch::channel(false);
+        let job = Job {
+            id: id.clone(),
+            kind: kind.to_string(),
+            target: target.to_string(),
+            state: JobState::Running,
+            phase: String::new(),
+            done: 0,
+            total: None,
+            error: None,
+            cancellable,
+            cancel_requested: false,
+            started_at: SystemTime::now(),
+            finished_at: None,
+        };
+        let mut inner = self.inner.lock().unwrap();
+        inner.finished.retain(|f| *f != id);
+        inner.jobs.insert(id.clone(), Entry { job, cancel, done });
+        JobHandle {
+            id,
+            registry: self.clone(),
+            cancel: cancel_rx,
+            finished: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Job> {
+        let inner = self.inner.lock().unwrap();
+        inner.jobs.get(id).map(|e| e.job.clone())
+    }
+
+    /// All jobs, oldest first; running ones only unless `include_finished`.
+    pub fn list(&self, include_finished: bool) -> Vec<Job> {
+        let inner = self.inner.lock().unwrap();
+        let mut jobs: Vec<Job> = inner
+            .jobs
+            .values()
+            .filter(|e| include_finished || !e.job.is_finished())
+            .map(|e| e.job.clone())
+            .collect();
+        jobs.sort_by(|a, b| a.started_at.cmp(&b.started_at).then(a.id.cmp(&b.id)));
+        jobs
+    }
+
+    /// The running job of `kind` working on `target`, if any.
+    pub fn find_running(&self, kind: &str, target: &str) -> Option<Job> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .jobs
+            .values()
+            .find(|e| !e.job.is_finished() && e.job.kind == kind && e.job.target == target)
+            .map(|e| e.job.clone())
+    }
+
+    /// Wait until the job has ended and return it; `None` if there is no
+    /// such job (any more).
+    pub async fn wait_finished(&self, id: &str) -> Option<Job> {
+        let mut done = self.inner.lock().unwrap().jobs.get(id)?.done.subscribe();
+        // An error means the job was evicted, i.e. it had finished
+        let _ = done.wait_for(|d| *d).await;
+        self.get(id)
+    }
+
+    /// Ask a running job to stop. The job stays running until its operation
+    /// notices.
+    pub fn cancel(&self, id: &str) -> Result<Job, CancelError> {
+        let mut inner = self.inner.lock().unwrap();
+        let entry = inner.jobs.get_mut(id).ok_or(CancelError::NotFound)?;
+        if entry.job.is_finished() {
+            return Err(CancelError::Finished);
+        }
+        if !entry.job.cancellable {
+            return Err(CancelError::NotCancellable);
+        }
+        entry.job.cancel_requested = true;
+        entry.cancel.send_replace(true);
+        Ok(entry.job.clone())
+    }
+
+    fn update(&self, id: &str, f: impl FnOnce(&mut Job)) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(entry) = inner.jobs.get_mut(id) {
+            f(&mut entry.job);
+        }
+    }
+
+    fn finish(&self, id: &str, state: JobState, error: Option<String>) {
+        let mut inner = self.inner.lock().unwrap();
+        let Some(entry) = inner.jobs.get_mut(id) else {
+            return;
+        };
+        entry.job.state = state;
+        entry.job.error = error;
+        entry.job.finished_at = Some(SystemTime::now());
+        entry.done.send_replace(true);
+        inner.finished.push_back(id.to_string());
+        while inner.finished.len() > inner.retention {
+            if let Some(old) = inner.finished.pop_front() {
+                inner.jobs.remove(&old);
+            }
+        }
+    }
+}
+
+/// The operation's side of a job. Wrap it in an `Arc` to report from
+/// several tasks.
+pub struct JobHandle {
+    id: String,
+    registry: JobRegistry,
+    cancel: watch::Receiver<bool>,
+    finished: AtomicBool,
+}
+
+impl JobHandle {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn set_phase(&self, phase: &str) {
+        self.registry.update(&self.id, |job| {
+            job.phase = phase.to_string();
+        });
+    }
+
+    pub fn set_progress(&self, done: u64, total: Option<u64>) {
+        self.registry.update(&self.id, |job| {
+            job.done = done;
+            job.total = total;
+        });
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        *self.cancel.borrow()
+    }
+
+    /// Resolves once the job is asked to stop.
+    pub async fn wait_cancelled(&self) {
+        let mut cancel = self.cancel.clone();
+        if cancel.wait_for(|c| *c).await.is_err() {
+            // The registry outlives every handle, so this never resolves
+            std::future::pending::<()>().await;
+        }
+    }
+
+    pub fn complete(&self) {
+        self.end(JobState::Completed, None);
+    }
+
+    pub fn fail(&self, error: impl fmt::Display) {
+        self.end(JobState::Failed, Some(error.to_string()));
+    }
+
+    /// The operation stopped after being cancelled.
+    pub fn finish_cancelled(&self) {
+        self.end(JobState::Cancelled, None);
+    }
+
+    fn end(&self, state: JobState, error: Option<String>) {
+        if !self.finished.swap(true, Ordering::AcqRel) {
+            self.registry.finish(&self.id, state, error);
+        }
+    }
+}
+
+impl Drop for JobHandle {
+    fn drop(&mut self) {
+        if self.is_cancelled() {
+            self.finish_cancelled();
+        } else {
+            self.fail("operation ended without finishing the job");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_phase_and_progress() {
+        let registry = JobRegistry::default();
+        let job = registry.start("import", "debian", true);
+        job.set_phase("downloading");
+        job.set_progress(25, Some(100));
+
+        let listed = registry.get(job.id()).unwrap();
+        assert_eq!(listed.kind, "import");
+        assert_eq!(listed.target, "debian");
+        assert_eq!(listed.phase, "downloading");
+        assert_eq!(listed.percent(), Some(25.0));
+        assert_eq!(listed.state, JobState::Running);
+
+        let id = job.id().to_string();
+        job.complete();
+        assert_eq!(registry.get(&id).unwrap().state, JobState::Completed);
+        assert!(registry.list(false).is_empty());
+        assert_eq!(registry.list(true).len(), 1);
+    }
+
+    #[tokio::test]
+    async fn cancel_signals_the_operation() {
+        let registry = JobRegistry::default();
+        let job = registry.start("memory-dump", "vm1", true);
+        let id = job.id().to_string();
+
+        let op = tokio::spawn(async move {
+            job.wait_cancelled().await;
+            job.finish_cancelled();
+        });
+        let requested = registry.cancel(&id).unwrap();
+        assert!(requested.cancel_requested);
+        op.await.unwrap();
+
+        assert_eq!(registry.get(&id).unwrap().state, JobState::Cancelled);
+        assert_eq!(registry.cancel(&id).unwrap_err(), CancelError::Finished);
+        assert_eq!(registry.cancel("nope").unwrap_err(), CancelError::NotFound);
+    }
+
+    #[tokio::test]
+    async fn waits_for_the_running_job_of_a_target() {
+        let registry = JobRegistry::default();
+        let job = registry.start("vm-start", "vm1", true);
+        assert!(registry.find_running("vm-start", "vm2").is_none());
+        let running = registry.find_running("vm-start", "vm1").unwrap();
+
+        let op = tokio::spawn(async move {
+            job.wait_cancelled().await;
+            job.finish_cancelled();
+        });
+        registry.cancel(&running.id).unwrap();
+        let finished = registry.wait_finished(&running.id).await.unwrap();
+        assert_eq!(finished.state, JobState::Cancelled);
+        assert!(registry.find_running("vm-start", "vm1").is_none());
+        assert!(registry.wait_finished("nope").await.is_none());
+        op.await.unwrap();
+    }
+
+    #[test]
+    fn refuses_to_cancel_uncancellable_jobs() {
+        let registry = JobRegistry::default();
+        let job = registry.start("volume-copy", "vol1", false);
+        assert_eq!(
+            registry.cancel(job.id()).unwrap_err(),
+            CancelError::NotCancellable
+        );
+        assert!(!job.is_cancelled());
+    }
+
+    #[test]
+    fn first_end_decides_the_state() {
+        let registry = JobRegistry::default();
+        let job = registry.start("volume-copy", "vol1", true);
+        job.complete();
+        job.fail("too late");
+        let id = job.id().to_string();
+        drop(job);
+        let job = registry.get(&id).unwrap();
+        assert_eq!(job.state, JobState::Completed);
+        assert_eq!(job.error, None);
+    }
+
+    #[test]
+    fn dropped_handle_fails_the_job() {
+        let registry = JobRegistry::default();
+        let id = registry.start("import", "x", true).id().to_string();
+        let job = registry.get(&id).unwrap();
+        assert_eq!(job.state, JobState::Failed);
+        assert!(job.error.is_some());
+    }
+
+    #[test]
+    fn keeps_only_the_newest_finished_jobs() {
+        let registry = JobRegistry::new(2);
+        let ids: Vec<String> = (0..3)
+            .map(|i| {
+                let job = registry.start("import", &i.to_string(), false);
+                let id = job.id().to_string();
+                job.complete();
+                id
+            })
+            .collect();
+        let running = registry.start("import", "running", false);
+
+        assert!(registry.get(&ids[0]).is_none());
+        assert!(registry.get(&ids[1]).is_some());
+        assert!(registry.get(&ids[2]).is_some());
+        assert!(registry.get(running.id()).is_some());
+    }
+}
@@ -0,0 +1,14 @@
+//! Code shared by the mvirt daemons, the cplane and the CLI.
+//!
+//! Unlike mvirt-log, which also carries the log service and its Raft
+//! store, this crate has no heavy dependencies, so anything that only
+//! needs these building blocks can link it alone.
+
+pub mod events;
+pub mod jobs;
+pub mod limits;
+pub mod naming;
+pub mod netboot;
+pub mod request_id;
+pub mod rule_window;
+pub mod watch;
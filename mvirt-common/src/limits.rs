@@ -0,0 +1,428 @@
+//! Request admission for mvirt's gRPC and REST servers.
+//!
+//! A [`Limiter`] combines a token bucket per client with a cap on requests
+//! in flight across all clients. A caller that hammers an endpoint in a
+//! loop gets rejected with a retry hint instead of queueing work on the
+//! daemon (or the cplane's Raft log) without bound.
+//!
+//! The daemons wrap their tonic server in [`GrpcLimitLayer`], which turns
+//! a rejection into `RESOURCE_EXHAUSTED` with a `retry-after` metadata
+//! entry; the cplane's REST router maps the same rejection to HTTP 429.
+//! The caller picks the client key: the daemons use the remote IP, the
+//! cplane the peer address until a credential has been validated and the
+//! account after that.
+//!
+//! The daemons only listen on TCP. Calls the node agent relays for the
+//! cplane arrive from loopback like those of a local `mvirt` CLI and share
+//! one bucket with them; the cplane limits its own callers before it gets
+//! that far. A connection without an address (mvirtd's in-process
+//! transport) is only held to the in-flight cap.
+
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use tonic::transport::server::TcpConnectInfo;
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// Buckets kept at most; a new client beyond this evicts the least
+/// recently used one.
+const MAX_TRACKED_CLIENTS: usize = 1024;
+/// Retry hint when the global cap is hit; in-flight calls finish fast.
+const OVERLOAD_RETRY: Duration = Duration::from_secs(1);
+
+const RATE_LIMIT_ENV: &str = "MVIRT_RATE_LIMIT";
+const RATE_BURST_ENV: &str = "MVIRT_RATE_BURST";
+const MAX_CONCURRENT_ENV: &str = "MVIRT_MAX_CONCURRENT_REQUESTS";
+
+/// Admission settings, shared by the daemons' command lines (or
+/// environment) and mvirtd's config file. `0` disables the respective
+/// limit.
+#[derive(Debug, Clone, Copy, clap::Args, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LimitConfig {
+    /// Sustained requests per second and client (0 = unlimited)
+    #[arg(long = "rate-limit", env = RATE_LIMIT_ENV, default_value_t = 100)]
+    pub requests_per_sec: u32,
+
+    /// Requests a client may burst above the sustained rate
+    #[arg(long = "rate-burst", env = RATE_BURST_ENV, default_value_t = 200)]
+    pub burst: u32,
+
+    /// Requests in flight across all clients (0 = unlimited)
+    #[arg(long = "max-concurrent-requests", env = MAX_CONCURRENT_ENV, default_value_t = 64)]
+    pub max_concurrent: usize,
+}
+
+impl LimitConfig {
+    /// Defaults overridden by `MVIRT_RATE_LIMIT`, `MVIRT_RATE_BURST` and
+    /// `MVIRT_MAX_CONCURRENT_REQUESTS`, for daemons without a command line.
+    pub fn from_env() -> Self {
+        fn var<T: std::str::FromStr>(name: &str, default: T) -> T {
+            match std::env::var(name) {
+                Ok(v) => v.parse().unwrap_or_else(|_| {
+                    tracing::warn!(value = %v, "Invalid {}; using default", name);
+                    default
+                }),
+                Err(_) => default,
+            }
+        }
+        let d = Self::default();
+        Self {
+            requests_per_sec: var(RATE_LIMIT_ENV, d.requests_per_sec),
+            burst: var(RATE_BURST_ENV, d.burst),
+            max_concurrent: var(MAX_CONCURRENT_ENV, d.max_concurrent),
+        }
+    }
+}
+
+impl Default for LimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_sec: 100,
+            burst: 200,
+            max_concurrent: 64,
+        }
+    }
+}
+
+/// Why a request was not admitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    /// The client exceeded its rate.
+    RateLimited { retry_after: Duration },
+    /// Too many requests in flight overall.
+    Overloaded,
+}
+
+impl Rejection {
+    /// Whole seconds a client should wait, at least one.
+    pub fn retry_after_secs(&self) -> u64 {
+        match self {
+            Rejection::RateLimited { retry_after } => {
+                retry_after.as_secs_f64().ceil().max(1.0) as u64
+            }
+            Rejection::Overloaded => OVERLOAD_RETRY.as_secs(),
+        }
+    }
+
+    pub fn message(&self) -> &'static str {
+        match self {
+            Rejection::RateLimited { .. } => "rate limit exceeded",
+            Rejection::Overloaded => "too many requests in flight",
+        }
+    }
+
+    pub fn into_status(self) -> Status {
+        let mut status = Status::resource_exhausted(self.message());
+        if let Ok(v) = self.retry_after_secs().to_string().parse() {
+            status.metadata_mut().insert("retry-after", v);
+        }
+        status
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Per-client token buckets plus a global in-flight counter.
+pub struct Limiter {
+    config: LimitConfig,
+    buckets: Mutex<HashMap<String, Bucket>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+/// Holds one in-flight slot until dropped.
+pub struct InFlight(Option<Arc<AtomicUsize>>);
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        if let Some(counter) = self.0.take() {
+            counter.fetch_sub(1, Ordering::AcqRel);
+        }
+    }
+}
+
+impl Limiter {
+    pub fn new(config: LimitConfig) -> Self {
+        Self {
+            config,
+            buckets: Mutex::new(HashMap::new()),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Admit one request from `client`. The returned guard counts against
+    /// the global cap until it is dropped.
+    pub fn admit(&self, client: &str) -> Result<InFlight, Rejection> {
+        self.take(client)?;
+        self.enter()
+    }
+
+    /// Take an in-flight slot without charging a client, for requests that
+    /// are charged with [`Limiter::take`] once their client is known.
+    pub fn enter(&self) -> Result<InFlight, Rejection> {
+        if self.config.max_concurrent == 0 {
+            return Ok(InFlight(None));
+        }
+        let prev = self.in_flight.fetch_add(1, Ordering::AcqRel);
+        if prev >= self.config.max_concurrent {
+            self.in_flight.fetch_sub(1, Ordering::AcqRel);
+            return Err(Rejection::Overloaded);
+        }
+        Ok(InFlight(Some(self.in_flight.clone())))
+    }
+
+    /// Charge one request to `client`.
+    pub fn take(&self, client: &str) -> Result<(), Rejection> {
+        self.take_token(client, Instant::now())
+    }
+
+    /// Fail like [`Limiter::take`] would, without charging `client`.
+    pub fn check(&self, client: &str) -> Result<(), Rejection> {
+        self.check_token(client, Instant::now())
+    }
+
+    fn take_token(&self, client: &str, now: Instant) -> Result<(), Rejection> {
+        let rate = self.config.requests_per_sec as f64;
+        if rate == 0.0 {
+            return Ok(());
+        }
+        let capacity = rate + self.config.burst as f64;
+        let mut buckets = self.buckets.lock().unwrap();
+        if buckets.len() >= MAX_TRACKED_CLIENTS && !buckets.contains_key(client) {
+            let lru = buckets
+                .iter()
+                .min_by_key(|(_, b)| b.updated)
+                .map(|(c, _)| c.clone());
+            if let Some(lru) = lru {
+                buckets.remove(&lru);
+            }
+        }
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+        bucket.tokens = refilled(bucket, now, rate, capacity);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(rate_limited(bucket.tokens, rate))
+        }
+    }
+
+    fn check_token(&self, client: &str, now: Instant) -> Result<(), Rejection> {
+        let rate = self.config.requests_per_sec as f64;
+        if rate == 0.0 {
+            return Ok(());
+        }
+        let capacity = rate + self.config.burst as f64;
+        let buckets = self.buckets.lock().unwrap();
+        match buckets
+            .get(client)
+            .map(|b| refilled(b, now, rate, capacity))
+        {
+            Some(tokens) if tokens < 1.0 => Err(rate_limited(tokens, rate)),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn refilled(bucket: &Bucket, now: Instant, rate: f64, capacity: f64) -> f64 {
+    let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+    (bucket.tokens + elapsed * rate).min(capacity)
+}
+
+fn rate_limited(tokens: f64, rate: f64) -> Rejection {
+    Rejection::RateLimited {
+        retry_after: Duration::from_secs_f64((1.0 - tokens) / rate),
+    }
+}
+
+/// Tower layer applying a [`Limiter`] to a tonic server:
+/// `Server::builder().layer(GrpcLimitLayer::new(config))`.
+///
+/// The in-flight slot is released when the response headers are sent, so
+/// long-lived streams don't hold it for their whole life.
+#[derive(Clone)]
+pub struct GrpcLimitLayer {
+    limiter: Arc<Limiter>,
+}
+
+impl GrpcLimitLayer {
+    pub fn new(config: LimitConfig) -> Self {
+        Self {
+            limiter: Arc::new(Limiter::new(config)),
+        }
+    }
+}
+
+impl<S> Layer<S> for GrpcLimitLayer {
+    type Service = GrpcLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcLimit {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcLimit<S> {
+    inner: S,
+    limiter: Arc<Limiter>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for GrpcLimit<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<tonic::body::Body>>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn std::future::Future<Output = Result<Self::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let client = req
+            .extensions()
+            .get::<TcpConnectInfo>()
+            .and_then(|info| info.remote_addr())
+            .map(|addr| addr.ip().to_string());
+        let admitted = match &client {
+            Some(client) => self.limiter.admit(client),
+            None => self.limiter.enter(),
+        };
+        let guard = match admitted {
+            Ok(guard) => guard,
+            Err(rejection) => {
+                tracing::debug!(?client, path = %req.uri().path(), ?rejection, "request rejected");
+                return Box::pin(async move { Ok(rejection.into_status().into_http()) });
+            }
+        };
+        // Swap in a fresh clone so the one that was polled ready serves
+        // this call (tower's usual `Service::call` dance).
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let response = inner.call(req).await;
+            drop(guard);
+            response
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(rate: u32, burst: u32, max_concurrent: usize) -> Limiter {
+        Limiter::new(LimitConfig {
+            requests_per_sec: rate,
+            burst,
+            max_concurrent,
+        })
+    }
+
+    #[test]
+    fn bucket_refills_over_time() {
+        let l = limiter(10, 0, 0);
+        let client = "192.0.2.1";
+        let start = Instant::now();
+        for _ in 0..10 {
+            l.take_token(client, start).unwrap();
+        }
+        let Err(Rejection::RateLimited { retry_after }) = l.take_token(client, start) else {
+            panic!("11th request within the same instant must be limited");
+        };
+        assert!(retry_after <= Duration::from_millis(100));
+
+        // Another client has its own bucket.
+        l.take_token("192.0.2.2", start).unwrap();
+
+        // 100ms later one token is back.
+        let later = start + Duration::from_millis(100);
+        l.take_token(client, later).unwrap();
+        assert!(l.take_token(client, later).is_err());
+    }
+
+    #[test]
+    fn concurrency_cap_releases_on_drop() {
+        let l = limiter(0, 0, 2);
+        let client = "::1";
+        let a = l.admit(client).unwrap();
+        let _b = l.admit(client).unwrap();
+        assert_eq!(l.admit(client).err(), Some(Rejection::Overloaded));
+        drop(a);
+        l.admit(client).unwrap();
+    }
+
+    #[test]
+    fn check_does_not_charge() {
+        let l = limiter(1, 0, 0);
+        let start = Instant::now();
+        l.check_token("a", start).unwrap();
+        l.take_token("a", start).unwrap();
+        assert!(l.check_token("a", start).is_err());
+        assert!(l.check_token("a", start).is_err());
+        l.check_token("a", start + Duration::from_secs(1)).unwrap();
+        l.take_token("a", start + Duration::from_secs(1)).unwrap();
+    }
+
+    #[test]
+    fn tracked_clients_are_capped_lru() {
+        let l = limiter(1, 0, 0);
+        let start = Instant::now();
+        for i in 0..MAX_TRACKED_CLIENTS {
+            let at = start + Duration::from_millis(i as u64);
+            l.take_token(&format!("client-{i}"), at).unwrap();
+        }
+        // client-0 is used again, so client-1 is now the least recent
+        let later = start + Duration::from_secs(10);
+        l.take_token("client-0", later).unwrap();
+        l.take_token("new", later).unwrap();
+
+        let buckets = l.buckets.lock().unwrap();
+        assert_eq!(buckets.len(), MAX_TRACKED_CLIENTS);
+        assert!(buckets.contains_key("client-0"));
+        assert!(!buckets.contains_key("client-1"));
+        assert!(buckets.contains_key("new"));
+    }
+
+    #[test]
+    fn enter_only_counts_in_flight() {
+        let l = limiter(1, 0, 1);
+        let _a = l.enter().unwrap();
+        assert_eq!(l.enter().err(), Some(Rejection::Overloaded));
+        assert!(l.buckets.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejection_status_carries_retry_after() {
+        let status = Rejection::RateLimited {
+            retry_after: Duration::from_millis(1500),
+        }
+        .into_status();
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "2");
+        assert_eq!(Rejection::Overloaded.retry_after_secs(), 1);
+    }
+}
@@ -0,0 +1,183 @@
+//! Resource name rules shared by the daemons and the cplane.
+//!
+//! Names of VMs, pods, volumes, templates, snapshots, networks, NICs and
+//! security groups show up in CLI tables, audit messages and ZFS
+//! properties, so they follow DNS-label-like rules: 1 to
+//! [`MAX_NAME_LEN`] lowercase letters, digits, `.`, `-` or `_`, starting
+//! and ending with a letter or digit. Names beginning with
+//! [`RESERVED_PREFIX`] are kept for resources mvirt creates itself.
+//!
+//! Create RPCs call [`validate_name`] and return the error as
+//! `INVALID_ARGUMENT`; the cplane's REST handlers answer 400 with the
+//! same message. Uniqueness is the caller's job since the scope differs
+//! per resource.
+
+use std::fmt;
+
+use tonic::Status;
+
+/// Longest accepted name, in bytes (the length of a DNS label).
+pub const MAX_NAME_LEN: usize = 63;
+
+/// Prefix reserved for names mvirt assigns itself.
+pub const RESERVED_PREFIX: &str = "mvirt-";
+
+/// Why a name was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NameErrorKind {
+    Empty,
+    TooLong(usize),
+    InvalidChar(char, usize),
+    BadStart(char),
+    BadEnd(char),
+    Reserved,
+}
+
+/// A rejected name, with the kind of resource it was meant for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameError {
+    pub resource: String,
+    pub name: String,
+    pub kind: NameErrorKind,
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let resource = &self.resource;
+        match &self.kind {
+            NameErrorKind::Empty => write!(f, "{} name is required", resource),
+            NameErrorKind::TooLong(len) => write!(
+                f,
+                "{} name is {} characters long; at most {} are allowed",
+                resource, len, MAX_NAME_LEN
+            ),
+            NameErrorKind::InvalidChar(ch, pos) => write!(
+                f,
+                "{} name '{}' contains {:?} at position {}; only lowercase letters, digits, '.', '-' and '_' are allowed",
+                resource, self.name, ch, pos
+            ),
+            NameErrorKind::BadStart(ch) => write!(
+                f,
+                "{} name '{}' must start with a lowercase letter or digit, not {:?}",
+                resource, self.name, ch
+            ),
+            NameErrorKind::BadEnd(ch) => write!(
+                f,
+                "{} name '{}' must end with a lowercase letter or digit, not {:?}",
+                resource, self.name, ch
+            ),
+            NameErrorKind::Reserved => write!(
+                f,
+                "{} name '{}' uses the reserved prefix '{}'",
+                resource, self.name, RESERVED_PREFIX
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
+
+impl From<NameError> for Status {
+    fn from(e: NameError) -> Self {
+        Status::invalid_argument(e.to_string())
+    }
+}
+
+fn is_alnum(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit()
+}
+
+/// Check `name` against the rules above. `resource` names the kind of
+/// resource for the error message, e.g. `"Volume"`.
+pub fn validate_name(resource: &str, name: &str) -> Result<(), NameError> {
+    let err = |kind| NameError {
+        resource: resource.to_string(),
+        name: name.to_string(),
+        kind,
+    };
+
+    let Some(first) = name.chars().next() else {
+        return Err(err(NameErrorKind::Empty));
+    };
+    if name.len() > MAX_NAME_LEN {
+        return Err(err(NameErrorKind::TooLong(name.chars().count())));
+    }
+    if let Some((pos, ch)) = name
+        .chars()
+        .enumerate()
+        .find(|&(_, c)| !(is_alnum(c) || c == '.' || c == '-' || c == '_'))
+    {
+        return Err(err(NameErrorKind::InvalidChar(ch, pos)));
+    }
+    if !is_alnum(first) {
+        return Err(err(NameErrorKind::BadStart(first)));
+    }
+    let last = name.chars().next_back().unwrap_or(first);
+    if !is_alnum(last) {
+        return Err(err(NameErrorKind::BadEnd(last)));
+    }
+    if name.starts_with(RESERVED_PREFIX) {
+        return Err(err(NameErrorKind::Reserved));
+    }
+    Ok(())
+}
+
+/// [`validate_name`] for resources whose name may be left out.
+pub fn validate_optional_name(resource: &str, name: Option<&str>) -> Result<(), NameError> {
+    match name {
+        Some(name) if !name.is_empty() => validate_name(resource, name),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kind(name: &str) -> Option<NameErrorKind> {
+        validate_name("VM", name).err().map(|e| e.kind)
+    }
+
+    #[test]
+    fn test_accepts_label_like_names() {
+        for name in [
+            "web",
+            "web-1",
+            "ubuntu-22.04",
+            "m1.small",
+            "db_primary",
+            "0",
+        ] {
+            assert_eq!(kind(name), None, "{name}");
+        }
+        assert_eq!(kind(&"a".repeat(MAX_NAME_LEN)), None);
+        assert_eq!(kind("mvirt"), None);
+    }
+
+    #[test]
+    fn test_rejects_malformed_names() {
+        assert_eq!(kind(""), Some(NameErrorKind::Empty));
+        assert_eq!(kind(&"a".repeat(300)), Some(NameErrorKind::TooLong(300)));
+        assert_eq!(kind("my vm"), Some(NameErrorKind::InvalidChar(' ', 2)));
+        assert_eq!(kind("Web"), Some(NameErrorKind::InvalidChar('W', 0)));
+        assert_eq!(kind("vm-ä"), Some(NameErrorKind::InvalidChar('ä', 3)));
+        assert_eq!(kind("-web"), Some(NameErrorKind::BadStart('-')));
+        assert_eq!(kind("web."), Some(NameErrorKind::BadEnd('.')));
+        assert_eq!(kind("mvirt-web"), Some(NameErrorKind::Reserved));
+    }
+
+    #[test]
+    fn test_error_message_names_resource_and_maps_to_invalid_argument() {
+        let err = validate_name("Volume", "data disk").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Volume name 'data disk' contains ' ' at position 4; only lowercase letters, digits, '.', '-' and '_' are allowed"
+        );
+        let status = Status::from(err);
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+
+        assert!(validate_optional_name("NIC", None).is_ok());
+        assert!(validate_optional_name("NIC", Some("")).is_ok());
+        assert!(validate_optional_name("NIC", Some("Eth0")).is_err());
+    }
+}
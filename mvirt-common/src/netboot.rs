@@ -0,0 +1,135 @@
+//! Network boot options shared by the network daemons and the cplane.
+//!
+//! A network can announce a boot file to VMs booting from it over DHCP.
+//! The file is either a TFTP file name, fetched from the network's boot
+//! server (PXE), or an `http(s)://` URL for UEFI HTTP boot, which needs no
+//! separate server. DHCP carries it in the BOOTP `file` field, so it is at
+//! most [`MAX_BOOT_FILE_LEN`] ASCII characters.
+//!
+//! mvirt-net and mvirt-ebpf call [`validate_network_boot`] on
+//! CreateNetwork and return the error as `INVALID_ARGUMENT`; the cplane's
+//! REST handlers answer 400 with the same message before the network is
+//! stored.
+
+use std::fmt;
+use std::net::Ipv4Addr;
+
+/// Longest boot file, in bytes (the BOOTP `file` field is 128 bytes,
+/// NUL-terminated).
+pub const MAX_BOOT_FILE_LEN: usize = 127;
+
+/// Why network boot options were rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NetworkBootError {
+    /// The boot server isn't an IPv4 address.
+    InvalidServer(String),
+    /// A boot server was set without a boot file.
+    MissingFile,
+    /// The boot file is too long or not ASCII.
+    InvalidFile(String),
+    /// A TFTP boot file was set without a boot server.
+    MissingServer(String),
+}
+
+impl fmt::Display for NetworkBootError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkBootError::InvalidServer(server) => write!(
+                f,
+                "Invalid boot server '{}': expected an IPv4 address",
+                server
+            ),
+            NetworkBootError::MissingFile => write!(f, "A boot server needs a boot file"),
+            NetworkBootError::InvalidFile(file) => write!(
+                f,
+                "Invalid boot file '{}': expected up to {} ASCII characters",
+                file, MAX_BOOT_FILE_LEN
+            ),
+            NetworkBootError::MissingServer(file) => write!(
+                f,
+                "Boot file '{}' is served over TFTP and needs a boot server",
+                file
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NetworkBootError {}
+
+/// Check a network's boot server and boot file, empty meaning unset.
+/// Returns them parsed, `None` for the ones that are unset.
+pub fn validate_network_boot(
+    boot_server: &str,
+    boot_file: &str,
+) -> Result<(Option<Ipv4Addr>, Option<String>), NetworkBootError> {
+    let server = match boot_server {
+        "" => None,
+        server => Some(
+            server
+                .parse::<Ipv4Addr>()
+                .map_err(|_| NetworkBootError::InvalidServer(server.to_string()))?,
+        ),
+    };
+
+    if boot_file.is_empty() {
+        return match server {
+            Some(_) => Err(NetworkBootError::MissingFile),
+            None => Ok((None, None)),
+        };
+    }
+    if boot_file.len() > MAX_BOOT_FILE_LEN || !boot_file.is_ascii() {
+        return Err(NetworkBootError::InvalidFile(boot_file.to_string()));
+    }
+
+    let is_url = boot_file.starts_with("http://") || boot_file.starts_with("https://");
+    if !is_url && server.is_none() {
+        return Err(NetworkBootError::MissingServer(boot_file.to_string()));
+    }
+
+    Ok((server, Some(boot_file.to_string())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_network_boot() {
+        assert_eq!(validate_network_boot("", "").unwrap(), (None, None));
+        assert_eq!(
+            validate_network_boot("10.0.0.5", "pxelinux.0").unwrap(),
+            (
+                Some(Ipv4Addr::new(10, 0, 0, 5)),
+                Some("pxelinux.0".to_string())
+            )
+        );
+        assert_eq!(
+            validate_network_boot("", "https://boot.example/ipxe.efi").unwrap(),
+            (None, Some("https://boot.example/ipxe.efi".to_string()))
+        );
+        // A URL doesn't need the server, but may come with one
+        assert!(validate_network_boot("10.0.0.5", "http://10.0.0.5/ipxe.efi").is_ok());
+
+        assert!(matches!(
+            validate_network_boot("boot.example", "pxelinux.0"),
+            Err(NetworkBootError::InvalidServer(_))
+        ));
+        assert_eq!(
+            validate_network_boot("10.0.0.5", ""),
+            Err(NetworkBootError::MissingFile)
+        );
+        assert!(matches!(
+            validate_network_boot("", "pxelinux.0"),
+            Err(NetworkBootError::MissingServer(_))
+        ));
+        assert!(matches!(
+            validate_network_boot("10.0.0.5", &"a".repeat(128)),
+            Err(NetworkBootError::InvalidFile(_))
+        ));
+        assert!(validate_network_boot("10.0.0.5", &"a".repeat(127)).is_ok());
+        assert!(matches!(
+            validate_network_boot("10.0.0.5", "bööt.efi"),
+            Err(NetworkBootError::InvalidFile(_))
+        ));
+    }
+}
@@ -0,0 +1,220 @@
+//! Request IDs that follow one change through every daemon it touches.
+//!
+//! The entry point (the CLI, or the cplane's REST API for callers that
+//! don't send one) picks an ID and sends it as the `x-request-id` header.
+//! Each server runs the call with the ID as the task's current request ID
+//! ([`scope`]), inside a `request` span carrying it, so every log line the
+//! call produces names it. Outgoing calls made from there pass it on
+//! through [`RequestIdChannel`], and mvirt-log's `AuditLogger` adds it
+//! to the entry's related object IDs, so a log query for that
+//! object and a grep over the daemons' journals find the same change
+//! everywhere.
+//!
+//! Servers echo the ID in the response headers, including on errors, so a
+//! caller that didn't pick one still learns it. IDs that aren't printable
+//! tokens are replaced rather than propagated.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use http::HeaderValue;
+use tonic::transport::{Channel, Endpoint};
+use tower::{Layer, Service};
+use tracing::Instrument;
+
+/// Header (and gRPC metadata key) carrying the request ID.
+pub const HEADER: &str = "x-request-id";
+
+/// Longest request ID accepted from a caller.
+const MAX_LEN: usize = 128;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+/// A new request ID, e.g. `req-01j9z3k4m5n6p7q8r9s0t1v2w3`.
+pub fn generate() -> String {
+    format!("req-{}", ulid::Ulid::new().to_string().to_lowercase())
+}
+
+/// Whether `id` may be propagated as a request ID: a short token of
+/// letters, digits and `-_.:`.
+pub fn is_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// The request ID of the call the current task is serving, if any.
+pub fn current() -> Option<String> {
+    CURRENT.try_with(|id| id.clone()).ok()
+}
+
+/// Run `f` with `id` as the current request ID.
+pub async fn scope<F: Future>(id: String, f: F) -> F::Output {
+    CURRENT.scope(id, f).await
+}
+
+/// `object_ids` plus the current request ID, for log entries.
+pub fn tag(mut object_ids: Vec<String>) -> Vec<String> {
+    if let Some(id) = current() {
+        if !object_ids.contains(&id) {
+            object_ids.push(id);
+        }
+    }
+    object_ids
+}
+
+/// The request ID in `headers`, if it is a valid one.
+pub fn from_headers(headers: &http::HeaderMap) -> Option<String> {
+    headers
+        .get(HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_valid(id))
+        .map(str::to_string)
+}
+
+/// The request ID a server echoed in an error, if any.
+pub fn from_status(status: &tonic::Status) -> Option<String> {
+    status
+        .metadata()
+        .get(HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Tower layer running each call of a tonic server under its request ID:
+/// `Server::builder().layer(GrpcRequestIdLayer)`.
+///
+/// Calls without a valid ID get a new one, which is also written into the
+/// request so proxies forward it.
+#[derive(Clone, Copy, Default)]
+pub struct GrpcRequestIdLayer;
+
+impl<S> Layer<S> for GrpcRequestIdLayer {
+    type Service = GrpcRequestId<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcRequestId { inner }
+    }
+}
+
+#[derive(Clone)]
+pub struct GrpcRequestId<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for GrpcRequestId<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future =
+        Pin<Box<dyn std::future::Future<Output = Result<Self::Response, S::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let id = from_headers(req.headers()).unwrap_or_else(generate);
+        let value = HeaderValue::from_str(&id).expect("request IDs are valid header values");
+        req.headers_mut().insert(HEADER, value.clone());
+        let span = tracing::info_span!("request", request_id = %id, path = %req.uri().path());
+
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(scope(
+            id,
+            async move {
+                let mut response = inner.call(req).await?;
+                response.headers_mut().insert(HEADER, value);
+                Ok(response)
+            }
+            .instrument(span),
+        ))
+    }
+}
+
+/// Client channel that sends the current request ID with every call.
+///
+/// A drop-in for [`Channel`] in the generated clients:
+/// `VmServiceClient::new(RequestIdChannel::new(channel))`. Calls made
+/// outside a [`scope`] go out without an ID, and the server picks one.
+#[derive(Clone, Debug)]
+pub struct RequestIdChannel {
+    inner: Channel,
+}
+
+impl RequestIdChannel {
+    pub fn new(inner: Channel) -> Self {
+        Self { inner }
+    }
+
+    /// Connect to `dst`, like the generated clients' `connect`.
+    pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+    where
+        D: TryInto<Endpoint>,
+        D::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Ok(Self::new(Endpoint::new(dst)?.connect().await?))
+    }
+}
+
+impl Service<http::Request<tonic::body::Body>> for RequestIdChannel {
+    type Response = <Channel as Service<http::Request<tonic::body::Body>>>::Response;
+    type Error = <Channel as Service<http::Request<tonic::body::Body>>>::Error;
+    type Future = <Channel as Service<http::Request<tonic::body::Body>>>::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Service::<http::Request<tonic::body::Body>>::poll_ready(&mut self.inner, cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<tonic::body::Body>) -> Self::Future {
+        if let Some(value) = current().and_then(|id| HeaderValue::from_str(&id).ok()) {
+            req.headers_mut().entry(HEADER).or_insert(value);
+        }
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_ids_are_valid() {
+        let id = generate();
+        assert!(id.starts_with("req-"));
+        assert!(is_valid(&id));
+        assert_ne!(id, generate());
+    }
+
+    #[test]
+    fn rejects_unprintable_ids() {
+        assert!(is_valid("apply-7f3a.2:1"));
+        assert!(!is_valid(""));
+        assert!(!is_valid("a b"));
+        assert!(!is_valid("a\nb"));
+        assert!(!is_valid("req/1"));
+        assert!(!is_valid(&"x".repeat(MAX_LEN + 1)));
+    }
+
+    #[tokio::test]
+    async fn tag_adds_current_id_once() {
+        assert_eq!(tag(vec!["vm-1".into()]), vec!["vm-1".to_string()]);
+        scope("req-1".into(), async {
+            assert_eq!(current().as_deref(), Some("req-1"));
+            assert_eq!(tag(vec!["vm-1".into()]), vec!["vm-1", "req-1"]);
+            assert_eq!(tag(vec!["req-1".into()]), vec!["req-1"]);
+        })
+        .await;
+        assert_eq!(current(), None);
+    }
+}
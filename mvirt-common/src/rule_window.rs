@@ -0,0 +1,306 @@
+//! Validity windows and weekly schedules of security group rules.
+//!
+//! Both network backends let a rule carry a validity window
+//! (`not_before`/`not_after`) and a weekly schedule such as
+//! `Mon-Fri 08:00-18:00` (UTC). The syntax and the activity check live
+//! here; each backend's timer re-evaluates its NICs as windows open and
+//! close.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::hash::Hash;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, Timelike, Utc};
+
+const DAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// Weekly schedule: a daily time range on a set of weekdays, in UTC.
+///
+/// Written as `[DAYS ]HH:MM-HH:MM`, where DAYS is a comma-separated list
+/// of days or day ranges (`Mon-Fri,Sun`) and defaults to every day. A
+/// range ending before it starts runs past midnight; the part after
+/// midnight belongs to the day it started on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleSchedule {
+    /// Bit 0 = Monday .. bit 6 = Sunday
+    days: u8,
+    /// Minutes after midnight, inclusive
+    start: u16,
+    /// Minutes after midnight, exclusive; up to 24:00
+    end: u16,
+}
+
+impl RuleSchedule {
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        let minute = (now.hour() * 60 + now.minute()) as u16;
+        let today = now.weekday().num_days_from_monday();
+        let on = |day: u32| self.days & (1 << day) != 0;
+        if self.start < self.end {
+            on(today) && (self.start..self.end).contains(&minute)
+        } else {
+            (on(today) && minute >= self.start) || (on((today + 6) % 7) && minute < self.end)
+        }
+    }
+}
+
+fn parse_day(s: &str) -> Option<u32> {
+    DAYS.iter()
+        .position(|d| d.eq_ignore_ascii_case(s))
+        .map(|i| i as u32)
+}
+
+fn parse_time(s: &str) -> Option<u16> {
+    let (h, m) = s.split_once(':')?;
+    let (h, m): (u16, u16) = (h.parse().ok()?, m.parse().ok()?);
+    match (h, m) {
+        (0..=23, 0..=59) | (24, 0) => Some(h * 60 + m),
+        _ => None,
+    }
+}
+
+impl FromStr for RuleSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (days, times) = match s.rsplit_once(' ') {
+            Some((days, times)) => (Some(days.trim()), times),
+            None => (None, s),
+        };
+
+        let days = match days {
+            None => 0x7f,
+            Some(days) => {
+                let mut mask = 0u8;
+                for part in days.split(',').map(str::trim) {
+                    let (first, last) = match part.split_once('-') {
+                        Some((a, b)) => (parse_day(a), parse_day(b)),
+                        None => (parse_day(part), parse_day(part)),
+                    };
+                    let (Some(first), Some(last)) = (first, last) else {
+                        return Err(format!("unknown day '{}'", part));
+                    };
+                    // Ranges wrap around the week, e.g. Sat-Mon
+                    let mut day = first;
+                    loop {
+                        mask |= 1 << day;
+                        if day == last {
+                            break;
+                        }
+                        day = (day + 1) % 7;
+                    }
+                }
+                mask
+            }
+        };
+
+        let (start, end) = times
+            .split_once('-')
+            .ok_or_else(|| format!("expected HH:MM-HH:MM, got '{}'", times))?;
+        let start = parse_time(start).filter(|&t| t < 24 * 60);
+        let end = parse_time(end);
+        let (Some(start), Some(end)) = (start, end) else {
+            return Err(format!("invalid time range '{}'", times));
+        };
+        if start == end {
+            return Err(format!("empty time range '{}'", times));
+        }
+
+        Ok(Self { days, start, end })
+    }
+}
+
+impl fmt::Display for RuleSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.days != 0x7f {
+            // Runs of consecutive days as ranges, Monday first
+            let mut parts = Vec::new();
+            let mut day = 0;
+            while day < 7 {
+                if self.days & (1 << day) == 0 {
+                    day += 1;
+                    continue;
+                }
+                let first = day;
+                while day + 1 < 7 && self.days & (1 << (day + 1)) != 0 {
+                    day += 1;
+                }
+                parts.push(if first == day {
+                    DAYS[first].to_string()
+                } else {
+                    format!("{}-{}", DAYS[first], DAYS[day])
+                });
+                day += 1;
+            }
+            write!(f, "{} ", parts.join(","))?;
+        }
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 60,
+            self.start % 60,
+            self.end / 60,
+            self.end % 60
+        )
+    }
+}
+
+/// A rule that may be limited to a validity window and a schedule.
+pub trait TimedRule {
+    type Id: Eq + Hash;
+
+    fn rule_id(&self) -> Self::Id;
+    fn not_before(&self) -> Option<DateTime<Utc>>;
+    fn not_after(&self) -> Option<DateTime<Utc>>;
+    fn schedule(&self) -> Option<RuleSchedule>;
+
+    /// Whether the rule is inside its validity window and schedule.
+    fn is_active_at(&self, now: DateTime<Utc>) -> bool {
+        self.not_before().is_none_or(|t| now >= t)
+            && self.not_after().is_none_or(|t| now < t)
+            && self.schedule().is_none_or(|s| s.is_active(now))
+    }
+
+    /// Whether the rule is ever inactive.
+    fn is_time_limited(&self) -> bool {
+        self.not_before().is_some() || self.not_after().is_some() || self.schedule().is_some()
+    }
+}
+
+/// Rules of a NIC that are active at `now`, or `None` if none of its
+/// rules is time-limited and it never needs re-evaluation.
+pub fn active_rules<R: TimedRule>(rules: &[R], now: DateTime<Utc>) -> Option<HashSet<R::Id>> {
+    if !rules.iter().any(R::is_time_limited) {
+        return None;
+    }
+    Some(
+        rules
+            .iter()
+            .filter(|r| r.is_active_at(now))
+            .map(R::rule_id)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    /// 2026-10-12 was a Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 12 + day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[derive(Clone, Default)]
+    struct Rule {
+        id: u32,
+        not_before: Option<DateTime<Utc>>,
+        not_after: Option<DateTime<Utc>>,
+        schedule: Option<RuleSchedule>,
+    }
+
+    impl TimedRule for Rule {
+        type Id = u32;
+
+        fn rule_id(&self) -> u32 {
+            self.id
+        }
+
+        fn not_before(&self) -> Option<DateTime<Utc>> {
+            self.not_before
+        }
+
+        fn not_after(&self) -> Option<DateTime<Utc>> {
+            self.not_after
+        }
+
+        fn schedule(&self) -> Option<RuleSchedule> {
+            self.schedule
+        }
+    }
+
+    #[test]
+    fn test_parse_and_display_schedule() {
+        let s: RuleSchedule = "Mon-Fri 08:00-18:00".parse().unwrap();
+        assert_eq!(s.to_string(), "Mon-Fri 08:00-18:00");
+        let s: RuleSchedule = "sat,sun,wed 22:30-06:00".parse().unwrap();
+        assert_eq!(s.to_string(), "Wed,Sat-Sun 22:30-06:00");
+        let s: RuleSchedule = "Sat-Mon 00:00-24:00".parse().unwrap();
+        assert_eq!(s.to_string(), "Mon,Sat-Sun 00:00-24:00");
+        let s: RuleSchedule = "09:00-10:00".parse().unwrap();
+        assert_eq!(s.to_string(), "09:00-10:00");
+
+        for bad in [
+            "",
+            "Mon",
+            "Mon 9-10",
+            "Funday 09:00-10:00",
+            "10:00-10:00",
+            "24:00-01:00",
+        ] {
+            assert!(bad.parse::<RuleSchedule>().is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn test_schedule_activity() {
+        let office: RuleSchedule = "Mon-Fri 08:00-18:00".parse().unwrap();
+        assert!(office.is_active(at(0, 8, 0)));
+        assert!(office.is_active(at(4, 17, 59)));
+        assert!(!office.is_active(at(0, 18, 0)));
+        assert!(!office.is_active(at(5, 12, 0)));
+
+        // Friday night into Saturday morning belongs to Friday
+        let night: RuleSchedule = "Fri 22:00-02:00".parse().unwrap();
+        assert!(night.is_active(at(4, 23, 0)));
+        assert!(night.is_active(at(5, 1, 59)));
+        assert!(!night.is_active(at(5, 2, 0)));
+        assert!(!night.is_active(at(3, 23, 0)));
+        assert!(!night.is_active(at(4, 1, 0)));
+    }
+
+    #[test]
+    fn test_rule_window() {
+        let mut r = Rule::default();
+        assert!(!r.is_time_limited());
+        assert!(r.is_active_at(at(0, 12, 0)));
+
+        r.not_before = Some(at(1, 9, 0));
+        r.not_after = Some(at(1, 17, 0));
+        assert!(r.is_time_limited());
+        assert!(!r.is_active_at(at(1, 8, 59)));
+        assert!(r.is_active_at(at(1, 9, 0)));
+        assert!(!r.is_active_at(at(1, 17, 0)));
+
+        r.schedule = Some("Tue 12:00-13:00".parse().unwrap());
+        assert!(!r.is_active_at(at(1, 10, 0)));
+        assert!(r.is_active_at(at(1, 12, 30)));
+    }
+
+    #[test]
+    fn test_active_rules_only_for_time_limited_nics() {
+        let permanent = Rule {
+            id: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            active_rules(std::slice::from_ref(&permanent), at(0, 12, 0)),
+            None
+        );
+
+        let expiring = Rule {
+            id: 2,
+            not_after: Some(at(0, 13, 0)),
+            ..Default::default()
+        };
+        let rules = [permanent, expiring];
+        assert_eq!(
+            active_rules(&rules, at(0, 12, 0)),
+            Some(HashSet::from([1, 2]))
+        );
+        assert_eq!(active_rules(&rules, at(0, 13, 0)), Some(HashSet::from([1])));
+    }
+}
@@ -0,0 +1,159 @@
+//! Watch streams: a snapshot of what exists, then live changes.
+//!
+//! A Watch RPC subscribes to its daemon's broadcast channel, then takes the
+//! snapshot, so nothing that happens in between is missed (at worst it is
+//! seen twice). [`watch_stream`] sends the snapshot and forwards events
+//! from the channel, optionally only those for one parent object, e.g. the
+//! allocations of one network.
+//!
+//! Watchers mirror state: one that silently skipped a removal would keep
+//! a stale entry forever. A watcher that falls behind the channel therefore
+//! gets `DATA_LOSS` and its stream ends, telling the client to re-sync.
+
+use tokio::sync::{broadcast, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::Status;
+use tracing::warn;
+
+/// Events buffered per watcher before the client has to read them.
+const WATCH_BUFFER: usize = 64;
+
+/// Whether an event with `key` passes `filter`: everything passes without
+/// a filter, else only events whose key matches.
+pub fn matches(filter: Option<&str>, key: Option<&str>) -> bool {
+    filter.is_none_or(|filter| key == Some(filter))
+}
+
+/// Stream `existing`, then the events on `events` whose `key` passes
+/// `filter` (see [`matches`]). `what` names the events in logs and errors.
+pub fn watch_stream<T, K>(
+    what: &'static str,
+    existing: Vec<T>,
+    mut events: broadcast::Receiver<T>,
+    filter: Option<String>,
+    key: K,
+) -> ReceiverStream<Result<T, Status>>
+where
+    T: Clone + Send + 'static,
+    K: Fn(&T) -> Option<&str> + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(WATCH_BUFFER);
+    tokio::spawn(async move {
+        for event in existing {
+            if tx.send(Ok(event)).await.is_err() {
+                return;
+            }
+        }
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if !matches(filter.as_deref(), key(&event)) {
+                        continue;
+                    }
+                    if tx.send(Ok(event)).await.is_err() {
+                        return;
+                    }
+                }
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(skipped = n, "{} watcher lagged, closing stream", what);
+                    let _ = tx
+                        .send(Err(Status::data_loss(format!(
+                            "{} events were dropped, re-sync required",
+                            what
+                        ))))
+                        .await;
+                    return;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            }
+        }
+    });
+    ReceiverStream::new(rx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_stream::StreamExt;
+    use tonic::Code;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Event {
+        network: Option<&'static str>,
+        n: u32,
+    }
+
+    fn event(network: &'static str, n: u32) -> Event {
+        Event {
+            network: Some(network),
+            n,
+        }
+    }
+
+    fn key(event: &Event) -> Option<&str> {
+        event.network
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches(None, Some("net-1")));
+        assert!(matches(None, None));
+        assert!(matches(Some("net-1"), Some("net-1")));
+        assert!(!matches(Some("net-1"), Some("net-2")));
+        assert!(!matches(Some("net-1"), None));
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_then_filtered_events() {
+        let (events, rx) = broadcast::channel(16);
+        let existing = vec![event("net-1", 0)];
+        let stream = watch_stream("Allocation", existing, rx, Some("net-1".into()), key);
+
+        events.send(event("net-2", 1)).unwrap();
+        events
+            .send(Event {
+                network: None,
+                n: 2,
+            })
+            .unwrap();
+        events.send(event("net-1", 3)).unwrap();
+        drop(events);
+
+        let seen: Vec<u32> = stream.map(|e| e.unwrap().n).collect().await;
+        assert_eq!(seen, [0, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_unfiltered_sees_everything() {
+        let (events, rx) = broadcast::channel(16);
+        let stream = watch_stream("Allocation", Vec::new(), rx, None, key);
+        events.send(event("net-2", 1)).unwrap();
+        events
+            .send(Event {
+                network: None,
+                n: 2,
+            })
+            .unwrap();
+        drop(events);
+
+        let seen: Vec<u32> = stream.map(|e| e.unwrap().n).collect().await;
+        assert_eq!(seen, [1, 2]);
+    }
+
+    #[tokio::test]
+    async fn test_lagged_watcher_gets_data_loss() {
+        let (events, rx) = broadcast::channel(2);
+        // Overflow the channel before the watcher reads anything
+        for n in 0..5 {
+            events.send(event("net-1", n)).unwrap();
+        }
+        let mut stream = watch_stream("Allocation", Vec::new(), rx, None, key);
+
+        let err = stream.next().await.unwrap().unwrap_err();
+        assert_eq!(err.code(), Code::DataLoss);
+        assert!(err.message().starts_with("Allocation events"));
+        // The stream ends instead of carrying on with a gap
+        assert!(stream.next().await.is_none());
+        drop(events);
+    }
+}
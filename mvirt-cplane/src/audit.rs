@@ -26,6 +26,8 @@ impl ApiAuditLogger {
 
     fn log_async(&self, level: LogLevel, message: String, object_ids: Vec<String>) {
         let inner = Arc::clone(&self.inner);
+        // The spawned task doesn't inherit the caller's request ID
+        let object_ids = mvirt_common::request_id::tag(object_ids);
         tokio::spawn(async move {
             inner.log(level, message, object_ids).await;
         });
@@ -183,6 +185,60 @@ impl ApiAuditLogger {
         );
     }
 
+    pub fn console_attached(&self, vm_id: &str, node_id: &str, who: &str, access: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Console attached: {} by {} on node {} ({})",
+                vm_id, who, node_id, access
+            ),
+            vec![vm_id.to_string(), node_id.to_string()],
+        );
+    }
+
+    pub fn console_detached(&self, vm_id: &str, who: &str, duration_secs: u64) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Console detached: {} by {} after {}s",
+                vm_id, who, duration_secs
+            ),
+            vec![vm_id.to_string()],
+        );
+    }
+
+    pub fn vm_placement_violated(&self, vm_id: &str, violation: &str) {
+        self.log_async(
+            LogLevel::Warn,
+            format!("VM placement violated: {} ({})", violation, vm_id),
+            vec![vm_id.to_string()],
+        );
+    }
+
+    pub fn vm_migration_started(&self, migration_id: &str, vm_id: &str, target_node_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("VM migration started: {} to node {}", vm_id, target_node_id),
+            vec![migration_id.to_string(), vm_id.to_string()],
+        );
+    }
+
+    pub fn vm_migration_completed(&self, migration_id: &str, vm_id: &str, node_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("VM migration completed: {} now on node {}", vm_id, node_id),
+            vec![migration_id.to_string(), vm_id.to_string()],
+        );
+    }
+
+    pub fn vm_migration_failed(&self, migration_id: &str, vm_id: &str, error: &str) {
+        self.log_async(
+            LogLevel::Warn,
+            format!("VM migration failed: {} ({})", vm_id, error),
+            vec![migration_id.to_string(), vm_id.to_string()],
+        );
+    }
+
     // Project events
     pub fn project_created(&self, project_slug: &str, project_name: &str) {
         self.log_async(
@@ -209,6 +265,17 @@ impl ApiAuditLogger {
         );
     }
 
+    pub fn volume_copied(&self, volume_id: &str, source_id: &str, node_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Volume {} copied to node {} as {}",
+                source_id, node_id, volume_id
+            ),
+            vec![volume_id.to_string(), source_id.to_string()],
+        );
+    }
+
     pub fn volume_deleted(&self, volume_id: &str) {
         self.log_async(
             LogLevel::Audit,
@@ -267,6 +334,57 @@ impl ApiAuditLogger {
         );
     }
 
+    // Flavor events
+    pub fn flavor_created(&self, flavor_id: &str, flavor_name: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Flavor created: {} ({})", flavor_name, flavor_id),
+            vec![flavor_id.to_string()],
+        );
+    }
+
+    pub fn flavor_deleted(&self, flavor_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Flavor deleted: {}", flavor_id),
+            vec![flavor_id.to_string()],
+        );
+    }
+
+    // Webhook events
+    pub fn webhook_created(&self, webhook_id: &str, url: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Webhook created: {} ({})", url, webhook_id),
+            vec![webhook_id.to_string()],
+        );
+    }
+
+    pub fn webhook_deleted(&self, webhook_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Webhook deleted: {}", webhook_id),
+            vec![webhook_id.to_string()],
+        );
+    }
+
+    // SSH key events
+    pub fn ssh_key_created(&self, key_id: &str, name: &str, fingerprint: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("SSH key created: {} ({}, {})", name, key_id, fingerprint),
+            vec![key_id.to_string()],
+        );
+    }
+
+    pub fn ssh_key_deleted(&self, key_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("SSH key deleted: {}", key_id),
+            vec![key_id.to_string()],
+        );
+    }
+
     pub fn template_import_started(&self, job_id: &str) {
         self.log_async(
             LogLevel::Audit,
@@ -274,6 +392,114 @@ impl ApiAuditLogger {
             vec![job_id.to_string()],
         );
     }
+
+    pub fn template_build_started(&self, template_id: &str, vm_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Template build started: {} (build VM {})",
+                template_id, vm_id
+            ),
+            vec![template_id.to_string(), vm_id.to_string()],
+        );
+    }
+
+    // Change history
+    pub fn change_recorded(&self, record: &crate::command::ChangeRecord) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "{} {} by {}: {} field(s) changed [{}]",
+                record.method,
+                record.path,
+                record
+                    .actor_email
+                    .as_deref()
+                    .or(record.actor.as_deref())
+                    .unwrap_or("anonymous"),
+                record.changes.len(),
+                record.id
+            ),
+            vec![record.resource_id.clone(), record.id.clone()],
+        );
+    }
+
+    // Trash
+    pub fn resource_trashed(&self, kind: &str, id: &str, purge_at: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("{} moved to trash: {} (purge at {})", kind, id, purge_at),
+            vec![id.to_string()],
+        );
+    }
+
+    pub fn resource_restored(&self, kind: &str, id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("{} restored from trash: {}", kind, id),
+            vec![id.to_string()],
+        );
+    }
+
+    pub fn resource_purged(&self, kind: &str, id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("{} purged from trash: {}", kind, id),
+            vec![id.to_string()],
+        );
+    }
+
+    // Garbage collection
+    pub fn orphan_collected(&self, node_id: &str, kind: &str, id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Orphaned {} {} deleted from node {}", kind, id, node_id),
+            vec![id.to_string(), node_id.to_string()],
+        );
+    }
+
+    // Jobs
+    pub fn job_cancelled(&self, id: &str, kind: &str, target: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Cancelled {} job {} of {}", kind, id, target),
+            vec![id.to_string(), target.to_string()],
+        );
+    }
+
+    // Upgrades
+    pub fn upgrade_started(&self, id: &str, daemons: &[String], nodes: usize) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Rolling restart {} of {} started on {} nodes",
+                id,
+                daemons.join(", "),
+                nodes
+            ),
+            vec![id.to_string()],
+        );
+    }
+
+    pub fn daemon_restarted(&self, node_id: &str, daemon: &str, from: &str, to: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Restarted {} on node {} ({} -> {})",
+                daemon, node_id, from, to
+            ),
+            vec![node_id.to_string()],
+        );
+    }
+
+    pub fn upgrade_finished(&self, id: &str, succeeded: bool, message: Option<&str>) {
+        let message = match (succeeded, message) {
+            (true, _) => format!("Rolling restart {} finished", id),
+            (false, Some(reason)) => format!("Rolling restart {} failed: {}", id, reason),
+            (false, None) => format!("Rolling restart {} failed", id),
+        };
+        self.log_async(LogLevel::Audit, message, vec![id.to_string()]);
+    }
 }
 
 pub fn create_audit_logger(
@@ -6,6 +6,11 @@
 //!
 //! Disabled when `MVIRT_OIDC_ISSUER` is unset — useful for local dev and the
 //! existing integration tests, which talk to the REST API without tokens.
+//!
+//! Optionally, a role claim can be mapped onto RBAC grants
+//! (`MVIRT_OIDC_ROLE_CLAIM` + `MVIRT_OIDC_ROLE_MAP`, see [`RoleMapping`]), so
+//! an existing SSO can hand out admin rights without mvirt-side memberships.
+//! `mvirt_sa_*` API keys keep working alongside for CLI automation.
 
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -14,7 +19,11 @@ use anyhow::{Context, Result, anyhow};
 use axum::{
     Extension,
     extract::{FromRequestParts, Request, State},
-    http::{StatusCode, header::AUTHORIZATION, request::Parts},
+    http::{
+        StatusCode,
+        header::{AUTHORIZATION, UPGRADE},
+        request::Parts,
+    },
     middleware::Next,
     response::{IntoResponse, Response},
 };
@@ -23,6 +32,8 @@ use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
 use tracing::{info, warn};
 
+use crate::command::{MembershipData, MembershipScope, Role};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthClaims {
     pub sub: String,
@@ -139,6 +150,120 @@ pub struct JwtValidator {
     userinfo_endpoint: Option<String>,
     keys: RwLock<HashMap<String, DecodingKey>>,
     http: reqwest::Client,
+    role_mapping: Option<RoleMapping>,
+}
+
+/// A validated token: the identity claims plus the RBAC grants its role
+/// claim maps to.
+#[derive(Debug, Clone)]
+pub struct ValidatedToken {
+    pub claims: AuthClaims,
+    pub grants: Vec<(MembershipScope, Role)>,
+}
+
+/// Maps values of one JWT claim onto RBAC grants.
+///
+/// `MVIRT_OIDC_ROLE_CLAIM` names the claim; a dotted path reaches into
+/// nested objects (Keycloak's `realm_access.roles`). The claim may be a
+/// string, an array of strings, or an object whose keys are the roles
+/// (Zitadel's `urn:zitadel:iam:org:project:roles`).
+///
+/// `MVIRT_OIDC_ROLE_MAP` is a comma-separated list of
+/// `<claim value>=<role>[:<slug>]`:
+///
+/// ```text
+/// mvirt-admins=platform-admin,acme-ops=org-admin:acme,web-devs=project-admin:web
+/// ```
+///
+/// Grants are evaluated per request and never persisted — removing the
+/// role in the IdP revokes it with the next token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoleMapping {
+    claim: String,
+    rules: Vec<(String, MembershipScope, Role)>,
+}
+
+impl RoleMapping {
+    pub fn from_env() -> Result<Option<Self>> {
+        let Ok(claim) = std::env::var("MVIRT_OIDC_ROLE_CLAIM") else {
+            return Ok(None);
+        };
+        let map = std::env::var("MVIRT_OIDC_ROLE_MAP").unwrap_or_default();
+        Self::parse(&claim, &map).map(Some)
+    }
+
+    pub fn parse(claim: &str, map: &str) -> Result<Self> {
+        let claim = claim.trim();
+        if claim.is_empty() {
+            return Err(anyhow!("MVIRT_OIDC_ROLE_CLAIM is empty"));
+        }
+        let mut rules = Vec::new();
+        for entry in map.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (value, grant) = entry
+                .split_once('=')
+                .ok_or_else(|| anyhow!("role map entry '{entry}' lacks '='"))?;
+            let (role, slug) = match grant.split_once(':') {
+                Some((role, slug)) => (role.trim(), Some(slug.trim())),
+                None => (grant.trim(), None),
+            };
+            let (scope, role) = match (role, slug) {
+                ("platform-admin", None) => (MembershipScope::Platform, Role::PlatformAdmin),
+                ("org-admin", Some(s)) if !s.is_empty() => (
+                    MembershipScope::Org {
+                        org_slug: s.to_string(),
+                    },
+                    Role::OrgAdmin,
+                ),
+                ("project-admin", Some(s)) if !s.is_empty() => (
+                    MembershipScope::Project {
+                        project_slug: s.to_string(),
+                    },
+                    Role::ProjectAdmin,
+                ),
+                _ => {
+                    return Err(anyhow!(
+                        "role map entry '{entry}': expected platform-admin, \
+                         org-admin:<org> or project-admin:<project>"
+                    ));
+                }
+            };
+            rules.push((value.trim().to_string(), scope, role));
+        }
+        if rules.is_empty() {
+            return Err(anyhow!("MVIRT_OIDC_ROLE_MAP has no entries"));
+        }
+        Ok(Self {
+            claim: claim.to_string(),
+            rules,
+        })
+    }
+
+    /// Grants for the role values found in `claims`.
+    pub fn grants(&self, claims: &serde_json::Value) -> Vec<(MembershipScope, Role)> {
+        let values = self.claim_values(claims);
+        self.rules
+            .iter()
+            .filter(|(value, _, _)| values.iter().any(|v| v == value))
+            .map(|(_, scope, role)| (scope.clone(), *role))
+            .collect()
+    }
+
+    fn claim_values<'a>(&self, claims: &'a serde_json::Value) -> Vec<&'a str> {
+        use serde_json::Value;
+        // Exact key first: Zitadel's claim names contain no dots but
+        // other URN-style names might.
+        let found = claims.get(&self.claim).or_else(|| {
+            self.claim
+                .split('.')
+                .try_fold(claims, |node, key| node.get(key))
+        });
+        match found {
+            Some(Value::String(s)) => vec![s.as_str()],
+            Some(Value::Array(items)) => items.iter().filter_map(Value::as_str).collect(),
+            Some(Value::Object(map)) => map.keys().map(String::as_str).collect(),
+            _ => Vec::new(),
+        }
+    }
 }
 
 impl JwtValidator {
@@ -148,10 +273,17 @@ impl JwtValidator {
             return Ok(None);
         };
         let audience = std::env::var("MVIRT_OIDC_AUDIENCE").ok();
-        Ok(Some(Arc::new(Self::new(issuer, audience).await?)))
+        let role_mapping = RoleMapping::from_env()?;
+        Ok(Some(Arc::new(
+            Self::new(issuer, audience, role_mapping).await?,
+        )))
     }
 
-    pub async fn new(issuer: String, audience: Option<String>) -> Result<Self> {
+    pub async fn new(
+        issuer: String,
+        audience: Option<String>,
+        role_mapping: Option<RoleMapping>,
+    ) -> Result<Self> {
         let http = reqwest::Client::builder()
             .timeout(std::time::Duration::from_secs(5))
             .build()?;
@@ -174,11 +306,13 @@ impl JwtValidator {
             userinfo_endpoint: cfg.userinfo_endpoint,
             keys: RwLock::new(HashMap::new()),
             http,
+            role_mapping,
         };
         validator.refresh_keys().await?;
         info!(
             issuer = %validator.issuer,
             userinfo = ?validator.userinfo_endpoint,
+            role_claim = ?validator.role_mapping.as_ref().map(|m| &m.claim),
             "JWT validator ready"
         );
         Ok(validator)
@@ -247,7 +381,7 @@ impl JwtValidator {
         Ok(())
     }
 
-    pub async fn validate(&self, token: &str) -> Result<AuthClaims> {
+    pub async fn validate(&self, token: &str) -> Result<ValidatedToken> {
         let header = decode_header(token).context("malformed JWT header")?;
         let kid = header.kid.context("JWT missing 'kid'")?;
         let alg = header.alg;
@@ -278,8 +412,18 @@ impl JwtValidator {
             // SPA client_id by default and we don't enforce it yet.
             v.validate_aud = false;
         }
-        let data = decode::<AuthClaims>(token, &key, &v).context("JWT validation failed")?;
-        Ok(data.claims)
+        // Decode into a raw map first so the role claim, whatever its
+        // name, is still around for the mapping.
+        let raw = decode::<serde_json::Value>(token, &key, &v)
+            .context("JWT validation failed")?
+            .claims;
+        let grants = self
+            .role_mapping
+            .as_ref()
+            .map(|m| m.grants(&raw))
+            .unwrap_or_default();
+        let claims = serde_json::from_value(raw).context("JWT claims malformed")?;
+        Ok(ValidatedToken { claims, grants })
     }
 }
 
@@ -290,13 +434,12 @@ impl JwtValidator {
 pub struct AuthContext {
     pub claims: AuthClaims,
     pub account: crate::command::AccountData,
-    pub memberships: Vec<crate::command::MembershipData>,
+    pub memberships: Vec<MembershipData>,
 }
 
 impl AuthContext {
     /// True if the caller has Platform/PlatformAdmin.
     pub fn is_platform_admin(&self) -> bool {
-        use crate::command::{MembershipScope, Role};
         self.memberships
             .iter()
             .any(|m| m.scope == MembershipScope::Platform && m.role == Role::PlatformAdmin)
@@ -304,7 +447,6 @@ impl AuthContext {
 
     /// True if the caller has Org/OrgAdmin for `org_slug` (or platform-admin).
     pub fn is_org_admin(&self, org_slug: &str) -> bool {
-        use crate::command::{MembershipScope, Role};
         if self.is_platform_admin() {
             return true;
         }
@@ -317,7 +459,6 @@ impl AuthContext {
     /// True if the caller has Project/ProjectAdmin for `project_slug` (or
     /// platform-admin / org-admin of the project's parent org).
     pub fn is_project_admin(&self, project_slug: &str, project_org_slug: Option<&str>) -> bool {
-        use crate::command::{MembershipScope, Role};
         if self.is_platform_admin() {
             return true;
         }
@@ -333,6 +474,22 @@ impl AuthContext {
     }
 }
 
+/// The `access_token` query parameter of a WebSocket upgrade. Browsers
+/// can't set headers on WebSockets, so consoles take the token this way;
+/// plain requests must still send a Bearer header.
+fn websocket_token(req: &Request) -> Option<String> {
+    let upgrade = req.headers().get(UPGRADE)?.to_str().ok()?;
+    if !upgrade.eq_ignore_ascii_case("websocket") {
+        return None;
+    }
+    req.uri()
+        .query()?
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("access_token="))
+        .filter(|token| !token.is_empty())
+        .map(str::to_string)
+}
+
 /// Axum middleware: validates the Bearer token, lazy-creates the Account
 /// from the OIDC `(iss, sub)`, attaches the `AuthContext` (claims + account
 /// + memberships) to request extensions.
@@ -350,7 +507,10 @@ pub async fn require_auth(
         .headers()
         .get(AUTHORIZATION)
         .and_then(|h| h.to_str().ok())
-        .and_then(|s| s.strip_prefix("Bearer "));
+        .and_then(|s| s.strip_prefix("Bearer "))
+        .map(str::to_string)
+        .or_else(|| websocket_token(&req));
+    let token = token.as_deref();
 
     // No bearer presented. In dev mode (no JWT validator configured) we let
     // the request through so handlers fall back to the unauthenticated-dev
@@ -385,8 +545,8 @@ pub async fn require_auth(
     let Some(validator) = state.jwt_validator.as_ref() else {
         return (StatusCode::UNAUTHORIZED, "JWT auth not configured").into_response();
     };
-    let claims = match validator.validate(token).await {
-        Ok(c) => c,
+    let ValidatedToken { claims, grants } = match validator.validate(token).await {
+        Ok(t) => t,
         Err(err) => {
             warn!(?err, "JWT validation failed");
             return (StatusCode::UNAUTHORIZED, "invalid token").into_response();
@@ -429,11 +589,22 @@ pub async fn require_auth(
         warn!(error = %e, "initial admin bootstrap failed");
     }
 
-    let memberships = state
+    let mut memberships = state
         .store
         .list_memberships_for_account(&account.id)
         .await
         .unwrap_or_default();
+    // IdP-mapped roles ride along as synthetic memberships for this
+    // request only.
+    let now = chrono::Utc::now().to_rfc3339();
+    memberships.extend(grants.into_iter().map(|(scope, role)| MembershipData {
+        id: format!("oidc:{}", claims.iss),
+        account_id: account.id.clone(),
+        scope,
+        role,
+        created_by_account: account.id.clone(),
+        created_at: now.clone(),
+    }));
 
     let ctx = AuthContext {
         claims: claims.clone(),
@@ -590,3 +761,79 @@ impl<S: Send + Sync> axum::extract::OptionalFromRequestParts<S> for Authenticate
             .map(AuthenticatedAccount))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn websocket_token_only_on_upgrades() {
+        let request = |uri: &str, upgrade: Option<&str>| {
+            let mut builder = Request::builder().uri(uri);
+            if let Some(upgrade) = upgrade {
+                builder = builder.header(UPGRADE, upgrade);
+            }
+            builder.body(axum::body::Body::empty()).unwrap()
+        };
+        let uri = "/v1/vms/vm-1/console?access=shared&access_token=abc";
+        assert_eq!(
+            websocket_token(&request(uri, Some("websocket"))).as_deref(),
+            Some("abc")
+        );
+        assert_eq!(websocket_token(&request(uri, None)), None);
+        assert_eq!(
+            websocket_token(&request(
+                "/v1/vms/vm-1/console?access_token=",
+                Some("websocket")
+            )),
+            None
+        );
+    }
+
+    #[test]
+    fn role_map_parses_all_scopes() {
+        let m = RoleMapping::parse(
+            "groups",
+            "ops=platform-admin, acme-ops=org-admin:acme,web=project-admin:web",
+        )
+        .unwrap();
+        assert_eq!(m.rules.len(), 3);
+        assert_eq!(m.rules[0].1, MembershipScope::Platform);
+        assert_eq!(
+            m.rules[1].1,
+            MembershipScope::Org {
+                org_slug: "acme".into()
+            }
+        );
+        assert_eq!(m.rules[2].2, Role::ProjectAdmin);
+
+        for bad in [
+            "",
+            "ops",
+            "ops=org-admin",
+            "ops=viewer",
+            "ops=platform-admin:x",
+        ] {
+            assert!(RoleMapping::parse("groups", bad).is_err(), "{bad}");
+        }
+        assert!(RoleMapping::parse(" ", "ops=platform-admin").is_err());
+    }
+
+    #[test]
+    fn role_claim_shapes() {
+        let m = RoleMapping::parse("roles", "ops=platform-admin,web=project-admin:web").unwrap();
+        assert_eq!(m.grants(&json!({"roles": ["web", "other"]})).len(), 1);
+        assert_eq!(m.grants(&json!({"roles": "ops"}))[0].1, Role::PlatformAdmin);
+        assert_eq!(m.grants(&json!({"roles": {"ops": {}, "web": {}}})).len(), 2);
+        assert!(m.grants(&json!({"groups": ["ops"]})).is_empty());
+
+        let nested = RoleMapping::parse("realm_access.roles", "ops=platform-admin").unwrap();
+        assert_eq!(
+            nested
+                .grants(&json!({"realm_access": {"roles": ["ops"]}}))
+                .len(),
+            1
+        );
+    }
+}
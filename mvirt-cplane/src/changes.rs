@@ -0,0 +1,356 @@
+//! Change history for mutating REST calls.
+//!
+//! [`record_changes`] wraps the user-facing routes. For every successful
+//! POST/PUT/PATCH/DELETE it snapshots the addressed resource from the store
+//! before and after the handler runs and records a [`ChangeRecord`]: the
+//! caller, the request body with secrets redacted, and a field-level diff
+//! of the stored object. The record ID is returned in `x-mvirt-change-id`
+//! and written into the mvirt-log audit entry for the same call, so either
+//! side leads to the other. `GET /v1/changes/{resource_id}` reads it back.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::{
+    body::{Body, Bytes, to_bytes},
+    extract::{OriginalUri, Request, State},
+    http::{HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use tracing::warn;
+
+use crate::auth::AuthContext;
+use crate::command::{ChangeRecord, FieldChange};
+use crate::rest::AppState;
+use crate::store::DataStore;
+
+/// Response header carrying the change record ID.
+pub const CHANGE_ID_HEADER: &str = "x-mvirt-change-id";
+
+/// Request bodies above this are refused, same as axum's `Json` default.
+const MAX_BODY: usize = 2 * 1024 * 1024;
+/// Single-object responses; anything larger is not a resource we diff.
+const MAX_RESPONSE: usize = 16 * 1024 * 1024;
+
+/// Route segments whose next segment is the ID of a stored resource we
+/// can snapshot. Sub-collections not listed here (rules, members,
+/// snapshots) are attributed to the enclosing resource.
+const TRACKED: &[&str] = &[
+    "vms",
+    "networks",
+    "nics",
+    "volumes",
+    "templates",
+    "security-groups",
+    "ssh-keys",
+    "flavors",
+    "orgs",
+    "projects",
+    "clusters",
+    "nodes",
+];
+
+/// Segments after a tracked kind that name an action, not an ID.
+const ACTIONS: &[&str] = &["import", "build", "versions"];
+
+/// Field names (case-insensitive, `_`/`-` ignored) containing any of these
+/// are replaced by `"<redacted>"` before the request or a snapshot is
+/// stored.
+const SECRET_MARKERS: &[&str] = &["password", "secret", "token", "privatekey", "userdata"];
+
+/// The resource a request addresses, from its path.
+#[derive(Debug, PartialEq)]
+struct Target {
+    kind: String,
+    /// `None` for creates; the ID comes from the response then.
+    id: Option<String>,
+    project_slug: Option<String>,
+}
+
+fn target(path: &str) -> Option<Target> {
+    let segments: Vec<&str> = path
+        .trim_start_matches("/v1")
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .collect();
+    let (project_slug, rest) = match segments.as_slice() {
+        ["projects", slug, rest @ ..] if !rest.is_empty() => (Some(slug.to_string()), rest),
+        all => (None, all),
+    };
+    let at = rest.iter().rposition(|s| TRACKED.contains(s))?;
+    let id = rest
+        .get(at + 1)
+        .filter(|s| !ACTIONS.contains(s))
+        .map(|s| s.to_string());
+    Some(Target {
+        kind: rest[at].to_string(),
+        id,
+        project_slug,
+    })
+}
+
+/// Axum middleware recording the change history of mutating calls. Runs
+/// inside [`crate::auth::require_auth`] so the caller is known.
+pub async fn record_changes(
+    State(state): State<Arc<AppState>>,
+    OriginalUri(uri): OriginalUri,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !matches!(
+        *req.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    ) {
+        return next.run(req).await;
+    }
+    let Some(target) = target(uri.path()) else {
+        return next.run(req).await;
+    };
+
+    let method = req.method().to_string();
+    let actor = req.extensions().get::<AuthContext>().map(|ctx| {
+        (
+            ctx.account.id.clone(),
+            ctx.account.email.clone().or(ctx.claims.email.clone()),
+        )
+    });
+    let (parts, body) = req.into_parts();
+    let body = match to_bytes(body, MAX_BODY).await {
+        Ok(b) => b,
+        Err(_) => {
+            return (
+                axum::http::StatusCode::PAYLOAD_TOO_LARGE,
+                "request too large",
+            )
+                .into_response();
+        }
+    };
+    let request = sanitized_request(&body);
+
+    let before = match target.id.as_deref() {
+        Some(id) => snapshot(state.store.as_ref(), &target.kind, id).await,
+        None => None,
+    };
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if !response.status().is_success() {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let Ok(body) = to_bytes(body, MAX_RESPONSE).await else {
+        return Response::from_parts(parts, Body::empty());
+    };
+
+    let resource_id = target.id.clone().or_else(|| created_id(&body));
+    if let Some(resource_id) = resource_id {
+        let after = snapshot(state.store.as_ref(), &target.kind, &resource_id).await;
+        let project_slug = target.project_slug.clone().or_else(|| {
+            [&after, &before]
+                .into_iter()
+                .flatten()
+                .find_map(|v| v.get("project_slug")?.as_str().map(str::to_string))
+        });
+        let record = ChangeRecord {
+            id: format!("chg-{}", uuid::Uuid::new_v4()),
+            timestamp: chrono::Utc::now().to_rfc3339(),
+            resource_kind: target.kind,
+            resource_id,
+            project_slug,
+            actor: actor.as_ref().map(|(id, _)| id.clone()),
+            actor_email: actor.and_then(|(_, email)| email),
+            method,
+            path: uri.path().to_string(),
+            request,
+            changes: diff(before.as_ref(), after.as_ref()),
+        };
+        if let Ok(v) = HeaderValue::from_str(&record.id) {
+            parts.headers.insert(CHANGE_ID_HEADER, v);
+        }
+        state.audit.change_recorded(&record);
+        if let Err(e) = state.store.record_change(record).await {
+            warn!(error = %e, "failed to record change history");
+        }
+    }
+    Response::from_parts(parts, Body::from(body))
+}
+
+/// Current stored form of a resource, as JSON.
+async fn snapshot(store: &dyn DataStore, kind: &str, id: &str) -> Option<Value> {
+    fn json<T: serde::Serialize>(v: Option<T>) -> Option<Value> {
+        let mut v = serde_json::to_value(v?).ok()?;
+        redact(&mut v);
+        Some(v)
+    }
+    match kind {
+        "vms" => json(store.get_vm(id).await.ok()?),
+        "networks" => json(store.get_network(id).await.ok()?),
+        "nics" => json(store.get_nic(id).await.ok()?),
+        "volumes" => json(store.get_volume(id).await.ok()?),
+        "templates" => json(store.get_template(id).await.ok()?),
+        "security-groups" => json(store.get_security_group(id).await.ok()?),
+        "ssh-keys" => json(store.get_ssh_key(id).await.ok()?),
+        "flavors" => json(store.get_flavor(id).await.ok()?),
+        "orgs" => json(store.get_org(id).await.ok()?),
+        "projects" => json(store.get_project(id).await.ok()?),
+        "clusters" => json(store.get_cluster(id).await.ok()?),
+        "nodes" => json(store.get_node(id).await.ok()?),
+        _ => None,
+    }
+}
+
+/// ID of the object a create returned: `id`, or `slug` for orgs/projects.
+fn created_id(body: &Bytes) -> Option<String> {
+    let v: Value = serde_json::from_slice(body).ok()?;
+    ["id", "slug"]
+        .iter()
+        .find_map(|k| v.get(k)?.as_str().map(str::to_string))
+}
+
+fn sanitized_request(body: &Bytes) -> Option<String> {
+    if body.is_empty() {
+        return None;
+    }
+    match serde_json::from_slice::<Value>(body) {
+        Ok(mut v) => {
+            redact(&mut v);
+            Some(v.to_string())
+        }
+        // Not JSON: keep the fact, not the bytes.
+        Err(_) => Some(format!("\"<{} bytes, not JSON>\"", body.len())),
+    }
+}
+
+fn redact(v: &mut Value) {
+    match v {
+        Value::Object(map) => {
+            for (k, v) in map.iter_mut() {
+                let key: String = k
+                    .chars()
+                    .filter(|c| *c != '_' && *c != '-')
+                    .collect::<String>()
+                    .to_ascii_lowercase();
+                if SECRET_MARKERS.iter().any(|m| key.contains(m)) {
+                    *v = Value::String("<redacted>".into());
+                } else {
+                    redact(v);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Field-level diff of two object snapshots. Nested objects are walked;
+/// arrays compare as a whole.
+fn diff(before: Option<&Value>, after: Option<&Value>) -> Vec<FieldChange> {
+    let mut old = BTreeMap::new();
+    let mut new = BTreeMap::new();
+    if let Some(v) = before {
+        flatten("", v, &mut old);
+    }
+    if let Some(v) = after {
+        flatten("", v, &mut new);
+    }
+    let mut paths: Vec<&String> = old.keys().chain(new.keys()).collect();
+    paths.sort();
+    paths.dedup();
+    paths
+        .into_iter()
+        .filter(|p| old.get(*p) != new.get(*p))
+        .map(|p| FieldChange {
+            path: p.clone(),
+            before: old.get(p).map(|v| v.to_string()),
+            after: new.get(p).map(|v| v.to_string()),
+        })
+        .collect()
+}
+
+fn flatten(prefix: &str, v: &Value, out: &mut BTreeMap<String, Value>) {
+    match v {
+        Value::Object(map) if !map.is_empty() => {
+            for (k, v) in map {
+                let path = if prefix.is_empty() {
+                    k.clone()
+                } else {
+                    format!("{prefix}.{k}")
+                };
+                flatten(&path, v, out);
+            }
+        }
+        _ => {
+            out.insert(prefix.to_string(), v.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn target_from_paths() {
+        let t = target("/v1/projects/web/vms/vm-1/start").unwrap();
+        assert_eq!(t.kind, "vms");
+        assert_eq!(t.id.as_deref(), Some("vm-1"));
+        assert_eq!(t.project_slug.as_deref(), Some("web"));
+
+        let t = target("/v1/projects/web/templates/build").unwrap();
+        assert_eq!((t.kind.as_str(), t.id), ("templates", None));
+
+        // Sub-collections belong to their parent.
+        let t = target("/v1/security-groups/sg-1/rules/r-1").unwrap();
+        assert_eq!(
+            (t.kind.as_str(), t.id.as_deref()),
+            ("security-groups", Some("sg-1"))
+        );
+
+        let t = target("/v1/orgs/acme/projects").unwrap();
+        assert_eq!((t.kind.as_str(), t.id), ("projects", None));
+
+        let t = target("/v1/projects/web").unwrap();
+        assert_eq!(
+            (t.kind.as_str(), t.id.as_deref()),
+            ("projects", Some("web"))
+        );
+
+        assert!(target("/v1/auth/signin").is_none());
+    }
+
+    #[test]
+    fn redacts_secret_fields() {
+        let body = Bytes::from(
+            json!({
+                "name": "vm",
+                "userData": "#cloud-config\npassword: hunter2",
+                "nested": {"api_token": "x", "publicKey": "ssh-ed25519 AAAA"},
+            })
+            .to_string(),
+        );
+        let v: Value = serde_json::from_str(&sanitized_request(&body).unwrap()).unwrap();
+        assert_eq!(v["name"], "vm");
+        assert_eq!(v["userData"], "<redacted>");
+        assert_eq!(v["nested"]["api_token"], "<redacted>");
+        assert_eq!(v["nested"]["publicKey"], "ssh-ed25519 AAAA");
+        assert_eq!(sanitized_request(&Bytes::new()), None);
+    }
+
+    #[test]
+    fn diff_reports_changed_fields() {
+        let before = json!({"name": "a", "spec": {"vcpus": 1, "tags": ["x"]}, "gone": 1});
+        let after = json!({"name": "a", "spec": {"vcpus": 2, "tags": ["x", "y"]}, "new": true});
+        let changes = diff(Some(&before), Some(&after));
+        let paths: Vec<_> = changes.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, ["gone", "new", "spec.tags", "spec.vcpus"]);
+        assert_eq!(changes[3].before.as_deref(), Some("1"));
+        assert_eq!(changes[3].after.as_deref(), Some("2"));
+        assert_eq!(changes[0].after, None);
+
+        // Create and delete: every field on one side only.
+        assert!(diff(None, Some(&after)).iter().all(|c| c.before.is_none()));
+        assert!(diff(Some(&before), None).iter().all(|c| c.after.is_none()));
+    }
+}
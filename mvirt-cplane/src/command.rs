@@ -46,6 +46,12 @@ pub enum Command {
         dns_servers: Vec<String>,
         ntp_servers: Vec<String>,
         is_public: bool,
+        /// TFTP server for PXE boot
+        #[serde(default)]
+        boot_server: Option<String>,
+        /// TFTP file name or http(s):// URL
+        #[serde(default)]
+        boot_file: Option<String>,
     },
     UpdateNetwork {
         request_id: String,
@@ -129,10 +135,42 @@ pub enum Command {
         timestamp: String,
         status: VmStatus,
     },
+    /// Written by the provisioning controller; replaces the per-hook status list.
+    UpdateVmProvisioning {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        hooks: Vec<HookStatus>,
+    },
     DeleteVm {
         request_id: String,
         id: String,
     },
+    /// Start moving a stopped VM to another node. The copy of its boot
+    /// volume on the target is created in the same commit; the migration
+    /// reconciler drives the rest.
+    CreateMigration {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        vm_id: String,
+        target_node_id: String,
+        target_volume_id: String,
+    },
+    /// Switch a migrating VM over to its copied volume and the target node,
+    /// and reset its NIC so the NIC reconciler re-creates it there.
+    RebindMigratedVm {
+        request_id: String,
+        id: String,
+        timestamp: String,
+    },
+    UpdateMigrationStatus {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        phase: MigrationPhase,
+        error: Option<String>,
+    },
 
     // Org operations — Org is identified by its slug (no separate UUID).
     CreateOrg {
@@ -406,6 +444,15 @@ pub enum Command {
         size_bytes: u64,
         template_id: Option<String>,
     },
+    /// Copy a Ready volume to another node; the reconciler streams it over.
+    CopyVolume {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        source_id: String,
+        node_id: String,
+        name: String,
+    },
     DeleteVolume {
         request_id: String,
         id: String,
@@ -445,6 +492,17 @@ pub enum Command {
         source_url: Option<String>,
         total_bytes: u64,
     },
+    /// Template built by booting a throwaway VM from a clone of another
+    /// template; the template reconciler promotes its disk once it powers off.
+    BuildTemplate {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        project_slug: String,
+        node_id: String,
+        name: String,
+        build: TemplateBuild,
+    },
     UpdateTemplateStatus {
         request_id: String,
         id: String,
@@ -507,6 +565,111 @@ pub enum Command {
         rule_id: String,
         description: Option<Option<String>>,
     },
+
+    // Flavor operations — platform-wide, name is unique.
+    CreateFlavor {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        name: String,
+        description: Option<String>,
+        vcpus: u32,
+        memory_mb: u64,
+        disk_size_bytes: Option<u64>,
+        network: Option<String>,
+        user_data: Option<String>,
+    },
+    DeleteFlavor {
+        request_id: String,
+        id: String,
+    },
+
+    // SSH key commands
+    CreateSshKey {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        project_slug: String,
+        name: String,
+        public_key: String,
+        fingerprint: String,
+        project_default: bool,
+    },
+    DeleteSshKey {
+        request_id: String,
+        id: String,
+    },
+
+    // Trash — a soft-deleted VM, volume or network keeps its row, marked
+    // with `trashed`, until it is restored or purged with the matching
+    // Delete* command.
+    TrashResource {
+        request_id: String,
+        kind: TrashKind,
+        id: String,
+        timestamp: String,
+        purge_at: String,
+    },
+    RestoreResource {
+        request_id: String,
+        kind: TrashKind,
+        id: String,
+        timestamp: String,
+    },
+
+    // Change history
+    RecordChange {
+        request_id: String,
+        record: ChangeRecord,
+    },
+
+    // Webhooks — platform-wide subscriptions to lifecycle events.
+    CreateWebhook {
+        request_id: String,
+        id: String,
+        timestamp: String,
+        url: String,
+        events: Vec<String>,
+        secret: String,
+        description: Option<String>,
+    },
+    /// Also drops the webhook's delivery history.
+    DeleteWebhook {
+        request_id: String,
+        id: String,
+    },
+    /// Insert or replace a delivery; written once per attempt.
+    RecordWebhookDelivery {
+        request_id: String,
+        delivery: WebhookDelivery,
+    },
+
+    // Notifications — generated from events, read in the UI.
+    /// Also drops notifications past retention, measured from the new
+    /// notification's `created_at`.
+    CreateNotification {
+        request_id: String,
+        notification: NotificationData,
+    },
+    MarkNotificationRead {
+        request_id: String,
+        id: String,
+    },
+    MarkAllNotificationsRead {
+        request_id: String,
+    },
+
+    // Cluster
+    /// The leader is stepping down and names `target` as its successor.
+    /// Replicated like any other command so that the target only campaigns
+    /// once its log holds everything the old leader committed.
+    TransferLeadership {
+        request_id: String,
+        target: u64,
+        /// RFC 3339; a target applying the entry later (e.g. replaying the
+        /// log after a restart) ignores it.
+        deadline: String,
+    },
 }
 
 impl Command {
@@ -527,7 +690,11 @@ impl Command {
             Command::CreateVm { request_id, .. } => request_id,
             Command::UpdateVmSpec { request_id, .. } => request_id,
             Command::UpdateVmStatus { request_id, .. } => request_id,
+            Command::UpdateVmProvisioning { request_id, .. } => request_id,
             Command::DeleteVm { request_id, .. } => request_id,
+            Command::CreateMigration { request_id, .. } => request_id,
+            Command::RebindMigratedVm { request_id, .. } => request_id,
+            Command::UpdateMigrationStatus { request_id, .. } => request_id,
             Command::CreateOrg { request_id, .. } => request_id,
             Command::UpdateOrg { request_id, .. } => request_id,
             Command::DeleteOrg { request_id, .. } => request_id,
@@ -554,11 +721,13 @@ impl Command {
             Command::CreateStaticApiKey { request_id, .. } => request_id,
             Command::RevokeStaticApiKey { request_id, .. } => request_id,
             Command::CreateVolume { request_id, .. } => request_id,
+            Command::CopyVolume { request_id, .. } => request_id,
             Command::DeleteVolume { request_id, .. } => request_id,
             Command::UpdateVolumeStatus { request_id, .. } => request_id,
             Command::ResizeVolume { request_id, .. } => request_id,
             Command::CreateSnapshot { request_id, .. } => request_id,
             Command::CreateTemplate { request_id, .. } => request_id,
+            Command::BuildTemplate { request_id, .. } => request_id,
             Command::UpdateTemplateStatus { request_id, .. } => request_id,
             Command::CreateSecurityGroup { request_id, .. } => request_id,
             Command::UpdateSecurityGroup { request_id, .. } => request_id,
@@ -566,8 +735,39 @@ impl Command {
             Command::CreateSecurityGroupRule { request_id, .. } => request_id,
             Command::DeleteSecurityGroupRule { request_id, .. } => request_id,
             Command::UpdateSecurityGroupRule { request_id, .. } => request_id,
+            Command::CreateFlavor { request_id, .. } => request_id,
+            Command::DeleteFlavor { request_id, .. } => request_id,
+            Command::CreateSshKey { request_id, .. } => request_id,
+            Command::DeleteSshKey { request_id, .. } => request_id,
+            Command::TrashResource { request_id, .. } => request_id,
+            Command::RestoreResource { request_id, .. } => request_id,
+            Command::RecordChange { request_id, .. } => request_id,
+            Command::CreateWebhook { request_id, .. } => request_id,
+            Command::DeleteWebhook { request_id, .. } => request_id,
+            Command::RecordWebhookDelivery { request_id, .. } => request_id,
+            Command::CreateNotification { request_id, .. } => request_id,
+            Command::MarkNotificationRead { request_id, .. } => request_id,
+            Command::MarkAllNotificationsRead { request_id } => request_id,
+            Command::TransferLeadership { request_id, .. } => request_id,
         }
     }
+
+    /// The request ID ([`mvirt_common::request_id`]) the command was submitted
+    /// under, if any.
+    pub fn trace(&self) -> Option<&str> {
+        self.request_id().split_once('/').map(|(trace, _)| trace)
+    }
+}
+
+/// A fresh idempotency key for a command. Under a request ID the key starts
+/// with it, so the events the command causes can be traced back to the
+/// request.
+pub fn new_request_id() -> String {
+    let key = uuid::Uuid::new_v4().to_string();
+    match mvirt_common::request_id::current() {
+        Some(trace) => format!("{trace}/{key}"),
+        None => key,
+    }
 }
 
 // =============================================================================
@@ -643,6 +843,54 @@ pub struct NodeResources {
     pub available_cpu_cores: u32,
     pub available_memory_mb: u64,
     pub available_storage_gb: u64,
+    /// Live host details from the node's heartbeat; None for nodes that
+    /// don't report them (older agents, manual registration).
+    #[serde(default)]
+    pub host: Option<HostTelemetry>,
+}
+
+/// Host details sampled by mvirt-node
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct HostTelemetry {
+    pub cpu_model: String,
+    pub cpu_sockets: u32,
+    pub threads_per_core: u32,
+    pub load1: f64,
+    pub load5: f64,
+    pub load15: f64,
+    pub kernel_version: String,
+    pub kvm: bool,
+    pub hugepage_size_kb: u64,
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
+    pub zfs_pool: String,
+    /// zpool health as reported by `zpool list`, e.g. ONLINE or DEGRADED.
+    #[serde(default)]
+    pub zfs_pool_health: String,
+    /// Percent of the last minute some task stalled on CPU, memory or IO
+    #[serde(default)]
+    pub cpu_pressure: f64,
+    #[serde(default)]
+    pub memory_pressure: f64,
+    #[serde(default)]
+    pub io_pressure: f64,
+    /// Percent of CPU time stolen by the hypervisor below the host
+    #[serde(default)]
+    pub cpu_steal: f64,
+}
+
+impl HostTelemetry {
+    /// The worst of the stall and steal figures, in percent.
+    pub fn pressure(&self) -> f64 {
+        [
+            self.cpu_pressure,
+            self.memory_pressure,
+            self.io_pressure,
+            self.cpu_steal,
+        ]
+        .into_iter()
+        .fold(0.0, f64::max)
+    }
 }
 
 // =============================================================================
@@ -665,6 +913,15 @@ pub struct NetworkData {
     pub nic_count: u32,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(default)]
+    pub trashed: Option<Trashed>,
+    /// TFTP server IPv4 address announced to network-booting VMs (PXE)
+    #[serde(default)]
+    pub boot_server: Option<String>,
+    /// Boot file announced over DHCP: a TFTP file name, or an http(s)://
+    /// URL for UEFI HTTP boot. Fixed at creation.
+    #[serde(default)]
+    pub boot_file: Option<String>,
 }
 
 /// NIC data stored in the state machine
@@ -718,8 +975,13 @@ pub struct VmData {
     pub id: String,
     pub spec: VmSpec,
     pub status: VmStatus,
+    /// One entry per `spec.provisioning` hook, in the same order.
+    #[serde(default)]
+    pub provisioning: Vec<HookStatus>,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(default)]
+    pub trashed: Option<Trashed>,
 }
 
 /// VmSpec - desired state for a VM (user-defined)
@@ -740,6 +1002,112 @@ pub struct VmSpec {
     #[serde(default)]
     pub user_data: Option<String>,
     pub desired_state: VmDesiredState,
+    /// First-boot hooks, run in order once the VM first reaches Running.
+    #[serde(default)]
+    pub provisioning: Vec<ProvisioningHook>,
+    /// SSH keys injected via cloud-init, on top of the project defaults.
+    #[serde(default)]
+    pub ssh_key_ids: Vec<String>,
+    /// Leave the VM stopped once the guest powered itself off instead of
+    /// starting it again (template build VMs).
+    #[serde(default)]
+    pub run_once: bool,
+    /// Set when the boot volume was cloned from a template for this VM
+    /// alone. `volume_id` points at it and it is deleted with the VM.
+    #[serde(default)]
+    pub disk: Option<VmDisk>,
+    /// Placement group the VM belongs to, enforced by the scheduler.
+    #[serde(default)]
+    pub placement: Option<VmPlacement>,
+}
+
+/// Membership of a VM in a placement group. Groups are identified by name
+/// within the VM's project; all members share one policy.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VmPlacement {
+    pub group: String,
+    pub policy: PlacementPolicy,
+}
+
+/// How the scheduler places the members of a placement group
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum PlacementPolicy {
+    /// Every member on a different node (HA pairs); a VM that cannot get a
+    /// node of its own is not created.
+    Spread,
+    /// All members on one node (latency); best effort, a VM is placed
+    /// elsewhere if the group's node cannot take it.
+    Pack,
+}
+
+impl PlacementPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PlacementPolicy::Spread => "spread",
+            PlacementPolicy::Pack => "pack",
+        }
+    }
+}
+
+/// Declarative boot disk: a clone of a template, sized for the VM
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VmDisk {
+    pub template_id: String,
+    pub size_bytes: u64,
+}
+
+/// Cold migration of a stopped VM to another node. Kept in raft so a
+/// cplane restart or leader change picks the job up where it stopped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MigrationData {
+    pub id: String,
+    pub spec: MigrationSpec,
+    pub status: MigrationStatus,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+/// MigrationSpec — what moves where, fixed at creation.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MigrationSpec {
+    pub vm_id: String,
+    pub project_slug: String,
+    pub source_node_id: String,
+    pub target_node_id: String,
+    pub source_volume_id: String,
+    /// Copy of the boot volume on the target node
+    pub target_volume_id: String,
+}
+
+/// MigrationStatus — progress, written by the migration reconciler.
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct MigrationStatus {
+    pub phase: MigrationPhase,
+    pub error: Option<String>,
+}
+
+/// Migration lifecycle phase
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum MigrationPhase {
+    /// Boot volume is streaming to the target node
+    #[default]
+    CopyingVolume,
+    /// VM is bound to the target; its VM, NIC and volume are being
+    /// removed from the source node
+    CleaningUp,
+    Completed,
+    /// Copy failed; the VM stays on the source node
+    Failed,
+}
+
+impl MigrationPhase {
+    /// Whether the migration still holds its VM.
+    pub fn is_active(&self) -> bool {
+        matches!(
+            self,
+            MigrationPhase::CopyingVolume | MigrationPhase::CleaningUp
+        )
+    }
 }
 
 /// Desired power state for a VM
@@ -779,6 +1147,83 @@ pub enum VmPhase {
     Failed,
 }
 
+/// A post-start provisioning step: wait until every condition holds, then
+/// run the action (if any). Hooks run once, on first boot only.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ProvisioningHook {
+    pub name: String,
+    pub wait: Vec<WaitCondition>,
+    pub action: Option<HookAction>,
+    /// Upper bound for the wait phase, counted from when the hook started.
+    pub timeout_secs: u32,
+}
+
+/// Condition a provisioning hook waits for before running its action.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WaitCondition {
+    /// The VM's guest agent (`mvirt-one --agent`) has signalled ready.
+    GuestReady,
+    /// A TCP connect to the VM's IPv4 address on `port` succeeds.
+    TcpPort { port: u16 },
+}
+
+/// Action executed by a provisioning hook.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub enum HookAction {
+    /// POST a JSON description of the VM to `url`; any 2xx counts as success.
+    Webhook { url: String },
+    /// Run `command` in the guest through its agent; exit status 0 counts
+    /// as success. Killed after `timeout_secs` (0 = the agent's 30s).
+    GuestCommand {
+        command: Vec<String>,
+        timeout_secs: u32,
+    },
+}
+
+/// Observed state of one provisioning hook.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HookStatus {
+    pub name: String,
+    pub phase: HookPhase,
+    pub started_at: Option<String>,
+    pub finished_at: Option<String>,
+    pub message: Option<String>,
+}
+
+impl HookStatus {
+    pub fn pending(hook: &ProvisioningHook) -> Self {
+        Self {
+            name: hook.name.clone(),
+            phase: HookPhase::Pending,
+            started_at: None,
+            finished_at: None,
+            message: None,
+        }
+    }
+}
+
+/// Provisioning hook lifecycle phase
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+pub enum HookPhase {
+    /// Not started; earlier hooks are still running
+    #[default]
+    Pending,
+    /// Waiting for the hook's conditions
+    Waiting,
+    /// Action completed
+    Succeeded,
+    /// Wait timed out or the action failed
+    Failed,
+    /// Not run because an earlier hook failed
+    Skipped,
+}
+
+impl HookPhase {
+    pub fn is_terminal(self) -> bool {
+        matches!(self, Self::Succeeded | Self::Failed | Self::Skipped)
+    }
+}
+
 // =============================================================================
 // Common Types
 // =============================================================================
@@ -801,6 +1246,34 @@ pub enum ResourcePhase {
     Failed,
 }
 
+/// Resource types that can be soft-deleted.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum TrashKind {
+    Vm,
+    Volume,
+    Network,
+}
+
+impl TrashKind {
+    /// Name for messages, e.g. "VM 'x' not found".
+    pub fn label(&self) -> &'static str {
+        match self {
+            TrashKind::Vm => "VM",
+            TrashKind::Volume => "Volume",
+            TrashKind::Network => "Network",
+        }
+    }
+}
+
+/// Marks a soft-deleted resource. It keeps its row (and its name) but is
+/// deactivated and left out of listings until restored, or purged once
+/// `purge_at` has passed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Trashed {
+    pub deleted_at: String,
+    pub purge_at: String,
+}
+
 // =============================================================================
 // Project Types
 // =============================================================================
@@ -1034,6 +1507,8 @@ pub struct VolumeData {
     pub status: VolumeStatus,
     pub created_at: String,
     pub updated_at: String,
+    #[serde(default)]
+    pub trashed: Option<Trashed>,
 }
 
 /// VolumeSpec — desired state, written by REST via Create/Update*Spec commands.
@@ -1044,6 +1519,9 @@ pub struct VolumeSpec {
     pub name: String,
     pub size_bytes: u64,
     pub template_id: Option<String>,
+    /// Volume this one is a copy of, streamed from its node.
+    #[serde(default)]
+    pub source_volume_id: Option<String>,
 }
 
 /// VolumeStatus — observed state, written exclusively by the reconciler via UpdateVolumeStatus.
@@ -1092,6 +1570,21 @@ pub struct TemplateSpec {
     pub node_id: String, // Node where the template is stored
     pub name: String,
     pub source_url: Option<String>,
+    /// Set for templates built from a cloud-init run instead of imported.
+    #[serde(default)]
+    pub build: Option<TemplateBuild>,
+}
+
+/// Resources of a template build. They all live on the template's node and
+/// are deleted once the build finished, successfully or not.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TemplateBuild {
+    pub base_template_id: String,
+    pub volume_id: String,
+    pub vm_id: String,
+    pub nic_id: String,
+    /// Give up if the VM has not powered off after this many seconds.
+    pub timeout_secs: u64,
 }
 
 /// TemplateStatus — observed state (import progress + clone count + final size).
@@ -1111,6 +1604,8 @@ pub enum TemplatePhase {
     #[default]
     Pending,
     Importing,
+    /// Build VM is running its user-data
+    Building,
     Ready,
     Failed,
 }
@@ -1152,6 +1647,164 @@ pub enum RuleDirection {
     Outbound,
 }
 
+// =============================================================================
+// Flavor Types
+// =============================================================================
+
+/// Flavor — a named VM profile (size + defaults) that VM creation can
+/// reference instead of spelling out every resource. Platform-wide so
+/// operators can publish standard sizes across projects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlavorData {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub vcpus: u32,
+    pub memory_mb: u64,
+    /// Minimum boot volume size. VMs whose volume is smaller are rejected.
+    pub disk_size_bytes: Option<u64>,
+    /// Network name (resolved inside the VM's project) a NIC is created in
+    /// when the VM request doesn't reference one.
+    pub network: Option<String>,
+    /// Cloud-init user-data used when the VM request carries none.
+    pub user_data: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+// =============================================================================
+// SSH Key Types
+// =============================================================================
+
+/// SshKey — a public key owned by a project. Keys are rendered into the
+/// cloud-init user-data of VMs that reference them, and of every VM in the
+/// project when `project_default` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshKeyData {
+    pub id: String,
+    pub project_slug: String,
+    pub name: String,
+    /// OpenSSH `authorized_keys` line: `<type> <base64> [comment]`.
+    pub public_key: String,
+    /// `SHA256:<base64>` as printed by `ssh-keygen -l`.
+    pub fingerprint: String,
+    pub project_default: bool,
+    pub created_at: String,
+}
+
+// =============================================================================
+// Change History Types
+// =============================================================================
+
+/// One mutating API call against a resource: who did it, the sanitized
+/// request, and the fields of the stored object it changed. `id` doubles
+/// as the correlation ID of the matching mvirt-log audit entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    pub id: String,
+    pub timestamp: String,
+    /// Route segment naming the resource type, e.g. `vms`, `orgs`.
+    pub resource_kind: String,
+    pub resource_id: String,
+    pub project_slug: Option<String>,
+    /// Account ID of the caller; `None` with auth disabled.
+    pub actor: Option<String>,
+    pub actor_email: Option<String>,
+    pub method: String,
+    pub path: String,
+    /// Request body as JSON with secret-looking fields redacted.
+    pub request: Option<String>,
+    pub changes: Vec<FieldChange>,
+}
+
+/// A changed field, addressed by a dotted path into the stored object.
+/// Values are JSON-encoded; `None` means absent on that side.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldChange {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+// =============================================================================
+// Webhook Types
+// =============================================================================
+
+/// A URL notified of lifecycle events (`vm.started`, `node.notready`, ...)
+/// whose type matches one of `events`. Deliveries are signed with
+/// HMAC-SHA256 over the body using `secret`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookData {
+    pub id: String,
+    pub url: String,
+    /// Event types, `<kind>.*` or `*`.
+    pub events: Vec<String>,
+    pub secret: String,
+    pub description: Option<String>,
+    pub created_at: String,
+}
+
+/// One event sent to one webhook, across all of its attempts.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub webhook_id: String,
+    pub event_type: String,
+    pub resource_id: String,
+    pub status: WebhookDeliveryStatus,
+    pub attempts: u32,
+    /// HTTP status of the last attempt, if the endpoint answered.
+    pub last_status_code: Option<u16>,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum WebhookDeliveryStatus {
+    /// Not yet acknowledged; a retry is scheduled.
+    Pending,
+    Succeeded,
+    /// Out of attempts.
+    Failed,
+}
+
+// =============================================================================
+// Notification Types
+// =============================================================================
+
+/// Something an operator should look at, raised when a node goes down, an
+/// import fails, a storage pool degrades and the like. Read state is shared
+/// by all users.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationData {
+    pub id: String,
+    pub severity: NotificationSeverity,
+    pub title: String,
+    pub message: String,
+    pub involved_object: InvolvedObject,
+    pub read: bool,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub enum NotificationSeverity {
+    Info,
+    Success,
+    Warning,
+    Error,
+}
+
+/// The resource a notification is about.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InvolvedObject {
+    /// Resource type as in events: `node`, `template`, `vm`, ...
+    pub kind: String,
+    pub id: String,
+    pub name: String,
+    pub project_slug: Option<String>,
+}
+
 // =============================================================================
 // Response Types
 // =============================================================================
@@ -1163,6 +1816,7 @@ pub enum Response {
     Network(NetworkData),
     Nic(NicData),
     Vm(VmData),
+    Migration(MigrationData),
     Org(OrgData),
     Project(ProjectData),
     Cluster(ClusterData),
@@ -1184,6 +1838,10 @@ pub enum Response {
     Volume(VolumeData),
     Template(TemplateData),
     SecurityGroup(SecurityGroupData),
+    Flavor(FlavorData),
+    SshKey(SshKeyData),
+    Webhook(WebhookData),
+    Notification(NotificationData),
     Deleted {
         id: String,
     },
@@ -0,0 +1,582 @@
+//! Cross-daemon garbage collector.
+//!
+//! Deleting a VM, NIC or volume removes it from raft state, but the
+//! reconcilers don't yet tear down what they created on the node (see the
+//! finalizer notes in `reconciler/`). The GC closes that gap: it lists what
+//! each connected node's vmm, net and zfs daemons hold, correlates it with
+//! cluster state by id, and reports or deletes what nothing owns.
+//!
+//! Only resources the cplane created are considered: VMs carrying the
+//! [`MANAGED_BY_LABEL`] label, NICs and volumes whose owner reference is of
+//! kind [`CPLANE_OWNER_KIND`]. Pods and their MicroVMs are node-local, and
+//! whatever was made with the CLI belongs to the operator; neither is ever
+//! touched. Resources created before the marker existed aren't either.
+//!
+//! A marked resource is an orphan when its id is unknown to cluster state,
+//! or state places it on a different node. Orphans are only deleted once they
+//! have stayed orphaned for the grace period, so a resource caught between
+//! a raft write and its reconcile is never touched. Within a pass VMs go
+//! first, then NICs, then volumes, so disks and NICs freed by a VM are
+//! collected together with it.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use mvirt_daemon_protos::net::{DeleteNicRequest, ListNicsRequest, Nic};
+use mvirt_daemon_protos::vmm::{
+    DeletePropagation, DeleteVmRequest, KillVmRequest, ListPodsRequest, ListVmsRequest, Vm, VmState,
+};
+use mvirt_daemon_protos::zfs::{DeleteVolumeRequest, ListVolumesRequest, Volume};
+use tokio::sync::Mutex;
+use tracing::{debug, info, warn};
+
+use crate::reconciler::Ctx;
+use crate::state::ApiState;
+use crate::tunnel::NodeHandle;
+
+/// Label the cplane puts on the VMs it creates.
+pub const MANAGED_BY_LABEL: &str = "mvirt.io/managed-by";
+/// Value of [`MANAGED_BY_LABEL`].
+pub const MANAGED_BY_CPLANE: &str = "cplane";
+/// Owner reference kind of the NICs and volumes the cplane creates; the id
+/// is the resource's own.
+pub const CPLANE_OWNER_KIND: &str = "cplane";
+
+/// What the GC does with orphans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum GcMode {
+    /// Don't run.
+    Off,
+    /// Report orphans only.
+    DryRun,
+    /// Report and delete orphans past the grace period.
+    Delete,
+}
+
+impl GcMode {
+    /// Whether an orphan that has been orphaned for `age` gets deleted.
+    fn collects(self, age: Duration, grace: Duration) -> bool {
+        self == GcMode::Delete && age >= grace
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrphanKind {
+    Vm,
+    Nic,
+    Volume,
+}
+
+impl fmt::Display for OrphanKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            OrphanKind::Vm => "vm",
+            OrphanKind::Nic => "nic",
+            OrphanKind::Volume => "volume",
+        })
+    }
+}
+
+/// A node resource no cluster object owns.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Orphan {
+    pub node_id: String,
+    pub kind: OrphanKind,
+    pub id: String,
+    /// Daemon-side name; zfs deletes volumes by name.
+    pub name: String,
+    pub running: bool,
+}
+
+impl Orphan {
+    fn key(&self) -> (String, OrphanKind, String) {
+        (self.node_id.clone(), self.kind, self.id.clone())
+    }
+}
+
+pub struct GarbageCollector {
+    ctx: Ctx,
+    mode: GcMode,
+    interval: Duration,
+    grace: Duration,
+    /// When each current orphan was first seen.
+    first_seen: Mutex<HashMap<(String, OrphanKind, String), Instant>>,
+}
+
+impl GarbageCollector {
+    pub fn new(ctx: Ctx, mode: GcMode, interval: Duration, grace: Duration) -> Self {
+        Self {
+            ctx,
+            mode,
+            interval,
+            grace,
+            first_seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Spawn the periodic GC loop. Returns immediately; does nothing in
+    /// [`GcMode::Off`].
+    pub fn spawn(self) {
+        if self.mode == GcMode::Off {
+            info!("garbage collector disabled");
+            return;
+        }
+        info!(
+            mode = ?self.mode,
+            interval = ?self.interval,
+            grace = ?self.grace,
+            "starting garbage collector"
+        );
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(self.interval);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // Let nodes connect and the reconcilers catch up first
+            tick.tick().await;
+            loop {
+                tick.tick().await;
+                self.run_once().await;
+            }
+        });
+    }
+
+    /// One pass over every connected node. Returns the orphans found.
+    pub async fn run_once(&self) -> Vec<Orphan> {
+        let known = Known::from_state(&self.ctx.store.snapshot().await);
+        let mut orphans = Vec::new();
+        let mut scanned = HashSet::new();
+        for node in self.ctx.registry.list().await {
+            match find_orphans(&known, &node).await {
+                Ok(found) => {
+                    scanned.insert(node.node_id.clone());
+                    orphans.extend(found);
+                }
+                Err(e) => {
+                    warn!(node = %node.node_id, error = %e, "gc: listing node resources failed")
+                }
+            }
+        }
+
+        let mut first_seen = self.first_seen.lock().await;
+        let ages = track_orphans(&mut first_seen, &orphans, &scanned, Instant::now());
+        for (orphan, age) in orphans.iter().zip(ages) {
+            if !self.mode.collects(age, self.grace) {
+                warn!(
+                    node = %orphan.node_id,
+                    kind = %orphan.kind,
+                    id = %orphan.id,
+                    name = %orphan.name,
+                    running = orphan.running,
+                    orphaned_for = ?age,
+                    "gc: orphaned resource"
+                );
+                continue;
+            }
+            let Some(node) = self.ctx.registry.get(&orphan.node_id).await else {
+                continue;
+            };
+            match delete_orphan(&node, orphan).await {
+                Ok(()) => {
+                    info!(node = %orphan.node_id, kind = %orphan.kind, id = %orphan.id, "gc: deleted orphan");
+                    self.ctx.audit.orphan_collected(
+                        &orphan.node_id,
+                        &orphan.kind.to_string(),
+                        &orphan.id,
+                    );
+                    first_seen.remove(&orphan.key());
+                }
+                Err(e) => {
+                    warn!(node = %orphan.node_id, kind = %orphan.kind, id = %orphan.id, error = %e, "gc: delete failed")
+                }
+            }
+        }
+
+        debug!(
+            nodes = scanned.len(),
+            orphans = orphans.len(),
+            "gc pass done"
+        );
+        orphans
+    }
+}
+
+/// How long each of `orphans` has been orphaned as of `now`, in order.
+/// Orphans seen for the first time start their clock; orphans that are
+/// gone or owned again are forgotten, but the clock keeps running for those
+/// on nodes that couldn't be listed this time (not in `scanned`).
+fn track_orphans(
+    first_seen: &mut HashMap<(String, OrphanKind, String), Instant>,
+    orphans: &[Orphan],
+    scanned: &HashSet<String>,
+    now: Instant,
+) -> Vec<Duration> {
+    let current: HashSet<_> = orphans.iter().map(Orphan::key).collect();
+    first_seen.retain(|key, _| current.contains(key) || !scanned.contains(&key.0));
+    orphans
+        .iter()
+        .map(|orphan| now.duration_since(*first_seen.entry(orphan.key()).or_insert(now)))
+        .collect()
+}
+
+/// Where cluster state places its VMs, NICs and volumes.
+#[derive(Debug, Default)]
+struct Known {
+    /// VM id to its node, if scheduled
+    vms: HashMap<String, Option<String>>,
+    nics: HashSet<String>,
+    /// Volume id to its node
+    volumes: HashMap<String, String>,
+}
+
+impl Known {
+    fn from_state(state: &ApiState) -> Self {
+        Self {
+            vms: state
+                .list_vms(None)
+                .into_iter()
+                .map(|vm| (vm.id, vm.status.node_id))
+                .collect(),
+            nics: state
+                .list_nics(None)
+                .into_iter()
+                .map(|nic| nic.id)
+                .collect(),
+            volumes: state
+                .list_volumes(None, None)
+                .into_iter()
+                .map(|vol| (vol.id, vol.spec.node_id))
+                .collect(),
+        }
+    }
+}
+
+/// List one node's VMs, pods, NICs and volumes and return the orphans
+/// among them, in deletion order.
+async fn find_orphans(known: &Known, node: &NodeHandle) -> Result<Vec<Orphan>, String> {
+    let vms = node
+        .vmm
+        .clone()
+        .list_vms(ListVmsRequest {})
+        .await
+        .map_err(|s| format!("list_vms: {}", s.message()))?
+        .into_inner()
+        .vms;
+    let pod_vms: HashSet<String> = node
+        .pods
+        .clone()
+        .list_pods(ListPodsRequest {})
+        .await
+        .map_err(|s| format!("list_pods: {}", s.message()))?
+        .into_inner()
+        .pods
+        .into_iter()
+        .map(|pod| pod.vm_id)
+        .collect();
+    let nics = node
+        .net
+        .clone()
+        .list_nics(ListNicsRequest {
+            network_id: String::new(),
+        })
+        .await
+        .map_err(|s| format!("list_nics: {}", s.message()))?
+        .into_inner()
+        .nics;
+    let volumes = node
+        .zfs
+        .clone()
+        .list_volumes(ListVolumesRequest {})
+        .await
+        .map_err(|s| format!("list_volumes: {}", s.message()))?
+        .into_inner()
+        .volumes;
+    Ok(classify(
+        known,
+        &node.node_id,
+        &vms,
+        &pod_vms,
+        &nics,
+        &volumes,
+    ))
+}
+
+/// The cplane-made resources of node `node_id` that no cluster object on
+/// that node owns, VMs first, then NICs, then volumes.
+fn classify(
+    known: &Known,
+    node_id: &str,
+    vms: &[Vm],
+    pod_vms: &HashSet<String>,
+    nics: &[Nic],
+    volumes: &[Volume],
+) -> Vec<Orphan> {
+    let mut orphans = Vec::new();
+
+    for vm in vms {
+        let managed = vm
+            .config
+            .as_ref()
+            .and_then(|c| c.labels.get(MANAGED_BY_LABEL))
+            .is_some_and(|v| v == MANAGED_BY_CPLANE);
+        if !managed || pod_vms.contains(&vm.id) {
+            continue;
+        }
+        let owned = known
+            .vms
+            .get(&vm.id)
+            .is_some_and(|n| n.as_deref().is_none_or(|n| n == node_id));
+        if !owned {
+            orphans.push(Orphan {
+                node_id: node_id.to_string(),
+                kind: OrphanKind::Vm,
+                id: vm.id.clone(),
+                name: vm.name.clone().unwrap_or_default(),
+                running: vm.state == VmState::Running as i32,
+            });
+        }
+    }
+
+    // NICs carry no node in cluster state yet; known id means owned.
+    for nic in nics {
+        let managed = nic
+            .owner
+            .as_ref()
+            .is_some_and(|o| o.kind == CPLANE_OWNER_KIND && o.id == nic.id);
+        if managed && !known.nics.contains(&nic.id) {
+            orphans.push(Orphan {
+                node_id: node_id.to_string(),
+                kind: OrphanKind::Nic,
+                id: nic.id.clone(),
+                name: nic.name.clone(),
+                running: false,
+            });
+        }
+    }
+
+    for volume in volumes {
+        let managed = volume
+            .owner
+            .as_ref()
+            .is_some_and(|o| o.kind == CPLANE_OWNER_KIND && o.id == volume.id);
+        if !managed {
+            continue;
+        }
+        if known.volumes.get(&volume.id).is_none_or(|n| n != node_id) {
+            orphans.push(Orphan {
+                node_id: node_id.to_string(),
+                kind: OrphanKind::Volume,
+                id: volume.id.clone(),
+                name: volume.name.clone(),
+                running: false,
+            });
+        }
+    }
+
+    orphans
+}
+
+async fn delete_orphan(node: &NodeHandle, orphan: &Orphan) -> Result<(), String> {
+    match orphan.kind {
+        OrphanKind::Vm => {
+            let mut vmm = node.vmm.clone();
+            if orphan.running {
+                vmm.kill_vm(KillVmRequest {
+                    id: orphan.id.clone(),
+                })
+                .await
+                .map_err(|s| format!("kill_vm: {}", s.message()))?;
+            }
+            vmm.delete_vm(DeleteVmRequest {
+                id: orphan.id.clone(),
+                propagation: DeletePropagation::Cascade as i32,
+                force: true,
+            })
+            .await
+            .map_err(|s| format!("delete_vm: {}", s.message()))?;
+        }
+        OrphanKind::Nic => {
+            node.net
+                .clone()
+                .delete_nic(DeleteNicRequest {
+                    id: orphan.id.clone(),
+                })
+                .await
+                .map_err(|s| format!("delete_nic: {}", s.message()))?;
+        }
+        OrphanKind::Volume => {
+            node.zfs
+                .clone()
+                .delete_volume(DeleteVolumeRequest {
+                    name: orphan.name.clone(),
+                })
+                .await
+                .map_err(|s| format!("delete_volume: {}", s.message()))?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mvirt_daemon_protos::net::OwnerReference as NicOwner;
+    use mvirt_daemon_protos::vmm::VmConfig;
+    use mvirt_daemon_protos::zfs::OwnerReference as VolumeOwner;
+
+    fn vm(id: &str, managed: bool) -> Vm {
+        let mut config = VmConfig::default();
+        if managed {
+            config
+                .labels
+                .insert(MANAGED_BY_LABEL.to_string(), MANAGED_BY_CPLANE.to_string());
+        }
+        Vm {
+            id: id.to_string(),
+            state: VmState::Running as i32,
+            config: Some(config),
+            ..Default::default()
+        }
+    }
+
+    fn nic(id: &str, owner_kind: Option<&str>) -> Nic {
+        Nic {
+            id: id.to_string(),
+            owner: owner_kind.map(|kind| NicOwner {
+                kind: kind.to_string(),
+                id: id.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn volume(id: &str, owner_kind: Option<&str>) -> Volume {
+        Volume {
+            id: id.to_string(),
+            name: format!("vol-{id}"),
+            owner: owner_kind.map(|kind| VolumeOwner {
+                kind: kind.to_string(),
+                id: id.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn ids(orphans: &[Orphan]) -> Vec<(OrphanKind, &str)> {
+        orphans.iter().map(|o| (o.kind, o.id.as_str())).collect()
+    }
+
+    #[test]
+    fn test_classify_only_collects_cplane_resources() {
+        let known = Known {
+            vms: HashMap::from([
+                ("vm-owned".to_string(), Some("node-1".to_string())),
+                ("vm-pending".to_string(), None),
+                ("vm-moved".to_string(), Some("node-2".to_string())),
+            ]),
+            nics: HashSet::from(["nic-owned".to_string()]),
+            volumes: HashMap::from([
+                ("vol-owned".to_string(), "node-1".to_string()),
+                ("vol-moved".to_string(), "node-2".to_string()),
+            ]),
+        };
+        let vms = [
+            vm("vm-owned", true),
+            vm("vm-pending", true),
+            vm("vm-moved", true),
+            vm("vm-deleted", true),
+            // Made with the CLI
+            vm("vm-cli", false),
+            // A pod's MicroVM, even if it somehow carries the label
+            vm("vm-pod", true),
+        ];
+        let pod_vms = HashSet::from(["vm-pod".to_string()]);
+        let nics = [
+            nic("nic-owned", Some(CPLANE_OWNER_KIND)),
+            nic("nic-deleted", Some(CPLANE_OWNER_KIND)),
+            nic("nic-pod", Some("pod")),
+            nic("nic-cli", None),
+        ];
+        let volumes = [
+            volume("vol-owned", Some(CPLANE_OWNER_KIND)),
+            volume("vol-moved", Some(CPLANE_OWNER_KIND)),
+            volume("vol-deleted", Some(CPLANE_OWNER_KIND)),
+            volume("vol-pod", Some("pod")),
+            volume("vol-cli", None),
+        ];
+
+        let orphans = classify(&known, "node-1", &vms, &pod_vms, &nics, &volumes);
+        assert_eq!(
+            ids(&orphans),
+            [
+                (OrphanKind::Vm, "vm-moved"),
+                (OrphanKind::Vm, "vm-deleted"),
+                (OrphanKind::Nic, "nic-deleted"),
+                (OrphanKind::Volume, "vol-moved"),
+                (OrphanKind::Volume, "vol-deleted"),
+            ]
+        );
+        assert!(orphans[0].running);
+        assert_eq!(orphans[3].name, "vol-vol-moved");
+    }
+
+    #[test]
+    fn test_classify_ignores_owner_ref_of_another_object() {
+        let mut foreign = nic("nic-1", Some(CPLANE_OWNER_KIND));
+        foreign.owner.as_mut().unwrap().id = "nic-2".to_string();
+        let orphans = classify(
+            &Known::default(),
+            "node-1",
+            &[],
+            &HashSet::new(),
+            &[foreign],
+            &[],
+        );
+        assert!(orphans.is_empty());
+    }
+
+    #[test]
+    fn test_grace_period() {
+        let grace = Duration::from_secs(600);
+        assert!(!GcMode::Delete.collects(Duration::from_secs(599), grace));
+        assert!(GcMode::Delete.collects(grace, grace));
+        assert!(!GcMode::DryRun.collects(Duration::from_secs(3600), grace));
+        assert!(!GcMode::Off.collects(Duration::from_secs(3600), grace));
+    }
+
+    fn orphan(node_id: &str, id: &str) -> Orphan {
+        Orphan {
+            node_id: node_id.to_string(),
+            kind: OrphanKind::Vm,
+            id: id.to_string(),
+            name: String::new(),
+            running: false,
+        }
+    }
+
+    #[test]
+    fn test_track_orphans_ages() {
+        let mut first_seen = HashMap::new();
+        let t0 = Instant::now();
+        let scanned = HashSet::from(["node-1".to_string(), "node-2".to_string()]);
+        let a = orphan("node-1", "a");
+        let b = orphan("node-2", "b");
+
+        let ages = track_orphans(&mut first_seen, &[a.clone(), b.clone()], &scanned, t0);
+        assert_eq!(ages, [Duration::ZERO, Duration::ZERO]);
+
+        // Still orphaned a minute later: the clock keeps running
+        let t1 = t0 + Duration::from_secs(60);
+        let ages = track_orphans(&mut first_seen, &[a.clone(), b.clone()], &scanned, t1);
+        assert_eq!(ages, [Duration::from_secs(60), Duration::from_secs(60)]);
+
+        // node-2 couldn't be listed: b keeps its clock; a was adopted and
+        // starts over when it's orphaned again
+        let t2 = t0 + Duration::from_secs(120);
+        let only_node_1 = HashSet::from(["node-1".to_string()]);
+        assert!(track_orphans(&mut first_seen, &[], &only_node_1, t2).is_empty());
+        let t3 = t0 + Duration::from_secs(180);
+        let ages = track_orphans(&mut first_seen, &[a, b], &scanned, t3);
+        assert_eq!(ages, [Duration::ZERO, Duration::from_secs(180)]);
+    }
+}
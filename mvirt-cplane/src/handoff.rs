@@ -0,0 +1,171 @@
+//! Leadership handoff for rolling restarts.
+//!
+//! A leader that simply exits leaves the cluster leaderless until a
+//! follower's election timeout fires. On shutdown the leader instead names
+//! its most caught-up voter as successor through a replicated
+//! [`Command::TransferLeadership`], waits for that voter to hold the entry
+//! and then stops. The successor campaigns as soon as it has applied the
+//! entry, ahead of the randomized timeouts of the other followers, and
+//! wins because no log is longer than its own. Every peer runs
+//! [`spawn_watcher`] for the successor side.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use mraft::{NodeId, RaftNode};
+use tokio::sync::RwLock;
+use tracing::{debug, info, warn};
+
+use crate::command::{Command, Response, new_request_id};
+use crate::state::ApiState;
+use crate::store::{ControlplaneStore, RaftStore};
+
+/// How long the successor keeps campaigning before giving up.
+const HANDOFF_TTL: Duration = Duration::from_secs(5);
+/// How long the leader waits for the successor to replicate its log.
+const CATCH_UP_TIMEOUT: Duration = Duration::from_secs(2);
+/// One more heartbeat after catch-up carries the commit index of the
+/// TransferLeadership entry to the successor.
+const COMMIT_GRACE: Duration = Duration::from_millis(100);
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+const CAMPAIGN_INTERVAL: Duration = Duration::from_millis(50);
+
+type Node = Arc<RwLock<RaftNode<Command, Response, ApiState>>>;
+
+/// Hand leadership to a follower if this peer is the leader. Call after
+/// the REST server drained and before shutting the raft node down.
+/// Returns the successor, or `None` if there was nothing to hand off.
+pub async fn step_down(node: &Node, store: &RaftStore, node_id: NodeId) -> Option<NodeId> {
+    if node.read().await.metrics().current_leader != Some(node_id) {
+        return None;
+    }
+    let voters = match store.get_membership().await {
+        Ok(m) => m.voters,
+        Err(e) => {
+            warn!(error = %e, "cannot read membership; skipping leadership handoff");
+            return None;
+        }
+    };
+    let target = pick_successor(node_id, &voters, &matched_indexes(node).await)?;
+
+    let deadline = Utc::now() + chrono::Duration::from_std(HANDOFF_TTL).unwrap_or_default();
+    let cmd = Command::TransferLeadership {
+        request_id: new_request_id(),
+        target,
+        deadline: deadline.to_rfc3339(),
+    };
+    if let Err(e) = store.submit(cmd).await {
+        warn!(error = %e, "leadership handoff not committed");
+        return None;
+    }
+
+    // The entry is committed, i.e. on a majority, but not necessarily on
+    // the successor yet.
+    let caught_up = tokio::time::timeout(CATCH_UP_TIMEOUT, async {
+        loop {
+            let last = node.read().await.metrics().last_log_index;
+            let matched = matched_indexes(node).await.get(&target).copied().flatten();
+            if matched.is_some() && matched >= last {
+                return;
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    })
+    .await;
+    if caught_up.is_err() {
+        warn!(
+            target,
+            "successor did not catch up; leaving it to an election"
+        );
+        return None;
+    }
+    tokio::time::sleep(COMMIT_GRACE).await;
+    Some(target)
+}
+
+/// Campaign when an applied TransferLeadership names this peer.
+pub fn spawn_watcher(node: Node, state: &ApiState, node_id: NodeId) {
+    let mut requests = state.handoff_requests();
+    tokio::spawn(async move {
+        while requests.changed().await.is_ok() {
+            let Some(req) = requests.borrow_and_update().clone() else {
+                continue;
+            };
+            if req.target != node_id {
+                continue;
+            }
+            match DateTime::parse_from_rfc3339(&req.deadline) {
+                Ok(deadline) => campaign(&node, node_id, deadline.with_timezone(&Utc)).await,
+                Err(e) => warn!(deadline = %req.deadline, error = %e, "bad handoff deadline"),
+            }
+        }
+    });
+}
+
+async fn campaign(node: &Node, node_id: NodeId, deadline: DateTime<Utc>) {
+    if Utc::now() >= deadline {
+        debug!("ignoring expired leadership handoff");
+        return;
+    }
+    info!("Leadership handed to this peer; campaigning");
+    // Peers still inside the old leader's lease turn the first votes down,
+    // so keep asking until one round wins.
+    while Utc::now() < deadline {
+        {
+            let node = node.read().await;
+            if node.metrics().current_leader == Some(node_id) {
+                info!("Took over leadership");
+                return;
+            }
+            if let Err(e) = node.raft().trigger().elect().await {
+                debug!(error = %e, "election trigger failed");
+            }
+        }
+        tokio::time::sleep(CAMPAIGN_INTERVAL).await;
+    }
+    warn!("Handoff deadline passed without winning the election");
+}
+
+/// Highest log index each follower holds, as the leader sees it.
+async fn matched_indexes(node: &Node) -> BTreeMap<NodeId, Option<u64>> {
+    let node = node.read().await;
+    node.metrics()
+        .replication
+        .as_ref()
+        .map(|r| {
+            r.iter()
+                .map(|(id, log)| (*id, log.as_ref().map(|l| l.index)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The voter other than `me` with the longest replicated log.
+fn pick_successor(
+    me: NodeId,
+    voters: &[NodeId],
+    matched: &BTreeMap<NodeId, Option<u64>>,
+) -> Option<NodeId> {
+    voters
+        .iter()
+        .filter(|id| **id != me)
+        .filter_map(|id| Some((matched.get(id).copied().flatten()?, *id)))
+        .max()
+        .map(|(_, id)| id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn successor_is_most_caught_up_voter() {
+        let matched = BTreeMap::from([(2, Some(40)), (3, Some(42)), (4, Some(50)), (5, None)]);
+        // 4 is a learner, 5 has nothing replicated yet.
+        assert_eq!(pick_successor(1, &[1, 2, 3, 5], &matched), Some(3));
+        assert_eq!(pick_successor(1, &[1, 5], &matched), None);
+        assert_eq!(pick_successor(1, &[1], &matched), None);
+    }
+}
@@ -1,14 +1,21 @@
 pub mod audit;
 pub mod auth;
 pub mod ca;
+pub mod changes;
 pub mod command;
+pub mod gc;
 pub mod grpc;
+pub mod handoff;
+pub mod notifications;
 pub mod reconciler;
 pub mod rest;
 pub mod scheduler;
 pub mod state;
 pub mod store;
+pub mod trash;
 pub mod tunnel;
+pub mod upgrade;
+pub mod webhooks;
 
 pub use audit::{ApiAuditLogger, create_audit_logger};
 pub use auth::{AuthClaims, AuthenticatedUser, JwtValidator};
@@ -8,15 +8,27 @@ use tokio::signal;
 use tokio::sync::{RwLock, broadcast};
 use tracing::{info, warn};
 
+use mvirt_common::jobs::JobRegistry;
+use mvirt_common::limits::{LimitConfig, Limiter};
 use mvirt_cplane::JwtValidator;
 use mvirt_cplane::audit::create_audit_logger;
-use mvirt_cplane::reconciler::Controller;
+use mvirt_cplane::gc::{GarbageCollector, GcMode};
+use mvirt_cplane::notifications::NotificationGenerator;
+use mvirt_cplane::reconciler::{Controller, Ctx};
 use mvirt_cplane::rest::{AppState, create_router};
+use mvirt_cplane::scheduler::Scheduler;
 use mvirt_cplane::store::{Event, RaftStore};
+use mvirt_cplane::trash::TrashPurger;
+use mvirt_cplane::upgrade::UpgradeController;
+use mvirt_cplane::webhooks::WebhookDispatcher;
 use mvirt_cplane::{
-    ApiAuditLogger, ApiState, Command, DataStore, NodeId, NodeRegistry, Response, ca, tunnel,
+    ApiAuditLogger, ApiState, Command, DataStore, NodeId, NodeRegistry, Response, ca, handoff,
+    tunnel,
 };
 
+/// How long SIGTERM waits for in-flight REST requests.
+const REST_DRAIN_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[derive(Parser)]
 #[command(name = "mvirt-cplane")]
 #[command(
@@ -109,6 +121,33 @@ struct Args {
     /// Join token (required with --join)
     #[arg(long)]
     token: Option<String>,
+
+    /// Garbage collection of node resources (VMs, NICs, volumes) that no
+    /// cluster object owns: report only, delete, or off.
+    #[arg(long, value_enum, default_value = "dry-run")]
+    gc_mode: GcMode,
+
+    /// Seconds between garbage collection passes
+    #[arg(long, default_value = "300")]
+    gc_interval: u64,
+
+    /// Seconds a resource must stay orphaned before it is deleted
+    #[arg(long, default_value = "600")]
+    gc_grace: u64,
+
+    /// Seconds deleted VMs, volumes and networks stay in the trash before
+    /// they are purged. 0 deletes immediately.
+    #[arg(long, default_value = "0")]
+    trash_retention: u64,
+
+    /// Place no new VMs on nodes whose CPU, memory or IO pressure (PSI,
+    /// percent of the last minute stalled) or CPU steal is above this
+    /// percentage. Unset places by resources alone.
+    #[arg(long, value_name = "PERCENT")]
+    max_node_pressure: Option<f64>,
+
+    #[command(flatten)]
+    limits: LimitConfig,
 }
 
 fn parse_peer(s: &str) -> Result<(NodeId, String), String> {
@@ -218,6 +257,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         ApiState::default()
     };
 
+    let handoff_state = api_state.clone();
     let mut node: RaftNode<Command, Response, ApiState> = RaftNode::new(config, api_state).await?;
     node.start().await?;
 
@@ -263,7 +303,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     node.set_event_sink(event_tx.clone());
 
     let raft_node = Arc::new(RwLock::new(node));
-    let store = Arc::new(RaftStore::new(raft_node.clone(), event_tx, node_id));
+    let mut scheduler = Scheduler::new();
+    if let Some(percent) = args.max_node_pressure {
+        scheduler = scheduler.with_max_pressure(percent);
+    }
+    let store =
+        Arc::new(RaftStore::new(raft_node.clone(), event_tx, node_id).with_scheduler(scheduler));
+    handoff::spawn_watcher(raft_node.clone(), &handoff_state, node_id);
 
     // self-AuditLogger uses mTLS even on loopback: the co-resident mvirt-log
     // requires client certs. Mint an ephemeral client cert against the same
@@ -314,6 +360,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         vec!["https://localhost:50052".to_string()]
     };
 
+    // Reverse-tunnel listener: nodes dial in, we get a Channel per connection.
+    let registry = Arc::new(NodeRegistry::new());
+
+    // Long-running work of this peer (volume copies), served at /v1/jobs.
+    let jobs = JobRegistry::default();
+
+    // Upgrade controller: rolling daemon restarts ordered over REST.
+    let upgrades = Arc::new(UpgradeController::new(Ctx {
+        store: store.clone(),
+        registry: registry.clone(),
+        audit: audit.clone(),
+        jobs: jobs.clone(),
+    }));
+
     let app_state = Arc::new(AppState {
         store: store.clone(),
         audit: audit.clone(),
@@ -322,17 +382,58 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         log_advertise,
         jwt_validator,
         initial_admin_email,
+        upgrades: Some(upgrades),
+        registry: Some(registry.clone()),
+        limiter: Some(Arc::new(Limiter::new(args.limits))),
+        trash_retention: (args.trash_retention > 0)
+            .then(|| Duration::from_secs(args.trash_retention)),
+        jobs,
     });
 
     let router = create_router(app_state.clone());
 
-    // Reverse-tunnel listener: nodes dial in, we get a Channel per connection.
-    let registry = Arc::new(NodeRegistry::new());
     let tunnel_addr: std::net::SocketAddr = args.tunnel_listen.parse()?;
 
     // Reconciler controller: subscribes to raft events + periodic resync,
     // dispatches per-resource RPCs against the daemon channels in the registry.
-    Controller::new(store.clone(), registry.clone(), audit.clone()).spawn(store.subscribe());
+    Controller::new(store.clone(), registry.clone(), audit.clone(), jobs.clone())
+        .spawn(store.subscribe());
+
+    // Webhook dispatcher: POSTs lifecycle events to registered webhooks.
+    WebhookDispatcher::new(store.clone()).spawn(store.subscribe());
+
+    // Notifications: raised for node outages, failed imports, degraded pools.
+    NotificationGenerator::new(store.clone()).spawn(store.subscribe());
+
+    // Garbage collector: removes (or reports) node resources left behind by
+    // deletes, which the reconcilers don't tear down yet.
+    GarbageCollector::new(
+        Ctx {
+            store: store.clone(),
+            registry: registry.clone(),
+            audit: audit.clone(),
+            jobs: jobs.clone(),
+        },
+        args.gc_mode,
+        Duration::from_secs(args.gc_interval),
+        Duration::from_secs(args.gc_grace),
+    )
+    .spawn();
+
+    // Trash purger: deletes soft-deleted resources once their undo window
+    // has passed.
+    if args.trash_retention > 0 {
+        TrashPurger::new(
+            Ctx {
+                store: store.clone(),
+                registry: registry.clone(),
+                audit: audit.clone(),
+                jobs: jobs.clone(),
+            },
+            Duration::from_secs(60),
+        )
+        .spawn();
+    }
 
     let listener = tokio::net::TcpListener::bind(&args.listen).await?;
     info!("REST API listening on {}", args.listen);
@@ -340,11 +441,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     let (shutdown_tx, mut shutdown_rx) = tokio::sync::watch::channel(false);
 
     let rest_handle = tokio::spawn(async move {
-        axum::serve(listener, router)
-            .with_graceful_shutdown(async move {
-                shutdown_rx.changed().await.ok();
-            })
-            .await
+        axum::serve(
+            listener,
+            router.into_make_service_with_connect_info::<std::net::SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move {
+            shutdown_rx.changed().await.ok();
+        })
+        .await
     });
 
     // Bootstrap the rest of PKI: a server cert for the tunnel listener.
@@ -409,9 +513,20 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     let _ = shutdown_tx.send(true);
 
-    let _ = rest_handle.await;
+    // Let in-flight requests finish their proposals before leadership
+    // moves; a write racing the handoff would fail with NotLeader.
+    if tokio::time::timeout(REST_DRAIN_TIMEOUT, rest_handle)
+        .await
+        .is_err()
+    {
+        warn!("REST requests still in flight after drain timeout");
+    }
     tunnel_handle.abort();
 
+    if let Some(successor) = handoff::step_down(&raft_node, &store, node_id).await {
+        info!("Leadership handed to peer {}", successor);
+    }
+
     info!("Shutting down Raft node...");
     let mut node = raft_node.write().await;
     node.shutdown().await?;
@@ -0,0 +1,329 @@
+//! Operator notifications.
+//!
+//! The generator watches state-machine events for conditions an operator
+//! should know about and raises a notification for each: a node going
+//! offline, a failed template import or build, a degraded ZFS pool, and
+//! VMs, volumes or migrations ending up failed. Recoveries (node back
+//! online, pool healthy again) are raised too, so the feed reads as a
+//! story rather than a list of alarms. Events are only emitted on the raft
+//! leader, so each condition is raised once per cluster.
+//!
+//! Notifications are replicated; the state machine prunes them after
+//! 30 days and beyond the newest 500.
+
+use std::sync::Arc;
+
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::command::{
+    InvolvedObject, MigrationPhase, NodeData, NodeStatus, NotificationSeverity, TemplatePhase,
+    VmPhase, VolumePhase,
+};
+use crate::store::{CreateNotificationRequest, Event, NotificationStore, RaftStore};
+
+/// zpool health of a pool that needs no attention.
+const POOL_HEALTHY: &str = "ONLINE";
+
+fn involved(kind: &str, id: &str, name: &str, project_slug: Option<&str>) -> InvolvedObject {
+    InvolvedObject {
+        kind: kind.to_string(),
+        id: id.to_string(),
+        name: name.to_string(),
+        project_slug: project_slug.map(str::to_string),
+    }
+}
+
+fn notification(
+    severity: NotificationSeverity,
+    title: &str,
+    message: String,
+    involved_object: InvolvedObject,
+) -> Option<CreateNotificationRequest> {
+    Some(CreateNotificationRequest {
+        severity,
+        title: title.to_string(),
+        message,
+        involved_object,
+    })
+}
+
+/// Reported zpool health of a node, if it reports one.
+fn pool_health(node: &NodeData) -> Option<(&str, &str)> {
+    let host = node.resources.host.as_ref()?;
+    (!host.zfs_pool_health.is_empty())
+        .then_some((host.zfs_pool.as_str(), host.zfs_pool_health.as_str()))
+}
+
+fn node_notification(old: &NodeData, new: &NodeData) -> Option<CreateNotificationRequest> {
+    let object = involved("node", &new.id, &new.name, None);
+    if old.status != new.status {
+        return match (old.status, new.status) {
+            (NodeStatus::Online, NodeStatus::Offline) => notification(
+                NotificationSeverity::Error,
+                "Node down",
+                format!("Node {} stopped sending heartbeats", new.name),
+                object,
+            ),
+            (NodeStatus::Offline, NodeStatus::Online) => notification(
+                NotificationSeverity::Success,
+                "Node back online",
+                format!("Node {} is sending heartbeats again", new.name),
+                object,
+            ),
+            _ => None,
+        };
+    }
+    let (pool, health) = pool_health(new)?;
+    let old_health = pool_health(old).map(|(_, h)| h);
+    if old_health == Some(health) {
+        return None;
+    }
+    if health != POOL_HEALTHY {
+        let severity = match health {
+            "DEGRADED" => NotificationSeverity::Warning,
+            _ => NotificationSeverity::Error,
+        };
+        return notification(
+            severity,
+            "Storage pool degraded",
+            format!("ZFS pool {} on node {} is {}", pool, new.name, health),
+            object,
+        );
+    }
+    // Only a recovery is news; a pool first reported healthy is not.
+    old_health?;
+    notification(
+        NotificationSeverity::Success,
+        "Storage pool healthy",
+        format!("ZFS pool {} on node {} is online again", pool, new.name),
+        object,
+    )
+}
+
+/// The notification an event calls for, if any.
+pub fn notification_for(event: &Event) -> Option<CreateNotificationRequest> {
+    match event {
+        Event::NodeUpdated { old, new, .. } => node_notification(old, new),
+        Event::TemplateUpdated { old, new, .. } if old.status.phase != new.status.phase => {
+            let object = involved(
+                "template",
+                &new.id,
+                &new.spec.name,
+                Some(&new.spec.project_slug),
+            );
+            let (action, failed) = if new.spec.build.is_some() {
+                ("build", "Template build failed")
+            } else {
+                ("import", "Template import failed")
+            };
+            match new.status.phase {
+                TemplatePhase::Failed => notification(
+                    NotificationSeverity::Error,
+                    failed,
+                    format!(
+                        "Template {} {} failed: {}",
+                        new.spec.name,
+                        action,
+                        new.status.error.as_deref().unwrap_or("unknown error")
+                    ),
+                    object,
+                ),
+                TemplatePhase::Ready => notification(
+                    NotificationSeverity::Success,
+                    "Template ready",
+                    format!("Template {} {} finished", new.spec.name, action),
+                    object,
+                ),
+                _ => None,
+            }
+        }
+        Event::VmStatusUpdated { old, new, .. }
+            if old.status.phase != new.status.phase && new.status.phase == VmPhase::Failed =>
+        {
+            notification(
+                NotificationSeverity::Error,
+                "VM failed",
+                format!(
+                    "VM {} failed: {}",
+                    new.spec.name,
+                    new.status.message.as_deref().unwrap_or("unknown error")
+                ),
+                involved("vm", &new.id, &new.spec.name, Some(&new.spec.project_slug)),
+            )
+        }
+        Event::VolumeStatusUpdated { old, new, .. }
+            if old.status.phase != new.status.phase && new.status.phase == VolumePhase::Failed =>
+        {
+            notification(
+                NotificationSeverity::Error,
+                "Volume failed",
+                format!(
+                    "Volume {} failed: {}",
+                    new.spec.name,
+                    new.status.error.as_deref().unwrap_or("unknown error")
+                ),
+                involved(
+                    "volume",
+                    &new.id,
+                    &new.spec.name,
+                    Some(&new.spec.project_slug),
+                ),
+            )
+        }
+        Event::MigrationUpdated { old, new, .. }
+            if old.status.phase != new.status.phase
+                && new.status.phase == MigrationPhase::Failed =>
+        {
+            notification(
+                NotificationSeverity::Error,
+                "Migration failed",
+                format!(
+                    "Migration of VM {} to node {} failed: {}",
+                    new.spec.vm_id,
+                    new.spec.target_node_id,
+                    new.status.error.as_deref().unwrap_or("unknown error")
+                ),
+                involved(
+                    "migration",
+                    &new.id,
+                    &new.spec.vm_id,
+                    Some(&new.spec.project_slug),
+                ),
+            )
+        }
+        _ => None,
+    }
+}
+
+pub struct NotificationGenerator {
+    store: Arc<RaftStore>,
+}
+
+impl NotificationGenerator {
+    pub fn new(store: Arc<RaftStore>) -> Self {
+        Self { store }
+    }
+
+    /// Spawn the generator loop. Returns immediately.
+    pub fn spawn(self, mut events: broadcast::Receiver<Event>) {
+        info!("starting notification generator");
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => {
+                        let Some(req) = notification_for(&event) else {
+                            continue;
+                        };
+                        debug!(title = %req.title, resource = %event.resource_id(), "notification");
+                        if let Err(e) = self.store.create_notification(req).await {
+                            warn!(error = %e, "notifications: raising failed");
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(
+                            skipped = n,
+                            "notifications lagged on event stream; events dropped"
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        info!("event channel closed; notification generator stopping");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{HostTelemetry, NodeResources};
+    use std::collections::HashMap;
+
+    fn node(status: NodeStatus, pool_health: &str) -> NodeData {
+        NodeData {
+            id: "node-1".to_string(),
+            name: "hv-1".to_string(),
+            address: "hv-1:6001".to_string(),
+            status,
+            resources: NodeResources {
+                host: Some(HostTelemetry {
+                    zfs_pool: "mvirt".to_string(),
+                    zfs_pool_health: pool_health.to_string(),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            labels: HashMap::new(),
+            last_heartbeat: "2024-01-01T00:00:00Z".to_string(),
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+            cluster_slug: None,
+            cert_serial_hex: None,
+            cert_expires_at: None,
+            hostname: None,
+            agent_version: None,
+        }
+    }
+
+    fn title(old: NodeData, new: NodeData) -> Option<String> {
+        let event = Event::NodeUpdated {
+            id: new.id.clone(),
+            old,
+            new,
+        };
+        notification_for(&event).map(|n| n.title)
+    }
+
+    #[test]
+    fn test_node_status() {
+        use NodeStatus::*;
+        assert_eq!(
+            title(node(Online, "ONLINE"), node(Offline, "ONLINE")).as_deref(),
+            Some("Node down")
+        );
+        assert_eq!(
+            title(node(Offline, "ONLINE"), node(Online, "ONLINE")).as_deref(),
+            Some("Node back online")
+        );
+        // Onboarding completing is not news.
+        assert_eq!(title(node(Unknown, ""), node(Online, "ONLINE")), None);
+        // Plain heartbeats neither.
+        assert_eq!(title(node(Online, "ONLINE"), node(Online, "ONLINE")), None);
+    }
+
+    #[test]
+    fn test_pool_health() {
+        use NodeStatus::Online;
+        let degraded = notification_for(&Event::NodeUpdated {
+            id: "node-1".to_string(),
+            old: node(Online, "ONLINE"),
+            new: node(Online, "DEGRADED"),
+        })
+        .unwrap();
+        assert_eq!(degraded.severity, NotificationSeverity::Warning);
+        assert_eq!(degraded.message, "ZFS pool mvirt on node hv-1 is DEGRADED");
+        assert_eq!(degraded.involved_object.kind, "node");
+
+        let faulted = notification_for(&Event::NodeUpdated {
+            id: "node-1".to_string(),
+            old: node(Online, "DEGRADED"),
+            new: node(Online, "FAULTED"),
+        })
+        .unwrap();
+        assert_eq!(faulted.severity, NotificationSeverity::Error);
+
+        assert_eq!(
+            title(node(Online, "DEGRADED"), node(Online, "DEGRADED")),
+            None
+        );
+        assert_eq!(
+            title(node(Online, "DEGRADED"), node(Online, "ONLINE")).as_deref(),
+            Some("Storage pool healthy")
+        );
+        // First report from a healthy pool.
+        assert_eq!(title(node(Online, ""), node(Online, "ONLINE")), None);
+    }
+}
@@ -1,8 +1,11 @@
 //! Shared context passed to every per-resource reconciler. Holds the cluster
-//! state store, the per-node tunnel registry, and the audit logger.
+//! state store, the per-node tunnel registry, the audit logger, and the
+//! jobs of long-running work this peer does itself.
 
 use std::sync::Arc;
 
+use mvirt_common::jobs::JobRegistry;
+
 use crate::audit::ApiAuditLogger;
 use crate::store::RaftStore;
 use crate::tunnel::NodeRegistry;
@@ -12,4 +15,5 @@ pub struct Ctx {
     pub store: Arc<RaftStore>,
     pub registry: Arc<NodeRegistry>,
     pub audit: Arc<ApiAuditLogger>,
+    pub jobs: JobRegistry,
 }
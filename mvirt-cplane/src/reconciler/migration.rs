@@ -0,0 +1,225 @@
+//! Migration reconciler — moves a stopped VM to another node.
+//!
+//! Phases (see `MigrationPhase`):
+//!   CopyingVolume → the volume reconciler streams the boot volume's copy
+//!     to the target; once it is Ready we rebind the VM and its NIC to the
+//!     target in a single raft commit (`RebindMigratedVm`).
+//!   CleaningUp → delete the VM, NIC and boot volume from the source node,
+//!     then drop the source volume from state.
+//!   Completed / Failed → nothing left to do.
+//!
+//! Every step is idempotent and keyed off the phase stored in raft, so a
+//! migration interrupted by a cplane restart, a leader change or a node
+//! that is offline for a while resumes on the next event or resync.
+
+use anyhow::Result;
+use chrono::Utc;
+use mvirt_daemon_protos::net::DeleteNicRequest;
+use mvirt_daemon_protos::vmm::{DeletePropagation, DeleteVmRequest};
+use mvirt_daemon_protos::zfs::DeleteVolumeRequest;
+use tonic::{Code, Status};
+use tracing::{info, warn};
+
+use super::Ctx;
+use crate::command::{Command, MigrationData, MigrationPhase, VolumePhase, new_request_id};
+use crate::state::ApiState;
+use crate::tunnel::NodeHandle;
+
+pub fn list_ids(state: &ApiState) -> Vec<String> {
+    state.migration_ids()
+}
+
+/// Drive the migration copying to the given volume, if any. Called on
+/// volume status writes so the VM is rebound as soon as the copy is Ready.
+pub async fn reconcile_for_volume(ctx: &Ctx, volume_id: &str) -> Result<()> {
+    let state = ctx.store.snapshot().await;
+    let copying: Vec<String> = state
+        .list_migrations(None)
+        .into_iter()
+        .filter(|m| m.status.phase == MigrationPhase::CopyingVolume)
+        .filter(|m| m.spec.target_volume_id == volume_id)
+        .map(|m| m.id)
+        .collect();
+    for id in copying {
+        reconcile(ctx, &id).await?;
+    }
+    Ok(())
+}
+
+pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
+    let state = ctx.store.snapshot().await;
+    let Some(migration) = state.get_migration(id) else {
+        return Ok(());
+    };
+
+    match migration.status.phase {
+        MigrationPhase::CopyingVolume => rebind(ctx, &state, &migration).await,
+        MigrationPhase::CleaningUp => cleanup_source(ctx, &state, &migration).await,
+        MigrationPhase::Completed | MigrationPhase::Failed => Ok(()),
+    }
+}
+
+/// Bind the VM to the target once its volume copy is Ready.
+async fn rebind(ctx: &Ctx, state: &ApiState, migration: &MigrationData) -> Result<()> {
+    let Some(vol) = state.get_volume(&migration.spec.target_volume_id) else {
+        return fail(ctx, state, migration, "volume copy was deleted".into()).await;
+    };
+    match vol.status.phase {
+        VolumePhase::Ready => {}
+        VolumePhase::Failed => {
+            let error = vol.status.error.unwrap_or_else(|| "unknown error".into());
+            return fail(
+                ctx,
+                state,
+                migration,
+                format!("volume copy failed: {error}"),
+            )
+            .await;
+        }
+        VolumePhase::Pending | VolumePhase::Creating => return Ok(()),
+    }
+
+    info!(
+        migration = %migration.id,
+        vm = %migration.spec.vm_id,
+        node = %migration.spec.target_node_id,
+        "volume copied; binding VM to target node"
+    );
+    let cmd = Command::RebindMigratedVm {
+        request_id: new_request_id(),
+        id: migration.id.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+    };
+    ctx.store
+        .submit(cmd)
+        .await
+        .map(|_| ())
+        .map_err(|e| anyhow::anyhow!("rebind migrated vm: {e}"))
+}
+
+/// Give up on a migration that has not moved the VM yet. The partial copy
+/// is dropped so the VM can be migrated again.
+async fn fail(ctx: &Ctx, state: &ApiState, migration: &MigrationData, error: String) -> Result<()> {
+    warn!(migration = %migration.id, vm = %migration.spec.vm_id, %error, "migration failed");
+    if state.get_volume(&migration.spec.target_volume_id).is_some() {
+        ctx.store
+            .submit(Command::DeleteVolume {
+                request_id: new_request_id(),
+                id: migration.spec.target_volume_id.clone(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("delete volume copy: {e}"))?;
+    }
+    ctx.audit
+        .vm_migration_failed(&migration.id, &migration.spec.vm_id, &error);
+    write_phase(ctx, migration, MigrationPhase::Failed, Some(error)).await
+}
+
+/// Remove what the VM left on the source node. Retried on every resync
+/// until the source node is reachable and all deletes went through.
+async fn cleanup_source(ctx: &Ctx, state: &ApiState, migration: &MigrationData) -> Result<()> {
+    let spec = &migration.spec;
+    let Some(node) = ctx.registry.get(&spec.source_node_id).await else {
+        warn!(migration = %migration.id, node = %spec.source_node_id, "source node not connected; will retry on resync");
+        return Ok(());
+    };
+
+    let nic_id = state
+        .get_vm(&spec.vm_id)
+        .map(|vm| vm.spec.nic_id)
+        .unwrap_or_default();
+    let volume_name = state
+        .get_volume(&spec.source_volume_id)
+        .map(|v| v.spec.name);
+    if let Err(e) = delete_from_node(&node, &spec.vm_id, &nic_id, volume_name.as_deref()).await {
+        warn!(migration = %migration.id, node = %spec.source_node_id, error = %e, "source cleanup failed; will retry on resync");
+        return Ok(());
+    }
+
+    if volume_name.is_some() {
+        ctx.store
+            .submit(Command::DeleteVolume {
+                request_id: new_request_id(),
+                id: spec.source_volume_id.clone(),
+            })
+            .await
+            .map_err(|e| anyhow::anyhow!("delete source volume: {e}"))?;
+    }
+
+    info!(migration = %migration.id, vm = %spec.vm_id, node = %spec.target_node_id, "migration completed");
+    ctx.audit
+        .vm_migration_completed(&migration.id, &spec.vm_id, &spec.target_node_id);
+    write_phase(ctx, migration, MigrationPhase::Completed, None).await
+}
+
+/// Delete the VM first so the NIC and disk it held are free.
+async fn delete_from_node(
+    node: &NodeHandle,
+    vm_id: &str,
+    nic_id: &str,
+    volume_name: Option<&str>,
+) -> std::result::Result<(), String> {
+    gone(
+        node.vmm
+            .clone()
+            .delete_vm(DeleteVmRequest {
+                id: vm_id.to_string(),
+                propagation: DeletePropagation::Cascade as i32,
+                force: false,
+            })
+            .await,
+        "delete_vm",
+    )?;
+    if !nic_id.is_empty() {
+        gone(
+            node.net
+                .clone()
+                .delete_nic(DeleteNicRequest {
+                    id: nic_id.to_string(),
+                })
+                .await,
+            "delete_nic",
+        )?;
+    }
+    if let Some(name) = volume_name {
+        gone(
+            node.zfs
+                .clone()
+                .delete_volume(DeleteVolumeRequest {
+                    name: name.to_string(),
+                })
+                .await,
+            "delete_volume",
+        )?;
+    }
+    Ok(())
+}
+
+/// A delete that finds nothing to delete succeeded too.
+fn gone<T>(result: std::result::Result<T, Status>, what: &str) -> std::result::Result<(), String> {
+    match result {
+        Ok(_) => Ok(()),
+        Err(s) if s.code() == Code::NotFound => Ok(()),
+        Err(s) => Err(format!("{what}: {}", s.message())),
+    }
+}
+
+async fn write_phase(
+    ctx: &Ctx,
+    migration: &MigrationData,
+    phase: MigrationPhase,
+    error: Option<String>,
+) -> Result<()> {
+    let cmd = Command::UpdateMigrationStatus {
+        request_id: new_request_id(),
+        id: migration.id.clone(),
+        timestamp: Utc::now().to_rfc3339(),
+        phase,
+        error,
+    };
+    ctx.store
+        .submit(cmd)
+        .await
+        .map(|_| ())
+        .map_err(|e| anyhow::anyhow!("write migration status: {e}"))
+}
@@ -14,8 +14,10 @@
 //! [`Controller::resync_all`].
 
 pub mod context;
+pub mod migration;
 pub mod network;
 pub mod nic;
+pub mod provisioning;
 pub mod security_group;
 pub mod template;
 pub mod vm;
@@ -24,8 +26,10 @@ pub mod volume;
 use std::sync::Arc;
 use std::time::Duration;
 
+use mvirt_common::jobs::JobRegistry;
+use mvirt_common::request_id;
 use tokio::sync::broadcast;
-use tracing::{debug, info, warn};
+use tracing::{Instrument, debug, info, info_span, warn};
 
 use crate::audit::ApiAuditLogger;
 use crate::store::{Event, RaftStore};
@@ -45,12 +49,14 @@ impl Controller {
         store: Arc<RaftStore>,
         registry: Arc<NodeRegistry>,
         audit: Arc<ApiAuditLogger>,
+        jobs: JobRegistry,
     ) -> Self {
         Self {
             ctx: Ctx {
                 store,
                 registry,
                 audit,
+                jobs,
             },
         }
     }
@@ -69,7 +75,7 @@ impl Controller {
                 tokio::select! {
                     _ = resync.tick() => self.resync_all().await,
                     msg = events.recv() => match msg {
-                        Ok(event) => self.dispatch_event(event).await,
+                        Ok(event) => self.dispatch_traced(event).await,
                         Err(broadcast::error::RecvError::Lagged(n)) => {
                             warn!(skipped = n, "controller lagged on event stream; forcing resync");
                             self.resync_all().await;
@@ -84,6 +90,24 @@ impl Controller {
         });
     }
 
+    /// Dispatch `event` under the request ID of the change that caused it,
+    /// so the daemon calls and log lines of its reconcile carry that ID.
+    async fn dispatch_traced(&self, event: Event) {
+        let trace = self
+            .ctx
+            .store
+            .snapshot()
+            .await
+            .trace_of(event.resource_id());
+        match trace {
+            Some(trace) => {
+                let span = info_span!("reconcile", request_id = %trace);
+                request_id::scope(trace, self.dispatch_event(event).instrument(span)).await
+            }
+            None => self.dispatch_event(event).await,
+        }
+    }
+
     /// Dispatch a single state-machine event to the appropriate reconciler.
     /// To wire a new resource type: add a match arm here.
     async fn dispatch_event(&self, event: Event) {
@@ -110,10 +134,25 @@ impl Controller {
                 let _ = vm::reconcile_for_nic(ctx, &id).await;
                 Ok(())
             }
-            Event::VmCreated(_)
-            | Event::VmUpdated { .. }
-            | Event::VmStatusUpdated { .. }
-            | Event::VmDeleted { .. } => vm::reconcile(ctx, &id).await,
+            Event::VmCreated(_) | Event::VmUpdated { .. } | Event::VmDeleted { .. } => {
+                vm::reconcile(ctx, &id).await
+            }
+            Event::MigrationCreated(_) | Event::MigrationUpdated { .. } => {
+                migration::reconcile(ctx, &id).await
+            }
+            Event::VmStatusUpdated { .. } => {
+                // Also covers provisioning writes: the VM reaching Running
+                // starts its hooks, and each hook write advances the next.
+                let r = vm::reconcile(ctx, &id).await;
+                if let Err(e) = provisioning::reconcile(ctx, &id).await {
+                    warn!(%id, error = %e, "provisioning reconcile failed");
+                }
+                // A template build finishes when its VM powers off.
+                if let Err(e) = template::reconcile_for_vm(ctx, &id).await {
+                    warn!(%id, error = %e, "template build reconcile failed");
+                }
+                r
+            }
             Event::VolumeCreated(_) | Event::VolumeDeleted { .. } => {
                 volume::reconcile(ctx, &id).await
             }
@@ -123,7 +162,8 @@ impl Controller {
                 // this volume to reach Ready.
                 let r = vm::reconcile_for_volume(ctx, &id).await;
                 let _ = r;
-                Ok(())
+                // A finished copy moves its migration on.
+                migration::reconcile_for_volume(ctx, &id).await
             }
             Event::TemplateCreated(_) | Event::TemplateUpdated { .. } => {
                 template::reconcile(ctx, &id).await
@@ -158,6 +198,9 @@ impl Controller {
         for id in vm::list_ids(&state) {
             let _ = vm::reconcile(ctx, &id).await;
         }
+        for id in provisioning::list_ids(&state) {
+            let _ = provisioning::reconcile(ctx, &id).await;
+        }
         for id in nic::list_ids(&state) {
             let _ = nic::reconcile(ctx, &id).await;
         }
@@ -170,5 +213,8 @@ impl Controller {
         for id in security_group::list_ids(&state) {
             let _ = security_group::reconcile(ctx, &id).await;
         }
+        for id in migration::list_ids(&state) {
+            let _ = migration::reconcile(ctx, &id).await;
+        }
     }
 }
@@ -1,19 +1,117 @@
-//! Network reconciler — converges desired NetworkData onto the relevant
-//! mvirt-ebpf daemons (one per node that hosts a NIC on this network).
+//! Network reconciler — spans each network across the nodes that host its
+//! NICs. Every such node's mvirt-net gets the overlay peers of the
+//! network: the other nodes' tunnel endpoints (VTEPs) and the addresses of
+//! the network's NICs on each, so VMs on different hosts share one L3
+//! network.
 //!
-//! Stub: structure in place, daemon RPC + status writeback to come.
+//! A node's VTEP is the address its tunnel connection comes from. The
+//! network itself is created on a node by the NIC reconciler; nodes whose
+//! daemon runs without an overlay (or mvirt-ebpf) are skipped.
+
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
 
 use anyhow::Result;
-use tracing::debug;
+use mvirt_daemon_protos::net::{OverlayPeer, SetOverlayPeersRequest};
+use tonic::Code;
+use tracing::{debug, warn};
 
 use super::Ctx;
+use crate::command::NicData;
 use crate::state::ApiState;
 
 pub fn list_ids(state: &ApiState) -> Vec<String> {
     state.network_ids()
 }
 
-pub async fn reconcile(_ctx: &Ctx, id: &str) -> Result<()> {
-    debug!(network = %id, "network reconcile (stub)");
+pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
+    let state = ctx.store.snapshot().await;
+    let Some(network) = state.get_network(id) else {
+        // Deleting the network on a node stops tunneling it there
+        return Ok(());
+    };
+
+    // Addresses of the network's NICs by the node of their VM
+    let mut by_node: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for nic in state.list_nics(Some(id)) {
+        let Some(node_id) = nic
+            .spec
+            .vm_id
+            .as_deref()
+            .and_then(|vm_id| state.get_vm(vm_id))
+            .and_then(|vm| vm.status.node_id)
+        else {
+            continue;
+        };
+        by_node
+            .entry(node_id)
+            .or_default()
+            .extend(nic_prefixes(&nic));
+    }
+    if by_node.is_empty() {
+        return Ok(());
+    }
+
+    let mut nodes = Vec::new();
+    for node_id in by_node.keys() {
+        match ctx.registry.get(node_id).await {
+            Some(node) => match node.address.parse::<SocketAddr>() {
+                Ok(addr) => nodes.push((node, addr.ip())),
+                Err(_) => {
+                    warn!(net = %id, node = %node_id, address = %node.address, "node has no tunnel address")
+                }
+            },
+            None => debug!(net = %id, node = %node_id, "node not connected; will retry on resync"),
+        }
+    }
+
+    for (node, _) in &nodes {
+        let peers = nodes
+            .iter()
+            .filter(|(other, _)| other.node_id != node.node_id)
+            .map(|(other, vtep)| OverlayPeer {
+                vtep: vtep.to_string(),
+                prefixes: by_node[&other.node_id].clone(),
+            })
+            .collect::<Vec<_>>();
+        let count = peers.len();
+        let result = node
+            .net
+            .clone()
+            .set_overlay_peers(SetOverlayPeersRequest {
+                network_id: network.id.clone(),
+                peers,
+            })
+            .await;
+        match result {
+            Ok(resp) => {
+                debug!(net = %network.name, node = %node.node_id, peers = count, vni = resp.into_inner().vni, "overlay peers set")
+            }
+            // Network not created on the node yet, or no overlay there
+            Err(s)
+                if matches!(
+                    s.code(),
+                    Code::NotFound | Code::Unimplemented | Code::FailedPrecondition
+                ) =>
+            {
+                debug!(net = %network.name, node = %node.node_id, reason = %s.message(), "overlay not set")
+            }
+            Err(s) => {
+                warn!(net = %network.name, node = %node.node_id, error = %s.message(), "set_overlay_peers failed")
+            }
+        }
+    }
     Ok(())
 }
+
+/// Host addresses and routed prefixes of a NIC, in CIDR notation
+fn nic_prefixes(nic: &NicData) -> Vec<String> {
+    let spec = &nic.spec;
+    spec.ipv4_address
+        .iter()
+        .map(|a| format!("{a}/32"))
+        .chain(spec.ipv6_address.iter().map(|a| format!("{a}/128")))
+        .chain(spec.routed_ipv4_prefixes.iter().cloned())
+        .chain(spec.routed_ipv6_prefixes.iter().cloned())
+        .collect()
+}
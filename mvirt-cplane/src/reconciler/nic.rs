@@ -13,14 +13,15 @@ use std::sync::Arc;
 use anyhow::Result;
 use chrono::Utc;
 use mvirt_daemon_protos::net::{
-    CreateNetworkRequest, CreateNicRequest, GetNetworkRequest, GetNicRequest, get_network_request,
-    get_nic_request,
+    CreateNetworkRequest, CreateNicRequest, GetNetworkRequest, GetNicRequest, OwnerReference,
+    get_network_request, get_nic_request,
 };
 use tonic::Code;
 use tracing::{info, warn};
 
 use super::Ctx;
-use crate::command::{Command, NetworkData, NicData, NicPhase};
+use crate::command::{Command, NetworkData, NicData, NicPhase, new_request_id};
+use crate::gc::CPLANE_OWNER_KIND;
 use crate::state::ApiState;
 use crate::tunnel::NodeHandle;
 
@@ -42,13 +43,30 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
         return Ok(());
     };
 
-    // Single-node assumption for now: pick the first connected node. Future
-    // work: stamp a node_id at create time (e.g. from the parent VM's
-    // schedule decision) and look it up here.
-    let nodes = ctx.registry.list().await;
-    let Some(node) = nodes.into_iter().next() else {
-        warn!(nic = %id, "no nodes connected; will retry on resync");
-        return Ok(());
+    // A NIC lives on the node of the VM it is attached to. Until that VM
+    // is placed (or for a NIC without one) fall back to the first
+    // connected node.
+    let vm_node = nic
+        .spec
+        .vm_id
+        .as_deref()
+        .and_then(|vm_id| state.get_vm(vm_id))
+        .and_then(|vm| vm.status.node_id);
+    let node = match vm_node {
+        Some(node_id) => {
+            let Some(node) = ctx.registry.get(&node_id).await else {
+                warn!(nic = %id, node = %node_id, "VM's node not connected; will retry on resync");
+                return Ok(());
+            };
+            node
+        }
+        None => {
+            let Some(node) = ctx.registry.list().await.into_iter().next() else {
+                warn!(nic = %id, "no nodes connected; will retry on resync");
+                return Ok(());
+            };
+            node
+        }
     };
 
     info!(nic = %id, node = %node.node_id, net = %network.name, "reconciling nic");
@@ -56,7 +74,7 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
     let result = ensure_nic(&node, &network, id, &nic).await;
     let cmd = match result {
         Ok(socket_path) => Command::UpdateNicStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             phase: NicPhase::Active,
@@ -64,7 +82,7 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
             message: None,
         },
         Err(e) => Command::UpdateNicStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             phase: NicPhase::Failed,
@@ -107,6 +125,12 @@ async fn ensure_nic(
                 dns_servers: network.dns_servers.clone(),
                 ntp_servers: network.ntp_servers.clone(),
                 is_public: network.is_public,
+                boot_server: network.boot_server.clone().unwrap_or_default(),
+                boot_file: network.boot_file.clone().unwrap_or_default(),
+                flow_logs: false,
+                flow_sample_rate: 0,
+                proxy_neighbors: false,
+                dns_forwarders: vec![],
             })
             .await
             .map_err(|s| format!("create_network: {}", s.message()))?;
@@ -133,6 +157,12 @@ async fn ensure_nic(
                 ipv6_address: nic.spec.ipv6_address.clone().unwrap_or_default(),
                 routed_ipv4_prefixes: nic.spec.routed_ipv4_prefixes.clone(),
                 routed_ipv6_prefixes: nic.spec.routed_ipv6_prefixes.clone(),
+                owner: Some(OwnerReference {
+                    kind: CPLANE_OWNER_KIND.to_string(),
+                    id: id.to_string(),
+                }),
+                delegated_ipv6_prefix_len: 0,
+                socket_access: None,
             })
             .await
             .map(|r| r.into_inner().socket_path)
@@ -0,0 +1,532 @@
+//! Provisioning reconciler — runs a VM's first-boot hooks once it reaches
+//! Running, so app bootstrap doesn't need external orchestration glue.
+//!
+//! Hooks run strictly in order. Each pass advances at most one hook:
+//!   Pending → Waiting (first pass after the VM is Running)
+//!   Waiting → Succeeded once all wait conditions hold and the action ran
+//!   Waiting → Failed on wait timeout or action error; later hooks → Skipped
+//!
+//! Probes and actions can take seconds, so each VM's hooks run in a task
+//! of their own rather than in the controller loop. The task advances
+//! hooks back-to-back while their conditions hold; a hook still waiting
+//! (e.g. for sshd to come up) is re-polled by the 30s resync.
+//!
+//! The cplane usually can't route to a VM's private address, so tcpPort
+//! waits are probed by the node the VM runs on. Webhook actions only go to
+//! public addresses: the cplane must not become a proxy into its own
+//! network or the metadata services of the hosts.
+//!
+//! guestReady waits and guestCommand actions go through the guest agent:
+//! VMs with such hooks are created with vsock, and the guest's init has to
+//! start `mvirt-one --agent` (e.g. from cloud-init). A guest without the
+//! agent never becomes ready and its hook times out.
+//!
+//! Hooks are first-boot only: terminal statuses are never reset, so a VM
+//! that is stopped and started again does not re-run them.
+
+use std::collections::HashSet;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use mvirt_daemon_protos::vmm::{GetVmRequest, RunGuestCommandRequest, RunGuestCommandResponse};
+use serde_json::json;
+use tracing::{info, warn};
+
+use super::Ctx;
+use crate::command::{
+    Command, HookAction, HookPhase, HookStatus, ProvisioningHook, VmData, VmPhase, WaitCondition,
+    new_request_id,
+};
+use crate::grpc::proto::ProbeTcpRequest;
+use crate::state::ApiState;
+use crate::tunnel::NodeHandle;
+
+const TCP_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// VMs whose hooks are running on this cplane. A task outlives the
+/// reconcile that started it, so later reconciles must not start another.
+static RUNNING: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// Whether the VM needs a guest agent for its hooks.
+pub fn needs_guest_agent(hooks: &[ProvisioningHook]) -> bool {
+    hooks.iter().any(|hook| {
+        hook.wait.contains(&WaitCondition::GuestReady)
+            || matches!(hook.action, Some(HookAction::GuestCommand { .. }))
+    })
+}
+
+/// VMs with at least one hook that hasn't finished yet.
+pub fn list_ids(state: &ApiState) -> Vec<String> {
+    state
+        .vm_ids()
+        .into_iter()
+        .filter_map(|id| state.get_vm(&id))
+        .filter(|vm| vm.provisioning.iter().any(|h| !h.phase.is_terminal()))
+        .map(|vm| vm.id)
+        .collect()
+}
+
+pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
+    if !RUNNING.lock().unwrap().insert(id.to_string()) {
+        return Ok(());
+    }
+    let ctx = ctx.clone();
+    let id = id.to_string();
+    tokio::spawn(async move {
+        loop {
+            match advance(&ctx, &id).await {
+                Ok(true) => {}
+                Ok(false) => break,
+                Err(e) => {
+                    warn!(vm = %id, error = %e, "provisioning failed");
+                    break;
+                }
+            }
+        }
+        RUNNING.lock().unwrap().remove(&id);
+    });
+    Ok(())
+}
+
+/// Advance the VM's first unfinished hook by one step. True if that hook
+/// finished, so the next one may be ready too.
+async fn advance(ctx: &Ctx, id: &str) -> Result<bool> {
+    let state = ctx.store.snapshot().await;
+    let Some(vm) = state.get_vm(id) else {
+        return Ok(false);
+    };
+    if vm.status.phase != VmPhase::Running || vm.spec.provisioning.len() != vm.provisioning.len() {
+        return Ok(false);
+    }
+    let Some(idx) = vm.provisioning.iter().position(|h| !h.phase.is_terminal()) else {
+        return Ok(false);
+    };
+
+    let hook = &vm.spec.provisioning[idx];
+    let mut hooks = vm.provisioning.clone();
+    let now = Utc::now();
+    let started_at = hooks[idx]
+        .started_at
+        .as_deref()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| t.with_timezone(&Utc))
+        .unwrap_or(now);
+
+    if hooks[idx].phase == HookPhase::Pending {
+        info!(vm = %id, hook = %hook.name, "starting provisioning hook");
+        hooks[idx].phase = HookPhase::Waiting;
+        hooks[idx].started_at = Some(now.to_rfc3339());
+    }
+
+    let ip = vm_ipv4(&state, &vm);
+    match unmet_condition(ctx, &vm, hook, ip.as_deref()).await {
+        Some(reason) => {
+            let elapsed = (now - started_at).num_seconds().max(0) as u64;
+            if elapsed < u64::from(hook.timeout_secs) {
+                hooks[idx].message = Some(reason);
+            } else {
+                finish(
+                    &mut hooks,
+                    idx,
+                    Err(format!(
+                        "timed out after {}s: {}",
+                        hook.timeout_secs, reason
+                    )),
+                );
+            }
+        }
+        None => {
+            let result = match &hook.action {
+                Some(action) => run_action(ctx, action, &vm, hook, ip.as_deref()).await,
+                None => Ok(()),
+            };
+            finish(&mut hooks, idx, result);
+        }
+    }
+
+    if hooks == vm.provisioning {
+        return Ok(false);
+    }
+    if hooks[idx].phase == HookPhase::Failed {
+        warn!(vm = %id, hook = %hook.name, message = ?hooks[idx].message, "provisioning hook failed");
+    }
+
+    let finished = hooks[idx].phase.is_terminal();
+    ctx.store
+        .submit(Command::UpdateVmProvisioning {
+            request_id: new_request_id(),
+            id: id.to_string(),
+            timestamp: now.to_rfc3339(),
+            hooks,
+        })
+        .await
+        .map(|_| finished)
+        .map_err(|e| anyhow::anyhow!("write provisioning status: {e}"))
+}
+
+/// Mark hook `idx` terminal. A failure skips every hook after it.
+fn finish(hooks: &mut [HookStatus], idx: usize, result: std::result::Result<(), String>) {
+    let now = Utc::now().to_rfc3339();
+    let (done, later) = hooks[idx..].split_first_mut().expect("idx in range");
+    done.finished_at = Some(now.clone());
+    match result {
+        Ok(()) => {
+            done.phase = HookPhase::Succeeded;
+            done.message = None;
+        }
+        Err(e) => {
+            done.phase = HookPhase::Failed;
+            done.message = Some(e);
+            for hook in later {
+                hook.phase = HookPhase::Skipped;
+                hook.finished_at = Some(now.clone());
+                hook.message = Some(format!("hook '{}' failed", done.name));
+            }
+        }
+    }
+}
+
+/// The VM's IPv4 address: the one reported in status, else the NIC's.
+fn vm_ipv4(state: &ApiState, vm: &VmData) -> Option<String> {
+    vm.status.ip_address.clone().or_else(|| {
+        state
+            .get_nic(&vm.spec.nic_id)
+            .and_then(|nic| nic.spec.ipv4_address)
+    })
+}
+
+/// The node the VM runs on.
+async fn vm_node(ctx: &Ctx, vm: &VmData) -> std::result::Result<Arc<NodeHandle>, String> {
+    let Some(node_id) = vm.status.node_id.as_deref() else {
+        return Err("VM is not placed on a node".to_string());
+    };
+    ctx.registry
+        .get(node_id)
+        .await
+        .ok_or_else(|| format!("node '{node_id}' is not connected"))
+}
+
+/// Returns a description of the first condition that doesn't hold yet.
+async fn unmet_condition(
+    ctx: &Ctx,
+    vm: &VmData,
+    hook: &ProvisioningHook,
+    ip: Option<&str>,
+) -> Option<String> {
+    for cond in &hook.wait {
+        match cond {
+            WaitCondition::GuestReady => {
+                let node = match vm_node(ctx, vm).await {
+                    Ok(node) => node,
+                    Err(reason) => return Some(reason),
+                };
+                let ready = node
+                    .vmm
+                    .clone()
+                    .get_vm(GetVmRequest { id: vm.id.clone() })
+                    .await
+                    .map(|resp| resp.into_inner().guest_ready);
+                if let Some(reason) = guest_not_ready(ready) {
+                    return Some(reason);
+                }
+            }
+            WaitCondition::TcpPort { port } => {
+                let Some(ip) = ip else {
+                    return Some("VM has no IPv4 address".to_string());
+                };
+                let node = match vm_node(ctx, vm).await {
+                    Ok(node) => node,
+                    Err(reason) => return Some(reason),
+                };
+                let probe = node
+                    .agent
+                    .clone()
+                    .probe_tcp(ProbeTcpRequest {
+                        address: ip.to_string(),
+                        port: (*port).into(),
+                        timeout_ms: TCP_PROBE_TIMEOUT.as_millis() as u32,
+                    })
+                    .await;
+                match probe {
+                    Ok(resp) if resp.get_ref().open => {}
+                    Ok(_) => return Some(format!("waiting for tcp port {port}")),
+                    Err(s) => return Some(format!("probing tcp port {port}: {}", s.message())),
+                }
+            }
+        }
+    }
+    None
+}
+
+/// Why a guestReady wait doesn't hold yet, given the node's answer.
+fn guest_not_ready(ready: std::result::Result<bool, tonic::Status>) -> Option<String> {
+    match ready {
+        Ok(true) => None,
+        Ok(false) => Some("waiting for the guest agent".to_string()),
+        Err(s) => Some(format!("checking the guest agent: {}", s.message())),
+    }
+}
+
+/// Outcome of a guestCommand action.
+fn guest_command_result(resp: RunGuestCommandResponse) -> std::result::Result<(), String> {
+    if resp.timed_out {
+        return Err("guest command timed out".to_string());
+    }
+    if resp.exit_code == 0 {
+        return Ok(());
+    }
+    let output = if resp.stderr.trim().is_empty() {
+        resp.stdout.trim()
+    } else {
+        resp.stderr.trim()
+    };
+    if output.is_empty() {
+        Err(format!("guest command exited with {}", resp.exit_code))
+    } else {
+        Err(format!(
+            "guest command exited with {}: {}",
+            resp.exit_code, output
+        ))
+    }
+}
+
+/// The address to send a webhook to, if it is a public one. Checked
+/// here rather than at creation because DNS may change in between; the
+/// request then goes to exactly this address.
+async fn webhook_target(url: &reqwest::Url) -> std::result::Result<(String, SocketAddr), String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!(
+            "webhook url scheme '{}' is not http(s)",
+            url.scheme()
+        ));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| "webhook url has no host".to_string())?;
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| format!("webhook: resolve {host}: {e}"))?
+        .collect();
+    if let Some(addr) = addrs.iter().find(|a| !is_public(a.ip())) {
+        return Err(format!(
+            "webhook host {host} resolves to non-public address {}",
+            addr.ip()
+        ));
+    }
+    let addr = addrs
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("webhook: {host} has no address"))?;
+    Ok((host.to_string(), addr))
+}
+
+/// Whether `ip` is reachable on the internet rather than on a host or
+/// private network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                // Shared address space (carrier-grade NAT)
+                || (a == 100 && (64..128).contains(&b))
+                || a == 0
+                || a >= 240)
+        }
+        IpAddr::V6(ip) => {
+            if let Some(v4) = ip.to_ipv4_mapped() {
+                return is_public(IpAddr::V4(v4));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_unspecified()
+                || ip.is_loopback()
+                || ip.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+async fn run_action(
+    ctx: &Ctx,
+    action: &HookAction,
+    vm: &VmData,
+    hook: &ProvisioningHook,
+    ip: Option<&str>,
+) -> std::result::Result<(), String> {
+    match action {
+        HookAction::GuestCommand {
+            command,
+            timeout_secs,
+        } => {
+            let node = vm_node(ctx, vm).await?;
+            let resp = node
+                .vmm
+                .clone()
+                .run_guest_command(RunGuestCommandRequest {
+                    vm_id: vm.id.clone(),
+                    command: command.clone(),
+                    timeout_seconds: *timeout_secs,
+                })
+                .await
+                .map_err(|s| format!("guest command: {}", s.message()))?;
+            guest_command_result(resp.into_inner())
+        }
+        HookAction::Webhook { url } => {
+            let body = json!({
+                "vmId": vm.id,
+                "name": vm.spec.name,
+                "projectSlug": vm.spec.project_slug,
+                "nodeId": vm.status.node_id,
+                "ipAddress": ip,
+                "hook": hook.name,
+            });
+            let url = reqwest::Url::parse(url).map_err(|e| format!("webhook url: {e}"))?;
+            let (host, addr) = webhook_target(&url).await?;
+            // Pinned to the checked address; redirects could lead anywhere
+            let client = reqwest::Client::builder()
+                .resolve(&host, addr)
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .map_err(|e| format!("webhook: {e}"))?;
+            let resp = client
+                .post(url)
+                .timeout(WEBHOOK_TIMEOUT)
+                .json(&body)
+                .send()
+                .await
+                .map_err(|e| format!("webhook: {e}"))?;
+            if resp.status().is_success() {
+                Ok(())
+            } else {
+                Err(format!("webhook returned HTTP {}", resp.status()))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(wait: Vec<WaitCondition>, action: Option<HookAction>) -> ProvisioningHook {
+        ProvisioningHook {
+            name: "h".to_string(),
+            wait,
+            action,
+            timeout_secs: 60,
+        }
+    }
+
+    #[test]
+    fn test_needs_guest_agent() {
+        let webhook = HookAction::Webhook {
+            url: "https://example.com".to_string(),
+        };
+        assert!(!needs_guest_agent(&[]));
+        assert!(!needs_guest_agent(&[hook(
+            vec![WaitCondition::TcpPort { port: 22 }],
+            Some(webhook.clone()),
+        )]));
+        assert!(needs_guest_agent(&[
+            hook(vec![], Some(webhook)),
+            hook(vec![WaitCondition::GuestReady], None),
+        ]));
+        assert!(needs_guest_agent(&[hook(
+            vec![],
+            Some(HookAction::GuestCommand {
+                command: vec!["true".to_string()],
+                timeout_secs: 0,
+            }),
+        )]));
+    }
+
+    #[test]
+    fn test_guest_ready_waits_for_the_agent() {
+        assert_eq!(guest_not_ready(Ok(true)), None);
+        assert_eq!(
+            guest_not_ready(Ok(false)).as_deref(),
+            Some("waiting for the guest agent")
+        );
+        let err = guest_not_ready(Err(tonic::Status::unavailable("node down"))).unwrap();
+        assert!(err.contains("node down"), "{err}");
+    }
+
+    #[test]
+    fn test_guest_command_result() {
+        let resp = |exit_code, stdout: &str, stderr: &str, timed_out| RunGuestCommandResponse {
+            exit_code,
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            timed_out,
+        };
+        assert_eq!(guest_command_result(resp(0, "done\n", "", false)), Ok(()));
+        assert_eq!(
+            guest_command_result(resp(2, "out\n", "no such file\n", false)),
+            Err("guest command exited with 2: no such file".to_string())
+        );
+        assert_eq!(
+            guest_command_result(resp(1, "out\n", "", false)),
+            Err("guest command exited with 1: out".to_string())
+        );
+        assert_eq!(
+            guest_command_result(resp(1, "", "", false)),
+            Err("guest command exited with 1".to_string())
+        );
+        assert_eq!(
+            guest_command_result(resp(-1, "", "", true)),
+            Err("guest command timed out".to_string())
+        );
+    }
+
+    #[test]
+    fn test_is_public() {
+        for ip in ["1.1.1.1", "203.0.114.7", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_webhook_target_rejects_internal_hosts() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost/hook",
+            "ftp://1.1.1.1/hook",
+        ] {
+            let url = reqwest::Url::parse(url).unwrap();
+            assert!(webhook_target(&url).await.is_err(), "{url}");
+        }
+        let url = reqwest::Url::parse("https://1.1.1.1/hook").unwrap();
+        let (host, addr) = webhook_target(&url).await.unwrap();
+        assert_eq!(host, "1.1.1.1");
+        assert_eq!(addr, "1.1.1.1:443".parse().unwrap());
+    }
+}
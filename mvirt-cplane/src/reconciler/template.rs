@@ -15,29 +15,63 @@
 //! Idempotency: every reconcile lists templates and active jobs first. If
 //! the template already exists, we short-circuit to Ready without firing
 //! another ImportTemplate.
+//!
+//! Built templates (`spec.build`) have no import job. Their build VM boots
+//! a clone of the base template with the user's cloud-init and powers
+//! itself off when done; we then snapshot its disk and promote the
+//! snapshot to the template. Once the build is Ready or Failed, the
+//! build's VM, NIC and volume are deleted from state and the node-side
+//! leftovers go the way of every orphan (see `gc`).
 
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use mvirt_daemon_protos::zfs::{
-    ImportJobState, ImportTemplateRequest, ListImportJobsRequest, ListTemplatesRequest,
+    CreateSnapshotRequest, ImportJobState, ImportTemplateRequest, ListImportJobsRequest,
+    ListSnapshotsRequest, ListTemplatesRequest, PromoteSnapshotRequest,
 };
 use tracing::{info, warn};
 
 use super::Ctx;
-use crate::command::{Command, TemplatePhase};
+use crate::command::{
+    Command, TemplateBuild, TemplateData, TemplatePhase, VmPhase, new_request_id,
+};
 use crate::state::ApiState;
 use crate::tunnel::NodeHandle;
 
+/// Snapshot of the build volume that gets promoted to the template
+const BUILD_SNAPSHOT: &str = "template-build";
+
 pub fn list_ids(state: &ApiState) -> Vec<String> {
     state.template_ids()
 }
 
+/// Drive the template the given VM is building, if any. Called on VM
+/// status writes so a build finishes as soon as its VM powered off.
+pub async fn reconcile_for_vm(ctx: &Ctx, vm_id: &str) -> Result<()> {
+    let state = ctx.store.snapshot().await;
+    let building: Vec<String> = state
+        .list_templates(None)
+        .into_iter()
+        .filter(|t| t.status.phase == TemplatePhase::Building)
+        .filter(|t| t.spec.build.as_ref().is_some_and(|b| b.vm_id == vm_id))
+        .map(|t| t.id)
+        .collect();
+    for id in building {
+        reconcile(ctx, &id).await?;
+    }
+    Ok(())
+}
+
 pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
     let state = ctx.store.snapshot().await;
     let Some(tmpl) = state.get_template(id) else {
         return Ok(());
     };
 
+    if let Some(build) = &tmpl.spec.build {
+        return reconcile_build(ctx, &state, &tmpl, build).await;
+    }
+
     if tmpl.status.phase == TemplatePhase::Ready {
         return Ok(());
     }
@@ -52,10 +86,18 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
 
     let outcome = drive(&node, &tmpl).await;
 
+    write_status(ctx, &tmpl, outcome).await
+}
+
+async fn write_status(
+    ctx: &Ctx,
+    tmpl: &TemplateData,
+    outcome: std::result::Result<Progress, String>,
+) -> Result<()> {
     let cmd = match outcome {
         Ok(progress) => Command::UpdateTemplateStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
-            id: id.to_string(),
+            request_id: new_request_id(),
+            id: tmpl.id.clone(),
             timestamp: Utc::now().to_rfc3339(),
             phase: progress.phase,
             bytes_written: progress.bytes_written,
@@ -63,8 +105,8 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
             error: None,
         },
         Err(e) => Command::UpdateTemplateStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
-            id: id.to_string(),
+            request_id: new_request_id(),
+            id: tmpl.id.clone(),
             timestamp: Utc::now().to_rfc3339(),
             phase: TemplatePhase::Failed,
             bytes_written: tmpl.status.bytes_written,
@@ -80,16 +122,142 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
         .map_err(|e| anyhow::anyhow!("write template status: {e}"))
 }
 
+/// One step of a template build. Nothing to do while the VM still runs.
+async fn reconcile_build(
+    ctx: &Ctx,
+    state: &ApiState,
+    tmpl: &TemplateData,
+    build: &TemplateBuild,
+) -> Result<()> {
+    if matches!(
+        tmpl.status.phase,
+        TemplatePhase::Ready | TemplatePhase::Failed
+    ) {
+        return cleanup_build(ctx, state, build).await;
+    }
+
+    let Some(vm) = state.get_vm(&build.vm_id) else {
+        return write_status(ctx, tmpl, Err("build VM was deleted".into())).await;
+    };
+    let outcome = match vm.status.phase {
+        VmPhase::Stopped => {
+            let Some(vol) = state.get_volume(&build.volume_id) else {
+                return write_status(ctx, tmpl, Err("build volume was deleted".into())).await;
+            };
+            let Some(node) = ctx.registry.get(&tmpl.spec.node_id).await else {
+                warn!(template = %tmpl.id, node = %tmpl.spec.node_id, "owning node not connected; will retry on resync");
+                return Ok(());
+            };
+            info!(template = %tmpl.id, vm = %vm.id, name = %tmpl.spec.name, "build VM powered off; promoting its disk");
+            promote_build(&node, &vol.spec.name, &tmpl.spec.name).await
+        }
+        VmPhase::Failed => Err(format!(
+            "build VM failed: {}",
+            vm.status.message.as_deref().unwrap_or("unknown error")
+        )),
+        _ if build_timed_out(tmpl, build) => Err(format!(
+            "build VM did not power off within {}s",
+            build.timeout_secs
+        )),
+        _ => return Ok(()),
+    };
+
+    write_status(ctx, tmpl, outcome).await
+}
+
+fn build_timed_out(tmpl: &TemplateData, build: &TemplateBuild) -> bool {
+    DateTime::parse_from_rfc3339(&tmpl.created_at)
+        .map(|started| {
+            let elapsed = Utc::now().signed_duration_since(started);
+            elapsed.num_seconds() >= build.timeout_secs as i64
+        })
+        .unwrap_or(false)
+}
+
+/// Snapshot the build volume and promote the snapshot. Safe to repeat
+/// after a partial failure: an existing template or snapshot is reused.
+async fn promote_build(
+    node: &NodeHandle,
+    volume_name: &str,
+    template_name: &str,
+) -> std::result::Result<Progress, String> {
+    let ready = |t: mvirt_daemon_protos::zfs::Template| Progress {
+        phase: TemplatePhase::Ready,
+        bytes_written: t.size_bytes,
+        size_bytes: t.size_bytes,
+    };
+    if let Some(t) = find_template(node, template_name).await? {
+        return Ok(ready(t));
+    }
+
+    let mut zfs = node.zfs.clone();
+    let snapshots = zfs
+        .list_snapshots(ListSnapshotsRequest {
+            volume_name: volume_name.to_string(),
+        })
+        .await
+        .map_err(|s| format!("list_snapshots: {}", s.message()))?
+        .into_inner()
+        .snapshots;
+    if !snapshots.iter().any(|s| s.name == BUILD_SNAPSHOT) {
+        zfs.create_snapshot(CreateSnapshotRequest {
+            volume_name: volume_name.to_string(),
+            snapshot_name: BUILD_SNAPSHOT.to_string(),
+        })
+        .await
+        .map_err(|s| format!("create_snapshot: {}", s.message()))?;
+    }
+
+    let template = zfs
+        .promote_snapshot_to_template(PromoteSnapshotRequest {
+            volume_name: volume_name.to_string(),
+            snapshot_name: BUILD_SNAPSHOT.to_string(),
+            template_name: template_name.to_string(),
+        })
+        .await
+        .map_err(|s| format!("promote_snapshot_to_template: {}", s.message()))?
+        .into_inner();
+    Ok(ready(template))
+}
+
+/// Delete whatever is left of a finished build. The VM goes first so the
+/// NIC is detached before it is deleted.
+async fn cleanup_build(ctx: &Ctx, state: &ApiState, build: &TemplateBuild) -> Result<()> {
+    let mut cmds = Vec::new();
+    if state.get_vm(&build.vm_id).is_some() {
+        cmds.push(Command::DeleteVm {
+            request_id: new_request_id(),
+            id: build.vm_id.clone(),
+        });
+    }
+    if state.get_nic(&build.nic_id).is_some() {
+        cmds.push(Command::DeleteNic {
+            request_id: new_request_id(),
+            id: build.nic_id.clone(),
+        });
+    }
+    if state.get_volume(&build.volume_id).is_some() {
+        cmds.push(Command::DeleteVolume {
+            request_id: new_request_id(),
+            id: build.volume_id.clone(),
+        });
+    }
+    for cmd in cmds {
+        ctx.store
+            .submit(cmd)
+            .await
+            .map_err(|e| anyhow::anyhow!("delete build resource: {e}"))?;
+    }
+    Ok(())
+}
+
 struct Progress {
     phase: TemplatePhase,
     bytes_written: u64,
     size_bytes: u64,
 }
 
-async fn drive(
-    node: &NodeHandle,
-    tmpl: &crate::command::TemplateData,
-) -> std::result::Result<Progress, String> {
+async fn drive(node: &NodeHandle, tmpl: &TemplateData) -> std::result::Result<Progress, String> {
     // (1) Template already exists on the node → Ready.
     if let Some(t) = find_template(node, &tmpl.spec.name).await? {
         return Ok(Progress {
@@ -23,8 +23,9 @@ use mvirt_daemon_protos::vmm::{
 use tonic::Code;
 use tracing::{info, warn};
 
-use super::Ctx;
-use crate::command::{Command, VmDesiredState, VmPhase, VmStatus, VolumePhase};
+use super::{Ctx, provisioning};
+use crate::command::{Command, VmDesiredState, VmPhase, VmStatus, VolumePhase, new_request_id};
+use crate::gc::{MANAGED_BY_CPLANE, MANAGED_BY_LABEL};
 use crate::state::ApiState;
 use crate::tunnel::NodeHandle;
 
@@ -84,6 +85,10 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
     if at_target(&vm.status.phase, target_running) {
         return Ok(());
     }
+    // The guest powering itself off is how a run-once VM finishes.
+    if vm.spec.run_once && vm.status.phase == VmPhase::Stopped {
+        return Ok(());
+    }
 
     // Boot disk must exist and be Ready before we can wire it into the VM
     // config. The Volume reconciler runs ahead of us via the event loop;
@@ -126,13 +131,30 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
         return Ok(());
     };
 
+    // Project-default keys plus the VM's own. Keys deleted since the VM
+    // was created are silently dropped.
+    let ssh_keys: Vec<String> = state
+        .list_ssh_keys_by_project(&vm.spec.project_slug)
+        .into_iter()
+        .filter(|k| k.project_default || vm.spec.ssh_key_ids.contains(&k.id))
+        .map(|k| k.public_key)
+        .collect();
+
     info!(vm = %id, node = %node_id, name = %vm.spec.name, phase = ?vm.status.phase, "reconciling vm");
 
-    let outcome = drive(&node, &vm, &disk_path, nic_attach.as_ref(), target_running).await;
+    let outcome = drive(
+        &node,
+        &vm,
+        &disk_path,
+        nic_attach.as_ref(),
+        &ssh_keys,
+        target_running,
+    )
+    .await;
 
     let cmd = match outcome {
         Ok(new_phase) => Command::UpdateVmStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             status: VmStatus {
@@ -143,7 +165,7 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
             },
         },
         Err(e) => Command::UpdateVmStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             status: VmStatus {
@@ -179,12 +201,13 @@ async fn drive(
     vm: &crate::command::VmData,
     disk_path: &str,
     nic_attach: Option<&(String, String)>,
+    ssh_keys: &[String],
     target_running: bool,
 ) -> std::result::Result<VmPhase, String> {
     // get_or_create: idempotent against partial failures and node restarts.
     let current = match get_vm(node, &vm.id).await? {
         Some(v) => v,
-        None => create_vm(node, vm, disk_path, nic_attach).await?,
+        None => create_vm(node, vm, disk_path, nic_attach, ssh_keys).await?,
     };
 
     let observed = VmState::try_from(current.state).unwrap_or(VmState::Unspecified);
@@ -225,6 +248,7 @@ async fn create_vm(
     vm: &crate::command::VmData,
     disk_path: &str,
     nic_attach: Option<&(String, String)>,
+    ssh_keys: &[String],
 ) -> std::result::Result<Vm, String> {
     let mut vmm = node.vmm.clone();
 
@@ -260,19 +284,27 @@ async fn create_vm(
         disks: vec![DiskConfig {
             path: disk_path.to_string(),
             readonly: false,
+            secret: None,
         }],
         nics,
         // Cloud-init datasource: caller-supplied user_data if any, else
         // a hostname-only stub. The stub is non-negotiable — without ANY
         // NoCloud seed, Ubuntu cloud-image's cloud-init hangs in the
         // metadata-probe loop forever and netplan never fires DHCP.
-        user_data: Some(vm.spec.user_data.clone().unwrap_or_else(|| {
-            format!(
-                "#cloud-config\nhostname: {}\n",
-                vm.spec.name.replace('_', "-")
-            )
-        })),
+        user_data: Some(with_ssh_keys(
+            vm.spec.user_data.clone().unwrap_or_else(|| {
+                format!(
+                    "#cloud-config\nhostname: {}\n",
+                    vm.spec.name.replace('_', "-")
+                )
+            }),
+            ssh_keys,
+        )),
         nested_virt: false,
+        labels: [(MANAGED_BY_LABEL.to_string(), MANAGED_BY_CPLANE.to_string())].into(),
+        max_vcpus: 0,
+        max_memory_mb: 0,
+        guest_agent: provisioning::needs_guest_agent(&vm.spec.provisioning),
     };
 
     vmm.create_vm(CreateVmRequest {
@@ -285,12 +317,69 @@ async fn create_vm(
     .map_err(|s| format!("create_vm: {}", s.message()))
 }
 
+const MIME_BOUNDARY: &str = "==MVIRT-USER-DATA==";
+
+/// Merge SSH keys into arbitrary user-data. Rather than editing the user's
+/// YAML (or script), wrap it in a multipart archive next to a cloud-config
+/// part whose `merge_how` appends to any `ssh_authorized_keys` list the
+/// user already has.
+fn with_ssh_keys(user_data: String, ssh_keys: &[String]) -> String {
+    if ssh_keys.is_empty() {
+        return user_data;
+    }
+
+    let mut keys_part = String::from("#cloud-config\nssh_authorized_keys:\n");
+    for key in ssh_keys {
+        let quoted = key.replace('\\', "\\\\").replace('"', "\\\"");
+        keys_part.push_str(&format!("  - \"{quoted}\"\n"));
+    }
+    keys_part.push_str(
+        "merge_how:\n  - name: list\n    settings: [append]\n  - name: dict\n    settings: [no_replace, recurse_list]\n",
+    );
+
+    // Already-MIME user-data carries its own headers and nests as-is.
+    let user_part = if user_data.starts_with("Content-Type:") {
+        user_data
+    } else {
+        format!(
+            "Content-Type: {}\n\n{}",
+            user_data_content_type(&user_data),
+            user_data
+        )
+    };
+
+    format!(
+        "Content-Type: multipart/mixed; boundary=\"{b}\"\nMIME-Version: 1.0\n\n\
+         --{b}\n{user_part}\n\
+         --{b}\nContent-Type: text/cloud-config\n\n{keys_part}\n\
+         --{b}--\n",
+        b = MIME_BOUNDARY
+    )
+}
+
+/// MIME type cloud-init expects for a user-data part, from its first line.
+fn user_data_content_type(user_data: &str) -> &'static str {
+    let first = user_data.lines().next().unwrap_or_default();
+    if first.starts_with("#!") {
+        "text/x-shellscript"
+    } else if first.starts_with("#include") {
+        "text/x-include-url"
+    } else if first.starts_with("#cloud-boothook") {
+        "text/cloud-boothook"
+    } else {
+        "text/cloud-config"
+    }
+}
+
 async fn start_vm(node: &NodeHandle, id: &str) -> std::result::Result<(), String> {
     let mut vmm = node.vmm.clone();
-    vmm.start_vm(StartVmRequest { id: id.to_string() })
-        .await
-        .map(|_| ())
-        .map_err(|s| format!("start_vm: {}", s.message()))
+    vmm.start_vm(StartVmRequest {
+        id: id.to_string(),
+        prepare_stages: vec![],
+    })
+    .await
+    .map(|_| ())
+    .map_err(|s| format!("start_vm: {}", s.message()))
 }
 
 async fn stop_vm(node: &NodeHandle, id: &str) -> std::result::Result<(), String> {
@@ -303,3 +392,24 @@ async fn stop_vm(node: &NodeHandle, id: &str) -> std::result::Result<(), String>
     .map(|_| ())
     .map_err(|s| format!("stop_vm: {}", s.message()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_ssh_keys_passthrough_without_keys() {
+        let ud = "#cloud-config\nhostname: vm\n".to_string();
+        assert_eq!(with_ssh_keys(ud.clone(), &[]), ud);
+    }
+
+    #[test]
+    fn test_with_ssh_keys_wraps_user_data() {
+        let ud = "#!/bin/sh\necho hi\n".to_string();
+        let out = with_ssh_keys(ud, &["ssh-ed25519 AAAA user@\"host\"".to_string()]);
+        assert!(out.starts_with("Content-Type: multipart/mixed"));
+        assert!(out.contains("Content-Type: text/x-shellscript\n\n#!/bin/sh"));
+        assert!(out.contains("  - \"ssh-ed25519 AAAA user@\\\"host\\\"\"\n"));
+        assert!(out.ends_with(&format!("--{}--\n", MIME_BOUNDARY)));
+    }
+}
@@ -1,18 +1,25 @@
 //! Volume reconciler — converges desired VolumeData onto the owning node's
 //! mvirt-zfs daemon via the reverse tunnel.
 
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock, Mutex};
 
 use anyhow::Result;
 use chrono::Utc;
+use mvirt_common::jobs::JobHandle;
+use mvirt_daemon_protos::zfs::volume_stream_chunk::Chunk;
 use mvirt_daemon_protos::zfs::{
-    CloneFromTemplateRequest, CreateVolumeRequest, GetVolumeRequest, Volume,
+    CloneFromTemplateRequest, CreateVolumeRequest, GetVolumeRequest, OwnerReference,
+    SendVolumeRequest, Volume,
 };
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
 use tonic::Code;
 use tracing::{info, warn};
 
 use super::Ctx;
-use crate::command::{Command, VolumePhase};
+use crate::command::{Command, VolumeData, VolumePhase, new_request_id};
+use crate::gc::CPLANE_OWNER_KIND;
 use crate::state::ApiState;
 use crate::tunnel::NodeHandle;
 
@@ -40,6 +47,10 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
         return Ok(());
     };
 
+    if let Some(source_id) = &spec.source_volume_id {
+        return start_copy(ctx, &state, node, &vol, source_id).await;
+    }
+
     info!(volume = %id, node = %spec.node_id, name = %spec.name, "reconciling volume");
     let result = if let Some(template_id) = &spec.template_id {
         let template_name = state
@@ -51,9 +62,17 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
         create_volume(&node, id, &spec.name, spec.size_bytes).await
     };
 
+    write_status(ctx, id, result).await
+}
+
+async fn write_status(
+    ctx: &Ctx,
+    id: &str,
+    result: std::result::Result<Volume, String>,
+) -> Result<()> {
     let cmd = match result {
         Ok(v) => Command::UpdateVolumeStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             phase: VolumePhase::Ready,
@@ -62,7 +81,7 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
             error: None,
         },
         Err(e) => Command::UpdateVolumeStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             phase: VolumePhase::Failed,
@@ -79,6 +98,156 @@ pub async fn reconcile(ctx: &Ctx, id: &str) -> Result<()> {
         .map_err(|e| anyhow::anyhow!("write volume status: {e}"))
 }
 
+/// Volume copies streaming on this cplane. A copy outlives the reconcile
+/// that started it, so later reconciles must not start a second stream.
+static COPIES: LazyLock<Mutex<HashSet<String>>> = LazyLock::new(Default::default);
+
+/// Start streaming `vol` from its source volume's node. Runs in the
+/// background so a long copy does not hold up the controller loop.
+async fn start_copy(
+    ctx: &Ctx,
+    state: &ApiState,
+    dst: Arc<NodeHandle>,
+    vol: &VolumeData,
+    source_id: &str,
+) -> Result<()> {
+    // A failed copy is not retried on resync; that would restream the
+    // whole volume every 30s.
+    if vol.status.phase == VolumePhase::Failed {
+        return Ok(());
+    }
+    let Some(source) = state.get_volume(source_id) else {
+        return write_status(ctx, &vol.id, Err("source volume was deleted".into())).await;
+    };
+    let Some(src) = ctx.registry.get(&source.spec.node_id).await else {
+        warn!(volume = %vol.id, node = %source.spec.node_id, "source node not connected; will retry on resync");
+        return Ok(());
+    };
+    if !COPIES.lock().unwrap().insert(vol.id.clone()) {
+        return Ok(());
+    }
+
+    info!(
+        volume = %vol.id,
+        source = %source_id,
+        from = %source.spec.node_id,
+        to = %vol.spec.node_id,
+        "copying volume"
+    );
+    if vol.status.phase == VolumePhase::Pending {
+        let cmd = Command::UpdateVolumeStatus {
+            request_id: new_request_id(),
+            id: vol.id.clone(),
+            timestamp: Utc::now().to_rfc3339(),
+            phase: VolumePhase::Creating,
+            path: None,
+            used_bytes: 0,
+            error: None,
+        };
+        if let Err(e) = ctx.store.submit(cmd).await {
+            warn!(volume = %vol.id, error = %e, "write volume status failed");
+        }
+    }
+
+    let ctx = ctx.clone();
+    let id = vol.id.clone();
+    let name = vol.spec.name.clone();
+    let job = Arc::new(ctx.jobs.start("volume-copy", &name, true));
+    job.set_phase("copying");
+    tokio::spawn(async move {
+        let result = copy_volume(&src, &dst, &source.spec.name, &id, &name, job.clone()).await;
+        match &result {
+            Ok(_) => job.complete(),
+            Err(_) if job.is_cancelled() => job.finish_cancelled(),
+            Err(e) => {
+                warn!(volume = %id, error = %e, "volume copy failed");
+                job.fail(e);
+            }
+        }
+        if let Err(e) = write_status(&ctx, &id, result).await {
+            warn!(volume = %id, error = %e, "write volume status failed");
+        }
+        COPIES.lock().unwrap().remove(&id);
+    });
+    Ok(())
+}
+
+/// Relay a volume from `src`'s mvirt-zfs to `dst`'s. Nodes only dial the
+/// cplane, so the stream passes through here chunk by chunk. Cancelling
+/// `job` ends the relay like a failed source would.
+async fn copy_volume(
+    src: &NodeHandle,
+    dst: &NodeHandle,
+    source_name: &str,
+    id: &str,
+    name: &str,
+    job: Arc<JobHandle>,
+) -> std::result::Result<Volume, String> {
+    let mut dst_zfs = dst.zfs.clone();
+    match dst_zfs
+        .get_volume(GetVolumeRequest {
+            name: name.to_string(),
+        })
+        .await
+    {
+        Ok(resp) => return Ok(resp.into_inner()),
+        Err(s) if s.code() == Code::NotFound => {}
+        Err(s) => return Err(format!("get_volume: {}", s.message())),
+    }
+
+    let mut src_zfs = src.zfs.clone();
+    let mut inbound = src_zfs
+        .send_volume(SendVolumeRequest {
+            name: source_name.to_string(),
+        })
+        .await
+        .map_err(|s| format!("send_volume: {}", s.message()))?
+        .into_inner();
+
+    // Name the copy in the header on the way through. If the source fails
+    // mid-stream, the relay ends before the checksum and the destination
+    // discards what it got.
+    let (tx, rx) = mpsc::channel(4);
+    let (id, name) = (id.to_string(), name.to_string());
+    let relay = tokio::spawn(async move {
+        let mut bytes = 0u64;
+        loop {
+            let message = tokio::select! {
+                message = inbound.message() => message,
+                _ = job.wait_cancelled() => return Err("cancelled".to_string()),
+            };
+            let Some(mut chunk) = message.map_err(|s| format!("send_volume: {}", s.message()))?
+            else {
+                break;
+            };
+            if let Some(Chunk::Data(data)) = chunk.chunk.as_ref() {
+                bytes += data.len() as u64;
+                job.set_progress(bytes, None);
+            }
+            if let Some(Chunk::Header(header)) = chunk.chunk.as_mut() {
+                header.name = name.clone();
+                header.id = id.clone();
+            }
+            if tx.send(chunk).await.is_err() {
+                // Destination gave up; its error is the one reported
+                break;
+            }
+        }
+        Ok::<(), String>(())
+    });
+
+    let received = dst_zfs.receive_volume(ReceiverStream::new(rx)).await;
+    let relayed = relay
+        .await
+        .unwrap_or_else(|e| Err(format!("relay task: {e}")));
+    match (received, relayed) {
+        (Ok(v), _) => Ok(v.into_inner()),
+        // The source's error explains the aborted receive
+        (Err(_), Err(e)) => Err(e),
+        (Err(s), Ok(())) => Err(format!("receive_volume: {}", s.message())),
+    }
+}
+
 async fn create_volume(
     node: &Arc<NodeHandle>,
     id: &str,
@@ -101,12 +270,21 @@ async fn create_volume(
         name: name.to_string(),
         size_bytes,
         volblocksize: None,
+        owner: Some(cplane_owner(id)),
     })
     .await
     .map(|r| r.into_inner())
     .map_err(|s| format!("create_volume: {}", s.message()))
 }
 
+/// Marks a volume as the cplane's, for the GC.
+fn cplane_owner(id: &str) -> OwnerReference {
+    OwnerReference {
+        kind: CPLANE_OWNER_KIND.to_string(),
+        id: id.to_string(),
+    }
+}
+
 async fn clone_from_template(
     node: &Arc<NodeHandle>,
     id: &str,
@@ -130,6 +308,7 @@ async fn clone_from_template(
         template_name: template_name.to_string(),
         new_volume_name: new_volume_name.to_string(),
         size_bytes: Some(size_bytes),
+        owner: Some(cplane_owner(id)),
     })
     .await
     .map(|r| r.into_inner())
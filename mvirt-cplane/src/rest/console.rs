@@ -0,0 +1,230 @@
+//! VM consoles over the REST API, for VMs on any node.
+//!
+//! `GET /v1/vms/{id}/console` upgrades to a WebSocket and relays it to the
+//! `Console` stream of the mvirt-vmm hosting the VM, through the node's
+//! tunnel. Binary frames carry console bytes both ways. Text frames carry
+//! JSON control messages: the server sends the session's status
+//! (`{"sessionId", "writable", "notice"}`) on attach and whenever write
+//! access changes, and a client may send `{"access": "takeover"}` to grab
+//! write access.
+//!
+//! Query parameters: `access` (`shared`, `read-only`, `exclusive`,
+//! `takeover`), `scrollback` (bytes of recent output to replay) and
+//! `client` (a name for the viewer, used when auth is off). Callers need
+//! access to the VM's project; sessions are audit-logged on attach and
+//! detach.
+
+use std::sync::Arc;
+use std::time::Instant;
+
+use axum::{
+    extract::{
+        Path, Query, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    response::Response,
+};
+use futures::{SinkExt, StreamExt};
+use mvirt_daemon_protos::vmm::{ConsoleAccess, ConsoleInput, ConsoleOutput};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::warn;
+
+use super::handlers::{ApiError, AppState};
+use super::ui_handlers::require_project_access;
+use crate::command::VmPhase;
+
+#[derive(Deserialize)]
+pub struct ConsoleQuery {
+    #[serde(default)]
+    access: Option<String>,
+    #[serde(default)]
+    scrollback: u32,
+    #[serde(default)]
+    client: Option<String>,
+}
+
+/// Control message from the client.
+#[derive(Deserialize)]
+struct ClientControl {
+    access: String,
+}
+
+/// Session status sent to the client.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct SessionStatus {
+    session_id: String,
+    writable: bool,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    notice: String,
+}
+
+fn parse_access(access: &str) -> Option<ConsoleAccess> {
+    match access {
+        "" | "shared" => Some(ConsoleAccess::Shared),
+        "read-only" => Some(ConsoleAccess::ReadOnly),
+        "exclusive" => Some(ConsoleAccess::Exclusive),
+        "takeover" => Some(ConsoleAccess::Takeover),
+        _ => None,
+    }
+}
+
+fn bad_request(error: String) -> ApiError {
+    ApiError { error, code: 400 }
+}
+
+/// Attach to a VM's console
+pub async fn console_ws(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<ConsoleQuery>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+    ws: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let access = query.access.as_deref().unwrap_or_default();
+    let access = parse_access(access)
+        .ok_or_else(|| bad_request(format!("Unknown console access '{}'", access)))?;
+
+    let vm = state
+        .store
+        .get_vm(&id)
+        .await?
+        .or(state.store.get_vm_by_name(&id).await?)
+        .ok_or_else(|| ApiError {
+            error: "VM not found".to_string(),
+            code: 404,
+        })?;
+    require_project_access(&state, &auth, &vm.spec.project_slug).await?;
+
+    let node_id = match (&vm.status.phase, &vm.status.node_id) {
+        (VmPhase::Running, Some(node_id)) => node_id.clone(),
+        _ => {
+            return Err(ApiError {
+                error: format!("VM '{}' is not running", vm.spec.name),
+                code: 409,
+            });
+        }
+    };
+    let registry = state.registry.as_ref().ok_or_else(|| ApiError {
+        error: "Consoles are not available on this server".to_string(),
+        code: 503,
+    })?;
+    let node = registry.get(&node_id).await.ok_or_else(|| ApiError {
+        error: format!("Node {} of VM '{}' is not connected", node_id, vm.spec.name),
+        code: 503,
+    })?;
+
+    // The viewer as the vmm logs it: the account if there is one
+    let who = match &auth {
+        Some(account) => {
+            let account = &account.0.account;
+            account.email.clone().unwrap_or_else(|| account.id.clone())
+        }
+        None => query.client.unwrap_or_else(|| "anonymous".to_string()),
+    };
+
+    // Attach before upgrading, so a vmm that refuses the session fails the
+    // request rather than closing a fresh WebSocket
+    let (tx, rx) = mpsc::channel::<ConsoleInput>(32);
+    let _ = tx
+        .send(ConsoleInput {
+            vm_id: vm.id.clone(),
+            access: access as i32,
+            client: format!("{} via mvirt-api", who),
+            history_bytes: query.scrollback,
+            ..Default::default()
+        })
+        .await;
+    let mut vmm = node.vmm.clone();
+    let output = vmm
+        .console(ReceiverStream::new(rx))
+        .await
+        .map_err(|s| ApiError {
+            error: format!("Failed to attach to console: {}", s.message()),
+            code: 502,
+        })?
+        .into_inner();
+
+    state
+        .audit
+        .console_attached(&vm.id, &node_id, &who, access.as_str_name());
+    let audit = state.audit.clone();
+    let vm_id = vm.id;
+    Ok(ws.on_upgrade(move |socket| async move {
+        let started = Instant::now();
+        relay(socket, tx, output).await;
+        audit.console_detached(&vm_id, &who, started.elapsed().as_secs());
+    }))
+}
+
+/// Relay between the WebSocket and the vmm stream until either side ends.
+async fn relay(
+    socket: WebSocket,
+    tx: mpsc::Sender<ConsoleInput>,
+    mut output: tonic::Streaming<ConsoleOutput>,
+) {
+    let (mut sink, mut stream) = socket.split();
+
+    let outgoing = async {
+        while let Some(result) = output.next().await {
+            let message = match result {
+                Ok(out) if out.data.is_empty() => {
+                    let status = SessionStatus {
+                        session_id: out.session_id,
+                        writable: out.writable,
+                        notice: out.notice,
+                    };
+                    Message::Text(serde_json::to_string(&status).unwrap_or_default().into())
+                }
+                Ok(out) => Message::Binary(out.data.into()),
+                Err(status) => {
+                    let status = SessionStatus {
+                        session_id: String::new(),
+                        writable: false,
+                        notice: status.message().to_string(),
+                    };
+                    let text = serde_json::to_string(&status).unwrap_or_default();
+                    let _ = sink.send(Message::Text(text.into())).await;
+                    break;
+                }
+            };
+            if sink.send(message).await.is_err() {
+                return;
+            }
+        }
+        let _ = sink.close().await;
+    };
+
+    let incoming = async {
+        while let Some(Ok(message)) = stream.next().await {
+            let input = match message {
+                Message::Binary(data) => ConsoleInput {
+                    data: data.to_vec(),
+                    ..Default::default()
+                },
+                Message::Text(text) => match serde_json::from_str::<ClientControl>(text.as_str()) {
+                    Ok(control) if control.access == "takeover" => ConsoleInput {
+                        access: ConsoleAccess::Takeover as i32,
+                        ..Default::default()
+                    },
+                    _ => {
+                        warn!(message = %text.as_str(), "Ignoring unknown console control message");
+                        continue;
+                    }
+                },
+                Message::Close(_) => break,
+                Message::Ping(_) | Message::Pong(_) => continue,
+            };
+            if tx.send(input).await.is_err() {
+                break;
+            }
+        }
+    };
+
+    tokio::select! {
+        _ = outgoing => {}
+        _ = incoming => {}
+    }
+}
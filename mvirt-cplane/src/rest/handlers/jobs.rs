@@ -0,0 +1,153 @@
+use axum::{
+    Json,
+    extract::{Path, Query, State},
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+use mvirt_common::jobs::CancelError;
+
+use super::{ApiError, AppState};
+
+/// Long-running operation of this peer, in the shape the daemons' ListJobs
+/// reports theirs
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Job {
+    pub id: String,
+    /// What the operation does, e.g. volume-copy
+    pub kind: String,
+    /// Name or ID of the object it works on
+    pub target: String,
+    /// running, completed, failed or cancelled
+    pub state: String,
+    /// Kind-specific step
+    pub phase: String,
+    /// Units of work done, usually bytes
+    pub done: u64,
+    /// Units of work in total, absent if unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub cancellable: bool,
+    pub cancel_requested: bool,
+    pub started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+}
+
+impl From<mvirt_common::jobs::Job> for Job {
+    fn from(job: mvirt_common::jobs::Job) -> Self {
+        let rfc3339 =
+            |t: std::time::SystemTime| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339();
+        Self {
+            state: job.state.as_str().to_string(),
+            id: job.id,
+            kind: job.kind,
+            target: job.target,
+            phase: job.phase,
+            done: job.done,
+            total: job.total,
+            error: job.error,
+            cancellable: job.cancellable,
+            cancel_requested: job.cancel_requested,
+            started_at: rfc3339(job.started_at),
+            finished_at: job.finished_at.map(rfc3339),
+        }
+    }
+}
+
+/// Query parameters for list jobs
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ListJobsQuery {
+    /// Include completed, failed and cancelled jobs
+    #[serde(default)]
+    pub include_finished: bool,
+}
+
+impl From<CancelError> for ApiError {
+    fn from(e: CancelError) -> Self {
+        let code = match e {
+            CancelError::NotFound => 404,
+            CancelError::NotCancellable | CancelError::Finished => 409,
+        };
+        ApiError {
+            error: e.to_string(),
+            code,
+        }
+    }
+}
+
+/// List this peer's long-running operations
+#[utoipa::path(
+    get,
+    path = "/v1/jobs",
+    params(
+        ("includeFinished" = Option<bool>, Query, description = "Include finished jobs")
+    ),
+    responses(
+        (status = 200, description = "Jobs, oldest first", body = Vec<Job>)
+    ),
+    tag = "jobs"
+)]
+pub async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListJobsQuery>,
+) -> Json<Vec<Job>> {
+    Json(
+        state
+            .jobs
+            .list(query.include_finished)
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+    )
+}
+
+/// Get a job
+#[utoipa::path(
+    get,
+    path = "/v1/jobs/{id}",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Job", body = Job),
+        (status = 404, description = "Job not found", body = ApiError)
+    ),
+    tag = "jobs"
+)]
+pub async fn get_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Job>, ApiError> {
+    let job = state.jobs.get(&id).ok_or_else(|| ApiError {
+        error: format!("Job '{}' not found", id),
+        code: 404,
+    })?;
+    Ok(Json(job.into()))
+}
+
+/// Cancel a job
+///
+/// Asks the operation to stop; the job reports `cancelled` once it has.
+#[utoipa::path(
+    post,
+    path = "/v1/jobs/{id}/cancel",
+    params(("id" = String, Path, description = "Job ID")),
+    responses(
+        (status = 200, description = "Cancellation requested", body = Job),
+        (status = 404, description = "Job not found", body = ApiError),
+        (status = 409, description = "Job finished or cannot be cancelled", body = ApiError)
+    ),
+    tag = "jobs"
+)]
+pub async fn cancel_job(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<Job>, ApiError> {
+    let job = state.jobs.cancel(&id)?;
+    state.audit.job_cancelled(&job.id, &job.kind, &job.target);
+    Ok(Json(job.into()))
+}
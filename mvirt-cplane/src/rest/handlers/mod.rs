@@ -1,17 +1,21 @@
 // Allow dead code for legacy handlers during transition to UI-compatible API
 #[allow(dead_code)]
 mod controlplane;
+mod jobs;
 #[allow(dead_code)]
 mod networks;
 #[allow(dead_code)]
 mod nics;
 #[allow(dead_code)]
 mod nodes;
+mod upgrades;
 #[allow(dead_code)]
 mod vms;
 
 use axum::{Json, http::StatusCode, response::IntoResponse};
 use mraft::NodeId;
+use mvirt_common::naming::NameError;
+use mvirt_common::netboot::NetworkBootError;
 use serde::Serialize;
 use std::sync::Arc;
 use utoipa::ToSchema;
@@ -21,12 +25,14 @@ use crate::store::{DataStore, StoreError};
 
 #[allow(unused_imports)]
 pub use controlplane::*;
+pub use jobs::*;
 #[allow(unused_imports)]
 pub use networks::*;
 #[allow(unused_imports)]
 pub use nics::*;
 #[allow(unused_imports)]
 pub use nodes::*;
+pub use upgrades::*;
 #[allow(unused_imports)]
 pub use vms::*;
 
@@ -53,6 +59,20 @@ pub struct AppState {
     /// OIDC login matching this email (and no platform-admin existing yet),
     /// the auth middleware grants Platform/PlatformAdmin to the new Account.
     pub initial_admin_email: Option<String>,
+    /// Rolling daemon restarts across nodes. `None` where no node registry
+    /// exists (tests) — upgrade endpoints return 503.
+    pub upgrades: Option<Arc<crate::upgrade::UpgradeController>>,
+    /// Connected nodes, for calls that go to the node hosting a resource
+    /// (consoles). `None` in tests — those endpoints return 503.
+    pub registry: Option<Arc<crate::tunnel::NodeRegistry>>,
+    /// Per-client rate limit and in-flight cap for the REST API. `None`
+    /// disables admission control (tests).
+    pub limiter: Option<Arc<mvirt_common::limits::Limiter>>,
+    /// How long REST deletes of VMs, volumes and networks stay undoable.
+    /// `None` deletes immediately.
+    pub trash_retention: Option<std::time::Duration>,
+    /// Long-running work of this peer, shared with the reconcilers.
+    pub jobs: mvirt_common::jobs::JobRegistry,
 }
 
 /// API error response
@@ -62,6 +82,17 @@ pub struct ApiError {
     pub code: u32,
 }
 
+/// [`ApiError`] as sent, with the ID of the failed request to look it up
+/// in the logs.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiErrorBody {
+    error: String,
+    code: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> axum::response::Response {
         let status = match self.code {
@@ -76,7 +107,12 @@ impl IntoResponse for ApiError {
             503 => StatusCode::SERVICE_UNAVAILABLE,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
-        (status, Json(self)).into_response()
+        let body = ApiErrorBody {
+            error: self.error,
+            code: self.code,
+            request_id: mvirt_common::request_id::current(),
+        };
+        (status, Json(body)).into_response()
     }
 }
 
@@ -123,6 +159,24 @@ impl From<StoreError> for ApiError {
     }
 }
 
+impl From<NameError> for ApiError {
+    fn from(e: NameError) -> Self {
+        ApiError {
+            error: e.to_string(),
+            code: 400,
+        }
+    }
+}
+
+impl From<NetworkBootError> for ApiError {
+    fn from(e: NetworkBootError) -> Self {
+        ApiError {
+            error: e.to_string(),
+            code: 400,
+        }
+    }
+}
+
 /// Version information
 #[derive(Serialize, ToSchema)]
 pub struct VersionInfo {
@@ -2,6 +2,7 @@ use axum::{
     Json,
     extract::{Path, Query, State},
 };
+use mvirt_common::netboot;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use utoipa::ToSchema;
@@ -33,6 +34,11 @@ pub struct CreateNetworkRequest {
     pub ntp_servers: Option<Vec<String>>,
     /// Enable public internet access
     pub is_public: Option<bool>,
+    /// TFTP server announced to network-booting VMs
+    pub boot_server: Option<String>,
+    /// Boot file announced to network-booting VMs: a TFTP file name (needs
+    /// boot_server) or an http(s):// URL for UEFI HTTP boot
+    pub boot_file: Option<String>,
 }
 
 /// Network resource
@@ -47,6 +53,8 @@ pub struct Network {
     pub dns_servers: Vec<String>,
     pub ntp_servers: Vec<String>,
     pub is_public: bool,
+    pub boot_server: Option<String>,
+    pub boot_file: Option<String>,
     pub nic_count: u32,
     pub created_at: String,
     pub updated_at: String,
@@ -64,6 +72,8 @@ impl From<NetworkData> for Network {
             dns_servers: data.dns_servers,
             ntp_servers: data.ntp_servers,
             is_public: data.is_public,
+            boot_server: data.boot_server,
+            boot_file: data.boot_file,
             nic_count: data.nic_count,
             created_at: data.created_at,
             updated_at: data.updated_at,
@@ -83,6 +93,8 @@ impl From<&NetworkData> for Network {
             dns_servers: data.dns_servers.clone(),
             ntp_servers: data.ntp_servers.clone(),
             is_public: data.is_public,
+            boot_server: data.boot_server.clone(),
+            boot_file: data.boot_file.clone(),
             nic_count: data.nic_count,
             created_at: data.created_at.clone(),
             updated_at: data.updated_at.clone(),
@@ -97,6 +109,7 @@ impl From<&NetworkData> for Network {
     request_body = CreateNetworkRequest,
     responses(
         (status = 200, description = "Network created", body = Network),
+        (status = 400, description = "Invalid boot options", body = ApiError),
         (status = 409, description = "Network name already exists", body = ApiError),
         (status = 503, description = "Not the leader", body = ApiError)
     ),
@@ -106,6 +119,10 @@ pub async fn create_network(
     State(state): State<Arc<AppState>>,
     Json(req): Json<CreateNetworkRequest>,
 ) -> Result<Json<Network>, ApiError> {
+    netboot::validate_network_boot(
+        req.boot_server.as_deref().unwrap_or_default(),
+        req.boot_file.as_deref().unwrap_or_default(),
+    )?;
     let store_req = StoreCreateNetworkRequest {
         project_slug: String::new(), // Legacy handler — no project_slug
         name: req.name.clone(),
@@ -116,6 +133,8 @@ pub async fn create_network(
         dns_servers: req.dns_servers.unwrap_or_default(),
         ntp_servers: req.ntp_servers.unwrap_or_default(),
         is_public: req.is_public.unwrap_or(false),
+        boot_server: req.boot_server.filter(|s| !s.is_empty()),
+        boot_file: req.boot_file.filter(|s| !s.is_empty()),
     };
 
     let data = state.store.create_network(store_req).await?;
@@ -7,7 +7,8 @@ use std::collections::HashMap;
 use std::sync::Arc;
 use utoipa::ToSchema;
 
-use crate::command::{NodeData, NodeResources, NodeStatus};
+use crate::command::{HostTelemetry, NodeData, NodeResources, NodeStatus};
+use crate::grpc::proto::{QueryUsageHistoryRequest, UsageGauge as ProtoUsageGauge, UsagePoint};
 use crate::store::{
     RegisterNodeRequest as StoreRegisterNodeRequest,
     UpdateNodeStatusRequest as StoreUpdateNodeStatusRequest,
@@ -24,6 +25,88 @@ pub struct HypervisorNodeResources {
     pub available_cpu_cores: u32,
     pub available_memory_mb: u64,
     pub available_storage_gb: u64,
+    /// Host details reported by the node agent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host: Option<HypervisorHostTelemetry>,
+}
+
+/// Live host details of a node
+#[derive(Serialize, Deserialize, ToSchema, Clone)]
+pub struct HypervisorHostTelemetry {
+    pub cpu_model: String,
+    pub cpu_sockets: u32,
+    pub threads_per_core: u32,
+    pub load1: f64,
+    pub load5: f64,
+    pub load15: f64,
+    pub kernel_version: String,
+    pub kvm: bool,
+    pub hugepage_size_kb: u64,
+    pub hugepages_total: u64,
+    pub hugepages_free: u64,
+    pub zfs_pool: String,
+    #[serde(default)]
+    pub zfs_pool_health: String,
+    /// Percent of the last minute some task stalled on CPU (PSI avg60)
+    #[serde(default)]
+    pub cpu_pressure: f64,
+    /// Percent of the last minute some task stalled on memory (PSI avg60)
+    #[serde(default)]
+    pub memory_pressure: f64,
+    /// Percent of the last minute some task stalled on IO (PSI avg60)
+    #[serde(default)]
+    pub io_pressure: f64,
+    /// Percent of CPU time stolen by the hypervisor below the host
+    #[serde(default)]
+    pub cpu_steal: f64,
+}
+
+impl From<HostTelemetry> for HypervisorHostTelemetry {
+    fn from(h: HostTelemetry) -> Self {
+        Self {
+            cpu_model: h.cpu_model,
+            cpu_sockets: h.cpu_sockets,
+            threads_per_core: h.threads_per_core,
+            load1: h.load1,
+            load5: h.load5,
+            load15: h.load15,
+            kernel_version: h.kernel_version,
+            kvm: h.kvm,
+            hugepage_size_kb: h.hugepage_size_kb,
+            hugepages_total: h.hugepages_total,
+            hugepages_free: h.hugepages_free,
+            zfs_pool: h.zfs_pool,
+            zfs_pool_health: h.zfs_pool_health,
+            cpu_pressure: h.cpu_pressure,
+            memory_pressure: h.memory_pressure,
+            io_pressure: h.io_pressure,
+            cpu_steal: h.cpu_steal,
+        }
+    }
+}
+
+impl From<HypervisorHostTelemetry> for HostTelemetry {
+    fn from(h: HypervisorHostTelemetry) -> Self {
+        Self {
+            cpu_model: h.cpu_model,
+            cpu_sockets: h.cpu_sockets,
+            threads_per_core: h.threads_per_core,
+            load1: h.load1,
+            load5: h.load5,
+            load15: h.load15,
+            kernel_version: h.kernel_version,
+            kvm: h.kvm,
+            hugepage_size_kb: h.hugepage_size_kb,
+            hugepages_total: h.hugepages_total,
+            hugepages_free: h.hugepages_free,
+            zfs_pool: h.zfs_pool,
+            zfs_pool_health: h.zfs_pool_health,
+            cpu_pressure: h.cpu_pressure,
+            memory_pressure: h.memory_pressure,
+            io_pressure: h.io_pressure,
+            cpu_steal: h.cpu_steal,
+        }
+    }
 }
 
 impl From<NodeResources> for HypervisorNodeResources {
@@ -35,6 +118,7 @@ impl From<NodeResources> for HypervisorNodeResources {
             available_cpu_cores: r.available_cpu_cores,
             available_memory_mb: r.available_memory_mb,
             available_storage_gb: r.available_storage_gb,
+            host: r.host.map(Into::into),
         }
     }
 }
@@ -48,6 +132,7 @@ impl From<HypervisorNodeResources> for NodeResources {
             available_cpu_cores: r.available_cpu_cores,
             available_memory_mb: r.available_memory_mb,
             available_storage_gb: r.available_storage_gb,
+            host: r.host.map(Into::into),
         }
     }
 }
@@ -274,3 +359,152 @@ pub async fn deregister_hypervisor_node(
     state.audit.hypervisor_node_deregistered(&id);
     Ok(Json(DeregisterNodeResponse { deregistered: true }))
 }
+
+/// Points returned for a usage history unless the query asks otherwise
+const DEFAULT_USAGE_POINTS: u32 = 500;
+/// Days of usage history returned unless the query asks otherwise
+const DEFAULT_USAGE_DAYS: u32 = 7;
+
+/// Query parameters for a node's usage history
+#[derive(Deserialize, ToSchema)]
+pub struct UsageHistoryQuery {
+    /// Days back from now (default 7, at most what the node keeps)
+    pub days: Option<u32>,
+    /// Most points to return; intervals are widened to fit (default 500)
+    pub points: Option<u32>,
+}
+
+/// Average and peak of one measure over an interval
+#[derive(Serialize, ToSchema)]
+pub struct UsageGauge {
+    pub avg: f64,
+    pub max: f64,
+}
+
+impl From<Option<ProtoUsageGauge>> for UsageGauge {
+    fn from(g: Option<ProtoUsageGauge>) -> Self {
+        let g = g.unwrap_or_default();
+        Self {
+            avg: g.avg,
+            max: g.max,
+        }
+    }
+}
+
+/// Usage of a node over one interval
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeUsagePoint {
+    /// Start of the interval (RFC 3339)
+    pub timestamp: String,
+    pub cpu_cores: u32,
+    pub memory_mb: u64,
+    pub storage_gb: u64,
+    /// 1-minute load average
+    pub cpu_load: UsageGauge,
+    pub memory_used_mb: UsageGauge,
+    /// Used space of the node's ZFS pool
+    pub storage_used_gb: UsageGauge,
+    pub vms_running: UsageGauge,
+    /// vCPUs of the running VMs
+    pub vm_vcpus: UsageGauge,
+    /// Memory of the running VMs
+    pub vm_memory_mb: UsageGauge,
+}
+
+impl From<UsagePoint> for NodeUsagePoint {
+    fn from(p: UsagePoint) -> Self {
+        Self {
+            timestamp: chrono::DateTime::from_timestamp(p.timestamp, 0)
+                .unwrap_or_default()
+                .to_rfc3339(),
+            cpu_cores: p.cpu_cores,
+            memory_mb: p.memory_mb,
+            storage_gb: p.storage_gb,
+            cpu_load: p.cpu_load.into(),
+            memory_used_mb: p.memory_used_mb.into(),
+            storage_used_gb: p.storage_used_gb.into(),
+            vms_running: p.vms_running.into(),
+            vm_vcpus: p.vm_vcpus.into(),
+            vm_memory_mb: p.vm_memory_mb.into(),
+        }
+    }
+}
+
+/// Usage history of a node, kept by its agent
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeUsageHistory {
+    pub node_id: String,
+    /// Width of each point in seconds
+    pub interval_secs: u32,
+    /// How far back the node keeps history
+    pub retention_days: u32,
+    /// Oldest first; intervals in which the node was down are missing
+    pub points: Vec<NodeUsagePoint>,
+}
+
+/// Get the usage history of a hypervisor node
+///
+/// Host load, memory, ZFS pool and VM usage over the last days, as
+/// averages and peaks per interval, for capacity trends. Asked live from
+/// the node, which must be connected.
+#[utoipa::path(
+    get,
+    path = "/v1/nodes/{id}/usage-history",
+    params(
+        ("id" = String, Path, description = "Node ID or name"),
+        ("days" = Option<u32>, Query, description = "Days back from now (default 7)"),
+        ("points" = Option<u32>, Query, description = "Most points to return (default 500)")
+    ),
+    responses(
+        (status = 200, description = "Usage history", body = NodeUsageHistory),
+        (status = 404, description = "Node not found", body = ApiError),
+        (status = 503, description = "Node not connected", body = ApiError)
+    ),
+    tag = "nodes"
+)]
+pub async fn get_node_usage_history(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    Query(query): Query<UsageHistoryQuery>,
+) -> Result<Json<NodeUsageHistory>, ApiError> {
+    let node = state
+        .store
+        .get_node(&id)
+        .await?
+        .or(state.store.get_node_by_name(&id).await?)
+        .ok_or_else(|| ApiError {
+            error: "Node not found".to_string(),
+            code: 404,
+        })?;
+    let registry = state.registry.as_ref().ok_or_else(|| ApiError {
+        error: "Usage history is not available on this server".to_string(),
+        code: 503,
+    })?;
+    let handle = registry.get(&node.id).await.ok_or_else(|| ApiError {
+        error: format!("Node '{}' is not connected", node.name),
+        code: 503,
+    })?;
+
+    let history = handle
+        .agent
+        .clone()
+        .query_usage_history(QueryUsageHistoryRequest {
+            days: query.days.unwrap_or(DEFAULT_USAGE_DAYS),
+            max_points: query.points.unwrap_or(DEFAULT_USAGE_POINTS),
+        })
+        .await
+        .map_err(|s| ApiError {
+            error: format!("Failed to query usage history: {}", s.message()),
+            code: 502,
+        })?
+        .into_inner();
+
+    Ok(Json(NodeUsageHistory {
+        node_id: node.id,
+        interval_secs: history.interval_secs,
+        retention_days: history.retention_days,
+        points: history.points.into_iter().map(Into::into).collect(),
+    }))
+}
@@ -0,0 +1,263 @@
+use axum::{Json, extract::State, http::StatusCode};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::time::Duration;
+use utoipa::ToSchema;
+
+use crate::upgrade::{
+    Daemon, NodeUpgradeStatus, NodeVersions, StepStatus, UpgradeController, UpgradeError,
+    UpgradePlan, UpgradeStatus,
+};
+
+use super::{ApiError, AppState};
+
+/// Default time a net restart waits for vhost-user VMs to stop.
+const DEFAULT_DRAIN_TIMEOUT_SECS: u64 = 300;
+
+/// Version of one daemon on a node
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct DaemonVersionInfo {
+    /// vmm, zfs or net
+    pub daemon: String,
+    /// Absent when the daemon did not answer
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    pub reachable: bool,
+}
+
+/// Daemon versions of a connected node
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeDaemonVersions {
+    pub node_id: String,
+    pub node_name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub agent_version: Option<String>,
+    pub daemons: Vec<DaemonVersionInfo>,
+    /// Set when the node agent could not be reached
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl From<NodeVersions> for NodeDaemonVersions {
+    fn from(v: NodeVersions) -> Self {
+        Self {
+            node_id: v.node_id,
+            node_name: v.node_name,
+            agent_version: v.agent_version,
+            daemons: v
+                .daemons
+                .into_iter()
+                .map(|(daemon, version)| DaemonVersionInfo {
+                    daemon: daemon.to_string(),
+                    reachable: version.is_some(),
+                    version,
+                })
+                .collect(),
+            error: v.error,
+        }
+    }
+}
+
+/// Request to start a rolling daemon restart
+#[derive(Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StartUpgradeRequest {
+    /// Daemons to restart, in order (default: zfs, net, vmm)
+    pub daemons: Option<Vec<String>>,
+    /// Node IDs or names, upgraded in this order (default: all connected nodes)
+    pub nodes: Option<Vec<String>>,
+    /// How long to wait for VMs with vhost-user NICs to stop before
+    /// restarting net (default: 300)
+    pub drain_timeout_secs: Option<u64>,
+    /// Restart net even if VMs did not drain in time
+    #[serde(default)]
+    pub force: bool,
+}
+
+/// One daemon restart within an upgrade
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UpgradeStep {
+    pub daemon: String,
+    /// pending, running, succeeded or failed
+    pub phase: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub previous_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    /// VMs that lost networking because a forced net restart went ahead
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub undrained_vms: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl From<StepStatus> for UpgradeStep {
+    fn from(s: StepStatus) -> Self {
+        Self {
+            daemon: s.daemon.to_string(),
+            phase: s.phase.to_string(),
+            previous_version: s.previous_version,
+            version: s.version,
+            undrained_vms: s.undrained_vms,
+            message: s.message,
+        }
+    }
+}
+
+/// Upgrade progress of one node
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeUpgrade {
+    pub node_id: String,
+    pub node_name: String,
+    pub phase: String,
+    pub steps: Vec<UpgradeStep>,
+}
+
+impl From<NodeUpgradeStatus> for NodeUpgrade {
+    fn from(n: NodeUpgradeStatus) -> Self {
+        Self {
+            node_id: n.node_id,
+            node_name: n.node_name,
+            phase: n.phase.to_string(),
+            steps: n.steps.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+/// Rolling daemon restart across nodes
+#[derive(Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct Upgrade {
+    pub id: String,
+    pub phase: String,
+    pub daemons: Vec<String>,
+    pub force: bool,
+    pub started_at: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    pub nodes: Vec<NodeUpgrade>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl From<UpgradeStatus> for Upgrade {
+    fn from(u: UpgradeStatus) -> Self {
+        Self {
+            id: u.id,
+            phase: u.phase.to_string(),
+            daemons: u.daemons.iter().map(ToString::to_string).collect(),
+            force: u.force,
+            started_at: u.started_at,
+            finished_at: u.finished_at,
+            nodes: u.nodes.into_iter().map(Into::into).collect(),
+            message: u.message,
+        }
+    }
+}
+
+impl From<UpgradeError> for ApiError {
+    fn from(e: UpgradeError) -> Self {
+        let code = match e {
+            UpgradeError::InProgress(_) => 409,
+            UpgradeError::NodeNotConnected(_) | UpgradeError::NoNodes => 422,
+            UpgradeError::NoDaemons => 400,
+            UpgradeError::RaftUnhealthy(_) => 503,
+        };
+        ApiError {
+            error: e.to_string(),
+            code,
+        }
+    }
+}
+
+fn controller(state: &AppState) -> Result<&Arc<UpgradeController>, ApiError> {
+    state.upgrades.as_ref().ok_or_else(|| ApiError {
+        error: "Upgrade controller not available".to_string(),
+        code: 503,
+    })
+}
+
+/// List daemon versions of all connected nodes
+#[utoipa::path(
+    get,
+    path = "/v1/nodes/versions",
+    responses(
+        (status = 200, description = "Daemon versions per connected node", body = Vec<NodeDaemonVersions>),
+        (status = 503, description = "Upgrade controller not available", body = ApiError)
+    ),
+    tag = "upgrades"
+)]
+pub async fn list_node_versions(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<NodeDaemonVersions>>, ApiError> {
+    let versions = controller(&state)?.versions().await;
+    Ok(Json(versions.into_iter().map(Into::into).collect()))
+}
+
+/// Start a rolling daemon restart
+///
+/// Restarts the given daemons node by node, one at a time, after the new
+/// packages have been installed on the nodes.
+#[utoipa::path(
+    post,
+    path = "/v1/upgrades",
+    request_body = StartUpgradeRequest,
+    responses(
+        (status = 202, description = "Upgrade started", body = Upgrade),
+        (status = 400, description = "Invalid daemon list", body = ApiError),
+        (status = 409, description = "An upgrade is already running", body = ApiError),
+        (status = 422, description = "Node not connected", body = ApiError),
+        (status = 503, description = "Not the leader or raft unhealthy", body = ApiError)
+    ),
+    tag = "upgrades"
+)]
+pub async fn start_upgrade(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<StartUpgradeRequest>,
+) -> Result<(StatusCode, Json<Upgrade>), ApiError> {
+    let daemons = match req.daemons {
+        Some(names) => names
+            .iter()
+            .map(|n| n.parse::<Daemon>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|error| ApiError { error, code: 400 })?,
+        None => Daemon::ALL.to_vec(),
+    };
+    let plan = UpgradePlan {
+        daemons,
+        nodes: req.nodes.unwrap_or_default(),
+        drain_timeout: Duration::from_secs(
+            req.drain_timeout_secs.unwrap_or(DEFAULT_DRAIN_TIMEOUT_SECS),
+        ),
+        force: req.force,
+    };
+    let status = controller(&state)?.start(plan).await?;
+    Ok((StatusCode::ACCEPTED, Json(status.into())))
+}
+
+/// Get the running or last finished upgrade
+#[utoipa::path(
+    get,
+    path = "/v1/upgrades/current",
+    responses(
+        (status = 200, description = "Upgrade progress", body = Upgrade),
+        (status = 404, description = "No upgrade has run", body = ApiError),
+        (status = 503, description = "Upgrade controller not available", body = ApiError)
+    ),
+    tag = "upgrades"
+)]
+pub async fn get_current_upgrade(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Upgrade>, ApiError> {
+    match controller(&state)?.current().await {
+        Some(status) => Ok(Json(status.into())),
+        None => Err(ApiError {
+            error: "No upgrade has run on this peer".to_string(),
+            code: 404,
+        }),
+    }
+}
@@ -162,6 +162,11 @@ pub async fn create_vm(
         image: req.image,
         user_data: None,
         desired_state,
+        provisioning: vec![],
+        ssh_key_ids: vec![],
+        run_once: false,
+        disk: None,
+        placement: None,
     };
 
     let store_req = StoreCreateVmRequest { spec };
@@ -1,4 +1,7 @@
+mod console;
 mod handlers;
+mod rate_limit;
+mod request_id;
 mod routes;
 pub mod ui_handlers;
 pub mod ui_types;
@@ -0,0 +1,207 @@
+//! Admission control for the REST API.
+//!
+//! Applies the shared [`Limiter`] in two places. [`limit_requests`] runs
+//! ahead of auth, so a client stuck in a retry loop is turned away before
+//! it costs a JWT validation or a Raft write. It can only key on the peer
+//! address there: a credential that hasn't been validated says nothing
+//! about who sent it. [`limit_accounts`] runs once auth has validated the
+//! credential and charges the account instead — behind the UI's reverse
+//! proxy every browser shares one address but not one account.
+//!
+//! A request that never reaches [`limit_accounts`] (a rejected or missing
+//! credential, or a route without auth) is charged to its peer address
+//! when it completes; once that bucket is empty, requests from the address
+//! are turned away before auth, with or without a credential. Behind a
+//! reverse proxy that bucket is the proxy's, shared by all its clients.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{StatusCode, header::AUTHORIZATION, header::RETRY_AFTER},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use mvirt_common::limits::{Limiter, Rejection};
+
+use super::handlers::{ApiError, AppState};
+use crate::auth::AuthContext;
+
+/// Set by [`limit_accounts`] once it has charged the caller's account.
+#[derive(Clone, Default)]
+struct Charged(Arc<AtomicBool>);
+
+pub async fn limit_requests(
+    State(limiter): State<Arc<Limiter>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let peer = req
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| format!("ip:{}", addr.ip()));
+    let credentialed =
+        req.headers().contains_key(AUTHORIZATION) || req.uri().query().is_some_and(has_token);
+
+    let _in_flight = match limiter.enter() {
+        Ok(guard) => guard,
+        Err(rejection) => return too_many_requests(rejection),
+    };
+    let Some(peer) = peer else {
+        return next.run(req).await;
+    };
+    if !credentialed {
+        if let Err(rejection) = limiter.take(&peer) {
+            return too_many_requests(rejection);
+        }
+        return next.run(req).await;
+    }
+
+    // Charged to the account if auth accepts the credential, else to the
+    // peer once we know
+    if let Err(rejection) = limiter.check(&peer) {
+        return too_many_requests(rejection);
+    }
+    let charged = Charged::default();
+    req.extensions_mut().insert(charged.clone());
+    let response = next.run(req).await;
+    if !charged.0.load(Ordering::Acquire) {
+        let _ = limiter.take(&peer);
+    }
+    response
+}
+
+/// Charges the account auth has attached to the request. Sits inside
+/// `require_auth`; without an authenticated account it does nothing.
+pub async fn limit_accounts(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(limiter) = &state.limiter else {
+        return next.run(req).await;
+    };
+    let Some(ctx) = req.extensions().get::<AuthContext>() else {
+        return next.run(req).await;
+    };
+    if let Err(rejection) = limiter.take(&format!("account:{}", ctx.account.id)) {
+        return too_many_requests(rejection);
+    }
+    if let Some(charged) = req.extensions().get::<Charged>() {
+        charged.0.store(true, Ordering::Release);
+    }
+    next.run(req).await
+}
+
+/// Whether a query string carries a WebSocket access token.
+fn has_token(query: &str) -> bool {
+    query
+        .split('&')
+        .any(|pair| pair.starts_with("access_token="))
+}
+
+fn too_many_requests(rejection: Rejection) -> Response {
+    let mut response = ApiError {
+        error: rejection.message().to_string(),
+        code: 429,
+    }
+    .into_response();
+    *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+    response
+        .headers_mut()
+        .insert(RETRY_AFTER, rejection.retry_after_secs().into());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{Router, body::Body, middleware, routing::get};
+    use mvirt_common::limits::LimitConfig;
+    use tower::ServiceExt;
+
+    fn router(limiter: Arc<Limiter>) -> Router {
+        Router::new()
+            .route("/", get(|| async { "ok" }))
+            .route(
+                "/denied",
+                get(|| async { StatusCode::UNAUTHORIZED.into_response() }),
+            )
+            .layer(middleware::from_fn_with_state(limiter, limit_requests))
+    }
+
+    fn request(path: &str, peer: [u8; 4], token: Option<&str>) -> Request {
+        let mut req = Request::builder().uri(path);
+        if let Some(token) = token {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+        let mut req = req.body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(SocketAddr::from((peer, 1234))));
+        req
+    }
+
+    fn limiter() -> Arc<Limiter> {
+        Arc::new(Limiter::new(LimitConfig {
+            requests_per_sec: 1,
+            burst: 1,
+            max_concurrent: 0,
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_random_tokens_share_the_peer_bucket() {
+        let limiter = limiter();
+        let app = router(limiter.clone());
+        // Every rejected credential costs the peer a token
+        for token in ["a", "b"] {
+            let resp = app
+                .clone()
+                .oneshot(request("/denied", [192, 0, 2, 1], Some(token)))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        }
+        let resp = app
+            .clone()
+            .oneshot(request("/denied", [192, 0, 2, 1], Some("c")))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(resp.headers().contains_key(RETRY_AFTER));
+
+        // Another peer is unaffected
+        let resp = app
+            .oneshot(request("/", [192, 0, 2, 2], None))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_unauthenticated_requests_charge_the_peer() {
+        let app = router(limiter());
+        for _ in 0..2 {
+            let resp = app
+                .clone()
+                .oneshot(request("/", [192, 0, 2, 1], None))
+                .await
+                .unwrap();
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let resp = app
+            .oneshot(request("/", [192, 0, 2, 1], None))
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn test_has_token() {
+        assert!(has_token("access_token=x"));
+        assert!(has_token("tail=10&access_token=x"));
+        assert!(!has_token("tail=10"));
+    }
+}
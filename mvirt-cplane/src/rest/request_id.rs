@@ -0,0 +1,23 @@
+//! Request IDs for the REST API.
+//!
+//! Takes the caller's `x-request-id` or makes one up, and runs the request
+//! under it: the Raft commands it submits carry it, so the reconciles
+//! they trigger and the daemon calls those make log the same ID (see
+//! [`mvirt_common::request_id`]). The ID is echoed in the response headers
+//! and in the body of error responses.
+
+use axum::{extract::Request, http::HeaderValue, middleware::Next, response::Response};
+use mvirt_common::request_id::{self, HEADER};
+use tracing::{Instrument, info_span};
+
+pub async fn assign_request_id(mut req: Request, next: Next) -> Response {
+    let id = request_id::from_headers(req.headers()).unwrap_or_else(request_id::generate);
+    let value = HeaderValue::from_str(&id).expect("request IDs are valid header values");
+    req.headers_mut().insert(HEADER, value.clone());
+    let span =
+        info_span!("request", request_id = %id, method = %req.method(), path = %req.uri().path());
+
+    let mut response = request_id::scope(id, next.run(req).instrument(span)).await;
+    response.headers_mut().insert(HEADER, value);
+    response
+}
@@ -6,23 +6,29 @@ use std::sync::Arc;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+use super::console;
 use super::handlers::{self, AppState};
+use super::rate_limit::{limit_accounts, limit_requests};
+use super::request_id::assign_request_id;
 use super::ui_handlers;
 use super::ui_types;
 use crate::auth::require_auth;
+use crate::changes::record_changes;
 
 #[derive(OpenApi)]
 #[openapi(
     info(
         title = "mvirt API Server",
         version = "0.1.0",
-        description = "REST API for the mvirt API Server. Provides distributed state management for Nodes, Networks, NICs, VMs, Volumes, Templates, Security Groups, and Projects via Raft consensus.",
+        description = "REST API for the mvirt API Server. Provides distributed state management for Nodes, Networks, NICs, VMs, Volumes, Templates, Security Groups, Flavors, SSH Keys, and Projects via Raft consensus.",
         license(name = "MIT")
     ),
     tags(
         (name = "system", description = "System information"),
         (name = "controlplane", description = "Control plane management"),
         (name = "nodes", description = "Hypervisor node registration and status"),
+        (name = "upgrades", description = "Daemon versions and rolling restarts"),
+        (name = "jobs", description = "Long-running operations of this API server"),
         (name = "orgs", description = "Organization management (tenancy container above Project)"),
         (name = "projects", description = "Project management"),
         (name = "clusters", description = "Cluster management (named groups of Nodes within an Org)"),
@@ -33,9 +39,14 @@ use crate::auth::require_auth;
         (name = "vms", description = "VM CRUD and lifecycle operations"),
         (name = "storage", description = "Volumes, templates, and storage pool"),
         (name = "security-groups", description = "Security group and firewall rule management"),
+        (name = "flavors", description = "VM profiles (standard sizes and defaults)"),
+        (name = "ssh-keys", description = "Project SSH keys injected via cloud-init"),
         (name = "service-accounts", description = "Project-scoped service accounts and their static API keys (ADR-0004)"),
         (name = "pods", description = "Pod and container management (stub)"),
-        (name = "logs", description = "Audit log queries")
+        (name = "logs", description = "Audit log queries"),
+        (name = "changes", description = "Per-resource change history of mutating API calls"),
+        (name = "webhooks", description = "Lifecycle event webhooks and their deliveries"),
+        (name = "notifications", description = "Operator notifications raised from cluster events")
     ),
     paths(
         // System & Cluster (internal)
@@ -49,6 +60,15 @@ use crate::auth::require_auth;
         handlers::list_hypervisor_nodes,
         handlers::update_hypervisor_node_status,
         handlers::deregister_hypervisor_node,
+        handlers::get_node_usage_history,
+        // Upgrades
+        handlers::list_node_versions,
+        handlers::start_upgrade,
+        handlers::get_current_upgrade,
+        // Jobs
+        handlers::list_jobs,
+        handlers::get_job,
+        handlers::cancel_job,
         // Orgs
         ui_handlers::list_orgs,
         ui_handlers::get_org,
@@ -99,14 +119,19 @@ use crate::auth::require_auth;
         ui_handlers::get_vm,
         ui_handlers::create_vm,
         ui_handlers::delete_vm,
+        ui_handlers::undelete_vm,
         ui_handlers::start_vm,
         ui_handlers::stop_vm,
         ui_handlers::kill_vm,
+        ui_handlers::move_vm,
+        ui_handlers::list_vm_migrations,
+        ui_handlers::get_migration,
         // Networks (UI)
         ui_handlers::list_networks,
         ui_handlers::get_network,
         ui_handlers::create_network,
         ui_handlers::delete_network,
+        ui_handlers::undelete_network,
         // NICs (UI)
         ui_handlers::list_nics,
         ui_handlers::get_nic,
@@ -119,10 +144,14 @@ use crate::auth::require_auth;
         ui_handlers::get_volume,
         ui_handlers::create_volume,
         ui_handlers::delete_volume,
+        ui_handlers::undelete_volume,
+        ui_handlers::list_trash,
         ui_handlers::resize_volume,
+        ui_handlers::copy_volume,
         ui_handlers::create_snapshot,
         ui_handlers::list_templates,
         ui_handlers::import_template,
+        ui_handlers::build_template,
         ui_handlers::get_import_job,
         ui_handlers::get_pool_stats,
         // Security Groups
@@ -134,15 +163,40 @@ use crate::auth::require_auth;
         ui_handlers::create_security_group_rule,
         ui_handlers::delete_security_group_rule,
         ui_handlers::update_security_group_rule,
+        // Flavors
+        ui_handlers::list_flavors,
+        ui_handlers::get_flavor,
+        ui_handlers::create_flavor,
+        ui_handlers::delete_flavor,
+        // SSH keys
+        ui_handlers::list_ssh_keys,
+        ui_handlers::get_ssh_key,
+        ui_handlers::create_ssh_key,
+        ui_handlers::delete_ssh_key,
         // Pods (stub)
         ui_handlers::list_pods,
         ui_handlers::get_pod,
+        ui_handlers::get_pod_stats,
         ui_handlers::create_pod,
         ui_handlers::delete_pod,
         ui_handlers::start_pod,
         ui_handlers::stop_pod,
+        ui_handlers::pause_pod,
+        ui_handlers::resume_pod,
         // Logs
         ui_handlers::query_logs,
+        // Change history
+        ui_handlers::get_change_history,
+        // Webhooks
+        ui_handlers::list_webhooks,
+        ui_handlers::get_webhook,
+        ui_handlers::create_webhook,
+        ui_handlers::delete_webhook,
+        ui_handlers::list_webhook_deliveries,
+        // Notifications
+        ui_handlers::list_notifications,
+        ui_handlers::mark_notification_read,
+        ui_handlers::mark_all_notifications_read,
     ),
     components(schemas(
         // Internal API schemas
@@ -157,10 +211,23 @@ use crate::auth::require_auth;
         handlers::RemovePeerResponse,
         handlers::RegisterHypervisorNodeRequest,
         handlers::HypervisorNodeResources,
+        handlers::HypervisorHostTelemetry,
         handlers::HypervisorNode,
         handlers::ListNodesQuery,
         handlers::UpdateNodeStatusRequest,
         handlers::DeregisterNodeResponse,
+        handlers::UsageHistoryQuery,
+        handlers::UsageGauge,
+        handlers::NodeUsagePoint,
+        handlers::NodeUsageHistory,
+        handlers::DaemonVersionInfo,
+        handlers::NodeDaemonVersions,
+        handlers::StartUpgradeRequest,
+        handlers::UpgradeStep,
+        handlers::NodeUpgrade,
+        handlers::Upgrade,
+        handlers::Job,
+        handlers::ListJobsQuery,
         handlers::ApiError,
         // UI schemas - Orgs
         ui_types::UiOrg,
@@ -203,9 +270,21 @@ use crate::auth::require_auth;
         ui_types::UiVm,
         ui_types::UiVmState,
         ui_types::UiVmConfig,
+        ui_types::UiVmDisk,
+        ui_types::UiVmPlacement,
+        ui_types::UiPlacementPolicy,
         ui_types::UiCreateVmRequest,
         ui_types::UiCreateVmConfig,
+        ui_types::UiProvisioningHook,
+        ui_types::UiWaitCondition,
+        ui_types::UiHookAction,
+        ui_types::UiHookStatus,
+        ui_types::UiHookPhase,
         ui_types::VmListResponse,
+        ui_types::UiMoveVmRequest,
+        ui_types::UiMigration,
+        ui_types::UiMigrationState,
+        ui_types::MigrationListResponse,
         // UI schemas - Networks
         ui_types::UiNetwork,
         ui_types::UiCreateNetworkRequest,
@@ -220,14 +299,20 @@ use crate::auth::require_auth;
         ui_types::UiSnapshot,
         ui_types::UiCreateVolumeRequest,
         ui_types::UiResizeVolumeRequest,
+        ui_types::UiCopyVolumeRequest,
         ui_types::UiCreateSnapshotRequest,
         ui_types::VolumeListResponse,
         ui_types::UiTemplate,
         ui_types::UiImportTemplateRequest,
+        ui_types::UiBuildTemplateRequest,
         ui_types::TemplateListResponse,
         ui_types::UiImportJob,
         ui_types::UiImportJobState,
         ui_types::UiPoolStats,
+        // UI schemas - Trash
+        ui_types::UiTrashKind,
+        ui_types::UiTrashItem,
+        ui_types::TrashListResponse,
         // UI schemas - Security Groups
         ui_types::UiSecurityGroup,
         ui_types::UiSecurityGroupRule,
@@ -238,17 +323,41 @@ use crate::auth::require_auth;
         ui_types::UiCreateSecurityGroupRuleRequest,
         ui_types::UiUpdateSecurityGroupRuleRequest,
         ui_types::SecurityGroupListResponse,
+        // UI schemas - Flavors
+        ui_types::UiFlavor,
+        ui_types::UiCreateFlavorRequest,
+        ui_types::FlavorListResponse,
+        // UI schemas - SSH keys
+        ui_types::UiSshKey,
+        ui_types::UiCreateSshKeyRequest,
+        ui_types::SshKeyListResponse,
         // UI schemas - Pods
         ui_types::UiPod,
         ui_types::UiPodState,
         ui_types::UiContainer,
         ui_types::UiContainerState,
         ui_types::UiContainerSpec,
+        ui_types::UiPodStats,
+        ui_types::UiContainerStats,
         ui_types::UiCreatePodRequest,
         ui_types::PodListResponse,
         // UI schemas - Logs
         ui_handlers::UiLogEntry,
         ui_handlers::LogsResponse,
+        // UI schemas - Change history
+        ui_types::UiChangeRecord,
+        ui_types::UiFieldChange,
+        ui_types::ChangeHistoryResponse,
+        // UI schemas - Webhooks
+        ui_types::UiWebhook,
+        ui_types::UiCreateWebhookRequest,
+        ui_types::WebhookListResponse,
+        ui_types::UiWebhookDelivery,
+        ui_types::WebhookDeliveryListResponse,
+        // UI schemas - Notifications
+        ui_types::UiNotification,
+        ui_types::UiNotificationType,
+        ui_types::UiInvolvedObject,
     ))
 )]
 pub struct ApiDoc;
@@ -282,7 +391,19 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             "/nodes/{id}/status",
             patch(handlers::update_hypervisor_node_status),
         )
-        .route("/nodes/{id}", delete(handlers::deregister_hypervisor_node));
+        .route("/nodes/{id}", delete(handlers::deregister_hypervisor_node))
+        .route(
+            "/nodes/{id}/usage-history",
+            get(handlers::get_node_usage_history),
+        )
+        // Upgrades
+        .route("/nodes/versions", get(handlers::list_node_versions))
+        .route("/upgrades", post(handlers::start_upgrade))
+        .route("/upgrades/current", get(handlers::get_current_upgrade))
+        // Jobs
+        .route("/jobs", get(handlers::list_jobs))
+        .route("/jobs/{id}", get(handlers::get_job))
+        .route("/jobs/{id}/cancel", post(handlers::cancel_job));
 
     // Global UI routes (not project-scoped)
     let global_routes = Router::new()
@@ -366,7 +487,25 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         // Logs
         .route("/logs", get(ui_handlers::query_logs))
         .route("/logs/stream", get(ui_handlers::log_events))
-        // Notifications (stub)
+        // Change history
+        .route(
+            "/changes/{resource_id}",
+            get(ui_handlers::get_change_history),
+        )
+        // Webhooks (platform-wide)
+        .route(
+            "/webhooks",
+            get(ui_handlers::list_webhooks).post(ui_handlers::create_webhook),
+        )
+        .route(
+            "/webhooks/{id}",
+            get(ui_handlers::get_webhook).delete(ui_handlers::delete_webhook),
+        )
+        .route(
+            "/webhooks/{id}/deliveries",
+            get(ui_handlers::list_webhook_deliveries),
+        )
+        // Notifications
         .route("/notifications", get(ui_handlers::list_notifications))
         .route(
             "/notifications/{id}/read",
@@ -376,24 +515,44 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             "/notifications/read-all",
             post(ui_handlers::mark_all_notifications_read),
         )
+        // Flavors (platform-wide VM profiles)
+        .route(
+            "/flavors",
+            get(ui_handlers::list_flavors).post(ui_handlers::create_flavor),
+        )
+        .route(
+            "/flavors/{id}",
+            get(ui_handlers::get_flavor).delete(ui_handlers::delete_flavor),
+        )
         // Pods (stub)
         .route("/pods", get(ui_handlers::list_pods))
         .route("/pods", post(ui_handlers::create_pod))
         .route("/pods/{id}", get(ui_handlers::get_pod))
+        .route("/pods/{id}/stats", get(ui_handlers::get_pod_stats))
         .route("/pods/{id}", delete(ui_handlers::delete_pod))
         .route("/pods/{id}/start", post(ui_handlers::start_pod))
         .route("/pods/{id}/stop", post(ui_handlers::stop_pod))
+        .route("/pods/{id}/pause", post(ui_handlers::pause_pod))
+        .route("/pods/{id}/resume", post(ui_handlers::resume_pod))
         // Resource by ID (globally unique IDs)
         // VMs
         .route("/vms/{id}", get(ui_handlers::get_vm))
         .route("/vms/{id}", delete(ui_handlers::delete_vm))
+        .route("/vms/{id}/undelete", post(ui_handlers::undelete_vm))
         .route("/vms/{id}/start", post(ui_handlers::start_vm))
         .route("/vms/{id}/stop", post(ui_handlers::stop_vm))
         .route("/vms/{id}/kill", post(ui_handlers::kill_vm))
-        .route("/vms/{id}/console", get(ui_handlers::console_ws))
+        .route("/vms/{id}/move", post(ui_handlers::move_vm))
+        .route("/vms/{id}/migrations", get(ui_handlers::list_vm_migrations))
+        .route("/vms/{id}/console", get(console::console_ws))
+        .route("/migrations/{id}", get(ui_handlers::get_migration))
         // Networks
         .route("/networks/{id}", get(ui_handlers::get_network))
         .route("/networks/{id}", delete(ui_handlers::delete_network))
+        .route(
+            "/networks/{id}/undelete",
+            post(ui_handlers::undelete_network),
+        )
         // NICs
         .route("/nics/{id}", get(ui_handlers::get_nic))
         .route("/nics/{id}", delete(ui_handlers::delete_nic))
@@ -402,7 +561,9 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         // Volumes
         .route("/volumes/{id}", get(ui_handlers::get_volume))
         .route("/volumes/{id}", delete(ui_handlers::delete_volume))
+        .route("/volumes/{id}/undelete", post(ui_handlers::undelete_volume))
         .route("/volumes/{id}/resize", post(ui_handlers::resize_volume))
+        .route("/volumes/{id}/copy", post(ui_handlers::copy_volume))
         .route(
             "/volumes/{id}/snapshots",
             post(ui_handlers::create_snapshot),
@@ -424,6 +585,11 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             "/security-groups/{sg_id}/rules/{rule_id}",
             delete(ui_handlers::delete_security_group_rule)
                 .patch(ui_handlers::update_security_group_rule),
+        )
+        // SSH keys
+        .route(
+            "/ssh-keys/{id}",
+            get(ui_handlers::get_ssh_key).delete(ui_handlers::delete_ssh_key),
         );
 
     // Project-scoped routes: /v1/projects/{project_slug}/...
@@ -444,9 +610,15 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route("/volumes", post(ui_handlers::create_volume))
         .route("/templates", get(ui_handlers::list_templates))
         .route("/templates/import", post(ui_handlers::import_template))
+        .route("/templates/build", post(ui_handlers::build_template))
+        // Trash (soft-deleted VMs, volumes and networks)
+        .route("/trash", get(ui_handlers::list_trash))
         // Security Groups
         .route("/security-groups", get(ui_handlers::list_security_groups))
         .route("/security-groups", post(ui_handlers::create_security_group))
+        // SSH keys
+        .route("/ssh-keys", get(ui_handlers::list_ssh_keys))
+        .route("/ssh-keys", post(ui_handlers::create_ssh_key))
         // Project members (ADR-0004)
         .route(
             "/members",
@@ -483,6 +655,25 @@ pub fn create_router(state: Arc<AppState>) -> Router {
     // User-facing routes get JWT auth applied when a validator is configured.
     // Internal routes are reached via the cplane-to-cplane network, not the
     // public REST endpoint, and stay unauthenticated for now.
+    // Change recording sits inside auth so it sees the caller; it runs
+    // regardless of `auth_enabled` (actor is empty then).
+    let global_routes = global_routes.layer(middleware::from_fn_with_state(
+        state.clone(),
+        record_changes,
+    ));
+    let project_routes = project_routes.layer(middleware::from_fn_with_state(
+        state.clone(),
+        record_changes,
+    ));
+    // Charges the account auth validated; sits between the two
+    let global_routes = global_routes.layer(middleware::from_fn_with_state(
+        state.clone(),
+        limit_accounts,
+    ));
+    let project_routes = project_routes.layer(middleware::from_fn_with_state(
+        state.clone(),
+        limit_accounts,
+    ));
     let (global_routes, project_routes) = if auth_enabled {
         (
             global_routes.layer(middleware::from_fn_with_state(state.clone(), require_auth)),
@@ -492,26 +683,40 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         (global_routes, project_routes)
     };
 
-    Router::new()
+    let router = Router::new()
         .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", ApiDoc::openapi()))
         .nest("/v1", internal_routes)
         .nest("/v1", global_routes)
         .nest("/v1", bootstrap_routes)
         .nest("/v1/projects/{project_slug}", project_routes)
-        .with_state(state)
-        .layer(
-            // `CorsLayer::permissive()` sets `Access-Control-Allow-Headers: *`,
-            // which per CORS spec does **not** cover `Authorization`. Browsers
-            // (Firefox first, then Chrome) are dropping that header from
-            // requests unless it's listed explicitly. List the headers we
-            // actually use so the JWT-auth Authorization header survives.
-            tower_http::cors::CorsLayer::new()
-                .allow_origin(tower_http::cors::Any)
-                .allow_methods(tower_http::cors::Any)
-                .allow_headers([
-                    axum::http::header::AUTHORIZATION,
-                    axum::http::header::CONTENT_TYPE,
-                    axum::http::header::ACCEPT,
-                ]),
-        )
+        .with_state(state.clone());
+
+    // Admission runs ahead of auth but inside CORS, so a 429 still carries
+    // the CORS headers the browser needs to read it.
+    let router = match state.limiter.clone() {
+        Some(limiter) => router.layer(middleware::from_fn_with_state(limiter, limit_requests)),
+        None => router,
+    };
+    // Outside admission, so rejected requests get an ID too
+    let router = router.layer(middleware::from_fn(assign_request_id));
+
+    router.layer(
+        // `CorsLayer::permissive()` sets `Access-Control-Allow-Headers: *`,
+        // which per CORS spec does **not** cover `Authorization`. Browsers
+        // (Firefox first, then Chrome) are dropping that header from
+        // requests unless it's listed explicitly. List the headers we
+        // actually use so the JWT-auth Authorization header survives.
+        tower_http::cors::CorsLayer::new()
+            .allow_origin(tower_http::cors::Any)
+            .allow_methods(tower_http::cors::Any)
+            .allow_headers([
+                axum::http::header::AUTHORIZATION,
+                axum::http::header::CONTENT_TYPE,
+                axum::http::header::ACCEPT,
+                axum::http::HeaderName::from_static(mvirt_common::request_id::HEADER),
+            ])
+            .expose_headers([axum::http::HeaderName::from_static(
+                mvirt_common::request_id::HEADER,
+            )]),
+    )
 }
@@ -9,14 +9,20 @@ use axum::{
     response::{IntoResponse, Sse, sse::Event as SseEvent},
 };
 use futures::stream::{Stream, StreamExt};
+use mvirt_common::{naming, netboot};
 use serde::{Deserialize, Serialize};
 use std::{convert::Infallible, sync::Arc, time::Duration};
 use utoipa::ToSchema;
 
 use super::handlers::{ApiError, AppState};
 use super::ui_types::*;
-use crate::command::{VmDesiredState, VmPhase, VmSpec, VmStatus};
+use crate::command::{
+    TemplateBuild, TemplatePhase, TrashKind, VmData, VmDesiredState, VmDisk, VmPhase, VmSpec,
+    VmStatus,
+};
+use crate::scheduler::{Scheduler, group_members, placement_violation};
 use crate::store::{
+    BuildTemplateRequest as StoreBuildTemplateRequest, CopyVolumeRequest as StoreCopyVolumeRequest,
     CreateClusterRequest as StoreCreateClusterRequest,
     CreateMembershipRequest as StoreCreateMembershipRequest,
     CreateNetworkRequest as StoreCreateNetworkRequest, CreateNicRequest as StoreCreateNicRequest,
@@ -24,7 +30,7 @@ use crate::store::{
     CreateOrgRequest as StoreCreateOrgRequest, CreateProjectRequest as StoreCreateProjectRequest,
     CreateSnapshotRequest as StoreCreateSnapshotRequest,
     CreateTemplateRequest as StoreCreateTemplateRequest, CreateVmRequest as StoreCreateVmRequest,
-    CreateVolumeRequest as StoreCreateVolumeRequest,
+    CreateVolumeRequest as StoreCreateVolumeRequest, MigrateVmRequest as StoreMigrateVmRequest,
     RedeemOnboardingTokenRequest as StoreRedeemOnboardingTokenRequest,
     ResizeVolumeRequest as StoreResizeVolumeRequest,
     UpdateClusterRequest as StoreUpdateClusterRequest, UpdateOrgRequest as StoreUpdateOrgRequest,
@@ -89,7 +95,7 @@ fn require_org_admin(
 /// exist (regardless of auth state — otherwise endpoint shapes drift
 /// between dev and prod), then in production also requires project-admin
 /// (cascading from org-admin of the parent Org and from platform-admin).
-async fn require_project_access(
+pub(super) async fn require_project_access(
     state: &AppState,
     auth: &Option<crate::auth::AuthenticatedAccount>,
     project_slug: &str,
@@ -1306,16 +1312,17 @@ pub async fn list_vms(
     require_project_access(&state, &auth, &project_slug).await?;
     let vms = state.store.list_vms_by_project(&project_slug).await?;
 
-    // Further filter by node if specified
+    // Further filter by node if specified; trashed VMs only show in the trash
     let vms: Vec<UiVm> = vms
-        .into_iter()
+        .iter()
+        .filter(|vm| vm.trashed.is_none())
         .filter(|vm| {
             query
                 .node_id
                 .as_ref()
                 .is_none_or(|nid| vm.status.node_id.as_deref() == Some(nid.as_str()))
         })
-        .map(UiVm::from)
+        .map(|vm| ui_vm_with_placement(vm.clone(), &vms))
         .collect();
 
     Ok(Json(VmListResponse { vms }))
@@ -1339,7 +1346,25 @@ pub async fn get_vm(
         code: 404,
     })?;
     require_project_access(&state, &auth, &vm.spec.project_slug).await?;
-    Ok(Json(UiVm::from(vm)))
+    let project_vms = if vm.spec.placement.is_some() {
+        state
+            .store
+            .list_vms_by_project(&vm.spec.project_slug)
+            .await?
+    } else {
+        vec![]
+    };
+    Ok(Json(ui_vm_with_placement(vm, &project_vms)))
+}
+
+/// UI form of a VM, with how it breaks its placement group's policy given
+/// the VMs of its project.
+fn ui_vm_with_placement(vm: VmData, project_vms: &[VmData]) -> UiVm {
+    let violation = placement_violation(&vm, &group_members(&vm.spec, project_vms));
+    UiVm {
+        placement_violation: violation,
+        ..UiVm::from(vm)
+    }
 }
 
 /// Create a new VM
@@ -1351,31 +1376,392 @@ pub async fn create_vm(
     Json(req): Json<UiCreateVmRequest>,
 ) -> Result<Json<UiVm>, ApiError> {
     require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_name("VM", &req.name)?;
+    validate_provisioning(&req.config.provisioning)?;
+    if let Some(placement) = &req.config.placement {
+        validate_slug(&placement.group, "placement.group")?;
+    }
+
+    let flavor = match req.flavor.as_deref().filter(|f| !f.is_empty()) {
+        Some(flavor) => Some(resolve_flavor(&state, flavor).await?),
+        None => None,
+    };
+    let cpu_cores = flavor_field("vcpus", req.config.vcpus, flavor.as_ref().map(|f| f.vcpus))?;
+    let memory_mb = flavor_field(
+        "memoryMb",
+        req.config.memory_mb,
+        flavor.as_ref().map(|f| f.memory_mb),
+    )?;
+
+    let min_disk_bytes = flavor.as_ref().and_then(|f| f.disk_size_bytes);
+    let disk = match &req.config.disk {
+        Some(_) if !req.config.volume_id.is_empty() => {
+            return Err(ApiError {
+                error: "Set either volumeId or disk, not both".into(),
+                code: 400,
+            });
+        }
+        Some(disk) => Some(resolve_disk(&state, &project_slug, disk, min_disk_bytes).await?),
+        None if req.config.volume_id.is_empty() => {
+            return Err(ApiError {
+                error: "volumeId or disk is required".into(),
+                code: 400,
+            });
+        }
+        None => None,
+    };
+    // A composed disk lives on its template's node, so the VM does too.
+    let node_selector = match &disk {
+        Some(d) => {
+            if let Some(selector) = &req.node_selector
+                && selector != &d.node_id
+            {
+                return Err(ApiError {
+                    error: format!(
+                        "nodeSelector '{}' conflicts with the disk template on node '{}'",
+                        selector, d.node_id
+                    ),
+                    code: 400,
+                });
+            }
+            Some(d.node_id.clone())
+        }
+        None => req.node_selector,
+    };
+
+    if disk.is_none()
+        && let Some(min_bytes) = min_disk_bytes
+    {
+        let volume = state
+            .store
+            .get_volume(&req.config.volume_id)
+            .await?
+            .ok_or_else(|| ApiError {
+                error: format!("Volume '{}' not found", req.config.volume_id),
+                code: 404,
+            })?;
+        if volume.spec.size_bytes < min_bytes {
+            return Err(ApiError {
+                error: format!(
+                    "Volume '{}' is smaller than the flavor's disk size ({} < {} bytes)",
+                    volume.spec.name, volume.spec.size_bytes, min_bytes
+                ),
+                code: 400,
+            });
+        }
+    }
+
+    let ssh_key_ids = resolve_ssh_keys(&state, &project_slug, &req.config.ssh_key_ids).await?;
+
+    // Names derived for a NIC or disk created alongside must be valid too.
+    if req.config.nic_id.is_empty() {
+        naming::validate_name("NIC", &format!("{}-nic0", req.name))?;
+    }
+    if disk.is_some() {
+        naming::validate_name("Volume", &format!("{}-disk0", req.name))?;
+    }
+
+    // No NIC given: create one in the flavor's default network. Tracked so
+    // it can be rolled back if the VM itself is rejected.
+    let mut created_nic = None;
+    let nic_id = if req.config.nic_id.is_empty() {
+        let Some(network_name) = flavor.as_ref().and_then(|f| f.network.as_deref()) else {
+            return Err(ApiError {
+                error: "nicId is required unless the flavor names a default network".into(),
+                code: 400,
+            });
+        };
+        let network = state
+            .store
+            .list_networks_by_project(&project_slug)
+            .await?
+            .into_iter()
+            .find(|n| n.name == network_name)
+            .ok_or_else(|| ApiError {
+                error: format!(
+                    "Flavor network '{}' not found in project '{}'",
+                    network_name, project_slug
+                ),
+                code: 404,
+            })?;
+        let nic = state
+            .store
+            .create_nic(StoreCreateNicRequest {
+                project_slug: project_slug.clone(),
+                network_id: network.id,
+                name: Some(format!("{}-nic0", req.name)),
+                mac_address: None,
+                ipv4_address: None,
+                ipv6_address: None,
+                routed_ipv4_prefixes: vec![],
+                routed_ipv6_prefixes: vec![],
+                security_group_id: None,
+            })
+            .await?;
+        state
+            .audit
+            .nic_created(&nic.id, &nic.spec.network_id, &nic.spec.mac_address);
+        created_nic = Some(nic.id.clone());
+        nic.id
+    } else {
+        req.config.nic_id
+    };
+
+    let mut created_volume = None;
+    let volume_id = match &disk {
+        Some(d) => {
+            let store_req = StoreCreateVolumeRequest {
+                project_slug: project_slug.clone(),
+                node_id: d.node_id.clone(),
+                name: format!("{}-disk0", req.name),
+                size_bytes: d.spec.size_bytes,
+                template_id: Some(d.spec.template_id.clone()),
+            };
+            match state.store.create_volume(store_req).await {
+                Ok(volume) => {
+                    state.audit.volume_created(&volume.id, &volume.spec.name);
+                    created_volume = Some(volume.id.clone());
+                    volume.id
+                }
+                Err(e) => {
+                    if let Some(nic_id) = created_nic
+                        && state.store.delete_nic(&nic_id).await.is_ok()
+                    {
+                        state.audit.nic_deleted(&nic_id);
+                    }
+                    return Err(e.into());
+                }
+            }
+        }
+        None => req.config.volume_id,
+    };
+
+    let user_data = req
+        .config
+        .user_data
+        .filter(|s| !s.is_empty())
+        .or_else(|| flavor.as_ref().and_then(|f| f.user_data.clone()));
+
     let spec = VmSpec {
         name: req.name.clone(),
         project_slug,
-        node_selector: req.node_selector,
-        cpu_cores: req.config.vcpus,
-        memory_mb: req.config.memory_mb,
-        volume_id: req.config.volume_id,
-        nic_id: req.config.nic_id,
+        node_selector,
+        cpu_cores,
+        memory_mb,
+        volume_id,
+        nic_id,
         image: req.config.image,
-        user_data: req.config.user_data.filter(|s| !s.is_empty()),
+        user_data,
         desired_state: VmDesiredState::Running,
+        provisioning: req
+            .config
+            .provisioning
+            .into_iter()
+            .map(Into::into)
+            .collect(),
+        ssh_key_ids,
+        run_once: false,
+        disk: disk.map(|d| d.spec),
+        placement: req.config.placement.map(Into::into),
     };
 
     let store_req = StoreCreateVmRequest { spec };
 
-    let data = state.store.create_and_schedule_vm(store_req).await?;
+    let data = match state.store.create_and_schedule_vm(store_req).await {
+        Ok(data) => data,
+        Err(e) => {
+            if let Some(nic_id) = created_nic
+                && state.store.delete_nic(&nic_id).await.is_ok()
+            {
+                state.audit.nic_deleted(&nic_id);
+            }
+            if let Some(volume_id) = created_volume
+                && state.store.delete_volume(&volume_id).await.is_ok()
+            {
+                state.audit.volume_deleted(&volume_id);
+            }
+            return Err(e.into());
+        }
+    };
     state.audit.vm_created(&data.id, &data.spec.name);
-    Ok(Json(UiVm::from(data)))
+    if data.spec.placement.is_none() {
+        return Ok(Json(UiVm::from(data)));
+    }
+    let project_vms = state.store.list_vms_by_project(&project_slug).await?;
+    let vm = ui_vm_with_placement(data, &project_vms);
+    if let Some(violation) = &vm.placement_violation {
+        state.audit.vm_placement_violated(&vm.id, violation);
+    }
+    Ok(Json(vm))
+}
+
+/// A declared boot disk, resolved against the project's templates
+struct ComposedDisk {
+    spec: VmDisk,
+    node_id: String,
+}
+
+/// Find the disk's template (by ID or name, Ready only) and settle its
+/// size: the requested size, else the larger of template and flavor size.
+async fn resolve_disk(
+    state: &AppState,
+    project_slug: &str,
+    disk: &UiVmDisk,
+    min_bytes: Option<u64>,
+) -> Result<ComposedDisk, ApiError> {
+    let template = state
+        .store
+        .list_templates_by_project(project_slug)
+        .await?
+        .into_iter()
+        .filter(|t| t.status.phase == TemplatePhase::Ready)
+        .find(|t| t.id == disk.from_template || t.spec.name == disk.from_template)
+        .ok_or_else(|| ApiError {
+            error: format!("Template '{}' not found", disk.from_template),
+            code: 404,
+        })?;
+
+    let min_bytes = min_bytes.unwrap_or(0);
+    let size_bytes = disk
+        .size_bytes
+        .unwrap_or_else(|| template.status.size_bytes.max(min_bytes));
+    if size_bytes < template.status.size_bytes {
+        return Err(ApiError {
+            error: format!(
+                "Disk is smaller than template '{}' ({} < {} bytes)",
+                template.spec.name, size_bytes, template.status.size_bytes
+            ),
+            code: 400,
+        });
+    }
+    if size_bytes < min_bytes {
+        return Err(ApiError {
+            error: format!(
+                "Disk is smaller than the flavor's disk size ({} < {} bytes)",
+                size_bytes, min_bytes
+            ),
+            code: 400,
+        });
+    }
+
+    Ok(ComposedDisk {
+        spec: VmDisk {
+            template_id: template.id,
+            size_bytes,
+        },
+        node_id: template.spec.node_id,
+    })
+}
+
+/// Reject provisioning hooks the controller could never run: duplicate or
+/// empty names, port 0, non-HTTP webhook targets and empty guest commands.
+fn validate_provisioning(hooks: &[UiProvisioningHook]) -> Result<(), ApiError> {
+    let bad = |error: String| ApiError { error, code: 400 };
+    let mut names = std::collections::HashSet::new();
+    for hook in hooks {
+        if hook.name.is_empty() {
+            return Err(bad("Provisioning hook name must not be empty".into()));
+        }
+        if !names.insert(hook.name.as_str()) {
+            return Err(bad(format!("Duplicate provisioning hook '{}'", hook.name)));
+        }
+        if hook
+            .wait
+            .iter()
+            .any(|c| matches!(c, UiWaitCondition::TcpPort { port: 0 }))
+        {
+            return Err(bad(format!(
+                "Provisioning hook '{}': tcpPort needs a non-zero port",
+                hook.name
+            )));
+        }
+        if let Some(UiHookAction::Webhook { url }) = &hook.action
+            && !(url.starts_with("http://") || url.starts_with("https://"))
+        {
+            return Err(bad(format!(
+                "Provisioning hook '{}': webhook url must be http(s)",
+                hook.name
+            )));
+        }
+        if let Some(UiHookAction::GuestCommand { command, .. }) = &hook.action
+            && command.first().is_none_or(|program| program.is_empty())
+        {
+            return Err(bad(format!(
+                "Provisioning hook '{}': guestCommand needs a command",
+                hook.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Map SSH key IDs or names to IDs of keys in the VM's project.
+async fn resolve_ssh_keys(
+    state: &AppState,
+    project_slug: &str,
+    refs: &[String],
+) -> Result<Vec<String>, ApiError> {
+    if refs.is_empty() {
+        return Ok(vec![]);
+    }
+    let keys = state.store.list_ssh_keys(project_slug).await?;
+    let mut ids = Vec::with_capacity(refs.len());
+    for r in refs {
+        let key = keys
+            .iter()
+            .find(|k| &k.id == r || &k.name == r)
+            .ok_or_else(|| ApiError {
+                error: format!("SSH key '{}' not found in project '{}'", r, project_slug),
+                code: 404,
+            })?;
+        if !ids.contains(&key.id) {
+            ids.push(key.id.clone());
+        }
+    }
+    Ok(ids)
+}
+
+/// Resolve a flavor by ID, falling back to its name.
+async fn resolve_flavor(
+    state: &AppState,
+    id_or_name: &str,
+) -> Result<crate::command::FlavorData, ApiError> {
+    let flavor = match state.store.get_flavor(id_or_name).await? {
+        Some(flavor) => Some(flavor),
+        None => state.store.get_flavor_by_name(id_or_name).await?,
+    };
+    flavor.ok_or_else(|| ApiError {
+        error: format!("Flavor '{}' not found", id_or_name),
+        code: 404,
+    })
+}
+
+/// Pick a sizing value from the request or the flavor. A value that
+/// contradicts the flavor is rejected — flavors are the enforced sizes.
+fn flavor_field<T: Copy + PartialEq + std::fmt::Display>(
+    field: &str,
+    requested: Option<T>,
+    from_flavor: Option<T>,
+) -> Result<T, ApiError> {
+    match (requested, from_flavor) {
+        (Some(r), Some(f)) if r != f => Err(ApiError {
+            error: format!("{} {} conflicts with the flavor ({})", field, r, f),
+            code: 400,
+        }),
+        (Some(v), _) | (None, Some(v)) => Ok(v),
+        (None, None) => Err(ApiError {
+            error: format!("{} is required when no flavor is given", field),
+            code: 400,
+        }),
+    }
 }
 
-/// Delete a VM
-#[utoipa::path(delete, path = "/v1/vms/{id}", params(("id" = String, Path)), responses((status = 204), (status = 404, body = ApiError)), tag = "vms")]
+/// Delete a VM, or move it to the trash when a retention is configured
+#[utoipa::path(delete, path = "/v1/vms/{id}", params(("id" = String, Path), ("purge" = Option<bool>, Query)), responses((status = 204), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "vms")]
 pub async fn delete_vm(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(query): Query<DeleteQuery>,
     auth: Option<crate::auth::AuthenticatedAccount>,
 ) -> Result<StatusCode, ApiError> {
     if state.jwt_validator.is_some() {
@@ -1385,11 +1771,43 @@ pub async fn delete_vm(
         })?;
         require_project_access(&state, &auth, &vm.spec.project_slug).await?;
     }
-    state.store.delete_vm(&id).await?;
-    state.audit.vm_deleted(&id);
+    if let Some(purge_at) = trash_purge_at(&state, &query) {
+        state.store.trash_vm(&id, purge_at.clone()).await?;
+        state.audit.resource_trashed("VM", &id, &purge_at);
+    } else {
+        state.store.delete_vm(&id).await?;
+        state.audit.vm_deleted(&id);
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Restore a VM from the trash. It comes back stopped.
+#[utoipa::path(post, path = "/v1/vms/{id}/undelete", params(("id" = String, Path)), responses((status = 200, body = UiVm), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "vms")]
+pub async fn undelete_vm(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<UiVm>, ApiError> {
+    if state.jwt_validator.is_some() {
+        let vm = state.store.get_vm(&id).await?.ok_or_else(|| ApiError {
+            error: format!("VM '{}' not found", id),
+            code: 404,
+        })?;
+        require_project_access(&state, &auth, &vm.spec.project_slug).await?;
+    }
+    let vm = state.store.restore_vm(&id).await?;
+    state.audit.resource_restored("VM", &id);
+    Ok(Json(UiVm::from(vm)))
+}
+
+/// Purge time for a delete that should go to the trash: a retention is
+/// configured and the caller didn't ask to purge right away.
+fn trash_purge_at(state: &AppState, query: &DeleteQuery) -> Option<String> {
+    let retention = state.trash_retention.filter(|_| !query.purge)?;
+    let retention = chrono::Duration::from_std(retention).ok()?;
+    Some((chrono::Utc::now() + retention).to_rfc3339())
+}
+
 /// Start a VM
 #[utoipa::path(post, path = "/v1/vms/{id}/start", params(("id" = String, Path)), responses((status = 200, body = UiVm), (status = 404, body = ApiError)), tag = "vms")]
 pub async fn start_vm(
@@ -1508,6 +1926,95 @@ pub async fn kill_vm(
     Ok(Json(UiVm::from(vm)))
 }
 
+/// Move a stopped VM to another node
+///
+/// Copies the boot volume to the target, re-creates the NIC there and
+/// removes the VM from the source node. The returned migration reports
+/// progress.
+#[utoipa::path(post, path = "/v1/vms/{id}/move", params(("id" = String, Path)), request_body = UiMoveVmRequest, responses((status = 200, body = UiMigration), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "vms")]
+pub async fn move_vm(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+    Json(req): Json<UiMoveVmRequest>,
+) -> Result<Json<UiMigration>, ApiError> {
+    let vm = state.store.get_vm(&id).await?.ok_or_else(|| ApiError {
+        error: format!("VM '{}' not found", id),
+        code: 404,
+    })?;
+    require_project_access(&state, &auth, &vm.spec.project_slug).await?;
+    let node = state
+        .store
+        .get_node(&req.target_node_id)
+        .await?
+        .ok_or_else(|| ApiError {
+            error: format!("Node '{}' not found", req.target_node_id),
+            code: 404,
+        })?;
+
+    // The target must be one the scheduler could have picked: online,
+    // matching the selector, with room, and not breaking a spread group.
+    let project_vms = state
+        .store
+        .list_vms_by_project(&vm.spec.project_slug)
+        .await?;
+    let members = group_members(&vm.spec, &project_vms);
+    Scheduler::new()
+        .select_node_in_group(std::slice::from_ref(&node), &vm.spec, &members)
+        .map_err(|e| ApiError {
+            error: format!(
+                "Node '{}' cannot take VM '{}': {}",
+                node.name, vm.spec.name, e
+            ),
+            code: 409,
+        })?;
+
+    let store_req = StoreMigrateVmRequest {
+        target_node_id: node.id.clone(),
+    };
+    let data = state.store.migrate_vm(&id, store_req).await?;
+    state.audit.vm_migration_started(&data.id, &id, &node.id);
+    Ok(Json(UiMigration::from(data)))
+}
+
+/// List a VM's migrations
+#[utoipa::path(get, path = "/v1/vms/{id}/migrations", params(("id" = String, Path)), responses((status = 200, body = MigrationListResponse), (status = 404, body = ApiError)), tag = "vms")]
+pub async fn list_vm_migrations(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<MigrationListResponse>, ApiError> {
+    let vm = state.store.get_vm(&id).await?.ok_or_else(|| ApiError {
+        error: format!("VM '{}' not found", id),
+        code: 404,
+    })?;
+    require_project_access(&state, &auth, &vm.spec.project_slug).await?;
+    let mut migrations = state.store.list_migrations(Some(&id)).await?;
+    migrations.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(Json(MigrationListResponse {
+        migrations: migrations.into_iter().map(UiMigration::from).collect(),
+    }))
+}
+
+/// Get a migration by ID (global)
+#[utoipa::path(get, path = "/v1/migrations/{id}", params(("id" = String, Path)), responses((status = 200, body = UiMigration), (status = 404, body = ApiError)), tag = "vms")]
+pub async fn get_migration(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<UiMigration>, ApiError> {
+    let migration = state
+        .store
+        .get_migration(&id)
+        .await?
+        .ok_or_else(|| ApiError {
+            error: "Migration not found".to_string(),
+            code: 404,
+        })?;
+    require_project_access(&state, &auth, &migration.spec.project_slug).await?;
+    Ok(Json(UiMigration::from(migration)))
+}
+
 /// SSE stream for VM events
 pub async fn vm_events(
     State(state): State<Arc<AppState>>,
@@ -1554,7 +2061,11 @@ pub async fn list_networks(
     require_project_access(&state, &auth, &project_slug).await?;
     let networks = state.store.list_networks_by_project(&project_slug).await?;
     Ok(Json(NetworkListResponse {
-        networks: networks.into_iter().map(UiNetwork::from).collect(),
+        networks: networks
+            .into_iter()
+            .filter(|n| n.trashed.is_none())
+            .map(UiNetwork::from)
+            .collect(),
     }))
 }
 
@@ -1588,6 +2099,11 @@ pub async fn create_network(
     Json(req): Json<UiCreateNetworkRequest>,
 ) -> Result<Json<UiNetwork>, ApiError> {
     require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_name("Network", &req.name)?;
+    netboot::validate_network_boot(
+        req.boot_server.as_deref().unwrap_or_default(),
+        req.boot_file.as_deref().unwrap_or_default(),
+    )?;
     let store_req = StoreCreateNetworkRequest {
         project_slug,
         name: req.name.clone(),
@@ -1598,6 +2114,8 @@ pub async fn create_network(
         dns_servers: req.dns_servers,
         ntp_servers: req.ntp_servers,
         is_public: req.is_public,
+        boot_server: req.boot_server.filter(|s| !s.is_empty()),
+        boot_file: req.boot_file.filter(|s| !s.is_empty()),
     };
 
     let data = state.store.create_network(store_req).await?;
@@ -1605,11 +2123,12 @@ pub async fn create_network(
     Ok(Json(UiNetwork::from(data)))
 }
 
-/// Delete a network
-#[utoipa::path(delete, path = "/v1/networks/{id}", params(("id" = String, Path)), responses((status = 204), (status = 404, body = ApiError)), tag = "networks")]
+/// Delete a network, or move it to the trash when a retention is configured
+#[utoipa::path(delete, path = "/v1/networks/{id}", params(("id" = String, Path), ("purge" = Option<bool>, Query)), responses((status = 204), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "networks")]
 pub async fn delete_network(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(query): Query<DeleteQuery>,
     auth: Option<crate::auth::AuthenticatedAccount>,
 ) -> Result<StatusCode, ApiError> {
     if state.jwt_validator.is_some() {
@@ -1623,11 +2142,39 @@ pub async fn delete_network(
             })?;
         require_project_access(&state, &auth, &net.project_slug).await?;
     }
-    state.store.delete_network(&id, false).await?;
-    state.audit.network_deleted(&id);
+    if let Some(purge_at) = trash_purge_at(&state, &query) {
+        state.store.trash_network(&id, purge_at.clone()).await?;
+        state.audit.resource_trashed("Network", &id, &purge_at);
+    } else {
+        state.store.delete_network(&id, false).await?;
+        state.audit.network_deleted(&id);
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Restore a network from the trash
+#[utoipa::path(post, path = "/v1/networks/{id}/undelete", params(("id" = String, Path)), responses((status = 200, body = UiNetwork), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "networks")]
+pub async fn undelete_network(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<UiNetwork>, ApiError> {
+    if state.jwt_validator.is_some() {
+        let net = state
+            .store
+            .get_network(&id)
+            .await?
+            .ok_or_else(|| ApiError {
+                error: format!("Network '{}' not found", id),
+                code: 404,
+            })?;
+        require_project_access(&state, &auth, &net.project_slug).await?;
+    }
+    let network = state.store.restore_network(&id).await?;
+    state.audit.resource_restored("Network", &id);
+    Ok(Json(UiNetwork::from(network)))
+}
+
 // =============================================================================
 // NIC Handlers
 // =============================================================================
@@ -1686,6 +2233,7 @@ pub async fn create_nic(
     Json(req): Json<UiCreateNicRequest>,
 ) -> Result<Json<UiNic>, ApiError> {
     require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_optional_name("NIC", req.name.as_deref())?;
     let store_req = StoreCreateNicRequest {
         project_slug,
         network_id: req.network_id,
@@ -1779,7 +2327,11 @@ pub async fn list_volumes(
         .list_volumes(Some(&project_slug), query.node_id.as_deref())
         .await?;
     Ok(Json(VolumeListResponse {
-        volumes: volumes.into_iter().map(UiVolume::from).collect(),
+        volumes: volumes
+            .into_iter()
+            .filter(|v| v.trashed.is_none())
+            .map(UiVolume::from)
+            .collect(),
     }))
 }
 
@@ -1807,6 +2359,7 @@ pub async fn create_volume(
     Json(req): Json<UiCreateVolumeRequest>,
 ) -> Result<Json<UiVolume>, ApiError> {
     require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_name("Volume", &req.name)?;
     let store_req = StoreCreateVolumeRequest {
         project_slug,
         node_id: req.node_id,
@@ -1820,11 +2373,12 @@ pub async fn create_volume(
     Ok(Json(UiVolume::from(data)))
 }
 
-/// Delete a volume
-#[utoipa::path(delete, path = "/v1/volumes/{id}", params(("id" = String, Path)), responses((status = 204), (status = 404, body = ApiError)), tag = "storage")]
+/// Delete a volume, or move it to the trash when a retention is configured
+#[utoipa::path(delete, path = "/v1/volumes/{id}", params(("id" = String, Path), ("purge" = Option<bool>, Query)), responses((status = 204), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "storage")]
 pub async fn delete_volume(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
+    Query(query): Query<DeleteQuery>,
     auth: Option<crate::auth::AuthenticatedAccount>,
 ) -> Result<StatusCode, ApiError> {
     if state.jwt_validator.is_some() {
@@ -1834,11 +2388,78 @@ pub async fn delete_volume(
         })?;
         require_project_access(&state, &auth, &vol.spec.project_slug).await?;
     }
-    state.store.delete_volume(&id).await?;
-    state.audit.volume_deleted(&id);
+    if let Some(purge_at) = trash_purge_at(&state, &query) {
+        state.store.trash_volume(&id, purge_at.clone()).await?;
+        state.audit.resource_trashed("Volume", &id, &purge_at);
+    } else {
+        state.store.delete_volume(&id).await?;
+        state.audit.volume_deleted(&id);
+    }
     Ok(StatusCode::NO_CONTENT)
 }
 
+/// Restore a volume from the trash
+#[utoipa::path(post, path = "/v1/volumes/{id}/undelete", params(("id" = String, Path)), responses((status = 200, body = UiVolume), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "storage")]
+pub async fn undelete_volume(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<UiVolume>, ApiError> {
+    if state.jwt_validator.is_some() {
+        let vol = state.store.get_volume(&id).await?.ok_or_else(|| ApiError {
+            error: format!("Volume '{}' not found", id),
+            code: 404,
+        })?;
+        require_project_access(&state, &auth, &vol.spec.project_slug).await?;
+    }
+    let volume = state.store.restore_volume(&id).await?;
+    state.audit.resource_restored("Volume", &id);
+    Ok(Json(UiVolume::from(volume)))
+}
+
+/// List the VMs, volumes and networks of a project that are in the trash
+#[utoipa::path(get, path = "/v1/projects/{project_slug}/trash", params(("project_slug" = String, Path)), responses((status = 200, body = TrashListResponse)), tag = "projects")]
+pub async fn list_trash(
+    State(state): State<Arc<AppState>>,
+    Path(project_slug): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<TrashListResponse>, ApiError> {
+    require_project_access(&state, &auth, &project_slug).await?;
+    let mut items = Vec::new();
+    for vm in state.store.list_vms_by_project(&project_slug).await? {
+        if let Some(trashed) = vm.trashed {
+            items.push(UiTrashItem::new(
+                TrashKind::Vm,
+                vm.id,
+                vm.spec.name,
+                trashed,
+            ));
+        }
+    }
+    for vol in state.store.list_volumes(Some(&project_slug), None).await? {
+        if let Some(trashed) = vol.trashed {
+            items.push(UiTrashItem::new(
+                TrashKind::Volume,
+                vol.id,
+                vol.spec.name,
+                trashed,
+            ));
+        }
+    }
+    for net in state.store.list_networks_by_project(&project_slug).await? {
+        if let Some(trashed) = net.trashed {
+            items.push(UiTrashItem::new(
+                TrashKind::Network,
+                net.id,
+                net.name,
+                trashed,
+            ));
+        }
+    }
+    items.sort_by(|a, b| a.purge_at.cmp(&b.purge_at));
+    Ok(Json(TrashListResponse { items }))
+}
+
 /// Resize a volume
 #[utoipa::path(post, path = "/v1/volumes/{id}/resize", params(("id" = String, Path)), request_body = UiResizeVolumeRequest, responses((status = 200, body = UiVolume), (status = 404, body = ApiError)), tag = "storage")]
 pub async fn resize_volume(
@@ -1863,13 +2484,13 @@ pub async fn resize_volume(
     Ok(Json(UiVolume::from(data)))
 }
 
-/// Create a snapshot on a volume
-#[utoipa::path(post, path = "/v1/volumes/{id}/snapshots", params(("id" = String, Path)), request_body = UiCreateSnapshotRequest, responses((status = 200, body = UiVolume), (status = 404, body = ApiError)), tag = "storage")]
-pub async fn create_snapshot(
+/// Copy a volume to another node
+#[utoipa::path(post, path = "/v1/volumes/{id}/copy", params(("id" = String, Path)), request_body = UiCopyVolumeRequest, responses((status = 200, body = UiVolume), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "storage")]
+pub async fn copy_volume(
     State(state): State<Arc<AppState>>,
     Path(id): Path<String>,
     auth: Option<crate::auth::AuthenticatedAccount>,
-    Json(req): Json<UiCreateSnapshotRequest>,
+    Json(req): Json<UiCopyVolumeRequest>,
 ) -> Result<Json<UiVolume>, ApiError> {
     if state.jwt_validator.is_some() {
         let vol = state.store.get_volume(&id).await?.ok_or_else(|| ApiError {
@@ -1878,7 +2499,33 @@ pub async fn create_snapshot(
         })?;
         require_project_access(&state, &auth, &vol.spec.project_slug).await?;
     }
-    let store_req = StoreCreateSnapshotRequest { name: req.name };
+    let store_req = StoreCopyVolumeRequest {
+        node_id: req.node_id,
+        name: req.name,
+    };
+
+    let data = state.store.copy_volume(&id, store_req).await?;
+    state.audit.volume_copied(&data.id, &id, &data.spec.node_id);
+    Ok(Json(UiVolume::from(data)))
+}
+
+/// Create a snapshot on a volume
+#[utoipa::path(post, path = "/v1/volumes/{id}/snapshots", params(("id" = String, Path)), request_body = UiCreateSnapshotRequest, responses((status = 200, body = UiVolume), (status = 404, body = ApiError)), tag = "storage")]
+pub async fn create_snapshot(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+    Json(req): Json<UiCreateSnapshotRequest>,
+) -> Result<Json<UiVolume>, ApiError> {
+    if state.jwt_validator.is_some() {
+        let vol = state.store.get_volume(&id).await?.ok_or_else(|| ApiError {
+            error: format!("Volume '{}' not found", id),
+            code: 404,
+        })?;
+        require_project_access(&state, &auth, &vol.spec.project_slug).await?;
+    }
+    naming::validate_name("Snapshot", &req.name)?;
+    let store_req = StoreCreateSnapshotRequest { name: req.name };
 
     let data = state.store.create_snapshot(&id, store_req).await?;
     state.audit.snapshot_created(&id);
@@ -1908,6 +2555,7 @@ pub async fn import_template(
     Json(req): Json<UiImportTemplateRequest>,
 ) -> Result<Json<UiImportJob>, ApiError> {
     require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_name("Template", &req.name)?;
     let store_req = StoreCreateTemplateRequest {
         project_slug,
         node_id: req.node_id,
@@ -1922,6 +2570,176 @@ pub async fn import_template(
     Ok(Json(UiImportJob::from(data)))
 }
 
+/// Sizing of a template build VM unless the request says otherwise
+const BUILD_VCPUS: u32 = 2;
+const BUILD_MEMORY_MB: u64 = 2048;
+const BUILD_TIMEOUT_SECS: u64 = 3600;
+
+/// Build a template from a cloud-init run
+///
+/// Clones the base template into a volume, boots a VM from it with the
+/// given user-data and promotes the disk to a new template once the VM
+/// powered itself off. Progress is reported like an import job.
+#[utoipa::path(post, path = "/v1/projects/{project_slug}/templates/build", params(("project_slug" = String, Path)), request_body = UiBuildTemplateRequest, responses((status = 200, body = UiImportJob), (status = 404, body = ApiError), (status = 409, body = ApiError)), tag = "storage")]
+pub async fn build_template(
+    State(state): State<Arc<AppState>>,
+    Path(project_slug): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+    Json(req): Json<UiBuildTemplateRequest>,
+) -> Result<Json<UiImportJob>, ApiError> {
+    require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_name("Template", &req.name)?;
+    if req.user_data.trim().is_empty() {
+        return Err(ApiError {
+            error: "userData is required; it must power the VM off when done".into(),
+            code: 400,
+        });
+    }
+
+    let base = state
+        .store
+        .get_template(&req.base_template_id)
+        .await?
+        .filter(|t| t.spec.project_slug == project_slug)
+        .ok_or_else(|| ApiError {
+            error: format!("Template '{}' not found", req.base_template_id),
+            code: 404,
+        })?;
+    if base.status.phase != TemplatePhase::Ready {
+        return Err(ApiError {
+            error: format!("Template '{}' is not ready", base.spec.name),
+            code: 409,
+        });
+    }
+    let network = state
+        .store
+        .get_network(&req.network_id)
+        .await?
+        .filter(|n| n.project_slug == project_slug)
+        .ok_or_else(|| ApiError {
+            error: format!("Network '{}' not found", req.network_id),
+            code: 404,
+        })?;
+
+    // Everything the build needs lives on the base template's node.
+    let node_id = base.spec.node_id.clone();
+    let build_name = format!("{}-build", req.name);
+    naming::validate_name("NIC", &format!("{}-nic0", build_name))?;
+
+    let volume = state
+        .store
+        .create_volume(StoreCreateVolumeRequest {
+            project_slug: project_slug.clone(),
+            node_id: node_id.clone(),
+            name: build_name.clone(),
+            size_bytes: req.size_bytes.unwrap_or(0).max(base.status.size_bytes),
+            template_id: Some(base.id.clone()),
+        })
+        .await?;
+    state.audit.volume_created(&volume.id, &volume.spec.name);
+
+    let nic = match state
+        .store
+        .create_nic(StoreCreateNicRequest {
+            project_slug: project_slug.clone(),
+            network_id: network.id,
+            name: Some(format!("{}-nic0", build_name)),
+            mac_address: None,
+            ipv4_address: None,
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            security_group_id: None,
+        })
+        .await
+    {
+        Ok(nic) => nic,
+        Err(e) => {
+            rollback_build(&state, None, None, Some(&volume.id)).await;
+            return Err(e.into());
+        }
+    };
+    state
+        .audit
+        .nic_created(&nic.id, &nic.spec.network_id, &nic.spec.mac_address);
+
+    let spec = VmSpec {
+        name: build_name,
+        project_slug: project_slug.clone(),
+        node_selector: Some(node_id.clone()),
+        cpu_cores: req.vcpus.unwrap_or(BUILD_VCPUS),
+        memory_mb: req.memory_mb.unwrap_or(BUILD_MEMORY_MB),
+        volume_id: volume.id.clone(),
+        nic_id: nic.id.clone(),
+        image: String::new(),
+        user_data: Some(req.user_data),
+        desired_state: VmDesiredState::Running,
+        provisioning: vec![],
+        ssh_key_ids: vec![],
+        run_once: true,
+        disk: None,
+        placement: None,
+    };
+    let vm = match state
+        .store
+        .create_and_schedule_vm(StoreCreateVmRequest { spec })
+        .await
+    {
+        Ok(vm) => vm,
+        Err(e) => {
+            rollback_build(&state, None, Some(&nic.id), Some(&volume.id)).await;
+            return Err(e.into());
+        }
+    };
+    state.audit.vm_created(&vm.id, &vm.spec.name);
+
+    let store_req = StoreBuildTemplateRequest {
+        project_slug,
+        node_id,
+        name: req.name,
+        build: TemplateBuild {
+            base_template_id: base.id,
+            volume_id: volume.id.clone(),
+            vm_id: vm.id.clone(),
+            nic_id: nic.id.clone(),
+            timeout_secs: req.timeout_secs.unwrap_or(BUILD_TIMEOUT_SECS),
+        },
+    };
+    let data = match state.store.build_template(store_req).await {
+        Ok(data) => data,
+        Err(e) => {
+            rollback_build(&state, Some(&vm.id), Some(&nic.id), Some(&volume.id)).await;
+            return Err(e.into());
+        }
+    };
+    state.audit.template_build_started(&data.id, &vm.id);
+    Ok(Json(UiImportJob::from(data)))
+}
+
+/// Undo the parts of a template build that were already created.
+async fn rollback_build(
+    state: &AppState,
+    vm_id: Option<&str>,
+    nic_id: Option<&str>,
+    volume_id: Option<&str>,
+) {
+    if let Some(id) = vm_id
+        && state.store.delete_vm(id).await.is_ok()
+    {
+        state.audit.vm_deleted(id);
+    }
+    if let Some(id) = nic_id
+        && state.store.delete_nic(id).await.is_ok()
+    {
+        state.audit.nic_deleted(id);
+    }
+    if let Some(id) = volume_id
+        && state.store.delete_volume(id).await.is_ok()
+    {
+        state.audit.volume_deleted(id);
+    }
+}
+
 /// Get an import job by ID (global)
 #[utoipa::path(get, path = "/v1/import-jobs/{id}", params(("id" = String, Path)), responses((status = 200, body = UiImportJob), (status = 404, body = ApiError)), tag = "storage")]
 pub async fn get_import_job(
@@ -2116,18 +2934,172 @@ pub async fn log_events(
 }
 
 // =============================================================================
-// Console Handler (stub)
+// Change History Handler
 // =============================================================================
 
-/// WebSocket handler for VM console (stub)
-pub async fn console_ws(
-    State(_state): State<Arc<AppState>>,
-    Path(_id): Path<String>,
-) -> impl IntoResponse {
-    (
-        StatusCode::NOT_IMPLEMENTED,
-        "Console WebSocket not implemented",
-    )
+/// Change history of one resource, oldest first
+///
+/// Project resources need project-admin in their project, orgs need
+/// org-admin; everything else, and history of since-deleted projects, is
+/// platform-admin only.
+#[utoipa::path(get, path = "/v1/changes/{resource_id}", params(("resource_id" = String, Path)), responses((status = 200, body = ChangeHistoryResponse), (status = 403, body = ApiError)), tag = "changes")]
+pub async fn get_change_history(
+    State(state): State<Arc<AppState>>,
+    Path(resource_id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<ChangeHistoryResponse>, ApiError> {
+    let records = state.store.list_changes(&resource_id).await?;
+    if state.jwt_validator.is_some()
+        && let Some(latest) = records.last()
+    {
+        let ctx = auth.as_ref().ok_or_else(|| ApiError {
+            error: "missing auth".into(),
+            code: 401,
+        })?;
+        let allowed = match (&latest.project_slug, latest.resource_kind.as_str()) {
+            (Some(project), _) => match state.store.get_project(project).await? {
+                Some(p) => ctx.0.is_project_admin(project, Some(&p.org_slug)),
+                None => ctx.0.is_platform_admin(),
+            },
+            (None, "orgs") => ctx.0.is_org_admin(&resource_id),
+            (None, _) => ctx.0.is_platform_admin(),
+        };
+        if !allowed {
+            return Err(ApiError {
+                error: "not allowed to read this resource's history".into(),
+                code: 403,
+            });
+        }
+    }
+    Ok(Json(ChangeHistoryResponse {
+        changes: records.into_iter().map(UiChangeRecord::from).collect(),
+    }))
+}
+
+// =============================================================================
+// Webhook Handlers
+// =============================================================================
+
+async fn get_webhook_or_404(
+    state: &AppState,
+    id: &str,
+) -> Result<crate::command::WebhookData, ApiError> {
+    state.store.get_webhook(id).await?.ok_or_else(|| ApiError {
+        error: format!("Webhook '{}' not found", id),
+        code: 404,
+    })
+}
+
+/// List webhooks
+#[utoipa::path(get, path = "/v1/webhooks", responses((status = 200, body = WebhookListResponse), (status = 403, body = ApiError)), tag = "webhooks")]
+pub async fn list_webhooks(
+    State(state): State<Arc<AppState>>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<WebhookListResponse>, ApiError> {
+    require_platform_admin(&state, &auth)?;
+    let mut webhooks = state.store.list_webhooks().await?;
+    webhooks.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(Json(WebhookListResponse {
+        webhooks: webhooks.into_iter().map(UiWebhook::from).collect(),
+    }))
+}
+
+/// Get a webhook
+#[utoipa::path(get, path = "/v1/webhooks/{id}", params(("id" = String, Path)), responses((status = 200, body = UiWebhook), (status = 404, body = ApiError)), tag = "webhooks")]
+pub async fn get_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<UiWebhook>, ApiError> {
+    require_platform_admin(&state, &auth)?;
+    Ok(Json(UiWebhook::from(
+        get_webhook_or_404(&state, &id).await?,
+    )))
+}
+
+/// Register a webhook
+///
+/// The response is the only place the signing secret is returned.
+#[utoipa::path(post, path = "/v1/webhooks", request_body = UiCreateWebhookRequest, responses((status = 200, body = UiWebhook), (status = 400, body = ApiError)), tag = "webhooks")]
+pub async fn create_webhook(
+    State(state): State<Arc<AppState>>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+    Json(req): Json<UiCreateWebhookRequest>,
+) -> Result<Json<UiWebhook>, ApiError> {
+    use crate::store::CreateWebhookRequest;
+    require_platform_admin(&state, &auth)?;
+    let bad_request = |error: String| ApiError { error, code: 400 };
+    match reqwest::Url::parse(&req.url) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        _ => return Err(bad_request(format!("Invalid webhook URL '{}'", req.url))),
+    }
+    if req.events.is_empty() {
+        return Err(bad_request("events must not be empty".into()));
+    }
+    if let Some(filter) = req
+        .events
+        .iter()
+        .find(|f| !crate::webhooks::valid_filter(f))
+    {
+        return Err(bad_request(format!("Unknown event type '{}'", filter)));
+    }
+    if req.secret.as_deref() == Some("") {
+        return Err(bad_request("secret must not be empty".into()));
+    }
+
+    let webhook = state
+        .store
+        .create_webhook(CreateWebhookRequest {
+            url: req.url,
+            events: req.events,
+            secret: req.secret,
+            description: req.description.filter(|d| !d.is_empty()),
+        })
+        .await?;
+
+    state.audit.webhook_created(&webhook.id, &webhook.url);
+
+    let secret = webhook.secret.clone();
+    Ok(Json(UiWebhook {
+        secret: Some(secret),
+        ..UiWebhook::from(webhook)
+    }))
+}
+
+/// Delete a webhook
+///
+/// Retries still pending for it are dropped.
+#[utoipa::path(delete, path = "/v1/webhooks/{id}", params(("id" = String, Path)), responses((status = 204), (status = 404, body = ApiError)), tag = "webhooks")]
+pub async fn delete_webhook(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<StatusCode, ApiError> {
+    require_platform_admin(&state, &auth)?;
+    state.store.delete_webhook(&id).await?;
+
+    state.audit.webhook_deleted(&id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Recent deliveries of a webhook, newest first
+#[utoipa::path(get, path = "/v1/webhooks/{id}/deliveries", params(("id" = String, Path)), responses((status = 200, body = WebhookDeliveryListResponse), (status = 404, body = ApiError)), tag = "webhooks")]
+pub async fn list_webhook_deliveries(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<WebhookDeliveryListResponse>, ApiError> {
+    require_platform_admin(&state, &auth)?;
+    let webhook = get_webhook_or_404(&state, &id).await?;
+    let deliveries = state.store.list_webhook_deliveries(&webhook.id).await?;
+    Ok(Json(WebhookDeliveryListResponse {
+        deliveries: deliveries
+            .into_iter()
+            .rev()
+            .map(UiWebhookDelivery::from)
+            .collect(),
+    }))
 }
 
 // =============================================================================
@@ -2176,6 +3148,7 @@ pub async fn create_security_group(
 ) -> Result<Json<UiSecurityGroup>, ApiError> {
     use crate::store::CreateSecurityGroupRequest;
     require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_name("Security group", &req.name)?;
 
     let sg = state
         .store
@@ -2377,25 +3350,253 @@ pub async fn update_security_group_rule(
 }
 
 // =============================================================================
-// Notification Handlers (stub - returns empty data)
+// Flavor Handlers
 // =============================================================================
 
-/// List notifications (stub - returns empty array)
-pub async fn list_notifications(State(_state): State<Arc<AppState>>) -> Json<Vec<()>> {
-    Json(vec![])
+/// List all flavors
+#[utoipa::path(get, path = "/v1/flavors", responses((status = 200, body = FlavorListResponse)), tag = "flavors")]
+pub async fn list_flavors(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<FlavorListResponse>, ApiError> {
+    let mut flavors = state.store.list_flavors().await?;
+    flavors.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(FlavorListResponse {
+        flavors: flavors.into_iter().map(UiFlavor::from).collect(),
+    }))
+}
+
+/// Get a flavor by ID or name
+#[utoipa::path(get, path = "/v1/flavors/{id}", params(("id" = String, Path)), responses((status = 200, body = UiFlavor), (status = 404, body = ApiError)), tag = "flavors")]
+pub async fn get_flavor(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<Json<UiFlavor>, ApiError> {
+    Ok(Json(UiFlavor::from(resolve_flavor(&state, &id).await?)))
+}
+
+/// Create a new flavor
+#[utoipa::path(post, path = "/v1/flavors", request_body = UiCreateFlavorRequest, responses((status = 200, body = UiFlavor), (status = 400, body = ApiError), (status = 409, body = ApiError)), tag = "flavors")]
+pub async fn create_flavor(
+    State(state): State<Arc<AppState>>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+    Json(req): Json<UiCreateFlavorRequest>,
+) -> Result<Json<UiFlavor>, ApiError> {
+    use crate::store::CreateFlavorRequest;
+    require_platform_admin(&state, &auth)?;
+    naming::validate_name("Flavor", &req.name)?;
+    if req.vcpus == 0 || req.memory_mb == 0 {
+        return Err(ApiError {
+            error: "vcpus and memoryMb must be greater than zero".into(),
+            code: 400,
+        });
+    }
+
+    let flavor = state
+        .store
+        .create_flavor(CreateFlavorRequest {
+            name: req.name,
+            description: req.description,
+            vcpus: req.vcpus,
+            memory_mb: req.memory_mb,
+            disk_size_bytes: req.disk_size_bytes.filter(|b| *b > 0),
+            network: req.network.filter(|n| !n.is_empty()),
+            user_data: req.user_data.filter(|u| !u.is_empty()),
+        })
+        .await?;
+
+    state.audit.flavor_created(&flavor.id, &flavor.name);
+
+    Ok(Json(UiFlavor::from(flavor)))
 }
 
-/// Mark a notification as read (stub - no-op)
+/// Delete a flavor
+#[utoipa::path(delete, path = "/v1/flavors/{id}", params(("id" = String, Path)), responses((status = 204), (status = 404, body = ApiError)), tag = "flavors")]
+pub async fn delete_flavor(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<StatusCode, ApiError> {
+    require_platform_admin(&state, &auth)?;
+    let flavor = resolve_flavor(&state, &id).await?;
+    state.store.delete_flavor(&flavor.id).await?;
+
+    state.audit.flavor_deleted(&flavor.id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+// =============================================================================
+// SSH Key Handlers
+// =============================================================================
+
+/// List SSH keys in a project
+#[utoipa::path(get, path = "/v1/projects/{project_slug}/ssh-keys", params(("project_slug" = String, Path)), responses((status = 200, body = SshKeyListResponse)), tag = "ssh-keys")]
+pub async fn list_ssh_keys(
+    State(state): State<Arc<AppState>>,
+    Path(project_slug): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<SshKeyListResponse>, ApiError> {
+    require_project_access(&state, &auth, &project_slug).await?;
+    let mut keys = state.store.list_ssh_keys(&project_slug).await?;
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(Json(SshKeyListResponse {
+        ssh_keys: keys.into_iter().map(UiSshKey::from).collect(),
+    }))
+}
+
+/// Get an SSH key by ID
+#[utoipa::path(get, path = "/v1/ssh-keys/{id}", params(("id" = String, Path)), responses((status = 200, body = UiSshKey), (status = 404, body = ApiError)), tag = "ssh-keys")]
+pub async fn get_ssh_key(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<Json<UiSshKey>, ApiError> {
+    let key = state.store.get_ssh_key(&id).await?.ok_or(ApiError {
+        error: "SSH key not found".to_string(),
+        code: 404,
+    })?;
+    require_project_access(&state, &auth, &key.project_slug).await?;
+    Ok(Json(UiSshKey::from(key)))
+}
+
+/// Register an SSH public key
+#[utoipa::path(post, path = "/v1/projects/{project_slug}/ssh-keys", params(("project_slug" = String, Path)), request_body = UiCreateSshKeyRequest, responses((status = 200, body = UiSshKey), (status = 400, body = ApiError), (status = 409, body = ApiError)), tag = "ssh-keys")]
+pub async fn create_ssh_key(
+    State(state): State<Arc<AppState>>,
+    Path(project_slug): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+    Json(req): Json<UiCreateSshKeyRequest>,
+) -> Result<Json<UiSshKey>, ApiError> {
+    use crate::store::CreateSshKeyRequest;
+    require_project_access(&state, &auth, &project_slug).await?;
+    naming::validate_name("SSH key", &req.name)?;
+    let (public_key, fingerprint) = parse_ssh_public_key(&req.public_key)?;
+
+    let key = state
+        .store
+        .create_ssh_key(CreateSshKeyRequest {
+            project_slug,
+            name: req.name,
+            public_key,
+            fingerprint,
+            project_default: req.project_default,
+        })
+        .await?;
+
+    state
+        .audit
+        .ssh_key_created(&key.id, &key.name, &key.fingerprint);
+
+    Ok(Json(UiSshKey::from(key)))
+}
+
+/// Delete an SSH key
+#[utoipa::path(delete, path = "/v1/ssh-keys/{id}", params(("id" = String, Path)), responses((status = 204), (status = 404, body = ApiError)), tag = "ssh-keys")]
+pub async fn delete_ssh_key(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+    auth: Option<crate::auth::AuthenticatedAccount>,
+) -> Result<StatusCode, ApiError> {
+    let key = state.store.get_ssh_key(&id).await?.ok_or(ApiError {
+        error: "SSH key not found".to_string(),
+        code: 404,
+    })?;
+    require_project_access(&state, &auth, &key.project_slug).await?;
+    state.store.delete_ssh_key(&id).await?;
+
+    state.audit.ssh_key_deleted(&id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+const SSH_KEY_TYPES: &[&str] = &[
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+];
+
+/// Validate an OpenSSH public key line and return it normalised (single
+/// spaces, trimmed) together with its `SHA256:` fingerprint.
+fn parse_ssh_public_key(line: &str) -> Result<(String, String), ApiError> {
+    use base64::Engine;
+    use sha2::{Digest, Sha256};
+
+    let bad = |error: &str| ApiError {
+        error: format!("Invalid SSH public key: {}", error),
+        code: 400,
+    };
+    let mut parts = line.split_whitespace();
+    let (Some(key_type), Some(blob_b64)) = (parts.next(), parts.next()) else {
+        return Err(bad("expected '<type> <base64> [comment]'"));
+    };
+    if !SSH_KEY_TYPES.contains(&key_type) {
+        return Err(bad(&format!("unsupported key type '{}'", key_type)));
+    }
+    let blob = base64::engine::general_purpose::STANDARD
+        .decode(blob_b64)
+        .map_err(|_| bad("key data is not valid base64"))?;
+    // The blob starts with the length-prefixed key type; it must agree
+    // with the declared one or the line was pasted together wrongly.
+    let embedded = blob
+        .get(..4)
+        .map(|len| u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize)
+        .and_then(|len| blob.get(4..4 + len));
+    if embedded != Some(key_type.as_bytes()) {
+        return Err(bad("key data does not match key type"));
+    }
+
+    let comment: Vec<&str> = parts.collect();
+    let normalised = if comment.is_empty() {
+        format!("{} {}", key_type, blob_b64)
+    } else {
+        format!("{} {} {}", key_type, blob_b64, comment.join(" "))
+    };
+    let fingerprint = format!(
+        "SHA256:{}",
+        base64::engine::general_purpose::STANDARD_NO_PAD.encode(Sha256::digest(&blob))
+    );
+    Ok((normalised, fingerprint))
+}
+
+// =============================================================================
+// Notification Handlers
+// =============================================================================
+
+/// List notifications, newest first. Read state is shared by all users.
+#[utoipa::path(get, path = "/v1/notifications", responses((status = 200, body = Vec<UiNotification>)), tag = "notifications")]
+pub async fn list_notifications(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<UiNotification>>, ApiError> {
+    let notifications = state.store.list_notifications().await?;
+    Ok(Json(
+        notifications
+            .into_iter()
+            .map(UiNotification::from)
+            .collect(),
+    ))
+}
+
+/// Mark a notification as read
+#[utoipa::path(post, path = "/v1/notifications/{id}/read", params(("id" = String, Path)), responses((status = 204), (status = 404, body = ApiError)), tag = "notifications")]
 pub async fn mark_notification_read(
-    State(_state): State<Arc<AppState>>,
-    Path(_id): Path<String>,
-) -> StatusCode {
-    StatusCode::NO_CONTENT
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    state.store.mark_notification_read(&id).await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
-/// Mark all notifications as read (stub - no-op)
-pub async fn mark_all_notifications_read(State(_state): State<Arc<AppState>>) -> StatusCode {
-    StatusCode::NO_CONTENT
+/// Mark all notifications as read
+#[utoipa::path(post, path = "/v1/notifications/read-all", responses((status = 204)), tag = "notifications")]
+pub async fn mark_all_notifications_read(
+    State(state): State<Arc<AppState>>,
+) -> Result<StatusCode, ApiError> {
+    state.store.mark_all_notifications_read().await?;
+    Ok(StatusCode::NO_CONTENT)
 }
 
 // =============================================================================
@@ -2430,6 +3631,18 @@ pub async fn get_pod(
     })
 }
 
+/// Get resource usage of a pod (stub)
+#[utoipa::path(get, path = "/v1/pods/{id}/stats", params(("id" = String, Path)), responses((status = 404, body = ApiError)), tag = "pods")]
+pub async fn get_pod_stats(
+    State(_state): State<Arc<AppState>>,
+    Path(_id): Path<String>,
+) -> Result<Json<super::ui_types::UiPodStats>, ApiError> {
+    Err(ApiError {
+        code: 404,
+        error: "Pod not found".into(),
+    })
+}
+
 /// Create a pod (stub)
 #[utoipa::path(post, path = "/v1/pods", request_body = super::ui_types::UiCreatePodRequest, responses((status = 501)), tag = "pods")]
 pub async fn create_pod(
@@ -2478,6 +3691,30 @@ pub async fn stop_pod(
     })
 }
 
+/// Pause a pod (stub)
+#[utoipa::path(post, path = "/v1/pods/{id}/pause", params(("id" = String, Path)), responses((status = 404, body = ApiError)), tag = "pods")]
+pub async fn pause_pod(
+    State(_state): State<Arc<AppState>>,
+    Path(_id): Path<String>,
+) -> Result<Json<super::ui_types::UiPod>, ApiError> {
+    Err(ApiError {
+        code: 404,
+        error: "Pod not found".into(),
+    })
+}
+
+/// Resume a paused pod (stub)
+#[utoipa::path(post, path = "/v1/pods/{id}/resume", params(("id" = String, Path)), responses((status = 404, body = ApiError)), tag = "pods")]
+pub async fn resume_pod(
+    State(_state): State<Arc<AppState>>,
+    Path(_id): Path<String>,
+) -> Result<Json<super::ui_types::UiPod>, ApiError> {
+    Err(ApiError {
+        code: 404,
+        error: "Pod not found".into(),
+    })
+}
+
 // =============================================================================
 // ServiceAccount + StaticApiKey handlers (ADR-0004)
 // =============================================================================
@@ -2541,12 +3778,7 @@ pub async fn create_service_account(
 ) -> Result<Json<UiServiceAccount>, ApiError> {
     require_project_access(&state, &auth, &project_slug).await?;
     let trimmed = req.name.trim().to_string();
-    if trimmed.is_empty() {
-        return Err(ApiError {
-            error: "name is required".into(),
-            code: 400,
-        });
-    }
+    naming::validate_name("Service account", &trimmed)?;
     let caller = caller_account_id(&state, &auth);
     let store_req = crate::store::CreateServiceAccountRequest {
         project_slug,
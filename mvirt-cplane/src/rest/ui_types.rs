@@ -8,8 +8,11 @@ use utoipa::ToSchema;
 use serde::Deserializer;
 
 use crate::command::{
-    ClusterData, NetworkData, NicData, OrgContact, OrgData, ProjectData, SnapshotData,
-    TemplateData, TemplatePhase, VmData, VmDesiredState, VmPhase, VolumeData, VolumePhase,
+    ChangeRecord, ClusterData, FlavorData, HookAction, HookPhase, HookStatus, MigrationData,
+    MigrationPhase, NetworkData, NicData, NotificationData, NotificationSeverity, OrgContact,
+    OrgData, PlacementPolicy, ProjectData, ProvisioningHook, SnapshotData, SshKeyData,
+    TemplateData, TemplatePhase, TrashKind, Trashed, VmData, VmDesiredState, VmPhase, VmPlacement,
+    VolumeData, VolumePhase, WaitCondition, WebhookData, WebhookDelivery, WebhookDeliveryStatus,
 };
 
 /// Tri-state deserializer for `Option<Option<T>>` PATCH semantics.
@@ -67,6 +70,64 @@ pub struct UiVmConfig {
     pub volume_id: String,
     pub nic_id: String,
     pub image: String,
+    /// Present when the boot volume was composed from a template for this VM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub disk: Option<UiVmDisk>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<UiVmPlacement>,
+}
+
+/// Placement group membership; groups are named per project.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiVmPlacement {
+    pub group: String,
+    pub policy: UiPlacementPolicy,
+}
+
+/// SPREAD puts every member on its own node, PACK keeps them on one.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub enum UiPlacementPolicy {
+    #[serde(rename = "SPREAD")]
+    Spread,
+    #[serde(rename = "PACK")]
+    Pack,
+}
+
+impl From<VmPlacement> for UiVmPlacement {
+    fn from(p: VmPlacement) -> Self {
+        Self {
+            group: p.group,
+            policy: match p.policy {
+                PlacementPolicy::Spread => UiPlacementPolicy::Spread,
+                PlacementPolicy::Pack => UiPlacementPolicy::Pack,
+            },
+        }
+    }
+}
+
+impl From<UiVmPlacement> for VmPlacement {
+    fn from(p: UiVmPlacement) -> Self {
+        Self {
+            group: p.group,
+            policy: match p.policy {
+                UiPlacementPolicy::Spread => PlacementPolicy::Spread,
+                UiPlacementPolicy::Pack => PlacementPolicy::Pack,
+            },
+        }
+    }
+}
+
+/// Boot disk declared on the VM: cloned from a template on VM creation and
+/// deleted with the VM.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiVmDisk {
+    /// Template name or ID; responses carry the ID
+    pub from_template: String,
+    /// Defaults to the template's size (or the flavor's disk size if larger)
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
 }
 
 /// UI-compatible VM representation
@@ -85,6 +146,17 @@ pub struct UiVm {
     pub node_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub ip_address: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub provisioning: Vec<UiHookStatus>,
+    /// How the VM currently breaks its placement group's policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement_violation: Option<String>,
+    /// When the VM was moved to the trash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// When a trashed VM is deleted for good
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_at: Option<String>,
 }
 
 impl From<VmData> for UiVm {
@@ -107,11 +179,20 @@ impl From<VmData> for UiVm {
                 volume_id: data.spec.volume_id.clone(),
                 nic_id: data.spec.nic_id.clone(),
                 image: data.spec.image.clone(),
+                disk: data.spec.disk.clone().map(|d| UiVmDisk {
+                    from_template: d.template_id,
+                    size_bytes: Some(d.size_bytes),
+                }),
+                placement: data.spec.placement.clone().map(Into::into),
             },
             created_at: data.created_at,
             started_at,
             node_id: data.status.node_id,
             ip_address: data.status.ip_address,
+            provisioning: data.provisioning.into_iter().map(Into::into).collect(),
+            placement_violation: None,
+            deleted_at: data.trashed.as_ref().map(|t| t.deleted_at.clone()),
+            purge_at: data.trashed.map(|t| t.purge_at),
         }
     }
 }
@@ -124,15 +205,30 @@ pub struct UiCreateVmRequest {
     pub config: UiCreateVmConfig,
     #[serde(default)]
     pub node_selector: Option<String>,
+    /// Flavor name or ID. Supplies vcpus / memory / user-data and, when
+    /// `nicId` is empty, the network a NIC is created in.
+    #[serde(default)]
+    pub flavor: Option<String>,
 }
 
 /// VM configuration for creation (UI-compatible)
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct UiCreateVmConfig {
-    pub vcpus: u32,
-    pub memory_mb: u64,
+    /// Required unless a flavor is given; must match the flavor if both are.
+    #[serde(default)]
+    pub vcpus: Option<u32>,
+    /// Required unless a flavor is given; must match the flavor if both are.
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Existing boot volume. Leave empty and set `disk` to have one cloned
+    /// from a template instead.
+    #[serde(default)]
     pub volume_id: String,
+    #[serde(default)]
+    pub disk: Option<UiVmDisk>,
+    /// May be empty when the flavor names a default network.
+    #[serde(default)]
     pub nic_id: String,
     pub image: String,
     /// Optional cloud-init user-data (YAML). If empty/missing, the cplane
@@ -142,6 +238,142 @@ pub struct UiCreateVmConfig {
     /// metadata-probe loop and never applies netplan, so DHCP never fires.
     #[serde(default)]
     pub user_data: Option<String>,
+    /// First-boot hooks, run in order once the VM first reaches RUNNING.
+    #[serde(default)]
+    pub provisioning: Vec<UiProvisioningHook>,
+    /// SSH keys (ID or name) to inject in addition to the project defaults.
+    #[serde(default)]
+    pub ssh_key_ids: Vec<String>,
+    /// Placement group to schedule the VM in
+    #[serde(default)]
+    pub placement: Option<UiVmPlacement>,
+}
+
+/// Default upper bound for a provisioning hook's wait phase.
+const DEFAULT_HOOK_TIMEOUT_SECS: u32 = 300;
+
+/// First-boot provisioning hook
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiProvisioningHook {
+    pub name: String,
+    /// All conditions must hold before the action runs.
+    #[serde(default)]
+    pub wait: Vec<UiWaitCondition>,
+    #[serde(default)]
+    pub action: Option<UiHookAction>,
+    /// Wait timeout in seconds (default 300).
+    #[serde(default)]
+    pub timeout_secs: Option<u32>,
+}
+
+/// Provisioning wait condition
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UiWaitCondition {
+    /// The guest agent (`mvirt-one --agent`, started by the guest's init)
+    /// has signalled ready
+    GuestReady,
+    /// A TCP connect from the VM's node to its IPv4 address succeeds
+    TcpPort { port: u16 },
+}
+
+/// Provisioning action
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UiHookAction {
+    /// POST the VM's id, name, project and IP as JSON. The URL must
+    /// resolve to public addresses only.
+    Webhook { url: String },
+    /// Run a command in the guest through its agent; succeeds on exit
+    /// status 0
+    #[serde(rename_all = "camelCase")]
+    GuestCommand {
+        command: Vec<String>,
+        /// Kill the command after this many seconds (default 30)
+        #[serde(default)]
+        timeout_secs: Option<u32>,
+    },
+}
+
+impl From<UiProvisioningHook> for ProvisioningHook {
+    fn from(hook: UiProvisioningHook) -> Self {
+        Self {
+            name: hook.name,
+            wait: hook
+                .wait
+                .into_iter()
+                .map(|c| match c {
+                    UiWaitCondition::GuestReady => WaitCondition::GuestReady,
+                    UiWaitCondition::TcpPort { port } => WaitCondition::TcpPort { port },
+                })
+                .collect(),
+            action: hook.action.map(|a| match a {
+                UiHookAction::Webhook { url } => HookAction::Webhook { url },
+                UiHookAction::GuestCommand {
+                    command,
+                    timeout_secs,
+                } => HookAction::GuestCommand {
+                    command,
+                    timeout_secs: timeout_secs.unwrap_or(0),
+                },
+            }),
+            timeout_secs: hook.timeout_secs.unwrap_or(DEFAULT_HOOK_TIMEOUT_SECS),
+        }
+    }
+}
+
+/// Provisioning hook phase
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub enum UiHookPhase {
+    #[serde(rename = "PENDING")]
+    Pending,
+    #[serde(rename = "WAITING")]
+    Waiting,
+    #[serde(rename = "SUCCEEDED")]
+    Succeeded,
+    #[serde(rename = "FAILED")]
+    Failed,
+    #[serde(rename = "SKIPPED")]
+    Skipped,
+}
+
+impl From<HookPhase> for UiHookPhase {
+    fn from(phase: HookPhase) -> Self {
+        match phase {
+            HookPhase::Pending => Self::Pending,
+            HookPhase::Waiting => Self::Waiting,
+            HookPhase::Succeeded => Self::Succeeded,
+            HookPhase::Failed => Self::Failed,
+            HookPhase::Skipped => Self::Skipped,
+        }
+    }
+}
+
+/// Observed state of a provisioning hook
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiHookStatus {
+    pub name: String,
+    pub phase: UiHookPhase,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl From<HookStatus> for UiHookStatus {
+    fn from(status: HookStatus) -> Self {
+        Self {
+            name: status.name,
+            phase: status.phase.into(),
+            started_at: status.started_at,
+            finished_at: status.finished_at,
+            message: status.message,
+        }
+    }
 }
 
 /// Response wrapper for VM list
@@ -151,6 +383,83 @@ pub struct VmListResponse {
     pub vms: Vec<UiVm>,
 }
 
+// =============================================================================
+// Migration Types
+// =============================================================================
+
+/// Request to move a stopped VM to another node (UI-compatible)
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiMoveVmRequest {
+    pub target_node_id: String,
+}
+
+/// UI-compatible migration phase
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub enum UiMigrationState {
+    #[serde(rename = "COPYING_VOLUME")]
+    CopyingVolume,
+    #[serde(rename = "CLEANING_UP")]
+    CleaningUp,
+    #[serde(rename = "COMPLETED")]
+    Completed,
+    #[serde(rename = "FAILED")]
+    Failed,
+}
+
+impl From<MigrationPhase> for UiMigrationState {
+    fn from(phase: MigrationPhase) -> Self {
+        match phase {
+            MigrationPhase::CopyingVolume => UiMigrationState::CopyingVolume,
+            MigrationPhase::CleaningUp => UiMigrationState::CleaningUp,
+            MigrationPhase::Completed => UiMigrationState::Completed,
+            MigrationPhase::Failed => UiMigrationState::Failed,
+        }
+    }
+}
+
+/// UI-compatible migration job representation
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiMigration {
+    pub id: String,
+    pub vm_id: String,
+    pub project_id: String,
+    pub source_node_id: String,
+    pub target_node_id: String,
+    pub source_volume_id: String,
+    pub target_volume_id: String,
+    pub state: UiMigrationState,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<MigrationData> for UiMigration {
+    fn from(data: MigrationData) -> Self {
+        Self {
+            id: data.id,
+            vm_id: data.spec.vm_id,
+            project_id: data.spec.project_slug,
+            source_node_id: data.spec.source_node_id,
+            target_node_id: data.spec.target_node_id,
+            source_volume_id: data.spec.source_volume_id,
+            target_volume_id: data.spec.target_volume_id,
+            state: data.status.phase.into(),
+            error: data.status.error,
+            created_at: data.created_at,
+            updated_at: data.updated_at,
+        }
+    }
+}
+
+/// Response wrapper for a VM's migrations
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct MigrationListResponse {
+    pub migrations: Vec<UiMigration>,
+}
+
 // =============================================================================
 // Network Types
 // =============================================================================
@@ -170,8 +479,20 @@ pub struct UiNetwork {
     pub ipv6_prefix: Option<String>,
     pub dns_servers: Vec<String>,
     pub is_public: bool,
+    /// TFTP server announced to network-booting VMs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boot_server: Option<String>,
+    /// TFTP file name or http(s):// URL announced to network-booting VMs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boot_file: Option<String>,
     pub nic_count: u32,
     pub created_at: String,
+    /// When the network was moved to the trash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// When a trashed network is deleted for good
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_at: Option<String>,
 }
 
 impl From<NetworkData> for UiNetwork {
@@ -186,8 +507,12 @@ impl From<NetworkData> for UiNetwork {
             ipv6_prefix: data.ipv6_prefix,
             dns_servers: data.dns_servers,
             is_public: data.is_public,
+            boot_server: data.boot_server,
+            boot_file: data.boot_file,
             nic_count: data.nic_count,
             created_at: data.created_at,
+            deleted_at: data.trashed.as_ref().map(|t| t.deleted_at.clone()),
+            purge_at: data.trashed.map(|t| t.purge_at),
         }
     }
 }
@@ -211,6 +536,14 @@ pub struct UiCreateNetworkRequest {
     pub ntp_servers: Vec<String>,
     #[serde(default)]
     pub is_public: bool,
+    /// TFTP server IPv4 address for PXE boot; needed unless `bootFile` is
+    /// an http(s):// URL
+    #[serde(default)]
+    pub boot_server: Option<String>,
+    /// Boot file announced over DHCP to VMs in network boot mode: a TFTP
+    /// file name, or an http(s):// URL for UEFI HTTP boot
+    #[serde(default)]
+    pub boot_file: Option<String>,
 }
 
 fn default_true() -> bool {
@@ -787,11 +1120,19 @@ pub struct UiVolume {
     pub snapshots: Vec<UiSnapshot>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub template_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_volume_id: Option<String>,
     pub phase: String,
     pub path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub error: Option<String>,
     pub created_at: String,
+    /// When the volume was moved to the trash
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<String>,
+    /// When a trashed volume is deleted for good
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub purge_at: Option<String>,
 }
 
 impl From<VolumeData> for UiVolume {
@@ -806,6 +1147,7 @@ impl From<VolumeData> for UiVolume {
             compression_ratio: data.status.compression_ratio,
             snapshots: data.status.snapshots.iter().map(UiSnapshot::from).collect(),
             template_id: data.spec.template_id,
+            source_volume_id: data.spec.source_volume_id,
             phase: match data.status.phase {
                 VolumePhase::Pending => "pending",
                 VolumePhase::Creating => "creating",
@@ -816,6 +1158,8 @@ impl From<VolumeData> for UiVolume {
             path: data.status.path,
             error: data.status.error,
             created_at: data.created_at,
+            deleted_at: data.trashed.as_ref().map(|t| t.deleted_at.clone()),
+            purge_at: data.trashed.map(|t| t.purge_at),
         }
     }
 }
@@ -831,6 +1175,14 @@ pub struct UiCreateVolumeRequest {
     pub template_id: Option<String>,
 }
 
+/// Request to copy a volume to another node (UI-compatible)
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiCopyVolumeRequest {
+    pub node_id: String,
+    pub name: String,
+}
+
 /// Request to resize a volume (UI-compatible)
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -893,6 +1245,31 @@ pub struct UiImportTemplateRequest {
     pub total_bytes: u64,
 }
 
+/// Request to build a template from a cloud-init run (UI-compatible).
+///
+/// A throwaway VM boots a clone of the base template with `userData` and
+/// must power itself off when it is done, e.g. with cloud-init's
+/// `power_state: {mode: poweroff}`. Its disk then becomes the template.
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiBuildTemplateRequest {
+    pub name: String,
+    pub base_template_id: String,
+    pub user_data: String,
+    /// Network the build VM is attached to while it runs
+    pub network_id: String,
+    /// Disk size of the build VM; defaults to the base template's size
+    #[serde(default)]
+    pub size_bytes: Option<u64>,
+    #[serde(default)]
+    pub vcpus: Option<u32>,
+    #[serde(default)]
+    pub memory_mb: Option<u64>,
+    /// Fail the build if the VM is still running after this long
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
 /// Response wrapper for template list
 #[derive(Debug, Clone, Serialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -921,7 +1298,7 @@ impl From<TemplatePhase> for UiImportJobState {
     fn from(phase: TemplatePhase) -> Self {
         match phase {
             TemplatePhase::Pending => UiImportJobState::Pending,
-            TemplatePhase::Importing => UiImportJobState::Running,
+            TemplatePhase::Importing | TemplatePhase::Building => UiImportJobState::Running,
             TemplatePhase::Ready => UiImportJobState::Completed,
             TemplatePhase::Failed => UiImportJobState::Failed,
         }
@@ -1020,6 +1397,70 @@ pub struct ListVolumesQuery {
     pub node_id: Option<String>,
 }
 
+/// Query parameters for deleting VMs, volumes and networks
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteQuery {
+    /// Skip the trash and delete right away
+    #[serde(default)]
+    pub purge: bool,
+}
+
+// =============================================================================
+// Trash Types
+// =============================================================================
+
+/// Kind of a trashed resource
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub enum UiTrashKind {
+    #[serde(rename = "VM")]
+    Vm,
+    #[serde(rename = "VOLUME")]
+    Volume,
+    #[serde(rename = "NETWORK")]
+    Network,
+}
+
+impl From<TrashKind> for UiTrashKind {
+    fn from(kind: TrashKind) -> Self {
+        match kind {
+            TrashKind::Vm => UiTrashKind::Vm,
+            TrashKind::Volume => UiTrashKind::Volume,
+            TrashKind::Network => UiTrashKind::Network,
+        }
+    }
+}
+
+/// A soft-deleted resource that can still be restored
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiTrashItem {
+    pub kind: UiTrashKind,
+    pub id: String,
+    pub name: String,
+    pub deleted_at: String,
+    pub purge_at: String,
+}
+
+impl UiTrashItem {
+    pub fn new(kind: TrashKind, id: String, name: String, trashed: Trashed) -> Self {
+        Self {
+            kind: kind.into(),
+            id,
+            name,
+            deleted_at: trashed.deleted_at,
+            purge_at: trashed.purge_at,
+        }
+    }
+}
+
+/// Response wrapper for the trash of a project
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct TrashListResponse {
+    pub items: Vec<UiTrashItem>,
+}
+
 // =============================================================================
 // Security Group Types
 // =============================================================================
@@ -1206,6 +1647,370 @@ pub struct SecurityGroupListResponse {
     pub security_groups: Vec<UiSecurityGroup>,
 }
 
+// =============================================================================
+// Flavor Types
+// =============================================================================
+
+/// UI-compatible flavor (VM profile)
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiFlavor {
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub vcpus: u32,
+    pub memory_mb: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk_size_bytes: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user_data: Option<String>,
+    pub created_at: String,
+}
+
+impl From<FlavorData> for UiFlavor {
+    fn from(data: FlavorData) -> Self {
+        Self {
+            id: data.id,
+            name: data.name,
+            description: data.description,
+            vcpus: data.vcpus,
+            memory_mb: data.memory_mb,
+            disk_size_bytes: data.disk_size_bytes,
+            network: data.network,
+            user_data: data.user_data,
+            created_at: data.created_at,
+        }
+    }
+}
+
+/// Request to create a flavor
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiCreateFlavorRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub vcpus: u32,
+    pub memory_mb: u64,
+    /// Minimum boot volume size in bytes.
+    #[serde(default)]
+    pub disk_size_bytes: Option<u64>,
+    /// Default network name, resolved within the VM's project.
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Cloud-init user-data snippet used when a VM brings none.
+    #[serde(default)]
+    pub user_data: Option<String>,
+}
+
+/// Response wrapper for flavor list
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct FlavorListResponse {
+    pub flavors: Vec<UiFlavor>,
+}
+
+// =============================================================================
+// SSH Key Types
+// =============================================================================
+
+/// UI-compatible SSH public key
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiSshKey {
+    pub id: String,
+    pub project_slug: String,
+    pub name: String,
+    pub public_key: String,
+    pub fingerprint: String,
+    /// Injected into every VM created in the project.
+    pub project_default: bool,
+    pub created_at: String,
+}
+
+impl From<SshKeyData> for UiSshKey {
+    fn from(data: SshKeyData) -> Self {
+        Self {
+            id: data.id,
+            project_slug: data.project_slug,
+            name: data.name,
+            public_key: data.public_key,
+            fingerprint: data.fingerprint,
+            project_default: data.project_default,
+            created_at: data.created_at,
+        }
+    }
+}
+
+/// Request to register an SSH public key
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiCreateSshKeyRequest {
+    pub name: String,
+    /// OpenSSH public key line, e.g. the contents of `~/.ssh/id_ed25519.pub`.
+    pub public_key: String,
+    #[serde(default)]
+    pub project_default: bool,
+}
+
+/// Response wrapper for SSH key list
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct SshKeyListResponse {
+    pub ssh_keys: Vec<UiSshKey>,
+}
+
+// =============================================================================
+// Change History Types
+// =============================================================================
+
+/// One recorded mutating API call against a resource
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiChangeRecord {
+    /// Also the correlation ID of the audit entry in mvirt-log.
+    pub id: String,
+    pub timestamp: String,
+    pub resource_kind: String,
+    pub resource_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_slug: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor_email: Option<String>,
+    pub method: String,
+    pub path: String,
+    /// Request body with secret fields redacted.
+    #[schema(value_type = Option<Object>)]
+    pub request: Option<serde_json::Value>,
+    pub changes: Vec<UiFieldChange>,
+}
+
+/// A field of the stored object changed by the call
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiFieldChange {
+    /// Dotted path into the stored object, e.g. `spec.memory_mb`.
+    pub path: String,
+    #[schema(value_type = Option<Object>)]
+    pub before: Option<serde_json::Value>,
+    #[schema(value_type = Option<Object>)]
+    pub after: Option<serde_json::Value>,
+}
+
+impl From<ChangeRecord> for UiChangeRecord {
+    fn from(data: ChangeRecord) -> Self {
+        let json = |s: String| serde_json::from_str(&s).unwrap_or(serde_json::Value::String(s));
+        Self {
+            id: data.id,
+            timestamp: data.timestamp,
+            resource_kind: data.resource_kind,
+            resource_id: data.resource_id,
+            project_slug: data.project_slug,
+            actor: data.actor,
+            actor_email: data.actor_email,
+            method: data.method,
+            path: data.path,
+            request: data.request.map(json),
+            changes: data
+                .changes
+                .into_iter()
+                .map(|c| UiFieldChange {
+                    path: c.path,
+                    before: c.before.map(json),
+                    after: c.after.map(json),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Response wrapper for a resource's change history
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeHistoryResponse {
+    pub changes: Vec<UiChangeRecord>,
+}
+
+// =============================================================================
+// Webhook Types
+// =============================================================================
+
+/// A URL notified of lifecycle events
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiWebhook {
+    pub id: String,
+    pub url: String,
+    /// Event types, `<kind>.*` or `*`.
+    pub events: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_at: String,
+    /// Signing secret, returned only by `POST /v1/webhooks`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+}
+
+impl From<WebhookData> for UiWebhook {
+    fn from(data: WebhookData) -> Self {
+        Self {
+            id: data.id,
+            url: data.url,
+            events: data.events,
+            description: data.description,
+            created_at: data.created_at,
+            secret: None,
+        }
+    }
+}
+
+/// Request to register a webhook
+#[derive(Debug, Clone, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiCreateWebhookRequest {
+    /// http(s) URL the events are POSTed to.
+    pub url: String,
+    /// Event types such as `vm.started` or `node.notready`, `<kind>.*`
+    /// or `*`.
+    pub events: Vec<String>,
+    /// HMAC signing secret; generated when omitted.
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// Response wrapper for webhook list
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookListResponse {
+    pub webhooks: Vec<UiWebhook>,
+}
+
+/// One event sent to a webhook
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiWebhookDelivery {
+    pub id: String,
+    pub event_type: String,
+    pub resource_id: String,
+    /// `pending` (retry scheduled), `succeeded` or `failed`.
+    pub status: String,
+    pub attempts: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_status_code: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+impl From<WebhookDelivery> for UiWebhookDelivery {
+    fn from(data: WebhookDelivery) -> Self {
+        Self {
+            id: data.id,
+            event_type: data.event_type,
+            resource_id: data.resource_id,
+            status: match data.status {
+                WebhookDeliveryStatus::Pending => "pending",
+                WebhookDeliveryStatus::Succeeded => "succeeded",
+                WebhookDeliveryStatus::Failed => "failed",
+            }
+            .to_string(),
+            attempts: data.attempts,
+            last_status_code: data.last_status_code,
+            last_error: data.last_error,
+            created_at: data.created_at,
+            updated_at: data.updated_at,
+        }
+    }
+}
+
+/// Response wrapper for a webhook's recent deliveries, newest first
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDeliveryListResponse {
+    pub deliveries: Vec<UiWebhookDelivery>,
+}
+
+// =============================================================================
+// Notification Types
+// =============================================================================
+
+/// UI-compatible notification severity
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+pub enum UiNotificationType {
+    #[serde(rename = "INFO")]
+    Info,
+    #[serde(rename = "SUCCESS")]
+    Success,
+    #[serde(rename = "WARNING")]
+    Warning,
+    #[serde(rename = "ERROR")]
+    Error,
+}
+
+impl From<NotificationSeverity> for UiNotificationType {
+    fn from(severity: NotificationSeverity) -> Self {
+        match severity {
+            NotificationSeverity::Info => UiNotificationType::Info,
+            NotificationSeverity::Success => UiNotificationType::Success,
+            NotificationSeverity::Warning => UiNotificationType::Warning,
+            NotificationSeverity::Error => UiNotificationType::Error,
+        }
+    }
+}
+
+/// The resource a notification is about
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiInvolvedObject {
+    /// `node`, `template`, `vm`, `volume` or `migration`
+    pub kind: String,
+    pub id: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub project_slug: Option<String>,
+}
+
+/// UI-compatible notification
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiNotification {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub notification_type: UiNotificationType,
+    pub title: String,
+    pub message: String,
+    pub involved_object: UiInvolvedObject,
+    pub read: bool,
+    pub created_at: String,
+}
+
+impl From<NotificationData> for UiNotification {
+    fn from(data: NotificationData) -> Self {
+        Self {
+            id: data.id,
+            notification_type: data.severity.into(),
+            title: data.title,
+            message: data.message,
+            involved_object: UiInvolvedObject {
+                kind: data.involved_object.kind,
+                id: data.involved_object.id,
+                name: data.involved_object.name,
+                project_slug: data.involved_object.project_slug,
+            },
+            read: data.read,
+            created_at: data.created_at,
+        }
+    }
+}
+
 // =============================================================================
 // Pod / Container Types (stub)
 // =============================================================================
@@ -1214,8 +2019,10 @@ pub struct SecurityGroupListResponse {
 #[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
 pub enum UiPodState {
     CREATED,
+    PULLING,
     STARTING,
     RUNNING,
+    PAUSED,
     STOPPING,
     STOPPED,
     FAILED,
@@ -1266,6 +2073,33 @@ pub struct UiPod {
     pub error_message: Option<String>,
 }
 
+/// Resource usage of a container, as sampled from its cgroup
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiContainerStats {
+    pub id: String,
+    pub name: String,
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memory_limit_bytes: Option<u64>,
+    pub pids: u64,
+}
+
+/// Resource usage of a pod, rolled up over its containers
+#[derive(Debug, Clone, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct UiPodStats {
+    pub pod_id: String,
+    pub timestamp: String,
+    /// Sum over containers; 100 is one fully used vCPU
+    pub cpu_percent: f64,
+    pub memory_bytes: u64,
+    pub vcpus: u32,
+    pub memory_limit_bytes: u64,
+    pub containers: Vec<UiContainerStats>,
+}
+
 /// Container spec for creating a pod
 #[derive(Debug, Clone, Deserialize, ToSchema)]
 #[serde(rename_all = "camelCase")]
@@ -1290,6 +2124,9 @@ pub struct UiCreatePodRequest {
     pub project_slug: String,
     pub network_id: String,
     pub containers: Vec<UiContainerSpec>,
+    /// Guest image (kernel/initramfs/rootfs) to boot; host default if unset
+    #[serde(default)]
+    pub guest_image: Option<String>,
 }
 
 /// Response wrapper for pod list
@@ -4,12 +4,22 @@
 //! - Node availability (online status)
 //! - Resource capacity (CPU, memory, storage)
 //! - Node selector constraints (if specified in VM spec)
+//! - Pressure: with a limit set, nodes whose tasks already stall on CPU,
+//!   memory or IO (or lose CPU time to steal) take no further VMs
+//! - Placement groups: spread members over distinct nodes, or pack them
+//!   onto one
 //! - Load balancing (prefer nodes with more available resources)
 
-use crate::command::{NodeData, NodeStatus, VmSpec};
+use std::collections::HashMap;
+
+use crate::command::{NodeData, NodeStatus, PlacementPolicy, VmData, VmSpec};
 
 /// Scheduler for VM placement decisions.
-pub struct Scheduler;
+#[derive(Debug, Clone, Copy)]
+pub struct Scheduler {
+    /// Percent of stall or steal above which a node takes no new VMs.
+    max_pressure: Option<f64>,
+}
 
 /// Result of scheduling a VM.
 #[derive(Debug, Clone)]
@@ -18,6 +28,8 @@ pub struct ScheduleResult {
     pub node_id: String,
     /// Reason for selection.
     pub reason: String,
+    /// Set when the VM's pack group could not be kept together.
+    pub placement_violation: Option<String>,
 }
 
 /// Error when scheduling fails.
@@ -32,6 +44,15 @@ pub enum ScheduleError {
         required_cpu: u32,
         required_memory: u64,
     },
+    /// Every node with room is stalling above the pressure limit.
+    NodesUnderPressure { max_pressure: f64 },
+    /// The placement group's members use a different policy.
+    PlacementPolicyMismatch {
+        group: String,
+        policy: PlacementPolicy,
+    },
+    /// Every eligible node already runs a member of the spread group.
+    SpreadUnsatisfiable { group: String },
 }
 
 impl std::fmt::Display for ScheduleError {
@@ -51,6 +72,28 @@ impl std::fmt::Display for ScheduleError {
                     required_cpu, required_memory
                 )
             }
+            ScheduleError::NodesUnderPressure { max_pressure } => {
+                write!(
+                    f,
+                    "Every node with sufficient resources is under pressure (above {}%)",
+                    max_pressure
+                )
+            }
+            ScheduleError::PlacementPolicyMismatch { group, policy } => {
+                write!(
+                    f,
+                    "Placement group '{}' already uses the {} policy",
+                    group,
+                    policy.as_str()
+                )
+            }
+            ScheduleError::SpreadUnsatisfiable { group } => {
+                write!(
+                    f,
+                    "Every eligible node already runs a member of spread group '{}'",
+                    group
+                )
+            }
         }
     }
 }
@@ -60,7 +103,14 @@ impl std::error::Error for ScheduleError {}
 impl Scheduler {
     /// Create a new scheduler.
     pub fn new() -> Self {
-        Self
+        Self { max_pressure: None }
+    }
+
+    /// Keep new VMs off nodes whose CPU, memory or IO pressure or CPU steal
+    /// is above `percent`.
+    pub fn with_max_pressure(mut self, percent: f64) -> Self {
+        self.max_pressure = Some(percent);
+        self
     }
 
     /// Select the best node for a VM.
@@ -69,12 +119,104 @@ impl Scheduler {
     /// 1. Node must be online
     /// 2. Node must match selector (if specified)
     /// 3. Node must have sufficient resources
-    /// 4. Prefer node with most available memory (load balancing)
+    /// 4. Node must not be under pressure (if a limit is set)
+    /// 5. Prefer node with most available memory (load balancing)
     pub fn select_node(
         &self,
         nodes: &[NodeData],
         spec: &VmSpec,
     ) -> Result<ScheduleResult, ScheduleError> {
+        self.select_node_in_group(nodes, spec, &[])
+    }
+
+    /// Select the best node for a VM in a placement group, given the
+    /// group's other members (see [`group_members`]).
+    ///
+    /// Spread excludes the nodes running members. Pack prefers the node
+    /// running the most members; when none of them can take the VM, it is
+    /// placed like any other and the result names the violation.
+    pub fn select_node_in_group(
+        &self,
+        nodes: &[NodeData],
+        spec: &VmSpec,
+        members: &[&VmData],
+    ) -> Result<ScheduleResult, ScheduleError> {
+        let candidates = self.candidates(nodes, spec)?;
+
+        let Some(placement) = &spec.placement else {
+            let best = most_memory(candidates).expect("candidates is not empty");
+            return Ok(Self::result(best, None));
+        };
+        if let Some(other) = members
+            .iter()
+            .filter_map(|m| m.spec.placement.as_ref())
+            .find(|p| p.policy != placement.policy)
+        {
+            return Err(ScheduleError::PlacementPolicyMismatch {
+                group: placement.group.clone(),
+                policy: other.policy,
+            });
+        }
+
+        // Members per node
+        let mut occupied: HashMap<&str, usize> = HashMap::new();
+        for node_id in members.iter().filter_map(|m| m.status.node_id.as_deref()) {
+            *occupied.entry(node_id).or_default() += 1;
+        }
+
+        match placement.policy {
+            PlacementPolicy::Spread => {
+                let free = candidates
+                    .into_iter()
+                    .filter(|n| !occupied.contains_key(n.id.as_str()));
+                let best = most_memory(free).ok_or_else(|| ScheduleError::SpreadUnsatisfiable {
+                    group: placement.group.clone(),
+                })?;
+                Ok(Self::result(best, None))
+            }
+            PlacementPolicy::Pack => {
+                let packed = candidates
+                    .iter()
+                    .filter_map(|n| occupied.get(n.id.as_str()).map(|count| (*count, *n)))
+                    .max_by_key(|(count, n)| (*count, n.resources.available_memory_mb))
+                    .map(|(_, n)| n);
+                match packed {
+                    Some(best) => Ok(Self::result(best, None)),
+                    None if occupied.is_empty() => {
+                        let best = most_memory(candidates).expect("candidates is not empty");
+                        Ok(Self::result(best, None))
+                    }
+                    None => {
+                        let best = most_memory(candidates).expect("candidates is not empty");
+                        let violation = format!(
+                            "pack group '{}' runs on {}, which cannot take the VM",
+                            placement.group,
+                            sorted_join(occupied.keys().copied())
+                        );
+                        Ok(Self::result(best, Some(violation)))
+                    }
+                }
+            }
+        }
+    }
+
+    fn result(node: &NodeData, placement_violation: Option<String>) -> ScheduleResult {
+        ScheduleResult {
+            node_id: node.id.clone(),
+            reason: format!(
+                "Selected node {} with {}MB available memory",
+                node.name, node.resources.available_memory_mb
+            ),
+            placement_violation,
+        }
+    }
+
+    /// Online nodes that match the selector and fit the VM.
+    fn candidates<'a>(
+        &self,
+        nodes: &'a [NodeData],
+        spec: &VmSpec,
+    ) -> Result<Vec<&'a NodeData>, ScheduleError> {
         // Filter to online nodes only
         let online_nodes: Vec<_> = nodes
             .iter()
@@ -115,19 +257,25 @@ impl Scheduler {
             });
         }
 
-        // Select node with most available memory (simple load balancing)
-        let best_node = with_resources
+        // Filter out nodes that already stall
+        let Some(max_pressure) = self.max_pressure else {
+            return Ok(with_resources);
+        };
+        let calm: Vec<_> = with_resources
             .into_iter()
-            .max_by_key(|n| n.resources.available_memory_mb)
-            .expect("with_resources is not empty");
+            .filter(|n| {
+                n.resources
+                    .host
+                    .as_ref()
+                    .is_none_or(|h| h.pressure() <= max_pressure)
+            })
+            .collect();
 
-        Ok(ScheduleResult {
-            node_id: best_node.id.clone(),
-            reason: format!(
-                "Selected node {} with {}MB available memory",
-                best_node.name, best_node.resources.available_memory_mb
-            ),
-        })
+        if calm.is_empty() {
+            return Err(ScheduleError::NodesUnderPressure { max_pressure });
+        }
+
+        Ok(calm)
     }
 
     /// Check if a node matches the selector.
@@ -157,9 +305,11 @@ impl Scheduler {
         false
     }
 
-    /// Check if a node has sufficient resources for the VM.
+    /// Check if a node has sufficient resources for the VM. A node whose
+    /// telemetry says it has no KVM cannot run any.
     fn has_sufficient_resources(&self, node: &NodeData, spec: &VmSpec) -> bool {
-        node.resources.available_cpu_cores >= spec.cpu_cores
+        node.resources.host.as_ref().is_none_or(|h| h.kvm)
+            && node.resources.available_cpu_cores >= spec.cpu_cores
             && node.resources.available_memory_mb >= spec.memory_mb
     }
 }
@@ -170,10 +320,84 @@ impl Default for Scheduler {
     }
 }
 
+/// The other VMs in the same project and placement group as `spec`.
+pub fn group_members<'a>(spec: &VmSpec, vms: &'a [VmData]) -> Vec<&'a VmData> {
+    let Some(placement) = &spec.placement else {
+        return vec![];
+    };
+    vms.iter()
+        .filter(|vm| {
+            vm.spec.name != spec.name
+                && vm.spec.project_slug == spec.project_slug
+                && vm
+                    .spec
+                    .placement
+                    .as_ref()
+                    .is_some_and(|p| p.group == placement.group)
+        })
+        .collect()
+}
+
+/// Whether a placed VM currently breaks its group's policy, given the
+/// group's other members: a spread VM sharing a node, or a pack VM on a
+/// different node than its peers.
+pub fn placement_violation(vm: &VmData, members: &[&VmData]) -> Option<String> {
+    let placement = vm.spec.placement.as_ref()?;
+    let node_id = vm.status.node_id.as_deref()?;
+    let placed = members
+        .iter()
+        .filter_map(|m| Some((m.spec.name.as_str(), m.status.node_id.as_deref()?)));
+
+    match placement.policy {
+        PlacementPolicy::Spread => {
+            let shared: Vec<&str> = placed
+                .filter(|(_, n)| *n == node_id)
+                .map(|(name, _)| name)
+                .collect();
+            (!shared.is_empty()).then(|| {
+                format!(
+                    "shares node {} with {} of spread group '{}'",
+                    node_id,
+                    sorted_join(shared),
+                    placement.group
+                )
+            })
+        }
+        PlacementPolicy::Pack => {
+            let elsewhere: Vec<&str> = placed
+                .filter(|(_, n)| *n != node_id)
+                .map(|(name, _)| name)
+                .collect();
+            (!elsewhere.is_empty()).then(|| {
+                format!(
+                    "{} of pack group '{}' run on other nodes",
+                    sorted_join(elsewhere),
+                    placement.group
+                )
+            })
+        }
+    }
+}
+
+/// Select node with most available memory (simple load balancing)
+fn most_memory<'a>(nodes: impl IntoIterator<Item = &'a NodeData>) -> Option<&'a NodeData> {
+    nodes
+        .into_iter()
+        .max_by_key(|n| n.resources.available_memory_mb)
+}
+
+fn sorted_join<'a>(items: impl IntoIterator<Item = &'a str>) -> String {
+    let mut items: Vec<&str> = items.into_iter().collect();
+    items.sort_unstable();
+    items.join(", ")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::command::{NodeResources, VmDesiredState};
+    use crate::command::{
+        HostTelemetry, NodeResources, VmDesiredState, VmPhase, VmPlacement, VmStatus,
+    };
     use std::collections::HashMap;
 
     fn make_node(id: &str, name: &str, status: NodeStatus, available_memory: u64) -> NodeData {
@@ -189,6 +413,7 @@ mod tests {
                 available_cpu_cores: 4,
                 available_memory_mb: available_memory,
                 available_storage_gb: 200,
+                host: None,
             },
             labels: HashMap::new(),
             last_heartbeat: "2024-01-01T00:00:00Z".to_string(),
@@ -214,9 +439,46 @@ mod tests {
             image: "ubuntu:22.04".to_string(),
             user_data: None,
             desired_state: VmDesiredState::Running,
+            provisioning: vec![],
+            ssh_key_ids: vec![],
+            run_once: false,
+            disk: None,
+            placement: None,
+        }
+    }
+
+    fn make_member(name: &str, node_id: &str, policy: PlacementPolicy) -> VmData {
+        let mut spec = make_spec(1, 1024, 10);
+        spec.name = name.to_string();
+        spec.placement = Some(VmPlacement {
+            group: "db".to_string(),
+            policy,
+        });
+        VmData {
+            id: format!("vm-{}", name),
+            spec,
+            status: VmStatus {
+                phase: VmPhase::Running,
+                node_id: Some(node_id.to_string()),
+                ip_address: None,
+                message: None,
+            },
+            provisioning: vec![],
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+            trashed: None,
         }
     }
 
+    fn grouped_spec(policy: PlacementPolicy) -> VmSpec {
+        let mut spec = make_spec(1, 1024, 10);
+        spec.placement = Some(VmPlacement {
+            group: "db".to_string(),
+            policy,
+        });
+        spec
+    }
+
     #[test]
     fn test_select_node_prefers_most_available_memory() {
         let scheduler = Scheduler::new();
@@ -231,6 +493,64 @@ mod tests {
         assert_eq!(result.node_id, "node-2"); // Most available memory
     }
 
+    #[test]
+    fn test_select_node_skips_nodes_without_kvm() {
+        let scheduler = Scheduler::new();
+        let mut no_kvm = make_node("node-1", "host1", NodeStatus::Online, 8192);
+        no_kvm.resources.host = Some(HostTelemetry {
+            kvm: false,
+            ..Default::default()
+        });
+        let mut kvm = make_node("node-2", "host2", NodeStatus::Online, 4096);
+        kvm.resources.host = Some(HostTelemetry {
+            kvm: true,
+            ..Default::default()
+        });
+        let spec = make_spec(1, 1024, 10);
+
+        let result = scheduler
+            .select_node(&[no_kvm.clone(), kvm], &spec)
+            .unwrap();
+        assert_eq!(result.node_id, "node-2");
+        assert!(scheduler.select_node(&[no_kvm], &spec).is_err());
+    }
+
+    #[test]
+    fn test_select_node_avoids_nodes_under_pressure() {
+        let mut stalling = make_node("node-1", "host1", NodeStatus::Online, 8192);
+        stalling.resources.host = Some(HostTelemetry {
+            kvm: true,
+            memory_pressure: 35.0,
+            ..Default::default()
+        });
+        let mut stolen = make_node("node-2", "host2", NodeStatus::Online, 6144);
+        stolen.resources.host = Some(HostTelemetry {
+            kvm: true,
+            cpu_steal: 25.0,
+            ..Default::default()
+        });
+        let mut calm = make_node("node-3", "host3", NodeStatus::Online, 2048);
+        calm.resources.host = Some(HostTelemetry {
+            kvm: true,
+            cpu_pressure: 5.0,
+            ..Default::default()
+        });
+        let nodes = vec![stalling.clone(), stolen.clone(), calm];
+        let spec = make_spec(1, 1024, 10);
+
+        // Without a limit, pressure doesn't matter
+        let result = Scheduler::new().select_node(&nodes, &spec).unwrap();
+        assert_eq!(result.node_id, "node-1");
+
+        let scheduler = Scheduler::new().with_max_pressure(20.0);
+        let result = scheduler.select_node(&nodes, &spec).unwrap();
+        assert_eq!(result.node_id, "node-3");
+        assert!(matches!(
+            scheduler.select_node(&[stalling, stolen], &spec),
+            Err(ScheduleError::NodesUnderPressure { .. })
+        ));
+    }
+
     #[test]
     fn test_select_node_filters_offline() {
         let scheduler = Scheduler::new();
@@ -301,4 +621,93 @@ mod tests {
         let result = scheduler.select_node(&nodes, &spec).unwrap();
         assert_eq!(result.node_id, "node-2"); // Matches label
     }
+
+    #[test]
+    fn test_spread_avoids_member_nodes() {
+        let scheduler = Scheduler::new();
+        let nodes = vec![
+            make_node("node-1", "host1", NodeStatus::Online, 8192),
+            make_node("node-2", "host2", NodeStatus::Online, 4096),
+        ];
+        let primary = make_member("db-1", "node-1", PlacementPolicy::Spread);
+        let spec = grouped_spec(PlacementPolicy::Spread);
+
+        let result = scheduler
+            .select_node_in_group(&nodes, &spec, &[&primary])
+            .unwrap();
+        assert_eq!(result.node_id, "node-2");
+        assert!(result.placement_violation.is_none());
+
+        let replica = make_member("db-2", "node-2", PlacementPolicy::Spread);
+        let result = scheduler.select_node_in_group(&nodes, &spec, &[&primary, &replica]);
+        assert!(matches!(
+            result,
+            Err(ScheduleError::SpreadUnsatisfiable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_pack_follows_members_or_reports_violation() {
+        let scheduler = Scheduler::new();
+        let mut nodes = vec![
+            make_node("node-1", "host1", NodeStatus::Online, 2048),
+            make_node("node-2", "host2", NodeStatus::Online, 8192),
+        ];
+        let member = make_member("app-1", "node-1", PlacementPolicy::Pack);
+        let spec = grouped_spec(PlacementPolicy::Pack);
+
+        let result = scheduler
+            .select_node_in_group(&nodes, &spec, &[&member])
+            .unwrap();
+        assert_eq!(result.node_id, "node-1");
+        assert!(result.placement_violation.is_none());
+
+        // The group's node is full: placed elsewhere, with a violation
+        nodes[0].resources.available_memory_mb = 512;
+        let result = scheduler
+            .select_node_in_group(&nodes, &spec, &[&member])
+            .unwrap();
+        assert_eq!(result.node_id, "node-2");
+        assert!(result.placement_violation.unwrap().contains("node-1"));
+    }
+
+    #[test]
+    fn test_group_policy_must_match() {
+        let scheduler = Scheduler::new();
+        let nodes = vec![make_node("node-1", "host1", NodeStatus::Online, 8192)];
+        let member = make_member("db-1", "node-1", PlacementPolicy::Pack);
+        let spec = grouped_spec(PlacementPolicy::Spread);
+
+        let result = scheduler.select_node_in_group(&nodes, &spec, &[&member]);
+        assert!(matches!(
+            result,
+            Err(ScheduleError::PlacementPolicyMismatch {
+                policy: PlacementPolicy::Pack,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_group_members_and_violations() {
+        let a = make_member("db-1", "node-1", PlacementPolicy::Spread);
+        let b = make_member("db-2", "node-1", PlacementPolicy::Spread);
+        let mut other_project = make_member("db-3", "node-1", PlacementPolicy::Spread);
+        other_project.spec.project_slug = "other".to_string();
+        let vms = vec![a.clone(), b.clone(), other_project];
+
+        let members = group_members(&a.spec, &vms);
+        assert_eq!(members.len(), 1);
+        assert_eq!(members[0].spec.name, "db-2");
+        assert_eq!(
+            placement_violation(&a, &members).as_deref(),
+            Some("shares node node-1 with db-2 of spread group 'db'")
+        );
+
+        let mut c = make_member("app-1", "node-1", PlacementPolicy::Pack);
+        let d = make_member("app-2", "node-1", PlacementPolicy::Pack);
+        assert_eq!(placement_violation(&c, &[&d]), None);
+        c.status.node_id = Some("node-2".to_string());
+        assert!(placement_violation(&c, &[&d]).is_some());
+    }
 }
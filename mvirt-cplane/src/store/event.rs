@@ -1,6 +1,8 @@
 //! Events emitted by state machine changes.
 
-use crate::command::{NetworkData, NicData, NodeData, TemplateData, VmData, VolumeData};
+use crate::command::{
+    MigrationData, NetworkData, NicData, NodeData, TemplateData, VmData, VolumeData,
+};
 
 /// Events emitted when state changes occur.
 ///
@@ -62,6 +64,16 @@ pub enum Event {
     /// A VM was deleted.
     VmDeleted { id: String },
 
+    // Migration events
+    /// A VM migration was started.
+    MigrationCreated(MigrationData),
+    /// A VM migration advanced to another phase.
+    MigrationUpdated {
+        id: String,
+        old: MigrationData,
+        new: MigrationData,
+    },
+
     // Volume events
     /// A new volume was created.
     VolumeCreated(VolumeData),
@@ -109,6 +121,7 @@ impl Event {
             | Event::VmUpdated { .. }
             | Event::VmStatusUpdated { .. }
             | Event::VmDeleted { .. } => "vm",
+            Event::MigrationCreated(_) | Event::MigrationUpdated { .. } => "migration",
             Event::VolumeCreated(_)
             | Event::VolumeStatusUpdated { .. }
             | Event::VolumeDeleted { .. } => "volume",
@@ -135,6 +148,8 @@ impl Event {
             Event::VmUpdated { id, .. } => id,
             Event::VmStatusUpdated { id, .. } => id,
             Event::VmDeleted { id } => id,
+            Event::MigrationCreated(m) => &m.id,
+            Event::MigrationUpdated { id, .. } => id,
             Event::VolumeCreated(v) => &v.id,
             Event::VolumeStatusUpdated { id, .. } => id,
             Event::VolumeDeleted { id, .. } => id,
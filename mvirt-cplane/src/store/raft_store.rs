@@ -8,28 +8,32 @@ use mraft::RaftNode;
 use tokio::sync::{RwLock, broadcast};
 
 use crate::command::{
-    AccountData, ClusterData, Command, MembershipData, MembershipScope, NetworkData, NicData,
-    NodeData, OrgContact, OrgData, ProjectData, Response, TemplateData, VmData, VmPhase, VmStatus,
-    VolumeData,
+    AccountData, ChangeRecord, ClusterData, Command, FlavorData, MembershipData, MembershipScope,
+    MigrationData, NetworkData, NicData, NodeData, NotificationData, OrgContact, OrgData,
+    ProjectData, Response, SshKeyData, TemplateData, TrashKind, VmData, VmPhase, VmStatus,
+    VolumeData, WebhookData, WebhookDelivery, new_request_id,
 };
-use crate::scheduler::Scheduler;
+use crate::scheduler::{ScheduleError, Scheduler, group_members};
 use crate::state::ApiState;
 
 use super::error::{Result, StoreError};
 use super::event::Event;
 use super::traits::{
-    AccountStore, BootstrapOutcome, ClusterStore, ControlplaneInfo, ControlplaneStore,
-    CreateClusterRequest, CreateMembershipRequest, CreateNetworkRequest, CreateNicRequest,
-    CreateOnboardingTokenRequest, CreateOrgRequest, CreateProjectRequest,
-    CreateSecurityGroupRequest, CreateSecurityGroupRuleRequest, CreateSnapshotRequest,
-    CreateTemplateRequest, CreateVmRequest, CreateVolumeRequest, DataStore, DeleteNetworkResult,
-    EnsureAccountRequest, Membership, MembershipPeer, NetworkStore, NicStore, NodeStore,
-    OnboardingStore, OrgStore, ProjectStore, RedeemOnboardingTokenRequest, RegisterNodeRequest,
-    ResizeVolumeRequest, SecurityGroupStore, TemplateStore, UpdateClusterRequest,
+    AccountStore, BootstrapOutcome, BuildTemplateRequest, ChangeStore, ClusterStore,
+    ControlplaneInfo, ControlplaneStore, CopyVolumeRequest, CreateClusterRequest,
+    CreateFlavorRequest, CreateMembershipRequest, CreateNetworkRequest, CreateNicRequest,
+    CreateNotificationRequest, CreateOnboardingTokenRequest, CreateOrgRequest,
+    CreateProjectRequest, CreateSecurityGroupRequest, CreateSecurityGroupRuleRequest,
+    CreateSnapshotRequest, CreateSshKeyRequest, CreateTemplateRequest, CreateVmRequest,
+    CreateVolumeRequest, CreateWebhookRequest, DataStore, DeleteNetworkResult,
+    EnsureAccountRequest, FlavorStore, Membership, MembershipPeer, MigrateVmRequest,
+    MigrationStore, NetworkStore, NicStore, NodeStore, NotificationStore, OnboardingStore,
+    OrgStore, ProjectStore, RedeemOnboardingTokenRequest, RegisterNodeRequest, ResizeVolumeRequest,
+    SecurityGroupStore, SshKeyStore, TemplateStore, TrashStore, UpdateClusterRequest,
     UpdateNetworkRequest, UpdateNetworkStatusRequest, UpdateNicRequest, UpdateNicStatusRequest,
     UpdateNodeStatusRequest, UpdateOrgRequest, UpdateSecurityGroupRequest,
     UpdateSecurityGroupRuleRequest, UpdateTemplateStatusRequest, UpdateVmSpecRequest,
-    UpdateVmStatusRequest, UpdateVolumeStatusRequest, VmStore, VolumeStore,
+    UpdateVmStatusRequest, UpdateVolumeStatusRequest, VmStore, VolumeStore, WebhookStore,
 };
 
 /// RaftStore wraps a RaftNode and implements the DataStore trait.
@@ -40,6 +44,7 @@ pub struct RaftStore {
     node: Arc<RwLock<RaftNode<Command, Response, ApiState>>>,
     events: broadcast::Sender<Event>,
     node_id: u64,
+    scheduler: Scheduler,
 }
 
 impl RaftStore {
@@ -53,9 +58,16 @@ impl RaftStore {
             node,
             events,
             node_id,
+            scheduler: Scheduler::new(),
         }
     }
 
+    /// Place new VMs with `scheduler` instead of the default one.
+    pub fn with_scheduler(mut self, scheduler: Scheduler) -> Self {
+        self.scheduler = scheduler;
+        self
+    }
+
     /// Get the event sender (for wiring up to the state machine).
     pub fn event_sender(&self) -> broadcast::Sender<Event> {
         self.events.clone()
@@ -115,7 +127,7 @@ impl NodeStore for RaftStore {
 
     async fn upsert_node(&self, id: &str, req: RegisterNodeRequest) -> Result<NodeData> {
         let cmd = Command::RegisterNode {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             name: req.name,
@@ -134,7 +146,7 @@ impl NodeStore for RaftStore {
 
     async fn update_node_status(&self, id: &str, req: UpdateNodeStatusRequest) -> Result<NodeData> {
         let cmd = Command::UpdateNodeStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             node_id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             status: req.status,
@@ -151,7 +163,7 @@ impl NodeStore for RaftStore {
 
     async fn deregister_node(&self, id: &str) -> Result<()> {
         let cmd = Command::DeregisterNode {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             node_id: id.to_string(),
         };
 
@@ -192,7 +204,7 @@ impl NetworkStore for RaftStore {
 
     async fn create_network(&self, req: CreateNetworkRequest) -> Result<NetworkData> {
         let cmd = Command::CreateNetwork {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             name: req.name,
@@ -204,6 +216,8 @@ impl NetworkStore for RaftStore {
             dns_servers: req.dns_servers,
             ntp_servers: req.ntp_servers,
             is_public: req.is_public,
+            boot_server: req.boot_server,
+            boot_file: req.boot_file,
         };
 
         match self.write_command(cmd).await? {
@@ -216,7 +230,7 @@ impl NetworkStore for RaftStore {
 
     async fn update_network(&self, id: &str, req: UpdateNetworkRequest) -> Result<NetworkData> {
         let cmd = Command::UpdateNetwork {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             dns_servers: req.dns_servers,
@@ -233,7 +247,7 @@ impl NetworkStore for RaftStore {
 
     async fn delete_network(&self, id: &str, force: bool) -> Result<DeleteNetworkResult> {
         let cmd = Command::DeleteNetwork {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             force,
         };
@@ -296,7 +310,7 @@ impl NicStore for RaftStore {
 
     async fn create_nic(&self, req: CreateNicRequest) -> Result<NicData> {
         let cmd = Command::CreateNic {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             project_slug: req.project_slug,
@@ -321,7 +335,7 @@ impl NicStore for RaftStore {
 
     async fn update_nic(&self, id: &str, req: UpdateNicRequest) -> Result<NicData> {
         let cmd = Command::UpdateNic {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             routed_ipv4_prefixes: req.routed_ipv4_prefixes,
@@ -338,7 +352,7 @@ impl NicStore for RaftStore {
 
     async fn delete_nic(&self, id: &str) -> Result<()> {
         let cmd = Command::DeleteNic {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
         };
 
@@ -352,7 +366,7 @@ impl NicStore for RaftStore {
 
     async fn attach_nic(&self, id: &str, vm_id: &str) -> Result<NicData> {
         let cmd = Command::AttachNic {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             vm_id: vm_id.to_string(),
@@ -368,7 +382,7 @@ impl NicStore for RaftStore {
 
     async fn detach_nic(&self, id: &str) -> Result<NicData> {
         let cmd = Command::DetachNic {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
         };
@@ -382,7 +396,7 @@ impl NicStore for RaftStore {
 
     async fn update_nic_status(&self, id: &str, req: UpdateNicStatusRequest) -> Result<NicData> {
         let cmd = Command::UpdateNicStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             phase: req.phase,
@@ -432,7 +446,7 @@ impl VmStore for RaftStore {
 
     async fn create_vm(&self, req: CreateVmRequest) -> Result<VmData> {
         let cmd = Command::CreateVm {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             spec: req.spec,
@@ -450,12 +464,22 @@ impl VmStore for RaftStore {
     async fn create_and_schedule_vm(&self, req: CreateVmRequest) -> Result<VmData> {
         // First, get all nodes to schedule
         let nodes = self.list_nodes().await?;
+        let vms = if req.spec.placement.is_some() {
+            self.list_vms_by_project(&req.spec.project_slug).await?
+        } else {
+            vec![]
+        };
 
         // Use scheduler to pick a node
-        let scheduler = Scheduler::new();
-        let schedule_result = scheduler
-            .select_node(&nodes, &req.spec)
-            .map_err(|e| StoreError::ScheduleFailed(e.to_string()))?;
+        let schedule_result = self
+            .scheduler
+            .select_node_in_group(&nodes, &req.spec, &group_members(&req.spec, &vms))
+            .map_err(|e| match e {
+                ScheduleError::PlacementPolicyMismatch { .. } => {
+                    StoreError::Conflict(e.to_string())
+                }
+                _ => StoreError::ScheduleFailed(e.to_string()),
+            })?;
 
         // Create the VM
         let vm = self.create_vm(req).await?;
@@ -466,7 +490,12 @@ impl VmStore for RaftStore {
                 phase: VmPhase::Scheduled,
                 node_id: Some(schedule_result.node_id),
                 ip_address: None,
-                message: Some(schedule_result.reason),
+                message: Some(
+                    schedule_result
+                        .placement_violation
+                        .map(|v| format!("Placement violated: {}", v))
+                        .unwrap_or(schedule_result.reason),
+                ),
             },
         };
 
@@ -475,7 +504,7 @@ impl VmStore for RaftStore {
 
     async fn update_vm_spec(&self, id: &str, req: UpdateVmSpecRequest) -> Result<VmData> {
         let cmd = Command::UpdateVmSpec {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             desired_state: req.desired_state,
@@ -484,6 +513,7 @@ impl VmStore for RaftStore {
         match self.write_command(cmd).await? {
             Response::Vm(data) => Ok(data),
             Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { code: 409, message } => Err(StoreError::Conflict(message)),
             Response::Error { message, .. } => Err(StoreError::Internal(message)),
             _ => Err(StoreError::Internal("unexpected response".into())),
         }
@@ -491,7 +521,7 @@ impl VmStore for RaftStore {
 
     async fn update_vm_status(&self, id: &str, req: UpdateVmStatusRequest) -> Result<VmData> {
         let cmd = Command::UpdateVmStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             status: req.status,
@@ -507,13 +537,14 @@ impl VmStore for RaftStore {
 
     async fn delete_vm(&self, id: &str) -> Result<()> {
         let cmd = Command::DeleteVm {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
         };
 
         match self.write_command(cmd).await? {
             Response::Deleted { .. } => Ok(()),
             Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { code: 409, message } => Err(StoreError::Conflict(message)),
             Response::Error { message, .. } => Err(StoreError::Internal(message)),
             _ => Err(StoreError::Internal("unexpected response".into())),
         }
@@ -607,7 +638,7 @@ impl OrgStore for RaftStore {
 
     async fn create_org(&self, req: CreateOrgRequest) -> Result<OrgData> {
         let cmd = Command::CreateOrg {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             slug: req.slug,
             name: req.name,
@@ -624,7 +655,7 @@ impl OrgStore for RaftStore {
 
     async fn update_org(&self, slug: &str, req: UpdateOrgRequest) -> Result<OrgData> {
         let cmd = Command::UpdateOrg {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             slug: slug.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             name: req.name,
@@ -641,7 +672,7 @@ impl OrgStore for RaftStore {
 
     async fn delete_org(&self, slug: &str) -> Result<()> {
         let cmd = Command::DeleteOrg {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             slug: slug.to_string(),
         };
 
@@ -677,7 +708,7 @@ impl ProjectStore for RaftStore {
 
     async fn create_project(&self, req: CreateProjectRequest) -> Result<ProjectData> {
         let cmd = Command::CreateProject {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             org_slug: req.org_slug,
             slug: req.slug,
@@ -696,7 +727,7 @@ impl ProjectStore for RaftStore {
 
     async fn delete_project(&self, slug: &str) -> Result<()> {
         let cmd = Command::DeleteProject {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             slug: slug.to_string(),
         };
 
@@ -731,7 +762,7 @@ impl ClusterStore for RaftStore {
 
     async fn create_cluster(&self, req: CreateClusterRequest) -> Result<ClusterData> {
         let cmd = Command::CreateCluster {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             org_slug: req.org_slug,
             slug: req.slug,
@@ -751,7 +782,7 @@ impl ClusterStore for RaftStore {
 
     async fn update_cluster(&self, slug: &str, req: UpdateClusterRequest) -> Result<ClusterData> {
         let cmd = Command::UpdateCluster {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             slug: slug.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             name: req.name,
@@ -769,7 +800,7 @@ impl ClusterStore for RaftStore {
 
     async fn delete_cluster(&self, slug: &str) -> Result<()> {
         let cmd = Command::DeleteCluster {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             slug: slug.to_string(),
         };
 
@@ -784,7 +815,7 @@ impl ClusterStore for RaftStore {
 
     async fn add_node_to_cluster(&self, cluster_slug: &str, node_id: &str) -> Result<ClusterData> {
         let cmd = Command::AddNodeToCluster {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             cluster_slug: cluster_slug.to_string(),
             node_id: node_id.to_string(),
@@ -804,7 +835,7 @@ impl ClusterStore for RaftStore {
         node_id: &str,
     ) -> Result<ClusterData> {
         let cmd = Command::RemoveNodeFromCluster {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             cluster_slug: cluster_slug.to_string(),
             node_id: node_id.to_string(),
@@ -839,7 +870,7 @@ impl VolumeStore for RaftStore {
 
     async fn create_volume(&self, req: CreateVolumeRequest) -> Result<VolumeData> {
         let cmd = Command::CreateVolume {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             project_slug: req.project_slug,
@@ -858,9 +889,28 @@ impl VolumeStore for RaftStore {
         }
     }
 
+    async fn copy_volume(&self, source_id: &str, req: CopyVolumeRequest) -> Result<VolumeData> {
+        let cmd = Command::CopyVolume {
+            request_id: new_request_id(),
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            source_id: source_id.to_string(),
+            node_id: req.node_id,
+            name: req.name,
+        };
+
+        match self.write_command(cmd).await? {
+            Response::Volume(data) => Ok(data),
+            Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { code: 409, message } => Err(StoreError::Conflict(message)),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
     async fn delete_volume(&self, id: &str) -> Result<()> {
         let cmd = Command::DeleteVolume {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
         };
 
@@ -874,7 +924,7 @@ impl VolumeStore for RaftStore {
 
     async fn resize_volume(&self, id: &str, req: ResizeVolumeRequest) -> Result<VolumeData> {
         let cmd = Command::ResizeVolume {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             size_bytes: req.size_bytes,
@@ -895,7 +945,7 @@ impl VolumeStore for RaftStore {
         req: CreateSnapshotRequest,
     ) -> Result<VolumeData> {
         let cmd = Command::CreateSnapshot {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             volume_id: volume_id.to_string(),
@@ -917,7 +967,7 @@ impl VolumeStore for RaftStore {
         req: UpdateVolumeStatusRequest,
     ) -> Result<VolumeData> {
         let cmd = Command::UpdateVolumeStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             phase: req.phase,
@@ -934,6 +984,40 @@ impl VolumeStore for RaftStore {
     }
 }
 
+#[async_trait]
+impl MigrationStore for RaftStore {
+    async fn list_migrations(&self, vm_id: Option<&str>) -> Result<Vec<MigrationData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.list_migrations(vm_id))
+    }
+
+    async fn get_migration(&self, id: &str) -> Result<Option<MigrationData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.get_migration(id))
+    }
+
+    async fn migrate_vm(&self, vm_id: &str, req: MigrateVmRequest) -> Result<MigrationData> {
+        let cmd = Command::CreateMigration {
+            request_id: new_request_id(),
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            vm_id: vm_id.to_string(),
+            target_node_id: req.target_node_id,
+            target_volume_id: uuid::Uuid::new_v4().to_string(),
+        };
+
+        match self.write_command(cmd).await? {
+            Response::Migration(data) => Ok(data),
+            Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { code: 409, message } => Err(StoreError::Conflict(message)),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+}
+
 #[async_trait]
 impl TemplateStore for RaftStore {
     async fn list_templates(&self, node_id: Option<&str>) -> Result<Vec<TemplateData>> {
@@ -956,7 +1040,7 @@ impl TemplateStore for RaftStore {
 
     async fn create_template(&self, req: CreateTemplateRequest) -> Result<TemplateData> {
         let cmd = Command::CreateTemplate {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             project_slug: req.project_slug,
@@ -975,13 +1059,33 @@ impl TemplateStore for RaftStore {
         }
     }
 
+    async fn build_template(&self, req: BuildTemplateRequest) -> Result<TemplateData> {
+        let cmd = Command::BuildTemplate {
+            request_id: new_request_id(),
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            project_slug: req.project_slug,
+            node_id: req.node_id,
+            name: req.name,
+            build: req.build,
+        };
+
+        match self.write_command(cmd).await? {
+            Response::Template(data) => Ok(data),
+            Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { code: 409, message } => Err(StoreError::Conflict(message)),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
     async fn update_template_status(
         &self,
         id: &str,
         req: UpdateTemplateStatusRequest,
     ) -> Result<TemplateData> {
         let cmd = Command::UpdateTemplateStatus {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
             timestamp: Utc::now().to_rfc3339(),
             phase: req.phase,
@@ -1027,7 +1131,7 @@ impl SecurityGroupStore for RaftStore {
         req: CreateSecurityGroupRequest,
     ) -> Result<crate::command::SecurityGroupData> {
         let cmd = Command::CreateSecurityGroup {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             project_slug: req.project_slug,
@@ -1051,7 +1155,7 @@ impl SecurityGroupStore for RaftStore {
         req: UpdateSecurityGroupRequest,
     ) -> Result<crate::command::SecurityGroupData> {
         let cmd = Command::UpdateSecurityGroup {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             id: id.to_string(),
             name: req.name,
@@ -1069,7 +1173,7 @@ impl SecurityGroupStore for RaftStore {
 
     async fn delete_security_group(&self, id: &str) -> Result<()> {
         let cmd = Command::DeleteSecurityGroup {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
         };
         match self.write_command(cmd).await? {
@@ -1089,7 +1193,7 @@ impl SecurityGroupStore for RaftStore {
         req: CreateSecurityGroupRuleRequest,
     ) -> Result<crate::command::SecurityGroupData> {
         let cmd = Command::CreateSecurityGroupRule {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: uuid::Uuid::new_v4().to_string(),
             timestamp: Utc::now().to_rfc3339(),
             security_group_id: security_group_id.to_string(),
@@ -1117,7 +1221,7 @@ impl SecurityGroupStore for RaftStore {
         rule_id: &str,
     ) -> Result<crate::command::SecurityGroupData> {
         let cmd = Command::DeleteSecurityGroupRule {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             security_group_id: security_group_id.to_string(),
             rule_id: rule_id.to_string(),
         };
@@ -1139,7 +1243,7 @@ impl SecurityGroupStore for RaftStore {
         req: UpdateSecurityGroupRuleRequest,
     ) -> Result<crate::command::SecurityGroupData> {
         let cmd = Command::UpdateSecurityGroupRule {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             security_group_id: security_group_id.to_string(),
             rule_id: rule_id.to_string(),
@@ -1156,6 +1260,335 @@ impl SecurityGroupStore for RaftStore {
     }
 }
 
+#[async_trait]
+impl FlavorStore for RaftStore {
+    async fn list_flavors(&self) -> Result<Vec<FlavorData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.list_flavors())
+    }
+
+    async fn get_flavor(&self, id: &str) -> Result<Option<FlavorData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.get_flavor(id))
+    }
+
+    async fn get_flavor_by_name(&self, name: &str) -> Result<Option<FlavorData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.get_flavor_by_name(name))
+    }
+
+    async fn create_flavor(&self, req: CreateFlavorRequest) -> Result<FlavorData> {
+        let cmd = Command::CreateFlavor {
+            request_id: new_request_id(),
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            name: req.name,
+            description: req.description,
+            vcpus: req.vcpus,
+            memory_mb: req.memory_mb,
+            disk_size_bytes: req.disk_size_bytes,
+            network: req.network,
+            user_data: req.user_data,
+        };
+        match self.write_command(cmd).await? {
+            Response::Flavor(flavor) => Ok(flavor),
+            Response::Error { code, message } => match code {
+                409 => Err(StoreError::Conflict(message)),
+                _ => Err(StoreError::Internal(message)),
+            },
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn delete_flavor(&self, id: &str) -> Result<()> {
+        let cmd = Command::DeleteFlavor {
+            request_id: new_request_id(),
+            id: id.to_string(),
+        };
+        match self.write_command(cmd).await? {
+            Response::Deleted { .. } => Ok(()),
+            Response::Error { code, message } => match code {
+                404 => Err(StoreError::NotFound(message)),
+                _ => Err(StoreError::Internal(message)),
+            },
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl SshKeyStore for RaftStore {
+    async fn list_ssh_keys(&self, project_slug: &str) -> Result<Vec<SshKeyData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.list_ssh_keys_by_project(project_slug))
+    }
+
+    async fn get_ssh_key(&self, id: &str) -> Result<Option<SshKeyData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.get_ssh_key(id))
+    }
+
+    async fn create_ssh_key(&self, req: CreateSshKeyRequest) -> Result<SshKeyData> {
+        let cmd = Command::CreateSshKey {
+            request_id: new_request_id(),
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            project_slug: req.project_slug,
+            name: req.name,
+            public_key: req.public_key,
+            fingerprint: req.fingerprint,
+            project_default: req.project_default,
+        };
+        match self.write_command(cmd).await? {
+            Response::SshKey(key) => Ok(key),
+            Response::Error { code, message } => match code {
+                404 => Err(StoreError::NotFound(message)),
+                409 => Err(StoreError::Conflict(message)),
+                _ => Err(StoreError::Internal(message)),
+            },
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn delete_ssh_key(&self, id: &str) -> Result<()> {
+        let cmd = Command::DeleteSshKey {
+            request_id: new_request_id(),
+            id: id.to_string(),
+        };
+        match self.write_command(cmd).await? {
+            Response::Deleted { .. } => Ok(()),
+            Response::Error { code, message } => match code {
+                404 => Err(StoreError::NotFound(message)),
+                _ => Err(StoreError::Internal(message)),
+            },
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+}
+
+#[async_trait]
+impl ChangeStore for RaftStore {
+    async fn record_change(&self, record: ChangeRecord) -> Result<()> {
+        let cmd = Command::RecordChange {
+            request_id: new_request_id(),
+            record,
+        };
+        match self.write_command(cmd).await? {
+            Response::Ack => Ok(()),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn list_changes(&self, resource_id: &str) -> Result<Vec<ChangeRecord>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.list_changes(resource_id))
+    }
+}
+
+#[async_trait]
+impl WebhookStore for RaftStore {
+    async fn list_webhooks(&self) -> Result<Vec<WebhookData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.list_webhooks())
+    }
+
+    async fn get_webhook(&self, id: &str) -> Result<Option<WebhookData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.get_webhook(id))
+    }
+
+    async fn create_webhook(&self, req: CreateWebhookRequest) -> Result<WebhookData> {
+        let cmd = Command::CreateWebhook {
+            request_id: new_request_id(),
+            id: uuid::Uuid::new_v4().to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            url: req.url,
+            events: req.events,
+            secret: req.secret.unwrap_or_else(generate_bare_token),
+            description: req.description,
+        };
+        match self.write_command(cmd).await? {
+            Response::Webhook(webhook) => Ok(webhook),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn delete_webhook(&self, id: &str) -> Result<()> {
+        let cmd = Command::DeleteWebhook {
+            request_id: new_request_id(),
+            id: id.to_string(),
+        };
+        match self.write_command(cmd).await? {
+            Response::Deleted { .. } => Ok(()),
+            Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn record_webhook_delivery(&self, delivery: WebhookDelivery) -> Result<()> {
+        let cmd = Command::RecordWebhookDelivery {
+            request_id: new_request_id(),
+            delivery,
+        };
+        match self.write_command(cmd).await? {
+            Response::Ack => Ok(()),
+            Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn list_webhook_deliveries(&self, webhook_id: &str) -> Result<Vec<WebhookDelivery>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.list_webhook_deliveries(webhook_id))
+    }
+}
+
+#[async_trait]
+impl NotificationStore for RaftStore {
+    async fn list_notifications(&self) -> Result<Vec<NotificationData>> {
+        let node = self.node.read().await;
+        let state = node.get_state().await;
+        Ok(state.list_notifications())
+    }
+
+    async fn create_notification(
+        &self,
+        req: CreateNotificationRequest,
+    ) -> Result<NotificationData> {
+        let cmd = Command::CreateNotification {
+            request_id: new_request_id(),
+            notification: NotificationData {
+                id: uuid::Uuid::new_v4().to_string(),
+                severity: req.severity,
+                title: req.title,
+                message: req.message,
+                involved_object: req.involved_object,
+                read: false,
+                created_at: Utc::now().to_rfc3339(),
+            },
+        };
+        match self.write_command(cmd).await? {
+            Response::Notification(notification) => Ok(notification),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn mark_notification_read(&self, id: &str) -> Result<NotificationData> {
+        let cmd = Command::MarkNotificationRead {
+            request_id: new_request_id(),
+            id: id.to_string(),
+        };
+        match self.write_command(cmd).await? {
+            Response::Notification(notification) => Ok(notification),
+            Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn mark_all_notifications_read(&self) -> Result<()> {
+        let cmd = Command::MarkAllNotificationsRead {
+            request_id: new_request_id(),
+        };
+        match self.write_command(cmd).await? {
+            Response::Ack => Ok(()),
+            Response::Error { message, .. } => Err(StoreError::Internal(message)),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+}
+
+impl RaftStore {
+    async fn trash_command(&self, kind: TrashKind, id: &str, purge_at: String) -> Result<Response> {
+        let cmd = Command::TrashResource {
+            request_id: new_request_id(),
+            kind,
+            id: id.to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            purge_at,
+        };
+        trash_response(self.write_command(cmd).await?)
+    }
+
+    async fn restore_command(&self, kind: TrashKind, id: &str) -> Result<Response> {
+        let cmd = Command::RestoreResource {
+            request_id: new_request_id(),
+            kind,
+            id: id.to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+        };
+        trash_response(self.write_command(cmd).await?)
+    }
+}
+
+fn trash_response(response: Response) -> Result<Response> {
+    match response {
+        Response::Error { code: 404, message } => Err(StoreError::NotFound(message)),
+        Response::Error { code: 409, message } => Err(StoreError::Conflict(message)),
+        Response::Error { message, .. } => Err(StoreError::Internal(message)),
+        other => Ok(other),
+    }
+}
+
+#[async_trait]
+impl TrashStore for RaftStore {
+    async fn trash_vm(&self, id: &str, purge_at: String) -> Result<VmData> {
+        match self.trash_command(TrashKind::Vm, id, purge_at).await? {
+            Response::Vm(data) => Ok(data),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn restore_vm(&self, id: &str) -> Result<VmData> {
+        match self.restore_command(TrashKind::Vm, id).await? {
+            Response::Vm(data) => Ok(data),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn trash_volume(&self, id: &str, purge_at: String) -> Result<VolumeData> {
+        match self.trash_command(TrashKind::Volume, id, purge_at).await? {
+            Response::Volume(data) => Ok(data),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn restore_volume(&self, id: &str) -> Result<VolumeData> {
+        match self.restore_command(TrashKind::Volume, id).await? {
+            Response::Volume(data) => Ok(data),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn trash_network(&self, id: &str, purge_at: String) -> Result<NetworkData> {
+        match self.trash_command(TrashKind::Network, id, purge_at).await? {
+            Response::Network(data) => Ok(data),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+
+    async fn restore_network(&self, id: &str) -> Result<NetworkData> {
+        match self.restore_command(TrashKind::Network, id).await? {
+            Response::Network(data) => Ok(data),
+            _ => Err(StoreError::Internal("unexpected response".into())),
+        }
+    }
+}
+
 #[async_trait]
 impl OnboardingStore for RaftStore {
     async fn ensure_internal_ca(&self, deployment_name: &str) -> Result<crate::ca::InternalCa> {
@@ -1173,7 +1606,7 @@ impl OnboardingStore for RaftStore {
         let ca = crate::ca::generate_root_ca(deployment_name)
             .map_err(|e| StoreError::Internal(format!("generate CA: {e}")))?;
         let cmd = Command::EnsureInternalCa {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             ca: ca.clone(),
         };
@@ -1206,7 +1639,7 @@ impl OnboardingStore for RaftStore {
             crate::ca::sign_server_cert(&ca, dns_names, crate::ca::new_serial())
                 .map_err(|e| StoreError::Internal(format!("sign server cert: {e}")))?;
         let cmd = Command::UpdateServerCert {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             cert_pem: signed.cert_pem.clone(),
             key_pem: key_pem.clone(),
@@ -1242,7 +1675,7 @@ impl OnboardingStore for RaftStore {
         let node_id = format!("node_{}", uuid::Uuid::new_v4().simple());
 
         let cmd = Command::CreateOnboardingToken {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             id: id.clone(),
             token_hash_hex: hash_hex,
@@ -1273,7 +1706,7 @@ impl OnboardingStore for RaftStore {
 
     async fn delete_onboarding_token(&self, cluster_slug: &str, id: &str) -> Result<()> {
         let cmd = Command::DeleteOnboardingToken {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             cluster_slug: cluster_slug.to_string(),
             id: id.to_string(),
         };
@@ -1291,7 +1724,7 @@ impl OnboardingStore for RaftStore {
     ) -> Result<BootstrapOutcome> {
         let hash_hex = sha256_hex(req.token.as_bytes());
         let cmd = Command::RedeemOnboardingToken {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             token_hash_hex: hash_hex,
             csr_pem: req.csr_pem,
@@ -1327,7 +1760,7 @@ impl OnboardingStore for RaftStore {
         reason: crate::command::RevocationReason,
     ) -> Result<()> {
         let cmd = Command::RevokeNodeCert {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             node_id: node_id.to_string(),
             reason,
@@ -1345,7 +1778,7 @@ impl OnboardingStore for RaftStore {
 impl AccountStore for RaftStore {
     async fn ensure_account_from_oidc(&self, req: EnsureAccountRequest) -> Result<AccountData> {
         let cmd = Command::EnsureAccountFromOidc {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             new_id: format!("acc_{}", uuid::Uuid::new_v4().simple()),
             iss: req.iss,
@@ -1365,7 +1798,7 @@ impl AccountStore for RaftStore {
         req: crate::store::CreateAccountByEmailRequest,
     ) -> Result<AccountData> {
         let cmd = Command::CreateAccountByEmail {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             id: format!("acc_{}", uuid::Uuid::new_v4().simple()),
             email: req.email,
@@ -1400,7 +1833,7 @@ impl AccountStore for RaftStore {
 
     async fn create_membership(&self, req: CreateMembershipRequest) -> Result<MembershipData> {
         let cmd = Command::CreateMembership {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             id: format!("mbr_{}", uuid::Uuid::new_v4().simple()),
             account_id: req.account_id,
@@ -1419,7 +1852,7 @@ impl AccountStore for RaftStore {
 
     async fn delete_membership(&self, id: &str) -> Result<()> {
         let cmd = Command::DeleteMembership {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             id: id.to_string(),
         };
         match self.write_command(cmd).await? {
@@ -1447,7 +1880,7 @@ impl AccountStore for RaftStore {
 
     async fn bootstrap_initial_platform_admin(&self, account_id: &str) -> Result<()> {
         let cmd = Command::BootstrapInitialPlatformAdmin {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             id: format!("mbr_{}", uuid::Uuid::new_v4().simple()),
             account_id: account_id.to_string(),
@@ -1474,7 +1907,7 @@ impl crate::store::ServiceAccountStore for RaftStore {
         req: crate::store::CreateServiceAccountRequest,
     ) -> Result<AccountData> {
         let cmd = Command::CreateServiceAccount {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             account_id: format!("acc_{}", uuid::Uuid::new_v4().simple()),
             membership_id: format!("mbr_{}", uuid::Uuid::new_v4().simple()),
@@ -1494,7 +1927,7 @@ impl crate::store::ServiceAccountStore for RaftStore {
 
     async fn delete_service_account(&self, account_id: &str) -> Result<()> {
         let cmd = Command::DeleteServiceAccount {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             account_id: account_id.to_string(),
         };
@@ -1521,7 +1954,7 @@ impl crate::store::ServiceAccountStore for RaftStore {
         req: crate::store::CreateStaticApiKeyRequest,
     ) -> Result<crate::command::ApiKeyData> {
         let cmd = Command::CreateStaticApiKey {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             id: format!("key_{}", uuid::Uuid::new_v4().simple()),
             account_id: req.account_id,
@@ -1543,7 +1976,7 @@ impl crate::store::ServiceAccountStore for RaftStore {
 
     async fn revoke_static_api_key(&self, id: &str) -> Result<crate::command::ApiKeyData> {
         let cmd = Command::RevokeStaticApiKey {
-            request_id: uuid::Uuid::new_v4().to_string(),
+            request_id: new_request_id(),
             timestamp: Utc::now().to_rfc3339(),
             id: id.to_string(),
         };
@@ -7,10 +7,11 @@ use async_trait::async_trait;
 use tokio::sync::broadcast;
 
 use crate::command::{
-    AccountData, ClusterData, MembershipData, MembershipScope, NetworkData, NicData, NodeData,
-    NodeResources, NodeStatus, OrgContact, OrgData, ProjectData, Role, RuleDirection,
-    SecurityGroupData, TemplateData, TemplatePhase, VmData, VmDesiredState, VmSpec, VmStatus,
-    VolumeData,
+    AccountData, ChangeRecord, ClusterData, FlavorData, InvolvedObject, MembershipData,
+    MembershipScope, MigrationData, NetworkData, NicData, NodeData, NodeResources, NodeStatus,
+    NotificationData, NotificationSeverity, OrgContact, OrgData, ProjectData, Role, RuleDirection,
+    SecurityGroupData, SshKeyData, TemplateBuild, TemplateData, TemplatePhase, VmData,
+    VmDesiredState, VmSpec, VmStatus, VolumeData, WebhookData, WebhookDelivery,
 };
 use std::collections::HashMap;
 
@@ -53,6 +54,8 @@ pub struct CreateNetworkRequest {
     pub dns_servers: Vec<String>,
     pub ntp_servers: Vec<String>,
     pub is_public: bool,
+    pub boot_server: Option<String>,
+    pub boot_file: Option<String>,
 }
 
 /// Request to update a network.
@@ -111,6 +114,12 @@ pub struct UpdateVmStatusRequest {
     pub status: VmStatus,
 }
 
+/// Request to move a stopped VM to another node.
+#[derive(Debug, Clone)]
+pub struct MigrateVmRequest {
+    pub target_node_id: String,
+}
+
 // =============================================================================
 // Project Request DTOs
 // =============================================================================
@@ -224,6 +233,13 @@ pub struct CreateVolumeRequest {
     pub template_id: Option<String>, // Clone from template
 }
 
+/// Request to copy a volume to another node.
+#[derive(Debug, Clone)]
+pub struct CopyVolumeRequest {
+    pub node_id: String,
+    pub name: String,
+}
+
 /// Request to resize a volume.
 #[derive(Debug, Clone)]
 pub struct ResizeVolumeRequest {
@@ -251,6 +267,16 @@ pub struct CreateTemplateRequest {
     pub total_bytes: u64,
 }
 
+/// Request to build a template from a VM run. The build's volume, VM and
+/// NIC must already exist.
+#[derive(Debug, Clone)]
+pub struct BuildTemplateRequest {
+    pub project_slug: String,
+    pub node_id: String,
+    pub name: String,
+    pub build: TemplateBuild,
+}
+
 /// Request to update template import status.
 #[derive(Debug, Clone)]
 pub struct UpdateTemplateStatusRequest {
@@ -446,6 +472,19 @@ pub trait VmStore: Send + Sync {
     async fn delete_vm(&self, id: &str) -> Result<()>;
 }
 
+/// Store trait for VM migrations.
+#[async_trait]
+pub trait MigrationStore: Send + Sync {
+    /// List migrations, optionally only those of one VM.
+    async fn list_migrations(&self, vm_id: Option<&str>) -> Result<Vec<MigrationData>>;
+
+    /// Get a migration by ID.
+    async fn get_migration(&self, id: &str) -> Result<Option<MigrationData>>;
+
+    /// Start moving a stopped VM to another node.
+    async fn migrate_vm(&self, vm_id: &str, req: MigrateVmRequest) -> Result<MigrationData>;
+}
+
 /// Store trait for control plane operations.
 #[async_trait]
 pub trait ControlplaneStore: Send + Sync {
@@ -668,6 +707,9 @@ pub trait VolumeStore: Send + Sync {
     /// Create a new volume.
     async fn create_volume(&self, req: CreateVolumeRequest) -> Result<VolumeData>;
 
+    /// Copy a Ready volume to another node under a new name.
+    async fn copy_volume(&self, source_id: &str, req: CopyVolumeRequest) -> Result<VolumeData>;
+
     /// Delete a volume.
     async fn delete_volume(&self, id: &str) -> Result<()>;
 
@@ -713,6 +755,9 @@ pub trait TemplateStore: Send + Sync {
     /// Create a template (with optional source_url for import).
     async fn create_template(&self, req: CreateTemplateRequest) -> Result<TemplateData>;
 
+    /// Create a template that is built from a VM run.
+    async fn build_template(&self, req: BuildTemplateRequest) -> Result<TemplateData>;
+
     /// Update a template's import status.
     async fn update_template_status(
         &self,
@@ -812,6 +857,184 @@ pub struct UpdateSecurityGroupRuleRequest {
     pub description: Option<Option<String>>,
 }
 
+// =============================================================================
+// Flavor Request DTOs
+// =============================================================================
+
+/// Request to create a flavor.
+#[derive(Debug, Clone)]
+pub struct CreateFlavorRequest {
+    pub name: String,
+    pub description: Option<String>,
+    pub vcpus: u32,
+    pub memory_mb: u64,
+    pub disk_size_bytes: Option<u64>,
+    pub network: Option<String>,
+    pub user_data: Option<String>,
+}
+
+/// Store trait for flavor (VM profile) operations.
+#[async_trait]
+pub trait FlavorStore: Send + Sync {
+    /// List all flavors.
+    async fn list_flavors(&self) -> Result<Vec<FlavorData>>;
+
+    /// Get a flavor by ID.
+    async fn get_flavor(&self, id: &str) -> Result<Option<FlavorData>>;
+
+    /// Get a flavor by name.
+    async fn get_flavor_by_name(&self, name: &str) -> Result<Option<FlavorData>>;
+
+    /// Create a new flavor.
+    async fn create_flavor(&self, req: CreateFlavorRequest) -> Result<FlavorData>;
+
+    /// Delete a flavor. VMs created from it keep their resources.
+    async fn delete_flavor(&self, id: &str) -> Result<()>;
+}
+
+// =============================================================================
+// SSH Key Request DTOs
+// =============================================================================
+
+/// Request to register an SSH public key in a project.
+#[derive(Debug, Clone)]
+pub struct CreateSshKeyRequest {
+    pub project_slug: String,
+    pub name: String,
+    pub public_key: String,
+    pub fingerprint: String,
+    pub project_default: bool,
+}
+
+/// Store trait for project SSH keys.
+#[async_trait]
+pub trait SshKeyStore: Send + Sync {
+    /// List SSH keys in a project.
+    async fn list_ssh_keys(&self, project_slug: &str) -> Result<Vec<SshKeyData>>;
+
+    /// Get an SSH key by ID.
+    async fn get_ssh_key(&self, id: &str) -> Result<Option<SshKeyData>>;
+
+    /// Register a new SSH key.
+    async fn create_ssh_key(&self, req: CreateSshKeyRequest) -> Result<SshKeyData>;
+
+    /// Delete an SSH key. Already-provisioned VMs keep it in authorized_keys.
+    async fn delete_ssh_key(&self, id: &str) -> Result<()>;
+}
+
+// =============================================================================
+// Change History Store Trait
+// =============================================================================
+
+/// Store trait for the per-resource change history.
+#[async_trait]
+pub trait ChangeStore: Send + Sync {
+    /// Append a change record. The oldest records of a busy resource are
+    /// dropped beyond a fixed cap.
+    async fn record_change(&self, record: ChangeRecord) -> Result<()>;
+
+    /// Change records of one resource, oldest first. History outlives the
+    /// resource itself.
+    async fn list_changes(&self, resource_id: &str) -> Result<Vec<ChangeRecord>>;
+}
+
+// =============================================================================
+// Webhook Store Trait
+// =============================================================================
+
+/// Request to register a webhook.
+#[derive(Debug, Clone)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    pub events: Vec<String>,
+    /// Signing secret; generated when `None`.
+    pub secret: Option<String>,
+    pub description: Option<String>,
+}
+
+/// Store trait for webhooks and their delivery history.
+#[async_trait]
+pub trait WebhookStore: Send + Sync {
+    /// List all webhooks.
+    async fn list_webhooks(&self) -> Result<Vec<WebhookData>>;
+
+    /// Get a webhook by ID.
+    async fn get_webhook(&self, id: &str) -> Result<Option<WebhookData>>;
+
+    /// Register a new webhook.
+    async fn create_webhook(&self, req: CreateWebhookRequest) -> Result<WebhookData>;
+
+    /// Delete a webhook along with its delivery history.
+    async fn delete_webhook(&self, id: &str) -> Result<()>;
+
+    /// Insert or update a delivery. Fails with `NotFound` once the webhook
+    /// is gone.
+    async fn record_webhook_delivery(&self, delivery: WebhookDelivery) -> Result<()>;
+
+    /// Recent deliveries of a webhook, oldest first.
+    async fn list_webhook_deliveries(&self, webhook_id: &str) -> Result<Vec<WebhookDelivery>>;
+}
+
+// =============================================================================
+// Notification Store Trait
+// =============================================================================
+
+/// Request to raise a notification.
+#[derive(Debug, Clone)]
+pub struct CreateNotificationRequest {
+    pub severity: NotificationSeverity,
+    pub title: String,
+    pub message: String,
+    pub involved_object: InvolvedObject,
+}
+
+/// Store trait for operator notifications.
+#[async_trait]
+pub trait NotificationStore: Send + Sync {
+    /// List notifications within retention, newest first.
+    async fn list_notifications(&self) -> Result<Vec<NotificationData>>;
+
+    /// Raise a new, unread notification.
+    async fn create_notification(&self, req: CreateNotificationRequest)
+    -> Result<NotificationData>;
+
+    /// Mark one notification as read.
+    async fn mark_notification_read(&self, id: &str) -> Result<NotificationData>;
+
+    /// Mark every notification as read.
+    async fn mark_all_notifications_read(&self) -> Result<()>;
+}
+
+// =============================================================================
+// Trash Store Trait
+// =============================================================================
+
+/// Store trait for soft-deleted VMs, volumes and networks.
+///
+/// A trashed resource keeps its ID and name but is hidden from listings
+/// and refuses changes until it is restored or purged. `purge_at` is an
+/// RFC 3339 timestamp.
+#[async_trait]
+pub trait TrashStore: Send + Sync {
+    /// Move a VM to the trash. The VM is stopped.
+    async fn trash_vm(&self, id: &str, purge_at: String) -> Result<VmData>;
+
+    /// Bring a trashed VM back, still stopped.
+    async fn restore_vm(&self, id: &str) -> Result<VmData>;
+
+    /// Move a volume to the trash. Fails while it boots a live VM.
+    async fn trash_volume(&self, id: &str, purge_at: String) -> Result<VolumeData>;
+
+    /// Bring a trashed volume back.
+    async fn restore_volume(&self, id: &str) -> Result<VolumeData>;
+
+    /// Move a network to the trash. Fails while it has NICs.
+    async fn trash_network(&self, id: &str, purge_at: String) -> Result<NetworkData>;
+
+    /// Bring a trashed network back.
+    async fn restore_network(&self, id: &str) -> Result<NetworkData>;
+}
+
 // =============================================================================
 // Composite DataStore Trait
 // =============================================================================
@@ -823,9 +1046,15 @@ pub struct UpdateSecurityGroupRuleRequest {
 /// - Network CRUD operations
 /// - NIC CRUD operations
 /// - VM CRUD operations
+/// - VM migrations
 /// - Project CRUD operations
 /// - Volume CRUD operations
 /// - Template and import operations
+/// - Flavor (VM profile) operations
+/// - SSH key operations
+/// - Change history
+/// - Webhooks and their deliveries
+/// - Soft-delete (trash) and restore
 /// - Control plane management operations
 /// - Event subscription for real-time updates
 pub trait DataStore:
@@ -833,6 +1062,7 @@ pub trait DataStore:
     + NetworkStore
     + NicStore
     + VmStore
+    + MigrationStore
     + OrgStore
     + ProjectStore
     + ClusterStore
@@ -842,6 +1072,12 @@ pub trait DataStore:
     + VolumeStore
     + TemplateStore
     + SecurityGroupStore
+    + FlavorStore
+    + SshKeyStore
+    + ChangeStore
+    + WebhookStore
+    + NotificationStore
+    + TrashStore
     + ControlplaneStore
     + Send
     + Sync
@@ -0,0 +1,99 @@
+//! Purging of soft-deleted resources.
+//!
+//! With `--trash-retention` set, deleting a VM, volume or network over REST
+//! only moves it to the trash (see `Command::TrashResource`): it is hidden
+//! and deactivated but can be restored until its `purge_at` time. The
+//! purger runs on the raft leader and deletes expired entries for good.
+//! Once purged, whatever is left on the node is picked up by the GC like
+//! after any other delete.
+
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tracing::{info, warn};
+
+use crate::command::{TrashKind, Trashed};
+use crate::reconciler::Ctx;
+use crate::state::ApiState;
+use crate::store::{ControlplaneStore, NetworkStore, StoreError, VmStore, VolumeStore};
+
+pub struct TrashPurger {
+    ctx: Ctx,
+    interval: Duration,
+}
+
+impl TrashPurger {
+    pub fn new(ctx: Ctx, interval: Duration) -> Self {
+        Self { ctx, interval }
+    }
+
+    /// Spawn the periodic purge loop. Returns immediately.
+    pub fn spawn(self) {
+        info!(interval = ?self.interval, "starting trash purger");
+        tokio::spawn(async move {
+            let mut tick = tokio::time::interval(self.interval);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                tick.tick().await;
+                self.run_once().await;
+            }
+        });
+    }
+
+    /// Delete every trashed resource past its purge time. Followers skip
+    /// the pass so each entry is purged once.
+    pub async fn run_once(&self) {
+        match self.ctx.store.get_controlplane_info().await {
+            Ok(info) if info.is_leader => {}
+            Ok(_) => return,
+            Err(e) => {
+                warn!(error = %e, "trash: controlplane info unavailable");
+                return;
+            }
+        }
+
+        let state = self.ctx.store.snapshot().await;
+        for (kind, id) in expired(&state, Utc::now()) {
+            let result = match kind {
+                TrashKind::Vm => self.ctx.store.delete_vm(&id).await,
+                TrashKind::Volume => self.ctx.store.delete_volume(&id).await,
+                TrashKind::Network => self.ctx.store.delete_network(&id, false).await.map(|_| ()),
+            };
+            match result {
+                // A purged VM takes its cloned boot volume along
+                Ok(()) | Err(StoreError::NotFound(_)) => {
+                    info!(kind = kind.label(), id = %id, "trash: purged");
+                    self.ctx.audit.resource_purged(kind.label(), &id);
+                }
+                Err(e) => {
+                    warn!(kind = kind.label(), id = %id, error = %e, "trash: purge failed")
+                }
+            }
+        }
+    }
+}
+
+fn is_expired(trashed: Option<&Trashed>, now: DateTime<Utc>) -> bool {
+    trashed.is_some_and(|t| DateTime::parse_from_rfc3339(&t.purge_at).is_ok_and(|at| at <= now))
+}
+
+/// Trashed resources due for purging at `now`, VMs first so their volumes
+/// are free by the time those come up.
+pub fn expired(state: &ApiState, now: DateTime<Utc>) -> Vec<(TrashKind, String)> {
+    let vms = state
+        .list_vms(None)
+        .into_iter()
+        .filter(|vm| is_expired(vm.trashed.as_ref(), now))
+        .map(|vm| (TrashKind::Vm, vm.id));
+    let volumes = state
+        .list_volumes(None, None)
+        .into_iter()
+        .filter(|vol| is_expired(vol.trashed.as_ref(), now))
+        .map(|vol| (TrashKind::Volume, vol.id));
+    let networks = state
+        .list_networks()
+        .into_iter()
+        .filter(|net| is_expired(net.trashed.as_ref(), now))
+        .map(|net| (TrashKind::Network, net.id));
+    vms.chain(volumes).chain(networks).collect()
+}
@@ -16,23 +16,25 @@ use std::net::SocketAddr;
 use std::sync::{Arc, Mutex as StdMutex};
 
 use anyhow::{Context, Result, anyhow};
+use mvirt_common::request_id::RequestIdChannel;
 use mvirt_daemon_protos::net::net_service_client::NetServiceClient;
+use mvirt_daemon_protos::vmm::pod_service_client::PodServiceClient;
 use mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient;
 use mvirt_daemon_protos::zfs::zfs_service_client::ZfsServiceClient;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::RwLock;
 use tokio_rustls::TlsAcceptor;
-use tonic::transport::{Channel, Endpoint, Uri};
+use tonic::transport::{Endpoint, Uri};
 use tower::service_fn;
 use tracing::{info, warn};
 
 use crate::ca::extract_identity_from_der;
-use crate::command::{NodeResources, NodeStatus, VmPhase, VmStatus};
+use crate::command::{HostTelemetry, NodeResources, NodeStatus, VmDesiredState, VmPhase, VmStatus};
 use crate::grpc::proto::node_agent_client::NodeAgentClient;
 use crate::grpc::proto::node_event::Kind as NodeEventKind;
 use crate::grpc::proto::{
-    CurrentResourcesRequest, NodeResources as ProtoNodeResources, VmStateChanged,
-    WatchEventsRequest,
+    CachedVmSpec, CurrentResourcesRequest, HostTelemetry as ProtoHostTelemetry,
+    NodeResources as ProtoNodeResources, SyncSpecsRequest, VmStateChanged, WatchEventsRequest,
 };
 use crate::store::{DataStore, UpdateNodeStatusRequest};
 
@@ -48,6 +50,31 @@ impl From<ProtoNodeResources> for NodeResources {
             available_cpu_cores: r.available_cpu_cores,
             available_memory_mb: r.available_memory_mb,
             available_storage_gb: r.available_storage_gb,
+            host: r.host.map(Into::into),
+        }
+    }
+}
+
+impl From<ProtoHostTelemetry> for HostTelemetry {
+    fn from(h: ProtoHostTelemetry) -> Self {
+        Self {
+            cpu_model: h.cpu_model,
+            cpu_sockets: h.cpu_sockets,
+            threads_per_core: h.threads_per_core,
+            load1: h.load1,
+            load5: h.load5,
+            load15: h.load15,
+            kernel_version: h.kernel_version,
+            kvm: h.kvm,
+            hugepage_size_kb: h.hugepage_size_kb,
+            hugepages_total: h.hugepages_total,
+            hugepages_free: h.hugepages_free,
+            zfs_pool: h.zfs_pool,
+            zfs_pool_health: h.zfs_pool_health,
+            cpu_pressure: h.cpu_pressure,
+            memory_pressure: h.memory_pressure,
+            io_pressure: h.io_pressure,
+            cpu_steal: h.cpu_steal,
         }
     }
 }
@@ -214,7 +241,7 @@ async fn handle_node_event<S: DataStore + ?Sized>(
 }
 
 async fn pull_resources<S: DataStore + ?Sized>(
-    agent: &mut NodeAgentClient<Channel>,
+    agent: &mut NodeAgentClient<RequestIdChannel>,
     store: &Arc<S>,
     node_id: &str,
 ) {
@@ -241,17 +268,51 @@ async fn pull_resources<S: DataStore + ?Sized>(
     }
 }
 
-/// Per-node connection state. All four clients share the same underlying
+/// Push the desired state of the node's VMs so it can keep them up while
+/// the tunnel is down. Errors are logged only, like [`pull_resources`].
+async fn push_specs<S: DataStore + ?Sized>(
+    agent: &mut NodeAgentClient<RequestIdChannel>,
+    store: &Arc<S>,
+    node_id: &str,
+) {
+    let vms = match store.list_vms_by_node(node_id).await {
+        Ok(vms) => vms,
+        Err(e) => {
+            warn!(node_id = %node_id, error = %e, "list_vms_by_node for spec push failed");
+            return;
+        }
+    };
+    let req = SyncSpecsRequest {
+        vms: vms
+            .into_iter()
+            .map(|vm| CachedVmSpec {
+                vm_id: vm.id,
+                name: vm.spec.name,
+                running: vm.spec.desired_state == VmDesiredState::Running,
+                run_once: vm.spec.run_once,
+            })
+            .collect(),
+    };
+    match agent.sync_specs(tonic::Request::new(req)).await {
+        Ok(_) => {}
+        // Nodes older than the spec cache
+        Err(e) if e.code() == tonic::Code::Unimplemented => {}
+        Err(e) => warn!(node_id = %node_id, error = %e, "SyncSpecs RPC failed"),
+    }
+}
+
+/// Per-node connection state. All five clients share the same underlying
 /// HTTP/2-over-TLS connection (the inverted tunnel socket).
 pub struct NodeHandle {
     pub node_id: String,
     pub name: String,
     pub cluster_slug: String,
     pub address: String,
-    pub agent: NodeAgentClient<Channel>,
-    pub vmm: VmServiceClient<Channel>,
-    pub zfs: ZfsServiceClient<Channel>,
-    pub net: NetServiceClient<Channel>,
+    pub agent: NodeAgentClient<RequestIdChannel>,
+    pub vmm: VmServiceClient<RequestIdChannel>,
+    pub pods: PodServiceClient<RequestIdChannel>,
+    pub zfs: ZfsServiceClient<RequestIdChannel>,
+    pub net: NetServiceClient<RequestIdChannel>,
 }
 
 #[derive(Default)]
@@ -413,10 +474,14 @@ async fn handle_connection(
     // Mark the node online + pull its initial resource snapshot. Without a
     // first pull, the cplane keeps a zero-resource view of the node and the
     // scheduler skips it for every placement decision.
+    // Reconciles run under the request ID of the change they apply, which
+    // the clients pass on to the node
+    let channel = RequestIdChannel::new(channel);
     let agent = NodeAgentClient::new(channel.clone());
     {
         let mut agent = agent.clone();
         pull_resources(&mut agent, &store, &node_id).await;
+        push_specs(&mut agent, &store, &node_id).await;
     }
 
     let handle = Arc::new(NodeHandle {
@@ -426,6 +491,7 @@ async fn handle_connection(
         address: peer.to_string(),
         agent,
         vmm: VmServiceClient::new(channel.clone()),
+        pods: PodServiceClient::new(channel.clone()),
         zfs: ZfsServiceClient::new(channel.clone()),
         net: NetServiceClient::new(channel),
     });
@@ -462,6 +528,7 @@ async fn handle_connection(
                     _ = resource_tick.tick() => {
                         let mut agent = handle.agent.clone();
                         pull_resources(&mut agent, &store, &node_id).await;
+                        push_specs(&mut agent, &store, &node_id).await;
                     }
                 }
             }
@@ -0,0 +1,489 @@
+//! Rolling daemon restarts for upgrades.
+//!
+//! Upgrading a host is installing the new packages and restarting its
+//! daemons; the restarts are what can hurt workloads. The controller runs
+//! them cluster-wide one node and one daemon at a time through
+//! NodeAgent.RestartDaemon, so only one daemon in the cluster is down at
+//! any moment. The node agent drains vhost-user VMs before restarting net
+//! and waits for each daemon to serve again; the controller waits for raft
+//! to be healthy before every step. The first failed step stops the
+//! rollout, leaving the remaining nodes untouched.
+//!
+//! Progress lives in memory on the peer running the rollout. A leader
+//! change fails the rollout at its next step; it is then restarted on the
+//! new leader, where already-upgraded daemons simply restart once more.
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+use tracing::{info, warn};
+
+use crate::grpc::proto::{
+    DaemonKind, DaemonVersionsRequest, RestartDaemonRequest, RestartDaemonResponse,
+};
+use crate::reconciler::Ctx;
+use crate::store::DataStore;
+
+const RAFT_HEALTH_TIMEOUT: Duration = Duration::from_secs(60);
+const RAFT_HEALTH_POLL: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Daemon {
+    Vmm,
+    Zfs,
+    Net,
+}
+
+impl Daemon {
+    /// Default restart order: storage and network before the VMs that use
+    /// them.
+    pub const ALL: [Daemon; 3] = [Daemon::Zfs, Daemon::Net, Daemon::Vmm];
+
+    fn kind(self) -> DaemonKind {
+        match self {
+            Daemon::Vmm => DaemonKind::Vmm,
+            Daemon::Zfs => DaemonKind::Zfs,
+            Daemon::Net => DaemonKind::Net,
+        }
+    }
+
+    fn from_kind(kind: DaemonKind) -> Option<Self> {
+        match kind {
+            DaemonKind::Vmm => Some(Daemon::Vmm),
+            DaemonKind::Zfs => Some(Daemon::Zfs),
+            DaemonKind::Net => Some(Daemon::Net),
+            DaemonKind::Unspecified => None,
+        }
+    }
+}
+
+impl fmt::Display for Daemon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Daemon::Vmm => "vmm",
+            Daemon::Zfs => "zfs",
+            Daemon::Net => "net",
+        })
+    }
+}
+
+impl FromStr for Daemon {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vmm" => Ok(Daemon::Vmm),
+            "zfs" => Ok(Daemon::Zfs),
+            "net" => Ok(Daemon::Net),
+            other => Err(format!("unknown daemon: {other}")),
+        }
+    }
+}
+
+/// What to restart where.
+#[derive(Debug, Clone)]
+pub struct UpgradePlan {
+    /// Restarted in this order on each node.
+    pub daemons: Vec<Daemon>,
+    /// Node ids or names; empty means every connected node.
+    pub nodes: Vec<String>,
+    /// How long a net restart waits for vhost-user VMs to stop.
+    pub drain_timeout: Duration,
+    /// Restart net even if VMs didn't drain.
+    pub force: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Phase::Pending => "pending",
+            Phase::Running => "running",
+            Phase::Succeeded => "succeeded",
+            Phase::Failed => "failed",
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct StepStatus {
+    pub daemon: Daemon,
+    pub phase: Phase,
+    pub previous_version: Option<String>,
+    pub version: Option<String>,
+    /// VMs that lost networking because a forced net restart went ahead.
+    pub undrained_vms: Vec<String>,
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NodeUpgradeStatus {
+    pub node_id: String,
+    pub node_name: String,
+    pub phase: Phase,
+    pub steps: Vec<StepStatus>,
+}
+
+#[derive(Debug, Clone)]
+pub struct UpgradeStatus {
+    pub id: String,
+    pub phase: Phase,
+    pub daemons: Vec<Daemon>,
+    pub force: bool,
+    pub started_at: String,
+    pub finished_at: Option<String>,
+    pub nodes: Vec<NodeUpgradeStatus>,
+    pub message: Option<String>,
+}
+
+/// Daemon versions reported by one connected node.
+#[derive(Debug, Clone)]
+pub struct NodeVersions {
+    pub node_id: String,
+    pub node_name: String,
+    pub agent_version: Option<String>,
+    /// `None` for a daemon that didn't answer.
+    pub daemons: Vec<(Daemon, Option<String>)>,
+    /// Set when the node agent itself couldn't be asked.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum UpgradeError {
+    #[error("upgrade {0} is still running")]
+    InProgress(String),
+    #[error("node {0} is not connected")]
+    NodeNotConnected(String),
+    #[error("no nodes to upgrade")]
+    NoNodes,
+    #[error("no daemons to restart")]
+    NoDaemons,
+    #[error("raft unhealthy: {0}")]
+    RaftUnhealthy(String),
+}
+
+pub struct UpgradeController {
+    ctx: Ctx,
+    /// The running or last finished rollout.
+    current: Mutex<Option<UpgradeStatus>>,
+}
+
+impl UpgradeController {
+    pub fn new(ctx: Ctx) -> Self {
+        Self {
+            ctx,
+            current: Mutex::new(None),
+        }
+    }
+
+    /// The running or last finished rollout, if any.
+    pub async fn current(&self) -> Option<UpgradeStatus> {
+        self.current.lock().await.clone()
+    }
+
+    /// Ask every connected node for its daemon versions.
+    pub async fn versions(&self) -> Vec<NodeVersions> {
+        let mut nodes = self.ctx.registry.list().await;
+        nodes.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut versions = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            let mut entry = NodeVersions {
+                node_id: node.node_id.clone(),
+                node_name: node.name.clone(),
+                agent_version: None,
+                daemons: Vec::new(),
+                error: None,
+            };
+            match node
+                .agent
+                .clone()
+                .daemon_versions(DaemonVersionsRequest {})
+                .await
+            {
+                Ok(resp) => {
+                    let resp = resp.into_inner();
+                    entry.agent_version = Some(resp.agent_version);
+                    entry.daemons = resp
+                        .daemons
+                        .into_iter()
+                        .filter_map(|d| {
+                            let daemon = Daemon::from_kind(d.daemon())?;
+                            Some((daemon, d.reachable.then_some(d.version)))
+                        })
+                        .collect();
+                }
+                Err(e) => entry.error = Some(e.message().to_string()),
+            }
+            versions.push(entry);
+        }
+        versions
+    }
+
+    /// Validate `plan` and start rolling it out in the background.
+    pub async fn start(self: &Arc<Self>, plan: UpgradePlan) -> Result<UpgradeStatus, UpgradeError> {
+        if plan.daemons.is_empty() {
+            return Err(UpgradeError::NoDaemons);
+        }
+        let mut current = self.current.lock().await;
+        if let Some(running) = current.as_ref()
+            && running.phase == Phase::Running
+        {
+            return Err(UpgradeError::InProgress(running.id.clone()));
+        }
+        self.raft_health()
+            .await
+            .map_err(UpgradeError::RaftUnhealthy)?;
+
+        let connected = self.ctx.registry.list().await;
+        let mut targets = if plan.nodes.is_empty() {
+            connected
+        } else {
+            let mut targets = Vec::new();
+            for wanted in &plan.nodes {
+                let node = connected
+                    .iter()
+                    .find(|n| n.node_id == *wanted || n.name == *wanted)
+                    .ok_or_else(|| UpgradeError::NodeNotConnected(wanted.clone()))?;
+                targets.push(node.clone());
+            }
+            targets
+        };
+        if targets.is_empty() {
+            return Err(UpgradeError::NoNodes);
+        }
+        if plan.nodes.is_empty() {
+            targets.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+
+        let status = UpgradeStatus {
+            id: uuid::Uuid::new_v4().to_string(),
+            phase: Phase::Running,
+            daemons: plan.daemons.clone(),
+            force: plan.force,
+            started_at: chrono::Utc::now().to_rfc3339(),
+            finished_at: None,
+            nodes: targets
+                .iter()
+                .map(|n| NodeUpgradeStatus {
+                    node_id: n.node_id.clone(),
+                    node_name: n.name.clone(),
+                    phase: Phase::Pending,
+                    steps: plan
+                        .daemons
+                        .iter()
+                        .map(|&daemon| StepStatus {
+                            daemon,
+                            phase: Phase::Pending,
+                            previous_version: None,
+                            version: None,
+                            undrained_vms: Vec::new(),
+                            message: None,
+                        })
+                        .collect(),
+                })
+                .collect(),
+            message: None,
+        };
+        *current = Some(status.clone());
+        drop(current);
+
+        info!(
+            upgrade = %status.id,
+            nodes = status.nodes.len(),
+            daemons = ?plan.daemons,
+            "starting rolling daemon restart"
+        );
+        self.ctx.audit.upgrade_started(
+            &status.id,
+            &plan
+                .daemons
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            status.nodes.len(),
+        );
+
+        let controller = Arc::clone(self);
+        tokio::spawn(async move { controller.run(plan).await });
+        Ok(status)
+    }
+
+    async fn run(&self, plan: UpgradePlan) {
+        let result = self.rollout(&plan).await;
+        let mut current = self.current.lock().await;
+        let Some(status) = current.as_mut() else {
+            return;
+        };
+        status.finished_at = Some(chrono::Utc::now().to_rfc3339());
+        match result {
+            Ok(()) => {
+                status.phase = Phase::Succeeded;
+                info!(upgrade = %status.id, "rolling daemon restart finished");
+            }
+            Err(e) => {
+                status.phase = Phase::Failed;
+                warn!(upgrade = %status.id, error = %e, "rolling daemon restart failed");
+                status.message = Some(e);
+            }
+        }
+        self.ctx.audit.upgrade_finished(
+            &status.id,
+            status.phase == Phase::Succeeded,
+            status.message.as_deref(),
+        );
+    }
+
+    async fn rollout(&self, plan: &UpgradePlan) -> Result<(), String> {
+        let nodes: Vec<String> = match self.current.lock().await.as_ref() {
+            Some(status) => status.nodes.iter().map(|n| n.node_id.clone()).collect(),
+            None => return Ok(()),
+        };
+
+        for (n, node_id) in nodes.iter().enumerate() {
+            self.update(|s| s.nodes[n].phase = Phase::Running).await;
+            let result = self.upgrade_node(n, node_id, plan).await;
+            let phase = if result.is_ok() {
+                Phase::Succeeded
+            } else {
+                Phase::Failed
+            };
+            self.update(|s| s.nodes[n].phase = phase).await;
+            result?;
+        }
+        Ok(())
+    }
+
+    async fn upgrade_node(
+        &self,
+        n: usize,
+        node_id: &str,
+        plan: &UpgradePlan,
+    ) -> Result<(), String> {
+        for (i, &daemon) in plan.daemons.iter().enumerate() {
+            let result = self.restart_step(n, i, node_id, plan).await;
+            self.update(|s| {
+                let step = &mut s.nodes[n].steps[i];
+                match &result {
+                    Ok(resp) => {
+                        step.phase = Phase::Succeeded;
+                        step.previous_version = Some(resp.previous_version.clone());
+                        step.version = Some(resp.version.clone());
+                        step.undrained_vms = resp.undrained_vms.clone();
+                    }
+                    Err(e) => {
+                        step.phase = Phase::Failed;
+                        step.message = Some(e.clone());
+                    }
+                }
+            })
+            .await;
+            let resp = result.map_err(|e| format!("{daemon} on node {node_id}: {e}"))?;
+            self.ctx.audit.daemon_restarted(
+                node_id,
+                &daemon.to_string(),
+                &resp.previous_version,
+                &resp.version,
+            );
+        }
+        Ok(())
+    }
+
+    async fn restart_step(
+        &self,
+        n: usize,
+        i: usize,
+        node_id: &str,
+        plan: &UpgradePlan,
+    ) -> Result<RestartDaemonResponse, String> {
+        let daemon = plan.daemons[i];
+        self.wait_raft_healthy().await?;
+        let node = self
+            .ctx
+            .registry
+            .get(node_id)
+            .await
+            .ok_or_else(|| "node disconnected".to_string())?;
+
+        self.update(|s| s.nodes[n].steps[i].phase = Phase::Running)
+            .await;
+        info!(node = %node_id, %daemon, "restarting daemon");
+
+        node.agent
+            .clone()
+            .restart_daemon(RestartDaemonRequest {
+                daemon: daemon.kind() as i32,
+                drain_timeout_secs: plan.drain_timeout.as_secs() as u32,
+                force: plan.force,
+            })
+            .await
+            .map(|r| r.into_inner())
+            .map_err(|s| s.message().to_string())
+    }
+
+    /// Raft is healthy when it has a leader and that leader is this peer,
+    /// which is the one driving the rollout.
+    async fn raft_health(&self) -> Result<(), String> {
+        let info = self
+            .ctx
+            .store
+            .get_controlplane_info()
+            .await
+            .map_err(|e| e.to_string())?;
+        match info.leader_id {
+            None => Err("no raft leader".to_string()),
+            Some(leader) if !info.is_leader => Err(format!(
+                "peer {} is not the raft leader (leader is {})",
+                info.peer_id, leader
+            )),
+            Some(_) => Ok(()),
+        }
+    }
+
+    async fn wait_raft_healthy(&self) -> Result<(), String> {
+        let deadline = Instant::now() + RAFT_HEALTH_TIMEOUT;
+        loop {
+            match self.raft_health().await {
+                Ok(()) => return Ok(()),
+                Err(e) if Instant::now() >= deadline => {
+                    return Err(format!("raft unhealthy: {e}"));
+                }
+                Err(e) => {
+                    warn!(error = %e, "waiting for raft to become healthy");
+                    tokio::time::sleep(RAFT_HEALTH_POLL).await;
+                }
+            }
+        }
+    }
+
+    async fn update(&self, f: impl FnOnce(&mut UpgradeStatus)) {
+        if let Some(status) = self.current.lock().await.as_mut() {
+            f(status);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_daemon_names_roundtrip() {
+        for daemon in Daemon::ALL {
+            assert_eq!(daemon.to_string().parse::<Daemon>(), Ok(daemon));
+            assert_eq!(Daemon::from_kind(daemon.kind()), Some(daemon));
+        }
+        assert!("log".parse::<Daemon>().is_err());
+        assert_eq!(Daemon::from_kind(DaemonKind::Unspecified), None);
+    }
+}
@@ -0,0 +1,400 @@
+//! Webhooks on resource lifecycle events.
+//!
+//! Platform admins register URLs together with a list of event types
+//! (`vm.started`, `node.notready`, `vm.*`, `*`). The dispatcher turns raft
+//! state-machine events into these lifecycle events and POSTs a JSON body
+//! to every matching webhook:
+//!
+//! ```text
+//! X-Mvirt-Event: vm.started
+//! X-Mvirt-Delivery: <delivery id>
+//! X-Mvirt-Signature: sha256=<hex HMAC-SHA256 of the body with the secret>
+//!
+//! {"id": "<delivery id>", "type": "vm.started", "timestamp": "...",
+//!  "resourceId": "...", "data": {...}}
+//! ```
+//!
+//! A delivery counts as done on any 2xx answer and is otherwise retried
+//! with exponential backoff. Every attempt is written back as a
+//! [`WebhookDelivery`] so its status can be read over REST. Events are only
+//! emitted on the raft leader, so each one is delivered once per cluster.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use tokio::sync::broadcast;
+use tracing::{debug, info, warn};
+
+use crate::command::{
+    MigrationPhase, NodeStatus, VmPhase, VolumePhase, WebhookData, WebhookDelivery,
+    WebhookDeliveryStatus,
+};
+use crate::store::{Event, RaftStore, StoreError, WebhookStore};
+
+/// Event types a webhook can subscribe to. Pods are still stubs, so the
+/// `pod.*` types are accepted but never fire yet.
+pub const EVENT_TYPES: &[&str] = &[
+    "vm.created",
+    "vm.started",
+    "vm.stopped",
+    "vm.failed",
+    "vm.deleted",
+    "node.registered",
+    "node.ready",
+    "node.notready",
+    "node.deregistered",
+    "volume.ready",
+    "volume.failed",
+    "volume.deleted",
+    "migration.started",
+    "migration.completed",
+    "migration.failed",
+    "pod.started",
+    "pod.stopped",
+    "pod.failed",
+];
+
+pub const EVENT_HEADER: &str = "x-mvirt-event";
+pub const DELIVERY_HEADER: &str = "x-mvirt-delivery";
+pub const SIGNATURE_HEADER: &str = "x-mvirt-signature";
+
+/// Attempts per delivery, the first included.
+const MAX_ATTEMPTS: u32 = 6;
+/// Delay before the first retry; doubled after each failed attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether `filter` is an event type, `<kind>.*` or `*`.
+pub fn valid_filter(filter: &str) -> bool {
+    if filter == "*" {
+        return true;
+    }
+    match filter.strip_suffix(".*") {
+        Some(kind) => EVENT_TYPES
+            .iter()
+            .any(|t| t.split_once('.').is_some_and(|(k, _)| k == kind)),
+        None => EVENT_TYPES.contains(&filter),
+    }
+}
+
+/// Whether `event_type` is selected by `filter`.
+pub fn matches(filter: &str, event_type: &str) -> bool {
+    match filter.strip_suffix('*') {
+        Some(prefix) => event_type.starts_with(prefix),
+        None => filter == event_type,
+    }
+}
+
+/// `sha256=<hex>` HMAC of `body` under `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts any key length");
+    mac.update(body);
+    let digest = mac.finalize().into_bytes();
+    let mut s = String::with_capacity(7 + digest.len() * 2);
+    s.push_str("sha256=");
+    for b in digest {
+        s.push_str(&format!("{:02x}", b));
+    }
+    s
+}
+
+fn lower<T: std::fmt::Debug>(v: T) -> String {
+    format!("{:?}", v).to_lowercase()
+}
+
+/// The lifecycle event behind a state-machine event, as its type and the
+/// `data` object of the delivery body. Most updates (spec edits,
+/// intermediate phases) have none.
+pub fn lifecycle_event(event: &Event) -> Option<(&'static str, Value)> {
+    match event {
+        Event::VmCreated(vm) => Some((
+            "vm.created",
+            json!({
+                "id": vm.id,
+                "name": vm.spec.name,
+                "projectSlug": vm.spec.project_slug,
+            }),
+        )),
+        Event::VmStatusUpdated { old, new, .. } if old.status.phase != new.status.phase => {
+            let kind = match new.status.phase {
+                VmPhase::Running => "vm.started",
+                VmPhase::Stopped => "vm.stopped",
+                VmPhase::Failed => "vm.failed",
+                _ => return None,
+            };
+            Some((
+                kind,
+                json!({
+                    "id": new.id,
+                    "name": new.spec.name,
+                    "projectSlug": new.spec.project_slug,
+                    "nodeId": new.status.node_id,
+                    "phase": lower(new.status.phase),
+                    "message": new.status.message,
+                }),
+            ))
+        }
+        Event::VmDeleted { id } => Some(("vm.deleted", json!({ "id": id }))),
+        Event::NodeRegistered(node) => Some((
+            "node.registered",
+            json!({ "id": node.id, "name": node.name, "address": node.address }),
+        )),
+        Event::NodeUpdated { old, new, .. } if old.status != new.status => {
+            let kind = match new.status {
+                NodeStatus::Online => "node.ready",
+                NodeStatus::Offline => "node.notready",
+                _ => return None,
+            };
+            Some((
+                kind,
+                json!({
+                    "id": new.id,
+                    "name": new.name,
+                    "address": new.address,
+                    "status": lower(new.status),
+                    "previousStatus": lower(old.status),
+                }),
+            ))
+        }
+        Event::NodeDeregistered { id } => Some(("node.deregistered", json!({ "id": id }))),
+        Event::VolumeStatusUpdated { old, new, .. } if old.status.phase != new.status.phase => {
+            let kind = match new.status.phase {
+                VolumePhase::Ready => "volume.ready",
+                VolumePhase::Failed => "volume.failed",
+                _ => return None,
+            };
+            Some((
+                kind,
+                json!({
+                    "id": new.id,
+                    "name": new.spec.name,
+                    "projectSlug": new.spec.project_slug,
+                    "nodeId": new.spec.node_id,
+                    "error": new.status.error,
+                }),
+            ))
+        }
+        Event::VolumeDeleted { id, node_id } => {
+            Some(("volume.deleted", json!({ "id": id, "nodeId": node_id })))
+        }
+        Event::MigrationCreated(m) => Some((
+            "migration.started",
+            json!({
+                "id": m.id,
+                "vmId": m.spec.vm_id,
+                "projectSlug": m.spec.project_slug,
+                "sourceNodeId": m.spec.source_node_id,
+                "targetNodeId": m.spec.target_node_id,
+            }),
+        )),
+        Event::MigrationUpdated { old, new, .. } if old.status.phase != new.status.phase => {
+            let kind = match new.status.phase {
+                MigrationPhase::Completed => "migration.completed",
+                MigrationPhase::Failed => "migration.failed",
+                _ => return None,
+            };
+            Some((
+                kind,
+                json!({
+                    "id": new.id,
+                    "vmId": new.spec.vm_id,
+                    "projectSlug": new.spec.project_slug,
+                    "sourceNodeId": new.spec.source_node_id,
+                    "targetNodeId": new.spec.target_node_id,
+                    "error": new.status.error,
+                }),
+            ))
+        }
+        _ => None,
+    }
+}
+
+pub struct WebhookDispatcher {
+    store: Arc<RaftStore>,
+    http: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(store: Arc<RaftStore>) -> Self {
+        Self {
+            store,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    /// Spawn the dispatch loop. Returns immediately.
+    pub fn spawn(self, mut events: broadcast::Receiver<Event>) {
+        info!("starting webhook dispatcher");
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(event) => self.dispatch(&event).await,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(
+                            skipped = n,
+                            "webhooks lagged on event stream; events dropped"
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        info!("event channel closed; webhook dispatcher stopping");
+                        break;
+                    }
+                }
+            }
+        });
+    }
+
+    /// Start one delivery per webhook subscribed to `event`. Each runs its
+    /// retries in its own task so a slow endpoint doesn't hold up others.
+    async fn dispatch(&self, event: &Event) {
+        let Some((event_type, data)) = lifecycle_event(event) else {
+            return;
+        };
+        let webhooks = match self.store.list_webhooks().await {
+            Ok(w) => w,
+            Err(e) => {
+                warn!(error = %e, "webhooks: listing failed");
+                return;
+            }
+        };
+        let resource_id = event.resource_id().to_string();
+        for webhook in webhooks
+            .into_iter()
+            .filter(|w| w.events.iter().any(|f| matches(f, event_type)))
+        {
+            let now = Utc::now().to_rfc3339();
+            let delivery = WebhookDelivery {
+                id: uuid::Uuid::new_v4().to_string(),
+                webhook_id: webhook.id.clone(),
+                event_type: event_type.to_string(),
+                resource_id: resource_id.clone(),
+                status: WebhookDeliveryStatus::Pending,
+                attempts: 0,
+                last_status_code: None,
+                last_error: None,
+                created_at: now.clone(),
+                updated_at: now.clone(),
+            };
+            let body = json!({
+                "id": delivery.id,
+                "type": event_type,
+                "timestamp": now,
+                "resourceId": resource_id,
+                "data": data,
+            });
+            debug!(webhook = %webhook.id, event_type, %resource_id, "webhook delivery");
+            let store = self.store.clone();
+            let http = self.http.clone();
+            tokio::spawn(deliver(store, http, webhook, delivery, body.to_string()));
+        }
+    }
+}
+
+async fn deliver(
+    store: Arc<RaftStore>,
+    http: reqwest::Client,
+    webhook: WebhookData,
+    mut delivery: WebhookDelivery,
+    body: String,
+) {
+    let signature = sign(&webhook.secret, body.as_bytes());
+    let mut backoff = INITIAL_BACKOFF;
+    loop {
+        delivery.attempts += 1;
+        let result = http
+            .post(&webhook.url)
+            .timeout(REQUEST_TIMEOUT)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .header(EVENT_HEADER, &delivery.event_type)
+            .header(DELIVERY_HEADER, &delivery.id)
+            .header(SIGNATURE_HEADER, &signature)
+            .body(body.clone())
+            .send()
+            .await;
+        let ok = match result {
+            Ok(resp) => {
+                delivery.last_status_code = Some(resp.status().as_u16());
+                delivery.last_error =
+                    (!resp.status().is_success()).then(|| format!("HTTP {}", resp.status()));
+                resp.status().is_success()
+            }
+            Err(e) => {
+                delivery.last_status_code = None;
+                delivery.last_error = Some(e.to_string());
+                false
+            }
+        };
+        delivery.status = if ok {
+            WebhookDeliveryStatus::Succeeded
+        } else if delivery.attempts >= MAX_ATTEMPTS {
+            WebhookDeliveryStatus::Failed
+        } else {
+            WebhookDeliveryStatus::Pending
+        };
+        delivery.updated_at = Utc::now().to_rfc3339();
+
+        match store.record_webhook_delivery(delivery.clone()).await {
+            Ok(()) => {}
+            Err(StoreError::NotFound(_)) => {
+                debug!(webhook = %webhook.id, "webhook deleted; dropping delivery");
+                return;
+            }
+            Err(e) => {
+                warn!(webhook = %webhook.id, error = %e, "webhooks: recording delivery failed")
+            }
+        }
+
+        match delivery.status {
+            WebhookDeliveryStatus::Pending => {
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            WebhookDeliveryStatus::Failed => {
+                warn!(
+                    webhook = %webhook.id,
+                    delivery = %delivery.id,
+                    error = delivery.last_error.as_deref().unwrap_or(""),
+                    "webhook delivery failed"
+                );
+                return;
+            }
+            WebhookDeliveryStatus::Succeeded => return,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filters() {
+        assert!(valid_filter("vm.started"));
+        assert!(valid_filter("pod.failed"));
+        assert!(valid_filter("node.*"));
+        assert!(valid_filter("*"));
+        assert!(!valid_filter("vm.exploded"));
+        assert!(!valid_filter("disk.*"));
+        assert!(!valid_filter("vm*"));
+
+        assert!(matches("*", "node.notready"));
+        assert!(matches("node.*", "node.notready"));
+        assert!(matches("vm.started", "vm.started"));
+        assert!(!matches("vm.started", "vm.stopped"));
+        assert!(!matches("vm.*", "volume.ready"));
+    }
+
+    #[test]
+    fn test_sign() {
+        // RFC 4231, test case 2.
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
@@ -104,6 +104,11 @@ impl TestCluster {
             log_advertise: Vec::new(),
             jwt_validator: None,
             initial_admin_email: None,
+            upgrades: None,
+            registry: None,
+            limiter: None,
+            trash_retention: None,
+            jobs: Default::default(),
         });
 
         let router = create_router(app_state);
@@ -191,6 +196,11 @@ impl TestCluster {
             log_advertise: Vec::new(),
             jwt_validator: None,
             initial_admin_email: None,
+            upgrades: None,
+            registry: None,
+            limiter: None,
+            trash_retention: None,
+            jobs: Default::default(),
         });
 
         let router = create_router(app_state);
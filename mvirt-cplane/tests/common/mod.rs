@@ -36,6 +36,12 @@ pub struct TestServer {
 impl TestServer {
     /// Spawn a single-node test server with in-memory storage.
     pub async fn spawn() -> Self {
+        Self::spawn_with_trash(None).await
+    }
+
+    /// Like `spawn()`, with REST deletes going to the trash for
+    /// `trash_retention` first.
+    pub async fn spawn_with_trash(trash_retention: Option<std::time::Duration>) -> Self {
         let raft_port = allocate_port();
         let node_id = 1u64;
 
@@ -83,6 +89,11 @@ impl TestServer {
             log_advertise: Vec::new(),
             jwt_validator: None,
             initial_admin_email: None,
+            upgrades: None,
+            registry: None,
+            limiter: None,
+            trash_retention,
+            jobs: Default::default(),
         });
 
         // Create router (auth off — tests run without OIDC).
@@ -268,6 +268,8 @@ async fn test_log_replication() {
             dns_servers: vec![],
             ntp_servers: vec![],
             is_public: false,
+            boot_server: None,
+            boot_file: None,
         })
         .await
         .expect("Write failed");
@@ -363,6 +365,8 @@ async fn test_read_from_follower() {
             dns_servers: vec!["8.8.8.8".to_string()],
             ntp_servers: vec![],
             is_public: true,
+            boot_server: None,
+            boot_file: None,
         })
         .await
         .expect("Write failed");
@@ -433,6 +437,8 @@ async fn test_majority_write_during_minority_failure() {
             dns_servers: vec![],
             ntp_servers: vec![],
             is_public: false,
+            boot_server: None,
+            boot_file: None,
         })
         .await
         .expect("Initial write failed");
@@ -523,6 +529,8 @@ async fn test_majority_write_during_minority_failure() {
             dns_servers: vec![],
             ntp_servers: vec![],
             is_public: false,
+            boot_server: None,
+            boot_file: None,
         })
         .await
         .expect("Write during minority failure should succeed");
@@ -602,6 +610,8 @@ async fn test_write_or_forward() {
             dns_servers: vec![],
             ntp_servers: vec![],
             is_public: false,
+            boot_server: None,
+            boot_file: None,
         })
         .await
         .expect("Forwarded write failed");
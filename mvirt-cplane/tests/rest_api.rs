@@ -110,6 +110,27 @@ async fn test_remove_node_not_found() {
     server.shutdown().await;
 }
 
+// =============================================================================
+// Upgrades
+// =============================================================================
+
+#[tokio::test]
+async fn test_upgrades_unavailable_without_controller() {
+    let server = common::TestServer::spawn().await;
+
+    let response = server.get("/nodes/versions").await;
+    assert_eq!(response.status(), 503);
+
+    let response = server
+        .post_json("/upgrades", &json!({ "daemons": ["net"] }))
+        .await;
+    assert_eq!(response.status(), 503);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["error"].is_string());
+
+    server.shutdown().await;
+}
+
 // =============================================================================
 // Network CRUD
 // =============================================================================
@@ -192,6 +213,89 @@ async fn test_create_network_duplicate_name() {
     server.shutdown().await;
 }
 
+#[tokio::test]
+async fn test_create_network_invalid_name() {
+    let server = common::TestServer::spawn().await;
+
+    let proj_resp = server
+        .post_json(
+            "/orgs/test/projects",
+            &json!({"slug": "badnetproj", "name": "bad-net-proj"}),
+        )
+        .await;
+    let proj: Value = proj_resp.json().await.unwrap();
+    let project_id = proj["slug"].as_str().unwrap();
+
+    let long = "n".repeat(300);
+    for name in ["my network", "Netz", "-net", long.as_str(), "mvirt-net"] {
+        let response = server
+            .post_json(
+                &format!("/projects/{}/networks", project_id),
+                &json!({ "name": name }),
+            )
+            .await;
+        assert_eq!(response.status(), 400, "{name}");
+        let body: Value = response.json().await.unwrap();
+        assert!(
+            body["error"].as_str().unwrap().starts_with("Network name"),
+            "{body}"
+        );
+    }
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_create_network_boot_options() {
+    let server = common::TestServer::spawn().await;
+
+    let proj_resp = server
+        .post_json(
+            "/orgs/test/projects",
+            &json!({"slug": "bootnetproj", "name": "boot-net-proj"}),
+        )
+        .await;
+    let proj: Value = proj_resp.json().await.unwrap();
+    let project_id = proj["slug"].as_str().unwrap();
+
+    let response = server
+        .post_json(
+            &format!("/projects/{}/networks", project_id),
+            &json!({
+                "name": "pxe",
+                "bootServer": "10.0.0.5",
+                "bootFile": "pxelinux.0"
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["bootServer"], "10.0.0.5");
+    assert_eq!(body["bootFile"], "pxelinux.0");
+
+    for (boot, error) in [
+        (
+            json!({"bootServer": "boot.example", "bootFile": "pxelinux.0"}),
+            "Invalid boot server",
+        ),
+        (json!({"bootServer": "10.0.0.5"}), "needs a boot file"),
+        (json!({"bootFile": "pxelinux.0"}), "needs a boot server"),
+    ] {
+        let mut req = json!({ "name": "bad-boot" });
+        req.as_object_mut()
+            .unwrap()
+            .extend(boot.as_object().unwrap().clone());
+        let response = server
+            .post_json(&format!("/projects/{}/networks", project_id), &req)
+            .await;
+        assert_eq!(response.status(), 400, "{req}");
+        let body: Value = response.json().await.unwrap();
+        assert!(body["error"].as_str().unwrap().contains(error), "{body}");
+    }
+
+    server.shutdown().await;
+}
+
 #[tokio::test]
 async fn test_get_network_by_id() {
     let server = common::TestServer::spawn().await;
@@ -274,6 +378,35 @@ async fn test_get_network_not_found() {
     server.shutdown().await;
 }
 
+#[tokio::test]
+async fn test_errors_carry_request_id() {
+    let server = common::TestServer::spawn().await;
+
+    let response = server
+        .client
+        .get(format!("{}/networks/non-existent-id", server.base_url()))
+        .header("x-request-id", "apply-42")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
+    assert_eq!(response.headers()["x-request-id"], "apply-42");
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["requestId"], "apply-42");
+
+    // Without one the server picks an ID
+    let response = server.get("/networks/non-existent-id").await;
+    let id = response.headers()["x-request-id"]
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(id.starts_with("req-"));
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["requestId"], id.as_str());
+
+    server.shutdown().await;
+}
+
 #[tokio::test]
 async fn test_list_networks() {
     let server = common::TestServer::spawn().await;
@@ -406,6 +539,79 @@ async fn test_delete_network_with_nics() {
 
 // Note: Force delete endpoint is not available in UI-compatible API
 
+#[tokio::test]
+async fn test_delete_network_to_trash_and_undelete() {
+    let server =
+        common::TestServer::spawn_with_trash(Some(std::time::Duration::from_secs(3600))).await;
+
+    server
+        .post_json(
+            "/orgs/test/projects",
+            &json!({"slug": "trashproj", "name": "trash-proj"}),
+        )
+        .await;
+    let created: Value = server
+        .post_json("/projects/trashproj/networks", &json!({"name": "oops"}))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let network_id = created["id"].as_str().unwrap();
+
+    // Deleting only moves it to the trash
+    let response = server.delete(&format!("/networks/{}", network_id)).await;
+    assert_eq!(response.status(), 204);
+    let list: Value = server
+        .get("/projects/trashproj/networks")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(list["networks"].as_array().unwrap().is_empty());
+    let trash: Value = server
+        .get("/projects/trashproj/trash")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(trash["items"][0]["kind"], "NETWORK");
+    assert_eq!(trash["items"][0]["id"], network_id);
+    let network: Value = server
+        .get(&format!("/networks/{}", network_id))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert!(network["purgeAt"].is_string());
+
+    // Undelete brings it back
+    let response = server
+        .post_json(&format!("/networks/{}/undelete", network_id), &json!({}))
+        .await;
+    assert_eq!(response.status(), 200);
+    let list: Value = server
+        .get("/projects/trashproj/networks")
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(list["networks"].as_array().unwrap().len(), 1);
+    let response = server
+        .post_json(&format!("/networks/{}/undelete", network_id), &json!({}))
+        .await;
+    assert_eq!(response.status(), 409);
+
+    // ?purge=true skips the trash
+    let response = server
+        .delete(&format!("/networks/{}?purge=true", network_id))
+        .await;
+    assert_eq!(response.status(), 204);
+    let response = server.get(&format!("/networks/{}", network_id)).await;
+    assert_eq!(response.status(), 404);
+
+    server.shutdown().await;
+}
+
 // =============================================================================
 // NIC CRUD
 // =============================================================================
@@ -1223,3 +1429,289 @@ async fn test_get_pool_stats() {
 
     server.shutdown().await;
 }
+
+// =============================================================================
+// Flavors
+// =============================================================================
+
+#[tokio::test]
+async fn test_create_and_get_flavor() {
+    let server = common::TestServer::spawn().await;
+
+    let response = server
+        .post_json(
+            "/flavors",
+            &json!({
+                "name": "m1.small",
+                "vcpus": 2,
+                "memoryMb": 2048,
+                "diskSizeBytes": 10737418240_u64,
+                "network": "default"
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+
+    let body: Value = response.json().await.unwrap();
+    let flavor_id = body["id"].as_str().unwrap().to_string();
+    assert_eq!(body["name"].as_str().unwrap(), "m1.small");
+    assert_eq!(body["vcpus"].as_u64().unwrap(), 2);
+    assert_eq!(body["network"].as_str().unwrap(), "default");
+
+    // Lookup by name and by id
+    let response = server.get("/flavors/m1.small").await;
+    assert_eq!(response.status(), 200);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["id"].as_str().unwrap(), flavor_id);
+
+    let response = server.get(&format!("/flavors/{}", flavor_id)).await;
+    assert_eq!(response.status(), 200);
+
+    let response = server.get("/flavors").await;
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["flavors"].as_array().unwrap().len(), 1);
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_create_flavor_validation() {
+    let server = common::TestServer::spawn().await;
+
+    let flavor = json!({"name": "m1.tiny", "vcpus": 1, "memoryMb": 512});
+    let response = server.post_json("/flavors", &flavor).await;
+    assert_eq!(response.status(), 200);
+
+    // Duplicate name
+    let response = server.post_json("/flavors", &flavor).await;
+    assert_eq!(response.status(), 409);
+
+    // Invalid name
+    let response = server
+        .post_json(
+            "/flavors",
+            &json!({"name": "M1 Tiny", "vcpus": 1, "memoryMb": 512}),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    // Zero-sized
+    let response = server
+        .post_json(
+            "/flavors",
+            &json!({"name": "m1.zero", "vcpus": 0, "memoryMb": 512}),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_delete_flavor() {
+    let server = common::TestServer::spawn().await;
+
+    server
+        .post_json(
+            "/flavors",
+            &json!({"name": "m1.medium", "vcpus": 4, "memoryMb": 4096}),
+        )
+        .await;
+
+    let response = server.delete("/flavors/m1.medium").await;
+    assert_eq!(response.status(), 204);
+
+    let response = server.get("/flavors/m1.medium").await;
+    assert_eq!(response.status(), 404);
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_create_vm_flavor_conflict() {
+    let server = common::TestServer::spawn().await;
+
+    let proj_resp = server
+        .post_json(
+            "/orgs/test/projects",
+            &json!({"slug": "flavorvmproj", "name": "flavor-vm-proj"}),
+        )
+        .await;
+    let proj: Value = proj_resp.json().await.unwrap();
+    let project_id = proj["slug"].as_str().unwrap();
+
+    server
+        .post_json(
+            "/flavors",
+            &json!({"name": "m1.large", "vcpus": 8, "memoryMb": 8192}),
+        )
+        .await;
+
+    // Explicit sizing must not contradict the flavor
+    let response = server
+        .post_json(
+            &format!("/projects/{}/vms", project_id),
+            &json!({
+                "name": "vm-1",
+                "flavor": "m1.large",
+                "config": {"vcpus": 2, "volumeId": "vol-1", "nicId": "nic-1", "image": "ubuntu"}
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    // Without a flavor, sizing is required
+    let response = server
+        .post_json(
+            &format!("/projects/{}/vms", project_id),
+            &json!({
+                "name": "vm-2",
+                "config": {"volumeId": "vol-1", "nicId": "nic-1", "image": "ubuntu"}
+            }),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    server.shutdown().await;
+}
+
+// =============================================================================
+// Provisioning hooks
+// =============================================================================
+
+#[tokio::test]
+async fn test_create_vm_provisioning_validation() {
+    let server = common::TestServer::spawn().await;
+
+    let proj_resp = server
+        .post_json(
+            "/orgs/test/projects",
+            &json!({"slug": "hookproj", "name": "hook-proj"}),
+        )
+        .await;
+    let proj: Value = proj_resp.json().await.unwrap();
+    let project_id = proj["slug"].as_str().unwrap();
+
+    let create = |provisioning: Value| {
+        json!({
+            "name": "vm-hooks",
+            "config": {
+                "vcpus": 1, "memoryMb": 512, "volumeId": "vol-1", "nicId": "nic-1",
+                "image": "ubuntu", "provisioning": provisioning
+            }
+        })
+    };
+
+    // Webhook must be http(s)
+    let response = server
+        .post_json(
+            &format!("/projects/{}/vms", project_id),
+            &create(json!([{
+                "name": "notify",
+                "wait": [{"type": "tcpPort", "port": 22}],
+                "action": {"type": "webhook", "url": "ftp://example.com/hook"}
+            }])),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    // Hook names must be unique
+    let response = server
+        .post_json(
+            &format!("/projects/{}/vms", project_id),
+            &create(json!([
+                {"name": "ready", "wait": [{"type": "guestReady"}]},
+                {"name": "ready", "wait": [{"type": "tcpPort", "port": 80}]}
+            ])),
+        )
+        .await;
+    assert_eq!(response.status(), 400);
+
+    server.shutdown().await;
+}
+
+// =============================================================================
+// SSH Keys
+// =============================================================================
+
+const TEST_ED25519_KEY: &str =
+    "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl user@laptop";
+
+#[tokio::test]
+async fn test_create_and_list_ssh_keys() {
+    let server = common::TestServer::spawn().await;
+
+    let proj_resp = server
+        .post_json(
+            "/orgs/test/projects",
+            &json!({"slug": "keyproj", "name": "key-proj"}),
+        )
+        .await;
+    let proj: Value = proj_resp.json().await.unwrap();
+    let project_id = proj["slug"].as_str().unwrap();
+
+    let response = server
+        .post_json(
+            &format!("/projects/{}/ssh-keys", project_id),
+            &json!({"name": "laptop", "publicKey": TEST_ED25519_KEY, "projectDefault": true}),
+        )
+        .await;
+    assert_eq!(response.status(), 200);
+    let key: Value = response.json().await.unwrap();
+    assert!(key["fingerprint"].as_str().unwrap().starts_with("SHA256:"));
+    assert_eq!(key["projectDefault"], true);
+    let key_id = key["id"].as_str().unwrap();
+
+    // Same key under another name is a conflict
+    let response = server
+        .post_json(
+            &format!("/projects/{}/ssh-keys", project_id),
+            &json!({"name": "laptop-2", "publicKey": TEST_ED25519_KEY}),
+        )
+        .await;
+    assert_eq!(response.status(), 409);
+
+    let response = server
+        .get(&format!("/projects/{}/ssh-keys", project_id))
+        .await;
+    let list: Value = response.json().await.unwrap();
+    assert_eq!(list["sshKeys"].as_array().unwrap().len(), 1);
+
+    let response = server.delete(&format!("/ssh-keys/{}", key_id)).await;
+    assert_eq!(response.status(), 204);
+    let response = server.get(&format!("/ssh-keys/{}", key_id)).await;
+    assert_eq!(response.status(), 404);
+
+    server.shutdown().await;
+}
+
+#[tokio::test]
+async fn test_create_ssh_key_validation() {
+    let server = common::TestServer::spawn().await;
+
+    let proj_resp = server
+        .post_json(
+            "/orgs/test/projects",
+            &json!({"slug": "badkeyproj", "name": "bad-key-proj"}),
+        )
+        .await;
+    let proj: Value = proj_resp.json().await.unwrap();
+    let project_id = proj["slug"].as_str().unwrap();
+
+    for public_key in [
+        "not a key",
+        "ssh-dss AAAAB3NzaC1kc3M= old",
+        // Declared type doesn't match the blob (ed25519 data)
+        "ssh-rsa AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl",
+    ] {
+        let response = server
+            .post_json(
+                &format!("/projects/{}/ssh-keys", project_id),
+                &json!({"name": "k", "publicKey": public_key}),
+            )
+            .await;
+        assert_eq!(response.status(), 400, "{public_key}");
+    }
+
+    server.shutdown().await;
+}
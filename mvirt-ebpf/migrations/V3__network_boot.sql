@@ -0,0 +1,3 @@
+-- Network boot (PXE / UEFI HTTP boot) options announced via DHCP
+ALTER TABLE networks ADD COLUMN boot_server TEXT;
+ALTER TABLE networks ADD COLUMN boot_file TEXT;
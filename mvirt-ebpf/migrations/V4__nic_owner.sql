@@ -0,0 +1,3 @@
+-- Owner reference: the pod or VM whose deletion takes the NIC with it
+ALTER TABLE nics ADD COLUMN owner_kind TEXT;
+ALTER TABLE nics ADD COLUMN owner_id TEXT;
@@ -0,0 +1,3 @@
+-- Flow log export per network
+ALTER TABLE networks ADD COLUMN flow_logs INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE networks ADD COLUMN flow_sample_rate INTEGER NOT NULL DEFAULT 1;
@@ -0,0 +1,2 @@
+-- Security groups in audit mode report would-drop packets instead of dropping
+ALTER TABLE security_groups ADD COLUMN audit INTEGER NOT NULL DEFAULT 0;
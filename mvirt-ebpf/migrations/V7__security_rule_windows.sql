@@ -0,0 +1,4 @@
+-- Validity windows and weekly schedules for security group rules
+ALTER TABLE security_group_rules ADD COLUMN not_before TEXT;
+ALTER TABLE security_group_rules ADD COLUMN not_after TEXT;
+ALTER TABLE security_group_rules ADD COLUMN schedule TEXT;
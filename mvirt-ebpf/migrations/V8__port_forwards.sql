@@ -0,0 +1,14 @@
+-- Port forwards: a TCP or UDP port of a host address forwarded to a port
+-- of a NIC's IPv4 address.
+CREATE TABLE port_forwards (
+    id TEXT PRIMARY KEY,
+    nic_id TEXT NOT NULL REFERENCES nics(id) ON DELETE CASCADE,
+    protocol TEXT NOT NULL,
+    host_ip TEXT NOT NULL,
+    host_port INTEGER NOT NULL,
+    vm_port INTEGER NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE(protocol, host_ip, host_port)
+);
+
+CREATE INDEX idx_port_forwards_nic_id ON port_forwards(nic_id);
@@ -256,6 +256,80 @@ impl ConnTrackEntry {
     }
 }
 
+/// Flow accounting key: the connection tuple as seen on one NIC in one
+/// direction. Egress flows are keyed by the VM's TAP ifindex, ingress
+/// flows by the TAP the packet is routed to.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FlowKey {
+    /// TAP ifindex of the NIC
+    pub ifindex: u32,
+    /// DIRECTION_INGRESS (to VM) or DIRECTION_EGRESS (from VM)
+    pub direction: u8,
+    /// Padding for alignment
+    pub _pad: [u8; 3],
+    /// 5-tuple as it appears in the packet
+    pub tuple: ConnTrackKey,
+}
+
+impl FlowKey {
+    pub const fn new(ifindex: u32, direction: u8, tuple: ConnTrackKey) -> Self {
+        Self {
+            ifindex,
+            direction,
+            _pad: [0; 3],
+            tuple,
+        }
+    }
+}
+
+/// Flow accounting counters. Only sampled packets are counted; userspace
+/// scales by the sample rate on export.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FlowStats {
+    /// First sampled packet (nanoseconds since boot)
+    pub first_seen_ns: u64,
+    /// Last sampled packet (nanoseconds since boot)
+    pub last_seen_ns: u64,
+    /// Sampled packets
+    pub packets: u64,
+    /// Sampled bytes (L2 frame length)
+    pub bytes: u64,
+}
+
+impl FlowStats {
+    pub const fn new(now_ns: u64, bytes: u64) -> Self {
+        Self {
+            first_seen_ns: now_ns,
+            last_seen_ns: now_ns,
+            packets: 1,
+            bytes,
+        }
+    }
+}
+
+/// Per-NIC flow accounting settings (ifindex -> config)
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct FlowConfig {
+    /// Whether flow accounting is enabled for this NIC
+    pub enabled: u8,
+    _padding: [u8; 3],
+    /// Count 1 in `sample_rate` packets (0 and 1 both mean every packet)
+    pub sample_rate: u32,
+}
+
+impl FlowConfig {
+    pub const fn new(sample_rate: u32) -> Self {
+        Self {
+            enabled: 1,
+            _padding: [0; 3],
+            sample_rate,
+        }
+    }
+}
+
 /// NIC security configuration
 /// Maps NIC ifindex to its security settings
 #[repr(C)]
@@ -263,7 +337,10 @@ impl ConnTrackEntry {
 pub struct NicSecurityConfig {
     /// Whether security filtering is enabled for this NIC
     pub enabled: u8,
-    _padding: [u8; 3],
+    /// Audit mode (1): packets the rules would drop are counted in
+    /// SECURITY_AUDIT and passed instead
+    pub audit: u8,
+    _padding: [u8; 2],
     /// Start index into SECURITY_RULES map for this NIC's rules
     pub rules_start: u32,
     /// Number of rules for this NIC
@@ -274,7 +351,8 @@ impl NicSecurityConfig {
     pub const fn new() -> Self {
         Self {
             enabled: 0,
-            _padding: [0; 3],
+            audit: 0,
+            _padding: [0; 2],
             rules_start: 0,
             rules_count: 0,
         }
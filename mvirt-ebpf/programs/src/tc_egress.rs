@@ -5,6 +5,7 @@
 //! - DHCP/ARP/NDP detection -> pass to userspace handler
 //! - Security group rule checking (egress rules)
 //! - Connection tracking for stateful filtering
+//! - Sampled flow accounting for flow log export
 //! - LPM routing lookup for IPv4/IPv6
 //! - bpf_redirect() for VM-to-VM traffic
 //! - Pass to kernel stack for external traffic
@@ -15,9 +16,9 @@
 use aya_ebpf::{
     bindings::TC_ACT_OK,
     bindings::TC_ACT_SHOT,
-    helpers::{bpf_ktime_get_ns, bpf_redirect},
+    helpers::{bpf_get_prandom_u32, bpf_ktime_get_ns, bpf_redirect},
     macros::{classifier, map},
-    maps::{HashMap, LpmTrie},
+    maps::{HashMap, LpmTrie, LruHashMap},
     programs::TcContext,
 };
 
@@ -26,10 +27,10 @@ use aya_ebpf::maps::lpm_trie::Key;
 use mvirt_ebpf_programs::{
     ACTION_DROP, ACTION_PASS, ACTION_REDIRECT, CT_STATE_NEW, ConnTrackEntry, ConnTrackKey,
     DHCP_CLIENT_PORT, DHCP_SERVER_PORT, DHCPV6_CLIENT_PORT, DHCPV6_SERVER_PORT, DIRECTION_EGRESS,
-    ETH_P_ARP, ETH_P_IP, ETH_P_IPV6, ICMPV6_NEIGHBOR_ADVERTISEMENT, ICMPV6_NEIGHBOR_SOLICITATION,
-    ICMPV6_ROUTER_ADVERTISEMENT, ICMPV6_ROUTER_SOLICITATION, IPPROTO_IPIP, IPPROTO_IPV6_ENCAP,
-    IPPROTO_TCP, IPPROTO_UDP, IPV6_HDR_SIZE, IfMac, LocalNicInfo, NicSecurityConfig, PROTO_ALL,
-    RouteEntry, SecurityRule, TunnelEndpoint,
+    ETH_P_ARP, ETH_P_IP, ETH_P_IPV6, FlowConfig, FlowKey, FlowStats, ICMPV6_NEIGHBOR_ADVERTISEMENT,
+    ICMPV6_NEIGHBOR_SOLICITATION, ICMPV6_ROUTER_ADVERTISEMENT, ICMPV6_ROUTER_SOLICITATION,
+    IPPROTO_IPIP, IPPROTO_IPV6_ENCAP, IPPROTO_TCP, IPPROTO_UDP, IPV6_HDR_SIZE, IfMac, LocalNicInfo,
+    NicSecurityConfig, PROTO_ALL, RouteEntry, SecurityRule, TunnelEndpoint,
 };
 
 // Local protocol constants
@@ -77,6 +78,15 @@ static TUNNEL_ENDPOINTS_V6: LpmTrie<[u8; 16], TunnelEndpoint> = LpmTrie::with_ma
 #[map]
 static LOCAL_NIC_INFO: HashMap<u32, LocalNicInfo> = HashMap::with_max_entries(256, 0);
 
+/// Flow accounting settings (ifindex -> config)
+#[map]
+static FLOW_CONFIG: HashMap<u32, FlowConfig> = HashMap::with_max_entries(256, 0);
+
+/// Flow accounting counters, read and expired by the userspace exporter.
+/// LRU so a flood of short flows evicts old entries instead of failing.
+#[map]
+static FLOWS: LruHashMap<FlowKey, FlowStats> = LruHashMap::with_max_entries(65536, 0);
+
 #[classifier]
 pub fn tc_egress(ctx: TcContext) -> i32 {
     match try_tc_egress(&ctx) {
@@ -159,6 +169,12 @@ fn try_tc_egress(ctx: &TcContext) -> Result<i32, ()> {
             ) {
                 return Ok(TC_ACT_SHOT);
             }
+            record_flow(
+                ctx,
+                ifindex,
+                DIRECTION_EGRESS,
+                ConnTrackKey::from_tuple(src_addr, dst_addr, src_port, dst_port, proto, 4),
+            );
 
             // LPM route lookup
             let key = Key::new(32, dst_ip4);
@@ -247,6 +263,12 @@ fn try_tc_egress(ctx: &TcContext) -> Result<i32, ()> {
             ) {
                 return Ok(TC_ACT_SHOT);
             }
+            record_flow(
+                ctx,
+                ifindex,
+                DIRECTION_EGRESS,
+                ConnTrackKey::from_tuple(src_addr, dst_addr, src_port, dst_port, next_hdr, 6),
+            );
 
             // LPM route lookup
             let key = Key::new(128, dst_addr);
@@ -331,6 +353,33 @@ fn check_security_egress(
     true
 }
 
+/// Count a packet in FLOWS if flow accounting is enabled for the NIC.
+/// Sampled NICs count 1 in `sample_rate` packets; userspace scales up.
+#[inline(always)]
+fn record_flow(ctx: &TcContext, ifindex: u32, direction: u8, tuple: ConnTrackKey) {
+    let config = match unsafe { FLOW_CONFIG.get(&ifindex) } {
+        Some(c) if c.enabled != 0 => c,
+        _ => return,
+    };
+    if config.sample_rate > 1 && unsafe { bpf_get_prandom_u32() } % config.sample_rate != 0 {
+        return;
+    }
+
+    let key = FlowKey::new(ifindex, direction, tuple);
+    let now_ns = unsafe { bpf_ktime_get_ns() };
+    let bytes = ctx.len() as u64;
+    match FLOWS.get_ptr_mut(&key) {
+        Some(stats) => unsafe {
+            (*stats).last_seen_ns = now_ns;
+            (*stats).packets += 1;
+            (*stats).bytes += bytes;
+        },
+        None => {
+            let _ = FLOWS.insert(&key, &FlowStats::new(now_ns, bytes), 0);
+        }
+    }
+}
+
 /// Check if a packet matches a security rule
 #[inline(always)]
 fn rule_matches(
@@ -8,6 +8,11 @@
 //! - Default DENY (except for established connections)
 //! - Check connection tracking for return traffic
 //! - Check ingress rules for new connections
+//!
+//! Allowed packets are counted for flow log export on the target NIC.
+//!
+//! NICs in audit mode are not filtered: packets no rule allows are counted
+//! in SECURITY_AUDIT as would-drop and delivered.
 
 #![no_std]
 #![no_main]
@@ -15,17 +20,17 @@
 use aya_ebpf::{
     bindings::TC_ACT_OK,
     bindings::TC_ACT_SHOT,
-    helpers::{bpf_ktime_get_ns, bpf_redirect},
+    helpers::{bpf_get_prandom_u32, bpf_ktime_get_ns, bpf_redirect},
     macros::{classifier, map},
-    maps::{HashMap, LpmTrie, lpm_trie::Key},
+    maps::{HashMap, LpmTrie, LruHashMap, lpm_trie::Key},
     programs::TcContext,
 };
 
 use mvirt_ebpf_programs::{
     ACTION_DROP, ACTION_PASS, ACTION_REDIRECT, CT_FLAG_SEEN_REPLY, CT_STATE_ESTABLISHED,
     CT_STATE_NEW, ConnTrackEntry, ConnTrackKey, DIRECTION_INGRESS, ETH_P_IP, ETH_P_IPV6,
-    IPPROTO_IPIP, IPPROTO_IPV6_ENCAP, IPPROTO_TCP, IPPROTO_UDP, IPV6_HDR_SIZE, IfMac,
-    NicSecurityConfig, PROTO_ALL, RouteEntry, SecurityRule, TunnelMetadata,
+    FlowConfig, FlowKey, FlowStats, IPPROTO_IPIP, IPPROTO_IPV6_ENCAP, IPPROTO_TCP, IPPROTO_UDP,
+    IPV6_HDR_SIZE, IfMac, NicSecurityConfig, PROTO_ALL, RouteEntry, SecurityRule, TunnelMetadata,
 };
 
 // Header sizes
@@ -56,6 +61,20 @@ static NIC_SECURITY: HashMap<u32, NicSecurityConfig> = HashMap::with_max_entries
 #[map]
 static CONN_TRACK: HashMap<ConnTrackKey, ConnTrackEntry> = HashMap::with_max_entries(65536, 0);
 
+/// Flow accounting settings (ifindex -> config)
+#[map]
+static FLOW_CONFIG: HashMap<u32, FlowConfig> = HashMap::with_max_entries(256, 0);
+
+/// Flow accounting counters, read and expired by the userspace exporter.
+/// LRU so a flood of short flows evicts old entries instead of failing.
+#[map]
+static FLOWS: LruHashMap<FlowKey, FlowStats> = LruHashMap::with_max_entries(65536, 0);
+
+/// Would-drop counters of NICs in security audit mode, per 5-tuple. Every
+/// packet is counted; read and expired by the userspace reporter.
+#[map]
+static SECURITY_AUDIT: LruHashMap<FlowKey, FlowStats> = LruHashMap::with_max_entries(16384, 0);
+
 #[classifier]
 pub fn tc_ingress(ctx: TcContext) -> i32 {
     match try_tc_ingress(&ctx) {
@@ -127,6 +146,7 @@ fn try_tc_ingress(ctx: &TcContext) -> Result<i32, ()> {
 
             // Check security for ingress traffic
             if !check_security_ingress(
+                ctx,
                 route.target_ifindex,
                 &src_addr,
                 &dst_addr,
@@ -137,6 +157,12 @@ fn try_tc_ingress(ctx: &TcContext) -> Result<i32, ()> {
             ) {
                 return Ok(TC_ACT_SHOT);
             }
+            record_flow(
+                ctx,
+                route.target_ifindex,
+                DIRECTION_INGRESS,
+                ConnTrackKey::from_tuple(src_addr, dst_addr, src_port, dst_port, proto, 4),
+            );
 
             handle_route(ctx, route)
         }
@@ -184,6 +210,7 @@ fn try_tc_ingress(ctx: &TcContext) -> Result<i32, ()> {
 
             // Check security for ingress traffic
             if !check_security_ingress(
+                ctx,
                 route.target_ifindex,
                 &src_addr,
                 &dst_addr,
@@ -194,6 +221,12 @@ fn try_tc_ingress(ctx: &TcContext) -> Result<i32, ()> {
             ) {
                 return Ok(TC_ACT_SHOT);
             }
+            record_flow(
+                ctx,
+                route.target_ifindex,
+                DIRECTION_INGRESS,
+                ConnTrackKey::from_tuple(src_addr, dst_addr, src_port, dst_port, next_hdr, 6),
+            );
 
             handle_route(ctx, route)
         }
@@ -205,9 +238,11 @@ fn try_tc_ingress(ctx: &TcContext) -> Result<i32, ()> {
 }
 
 /// Check security rules for ingress traffic
-/// For ingress: default DENY (check CT first, then rules)
+/// For ingress: default DENY (check CT first, then rules); in audit mode
+/// the would-be drop is counted and the packet allowed
 #[inline(always)]
 fn check_security_ingress(
+    ctx: &TcContext,
     target_ifindex: u32,
     src_addr: &[u8; 16],
     dst_addr: &[u8; 16],
@@ -274,10 +309,63 @@ fn check_security_ingress(
         iter += 1;
     }
 
-    // No rule matched - deny (default for ingress)
+    // No rule matched - deny (default for ingress), or only count it
+    if config.audit != 0 {
+        let tuple = ConnTrackKey::from_tuple(
+            *src_addr, *dst_addr, src_port, dst_port, protocol, ip_version,
+        );
+        record_would_drop(ctx, target_ifindex, tuple);
+        return true;
+    }
     false
 }
 
+/// Count a packet the NIC's rules would have dropped. No CT entry is
+/// created, so every packet of the flow keeps being counted.
+#[inline(always)]
+fn record_would_drop(ctx: &TcContext, ifindex: u32, tuple: ConnTrackKey) {
+    let key = FlowKey::new(ifindex, DIRECTION_INGRESS, tuple);
+    let now_ns = unsafe { bpf_ktime_get_ns() };
+    let bytes = ctx.len() as u64;
+    match SECURITY_AUDIT.get_ptr_mut(&key) {
+        Some(stats) => unsafe {
+            (*stats).last_seen_ns = now_ns;
+            (*stats).packets += 1;
+            (*stats).bytes += bytes;
+        },
+        None => {
+            let _ = SECURITY_AUDIT.insert(&key, &FlowStats::new(now_ns, bytes), 0);
+        }
+    }
+}
+
+/// Count a packet in FLOWS if flow accounting is enabled for the NIC.
+/// Sampled NICs count 1 in `sample_rate` packets; userspace scales up.
+#[inline(always)]
+fn record_flow(ctx: &TcContext, ifindex: u32, direction: u8, tuple: ConnTrackKey) {
+    let config = match unsafe { FLOW_CONFIG.get(&ifindex) } {
+        Some(c) if c.enabled != 0 => c,
+        _ => return,
+    };
+    if config.sample_rate > 1 && unsafe { bpf_get_prandom_u32() } % config.sample_rate != 0 {
+        return;
+    }
+
+    let key = FlowKey::new(ifindex, direction, tuple);
+    let now_ns = unsafe { bpf_ktime_get_ns() };
+    let bytes = ctx.len() as u64;
+    match FLOWS.get_ptr_mut(&key) {
+        Some(stats) => unsafe {
+            (*stats).last_seen_ns = now_ns;
+            (*stats).packets += 1;
+            (*stats).bytes += bytes;
+        },
+        None => {
+            let _ = FLOWS.insert(&key, &FlowStats::new(now_ns, bytes), 0);
+        }
+    }
+}
+
 /// Check if a packet matches a security rule
 #[inline(always)]
 fn rule_matches(
@@ -496,6 +584,7 @@ fn handle_tunnel_decap(ctx: &TcContext, next_hdr: u8) -> Result<i32, ()> {
 
         // Check security (using extracted SG_ID in future)
         if !check_security_ingress(
+            ctx,
             route.target_ifindex,
             &src_addr,
             &dst_addr,
@@ -542,6 +631,7 @@ fn handle_tunnel_decap(ctx: &TcContext, next_hdr: u8) -> Result<i32, ()> {
 
         // Check security
         if !check_security_ingress(
+            ctx,
             route.target_ifindex,
             &src_addr,
             &dst_addr,
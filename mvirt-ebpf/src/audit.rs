@@ -6,7 +6,7 @@
 use std::sync::Arc;
 
 use mvirt_log::{AuditLogger, LogLevel};
-use tonic::transport::ClientTlsConfig;
+use tonic::transport::{Channel, ClientTlsConfig};
 
 /// eBPF network audit logger with domain-specific methods.
 ///
@@ -28,6 +28,13 @@ impl EbpfAuditLogger {
         }
     }
 
+    /// Create an audit logger on an existing mvirt-log channel
+    pub fn with_channel(channel: Channel) -> Self {
+        Self {
+            inner: Arc::new(AuditLogger::with_channel(channel, "ebpf")),
+        }
+    }
+
     /// Create a noop audit logger (for testing)
     #[allow(dead_code)]
     pub fn new_noop() -> Self {
@@ -39,6 +46,8 @@ impl EbpfAuditLogger {
     /// Fire-and-forget log helper
     fn log_async(&self, level: LogLevel, message: String, object_ids: Vec<String>) {
         let inner = Arc::clone(&self.inner);
+        // The spawned task doesn't inherit the caller's request ID
+        let object_ids = mvirt_common::request_id::tag(object_ids);
         tokio::spawn(async move {
             inner.log(level, message, object_ids).await;
         });
@@ -212,6 +221,87 @@ impl EbpfAuditLogger {
             vec![sg_id.to_string(), nic_id.to_string()],
         );
     }
+
+    pub fn security_group_audit_set(&self, sg_id: &str, sg_name: &str, audit: bool) {
+        let mode = if audit { "audit" } else { "enforce" };
+        self.log_async(
+            LogLevel::Audit,
+            format!("Security group '{}' set to {} mode", sg_name, mode),
+            vec![sg_id.to_string()],
+        );
+    }
+
+    // === Port Forward Events ===
+
+    pub fn port_forward_created(&self, forward_id: &str, nic_id: &str, host: &str, vm: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Port forwarded: {} to {}", host, vm),
+            vec![forward_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    pub fn port_forward_deleted(&self, forward_id: &str, nic_id: &str, host: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Port forward removed: {}", host),
+            vec![forward_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    // === Flow Logs ===
+
+    pub fn flow_record(&self, nic_id: &str, network_id: &str, flow: &str) {
+        self.log_async(
+            LogLevel::Info,
+            format!("Flow {}", flow),
+            vec![nic_id.to_string(), network_id.to_string()],
+        );
+    }
+
+    // === Security Audit Mode ===
+
+    pub fn security_audit(&self, nic_id: &str, report: &str) {
+        self.log_async(
+            LogLevel::Notice,
+            format!("Security audit: {}", report),
+            vec![nic_id.to_string()],
+        );
+    }
+
+    // === Cleanup ===
+
+    pub fn orphan_removed(&self, kind: &str, name: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Removed orphaned {} {}", kind, name),
+            vec![],
+        );
+    }
+
+    pub fn address_reallocated(&self, nic_id: &str, old: &str, new: Option<&str>, reason: &str) {
+        let message = match new {
+            Some(new) => format!(
+                "Moved {} off reserved {} {} to {}",
+                nic_id, reason, old, new
+            ),
+            None => format!(
+                "NIC {} holds reserved {} {}; no free address to move it to",
+                nic_id, reason, old
+            ),
+        };
+        self.log_async(LogLevel::Audit, message, vec![nic_id.to_string()]);
+    }
+
+    // === Host Migration ===
+
+    pub fn state_imported(&self, networks: usize, nics: usize) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Host state imported: {} networks, {} NICs", networks, nics),
+            vec![],
+        );
+    }
 }
 
 /// Create a shared eBPF network audit logger
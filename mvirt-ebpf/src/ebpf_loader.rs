@@ -17,6 +17,14 @@ pub const ACTION_DROP: u8 = 0;
 pub const ACTION_REDIRECT: u8 = 1;
 pub const ACTION_PASS: u8 = 2;
 
+/// Default paths of the installed eBPF programs
+pub const EGRESS_PROGRAM_PATH: &str = "/usr/lib/mvirt/ebpf/tc-egress";
+pub const INGRESS_PROGRAM_PATH: &str = "/usr/lib/mvirt/ebpf/tc-ingress";
+
+/// Flow directions (must match eBPF program)
+pub const DIRECTION_INGRESS: u8 = 0;
+pub const DIRECTION_EGRESS: u8 = 1;
+
 /// Route entry for LPM lookup result.
 /// Must match the eBPF struct exactly.
 #[repr(C)]
@@ -113,7 +121,9 @@ unsafe impl aya::Pod for SecurityRule {}
 pub struct NicSecurityConfig {
     /// Whether security filtering is enabled
     pub enabled: u8,
-    _padding: [u8; 3],
+    /// Count would-drop packets instead of dropping them
+    pub audit: u8,
+    _padding: [u8; 2],
     /// Start index into SECURITY_RULES map
     pub rules_start: u32,
     /// Number of rules
@@ -121,10 +131,11 @@ pub struct NicSecurityConfig {
 }
 
 impl NicSecurityConfig {
-    pub fn new(enabled: bool, rules_start: u32, rules_count: u32) -> Self {
+    pub fn new(enabled: bool, audit: bool, rules_start: u32, rules_count: u32) -> Self {
         Self {
             enabled: if enabled { 1 } else { 0 },
-            _padding: [0; 3],
+            audit: if audit { 1 } else { 0 },
+            _padding: [0; 2],
             rules_start,
             rules_count,
         }
@@ -161,6 +172,58 @@ pub struct ConnTrackEntry {
 
 unsafe impl aya::Pod for ConnTrackEntry {}
 
+/// Flow accounting key: a 5-tuple on one NIC in one direction.
+/// Must match the eBPF struct exactly.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FlowKey {
+    /// TAP ifindex of the NIC
+    pub ifindex: u32,
+    /// DIRECTION_INGRESS or DIRECTION_EGRESS
+    pub direction: u8,
+    _padding: [u8; 3],
+    pub tuple: ConnTrackKey,
+}
+
+unsafe impl aya::Pod for FlowKey {}
+
+/// Sampled flow counters.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FlowStats {
+    /// First sampled packet (monotonic ns since boot)
+    pub first_seen_ns: u64,
+    /// Last sampled packet (monotonic ns since boot)
+    pub last_seen_ns: u64,
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+unsafe impl aya::Pod for FlowStats {}
+
+/// Per-NIC flow accounting settings.
+/// Must match the eBPF struct exactly.
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FlowConfig {
+    pub enabled: u8,
+    _padding: [u8; 3],
+    /// Count 1 in `sample_rate` packets
+    pub sample_rate: u32,
+}
+
+impl FlowConfig {
+    pub fn new(sample_rate: u32) -> Self {
+        Self {
+            enabled: 1,
+            _padding: [0; 3],
+            sample_rate,
+        }
+    }
+}
+
+unsafe impl aya::Pod for FlowConfig {}
+
 /// Tunnel endpoint for remote hypervisors.
 /// Maps inner destination subnet to remote HV prefix.
 #[repr(C)]
@@ -269,17 +332,20 @@ impl EbpfManager {
 
     /// Load eBPF programs from default paths.
     pub fn load() -> Result<Self> {
-        // Default paths for installed eBPF programs
-        let egress_path = "/usr/lib/mvirt/ebpf/tc-egress";
-        let ingress_path = "/usr/lib/mvirt/ebpf/tc-ingress";
-
         // Check if files exist, otherwise return empty manager
-        if !std::path::Path::new(egress_path).exists() {
+        if !std::path::Path::new(EGRESS_PROGRAM_PATH).exists() {
             info!("eBPF programs not found, using stub implementation");
             return Ok(Self::new());
         }
 
-        Self::load_from_paths(egress_path, ingress_path)
+        Self::load_from_paths(EGRESS_PROGRAM_PATH, INGRESS_PROGRAM_PATH)
+    }
+
+    /// Whether the installed programs load on this kernel. Unlike
+    /// [`load`](Self::load), a host without them doesn't count.
+    pub fn probe() -> bool {
+        std::path::Path::new(EGRESS_PROGRAM_PATH).exists()
+            && Self::load_from_paths(EGRESS_PROGRAM_PATH, INGRESS_PROGRAM_PATH).is_ok()
     }
 
     /// Attach TC egress program to a TAP interface.
@@ -651,6 +717,23 @@ impl EbpfManager {
         Ok(())
     }
 
+    /// Remove a security rule from the ingress SECURITY_RULES map.
+    pub async fn remove_ingress_security_rule(&self, rule_index: u32) -> Result<()> {
+        let mut guard = self.ingress_bpf.write().await;
+        let bpf = match guard.as_mut() {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+
+        let mut rules: HashMap<&mut MapData, u32, SecurityRule> = bpf
+            .map_mut("SECURITY_RULES")
+            .ok_or_else(|| EbpfError::MapNotFound("SECURITY_RULES".to_string()))?
+            .try_into()?;
+
+        let _ = rules.remove(&rule_index);
+        Ok(())
+    }
+
     /// Set NIC security configuration in the ingress NIC_SECURITY map.
     pub async fn set_ingress_nic_security_config(
         &self,
@@ -672,6 +755,59 @@ impl EbpfManager {
         Ok(())
     }
 
+    /// Remove NIC security configuration from the ingress NIC_SECURITY map.
+    pub async fn remove_ingress_nic_security_config(&self, if_index: u32) -> Result<()> {
+        let mut guard = self.ingress_bpf.write().await;
+        let bpf = match guard.as_mut() {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+
+        let mut configs: HashMap<&mut MapData, u32, NicSecurityConfig> = bpf
+            .map_mut("NIC_SECURITY")
+            .ok_or_else(|| EbpfError::MapNotFound("NIC_SECURITY".to_string()))?
+            .try_into()?;
+
+        let _ = configs.remove(&if_index);
+        Ok(())
+    }
+
+    /// Snapshot of the would-drop counters of NICs in audit mode.
+    pub async fn read_security_audit(&self) -> Result<Vec<(FlowKey, FlowStats)>> {
+        let guard = self.ingress_bpf.read().await;
+        let bpf = match guard.as_ref() {
+            Some(b) => b,
+            None => return Ok(Vec::new()),
+        };
+
+        let map: HashMap<&MapData, FlowKey, FlowStats> = bpf
+            .map("SECURITY_AUDIT")
+            .ok_or_else(|| EbpfError::MapNotFound("SECURITY_AUDIT".to_string()))?
+            .try_into()?;
+
+        // Entries can be evicted mid-walk; skip the ones that vanish
+        Ok(map.iter().filter_map(|entry| entry.ok()).collect())
+    }
+
+    /// Remove would-drop counters.
+    pub async fn remove_security_audit(&self, keys: &[FlowKey]) -> Result<()> {
+        let mut guard = self.ingress_bpf.write().await;
+        let bpf = match guard.as_mut() {
+            Some(b) => b,
+            None => return Ok(()),
+        };
+
+        let mut map: HashMap<&mut MapData, FlowKey, FlowStats> = bpf
+            .map_mut("SECURITY_AUDIT")
+            .ok_or_else(|| EbpfError::MapNotFound("SECURITY_AUDIT".to_string()))?
+            .try_into()?;
+
+        for key in keys {
+            let _ = map.remove(key);
+        }
+        Ok(())
+    }
+
     // ========== Tunnel Endpoint Management ==========
 
     /// Add IPv4 tunnel endpoint for remote subnet.
@@ -792,6 +928,93 @@ impl EbpfManager {
         let _ = nic_info.remove(&ifindex);
         Ok(())
     }
+
+    // ========== Flow Accounting ==========
+
+    /// Enable flow accounting for a NIC in both programs; egress counts
+    /// traffic from the VM, ingress traffic routed to it.
+    pub async fn set_flow_config(&self, ifindex: u32, config: FlowConfig) -> Result<()> {
+        for program in [&self.egress_bpf, &self.ingress_bpf] {
+            let mut guard = program.write().await;
+            let bpf = match guard.as_mut() {
+                Some(b) => b,
+                None => return Ok(()),
+            };
+
+            let mut configs: HashMap<&mut MapData, u32, FlowConfig> = bpf
+                .map_mut("FLOW_CONFIG")
+                .ok_or_else(|| EbpfError::MapNotFound("FLOW_CONFIG".to_string()))?
+                .try_into()?;
+
+            configs.insert(ifindex, config, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Disable flow accounting for a NIC. Counters already collected stay
+    /// in FLOWS until the exporter drains them.
+    pub async fn remove_flow_config(&self, ifindex: u32) -> Result<()> {
+        for program in [&self.egress_bpf, &self.ingress_bpf] {
+            let mut guard = program.write().await;
+            let bpf = match guard.as_mut() {
+                Some(b) => b,
+                None => return Ok(()),
+            };
+
+            let mut configs: HashMap<&mut MapData, u32, FlowConfig> = bpf
+                .map_mut("FLOW_CONFIG")
+                .ok_or_else(|| EbpfError::MapNotFound("FLOW_CONFIG".to_string()))?
+                .try_into()?;
+
+            let _ = configs.remove(&ifindex);
+        }
+        Ok(())
+    }
+
+    /// Snapshot of all flow counters from both programs.
+    pub async fn read_flows(&self) -> Result<Vec<(FlowKey, FlowStats)>> {
+        let mut flows = Vec::new();
+        for program in [&self.egress_bpf, &self.ingress_bpf] {
+            let guard = program.read().await;
+            let bpf = match guard.as_ref() {
+                Some(b) => b,
+                None => return Ok(flows),
+            };
+
+            let map: HashMap<&MapData, FlowKey, FlowStats> = bpf
+                .map("FLOWS")
+                .ok_or_else(|| EbpfError::MapNotFound("FLOWS".to_string()))?
+                .try_into()?;
+
+            // Entries can be evicted mid-walk; skip the ones that vanish
+            flows.extend(map.iter().filter_map(|entry| entry.ok()));
+        }
+        Ok(flows)
+    }
+
+    /// Remove flow counters, each from the program that owns its direction.
+    pub async fn remove_flows(&self, keys: &[FlowKey]) -> Result<()> {
+        for (program, direction) in [
+            (&self.egress_bpf, DIRECTION_EGRESS),
+            (&self.ingress_bpf, DIRECTION_INGRESS),
+        ] {
+            let mut guard = program.write().await;
+            let bpf = match guard.as_mut() {
+                Some(b) => b,
+                None => return Ok(()),
+            };
+
+            let mut map: HashMap<&mut MapData, FlowKey, FlowStats> = bpf
+                .map_mut("FLOWS")
+                .ok_or_else(|| EbpfError::MapNotFound("FLOWS".to_string()))?
+                .try_into()?;
+
+            for key in keys.iter().filter(|k| k.direction == direction) {
+                let _ = map.remove(key);
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Default for EbpfManager {
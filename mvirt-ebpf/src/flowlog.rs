@@ -0,0 +1,584 @@
+//! Flow log export.
+//!
+//! For NICs whose network has flow logs enabled, the TC programs count
+//! packets and bytes per 5-tuple and direction in the FLOWS map, sampling
+//! 1 in N packets when the network sets a sample rate. This task reads the
+//! map on an interval, turns what each flow grew by since the last read
+//! into a record (scaled by the sample rate), and sends the records to
+//! mvirt-log and, if configured, an IPFIX collector.
+//!
+//! Flows idle past the timeout are exported one last time and removed from
+//! the map, as are the flows of NICs that were torn down. Traffic arriving
+//! over a cross-host tunnel is not counted on ingress.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use tokio::sync::RwLock;
+use tokio::time;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::audit::EbpfAuditLogger;
+use crate::ebpf_loader::{DIRECTION_EGRESS, EbpfManager, FlowKey, FlowStats};
+use crate::ipfix::IpfixExporter;
+
+/// Default export interval in seconds
+pub const DEFAULT_EXPORT_INTERVAL_SECS: u64 = 60;
+
+/// Default idle time after which a flow is closed, in seconds
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 120;
+
+/// Flow log exporter settings.
+#[derive(Debug, Clone)]
+pub struct FlowLogConfig {
+    pub interval: Duration,
+    pub idle_timeout: Duration,
+    /// IPFIX collector; records only go to mvirt-log when unset
+    pub ipfix_collector: Option<SocketAddr>,
+}
+
+impl Default for FlowLogConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(DEFAULT_EXPORT_INTERVAL_SECS),
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+            ipfix_collector: None,
+        }
+    }
+}
+
+/// Direction of a flow relative to the VM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FlowDirection {
+    /// Towards the VM
+    Ingress,
+    /// From the VM
+    Egress,
+}
+
+impl fmt::Display for FlowDirection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            FlowDirection::Ingress => "ingress",
+            FlowDirection::Egress => "egress",
+        })
+    }
+}
+
+/// One exported flow record: what a flow added since the previous export.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FlowRecord {
+    pub nic_id: Uuid,
+    pub network_id: Uuid,
+    pub direction: FlowDirection,
+    pub src_addr: IpAddr,
+    pub dst_addr: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub protocol: u8,
+    /// Packets since the previous record, scaled by the sample rate
+    pub packets: u64,
+    /// Bytes since the previous record, scaled by the sample rate
+    pub bytes: u64,
+    /// First packet of the flow
+    pub start: DateTime<Utc>,
+    /// Latest packet of the flow
+    pub end: DateTime<Utc>,
+}
+
+/// Lowercase protocol name, or the number for others.
+pub(crate) fn protocol_name(protocol: u8) -> String {
+    match protocol {
+        1 => "icmp".to_string(),
+        6 => "tcp".to_string(),
+        17 => "udp".to_string(),
+        58 => "icmpv6".to_string(),
+        p => p.to_string(),
+    }
+}
+
+impl fmt::Display for FlowRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} -> {} packets={} bytes={} start={} end={}",
+            self.direction,
+            protocol_name(self.protocol),
+            SocketAddr::new(self.src_addr, self.src_port),
+            SocketAddr::new(self.dst_addr, self.dst_port),
+            self.packets,
+            self.bytes,
+            self.start.to_rfc3339(),
+            self.end.to_rfc3339(),
+        )
+    }
+}
+
+/// A NIC with flow logs enabled.
+#[derive(Debug, Clone)]
+pub struct FlowNic {
+    pub nic_id: Uuid,
+    pub network_id: Uuid,
+    pub sample_rate: u32,
+}
+
+#[derive(Debug, Clone)]
+struct Registered {
+    nic: FlowNic,
+    /// Torn down or flow logs disabled; dropped after the next export
+    retired: bool,
+}
+
+/// NICs with flow logs enabled, by TAP ifindex. The gRPC service registers
+/// NICs here; the exporter needs them to attribute and scale records.
+#[derive(Default)]
+pub struct FlowNics {
+    nics: RwLock<HashMap<u32, Registered>>,
+}
+
+impl FlowNics {
+    pub async fn register(&self, ifindex: u32, nic: FlowNic) {
+        self.nics.write().await.insert(
+            ifindex,
+            Registered {
+                nic,
+                retired: false,
+            },
+        );
+    }
+
+    /// Stop tracking a NIC. Counts collected so far are still exported.
+    pub async fn retire(&self, ifindex: u32) {
+        if let Some(registered) = self.nics.write().await.get_mut(&ifindex) {
+            registered.retired = true;
+        }
+    }
+
+    async fn snapshot(&self) -> HashMap<u32, Registered> {
+        self.nics.read().await.clone()
+    }
+
+    /// Forget retired NICs, unless they were registered again meanwhile.
+    async fn drop_retired(&self, ifindexes: &[u32]) {
+        let mut nics = self.nics.write().await;
+        for ifindex in ifindexes {
+            if nics.get(ifindex).is_some_and(|r| r.retired) {
+                nics.remove(ifindex);
+            }
+        }
+    }
+}
+
+/// Decoded FLOWS key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowId {
+    ifindex: u32,
+    direction: FlowDirection,
+    src_addr: IpAddr,
+    dst_addr: IpAddr,
+    src_port: u16,
+    dst_port: u16,
+    protocol: u8,
+}
+
+impl From<&FlowKey> for FlowId {
+    fn from(key: &FlowKey) -> Self {
+        let addr = |bytes: [u8; 16]| -> IpAddr {
+            if key.tuple.ip_version == 4 {
+                Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).into()
+            } else {
+                Ipv6Addr::from(bytes).into()
+            }
+        };
+        Self {
+            ifindex: key.ifindex,
+            direction: if key.direction == DIRECTION_EGRESS {
+                FlowDirection::Egress
+            } else {
+                FlowDirection::Ingress
+            },
+            src_addr: addr(key.tuple.src_addr),
+            dst_addr: addr(key.tuple.dst_addr),
+            src_port: key.tuple.src_port,
+            dst_port: key.tuple.dst_port,
+            protocol: key.tuple.protocol,
+        }
+    }
+}
+
+/// Sampled counters of a flow as of its last export.
+#[derive(Debug, Clone, Copy, Default)]
+struct Exported {
+    first_seen_ns: u64,
+    packets: u64,
+    bytes: u64,
+}
+
+/// Maps the BPF monotonic clock to wall-clock time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Clock {
+    pub(crate) now_ns: u64,
+    now: DateTime<Utc>,
+}
+
+impl Clock {
+    pub(crate) fn now() -> Self {
+        let mut ts = libc::timespec {
+            tv_sec: 0,
+            tv_nsec: 0,
+        };
+        // SAFETY: ts is a valid timespec; bpf_ktime_get_ns reads CLOCK_MONOTONIC
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+        Self {
+            now_ns: ts.tv_sec as u64 * 1_000_000_000 + ts.tv_nsec as u64,
+            now: Utc::now(),
+        }
+    }
+
+    fn wall(&self, ns: u64) -> DateTime<Utc> {
+        self.now - chrono::Duration::nanoseconds(self.now_ns.saturating_sub(ns) as i64)
+    }
+}
+
+/// Records for what each flow grew by since `exported`, and the flows to
+/// remove from the map. `exported` is updated for the flows that stay.
+fn collect(
+    flows: &[(FlowId, FlowStats)],
+    nics: &HashMap<u32, Registered>,
+    exported: &mut HashMap<FlowId, Exported>,
+    clock: Clock,
+    idle_timeout: Duration,
+) -> (Vec<FlowRecord>, Vec<FlowId>) {
+    let mut records = Vec::new();
+    let mut expired = Vec::new();
+
+    for (id, stats) in flows {
+        // NIC gone before we saw it; there is nothing to attribute to
+        let Some(registered) = nics.get(&id.ifindex) else {
+            exported.remove(id);
+            expired.push(*id);
+            continue;
+        };
+
+        // The LRU map may have evicted and restarted the flow meanwhile
+        let prev = exported
+            .get(id)
+            .filter(|e| e.first_seen_ns == stats.first_seen_ns && e.packets <= stats.packets)
+            .copied()
+            .unwrap_or_default();
+
+        let packets = stats.packets - prev.packets;
+        if packets > 0 {
+            let rate = registered.nic.sample_rate.max(1) as u64;
+            records.push(FlowRecord {
+                nic_id: registered.nic.nic_id,
+                network_id: registered.nic.network_id,
+                direction: id.direction,
+                src_addr: id.src_addr,
+                dst_addr: id.dst_addr,
+                src_port: id.src_port,
+                dst_port: id.dst_port,
+                protocol: id.protocol,
+                packets: packets * rate,
+                bytes: stats.bytes.saturating_sub(prev.bytes) * rate,
+                start: clock.wall(stats.first_seen_ns),
+                end: clock.wall(stats.last_seen_ns),
+            });
+        }
+
+        let idle =
+            clock.now_ns.saturating_sub(stats.last_seen_ns) >= idle_timeout.as_nanos() as u64;
+        if idle || registered.retired {
+            exported.remove(id);
+            expired.push(*id);
+        } else {
+            exported.insert(
+                *id,
+                Exported {
+                    first_seen_ns: stats.first_seen_ns,
+                    packets: stats.packets,
+                    bytes: stats.bytes,
+                },
+            );
+        }
+    }
+
+    // Forget flows the kernel evicted
+    let live: HashSet<_> = flows.iter().map(|(id, _)| *id).collect();
+    exported.retain(|id, _| live.contains(id));
+
+    (records, expired)
+}
+
+/// Flow log export task handle.
+pub struct FlowLogExporter {
+    nics: Arc<FlowNics>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl FlowLogExporter {
+    /// Start a new flow log export task.
+    pub async fn start(
+        ebpf: Arc<EbpfManager>,
+        audit: Arc<EbpfAuditLogger>,
+        config: FlowLogConfig,
+    ) -> std::io::Result<Self> {
+        let ipfix = match config.ipfix_collector {
+            Some(collector) => Some(IpfixExporter::connect(collector).await?),
+            None => None,
+        };
+        let nics = Arc::new(FlowNics::default());
+
+        info!(
+            interval = ?config.interval,
+            idle_timeout = ?config.idle_timeout,
+            ipfix_collector = ?config.ipfix_collector,
+            "Flow log export task started"
+        );
+
+        let task = tokio::spawn(export_loop(ebpf, Arc::clone(&nics), audit, ipfix, config));
+        Ok(Self { nics, task })
+    }
+
+    /// Registry of NICs to export flows for; hand this to the gRPC service.
+    pub fn nics(&self) -> Arc<FlowNics> {
+        Arc::clone(&self.nics)
+    }
+
+    /// Stop the export task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Flow log export task stopped");
+    }
+}
+
+/// Main export loop.
+async fn export_loop(
+    ebpf: Arc<EbpfManager>,
+    nics: Arc<FlowNics>,
+    audit: Arc<EbpfAuditLogger>,
+    mut ipfix: Option<IpfixExporter>,
+    config: FlowLogConfig,
+) {
+    let mut interval = time::interval(config.interval);
+    let mut exported = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let flows = match ebpf.read_flows().await {
+            Ok(flows) => flows,
+            Err(e) => {
+                warn!(error = %e, "Failed to read flow accounting map");
+                continue;
+            }
+        };
+        let keys: HashMap<FlowId, FlowKey> = flows
+            .iter()
+            .map(|(key, _)| (FlowId::from(key), *key))
+            .collect();
+        let flows: Vec<_> = flows
+            .iter()
+            .map(|(key, stats)| (FlowId::from(key), *stats))
+            .collect();
+
+        let registered = nics.snapshot().await;
+        let (records, expired) = collect(
+            &flows,
+            &registered,
+            &mut exported,
+            Clock::now(),
+            config.idle_timeout,
+        );
+
+        let expired: Vec<FlowKey> = expired
+            .iter()
+            .filter_map(|id| keys.get(id))
+            .copied()
+            .collect();
+        if let Err(e) = ebpf.remove_flows(&expired).await {
+            warn!(error = %e, "Failed to remove expired flows");
+        }
+        let retired: Vec<u32> = registered
+            .iter()
+            .filter(|(_, r)| r.retired)
+            .map(|(ifindex, _)| *ifindex)
+            .collect();
+        nics.drop_retired(&retired).await;
+
+        for record in &records {
+            audit.flow_record(
+                &record.nic_id.to_string(),
+                &record.network_id.to_string(),
+                &record.to_string(),
+            );
+        }
+        if let Some(ipfix) = ipfix.as_mut()
+            && let Err(e) = ipfix.send(&records).await
+        {
+            warn!(error = %e, "Failed to send IPFIX records");
+        }
+
+        debug!(
+            flows = flows.len(),
+            records = records.len(),
+            expired = expired.len(),
+            "Flow log export"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn flow_id(ifindex: u32) -> FlowId {
+        FlowId {
+            ifindex,
+            direction: FlowDirection::Egress,
+            src_addr: "10.0.0.2".parse().unwrap(),
+            dst_addr: "1.1.1.1".parse().unwrap(),
+            src_port: 40000,
+            dst_port: 443,
+            protocol: 6,
+        }
+    }
+
+    fn stats(first_seen_ns: u64, last_seen_ns: u64, packets: u64, bytes: u64) -> FlowStats {
+        FlowStats {
+            first_seen_ns,
+            last_seen_ns,
+            packets,
+            bytes,
+        }
+    }
+
+    fn nics(sample_rate: u32, retired: bool) -> HashMap<u32, Registered> {
+        HashMap::from([(
+            7,
+            Registered {
+                nic: FlowNic {
+                    nic_id: Uuid::from_u128(1),
+                    network_id: Uuid::from_u128(2),
+                    sample_rate,
+                },
+                retired,
+            },
+        )])
+    }
+
+    fn clock(now_ns: u64) -> Clock {
+        Clock {
+            now_ns,
+            now: DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    const IDLE: Duration = Duration::from_secs(120);
+
+    #[test]
+    fn test_records_are_deltas_since_last_export() {
+        let mut exported = HashMap::new();
+        let nics = nics(1, false);
+
+        let flows = [(flow_id(7), stats(10 * SECOND, 20 * SECOND, 5, 500))];
+        let (records, expired) = collect(&flows, &nics, &mut exported, clock(30 * SECOND), IDLE);
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].packets, records[0].bytes), (5, 500));
+        assert_eq!(records[0].nic_id, Uuid::from_u128(1));
+        assert!(expired.is_empty());
+
+        let flows = [(flow_id(7), stats(10 * SECOND, 50 * SECOND, 8, 900))];
+        let (records, _) = collect(&flows, &nics, &mut exported, clock(60 * SECOND), IDLE);
+        assert_eq!((records[0].packets, records[0].bytes), (3, 400));
+
+        // Nothing new: no record
+        let (records, _) = collect(&flows, &nics, &mut exported, clock(70 * SECOND), IDLE);
+        assert!(records.is_empty());
+    }
+
+    #[test]
+    fn test_counts_scaled_by_sample_rate() {
+        let flows = [(flow_id(7), stats(0, SECOND, 3, 300))];
+        let (records, _) = collect(
+            &flows,
+            &nics(100, false),
+            &mut HashMap::new(),
+            clock(2 * SECOND),
+            IDLE,
+        );
+        assert_eq!((records[0].packets, records[0].bytes), (300, 30_000));
+    }
+
+    #[test]
+    fn test_timestamps_mapped_to_wall_clock() {
+        let flows = [(flow_id(7), stats(10 * SECOND, 25 * SECOND, 1, 60))];
+        let clock = clock(30 * SECOND);
+        let (records, _) = collect(&flows, &nics(1, false), &mut HashMap::new(), clock, IDLE);
+        assert_eq!(records[0].start, clock.now - chrono::Duration::seconds(20));
+        assert_eq!(records[0].end, clock.now - chrono::Duration::seconds(5));
+    }
+
+    #[test]
+    fn test_idle_flows_expire_after_final_export() {
+        let mut exported = HashMap::new();
+        let flows = [(flow_id(7), stats(0, SECOND, 2, 200))];
+        let (records, expired) = collect(
+            &flows,
+            &nics(1, false),
+            &mut exported,
+            clock(SECOND + IDLE.as_nanos() as u64),
+            IDLE,
+        );
+        assert_eq!(records.len(), 1);
+        assert_eq!(expired, vec![flow_id(7)]);
+        assert!(exported.is_empty());
+    }
+
+    #[test]
+    fn test_retired_and_unknown_nics_expire() {
+        let flows = [
+            (flow_id(7), stats(0, SECOND, 2, 200)),
+            (flow_id(8), stats(0, SECOND, 2, 200)),
+        ];
+        let (records, expired) = collect(
+            &flows,
+            &nics(1, true),
+            &mut HashMap::new(),
+            clock(2 * SECOND),
+            IDLE,
+        );
+        // The retired NIC's last counts are still exported
+        assert_eq!(records.len(), 1);
+        assert_eq!(expired.len(), 2);
+    }
+
+    #[test]
+    fn test_restarted_flow_counts_from_zero() {
+        let mut exported = HashMap::new();
+        let nics = nics(1, false);
+
+        let flows = [(flow_id(7), stats(0, SECOND, 10, 1000))];
+        collect(&flows, &nics, &mut exported, clock(2 * SECOND), IDLE);
+
+        // Evicted and recreated by the kernel with fresh counters
+        let flows = [(flow_id(7), stats(3 * SECOND, 4 * SECOND, 2, 120))];
+        let (records, _) = collect(&flows, &nics, &mut exported, clock(5 * SECOND), IDLE);
+        assert_eq!((records[0].packets, records[0].bytes), (2, 120));
+    }
+
+    #[test]
+    fn test_record_display() {
+        let flows = [(flow_id(7), stats(0, 0, 1, 60))];
+        let (records, _) = collect(&flows, &nics(1, false), &mut HashMap::new(), clock(0), IDLE);
+        let line = records[0].to_string();
+        assert!(line.starts_with("egress tcp 10.0.0.2:40000 -> 1.1.1.1:443 packets=1 bytes=60"));
+    }
+}
@@ -3,26 +3,37 @@
 use super::proto::net_service_server::NetService;
 use super::proto::*;
 use super::storage::{
-    NetworkData, NicData, NicState, SecurityGroupData, SecurityGroupRuleData, Storage,
-    generate_mac_address,
+    NetworkData, NicData, NicState, OwnerRef, PortForwardData, STATE_VERSION, SecurityGroupData,
+    SecurityGroupRuleData, Storage, generate_mac_address, snapshot_len,
 };
 use super::validation::{
     ValidationError, allocate_ipv4_address, allocate_ipv6_address, parse_routed_prefixes,
-    validate_create_network, validate_create_nic, validate_create_security_group,
-    validate_security_group_rule,
+    reserved_ipv4, reserved_ipv6, validate_create_network, validate_create_nic,
+    validate_create_port_forward, validate_create_security_group, validate_flow_sample_rate,
+    validate_rule_window, validate_security_group_rule,
 };
 use crate::audit::EbpfAuditLogger;
-use crate::ebpf_loader::{ACTION_REDIRECT, EbpfManager, RouteEntry};
+use crate::ebpf_loader::{ACTION_REDIRECT, EbpfManager, FlowConfig, LocalNicInfo, RouteEntry};
+use crate::flowlog::{FlowNic, FlowNics};
 use crate::nat;
 use crate::proto_handler::{GATEWAY_MAC, ProtocolHandler};
+use crate::route_sync::OverlayPrefix;
+use crate::rule_window::WindowNics;
+use crate::security;
+use crate::security_audit::AuditNics;
 use crate::tap::{
     add_host_route_v4, add_host_route_v6, create_persistent_tap, delete_tap_interface,
     remove_host_route_v4, remove_host_route_v6, set_interface_mac, set_interface_up,
     tap_name_from_nic_id,
 };
 use chrono::Utc;
+use mvirt_common::events::{Event, EventBus};
+use mvirt_common::naming;
+use mvirt_common::netboot::validate_network_boot;
+use mvirt_common::rule_window::TimedRule;
+use mvirt_common::watch;
 use std::collections::HashMap;
-use std::net::IpAddr;
+use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tonic::{Request, Response, Status};
@@ -59,11 +70,72 @@ fn storage_err_to_status(e: super::storage::StorageError) -> Status {
         super::storage::StorageError::SecurityGroupHasNics(id) => {
             Status::failed_precondition(format!("Security group {} has attached NICs", id))
         }
+        super::storage::StorageError::PortForwardNotFound(id) => {
+            Status::not_found(format!("Port forward not found: {}", id))
+        }
+        super::storage::StorageError::PortForwardExists(endpoint) => {
+            Status::already_exists(format!("Port already forwarded: {}", endpoint))
+        }
         _ => Status::internal(e.to_string()),
     }
 }
 
+/// Add masquerade rules for a public network's subnets. Failures are
+/// logged; the network works without them, only not beyond the host.
+fn add_masquerade(network: &NetworkData) {
+    if network.is_public {
+        if let Some(subnet) = network.ipv4_subnet {
+            match nat::get_default_interface() {
+                Ok(out_iface) => {
+                    if let Err(e) = nat::add_masquerade_v4(subnet, &out_iface) {
+                        warn!(subnet = %subnet, error = %e, "Failed to add masquerade rule");
+                    }
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to get default interface for masquerade");
+                }
+            }
+        }
+        if let Some(prefix) = network.ipv6_prefix {
+            match nat::get_default_interface() {
+                Ok(out_iface) => {
+                    if let Err(e) = nat::add_masquerade_v6(prefix, &out_iface) {
+                        warn!(prefix = %prefix, error = %e, "Failed to add IPv6 masquerade rule");
+                    }
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to get default interface for IPv6 masquerade");
+                }
+            }
+        }
+    }
+}
+
 /// Convert validation error to gRPC status.
+/// Add the DNAT rule of a port forward to a NIC without an IPv4 address
+/// as a no-op.
+fn add_port_forward_rule(forward: &PortForwardData, nic: &NicData) -> nat::Result<()> {
+    match nic.ipv4_address {
+        Some(vm_ip) => nat::add_port_forward(
+            forward.protocol.as_str(),
+            forward.host_ip,
+            forward.host_port,
+            vm_ip,
+            forward.vm_port,
+        ),
+        None => Ok(()),
+    }
+}
+
+/// Remove the DNAT rule of a port forward.
+fn remove_port_forward_rule(forward: &PortForwardData) -> nat::Result<()> {
+    nat::remove_port_forward(
+        forward.protocol.as_str(),
+        forward.host_ip,
+        forward.host_port,
+    )
+}
+
 fn validation_err_to_status(e: ValidationError) -> Status {
     Status::invalid_argument(e.to_string())
 }
@@ -83,6 +155,13 @@ fn network_data_to_proto(data: &NetworkData, nic_count: u32) -> Network {
         created_at: data.created_at.to_rfc3339(),
         updated_at: data.updated_at.to_rfc3339(),
         is_public: data.is_public,
+        boot_server: data.boot_server.map(|a| a.to_string()).unwrap_or_default(),
+        boot_file: data.boot_file.clone().unwrap_or_default(),
+        flow_logs: data.flow_logs,
+        flow_sample_rate: data.flow_sample_rate,
+        proxy_neighbors: false,
+        conflicts: Vec::new(),
+        dns_forwarders: Vec::new(),
     }
 }
 
@@ -109,10 +188,57 @@ fn nic_data_to_proto(data: &NicData) -> Nic {
         state: data.state as i32,
         created_at: data.created_at.to_rfc3339(),
         updated_at: data.updated_at.to_rfc3339(),
+        owner: data.owner.as_ref().map(|o| OwnerReference {
+            kind: o.kind.clone(),
+            id: o.id.clone(),
+        }),
+        // TAP-backed NICs have no administrative link state
+        link_state: NicLinkState::Up as i32,
+        reactor_crashes: 0,
+        last_reactor_crash: String::new(),
+        delegated_ipv6_prefix: String::new(),
+        socket_access: None,
+        socket_error: String::new(),
+    }
+}
+
+/// Convert NicData to proto Allocation.
+fn nic_data_to_allocation(data: &NicData) -> Allocation {
+    Allocation {
+        network_id: data.network_id.to_string(),
+        nic_id: data.id.to_string(),
+        name: data.name.clone().unwrap_or_default(),
+        mac_address: data.mac_string(),
+        ipv4_address: data.ipv4_address.map(|a| a.to_string()).unwrap_or_default(),
+        ipv6_address: data.ipv6_address.map(|a| a.to_string()).unwrap_or_default(),
+        routed_ipv4_prefixes: data
+            .routed_ipv4_prefixes
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+        routed_ipv6_prefixes: data
+            .routed_ipv6_prefixes
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
     }
 }
 
 /// Convert SecurityGroupData to proto SecurityGroup.
+/// Convert PortForwardData to proto PortForward.
+fn port_forward_data_to_proto(data: &PortForwardData, vm_ip: Option<Ipv4Addr>) -> PortForward {
+    PortForward {
+        id: data.id.to_string(),
+        nic_id: data.nic_id.to_string(),
+        protocol: data.protocol as i32,
+        host_ip: data.host_ip.to_string(),
+        host_port: data.host_port as u32,
+        vm_ip: vm_ip.map(|a| a.to_string()).unwrap_or_default(),
+        vm_port: data.vm_port as u32,
+        created_at: data.created_at.to_rfc3339(),
+    }
+}
+
 fn security_group_data_to_proto(
     data: &SecurityGroupData,
     rules: Vec<SecurityGroupRule>,
@@ -126,6 +252,7 @@ fn security_group_data_to_proto(
         nic_count,
         created_at: data.created_at.to_rfc3339(),
         updated_at: data.updated_at.to_rfc3339(),
+        audit: data.audit,
     }
 }
 
@@ -142,6 +269,10 @@ fn security_group_rule_data_to_proto(data: &SecurityGroupRuleData) -> SecurityGr
         description: data.description.clone().unwrap_or_default(),
         created_at: data.created_at.to_rfc3339(),
         updated_at: data.updated_at.to_rfc3339(),
+        not_before: data.not_before.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        not_after: data.not_after.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        schedule: data.schedule.map(|s| s.to_string()).unwrap_or_default(),
+        active: data.is_active_at(Utc::now()),
     }
 }
 
@@ -165,6 +296,19 @@ pub struct EbpfNetServiceImpl {
     /// publisher emits current snapshot (None payload ⇒ deletion).
     nic_events: tokio::sync::broadcast::Sender<super::proto::NicEvent>,
     network_events: tokio::sync::broadcast::Sender<super::proto::NetworkEvent>,
+    /// Address allocations (NIC added / removed) for external DNS / IPAM.
+    allocation_events: tokio::sync::broadcast::Sender<AllocationEvent>,
+    /// NICs to export flow logs for; None when no exporter runs
+    flow_nics: Option<Arc<FlowNics>>,
+    /// NICs to report security audit drops for; None when no reporter runs
+    audit_nics: Option<Arc<AuditNics>>,
+    /// NICs whose time-limited rules the window timer re-evaluates; None
+    /// when no timer runs
+    window_nics: Option<Arc<WindowNics>>,
+    /// This host's overlay prefix; NICs only tunnel to other hosts when set
+    overlay_prefix: Option<OverlayPrefix>,
+    /// Tells the other daemons of the host about deleted NICs
+    host_events: Option<EventBus>,
 }
 
 impl EbpfNetServiceImpl {
@@ -177,6 +321,7 @@ impl EbpfNetServiceImpl {
     ) -> Self {
         let (nic_events, _) = tokio::sync::broadcast::channel(64);
         let (network_events, _) = tokio::sync::broadcast::channel(64);
+        let (allocation_events, _) = tokio::sync::broadcast::channel(64);
         Self {
             storage,
             ebpf,
@@ -185,7 +330,127 @@ impl EbpfNetServiceImpl {
             nics: Arc::new(RwLock::new(HashMap::new())),
             nic_events,
             network_events,
+            allocation_events,
+            flow_nics: None,
+            audit_nics: None,
+            window_nics: None,
+            overlay_prefix: None,
+            host_events: None,
+        }
+    }
+
+    /// Count flows for NICs in networks with flow logs enabled and report
+    /// them to the given exporter registry.
+    pub fn with_flow_logs(mut self, flow_nics: Arc<FlowNics>) -> Self {
+        self.flow_nics = Some(flow_nics);
+        self
+    }
+
+    /// Report the would-be drops of NICs in audit mode to the given
+    /// reporter registry.
+    pub fn with_security_audit(mut self, audit_nics: Arc<AuditNics>) -> Self {
+        self.audit_nics = Some(audit_nics);
+        self
+    }
+
+    /// Let the given timer enable and disable time-limited rules as their
+    /// windows open and close.
+    pub fn with_rule_windows(mut self, window_nics: Arc<WindowNics>) -> Self {
+        self.window_nics = Some(window_nics);
+        self
+    }
+
+    /// Tunnel traffic for NICs on other hosts from this host's overlay
+    /// prefix.
+    pub fn with_overlay_prefix(mut self, prefix: OverlayPrefix) -> Self {
+        self.overlay_prefix = Some(prefix);
+        self
+    }
+
+    /// Publish NIC deletions on the host's event bus, so mvirt-vmm
+    /// unplugs them from VMs.
+    pub fn with_host_events(mut self, bus: EventBus) -> Self {
+        self.host_events = Some(bus);
+        self
+    }
+
+    /// Program a NIC's security groups and rules into the TC programs.
+    async fn apply_security(&self, if_index: u32, nic_id: &Uuid) -> Result<(), Status> {
+        let groups = self
+            .storage
+            .list_security_groups_for_nic(nic_id)
+            .map_err(storage_err_to_status)?;
+        let rules = self
+            .storage
+            .get_all_rules_for_nic(nic_id)
+            .map_err(storage_err_to_status)?;
+
+        if let Some(audit_nics) = &self.audit_nics {
+            if groups.iter().any(|g| g.audit) {
+                audit_nics.register(if_index, *nic_id).await;
+            } else {
+                audit_nics.unregister(if_index).await;
+            }
+        }
+        security::apply(&self.ebpf, if_index, &groups, &rules)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to apply security groups: {}", e)))
+    }
+
+    /// Re-program the security groups of those NICs that are active here.
+    async fn reapply_security(&self, nic_ids: &[Uuid]) -> Result<(), Status> {
+        for nic_id in nic_ids {
+            let if_index = self.nics.read().await.get(nic_id).map(|m| m.if_index);
+            if let Some(if_index) = if_index {
+                self.apply_security(if_index, nic_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// IDs of the NICs a security group is attached to.
+    fn security_group_nic_ids(&self, sg_id: &Uuid) -> Result<Vec<Uuid>, Status> {
+        Ok(self
+            .storage
+            .list_nics_in_security_group(sg_id)
+            .map_err(storage_err_to_status)?
+            .into_iter()
+            .map(|nic| nic.id)
+            .collect())
+    }
+
+    /// Enable or disable flow accounting for a NIC per its network.
+    async fn apply_flow_logs(
+        &self,
+        if_index: u32,
+        nic: &NicData,
+        network: &NetworkData,
+    ) -> Result<(), Status> {
+        let Some(flow_nics) = &self.flow_nics else {
+            return Ok(());
+        };
+
+        if network.flow_logs {
+            // Register first so the exporter knows every flow it reads
+            flow_nics
+                .register(
+                    if_index,
+                    FlowNic {
+                        nic_id: nic.id,
+                        network_id: network.id,
+                        sample_rate: network.flow_sample_rate,
+                    },
+                )
+                .await;
+            self.ebpf
+                .set_flow_config(if_index, FlowConfig::new(network.flow_sample_rate))
+                .await
+                .map_err(|e| Status::internal(format!("Failed to enable flow logs: {}", e)))?;
+        } else {
+            let _ = self.ebpf.remove_flow_config(if_index).await;
+            flow_nics.retire(if_index).await;
         }
+        Ok(())
     }
 
     fn publish_nic(&self, nic_id: &str, nic: Option<super::proto::Nic>) {
@@ -196,6 +461,16 @@ impl EbpfNetServiceImpl {
         });
     }
 
+    fn publish_nic_deleted(&self, nic: &NicData) {
+        if let Some(bus) = &self.host_events {
+            bus.publish(Event::NicDeleted {
+                nic_id: nic.id.to_string(),
+                mac: nic.mac_string(),
+                socket: format!("tap:{}", nic.tap_name),
+            });
+        }
+    }
+
     fn publish_network(&self, network_id: &str, network: Option<super::proto::Network>) {
         let _ = self.network_events.send(super::proto::NetworkEvent {
             network_id: network_id.to_string(),
@@ -204,6 +479,14 @@ impl EbpfNetServiceImpl {
         });
     }
 
+    fn publish_allocation(&self, event_type: AllocationEventType, nic: &NicData) {
+        let _ = self.allocation_events.send(AllocationEvent {
+            r#type: event_type as i32,
+            timestamp: chrono::Utc::now().timestamp(),
+            allocation: Some(nic_data_to_allocation(nic)),
+        });
+    }
+
     /// Resolve network by ID or name.
     async fn resolve_network(&self, id: &str, name: &str) -> Result<NetworkData, Status> {
         if !id.is_empty() {
@@ -289,6 +572,21 @@ impl EbpfNetServiceImpl {
                 .map_err(|e| Status::internal(format!("Failed to add kernel route: {}", e)))?;
         }
 
+        // Source of tunnelled packets; the ifindex doubles as the NIC's
+        // function ID in the outer address
+        if let Some(prefix) = self.overlay_prefix {
+            self.ebpf
+                .set_local_nic_info(if_index, LocalNicInfo::new(if_index as u16, 0, prefix.0))
+                .await
+                .map_err(|e| Status::internal(format!("Failed to set tunnel source: {}", e)))?;
+        }
+
+        self.apply_flow_logs(if_index, nic, network).await?;
+        self.apply_security(if_index, &nic.id).await?;
+        if let Some(window_nics) = &self.window_nics {
+            window_nics.register(if_index, nic.id).await;
+        }
+
         // Store managed NIC
         let mut nics = self.nics.write().await;
         nics.insert(
@@ -339,6 +637,33 @@ impl EbpfNetServiceImpl {
             }
         }
 
+        if let (Some(if_idx), Some(flow_nics)) = (if_index, &self.flow_nics) {
+            let _ = self.ebpf.remove_flow_config(if_idx).await;
+            flow_nics.retire(if_idx).await;
+        }
+
+        if let Some(if_idx) = if_index {
+            if self.overlay_prefix.is_some() {
+                let _ = self.ebpf.remove_local_nic_info(if_idx).await;
+            }
+            let _ = security::clear(&self.ebpf, if_idx).await;
+            if let Some(audit_nics) = &self.audit_nics {
+                audit_nics.unregister(if_idx).await;
+            }
+            if let Some(window_nics) = &self.window_nics {
+                window_nics.unregister(if_idx).await;
+            }
+        }
+
+        // Stop forwarding to the NIC; its port forwards go with it
+        for forward in self
+            .storage
+            .list_port_forwards_for_nic(&nic.id)
+            .unwrap_or_default()
+        {
+            let _ = remove_port_forward_rule(&forward);
+        }
+
         // Delete the persistent TAP interface
         let _ = delete_tap_interface(&nic.tap_name).await;
 
@@ -414,6 +739,7 @@ impl EbpfNetServiceImpl {
                 }
             };
 
+            let nic = self.reallocate_reserved(nic, &network);
             match self.setup_nic(&nic, &network).await {
                 Ok(()) => recovered += 1,
                 Err(e) => {
@@ -424,8 +750,94 @@ impl EbpfNetServiceImpl {
         }
 
         info!(recovered, failed, "NIC recovery complete");
+
+        // nftables starts out empty; forward to the NICs' current addresses
+        self.apply_port_forwards();
         Ok(())
     }
+
+    /// Add the DNAT rules of all stored port forwards. Failures are logged.
+    fn apply_port_forwards(&self) {
+        let forwards = match self.storage.list_port_forwards() {
+            Ok(forwards) => forwards,
+            Err(e) => {
+                warn!(error = %e, "Failed to list port forwards");
+                return;
+            }
+        };
+        for forward in &forwards {
+            let Ok(Some(nic)) = self.storage.get_nic_by_id(&forward.nic_id) else {
+                continue;
+            };
+            if let Err(e) = add_port_forward_rule(forward, &nic) {
+                warn!(forward_id = %forward.id, error = %e, "Failed to add port forward rule");
+            }
+        }
+    }
+
+    /// Move a NIC off addresses that were allocated or requested before
+    /// they were reserved, so DHCP never hands them out again.
+    fn reallocate_reserved(&self, mut nic: NicData, network: &NetworkData) -> NicData {
+        let mut moved = Vec::new();
+        if let Some(old) = nic.ipv4_address
+            && let Some(reason) = reserved_ipv4(network.ipv4_subnet, old)
+        {
+            let new = match (network.ipv4_subnet, network.ipv4_gateway()) {
+                (Some(subnet), Some(gateway)) => self
+                    .storage
+                    .get_used_ipv4_addresses(&network.id)
+                    .ok()
+                    .and_then(|used| allocate_ipv4_address(subnet, &used, gateway)),
+                _ => None,
+            };
+            if new.is_some() {
+                nic.ipv4_address = new;
+            }
+            moved.push((IpAddr::V4(old), new.map(IpAddr::V4), reason));
+        }
+        if let Some(old) = nic.ipv6_address
+            && let Some(reason) = reserved_ipv6(network.ipv6_prefix, old)
+        {
+            let new = match (network.ipv6_prefix, network.ipv6_gateway()) {
+                (Some(prefix), Some(gateway)) => self
+                    .storage
+                    .get_used_ipv6_addresses(&network.id)
+                    .ok()
+                    .and_then(|used| allocate_ipv6_address(prefix, &used, gateway)),
+                _ => None,
+            };
+            if new.is_some() {
+                nic.ipv6_address = new;
+            }
+            moved.push((IpAddr::V6(old), new.map(IpAddr::V6), reason));
+        }
+        if moved.is_empty() {
+            return nic;
+        }
+
+        if let Err(e) =
+            self.storage
+                .update_nic_addresses(&nic.id, nic.ipv4_address, nic.ipv6_address)
+        {
+            warn!(nic_id = %nic.id, error = %e, "Failed to store reallocated addresses");
+        }
+        for (old, new, reason) in moved {
+            warn!(
+                nic_id = %nic.id,
+                old = %old,
+                new = ?new,
+                reason,
+                "Address reserved by IPAM was allocated"
+            );
+            self.audit.address_reallocated(
+                &nic.id.to_string(),
+                &old.to_string(),
+                new.map(|a| a.to_string()).as_deref(),
+                reason,
+            );
+        }
+        nic
+    }
 }
 
 #[tonic::async_trait]
@@ -441,6 +853,15 @@ impl NetService for EbpfNetServiceImpl {
         }))
     }
 
+    async fn reload(
+        &self,
+        _request: Request<ReloadRequest>,
+    ) -> Result<Response<ReloadResponse>, Status> {
+        Err(Status::unimplemented(
+            "Reload is only supported in mvirt-net",
+        ))
+    }
+
     // ========== Network Operations ==========
 
     async fn create_network(
@@ -451,6 +872,17 @@ impl NetService for EbpfNetServiceImpl {
 
         info!(name = %req.name, is_public = req.is_public, "CreateNetwork");
 
+        if req.proxy_neighbors {
+            return Err(Status::unimplemented(
+                "Neighbor proxying is only supported by mvirt-net",
+            ));
+        }
+        if !req.dns_forwarders.is_empty() {
+            return Err(Status::unimplemented(
+                "DNS forwarding is only supported by mvirt-net",
+            ));
+        }
+
         // Validate
         let (ipv4_subnet, ipv6_prefix, dns_servers) = validate_create_network(
             &req.name,
@@ -464,6 +896,11 @@ impl NetService for EbpfNetServiceImpl {
         )
         .map_err(validation_err_to_status)?;
 
+        let (boot_server, boot_file) = validate_network_boot(&req.boot_server, &req.boot_file)
+            .map_err(|e| validation_err_to_status(e.into()))?;
+        let flow_sample_rate =
+            validate_flow_sample_rate(req.flow_sample_rate).map_err(validation_err_to_status)?;
+
         // Parse NTP servers
         let ntp_servers: Vec<IpAddr> = req
             .ntp_servers
@@ -489,6 +926,10 @@ impl NetService for EbpfNetServiceImpl {
             dns_servers,
             ntp_servers,
             is_public: req.is_public,
+            boot_server,
+            boot_file,
+            flow_logs: req.flow_logs,
+            flow_sample_rate,
             created_at: now,
             updated_at: now,
         };
@@ -498,33 +939,7 @@ impl NetService for EbpfNetServiceImpl {
             .create_network(&network)
             .map_err(storage_err_to_status)?;
 
-        // Add masquerade rule for public networks
-        if network.is_public {
-            if let Some(subnet) = network.ipv4_subnet {
-                match nat::get_default_interface() {
-                    Ok(out_iface) => {
-                        if let Err(e) = nat::add_masquerade_v4(subnet, &out_iface) {
-                            warn!(subnet = %subnet, error = %e, "Failed to add masquerade rule");
-                        }
-                    }
-                    Err(e) => {
-                        warn!(error = %e, "Failed to get default interface for masquerade");
-                    }
-                }
-            }
-            if let Some(prefix) = network.ipv6_prefix {
-                match nat::get_default_interface() {
-                    Ok(out_iface) => {
-                        if let Err(e) = nat::add_masquerade_v6(prefix, &out_iface) {
-                            warn!(prefix = %prefix, error = %e, "Failed to add IPv6 masquerade rule");
-                        }
-                    }
-                    Err(e) => {
-                        warn!(error = %e, "Failed to get default interface for IPv6 masquerade");
-                    }
-                }
-            }
-        }
+        add_masquerade(&network);
 
         info!(id = %network.id, name = %network.name, "Network created");
         self.audit
@@ -583,6 +998,23 @@ impl NetService for EbpfNetServiceImpl {
         let uuid = Uuid::parse_str(&req.id)
             .map_err(|_| Status::invalid_argument(format!("Invalid network ID: {}", req.id)))?;
 
+        if req.proxy_neighbors == Some(true) {
+            return Err(Status::unimplemented(
+                "Neighbor proxying is only supported by mvirt-net",
+            ));
+        }
+        if !req.dns_forwarders.is_empty() {
+            return Err(Status::unimplemented(
+                "DNS forwarding is only supported by mvirt-net",
+            ));
+        }
+
+        let flow_sample_rate = req
+            .flow_sample_rate
+            .map(validate_flow_sample_rate)
+            .transpose()
+            .map_err(validation_err_to_status)?;
+
         // Parse servers
         let dns_servers: Vec<IpAddr> = req
             .dns_servers
@@ -602,12 +1034,44 @@ impl NetService for EbpfNetServiceImpl {
             .update_network(&uuid, &dns_servers, &ntp_servers)
             .map_err(storage_err_to_status)?;
 
+        if req.flow_logs.is_some() || flow_sample_rate.is_some() {
+            let current = self
+                .storage
+                .get_network_by_id(&uuid)
+                .map_err(storage_err_to_status)?
+                .ok_or_else(|| Status::not_found("Network not found"))?;
+            self.storage
+                .update_network_flow_logs(
+                    &uuid,
+                    req.flow_logs.unwrap_or(current.flow_logs),
+                    flow_sample_rate.unwrap_or(current.flow_sample_rate),
+                )
+                .map_err(storage_err_to_status)?;
+        }
+
         let network = self
             .storage
             .get_network_by_id(&uuid)
             .map_err(storage_err_to_status)?
             .ok_or_else(|| Status::not_found("Network not found"))?;
 
+        // Apply flow log changes to the network's running NICs
+        if req.flow_logs.is_some() || flow_sample_rate.is_some() {
+            let nics = self
+                .storage
+                .list_nics_in_network(&uuid)
+                .map_err(storage_err_to_status)?;
+            let managed = self.nics.read().await;
+            let active: Vec<_> = nics
+                .iter()
+                .filter_map(|nic| managed.get(&nic.id).map(|m| (m.if_index, nic)))
+                .collect();
+            drop(managed);
+            for (if_index, nic) in active {
+                self.apply_flow_logs(if_index, nic, &network).await?;
+            }
+        }
+
         let nic_count = self
             .storage
             .count_nics_in_network(&uuid)
@@ -656,6 +1120,9 @@ impl NetService for EbpfNetServiceImpl {
 
             for nic in &nics {
                 self.teardown_nic(nic).await?;
+                self.publish_nic(&nic.id.to_string(), None);
+                self.publish_allocation(AllocationEventType::Removed, nic);
+                self.publish_nic_deleted(nic);
             }
         }
 
@@ -698,6 +1165,17 @@ impl NetService for EbpfNetServiceImpl {
         let req = request.into_inner();
 
         info!(network_id = %req.network_id, "CreateNic");
+        naming::validate_optional_name("NIC", Some(&req.name))?;
+        if req.delegated_ipv6_prefix_len != 0 {
+            return Err(Status::unimplemented(
+                "IPv6 prefix delegation is not supported in mvirt-ebpf",
+            ));
+        }
+        if req.socket_access.is_some() {
+            return Err(Status::unimplemented(
+                "vhost-user socket access is not supported in mvirt-ebpf",
+            ));
+        }
 
         // Resolve network
         let network = self.resolve_network(&req.network_id, "").await?;
@@ -796,6 +1274,10 @@ impl NetService for EbpfNetServiceImpl {
             state: NicState::Created,
             created_at: now,
             updated_at: now,
+            owner: req.owner.map(|o| OwnerRef {
+                kind: o.kind,
+                id: o.id,
+            }),
         };
 
         // Store first (to ensure DB consistency)
@@ -829,6 +1311,7 @@ impl NetService for EbpfNetServiceImpl {
 
         let proto = nic_data_to_proto(&nic);
         self.publish_nic(&nic.id.to_string(), Some(proto.clone()));
+        self.publish_allocation(AllocationEventType::Added, &nic);
         Ok(Response::new(proto))
     }
 
@@ -886,6 +1369,12 @@ impl NetService for EbpfNetServiceImpl {
         let uuid = Uuid::parse_str(&req.id)
             .map_err(|_| Status::invalid_argument(format!("Invalid NIC ID: {}", req.id)))?;
 
+        if req.link_state == NicLinkState::Down as i32 {
+            return Err(Status::unimplemented(
+                "Setting NIC links down is only supported by mvirt-net",
+            ));
+        }
+
         // Parse routed prefixes
         let (routed_v4, routed_v6) =
             parse_routed_prefixes(&req.routed_ipv4_prefixes, &req.routed_ipv6_prefixes)
@@ -906,6 +1395,15 @@ impl NetService for EbpfNetServiceImpl {
         Ok(Response::new(nic_data_to_proto(&nic)))
     }
 
+    async fn set_nic_link_state(
+        &self,
+        _request: Request<SetNicLinkStateRequest>,
+    ) -> Result<Response<Nic>, Status> {
+        Err(Status::unimplemented(
+            "Setting NIC links down is only supported by mvirt-net",
+        ))
+    }
+
     async fn delete_nic(
         &self,
         request: Request<DeleteNicRequest>,
@@ -928,6 +1426,8 @@ impl NetService for EbpfNetServiceImpl {
             self.audit
                 .nic_deleted(&nic.id.to_string(), &nic.network_id.to_string());
             self.publish_nic(&nic.id.to_string(), None);
+            self.publish_allocation(AllocationEventType::Removed, nic);
+            self.publish_nic_deleted(nic);
         }
 
         // Delete from DB
@@ -982,6 +1482,320 @@ impl NetService for EbpfNetServiceImpl {
         }))
     }
 
+    // ========== Static Route Operations (not supported in mvirt-ebpf) ==========
+    //
+    // The eBPF egress route maps are exact-match on host addresses, so CIDR
+    // static routes cannot be expressed there yet.
+
+    async fn add_route(
+        &self,
+        _request: Request<AddRouteRequest>,
+    ) -> Result<Response<Route>, Status> {
+        Err(Status::unimplemented(
+            "Static routes are only supported in mvirt-net",
+        ))
+    }
+
+    async fn remove_route(
+        &self,
+        _request: Request<RemoveRouteRequest>,
+    ) -> Result<Response<RemoveRouteResponse>, Status> {
+        Err(Status::unimplemented(
+            "Static routes are only supported in mvirt-net",
+        ))
+    }
+
+    async fn list_routes(
+        &self,
+        _request: Request<ListRoutesRequest>,
+    ) -> Result<Response<ListRoutesResponse>, Status> {
+        Err(Status::unimplemented(
+            "Static routes are only supported in mvirt-net",
+        ))
+    }
+
+    // ========== DHCP Lease Operations (not supported in mvirt-ebpf) ==========
+
+    async fn create_lease(
+        &self,
+        _request: Request<CreateLeaseRequest>,
+    ) -> Result<Response<Lease>, Status> {
+        Err(Status::unimplemented(
+            "DHCP leases are only supported in mvirt-net",
+        ))
+    }
+
+    async fn list_leases(
+        &self,
+        _request: Request<ListLeasesRequest>,
+    ) -> Result<Response<ListLeasesResponse>, Status> {
+        Err(Status::unimplemented(
+            "DHCP leases are only supported in mvirt-net",
+        ))
+    }
+
+    async fn delete_lease(
+        &self,
+        _request: Request<DeleteLeaseRequest>,
+    ) -> Result<Response<DeleteLeaseResponse>, Status> {
+        Err(Status::unimplemented(
+            "DHCP leases are only supported in mvirt-net",
+        ))
+    }
+
+    // ========== Network Peering Operations (not supported in mvirt-ebpf) ==========
+
+    async fn create_peering(
+        &self,
+        _request: Request<CreatePeeringRequest>,
+    ) -> Result<Response<Peering>, Status> {
+        Err(Status::unimplemented(
+            "Network peerings are only supported in mvirt-net",
+        ))
+    }
+
+    async fn list_peerings(
+        &self,
+        _request: Request<ListPeeringsRequest>,
+    ) -> Result<Response<ListPeeringsResponse>, Status> {
+        Err(Status::unimplemented(
+            "Network peerings are only supported in mvirt-net",
+        ))
+    }
+
+    async fn delete_peering(
+        &self,
+        _request: Request<DeletePeeringRequest>,
+    ) -> Result<Response<DeletePeeringResponse>, Status> {
+        Err(Status::unimplemented(
+            "Network peerings are only supported in mvirt-net",
+        ))
+    }
+
+    async fn connect_networks(
+        &self,
+        _request: Request<ConnectNetworksRequest>,
+    ) -> Result<Response<Peering>, Status> {
+        Err(Status::unimplemented(
+            "Network peerings are only supported in mvirt-net",
+        ))
+    }
+
+    async fn disconnect_networks(
+        &self,
+        _request: Request<DisconnectNetworksRequest>,
+    ) -> Result<Response<DisconnectNetworksResponse>, Status> {
+        Err(Status::unimplemented(
+            "Network peerings are only supported in mvirt-net",
+        ))
+    }
+
+    async fn get_isolation_stats(
+        &self,
+        _request: Request<GetIsolationStatsRequest>,
+    ) -> Result<Response<IsolationStats>, Status> {
+        Err(Status::unimplemented(
+            "Strict network isolation is only enforced by mvirt-net",
+        ))
+    }
+
+    async fn set_overlay_peers(
+        &self,
+        _request: Request<SetOverlayPeersRequest>,
+    ) -> Result<Response<SetOverlayPeersResponse>, Status> {
+        Err(Status::unimplemented(
+            "Overlays are only supported in mvirt-net",
+        ))
+    }
+
+    // ========== Port Forward Operations ==========
+
+    async fn create_port_forward(
+        &self,
+        request: Request<CreatePortForwardRequest>,
+    ) -> Result<Response<PortForward>, Status> {
+        let req = request.into_inner();
+
+        info!(
+            nic_id = %req.nic_id,
+            host_ip = %req.host_ip,
+            host_port = req.host_port,
+            vm_port = req.vm_port,
+            "CreatePortForward"
+        );
+
+        if req.nic_id.is_empty() {
+            return Err(Status::invalid_argument("NIC ID required"));
+        }
+        let nic = self.resolve_nic(&req.nic_id, "").await?;
+        let (protocol, host_ip, host_port, vm_port) = validate_create_port_forward(
+            &nic,
+            req.protocol,
+            &req.host_ip,
+            req.host_port,
+            req.vm_port,
+            &self.storage,
+        )
+        .map_err(validation_err_to_status)?;
+
+        let forward = PortForwardData {
+            id: Uuid::new_v4(),
+            nic_id: nic.id,
+            protocol,
+            host_ip,
+            host_port,
+            vm_port,
+            created_at: Utc::now(),
+        };
+
+        self.storage
+            .create_port_forward(&forward)
+            .map_err(storage_err_to_status)?;
+
+        if let Err(e) = add_port_forward_rule(&forward, &nic) {
+            let _ = self.storage.delete_port_forward(&forward.id);
+            return Err(Status::internal(format!(
+                "Failed to add port forward rule: {}",
+                e
+            )));
+        }
+
+        let endpoint = forward.host_endpoint();
+        let vm = format!(
+            "{}:{}",
+            nic.ipv4_address.map(|a| a.to_string()).unwrap_or_default(),
+            forward.vm_port
+        );
+        info!(id = %forward.id, nic_id = %nic.id, host = %endpoint, vm = %vm, "Port forward created");
+        self.audit.port_forward_created(
+            &forward.id.to_string(),
+            &nic.id.to_string(),
+            &endpoint,
+            &vm,
+        );
+
+        Ok(Response::new(port_forward_data_to_proto(
+            &forward,
+            nic.ipv4_address,
+        )))
+    }
+
+    async fn list_port_forwards(
+        &self,
+        request: Request<ListPortForwardsRequest>,
+    ) -> Result<Response<ListPortForwardsResponse>, Status> {
+        let req = request.into_inner();
+
+        let forwards = if req.nic_id.is_empty() {
+            self.storage.list_port_forwards()
+        } else {
+            let nic = self.resolve_nic(&req.nic_id, "").await?;
+            self.storage.list_port_forwards_for_nic(&nic.id)
+        }
+        .map_err(storage_err_to_status)?;
+
+        let mut port_forwards = Vec::with_capacity(forwards.len());
+        for forward in &forwards {
+            let vm_ip = self
+                .storage
+                .get_nic_by_id(&forward.nic_id)
+                .map_err(storage_err_to_status)?
+                .and_then(|nic| nic.ipv4_address);
+            port_forwards.push(port_forward_data_to_proto(forward, vm_ip));
+        }
+
+        Ok(Response::new(ListPortForwardsResponse { port_forwards }))
+    }
+
+    async fn delete_port_forward(
+        &self,
+        request: Request<DeletePortForwardRequest>,
+    ) -> Result<Response<DeletePortForwardResponse>, Status> {
+        let req = request.into_inner();
+
+        let uuid = Uuid::parse_str(&req.id).map_err(|_| {
+            Status::invalid_argument(format!("Invalid port forward ID: {}", req.id))
+        })?;
+
+        let forward = self
+            .storage
+            .get_port_forward_by_id(&uuid)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Port forward not found: {}", req.id)))?;
+
+        if let Err(e) = remove_port_forward_rule(&forward) {
+            warn!(id = %uuid, error = %e, "Failed to remove port forward rule");
+        }
+
+        let deleted = self
+            .storage
+            .delete_port_forward(&uuid)
+            .map_err(storage_err_to_status)?;
+
+        let endpoint = forward.host_endpoint();
+        info!(id = %uuid, nic_id = %forward.nic_id, host = %endpoint, "Port forward deleted");
+        self.audit
+            .port_forward_deleted(&uuid.to_string(), &forward.nic_id.to_string(), &endpoint);
+
+        Ok(Response::new(DeletePortForwardResponse { deleted }))
+    }
+
+    // ========== Floating IP Operations (not supported in mvirt-ebpf) ==========
+
+    async fn create_floating_ip(
+        &self,
+        _request: Request<CreateFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        Err(Status::unimplemented(
+            "Floating IPs are only supported in mvirt-net",
+        ))
+    }
+
+    async fn get_floating_ip(
+        &self,
+        _request: Request<GetFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        Err(Status::unimplemented(
+            "Floating IPs are only supported in mvirt-net",
+        ))
+    }
+
+    async fn list_floating_ips(
+        &self,
+        _request: Request<ListFloatingIpsRequest>,
+    ) -> Result<Response<ListFloatingIpsResponse>, Status> {
+        Err(Status::unimplemented(
+            "Floating IPs are only supported in mvirt-net",
+        ))
+    }
+
+    async fn associate_floating_ip(
+        &self,
+        _request: Request<AssociateFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        Err(Status::unimplemented(
+            "Floating IPs are only supported in mvirt-net",
+        ))
+    }
+
+    async fn disassociate_floating_ip(
+        &self,
+        _request: Request<DisassociateFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        Err(Status::unimplemented(
+            "Floating IPs are only supported in mvirt-net",
+        ))
+    }
+
+    async fn delete_floating_ip(
+        &self,
+        _request: Request<DeleteFloatingIpRequest>,
+    ) -> Result<Response<DeleteFloatingIpResponse>, Status> {
+        Err(Status::unimplemented(
+            "Floating IPs are only supported in mvirt-net",
+        ))
+    }
+
     // ========== Security Group Operations ==========
 
     async fn create_security_group(
@@ -1004,6 +1818,7 @@ impl NetService for EbpfNetServiceImpl {
             } else {
                 Some(req.description)
             },
+            audit: req.audit,
             created_at: now,
             updated_at: now,
         };
@@ -1121,6 +1936,9 @@ impl NetService for EbpfNetServiceImpl {
             .get_security_group_by_id(&uuid)
             .map_err(storage_err_to_status)?;
 
+        // Collect the NICs to re-program before detaching them
+        let nic_ids = self.security_group_nic_ids(&uuid)?;
+
         // Detach from all NICs if force
         let nics_detached = if req.force && nic_count > 0 {
             self.storage
@@ -1135,6 +1953,7 @@ impl NetService for EbpfNetServiceImpl {
             .storage
             .delete_security_group(&uuid)
             .map_err(storage_err_to_status)?;
+        self.reapply_security(&nic_ids).await?;
 
         if let Some(s) = sg {
             self.audit
@@ -1170,6 +1989,9 @@ impl NetService for EbpfNetServiceImpl {
                 &req.cidr,
             )
             .map_err(validation_err_to_status)?;
+        let (not_before, not_after, schedule) =
+            validate_rule_window(&req.not_before, &req.not_after, &req.schedule)
+                .map_err(validation_err_to_status)?;
 
         let now = Utc::now();
         let rule = SecurityGroupRuleData {
@@ -1189,6 +2011,9 @@ impl NetService for EbpfNetServiceImpl {
             } else {
                 Some(req.description)
             },
+            not_before,
+            not_after,
+            schedule,
             created_at: now,
             updated_at: now,
         };
@@ -1197,6 +2022,8 @@ impl NetService for EbpfNetServiceImpl {
         self.storage
             .create_security_group_rule(&rule)
             .map_err(storage_err_to_status)?;
+        self.reapply_security(&self.security_group_nic_ids(&sg.id)?)
+            .await?;
 
         info!(
             id = %rule.id,
@@ -1232,6 +2059,8 @@ impl NetService for EbpfNetServiceImpl {
             .map_err(storage_err_to_status)?;
 
         if let Some(r) = rule {
+            self.reapply_security(&self.security_group_nic_ids(&r.security_group_id)?)
+                .await?;
             self.audit
                 .security_group_rule_removed(&r.id.to_string(), &r.security_group_id.to_string());
         }
@@ -1266,6 +2095,7 @@ impl NetService for EbpfNetServiceImpl {
             .map_err(storage_err_to_status)?;
 
         if attached {
+            self.reapply_security(&[nic.id]).await?;
             info!(
                 nic_id = %nic.id,
                 security_group_id = %sg.id,
@@ -1299,6 +2129,7 @@ impl NetService for EbpfNetServiceImpl {
             .map_err(storage_err_to_status)?;
 
         if detached {
+            self.reapply_security(&[nic.id]).await?;
             info!(
                 nic_id = %nic.id,
                 security_group_id = %sg.id,
@@ -1311,6 +2142,147 @@ impl NetService for EbpfNetServiceImpl {
         Ok(Response::new(DetachSecurityGroupResponse { detached }))
     }
 
+    async fn set_security_group_audit(
+        &self,
+        request: Request<SetSecurityGroupAuditRequest>,
+    ) -> Result<Response<SecurityGroup>, Status> {
+        let req = request.into_inner();
+
+        info!(
+            security_group_id = %req.security_group_id,
+            audit = req.audit,
+            "SetSecurityGroupAudit"
+        );
+
+        let sg = self
+            .resolve_security_group(&req.security_group_id, "")
+            .await?;
+
+        self.storage
+            .set_security_group_audit(&sg.id, req.audit)
+            .map_err(storage_err_to_status)?;
+        let nic_ids = self.security_group_nic_ids(&sg.id)?;
+        self.reapply_security(&nic_ids).await?;
+
+        info!(
+            security_group_id = %sg.id,
+            audit = req.audit,
+            nics = nic_ids.len(),
+            "Security group audit mode set"
+        );
+        self.audit
+            .security_group_audit_set(&sg.id.to_string(), &sg.name, req.audit);
+
+        let sg = self
+            .storage
+            .get_security_group_by_id(&sg.id)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Security group not found: {}", sg.id)))?;
+        let rules = self
+            .storage
+            .list_rules_for_security_group(&sg.id)
+            .map_err(storage_err_to_status)?;
+        let proto_rules: Vec<SecurityGroupRule> = rules
+            .iter()
+            .map(security_group_rule_data_to_proto)
+            .collect();
+
+        Ok(Response::new(security_group_data_to_proto(
+            &sg,
+            proto_rules,
+            nic_ids.len() as u32,
+        )))
+    }
+
+    // Host migration
+
+    async fn export_state(
+        &self,
+        _request: Request<ExportStateRequest>,
+    ) -> Result<Response<StateSnapshot>, Status> {
+        let state = self.storage.export_state().map_err(storage_err_to_status)?;
+        let data = serde_json::to_vec(&state).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(StateSnapshot {
+            version: STATE_VERSION,
+            data,
+        }))
+    }
+
+    async fn import_state(
+        &self,
+        request: Request<ImportStateRequest>,
+    ) -> Result<Response<ImportStateResponse>, Status> {
+        let req = request.into_inner();
+        let snapshot = req
+            .snapshot
+            .ok_or_else(|| Status::invalid_argument("snapshot is required"))?;
+        if snapshot.version != STATE_VERSION {
+            return Err(Status::invalid_argument(format!(
+                "Unsupported state snapshot version {} (expected {})",
+                snapshot.version, STATE_VERSION
+            )));
+        }
+        let state: serde_json::Value = serde_json::from_slice(&snapshot.data)
+            .map_err(|e| Status::invalid_argument(format!("Invalid state snapshot: {}", e)))?;
+
+        if !self.storage.is_empty().map_err(storage_err_to_status)? {
+            return Err(Status::failed_precondition(
+                "Networks already exist on this host",
+            ));
+        }
+
+        // Everything a NIC needs on the host is created here, so a dry run
+        // has nothing to check.
+        if req.dry_run {
+            return Ok(Response::new(ImportStateResponse {
+                records: snapshot_len(&state) as u32,
+                missing: vec![],
+            }));
+        }
+
+        let records = self
+            .storage
+            .import_state(&state)
+            .map_err(storage_err_to_status)?;
+        let networks = self
+            .storage
+            .list_networks()
+            .map_err(storage_err_to_status)?;
+        let nics = self.storage.list_nics().map_err(storage_err_to_status)?;
+
+        for network in &networks {
+            add_masquerade(network);
+        }
+        self.recover_nics().await?;
+
+        let mut missing = Vec::new();
+        for nic in &nics {
+            if std::path::Path::new("/sys/class/net")
+                .join(&nic.tap_name)
+                .exists()
+            {
+                self.publish_nic(&nic.id.to_string(), Some(nic_data_to_proto(nic)));
+                self.publish_allocation(AllocationEventType::Added, nic);
+            } else {
+                missing.push(format!("NIC {}: TAP {}", nic.id, nic.tap_name));
+            }
+        }
+        for network in &networks {
+            let nic_count = nics.iter().filter(|n| n.network_id == network.id).count();
+            self.publish_network(
+                &network.id.to_string(),
+                Some(network_data_to_proto(network, nic_count as u32)),
+            );
+        }
+
+        info!(records, missing = missing.len(), "Imported network state");
+        self.audit.state_imported(networks.len(), nics.len());
+        Ok(Response::new(ImportStateResponse {
+            records: records as u32,
+            missing,
+        }))
+    }
+
     type WatchNicsStream =
         tokio_stream::wrappers::ReceiverStream<Result<super::proto::NicEvent, Status>>;
 
@@ -1364,4 +2336,129 @@ impl NetService for EbpfNetServiceImpl {
             out_rx,
         )))
     }
+
+    type WatchAllocationsStream =
+        tokio_stream::wrappers::ReceiverStream<Result<AllocationEvent, Status>>;
+
+    async fn watch_allocations(
+        &self,
+        request: Request<WatchAllocationsRequest>,
+    ) -> Result<Response<Self::WatchAllocationsStream>, Status> {
+        let req = request.into_inner();
+
+        let network_filter = if req.network_id.is_empty() {
+            None
+        } else if Uuid::parse_str(&req.network_id).is_ok() {
+            Some(self.resolve_network(&req.network_id, "").await?.id)
+        } else {
+            Some(self.resolve_network("", &req.network_id).await?.id)
+        };
+
+        // Subscribe before the snapshot so nothing slips through in between
+        let rx = self.allocation_events.subscribe();
+
+        let existing = if req.include_existing {
+            match &network_filter {
+                Some(network_id) => self.storage.list_nics_in_network(network_id),
+                None => self.storage.list_nics(),
+            }
+            .map_err(storage_err_to_status)?
+        } else {
+            Vec::new()
+        };
+
+        let now = chrono::Utc::now().timestamp();
+        let existing = existing
+            .iter()
+            .map(|nic| AllocationEvent {
+                r#type: AllocationEventType::Added as i32,
+                timestamp: now,
+                allocation: Some(nic_data_to_allocation(nic)),
+            })
+            .collect();
+        Ok(Response::new(watch::watch_stream(
+            "Allocation",
+            existing,
+            rx,
+            network_filter.map(|id| id.to_string()),
+            |ev: &AllocationEvent| ev.allocation.as_ref().map(|a| a.network_id.as_str()),
+        )))
+    }
+
+    // Packets are forwarded by TC programs in the kernel; there is no
+    // userspace reactor to time.
+
+    async fn get_latency_stats(
+        &self,
+        _request: Request<GetLatencyStatsRequest>,
+    ) -> Result<Response<LatencyStats>, Status> {
+        Err(Status::unimplemented(
+            "Latency histograms are only recorded by mvirt-net",
+        ))
+    }
+
+    async fn reset_latency_stats(
+        &self,
+        _request: Request<ResetLatencyStatsRequest>,
+    ) -> Result<Response<ResetLatencyStatsResponse>, Status> {
+        Err(Status::unimplemented(
+            "Latency histograms are only recorded by mvirt-net",
+        ))
+    }
+
+    async fn get_nic_stats(
+        &self,
+        _request: Request<GetNicStatsRequest>,
+    ) -> Result<Response<NicStats>, Status> {
+        Err(Status::unimplemented(
+            "Per-NIC traffic counters are only kept by mvirt-net",
+        ))
+    }
+
+    async fn get_rx_pool_stats(
+        &self,
+        _request: Request<GetRxPoolStatsRequest>,
+    ) -> Result<Response<RxPoolStats>, Status> {
+        Err(Status::unimplemented(
+            "RX buffer pools only exist in mvirt-net",
+        ))
+    }
+
+    async fn get_huge_page_usage(
+        &self,
+        _request: Request<GetHugePageUsageRequest>,
+    ) -> Result<Response<HugePageUsage>, Status> {
+        Err(Status::unimplemented(
+            "mvirt-ebpf has no userspace packet buffers",
+        ))
+    }
+
+    async fn start_capture(
+        &self,
+        _request: Request<StartCaptureRequest>,
+    ) -> Result<Response<CaptureInfo>, Status> {
+        Err(Status::unimplemented(
+            "Packet capture is only available in mvirt-net",
+        ))
+    }
+
+    type StreamCaptureStream = tokio_stream::wrappers::ReceiverStream<Result<CaptureChunk, Status>>;
+
+    async fn stream_capture(
+        &self,
+        _request: Request<StreamCaptureRequest>,
+    ) -> Result<Response<Self::StreamCaptureStream>, Status> {
+        Err(Status::unimplemented(
+            "Packet capture is only available in mvirt-net",
+        ))
+    }
+
+    async fn stop_capture(
+        &self,
+        _request: Request<StopCaptureRequest>,
+    ) -> Result<Response<CaptureStats>, Status> {
+        Err(Status::unimplemented(
+            "Packet capture is only available in mvirt-net",
+        ))
+    }
 }
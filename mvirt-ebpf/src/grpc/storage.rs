@@ -2,6 +2,7 @@
 
 use chrono::{DateTime, Utc};
 use ipnet::{Ipv4Net, Ipv6Net};
+use mvirt_common::rule_window::RuleSchedule;
 use refinery::embed_migrations;
 use rusqlite::{Connection, OptionalExtension, Row, params};
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
@@ -47,6 +48,12 @@ pub enum StorageError {
 
     #[error("Security group has attached NICs: {0}")]
     SecurityGroupHasNics(String),
+
+    #[error("Port forward not found: {0}")]
+    PortForwardNotFound(String),
+
+    #[error("Port already forwarded: {0}")]
+    PortForwardExists(String),
 }
 
 pub type Result<T> = std::result::Result<T, StorageError>;
@@ -91,6 +98,14 @@ pub struct NetworkData {
     pub dns_servers: Vec<IpAddr>,
     pub ntp_servers: Vec<IpAddr>,
     pub is_public: bool,
+    /// TFTP server announced to PXE clients (DHCP siaddr).
+    pub boot_server: Option<Ipv4Addr>,
+    /// Boot file name (TFTP) or URL (UEFI HTTP boot) announced via DHCP.
+    pub boot_file: Option<String>,
+    /// Export flow records for NICs in this network.
+    pub flow_logs: bool,
+    /// Count 1 in this many packets for flow logs (1 = every packet).
+    pub flow_sample_rate: u32,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -128,6 +143,15 @@ pub struct NicData {
     pub state: NicState,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Pod or VM whose deletion cascades to this NIC.
+    pub owner: Option<OwnerRef>,
+}
+
+/// Reference to the object owning a NIC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerRef {
+    pub kind: String,
+    pub id: String,
 }
 
 impl NicData {
@@ -261,6 +285,8 @@ pub struct SecurityGroupData {
     pub id: Uuid,
     pub name: String,
     pub description: Option<String>,
+    /// Report packets the rules would drop instead of dropping them
+    pub audit: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -276,6 +302,12 @@ pub struct SecurityGroupRuleData {
     pub port_end: Option<u16>,
     pub cidr: Option<String>,
     pub description: Option<String>,
+    /// Rule applies from this time on
+    pub not_before: Option<DateTime<Utc>>,
+    /// Rule expires at this time
+    pub not_after: Option<DateTime<Utc>>,
+    /// Weekly times the rule applies, within its validity window
+    pub schedule: Option<RuleSchedule>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -288,6 +320,32 @@ pub struct NicSecurityGroupData {
     pub created_at: DateTime<Utc>,
 }
 
+/// Port forward stored in the database: `protocol` traffic for
+/// `host_ip:host_port` goes to `vm_port` of the NIC's IPv4 address.
+#[derive(Debug, Clone)]
+pub struct PortForwardData {
+    pub id: Uuid,
+    pub nic_id: Uuid,
+    /// TCP or UDP
+    pub protocol: RuleProtocol,
+    pub host_ip: Ipv4Addr,
+    pub host_port: u16,
+    pub vm_port: u16,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PortForwardData {
+    /// The forwarded port as `tcp/203.0.113.10:8080`.
+    pub fn host_endpoint(&self) -> String {
+        format!(
+            "{}/{}:{}",
+            self.protocol.as_str(),
+            self.host_ip,
+            self.host_port
+        )
+    }
+}
+
 /// SQLite storage for networks and NICs.
 pub struct Storage {
     conn: Mutex<Connection>,
@@ -331,8 +389,8 @@ impl Storage {
         let ntp_json = serde_json::to_string(&network.ntp_servers)?;
 
         conn.execute(
-            "INSERT INTO networks (id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            "INSERT INTO networks (id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, boot_server, boot_file, flow_logs, flow_sample_rate, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
             params![
                 network.id.to_string(),
                 network.name,
@@ -343,6 +401,10 @@ impl Storage {
                 dns_json,
                 ntp_json,
                 network.is_public,
+                network.boot_server.map(|a| a.to_string()),
+                network.boot_file,
+                network.flow_logs,
+                network.flow_sample_rate,
                 network.created_at.to_rfc3339(),
                 network.updated_at.to_rfc3339(),
             ],
@@ -361,7 +423,7 @@ impl Storage {
     pub fn get_network_by_id(&self, id: &Uuid) -> Result<Option<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, boot_server, boot_file, flow_logs, flow_sample_rate, created_at, updated_at
              FROM networks WHERE id = ?1",
             params![id.to_string()],
             |row| Ok(Self::row_to_network(row)),
@@ -374,7 +436,7 @@ impl Storage {
     pub fn get_network_by_name(&self, name: &str) -> Result<Option<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, boot_server, boot_file, flow_logs, flow_sample_rate, created_at, updated_at
              FROM networks WHERE name = ?1",
             params![name],
             |row| Ok(Self::row_to_network(row)),
@@ -387,7 +449,7 @@ impl Storage {
     pub fn list_networks(&self) -> Result<Vec<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, boot_server, boot_file, flow_logs, flow_sample_rate, created_at, updated_at
              FROM networks ORDER BY created_at",
         )?;
 
@@ -404,7 +466,7 @@ impl Storage {
     pub fn list_public_networks(&self) -> Result<Vec<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, boot_server, boot_file, flow_logs, flow_sample_rate, created_at, updated_at
              FROM networks WHERE is_public = 1 ORDER BY created_at",
         )?;
 
@@ -440,6 +502,27 @@ impl Storage {
         Ok(())
     }
 
+    /// Update network flow log settings.
+    pub fn update_network_flow_logs(
+        &self,
+        id: &Uuid,
+        enabled: bool,
+        sample_rate: u32,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "UPDATE networks SET flow_logs = ?1, flow_sample_rate = ?2, updated_at = ?3 WHERE id = ?4",
+            params![enabled, sample_rate, now, id.to_string()],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::NetworkNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
     /// Delete a network by ID.
     pub fn delete_network(&self, id: &Uuid) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
@@ -471,8 +554,12 @@ impl Storage {
         let dns_json: String = row.get(6)?;
         let ntp_json: String = row.get(7)?;
         let is_public: bool = row.get(8)?;
-        let created_at_str: String = row.get(9)?;
-        let updated_at_str: String = row.get(10)?;
+        let boot_server_str: Option<String> = row.get(9)?;
+        let boot_file: Option<String> = row.get(10)?;
+        let flow_logs: bool = row.get(11)?;
+        let flow_sample_rate: u32 = row.get(12)?;
+        let created_at_str: String = row.get(13)?;
+        let updated_at_str: String = row.get(14)?;
 
         Ok(NetworkData {
             id: Uuid::parse_str(&id_str).unwrap(),
@@ -484,6 +571,10 @@ impl Storage {
             dns_servers: serde_json::from_str(&dns_json)?,
             ntp_servers: serde_json::from_str(&ntp_json)?,
             is_public,
+            boot_server: boot_server_str.map(|s| s.parse().unwrap()),
+            boot_file,
+            flow_logs,
+            flow_sample_rate,
             created_at: DateTime::parse_from_rfc3339(&created_at_str)
                 .unwrap()
                 .with_timezone(&Utc),
@@ -513,8 +604,8 @@ impl Storage {
         )?;
 
         conn.execute(
-            "INSERT INTO nics (id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            "INSERT INTO nics (id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at, owner_kind, owner_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
             params![
                 nic.id.to_string(),
                 nic.name,
@@ -528,6 +619,8 @@ impl Storage {
                 i32::from(nic.state),
                 nic.created_at.to_rfc3339(),
                 nic.updated_at.to_rfc3339(),
+                nic.owner.as_ref().map(|o| &o.kind),
+                nic.owner.as_ref().map(|o| &o.id),
             ],
         )?;
 
@@ -538,7 +631,7 @@ impl Storage {
     pub fn get_nic_by_id(&self, id: &Uuid) -> Result<Option<NicData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at, owner_kind, owner_id
              FROM nics WHERE id = ?1",
             params![id.to_string()],
             |row| Ok(Self::row_to_nic(row)),
@@ -551,7 +644,7 @@ impl Storage {
     pub fn get_nic_by_name(&self, name: &str) -> Result<Option<NicData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at, owner_kind, owner_id
              FROM nics WHERE name = ?1",
             params![name],
             |row| Ok(Self::row_to_nic(row)),
@@ -564,7 +657,7 @@ impl Storage {
     pub fn list_nics(&self) -> Result<Vec<NicData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at, owner_kind, owner_id
              FROM nics ORDER BY created_at",
         )?;
 
@@ -581,7 +674,7 @@ impl Storage {
     pub fn list_nics_in_network(&self, network_id: &Uuid) -> Result<Vec<NicData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, tap_name, state, created_at, updated_at, owner_kind, owner_id
              FROM nics WHERE network_id = ?1 ORDER BY created_at",
         )?;
 
@@ -629,6 +722,32 @@ impl Storage {
         Ok(())
     }
 
+    /// Move a NIC to other addresses.
+    pub fn update_nic_addresses(
+        &self,
+        id: &Uuid,
+        ipv4: Option<Ipv4Addr>,
+        ipv6: Option<Ipv6Addr>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "UPDATE nics SET ipv4_address = ?1, ipv6_address = ?2, updated_at = ?3 WHERE id = ?4",
+            params![
+                ipv4.map(|a| a.to_string()),
+                ipv6.map(|a| a.to_string()),
+                now,
+                id.to_string()
+            ],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::NicNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
     /// Update NIC state.
     pub fn update_nic_state(&self, id: &Uuid, state: NicState) -> Result<()> {
         let conn = self.conn.lock().unwrap();
@@ -721,6 +840,8 @@ impl Storage {
         let state_int: i32 = row.get(9)?;
         let created_at_str: String = row.get(10)?;
         let updated_at_str: String = row.get(11)?;
+        let owner_kind: Option<String> = row.get(12)?;
+        let owner_id: Option<String> = row.get(13)?;
 
         // Parse MAC address
         let mac_parts: Vec<&str> = mac_str.split(':').collect();
@@ -756,6 +877,9 @@ impl Storage {
             updated_at: DateTime::parse_from_rfc3339(&updated_at_str)
                 .unwrap()
                 .with_timezone(&Utc),
+            owner: owner_kind
+                .zip(owner_id)
+                .map(|(kind, id)| OwnerRef { kind, id }),
         })
     }
 }
@@ -768,14 +892,15 @@ impl Storage {
         let conn = self.conn.lock().unwrap();
 
         conn.execute(
-            "INSERT INTO security_groups (id, name, description, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+            "INSERT INTO security_groups (id, name, description, created_at, updated_at, audit)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
                 sg.id.to_string(),
                 sg.name,
                 sg.description,
                 sg.created_at.to_rfc3339(),
                 sg.updated_at.to_rfc3339(),
+                sg.audit,
             ],
         )
         .map_err(|e| {
@@ -794,7 +919,7 @@ impl Storage {
     pub fn get_security_group_by_id(&self, id: &Uuid) -> Result<Option<SecurityGroupData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, description, created_at, updated_at
+            "SELECT id, name, description, created_at, updated_at, audit
              FROM security_groups WHERE id = ?1",
             params![id.to_string()],
             |row| Ok(Self::row_to_security_group(row)),
@@ -807,7 +932,7 @@ impl Storage {
     pub fn get_security_group_by_name(&self, name: &str) -> Result<Option<SecurityGroupData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, description, created_at, updated_at
+            "SELECT id, name, description, created_at, updated_at, audit
              FROM security_groups WHERE name = ?1",
             params![name],
             |row| Ok(Self::row_to_security_group(row)),
@@ -820,7 +945,7 @@ impl Storage {
     pub fn list_security_groups(&self) -> Result<Vec<SecurityGroupData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, description, created_at, updated_at
+            "SELECT id, name, description, created_at, updated_at, audit
              FROM security_groups ORDER BY created_at",
         )?;
 
@@ -843,6 +968,22 @@ impl Storage {
         Ok(rows > 0)
     }
 
+    /// Switch a security group between audit and enforcing mode.
+    pub fn set_security_group_audit(&self, id: &Uuid, audit: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "UPDATE security_groups SET audit = ?1, updated_at = ?2 WHERE id = ?3",
+            params![audit, now, id.to_string()],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::SecurityGroupNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
     /// Count NICs attached to a security group.
     pub fn count_nics_in_security_group(&self, sg_id: &Uuid) -> Result<u32> {
         let conn = self.conn.lock().unwrap();
@@ -860,11 +1001,13 @@ impl Storage {
         let description: Option<String> = row.get(2)?;
         let created_at_str: String = row.get(3)?;
         let updated_at_str: String = row.get(4)?;
+        let audit: bool = row.get(5)?;
 
         Ok(SecurityGroupData {
             id: Uuid::parse_str(&id_str).unwrap(),
             name,
             description,
+            audit,
             created_at: DateTime::parse_from_rfc3339(&created_at_str)
                 .unwrap()
                 .with_timezone(&Utc),
@@ -881,8 +1024,8 @@ impl Storage {
         let conn = self.conn.lock().unwrap();
 
         conn.execute(
-            "INSERT INTO security_group_rules (id, security_group_id, direction, protocol, port_start, port_end, cidr, description, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            "INSERT INTO security_group_rules (id, security_group_id, direction, protocol, port_start, port_end, cidr, description, created_at, updated_at, not_before, not_after, schedule)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
             params![
                 rule.id.to_string(),
                 rule.security_group_id.to_string(),
@@ -894,6 +1037,9 @@ impl Storage {
                 rule.description,
                 rule.created_at.to_rfc3339(),
                 rule.updated_at.to_rfc3339(),
+                rule.not_before.map(|t| t.to_rfc3339()),
+                rule.not_after.map(|t| t.to_rfc3339()),
+                rule.schedule.map(|s| s.to_string()),
             ],
         )?;
 
@@ -907,7 +1053,7 @@ impl Storage {
     ) -> Result<Option<SecurityGroupRuleData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, security_group_id, direction, protocol, port_start, port_end, cidr, description, created_at, updated_at
+            "SELECT id, security_group_id, direction, protocol, port_start, port_end, cidr, description, created_at, updated_at, not_before, not_after, schedule
              FROM security_group_rules WHERE id = ?1",
             params![id.to_string()],
             |row| Ok(Self::row_to_security_group_rule(row)),
@@ -923,7 +1069,7 @@ impl Storage {
     ) -> Result<Vec<SecurityGroupRuleData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, security_group_id, direction, protocol, port_start, port_end, cidr, description, created_at, updated_at
+            "SELECT id, security_group_id, direction, protocol, port_start, port_end, cidr, description, created_at, updated_at, not_before, not_after, schedule
              FROM security_group_rules WHERE security_group_id = ?1 ORDER BY created_at",
         )?;
 
@@ -959,6 +1105,14 @@ impl Storage {
         let description: Option<String> = row.get(7)?;
         let created_at_str: String = row.get(8)?;
         let updated_at_str: String = row.get(9)?;
+        let not_before_str: Option<String> = row.get(10)?;
+        let not_after_str: Option<String> = row.get(11)?;
+        let schedule_str: Option<String> = row.get(12)?;
+        let parse_time = |s: String| {
+            DateTime::parse_from_rfc3339(&s)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
 
         Ok(SecurityGroupRuleData {
             id: Uuid::parse_str(&id_str).unwrap(),
@@ -969,6 +1123,9 @@ impl Storage {
             port_end: port_end.map(|p| p as u16),
             cidr,
             description,
+            not_before: not_before_str.map(parse_time),
+            not_after: not_after_str.map(parse_time),
+            schedule: schedule_str.map(|s| s.parse().unwrap()),
             created_at: DateTime::parse_from_rfc3339(&created_at_str)
                 .unwrap()
                 .with_timezone(&Utc),
@@ -1028,7 +1185,7 @@ impl Storage {
     pub fn list_security_groups_for_nic(&self, nic_id: &Uuid) -> Result<Vec<SecurityGroupData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT sg.id, sg.name, sg.description, sg.created_at, sg.updated_at
+            "SELECT sg.id, sg.name, sg.description, sg.created_at, sg.updated_at, sg.audit
              FROM security_groups sg
              INNER JOIN nic_security_groups nsg ON sg.id = nsg.security_group_id
              WHERE nsg.nic_id = ?1
@@ -1074,7 +1231,8 @@ impl Storage {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
             "SELECT r.id, r.security_group_id, r.direction, r.protocol, r.port_start, r.port_end,
-                    r.cidr, r.description, r.created_at, r.updated_at
+                    r.cidr, r.description, r.created_at, r.updated_at, r.not_before, r.not_after,
+                    r.schedule
              FROM security_group_rules r
              INNER JOIN nic_security_groups nsg ON r.security_group_id = nsg.security_group_id
              WHERE nsg.nic_id = ?1
@@ -1102,6 +1260,235 @@ impl Storage {
         )?;
         Ok(count > 0)
     }
+
+    // ========== Port Forward Operations ==========
+
+    /// Create a port forward.
+    pub fn create_port_forward(&self, forward: &PortForwardData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO port_forwards (id, nic_id, protocol, host_ip, host_port, vm_port, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                forward.id.to_string(),
+                forward.nic_id.to_string(),
+                forward.protocol.as_str(),
+                forward.host_ip.to_string(),
+                forward.host_port,
+                forward.vm_port,
+                forward.created_at.to_rfc3339(),
+            ],
+        )
+        .map_err(|e| {
+            if let rusqlite::Error::SqliteFailure(ref err, _) = e
+                && err.code == rusqlite::ErrorCode::ConstraintViolation
+            {
+                return StorageError::PortForwardExists(forward.host_endpoint());
+            }
+            StorageError::Database(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Get a port forward by ID.
+    pub fn get_port_forward_by_id(&self, id: &Uuid) -> Result<Option<PortForwardData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, nic_id, protocol, host_ip, host_port, vm_port, created_at
+             FROM port_forwards WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_port_forward(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// List all port forwards.
+    pub fn list_port_forwards(&self) -> Result<Vec<PortForwardData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, nic_id, protocol, host_ip, host_port, vm_port, created_at
+             FROM port_forwards ORDER BY created_at",
+        )?;
+
+        let forwards = stmt
+            .query_map([], |row| Ok(Self::row_to_port_forward(row)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(forwards)
+    }
+
+    /// List the port forwards to a NIC.
+    pub fn list_port_forwards_for_nic(&self, nic_id: &Uuid) -> Result<Vec<PortForwardData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, nic_id, protocol, host_ip, host_port, vm_port, created_at
+             FROM port_forwards WHERE nic_id = ?1 ORDER BY created_at",
+        )?;
+
+        let forwards = stmt
+            .query_map(params![nic_id.to_string()], |row| {
+                Ok(Self::row_to_port_forward(row))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(forwards)
+    }
+
+    /// Delete a port forward by ID.
+    pub fn delete_port_forward(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM port_forwards WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn row_to_port_forward(row: &Row) -> Result<PortForwardData> {
+        let id_str: String = row.get(0)?;
+        let nic_id_str: String = row.get(1)?;
+        let protocol_str: String = row.get(2)?;
+        let host_ip_str: String = row.get(3)?;
+        let host_port: u16 = row.get(4)?;
+        let vm_port: u16 = row.get(5)?;
+        let created_at_str: String = row.get(6)?;
+
+        Ok(PortForwardData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            nic_id: Uuid::parse_str(&nic_id_str).unwrap(),
+            protocol: RuleProtocol::parse(&protocol_str),
+            host_ip: host_ip_str.parse().unwrap(),
+            host_port,
+            vm_port,
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+
+    // ========== Host Migration ==========
+
+    /// Network, NIC, security group and port forward rows for a state
+    /// snapshot, as `{"table": [{column: value}]}`.
+    pub fn export_state(&self) -> Result<serde_json::Value> {
+        let conn = self.conn.lock().unwrap();
+        let mut state = serde_json::Map::new();
+        for table in STATE_TABLES {
+            state.insert(table.to_string(), export_table(&conn, table)?.into());
+        }
+        Ok(state.into())
+    }
+
+    /// Insert the rows of a state snapshot into an empty store. Returns the
+    /// number of rows.
+    pub fn import_state(&self, state: &serde_json::Value) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut rows = 0;
+        for table in STATE_TABLES {
+            rows += import_table(&tx, table, snapshot_rows(state, table))?;
+        }
+        tx.commit()?;
+        Ok(rows)
+    }
+
+    /// Whether no network is defined; imports only go into an empty store.
+    pub fn is_empty(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM networks", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+}
+
+/// Format version of [`Storage::export_state`] snapshots.
+pub const STATE_VERSION: u32 = 1;
+
+/// Tables in a state snapshot, parents first.
+const STATE_TABLES: &[&str] = &[
+    "networks",
+    "nics",
+    "security_groups",
+    "security_group_rules",
+    "nic_security_groups",
+    "port_forwards",
+];
+
+/// Number of rows in a state snapshot.
+pub fn snapshot_len(state: &serde_json::Value) -> usize {
+    STATE_TABLES
+        .iter()
+        .map(|t| snapshot_rows(state, t).len())
+        .sum()
+}
+
+fn snapshot_rows<'a>(state: &'a serde_json::Value, table: &str) -> &'a [serde_json::Value] {
+    state[table]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get("name"))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(columns)
+}
+
+/// All rows of `table` as JSON objects keyed by column.
+fn export_table(conn: &Connection, table: &str) -> Result<Vec<serde_json::Value>> {
+    let fields = table_columns(conn, table)?
+        .iter()
+        .map(|c| format!("'{0}', \"{0}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let json: String = conn.query_row(
+        &format!(
+            "SELECT json_group_array(json_object({})) FROM {}",
+            fields, table
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Insert rows written by [`export_table`]. Columns missing from the rows,
+/// as in an export from an older version, get their defaults.
+fn import_table(conn: &Connection, table: &str, rows: &[serde_json::Value]) -> Result<usize> {
+    let Some(first) = rows.first().and_then(|r| r.as_object()) else {
+        return Ok(0);
+    };
+    let columns: Vec<String> = table_columns(conn, table)?
+        .into_iter()
+        .filter(|c| first.contains_key(c))
+        .collect();
+    let names = columns
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = columns
+        .iter()
+        .map(|c| format!("json_extract(value, '$.\"{}\"')", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let rows = conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) SELECT {} FROM json_each(?1)",
+            table, names, values
+        ),
+        params![serde_json::to_string(rows)?],
+    )?;
+    Ok(rows)
 }
 
 /// Parse MAC address string to bytes.
@@ -1147,6 +1534,10 @@ mod tests {
             dns_servers: vec!["8.8.8.8".parse().unwrap()],
             ntp_servers: vec![],
             is_public: true,
+            boot_server: None,
+            boot_file: None,
+            flow_logs: false,
+            flow_sample_rate: 1,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -1157,6 +1548,14 @@ mod tests {
         assert_eq!(fetched.name, "test-network");
         assert!(fetched.ipv4_enabled);
         assert_eq!(fetched.ipv4_subnet, Some("10.0.0.0/24".parse().unwrap()));
+        assert!(!fetched.flow_logs);
+
+        storage
+            .update_network_flow_logs(&network.id, true, 100)
+            .unwrap();
+        let fetched = storage.get_network_by_id(&network.id).unwrap().unwrap();
+        assert!(fetched.flow_logs);
+        assert_eq!(fetched.flow_sample_rate, 100);
     }
 
     #[test]
@@ -1173,6 +1572,10 @@ mod tests {
             dns_servers: vec![],
             ntp_servers: vec![],
             is_public: false,
+            boot_server: None,
+            boot_file: None,
+            flow_logs: false,
+            flow_sample_rate: 1,
             created_at: Utc::now(),
             updated_at: Utc::now(),
         };
@@ -1191,6 +1594,10 @@ mod tests {
             state: NicState::Created,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            owner: Some(OwnerRef {
+                kind: "pod".to_string(),
+                id: "pod-1".to_string(),
+            }),
         };
         storage.create_nic(&nic).unwrap();
 
@@ -1198,6 +1605,69 @@ mod tests {
         assert_eq!(fetched.name, Some("test-nic".to_string()));
         assert_eq!(fetched.ipv4_address, Some("10.0.0.5".parse().unwrap()));
         assert_eq!(fetched.tap_name, "tap_test");
+        assert_eq!(fetched.owner, nic.owner);
+    }
+
+    #[test]
+    fn test_storage_state_roundtrip() {
+        let source = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec!["10.0.0.1".parse().unwrap()],
+            ntp_servers: vec![],
+            is_public: false,
+            boot_server: None,
+            boot_file: None,
+            flow_logs: false,
+            flow_sample_rate: 1,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        source.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x07],
+            ipv4_address: Some("10.0.0.7".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec!["192.168.7.0/24".parse().unwrap()],
+            routed_ipv6_prefixes: vec![],
+            tap_name: "tap_7".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+        };
+        source.create_nic(&nic).unwrap();
+
+        let state = source.export_state().unwrap();
+        assert_eq!(snapshot_len(&state), 2);
+
+        let target = Storage::in_memory().unwrap();
+        assert!(target.is_empty().unwrap());
+        assert_eq!(target.import_state(&state).unwrap(), 2);
+        assert!(!target.is_empty().unwrap());
+
+        let restored = target.get_network_by_id(&network.id).unwrap().unwrap();
+        assert_eq!(restored.dns_servers, network.dns_servers);
+        let restored = target.get_nic_by_id(&nic.id).unwrap().unwrap();
+        assert_eq!(restored.name, None);
+        assert_eq!(restored.mac_address, nic.mac_address);
+        assert_eq!(restored.ipv4_address, nic.ipv4_address);
+        assert_eq!(restored.routed_ipv4_prefixes, nic.routed_ipv4_prefixes);
+        assert_eq!(restored.tap_name, nic.tap_name);
+
+        // An empty snapshot imports nothing
+        let empty = Storage::in_memory().unwrap();
+        assert_eq!(empty.import_state(&serde_json::json!({})).unwrap(), 0);
     }
 
     #[test]
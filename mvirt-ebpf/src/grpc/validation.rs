@@ -1,15 +1,20 @@
 //! Input validation for gRPC requests.
 
-use super::storage::{RuleDirection, RuleProtocol, Storage, parse_mac_address};
+use super::storage::{NicData, RuleDirection, RuleProtocol, Storage, parse_mac_address};
+use crate::proto_handler::{GATEWAY_IPV4_LINK_LOCAL, GATEWAY_IPV6_LINK_LOCAL};
+use chrono::{DateTime, Utc};
 use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use mvirt_common::naming::{self, NameError};
+use mvirt_common::netboot::NetworkBootError;
+use mvirt_common::rule_window::RuleSchedule;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use thiserror::Error;
 
 /// Validation errors.
 #[derive(Debug, Error)]
 pub enum ValidationError {
-    #[error("Network name is required")]
-    NetworkNameRequired,
+    #[error(transparent)]
+    InvalidName(#[from] NameError),
 
     #[error("At least one IP version (IPv4 or IPv6) must be enabled")]
     NoIpVersionEnabled,
@@ -47,6 +52,12 @@ pub enum ValidationError {
     #[error("IPv4 address {0} is already in use")]
     Ipv4AddressInUse(String),
 
+    #[error("IPv4 address {0} is reserved as the {1}")]
+    Ipv4AddressReserved(String, &'static str),
+
+    #[error("IPv6 address {0} is reserved as the {1}")]
+    Ipv6AddressReserved(String, &'static str),
+
     #[error("IPv6 address {0} is already in use")]
     Ipv6AddressInUse(String),
 
@@ -63,9 +74,6 @@ pub enum ValidationError {
     NetworkIdRequired,
 
     // Security Group validation errors
-    #[error("Security group name is required")]
-    SecurityGroupNameRequired,
-
     #[error("Security group identifier required")]
     SecurityGroupIdRequired,
 
@@ -90,11 +98,38 @@ pub enum ValidationError {
     #[error("Invalid CIDR: {0}")]
     InvalidCidr(String),
 
+    #[error("Invalid rule time: {0} (expected RFC 3339, e.g. 2026-01-31T18:00:00Z)")]
+    InvalidRuleTime(String),
+
+    #[error("Rule not_after must be later than not_before")]
+    EmptyRuleWindow,
+
+    #[error("Invalid rule schedule: {0}")]
+    InvalidRuleSchedule(String),
+
     #[error("Rule ID is required")]
     RuleIdRequired,
 
     #[error("NIC identifier required")]
     NicIdRequired,
+
+    #[error(transparent)]
+    InvalidNetworkBoot(#[from] NetworkBootError),
+
+    #[error("Invalid flow sample rate: {0} (must be 1-{MAX_FLOW_SAMPLE_RATE})")]
+    InvalidFlowSampleRate(u32),
+
+    #[error("Port forwards need a NIC with an IPv4 address")]
+    PortForwardRequiresIpv4,
+
+    #[error("Port forwards are TCP or UDP")]
+    InvalidPortForwardProtocol,
+
+    #[error("Invalid host address: {0}")]
+    InvalidHostAddress(String),
+
+    #[error("Host address {0} lies within public network '{1}' ({2})")]
+    HostAddressInNetwork(String, String, String),
 }
 
 pub type Result<T> = std::result::Result<T, ValidationError>;
@@ -126,10 +161,7 @@ pub fn validate_create_network(
     dns_servers: &[String],
     storage: &Storage,
 ) -> Result<(Option<Ipv4Net>, Option<Ipv6Net>, Vec<IpAddr>)> {
-    // Name required
-    if name.trim().is_empty() {
-        return Err(ValidationError::NetworkNameRequired);
-    }
+    naming::validate_name("Network", name)?;
 
     // At least one IP version
     if !ipv4_enabled && !ipv6_enabled {
@@ -212,6 +244,19 @@ pub fn validate_create_network(
     Ok((parsed_v4, parsed_v6, parsed_dns))
 }
 
+/// Largest flow sample rate; sparser sampling makes the scaled counts
+/// too coarse to bill on.
+pub const MAX_FLOW_SAMPLE_RATE: u32 = 65536;
+
+/// Validate a flow log sample rate. 0 means every packet, like 1.
+pub fn validate_flow_sample_rate(rate: u32) -> Result<u32> {
+    match rate {
+        0 => Ok(1),
+        1..=MAX_FLOW_SAMPLE_RATE => Ok(rate),
+        _ => Err(ValidationError::InvalidFlowSampleRate(rate)),
+    }
+}
+
 /// Validate NIC creation request.
 #[allow(clippy::type_complexity)]
 pub fn validate_create_nic(
@@ -248,6 +293,12 @@ pub fn validate_create_nic(
                 subnet.to_string(),
             ));
         }
+        if let Some(reason) = reserved_ipv4(ipv4_subnet, addr) {
+            return Err(ValidationError::Ipv4AddressReserved(
+                addr.to_string(),
+                reason,
+            ));
+        }
         Some(addr)
     };
 
@@ -268,12 +319,56 @@ pub fn validate_create_nic(
                 prefix.to_string(),
             ));
         }
+        if let Some(reason) = reserved_ipv6(ipv6_prefix, addr) {
+            return Err(ValidationError::Ipv6AddressReserved(
+                addr.to_string(),
+                reason,
+            ));
+        }
         Some(addr)
     };
 
     Ok((mac, ipv4, ipv6))
 }
 
+/// Why an IPv4 address can't go to a NIC, if it can't: the subnet's
+/// network, broadcast and gateway (network + 1) addresses, and the
+/// link-local gateway.
+pub fn reserved_ipv4(subnet: Option<Ipv4Net>, addr: Ipv4Addr) -> Option<&'static str> {
+    if addr == GATEWAY_IPV4_LINK_LOCAL {
+        return Some("link-local gateway");
+    }
+    let subnet = subnet?;
+    let network = u32::from(subnet.network());
+    if addr == subnet.network() {
+        Some("network address")
+    } else if addr == subnet.broadcast() {
+        Some("broadcast address")
+    } else if u32::from(addr) == network + 1 {
+        Some("gateway address")
+    } else {
+        None
+    }
+}
+
+/// Why an IPv6 address can't go to a NIC, if it can't: the prefix's
+/// subnet-router anycast and gateway (::1) addresses, and the link-local
+/// gateway.
+pub fn reserved_ipv6(prefix: Option<Ipv6Net>, addr: Ipv6Addr) -> Option<&'static str> {
+    if addr == GATEWAY_IPV6_LINK_LOCAL {
+        return Some("link-local gateway");
+    }
+    let prefix = prefix?;
+    let network = u128::from(prefix.network());
+    if addr == prefix.network() {
+        Some("network address")
+    } else if u128::from(addr) == network + 1 {
+        Some("gateway address")
+    } else {
+        None
+    }
+}
+
 /// Allocate the next available IPv4 address in a subnet.
 pub fn allocate_ipv4_address(
     subnet: Ipv4Net,
@@ -286,7 +381,7 @@ pub fn allocate_ipv4_address(
     // Start from network + 2 (skip network and gateway)
     for i in (network + 2)..broadcast {
         let addr = Ipv4Addr::from(i);
-        if addr != gateway && !used.contains(&addr) {
+        if addr != gateway && reserved_ipv4(Some(subnet), addr).is_none() && !used.contains(&addr) {
             return Some(addr);
         }
     }
@@ -305,7 +400,7 @@ pub fn allocate_ipv6_address(
     for i in 2u128..1000 {
         // Limit search to first 1000 addresses
         let addr = Ipv6Addr::from(network + i);
-        if addr != gateway && !used.contains(&addr) {
+        if addr != gateway && reserved_ipv6(Some(prefix), addr).is_none() && !used.contains(&addr) {
             return Some(addr);
         }
     }
@@ -346,12 +441,67 @@ pub fn parse_routed_prefixes(
 
 /// Validate security group creation request.
 pub fn validate_create_security_group(name: &str) -> Result<()> {
-    if name.trim().is_empty() {
-        return Err(ValidationError::SecurityGroupNameRequired);
-    }
+    naming::validate_name("Security group", name)?;
     Ok(())
 }
 
+/// Validated port forward: protocol, host address, host port and VM port.
+pub type ValidatedPortForward = (RuleProtocol, Ipv4Addr, u16, u16);
+
+/// Validate a port forward to a NIC. A `vm_port` of 0 forwards to the
+/// host port. Whether the host port is still free is left to the store.
+pub fn validate_create_port_forward(
+    nic: &NicData,
+    protocol: i32,
+    host_ip: &str,
+    host_port: u32,
+    vm_port: u32,
+    storage: &Storage,
+) -> Result<ValidatedPortForward> {
+    if nic.ipv4_address.is_none() {
+        return Err(ValidationError::PortForwardRequiresIpv4);
+    }
+
+    let proto = RuleProtocol::from(protocol);
+    if !matches!(proto, RuleProtocol::Tcp | RuleProtocol::Udp) {
+        return Err(ValidationError::InvalidPortForwardProtocol);
+    }
+
+    let addr: Ipv4Addr = host_ip
+        .parse()
+        .map_err(|_| ValidationError::InvalidIpv4Address(host_ip.to_string()))?;
+    if addr.is_unspecified()
+        || addr.is_loopback()
+        || addr.is_link_local()
+        || addr.is_multicast()
+        || addr.is_broadcast()
+    {
+        return Err(ValidationError::InvalidHostAddress(host_ip.to_string()));
+    }
+    // Addresses of public subnets are routed to the NICs
+    for network in storage.list_public_networks().unwrap_or_default() {
+        if let Some(subnet) = network.ipv4_subnet
+            && subnet.contains(&addr)
+        {
+            return Err(ValidationError::HostAddressInNetwork(
+                addr.to_string(),
+                network.name,
+                subnet.to_string(),
+            ));
+        }
+    }
+
+    if host_port == 0 || host_port > 65535 {
+        return Err(ValidationError::PortOutOfRange(host_port));
+    }
+    if vm_port > 65535 {
+        return Err(ValidationError::PortOutOfRange(vm_port));
+    }
+    let vm_port = if vm_port == 0 { host_port } else { vm_port };
+
+    Ok((proto, addr, host_port as u16, vm_port as u16))
+}
+
 /// Parsed CIDR information for eBPF rules
 #[derive(Debug, Clone)]
 pub struct ParsedCidr {
@@ -434,6 +584,48 @@ pub fn validate_security_group_rule(
     Ok((dir, proto, parsed_port_start, parsed_port_end, parsed_cidr))
 }
 
+/// Validated rule validity window and schedule.
+pub type ValidatedWindow = (
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Option<RuleSchedule>,
+);
+
+/// Validate a rule's validity window and schedule; empty strings leave
+/// that part unrestricted.
+pub fn validate_rule_window(
+    not_before: &str,
+    not_after: &str,
+    schedule: &str,
+) -> Result<ValidatedWindow> {
+    let parse_time = |s: &str| {
+        if s.is_empty() {
+            return Ok(None);
+        }
+        DateTime::parse_from_rfc3339(s)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|_| ValidationError::InvalidRuleTime(s.to_string()))
+    };
+    let not_before = parse_time(not_before)?;
+    let not_after = parse_time(not_after)?;
+    if let (Some(from), Some(until)) = (not_before, not_after)
+        && until <= from
+    {
+        return Err(ValidationError::EmptyRuleWindow);
+    }
+
+    let schedule =
+        if schedule.is_empty() {
+            None
+        } else {
+            Some(schedule.parse().map_err(|e| {
+                ValidationError::InvalidRuleSchedule(format!("{}: {}", schedule, e))
+            })?)
+        };
+
+    Ok((not_before, not_after, schedule))
+}
+
 /// Parse a CIDR string into address bytes and prefix length.
 pub fn parse_cidr(cidr: &str) -> Result<ParsedCidr> {
     let net: IpNet = cidr
@@ -0,0 +1,292 @@
+//! Minimal IPFIX (RFC 7011) export of flow records over UDP.
+//!
+//! Every message carries both templates (IPv4 and IPv6 flows) ahead of its
+//! data sets. Over UDP a collector may miss or forget templates, so
+//! resending them with each message is simpler than tracking refresh
+//! intervals; flow log messages go out once per export interval anyway.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tokio::net::UdpSocket;
+
+use crate::flowlog::{FlowDirection, FlowRecord};
+
+const IPFIX_VERSION: u16 = 10;
+const MESSAGE_HEADER_LEN: usize = 16;
+const SET_HEADER_LEN: usize = 4;
+const TEMPLATE_SET_ID: u16 = 2;
+
+const TEMPLATE_V4: u16 = 256;
+const TEMPLATE_V6: u16 = 257;
+
+/// Data records per message, keeping messages below a 1500 byte MTU.
+const RECORDS_PER_MESSAGE: usize = 10;
+
+/// NIC UUIDs go into interfaceDescription as fixed-length strings.
+const UUID_LEN: u16 = 36;
+
+// Information elements (IANA IPFIX registry)
+const IE_OCTET_DELTA_COUNT: u16 = 1;
+const IE_PACKET_DELTA_COUNT: u16 = 2;
+const IE_PROTOCOL_IDENTIFIER: u16 = 4;
+const IE_SOURCE_TRANSPORT_PORT: u16 = 7;
+const IE_SOURCE_IPV4_ADDRESS: u16 = 8;
+const IE_DESTINATION_TRANSPORT_PORT: u16 = 11;
+const IE_DESTINATION_IPV4_ADDRESS: u16 = 12;
+const IE_SOURCE_IPV6_ADDRESS: u16 = 27;
+const IE_DESTINATION_IPV6_ADDRESS: u16 = 28;
+const IE_FLOW_DIRECTION: u16 = 61;
+const IE_INTERFACE_DESCRIPTION: u16 = 83;
+const IE_FLOW_START_MILLISECONDS: u16 = 152;
+const IE_FLOW_END_MILLISECONDS: u16 = 153;
+
+/// Template fields after the addresses; shared by both templates.
+const COMMON_FIELDS: &[(u16, u16)] = &[
+    (IE_SOURCE_TRANSPORT_PORT, 2),
+    (IE_DESTINATION_TRANSPORT_PORT, 2),
+    (IE_PROTOCOL_IDENTIFIER, 1),
+    (IE_FLOW_DIRECTION, 1),
+    (IE_PACKET_DELTA_COUNT, 8),
+    (IE_OCTET_DELTA_COUNT, 8),
+    (IE_FLOW_START_MILLISECONDS, 8),
+    (IE_FLOW_END_MILLISECONDS, 8),
+    (IE_INTERFACE_DESCRIPTION, UUID_LEN),
+];
+
+/// Sends flow records to an IPFIX collector.
+pub struct IpfixExporter {
+    socket: UdpSocket,
+    /// Data records sent so far (the message header's sequence number).
+    sequence: u32,
+}
+
+impl IpfixExporter {
+    /// Bind a UDP socket for sending to `collector`.
+    pub async fn connect(collector: SocketAddr) -> std::io::Result<Self> {
+        let local: SocketAddr = match collector {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        socket.connect(collector).await?;
+        Ok(Self {
+            socket,
+            sequence: 0,
+        })
+    }
+
+    /// Send `records`, split across as many messages as needed.
+    pub async fn send(&mut self, records: &[FlowRecord]) -> std::io::Result<()> {
+        let export_time = chrono::Utc::now().timestamp() as u32;
+        for message in encode_messages(records, export_time, &mut self.sequence) {
+            self.socket.send(&message).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Encode `records` into IPFIX messages, advancing `sequence` by the
+/// number of data records written.
+pub fn encode_messages(
+    records: &[FlowRecord],
+    export_time: u32,
+    sequence: &mut u32,
+) -> Vec<Vec<u8>> {
+    records
+        .chunks(RECORDS_PER_MESSAGE)
+        .map(|chunk| {
+            let message = encode_message(chunk, export_time, *sequence);
+            *sequence = sequence.wrapping_add(chunk.len() as u32);
+            message
+        })
+        .collect()
+}
+
+fn encode_message(records: &[FlowRecord], export_time: u32, sequence: u32) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(1400);
+    buf.extend_from_slice(&IPFIX_VERSION.to_be_bytes());
+    buf.extend_from_slice(&0u16.to_be_bytes()); // length, patched below
+    buf.extend_from_slice(&export_time.to_be_bytes());
+    buf.extend_from_slice(&sequence.to_be_bytes());
+    buf.extend_from_slice(&0u32.to_be_bytes()); // observation domain
+    debug_assert_eq!(buf.len(), MESSAGE_HEADER_LEN);
+
+    write_set(&mut buf, TEMPLATE_SET_ID, |buf| {
+        write_template(
+            buf,
+            TEMPLATE_V4,
+            IE_SOURCE_IPV4_ADDRESS,
+            IE_DESTINATION_IPV4_ADDRESS,
+            4,
+        );
+        write_template(
+            buf,
+            TEMPLATE_V6,
+            IE_SOURCE_IPV6_ADDRESS,
+            IE_DESTINATION_IPV6_ADDRESS,
+            16,
+        );
+    });
+
+    let (v4, v6): (Vec<_>, Vec<_>) = records.iter().partition(|r| r.src_addr.is_ipv4());
+    for (template, records) in [(TEMPLATE_V4, v4), (TEMPLATE_V6, v6)] {
+        if records.is_empty() {
+            continue;
+        }
+        write_set(&mut buf, template, |buf| {
+            for record in records {
+                write_record(buf, record);
+            }
+        });
+    }
+
+    let len = buf.len() as u16;
+    buf[2..4].copy_from_slice(&len.to_be_bytes());
+    buf
+}
+
+/// Write a set header and the body from `body`, then fill in its length.
+fn write_set(buf: &mut Vec<u8>, set_id: u16, body: impl FnOnce(&mut Vec<u8>)) {
+    let start = buf.len();
+    buf.extend_from_slice(&set_id.to_be_bytes());
+    buf.extend_from_slice(&0u16.to_be_bytes());
+    body(buf);
+    let len = (buf.len() - start) as u16;
+    buf[start + 2..start + SET_HEADER_LEN].copy_from_slice(&len.to_be_bytes());
+}
+
+fn write_template(buf: &mut Vec<u8>, id: u16, src_ie: u16, dst_ie: u16, addr_len: u16) {
+    let fields = [(src_ie, addr_len), (dst_ie, addr_len)]
+        .into_iter()
+        .chain(COMMON_FIELDS.iter().copied());
+    buf.extend_from_slice(&id.to_be_bytes());
+    buf.extend_from_slice(&(2 + COMMON_FIELDS.len() as u16).to_be_bytes());
+    for (ie, len) in fields {
+        buf.extend_from_slice(&ie.to_be_bytes());
+        buf.extend_from_slice(&len.to_be_bytes());
+    }
+}
+
+fn write_record(buf: &mut Vec<u8>, record: &FlowRecord) {
+    for addr in [record.src_addr, record.dst_addr] {
+        match addr {
+            IpAddr::V4(a) => buf.extend_from_slice(&a.octets()),
+            IpAddr::V6(a) => buf.extend_from_slice(&a.octets()),
+        }
+    }
+    buf.extend_from_slice(&record.src_port.to_be_bytes());
+    buf.extend_from_slice(&record.dst_port.to_be_bytes());
+    buf.push(record.protocol);
+    // flowDirection: 0 = ingress, 1 = egress, both as seen by the VM's NIC
+    buf.push(match record.direction {
+        FlowDirection::Ingress => 0,
+        FlowDirection::Egress => 1,
+    });
+    buf.extend_from_slice(&record.packets.to_be_bytes());
+    buf.extend_from_slice(&record.bytes.to_be_bytes());
+    buf.extend_from_slice(&(record.start.timestamp_millis() as u64).to_be_bytes());
+    buf.extend_from_slice(&(record.end.timestamp_millis() as u64).to_be_bytes());
+    buf.extend_from_slice(record.nic_id.hyphenated().to_string().as_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use uuid::Uuid;
+
+    fn record(src: IpAddr, dst: IpAddr) -> FlowRecord {
+        FlowRecord {
+            nic_id: Uuid::nil(),
+            network_id: Uuid::nil(),
+            direction: FlowDirection::Egress,
+            src_addr: src,
+            dst_addr: dst,
+            src_port: 40000,
+            dst_port: 443,
+            protocol: 6,
+            packets: 10,
+            bytes: 1500,
+            start: Utc.timestamp_millis_opt(1_000).unwrap(),
+            end: Utc.timestamp_millis_opt(2_000).unwrap(),
+        }
+    }
+
+    fn u16_at(buf: &[u8], offset: usize) -> u16 {
+        u16::from_be_bytes([buf[offset], buf[offset + 1]])
+    }
+
+    /// Set IDs in a message, in order.
+    fn set_ids(message: &[u8]) -> Vec<u16> {
+        let mut ids = Vec::new();
+        let mut offset = MESSAGE_HEADER_LEN;
+        while offset < message.len() {
+            ids.push(u16_at(message, offset));
+            offset += u16_at(message, offset + 2) as usize;
+        }
+        assert_eq!(offset, message.len());
+        ids
+    }
+
+    #[test]
+    fn test_message_header_and_sets() {
+        let records = [
+            record("10.0.0.2".parse().unwrap(), "1.1.1.1".parse().unwrap()),
+            record("fd00::2".parse().unwrap(), "2001:db8::1".parse().unwrap()),
+        ];
+        let mut sequence = 0;
+        let messages = encode_messages(&records, 1234, &mut sequence);
+
+        assert_eq!(messages.len(), 1);
+        let message = &messages[0];
+        assert_eq!(u16_at(message, 0), IPFIX_VERSION);
+        assert_eq!(u16_at(message, 2) as usize, message.len());
+        assert_eq!(&message[4..8], &1234u32.to_be_bytes());
+        assert_eq!(&message[8..12], &0u32.to_be_bytes());
+        assert_eq!(
+            set_ids(message),
+            vec![TEMPLATE_SET_ID, TEMPLATE_V4, TEMPLATE_V6]
+        );
+        assert_eq!(sequence, 2);
+    }
+
+    #[test]
+    fn test_ipv4_record_layout() {
+        let records = [record(
+            "10.0.0.2".parse().unwrap(),
+            "1.1.1.1".parse().unwrap(),
+        )];
+        let message = &encode_messages(&records, 0, &mut 0)[0];
+
+        let template_set_len = u16_at(message, MESSAGE_HEADER_LEN + 2) as usize;
+        let data = &message[MESSAGE_HEADER_LEN + template_set_len + SET_HEADER_LEN..];
+        assert_eq!(data.len(), 4 + 4 + 2 + 2 + 1 + 1 + 8 + 8 + 8 + 8 + 36);
+        assert_eq!(&data[0..4], &[10, 0, 0, 2]);
+        assert_eq!(&data[4..8], &[1, 1, 1, 1]);
+        assert_eq!(u16_at(data, 8), 40000);
+        assert_eq!(u16_at(data, 10), 443);
+        assert_eq!(data[12], 6);
+        assert_eq!(data[13], 1);
+        assert_eq!(&data[14..22], &10u64.to_be_bytes());
+        assert_eq!(&data[22..30], &1500u64.to_be_bytes());
+        assert_eq!(&data[30..38], &1_000u64.to_be_bytes());
+        assert_eq!(&data[38..46], &2_000u64.to_be_bytes());
+        assert_eq!(&data[46..], Uuid::nil().to_string().as_bytes());
+    }
+
+    #[test]
+    fn test_records_split_across_messages() {
+        let records: Vec<_> = (0..25)
+            .map(|_| record("10.0.0.2".parse().unwrap(), "1.1.1.1".parse().unwrap()))
+            .collect();
+        let mut sequence = 7;
+        let messages = encode_messages(&records, 0, &mut sequence);
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(&messages[0][8..12], &7u32.to_be_bytes());
+        assert_eq!(&messages[1][8..12], &17u32.to_be_bytes());
+        assert_eq!(&messages[2][8..12], &27u32.to_be_bytes());
+        assert_eq!(sequence, 32);
+        assert!(messages.iter().all(|m| m.len() < 1400));
+    }
+}
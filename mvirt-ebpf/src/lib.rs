@@ -16,9 +16,17 @@
 pub mod audit;
 pub mod conntrack;
 pub mod ebpf_loader;
+pub mod flowlog;
 pub mod grpc;
+pub mod ipfix;
 pub mod nat;
+pub mod orphans;
 pub mod proto_handler;
+pub mod proto_limits;
+pub mod route_sync;
+pub mod rule_window;
+pub mod security;
+pub mod security_audit;
 pub mod tap;
 
 #[cfg(any(test, feature = "test-util"))]
@@ -28,9 +36,15 @@ pub mod test_util;
 pub use audit::{EbpfAuditLogger, create_audit_logger};
 pub use conntrack::ConnTrackCleaner;
 pub use ebpf_loader::EbpfManager;
+pub use flowlog::{FlowLogConfig, FlowLogExporter, FlowNics};
 pub use grpc::{EbpfNetServiceImpl, NetworkData, NicData, NicState, Storage};
+pub use mvirt_common::rule_window::RuleSchedule;
 pub use proto_handler::{
     GATEWAY_IPV4_LINK_LOCAL, GATEWAY_IPV6_LINK_LOCAL, GATEWAY_MAC, ProtocolHandler,
     process_packet_sync,
 };
+pub use proto_limits::{ProtoLimits, ProtoStatsSnapshot};
+pub use route_sync::{OverlayPrefix, Peer, RouteSync};
+pub use rule_window::{RuleWindowTimer, WindowNics};
+pub use security_audit::{AuditNics, SecurityAuditConfig, SecurityAuditReporter};
 pub use tap::TapDevice;
@@ -1,11 +1,20 @@
 //! mvirt-ebpf daemon: eBPF-based network service for mvirt VMs.
 
+use mvirt_common::events::EventBus;
+use mvirt_common::limits::{GrpcLimitLayer, LimitConfig};
+use mvirt_common::request_id::GrpcRequestIdLayer;
 use mvirt_ebpf::audit::create_audit_logger;
 use mvirt_ebpf::ebpf_loader::EbpfManager;
+use mvirt_ebpf::flowlog::{FlowLogConfig, FlowLogExporter};
 use mvirt_ebpf::grpc::proto::net_service_server::NetServiceServer;
 use mvirt_ebpf::grpc::{EbpfNetServiceImpl, Storage};
 use mvirt_ebpf::nat;
+use mvirt_ebpf::orphans::{DEFAULT_SWEEP_INTERVAL_SECS, OrphanSweeper};
 use mvirt_ebpf::proto_handler::ProtocolHandler;
+use mvirt_ebpf::proto_limits::ProtoLimits;
+use mvirt_ebpf::route_sync::{OverlayPrefix, Peer, RouteSync};
+use mvirt_ebpf::rule_window::{DEFAULT_CHECK_INTERVAL_SECS, RuleWindowTimer};
+use mvirt_ebpf::security_audit::{SecurityAuditConfig, SecurityAuditReporter};
 use mvirt_log::tls_config_from_paths;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -31,6 +40,33 @@ const TLS_CA_DEFAULT: &str = "/var/lib/mvirt-node/ca.pem";
 const TLS_CERT_DEFAULT: &str = "/var/lib/mvirt-node/cert.pem";
 const TLS_KEY_DEFAULT: &str = "/var/lib/mvirt-node/key.pem";
 
+/// Flow log export interval in seconds (default 60).
+const FLOW_EXPORT_INTERVAL_ENV: &str = "MVIRT_FLOW_EXPORT_INTERVAL";
+
+/// IPFIX collector for flow logs, e.g. `[2001:db8::1]:4739`; unset sends
+/// flow records to mvirt-log only.
+const FLOW_IPFIX_COLLECTOR_ENV: &str = "MVIRT_FLOW_IPFIX_COLLECTOR";
+
+/// Security audit report interval in seconds (default 60).
+const SECURITY_AUDIT_INTERVAL_ENV: &str = "MVIRT_SECURITY_AUDIT_INTERVAL";
+
+/// Orphaned TAP device sweep interval in seconds (default 300).
+const ORPHAN_SWEEP_INTERVAL_ENV: &str = "MVIRT_ORPHAN_SWEEP_INTERVAL";
+
+/// This host's /80 overlay prefix, e.g. `fd00:0:0:1::`; unset disables
+/// tunnelling to other hosts.
+const OVERLAY_PREFIX_ENV: &str = "MVIRT_OVERLAY_PREFIX";
+
+/// Other hosts to route to, comma-separated `<overlay prefix>=<endpoint>`
+/// of their net daemons.
+const OVERLAY_PEERS_ENV: &str = "MVIRT_OVERLAY_PEERS";
+
+/// ARP/DHCP/NDP responses per second and NIC (default 50, 0 = unlimited).
+const PROTO_RATE_LIMIT_ENV: &str = "MVIRT_PROTO_RATE_LIMIT";
+
+/// Pending DHCP transactions per NIC (default 16).
+const DHCP_MAX_PENDING_ENV: &str = "MVIRT_DHCP_MAX_PENDING";
+
 #[tokio::main(flavor = "current_thread")]
 async fn main() {
     mvirt_log::tracing_setup::init("info", &["h2=warn"]);
@@ -72,7 +108,20 @@ async fn main() {
     info!("eBPF programs loaded");
 
     // Create protocol handler
-    let proto_handler = Arc::new(ProtocolHandler::new());
+    let mut proto_limits = ProtoLimits::default();
+    if let Ok(rate) = std::env::var(PROTO_RATE_LIMIT_ENV) {
+        match rate.parse() {
+            Ok(rate) => proto_limits.responses_per_sec = rate,
+            Err(_) => warn!(value = %rate, "Invalid {}; using default", PROTO_RATE_LIMIT_ENV),
+        }
+    }
+    if let Ok(max) = std::env::var(DHCP_MAX_PENDING_ENV) {
+        match max.parse() {
+            Ok(max) if max > 0 => proto_limits.max_pending_dhcp = max,
+            _ => warn!(value = %max, "Invalid {}; using default", DHCP_MAX_PENDING_ENV),
+        }
+    }
+    let proto_handler = Arc::new(ProtocolHandler::new().with_limits(proto_limits));
 
     // Create audit logger
     let log_endpoints: Vec<String> = std::env::var(LOG_ENDPOINTS_ENV)
@@ -93,15 +142,98 @@ async fn main() {
             None
         }
     };
-    let audit = create_audit_logger(log_endpoints, tls);
+    let audit = create_audit_logger(log_endpoints, tls.clone());
+
+    // Start flow log exporter
+    let mut flow_config = FlowLogConfig::default();
+    if let Ok(secs) = std::env::var(FLOW_EXPORT_INTERVAL_ENV) {
+        match secs.parse::<u64>() {
+            Ok(secs) if secs > 0 => flow_config.interval = std::time::Duration::from_secs(secs),
+            _ => warn!(value = %secs, "Invalid {}; using default", FLOW_EXPORT_INTERVAL_ENV),
+        }
+    }
+    if let Ok(addr) = std::env::var(FLOW_IPFIX_COLLECTOR_ENV) {
+        match addr.parse() {
+            Ok(addr) => flow_config.ipfix_collector = Some(addr),
+            Err(_) => {
+                warn!(value = %addr, "Invalid {}; IPFIX export disabled", FLOW_IPFIX_COLLECTOR_ENV)
+            }
+        }
+    }
+    let flow_log =
+        match FlowLogExporter::start(Arc::clone(&ebpf), Arc::clone(&audit), flow_config).await {
+            Ok(f) => f,
+            Err(e) => {
+                error!(error = %e, "Failed to start flow log exporter");
+                std::process::exit(1);
+            }
+        };
+
+    // Start security audit reporter
+    let mut audit_config = SecurityAuditConfig::default();
+    if let Ok(secs) = std::env::var(SECURITY_AUDIT_INTERVAL_ENV) {
+        match secs.parse::<u64>() {
+            Ok(secs) if secs > 0 => audit_config.interval = std::time::Duration::from_secs(secs),
+            _ => warn!(value = %secs, "Invalid {}; using default", SECURITY_AUDIT_INTERVAL_ENV),
+        }
+    }
+    let security_audit =
+        SecurityAuditReporter::start(Arc::clone(&ebpf), Arc::clone(&audit), audit_config);
+
+    // Start security rule window timer
+    let rule_windows = RuleWindowTimer::start(
+        Arc::clone(&storage),
+        Arc::clone(&ebpf),
+        std::time::Duration::from_secs(DEFAULT_CHECK_INTERVAL_SECS),
+    );
 
     // Create gRPC service
-    let service = EbpfNetServiceImpl::new(
+    let mut service = EbpfNetServiceImpl::new(
         Arc::clone(&storage),
         Arc::clone(&ebpf),
         Arc::clone(&proto_handler),
-        audit,
-    );
+        Arc::clone(&audit),
+    )
+    .with_flow_logs(flow_log.nics())
+    .with_security_audit(security_audit.nics())
+    .with_rule_windows(rule_windows.nics());
+
+    // Tunnel to NICs on other hosts
+    let overlay_prefix = std::env::var(OVERLAY_PREFIX_ENV).ok().and_then(|prefix| {
+        match prefix.parse::<OverlayPrefix>() {
+            Ok(prefix) => Some(prefix),
+            Err(e) => {
+                warn!(error = %e, "Invalid {}; tunnelling disabled", OVERLAY_PREFIX_ENV);
+                None
+            }
+        }
+    });
+    let mut route_sync = None;
+    if let Some(prefix) = overlay_prefix {
+        service = service.with_overlay_prefix(prefix);
+        let peers: Vec<Peer> = std::env::var(OVERLAY_PEERS_ENV)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .filter_map(|peer| match peer.parse() {
+                Ok(peer) => Some(peer),
+                Err(e) => {
+                    warn!(error = %e, "Invalid entry in {}; skipped", OVERLAY_PEERS_ENV);
+                    None
+                }
+            })
+            .collect();
+        if !peers.is_empty() {
+            route_sync = Some(RouteSync::start(Arc::clone(&ebpf), peers, tls));
+        }
+    }
+
+    // Deleted NICs are unplugged from VMs by mvirt-vmm
+    match EventBus::new("net") {
+        Ok(bus) => service = service.with_host_events(bus),
+        Err(e) => warn!(error = %e, "No host event bus; NIC deletions are not published"),
+    }
 
     // Recover NICs from database
     if let Err(e) = service.recover_nics().await {
@@ -109,6 +241,17 @@ async fn main() {
         // Continue anyway - some NICs may have been recovered
     }
 
+    // Delete TAP devices left behind by NICs that no longer exist
+    OrphanSweeper::sweep(&storage, &audit).await;
+    let mut sweep_interval = std::time::Duration::from_secs(DEFAULT_SWEEP_INTERVAL_SECS);
+    if let Ok(secs) = std::env::var(ORPHAN_SWEEP_INTERVAL_ENV) {
+        match secs.parse::<u64>() {
+            Ok(secs) if secs > 0 => sweep_interval = std::time::Duration::from_secs(secs),
+            _ => warn!(value = %secs, "Invalid {}; using default", ORPHAN_SWEEP_INTERVAL_ENV),
+        }
+    }
+    let orphan_sweeper = OrphanSweeper::start(Arc::clone(&storage), audit, sweep_interval);
+
     // Parse address
     let addr = GRPC_ADDR.parse().expect("Invalid gRPC address");
 
@@ -120,6 +263,8 @@ async fn main() {
 
     // Start server with graceful shutdown
     let server = Server::builder()
+        .layer(GrpcRequestIdLayer)
+        .layer(GrpcLimitLayer::new(LimitConfig::from_env()))
         .add_service(NetServiceServer::new(service))
         .serve_with_shutdown(addr, async {
             tokio::select! {
@@ -135,6 +280,13 @@ async fn main() {
 
     // Cleanup
     info!("Shutting down...");
+    flow_log.stop();
+    security_audit.stop();
+    rule_windows.stop();
+    orphan_sweeper.stop();
+    if let Some(route_sync) = route_sync {
+        route_sync.stop();
+    }
     if let Err(e) = nat::cleanup_nftables() {
         error!(error = %e, "Failed to cleanup nftables");
     }
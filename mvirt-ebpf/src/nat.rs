@@ -2,6 +2,7 @@
 
 use ipnet::{Ipv4Net, Ipv6Net};
 use std::io;
+use std::net::Ipv4Addr;
 use std::process::Command;
 use thiserror::Error;
 use tracing::{info, warn};
@@ -20,6 +21,7 @@ pub type Result<T> = std::result::Result<T, NatError>;
 
 const TABLE_NAME: &str = "mvirt_ebpf";
 const NAT_CHAIN: &str = "postrouting";
+const DNAT_CHAIN: &str = "prerouting";
 
 /// Get the default outbound interface (the one with the default route).
 pub fn get_default_interface() -> Result<String> {
@@ -84,6 +86,26 @@ pub fn init_nftables() -> Result<()> {
         }
     }
 
+    // Create NAT chain for prerouting (port forwards)
+    let output = Command::new("nft")
+        .args([
+            "add",
+            "chain",
+            "inet",
+            TABLE_NAME,
+            DNAT_CHAIN,
+            "{ type nat hook prerouting priority dstnat; }",
+        ])
+        .output()
+        .map_err(NatError::Command)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if !stderr.contains("exists") {
+            return Err(NatError::NftFailed(stderr.to_string()));
+        }
+    }
+
     info!(table = TABLE_NAME, "nftables initialized");
     Ok(())
 }
@@ -210,6 +232,94 @@ pub fn remove_masquerade_v6(prefix: Ipv6Net, out_iface: &str) -> Result<()> {
     Ok(())
 }
 
+/// Port forward rule: `protocol` ("tcp" or "udp") traffic for
+/// `host_ip:host_port` goes to `vm_ip:vm_port`. The kernel's connection
+/// tracking translates the replies back.
+fn port_forward_rule(
+    protocol: &str,
+    host_ip: Ipv4Addr,
+    host_port: u16,
+    vm_ip: Ipv4Addr,
+    vm_port: u16,
+) -> String {
+    format!(
+        "{} dnat ip to {}:{}",
+        port_forward_match(protocol, host_ip, host_port),
+        vm_ip,
+        vm_port
+    )
+}
+
+/// Match part of a port forward rule, as `nft list` prints it.
+fn port_forward_match(protocol: &str, host_ip: Ipv4Addr, host_port: u16) -> String {
+    format!("ip daddr {} {} dport {} ", host_ip, protocol, host_port)
+}
+
+/// Add a DNAT rule forwarding a host port to a VM.
+pub fn add_port_forward(
+    protocol: &str,
+    host_ip: Ipv4Addr,
+    host_port: u16,
+    vm_ip: Ipv4Addr,
+    vm_port: u16,
+) -> Result<()> {
+    let rule = port_forward_rule(protocol, host_ip, host_port, vm_ip, vm_port);
+
+    let output = Command::new("nft")
+        .args(["add", "rule", "inet", TABLE_NAME, DNAT_CHAIN, &rule])
+        .output()
+        .map_err(NatError::Command)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(NatError::NftFailed(stderr.to_string()));
+    }
+
+    info!(protocol, host_ip = %host_ip, host_port, vm_ip = %vm_ip, vm_port, "Port forward rule added");
+    Ok(())
+}
+
+/// Remove the DNAT rule of a forwarded host port.
+pub fn remove_port_forward(protocol: &str, host_ip: Ipv4Addr, host_port: u16) -> Result<()> {
+    let output = Command::new("nft")
+        .args(["-a", "list", "chain", "inet", TABLE_NAME, DNAT_CHAIN])
+        .output()
+        .map_err(NatError::Command)?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(NatError::NftFailed(stderr.to_string()));
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let search_pattern = port_forward_match(protocol, host_ip, host_port);
+
+    for line in stdout.lines() {
+        if line.contains(&search_pattern)
+            && let Some(handle) = extract_handle(line)
+        {
+            let output = Command::new("nft")
+                .args([
+                    "delete",
+                    "rule",
+                    "inet",
+                    TABLE_NAME,
+                    DNAT_CHAIN,
+                    "handle",
+                    &handle.to_string(),
+                ])
+                .output()
+                .map_err(NatError::Command)?;
+
+            if output.status.success() {
+                info!(protocol, host_ip = %host_ip, host_port, "Port forward rule removed");
+            }
+        }
+    }
+
+    Ok(())
+}
+
 /// Clean up all nftables rules on shutdown.
 pub fn cleanup_nftables() -> Result<()> {
     let output = Command::new("nft")
@@ -251,4 +361,21 @@ mod tests {
         let line = "  ip saddr 10.0.0.0/24 oifname eth0 masquerade";
         assert_eq!(extract_handle(line), None);
     }
+
+    #[test]
+    fn test_port_forward_rule() {
+        let host: Ipv4Addr = "203.0.113.10".parse().unwrap();
+        let vm: Ipv4Addr = "10.0.0.2".parse().unwrap();
+        assert_eq!(
+            port_forward_rule("tcp", host, 8080, vm, 80),
+            "ip daddr 203.0.113.10 tcp dport 8080 dnat ip to 10.0.0.2:80"
+        );
+
+        // Listed rules are found by their match, not by a shorter port
+        let listed = "  ip daddr 203.0.113.10 tcp dport 8080 dnat ip to 10.0.0.2:80 # handle 7";
+        assert!(listed.contains(&port_forward_match("tcp", host, 8080)));
+        assert!(!listed.contains(&port_forward_match("tcp", host, 80)));
+        assert!(!listed.contains(&port_forward_match("udp", host, 8080)));
+        assert_eq!(extract_handle(listed), Some(7));
+    }
 }
@@ -0,0 +1,173 @@
+//! Removal of TAP devices no NIC owns.
+//!
+//! TAP devices are created persistent so cloud-hypervisor can open them,
+//! which also means they outlive everything else: a crash between creating
+//! a TAP and storing its NIC, or between deleting a NIC and its device,
+//! leaves the device on the host with nothing to ever delete it. The sweeper
+//! lists the host's `tap_*` devices named like ours, compares them against
+//! the NICs in the store and deletes the rest, at startup and then on an
+//! interval.
+//!
+//! A NIC is stored before its TAP is created and deleted while its TAP
+//! still exists, so a periodic pass can catch a NIC in the middle of
+//! either. Only devices found unclaimed by two passes in a row are deleted;
+//! the startup pass, which runs before any request is served, deletes
+//! right away.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time;
+use tracing::{debug, info, warn};
+
+use crate::audit::EbpfAuditLogger;
+use crate::grpc::Storage;
+use crate::tap::{delete_tap_interface, is_nic_tap_name};
+
+/// Default sweep interval in seconds
+pub const DEFAULT_SWEEP_INTERVAL_SECS: u64 = 300;
+
+const SYS_CLASS_NET: &str = "/sys/class/net";
+
+/// Names in `existing` that aren't `referenced`.
+pub fn orphans(existing: Vec<String>, referenced: &HashSet<String>) -> Vec<String> {
+    existing
+        .into_iter()
+        .filter(|name| !referenced.contains(name))
+        .collect()
+}
+
+/// The part of `found` that `suspects` already held; `found` replaces
+/// `suspects` for the next pass.
+pub fn confirmed(suspects: &mut HashSet<String>, found: Vec<String>) -> Vec<String> {
+    let confirmed = found
+        .iter()
+        .filter(|name| suspects.contains(*name))
+        .cloned()
+        .collect();
+    *suspects = found.into_iter().collect();
+    confirmed
+}
+
+/// Host devices named like NIC TAPs.
+fn list_taps() -> std::io::Result<Vec<String>> {
+    let mut taps = Vec::new();
+    for entry in std::fs::read_dir(SYS_CLASS_NET)? {
+        if let Some(name) = entry?.file_name().to_str()
+            && is_nic_tap_name(name)
+        {
+            taps.push(name.to_string());
+        }
+    }
+    Ok(taps)
+}
+
+/// Unclaimed TAP devices, or `None` if the devices or the store couldn't
+/// be listed.
+fn find_orphans(storage: &Storage) -> Option<Vec<String>> {
+    let taps = match list_taps() {
+        Ok(taps) => taps,
+        Err(e) => {
+            warn!(error = %e, "Failed to list network devices");
+            return None;
+        }
+    };
+    let referenced = match storage.list_nics() {
+        Ok(nics) => nics.into_iter().map(|nic| nic.tap_name).collect(),
+        Err(e) => {
+            warn!(error = %e, "Failed to list NICs for orphan sweep");
+            return None;
+        }
+    };
+    Some(orphans(taps, &referenced))
+}
+
+async fn remove(orphans: Vec<String>, audit: &EbpfAuditLogger) {
+    for name in orphans {
+        match delete_tap_interface(&name).await {
+            Ok(()) => {
+                info!(tap = %name, "Deleted orphaned TAP device");
+                audit.orphan_removed("TAP device", &name);
+            }
+            Err(e) => warn!(tap = %name, error = %e, "Failed to delete orphaned TAP device"),
+        }
+    }
+}
+
+/// Periodic TAP sweeper.
+pub struct OrphanSweeper {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl OrphanSweeper {
+    /// Delete every unclaimed TAP device now. Call before serving requests.
+    pub async fn sweep(storage: &Storage, audit: &EbpfAuditLogger) {
+        if let Some(orphans) = find_orphans(storage) {
+            remove(orphans, audit).await;
+        }
+    }
+
+    /// Start a new sweeper task.
+    pub fn start(storage: Arc<Storage>, audit: Arc<EbpfAuditLogger>, interval: Duration) -> Self {
+        info!(interval = ?interval, "TAP orphan sweeper started");
+
+        let task = tokio::spawn(sweep_loop(storage, audit, interval));
+        Self { task }
+    }
+
+    /// Stop the sweeper task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("TAP orphan sweeper stopped");
+    }
+}
+
+/// Main sweeper loop.
+async fn sweep_loop(storage: Arc<Storage>, audit: Arc<EbpfAuditLogger>, interval: Duration) {
+    let mut interval = time::interval(interval);
+    // Skip the immediate tick; the startup sweep covered it
+    interval.tick().await;
+    let mut suspects = HashSet::new();
+
+    loop {
+        interval.tick().await;
+
+        let Some(found) = find_orphans(&storage) else {
+            continue;
+        };
+        debug!(suspects = found.len(), "TAP orphan sweep");
+        remove(confirmed(&mut suspects, found), &audit).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_orphans() {
+        let referenced: HashSet<String> = names(&["tap_1234567"]).into_iter().collect();
+        assert_eq!(
+            orphans(names(&["tap_1234567", "tap_abcdef0"]), &referenced),
+            names(&["tap_abcdef0"])
+        );
+    }
+
+    #[test]
+    fn test_confirmed_after_second_pass() {
+        let mut suspects = HashSet::new();
+        assert!(confirmed(&mut suspects, names(&["tap_1111111", "tap_2222222"])).is_empty());
+        // tap_2222222's NIC was stored meanwhile
+        assert_eq!(
+            confirmed(&mut suspects, names(&["tap_1111111"])),
+            names(&["tap_1111111"])
+        );
+        assert!(confirmed(&mut suspects, Vec::new()).is_empty());
+        assert!(suspects.is_empty());
+    }
+}
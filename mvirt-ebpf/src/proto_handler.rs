@@ -1,6 +1,7 @@
 //! Protocol handlers for DHCP, ARP, and NDP via AF_PACKET raw sockets.
 
 use crate::grpc::storage::{NetworkData, NicData};
+use crate::proto_limits::{ProtoGuard, ProtoLimits, ProtoStats, ProtoStatsSnapshot};
 use dhcproto::v4::{
     Decodable, DhcpOption, Encodable, Message as DhcpMessage, MessageType as DhcpMessageType,
     Opcode,
@@ -19,10 +20,11 @@ use std::io;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
 use std::sync::Arc;
+use std::time::Instant;
 use thiserror::Error;
 use tokio::io::unix::AsyncFd;
 use tokio::sync::RwLock;
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
 /// Fixed gateway MAC address (same as mvirt-net for consistency).
 /// Uses locally administered, unicast format.
@@ -61,19 +63,36 @@ pub struct NicConfig {
 pub struct ProtocolHandler {
     /// Configurations indexed by TAP interface index
     configs: Arc<RwLock<HashMap<u32, NicConfig>>>,
+    /// Responder counters indexed by TAP interface index
+    stats: Arc<RwLock<HashMap<u32, Arc<ProtoStats>>>>,
+    /// Rate limits applied to each NIC's handler
+    limits: ProtoLimits,
 }
 
 impl ProtocolHandler {
     pub fn new() -> Self {
         Self {
             configs: Arc::new(RwLock::new(HashMap::new())),
+            stats: Arc::new(RwLock::new(HashMap::new())),
+            limits: ProtoLimits::default(),
         }
     }
 
+    /// Set the limits for handlers spawned after this call.
+    pub fn with_limits(mut self, limits: ProtoLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
     /// Register a NIC for protocol handling.
     pub async fn register_nic(&self, if_index: u32, nic: NicData, network: NetworkData) {
         let mut configs = self.configs.write().await;
         configs.insert(if_index, NicConfig { nic, network });
+        self.stats
+            .write()
+            .await
+            .entry(if_index)
+            .or_insert_with(Default::default);
         info!(if_index, "NIC registered for protocol handling");
     }
 
@@ -81,18 +100,51 @@ impl ProtocolHandler {
     pub async fn unregister_nic(&self, if_index: u32) {
         let mut configs = self.configs.write().await;
         configs.remove(&if_index);
+        if let Some(stats) = self.stats.write().await.remove(&if_index) {
+            let stats = stats.snapshot();
+            if stats.rate_limited + stats.malformed + stats.dhcp_rejected > 0 {
+                warn!(if_index, ?stats, "NIC protocol handler dropped packets");
+            }
+        }
         info!(if_index, "NIC unregistered from protocol handling");
     }
 
+    /// Responder counters of a registered NIC.
+    pub async fn stats(&self, if_index: u32) -> Option<ProtoStatsSnapshot> {
+        let stats = self.stats.read().await;
+        stats.get(&if_index).map(|s| s.snapshot())
+    }
+
+    /// Admission guard for a handler task, sharing the NIC's counters.
+    async fn guard(
+        stats: &RwLock<HashMap<u32, Arc<ProtoStats>>>,
+        limits: ProtoLimits,
+        if_index: u32,
+    ) -> (ProtoGuard, Arc<ProtoStats>) {
+        let stats = Arc::clone(
+            stats
+                .write()
+                .await
+                .entry(if_index)
+                .or_insert_with(Default::default),
+        );
+        let guard = ProtoGuard::new(limits, Arc::clone(&stats), Instant::now());
+        (guard, stats)
+    }
+
     /// Spawn a handler task for a TAP interface.
     ///
     /// Note: This uses AF_PACKET sockets which work for bridged interfaces
     /// but not for direct TAP access. Use `spawn_handler_with_fd` for TAP devices.
     pub fn spawn_handler(&self, tap_name: String, if_index: u32) -> tokio::task::JoinHandle<()> {
         let configs = Arc::clone(&self.configs);
+        let stats = Arc::clone(&self.stats);
+        let limits = self.limits.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = run_handler_af_packet(tap_name.clone(), if_index, configs).await {
+            let guard = Self::guard(&stats, limits, if_index).await;
+            if let Err(e) = run_handler_af_packet(tap_name.clone(), if_index, configs, guard).await
+            {
                 error!(tap_name, if_index, error = %e, "Protocol handler failed");
             }
         })
@@ -109,9 +161,14 @@ impl ProtocolHandler {
         tap_fd: OwnedFd,
     ) -> tokio::task::JoinHandle<()> {
         let configs = Arc::clone(&self.configs);
+        let stats = Arc::clone(&self.stats);
+        let limits = self.limits.clone();
 
         tokio::spawn(async move {
-            if let Err(e) = run_handler_tap_fd(tap_name.clone(), if_index, tap_fd, configs).await {
+            let guard = Self::guard(&stats, limits, if_index).await;
+            if let Err(e) =
+                run_handler_tap_fd(tap_name.clone(), if_index, tap_fd, configs, guard).await
+            {
                 error!(tap_name, if_index, error = %e, "Protocol handler failed");
             }
         })
@@ -145,6 +202,7 @@ async fn run_handler_tap_fd(
     if_index: u32,
     tap_fd: OwnedFd,
     configs: Arc<RwLock<HashMap<u32, NicConfig>>>,
+    (mut guard, stats): (ProtoGuard, Arc<ProtoStats>),
 ) -> Result<()> {
     // Set non-blocking
     let flags = unsafe { libc::fcntl(tap_fd.as_raw_fd(), libc::F_GETFL) };
@@ -193,6 +251,11 @@ async fn run_handler_tap_fd(
         let packet = &buf[..n];
         debug!(n, "Received packet from TAP fd");
 
+        if let Err(reason) = guard.admit(packet, Instant::now()) {
+            debug!(if_index, ?reason, "Protocol packet dropped");
+            continue;
+        }
+
         // Get config for this interface
         let configs_guard = configs.read().await;
         let config = match configs_guard.get(&if_index) {
@@ -204,6 +267,7 @@ async fn run_handler_tap_fd(
         // Process packet
         if let Some(response) = process_packet(&config, packet) {
             debug!(len = response.len(), "Sending response to TAP fd");
+            stats.record_response();
             // Write response back to TAP
             let mut write_guard = async_fd.writable().await?;
             let _ = write_guard.try_io(|fd| -> io::Result<usize> {
@@ -232,6 +296,7 @@ async fn run_handler_af_packet(
     tap_name: String,
     if_index: u32,
     configs: Arc<RwLock<HashMap<u32, NicConfig>>>,
+    (mut guard, stats): (ProtoGuard, Arc<ProtoStats>),
 ) -> Result<()> {
     // Create raw socket bound to the TAP interface
     let socket = Socket::new(
@@ -313,6 +378,11 @@ async fn run_handler_af_packet(
         let packet = &buf[..n];
         debug!(n, "Received packet on AF_PACKET socket");
 
+        if let Err(reason) = guard.admit(packet, Instant::now()) {
+            debug!(if_index, ?reason, "Protocol packet dropped");
+            continue;
+        }
+
         // Get config for this interface
         let configs_guard = configs.read().await;
         let config = match configs_guard.get(&if_index) {
@@ -324,6 +394,7 @@ async fn run_handler_af_packet(
         // Process packet
         if let Some(response) = process_packet(&config, packet) {
             // Send response
+            stats.record_response();
             let mut write_guard = async_fd.writable().await?;
             let _ = write_guard.try_io(|fd| -> io::Result<usize> {
                 let ret = unsafe {
@@ -637,6 +708,34 @@ fn process_dhcp(
             .insert(DhcpOption::DomainNameServer(dns_v4));
     }
 
+    // Network boot (PXE / UEFI HTTP boot). Only answered for clients that
+    // identify as boot firmware, so the booted OS gets a plain lease.
+    if let Some(boot_file) = &config.network.boot_file {
+        let client_class = match dhcp_msg
+            .opts()
+            .get(dhcproto::v4::OptionCode::ClassIdentifier)
+        {
+            Some(DhcpOption::ClassIdentifier(class)) => class.as_slice(),
+            _ => &[],
+        };
+        let is_url = boot_file.starts_with("http://") || boot_file.starts_with("https://");
+
+        if is_url && client_class.starts_with(b"HTTPClient") {
+            reply
+                .opts_mut()
+                .insert(DhcpOption::ClassIdentifier(b"HTTPClient".to_vec()));
+            reply.set_fname(boot_file.as_bytes());
+        } else if !is_url && client_class.starts_with(b"PXEClient") {
+            reply
+                .opts_mut()
+                .insert(DhcpOption::ClassIdentifier(b"PXEClient".to_vec()));
+            if let Some(server) = config.network.boot_server {
+                reply.set_siaddr(server);
+            }
+            reply.set_fname(boot_file.as_bytes());
+        }
+    }
+
     // Encode DHCP message
     let mut dhcp_buf = Vec::new();
     let mut encoder = dhcproto::encoder::Encoder::new(&mut dhcp_buf);
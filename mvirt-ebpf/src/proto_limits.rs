@@ -0,0 +1,441 @@
+//! Rate limiting and accounting for the userspace protocol responder.
+//!
+//! Every frame the TC program passes up for ARP/DHCP/NDP handling lands in
+//! [`crate::proto_handler`], one task per NIC. A guest can send those as
+//! fast as it likes, so each NIC gets a [`ProtoGuard`] in front of the
+//! responder: a token bucket limiting how many protocol packets are
+//! answered, a cap on DHCP transactions (DISCOVERs without a REQUEST yet),
+//! and counters for malformed and dropped packets.
+
+use crate::proto_handler::{GATEWAY_IPV4_LINK_LOCAL, GATEWAY_IPV6_LINK_LOCAL};
+use dhcproto::v4::{Decodable, DhcpOption, Message as DhcpMessage, MessageType, OptionCode};
+use smoltcp::wire::{
+    ArpOperation, ArpPacket, EthernetFrame, EthernetProtocol, Icmpv6Message, Icmpv6Packet,
+    IpProtocol, Ipv4Address, Ipv4Packet, Ipv6Address, Ipv6Packet, UdpPacket,
+};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default sustained rate of answered protocol packets per NIC.
+pub const DEFAULT_RESPONSES_PER_SEC: u32 = 50;
+
+/// Default burst on top of the sustained rate (a booting guest sends DHCP,
+/// DHCPv6, RS and a few NS/ARP at once).
+pub const DEFAULT_RESPONSE_BURST: u32 = 100;
+
+/// Default cap on DHCP transactions in flight per NIC.
+pub const DEFAULT_MAX_PENDING_DHCP: usize = 16;
+
+/// DHCP transactions without a REQUEST are forgotten after this long.
+const DHCP_TRANSACTION_TIMEOUT: Duration = Duration::from_secs(60);
+
+const IPV4_HEADER_LEN: usize = 20;
+const IPV6_HEADER_LEN: usize = 40;
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const DHCPV6_SERVER_PORT: u16 = 547;
+
+/// Limits applied to each NIC's protocol responder.
+#[derive(Debug, Clone)]
+pub struct ProtoLimits {
+    /// Sustained protocol packets answered per second; 0 disables the limit
+    pub responses_per_sec: u32,
+    /// Packets that may be answered at once before the rate applies
+    pub burst: u32,
+    /// DHCP DISCOVERs awaiting a REQUEST; further DISCOVERs are dropped
+    pub max_pending_dhcp: usize,
+}
+
+impl Default for ProtoLimits {
+    fn default() -> Self {
+        Self {
+            responses_per_sec: DEFAULT_RESPONSES_PER_SEC,
+            burst: DEFAULT_RESPONSE_BURST,
+            max_pending_dhcp: DEFAULT_MAX_PENDING_DHCP,
+        }
+    }
+}
+
+/// Per-NIC responder counters.
+#[derive(Debug, Default)]
+pub struct ProtoStats {
+    responses: AtomicU64,
+    rate_limited: AtomicU64,
+    malformed: AtomicU64,
+    dhcp_rejected: AtomicU64,
+}
+
+impl ProtoStats {
+    pub fn record_response(&self) {
+        self.responses.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> ProtoStatsSnapshot {
+        ProtoStatsSnapshot {
+            responses: self.responses.load(Ordering::Relaxed),
+            rate_limited: self.rate_limited.load(Ordering::Relaxed),
+            malformed: self.malformed.load(Ordering::Relaxed),
+            dhcp_rejected: self.dhcp_rejected.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Point-in-time copy of [`ProtoStats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtoStatsSnapshot {
+    /// Responses sent to the guest
+    pub responses: u64,
+    /// Protocol packets dropped by the token bucket
+    pub rate_limited: u64,
+    /// Frames that failed to parse as the protocol they claimed to be
+    pub malformed: u64,
+    /// DHCP DISCOVERs dropped because too many transactions were pending
+    pub dhcp_rejected: u64,
+}
+
+/// Why a frame was not handed to the responder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rejection {
+    Malformed,
+    RateLimited,
+    DhcpPendingLimit,
+}
+
+/// Token bucket refilled continuously at `rate` tokens per second.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: u32, burst: u32, now: Instant) -> Self {
+        let capacity = burst.max(1) as f64;
+        Self {
+            rate: rate as f64,
+            capacity,
+            tokens: capacity,
+            last: now,
+        }
+    }
+
+    fn try_take(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+        self.last = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Admission control for one NIC's protocol responder.
+pub struct ProtoGuard {
+    limits: ProtoLimits,
+    bucket: TokenBucket,
+    /// Pending DHCP transactions: xid -> time of the last DISCOVER
+    dhcp_pending: HashMap<u32, Instant>,
+    stats: Arc<ProtoStats>,
+}
+
+impl ProtoGuard {
+    pub fn new(limits: ProtoLimits, stats: Arc<ProtoStats>, now: Instant) -> Self {
+        let bucket = TokenBucket::new(limits.responses_per_sec, limits.burst, now);
+        Self {
+            limits,
+            bucket,
+            dhcp_pending: HashMap::new(),
+            stats,
+        }
+    }
+
+    /// Decide whether `packet` may be processed. Frames that aren't for the
+    /// responder are admitted without taking a token; the responder ignores
+    /// them.
+    pub fn admit(&mut self, packet: &[u8], now: Instant) -> Result<(), Rejection> {
+        let kind = match classify(packet) {
+            Ok(Some(kind)) => kind,
+            Ok(None) => return Ok(()),
+            Err(()) => {
+                self.stats.malformed.fetch_add(1, Ordering::Relaxed);
+                return Err(Rejection::Malformed);
+            }
+        };
+
+        if let ProtoKind::Dhcp { msg_type, xid } = kind
+            && !self.track_dhcp(msg_type, xid, now)
+        {
+            self.stats.dhcp_rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(Rejection::DhcpPendingLimit);
+        }
+
+        if self.limits.responses_per_sec > 0 && !self.bucket.try_take(now) {
+            self.stats.rate_limited.fetch_add(1, Ordering::Relaxed);
+            return Err(Rejection::RateLimited);
+        }
+        Ok(())
+    }
+
+    /// Track a DHCP message. DISCOVER opens (or refreshes) a transaction,
+    /// REQUEST, DECLINE and RELEASE close it. Returns false if a new
+    /// transaction would exceed the cap.
+    fn track_dhcp(&mut self, msg_type: MessageType, xid: u32, now: Instant) -> bool {
+        self.dhcp_pending.retain(|_, started| {
+            now.saturating_duration_since(*started) < DHCP_TRANSACTION_TIMEOUT
+        });
+
+        match msg_type {
+            MessageType::Discover => {
+                if !self.dhcp_pending.contains_key(&xid)
+                    && self.dhcp_pending.len() >= self.limits.max_pending_dhcp
+                {
+                    return false;
+                }
+                self.dhcp_pending.insert(xid, now);
+            }
+            MessageType::Request | MessageType::Decline | MessageType::Release => {
+                self.dhcp_pending.remove(&xid);
+            }
+            _ => {}
+        }
+        true
+    }
+}
+
+/// Protocol packets the responder answers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProtoKind {
+    Arp,
+    Dhcp { msg_type: MessageType, xid: u32 },
+    Icmp,
+    Icmpv6,
+    Dhcpv6,
+}
+
+/// Classify a frame: `Ok(None)` if the responder wouldn't answer it,
+/// `Err` if it is addressed to the responder but doesn't parse.
+///
+/// The raw socket also sees the guest's regular traffic (including GSO
+/// frames truncated by the receive buffer), so IP headers are only checked
+/// in full once the frame turned out to be for the responder.
+fn classify(packet: &[u8]) -> Result<Option<ProtoKind>, ()> {
+    let eth = EthernetFrame::new_checked(packet).map_err(|_| ())?;
+    let payload = eth.payload();
+    match eth.ethertype() {
+        EthernetProtocol::Arp => {
+            let arp = ArpPacket::new_checked(payload).map_err(|_| ())?;
+            Ok((arp.operation() == ArpOperation::Request).then_some(ProtoKind::Arp))
+        }
+        EthernetProtocol::Ipv4 if payload.len() >= IPV4_HEADER_LEN => {
+            let gateway = Ipv4Address::from_bytes(&GATEWAY_IPV4_LINK_LOCAL.octets());
+            let ip = Ipv4Packet::new_unchecked(payload);
+            match ip.next_header() {
+                IpProtocol::Icmp if ip.dst_addr() == gateway => {
+                    Ipv4Packet::new_checked(payload).map_err(|_| ())?;
+                    Ok(Some(ProtoKind::Icmp))
+                }
+                IpProtocol::Udp => {
+                    let ip = Ipv4Packet::new_checked(payload).map_err(|_| ())?;
+                    let udp = UdpPacket::new_checked(ip.payload()).map_err(|_| ())?;
+                    if udp.src_port() != DHCP_CLIENT_PORT || udp.dst_port() != DHCP_SERVER_PORT {
+                        return Ok(None);
+                    }
+                    let msg =
+                        DhcpMessage::decode(&mut dhcproto::decoder::Decoder::new(udp.payload()))
+                            .map_err(|_| ())?;
+                    match msg.opts().get(OptionCode::MessageType) {
+                        Some(DhcpOption::MessageType(msg_type)) => Ok(Some(ProtoKind::Dhcp {
+                            msg_type: *msg_type,
+                            xid: msg.xid(),
+                        })),
+                        _ => Err(()),
+                    }
+                }
+                _ => Ok(None),
+            }
+        }
+        EthernetProtocol::Ipv6 if payload.len() >= IPV6_HEADER_LEN => {
+            let ip = Ipv6Packet::new_unchecked(payload);
+            match ip.next_header() {
+                IpProtocol::Icmpv6 => {
+                    let ip = Ipv6Packet::new_checked(payload).map_err(|_| ())?;
+                    let icmp = Icmpv6Packet::new_checked(ip.payload()).map_err(|_| ())?;
+                    let gateway = Ipv6Address::from_bytes(&GATEWAY_IPV6_LINK_LOCAL.octets());
+                    let answered = match icmp.msg_type() {
+                        Icmpv6Message::NeighborSolicit | Icmpv6Message::RouterSolicit => true,
+                        Icmpv6Message::EchoRequest => ip.dst_addr() == gateway,
+                        _ => false,
+                    };
+                    Ok(answered.then_some(ProtoKind::Icmpv6))
+                }
+                IpProtocol::Udp => {
+                    let ip = Ipv6Packet::new_checked(payload).map_err(|_| ())?;
+                    let udp = UdpPacket::new_checked(ip.payload()).map_err(|_| ())?;
+                    Ok((udp.dst_port() == DHCPV6_SERVER_PORT).then_some(ProtoKind::Dhcpv6))
+                }
+                _ => Ok(None),
+            }
+        }
+        EthernetProtocol::Ipv4 | EthernetProtocol::Ipv6 => Err(()),
+        _ => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dhcproto::v4::{Encodable, Opcode};
+    use smoltcp::phy::ChecksumCapabilities;
+    use smoltcp::wire::{EthernetAddress, EthernetRepr, IpAddress, Ipv4Repr, UdpRepr};
+
+    const VM_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+    fn dhcp_frame(msg_type: MessageType, xid: u32) -> Vec<u8> {
+        let mut msg = DhcpMessage::default();
+        msg.set_opcode(Opcode::BootRequest);
+        msg.set_xid(xid);
+        msg.set_chaddr(&VM_MAC);
+        msg.opts_mut().insert(DhcpOption::MessageType(msg_type));
+        let mut dhcp = Vec::new();
+        msg.encode(&mut dhcproto::encoder::Encoder::new(&mut dhcp))
+            .unwrap();
+
+        let udp_repr = UdpRepr {
+            src_port: 68,
+            dst_port: 67,
+        };
+        let ip_repr = Ipv4Repr {
+            src_addr: Ipv4Address::UNSPECIFIED,
+            dst_addr: Ipv4Address::BROADCAST,
+            next_header: IpProtocol::Udp,
+            payload_len: udp_repr.header_len() + dhcp.len(),
+            hop_limit: 64,
+        };
+        let eth_repr = EthernetRepr {
+            src_addr: EthernetAddress(VM_MAC),
+            dst_addr: EthernetAddress::BROADCAST,
+            ethertype: EthernetProtocol::Ipv4,
+        };
+
+        let mut buf = vec![0u8; eth_repr.buffer_len() + ip_repr.buffer_len() + ip_repr.payload_len];
+        let mut eth = EthernetFrame::new_unchecked(&mut buf);
+        eth_repr.emit(&mut eth);
+        let mut ip = Ipv4Packet::new_unchecked(eth.payload_mut());
+        ip_repr.emit(&mut ip, &ChecksumCapabilities::default());
+        let mut udp = UdpPacket::new_unchecked(ip.payload_mut());
+        udp_repr.emit(
+            &mut udp,
+            &IpAddress::Ipv4(ip_repr.src_addr),
+            &IpAddress::Ipv4(ip_repr.dst_addr),
+            dhcp.len(),
+            |b| b.copy_from_slice(&dhcp),
+            &ChecksumCapabilities::default(),
+        );
+        buf
+    }
+
+    fn guard(limits: ProtoLimits) -> (ProtoGuard, Arc<ProtoStats>, Instant) {
+        let stats = Arc::new(ProtoStats::default());
+        let now = Instant::now();
+        (ProtoGuard::new(limits, Arc::clone(&stats), now), stats, now)
+    }
+
+    #[test]
+    fn test_token_bucket_limits_and_refills() {
+        let (mut guard, stats, now) = guard(ProtoLimits {
+            responses_per_sec: 10,
+            burst: 5,
+            max_pending_dhcp: 16,
+        });
+        let request = dhcp_frame(MessageType::Request, 1);
+
+        for _ in 0..5 {
+            assert_eq!(guard.admit(&request, now), Ok(()));
+        }
+        assert_eq!(guard.admit(&request, now), Err(Rejection::RateLimited));
+
+        // 10/s refills one token every 100ms
+        let later = now + Duration::from_millis(100);
+        assert_eq!(guard.admit(&request, later), Ok(()));
+        assert_eq!(guard.admit(&request, later), Err(Rejection::RateLimited));
+        assert_eq!(stats.snapshot().rate_limited, 2);
+    }
+
+    #[test]
+    fn test_dhcp_pending_cap() {
+        let (mut guard, stats, now) = guard(ProtoLimits {
+            responses_per_sec: 0,
+            burst: 0,
+            max_pending_dhcp: 2,
+        });
+
+        assert!(
+            guard
+                .admit(&dhcp_frame(MessageType::Discover, 1), now)
+                .is_ok()
+        );
+        assert!(
+            guard
+                .admit(&dhcp_frame(MessageType::Discover, 2), now)
+                .is_ok()
+        );
+        // Retransmitted DISCOVER of a known transaction is fine
+        assert!(
+            guard
+                .admit(&dhcp_frame(MessageType::Discover, 2), now)
+                .is_ok()
+        );
+        assert_eq!(
+            guard.admit(&dhcp_frame(MessageType::Discover, 3), now),
+            Err(Rejection::DhcpPendingLimit)
+        );
+
+        // REQUEST completes a transaction and frees a slot
+        assert!(
+            guard
+                .admit(&dhcp_frame(MessageType::Request, 1), now)
+                .is_ok()
+        );
+        assert!(
+            guard
+                .admit(&dhcp_frame(MessageType::Discover, 3), now)
+                .is_ok()
+        );
+
+        // Stale transactions expire
+        let later = now + DHCP_TRANSACTION_TIMEOUT;
+        assert!(
+            guard
+                .admit(&dhcp_frame(MessageType::Discover, 4), later)
+                .is_ok()
+        );
+        assert_eq!(stats.snapshot().dhcp_rejected, 1);
+    }
+
+    #[test]
+    fn test_malformed_and_unrelated_frames() {
+        let (mut guard, stats, now) = guard(ProtoLimits::default());
+
+        // Truncated DHCP payload
+        let mut frame = dhcp_frame(MessageType::Discover, 1);
+        frame.truncate(14 + 20 + 8 + 10);
+        assert_eq!(guard.admit(&frame, now), Err(Rejection::Malformed));
+
+        // Too short for an Ethernet header
+        assert_eq!(guard.admit(&[0u8; 6], now), Err(Rejection::Malformed));
+
+        // Unknown ethertype is not the responder's business
+        let mut other = vec![0u8; 64];
+        other[12..14].copy_from_slice(&0x88b5u16.to_be_bytes());
+        assert_eq!(guard.admit(&other, now), Ok(()));
+
+        assert_eq!(stats.snapshot().malformed, 2);
+    }
+}
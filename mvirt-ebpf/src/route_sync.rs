@@ -0,0 +1,403 @@
+//! Overlay routes to NICs on other hosts.
+//!
+//! Every host owns a /80 overlay prefix. Packets a VM sends to a NIC on
+//! another host are wrapped in an IPv6 header addressed into that host's
+//! prefix (see TUNNEL_ENDPOINTS in the egress program) and handed to the
+//! kernel, which routes them over the uplink.
+//!
+//! There is no cluster-wide allocation stream yet, so the routes come from
+//! the hosts themselves: for each configured peer this task follows the
+//! `WatchAllocations` stream of its net daemon (mvirt-net and mvirt-ebpf
+//! serve the same one) and points the addresses and routed prefixes of the
+//! peer's NICs at the peer's overlay prefix. A prefix two peers announce,
+//! e.g. mid-migration, goes to the one configured first.
+//!
+//! Changes are batched for a moment before the maps are updated. A peer
+//! whose stream drops keeps its routes until it is back and has replayed
+//! its allocations; dropping them would cut traffic the peer most likely
+//! still forwards.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::{self, Instant};
+use tonic::transport::{ClientTlsConfig, Endpoint};
+use tracing::{debug, info, warn};
+
+use crate::ebpf_loader::EbpfManager;
+use crate::grpc::proto::net_service_client::NetServiceClient;
+use crate::grpc::proto::{Allocation, AllocationEventType, WatchAllocationsRequest};
+
+/// Allocation changes are batched for this long before routes are updated.
+const SETTLE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Delay before reconnecting to a peer whose stream dropped.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// A host's /80 overlay prefix: the upper 10 bytes of the outer addresses
+/// of tunnelled packets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverlayPrefix(pub [u8; 10]);
+
+impl FromStr for OverlayPrefix {
+    type Err = String;
+
+    /// `fd00:1:2:3:4::` or `fd00:1:2:3:4::/80`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let addr = s.strip_suffix("/80").unwrap_or(s);
+        let addr: Ipv6Addr = addr
+            .parse()
+            .map_err(|_| format!("invalid overlay prefix '{}'", s))?;
+        Self::try_from(addr)
+    }
+}
+
+impl TryFrom<Ipv6Addr> for OverlayPrefix {
+    type Error = String;
+
+    fn try_from(addr: Ipv6Addr) -> Result<Self, Self::Error> {
+        let octets = addr.octets();
+        if octets[10..].iter().any(|&b| b != 0) {
+            return Err(format!("overlay prefix {} has bits set past /80", addr));
+        }
+        let mut prefix = [0u8; 10];
+        prefix.copy_from_slice(&octets[..10]);
+        Ok(Self(prefix))
+    }
+}
+
+impl fmt::Display for OverlayPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut octets = [0u8; 16];
+        octets[..10].copy_from_slice(&self.0);
+        write!(f, "{}/80", Ipv6Addr::from(octets))
+    }
+}
+
+/// Another host: its overlay prefix and the gRPC endpoint of its net
+/// daemon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Peer {
+    pub prefix: OverlayPrefix,
+    pub endpoint: String,
+}
+
+impl FromStr for Peer {
+    type Err = String;
+
+    /// `<overlay prefix>=<endpoint>`, e.g. `fd00:0:0:2::=http://[fd00::2]:50054`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (prefix, endpoint) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected <prefix>=<endpoint>, got '{}'", s))?;
+        let endpoint = endpoint.trim();
+        if endpoint.is_empty() {
+            return Err(format!("missing endpoint in '{}'", s));
+        }
+        Ok(Self {
+            prefix: prefix.trim().parse()?,
+            endpoint: endpoint.to_string(),
+        })
+    }
+}
+
+/// Addresses and routed prefixes of an allocation; unparsable entries are
+/// skipped.
+fn allocation_prefixes(alloc: &Allocation) -> Vec<IpNet> {
+    let mut prefixes = Vec::new();
+    if let Ok(addr) = alloc.ipv4_address.parse::<Ipv4Addr>() {
+        prefixes.push(IpNet::V4(Ipv4Net::from(addr)));
+    }
+    if let Ok(addr) = alloc.ipv6_address.parse::<Ipv6Addr>() {
+        prefixes.push(IpNet::V6(Ipv6Net::from(addr)));
+    }
+    for prefix in alloc
+        .routed_ipv4_prefixes
+        .iter()
+        .chain(&alloc.routed_ipv6_prefixes)
+    {
+        match prefix.parse::<IpNet>() {
+            Ok(net) => prefixes.push(net.trunc()),
+            Err(_) => {
+                debug!(nic_id = %alloc.nic_id, prefix = %prefix, "Skipping invalid routed prefix")
+            }
+        }
+    }
+    prefixes
+}
+
+/// Overlay route per destination for the allocations of each peer, given
+/// in peer order.
+pub fn desired_routes<'a>(
+    peers: impl IntoIterator<Item = (OverlayPrefix, &'a HashMap<String, Allocation>)>,
+) -> BTreeMap<IpNet, OverlayPrefix> {
+    let mut routes = BTreeMap::new();
+    for (prefix, allocations) in peers {
+        for alloc in allocations.values() {
+            for net in allocation_prefixes(alloc) {
+                routes.entry(net).or_insert(prefix);
+            }
+        }
+    }
+    routes
+}
+
+/// What a peer's stream reports to the sync task.
+enum Update {
+    /// A new stream; the peer's allocations follow
+    Reset,
+    Added(Allocation),
+    Removed(String),
+}
+
+/// Overlay route synchronization for a set of peers.
+pub struct RouteSync {
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl RouteSync {
+    /// Start following the peers. `tls` is used for `https://` endpoints.
+    pub fn start(ebpf: Arc<EbpfManager>, peers: Vec<Peer>, tls: Option<ClientTlsConfig>) -> Self {
+        info!(peers = peers.len(), "Overlay route sync started");
+
+        let (tx, rx) = mpsc::channel(256);
+        let mut tasks: Vec<JoinHandle<()>> = peers
+            .iter()
+            .enumerate()
+            .map(|(i, peer)| tokio::spawn(watch_peer(i, peer.clone(), tls.clone(), tx.clone())))
+            .collect();
+        let prefixes = peers.iter().map(|p| p.prefix).collect();
+        tasks.push(tokio::spawn(sync_loop(ebpf, prefixes, rx)));
+        Self { tasks }
+    }
+
+    /// Stop following the peers. Programmed routes stay until the programs
+    /// are unloaded.
+    pub fn stop(self) {
+        for task in self.tasks {
+            task.abort();
+        }
+        info!("Overlay route sync stopped");
+    }
+}
+
+/// Follow one peer's allocation stream, reconnecting when it drops.
+async fn watch_peer(
+    index: usize,
+    peer: Peer,
+    tls: Option<ClientTlsConfig>,
+    tx: mpsc::Sender<(usize, Update)>,
+) {
+    loop {
+        if let Err(e) = follow(index, &peer, tls.as_ref(), &tx).await {
+            warn!(peer = %peer.endpoint, error = %e, "Allocation stream of peer lost");
+        }
+        if tx.is_closed() {
+            return;
+        }
+        time::sleep(RECONNECT_DELAY).await;
+    }
+}
+
+async fn follow(
+    index: usize,
+    peer: &Peer,
+    tls: Option<&ClientTlsConfig>,
+    tx: &mpsc::Sender<(usize, Update)>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut endpoint = Endpoint::from_shared(peer.endpoint.clone())?;
+    if let Some(tls) = tls
+        && peer.endpoint.starts_with("https://")
+    {
+        endpoint = endpoint.tls_config(tls.clone())?;
+    }
+    let mut client = NetServiceClient::new(endpoint.connect().await?);
+    let mut stream = client
+        .watch_allocations(WatchAllocationsRequest {
+            network_id: String::new(),
+            include_existing: true,
+        })
+        .await?
+        .into_inner();
+    info!(peer = %peer.endpoint, prefix = %peer.prefix, "Following allocations of peer");
+
+    // The stream replays every allocation first
+    if tx.send((index, Update::Reset)).await.is_err() {
+        return Ok(());
+    }
+    while let Some(event) = stream.message().await? {
+        let Some(alloc) = event.allocation else {
+            continue;
+        };
+        let update = if event.r#type == AllocationEventType::Removed as i32 {
+            Update::Removed(alloc.nic_id)
+        } else {
+            Update::Added(alloc)
+        };
+        if tx.send((index, update)).await.is_err() {
+            return Ok(());
+        }
+    }
+    Err("stream closed".into())
+}
+
+/// Collect the peers' allocations and keep the tunnel maps in line.
+async fn sync_loop(
+    ebpf: Arc<EbpfManager>,
+    prefixes: Vec<OverlayPrefix>,
+    mut rx: mpsc::Receiver<(usize, Update)>,
+) {
+    let mut allocations: Vec<HashMap<String, Allocation>> = vec![HashMap::new(); prefixes.len()];
+    let mut programmed: BTreeMap<IpNet, OverlayPrefix> = BTreeMap::new();
+    let mut deadline: Option<Instant> = None;
+
+    loop {
+        let settle = async {
+            match deadline {
+                Some(deadline) => time::sleep_until(deadline).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            update = rx.recv() => {
+                let Some((index, update)) = update else {
+                    return;
+                };
+                match update {
+                    Update::Reset => allocations[index].clear(),
+                    Update::Added(alloc) => {
+                        allocations[index].insert(alloc.nic_id.clone(), alloc);
+                    }
+                    Update::Removed(nic_id) => {
+                        allocations[index].remove(&nic_id);
+                    }
+                }
+                deadline.get_or_insert_with(|| Instant::now() + SETTLE_INTERVAL);
+            }
+            _ = settle => {
+                deadline = None;
+                let desired = desired_routes(prefixes.iter().copied().zip(&allocations));
+                apply(&ebpf, &mut programmed, desired).await;
+            }
+        }
+    }
+}
+
+/// Program the difference between `programmed` and `desired`.
+async fn apply(
+    ebpf: &EbpfManager,
+    programmed: &mut BTreeMap<IpNet, OverlayPrefix>,
+    desired: BTreeMap<IpNet, OverlayPrefix>,
+) {
+    let stale: Vec<IpNet> = programmed
+        .keys()
+        .filter(|net| !desired.contains_key(*net))
+        .copied()
+        .collect();
+    for net in stale {
+        let result = match net {
+            IpNet::V4(net) => {
+                ebpf.remove_tunnel_endpoint_v4(net.network(), net.prefix_len())
+                    .await
+            }
+            IpNet::V6(net) => {
+                ebpf.remove_tunnel_endpoint_v6(net.network(), net.prefix_len())
+                    .await
+            }
+        };
+        match result {
+            Ok(()) => {
+                debug!(destination = %net, "Overlay route removed");
+                programmed.remove(&net);
+            }
+            Err(e) => warn!(destination = %net, error = %e, "Failed to remove overlay route"),
+        }
+    }
+
+    for (net, prefix) in desired {
+        if programmed.get(&net) == Some(&prefix) {
+            continue;
+        }
+        let result = match net {
+            IpNet::V4(net) => {
+                ebpf.add_tunnel_endpoint_v4(net.network(), net.prefix_len(), prefix.0)
+                    .await
+            }
+            IpNet::V6(net) => {
+                ebpf.add_tunnel_endpoint_v6(net.network(), net.prefix_len(), prefix.0)
+                    .await
+            }
+        };
+        match result {
+            Ok(()) => {
+                debug!(destination = %net, via = %prefix, "Overlay route set");
+                programmed.insert(net, prefix);
+            }
+            Err(e) => warn!(destination = %net, error = %e, "Failed to set overlay route"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn alloc(nic_id: &str, v4: &str, v6: &str, routed: &[&str]) -> Allocation {
+        Allocation {
+            nic_id: nic_id.to_string(),
+            ipv4_address: v4.to_string(),
+            ipv6_address: v6.to_string(),
+            routed_ipv4_prefixes: routed.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    fn peer_allocs(allocs: Vec<Allocation>) -> HashMap<String, Allocation> {
+        allocs.into_iter().map(|a| (a.nic_id.clone(), a)).collect()
+    }
+
+    #[test]
+    fn test_parse_peer() {
+        let peer: Peer = "fd00:0:0:2::=http://[fd00::2]:50054".parse().unwrap();
+        assert_eq!(peer.prefix.0, [0xfd, 0, 0, 0, 0, 0, 0, 2, 0, 0]);
+        assert_eq!(peer.endpoint, "http://[fd00::2]:50054");
+        assert_eq!(peer.prefix.to_string(), "fd00:0:0:2::/80");
+
+        let peer: Peer = "fd00:0:0:2:1::/80 = https://hv-2:50054".parse().unwrap();
+        assert_eq!(peer.prefix.0[9], 1);
+
+        assert!("fd00::1=http://hv-2:50054".parse::<Peer>().is_err());
+        assert!("http://hv-2:50054".parse::<Peer>().is_err());
+        assert!("fd00::=".parse::<Peer>().is_err());
+    }
+
+    #[test]
+    fn test_desired_routes() {
+        let a: OverlayPrefix = "fd00:0:0:1::".parse().unwrap();
+        let b: OverlayPrefix = "fd00:0:0:2::".parse().unwrap();
+        let first = peer_allocs(vec![
+            alloc("n1", "10.0.0.5", "fd10::5", &["10.1.0.7/24"]),
+            alloc("n2", "", "", &["bogus"]),
+        ]);
+        let second = peer_allocs(vec![
+            alloc("n3", "10.0.0.5", "", &[]),
+            alloc("n4", "10.0.0.6", "", &[]),
+        ]);
+
+        let routes = desired_routes([(a, &first), (b, &second)]);
+        let route = |s: &str| routes.get(&s.parse::<IpNet>().unwrap()).copied();
+        assert_eq!(routes.len(), 4);
+        // Announced by both, the first peer wins
+        assert_eq!(route("10.0.0.5/32"), Some(a));
+        assert_eq!(route("fd10::5/128"), Some(a));
+        assert_eq!(route("10.1.0.0/24"), Some(a));
+        assert_eq!(route("10.0.0.6/32"), Some(b));
+    }
+}
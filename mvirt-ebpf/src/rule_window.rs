@@ -0,0 +1,133 @@
+//! Time-limited and scheduled security group rules.
+//!
+//! A rule may carry a validity window (`not_before`/`not_after`) and a
+//! weekly schedule such as `Mon-Fri 08:00-18:00` (UTC), checked by
+//! [`mvirt_common::rule_window`]. Rules outside their window stay in their
+//! SECURITY_RULES slot with the enabled flag cleared. This task re-evaluates the rules of every active NIC on an
+//! interval and reprograms a NIC when one of its rules opens or closes, so
+//! temporary access grants expire without anyone revoking them.
+//!
+//! Connections admitted while a rule was active keep their conntrack
+//! entry and run until it idles out.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use mvirt_common::rule_window::{RuleSchedule, TimedRule, active_rules};
+use tokio::sync::RwLock;
+use tokio::time;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::ebpf_loader::EbpfManager;
+use crate::grpc::{SecurityGroupRuleData, Storage};
+use crate::security;
+
+/// Default evaluation interval in seconds
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 10;
+
+impl TimedRule for SecurityGroupRuleData {
+    type Id = Uuid;
+
+    fn rule_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn not_before(&self) -> Option<DateTime<Utc>> {
+        self.not_before
+    }
+
+    fn not_after(&self) -> Option<DateTime<Utc>> {
+        self.not_after
+    }
+
+    fn schedule(&self) -> Option<RuleSchedule> {
+        self.schedule
+    }
+}
+
+/// Rule window timer handle.
+pub struct RuleWindowTimer {
+    nics: Arc<WindowNics>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl RuleWindowTimer {
+    /// Start a new timer task.
+    pub fn start(storage: Arc<Storage>, ebpf: Arc<EbpfManager>, interval: Duration) -> Self {
+        let nics = Arc::new(WindowNics::default());
+
+        info!(interval = ?interval, "Security rule window timer started");
+
+        let task = tokio::spawn(timer_loop(storage, ebpf, Arc::clone(&nics), interval));
+        Self { nics, task }
+    }
+
+    /// Registry of active NICs; hand this to the gRPC service.
+    pub fn nics(&self) -> Arc<WindowNics> {
+        Arc::clone(&self.nics)
+    }
+
+    /// Stop the timer task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Security rule window timer stopped");
+    }
+}
+
+/// Main timer loop.
+async fn timer_loop(
+    storage: Arc<Storage>,
+    ebpf: Arc<EbpfManager>,
+    nics: Arc<WindowNics>,
+    interval: Duration,
+) {
+    let mut interval = time::interval(interval);
+    // Active rule set per ifindex as last programmed by this task
+    let mut programmed: HashMap<u32, HashSet<Uuid>> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let nics = nics.snapshot().await;
+        programmed.retain(|ifindex, _| nics.contains_key(ifindex));
+
+        for (ifindex, nic_id) in nics {
+            let (groups, rules) = match (
+                storage.list_security_groups_for_nic(&nic_id),
+                storage.get_all_rules_for_nic(&nic_id),
+            ) {
+                (Ok(groups), Ok(rules)) => (groups, rules),
+                (Err(e), _) | (_, Err(e)) => {
+                    warn!(nic_id = %nic_id, error = %e, "Failed to load security rules");
+                    continue;
+                }
+            };
+
+            let Some(active) = active_rules(&rules, Utc::now()) else {
+                programmed.remove(&ifindex);
+                continue;
+            };
+            if programmed.get(&ifindex) == Some(&active) {
+                continue;
+            }
+
+            match security::apply(&ebpf, ifindex, &groups, &rules).await {
+                Ok(()) => {
+                    debug!(
+                        nic_id = %nic_id,
+                        active = active.len(),
+                        rules = rules.len(),
+                        "Reprogrammed time-limited security rules"
+                    );
+                    programmed.insert(ifindex, active);
+                }
+                Err(e) => {
+                    warn!(nic_id = %nic_id, error = %e, "Failed to reprogram security rules");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,209 @@
+//! Security group enforcement in the TC programs.
+//!
+//! The rules of all groups attached to a NIC are written to the
+//! SECURITY_RULES maps of both programs, in a block of
+//! [`MAX_RULES_PER_NIC`] slots reserved for the NIC's ifindex, and the
+//! NIC's NIC_SECURITY entry points at that block. With a group attached,
+//! ingress is default-deny; egress stays allowed and is only tracked.
+//!
+//! A NIC is in audit mode while any of its groups is: the ingress program
+//! then passes what its rules would drop and counts it for
+//! [`crate::security_audit`]. Enforcement starts once every attached group
+//! has left audit mode.
+//!
+//! Rules outside their validity window or schedule keep their slot with
+//! the enabled flag cleared; [`crate::rule_window`] reprograms NICs as
+//! windows open and close.
+
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
+use mvirt_common::rule_window::TimedRule;
+use tracing::warn;
+
+use crate::ebpf_loader::{
+    DIRECTION_EGRESS, DIRECTION_INGRESS, EbpfManager, NicSecurityConfig, Result, SecurityRule,
+};
+use crate::grpc::{RuleDirection, SecurityGroupData, SecurityGroupRuleData};
+
+/// Rule slots per NIC; the programs evaluate at most this many.
+pub const MAX_RULES_PER_NIC: u32 = 64;
+
+/// First SECURITY_RULES index of a NIC's block.
+fn rules_start(ifindex: u32) -> u32 {
+    ifindex.wrapping_mul(MAX_RULES_PER_NIC)
+}
+
+/// BPF form of a stored rule, enabled if it is active at `now`; `None` if
+/// its CIDR does not parse.
+pub fn bpf_rule(rule: &SecurityGroupRuleData, now: DateTime<Utc>) -> Option<SecurityRule> {
+    let direction = match rule.direction {
+        RuleDirection::Egress => DIRECTION_EGRESS,
+        _ => DIRECTION_INGRESS,
+    };
+    let (ip_version, cidr_addr, prefix_len) = match rule.cidr.as_deref() {
+        None => (0, [0u8; 16], 0),
+        Some(cidr) => match cidr.parse::<IpNet>().ok()? {
+            IpNet::V4(net) => {
+                let mut addr = [0u8; 16];
+                addr[..4].copy_from_slice(&net.network().octets());
+                (4, addr, net.prefix_len())
+            }
+            IpNet::V6(net) => (6, net.network().octets(), net.prefix_len()),
+        },
+    };
+    let mut bpf = SecurityRule::new(
+        direction,
+        rule.protocol.to_ip_protocol(),
+        ip_version,
+        rule.port_start.unwrap_or(0),
+        rule.port_end.unwrap_or(0),
+        cidr_addr,
+        prefix_len,
+    );
+    bpf.enabled = rule.is_active_at(now) as u8;
+    Some(bpf)
+}
+
+/// NIC_SECURITY entry for a NIC's groups and rule count; `None` when no
+/// group is attached and the NIC is not filtered.
+pub fn nic_config(
+    ifindex: u32,
+    groups: &[SecurityGroupData],
+    rules_count: u32,
+) -> Option<NicSecurityConfig> {
+    if groups.is_empty() {
+        return None;
+    }
+    let audit = groups.iter().any(|g| g.audit);
+    Some(NicSecurityConfig::new(
+        true,
+        audit,
+        rules_start(ifindex),
+        rules_count,
+    ))
+}
+
+/// Program a NIC's attached groups and their rules into both programs.
+pub async fn apply(
+    ebpf: &EbpfManager,
+    ifindex: u32,
+    groups: &[SecurityGroupData],
+    rules: &[SecurityGroupRuleData],
+) -> Result<()> {
+    let now = Utc::now();
+    let mut bpf_rules: Vec<SecurityRule> = rules.iter().filter_map(|r| bpf_rule(r, now)).collect();
+    if bpf_rules.len() > MAX_RULES_PER_NIC as usize {
+        warn!(
+            ifindex,
+            rules = bpf_rules.len(),
+            max = MAX_RULES_PER_NIC,
+            "Too many security rules for NIC; ignoring the rest"
+        );
+        bpf_rules.truncate(MAX_RULES_PER_NIC as usize);
+    }
+
+    let start = rules_start(ifindex);
+    for (i, rule) in bpf_rules.iter().enumerate() {
+        ebpf.set_security_rule(start + i as u32, *rule).await?;
+        ebpf.set_ingress_security_rule(start + i as u32, *rule)
+            .await?;
+    }
+    // Clear the slots of rules removed since the last update
+    for i in bpf_rules.len() as u32..MAX_RULES_PER_NIC {
+        ebpf.remove_security_rule(start + i).await?;
+        ebpf.remove_ingress_security_rule(start + i).await?;
+    }
+
+    match nic_config(ifindex, groups, bpf_rules.len() as u32) {
+        Some(config) => {
+            ebpf.set_nic_security_config(ifindex, config).await?;
+            ebpf.set_ingress_nic_security_config(ifindex, config)
+                .await?;
+        }
+        None => {
+            ebpf.remove_nic_security_config(ifindex).await?;
+            ebpf.remove_ingress_nic_security_config(ifindex).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Stop filtering a NIC that is being torn down.
+pub async fn clear(ebpf: &EbpfManager, ifindex: u32) -> Result<()> {
+    apply(ebpf, ifindex, &[], &[]).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::RuleProtocol;
+    use uuid::Uuid;
+
+    fn rule(direction: RuleDirection, cidr: Option<&str>) -> SecurityGroupRuleData {
+        SecurityGroupRuleData {
+            id: Uuid::new_v4(),
+            security_group_id: Uuid::new_v4(),
+            direction,
+            protocol: RuleProtocol::Tcp,
+            port_start: Some(22),
+            port_end: Some(22),
+            cidr: cidr.map(String::from),
+            description: None,
+            not_before: None,
+            not_after: None,
+            schedule: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn group(audit: bool) -> SecurityGroupData {
+        SecurityGroupData {
+            id: Uuid::new_v4(),
+            name: "sg".to_string(),
+            description: None,
+            audit,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_bpf_rule_from_stored_rule() {
+        let now = Utc::now();
+        let r = bpf_rule(&rule(RuleDirection::Ingress, Some("10.1.2.0/24")), now).unwrap();
+        assert_eq!(r.enabled, 1);
+        assert_eq!(r.direction, DIRECTION_INGRESS);
+        assert_eq!(r.protocol, 6);
+        assert_eq!(r.ip_version, 4);
+        assert_eq!((r.port_start, r.port_end), (22, 22));
+        assert_eq!(&r.cidr_addr[..4], &[10, 1, 2, 0]);
+        assert_eq!(r.cidr_prefix_len, 24);
+
+        let r = bpf_rule(&rule(RuleDirection::Egress, None), now).unwrap();
+        assert_eq!(r.direction, DIRECTION_EGRESS);
+        assert_eq!((r.ip_version, r.cidr_prefix_len), (0, 0));
+
+        let r = bpf_rule(&rule(RuleDirection::Ingress, Some("fd00::/8")), now).unwrap();
+        assert_eq!((r.ip_version, r.cidr_prefix_len), (6, 8));
+
+        assert!(bpf_rule(&rule(RuleDirection::Ingress, Some("bogus")), now).is_none());
+
+        let mut expired = rule(RuleDirection::Ingress, None);
+        expired.not_after = Some(now - chrono::Duration::minutes(1));
+        assert_eq!(bpf_rule(&expired, now).unwrap().enabled, 0);
+    }
+
+    #[test]
+    fn test_nic_audits_while_any_group_does() {
+        assert!(nic_config(3, &[], 0).is_none());
+
+        let enforcing = nic_config(3, &[group(false)], 2).unwrap();
+        assert_eq!((enforcing.enabled, enforcing.audit), (1, 0));
+        assert_eq!(enforcing.rules_start, 3 * MAX_RULES_PER_NIC);
+        assert_eq!(enforcing.rules_count, 2);
+
+        let audit = nic_config(3, &[group(false), group(true)], 2).unwrap();
+        assert_eq!(audit.audit, 1);
+    }
+}
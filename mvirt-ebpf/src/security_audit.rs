@@ -0,0 +1,412 @@
+//! Security audit mode reporting.
+//!
+//! NICs with a security group in audit mode are not filtered: the ingress
+//! program counts every packet their rules would drop in the
+//! SECURITY_AUDIT map, per 5-tuple, and delivers it. This task reads the
+//! map on an interval and logs, per NIC, how much traffic would have been
+//! dropped since the previous report, with the busiest tuples as samples.
+//! Operators use it to check a rule set against live traffic before
+//! switching the group to enforcing.
+//!
+//! Tuples idle past the timeout are reported one last time and removed
+//! from the map, as are the tuples of NICs that were torn down.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::RwLock;
+use tokio::time;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::audit::EbpfAuditLogger;
+use crate::ebpf_loader::{EbpfManager, FlowKey, FlowStats};
+use crate::flowlog::{Clock, protocol_name};
+
+/// Default report interval in seconds
+pub const DEFAULT_REPORT_INTERVAL_SECS: u64 = 60;
+
+/// Default idle time after which a tuple is forgotten, in seconds
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 120;
+
+/// Default number of sample tuples per NIC and report
+pub const DEFAULT_SAMPLES: usize = 5;
+
+/// Audit reporter settings.
+#[derive(Debug, Clone)]
+pub struct SecurityAuditConfig {
+    pub interval: Duration,
+    pub idle_timeout: Duration,
+    /// Tuples listed per NIC and report, busiest first
+    pub samples: usize,
+}
+
+impl Default for SecurityAuditConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(DEFAULT_REPORT_INTERVAL_SECS),
+            idle_timeout: Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS),
+            samples: DEFAULT_SAMPLES,
+        }
+    }
+}
+
+/// Active NICs by TAP ifindex, to attribute counters to NIC IDs. The gRPC
+/// service registers every NIC it sets up.
+#[derive(Default)]
+pub struct AuditNics {
+    nics: RwLock<HashMap<u32, Uuid>>,
+}
+
+impl AuditNics {
+    pub async fn register(&self, ifindex: u32, nic_id: Uuid) {
+        self.nics.write().await.insert(ifindex, nic_id);
+    }
+
+    pub async fn unregister(&self, ifindex: u32) {
+        self.nics.write().await.remove(&ifindex);
+    }
+
+    async fn snapshot(&self) -> HashMap<u32, Uuid> {
+        self.nics.read().await.clone()
+    }
+}
+
+/// Decoded SECURITY_AUDIT key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Tuple {
+    ifindex: u32,
+    src: SocketAddr,
+    dst: SocketAddr,
+    protocol: u8,
+}
+
+impl From<&FlowKey> for Tuple {
+    fn from(key: &FlowKey) -> Self {
+        let addr = |bytes: [u8; 16]| -> IpAddr {
+            if key.tuple.ip_version == 4 {
+                Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3]).into()
+            } else {
+                Ipv6Addr::from(bytes).into()
+            }
+        };
+        Self {
+            ifindex: key.ifindex,
+            src: SocketAddr::new(addr(key.tuple.src_addr), key.tuple.src_port),
+            dst: SocketAddr::new(addr(key.tuple.dst_addr), key.tuple.dst_port),
+            protocol: key.tuple.protocol,
+        }
+    }
+}
+
+/// Counters of a tuple as of its last report.
+#[derive(Debug, Clone, Copy, Default)]
+struct Reported {
+    first_seen_ns: u64,
+    packets: u64,
+    bytes: u64,
+}
+
+/// Would-drop traffic of one NIC since the previous report.
+#[derive(Debug, Clone, PartialEq)]
+struct NicReport {
+    nic_id: Uuid,
+    packets: u64,
+    bytes: u64,
+    /// Tuples that would have been dropped
+    tuples: usize,
+    /// Busiest tuples with their packet counts
+    samples: Vec<(Tuple, u64)>,
+}
+
+impl fmt::Display for NicReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "would drop {} packets ({} bytes) in {} flows",
+            self.packets, self.bytes, self.tuples
+        )?;
+        for (i, (tuple, packets)) in self.samples.iter().enumerate() {
+            write!(
+                f,
+                "{} {} {} -> {} packets={}",
+                if i == 0 { "; e.g." } else { "," },
+                protocol_name(tuple.protocol),
+                tuple.src,
+                tuple.dst,
+                packets
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Per-NIC reports of what each tuple grew by since `reported`, and the
+/// tuples to remove from the map. `reported` is updated for the rest.
+fn collect(
+    entries: &[(Tuple, FlowStats)],
+    nics: &HashMap<u32, Uuid>,
+    reported: &mut HashMap<Tuple, Reported>,
+    now_ns: u64,
+    config: &SecurityAuditConfig,
+) -> (Vec<NicReport>, Vec<Tuple>) {
+    let mut by_nic: HashMap<u32, NicReport> = HashMap::new();
+    let mut expired = Vec::new();
+
+    for (tuple, stats) in entries {
+        let Some(nic_id) = nics.get(&tuple.ifindex) else {
+            reported.remove(tuple);
+            expired.push(*tuple);
+            continue;
+        };
+
+        // The LRU map may have evicted and restarted the tuple meanwhile
+        let prev = reported
+            .get(tuple)
+            .filter(|r| r.first_seen_ns == stats.first_seen_ns && r.packets <= stats.packets)
+            .copied()
+            .unwrap_or_default();
+
+        let packets = stats.packets - prev.packets;
+        if packets > 0 {
+            let report = by_nic.entry(tuple.ifindex).or_insert_with(|| NicReport {
+                nic_id: *nic_id,
+                packets: 0,
+                bytes: 0,
+                tuples: 0,
+                samples: Vec::new(),
+            });
+            report.packets += packets;
+            report.bytes += stats.bytes.saturating_sub(prev.bytes);
+            report.tuples += 1;
+            report.samples.push((*tuple, packets));
+        }
+
+        let idle =
+            now_ns.saturating_sub(stats.last_seen_ns) >= config.idle_timeout.as_nanos() as u64;
+        if idle {
+            reported.remove(tuple);
+            expired.push(*tuple);
+        } else {
+            reported.insert(
+                *tuple,
+                Reported {
+                    first_seen_ns: stats.first_seen_ns,
+                    packets: stats.packets,
+                    bytes: stats.bytes,
+                },
+            );
+        }
+    }
+
+    // Forget tuples the kernel evicted
+    let live: HashSet<_> = entries.iter().map(|(t, _)| *t).collect();
+    reported.retain(|t, _| live.contains(t));
+
+    let mut reports: Vec<NicReport> = by_nic.into_values().collect();
+    for report in &mut reports {
+        report
+            .samples
+            .sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.src.cmp(&b.0.src)));
+        report.samples.truncate(config.samples);
+    }
+    reports.sort_by_key(|r| r.nic_id);
+    (reports, expired)
+}
+
+/// Security audit report task handle.
+pub struct SecurityAuditReporter {
+    nics: Arc<AuditNics>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl SecurityAuditReporter {
+    /// Start a new report task.
+    pub fn start(
+        ebpf: Arc<EbpfManager>,
+        audit: Arc<EbpfAuditLogger>,
+        config: SecurityAuditConfig,
+    ) -> Self {
+        let nics = Arc::new(AuditNics::default());
+
+        info!(
+            interval = ?config.interval,
+            samples = config.samples,
+            "Security audit report task started"
+        );
+
+        let task = tokio::spawn(report_loop(ebpf, Arc::clone(&nics), audit, config));
+        Self { nics, task }
+    }
+
+    /// Registry of active NICs; hand this to the gRPC service.
+    pub fn nics(&self) -> Arc<AuditNics> {
+        Arc::clone(&self.nics)
+    }
+
+    /// Stop the report task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Security audit report task stopped");
+    }
+}
+
+/// Main report loop.
+async fn report_loop(
+    ebpf: Arc<EbpfManager>,
+    nics: Arc<AuditNics>,
+    audit: Arc<EbpfAuditLogger>,
+    config: SecurityAuditConfig,
+) {
+    let mut interval = time::interval(config.interval);
+    let mut reported = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let entries = match ebpf.read_security_audit().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(error = %e, "Failed to read security audit map");
+                continue;
+            }
+        };
+        let keys: HashMap<Tuple, FlowKey> = entries
+            .iter()
+            .map(|(key, _)| (Tuple::from(key), *key))
+            .collect();
+        let entries: Vec<_> = entries
+            .iter()
+            .map(|(key, stats)| (Tuple::from(key), *stats))
+            .collect();
+
+        let (reports, expired) = collect(
+            &entries,
+            &nics.snapshot().await,
+            &mut reported,
+            Clock::now().now_ns,
+            &config,
+        );
+
+        let expired: Vec<FlowKey> = expired
+            .iter()
+            .filter_map(|t| keys.get(t))
+            .copied()
+            .collect();
+        if let Err(e) = ebpf.remove_security_audit(&expired).await {
+            warn!(error = %e, "Failed to remove expired security audit counters");
+        }
+
+        for report in &reports {
+            info!(nic_id = %report.nic_id, "Security audit: {}", report);
+            audit.security_audit(&report.nic_id.to_string(), &report.to_string());
+        }
+
+        debug!(
+            tuples = entries.len(),
+            reports = reports.len(),
+            expired = expired.len(),
+            "Security audit report"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: u64 = 1_000_000_000;
+
+    fn tuple(ifindex: u32, src_port: u16) -> Tuple {
+        Tuple {
+            ifindex,
+            src: SocketAddr::new("203.0.113.9".parse().unwrap(), src_port),
+            dst: SocketAddr::new("10.0.0.2".parse().unwrap(), 22),
+            protocol: 6,
+        }
+    }
+
+    fn stats(first_seen_ns: u64, last_seen_ns: u64, packets: u64, bytes: u64) -> FlowStats {
+        FlowStats {
+            first_seen_ns,
+            last_seen_ns,
+            packets,
+            bytes,
+        }
+    }
+
+    fn nics() -> HashMap<u32, Uuid> {
+        HashMap::from([(7, Uuid::from_u128(1))])
+    }
+
+    fn config(samples: usize) -> SecurityAuditConfig {
+        SecurityAuditConfig {
+            samples,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_reports_are_per_nic_deltas() {
+        let mut reported = HashMap::new();
+        let entries = [
+            (tuple(7, 40000), stats(0, SECOND, 4, 240)),
+            (tuple(7, 40001), stats(0, SECOND, 1, 60)),
+        ];
+        let (reports, expired) = collect(&entries, &nics(), &mut reported, 2 * SECOND, &config(5));
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].nic_id, Uuid::from_u128(1));
+        assert_eq!((reports[0].packets, reports[0].bytes), (5, 300));
+        assert_eq!(reports[0].tuples, 2);
+        assert!(expired.is_empty());
+
+        // Only what grew since is reported
+        let entries = [
+            (tuple(7, 40000), stats(0, 3 * SECOND, 6, 360)),
+            (tuple(7, 40001), stats(0, SECOND, 1, 60)),
+        ];
+        let (reports, _) = collect(&entries, &nics(), &mut reported, 4 * SECOND, &config(5));
+        assert_eq!((reports[0].packets, reports[0].bytes), (2, 120));
+        assert_eq!(reports[0].tuples, 1);
+
+        // Nothing new: no report
+        let (reports, _) = collect(&entries, &nics(), &mut reported, 5 * SECOND, &config(5));
+        assert!(reports.is_empty());
+    }
+
+    #[test]
+    fn test_samples_are_busiest_tuples() {
+        let entries = [
+            (tuple(7, 1), stats(0, 0, 1, 60)),
+            (tuple(7, 2), stats(0, 0, 9, 540)),
+            (tuple(7, 3), stats(0, 0, 5, 300)),
+        ];
+        let (reports, _) = collect(&entries, &nics(), &mut HashMap::new(), 0, &config(2));
+        assert_eq!(reports[0].tuples, 3);
+        assert_eq!(reports[0].samples, vec![(tuple(7, 2), 9), (tuple(7, 3), 5)]);
+        assert_eq!(
+            reports[0].to_string(),
+            "would drop 15 packets (900 bytes) in 3 flows; e.g. \
+             tcp 203.0.113.9:2 -> 10.0.0.2:22 packets=9, tcp 203.0.113.9:3 -> 10.0.0.2:22 packets=5"
+        );
+    }
+
+    #[test]
+    fn test_idle_and_unknown_tuples_expire() {
+        let mut reported = HashMap::new();
+        let idle = config(5).idle_timeout.as_nanos() as u64;
+        let entries = [
+            (tuple(7, 1), stats(0, SECOND, 2, 120)),
+            (tuple(8, 1), stats(0, SECOND, 2, 120)),
+        ];
+        let (reports, expired) =
+            collect(&entries, &nics(), &mut reported, SECOND + idle, &config(5));
+        // The idle tuple is reported one last time
+        assert_eq!(reports.len(), 1);
+        assert_eq!(expired.len(), 2);
+        assert!(reported.is_empty());
+    }
+}
@@ -470,6 +470,12 @@ pub fn tap_name_from_nic_id(nic_id: &uuid::Uuid) -> String {
     format!("tap_{}", &id_str[..7])
 }
 
+/// Whether `name` has the form [`tap_name_from_nic_id`] produces.
+pub fn is_nic_tap_name(name: &str) -> bool {
+    name.strip_prefix("tap_")
+        .is_some_and(|id| id.len() == 7 && id.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -480,6 +486,10 @@ mod tests {
         let name = tap_name_from_nic_id(&id);
         assert_eq!(name, "tap_1234567");
         assert!(name.len() <= 15);
+        assert!(is_nic_tap_name(&name));
+        assert!(!is_nic_tap_name("tap_12345"));
+        assert!(!is_nic_tap_name("tap_vm-1234"));
+        assert!(!is_nic_tap_name("tap0"));
     }
 
     #[test]
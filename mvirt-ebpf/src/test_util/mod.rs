@@ -33,6 +33,7 @@ pub fn test_nic_config(mac: [u8; 6], ipv4: Ipv4Addr, network_id: Uuid) -> NicDat
         state: NicState::Active,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        owner: None,
     }
 }
 
@@ -48,6 +49,10 @@ pub fn test_network_config(subnet: Ipv4Net, dns: IpAddr) -> NetworkData {
         dns_servers: vec![dns],
         ntp_servers: vec![],
         is_public: false,
+        boot_server: None,
+        boot_file: None,
+        flow_logs: false,
+        flow_sample_rate: 1,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     }
@@ -94,6 +99,7 @@ pub fn test_nic_config_ipv6(
         state: NicState::Active,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        owner: None,
     }
 }
 
@@ -113,6 +119,10 @@ pub fn test_network_config_ipv6(
         dns_servers,
         ntp_servers: vec![],
         is_public: false,
+        boot_server: None,
+        boot_file: None,
+        flow_logs: false,
+        flow_sample_rate: 1,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     }
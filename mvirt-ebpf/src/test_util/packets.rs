@@ -85,6 +85,21 @@ pub fn create_dhcp_discover(client_mac: [u8; 6], xid: u32) -> Vec<u8> {
         [0, 0, 0, 0],
         None,
         None,
+        None,
+    )
+}
+
+/// Create a DHCP DISCOVER packet carrying a vendor class identifier (option 60),
+/// e.g. `PXEClient:Arch:00007` or `HTTPClient:Arch:00016` from UEFI firmware.
+pub fn create_dhcp_discover_with_class(client_mac: [u8; 6], xid: u32, class: &[u8]) -> Vec<u8> {
+    create_dhcp_packet(
+        client_mac,
+        xid,
+        DhcpMessageType::Discover,
+        [0, 0, 0, 0],
+        None,
+        None,
+        Some(class),
     )
 }
 
@@ -102,6 +117,7 @@ pub fn create_dhcp_request(
         [0, 0, 0, 0],
         Some(requested_ip),
         Some(server_id),
+        None,
     )
 }
 
@@ -112,6 +128,7 @@ fn create_dhcp_packet(
     client_ip: [u8; 4],
     requested_ip: Option<[u8; 4]>,
     server_id: Option<[u8; 4]>,
+    vendor_class: Option<&[u8]>,
 ) -> Vec<u8> {
     // Build DHCP payload (BOOTP + options)
     let mut dhcp = vec![0u8; 300];
@@ -155,6 +172,14 @@ fn create_dhcp_packet(
         idx += 6;
     }
 
+    // Vendor class identifier
+    if let Some(class) = vendor_class {
+        dhcp[idx] = 60;
+        dhcp[idx + 1] = class.len() as u8;
+        dhcp[idx + 2..idx + 2 + class.len()].copy_from_slice(class);
+        idx += 2 + class.len();
+    }
+
     // Parameter request list
     dhcp[idx] = 55;
     dhcp[idx + 1] = 4;
@@ -227,6 +252,10 @@ pub struct DhcpResponse {
     pub router: Option<[u8; 4]>,
     pub dns_servers: Vec<[u8; 4]>,
     pub lease_time: Option<u32>,
+    /// BOOTP `file` field (empty when unset)
+    pub boot_file: String,
+    /// Vendor class identifier (option 60)
+    pub class_identifier: Option<Vec<u8>>,
 }
 
 /// Parse a DHCP response packet (without virtio-net header)
@@ -255,6 +284,10 @@ pub fn parse_dhcp_response(packet: &[u8]) -> Option<DhcpResponse> {
     let xid = u32::from_be_bytes([dhcp[4], dhcp[5], dhcp[6], dhcp[7]]);
     let your_ip = [dhcp[16], dhcp[17], dhcp[18], dhcp[19]];
     let server_ip = [dhcp[20], dhcp[21], dhcp[22], dhcp[23]];
+    let file = &dhcp[108..236];
+    let boot_file =
+        String::from_utf8_lossy(&file[..file.iter().position(|b| *b == 0).unwrap_or(file.len())])
+            .into_owned();
 
     // Check magic cookie
     if dhcp[236..240] != [99, 130, 83, 99] {
@@ -267,6 +300,7 @@ pub fn parse_dhcp_response(packet: &[u8]) -> Option<DhcpResponse> {
     let mut router = None;
     let mut dns_servers = Vec::new();
     let mut lease_time = None;
+    let mut class_identifier = None;
 
     let mut idx = 240;
     while idx < dhcp.len() {
@@ -306,6 +340,7 @@ pub fn parse_dhcp_response(packet: &[u8]) -> Option<DhcpResponse> {
             51 if len == 4 => {
                 lease_time = Some(u32::from_be_bytes([data[0], data[1], data[2], data[3]]))
             }
+            60 => class_identifier = Some(data.to_vec()),
             _ => {}
         }
         idx += 2 + len;
@@ -320,6 +355,8 @@ pub fn parse_dhcp_response(packet: &[u8]) -> Option<DhcpResponse> {
         router,
         dns_servers,
         lease_time,
+        boot_file,
+        class_identifier,
     })
 }
 
@@ -8,8 +8,8 @@
 use mvirt_ebpf::GATEWAY_IPV4_LINK_LOCAL;
 use mvirt_ebpf::process_packet_sync;
 use mvirt_ebpf::test_util::{
-    DhcpMessageType, create_dhcp_discover, create_dhcp_request, parse_dhcp_response,
-    test_network_config, test_nic_config,
+    DhcpMessageType, create_dhcp_discover, create_dhcp_discover_with_class, create_dhcp_request,
+    parse_dhcp_response, test_network_config, test_nic_config,
 };
 use std::net::{IpAddr, Ipv4Addr};
 
@@ -177,3 +177,69 @@ fn test_dhcp_multiple_requests() {
         assert_eq!(offer.xid, xid, "XID mismatch for request {}", i);
     }
 }
+
+/// PXE firmware gets the TFTP server and boot file; a plain client does not.
+#[test]
+fn test_dhcp_pxe_boot_options() {
+    let mut network = test_network_config(
+        SUBNET.parse().unwrap(),
+        IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+    );
+    network.boot_server = Some(Ipv4Addr::new(192, 168, 100, 10));
+    network.boot_file = Some("ipxe.efi".to_string());
+
+    let nic = test_nic_config(TEST_MAC, TEST_IP, network.id);
+
+    let discover = create_dhcp_discover_with_class(TEST_MAC, XID, b"PXEClient:Arch:00007");
+    let response =
+        process_packet_sync(&nic, &network, &discover).expect("Should get DHCP OFFER for DISCOVER");
+    let offer = parse_dhcp_response(&response).expect("Should parse DHCP OFFER");
+
+    assert_eq!(offer.boot_file, "ipxe.efi");
+    assert_eq!(
+        Ipv4Addr::from(offer.server_ip),
+        Ipv4Addr::new(192, 168, 100, 10)
+    );
+    assert_eq!(offer.class_identifier.as_deref(), Some(&b"PXEClient"[..]));
+
+    // The installed OS does not identify as PXE firmware
+    let discover = create_dhcp_discover(TEST_MAC, XID);
+    let response =
+        process_packet_sync(&nic, &network, &discover).expect("Should get DHCP OFFER for DISCOVER");
+    let offer = parse_dhcp_response(&response).expect("Should parse DHCP OFFER");
+
+    assert!(offer.boot_file.is_empty());
+    assert_eq!(Ipv4Addr::from(offer.server_ip), GATEWAY_IPV4_LINK_LOCAL);
+    assert!(offer.class_identifier.is_none());
+}
+
+/// UEFI HTTP boot clients get the boot URL and the HTTPClient class echoed back.
+#[test]
+fn test_dhcp_http_boot_options() {
+    let mut network = test_network_config(
+        SUBNET.parse().unwrap(),
+        IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)),
+    );
+    network.boot_file = Some("http://boot.example.com/images/installer.efi".to_string());
+
+    let nic = test_nic_config(TEST_MAC, TEST_IP, network.id);
+
+    let discover = create_dhcp_discover_with_class(TEST_MAC, XID, b"HTTPClient:Arch:00016");
+    let response =
+        process_packet_sync(&nic, &network, &discover).expect("Should get DHCP OFFER for DISCOVER");
+    let offer = parse_dhcp_response(&response).expect("Should parse DHCP OFFER");
+
+    assert_eq!(
+        offer.boot_file,
+        "http://boot.example.com/images/installer.efi"
+    );
+    assert_eq!(offer.class_identifier.as_deref(), Some(&b"HTTPClient"[..]));
+
+    // A PXE (TFTP) client cannot use an HTTP URL
+    let discover = create_dhcp_discover_with_class(TEST_MAC, XID, b"PXEClient:Arch:00007");
+    let response =
+        process_packet_sync(&nic, &network, &discover).expect("Should get DHCP OFFER for DISCOVER");
+    let offer = parse_dhcp_response(&response).expect("Should parse DHCP OFFER");
+
+    assert!(offer.boot_file.is_empty());
+}
@@ -5,6 +5,7 @@
 //! These tests don't require CAP_NET_ADMIN as they use in-memory storage
 //! and don't create actual TAP devices.
 
+use mvirt_common::rule_window::TimedRule;
 use mvirt_ebpf::grpc::{
     NetworkData, NicData, NicState, RuleDirection, RuleProtocol, SecurityGroupData,
     SecurityGroupRuleData, Storage,
@@ -24,6 +25,10 @@ fn create_test_network(storage: &Storage) -> NetworkData {
         dns_servers: vec![IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8))],
         ntp_servers: vec![],
         is_public: false,
+        boot_server: None,
+        boot_file: None,
+        flow_logs: false,
+        flow_sample_rate: 1,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -48,6 +53,7 @@ fn create_test_nic(storage: &Storage, network_id: Uuid) -> NicData {
         state: NicState::Created,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        owner: None,
     };
     storage.create_nic(&nic).expect("Failed to create NIC");
     nic
@@ -59,6 +65,7 @@ fn create_test_security_group(storage: &Storage, name: &str) -> SecurityGroupDat
         id: Uuid::new_v4(),
         name: name.to_string(),
         description: Some("Test security group".to_string()),
+        audit: false,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -140,6 +147,7 @@ fn test_security_group_name_unique() {
         id: Uuid::new_v4(),
         name: "unique-name".to_string(),
         description: None,
+        audit: false,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -147,6 +155,37 @@ fn test_security_group_name_unique() {
     assert!(result.is_err());
 }
 
+#[test]
+fn test_set_security_group_audit() {
+    let storage = Storage::in_memory().expect("Failed to create storage");
+    let sg = create_test_security_group(&storage, "audited");
+    assert!(!sg.audit);
+
+    storage
+        .set_security_group_audit(&sg.id, true)
+        .expect("Set audit failed");
+    let fetched = storage
+        .get_security_group_by_id(&sg.id)
+        .expect("Get failed")
+        .unwrap();
+    assert!(fetched.audit);
+
+    storage
+        .set_security_group_audit(&sg.id, false)
+        .expect("Set audit failed");
+    let fetched = storage
+        .get_security_group_by_name("audited")
+        .expect("Get failed")
+        .unwrap();
+    assert!(!fetched.audit);
+
+    assert!(
+        storage
+            .set_security_group_audit(&Uuid::new_v4(), true)
+            .is_err()
+    );
+}
+
 // ============================================================================
 // Security Group Rule Tests
 // ============================================================================
@@ -166,6 +205,9 @@ fn test_add_security_group_rule() {
         port_end: Some(22),
         cidr: Some("0.0.0.0/0".to_string()),
         description: Some("SSH access".to_string()),
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -198,6 +240,9 @@ fn test_add_multiple_rules() {
         port_end: Some(22),
         cidr: Some("10.0.0.0/8".to_string()),
         description: Some("SSH".to_string()),
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -213,6 +258,9 @@ fn test_add_multiple_rules() {
         port_end: Some(80),
         cidr: Some("0.0.0.0/0".to_string()),
         description: Some("HTTP".to_string()),
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -228,6 +276,9 @@ fn test_add_multiple_rules() {
         port_end: Some(443),
         cidr: Some("0.0.0.0/0".to_string()),
         description: Some("HTTPS".to_string()),
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -237,6 +288,46 @@ fn test_add_multiple_rules() {
     assert_eq!(rules.len(), 3);
 }
 
+#[test]
+fn test_rule_window_roundtrip() {
+    let storage = Storage::in_memory().expect("Failed to create storage");
+    let sg = create_test_security_group(&storage, "window-test");
+
+    let now = chrono::Utc::now();
+    let not_after = now + chrono::Duration::hours(2);
+    let rule = SecurityGroupRuleData {
+        id: Uuid::new_v4(),
+        security_group_id: sg.id,
+        direction: RuleDirection::Ingress,
+        protocol: RuleProtocol::Tcp,
+        port_start: Some(22),
+        port_end: Some(22),
+        cidr: Some("198.51.100.7/32".to_string()),
+        description: Some("Temporary support access".to_string()),
+        not_before: None,
+        not_after: Some(not_after),
+        schedule: Some("Mon-Fri 08:00-18:00".parse().unwrap()),
+        created_at: now,
+        updated_at: now,
+    };
+    storage.create_security_group_rule(&rule).unwrap();
+
+    let fetched = storage
+        .get_security_group_rule_by_id(&rule.id)
+        .unwrap()
+        .unwrap();
+    assert_eq!(fetched.not_before, None);
+    assert_eq!(
+        fetched.not_after.map(|t| t.timestamp()),
+        Some(not_after.timestamp())
+    );
+    assert_eq!(
+        fetched.schedule.map(|s| s.to_string()).as_deref(),
+        Some("Mon-Fri 08:00-18:00")
+    );
+    assert!(!fetched.is_active_at(not_after));
+}
+
 #[test]
 fn test_remove_security_group_rule() {
     let storage = Storage::in_memory().expect("Failed to create storage");
@@ -251,6 +342,9 @@ fn test_remove_security_group_rule() {
         port_end: Some(22),
         cidr: None,
         description: None,
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -282,6 +376,9 @@ fn test_rules_deleted_with_security_group() {
         port_end: None,
         cidr: None,
         description: None,
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -375,6 +472,7 @@ fn test_security_group_on_multiple_nics() {
         state: NicState::Created,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        owner: None,
     };
     storage.create_nic(&nic1).unwrap();
 
@@ -391,6 +489,7 @@ fn test_security_group_on_multiple_nics() {
         state: NicState::Created,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        owner: None,
     };
     storage.create_nic(&nic2).unwrap();
 
@@ -493,6 +592,9 @@ fn test_rule_protocols() {
             },
             cidr: None,
             description: None,
+            not_before: None,
+            not_after: None,
+            schedule: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         };
@@ -520,6 +622,9 @@ fn test_rule_directions() {
         port_end: Some(22),
         cidr: None,
         description: None,
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -535,6 +640,9 @@ fn test_rule_directions() {
         port_end: None,
         cidr: None,
         description: None,
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -581,6 +689,9 @@ fn test_ipv4_cidr_rules() {
             port_end: Some(443),
             cidr: Some(cidr.to_string()),
             description: None,
+            not_before: None,
+            not_after: None,
+            schedule: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         };
@@ -615,6 +726,9 @@ fn test_ipv6_cidr_rules() {
             port_end: Some(443),
             cidr: Some(cidr.to_string()),
             description: None,
+            not_before: None,
+            not_after: None,
+            schedule: None,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
         };
@@ -642,6 +756,9 @@ fn test_port_range_rule() {
         port_end: Some(9000),
         cidr: None,
         description: Some("High ports".to_string()),
+        not_before: None,
+        not_after: None,
+        schedule: None,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -21,6 +21,10 @@ fn create_test_network(storage: &Storage) -> NetworkData {
         dns_servers: vec![IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8))],
         ntp_servers: vec![],
         is_public: false,
+        boot_server: None,
+        boot_file: None,
+        flow_logs: false,
+        flow_sample_rate: 1,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
     };
@@ -45,6 +49,7 @@ fn create_test_nic(storage: &Storage, network_id: Uuid) -> NicData {
         state: NicState::Created,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        owner: None,
     };
     storage.create_nic(&nic).expect("Failed to create NIC");
     nic
@@ -112,6 +117,7 @@ fn test_storage_list_nics_in_network() {
             state: NicState::Created,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            owner: None,
         };
         storage.create_nic(&nic).expect("Failed to create NIC");
     }
@@ -224,6 +230,7 @@ fn test_storage_get_used_ipv4_addresses() {
             state: NicState::Created,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            owner: None,
         };
         storage.create_nic(&nic).expect("Failed to create NIC");
     }
@@ -263,6 +270,7 @@ fn test_storage_count_nics_in_network() {
             state: NicState::Created,
             created_at: chrono::Utc::now(),
             updated_at: chrono::Utc::now(),
+            owner: None,
         };
         storage.create_nic(&nic).expect("Failed to create NIC");
     }
@@ -326,6 +334,7 @@ fn test_nic_mac_string() {
         state: NicState::Created,
         created_at: chrono::Utc::now(),
         updated_at: chrono::Utc::now(),
+        owner: None,
     };
 
     assert_eq!(nic.mac_string(), "02:ab:cd:ef:12:34");
@@ -61,6 +61,15 @@ impl AuditLogger {
         })
     }
 
+    /// Build an audit logger on an existing channel, e.g. an in-process
+    /// connection to a co-located mvirt-log.
+    pub fn with_channel(channel: Channel, component: &str) -> Self {
+        Self {
+            client: Some(LogServiceClient::new(channel)),
+            component: component.to_string(),
+        }
+    }
+
     /// Create a noop audit logger (for testing or when remote logging is
     /// intentionally disabled). Tracing-side logging still happens.
     pub fn new_noop() -> Self {
@@ -72,11 +81,13 @@ impl AuditLogger {
 
     /// Log an audit event.
     ///
-    /// Always emits via local `tracing`. If a remote client is configured,
+    /// The current [request ID](mvirt_common::request_id), if any, is added to
+    /// `object_ids`. Always emits via local `tracing`. If a remote client is configured,
     /// also fires a `LogService.Log` RPC; transient failures are swallowed
     /// because the local trace is the durable record.
     pub async fn log(&self, level: LogLevel, message: impl Into<String>, object_ids: Vec<String>) {
         let message = message.into();
+        let object_ids = mvirt_common::request_id::tag(object_ids);
 
         match level {
             LogLevel::Emergency | LogLevel::Alert | LogLevel::Critical | LogLevel::Error => {
@@ -109,6 +120,38 @@ impl AuditLogger {
             }
         }
     }
+
+    /// Relay an entry produced elsewhere (e.g. a guest's console or
+    /// container output) under `component`, keeping its timestamp.
+    ///
+    /// Unlike [`log`](Self::log) this does not echo to local `tracing`:
+    /// the source keeps its own copy, and workload output would drown the
+    /// daemon's log. Dropped silently without a remote client.
+    pub async fn forward(
+        &self,
+        component: &str,
+        level: LogLevel,
+        timestamp_ns: i64,
+        message: String,
+        object_ids: Vec<String>,
+    ) {
+        let Some(mut client) = self.client.clone() else {
+            return;
+        };
+        let request = LogRequest {
+            entry: Some(LogEntry {
+                id: String::new(),
+                timestamp_ns,
+                message,
+                level: level as i32,
+                component: component.to_string(),
+                related_object_ids: object_ids,
+            }),
+        };
+        if let Err(e) = client.log(request).await {
+            tracing::debug!(error = %e, "log forward RPC failed");
+        }
+    }
 }
 
 /// Build a `ClientTlsConfig` from PEM files on disk.
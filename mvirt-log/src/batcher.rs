@@ -1,3 +1,5 @@
+//! Groups incoming log entries into Raft batches.
+
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::mpsc;
@@ -5,8 +7,8 @@ use tokio::time::{timeout, Duration};
 use tracing::{error, info};
 use ulid::Ulid;
 
-use mvirt_log::distributed::DistributedLogStore;
-use mvirt_log::LogEntry;
+use crate::distributed::DistributedLogStore;
+use crate::LogEntry;
 
 const BATCH_SIZE: usize = 100;
 const FLUSH_TIMEOUT: Duration = Duration::from_millis(50);
@@ -0,0 +1,345 @@
+//! ClickHouse storage backend.
+//!
+//! All mvirt-log peers write to one shared table over ClickHouse's HTTP
+//! interface. Every peer applies every Raft batch, so each batch is
+//! inserted once per peer; the inserts carry a deduplication token derived
+//! from the batch's entry ids, and the table keeps a deduplication window,
+//! so ClickHouse stores the batch only once.
+//!
+//! The schema is created on connect by running [`MIGRATIONS`] in order;
+//! each statement is idempotent. Existing embedded stores are copied over
+//! with `mvirt-log migrate`.
+
+use std::future::Future;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::storage::{BackendConfig, LogBackend};
+use crate::LogEntry;
+
+const DEFAULT_DATABASE: &str = "mvirt";
+const TABLE: &str = "logs";
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Schema statements, oldest first. `{db}` is replaced by the database
+/// name. Append new statements; never edit shipped ones.
+const MIGRATIONS: &[&str] = &[
+    "CREATE DATABASE IF NOT EXISTS {db}",
+    "CREATE TABLE IF NOT EXISTS {db}.logs (
+        id String,
+        timestamp_ns Int64,
+        level Int32,
+        component LowCardinality(String),
+        message String,
+        related_object_ids Array(String),
+        INDEX idx_objects related_object_ids TYPE bloom_filter GRANULARITY 4
+    ) ENGINE = MergeTree
+    PARTITION BY toYYYYMM(fromUnixTimestamp64Nano(timestamp_ns))
+    ORDER BY id
+    SETTINGS non_replicated_deduplication_window = 1000",
+];
+
+/// One row of the logs table, as sent and received in JSONEachRow format.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct Row {
+    id: String,
+    timestamp_ns: i64,
+    level: i32,
+    component: String,
+    message: String,
+    related_object_ids: Vec<String>,
+}
+
+impl From<LogEntry> for Row {
+    fn from(e: LogEntry) -> Self {
+        Self {
+            id: e.id,
+            timestamp_ns: e.timestamp_ns,
+            level: e.level,
+            component: e.component,
+            message: e.message,
+            related_object_ids: e.related_object_ids,
+        }
+    }
+}
+
+impl From<Row> for LogEntry {
+    fn from(r: Row) -> Self {
+        Self {
+            id: r.id,
+            timestamp_ns: r.timestamp_ns,
+            level: r.level,
+            component: r.component,
+            message: r.message,
+            related_object_ids: r.related_object_ids,
+        }
+    }
+}
+
+pub struct ClickHouseBackend {
+    client: reqwest::Client,
+    url: String,
+    database: String,
+    user: Option<String>,
+    password: Option<String>,
+}
+
+impl ClickHouseBackend {
+    /// Connect and bring the schema up to date.
+    pub async fn connect(config: &BackendConfig) -> Result<Self> {
+        let url = config
+            .clickhouse_url
+            .clone()
+            .ok_or_else(|| anyhow!("--clickhouse-url is required for the clickhouse backend"))?;
+        let database = config
+            .clickhouse_database
+            .clone()
+            .unwrap_or_else(|| DEFAULT_DATABASE.to_string());
+        if !is_identifier(&database) {
+            bail!("invalid ClickHouse database name {database:?}");
+        }
+        let backend = Self {
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()?,
+            url: url.trim_end_matches('/').to_string(),
+            database,
+            user: config.clickhouse_user.clone(),
+            password: config.clickhouse_password.clone(),
+        };
+        for statement in MIGRATIONS {
+            backend
+                .execute(
+                    &statement.replace("{db}", &backend.database),
+                    &[],
+                    &[],
+                    None,
+                )
+                .await
+                .context("ClickHouse schema migration")?;
+        }
+        info!(url = %backend.url, database = %backend.database, "Using ClickHouse log storage");
+        Ok(backend)
+    }
+
+    /// Run one statement. `params` become `param_<name>` query parameters
+    /// for `{name:Type}` placeholders, `settings` are passed as query
+    /// settings; `body` follows the statement (insert data).
+    async fn execute(
+        &self,
+        query: &str,
+        params: &[(&str, String)],
+        settings: &[(&str, String)],
+        body: Option<Vec<u8>>,
+    ) -> Result<String> {
+        let mut url_params: Vec<(String, String)> = params
+            .iter()
+            .map(|(k, v)| (format!("param_{k}"), v.clone()))
+            .chain(settings.iter().map(|(k, v)| (k.to_string(), v.clone())))
+            .collect();
+        let body = match body {
+            // The statement goes in the URL so the body is pure data.
+            Some(data) => {
+                url_params.push(("query".to_string(), query.to_string()));
+                data
+            }
+            None => query.as_bytes().to_vec(),
+        };
+        let mut request = self.client.post(&self.url).query(&url_params).body(body);
+        if let Some(user) = &self.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+        let response = request.send().await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            bail!("ClickHouse returned {status}: {}", text.trim());
+        }
+        Ok(text)
+    }
+
+    async fn insert(&self, entries: Vec<LogEntry>) -> Result<()> {
+        let Some(token) = dedup_token(&entries) else {
+            return Ok(());
+        };
+        let mut body = Vec::new();
+        for entry in entries {
+            serde_json::to_writer(&mut body, &Row::from(entry))?;
+            body.push(b'\n');
+        }
+        let query = format!("INSERT INTO {}.{TABLE} FORMAT JSONEachRow", self.database);
+        let settings = [("insert_deduplication_token", token)];
+        self.execute(&query, &[], &settings, Some(body)).await?;
+        Ok(())
+    }
+
+    async fn select(
+        &self,
+        object_id: Option<String>,
+        start_ns: Option<i64>,
+        end_ns: Option<i64>,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<LogEntry>> {
+        let (query, params) =
+            select_query(&self.database, object_id, start_ns, end_ns, after, limit);
+        let text = self.execute(&query, &params, &[], None).await?;
+        text.lines()
+            .filter(|l| !l.is_empty())
+            .map(|l| {
+                serde_json::from_str::<Row>(l)
+                    .map(Into::into)
+                    .map_err(Into::into)
+            })
+            .collect()
+    }
+}
+
+impl LogBackend for ClickHouseBackend {
+    fn append_batch(&self, entries: Vec<LogEntry>) -> Result<()> {
+        block_on(self.insert(entries))
+    }
+
+    fn query_after(
+        &self,
+        object_id: Option<String>,
+        start_ns: Option<i64>,
+        end_ns: Option<i64>,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<LogEntry>> {
+        block_on(self.select(object_id, start_ns, end_ns, after, limit))
+    }
+}
+
+/// Run an async request from the synchronous backend interface. The
+/// state machine and the query path both run on the multi-threaded
+/// runtime, where `block_in_place` hands the worker's other tasks off.
+fn block_on<F: Future>(fut: F) -> F::Output {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => tokio::task::block_in_place(|| handle.block_on(fut)),
+        Err(_) => tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("build runtime")
+            .block_on(fut),
+    }
+}
+
+/// Same for every peer inserting the same batch, distinct across batches.
+fn dedup_token(entries: &[LogEntry]) -> Option<String> {
+    let first = entries.first()?;
+    let last = entries.last()?;
+    Some(format!("{}-{}-{}", first.id, last.id, entries.len()))
+}
+
+fn select_query(
+    database: &str,
+    object_id: Option<String>,
+    start_ns: Option<i64>,
+    end_ns: Option<i64>,
+    after: Option<&str>,
+    limit: usize,
+) -> (String, Vec<(&'static str, String)>) {
+    let mut filters = Vec::new();
+    let mut params = Vec::new();
+    if let Some(obj) = object_id {
+        filters.push("has(related_object_ids, {obj:String})");
+        params.push(("obj", obj));
+    }
+    if let Some(start) = start_ns {
+        filters.push("timestamp_ns >= {start:Int64}");
+        params.push(("start", start.to_string()));
+    }
+    if let Some(end) = end_ns {
+        filters.push("timestamp_ns <= {end:Int64}");
+        params.push(("end", end.to_string()));
+    }
+    // ULID strings sort like the ids themselves
+    if let Some(after) = after {
+        filters.push("id > {after:String}");
+        params.push(("after", after.to_string()));
+    }
+    let filter = if filters.is_empty() {
+        String::new()
+    } else {
+        format!(" WHERE {}", filters.join(" AND "))
+    };
+    let query = format!(
+        "SELECT id, timestamp_ns, level, component, message, related_object_ids \
+         FROM {database}.{TABLE}{filter} ORDER BY id LIMIT {{limit:UInt64}} FORMAT JSONEachRow"
+    );
+    params.push(("limit", limit.to_string()));
+    (query, params)
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(|c: char| c.is_ascii_digit())
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str) -> LogEntry {
+        LogEntry {
+            id: id.to_string(),
+            timestamp_ns: 1,
+            message: "m".to_string(),
+            level: 1,
+            component: "vmm".to_string(),
+            related_object_ids: vec!["vm-1".to_string()],
+        }
+    }
+
+    #[test]
+    fn select_binds_filters_as_parameters() {
+        let (query, params) = select_query("mvirt", Some("vm-1".into()), Some(5), None, None, 10);
+        assert!(query.contains("FROM mvirt.logs WHERE has(related_object_ids, {obj:String}) AND timestamp_ns >= {start:Int64} ORDER BY id"));
+        assert_eq!(
+            params,
+            [
+                ("obj", "vm-1".to_string()),
+                ("start", "5".to_string()),
+                ("limit", "10".to_string())
+            ]
+        );
+
+        let (query, _) = select_query("mvirt", None, None, None, None, 10);
+        assert!(query.contains("FROM mvirt.logs ORDER BY id"));
+
+        let (query, params) = select_query("mvirt", None, None, None, Some("01A"), 10);
+        assert!(query.contains("FROM mvirt.logs WHERE id > {after:String} ORDER BY id"));
+        assert_eq!(params[0], ("after", "01A".to_string()));
+    }
+
+    #[test]
+    fn rows_round_trip() {
+        let line = serde_json::to_string(&Row::from(entry("01A"))).unwrap();
+        let back: LogEntry = serde_json::from_str::<Row>(&line).unwrap().into();
+        assert_eq!(back, entry("01A"));
+    }
+
+    #[test]
+    fn dedup_token_identifies_batch() {
+        assert_eq!(dedup_token(&[]), None);
+        let batch = [entry("01A"), entry("01B")];
+        assert_eq!(dedup_token(&batch).unwrap(), "01A-01B-2");
+    }
+
+    #[test]
+    fn database_names_are_identifiers() {
+        assert!(is_identifier("mvirt_logs"));
+        assert!(!is_identifier("1db"));
+        assert!(!is_identifier("db; DROP TABLE x"));
+        assert!(!is_identifier(""));
+    }
+}
@@ -39,4 +39,16 @@ impl DistributedLogStore {
         let manager = log_manager();
         manager.query(object_id, start_ns, end_ns, limit)
     }
+
+    pub async fn query_after(
+        &self,
+        object_id: Option<String>,
+        start_ns: Option<i64>,
+        end_ns: Option<i64>,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<LogEntry>> {
+        let manager = log_manager();
+        manager.query_after(object_id, start_ns, end_ns, after, limit)
+    }
 }
@@ -0,0 +1,129 @@
+//! Bulk export of log entries for offline analysis.
+//!
+//! The Export RPC pages through the backend with
+//! [`LogBackend::query_after`](crate::storage::LogBackend::query_after)
+//! and sends each page as one self-contained chunk: whole NDJSON records,
+//! gzip-compressed as a separate gzip member unless plain NDJSON was
+//! asked for. Concatenated gzip members are a valid gzip file, so a
+//! client appends chunks as they come and, after an interruption, asks
+//! again with the cursor of the last chunk it wrote.
+//!
+//! The records are flat JSON objects that DuckDB, ClickHouse or `jq` read
+//! directly.
+
+use std::io::Write;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+use crate::proto::ExportFormat;
+use crate::{LogEntry, LogLevel};
+
+/// Entries per chunk.
+pub const PAGE_SIZE: usize = 1000;
+
+/// One exported entry. The level is spelled out so the file reads
+/// without the proto definitions.
+#[derive(Serialize)]
+struct Record<'a> {
+    id: &'a str,
+    timestamp_ns: i64,
+    level: &'a str,
+    component: &'a str,
+    message: &'a str,
+    related_object_ids: &'a [String],
+}
+
+impl<'a> From<&'a LogEntry> for Record<'a> {
+    fn from(e: &'a LogEntry) -> Self {
+        Self {
+            id: &e.id,
+            timestamp_ns: e.timestamp_ns,
+            level: LogLevel::try_from(e.level)
+                .map(|l| l.as_str_name())
+                .unwrap_or("UNKNOWN"),
+            component: &e.component,
+            message: &e.message,
+            related_object_ids: &e.related_object_ids,
+        }
+    }
+}
+
+/// Encode one page of entries as a chunk in `format`.
+pub fn encode_chunk(entries: &[LogEntry], format: ExportFormat) -> std::io::Result<Vec<u8>> {
+    let mut ndjson = Vec::new();
+    for entry in entries {
+        serde_json::to_writer(&mut ndjson, &Record::from(entry))?;
+        ndjson.push(b'\n');
+    }
+    match format {
+        ExportFormat::Ndjson => Ok(ndjson),
+        ExportFormat::NdjsonGzip => {
+            let mut gz = GzEncoder::new(Vec::new(), Compression::default());
+            gz.write_all(&ndjson)?;
+            gz.finish()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::MultiGzDecoder;
+    use std::io::Read;
+
+    fn entry(id: &str, level: LogLevel, message: &str) -> LogEntry {
+        LogEntry {
+            id: id.to_string(),
+            timestamp_ns: 1_700_000_000_000_000_000,
+            message: message.to_string(),
+            level: level as i32,
+            component: "vmm".to_string(),
+            related_object_ids: vec!["vm-1".to_string()],
+        }
+    }
+
+    #[test]
+    fn ndjson_records_spell_out_the_level() {
+        let chunk = encode_chunk(
+            &[entry("01A", LogLevel::Audit, "VM created: vm-1")],
+            ExportFormat::Ndjson,
+        )
+        .unwrap();
+        let line = String::from_utf8(chunk).unwrap();
+        let record: serde_json::Value = serde_json::from_str(line.trim_end()).unwrap();
+        assert_eq!(record["level"], "AUDIT");
+        assert_eq!(record["message"], "VM created: vm-1");
+        assert_eq!(record["related_object_ids"][0], "vm-1");
+    }
+
+    #[test]
+    fn gzip_chunks_concatenate_into_one_file() {
+        let mut file = encode_chunk(
+            &[entry("01A", LogLevel::Info, "first")],
+            ExportFormat::NdjsonGzip,
+        )
+        .unwrap();
+        file.extend(
+            encode_chunk(
+                &[
+                    entry("01B", LogLevel::Warn, "second"),
+                    entry("01C", LogLevel::Error, "third"),
+                ],
+                ExportFormat::NdjsonGzip,
+            )
+            .unwrap(),
+        );
+
+        let mut text = String::new();
+        MultiGzDecoder::new(file.as_slice())
+            .read_to_string(&mut text)
+            .unwrap();
+        let ids: Vec<String> = text
+            .lines()
+            .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap()["id"].to_string())
+            .collect();
+        assert_eq!(ids, ["\"01A\"", "\"01B\"", "\"01C\""]);
+    }
+}
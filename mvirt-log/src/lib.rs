@@ -41,14 +41,21 @@ pub mod proto {
 }
 
 mod audit;
+pub mod batcher;
+pub mod clickhouse;
 pub mod command;
 pub mod distributed;
+pub mod export;
+pub mod server;
 pub mod storage;
 
 // Re-export commonly used types at crate root
 pub use proto::log_service_client::LogServiceClient;
 pub use proto::log_service_server::{LogService, LogServiceServer};
-pub use proto::{LogEntry, LogLevel, LogRequest, LogResponse, QueryRequest};
+pub use proto::{
+    ExportChunk, ExportFormat, ExportRequest, LogEntry, LogLevel, LogRequest, LogResponse,
+    QueryRequest,
+};
 
 // Re-export AuditLogger
 pub use audit::{create_audit_logger, tls_config_from_paths, AuditLogger};
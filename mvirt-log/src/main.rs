@@ -1,25 +1,13 @@
-use clap::Parser;
-use mraft::{NodeConfig, NodeId, RaftNode, StorageBackend};
+use clap::{Parser, Subcommand};
+use mraft::{NodeConfig, NodeId, StorageBackend};
 use std::collections::BTreeMap;
 use std::path::PathBuf;
-use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{mpsc, RwLock};
-use tokio_stream::wrappers::ReceiverStream;
-use tonic::{
-    transport::{Certificate, Identity, Server, ServerTlsConfig},
-    Request, Response, Status,
-};
-use tracing::{info, warn};
-
-use mvirt_log::command::{LogCommand, LogCommandResponse};
-use mvirt_log::distributed::DistributedLogStore;
-use mvirt_log::proto::{GetVersionRequest, VersionInfo};
-use mvirt_log::storage::{init_log_manager, LogManager, LogStateMachine};
-use mvirt_log::{LogEntry, LogRequest, LogResponse, LogService, LogServiceServer, QueryRequest};
-
-mod batcher;
-use batcher::Batcher;
+use tonic::transport::{Certificate, Identity, Server, ServerTlsConfig};
+use tracing::info;
+
+use mvirt_log::server::{start_node, LogServer};
+use mvirt_log::storage::{init_log_manager, open_backend, BackendConfig, LogBackend, LogManager};
+use mvirt_log::LogServiceServer;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
@@ -65,6 +53,27 @@ struct Args {
     /// Run in development mode (single-node, ephemeral storage)
     #[arg(long)]
     dev: bool,
+
+    #[command(flatten)]
+    storage: BackendConfig,
+
+    #[command(subcommand)]
+    command: Option<Cmd>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Copy every entry of an embedded store into the configured backend
+    /// (e.g. when switching a host to ClickHouse), then exit
+    Migrate {
+        /// Data directory of the embedded store to read
+        #[arg(long)]
+        from: PathBuf,
+
+        /// Entries per write
+        #[arg(long, default_value = "1000")]
+        batch_size: usize,
+    },
 }
 
 fn parse_peer(s: &str) -> Result<(NodeId, String), String> {
@@ -75,78 +84,6 @@ fn parse_peer(s: &str) -> Result<(NodeId, String), String> {
     Ok((id, addr.to_string()))
 }
 
-pub struct MyLogService {
-    store: Arc<DistributedLogStore>,
-    batcher: Arc<Batcher>,
-}
-
-#[tonic::async_trait]
-impl LogService for MyLogService {
-    async fn get_version(
-        &self,
-        _request: Request<GetVersionRequest>,
-    ) -> Result<Response<VersionInfo>, Status> {
-        Ok(Response::new(VersionInfo {
-            version: env!("CARGO_PKG_VERSION").to_string(),
-        }))
-    }
-
-    async fn log(&self, request: Request<LogRequest>) -> Result<Response<LogResponse>, Status> {
-        let req = request.into_inner();
-        let entry = req
-            .entry
-            .ok_or_else(|| Status::invalid_argument("Missing entry"))?;
-
-        self.batcher.submit(entry);
-        Ok(Response::new(LogResponse { id: String::new() }))
-    }
-
-    type QueryStream = ReceiverStream<Result<LogEntry, Status>>;
-
-    async fn query(
-        &self,
-        request: Request<QueryRequest>,
-    ) -> Result<Response<Self::QueryStream>, Status> {
-        let req = request.into_inner();
-        let (tx, rx) = mpsc::channel(64);
-        let store = self.store.clone();
-
-        tokio::spawn(async move {
-            let limit = if req.limit == 0 {
-                100
-            } else {
-                req.limit as usize
-            };
-
-            let start = req.start_time_ns;
-            let end = req.end_time_ns;
-
-            let obj = match req.object_id {
-                Some(o) if o.is_empty() => None,
-                Some(o) => Some(o),
-                None => None,
-            };
-
-            match store.query(obj, start, end, limit).await {
-                Ok(logs) => {
-                    for log in logs {
-                        if tx.send(Ok(log)).await.is_err() {
-                            break;
-                        }
-                    }
-                }
-                Err(e) => {
-                    let _ = tx
-                        .send(Err(Status::internal(format!("Query failed: {}", e))))
-                        .await;
-                }
-            }
-        });
-
-        Ok(Response::new(ReceiverStream::new(rx)))
-    }
-}
-
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     tracing_subscriber::fmt::init();
@@ -156,9 +93,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
 
     std::fs::create_dir_all(&args.data_dir)?;
 
-    info!("Opening log storage at {:?}", args.data_dir);
-    let manager = Arc::new(LogManager::new(&args.data_dir)?);
-    init_log_manager(manager);
+    info!("Opening {:?} log storage", args.storage.kind);
+    let backend = open_backend(&args.storage, &args.data_dir).await?;
+
+    if let Some(Cmd::Migrate { from, batch_size }) = args.command {
+        let source = LogManager::new(&from)?;
+        let copied = migrate(&source, backend.as_ref(), batch_size.max(1))?;
+        info!("Copied {} log entries from {}", copied, from.display());
+        return Ok(());
+    }
+    init_log_manager(backend);
 
     let peers: BTreeMap<NodeId, String> = args.peer.into_iter().collect();
 
@@ -176,29 +120,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         raft_config: None,
     };
 
-    let mut node: RaftNode<LogCommand, LogCommandResponse, LogStateMachine> =
-        RaftNode::new(config, LogStateMachine).await?;
-    node.start().await?;
-
-    if args.bootstrap || args.dev {
-        info!("Bootstrapping new cluster");
-        node.generate_cluster_secret();
-        node.initialize_cluster().await?;
-    }
-
-    info!("Waiting for leader election...");
-    if let Some(leader) = node.wait_for_leader(Duration::from_secs(10)).await {
-        info!("Leader elected: node {}", leader);
-    } else {
-        warn!("No leader elected within timeout");
-    }
-
-    let raft_node = Arc::new(RwLock::new(node));
-    let store = Arc::new(DistributedLogStore::new(raft_node));
-    let batcher = Arc::new(Batcher::new(store.clone()));
+    let store = start_node(config, args.bootstrap || args.dev).await?;
 
     let addr = args.listen.parse()?;
-    let service = MyLogService { store, batcher };
+    let service = LogServer::new(store);
 
     let tls = build_tls_config(args.tls_ca, args.tls_cert, args.tls_key, args.dev)?;
 
@@ -218,6 +143,30 @@ async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     Ok(())
 }
 
+/// Copy all entries of `source` to `target`, oldest first. Entries keep
+/// their ids, so rerunning an interrupted copy doesn't duplicate anything
+/// in ClickHouse (inserts of the same batch are deduplicated).
+fn migrate(
+    source: &LogManager,
+    target: &dyn LogBackend,
+    batch_size: usize,
+) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+    let mut copied = 0;
+    let mut after: Option<String> = None;
+    loop {
+        let batch = source.scan(after.as_deref(), batch_size)?;
+        let Some(last) = batch.last() else {
+            return Ok(copied);
+        };
+        after = Some(last.id.clone());
+        copied += batch.len();
+        target.append_batch(batch)?;
+        if copied % (batch_size * 100) < batch_size {
+            info!("Copied {} entries so far", copied);
+        }
+    }
+}
+
 /// Construct the tonic ServerTlsConfig from PEM files.
 ///
 /// All three paths are required together. In --dev mode all three may be
@@ -0,0 +1,195 @@
+//! LogService implementation and Raft node startup.
+//!
+//! Used by the `mvirt-log` binary and by `mvirtd`, which embeds the log
+//! service next to the other host daemons.
+
+use mraft::{NodeConfig, RaftNode};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, RwLock};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+
+use crate::batcher::Batcher;
+use crate::command::{LogCommand, LogCommandResponse};
+use crate::distributed::DistributedLogStore;
+use crate::export;
+use crate::proto::{GetVersionRequest, VersionInfo};
+use crate::storage::LogStateMachine;
+use crate::{
+    ExportChunk, ExportFormat, ExportRequest, LogEntry, LogRequest, LogResponse, LogService,
+    QueryRequest,
+};
+
+/// How long to wait for a leader before serving anyway.
+const LEADER_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Start this node's Raft instance, bootstrapping a new cluster if asked,
+/// and wait (bounded) for a leader.
+///
+/// Expects the global log manager to be initialized.
+pub async fn start_node(
+    config: NodeConfig,
+    bootstrap: bool,
+) -> Result<Arc<DistributedLogStore>, Box<dyn std::error::Error + Send + Sync>> {
+    let mut node: RaftNode<LogCommand, LogCommandResponse, LogStateMachine> =
+        RaftNode::new(config, LogStateMachine).await?;
+    node.start().await?;
+
+    if bootstrap {
+        info!("Bootstrapping new cluster");
+        node.generate_cluster_secret();
+        node.initialize_cluster().await?;
+    }
+
+    info!("Waiting for leader election...");
+    if let Some(leader) = node.wait_for_leader(LEADER_TIMEOUT).await {
+        info!("Leader elected: node {}", leader);
+    } else {
+        warn!("No leader elected within timeout");
+    }
+
+    Ok(Arc::new(DistributedLogStore::new(Arc::new(RwLock::new(
+        node,
+    )))))
+}
+
+pub struct LogServer {
+    store: Arc<DistributedLogStore>,
+    batcher: Arc<Batcher>,
+}
+
+impl LogServer {
+    pub fn new(store: Arc<DistributedLogStore>) -> Self {
+        let batcher = Arc::new(Batcher::new(store.clone()));
+        Self { store, batcher }
+    }
+}
+
+#[tonic::async_trait]
+impl LogService for LogServer {
+    async fn get_version(
+        &self,
+        _request: Request<GetVersionRequest>,
+    ) -> Result<Response<VersionInfo>, Status> {
+        Ok(Response::new(VersionInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+        }))
+    }
+
+    async fn log(&self, request: Request<LogRequest>) -> Result<Response<LogResponse>, Status> {
+        let req = request.into_inner();
+        let entry = req
+            .entry
+            .ok_or_else(|| Status::invalid_argument("Missing entry"))?;
+
+        self.batcher.submit(entry);
+        Ok(Response::new(LogResponse { id: String::new() }))
+    }
+
+    type QueryStream = ReceiverStream<Result<LogEntry, Status>>;
+
+    async fn query(
+        &self,
+        request: Request<QueryRequest>,
+    ) -> Result<Response<Self::QueryStream>, Status> {
+        let req = request.into_inner();
+        let (tx, rx) = mpsc::channel(64);
+        let store = self.store.clone();
+
+        tokio::spawn(async move {
+            let limit = if req.limit == 0 {
+                100
+            } else {
+                req.limit as usize
+            };
+
+            let start = req.start_time_ns;
+            let end = req.end_time_ns;
+
+            let obj = match req.object_id {
+                Some(o) if o.is_empty() => None,
+                Some(o) => Some(o),
+                None => None,
+            };
+
+            match store.query(obj, start, end, limit).await {
+                Ok(logs) => {
+                    for log in logs {
+                        if tx.send(Ok(log)).await.is_err() {
+                            break;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let _ = tx
+                        .send(Err(Status::internal(format!("Query failed: {}", e))))
+                        .await;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ExportStream = ReceiverStream<Result<ExportChunk, Status>>;
+
+    async fn export(
+        &self,
+        request: Request<ExportRequest>,
+    ) -> Result<Response<Self::ExportStream>, Status> {
+        let req = request.into_inner();
+        let format = ExportFormat::try_from(req.format)
+            .map_err(|_| Status::invalid_argument("Unknown export format"))?;
+        let (tx, rx) = mpsc::channel(4);
+        let store = self.store.clone();
+
+        tokio::spawn(async move {
+            let obj = req.object_id.filter(|o| !o.is_empty());
+            let mut cursor = Some(req.cursor).filter(|c| !c.is_empty());
+            loop {
+                let page = match store
+                    .query_after(
+                        obj.clone(),
+                        req.start_time_ns,
+                        req.end_time_ns,
+                        cursor.as_deref(),
+                        export::PAGE_SIZE,
+                    )
+                    .await
+                {
+                    Ok(page) => page,
+                    Err(e) => {
+                        let _ = tx
+                            .send(Err(Status::internal(format!("Export failed: {}", e))))
+                            .await;
+                        return;
+                    }
+                };
+                let Some(last) = page.last() else {
+                    return;
+                };
+                let chunk = match export::encode_chunk(&page, format) {
+                    Ok(data) => ExportChunk {
+                        data,
+                        cursor: last.id.clone(),
+                        entries: page.len() as u32,
+                    },
+                    Err(e) => {
+                        let _ = tx
+                            .send(Err(Status::internal(format!("Export failed: {}", e))))
+                            .await;
+                        return;
+                    }
+                };
+                cursor = Some(chunk.cursor.clone());
+                if tx.send(Ok(chunk)).await.is_err() || page.len() < export::PAGE_SIZE {
+                    return;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
@@ -1,3 +1,11 @@
+//! Log entry storage.
+//!
+//! Raft replicates batches of entries; each peer's state machine hands
+//! them to a [`LogBackend`]. The default is the embedded redb store
+//! ([`LogManager`]), one copy per peer. For high-volume deployments the
+//! peers can share a ClickHouse database instead (see
+//! [`crate::clickhouse`]); the backend is picked with [`BackendConfig`].
+
 use anyhow::Result;
 use mraft::StateMachine;
 use prost::Message;
@@ -17,24 +25,110 @@ const TABLE_IDX_OBJECT: TableDefinition<(&str, u128), ()> = TableDefinition::new
 const TABLE_IDX_COMPONENT: TableDefinition<(&str, i32, u128), ()> =
     TableDefinition::new("idx_component");
 
-/// Global LogManager instance, set once at startup before Raft node creation.
-static LOG_MANAGER: OnceLock<Arc<LogManager>> = OnceLock::new();
+/// Where a peer stores the entries it applies.
+///
+/// Called from the Raft state machine, so both methods are synchronous.
+/// Entries arrive with `id` (a ULID) and `timestamp_ns` already set;
+/// queries return them in id order.
+pub trait LogBackend: Send + Sync {
+    fn append_batch(&self, entries: Vec<LogEntry>) -> Result<()>;
+
+    fn query(
+        &self,
+        object_id: Option<String>,
+        start_ns: Option<i64>,
+        end_ns: Option<i64>,
+        limit: usize,
+    ) -> Result<Vec<LogEntry>> {
+        self.query_after(object_id, start_ns, end_ns, None, limit)
+    }
+
+    /// [`query`](Self::query), skipping entries up to and including the
+    /// id `after`. Paging with the last id seen walks the whole result.
+    fn query_after(
+        &self,
+        object_id: Option<String>,
+        start_ns: Option<i64>,
+        end_ns: Option<i64>,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<LogEntry>>;
+}
+
+/// Storage backend kinds.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// redb file in the data directory
+    #[default]
+    Embedded,
+    /// Shared ClickHouse database, over its HTTP interface
+    Clickhouse,
+}
+
+/// Storage settings, shared by the mvirt-log command line and mvirtd's
+/// `[log.storage]` table.
+#[derive(Debug, Clone, Default, clap::Args, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BackendConfig {
+    /// Storage backend for log entries
+    #[arg(
+        long = "backend",
+        value_enum,
+        env = "MVIRT_LOG_BACKEND",
+        default_value_t
+    )]
+    #[serde(rename = "backend")]
+    pub kind: BackendKind,
+
+    /// ClickHouse HTTP endpoint, e.g. http://clickhouse:8123
+    #[arg(long, env = "MVIRT_LOG_CLICKHOUSE_URL")]
+    pub clickhouse_url: Option<String>,
+
+    /// ClickHouse database (created if missing)
+    #[arg(long, env = "MVIRT_LOG_CLICKHOUSE_DATABASE")]
+    pub clickhouse_database: Option<String>,
+
+    /// ClickHouse user
+    #[arg(long, env = "MVIRT_LOG_CLICKHOUSE_USER")]
+    pub clickhouse_user: Option<String>,
+
+    /// ClickHouse password
+    #[arg(long, env = "MVIRT_LOG_CLICKHOUSE_PASSWORD", hide_env_values = true)]
+    pub clickhouse_password: Option<String>,
+}
+
+/// Open the configured backend. `data_dir` holds the embedded store.
+pub async fn open_backend(config: &BackendConfig, data_dir: &Path) -> Result<Arc<dyn LogBackend>> {
+    match config.kind {
+        BackendKind::Embedded => Ok(Arc::new(LogManager::new(data_dir)?)),
+        BackendKind::Clickhouse => {
+            let backend = crate::clickhouse::ClickHouseBackend::connect(config).await?;
+            Ok(Arc::new(backend))
+        }
+    }
+}
+
+/// Global backend, set once at startup before Raft node creation.
+static LOG_MANAGER: OnceLock<Arc<dyn LogBackend>> = OnceLock::new();
 
-/// Initialize the global LogManager. Must be called before creating the RaftNode.
-pub fn init_log_manager(manager: Arc<LogManager>) {
+/// Initialize the global backend. Must be called before creating the RaftNode.
+pub fn init_log_manager(manager: Arc<dyn LogBackend>) {
     assert!(
         LOG_MANAGER.set(manager).is_ok(),
         "LogManager already initialized"
     );
 }
 
-/// Get the global LogManager.
-pub fn log_manager() -> &'static Arc<LogManager> {
+/// Get the global backend.
+pub fn log_manager() -> &'static Arc<dyn LogBackend> {
     LOG_MANAGER
         .get()
         .expect("LogManager not initialized — call init_log_manager() first")
 }
 
+/// The embedded store: entries keyed by ULID, plus indexes by object and
+/// by component/level.
 pub struct LogManager {
     db: Database,
 }
@@ -88,11 +182,34 @@ impl LogManager {
         start_ns: Option<i64>,
         end_ns: Option<i64>,
         limit: usize,
+    ) -> Result<Vec<LogEntry>> {
+        self.query_after(object_id, start_ns, end_ns, None, limit)
+    }
+
+    pub fn query_after(
+        &self,
+        object_id: Option<String>,
+        start_ns: Option<i64>,
+        end_ns: Option<i64>,
+        after: Option<&str>,
+        limit: usize,
     ) -> Result<Vec<LogEntry>> {
         let start_ms = start_ns.unwrap_or(0) / 1_000_000;
         let end_ms = end_ns.unwrap_or(i64::MAX) / 1_000_000;
-        let min_ulid = Ulid::from_parts(start_ms as u64, 0).0;
+        let mut min_ulid = Ulid::from_parts(start_ms as u64, 0).0;
         let max_ulid = Ulid::from_parts(end_ms as u64, u128::MAX).0;
+        if let Some(id) = after {
+            let ulid: Ulid = id
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid ULID {id}: {e}"))?;
+            match ulid.0.checked_add(1) {
+                Some(next) => min_ulid = min_ulid.max(next),
+                None => return Ok(Vec::new()),
+            }
+        }
+        if min_ulid > max_ulid {
+            return Ok(Vec::new());
+        }
 
         let txn = self.db.begin_read()?;
         let logs = txn.open_table(TABLE_LOGS)?;
@@ -128,9 +245,51 @@ impl LogManager {
 
         Ok(results)
     }
+
+    /// Up to `limit` entries with ids after `after`, oldest first; for
+    /// copying the whole store elsewhere.
+    pub fn scan(&self, after: Option<&str>, limit: usize) -> Result<Vec<LogEntry>> {
+        let start = match after {
+            Some(id) => {
+                let ulid: Ulid = id
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid ULID {id}: {e}"))?;
+                match ulid.0.checked_add(1) {
+                    Some(next) => next,
+                    None => return Ok(Vec::new()),
+                }
+            }
+            None => 0,
+        };
+        let txn = self.db.begin_read()?;
+        let logs = txn.open_table(TABLE_LOGS)?;
+        let mut results = Vec::new();
+        for item in logs.range(start..)?.take(limit) {
+            let (_, value) = item?;
+            results.push(LogEntry::decode(value.value())?);
+        }
+        Ok(results)
+    }
+}
+
+impl LogBackend for LogManager {
+    fn append_batch(&self, entries: Vec<LogEntry>) -> Result<()> {
+        LogManager::append_batch(self, entries)
+    }
+
+    fn query_after(
+        &self,
+        object_id: Option<String>,
+        start_ns: Option<i64>,
+        end_ns: Option<i64>,
+        after: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<LogEntry>> {
+        LogManager::query_after(self, object_id, start_ns, end_ns, after, limit)
+    }
 }
 
-/// Raft state machine. Accesses the global LogManager for writes.
+/// Raft state machine. Writes through the global backend.
 /// Serialization is stubbed — snapshot/restore deferred for append-only log data.
 #[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct LogStateMachine;
@@ -151,7 +310,7 @@ impl StateMachine<LogCommand, LogCommandResponse> for LogStateMachine {
         }
     }
 
-    // Append-only log data: the SQLite file owned by `log_manager` is the
+    // Append-only log data: the backend behind `log_manager` is the
     // source of truth and is independently durable. Raft snapshots don't
     // need to carry log content, so these are no-ops. `requires_external_persistence`
     // (mraft) signals that to the snapshot/restore machinery.
@@ -312,6 +471,69 @@ mod tests {
         assert_eq!(r2[0].message, "multi-obj");
     }
 
+    #[test]
+    fn scan_pages_through_everything() {
+        let dir = TempDir::new().unwrap();
+        let mgr = LogManager::new(dir.path()).unwrap();
+
+        let entries: Vec<LogEntry> = (0..5)
+            .map(|i| {
+                let ts = 1_700_000_000_000_000_000i64 + i * 1_000_000;
+                let id = ulid_at_ms((ts / 1_000_000) as u64);
+                make_entry(&id, ts, &format!("log {i}"), vec![])
+            })
+            .collect();
+        mgr.append_batch(entries).unwrap();
+
+        let mut seen = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let page = mgr.scan(after.as_deref(), 2).unwrap();
+            let Some(last) = page.last() else { break };
+            after = Some(last.id.clone());
+            seen.extend(page.into_iter().map(|e| e.message));
+        }
+        assert_eq!(seen, ["log 0", "log 1", "log 2", "log 3", "log 4"]);
+    }
+
+    #[test]
+    fn query_after_resumes_filtered_results() {
+        let dir = TempDir::new().unwrap();
+        let mgr = LogManager::new(dir.path()).unwrap();
+
+        // Same millisecond, so only the cursor tells them apart
+        let ts = 1_700_000_000_000_000_000i64;
+        let mut ids: Vec<String> = (0..4)
+            .map(|_| ulid_at_ms((ts / 1_000_000) as u64))
+            .collect();
+        ids.sort();
+        let entries = ids
+            .iter()
+            .enumerate()
+            .map(|(i, id)| {
+                let obj = if i % 2 == 0 { "vm-1" } else { "vm-2" };
+                make_entry(id, ts, &format!("log {i}"), vec![obj])
+            })
+            .collect();
+        mgr.append_batch(entries).unwrap();
+
+        let page = mgr
+            .query_after(Some("vm-1".to_string()), None, None, None, 1)
+            .unwrap();
+        assert_eq!(page[0].message, "log 0");
+        let page = mgr
+            .query_after(Some("vm-1".to_string()), None, None, Some(&page[0].id), 10)
+            .unwrap();
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].message, "log 2");
+
+        let rest = mgr
+            .query_after(None, None, None, Some(&ids[1]), 10)
+            .unwrap();
+        let messages: Vec<_> = rest.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["log 2", "log 3"]);
+    }
+
     #[test]
     fn state_machine_apply() {
         let dir = TempDir::new().unwrap();
@@ -0,0 +1,4 @@
+-- Owner, group and mode of the NIC's vhost-user socket; NULL = daemon default
+ALTER TABLE nics ADD COLUMN socket_uid INTEGER;
+ALTER TABLE nics ADD COLUMN socket_gid INTEGER;
+ALTER TABLE nics ADD COLUMN socket_mode INTEGER;
@@ -0,0 +1,9 @@
+-- Floating IPs: public IPv4 addresses from the daemon's pool, translated
+-- 1:1 to the IPv4 address of at most one NIC each. A NIC has at most one.
+CREATE TABLE floating_ips (
+    id TEXT PRIMARY KEY,
+    address TEXT NOT NULL UNIQUE,
+    nic_id TEXT UNIQUE REFERENCES nics(id) ON DELETE SET NULL,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
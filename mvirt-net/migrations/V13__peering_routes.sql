@@ -0,0 +1,5 @@
+-- Peerings made with ConnectNetworks also route between the two networks;
+-- allowed_prefixes (JSON array) limits them to those addresses, empty
+-- for all. 0 = isolation only, 1 = routed
+ALTER TABLE peerings ADD COLUMN routed INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE peerings ADD COLUMN allowed_prefixes TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,3 @@
+-- Upstream DNS servers (JSON array of socket addresses) the gateway
+-- forwards names outside a network's zone to; empty uses the daemon's
+ALTER TABLE networks ADD COLUMN dns_forwarders TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,4 @@
+-- Network boot options handed out via DHCPv4: the TFTP server (NULL for
+-- none) and the boot file or UEFI HTTP boot URL (NULL = no network boot)
+ALTER TABLE networks ADD COLUMN boot_server TEXT;
+ALTER TABLE networks ADD COLUMN boot_file TEXT;
@@ -0,0 +1,11 @@
+-- Static routes table
+CREATE TABLE routes (
+    id TEXT PRIMARY KEY,
+    network_id TEXT NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    destination TEXT NOT NULL,
+    next_hop TEXT,
+    created_at TEXT NOT NULL,
+    UNIQUE(network_id, destination)
+);
+
+CREATE INDEX idx_routes_network_id ON routes(network_id);
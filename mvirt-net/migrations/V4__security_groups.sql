@@ -0,0 +1,37 @@
+-- Security groups, shared with mvirt-ebpf through the same API.
+-- Rules are always "allow"; with a group attached, ingress is default-deny.
+CREATE TABLE security_groups (
+    id TEXT PRIMARY KEY,
+    name TEXT UNIQUE NOT NULL,
+    description TEXT,
+    audit INTEGER NOT NULL DEFAULT 0,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE security_group_rules (
+    id TEXT PRIMARY KEY,
+    security_group_id TEXT NOT NULL REFERENCES security_groups(id) ON DELETE CASCADE,
+    direction TEXT NOT NULL CHECK(direction IN ('ingress', 'egress')),
+    protocol TEXT NOT NULL CHECK(protocol IN ('all', 'tcp', 'udp', 'icmp', 'icmpv6')),
+    port_start INTEGER,
+    port_end INTEGER,
+    cidr TEXT,  -- NULL = any
+    description TEXT,
+    not_before TEXT,
+    not_after TEXT,
+    schedule TEXT,
+    created_at TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
+
+CREATE TABLE nic_security_groups (
+    nic_id TEXT NOT NULL REFERENCES nics(id) ON DELETE CASCADE,
+    security_group_id TEXT NOT NULL REFERENCES security_groups(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    PRIMARY KEY (nic_id, security_group_id)
+);
+
+CREATE INDEX idx_security_group_rules_sg_id ON security_group_rules(security_group_id);
+CREATE INDEX idx_nic_security_groups_nic_id ON nic_security_groups(nic_id);
+CREATE INDEX idx_nic_security_groups_sg_id ON nic_security_groups(security_group_id);
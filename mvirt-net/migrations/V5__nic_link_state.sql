@@ -0,0 +1,2 @@
+-- Administrative link state the guest sees; 0 = down, 1 = up
+ALTER TABLE nics ADD COLUMN link_up INTEGER NOT NULL DEFAULT 1;
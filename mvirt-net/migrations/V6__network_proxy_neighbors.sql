@@ -0,0 +1,2 @@
+-- Proxy ARP/ND on the uplink for the routed prefixes of a network's NICs
+ALTER TABLE networks ADD COLUMN proxy_neighbors INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,14 @@
+-- Static DHCP leases: addresses a NIC's DHCP server hands to other MACs
+-- behind it (nested VMs, containers on a bridge in the guest)
+CREATE TABLE leases (
+    id TEXT PRIMARY KEY,
+    network_id TEXT NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    nic_id TEXT NOT NULL REFERENCES nics(id) ON DELETE CASCADE,
+    mac_address TEXT NOT NULL,
+    ipv4_address TEXT NOT NULL,
+    created_at TEXT NOT NULL,
+    UNIQUE(network_id, mac_address),
+    UNIQUE(network_id, ipv4_address)
+);
+
+CREATE INDEX idx_leases_nic_id ON leases(nic_id);
@@ -0,0 +1,2 @@
+-- IPv6 prefix delegated to the NIC over DHCPv6 (IA_PD); NULL = none
+ALTER TABLE nics ADD COLUMN delegated_ipv6_prefix TEXT;
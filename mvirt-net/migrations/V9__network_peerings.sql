@@ -0,0 +1,11 @@
+-- Network peerings: pairs of networks strict isolation lets talk to each
+-- other. network_a_id is the lower of the two IDs.
+CREATE TABLE peerings (
+    id TEXT PRIMARY KEY,
+    network_a_id TEXT NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    network_b_id TEXT NOT NULL REFERENCES networks(id) ON DELETE CASCADE,
+    created_at TEXT NOT NULL,
+    UNIQUE(network_a_id, network_b_id)
+);
+
+CREATE INDEX idx_peerings_network_b_id ON peerings(network_b_id);
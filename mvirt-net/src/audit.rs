@@ -3,41 +3,61 @@
 //! Wraps the shared AuditLogger with network-specific convenience methods.
 //! All logging is fire-and-forget (non-blocking) to avoid blocking gRPC handlers.
 
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
 use mvirt_log::{AuditLogger, LogLevel};
-use tonic::transport::ClientTlsConfig;
+use tonic::transport::{Channel, ClientTlsConfig};
 
 /// Network audit logger with domain-specific methods.
 ///
 /// All log methods are fire-and-forget: they spawn a task to send the log
 /// and return immediately without blocking the caller.
 pub struct NetAuditLogger {
-    inner: Arc<AuditLogger>,
+    /// Replaced when the log endpoints are reloaded
+    inner: RwLock<Arc<AuditLogger>>,
 }
 
 impl NetAuditLogger {
     /// Create a new network audit logger
     pub fn new(endpoints: Vec<String>, tls: Option<ClientTlsConfig>) -> Self {
-        let inner = AuditLogger::new(endpoints, "net", tls).unwrap_or_else(|e| {
+        Self {
+            inner: RwLock::new(Arc::new(Self::connect(endpoints, tls))),
+        }
+    }
+
+    /// Send to other mvirt-log endpoints from now on; entries already on
+    /// their way go to the old ones.
+    pub fn set_endpoints(&self, endpoints: Vec<String>, tls: Option<ClientTlsConfig>) {
+        let inner = Arc::new(Self::connect(endpoints, tls));
+        *self.inner.write().unwrap() = inner;
+    }
+
+    fn connect(endpoints: Vec<String>, tls: Option<ClientTlsConfig>) -> AuditLogger {
+        AuditLogger::new(endpoints, "net", tls).unwrap_or_else(|e| {
             tracing::warn!(error = %e, "NetAuditLogger init failed; falling back to noop");
             AuditLogger::new_noop()
-        });
+        })
+    }
+
+    /// Create an audit logger on an existing mvirt-log channel
+    pub fn with_channel(channel: Channel) -> Self {
         Self {
-            inner: Arc::new(inner),
+            inner: RwLock::new(Arc::new(AuditLogger::with_channel(channel, "net"))),
         }
     }
 
     /// Create a noop audit logger (for testing)
     pub fn new_noop() -> Self {
         Self {
-            inner: Arc::new(AuditLogger::new_noop()),
+            inner: RwLock::new(Arc::new(AuditLogger::new_noop())),
         }
     }
 
     /// Fire-and-forget log helper
     fn log_async(&self, level: LogLevel, message: String, object_ids: Vec<String>) {
-        let inner = Arc::clone(&self.inner);
+        let inner = Arc::clone(&self.inner.read().unwrap());
+        // The spawned task doesn't inherit the caller's request ID
+        let object_ids = mvirt_common::request_id::tag(object_ids);
         tokio::spawn(async move {
             inner.log(level, message, object_ids).await;
         });
@@ -101,6 +121,14 @@ impl NetAuditLogger {
         );
     }
 
+    pub fn nic_link_state(&self, nic_id: &str, up: bool) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("NIC link set {}", if up { "up" } else { "down" }),
+            vec![nic_id.to_string()],
+        );
+    }
+
     pub fn nic_deleted(&self, nic_id: &str, network_id: &str) {
         self.log_async(
             LogLevel::Audit,
@@ -125,6 +153,22 @@ impl NetAuditLogger {
         );
     }
 
+    pub fn nic_reactor_crashed(&self, nic_id: &str, crashes: u32, panic: &str) {
+        self.log_async(
+            LogLevel::Error,
+            format!("NIC reactor crashed ({} crashes): {}", crashes, panic),
+            vec![nic_id.to_string()],
+        );
+    }
+
+    pub fn nic_reactor_restarted(&self, nic_id: &str) {
+        self.log_async(
+            LogLevel::Info,
+            "NIC reactor restarted".to_string(),
+            vec![nic_id.to_string()],
+        );
+    }
+
     // === Routing Events ===
 
     pub fn route_added(&self, nic_id: &str, prefix: &str) {
@@ -142,6 +186,328 @@ impl NetAuditLogger {
             vec![nic_id.to_string()],
         );
     }
+
+    pub fn static_route_added(
+        &self,
+        route_id: &str,
+        network_id: &str,
+        destination: &str,
+        next_hop: Option<&str>,
+    ) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Static route added: {} via {}",
+                destination,
+                next_hop.unwrap_or("uplink")
+            ),
+            vec![route_id.to_string(), network_id.to_string()],
+        );
+    }
+
+    pub fn static_route_removed(&self, route_id: &str, network_id: &str, destination: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Static route removed: {}", destination),
+            vec![route_id.to_string(), network_id.to_string()],
+        );
+    }
+
+    pub fn lease_created(&self, lease_id: &str, nic_id: &str, mac: &str, ipv4: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("DHCP lease created: {} for {}", ipv4, mac),
+            vec![lease_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    pub fn lease_deleted(&self, lease_id: &str, nic_id: &str, ipv4: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("DHCP lease deleted: {}", ipv4),
+            vec![lease_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    // === Network Peering Events ===
+
+    pub fn peering_created(&self, peering_id: &str, network_a_id: &str, network_b_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Networks peered: {} and {}", network_a_id, network_b_id),
+            vec![
+                peering_id.to_string(),
+                network_a_id.to_string(),
+                network_b_id.to_string(),
+            ],
+        );
+    }
+
+    pub fn peering_deleted(&self, peering_id: &str, network_a_id: &str, network_b_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Network peering removed: {} and {}",
+                network_a_id, network_b_id
+            ),
+            vec![
+                peering_id.to_string(),
+                network_a_id.to_string(),
+                network_b_id.to_string(),
+            ],
+        );
+    }
+
+    pub fn networks_connected(
+        &self,
+        peering_id: &str,
+        network_a_id: &str,
+        network_b_id: &str,
+        allowed_prefixes: &[String],
+    ) {
+        let allowed = if allowed_prefixes.is_empty() {
+            "all addresses".to_string()
+        } else {
+            allowed_prefixes.join(", ")
+        };
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Networks connected: {} and {} ({})",
+                network_a_id, network_b_id, allowed
+            ),
+            vec![
+                peering_id.to_string(),
+                network_a_id.to_string(),
+                network_b_id.to_string(),
+            ],
+        );
+    }
+
+    pub fn networks_disconnected(&self, peering_id: &str, network_a_id: &str, network_b_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Networks disconnected: {} and {}",
+                network_a_id, network_b_id
+            ),
+            vec![
+                peering_id.to_string(),
+                network_a_id.to_string(),
+                network_b_id.to_string(),
+            ],
+        );
+    }
+
+    // === Port Forward Events ===
+
+    pub fn port_forward_created(&self, forward_id: &str, nic_id: &str, host: &str, vm: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Port forwarded: {} to {}", host, vm),
+            vec![forward_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    pub fn port_forward_deleted(&self, forward_id: &str, nic_id: &str, host: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Port forward removed: {}", host),
+            vec![forward_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    // === Floating IP Events ===
+
+    pub fn floating_ip_created(&self, fip_id: &str, address: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Floating IP {} allocated", address),
+            vec![fip_id.to_string()],
+        );
+    }
+
+    pub fn floating_ip_associated(&self, fip_id: &str, address: &str, nic_id: &str, vm_ip: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Floating IP {} associated with {}", address, vm_ip),
+            vec![fip_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    pub fn floating_ip_disassociated(&self, fip_id: &str, address: &str, nic_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Floating IP {} disassociated", address),
+            vec![fip_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    pub fn floating_ip_deleted(&self, fip_id: &str, address: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Floating IP {} released", address),
+            vec![fip_id.to_string()],
+        );
+    }
+
+    // === Security Group Events ===
+
+    pub fn security_group_created(&self, sg_id: &str, sg_name: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Security group '{}' created", sg_name),
+            vec![sg_id.to_string()],
+        );
+    }
+
+    pub fn security_group_deleted(&self, sg_id: &str, sg_name: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Security group '{}' deleted", sg_name),
+            vec![sg_id.to_string()],
+        );
+    }
+
+    pub fn security_group_rule_added(&self, rule_id: &str, sg_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            "Security group rule added".to_string(),
+            vec![rule_id.to_string(), sg_id.to_string()],
+        );
+    }
+
+    pub fn security_group_rule_removed(&self, rule_id: &str, sg_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            "Security group rule removed".to_string(),
+            vec![rule_id.to_string(), sg_id.to_string()],
+        );
+    }
+
+    pub fn security_group_attached(&self, sg_id: &str, nic_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            "Security group attached to NIC".to_string(),
+            vec![sg_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    pub fn security_group_detached(&self, sg_id: &str, nic_id: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            "Security group detached from NIC".to_string(),
+            vec![sg_id.to_string(), nic_id.to_string()],
+        );
+    }
+
+    pub fn security_group_audit_set(&self, sg_id: &str, sg_name: &str, audit: bool) {
+        let mode = if audit { "audit" } else { "enforce" };
+        self.log_async(
+            LogLevel::Audit,
+            format!("Security group '{}' set to {} mode", sg_name, mode),
+            vec![sg_id.to_string()],
+        );
+    }
+
+    // === Packet Capture ===
+
+    pub fn capture_started(&self, capture_id: &str, nic_ids: &[String]) {
+        let mut object_ids = vec![capture_id.to_string()];
+        object_ids.extend_from_slice(nic_ids);
+        self.log_async(
+            LogLevel::Audit,
+            format!("Packet capture started on {} NIC(s)", nic_ids.len()),
+            object_ids,
+        );
+    }
+
+    pub fn capture_stopped(&self, capture_id: &str, captured: u64, dropped: u64) {
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Packet capture stopped: {} frames captured, {} dropped",
+                captured, dropped
+            ),
+            vec![capture_id.to_string()],
+        );
+    }
+
+    // === Cleanup ===
+
+    pub fn orphan_removed(&self, kind: &str, name: &str) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Removed orphaned {} {}", kind, name),
+            vec![],
+        );
+    }
+
+    pub fn address_reallocated(&self, nic_id: &str, old: &str, new: Option<&str>, reason: &str) {
+        let message = match new {
+            Some(new) => format!(
+                "Moved {} off reserved {} {} to {}",
+                nic_id, reason, old, new
+            ),
+            None => format!(
+                "NIC {} holds reserved {} {}; no free address to move it to",
+                nic_id, reason, old
+            ),
+        };
+        self.log_async(LogLevel::Audit, message, vec![nic_id.to_string()]);
+    }
+
+    // === Address Conflicts ===
+
+    pub fn address_conflict(
+        &self,
+        nic_id: &str,
+        owner_nic_id: &str,
+        kind: &str,
+        address: &str,
+        quarantined: bool,
+    ) {
+        let mut message = format!(
+            "NIC {} sends from {} {} allocated to NIC {}",
+            nic_id, kind, address, owner_nic_id
+        );
+        if quarantined {
+            message.push_str("; link set down");
+        }
+        self.log_async(
+            LogLevel::Warn,
+            message,
+            vec![nic_id.to_string(), owner_nic_id.to_string()],
+        );
+    }
+
+    // === Configuration ===
+
+    pub fn config_reloaded(&self, changed: &[&str], nics_started: usize) {
+        let changed = if changed.is_empty() {
+            "nothing".to_string()
+        } else {
+            changed.join(", ")
+        };
+        self.log_async(
+            LogLevel::Audit,
+            format!(
+                "Configuration reloaded: changed {}, started {} NICs",
+                changed, nics_started
+            ),
+            vec![],
+        );
+    }
+
+    // === Host Migration ===
+
+    pub fn state_imported(&self, networks: usize, nics: usize) {
+        self.log_async(
+            LogLevel::Audit,
+            format!("Host state imported: {} networks, {} NICs", networks, nics),
+            vec![],
+        );
+    }
 }
 
 /// Create a shared network audit logger
@@ -0,0 +1,439 @@
+//! Packet capture for debugging guest traffic.
+//!
+//! Every reactor owns a [`CaptureTap`]. While no capture is attached, the
+//! hot path pays a single relaxed load per packet. An attached
+//! [`CaptureSession`] gets sampled frames copied into its bounded channel,
+//! which acts as the ring buffer between the reactor and the gRPC stream:
+//! the reactor never waits on it. Frames that find the channel full are
+//! skipped and counted as dropped; frames arriving while the control plane
+//! changes the tap's session list are skipped as well.
+//!
+//! Frames are Ethernet frames as the guest sees them, without the virtio
+//! header. [`pcap_header`] and [`pcap_record`] encode them in the classic
+//! pcap format.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+/// Bytes kept per frame unless asked otherwise.
+pub const DEFAULT_SNAPLEN: u32 = 65535;
+
+/// Frames buffered per capture unless asked otherwise.
+pub const DEFAULT_BUFFER_FRAMES: usize = 4096;
+
+/// pcap link type for Ethernet.
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// Which way a frame travels, seen from the guest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Sent by the guest
+    FromGuest,
+    /// Delivered to the guest
+    ToGuest,
+}
+
+/// A frame copied off the data path.
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    pub timestamp: SystemTime,
+    /// Length of the frame on the wire
+    pub orig_len: u32,
+    /// The frame, cut to the snaplen
+    pub data: Vec<u8>,
+}
+
+/// What a capture keeps.
+#[derive(Debug, Clone, Copy)]
+pub struct CaptureOptions {
+    /// Only frames going this way (`None` = both)
+    pub direction: Option<Direction>,
+    pub snaplen: u32,
+    /// Keep every Nth matching frame
+    pub sample_every: u64,
+    /// Frames buffered before drops
+    pub buffer_frames: usize,
+    /// Stop copying after this many frames (0 = no limit)
+    pub max_frames: u64,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> Self {
+        Self {
+            direction: None,
+            snaplen: DEFAULT_SNAPLEN,
+            sample_every: 1,
+            buffer_frames: DEFAULT_BUFFER_FRAMES,
+            max_frames: 0,
+        }
+    }
+}
+
+/// Counters of a capture.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CaptureStats {
+    /// Frames matching the direction
+    pub seen: u64,
+    /// Frames handed to the stream
+    pub captured: u64,
+    /// Sampled frames lost to a full buffer
+    pub dropped: u64,
+}
+
+/// A capture as the reactors see it.
+pub struct CaptureSession {
+    options: CaptureOptions,
+    tx: mpsc::Sender<CapturedFrame>,
+    seen: AtomicU64,
+    captured: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl CaptureSession {
+    /// Create a session and the receiving end of its buffer.
+    pub fn new(options: CaptureOptions) -> (Arc<Self>, mpsc::Receiver<CapturedFrame>) {
+        let options = CaptureOptions {
+            snaplen: options.snaplen.max(1),
+            sample_every: options.sample_every.max(1),
+            buffer_frames: options.buffer_frames.max(1),
+            ..options
+        };
+        let (tx, rx) = mpsc::channel(options.buffer_frames);
+        let session = Arc::new(Self {
+            options,
+            tx,
+            seen: AtomicU64::new(0),
+            captured: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+        });
+        (session, rx)
+    }
+
+    pub fn options(&self) -> &CaptureOptions {
+        &self.options
+    }
+
+    pub fn stats(&self) -> CaptureStats {
+        CaptureStats {
+            seen: self.seen.load(Ordering::Relaxed),
+            captured: self.captured.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Offer a frame of `frame_len` bytes; `copy` fills the kept part.
+    fn offer(&self, direction: Direction, frame_len: usize, copy: &mut impl FnMut(&mut [u8])) {
+        if self.options.direction.is_some_and(|d| d != direction) {
+            return;
+        }
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed);
+        if !seen.is_multiple_of(self.options.sample_every)
+            || (self.options.max_frames > 0
+                && self.captured.load(Ordering::Relaxed) >= self.options.max_frames)
+        {
+            return;
+        }
+        let permit = match self.tx.try_reserve() {
+            Ok(permit) => permit,
+            Err(mpsc::error::TrySendError::Full(())) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return;
+            }
+            // Nobody is reading anymore; the capture is being stopped
+            Err(mpsc::error::TrySendError::Closed(())) => return,
+        };
+        let mut data = vec![0u8; frame_len.min(self.options.snaplen as usize)];
+        copy(&mut data);
+        permit.send(CapturedFrame {
+            timestamp: SystemTime::now(),
+            orig_len: frame_len as u32,
+            data,
+        });
+        self.captured.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Default)]
+struct TapInner {
+    active: AtomicBool,
+    sessions: Mutex<Vec<Arc<CaptureSession>>>,
+}
+
+/// A reactor's attachment point for captures, shared with its handle.
+#[derive(Clone, Default)]
+pub struct CaptureTap {
+    inner: Arc<TapInner>,
+}
+
+impl CaptureTap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any capture is attached. Check this before building a frame.
+    #[inline]
+    pub fn is_active(&self) -> bool {
+        self.inner.active.load(Ordering::Relaxed)
+    }
+
+    pub fn attach(&self, session: Arc<CaptureSession>) {
+        let mut sessions = self.inner.sessions.lock().unwrap();
+        sessions.push(session);
+        self.inner.active.store(true, Ordering::Relaxed);
+    }
+
+    pub fn detach(&self, session: &Arc<CaptureSession>) {
+        let mut sessions = self.inner.sessions.lock().unwrap();
+        sessions.retain(|s| !Arc::ptr_eq(s, session));
+        self.inner
+            .active
+            .store(!sessions.is_empty(), Ordering::Relaxed);
+    }
+
+    /// Offer a frame to the attached captures. `copy` fills a buffer with
+    /// the start of the frame and is only called for frames that are kept.
+    pub fn record(&self, direction: Direction, frame_len: usize, mut copy: impl FnMut(&mut [u8])) {
+        // Never wait on the control plane attaching or detaching
+        let Ok(sessions) = self.inner.sessions.try_lock() else {
+            return;
+        };
+        for session in sessions.iter() {
+            session.offer(direction, frame_len, &mut copy);
+        }
+    }
+}
+
+/// A running capture: its session, the taps it is attached to and, until a
+/// stream takes it, its buffer.
+struct Capture {
+    session: Arc<CaptureSession>,
+    taps: Vec<CaptureTap>,
+    rx: Option<mpsc::Receiver<CapturedFrame>>,
+}
+
+/// Captures of the daemon by ID.
+#[derive(Default)]
+pub struct CaptureManager {
+    captures: Mutex<HashMap<String, Capture>>,
+}
+
+impl CaptureManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a capture on `taps` and return its ID.
+    pub fn start(&self, taps: Vec<CaptureTap>, options: CaptureOptions) -> String {
+        let (session, rx) = CaptureSession::new(options);
+        for tap in &taps {
+            tap.attach(Arc::clone(&session));
+        }
+        let id = Uuid::new_v4().to_string();
+        self.captures.lock().unwrap().insert(
+            id.clone(),
+            Capture {
+                session,
+                taps,
+                rx: Some(rx),
+            },
+        );
+        id
+    }
+
+    /// Hand out a capture's buffer along with its options. A capture has a
+    /// single stream: `None` for unknown captures and ones already taken.
+    pub fn take_stream(&self, id: &str) -> Option<(mpsc::Receiver<CapturedFrame>, CaptureOptions)> {
+        let mut captures = self.captures.lock().unwrap();
+        let capture = captures.get_mut(id)?;
+        let rx = capture.rx.take()?;
+        Some((rx, *capture.session.options()))
+    }
+
+    /// Detach a capture from its taps. Its stream ends once the frames
+    /// already buffered are read.
+    pub fn stop(&self, id: &str) -> Option<CaptureStats> {
+        let capture = self.captures.lock().unwrap().remove(id)?;
+        for tap in &capture.taps {
+            tap.detach(&capture.session);
+        }
+        Some(capture.session.stats())
+    }
+}
+
+/// The pcap file header.
+pub fn pcap_header(snaplen: u32) -> Vec<u8> {
+    let mut header = Vec::with_capacity(24);
+    header.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+    header.extend_from_slice(&2u16.to_le_bytes());
+    header.extend_from_slice(&4u16.to_le_bytes());
+    header.extend_from_slice(&0i32.to_le_bytes()); // thiszone
+    header.extend_from_slice(&0u32.to_le_bytes()); // sigfigs
+    header.extend_from_slice(&snaplen.to_le_bytes());
+    header.extend_from_slice(&LINKTYPE_ETHERNET.to_le_bytes());
+    header
+}
+
+/// Append a frame as a pcap record.
+pub fn pcap_record(out: &mut Vec<u8>, frame: &CapturedFrame) {
+    let since_epoch = frame
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    out.extend_from_slice(&(since_epoch.as_secs() as u32).to_le_bytes());
+    out.extend_from_slice(&since_epoch.subsec_micros().to_le_bytes());
+    out.extend_from_slice(&(frame.data.len() as u32).to_le_bytes());
+    out.extend_from_slice(&frame.orig_len.to_le_bytes());
+    out.extend_from_slice(&frame.data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn fill(buf: &mut [u8]) {
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = i as u8;
+        }
+    }
+
+    #[test]
+    fn idle_tap_is_inactive() {
+        let tap = CaptureTap::new();
+        assert!(!tap.is_active());
+
+        let (session, _rx) = CaptureSession::new(CaptureOptions::default());
+        tap.attach(Arc::clone(&session));
+        assert!(tap.is_active());
+        tap.detach(&session);
+        assert!(!tap.is_active());
+    }
+
+    #[test]
+    fn frames_are_cut_to_the_snaplen() {
+        let tap = CaptureTap::new();
+        let (session, mut rx) = CaptureSession::new(CaptureOptions {
+            snaplen: 4,
+            ..Default::default()
+        });
+        tap.attach(session);
+
+        tap.record(Direction::FromGuest, 10, fill);
+        let frame = rx.try_recv().unwrap();
+        assert_eq!(frame.orig_len, 10);
+        assert_eq!(frame.data, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn direction_and_sampling_filter_frames() {
+        let tap = CaptureTap::new();
+        let (session, mut rx) = CaptureSession::new(CaptureOptions {
+            direction: Some(Direction::ToGuest),
+            sample_every: 2,
+            ..Default::default()
+        });
+        tap.attach(Arc::clone(&session));
+
+        for _ in 0..4 {
+            tap.record(Direction::ToGuest, 60, fill);
+            tap.record(Direction::FromGuest, 60, |_| panic!("not captured"));
+        }
+        assert_eq!(
+            session.stats(),
+            CaptureStats {
+                seen: 4,
+                captured: 2,
+                dropped: 0
+            }
+        );
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_ok());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn copying_ends_at_max_frames() {
+        let tap = CaptureTap::new();
+        let (session, _rx) = CaptureSession::new(CaptureOptions {
+            max_frames: 2,
+            ..Default::default()
+        });
+        tap.attach(Arc::clone(&session));
+
+        for _ in 0..5 {
+            tap.record(Direction::FromGuest, 60, fill);
+        }
+        let stats = session.stats();
+        assert_eq!(stats.captured, 2);
+        assert_eq!(stats.dropped, 0);
+    }
+
+    #[test]
+    fn full_buffer_drops_instead_of_blocking() {
+        let tap = CaptureTap::new();
+        let (session, _rx) = CaptureSession::new(CaptureOptions {
+            buffer_frames: 2,
+            ..Default::default()
+        });
+        tap.attach(Arc::clone(&session));
+
+        for _ in 0..5 {
+            tap.record(Direction::FromGuest, 60, fill);
+        }
+        let stats = session.stats();
+        assert_eq!(stats.captured, 2);
+        assert_eq!(stats.dropped, 3);
+    }
+
+    #[test]
+    fn stop_detaches_and_ends_the_stream() {
+        let manager = CaptureManager::new();
+        let tap = CaptureTap::new();
+        let id = manager.start(vec![tap.clone()], CaptureOptions::default());
+        tap.record(Direction::FromGuest, 60, fill);
+
+        let (mut rx, options) = manager.take_stream(&id).unwrap();
+        assert_eq!(options.snaplen, DEFAULT_SNAPLEN);
+        assert!(manager.take_stream(&id).is_none());
+
+        let stats = manager.stop(&id).unwrap();
+        assert_eq!(stats.captured, 1);
+        assert!(!tap.is_active());
+        assert!(manager.stop(&id).is_none());
+
+        // The buffered frame is still delivered, then the stream ends
+        assert!(rx.try_recv().is_ok());
+        assert!(matches!(
+            rx.try_recv(),
+            Err(mpsc::error::TryRecvError::Disconnected)
+        ));
+    }
+
+    #[test]
+    fn pcap_encoding() {
+        let header = pcap_header(128);
+        assert_eq!(header.len(), 24);
+        assert_eq!(&header[..4], &[0xd4, 0xc3, 0xb2, 0xa1]);
+        assert_eq!(&header[16..20], &128u32.to_le_bytes());
+        assert_eq!(&header[20..24], &1u32.to_le_bytes());
+
+        let frame = CapturedFrame {
+            timestamp: UNIX_EPOCH + Duration::new(1_700_000_000, 250_000_000),
+            orig_len: 100,
+            data: vec![0xaa; 3],
+        };
+        let mut record = Vec::new();
+        pcap_record(&mut record, &frame);
+        assert_eq!(record.len(), 16 + 3);
+        assert_eq!(&record[0..4], &1_700_000_000u32.to_le_bytes());
+        assert_eq!(&record[4..8], &250_000u32.to_le_bytes());
+        assert_eq!(&record[8..12], &3u32.to_le_bytes());
+        assert_eq!(&record[12..16], &100u32.to_le_bytes());
+        assert_eq!(&record[16..], &[0xaa; 3]);
+    }
+}
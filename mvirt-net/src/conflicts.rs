@@ -0,0 +1,436 @@
+//! MAC and IP conflicts between NICs.
+//!
+//! Each reactor notes the source MACs and IPs its guest sends from that
+//! aren't the NIC's (see [`crate::reactor::sources`]). This task takes them
+//! on an interval and looks up which NIC each one is allocated to: MACs
+//! across all NICs, IPs, routed prefixes and DHCP leases within the sending
+//! NIC's network only, since private networks may overlap. A source
+//! allocated to another NIC is a conflict. It goes to the audit log with
+//! both NICs and shows in the status of both their networks; with
+//! quarantine enabled the sending NIC's link is also set down, as
+//! SetNicLinkState would, until an operator sets it up again.
+//!
+//! Sources allocated to no NIC aren't conflicts; they are routed like any
+//! other traffic. A conflict not seen for [`CONFLICT_EXPIRY`] is dropped,
+//! unless its NIC is quarantined and still down.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
+use tokio::time;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::audit::NetAuditLogger;
+use crate::grpc::NetworkManager;
+use crate::grpc::storage::{self, LeaseData, NicData, Storage, format_mac_address};
+use crate::reactor::sources::ForeignSource;
+
+/// Default check interval in seconds
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 5;
+
+/// Conflicts not seen for this long are dropped
+pub const CONFLICT_EXPIRY: Duration = Duration::from_secs(600);
+
+/// Kind of address two NICs conflict on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    Mac,
+    Ip,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictKind::Mac => write!(f, "MAC"),
+            ConflictKind::Ip => write!(f, "IP"),
+        }
+    }
+}
+
+/// A NIC's guest sending from an address allocated to another NIC.
+#[derive(Debug, Clone)]
+pub struct AddressConflict {
+    pub kind: ConflictKind,
+    /// The MAC or IP address
+    pub address: String,
+    /// NIC whose guest sent from the address, and its network
+    pub nic_id: Uuid,
+    pub network_id: Uuid,
+    /// NIC the address is allocated to, and its network
+    pub owner_nic_id: Uuid,
+    pub owner_network_id: Uuid,
+    pub first_seen: DateTime<Utc>,
+    pub last_seen: DateTime<Utc>,
+    /// Frames sent from the address
+    pub frames: u64,
+    /// Whether the sending NIC's link was set down for it
+    pub quarantined: bool,
+}
+
+/// Which NIC each MAC, IP and routed prefix is allocated to.
+#[derive(Debug, Default)]
+pub struct Allocations {
+    /// Network of each NIC
+    networks: HashMap<Uuid, Uuid>,
+    /// NICs whose link is down
+    down: HashSet<Uuid>,
+    macs: HashMap<[u8; 6], Uuid>,
+    /// Addresses by network
+    ips: HashMap<(Uuid, IpAddr), Uuid>,
+    /// Routed and delegated prefixes as (network, prefix, NIC)
+    prefixes: Vec<(Uuid, IpNet, Uuid)>,
+}
+
+impl Allocations {
+    pub fn new(nics: &[NicData], leases: &[LeaseData]) -> Self {
+        let mut allocations = Allocations::default();
+        for nic in nics {
+            allocations.networks.insert(nic.id, nic.network_id);
+            if !nic.link_up {
+                allocations.down.insert(nic.id);
+            }
+            allocations.macs.insert(nic.mac_address, nic.id);
+            let addresses = nic
+                .ipv4_address
+                .map(IpAddr::from)
+                .into_iter()
+                .chain(nic.ipv6_address.map(IpAddr::from));
+            for ip in addresses {
+                allocations.ips.insert((nic.network_id, ip), nic.id);
+            }
+            let prefixes = nic
+                .routed_ipv4_prefixes
+                .iter()
+                .map(|p| IpNet::from(*p))
+                .chain(nic.routed_ipv6_prefixes.iter().map(|p| IpNet::from(*p)))
+                .chain(nic.delegated_ipv6_prefix.map(IpNet::from));
+            for prefix in prefixes {
+                allocations.prefixes.push((nic.network_id, prefix, nic.id));
+            }
+        }
+        for lease in leases {
+            allocations.macs.insert(lease.mac_address, lease.nic_id);
+            allocations
+                .ips
+                .insert((lease.network_id, lease.ipv4_address.into()), lease.nic_id);
+        }
+        allocations
+    }
+
+    /// All NICs and DHCP leases in `storage`
+    pub fn load(storage: &Storage) -> storage::Result<Self> {
+        let nics = storage.list_nics()?;
+        let mut leases = Vec::new();
+        for network in storage.list_networks()? {
+            leases.extend(storage.list_leases_in_network(&network.id)?);
+        }
+        Ok(Self::new(&nics, &leases))
+    }
+
+    /// NIC other than `nic_id` that `source` is allocated to.
+    pub fn owner(&self, nic_id: &Uuid, source: &ForeignSource) -> Option<Uuid> {
+        let owner = match source {
+            ForeignSource::Mac(mac) => self.macs.get(mac).copied(),
+            ForeignSource::Ip(ip) => {
+                let network_id = self.networks.get(nic_id)?;
+                self.ips.get(&(*network_id, *ip)).copied().or_else(|| {
+                    self.prefixes
+                        .iter()
+                        .filter(|(n, p, _)| n == network_id && p.contains(ip))
+                        .max_by_key(|(_, p, _)| p.prefix_len())
+                        .map(|(_, _, owner)| *owner)
+                })
+            }
+        };
+        owner.filter(|owner| owner != nic_id)
+    }
+}
+
+/// Conflicts found so far, shared with the gRPC service.
+#[derive(Debug, Default)]
+pub struct Conflicts {
+    by_source: Mutex<HashMap<(Uuid, ForeignSource), AddressConflict>>,
+}
+
+impl Conflicts {
+    /// Conflicts involving a NIC of `network_id`, oldest first.
+    pub fn in_network(&self, network_id: &Uuid) -> Vec<AddressConflict> {
+        let mut conflicts: Vec<AddressConflict> = self
+            .by_source
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|c| c.network_id == *network_id || c.owner_network_id == *network_id)
+            .cloned()
+            .collect();
+        conflicts.sort_by_key(|c| c.first_seen);
+        conflicts
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_source.lock().unwrap().is_empty()
+    }
+
+    /// Add the sources each NIC's guest sent from since the last update
+    /// and drop conflicts that expired or whose NICs are gone. Returns the
+    /// new conflicts, marked quarantined if `quarantine` is set.
+    pub fn update(
+        &self,
+        allocations: &Allocations,
+        observed: Vec<(Uuid, HashMap<ForeignSource, u64>)>,
+        quarantine: bool,
+        now: DateTime<Utc>,
+    ) -> Vec<AddressConflict> {
+        let mut by_source = self.by_source.lock().unwrap();
+        let mut new = Vec::new();
+        for (nic_id, sources) in observed {
+            for (source, frames) in sources {
+                let Some(owner_nic_id) = allocations.owner(&nic_id, &source) else {
+                    continue;
+                };
+                let (Some(&network_id), Some(&owner_network_id)) = (
+                    allocations.networks.get(&nic_id),
+                    allocations.networks.get(&owner_nic_id),
+                ) else {
+                    continue;
+                };
+                if let Some(conflict) = by_source.get_mut(&(nic_id, source)) {
+                    conflict.owner_nic_id = owner_nic_id;
+                    conflict.last_seen = now;
+                    conflict.frames += frames;
+                    continue;
+                }
+                let (kind, address) = match source {
+                    ForeignSource::Mac(mac) => (ConflictKind::Mac, format_mac_address(&mac)),
+                    ForeignSource::Ip(ip) => (ConflictKind::Ip, ip.to_string()),
+                };
+                let conflict = AddressConflict {
+                    kind,
+                    address,
+                    nic_id,
+                    network_id,
+                    owner_nic_id,
+                    owner_network_id,
+                    first_seen: now,
+                    last_seen: now,
+                    frames,
+                    quarantined: quarantine,
+                };
+                by_source.insert((nic_id, source), conflict.clone());
+                new.push(conflict);
+            }
+        }
+
+        let expiry = chrono::Duration::from_std(CONFLICT_EXPIRY).unwrap();
+        by_source.retain(|_, c| {
+            let nics_exist = allocations.networks.contains_key(&c.nic_id)
+                && allocations.networks.contains_key(&c.owner_nic_id);
+            let held = c.quarantined && allocations.down.contains(&c.nic_id);
+            nics_exist && (held || now - c.last_seen < expiry)
+        });
+        new
+    }
+}
+
+/// Conflict monitor task.
+pub struct ConflictMonitor {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ConflictMonitor {
+    /// Start a new monitor task. With `quarantine`, NICs sending from
+    /// another NIC's address get their link set down.
+    pub fn start(
+        storage: Arc<Storage>,
+        manager: Arc<NetworkManager>,
+        audit: Arc<NetAuditLogger>,
+        conflicts: Arc<Conflicts>,
+        interval: Duration,
+        quarantine: bool,
+    ) -> Self {
+        info!(interval = ?interval, quarantine, "Conflict monitor started");
+
+        let task = tokio::spawn(monitor_loop(
+            storage, manager, audit, conflicts, interval, quarantine,
+        ));
+        Self { task }
+    }
+
+    /// Stop the monitor task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Conflict monitor stopped");
+    }
+}
+
+/// Main monitor loop.
+async fn monitor_loop(
+    storage: Arc<Storage>,
+    manager: Arc<NetworkManager>,
+    audit: Arc<NetAuditLogger>,
+    conflicts: Arc<Conflicts>,
+    interval: Duration,
+    quarantine: bool,
+) {
+    let mut interval = time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        let observed = manager.take_foreign_sources().await;
+        if observed.is_empty() && conflicts.is_empty() {
+            continue;
+        }
+        let allocations = match Allocations::load(&storage) {
+            Ok(allocations) => allocations,
+            Err(e) => {
+                warn!(error = %e, "Failed to load allocations");
+                continue;
+            }
+        };
+
+        for conflict in conflicts.update(&allocations, observed, quarantine, Utc::now()) {
+            warn!(
+                nic_id = %conflict.nic_id,
+                owner_nic_id = %conflict.owner_nic_id,
+                kind = %conflict.kind,
+                address = %conflict.address,
+                quarantined = conflict.quarantined,
+                "NIC sends from an address allocated to another NIC"
+            );
+            let nic_id = conflict.nic_id.to_string();
+            audit.address_conflict(
+                &nic_id,
+                &conflict.owner_nic_id.to_string(),
+                &conflict.kind.to_string(),
+                &conflict.address,
+                conflict.quarantined,
+            );
+            if conflict.quarantined {
+                if let Err(e) = storage.update_nic_link_up(&conflict.nic_id, false) {
+                    warn!(nic_id = %nic_id, error = %e, "Failed to quarantine NIC");
+                    continue;
+                }
+                manager.set_nic_link_up(&conflict.nic_id, false).await;
+                audit.nic_link_state(&nic_id, false);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::socket_access::SocketAccess;
+    use std::net::Ipv4Addr;
+
+    fn nic(network_id: Uuid, mac_last: u8, ipv4: [u8; 4]) -> NicData {
+        NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id,
+            mac_address: [0x02, 0, 0, 0, 0, mac_last],
+            ipv4_address: Some(Ipv4Addr::from(ipv4)),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: String::new(),
+            state: crate::grpc::storage::NicState::Active,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: SocketAccess::default(),
+        }
+    }
+
+    fn ip(s: &str) -> ForeignSource {
+        ForeignSource::Ip(s.parse().unwrap())
+    }
+
+    #[test]
+    fn test_owner_lookup() {
+        let (net_a, net_b) = (Uuid::new_v4(), Uuid::new_v4());
+        let a1 = nic(net_a, 1, [10, 0, 0, 2]);
+        let mut a2 = nic(net_a, 2, [10, 0, 0, 3]);
+        a2.routed_ipv4_prefixes = vec!["192.168.5.0/24".parse().unwrap()];
+        // Same subnet in another network
+        let b1 = nic(net_b, 3, [10, 0, 0, 4]);
+        let allocations = Allocations::new(&[a1.clone(), a2.clone(), b1.clone()], &[]);
+
+        assert_eq!(allocations.owner(&a1.id, &ip("10.0.0.3")), Some(a2.id));
+        assert_eq!(allocations.owner(&a1.id, &ip("192.168.5.9")), Some(a2.id));
+        assert_eq!(allocations.owner(&a2.id, &ip("192.168.5.9")), None);
+        assert_eq!(allocations.owner(&a1.id, &ip("10.0.0.4")), None);
+        assert_eq!(allocations.owner(&a1.id, &ip("10.0.0.99")), None);
+        // MACs are unique across networks
+        assert_eq!(
+            allocations.owner(&a1.id, &ForeignSource::Mac(b1.mac_address)),
+            Some(b1.id)
+        );
+    }
+
+    #[test]
+    fn test_conflicts_are_new_once_and_expire() {
+        let net = Uuid::new_v4();
+        let a = nic(net, 1, [10, 0, 0, 2]);
+        let b = nic(net, 2, [10, 0, 0, 3]);
+        let allocations = Allocations::new(&[a.clone(), b.clone()], &[]);
+        let conflicts = Conflicts::default();
+        let seen = |n| vec![(a.id, HashMap::from([(ip("10.0.0.3"), n)]))];
+        let start = Utc::now();
+
+        let new = conflicts.update(&allocations, seen(3), false, start);
+        assert_eq!(new.len(), 1);
+        assert_eq!((new[0].nic_id, new[0].owner_nic_id), (a.id, b.id));
+        assert_eq!(new[0].kind, ConflictKind::Ip);
+        assert!(
+            conflicts
+                .update(&allocations, seen(2), false, start)
+                .is_empty()
+        );
+        assert_eq!(conflicts.in_network(&net)[0].frames, 5);
+        assert!(conflicts.in_network(&Uuid::new_v4()).is_empty());
+
+        let later = start + chrono::Duration::from_std(CONFLICT_EXPIRY).unwrap();
+        conflicts.update(&allocations, vec![], false, later);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn test_quarantined_conflicts_stay_while_down() {
+        let net = Uuid::new_v4();
+        let a = nic(net, 1, [10, 0, 0, 2]);
+        let b = nic(net, 2, [10, 0, 0, 3]);
+        let conflicts = Conflicts::default();
+        let start = Utc::now();
+        let seen = vec![(
+            a.id,
+            HashMap::from([(ForeignSource::Mac(b.mac_address), 1)]),
+        )];
+        let allocations = Allocations::new(&[a.clone(), b.clone()], &[]);
+        assert!(conflicts.update(&allocations, seen, true, start)[0].quarantined);
+
+        let mut down = a.clone();
+        down.link_up = false;
+        let later = start + chrono::Duration::from_std(CONFLICT_EXPIRY).unwrap();
+        conflicts.update(
+            &Allocations::new(&[down, b.clone()], &[]),
+            vec![],
+            true,
+            later,
+        );
+        assert!(!conflicts.is_empty());
+
+        // Deleting either NIC ends it
+        conflicts.update(&Allocations::new(&[b], &[]), vec![], true, later);
+        assert!(conflicts.is_empty());
+    }
+}
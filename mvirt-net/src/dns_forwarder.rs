@@ -0,0 +1,772 @@
+//! DNS forwarder behind the gateway's resolver.
+//!
+//! The gateway answers the names of a network's zone itself (see
+//! [`crate::reactor::dns`]). Other names are resolved upstream if the
+//! network has upstream servers: its own, or else the daemon's. The NIC's
+//! reactor hands such a query to the forwarder thread, which answers it
+//! from its cache or asks the upstream servers in turn over UDP, and hands
+//! the reply back to the reactor to give to the guest. VMs thus only need
+//! the gateway as resolver, whether or not they can reach other servers.
+//!
+//! Every query goes upstream from a socket of its own, bound to a fresh
+//! ephemeral port and connected to the server asked, so an off-path
+//! attacker has to guess the port as well as the ID to spoof a reply.
+//!
+//! Replies are cached per name, type and upstream servers for the smallest
+//! TTL of their records, at most [`MAX_CACHE_TTL`]; the TTLs of a cached
+//! reply count down. Failures and truncated replies aren't cached. Replies
+//! longer than [`MAX_REPLY`] reach the guest truncated; queries over TCP
+//! are not forwarded.
+
+use crate::reactor::dns::{
+    DNS_HEADER_SIZE, Origin, RCODE_FORMERR, RCODE_NOERROR, RCODE_NXDOMAIN, RCODE_REFUSED,
+    RCODE_SERVFAIL, read_name,
+};
+use nix::libc;
+use nix::sys::eventfd::{EfdFlags, EventFd};
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Port of upstream servers given without one
+pub const DNS_PORT: u16 = 53;
+
+/// Longest TTL a reply is cached for, in seconds
+pub const MAX_CACHE_TTL: u32 = 300;
+
+/// Longest reply handed to a guest; longer ones are truncated. Fits in
+/// any IPv6 MTU (the EDNS buffer size recommended since DNS flag day 2020).
+pub const MAX_REPLY: usize = 1232;
+
+/// How long an upstream server gets to reply before the next is asked
+const UPSTREAM_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Queries waiting for upstream servers, each holding a socket; more are
+/// answered SERVFAIL
+const MAX_PENDING: usize = 256;
+
+/// Cached replies; while full, new replies aren't cached
+const MAX_CACHE_ENTRIES: usize = 4096;
+
+/// How often the forwarder thread checks timeouts and whether it should
+/// stop, in ms
+const POLL_INTERVAL_MS: i32 = 100;
+
+/// Largest datagram received
+const MAX_DATAGRAM: usize = 65536;
+
+/// Record type of the EDNS pseudo-record, whose TTL field holds flags
+const TYPE_OPT: u16 = 41;
+
+/// Header flags
+const FLAG_QR: u16 = 0x8000;
+const FLAG_TC: u16 = 0x0200;
+const FLAG_RD: u16 = 0x0100;
+const FLAG_RA: u16 = 0x0080;
+
+/// A guest's query to resolve upstream.
+#[derive(Debug)]
+pub struct Query {
+    /// The guest's DNS message
+    pub message: Vec<u8>,
+    /// Servers to ask, in order
+    pub upstreams: Arc<[SocketAddr]>,
+    /// Where the reply goes
+    pub origin: Origin,
+}
+
+/// Parse an upstream server: an address with or without a port.
+pub fn parse_upstream(s: &str) -> Option<SocketAddr> {
+    s.parse()
+        .ok()
+        .or_else(|| s.parse().ok().map(|ip| SocketAddr::new(ip, DNS_PORT)))
+}
+
+/// The forwarder thread and the daemon's upstream servers.
+pub struct DnsForwarder {
+    upstreams: Arc<[SocketAddr]>,
+    queries: Sender<Query>,
+    eventfd: Arc<EventFd>,
+    stop: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl DnsForwarder {
+    /// Start the thread. `upstreams` serve networks without servers of
+    /// their own; none leaves those networks refusing names outside their
+    /// zone.
+    pub fn start(upstreams: Vec<SocketAddr>) -> io::Result<Self> {
+        let eventfd = Arc::new(
+            EventFd::from_value_and_flags(0, EfdFlags::EFD_NONBLOCK).map_err(io::Error::from)?,
+        );
+
+        let (queries, queries_rx) = mpsc::channel();
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker = Worker {
+            queries: queries_rx,
+            eventfd: Arc::clone(&eventfd),
+            cache: Cache::default(),
+            pending: HashMap::new(),
+        };
+        let thread = {
+            let stop = Arc::clone(&stop);
+            std::thread::Builder::new()
+                .name("dns-forwarder".to_string())
+                .spawn(move || worker.run(&stop))?
+        };
+
+        info!(upstreams = ?upstreams, "DNS forwarder started");
+        Ok(DnsForwarder {
+            upstreams: upstreams.into(),
+            queries,
+            eventfd,
+            stop,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// The daemon's upstream servers.
+    pub fn upstreams(&self) -> &Arc<[SocketAddr]> {
+        &self.upstreams
+    }
+
+    /// Resolve a guest's query; the reply goes to its origin.
+    pub fn submit(&self, query: Query) {
+        if self.queries.send(query).is_ok() {
+            let _ = self.eventfd.write(1);
+        }
+    }
+
+    /// Stop the thread and wait for it to exit. Queries still waiting for
+    /// upstream servers get no reply.
+    pub fn stop(&self) {
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            self.stop.store(true, Ordering::Relaxed);
+            let _ = self.eventfd.write(1);
+            let _ = thread.join();
+            info!("DNS forwarder stopped");
+        }
+    }
+}
+
+impl fmt::Debug for DnsForwarder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DnsForwarder")
+            .field("upstreams", &self.upstreams)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for DnsForwarder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// What a reply is cached under.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    name: String,
+    qtype: u16,
+    qclass: u16,
+    upstreams: Arc<[SocketAddr]>,
+}
+
+/// The question of a message with exactly one: the key it is cached under
+/// (given the upstream servers) and the offset after it.
+fn question(message: &[u8], upstreams: &Arc<[SocketAddr]>) -> Option<(Key, usize)> {
+    if message.len() < DNS_HEADER_SIZE || message[4..6] != [0, 1] {
+        return None;
+    }
+    let (name, end) = read_name(message, DNS_HEADER_SIZE)?;
+    let fixed = message.get(end..end + 4)?;
+    let key = Key {
+        name,
+        qtype: u16::from_be_bytes([fixed[0], fixed[1]]),
+        qclass: u16::from_be_bytes([fixed[2], fixed[3]]),
+        upstreams: Arc::clone(upstreams),
+    };
+    Some((key, end + 4))
+}
+
+/// Whether `reply` answers the question `key`, encoded in the same
+/// `question_end` bytes as asked; only then can the question asked be put
+/// back in.
+fn answers(reply: &[u8], key: &Key, question_end: usize) -> bool {
+    reply.len() > 2
+        && reply[2] & 0x80 != 0
+        && question(reply, &key.upstreams).is_some_and(|(k, end)| k == *key && end == question_end)
+}
+
+/// Offset after the possibly compressed name at `offset`.
+fn skip_name(message: &[u8], mut offset: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(offset)?;
+        match len {
+            0 => return Some(offset + 1),
+            // A pointer ends the name
+            0xc0.. => return message.get(offset + 1).map(|_| offset + 2),
+            0x40.. => return None,
+            _ => offset += 1 + len as usize,
+        }
+    }
+}
+
+/// Offsets of the TTLs of the records of a reply to a single question,
+/// the EDNS pseudo-record left out.
+fn ttl_offsets(message: &[u8], question_end: usize) -> Option<Vec<usize>> {
+    let count = |at: usize| u16::from_be_bytes([message[at], message[at + 1]]) as usize;
+    let records = count(6) + count(8) + count(10);
+
+    let mut offsets = Vec::with_capacity(records);
+    let mut offset = question_end;
+    for _ in 0..records {
+        offset = skip_name(message, offset)?;
+        let fixed = message.get(offset..offset + 10)?;
+        if u16::from_be_bytes([fixed[0], fixed[1]]) != TYPE_OPT {
+            offsets.push(offset + 4);
+        }
+        let rdlength = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+        offset += 10 + rdlength;
+    }
+    (offset <= message.len()).then_some(offsets)
+}
+
+fn read_ttl(message: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(message[offset..offset + 4].try_into().unwrap())
+}
+
+/// A reply of just the query's header and question, with `flags` added.
+fn header_reply(query: &[u8], question_end: usize, flags: u16) -> Vec<u8> {
+    let mut reply = query[..question_end].to_vec();
+    let query_flags = u16::from_be_bytes([query[2], query[3]]);
+    let flags = FLAG_QR | FLAG_RA | (query_flags & FLAG_RD) | flags;
+    reply[2..4].copy_from_slice(&flags.to_be_bytes());
+    reply[4..6].copy_from_slice(&1u16.to_be_bytes());
+    reply[6..12].fill(0);
+    reply
+}
+
+/// The reply to a query that couldn't be resolved, with the question
+/// ending at `question_end` if it could be read.
+fn error_reply(query: &[u8], question_end: Option<usize>, rcode: u8) -> Vec<u8> {
+    let mut reply = header_reply(query, question_end.unwrap_or(DNS_HEADER_SIZE), rcode as u16);
+    if question_end.is_none() {
+        reply[4..6].fill(0);
+    }
+    reply
+}
+
+/// A cached reply.
+struct Entry {
+    reply: Vec<u8>,
+    question_end: usize,
+    ttls: Vec<usize>,
+    stored: Instant,
+    expires: Instant,
+}
+
+/// Replies by question.
+#[derive(Default)]
+struct Cache {
+    entries: HashMap<Key, Entry>,
+}
+
+impl Cache {
+    /// The cached reply to `query`, with the query's ID and question and
+    /// the TTLs counted down.
+    fn get(&self, key: &Key, query: &[u8], question_end: usize, now: Instant) -> Option<Vec<u8>> {
+        let entry = self
+            .entries
+            .get(key)
+            .filter(|e| e.expires > now && e.question_end == question_end)?;
+        let elapsed = now.duration_since(entry.stored).as_secs() as u32;
+
+        let mut reply = entry.reply.clone();
+        reply[..2].copy_from_slice(&query[..2]);
+        reply[DNS_HEADER_SIZE..question_end].copy_from_slice(&query[DNS_HEADER_SIZE..question_end]);
+        for &offset in &entry.ttls {
+            let ttl = read_ttl(&reply, offset).saturating_sub(elapsed);
+            reply[offset..offset + 4].copy_from_slice(&ttl.to_be_bytes());
+        }
+        Some(reply)
+    }
+
+    /// Cache a reply until its smallest TTL runs out. Failures, truncated
+    /// replies and replies without records aren't cached.
+    fn insert(&mut self, key: Key, reply: &[u8], question_end: usize, now: Instant) {
+        let flags = u16::from_be_bytes([reply[2], reply[3]]);
+        let rcode = (flags & 0x0f) as u8;
+        if flags & FLAG_TC != 0 || !matches!(rcode, RCODE_NOERROR | RCODE_NXDOMAIN) {
+            return;
+        }
+        let Some(ttls) = ttl_offsets(reply, question_end) else {
+            return;
+        };
+        let Some(ttl) = ttls.iter().map(|&o| read_ttl(reply, o)).min() else {
+            return;
+        };
+        let ttl = ttl.min(MAX_CACHE_TTL);
+        if ttl == 0 {
+            return;
+        }
+
+        if self.entries.len() >= MAX_CACHE_ENTRIES {
+            self.entries.retain(|_, e| e.expires > now);
+            if self.entries.len() >= MAX_CACHE_ENTRIES {
+                return;
+            }
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                reply: reply.to_vec(),
+                question_end,
+                ttls,
+                stored: now,
+                expires: now + Duration::from_secs(ttl as u64),
+            },
+        );
+    }
+}
+
+/// A query waiting for an upstream server.
+struct Pending {
+    query: Query,
+    key: Key,
+    question_end: usize,
+    /// Index of the server asked
+    server: usize,
+    /// Connected to that server; none until it is asked
+    socket: Option<UdpSocket>,
+    sent: Instant,
+}
+
+/// State of the forwarder thread.
+struct Worker {
+    queries: Receiver<Query>,
+    eventfd: Arc<EventFd>,
+    cache: Cache,
+    /// Queries asked upstream, by the ID they were asked with
+    pending: HashMap<u16, Pending>,
+}
+
+impl Worker {
+    /// Resolve queries until told to stop.
+    fn run(mut self, stop: &AtomicBool) {
+        let pollfd = |fd| libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let mut buf = vec![0u8; MAX_DATAGRAM];
+
+        while !stop.load(Ordering::Relaxed) {
+            let asked: Vec<(u16, RawFd)> = self
+                .pending
+                .iter()
+                .filter_map(|(id, p)| Some((*id, p.socket.as_ref()?.as_raw_fd())))
+                .collect();
+            let mut fds = Vec::with_capacity(asked.len() + 1);
+            fds.push(pollfd(self.eventfd.as_raw_fd()));
+            fds.extend(asked.iter().map(|&(_, fd)| pollfd(fd)));
+
+            let ready = unsafe {
+                libc::poll(
+                    fds.as_mut_ptr(),
+                    fds.len() as libc::nfds_t,
+                    POLL_INTERVAL_MS,
+                )
+            };
+            if ready < 0 {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                warn!(error = %e, "DNS forwarder poll failed, stopping");
+                return;
+            }
+
+            if fds[0].revents & libc::POLLIN != 0 {
+                let _ = self.eventfd.read();
+            }
+            while let Ok(query) = self.queries.try_recv() {
+                self.query(query, Instant::now());
+            }
+
+            for (&(id, _), pollfd) in asked.iter().zip(&fds[1..]) {
+                if pollfd.revents == 0 {
+                    continue;
+                }
+                // One datagram per socket and round; the poll brings back
+                // sockets with more
+                let Some(socket) = self.pending.get(&id).and_then(|p| p.socket.as_ref()) else {
+                    continue;
+                };
+                match socket.recv(&mut buf) {
+                    Ok(len) => self.receive(id, &buf[..len], Instant::now()),
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    // E.g. the server's port is closed
+                    Err(e) => {
+                        debug!(error = %e, "DNS forwarder receive failed");
+                        self.next_server(id, Instant::now());
+                    }
+                }
+            }
+
+            self.expire(Instant::now());
+        }
+    }
+
+    /// Answer a guest's query from the cache or ask upstream.
+    fn query(&mut self, query: Query, now: Instant) {
+        let Some((key, question_end)) = question(&query.message, &query.upstreams) else {
+            query
+                .origin
+                .reply(&error_reply(&query.message, None, RCODE_FORMERR));
+            return;
+        };
+        if let Some(reply) = self.cache.get(&key, &query.message, question_end, now) {
+            debug!(name = %key.name, qtype = key.qtype, "DNS reply from cache");
+            query.origin.reply(&reply);
+            return;
+        }
+        if self.pending.len() >= MAX_PENDING {
+            debug!(name = %key.name, "Too many DNS queries upstream");
+            query.origin.reply(&error_reply(
+                &query.message,
+                Some(question_end),
+                RCODE_SERVFAIL,
+            ));
+            return;
+        }
+
+        let id = loop {
+            let id = rand::random::<u16>();
+            if !self.pending.contains_key(&id) {
+                break id;
+            }
+        };
+        self.pending.insert(
+            id,
+            Pending {
+                query,
+                key,
+                question_end,
+                server: 0,
+                socket: None,
+                sent: now,
+            },
+        );
+        self.ask(id, now);
+    }
+
+    /// Send a pending query to its current upstream server, or the next
+    /// one that can be reached. Once none is left the guest gets SERVFAIL.
+    fn ask(&mut self, id: u16, now: Instant) {
+        let Some(pending) = self.pending.get_mut(&id) else {
+            return;
+        };
+        let mut message = pending.query.message.clone();
+        message[..2].copy_from_slice(&id.to_be_bytes());
+
+        while let Some(&server) = pending.query.upstreams.get(pending.server) {
+            match send_upstream(&message, server) {
+                Ok(socket) => {
+                    pending.socket = Some(socket);
+                    pending.sent = now;
+                    return;
+                }
+                Err(e) => debug!(server = %server, error = %e, "DNS query not sent"),
+            }
+            pending.server += 1;
+        }
+
+        let pending = self.pending.remove(&id).unwrap();
+        debug!(name = %pending.key.name, "No DNS server replied");
+        pending.query.origin.reply(&error_reply(
+            &pending.query.message,
+            Some(pending.question_end),
+            RCODE_SERVFAIL,
+        ));
+    }
+
+    /// Take a reply to the query asked with `id` from the socket it was
+    /// asked on.
+    fn receive(&mut self, id: u16, datagram: &[u8], now: Instant) {
+        let Some(pending) = self.pending.get(&id) else {
+            return;
+        };
+        // Only the question asked, with the same encoding, since the
+        // guest's is put back in
+        if datagram.get(..2) != Some(&id.to_be_bytes()[..])
+            || !answers(datagram, &pending.key, pending.question_end)
+        {
+            debug!(name = %pending.key.name, "Unexpected DNS reply dropped");
+            return;
+        }
+        let mut pending = self.pending.remove(&id).unwrap();
+
+        // A failing server is skipped while others are left
+        let rcode = datagram[3] & 0x0f;
+        if matches!(rcode, RCODE_SERVFAIL | RCODE_REFUSED)
+            && pending.server + 1 < pending.query.upstreams.len()
+        {
+            pending.server += 1;
+            self.pending.insert(id, pending);
+            self.ask(id, now);
+            return;
+        }
+
+        let question_end = pending.question_end;
+        let mut reply = if datagram.len() > MAX_REPLY {
+            header_reply(datagram, question_end, FLAG_TC | rcode as u16)
+        } else {
+            datagram.to_vec()
+        };
+        self.cache
+            .insert(pending.key.clone(), &reply, question_end, now);
+
+        // The guest's ID and question, case included
+        let query = &pending.query.message;
+        reply[..2].copy_from_slice(&query[..2]);
+        reply[DNS_HEADER_SIZE..question_end].copy_from_slice(&query[DNS_HEADER_SIZE..question_end]);
+        debug!(name = %pending.key.name, rcode, len = reply.len(), "DNS reply forwarded");
+        pending.query.origin.reply(&reply);
+    }
+
+    /// Ask the next server, the current one having failed.
+    fn next_server(&mut self, id: u16, now: Instant) {
+        if let Some(pending) = self.pending.get_mut(&id) {
+            pending.server += 1;
+        }
+        self.ask(id, now);
+    }
+
+    /// Move queries a server didn't answer in time on to the next server.
+    fn expire(&mut self, now: Instant) {
+        let late: Vec<u16> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| now.duration_since(p.sent) >= UPSTREAM_TIMEOUT)
+            .map(|(id, _)| *id)
+            .collect();
+        for id in late {
+            self.next_server(id, now);
+        }
+    }
+}
+
+/// Send `message` to `server` from a new socket on a random ephemeral
+/// port, connected so that only `server` can answer on it.
+fn send_upstream(message: &[u8], server: SocketAddr) -> io::Result<UdpSocket> {
+    // Hosts without IPv6 fail here and move on to the next server
+    let socket = if server.is_ipv4() {
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?
+    } else {
+        UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))?
+    };
+    socket.set_nonblocking(true)?;
+    socket.connect(server)?;
+    socket.send(message)?;
+    Ok(socket)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstreams() -> Arc<[SocketAddr]> {
+        Arc::from(vec!["192.0.2.53:53".parse().unwrap()])
+    }
+
+    fn query(id: u16, name: &str) -> Vec<u8> {
+        let mut q = id.to_be_bytes().to_vec();
+        q.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.split('.') {
+            q.push(label.len() as u8);
+            q.extend_from_slice(label.as_bytes());
+        }
+        q.extend_from_slice(&[0, 0, 1, 0, 1]);
+        q
+    }
+
+    /// A reply to `query` with A records of the given TTLs, named by
+    /// pointers to the question, and an EDNS record.
+    fn reply(query: &[u8], rcode: u8, ttls: &[u32]) -> Vec<u8> {
+        let mut r = query.to_vec();
+        r[2] = 0x81;
+        r[3] = 0x80 | rcode;
+        r[6..8].copy_from_slice(&(ttls.len() as u16).to_be_bytes());
+        r[10..12].copy_from_slice(&1u16.to_be_bytes());
+        for ttl in ttls {
+            r.extend_from_slice(&[0xc0, 12, 0, 1, 0, 1]);
+            r.extend_from_slice(&ttl.to_be_bytes());
+            r.extend_from_slice(&[0, 4, 192, 0, 2, 1]);
+        }
+        // OPT: root name, type 41, payload size, flags in the TTL
+        r.extend_from_slice(&[0, 0, 41, 0x10, 0, 0, 0, 0x80, 0, 0, 0]);
+        r
+    }
+
+    #[test]
+    fn test_parse_upstream() {
+        assert_eq!(
+            parse_upstream("9.9.9.9"),
+            Some("9.9.9.9:53".parse().unwrap())
+        );
+        assert_eq!(
+            parse_upstream("[2620:fe::fe]:5353"),
+            Some("[2620:fe::fe]:5353".parse().unwrap())
+        );
+        assert_eq!(parse_upstream("dns.example"), None);
+    }
+
+    #[test]
+    fn test_ttl_offsets() {
+        let q = query(1, "example.com");
+        let (_, end) = question(&q, &upstreams()).unwrap();
+        let r = reply(&q, RCODE_NOERROR, &[60, 30]);
+
+        // The OPT record's flags are no TTL
+        let offsets = ttl_offsets(&r, end).unwrap();
+        assert_eq!(offsets.len(), 2);
+        assert_eq!(read_ttl(&r, offsets[0]), 60);
+        assert_eq!(read_ttl(&r, offsets[1]), 30);
+
+        assert_eq!(ttl_offsets(&r[..r.len() - 1], end), None);
+    }
+
+    #[test]
+    fn test_cache_counts_down() {
+        let now = Instant::now();
+        let mut cache = Cache::default();
+        let q = query(1, "example.com");
+        let (key, end) = question(&q, &upstreams()).unwrap();
+        cache.insert(key, &reply(&q, RCODE_NOERROR, &[600, 30]), end, now);
+
+        // Another guest's query, in other case
+        let other = query(7, "EXAMPLE.com");
+        let (key, end) = question(&other, &upstreams()).unwrap();
+        let later = now + Duration::from_secs(10);
+        let cached = cache.get(&key, &other, end, later).unwrap();
+        assert_eq!(&cached[..2], &[0, 7]);
+        assert_eq!(&cached[13..20], b"EXAMPLE");
+        let offsets = ttl_offsets(&cached, end).unwrap();
+        assert_eq!(read_ttl(&cached, offsets[0]), 590);
+        assert_eq!(read_ttl(&cached, offsets[1]), 20);
+
+        // Gone with the smallest TTL, and only for the same servers
+        assert!(
+            cache
+                .get(&key, &other, end, now + Duration::from_secs(30))
+                .is_none()
+        );
+        let (key, _) = question(&other, &Arc::from(vec![])).unwrap();
+        assert!(cache.get(&key, &other, end, later).is_none());
+    }
+
+    #[test]
+    fn test_cache_skips_failures() {
+        let now = Instant::now();
+        let mut cache = Cache::default();
+        let q = query(1, "example.com");
+        let (key, end) = question(&q, &upstreams()).unwrap();
+
+        cache.insert(key.clone(), &reply(&q, RCODE_SERVFAIL, &[60]), end, now);
+        cache.insert(key.clone(), &reply(&q, RCODE_NOERROR, &[]), end, now);
+        cache.insert(key.clone(), &reply(&q, RCODE_NOERROR, &[0]), end, now);
+        let mut truncated = reply(&q, RCODE_NOERROR, &[60]);
+        truncated[2] |= 0x02;
+        cache.insert(key.clone(), &truncated, end, now);
+        assert!(cache.get(&key, &q, end, now).is_none());
+
+        // Long TTLs are capped
+        cache.insert(key.clone(), &reply(&q, RCODE_NXDOMAIN, &[86400]), end, now);
+        let later = now + Duration::from_secs(MAX_CACHE_TTL as u64);
+        assert!(cache.get(&key, &q, end, later).is_none());
+    }
+
+    #[test]
+    fn test_reply_question_must_match_encoding() {
+        let q = query(1, "example.com");
+        let (key, end) = question(&q, &upstreams()).unwrap();
+        assert!(answers(&reply(&q, RCODE_NOERROR, &[60]), &key, end));
+        // Not a reply
+        assert!(!answers(&q, &key, end));
+
+        // The same name to the cache, in fewer bytes: a label of one
+        // invalid byte reads like a truncated two-byte sequence
+        let long = {
+            let mut q = query(1, "xx.com");
+            q[13..15].copy_from_slice(&[0xe2, 0x82]);
+            q
+        };
+        let short = {
+            let mut q = query(1, "x.com");
+            q[13] = 0xff;
+            q
+        };
+        let (key, end) = question(&long, &upstreams()).unwrap();
+        let (short_key, short_end) = question(&short, &upstreams()).unwrap();
+        assert_eq!(key, short_key);
+        assert_ne!(end, short_end);
+        assert!(!answers(&reply(&short, RCODE_NOERROR, &[60]), &key, end));
+
+        // Nor does the cache splice one into the other
+        let now = Instant::now();
+        let mut cache = Cache::default();
+        cache.insert(key.clone(), &reply(&long, RCODE_NOERROR, &[60]), end, now);
+        assert!(cache.get(&key, &short, short_end, now).is_none());
+        assert!(cache.get(&key, &long, end, now).is_some());
+    }
+
+    #[test]
+    fn test_queries_leave_from_their_own_ports() {
+        let server = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let q = query(1, "example.com");
+        let a = send_upstream(&q, server_addr).unwrap();
+        let b = send_upstream(&q, server_addr).unwrap();
+        assert_ne!(
+            a.local_addr().unwrap().port(),
+            b.local_addr().unwrap().port()
+        );
+
+        // Only the server asked can answer
+        let other = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        other.send_to(b"spoofed", a.local_addr().unwrap()).unwrap();
+        let mut buf = [0u8; 512];
+        let (len, from) = server.recv_from(&mut buf).unwrap();
+        assert_eq!(&buf[..len], &q[..]);
+        server.send_to(b"reply", from).unwrap();
+        a.set_nonblocking(false).unwrap();
+        a.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let len = a.recv(&mut buf).unwrap();
+        assert_eq!(&buf[..len], b"reply");
+    }
+
+    #[test]
+    fn test_error_reply() {
+        let q = query(0x1234, "example.com");
+        let (_, end) = question(&q, &upstreams()).unwrap();
+
+        let r = error_reply(&q, Some(end), RCODE_SERVFAIL);
+        assert_eq!(r.len(), end);
+        // QR, RD, RA
+        assert_eq!(&r[..4], &[0x12, 0x34, 0x81, 0x80 | RCODE_SERVFAIL]);
+        assert_eq!(&r[4..6], &[0, 1]);
+
+        let r = error_reply(&q, None, RCODE_FORMERR);
+        assert_eq!(r.len(), DNS_HEADER_SIZE);
+        assert_eq!(&r[4..6], &[0, 0]);
+    }
+}
@@ -1,22 +1,40 @@
 //! NetworkManager - Router lifecycle management for networks and NICs.
 
-use super::storage::{NetworkData, NicData, Storage};
-use crate::reactor::{ReactorId, ReactorRegistry};
-use crate::router::{Router, TUN_BUFFER_COUNT, TUN_BUFFER_SIZE, VhostConfig};
+use super::storage::{LeaseData, NetworkData, NicData, NicState, RouteData, Storage};
+use crate::dns_forwarder::DnsForwarder;
+use crate::hugepage::{self, HugePageManager};
+use crate::neighbor_proxy::{NeighborProxy, ProxyPrefixes};
+use crate::netns::{self, UplinkNamespace};
+use crate::overlay::{Overlay, OverlayError, OverlayPeer};
+use crate::reactor::dns::{DnsZone, Forward};
+use crate::reactor::firewall::SecurityPolicy;
+use crate::reactor::isolation::{Isolation, IsolationMap};
+use crate::reactor::nat::{FloatingIp, PortForward};
+use crate::reactor::sources::ForeignSource;
+use crate::reactor::{DEFAULT_TX_BURST, ReactorHandle, ReactorId, ReactorRegistry, pmtu, rx_pool};
+use crate::reactor_supervisor::{ReactorCrashes, ReactorEvent};
+use crate::router::{Placement, Router, TUN_BUFFER_COUNT, TUN_BUFFER_SIZE, VhostConfig};
 use crate::routing::{IpPrefix, RouteTarget};
-use ipnet::{Ipv4Net, Ipv6Net};
+use crate::security;
+use crate::socket_access::SocketAccess;
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
 use nix::libc;
+use rtnetlink::Handle;
 use std::collections::{HashMap, HashSet};
 use std::io;
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU16, AtomicUsize, Ordering};
+use std::time::Instant;
 use thiserror::Error;
 use tokio::sync::Mutex;
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
-/// Directory for vhost-user sockets.
-const SOCKET_DIR: &str = "/run/mvirt/net";
+/// Directory for vhost-user sockets, unless `MVIRT_NET_SOCKET_DIR` names
+/// another.
+pub const SOCKET_DIR: &str = "/run/mvirt/net";
 
 /// Manager errors.
 #[derive(Debug, Error)]
@@ -38,6 +56,12 @@ pub enum ManagerError {
 
     #[error("TUN device not available")]
     TunNotAvailable,
+
+    #[error("Overlay not configured")]
+    OverlayNotConfigured,
+
+    #[error("Overlay error: {0}")]
+    Overlay(#[from] OverlayError),
 }
 
 pub type Result<T> = std::result::Result<T, ManagerError>;
@@ -49,6 +73,17 @@ struct ManagedNic {
     router: Router,
     /// Routing table ID for this NIC
     table_id: Uuid,
+    /// Crash history of the router's reactor
+    crashes: ReactorCrashes,
+    /// IPv4 default route to the TUN reactor installed for the replies of
+    /// port forwards or for a floating IP (NICs in private networks only)
+    forwarded: bool,
+    /// Routes to the NICs of connected networks and the reactor each one
+    /// points at
+    peer_routes: HashMap<IpNet, ReactorId>,
+    /// Routes to addresses of the network on other hosts, through the
+    /// overlay
+    overlay_routes: HashSet<IpNet>,
 }
 
 /// NetworkManager manages the lifecycle of routers for networks and NICs.
@@ -67,6 +102,35 @@ pub struct NetworkManager {
     tun_table_id: Mutex<Option<Uuid>>,
     /// Managed NICs by ID
     nics: Mutex<HashMap<Uuid, ManagedNic>>,
+    /// MTU of routed traffic for all NICs
+    mtu: AtomicU16,
+    /// Guest TX descriptors handled per burst by NIC reactors
+    tx_burst: AtomicUsize,
+    /// Cap on each reactor's TUN RX buffer pool
+    rx_buffer_max: AtomicUsize,
+    /// CPUs reactors are pinned to, round-robin; empty leaves them unpinned
+    reactor_cpus: Vec<usize>,
+    /// Index into `reactor_cpus` of the next router's CPU
+    next_cpu: AtomicUsize,
+    /// Packet buffer memory of all routers
+    hugepages: HugePageManager,
+    /// Namespace the TUN router and its kernel routes live in, `None` for
+    /// the host namespace
+    uplink: Option<UplinkNamespace>,
+    /// Proxy ARP/NDP on the upstream interface, if configured
+    neighbor_proxy: Option<NeighborProxy>,
+    /// Drop traffic between networks that aren't peered
+    strict_isolation: AtomicBool,
+    /// Host addresses of port forwards and floating IPs routed into the TUN
+    forward_addresses: Mutex<HashSet<Ipv4Addr>>,
+    /// Directory new NICs get their vhost-user socket in
+    socket_dir: PathBuf,
+    /// Socket owner, group and mode where a NIC sets none
+    socket_defaults: SocketAccess,
+    /// Tunnel to NICs of the same networks on other hosts, if configured
+    overlay: Option<Arc<Overlay>>,
+    /// Resolver for names outside the networks' zones, if configured
+    dns_forwarder: Option<Arc<DnsForwarder>>,
 }
 
 impl NetworkManager {
@@ -80,9 +144,233 @@ impl NetworkManager {
             tun_router: Mutex::new(None),
             tun_table_id: Mutex::new(None),
             nics: Mutex::new(HashMap::new()),
+            mtu: AtomicU16::new(pmtu::DEFAULT_MTU),
+            tx_burst: AtomicUsize::new(DEFAULT_TX_BURST),
+            rx_buffer_max: AtomicUsize::new(rx_pool::DEFAULT_RX_BUFFER_MAX),
+            reactor_cpus: Vec::new(),
+            next_cpu: AtomicUsize::new(0),
+            hugepages: HugePageManager::new(),
+            uplink: None,
+            neighbor_proxy: None,
+            strict_isolation: AtomicBool::new(false),
+            forward_addresses: Mutex::new(HashSet::new()),
+            socket_dir: PathBuf::from(SOCKET_DIR),
+            socket_defaults: SocketAccess::default(),
+            overlay: None,
+            dns_forwarder: None,
+        }
+    }
+
+    /// Set the MTU of routed traffic. VMs get an ICMP too-big reply for
+    /// larger packets, so it must not exceed the MTU of the path behind the
+    /// host (e.g. the overlay's MTU). Applies to NIC routers created after
+    /// this call; see [`Self::apply_tunables`] for running ones.
+    pub fn with_mtu(self, mtu: u16) -> Self {
+        self.mtu.store(mtu, Ordering::Relaxed);
+        self
+    }
+
+    /// Set how many descriptors NIC reactors take off a guest's TX ring
+    /// per burst. Applies to NIC routers created after this call.
+    pub fn with_tx_burst(self, tx_burst: usize) -> Self {
+        self.tx_burst.store(tx_burst, Ordering::Relaxed);
+        self
+    }
+
+    /// Set how many buffers a reactor's TUN RX pool may grow to under
+    /// sustained load. Applies to routers created after this call.
+    pub fn with_rx_buffer_max(self, rx_buffer_max: usize) -> Self {
+        self.rx_buffer_max.store(rx_buffer_max, Ordering::Relaxed);
+        self
+    }
+
+    /// Pin reactors to `cpus`, one CPU per router in turn, with their
+    /// buffers on the CPU's NUMA node. Applies to routers created after
+    /// this call.
+    pub fn with_reactor_cpus(mut self, cpus: Vec<usize>) -> Self {
+        self.reactor_cpus = cpus;
+        self
+    }
+
+    /// Run the TUN router in `uplink` instead of the host namespace. Must
+    /// be set before [`Self::init_tun`].
+    pub fn with_uplink_namespace(mut self, uplink: UplinkNamespace) -> Self {
+        self.uplink = Some(uplink);
+        self
+    }
+
+    /// Answer ARP/ND with `proxy` for the routed prefixes of networks that
+    /// enable it.
+    pub fn with_neighbor_proxy(mut self, proxy: NeighborProxy) -> Self {
+        self.neighbor_proxy = Some(proxy);
+        self
+    }
+
+    /// Drop traffic between networks that aren't peered, even where routes
+    /// join them (e.g. public networks meeting behind the uplink).
+    pub fn with_strict_isolation(self, strict: bool) -> Self {
+        self.strict_isolation.store(strict, Ordering::Relaxed);
+        self
+    }
+
+    /// Put the vhost-user sockets of new NICs in `dir`. NICs keep the path
+    /// they were created with.
+    pub fn with_socket_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.socket_dir = dir.into();
+        self
+    }
+
+    /// Give NIC sockets this owner, group and mode where the NIC doesn't
+    /// set its own.
+    pub fn with_socket_access(mut self, defaults: SocketAccess) -> Self {
+        self.socket_defaults = defaults;
+        self
+    }
+
+    /// Tunnel traffic to the addresses of a network on other hosts through
+    /// `overlay`. Which addresses those are is set per network with
+    /// [`Self::set_overlay_peers`].
+    pub fn with_overlay(mut self, overlay: Arc<Overlay>) -> Self {
+        self.overlay = Some(overlay);
+        self
+    }
+
+    /// The overlay endpoint, if configured.
+    pub fn overlay(&self) -> Option<&Arc<Overlay>> {
+        self.overlay.as_ref()
+    }
+
+    /// Forward DNS queries for names outside a network's zone through
+    /// `forwarder`, to the network's upstream servers or else the
+    /// forwarder's.
+    pub fn with_dns_forwarder(mut self, forwarder: Arc<DnsForwarder>) -> Self {
+        self.dns_forwarder = Some(forwarder);
+        self
+    }
+
+    /// The DNS forwarder, if configured.
+    pub fn dns_forwarder(&self) -> Option<&Arc<DnsForwarder>> {
+        self.dns_forwarder.as_ref()
+    }
+
+    /// Directory new NICs get their vhost-user socket in.
+    pub fn socket_dir(&self) -> &Path {
+        &self.socket_dir
+    }
+
+    /// Why a VMM running as the owner and group of `access` (or the
+    /// defaults) can't reach the socket directory, `None` if it can.
+    pub fn socket_dir_error(&self, access: SocketAccess) -> Option<String> {
+        access
+            .or(self.socket_defaults)
+            .directory_error(&self.socket_dir)
+    }
+
+    /// Socket path for a new NIC.
+    pub fn socket_path(&self, nic_id: &Uuid) -> String {
+        generate_socket_path(&self.socket_dir, nic_id)
+    }
+
+    /// Owner, group and mode the socket of `nic` gets.
+    pub fn socket_access(&self, nic: &NicData) -> SocketAccess {
+        nic.socket_access.or(self.socket_defaults)
+    }
+
+    /// Why the VMM of `nic` can't connect to its socket, `None` if it can
+    /// or the NIC has no router on this host.
+    pub async fn socket_error(&self, nic: &NicData) -> Option<String> {
+        if !self.nics.lock().await.contains_key(&nic.id) {
+            return None;
+        }
+        self.socket_access(nic)
+            .access_error(Path::new(&nic.socket_path))
+    }
+
+    /// Whether strict isolation between networks is enabled.
+    pub fn strict_isolation(&self) -> bool {
+        self.strict_isolation.load(Ordering::Relaxed)
+    }
+
+    /// Change the MTU, TX burst size, RX pool cap and strict isolation of
+    /// the running daemon. Running reactors pick the values up in place,
+    /// without touching their vhost-user sessions; guests learn a new MTU
+    /// on their next DHCP renewal.
+    pub async fn apply_tunables(
+        &self,
+        mtu: u16,
+        tx_burst: usize,
+        rx_buffer_max: usize,
+        strict_isolation: bool,
+    ) -> Result<()> {
+        self.mtu.store(mtu, Ordering::Relaxed);
+        self.tx_burst.store(tx_burst, Ordering::Relaxed);
+        self.rx_buffer_max.store(rx_buffer_max, Ordering::Relaxed);
+        let was_strict = self
+            .strict_isolation
+            .swap(strict_isolation, Ordering::Relaxed);
+
+        if let Some(tun) = self.tun_router.lock().await.as_ref() {
+            tun.reactor_handle().set_rx_buffer_max(rx_buffer_max);
+        }
+        {
+            let nics_guard = self.nics.lock().await;
+            for managed in nics_guard.values() {
+                let handle = managed.router.reactor_handle();
+                handle.set_mtu(mtu);
+                handle.set_tx_burst(tx_burst);
+                handle.set_rx_buffer_max(rx_buffer_max);
+                if was_strict && !strict_isolation {
+                    handle.set_isolation(None);
+                }
+            }
+        }
+        if strict_isolation {
+            self.sync_isolation().await?;
+        }
+        Ok(())
+    }
+
+    /// Map buffers for `routers` routers ahead of time, spread over the
+    /// NUMA nodes of the reactor CPUs the way routers will be placed.
+    /// Returns how many could be mapped; the rest are mapped on demand.
+    pub fn preallocate_hugepages(&self, routers: usize) -> usize {
+        let size = 2 * TUN_BUFFER_COUNT * TUN_BUFFER_SIZE;
+        let mut per_node: HashMap<Option<usize>, usize> = HashMap::new();
+        for i in 0..routers {
+            let cpu = self.reactor_cpus.get(i % self.reactor_cpus.len().max(1));
+            *per_node
+                .entry(cpu.and_then(|&cpu| hugepage::numa_node_of_cpu(cpu)))
+                .or_default() += 1;
+        }
+        per_node
+            .into_iter()
+            .map(|(node, count)| self.hugepages.preallocate(size, count, node))
+            .sum()
+    }
+
+    /// Packet buffer memory of all routers.
+    pub fn hugepages(&self) -> &HugePageManager {
+        &self.hugepages
+    }
+
+    /// Placement of the next router.
+    fn next_placement(&self) -> Placement {
+        let cpu = (!self.reactor_cpus.is_empty()).then(|| {
+            let i = self.next_cpu.fetch_add(1, Ordering::Relaxed);
+            self.reactor_cpus[i % self.reactor_cpus.len()]
+        });
+        Placement {
+            cpu,
+            hugepages: Some(self.hugepages.clone()),
+            netns: None,
         }
     }
 
+    /// Netlink handle for the namespace of the TUN router's kernel routes.
+    fn netlink(&self) -> io::Result<Handle> {
+        netns::netlink(self.uplink.as_ref().map(UplinkNamespace::netns))
+    }
+
     /// Get a reference to the reactor registry.
     pub fn registry(&self) -> &Arc<ReactorRegistry> {
         &self.registry
@@ -101,7 +389,7 @@ impl NetworkManager {
 
         info!(name = %tun_name, "Initializing global TUN device");
 
-        let router = Router::with_shared_registry(
+        let router = Router::with_placement(
             tun_name,
             None, // No IP address on TUN device
             TUN_BUFFER_SIZE,
@@ -109,9 +397,16 @@ impl NetworkManager {
             TUN_BUFFER_COUNT,
             None,
             Arc::clone(&self.registry),
+            Placement {
+                netns: self.uplink.as_ref().map(|u| u.netns().clone()),
+                ..self.next_placement()
+            },
         )
         .await
         .map_err(|e| ManagerError::RouterCreationFailed(e.to_string()))?;
+        router
+            .reactor_handle()
+            .set_rx_buffer_max(self.rx_buffer_max.load(Ordering::Relaxed));
 
         // Create routing table for TUN
         let table_id = Uuid::new_v4();
@@ -166,6 +461,12 @@ impl NetworkManager {
             match self.create_nic_router(&nic, &network).await {
                 Ok(()) => {
                     info!(nic_id = %nic.id, socket = %nic.socket_path, "Recovered NIC router");
+                    // A reactor crash recorded before the restart is over
+                    if nic.state == NicState::Error
+                        && let Err(e) = self.storage.update_nic_state(&nic.id, NicState::Created)
+                    {
+                        warn!(nic_id = %nic.id, error = %e, "Failed to reset NIC state");
+                    }
                     recovered += 1;
                 }
                 Err(e) => {
@@ -179,6 +480,31 @@ impl NetworkManager {
         Ok(())
     }
 
+    /// Start routers for NICs in the database that have none, e.g. written
+    /// by an import or by hand. Running NICs are left alone. Returns how
+    /// many were started.
+    pub async fn start_missing_nics(&self) -> Result<usize> {
+        let running: HashSet<Uuid> = self.nics.lock().await.keys().copied().collect();
+        let mut started = 0;
+        for nic in self.storage.list_nics()? {
+            if running.contains(&nic.id) {
+                continue;
+            }
+            let Some(network) = self.storage.get_network_by_id(&nic.network_id)? else {
+                warn!(nic_id = %nic.id, network_id = %nic.network_id, "NIC's network not found, skipping");
+                continue;
+            };
+            match self.create_nic_router(&nic, &network).await {
+                Ok(()) => {
+                    info!(nic_id = %nic.id, socket = %nic.socket_path, "Started NIC router");
+                    started += 1;
+                }
+                Err(e) => warn!(nic_id = %nic.id, error = %e, "Failed to start NIC router"),
+            }
+        }
+        Ok(started)
+    }
+
     /// Get the TUN reactor ID.
     pub async fn tun_reactor_id(&self) -> Option<ReactorId> {
         let guard = self.tun_router.lock().await;
@@ -195,7 +521,11 @@ impl NetworkManager {
         }
 
         // Ensure socket directory exists
-        std::fs::create_dir_all(SOCKET_DIR)?;
+        if let Some(dir) = Path::new(&nic.socket_path).parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let policy = self.security_policy(&nic.id)?;
 
         // Build VhostConfig
         let mut vhost_config = VhostConfig::new(&nic.socket_path, nic.mac_address);
@@ -217,9 +547,19 @@ impl NetworkManager {
         ) {
             vhost_config = vhost_config.with_ipv6(addr, gateway, prefix.prefix_len());
         }
+        if let Some(delegated) = nic.delegated_ipv6_prefix {
+            vhost_config = vhost_config.with_delegated_prefix(delegated);
+        }
 
-        // Add DNS servers
-        vhost_config = vhost_config.with_dns(network.dns_servers.clone());
+        // Add DNS servers, boot options, the routed MTU, the TX burst size
+        // and the link state
+        vhost_config = vhost_config
+            .with_dns(network.dns_servers.clone())
+            .with_boot(network.boot_server, network.boot_file.clone())
+            .with_mtu(self.mtu.load(Ordering::Relaxed))
+            .with_tx_burst(self.tx_burst.load(Ordering::Relaxed))
+            .with_link_up(nic.link_up)
+            .with_socket_access(self.socket_access(nic));
 
         // Create TUN for this NIC (each NIC needs its own TUN for routing)
         // Using a unique TUN name based on NIC ID
@@ -234,7 +574,7 @@ impl NetworkManager {
             "Creating vhost router for NIC"
         );
 
-        let router = Router::with_shared_registry(
+        let router = Router::with_placement(
             &tun_name,
             Some((tun_ip, prefix_len)),
             TUN_BUFFER_SIZE,
@@ -242,10 +582,72 @@ impl NetworkManager {
             TUN_BUFFER_COUNT,
             Some(vhost_config),
             Arc::clone(&self.registry),
+            self.next_placement(),
         )
         .await
         .map_err(|e| ManagerError::RouterCreationFailed(e.to_string()))?;
 
+        let table_id = self
+            .setup_nic_reactor(&nics_guard, &router, nic, network, policy)
+            .await?;
+        let reactor_id = router.reactor_id();
+
+        nics_guard.insert(
+            nic.id,
+            ManagedNic {
+                data: nic.clone(),
+                router,
+                table_id,
+                crashes: ReactorCrashes::new(Instant::now()),
+                forwarded: false,
+                peer_routes: HashMap::new(),
+                overlay_routes: HashSet::new(),
+            },
+        );
+
+        // Install the network's static routes. This runs after insertion
+        // because the new NIC may be the next hop for routes in other tables.
+        let tun_reactor_id = self.tun_reactor_id().await;
+        if let Err(e) = self.sync_static_routes(&nics_guard, &network.id, tun_reactor_id) {
+            warn!(nic_id = %nic.id, error = %e, "Failed to install static routes");
+        }
+        if let Err(e) = self.sync_leases(&nics_guard, &network.id).await {
+            warn!(nic_id = %nic.id, error = %e, "Failed to install DHCP leases");
+        }
+        if let Err(e) = self.sync_dns(&nics_guard, &network.id) {
+            warn!(nic_id = %nic.id, error = %e, "Failed to install DNS names");
+        }
+        if let Err(e) = self.push_nat(&mut nics_guard).await {
+            warn!(nic_id = %nic.id, error = %e, "Failed to install port forwards and floating IPs");
+        }
+        if let Err(e) = self.push_peering_routes(&mut nics_guard) {
+            warn!(nic_id = %nic.id, error = %e, "Failed to install routes to connected networks");
+        }
+        self.push_overlay(&mut nics_guard);
+
+        info!(nic_id = %nic.id, reactor_id = %reactor_id, "NIC router created");
+
+        Ok(())
+    }
+
+    /// Set up a NIC router's reactor: its routing table with the NIC's own
+    /// addresses, the way out of public networks, routes to and from the
+    /// other NICs in the network and the security policy. Returns the
+    /// routing table ID. Static routes and DHCP leases are left to the
+    /// caller.
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    async fn setup_nic_reactor(
+        &self,
+        nics_guard: &HashMap<Uuid, ManagedNic>,
+        router: &Router,
+        nic: &NicData,
+        network: &NetworkData,
+        policy: Option<SecurityPolicy>,
+    ) -> Result<Uuid> {
+        router
+            .reactor_handle()
+            .set_rx_buffer_max(self.rx_buffer_max.load(Ordering::Relaxed));
+
         let reactor_id = router.reactor_id();
 
         // Create routing table for this NIC
@@ -295,22 +697,160 @@ impl NetworkManager {
         }
 
         // Add VM-to-VM routes for other NICs in the same network
-        self.add_vm_to_vm_routes(&nics_guard, &router, nic, network, table_id);
+        self.add_vm_to_vm_routes(nics_guard, router, nic, network, table_id);
 
-        nics_guard.insert(
-            nic.id,
-            ManagedNic {
-                data: nic.clone(),
-                router,
-                table_id,
-            },
-        );
+        // Filter per the NIC's security groups before the VM connects
+        router.reactor_handle().set_security_policy(policy);
 
-        info!(nic_id = %nic.id, reactor_id = %reactor_id, "NIC router created");
+        if let Some(map) = self.isolation_map()? {
+            router.reactor_handle().set_isolation(Some(Isolation {
+                network_id: network.id,
+                map,
+            }));
+        }
+
+        Ok(table_id)
+    }
+
+    /// Look for crashed NIC reactors and restart those whose backoff has
+    /// passed. A crashed reactor's NIC is put into the Error state until
+    /// its reactor is back.
+    pub async fn supervise_reactors(&self, now: Instant) -> Vec<ReactorEvent> {
+        let mut nics_guard = self.nics.lock().await;
+        let mut events = Vec::new();
+        let mut due = Vec::new();
+
+        for (nic_id, managed) in nics_guard.iter_mut() {
+            if managed.crashes.restart_at.is_some() {
+                if managed.crashes.restart_due(now) {
+                    due.push(*nic_id);
+                }
+                continue;
+            }
+            let Some(panic) = managed.router.crashed() else {
+                continue;
+            };
+
+            let state = match self.storage.get_nic_by_id(nic_id) {
+                Ok(Some(nic)) if nic.state != NicState::Error => nic.state,
+                _ => NicState::Created,
+            };
+            let restart_in = managed.crashes.crashed(panic.clone(), state, now);
+            if let Err(e) = self.storage.update_nic_state(nic_id, NicState::Error) {
+                warn!(nic_id = %nic_id, error = %e, "Failed to set NIC state");
+            }
+            events.push(ReactorEvent::Crashed {
+                nic_id: *nic_id,
+                crashes: managed.crashes.count,
+                panic,
+                restart_in,
+            });
+        }
 
+        for nic_id in due {
+            match self.restart_nic_reactor(&mut nics_guard, &nic_id).await {
+                Ok(()) => {
+                    let Some(managed) = nics_guard.get_mut(&nic_id) else {
+                        continue;
+                    };
+                    managed.crashes.restarted(now);
+                    if let Err(e) = self
+                        .storage
+                        .update_nic_state(&nic_id, managed.crashes.state_before)
+                    {
+                        warn!(nic_id = %nic_id, error = %e, "Failed to set NIC state");
+                    }
+                    events.push(ReactorEvent::Restarted { nic_id });
+                }
+                Err(e) => {
+                    let Some(managed) = nics_guard.get_mut(&nic_id) else {
+                        continue;
+                    };
+                    let retry_in = managed.crashes.restart_failed(now);
+                    events.push(ReactorEvent::RestartFailed {
+                        nic_id,
+                        error: e.to_string(),
+                        retry_in,
+                    });
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Restart a NIC's crashed reactor and set it up again from the store.
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    async fn restart_nic_reactor(
+        &self,
+        nics_guard: &mut HashMap<Uuid, ManagedNic>,
+        nic_id: &Uuid,
+    ) -> Result<()> {
+        let mut managed = nics_guard
+            .remove(nic_id)
+            .ok_or_else(|| ManagerError::NicNotFound(nic_id.to_string()))?;
+
+        // The NIC goes back into the map whatever happens, so that it is
+        // retried or torn down later
+        let result: Result<Uuid> = async {
+            let nic = self
+                .storage
+                .get_nic_by_id(nic_id)?
+                .ok_or_else(|| ManagerError::NicNotFound(nic_id.to_string()))?;
+            let network = self
+                .storage
+                .get_network_by_id(&nic.network_id)?
+                .ok_or_else(|| ManagerError::NetworkNotFound(nic.network_id.to_string()))?;
+            let policy = self.security_policy(nic_id)?;
+
+            managed
+                .router
+                .restart_reactor()
+                .await
+                .map_err(|e| ManagerError::RouterCreationFailed(e.to_string()))?;
+            managed.table_id = self
+                .setup_nic_reactor(nics_guard, &managed.router, &nic, &network, policy)
+                .await?;
+            managed.data = nic;
+            managed.forwarded = false;
+            managed.peer_routes.clear();
+            managed.overlay_routes.clear();
+            Ok(network.id)
+        }
+        .await;
+        nics_guard.insert(*nic_id, managed);
+
+        let network_id = result?;
+        let tun_reactor_id = self.tun_reactor_id().await;
+        if let Err(e) = self.sync_static_routes(nics_guard, &network_id, tun_reactor_id) {
+            warn!(nic_id = %nic_id, error = %e, "Failed to reinstall static routes");
+        }
+        if let Err(e) = self.sync_leases(nics_guard, &network_id).await {
+            warn!(nic_id = %nic_id, error = %e, "Failed to reinstall DHCP leases");
+        }
+        if let Err(e) = self.sync_dns(nics_guard, &network_id) {
+            warn!(nic_id = %nic_id, error = %e, "Failed to reinstall DNS names");
+        }
+        // Forwards and floating IPs point at the reactor, which has a new ID now
+        if let Err(e) = self.push_nat(nics_guard).await {
+            warn!(nic_id = %nic_id, error = %e, "Failed to reinstall port forwards and floating IPs");
+        }
+        // Same for the routes of connected networks to this NIC
+        if let Err(e) = self.push_peering_routes(nics_guard) {
+            warn!(nic_id = %nic_id, error = %e, "Failed to reinstall routes to connected networks");
+        }
+        // And for the overlay, which delivers to the reactor by its ID
+        self.push_overlay(nics_guard);
         Ok(())
     }
 
+    /// Crash history of a NIC's reactor; `None` for NICs without a router
+    /// on this host.
+    pub async fn reactor_crashes(&self, nic_id: &Uuid) -> Option<ReactorCrashes> {
+        let nics_guard = self.nics.lock().await;
+        nics_guard.get(nic_id).map(|m| m.crashes.clone())
+    }
+
     /// Shutdown and remove a NIC router.
     pub async fn remove_nic_router(&self, nic_id: &Uuid) -> Result<()> {
         let mut nics_guard = self.nics.lock().await;
@@ -323,6 +863,21 @@ impl NetworkManager {
                 self.remove_nic_route_from_tun(ipv4).await?;
             }
 
+            // Withdraw the delegated prefix so it isn't routed to a dead
+            // reactor until it is delegated again
+            if let Some(delegated) = managed.data.delegated_ipv6_prefix {
+                let prefix = IpPrefix::V6(delegated);
+                for other in nics_guard.values() {
+                    if other.data.network_id == managed.data.network_id {
+                        other
+                            .router
+                            .reactor_handle()
+                            .remove_route(other.table_id, prefix.clone());
+                    }
+                }
+                self.set_tun_host_route(prefix, None).await;
+            }
+
             // Prepare shutdown
             managed.router.prepare_shutdown();
 
@@ -333,11 +888,442 @@ impl NetworkManager {
 
             // Remove socket file
             let _ = std::fs::remove_file(&managed.data.socket_path);
+
+            // Withdraw static routes that used this NIC as next hop
+            let tun_reactor_id = self.tun_reactor_id().await;
+            if let Err(e) =
+                self.sync_static_routes(&nics_guard, &managed.data.network_id, tun_reactor_id)
+            {
+                warn!(nic_id = %nic_id, error = %e, "Failed to resync static routes");
+            }
+
+            // Withdraw the routes to the addresses leased behind this NIC
+            if let Err(e) = self
+                .sync_leases(&nics_guard, &managed.data.network_id)
+                .await
+            {
+                warn!(nic_id = %nic_id, error = %e, "Failed to resync DHCP leases");
+            }
+
+            // Forget the NIC's name
+            if let Err(e) = self.sync_dns(&nics_guard, &managed.data.network_id) {
+                warn!(nic_id = %nic_id, error = %e, "Failed to resync DNS names");
+            }
+
+            // Stop forwarding to the dead reactor
+            if let Err(e) = self.push_nat(&mut nics_guard).await {
+                warn!(nic_id = %nic_id, error = %e, "Failed to resync port forwards and floating IPs");
+            }
+
+            // Withdraw the routes of connected networks to this NIC
+            if let Err(e) = self.push_peering_routes(&mut nics_guard) {
+                warn!(nic_id = %nic_id, error = %e, "Failed to resync routes to connected networks");
+            }
+
+            // Stop delivering tunneled packets to it
+            self.push_overlay(&mut nics_guard);
         }
 
         Ok(())
     }
 
+    /// Push a NIC's security groups and their active rules to its reactor.
+    /// NICs without a router on this host are skipped.
+    pub async fn apply_security(&self, nic_id: &Uuid) -> Result<()> {
+        let policy = self.security_policy(nic_id)?;
+        let nics_guard = self.nics.lock().await;
+        if let Some(managed) = nics_guard.get(nic_id) {
+            debug!(
+                nic_id = %nic_id,
+                filtered = policy.is_some(),
+                "Applying security groups"
+            );
+            managed.router.reactor_handle().set_security_policy(policy);
+        }
+        Ok(())
+    }
+
+    /// Report a NIC's link as up or down to its guest. NICs without a
+    /// router on this host are skipped.
+    pub async fn set_nic_link_up(&self, nic_id: &Uuid, up: bool) {
+        let nics_guard = self.nics.lock().await;
+        if let Some(managed) = nics_guard.get(nic_id) {
+            info!(nic_id = %nic_id, up, "Setting NIC link state");
+            managed.router.set_link_up(up);
+        }
+    }
+
+    /// Take the sources each NIC's guest sent from since the last call that
+    /// aren't the NIC's. NICs without any are left out.
+    pub async fn take_foreign_sources(&self) -> Vec<(Uuid, HashMap<ForeignSource, u64>)> {
+        let nics_guard = self.nics.lock().await;
+        nics_guard
+            .iter()
+            .map(|(id, managed)| (*id, managed.router.reactor_handle().sources().take()))
+            .filter(|(_, sources)| !sources.is_empty())
+            .collect()
+    }
+
+    /// Read something off a NIC's reactor, or off all NIC reactors and the
+    /// TUN reactor (as `"tun"`) without one. NICs without a router on this
+    /// host are left out.
+    pub async fn reactors<T>(
+        &self,
+        nic_id: Option<&Uuid>,
+        read: impl Fn(&ReactorHandle) -> T,
+    ) -> Vec<(String, T)> {
+        let nics_guard = self.nics.lock().await;
+        let mut values: Vec<(String, T)> = nics_guard
+            .iter()
+            .filter(|(id, _)| nic_id.is_none_or(|wanted| wanted == *id))
+            .map(|(id, managed)| (id.to_string(), read(managed.router.reactor_handle())))
+            .collect();
+        values.sort_by(|a, b| a.0.cmp(&b.0));
+        if nic_id.is_none()
+            && let Some(router) = self.tun_router.lock().await.as_ref()
+        {
+            values.push(("tun".to_string(), read(router.reactor_handle())));
+        }
+        values
+    }
+
+    /// Push the networks' prefixes and peerings to every NIC reactor. Call
+    /// after networks, routed prefixes of NICs or peerings change; does
+    /// nothing without strict isolation.
+    pub async fn sync_isolation(&self) -> Result<()> {
+        let Some(map) = self.isolation_map()? else {
+            return Ok(());
+        };
+        let nics_guard = self.nics.lock().await;
+        for managed in nics_guard.values() {
+            managed
+                .router
+                .reactor_handle()
+                .set_isolation(Some(Isolation {
+                    network_id: managed.data.network_id,
+                    map: Arc::clone(&map),
+                }));
+        }
+        debug!(nics = nics_guard.len(), "Isolation synced");
+        Ok(())
+    }
+
+    /// Which network each address belongs to and which networks are
+    /// peered, from the store. `None` without strict isolation.
+    fn isolation_map(&self) -> Result<Option<Arc<IsolationMap>>> {
+        if !self.strict_isolation() {
+            return Ok(None);
+        }
+        let mut map = IsolationMap::new();
+        for network in self.storage.list_networks()? {
+            if let Some(subnet) = network.ipv4_subnet {
+                map.add_v4(subnet, network.id);
+            }
+            if let Some(prefix) = network.ipv6_prefix {
+                map.add_v6(prefix, network.id);
+            }
+        }
+        // Routed prefixes lie outside the subnets but belong to the NIC's network
+        for nic in self.storage.list_nics()? {
+            for prefix in &nic.routed_ipv4_prefixes {
+                map.add_v4(*prefix, nic.network_id);
+            }
+            for prefix in nic
+                .routed_ipv6_prefixes
+                .iter()
+                .chain(nic.delegated_ipv6_prefix.iter())
+            {
+                map.add_v6(*prefix, nic.network_id);
+            }
+        }
+        for peering in self.storage.list_peerings()? {
+            map.add_limited_peering(
+                peering.network_a_id,
+                peering.network_b_id,
+                peering.allowed_prefixes,
+            );
+        }
+        Ok(Some(Arc::new(map)))
+    }
+
+    /// Compile a NIC's stored security groups for its reactor.
+    fn security_policy(&self, nic_id: &Uuid) -> Result<Option<SecurityPolicy>> {
+        let groups = self.storage.list_security_groups_for_nic(nic_id)?;
+        let rules = self.storage.get_all_rules_for_nic(nic_id)?;
+        Ok(security::policy(&groups, &rules, chrono::Utc::now()))
+    }
+
+    /// Install a static route into every NIC routing table of its network.
+    pub async fn add_static_route(&self, route: &RouteData) -> Result<()> {
+        let nics_guard = self.nics.lock().await;
+        let tun_reactor_id = self.tun_reactor_id().await;
+        self.sync_static_routes(&nics_guard, &route.network_id, tun_reactor_id)?;
+
+        info!(
+            network_id = %route.network_id,
+            destination = %route.destination,
+            next_hop = ?route.next_hop,
+            "Static route installed"
+        );
+        Ok(())
+    }
+
+    /// Withdraw a static route from every NIC routing table of its network.
+    pub async fn remove_static_route(&self, route: &RouteData) -> Result<()> {
+        let nics_guard = self.nics.lock().await;
+
+        for managed in nics_guard.values() {
+            if managed.data.network_id == route.network_id {
+                managed
+                    .router
+                    .reactor_handle()
+                    .remove_route(managed.table_id, ip_prefix(route.destination));
+            }
+        }
+
+        info!(
+            network_id = %route.network_id,
+            destination = %route.destination,
+            "Static route withdrawn"
+        );
+        Ok(())
+    }
+
+    /// Reconcile stored static routes into the routing tables of a network's NICs.
+    ///
+    /// Routes whose next hop is not currently backed by a router (NIC not yet
+    /// created, or the NIC is the next hop itself) are withdrawn instead.
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    fn sync_static_routes(
+        &self,
+        nics_guard: &HashMap<Uuid, ManagedNic>,
+        network_id: &Uuid,
+        tun_reactor_id: Option<ReactorId>,
+    ) -> Result<()> {
+        let routes = self.storage.list_routes_in_network(network_id)?;
+        if routes.is_empty() {
+            return Ok(());
+        }
+
+        for route in &routes {
+            let target = match route.next_hop {
+                Some(hop) => nics_guard
+                    .values()
+                    .find(|m| {
+                        m.data.network_id == *network_id
+                            && match hop {
+                                IpAddr::V4(addr) => m.data.ipv4_address == Some(addr),
+                                IpAddr::V6(addr) => m.data.ipv6_address == Some(addr),
+                            }
+                    })
+                    .map(|m| m.router.reactor_id()),
+                None => tun_reactor_id,
+            };
+
+            for managed in nics_guard.values() {
+                if managed.data.network_id != *network_id {
+                    continue;
+                }
+
+                let handle = managed.router.reactor_handle();
+                match target {
+                    Some(id) if id != managed.router.reactor_id() => {
+                        handle.add_route(
+                            managed.table_id,
+                            ip_prefix(route.destination),
+                            RouteTarget::reactor(id),
+                        );
+                    }
+                    _ => handle.remove_route(managed.table_id, ip_prefix(route.destination)),
+                }
+            }
+
+            if target.is_none() {
+                debug!(
+                    route_id = %route.id,
+                    next_hop = ?route.next_hop,
+                    "Static route next hop not available"
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Serve a new static DHCP lease and route its address to its NIC.
+    pub async fn add_lease(&self, lease: &LeaseData) -> Result<()> {
+        let nics_guard = self.nics.lock().await;
+        self.sync_leases(&nics_guard, &lease.network_id).await?;
+
+        info!(
+            nic_id = %lease.nic_id,
+            mac = %lease.mac_string(),
+            ipv4 = %lease.ipv4_address,
+            "DHCP lease installed"
+        );
+        Ok(())
+    }
+
+    /// Stop serving a deleted static DHCP lease and withdraw the route to
+    /// its address.
+    pub async fn remove_lease(&self, lease: &LeaseData) -> Result<()> {
+        let nics_guard = self.nics.lock().await;
+        let prefix = IpPrefix::V4(Ipv4Net::new(lease.ipv4_address, 32).unwrap());
+
+        for managed in nics_guard.values() {
+            if managed.data.network_id == lease.network_id {
+                managed
+                    .router
+                    .reactor_handle()
+                    .remove_route(managed.table_id, prefix.clone());
+            }
+        }
+        self.set_tun_host_route(prefix, None).await;
+        self.sync_leases(&nics_guard, &lease.network_id).await?;
+
+        info!(
+            nic_id = %lease.nic_id,
+            ipv4 = %lease.ipv4_address,
+            "DHCP lease withdrawn"
+        );
+        Ok(())
+    }
+
+    /// Reconcile the stored static DHCP leases of a network: each NIC's
+    /// reactor answers DHCP for the MACs leased behind it, and the leased
+    /// addresses are routed to that reactor from the other NICs of the
+    /// network and, on public networks, from the uplink. Routes to
+    /// addresses leased behind NICs without a router are withdrawn.
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    async fn sync_leases(
+        &self,
+        nics_guard: &HashMap<Uuid, ManagedNic>,
+        network_id: &Uuid,
+    ) -> Result<()> {
+        let leases = self.storage.list_leases_in_network(network_id)?;
+        let is_public = self
+            .storage
+            .get_network_by_id(network_id)?
+            .is_some_and(|n| n.is_public);
+
+        for (nic_id, managed) in nics_guard.iter() {
+            if managed.data.network_id != *network_id {
+                continue;
+            }
+            let own = leases
+                .iter()
+                .filter(|l| l.nic_id == *nic_id)
+                .map(|l| (l.mac_address, l.ipv4_address))
+                .collect();
+            managed.router.reactor_handle().set_dhcp_leases(own);
+        }
+
+        for lease in &leases {
+            let prefix = IpPrefix::V4(Ipv4Net::new(lease.ipv4_address, 32).unwrap());
+            let target = nics_guard.get(&lease.nic_id).map(|m| m.router.reactor_id());
+
+            for managed in nics_guard.values() {
+                if managed.data.network_id != *network_id {
+                    continue;
+                }
+
+                let handle = managed.router.reactor_handle();
+                match target {
+                    Some(id) if id != managed.router.reactor_id() => {
+                        handle.add_route(
+                            managed.table_id,
+                            prefix.clone(),
+                            RouteTarget::reactor(id),
+                        );
+                    }
+                    _ => handle.remove_route(managed.table_id, prefix.clone()),
+                }
+            }
+
+            if is_public {
+                self.set_tun_host_route(prefix, target).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Route a host address or prefix from the global TUN to a reactor, or
+    /// withdraw the route without one.
+    /// Build the DNS zone of a network from its named NICs and hand it to
+    /// their reactors.
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    fn sync_dns(&self, nics_guard: &HashMap<Uuid, ManagedNic>, network_id: &Uuid) -> Result<()> {
+        let network = self
+            .storage
+            .get_network_by_id(network_id)?
+            .ok_or_else(|| ManagerError::NetworkNotFound(network_id.to_string()))?;
+
+        let members = || {
+            nics_guard
+                .values()
+                .filter(|m| m.data.network_id == *network_id)
+        };
+        let mut zone = DnsZone::new(&network.name);
+        if let Some(forwarder) = &self.dns_forwarder {
+            let upstreams = if network.dns_forwarders.is_empty() {
+                Arc::clone(forwarder.upstreams())
+            } else {
+                Arc::from(network.dns_forwarders)
+            };
+            if !upstreams.is_empty() {
+                zone.set_forward(Forward {
+                    forwarder: Arc::clone(forwarder),
+                    upstreams,
+                });
+            }
+        }
+        for managed in members() {
+            let Some(name) = managed.data.name.as_deref().filter(|n| !n.is_empty()) else {
+                continue;
+            };
+            if let Some(addr) = managed.data.ipv4_address {
+                zone.add(name, IpAddr::V4(addr));
+            }
+            if let Some(addr) = managed.data.ipv6_address {
+                zone.add(name, IpAddr::V6(addr));
+            }
+        }
+
+        debug!(network_id = %network_id, domain = zone.domain(), names = zone.len(), "DNS zone synced");
+        let zone = Arc::new(zone);
+        for managed in members() {
+            managed
+                .router
+                .reactor_handle()
+                .set_dns_zone(Some(Arc::clone(&zone)));
+        }
+        Ok(())
+    }
+
+    /// Push a network's DNS zone to its NICs again, e.g. after its
+    /// forwarders changed.
+    pub async fn sync_network_dns(&self, network_id: &Uuid) -> Result<()> {
+        let nics_guard = self.nics.lock().await;
+        self.sync_dns(&nics_guard, network_id)
+    }
+
+    async fn set_tun_host_route(&self, prefix: IpPrefix, target: Option<ReactorId>) {
+        let tun_guard = self.tun_router.lock().await;
+        let table_guard = self.tun_table_id.lock().await;
+
+        if let (Some(router), Some(table_id)) = (tun_guard.as_ref(), *table_guard) {
+            match target {
+                Some(id) => {
+                    router
+                        .reactor_handle()
+                        .add_route(table_id, prefix, RouteTarget::reactor(id))
+                }
+                None => router.reactor_handle().remove_route(table_id, prefix),
+            }
+        }
+    }
+
     /// Add host route for a NIC to the global TUN.
     async fn add_nic_route_to_tun(&self, nic: &NicData, reactor_id: ReactorId) -> Result<()> {
         let tun_guard = self.tun_router.lock().await;
@@ -363,6 +1349,19 @@ impl NetworkManager {
                 );
                 debug!(ipv6 = %ipv6, reactor_id = %reactor_id, "Added host route to TUN");
             }
+
+            if let Some(delegated) = nic.delegated_ipv6_prefix {
+                router.reactor_handle().add_route(
+                    table_id,
+                    IpPrefix::V6(delegated),
+                    RouteTarget::reactor(reactor_id),
+                );
+                debug!(
+                    prefix = %delegated,
+                    reactor_id = %reactor_id,
+                    "Added delegated prefix route to TUN"
+                );
+            }
         }
 
         Ok(())
@@ -423,6 +1422,22 @@ impl NetworkManager {
                     RouteTarget::reactor(router.reactor_id()),
                 );
             }
+
+            // Delegated prefixes, both ways
+            if let Some(other_delegated) = other_managed.data.delegated_ipv6_prefix {
+                router.reactor_handle().add_route(
+                    new_nic_table_id,
+                    IpPrefix::V6(other_delegated),
+                    RouteTarget::reactor(other_managed.router.reactor_id()),
+                );
+            }
+            if let Some(delegated) = nic.delegated_ipv6_prefix {
+                other_managed.router.reactor_handle().add_route(
+                    other_managed.table_id,
+                    IpPrefix::V6(delegated),
+                    RouteTarget::reactor(router.reactor_id()),
+                );
+            }
         }
     }
 
@@ -437,6 +1452,7 @@ impl NetworkManager {
         if let (Some(router), Some(table_id)) = (tun_guard.as_ref(), *table_guard) {
             let networks = self.storage.list_public_networks()?;
             let tun_if_index = router.tun_if_index();
+            let netlink = self.netlink()?;
 
             // Collect desired subnets
             let mut desired_v4: HashSet<Ipv4Net> = HashSet::new();
@@ -453,15 +1469,16 @@ impl NetworkManager {
 
             // Get current routes via TUN device
             let (current_v4, current_v6) =
-                Self::get_kernel_routes_for_interface(tun_if_index).await?;
+                Self::get_kernel_routes_for_interface(&netlink, tun_if_index).await?;
 
             // Add missing routes
             for subnet in &desired_v4 {
                 if !current_v4.contains(subnet)
-                    && let Err(e) = Self::add_kernel_route_v4(tun_if_index, *subnet).await
+                    && let Err(e) = Self::add_kernel_route_v4(&netlink, tun_if_index, *subnet).await
                 {
                     warn!(subnet = %subnet, error = %e, "Failed to add kernel route");
                 }
+                self.add_uplink_route(IpNet::V4(*subnet)).await;
                 // Always update internal LPM route
                 router.reactor_handle().add_route(
                     table_id,
@@ -474,10 +1491,11 @@ impl NetworkManager {
 
             for prefix in &desired_v6 {
                 if !current_v6.contains(prefix)
-                    && let Err(e) = Self::add_kernel_route_v6(tun_if_index, *prefix).await
+                    && let Err(e) = Self::add_kernel_route_v6(&netlink, tun_if_index, *prefix).await
                 {
                     warn!(prefix = %prefix, error = %e, "Failed to add IPv6 kernel route");
                 }
+                self.add_uplink_route(IpNet::V6(*prefix)).await;
                 router.reactor_handle().add_route(
                     table_id,
                     IpPrefix::V6(*prefix),
@@ -491,18 +1509,24 @@ impl NetworkManager {
             for subnet in &current_v4 {
                 if !desired_v4.contains(subnet) {
                     info!(subnet = %subnet, "Removing stale kernel route");
-                    if let Err(e) = Self::delete_kernel_route_v4(tun_if_index, *subnet).await {
+                    if let Err(e) =
+                        Self::delete_kernel_route_v4(&netlink, tun_if_index, *subnet).await
+                    {
                         warn!(subnet = %subnet, error = %e, "Failed to delete stale kernel route");
                     }
+                    self.remove_uplink_route(IpNet::V4(*subnet)).await;
                 }
             }
 
             for prefix in &current_v6 {
                 if !desired_v6.contains(prefix) {
                     info!(prefix = %prefix, "Removing stale IPv6 kernel route");
-                    if let Err(e) = Self::delete_kernel_route_v6(tun_if_index, *prefix).await {
+                    if let Err(e) =
+                        Self::delete_kernel_route_v6(&netlink, tun_if_index, *prefix).await
+                    {
                         warn!(prefix = %prefix, error = %e, "Failed to delete stale IPv6 kernel route");
                     }
+                    self.remove_uplink_route(IpNet::V6(*prefix)).await;
                 }
             }
 
@@ -520,14 +1544,12 @@ impl NetworkManager {
 
     /// Query kernel routing table for routes via a specific interface.
     async fn get_kernel_routes_for_interface(
+        handle: &Handle,
         if_index: u32,
     ) -> io::Result<(HashSet<Ipv4Net>, HashSet<Ipv6Net>)> {
         use futures::TryStreamExt;
         use netlink_packet_route::route::RouteAttribute;
 
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
-
         let mut v4_routes = HashSet::new();
         let mut v6_routes = HashSet::new();
 
@@ -596,10 +1618,11 @@ impl NetworkManager {
     }
 
     /// Add a kernel route via rtnetlink.
-    async fn add_kernel_route_v4(if_index: u32, subnet: Ipv4Net) -> io::Result<()> {
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
-
+    async fn add_kernel_route_v4(
+        handle: &Handle,
+        if_index: u32,
+        subnet: Ipv4Net,
+    ) -> io::Result<()> {
         match handle
             .route()
             .add()
@@ -622,10 +1645,11 @@ impl NetworkManager {
     }
 
     /// Add an IPv6 kernel route via rtnetlink.
-    async fn add_kernel_route_v6(if_index: u32, prefix: Ipv6Net) -> io::Result<()> {
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
-
+    async fn add_kernel_route_v6(
+        handle: &Handle,
+        if_index: u32,
+        prefix: Ipv6Net,
+    ) -> io::Result<()> {
         match handle
             .route()
             .add()
@@ -648,15 +1672,16 @@ impl NetworkManager {
     }
 
     /// Delete an IPv4 kernel route via rtnetlink.
-    async fn delete_kernel_route_v4(if_index: u32, subnet: Ipv4Net) -> io::Result<()> {
+    async fn delete_kernel_route_v4(
+        handle: &Handle,
+        if_index: u32,
+        subnet: Ipv4Net,
+    ) -> io::Result<()> {
         use netlink_packet_route::AddressFamily;
         use netlink_packet_route::route::{
             RouteAddress, RouteAttribute, RouteHeader, RouteMessage,
         };
 
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
-
         let mut message = RouteMessage::default();
         message.header.address_family = AddressFamily::Inet;
         message.header.destination_prefix_length = subnet.prefix_len();
@@ -682,15 +1707,16 @@ impl NetworkManager {
     }
 
     /// Delete an IPv6 kernel route via rtnetlink.
-    async fn delete_kernel_route_v6(if_index: u32, prefix: Ipv6Net) -> io::Result<()> {
+    async fn delete_kernel_route_v6(
+        handle: &Handle,
+        if_index: u32,
+        prefix: Ipv6Net,
+    ) -> io::Result<()> {
         use netlink_packet_route::AddressFamily;
         use netlink_packet_route::route::{
             RouteAddress, RouteAttribute, RouteHeader, RouteMessage,
         };
 
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
-
         let mut message = RouteMessage::default();
         message.header.address_family = AddressFamily::Inet6;
         message.header.destination_prefix_length = prefix.prefix_len();
@@ -726,11 +1752,13 @@ impl NetworkManager {
 
         if let (Some(router), Some(table_id)) = (tun_guard.as_ref(), *table_guard) {
             let tun_if_index = router.tun_if_index();
+            let netlink = self.netlink()?;
 
             if let Some(subnet) = network.ipv4_subnet {
-                if let Err(e) = Self::add_kernel_route_v4(tun_if_index, subnet).await {
+                if let Err(e) = Self::add_kernel_route_v4(&netlink, tun_if_index, subnet).await {
                     warn!(subnet = %subnet, error = %e, "Failed to add kernel route");
                 }
+                self.add_uplink_route(IpNet::V4(subnet)).await;
                 router.reactor_handle().add_route(
                     table_id,
                     IpPrefix::V4(subnet),
@@ -742,9 +1770,10 @@ impl NetworkManager {
             }
 
             if let Some(prefix) = network.ipv6_prefix {
-                if let Err(e) = Self::add_kernel_route_v6(tun_if_index, prefix).await {
+                if let Err(e) = Self::add_kernel_route_v6(&netlink, tun_if_index, prefix).await {
                     warn!(prefix = %prefix, error = %e, "Failed to add IPv6 kernel route");
                 }
+                self.add_uplink_route(IpNet::V6(prefix)).await;
                 router.reactor_handle().add_route(
                     table_id,
                     IpPrefix::V6(prefix),
@@ -770,11 +1799,13 @@ impl NetworkManager {
 
         if let (Some(router), Some(table_id)) = (tun_guard.as_ref(), *table_guard) {
             let tun_if_index = router.tun_if_index();
+            let netlink = self.netlink()?;
 
             if let Some(subnet) = network.ipv4_subnet {
-                if let Err(e) = Self::delete_kernel_route_v4(tun_if_index, subnet).await {
+                if let Err(e) = Self::delete_kernel_route_v4(&netlink, tun_if_index, subnet).await {
                     warn!(subnet = %subnet, error = %e, "Failed to delete kernel route");
                 }
+                self.remove_uplink_route(IpNet::V4(subnet)).await;
                 router
                     .reactor_handle()
                     .remove_route(table_id, IpPrefix::V4(subnet));
@@ -782,9 +1813,10 @@ impl NetworkManager {
             }
 
             if let Some(prefix) = network.ipv6_prefix {
-                if let Err(e) = Self::delete_kernel_route_v6(tun_if_index, prefix).await {
+                if let Err(e) = Self::delete_kernel_route_v6(&netlink, tun_if_index, prefix).await {
                     warn!(prefix = %prefix, error = %e, "Failed to delete IPv6 kernel route");
                 }
+                self.remove_uplink_route(IpNet::V6(prefix)).await;
                 router
                     .reactor_handle()
                     .remove_route(table_id, IpPrefix::V6(prefix));
@@ -795,10 +1827,360 @@ impl NetworkManager {
         Ok(())
     }
 
+    /// Push the port forwards and floating IPs of NICs with a router on
+    /// this host to the TUN reactor and route their host addresses into the
+    /// TUN. NICs in private networks get an IPv4 default route to the TUN
+    /// reactor for the replies, or for everything with a floating IP. Call
+    /// after port forwards or floating IPs change.
+    pub async fn sync_nat(&self) -> Result<()> {
+        let mut nics_guard = self.nics.lock().await;
+        self.push_nat(&mut nics_guard).await
+    }
+
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    async fn push_nat(&self, nics_guard: &mut HashMap<Uuid, ManagedNic>) -> Result<()> {
+        let Some(tun_reactor_id) = self.tun_reactor_id().await else {
+            return Ok(());
+        };
+
+        let mut forwards = Vec::new();
+        for fwd in self.storage.list_port_forwards()? {
+            let Some(managed) = nics_guard.get(&fwd.nic_id) else {
+                continue;
+            };
+            let Some(vm_ip) = managed.data.ipv4_address else {
+                continue;
+            };
+            let is_public = self
+                .storage
+                .get_network_by_id(&managed.data.network_id)?
+                .is_some_and(|n| n.is_public);
+            forwards.push(PortForward {
+                protocol: fwd.protocol.to_ip_protocol(),
+                host_ip: fwd.host_ip,
+                host_port: fwd.host_port,
+                vm_ip,
+                vm_port: fwd.vm_port,
+                target: managed.router.reactor_id(),
+                reply_only: !is_public,
+            });
+        }
+
+        let mut floating_ips = Vec::new();
+        let mut floating_private = HashSet::new();
+        for fip in self.storage.list_floating_ips()? {
+            let Some(managed) = fip.nic_id.and_then(|id| nics_guard.get(&id)) else {
+                continue;
+            };
+            let Some(vm_ip) = managed.data.ipv4_address else {
+                continue;
+            };
+            let is_public = self
+                .storage
+                .get_network_by_id(&managed.data.network_id)?
+                .is_some_and(|n| n.is_public);
+            if !is_public {
+                floating_private.insert(managed.router.reactor_id());
+            }
+            floating_ips.push(FloatingIp {
+                address: fip.address,
+                vm_ip,
+                target: managed.router.reactor_id(),
+            });
+        }
+
+        // Public NICs already route everything else to the TUN reactor
+        let mut to_tun: HashSet<ReactorId> = forwards
+            .iter()
+            .filter(|f| f.reply_only)
+            .map(|f| f.target)
+            .collect();
+        to_tun.extend(floating_private);
+        let mut changed_networks = HashSet::new();
+        for managed in nics_guard.values_mut() {
+            let forwarded = to_tun.contains(&managed.router.reactor_id());
+            if forwarded == managed.forwarded {
+                continue;
+            }
+            let default = IpPrefix::V4("0.0.0.0/0".parse().unwrap());
+            let handle = managed.router.reactor_handle();
+            if forwarded {
+                handle.add_route(
+                    managed.table_id,
+                    default,
+                    RouteTarget::reactor(tun_reactor_id),
+                );
+            } else {
+                handle.remove_route(managed.table_id, default);
+            }
+            managed.forwarded = forwarded;
+            changed_networks.insert(managed.data.network_id);
+        }
+        // A static default route of the network takes precedence
+        for network_id in &changed_networks {
+            self.sync_static_routes(nics_guard, network_id, Some(tun_reactor_id))?;
+        }
+
+        let addresses = forwards
+            .iter()
+            .map(|f| f.host_ip)
+            .chain(floating_ips.iter().map(|f| f.address))
+            .collect();
+        self.route_forward_addresses(addresses).await?;
+
+        let tun_guard = self.tun_router.lock().await;
+        if let Some(router) = tun_guard.as_ref() {
+            debug!(
+                forwards = forwards.len(),
+                floating_ips = floating_ips.len(),
+                "Port forwards and floating IPs synced"
+            );
+            router.reactor_handle().set_port_forwards(forwards);
+            router.reactor_handle().set_floating_ips(floating_ips);
+        }
+        Ok(())
+    }
+
+    /// Route between the NICs of connected networks: every NIC of one
+    /// network gets a route to each address of the other network's NICs
+    /// that the peering allows, and stale routes are removed. Call after
+    /// peerings are connected or disconnected.
+    pub async fn sync_peering_routes(&self) -> Result<()> {
+        let mut nics_guard = self.nics.lock().await;
+        self.push_peering_routes(&mut nics_guard)
+    }
+
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    fn push_peering_routes(&self, nics_guard: &mut HashMap<Uuid, ManagedNic>) -> Result<()> {
+        let mut wanted: HashMap<Uuid, HashMap<IpNet, ReactorId>> = HashMap::new();
+        for peering in self.storage.list_peerings()? {
+            if !peering.routed {
+                continue;
+            }
+            for target in nics_guard.values() {
+                let Some(source_network) = peering.peer_of(&target.data.network_id) else {
+                    continue;
+                };
+                let prefixes: Vec<IpNet> = nic_addresses(&target.data)
+                    .into_iter()
+                    .filter(|p| peering.allows(p))
+                    .collect();
+                for (source_id, source) in nics_guard.iter() {
+                    if source.data.network_id != source_network {
+                        continue;
+                    }
+                    let routes = wanted.entry(*source_id).or_default();
+                    for prefix in &prefixes {
+                        routes.insert(*prefix, target.router.reactor_id());
+                    }
+                }
+            }
+        }
+
+        let mut routes = 0;
+        for (nic_id, managed) in nics_guard.iter_mut() {
+            let wanted = wanted.remove(nic_id).unwrap_or_default();
+            let handle = managed.router.reactor_handle();
+            for prefix in managed.peer_routes.keys() {
+                if !wanted.contains_key(prefix) {
+                    handle.remove_route(managed.table_id, ip_prefix(*prefix));
+                }
+            }
+            for (prefix, target) in &wanted {
+                if managed.peer_routes.get(prefix) != Some(target) {
+                    handle.add_route(
+                        managed.table_id,
+                        ip_prefix(*prefix),
+                        RouteTarget::reactor(*target),
+                    );
+                }
+            }
+            routes += wanted.len();
+            managed.peer_routes = wanted;
+        }
+        debug!(routes, "Routes to connected networks synced");
+        Ok(())
+    }
+
+    /// Replace the other hosts a network spans and the addresses of its
+    /// NICs on each; no peers stop tunneling the network. Returns the
+    /// network's VNI.
+    pub async fn set_overlay_peers(
+        &self,
+        network_id: Uuid,
+        peers: Vec<OverlayPeer>,
+    ) -> Result<u32> {
+        let overlay = self
+            .overlay
+            .as_ref()
+            .ok_or(ManagerError::OverlayNotConfigured)?;
+        let vni = overlay.set_peers(network_id, peers)?;
+        let mut nics_guard = self.nics.lock().await;
+        self.push_overlay(&mut nics_guard);
+        Ok(vni)
+    }
+
+    /// Point the overlay at the NICs on this host and route every NIC to
+    /// the addresses of its network behind other hosts, removing stale
+    /// routes. Addresses of NICs on this host are never routed away. A
+    /// no-op without an overlay.
+    /// Note: Caller must pass the nics_guard to avoid deadlock.
+    fn push_overlay(&self, nics_guard: &mut HashMap<Uuid, ManagedNic>) {
+        let Some(overlay) = &self.overlay else {
+            return;
+        };
+
+        let local: Vec<(Uuid, ReactorId, Vec<IpNet>)> = nics_guard
+            .values()
+            .map(|m| {
+                (
+                    m.data.network_id,
+                    m.router.reactor_id(),
+                    nic_addresses(&m.data),
+                )
+            })
+            .collect();
+        overlay.set_local(&local);
+
+        let mut remote: HashMap<Uuid, HashSet<IpNet>> = HashMap::new();
+        for (network_id, _, _) in &local {
+            remote.entry(*network_id).or_insert_with(|| {
+                let own: HashSet<IpNet> = local
+                    .iter()
+                    .filter(|(n, _, _)| n == network_id)
+                    .flat_map(|(_, _, prefixes)| prefixes.iter().copied())
+                    .collect();
+                overlay
+                    .remote_prefixes(network_id)
+                    .into_iter()
+                    .filter(|p| !own.contains(p))
+                    .collect()
+            });
+        }
+
+        let mut routes = 0;
+        for managed in nics_guard.values_mut() {
+            let wanted = remote
+                .get(&managed.data.network_id)
+                .cloned()
+                .unwrap_or_default();
+            let handle = managed.router.reactor_handle();
+            for prefix in managed.overlay_routes.difference(&wanted) {
+                handle.remove_route(managed.table_id, ip_prefix(*prefix));
+            }
+            for prefix in wanted.difference(&managed.overlay_routes) {
+                handle.add_route(
+                    managed.table_id,
+                    ip_prefix(*prefix),
+                    RouteTarget::reactor(overlay.reactor_id()),
+                );
+            }
+            routes += wanted.len();
+            managed.overlay_routes = wanted;
+        }
+        debug!(routes, "Overlay routes synced");
+    }
+
+    /// Route the host addresses of port forwards and associated floating
+    /// IPs into the TUN and stop routing those that are no longer used.
+    async fn route_forward_addresses(&self, addresses: HashSet<Ipv4Addr>) -> Result<()> {
+        let mut current = self.forward_addresses.lock().await;
+        let tun_guard = self.tun_router.lock().await;
+        let Some(router) = tun_guard.as_ref() else {
+            return Ok(());
+        };
+        let tun_if_index = router.tun_if_index();
+        let netlink = self.netlink()?;
+
+        for addr in addresses.difference(&current) {
+            let host = Ipv4Net::new(*addr, 32).unwrap();
+            if let Err(e) = Self::add_kernel_route_v4(&netlink, tun_if_index, host).await {
+                warn!(host_ip = %addr, error = %e, "Failed to add kernel route for forwarded address");
+            }
+            self.add_uplink_route(IpNet::V4(host)).await;
+            info!(host_ip = %addr, "Routing forwarded address into TUN");
+        }
+        for addr in current.difference(&addresses) {
+            let host = Ipv4Net::new(*addr, 32).unwrap();
+            if let Err(e) = Self::delete_kernel_route_v4(&netlink, tun_if_index, host).await {
+                warn!(host_ip = %addr, error = %e, "Failed to delete kernel route for forwarded address");
+            }
+            self.remove_uplink_route(IpNet::V4(host)).await;
+            info!(host_ip = %addr, "Stopped routing forwarded address into TUN");
+        }
+
+        *current = addresses;
+        Ok(())
+    }
+
+    /// Route `prefix` from the host into the uplink namespace, if any.
+    async fn add_uplink_route(&self, prefix: IpNet) {
+        if let Some(uplink) = &self.uplink
+            && let Err(e) = uplink.add_host_route(prefix).await
+        {
+            warn!(prefix = %prefix, error = %e, "Failed to route prefix into uplink namespace");
+        }
+    }
+
+    /// Remove the host route of `prefix` into the uplink namespace, if any.
+    async fn remove_uplink_route(&self, prefix: IpNet) {
+        if let Some(uplink) = &self.uplink
+            && let Err(e) = uplink.remove_host_route(prefix).await
+        {
+            warn!(prefix = %prefix, error = %e, "Failed to remove route into uplink namespace");
+        }
+    }
+
+    /// Point the neighbor proxy at the routed prefixes of all NICs in
+    /// public networks that enable it and at the host addresses of port
+    /// forwards and floating IPs. A no-op without a proxy.
+    pub fn sync_neighbor_proxy(&self) -> Result<()> {
+        let Some(proxy) = &self.neighbor_proxy else {
+            return Ok(());
+        };
+
+        let mut prefixes = ProxyPrefixes::new();
+        for network in self.storage.list_public_networks()? {
+            if !network.proxy_neighbors {
+                continue;
+            }
+            for nic in self.storage.list_nics_in_network(&network.id)? {
+                for prefix in &nic.routed_ipv4_prefixes {
+                    prefixes.insert_v4(*prefix);
+                }
+                for prefix in &nic.routed_ipv6_prefixes {
+                    prefixes.insert_v6(*prefix);
+                }
+            }
+        }
+        // Host addresses of port forwards and floating IPs are routed into the
+        // TUN as well
+        for fwd in self.storage.list_port_forwards()? {
+            prefixes.insert_v4(Ipv4Net::new(fwd.host_ip, 32).unwrap());
+        }
+        for fip in self.storage.list_floating_ips()? {
+            if fip.nic_id.is_some() {
+                prefixes.insert_v4(Ipv4Net::new(fip.address, 32).unwrap());
+            }
+        }
+
+        info!(
+            interface = %proxy.interface(),
+            prefixes = prefixes.len(),
+            "Syncing neighbor proxy"
+        );
+        proxy.set_prefixes(prefixes);
+        Ok(())
+    }
+
     /// Shutdown the manager and all routers.
     pub async fn shutdown(&self) -> Result<()> {
         info!("Shutting down NetworkManager");
 
+        if let Some(proxy) = &self.neighbor_proxy {
+            proxy.stop();
+        }
+
         // Shutdown all NIC routers
         let mut nics_guard = self.nics.lock().await;
         for (nic_id, managed) in nics_guard.drain() {
@@ -809,6 +2191,13 @@ impl NetworkManager {
             }
         }
 
+        if let Some(overlay) = &self.overlay {
+            overlay.stop();
+        }
+        if let Some(forwarder) = &self.dns_forwarder {
+            forwarder.stop();
+        }
+
         // Shutdown TUN router
         let mut tun_guard = self.tun_router.lock().await;
         if let Some(router) = tun_guard.take() {
@@ -823,9 +2212,32 @@ impl NetworkManager {
     }
 }
 
-/// Generate socket path for a NIC.
-pub fn generate_socket_path(nic_id: &Uuid) -> String {
-    format!("{}/nic-{}.sock", SOCKET_DIR, nic_id)
+/// Host routes to a NIC's addresses and its delegated prefix.
+fn nic_addresses(nic: &NicData) -> Vec<IpNet> {
+    let mut prefixes = Vec::new();
+    if let Some(addr) = nic.ipv4_address {
+        prefixes.push(IpNet::V4(Ipv4Net::new(addr, 32).unwrap()));
+    }
+    if let Some(addr) = nic.ipv6_address {
+        prefixes.push(IpNet::V6(Ipv6Net::new(addr, 128).unwrap()));
+    }
+    if let Some(delegated) = nic.delegated_ipv6_prefix {
+        prefixes.push(IpNet::V6(delegated));
+    }
+    prefixes
+}
+
+/// Convert a static route destination into a routing table prefix.
+fn ip_prefix(net: IpNet) -> IpPrefix {
+    match net {
+        IpNet::V4(net) => IpPrefix::V4(net),
+        IpNet::V6(net) => IpPrefix::V6(net),
+    }
+}
+
+/// Generate socket path for a NIC in `dir`.
+pub fn generate_socket_path(dir: &Path, nic_id: &Uuid) -> String {
+    format!("{}/nic-{}.sock", dir.display(), nic_id)
 }
 
 #[cfg(test)]
@@ -835,10 +2247,15 @@ mod tests {
     #[test]
     fn test_generate_socket_path() {
         let id = Uuid::parse_str("550e8400-e29b-41d4-a716-446655440000").unwrap();
-        let path = generate_socket_path(&id);
+        let path = generate_socket_path(Path::new(SOCKET_DIR), &id);
         assert_eq!(
             path,
             "/run/mvirt/net/nic-550e8400-e29b-41d4-a716-446655440000.sock"
         );
+        let path = generate_socket_path(Path::new("/var/lib/mvirt/sockets"), &id);
+        assert_eq!(
+            path,
+            "/var/lib/mvirt/sockets/nic-550e8400-e29b-41d4-a716-446655440000.sock"
+        );
     }
 }
@@ -1,24 +1,56 @@
 //! gRPC NetService implementation.
 
-use super::manager::{NetworkManager, generate_socket_path};
+use super::manager::NetworkManager;
 use super::proto::net_service_server::NetService;
 use super::proto::*;
-use super::storage::{NetworkData, NicData, NicState, Storage, generate_mac_address};
+use super::storage::{
+    FloatingIpData, LeaseData, NetworkData, NicData, NicState, OwnerRef, PeeringData,
+    PortForwardData, RouteData, STATE_VERSION, SecurityGroupData, SecurityGroupRuleData, Storage,
+    generate_mac_address, snapshot_len,
+};
 use super::validation::{
-    ValidationError, allocate_ipv4_address, allocate_ipv6_address, validate_create_network,
-    validate_create_nic,
+    ValidationError, allocate_delegated_ipv6_prefix, allocate_floating_ip, allocate_ipv4_address,
+    allocate_ipv6_address, validate_add_route, validate_associate_floating_ip,
+    validate_connect_networks, validate_create_lease, validate_create_network, validate_create_nic,
+    validate_create_port_forward, validate_create_security_group, validate_delegated_prefix_len,
+    validate_floating_ip_address, validate_rule_window, validate_security_group_rule,
+    validate_socket_access,
 };
 use crate::audit::NetAuditLogger;
+use crate::capture::{self, CaptureManager, CaptureOptions, pcap_header, pcap_record};
+use crate::conflicts::{self, Conflicts};
+use crate::dns_forwarder;
+use crate::overlay;
+use crate::reactor::ReactorHandle;
+use crate::reactor::latency::{self, LatencyRecorder};
+use crate::reload::Reloader;
+use crate::socket_access;
 use chrono::Utc;
-use std::net::IpAddr;
+use ipnet::{IpNet, Ipv4Net};
+use mvirt_common::events::{Event, EventBus};
+use mvirt_common::naming;
+use mvirt_common::netboot::validate_network_boot;
+use mvirt_common::rule_window::TimedRule;
+use mvirt_common::watch;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::sync::Arc;
 use tonic::{Request, Response, Status};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 use uuid::Uuid;
 
 /// Version string for GetVersion RPC.
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Most frames a capture may buffer.
+const MAX_CAPTURE_BUFFER_FRAMES: usize = 65536;
+
+/// Default and longest window GetNicStats measures rates over.
+const DEFAULT_STATS_INTERVAL_MS: u32 = 1000;
+const MAX_STATS_INTERVAL_MS: u32 = 10_000;
+
+/// Size a capture stream fills its chunks up to.
+const CAPTURE_CHUNK_BYTES: usize = 64 * 1024;
+
 /// Convert storage error to gRPC status.
 fn storage_err_to_status(e: super::storage::StorageError) -> Status {
     match e {
@@ -34,13 +66,55 @@ fn storage_err_to_status(e: super::storage::StorageError) -> Status {
         super::storage::StorageError::IpAddressInUse(addr) => {
             Status::already_exists(format!("IP address already in use: {}", addr))
         }
+        super::storage::StorageError::RouteNotFound(id) => {
+            Status::not_found(format!("Route not found: {}", id))
+        }
+        super::storage::StorageError::RouteExists(dest) => {
+            Status::already_exists(format!("Route already exists: {}", dest))
+        }
+        super::storage::StorageError::LeaseNotFound(id) => {
+            Status::not_found(format!("Lease not found: {}", id))
+        }
+        super::storage::StorageError::LeaseExists(lease) => {
+            Status::already_exists(format!("Lease already exists: {}", lease))
+        }
+        super::storage::StorageError::PeeringNotFound(id) => {
+            Status::not_found(format!("Peering not found: {}", id))
+        }
+        super::storage::StorageError::PeeringExists(peering) => {
+            Status::already_exists(format!("Peering already exists: {}", peering))
+        }
+        super::storage::StorageError::PortForwardNotFound(id) => {
+            Status::not_found(format!("Port forward not found: {}", id))
+        }
+        super::storage::StorageError::PortForwardExists(endpoint) => {
+            Status::already_exists(format!("Port already forwarded: {}", endpoint))
+        }
+        super::storage::StorageError::FloatingIpNotFound(id) => {
+            Status::not_found(format!("Floating IP not found: {}", id))
+        }
+        super::storage::StorageError::FloatingIpExists(address) => {
+            Status::already_exists(format!("Floating IP already exists: {}", address))
+        }
+        super::storage::StorageError::NicHasFloatingIp(nic_id) => {
+            Status::failed_precondition(format!("NIC already has a floating IP: {}", nic_id))
+        }
+        super::storage::StorageError::SecurityGroupNotFound(id) => {
+            Status::not_found(format!("Security group not found: {}", id))
+        }
+        super::storage::StorageError::SecurityGroupNameExists(name) => {
+            Status::already_exists(format!("Security group name already exists: {}", name))
+        }
         _ => Status::internal(e.to_string()),
     }
 }
 
 /// Convert validation error to gRPC status.
 fn validation_err_to_status(e: ValidationError) -> Status {
-    Status::invalid_argument(e.to_string())
+    match e {
+        ValidationError::Storage(e) => Status::internal(e.to_string()),
+        e => Status::invalid_argument(e.to_string()),
+    }
 }
 
 /// Convert manager error to gRPC status.
@@ -52,6 +126,10 @@ fn manager_err_to_status(e: super::manager::ManagerError) -> Status {
         super::manager::ManagerError::NicNotFound(id) => {
             Status::not_found(format!("NIC not found: {}", id))
         }
+        super::manager::ManagerError::OverlayNotConfigured => {
+            Status::failed_precondition(e.to_string())
+        }
+        super::manager::ManagerError::Overlay(_) => Status::invalid_argument(e.to_string()),
         _ => Status::internal(e.to_string()),
     }
 }
@@ -71,6 +149,44 @@ fn network_data_to_proto(data: &NetworkData, nic_count: u32) -> Network {
         created_at: data.created_at.to_rfc3339(),
         updated_at: data.updated_at.to_rfc3339(),
         is_public: data.is_public,
+        boot_server: data.boot_server.map(|a| a.to_string()).unwrap_or_default(),
+        boot_file: data.boot_file.clone().unwrap_or_default(),
+        flow_logs: false,
+        flow_sample_rate: 0,
+        proxy_neighbors: data.proxy_neighbors,
+        conflicts: Vec::new(),
+        dns_forwarders: data.dns_forwarders.iter().map(|a| a.to_string()).collect(),
+    }
+}
+
+/// Convert an address conflict to proto AddressConflict.
+fn conflict_to_proto(conflict: &conflicts::AddressConflict) -> AddressConflict {
+    let kind = match conflict.kind {
+        conflicts::ConflictKind::Mac => ConflictKind::Mac,
+        conflicts::ConflictKind::Ip => ConflictKind::Ip,
+    };
+    AddressConflict {
+        kind: kind as i32,
+        address: conflict.address.clone(),
+        nic_id: conflict.nic_id.to_string(),
+        owner_nic_id: conflict.owner_nic_id.to_string(),
+        first_seen: conflict.first_seen.to_rfc3339(),
+        last_seen: conflict.last_seen.to_rfc3339(),
+        frames: conflict.frames,
+        quarantined: conflict.quarantined,
+    }
+}
+
+/// Parse a requested link state; `None` for unspecified.
+fn parse_link_state(link_state: i32) -> Result<Option<bool>, Status> {
+    match NicLinkState::try_from(link_state) {
+        Ok(NicLinkState::Unspecified) => Ok(None),
+        Ok(NicLinkState::Up) => Ok(Some(true)),
+        Ok(NicLinkState::Down) => Ok(Some(false)),
+        Err(_) => Err(Status::invalid_argument(format!(
+            "Invalid link state: {}",
+            link_state
+        ))),
     }
 }
 
@@ -97,6 +213,153 @@ fn nic_data_to_proto(data: &NicData) -> Nic {
         state: data.state as i32,
         created_at: data.created_at.to_rfc3339(),
         updated_at: data.updated_at.to_rfc3339(),
+        owner: data.owner.as_ref().map(|o| OwnerReference {
+            kind: o.kind.clone(),
+            id: o.id.clone(),
+        }),
+        link_state: i32::from(if data.link_up {
+            NicLinkState::Up
+        } else {
+            NicLinkState::Down
+        }),
+        reactor_crashes: 0,
+        last_reactor_crash: String::new(),
+        delegated_ipv6_prefix: data
+            .delegated_ipv6_prefix
+            .map(|p| p.to_string())
+            .unwrap_or_default(),
+        socket_access: Some(SocketAccess {
+            uid: data.socket_access.uid,
+            gid: data.socket_access.gid,
+            mode: data.socket_access.mode,
+        }),
+        socket_error: String::new(),
+    }
+}
+
+/// Convert NicData to proto Allocation.
+fn nic_data_to_allocation(data: &NicData) -> Allocation {
+    Allocation {
+        network_id: data.network_id.to_string(),
+        nic_id: data.id.to_string(),
+        name: data.name.clone().unwrap_or_default(),
+        mac_address: data.mac_string(),
+        ipv4_address: data.ipv4_address.map(|a| a.to_string()).unwrap_or_default(),
+        ipv6_address: data.ipv6_address.map(|a| a.to_string()).unwrap_or_default(),
+        routed_ipv4_prefixes: data
+            .routed_ipv4_prefixes
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+        routed_ipv6_prefixes: data
+            .routed_ipv6_prefixes
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+    }
+}
+
+/// Convert RouteData to proto Route.
+fn route_data_to_proto(data: &RouteData) -> Route {
+    Route {
+        id: data.id.to_string(),
+        network_id: data.network_id.to_string(),
+        destination: data.destination.to_string(),
+        next_hop: data.next_hop.map(|a| a.to_string()).unwrap_or_default(),
+        via_uplink: data.next_hop.is_none(),
+        created_at: data.created_at.to_rfc3339(),
+    }
+}
+
+/// Convert LeaseData to proto Lease.
+fn lease_data_to_proto(data: &LeaseData) -> Lease {
+    Lease {
+        id: data.id.to_string(),
+        network_id: data.network_id.to_string(),
+        nic_id: data.nic_id.to_string(),
+        mac_address: data.mac_string(),
+        ipv4_address: data.ipv4_address.to_string(),
+        created_at: data.created_at.to_rfc3339(),
+    }
+}
+
+/// Convert PeeringData to proto Peering.
+fn peering_data_to_proto(data: &PeeringData) -> Peering {
+    Peering {
+        id: data.id.to_string(),
+        network_a_id: data.network_a_id.to_string(),
+        network_b_id: data.network_b_id.to_string(),
+        created_at: data.created_at.to_rfc3339(),
+        routed: data.routed,
+        allowed_prefixes: data
+            .allowed_prefixes
+            .iter()
+            .map(|p| p.to_string())
+            .collect(),
+    }
+}
+
+/// Convert PortForwardData to proto PortForward.
+fn port_forward_data_to_proto(data: &PortForwardData, vm_ip: Option<Ipv4Addr>) -> PortForward {
+    PortForward {
+        id: data.id.to_string(),
+        nic_id: data.nic_id.to_string(),
+        protocol: data.protocol as i32,
+        host_ip: data.host_ip.to_string(),
+        host_port: data.host_port as u32,
+        vm_ip: vm_ip.map(|a| a.to_string()).unwrap_or_default(),
+        vm_port: data.vm_port as u32,
+        created_at: data.created_at.to_rfc3339(),
+    }
+}
+
+/// Convert FloatingIpData to proto FloatingIp.
+fn floating_ip_data_to_proto(data: &FloatingIpData, nic_ip: Option<Ipv4Addr>) -> FloatingIp {
+    FloatingIp {
+        id: data.id.to_string(),
+        address: data.address.to_string(),
+        nic_id: data.nic_id.map(|id| id.to_string()).unwrap_or_default(),
+        nic_ipv4_address: nic_ip.map(|a| a.to_string()).unwrap_or_default(),
+        created_at: data.created_at.to_rfc3339(),
+        updated_at: data.updated_at.to_rfc3339(),
+    }
+}
+
+/// Convert SecurityGroupData to proto SecurityGroup.
+fn security_group_data_to_proto(
+    data: &SecurityGroupData,
+    rules: Vec<SecurityGroupRule>,
+    nic_count: u32,
+) -> SecurityGroup {
+    SecurityGroup {
+        id: data.id.to_string(),
+        name: data.name.clone(),
+        description: data.description.clone().unwrap_or_default(),
+        rules,
+        nic_count,
+        created_at: data.created_at.to_rfc3339(),
+        updated_at: data.updated_at.to_rfc3339(),
+        audit: data.audit,
+    }
+}
+
+/// Convert SecurityGroupRuleData to proto SecurityGroupRule.
+fn security_group_rule_data_to_proto(data: &SecurityGroupRuleData) -> SecurityGroupRule {
+    SecurityGroupRule {
+        id: data.id.to_string(),
+        security_group_id: data.security_group_id.to_string(),
+        direction: data.direction as i32,
+        protocol: data.protocol as i32,
+        port_start: data.port_start.unwrap_or(0) as u32,
+        port_end: data.port_end.unwrap_or(0) as u32,
+        cidr: data.cidr.clone().unwrap_or_default(),
+        description: data.description.clone().unwrap_or_default(),
+        created_at: data.created_at.to_rfc3339(),
+        updated_at: data.updated_at.to_rfc3339(),
+        not_before: data.not_before.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        not_after: data.not_after.map(|t| t.to_rfc3339()).unwrap_or_default(),
+        schedule: data.schedule.map(|s| s.to_string()).unwrap_or_default(),
+        active: data.is_active_at(Utc::now()),
     }
 }
 
@@ -105,6 +368,19 @@ pub struct NetServiceImpl {
     storage: Arc<Storage>,
     manager: Arc<NetworkManager>,
     audit: Arc<NetAuditLogger>,
+    /// Broadcast bus for address allocations (NIC added / removed).
+    allocation_events: tokio::sync::broadcast::Sender<AllocationEvent>,
+    /// Running packet captures
+    captures: Arc<CaptureManager>,
+    /// Applies reloaded settings; `None` where the embedding daemon owns
+    /// the configuration
+    reloader: Option<Arc<Reloader>>,
+    /// Prefixes floating IPs are allocated from
+    floating_ip_pool: Vec<Ipv4Net>,
+    /// MAC and IP conflicts the conflict monitor found
+    conflicts: Arc<Conflicts>,
+    /// Tells the other daemons of the host about deleted NICs
+    host_events: Option<EventBus>,
 }
 
 impl NetServiceImpl {
@@ -114,11 +390,103 @@ impl NetServiceImpl {
         manager: Arc<NetworkManager>,
         audit: Arc<NetAuditLogger>,
     ) -> Self {
+        let (allocation_events, _) = tokio::sync::broadcast::channel(64);
         Self {
             storage,
             manager,
             audit,
+            allocation_events,
+            captures: Arc::new(CaptureManager::new()),
+            reloader: None,
+            floating_ip_pool: Vec::new(),
+            conflicts: Arc::new(Conflicts::default()),
+            host_events: None,
+        }
+    }
+
+    /// Serve the Reload RPC with `reloader`.
+    pub fn with_reloader(mut self, reloader: Arc<Reloader>) -> Self {
+        self.reloader = Some(reloader);
+        self
+    }
+
+    /// Allocate floating IPs from `pool`.
+    pub fn with_floating_ip_pool(mut self, pool: Vec<Ipv4Net>) -> Self {
+        self.floating_ip_pool = pool;
+        self
+    }
+
+    /// Show the conflicts `conflicts` holds in the status of networks.
+    pub fn with_conflicts(mut self, conflicts: Arc<Conflicts>) -> Self {
+        self.conflicts = conflicts;
+        self
+    }
+
+    /// Publish NIC deletions on the host's event bus, so mvirt-vmm
+    /// unplugs them from VMs.
+    pub fn with_host_events(mut self, bus: EventBus) -> Self {
+        self.host_events = Some(bus);
+        self
+    }
+
+    /// Parse a network's upstream DNS servers. Any require the daemon's
+    /// DNS forwarder.
+    fn parse_dns_forwarders(&self, values: &[String]) -> Result<Vec<SocketAddr>, Status> {
+        let forwarders = values
+            .iter()
+            .filter(|s| !s.is_empty())
+            .map(|s| {
+                dns_forwarder::parse_upstream(s)
+                    .ok_or_else(|| Status::invalid_argument(format!("Invalid DNS forwarder: {s}")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if !forwarders.is_empty() && self.manager.dns_forwarder().is_none() {
+            return Err(Status::failed_precondition(
+                "DNS forwarding is not enabled (MVIRT_NET_DNS_FORWARDERS)",
+            ));
+        }
+        Ok(forwarders)
+    }
+
+    /// [`network_data_to_proto`] with the address conflicts of the network.
+    fn network_to_proto(&self, network: &NetworkData, nic_count: u32) -> Network {
+        let mut proto = network_data_to_proto(network, nic_count);
+        proto.conflicts = self
+            .conflicts
+            .in_network(&network.id)
+            .iter()
+            .map(conflict_to_proto)
+            .collect();
+        proto
+    }
+
+    /// [`nic_data_to_proto`] with the crash history of the NIC's reactor.
+    async fn nic_to_proto(&self, nic: &NicData) -> Nic {
+        let mut proto = nic_data_to_proto(nic);
+        if let Some(crashes) = self.manager.reactor_crashes(&nic.id).await {
+            proto.reactor_crashes = crashes.count;
+            proto.last_reactor_crash = crashes.last_panic.unwrap_or_default();
         }
+        proto.socket_error = self.manager.socket_error(nic).await.unwrap_or_default();
+        proto
+    }
+
+    fn publish_nic_deleted(&self, nic: &NicData) {
+        if let Some(bus) = &self.host_events {
+            bus.publish(Event::NicDeleted {
+                nic_id: nic.id.to_string(),
+                mac: nic.mac_string(),
+                socket: nic.socket_path.clone(),
+            });
+        }
+    }
+
+    fn publish_allocation(&self, event_type: AllocationEventType, nic: &NicData) {
+        let _ = self.allocation_events.send(AllocationEvent {
+            r#type: event_type as i32,
+            timestamp: Utc::now().timestamp(),
+            allocation: Some(nic_data_to_allocation(nic)),
+        });
     }
 
     /// Resolve network by ID or name.
@@ -139,6 +507,194 @@ impl NetServiceImpl {
             Err(Status::invalid_argument("Network ID or name required"))
         }
     }
+
+    /// Resolve a floating IP by ID or address.
+    fn resolve_floating_ip(&self, id: &str) -> Result<FloatingIpData, Status> {
+        if id.is_empty() {
+            return Err(Status::invalid_argument(
+                "Floating IP ID or address required",
+            ));
+        }
+        let fip = if let Ok(uuid) = Uuid::parse_str(id) {
+            self.storage.get_floating_ip_by_id(&uuid)
+        } else if let Ok(address) = id.parse::<Ipv4Addr>() {
+            self.storage.get_floating_ip_by_address(address)
+        } else {
+            return Err(Status::invalid_argument(format!(
+                "Invalid floating IP: {}",
+                id
+            )));
+        };
+        fip.map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Floating IP not found: {}", id)))
+    }
+
+    /// [`floating_ip_data_to_proto`] with the address of the NIC.
+    fn floating_ip_to_proto(&self, fip: &FloatingIpData) -> Result<FloatingIp, Status> {
+        let nic_ip = match fip.nic_id {
+            Some(nic_id) => self
+                .storage
+                .get_nic_by_id(&nic_id)
+                .map_err(storage_err_to_status)?
+                .and_then(|nic| nic.ipv4_address),
+            None => None,
+        };
+        Ok(floating_ip_data_to_proto(fip, nic_ip))
+    }
+
+    /// Resolve NIC by ID.
+    async fn resolve_nic(&self, id: &str) -> Result<NicData, Status> {
+        if id.is_empty() {
+            return Err(Status::invalid_argument("NIC ID required"));
+        }
+        let uuid = Uuid::parse_str(id)
+            .map_err(|_| Status::invalid_argument(format!("Invalid NIC ID: {}", id)))?;
+        self.storage
+            .get_nic_by_id(&uuid)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("NIC not found: {}", id)))
+    }
+
+    /// Resolve security group by ID or name.
+    async fn resolve_security_group(
+        &self,
+        id: &str,
+        name: &str,
+    ) -> Result<SecurityGroupData, Status> {
+        if !id.is_empty() {
+            let uuid = Uuid::parse_str(id).map_err(|_| {
+                Status::invalid_argument(format!("Invalid security group ID: {}", id))
+            })?;
+            self.storage
+                .get_security_group_by_id(&uuid)
+                .map_err(storage_err_to_status)?
+                .ok_or_else(|| Status::not_found(format!("Security group not found: {}", id)))
+        } else if !name.is_empty() {
+            self.storage
+                .get_security_group_by_name(name)
+                .map_err(storage_err_to_status)?
+                .ok_or_else(|| Status::not_found(format!("Security group not found: {}", name)))
+        } else {
+            Err(Status::invalid_argument(
+                "Security group ID or name required",
+            ))
+        }
+    }
+
+    /// IDs of the NICs a security group is attached to.
+    fn security_group_nic_ids(&self, sg_id: &Uuid) -> Result<Vec<Uuid>, Status> {
+        self.storage
+            .list_nic_ids_in_security_group(sg_id)
+            .map_err(storage_err_to_status)
+    }
+
+    /// Push the security groups of the given NICs to their reactors.
+    async fn apply_security(&self, nic_ids: &[Uuid]) -> Result<(), Status> {
+        for nic_id in nic_ids {
+            self.manager
+                .apply_security(nic_id)
+                .await
+                .map_err(manager_err_to_status)?;
+        }
+        Ok(())
+    }
+
+    /// Proto form of a security group with its rules and NIC count.
+    fn security_group_to_proto(&self, sg: &SecurityGroupData) -> Result<SecurityGroup, Status> {
+        let rules = self
+            .storage
+            .list_rules_for_security_group(&sg.id)
+            .map_err(storage_err_to_status)?;
+        let nic_count = self.security_group_nic_ids(&sg.id)?.len() as u32;
+        Ok(security_group_data_to_proto(
+            sg,
+            rules
+                .iter()
+                .map(security_group_rule_data_to_proto)
+                .collect(),
+            nic_count,
+        ))
+    }
+
+    /// Persist a NIC's link state and apply it to its router.
+    async fn set_link_up(&self, id: &Uuid, up: bool) -> Result<(), Status> {
+        self.storage
+            .update_nic_link_up(id, up)
+            .map_err(storage_err_to_status)?;
+        self.manager.set_nic_link_up(id, up).await;
+        self.audit.nic_link_state(&id.to_string(), up);
+        Ok(())
+    }
+
+    /// Point the neighbor proxy at the current routed prefixes. A stale
+    /// proxy only costs upstream reachability, so failures are logged
+    /// rather than failing the request.
+    fn sync_neighbor_proxy(&self) {
+        if let Err(e) = self.manager.sync_neighbor_proxy() {
+            warn!(error = %e, "Failed to sync neighbor proxy");
+        }
+    }
+
+    /// Push the networks' prefixes and peerings to the reactors for strict
+    /// isolation. Failures are logged rather than failing the request; the
+    /// next change or restart catches up.
+    async fn sync_isolation(&self) {
+        if let Err(e) = self.manager.sync_isolation().await {
+            warn!(error = %e, "Failed to sync network isolation");
+        }
+    }
+
+    /// Route between the NICs of connected networks. Failures are logged
+    /// like those of [`Self::sync_isolation`].
+    async fn sync_peering_routes(&self) {
+        if let Err(e) = self.manager.sync_peering_routes().await {
+            warn!(error = %e, "Failed to sync routes between connected networks");
+        }
+    }
+
+    /// Read something off a NIC's reactor, or off all reactors for an
+    /// empty ID.
+    async fn reactors<T>(
+        &self,
+        nic_id: &str,
+        read: impl Fn(&ReactorHandle) -> T,
+    ) -> Result<Vec<(String, T)>, Status> {
+        if nic_id.is_empty() {
+            return Ok(self.manager.reactors(None, read).await);
+        }
+        let nic = self.resolve_nic(nic_id).await?;
+        let values = self.manager.reactors(Some(&nic.id), read).await;
+        if values.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "NIC {} has no reactor on this host",
+                nic_id
+            )));
+        }
+        Ok(values)
+    }
+
+    /// Latency recorders of a NIC's reactor, or of all reactors for an
+    /// empty ID.
+    async fn latency_recorders(
+        &self,
+        nic_id: &str,
+    ) -> Result<Vec<(String, LatencyRecorder)>, Status> {
+        if !latency::ENABLED {
+            return Err(Status::unimplemented(
+                "mvirt-net was built without the latency-histograms feature",
+            ));
+        }
+        self.reactors(nic_id, |h| h.latency().clone()).await
+    }
+
+    /// Resolve network from a field that accepts either UUID or name.
+    async fn resolve_network_ref(&self, id_or_name: &str) -> Result<NetworkData, Status> {
+        if Uuid::parse_str(id_or_name).is_ok() {
+            self.resolve_network(id_or_name, "").await
+        } else {
+            self.resolve_network("", id_or_name).await
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -154,6 +710,26 @@ impl NetService for NetServiceImpl {
         }))
     }
 
+    async fn reload(
+        &self,
+        _request: Request<ReloadRequest>,
+    ) -> Result<Response<ReloadResponse>, Status> {
+        info!("Reload");
+        let Some(reloader) = &self.reloader else {
+            return Err(Status::failed_precondition(
+                "Reload is not available here; the embedding daemon owns the configuration",
+            ));
+        };
+        let summary = reloader
+            .reload()
+            .await
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        Ok(Response::new(ReloadResponse {
+            changed: summary.changed.iter().map(|s| s.to_string()).collect(),
+            nics_started: summary.nics_started as u32,
+        }))
+    }
+
     // ========== Network Operations ==========
 
     async fn create_network(
@@ -164,6 +740,17 @@ impl NetService for NetServiceImpl {
 
         info!(name = %req.name, is_public = req.is_public, "CreateNetwork");
 
+        if req.flow_logs {
+            return Err(Status::unimplemented(
+                "Flow logs are only supported in mvirt-ebpf",
+            ));
+        }
+        if req.proxy_neighbors && !req.is_public {
+            return Err(Status::invalid_argument(
+                "Neighbor proxying requires a public network",
+            ));
+        }
+
         // Validate
         let (ipv4_subnet, ipv6_prefix, dns_servers) = validate_create_network(
             &req.name,
@@ -176,6 +763,9 @@ impl NetService for NetServiceImpl {
             &self.storage,
         )
         .map_err(validation_err_to_status)?;
+        let dns_forwarders = self.parse_dns_forwarders(&req.dns_forwarders)?;
+        let (boot_server, boot_file) = validate_network_boot(&req.boot_server, &req.boot_file)
+            .map_err(|e| validation_err_to_status(e.into()))?;
 
         // Parse NTP servers
         let ntp_servers: Vec<IpAddr> = req
@@ -198,6 +788,10 @@ impl NetService for NetServiceImpl {
             is_public: req.is_public,
             created_at: now,
             updated_at: now,
+            proxy_neighbors: req.proxy_neighbors,
+            dns_forwarders,
+            boot_server,
+            boot_file,
         };
 
         self.storage
@@ -209,6 +803,7 @@ impl NetService for NetServiceImpl {
             .add_public_network_routes(&network)
             .await
             .map_err(manager_err_to_status)?;
+        self.sync_isolation().await;
 
         info!(id = %network.id, name = %network.name, "Network created");
         self.audit
@@ -235,7 +830,7 @@ impl NetService for NetServiceImpl {
             .count_nics_in_network(&network.id)
             .map_err(storage_err_to_status)?;
 
-        Ok(Response::new(network_data_to_proto(&network, nic_count)))
+        Ok(Response::new(self.network_to_proto(&network, nic_count)))
     }
 
     async fn list_networks(
@@ -253,7 +848,7 @@ impl NetService for NetServiceImpl {
                 .storage
                 .count_nics_in_network(&network.id)
                 .map_err(storage_err_to_status)?;
-            proto_networks.push(network_data_to_proto(network, nic_count));
+            proto_networks.push(self.network_to_proto(network, nic_count));
         }
 
         Ok(Response::new(ListNetworksResponse {
@@ -270,6 +865,12 @@ impl NetService for NetServiceImpl {
         let uuid = Uuid::parse_str(&req.id)
             .map_err(|_| Status::invalid_argument(format!("Invalid network ID: {}", req.id)))?;
 
+        if req.flow_logs == Some(true) {
+            return Err(Status::unimplemented(
+                "Flow logs are only supported in mvirt-ebpf",
+            ));
+        }
+
         // Parse DNS and NTP servers
         let dns_servers: Vec<IpAddr> = req
             .dns_servers
@@ -285,10 +886,42 @@ impl NetService for NetServiceImpl {
             .filter_map(|s| s.parse().ok())
             .collect();
 
+        let dns_forwarders = self.parse_dns_forwarders(&req.dns_forwarders)?;
+
+        if let Some(proxy_neighbors) = req.proxy_neighbors {
+            let network = self
+                .storage
+                .get_network_by_id(&uuid)
+                .map_err(storage_err_to_status)?
+                .ok_or_else(|| Status::not_found(format!("Network not found: {}", req.id)))?;
+            if proxy_neighbors && !network.is_public {
+                return Err(Status::invalid_argument(
+                    "Neighbor proxying requires a public network",
+                ));
+            }
+        }
+
         self.storage
             .update_network(&uuid, &dns_servers, &ntp_servers)
             .map_err(storage_err_to_status)?;
 
+        if let Some(proxy_neighbors) = req.proxy_neighbors {
+            self.storage
+                .update_network_proxy_neighbors(&uuid, proxy_neighbors)
+                .map_err(storage_err_to_status)?;
+            self.sync_neighbor_proxy();
+        }
+
+        if !dns_forwarders.is_empty() || req.reset_dns_forwarders {
+            self.storage
+                .update_network_dns_forwarders(&uuid, &dns_forwarders)
+                .map_err(storage_err_to_status)?;
+            self.manager
+                .sync_network_dns(&uuid)
+                .await
+                .map_err(manager_err_to_status)?;
+        }
+
         // Fetch updated network
         let network = self
             .storage
@@ -305,7 +938,7 @@ impl NetService for NetServiceImpl {
         self.audit
             .network_updated(&network.id.to_string(), &network.name);
 
-        Ok(Response::new(network_data_to_proto(&network, nic_count)))
+        Ok(Response::new(self.network_to_proto(&network, nic_count)))
     }
 
     async fn delete_network(
@@ -357,6 +990,8 @@ impl NetService for NetServiceImpl {
                     .delete_nic(&nic.id)
                     .map_err(storage_err_to_status)?;
 
+                self.publish_allocation(AllocationEventType::Removed, nic);
+                self.publish_nic_deleted(nic);
                 nics_deleted += 1;
             }
         }
@@ -372,6 +1007,15 @@ impl NetService for NetServiceImpl {
             .storage
             .delete_network(&uuid)
             .map_err(storage_err_to_status)?;
+        if nics_deleted > 0 {
+            self.sync_neighbor_proxy();
+        }
+        self.sync_isolation().await;
+        if self.manager.overlay().is_some()
+            && let Err(e) = self.manager.set_overlay_peers(uuid, Vec::new()).await
+        {
+            warn!(id = %uuid, error = %e, "Failed to stop tunneling network");
+        }
 
         info!(id = %uuid, nics_deleted = nics_deleted, "Network deleted");
         self.audit.network_deleted(&uuid.to_string(), &network.name);
@@ -391,6 +1035,7 @@ impl NetService for NetServiceImpl {
         let req = request.into_inner();
 
         info!(network_id = %req.network_id, name = ?req.name, "CreateNic");
+        naming::validate_optional_name("NIC", Some(&req.name))?;
 
         // Resolve network (by ID or name)
         let network = if let Ok(uuid) = Uuid::parse_str(&req.network_id) {
@@ -420,6 +1065,22 @@ impl NetService for NetServiceImpl {
             &self.storage,
         )
         .map_err(validation_err_to_status)?;
+        let delegated_len = validate_delegated_prefix_len(&network, req.delegated_ipv6_prefix_len)
+            .map_err(validation_err_to_status)?;
+        let socket_access = req
+            .socket_access
+            .map(|a| socket_access::SocketAccess {
+                uid: a.uid,
+                gid: a.gid,
+                mode: a.mode,
+            })
+            .unwrap_or_default();
+        validate_socket_access(&socket_access).map_err(validation_err_to_status)?;
+
+        // A VMM that can't get to the socket directory can't connect
+        if let Some(e) = self.manager.socket_dir_error(socket_access) {
+            return Err(Status::failed_precondition(e));
+        }
 
         // Generate MAC if not provided
         let mac_address = mac.unwrap_or_else(generate_mac_address);
@@ -437,8 +1098,20 @@ impl NetService for NetServiceImpl {
             None
         };
 
+        let delegated_ipv6_prefix = match delegated_len {
+            Some(len) => Some(
+                allocate_delegated_ipv6_prefix(&network, len, &self.storage).ok_or_else(|| {
+                    Status::resource_exhausted(format!(
+                        "No free /{} in network {} to delegate",
+                        len, network.name
+                    ))
+                })?,
+            ),
+            None => None,
+        };
+
         let nic_id = Uuid::new_v4();
-        let socket_path = generate_socket_path(&nic_id);
+        let socket_path = self.manager.socket_path(&nic_id);
         let now = Utc::now();
 
         let nic = NicData {
@@ -458,6 +1131,13 @@ impl NetService for NetServiceImpl {
             state: NicState::Created,
             created_at: now,
             updated_at: now,
+            owner: req.owner.map(|o| OwnerRef {
+                kind: o.kind,
+                id: o.id,
+            }),
+            link_up: true,
+            delegated_ipv6_prefix,
+            socket_access,
         };
 
         // Save to storage
@@ -472,6 +1152,8 @@ impl NetService for NetServiceImpl {
             let _ = self.storage.delete_nic(&nic.id);
             return Err(manager_err_to_status(e));
         }
+        self.sync_neighbor_proxy();
+        self.sync_isolation().await;
 
         info!(
             id = %nic.id,
@@ -488,8 +1170,9 @@ impl NetService for NetServiceImpl {
             ipv4_address.map(|a| a.to_string()).as_deref(),
             ipv6_address.map(|a| a.to_string()).as_deref(),
         );
+        self.publish_allocation(AllocationEventType::Added, &nic);
 
-        Ok(Response::new(nic_data_to_proto(&nic)))
+        Ok(Response::new(self.nic_to_proto(&nic).await))
     }
 
     async fn get_nic(&self, request: Request<GetNicRequest>) -> Result<Response<Nic>, Status> {
@@ -515,7 +1198,7 @@ impl NetService for NetServiceImpl {
                 .ok_or_else(|| Status::not_found(format!("NIC not found: {}", name)))?
         };
 
-        Ok(Response::new(nic_data_to_proto(&nic)))
+        Ok(Response::new(self.nic_to_proto(&nic).await))
     }
 
     async fn list_nics(
@@ -535,7 +1218,10 @@ impl NetService for NetServiceImpl {
                 .map_err(storage_err_to_status)?
         };
 
-        let proto_nics: Vec<Nic> = nics.iter().map(nic_data_to_proto).collect();
+        let mut proto_nics = Vec::with_capacity(nics.len());
+        for nic in &nics {
+            proto_nics.push(self.nic_to_proto(nic).await);
+        }
 
         Ok(Response::new(ListNicsResponse { nics: proto_nics }))
     }
@@ -550,7 +1236,7 @@ impl NetService for NetServiceImpl {
             .map_err(|_| Status::invalid_argument(format!("Invalid NIC ID: {}", req.id)))?;
 
         // Parse routed prefixes
-        let routed_v4: Vec<ipnet::Ipv4Net> = req
+        let routed_v4: Vec<Ipv4Net> = req
             .routed_ipv4_prefixes
             .iter()
             .filter(|s| !s.is_empty())
@@ -564,9 +1250,17 @@ impl NetService for NetServiceImpl {
             .filter_map(|s| s.parse().ok())
             .collect();
 
+        let link_up = parse_link_state(req.link_state)?;
+
         self.storage
             .update_nic_routed_prefixes(&uuid, &routed_v4, &routed_v6)
             .map_err(storage_err_to_status)?;
+        self.sync_neighbor_proxy();
+        self.sync_isolation().await;
+
+        if let Some(up) = link_up {
+            self.set_link_up(&uuid, up).await?;
+        }
 
         // Fetch updated NIC
         let nic = self
@@ -581,6 +1275,26 @@ impl NetService for NetServiceImpl {
         Ok(Response::new(nic_data_to_proto(&nic)))
     }
 
+    async fn set_nic_link_state(
+        &self,
+        request: Request<SetNicLinkStateRequest>,
+    ) -> Result<Response<Nic>, Status> {
+        let req = request.into_inner();
+        let nic = self.resolve_nic(&req.id).await?;
+        let up = parse_link_state(req.link_state)?
+            .ok_or_else(|| Status::invalid_argument("Link state required"))?;
+
+        self.set_link_up(&nic.id, up).await?;
+        info!(id = %nic.id, up, "NIC link state set");
+
+        let nic = self
+            .storage
+            .get_nic_by_id(&nic.id)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("NIC not found: {}", req.id)))?;
+        Ok(Response::new(nic_data_to_proto(&nic)))
+    }
+
     async fn delete_nic(
         &self,
         request: Request<DeleteNicRequest>,
@@ -608,10 +1322,16 @@ impl NetService for NetServiceImpl {
             .storage
             .delete_nic(&uuid)
             .map_err(storage_err_to_status)?;
+        self.sync_neighbor_proxy();
+        self.sync_isolation().await;
 
         info!(id = %uuid, "NIC deleted");
         self.audit
             .nic_deleted(&uuid.to_string(), &nic.network_id.to_string());
+        if deleted {
+            self.publish_allocation(AllocationEventType::Removed, &nic);
+            self.publish_nic_deleted(&nic);
+        }
 
         Ok(Response::new(DeleteNicResponse { deleted }))
     }
@@ -639,81 +1359,1559 @@ impl NetService for NetServiceImpl {
         }))
     }
 
-    // ========== Security Group Operations (not supported in mvirt-net) ==========
-    //
-    // Security Groups are only supported in mvirt-ebpf (eBPF-based networking).
-    // These stub implementations return Unimplemented for the vhost-user backend.
+    // ========== Static Route Operations ==========
 
-    async fn create_security_group(
+    async fn add_route(
         &self,
-        _request: Request<CreateSecurityGroupRequest>,
-    ) -> Result<Response<SecurityGroup>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
-    }
+        request: Request<AddRouteRequest>,
+    ) -> Result<Response<Route>, Status> {
+        let req = request.into_inner();
 
-    async fn get_security_group(
-        &self,
-        _request: Request<GetSecurityGroupRequest>,
-    ) -> Result<Response<SecurityGroup>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
-    }
+        info!(
+            network_id = %req.network_id,
+            destination = %req.destination,
+            next_hop = %req.next_hop,
+            via_uplink = req.via_uplink,
+            "AddRoute"
+        );
 
-    async fn list_security_groups(
-        &self,
-        _request: Request<ListSecurityGroupsRequest>,
-    ) -> Result<Response<ListSecurityGroupsResponse>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
-    }
+        let network = self.resolve_network_ref(&req.network_id).await?;
+
+        let (destination, next_hop) = validate_add_route(
+            &network,
+            &req.destination,
+            &req.next_hop,
+            req.via_uplink,
+            &self.storage,
+        )
+        .map_err(validation_err_to_status)?;
+
+        let route = RouteData {
+            id: Uuid::new_v4(),
+            network_id: network.id,
+            destination,
+            next_hop,
+            created_at: Utc::now(),
+        };
+
+        self.storage
+            .create_route(&route)
+            .map_err(storage_err_to_status)?;
+
+        if let Err(e) = self.manager.add_static_route(&route).await {
+            error!(route_id = %route.id, error = %e, "Failed to install static route");
+            let _ = self.storage.delete_route(&route.id);
+            return Err(manager_err_to_status(e));
+        }
+
+        info!(id = %route.id, network_id = %network.id, "Route added");
+        self.audit.static_route_added(
+            &route.id.to_string(),
+            &network.id.to_string(),
+            &route.destination.to_string(),
+            route.next_hop.map(|a| a.to_string()).as_deref(),
+        );
+
+        Ok(Response::new(route_data_to_proto(&route)))
+    }
+
+    async fn remove_route(
+        &self,
+        request: Request<RemoveRouteRequest>,
+    ) -> Result<Response<RemoveRouteResponse>, Status> {
+        let req = request.into_inner();
+
+        let network = self.resolve_network_ref(&req.network_id).await?;
+
+        let uuid = Uuid::parse_str(&req.id)
+            .map_err(|_| Status::invalid_argument(format!("Invalid route ID: {}", req.id)))?;
+
+        let route = self
+            .storage
+            .get_route_by_id(&uuid)
+            .map_err(storage_err_to_status)?
+            .filter(|r| r.network_id == network.id)
+            .ok_or_else(|| Status::not_found(format!("Route not found: {}", req.id)))?;
+
+        let deleted = self
+            .storage
+            .delete_route(&uuid)
+            .map_err(storage_err_to_status)?;
+
+        self.manager
+            .remove_static_route(&route)
+            .await
+            .map_err(manager_err_to_status)?;
+
+        info!(id = %uuid, network_id = %network.id, "Route removed");
+        self.audit.static_route_removed(
+            &uuid.to_string(),
+            &network.id.to_string(),
+            &route.destination.to_string(),
+        );
+
+        Ok(Response::new(RemoveRouteResponse { deleted }))
+    }
+
+    async fn list_routes(
+        &self,
+        request: Request<ListRoutesRequest>,
+    ) -> Result<Response<ListRoutesResponse>, Status> {
+        let req = request.into_inner();
+
+        let network = self.resolve_network_ref(&req.network_id).await?;
+
+        let routes = self
+            .storage
+            .list_routes_in_network(&network.id)
+            .map_err(storage_err_to_status)?;
+
+        Ok(Response::new(ListRoutesResponse {
+            routes: routes.iter().map(route_data_to_proto).collect(),
+        }))
+    }
+
+    // ========== DHCP Lease Operations ==========
+
+    async fn create_lease(
+        &self,
+        request: Request<CreateLeaseRequest>,
+    ) -> Result<Response<Lease>, Status> {
+        let req = request.into_inner();
+
+        info!(
+            nic_id = %req.nic_id,
+            mac = %req.mac_address,
+            ipv4 = %req.ipv4_address,
+            "CreateLease"
+        );
+
+        let nic = self.resolve_nic(&req.nic_id).await?;
+        let network = self
+            .storage
+            .get_network_by_id(&nic.network_id)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Network not found: {}", nic.network_id)))?;
+
+        let (mac_address, ipv4) = validate_create_lease(
+            &network,
+            &nic,
+            &req.mac_address,
+            &req.ipv4_address,
+            &self.storage,
+        )
+        .map_err(validation_err_to_status)?;
+        let ipv4_address = ipv4
+            .or_else(|| allocate_ipv4_address(&network, &self.storage))
+            .ok_or_else(|| {
+                Status::resource_exhausted(format!(
+                    "No free IPv4 address in network {}",
+                    network.name
+                ))
+            })?;
+
+        let lease = LeaseData {
+            id: Uuid::new_v4(),
+            network_id: network.id,
+            nic_id: nic.id,
+            mac_address,
+            ipv4_address,
+            created_at: Utc::now(),
+        };
+
+        self.storage
+            .create_lease(&lease)
+            .map_err(storage_err_to_status)?;
+
+        if let Err(e) = self.manager.add_lease(&lease).await {
+            error!(lease_id = %lease.id, error = %e, "Failed to install DHCP lease");
+            let _ = self.storage.delete_lease(&lease.id);
+            return Err(manager_err_to_status(e));
+        }
+
+        info!(id = %lease.id, nic_id = %nic.id, "Lease created");
+        self.audit.lease_created(
+            &lease.id.to_string(),
+            &nic.id.to_string(),
+            &lease.mac_string(),
+            &lease.ipv4_address.to_string(),
+        );
+
+        Ok(Response::new(lease_data_to_proto(&lease)))
+    }
+
+    async fn list_leases(
+        &self,
+        request: Request<ListLeasesRequest>,
+    ) -> Result<Response<ListLeasesResponse>, Status> {
+        let req = request.into_inner();
+
+        let leases = if !req.nic_id.is_empty() {
+            let nic = self.resolve_nic(&req.nic_id).await?;
+            self.storage.list_leases_for_nic(&nic.id)
+        } else if !req.network_id.is_empty() {
+            let network = self.resolve_network_ref(&req.network_id).await?;
+            self.storage.list_leases_in_network(&network.id)
+        } else {
+            return Err(Status::invalid_argument("Network or NIC ID required"));
+        }
+        .map_err(storage_err_to_status)?;
+
+        Ok(Response::new(ListLeasesResponse {
+            leases: leases.iter().map(lease_data_to_proto).collect(),
+        }))
+    }
+
+    async fn delete_lease(
+        &self,
+        request: Request<DeleteLeaseRequest>,
+    ) -> Result<Response<DeleteLeaseResponse>, Status> {
+        let req = request.into_inner();
+
+        let uuid = Uuid::parse_str(&req.id)
+            .map_err(|_| Status::invalid_argument(format!("Invalid lease ID: {}", req.id)))?;
+
+        let lease = self
+            .storage
+            .get_lease_by_id(&uuid)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Lease not found: {}", req.id)))?;
+
+        let deleted = self
+            .storage
+            .delete_lease(&uuid)
+            .map_err(storage_err_to_status)?;
+
+        self.manager
+            .remove_lease(&lease)
+            .await
+            .map_err(manager_err_to_status)?;
+
+        info!(id = %uuid, nic_id = %lease.nic_id, "Lease deleted");
+        self.audit.lease_deleted(
+            &uuid.to_string(),
+            &lease.nic_id.to_string(),
+            &lease.ipv4_address.to_string(),
+        );
+
+        Ok(Response::new(DeleteLeaseResponse { deleted }))
+    }
+
+    // ========== Network Peering Operations ==========
+
+    async fn create_peering(
+        &self,
+        request: Request<CreatePeeringRequest>,
+    ) -> Result<Response<Peering>, Status> {
+        let req = request.into_inner();
+
+        info!(network_a = %req.network_a, network_b = %req.network_b, "CreatePeering");
+
+        if req.network_a.is_empty() || req.network_b.is_empty() {
+            return Err(Status::invalid_argument("Two networks required"));
+        }
+        let a = self.resolve_network_ref(&req.network_a).await?;
+        let b = self.resolve_network_ref(&req.network_b).await?;
+        if a.id == b.id {
+            return Err(Status::invalid_argument(
+                "A network can't be peered with itself",
+            ));
+        }
+
+        let peering = PeeringData::new(a.id, b.id);
+        self.storage
+            .create_peering(&peering)
+            .map_err(storage_err_to_status)?;
+        self.sync_isolation().await;
+
+        info!(id = %peering.id, network_a = %a.name, network_b = %b.name, "Peering created");
+        self.audit.peering_created(
+            &peering.id.to_string(),
+            &peering.network_a_id.to_string(),
+            &peering.network_b_id.to_string(),
+        );
+
+        Ok(Response::new(peering_data_to_proto(&peering)))
+    }
+
+    async fn list_peerings(
+        &self,
+        request: Request<ListPeeringsRequest>,
+    ) -> Result<Response<ListPeeringsResponse>, Status> {
+        let req = request.into_inner();
+
+        let peerings = if req.network_id.is_empty() {
+            self.storage.list_peerings()
+        } else {
+            let network = self.resolve_network_ref(&req.network_id).await?;
+            self.storage.list_peerings_for_network(&network.id)
+        }
+        .map_err(storage_err_to_status)?;
+
+        Ok(Response::new(ListPeeringsResponse {
+            peerings: peerings.iter().map(peering_data_to_proto).collect(),
+        }))
+    }
+
+    async fn delete_peering(
+        &self,
+        request: Request<DeletePeeringRequest>,
+    ) -> Result<Response<DeletePeeringResponse>, Status> {
+        let req = request.into_inner();
+
+        let uuid = Uuid::parse_str(&req.id)
+            .map_err(|_| Status::invalid_argument(format!("Invalid peering ID: {}", req.id)))?;
+
+        let peering = self
+            .storage
+            .get_peering_by_id(&uuid)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Peering not found: {}", req.id)))?;
+
+        let deleted = self
+            .storage
+            .delete_peering(&uuid)
+            .map_err(storage_err_to_status)?;
+        self.sync_isolation().await;
+        if peering.routed {
+            self.sync_peering_routes().await;
+        }
+
+        info!(id = %uuid, "Peering deleted");
+        self.audit.peering_deleted(
+            &uuid.to_string(),
+            &peering.network_a_id.to_string(),
+            &peering.network_b_id.to_string(),
+        );
+
+        Ok(Response::new(DeletePeeringResponse { deleted }))
+    }
+
+    async fn connect_networks(
+        &self,
+        request: Request<ConnectNetworksRequest>,
+    ) -> Result<Response<Peering>, Status> {
+        let req = request.into_inner();
+
+        info!(
+            network_a = %req.network_a,
+            network_b = %req.network_b,
+            allowed = ?req.allowed_prefixes,
+            "ConnectNetworks"
+        );
+
+        if req.network_a.is_empty() || req.network_b.is_empty() {
+            return Err(Status::invalid_argument("Two networks required"));
+        }
+        let a = self.resolve_network_ref(&req.network_a).await?;
+        let b = self.resolve_network_ref(&req.network_b).await?;
+        let allowed = validate_connect_networks(&a, &b, &req.allowed_prefixes)
+            .map_err(validation_err_to_status)?;
+
+        // Connecting peered networks routes the existing peering
+        let peering = match self
+            .storage
+            .get_peering_between(&a.id, &b.id)
+            .map_err(storage_err_to_status)?
+        {
+            Some(mut peering) => {
+                self.storage
+                    .update_peering_routes(&peering.id, true, &allowed)
+                    .map_err(storage_err_to_status)?;
+                peering.routed = true;
+                peering.allowed_prefixes = allowed;
+                peering
+            }
+            None => {
+                let mut peering = PeeringData::new(a.id, b.id);
+                peering.routed = true;
+                peering.allowed_prefixes = allowed;
+                self.storage
+                    .create_peering(&peering)
+                    .map_err(storage_err_to_status)?;
+                peering
+            }
+        };
+        self.sync_isolation().await;
+        self.sync_peering_routes().await;
+
+        info!(id = %peering.id, network_a = %a.name, network_b = %b.name, "Networks connected");
+        let proto = peering_data_to_proto(&peering);
+        self.audit.networks_connected(
+            &proto.id,
+            &proto.network_a_id,
+            &proto.network_b_id,
+            &proto.allowed_prefixes,
+        );
+
+        Ok(Response::new(proto))
+    }
+
+    async fn disconnect_networks(
+        &self,
+        request: Request<DisconnectNetworksRequest>,
+    ) -> Result<Response<DisconnectNetworksResponse>, Status> {
+        let req = request.into_inner();
+
+        info!(network_a = %req.network_a, network_b = %req.network_b, "DisconnectNetworks");
+
+        if req.network_a.is_empty() || req.network_b.is_empty() {
+            return Err(Status::invalid_argument("Two networks required"));
+        }
+        let a = self.resolve_network_ref(&req.network_a).await?;
+        let b = self.resolve_network_ref(&req.network_b).await?;
+
+        let peering = self
+            .storage
+            .get_peering_between(&a.id, &b.id)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "Networks {} and {} are not connected",
+                    a.name, b.name
+                ))
+            })?;
+
+        let disconnected = self
+            .storage
+            .delete_peering(&peering.id)
+            .map_err(storage_err_to_status)?;
+        self.sync_isolation().await;
+        self.sync_peering_routes().await;
+
+        info!(id = %peering.id, network_a = %a.name, network_b = %b.name, "Networks disconnected");
+        self.audit.networks_disconnected(
+            &peering.id.to_string(),
+            &peering.network_a_id.to_string(),
+            &peering.network_b_id.to_string(),
+        );
+
+        Ok(Response::new(DisconnectNetworksResponse { disconnected }))
+    }
+
+    async fn get_isolation_stats(
+        &self,
+        request: Request<GetIsolationStatsRequest>,
+    ) -> Result<Response<IsolationStats>, Status> {
+        let req = request.into_inner();
+
+        // The TUN reactor has no network to isolate
+        let nics = self
+            .reactors(&req.nic_id, |h| h.traffic().cross_network_blocked_total())
+            .await?
+            .into_iter()
+            .filter(|(reactor, _)| reactor != "tun")
+            .map(|(nic_id, blocked)| NicIsolationStats { nic_id, blocked })
+            .collect();
+
+        Ok(Response::new(IsolationStats {
+            strict: self.manager.strict_isolation(),
+            nics,
+        }))
+    }
+
+    async fn set_overlay_peers(
+        &self,
+        request: Request<SetOverlayPeersRequest>,
+    ) -> Result<Response<SetOverlayPeersResponse>, Status> {
+        let req = request.into_inner();
+
+        info!(network_id = %req.network_id, peers = req.peers.len(), "SetOverlayPeers");
+
+        let network = self.resolve_network_ref(&req.network_id).await?;
+        let peers = req
+            .peers
+            .iter()
+            .map(|peer| {
+                let vtep: IpAddr = peer.vtep.parse().map_err(|_| {
+                    Status::invalid_argument(format!("Invalid VTEP address: {}", peer.vtep))
+                })?;
+                let prefixes = peer
+                    .prefixes
+                    .iter()
+                    .map(|p| {
+                        p.parse::<IpNet>()
+                            .map_err(|_| Status::invalid_argument(format!("Invalid prefix: {}", p)))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(overlay::OverlayPeer { vtep, prefixes })
+            })
+            .collect::<Result<Vec<_>, Status>>()?;
+
+        let vni = self
+            .manager
+            .set_overlay_peers(network.id, peers)
+            .await
+            .map_err(manager_err_to_status)?;
+
+        info!(network = %network.name, vni, peers = req.peers.len(), "Overlay peers set");
+
+        Ok(Response::new(SetOverlayPeersResponse { vni }))
+    }
+
+    // ========== Port Forward Operations ==========
+
+    async fn create_port_forward(
+        &self,
+        request: Request<CreatePortForwardRequest>,
+    ) -> Result<Response<PortForward>, Status> {
+        let req = request.into_inner();
+
+        info!(
+            nic_id = %req.nic_id,
+            host_ip = %req.host_ip,
+            host_port = req.host_port,
+            vm_port = req.vm_port,
+            "CreatePortForward"
+        );
+
+        let nic = self.resolve_nic(&req.nic_id).await?;
+        let (protocol, host_ip, host_port, vm_port) = validate_create_port_forward(
+            &nic,
+            req.protocol,
+            &req.host_ip,
+            req.host_port,
+            req.vm_port,
+            &self.storage,
+        )
+        .map_err(validation_err_to_status)?;
+
+        let forward = PortForwardData {
+            id: Uuid::new_v4(),
+            nic_id: nic.id,
+            protocol,
+            host_ip,
+            host_port,
+            vm_port,
+            created_at: Utc::now(),
+        };
+
+        self.storage
+            .create_port_forward(&forward)
+            .map_err(storage_err_to_status)?;
+
+        if let Err(e) = self.manager.sync_nat().await {
+            error!(forward_id = %forward.id, error = %e, "Failed to install port forward");
+            let _ = self.storage.delete_port_forward(&forward.id);
+            return Err(manager_err_to_status(e));
+        }
+        self.sync_neighbor_proxy();
+
+        let endpoint = forward.host_endpoint();
+        let vm = format!(
+            "{}:{}",
+            nic.ipv4_address.map(|a| a.to_string()).unwrap_or_default(),
+            forward.vm_port
+        );
+        info!(id = %forward.id, nic_id = %nic.id, host = %endpoint, vm = %vm, "Port forward created");
+        self.audit.port_forward_created(
+            &forward.id.to_string(),
+            &nic.id.to_string(),
+            &endpoint,
+            &vm,
+        );
+
+        Ok(Response::new(port_forward_data_to_proto(
+            &forward,
+            nic.ipv4_address,
+        )))
+    }
+
+    async fn list_port_forwards(
+        &self,
+        request: Request<ListPortForwardsRequest>,
+    ) -> Result<Response<ListPortForwardsResponse>, Status> {
+        let req = request.into_inner();
+
+        let forwards = if req.nic_id.is_empty() {
+            self.storage.list_port_forwards()
+        } else {
+            let nic = self.resolve_nic(&req.nic_id).await?;
+            self.storage.list_port_forwards_for_nic(&nic.id)
+        }
+        .map_err(storage_err_to_status)?;
+
+        let mut port_forwards = Vec::with_capacity(forwards.len());
+        for forward in &forwards {
+            let vm_ip = self
+                .storage
+                .get_nic_by_id(&forward.nic_id)
+                .map_err(storage_err_to_status)?
+                .and_then(|nic| nic.ipv4_address);
+            port_forwards.push(port_forward_data_to_proto(forward, vm_ip));
+        }
+
+        Ok(Response::new(ListPortForwardsResponse { port_forwards }))
+    }
+
+    async fn delete_port_forward(
+        &self,
+        request: Request<DeletePortForwardRequest>,
+    ) -> Result<Response<DeletePortForwardResponse>, Status> {
+        let req = request.into_inner();
+
+        let uuid = Uuid::parse_str(&req.id).map_err(|_| {
+            Status::invalid_argument(format!("Invalid port forward ID: {}", req.id))
+        })?;
+
+        let forward = self
+            .storage
+            .get_port_forward_by_id(&uuid)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Port forward not found: {}", req.id)))?;
+
+        let deleted = self
+            .storage
+            .delete_port_forward(&uuid)
+            .map_err(storage_err_to_status)?;
+
+        self.manager
+            .sync_nat()
+            .await
+            .map_err(manager_err_to_status)?;
+        self.sync_neighbor_proxy();
+
+        let endpoint = forward.host_endpoint();
+        info!(id = %uuid, nic_id = %forward.nic_id, host = %endpoint, "Port forward deleted");
+        self.audit
+            .port_forward_deleted(&uuid.to_string(), &forward.nic_id.to_string(), &endpoint);
+
+        Ok(Response::new(DeletePortForwardResponse { deleted }))
+    }
+
+    // ========== Floating IP Operations ==========
+
+    async fn create_floating_ip(
+        &self,
+        request: Request<CreateFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        let req = request.into_inner();
+
+        info!(address = %req.address, nic_id = %req.nic_id, "CreateFloatingIp");
+
+        if self.floating_ip_pool.is_empty() {
+            return Err(Status::failed_precondition(
+                "No floating IP pool configured (MVIRT_NET_FLOATING_IP_POOL)",
+            ));
+        }
+        let address = if req.address.is_empty() {
+            allocate_floating_ip(&self.floating_ip_pool, &self.storage)
+                .ok_or_else(|| Status::resource_exhausted("Floating IP pool exhausted"))?
+        } else {
+            validate_floating_ip_address(&req.address, &self.floating_ip_pool, &self.storage)
+                .map_err(validation_err_to_status)?
+        };
+        let nic = if req.nic_id.is_empty() {
+            None
+        } else {
+            let nic = self.resolve_nic(&req.nic_id).await?;
+            validate_associate_floating_ip(&nic).map_err(validation_err_to_status)?;
+            Some(nic)
+        };
+
+        let now = Utc::now();
+        let fip = FloatingIpData {
+            id: Uuid::new_v4(),
+            address,
+            nic_id: nic.as_ref().map(|n| n.id),
+            created_at: now,
+            updated_at: now,
+        };
+        self.storage
+            .create_floating_ip(&fip)
+            .map_err(storage_err_to_status)?;
+
+        if let Err(e) = self.manager.sync_nat().await {
+            error!(fip_id = %fip.id, error = %e, "Failed to install floating IP");
+            let _ = self.storage.delete_floating_ip(&fip.id);
+            return Err(manager_err_to_status(e));
+        }
+        self.sync_neighbor_proxy();
+
+        info!(id = %fip.id, address = %address, nic_id = ?fip.nic_id, "Floating IP created");
+        self.audit
+            .floating_ip_created(&fip.id.to_string(), &address.to_string());
+        if let Some(nic) = &nic {
+            self.audit.floating_ip_associated(
+                &fip.id.to_string(),
+                &address.to_string(),
+                &nic.id.to_string(),
+                &nic.ipv4_address.map(|a| a.to_string()).unwrap_or_default(),
+            );
+        }
+
+        Ok(Response::new(floating_ip_data_to_proto(
+            &fip,
+            nic.and_then(|n| n.ipv4_address),
+        )))
+    }
+
+    async fn get_floating_ip(
+        &self,
+        request: Request<GetFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        let req = request.into_inner();
+        let fip = self.resolve_floating_ip(&req.id)?;
+        Ok(Response::new(self.floating_ip_to_proto(&fip)?))
+    }
+
+    async fn list_floating_ips(
+        &self,
+        request: Request<ListFloatingIpsRequest>,
+    ) -> Result<Response<ListFloatingIpsResponse>, Status> {
+        let req = request.into_inner();
+
+        let fips = if req.nic_id.is_empty() {
+            self.storage.list_floating_ips()
+        } else {
+            let nic = self.resolve_nic(&req.nic_id).await?;
+            self.storage
+                .get_floating_ip_for_nic(&nic.id)
+                .map(|fip| fip.into_iter().collect())
+        }
+        .map_err(storage_err_to_status)?;
+
+        let floating_ips = fips
+            .iter()
+            .map(|fip| self.floating_ip_to_proto(fip))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Response::new(ListFloatingIpsResponse { floating_ips }))
+    }
+
+    async fn associate_floating_ip(
+        &self,
+        request: Request<AssociateFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        let req = request.into_inner();
+
+        info!(id = %req.id, nic_id = %req.nic_id, "AssociateFloatingIp");
+
+        let fip = self.resolve_floating_ip(&req.id)?;
+        let nic = self.resolve_nic(&req.nic_id).await?;
+        validate_associate_floating_ip(&nic).map_err(validation_err_to_status)?;
+        if fip.nic_id == Some(nic.id) {
+            return Ok(Response::new(floating_ip_data_to_proto(
+                &fip,
+                nic.ipv4_address,
+            )));
+        }
+
+        let associated = self
+            .storage
+            .set_floating_ip_nic(&fip.id, Some(nic.id))
+            .map_err(storage_err_to_status)?;
+        if let Err(e) = self.manager.sync_nat().await {
+            error!(fip_id = %fip.id, error = %e, "Failed to associate floating IP");
+            let _ = self.storage.set_floating_ip_nic(&fip.id, fip.nic_id);
+            return Err(manager_err_to_status(e));
+        }
+        self.sync_neighbor_proxy();
+
+        info!(
+            id = %fip.id,
+            address = %fip.address,
+            nic_id = %nic.id,
+            previous = ?fip.nic_id,
+            "Floating IP associated"
+        );
+        if let Some(previous) = fip.nic_id {
+            self.audit.floating_ip_disassociated(
+                &fip.id.to_string(),
+                &fip.address.to_string(),
+                &previous.to_string(),
+            );
+        }
+        self.audit.floating_ip_associated(
+            &fip.id.to_string(),
+            &fip.address.to_string(),
+            &nic.id.to_string(),
+            &nic.ipv4_address.map(|a| a.to_string()).unwrap_or_default(),
+        );
+
+        Ok(Response::new(floating_ip_data_to_proto(
+            &associated,
+            nic.ipv4_address,
+        )))
+    }
+
+    async fn disassociate_floating_ip(
+        &self,
+        request: Request<DisassociateFloatingIpRequest>,
+    ) -> Result<Response<FloatingIp>, Status> {
+        let req = request.into_inner();
+
+        info!(id = %req.id, "DisassociateFloatingIp");
+
+        let fip = self.resolve_floating_ip(&req.id)?;
+        let Some(previous) = fip.nic_id else {
+            return Ok(Response::new(floating_ip_data_to_proto(&fip, None)));
+        };
+
+        let disassociated = self
+            .storage
+            .set_floating_ip_nic(&fip.id, None)
+            .map_err(storage_err_to_status)?;
+        self.manager
+            .sync_nat()
+            .await
+            .map_err(manager_err_to_status)?;
+        self.sync_neighbor_proxy();
+
+        info!(id = %fip.id, address = %fip.address, nic_id = %previous, "Floating IP disassociated");
+        self.audit.floating_ip_disassociated(
+            &fip.id.to_string(),
+            &fip.address.to_string(),
+            &previous.to_string(),
+        );
+
+        Ok(Response::new(floating_ip_data_to_proto(
+            &disassociated,
+            None,
+        )))
+    }
+
+    async fn delete_floating_ip(
+        &self,
+        request: Request<DeleteFloatingIpRequest>,
+    ) -> Result<Response<DeleteFloatingIpResponse>, Status> {
+        let req = request.into_inner();
+
+        let fip = self.resolve_floating_ip(&req.id)?;
+        let deleted = self
+            .storage
+            .delete_floating_ip(&fip.id)
+            .map_err(storage_err_to_status)?;
+
+        self.manager
+            .sync_nat()
+            .await
+            .map_err(manager_err_to_status)?;
+        self.sync_neighbor_proxy();
+
+        info!(id = %fip.id, address = %fip.address, nic_id = ?fip.nic_id, "Floating IP deleted");
+        if let Some(nic_id) = fip.nic_id {
+            self.audit.floating_ip_disassociated(
+                &fip.id.to_string(),
+                &fip.address.to_string(),
+                &nic_id.to_string(),
+            );
+        }
+        self.audit
+            .floating_ip_deleted(&fip.id.to_string(), &fip.address.to_string());
+
+        Ok(Response::new(DeleteFloatingIpResponse { deleted }))
+    }
+
+    // ========== Security Group Operations ==========
+
+    async fn create_security_group(
+        &self,
+        request: Request<CreateSecurityGroupRequest>,
+    ) -> Result<Response<SecurityGroup>, Status> {
+        let req = request.into_inner();
+
+        info!(name = %req.name, "CreateSecurityGroup");
+        validate_create_security_group(&req.name).map_err(validation_err_to_status)?;
+
+        let now = Utc::now();
+        let sg = SecurityGroupData {
+            id: Uuid::new_v4(),
+            name: req.name,
+            description: if req.description.is_empty() {
+                None
+            } else {
+                Some(req.description)
+            },
+            audit: req.audit,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.storage
+            .create_security_group(&sg)
+            .map_err(storage_err_to_status)?;
+
+        info!(id = %sg.id, name = %sg.name, "Security group created");
+        self.audit
+            .security_group_created(&sg.id.to_string(), &sg.name);
+
+        Ok(Response::new(security_group_data_to_proto(&sg, vec![], 0)))
+    }
+
+    async fn get_security_group(
+        &self,
+        request: Request<GetSecurityGroupRequest>,
+    ) -> Result<Response<SecurityGroup>, Status> {
+        let req = request.into_inner();
+
+        let (id, name) = match req.identifier {
+            Some(get_security_group_request::Identifier::Id(id)) => (id, String::new()),
+            Some(get_security_group_request::Identifier::Name(name)) => (String::new(), name),
+            None => {
+                return Err(Status::invalid_argument(
+                    "Security group ID or name required",
+                ));
+            }
+        };
+
+        let sg = self.resolve_security_group(&id, &name).await?;
+        Ok(Response::new(self.security_group_to_proto(&sg)?))
+    }
+
+    async fn list_security_groups(
+        &self,
+        _request: Request<ListSecurityGroupsRequest>,
+    ) -> Result<Response<ListSecurityGroupsResponse>, Status> {
+        let groups = self
+            .storage
+            .list_security_groups()
+            .map_err(storage_err_to_status)?;
+
+        let security_groups = groups
+            .iter()
+            .map(|sg| self.security_group_to_proto(sg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Response::new(ListSecurityGroupsResponse {
+            security_groups,
+        }))
+    }
 
     async fn delete_security_group(
         &self,
-        _request: Request<DeleteSecurityGroupRequest>,
+        request: Request<DeleteSecurityGroupRequest>,
     ) -> Result<Response<DeleteSecurityGroupResponse>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
+        let req = request.into_inner();
+
+        let uuid = Uuid::parse_str(&req.id).map_err(|_| {
+            Status::invalid_argument(format!("Invalid security group ID: {}", req.id))
+        })?;
+
+        let nic_ids = self
+            .storage
+            .list_nic_ids_in_security_group(&uuid)
+            .map_err(storage_err_to_status)?;
+        if !nic_ids.is_empty() && !req.force {
+            return Err(Status::failed_precondition(format!(
+                "Security group has {} attached NICs, use force=true to delete",
+                nic_ids.len()
+            )));
+        }
+
+        let sg = self
+            .storage
+            .get_security_group_by_id(&uuid)
+            .map_err(storage_err_to_status)?;
+
+        // Rules and attachments go with the group
+        let deleted = self
+            .storage
+            .delete_security_group(&uuid)
+            .map_err(storage_err_to_status)?;
+        self.apply_security(&nic_ids).await?;
+
+        if let Some(s) = sg {
+            info!(id = %s.id, nics_detached = nic_ids.len(), "Security group deleted");
+            self.audit
+                .security_group_deleted(&s.id.to_string(), &s.name);
+        }
+
+        Ok(Response::new(DeleteSecurityGroupResponse {
+            deleted,
+            nics_detached: nic_ids.len() as u32,
+        }))
     }
 
     async fn add_security_group_rule(
         &self,
-        _request: Request<AddSecurityGroupRuleRequest>,
+        request: Request<AddSecurityGroupRuleRequest>,
     ) -> Result<Response<SecurityGroupRule>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
+        let req = request.into_inner();
+
+        info!(security_group_id = %req.security_group_id, "AddSecurityGroupRule");
+
+        let sg = self
+            .resolve_security_group(&req.security_group_id, "")
+            .await?;
+
+        let (direction, protocol, port_start, port_end) = validate_security_group_rule(
+            req.direction,
+            req.protocol,
+            req.port_start,
+            req.port_end,
+            &req.cidr,
+        )
+        .map_err(validation_err_to_status)?;
+        let (not_before, not_after, schedule) =
+            validate_rule_window(&req.not_before, &req.not_after, &req.schedule)
+                .map_err(validation_err_to_status)?;
+
+        let now = Utc::now();
+        let rule = SecurityGroupRuleData {
+            id: Uuid::new_v4(),
+            security_group_id: sg.id,
+            direction,
+            protocol,
+            port_start,
+            port_end,
+            cidr: if req.cidr.is_empty() {
+                None
+            } else {
+                Some(req.cidr)
+            },
+            description: if req.description.is_empty() {
+                None
+            } else {
+                Some(req.description)
+            },
+            not_before,
+            not_after,
+            schedule,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.storage
+            .create_security_group_rule(&rule)
+            .map_err(storage_err_to_status)?;
+        self.apply_security(&self.security_group_nic_ids(&sg.id)?)
+            .await?;
+
+        info!(
+            id = %rule.id,
+            security_group_id = %sg.id,
+            direction = %direction.as_str(),
+            protocol = %protocol.as_str(),
+            "Security group rule added"
+        );
+        self.audit
+            .security_group_rule_added(&rule.id.to_string(), &sg.id.to_string());
+
+        Ok(Response::new(security_group_rule_data_to_proto(&rule)))
     }
 
     async fn remove_security_group_rule(
         &self,
-        _request: Request<RemoveSecurityGroupRuleRequest>,
+        request: Request<RemoveSecurityGroupRuleRequest>,
     ) -> Result<Response<RemoveSecurityGroupRuleResponse>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
+        let req = request.into_inner();
+
+        let uuid = Uuid::parse_str(&req.rule_id)
+            .map_err(|_| Status::invalid_argument(format!("Invalid rule ID: {}", req.rule_id)))?;
+
+        let rule = self
+            .storage
+            .get_security_group_rule_by_id(&uuid)
+            .map_err(storage_err_to_status)?;
+
+        let deleted = self
+            .storage
+            .delete_security_group_rule(&uuid)
+            .map_err(storage_err_to_status)?;
+
+        if let Some(r) = rule {
+            self.apply_security(&self.security_group_nic_ids(&r.security_group_id)?)
+                .await?;
+            self.audit
+                .security_group_rule_removed(&r.id.to_string(), &r.security_group_id.to_string());
+        }
+
+        Ok(Response::new(RemoveSecurityGroupRuleResponse { deleted }))
     }
 
     async fn attach_security_group(
         &self,
-        _request: Request<AttachSecurityGroupRequest>,
+        request: Request<AttachSecurityGroupRequest>,
     ) -> Result<Response<AttachSecurityGroupResponse>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
+        let req = request.into_inner();
+
+        info!(
+            nic_id = %req.nic_id,
+            security_group_id = %req.security_group_id,
+            "AttachSecurityGroup"
+        );
+
+        let nic = self.resolve_nic(&req.nic_id).await?;
+        let sg = self
+            .resolve_security_group(&req.security_group_id, "")
+            .await?;
+
+        let attached = self
+            .storage
+            .attach_security_group(&nic.id, &sg.id)
+            .map_err(storage_err_to_status)?;
+
+        if attached {
+            self.apply_security(&[nic.id]).await?;
+            info!(
+                nic_id = %nic.id,
+                security_group_id = %sg.id,
+                "Security group attached to NIC"
+            );
+            self.audit
+                .security_group_attached(&sg.id.to_string(), &nic.id.to_string());
+        }
+
+        Ok(Response::new(AttachSecurityGroupResponse { attached }))
     }
 
     async fn detach_security_group(
         &self,
-        _request: Request<DetachSecurityGroupRequest>,
+        request: Request<DetachSecurityGroupRequest>,
     ) -> Result<Response<DetachSecurityGroupResponse>, Status> {
-        Err(Status::unimplemented(
-            "Security groups are only supported in mvirt-ebpf",
-        ))
+        let req = request.into_inner();
+
+        let nic = self.resolve_nic(&req.nic_id).await?;
+        let sg = self
+            .resolve_security_group(&req.security_group_id, "")
+            .await?;
+
+        let detached = self
+            .storage
+            .detach_security_group(&nic.id, &sg.id)
+            .map_err(storage_err_to_status)?;
+
+        if detached {
+            self.apply_security(&[nic.id]).await?;
+            info!(
+                nic_id = %nic.id,
+                security_group_id = %sg.id,
+                "Security group detached from NIC"
+            );
+            self.audit
+                .security_group_detached(&sg.id.to_string(), &nic.id.to_string());
+        }
+
+        Ok(Response::new(DetachSecurityGroupResponse { detached }))
+    }
+
+    async fn set_security_group_audit(
+        &self,
+        request: Request<SetSecurityGroupAuditRequest>,
+    ) -> Result<Response<SecurityGroup>, Status> {
+        let req = request.into_inner();
+
+        info!(
+            security_group_id = %req.security_group_id,
+            audit = req.audit,
+            "SetSecurityGroupAudit"
+        );
+
+        let sg = self
+            .resolve_security_group(&req.security_group_id, "")
+            .await?;
+
+        self.storage
+            .set_security_group_audit(&sg.id, req.audit)
+            .map_err(storage_err_to_status)?;
+        self.apply_security(&self.security_group_nic_ids(&sg.id)?)
+            .await?;
+
+        self.audit
+            .security_group_audit_set(&sg.id.to_string(), &sg.name, req.audit);
+
+        let sg = self
+            .storage
+            .get_security_group_by_id(&sg.id)
+            .map_err(storage_err_to_status)?
+            .ok_or_else(|| Status::not_found(format!("Security group not found: {}", sg.id)))?;
+        Ok(Response::new(self.security_group_to_proto(&sg)?))
+    }
+
+    // Host migration
+
+    async fn export_state(
+        &self,
+        _request: Request<ExportStateRequest>,
+    ) -> Result<Response<StateSnapshot>, Status> {
+        let state = self.storage.export_state().map_err(storage_err_to_status)?;
+        let data = serde_json::to_vec(&state).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(StateSnapshot {
+            version: STATE_VERSION,
+            data,
+        }))
+    }
+
+    async fn import_state(
+        &self,
+        request: Request<ImportStateRequest>,
+    ) -> Result<Response<ImportStateResponse>, Status> {
+        let req = request.into_inner();
+        let snapshot = req
+            .snapshot
+            .ok_or_else(|| Status::invalid_argument("snapshot is required"))?;
+        if snapshot.version != STATE_VERSION {
+            return Err(Status::invalid_argument(format!(
+                "Unsupported state snapshot version {} (expected {})",
+                snapshot.version, STATE_VERSION
+            )));
+        }
+        let state: serde_json::Value = serde_json::from_slice(&snapshot.data)
+            .map_err(|e| Status::invalid_argument(format!("Invalid state snapshot: {}", e)))?;
+
+        if !self.storage.is_empty().map_err(storage_err_to_status)? {
+            return Err(Status::failed_precondition(
+                "Networks already exist on this host",
+            ));
+        }
+
+        // Everything a NIC needs on the host is created here, so a dry run
+        // has nothing to check.
+        if req.dry_run {
+            return Ok(Response::new(ImportStateResponse {
+                records: snapshot_len(&state) as u32,
+                missing: vec![],
+            }));
+        }
+
+        let records = self
+            .storage
+            .import_state(&state)
+            .map_err(storage_err_to_status)?;
+        let networks = self
+            .storage
+            .list_networks()
+            .map_err(storage_err_to_status)?;
+        let nics = self.storage.list_nics().map_err(storage_err_to_status)?;
+
+        for network in networks.iter().filter(|n| n.is_public) {
+            if let Err(e) = self.manager.add_public_network_routes(network).await {
+                warn!(network_id = %network.id, error = %e, "Failed to add public network routes");
+            }
+        }
+        self.manager
+            .recover_nics()
+            .await
+            .map_err(manager_err_to_status)?;
+
+        let mut missing = Vec::new();
+        for nic in &nics {
+            if std::path::Path::new(&nic.socket_path).exists() {
+                self.publish_allocation(AllocationEventType::Added, nic);
+            } else {
+                missing.push(format!("NIC {}: socket {}", nic.id, nic.socket_path));
+            }
+        }
+
+        info!(records, missing = missing.len(), "Imported network state");
+        self.audit.state_imported(networks.len(), nics.len());
+        Ok(Response::new(ImportStateResponse {
+            records: records as u32,
+            missing,
+        }))
+    }
+
+    // ========== Latency Histograms ==========
+
+    async fn get_latency_stats(
+        &self,
+        request: Request<GetLatencyStatsRequest>,
+    ) -> Result<Response<LatencyStats>, Status> {
+        let req = request.into_inner();
+        let recorders = self.latency_recorders(&req.nic_id).await?;
+
+        let reactors = recorders
+            .into_iter()
+            .map(|(reactor, recorder)| {
+                let stages = recorder
+                    .summary()
+                    .into_iter()
+                    .map(|s| StageLatency {
+                        stage: s.stage.to_string(),
+                        count: s.count,
+                        min_ns: s.min_ns,
+                        p50_ns: s.p50_ns,
+                        p90_ns: s.p90_ns,
+                        p99_ns: s.p99_ns,
+                        p999_ns: s.p999_ns,
+                        max_ns: s.max_ns,
+                        mean_ns: s.mean_ns,
+                    })
+                    .collect();
+                if req.reset {
+                    recorder.reset();
+                }
+                ReactorLatency { reactor, stages }
+            })
+            .collect();
+        Ok(Response::new(LatencyStats { reactors }))
+    }
+
+    async fn reset_latency_stats(
+        &self,
+        request: Request<ResetLatencyStatsRequest>,
+    ) -> Result<Response<ResetLatencyStatsResponse>, Status> {
+        let req = request.into_inner();
+        let recorders = self.latency_recorders(&req.nic_id).await?;
+        for (_, recorder) in &recorders {
+            recorder.reset();
+        }
+        info!(reactors = recorders.len(), "Reset latency histograms");
+        Ok(Response::new(ResetLatencyStatsResponse {
+            reactors: recorders.len() as u32,
+        }))
+    }
+
+    // ========== RX Buffer Pools ==========
+
+    async fn get_nic_stats(
+        &self,
+        request: Request<GetNicStatsRequest>,
+    ) -> Result<Response<NicStats>, Status> {
+        let req = request.into_inner();
+        let interval_ms = match req.interval_ms {
+            0 => DEFAULT_STATS_INTERVAL_MS,
+            ms => ms.min(MAX_STATS_INTERVAL_MS),
+        };
+
+        // The TUN reactor carries no guest traffic
+        let counters: Vec<_> = self
+            .reactors(&req.nic_id, |h| Arc::clone(h.traffic()))
+            .await?
+            .into_iter()
+            .filter(|(reactor, _)| reactor != "tun")
+            .collect();
+        let before: Vec<_> = counters.iter().map(|(_, c)| c.snapshot()).collect();
+        let started = std::time::Instant::now();
+        tokio::time::sleep(std::time::Duration::from_millis(interval_ms.into())).await;
+        let elapsed = started.elapsed();
+
+        let nics = counters
+            .into_iter()
+            .zip(before)
+            .map(|((nic_id, counters), before)| {
+                let now = counters.snapshot();
+                let rates = now.rates_since(&before, elapsed);
+                NicTraffic {
+                    nic_id,
+                    rx_packets: now.rx_packets,
+                    rx_bytes: now.rx_bytes,
+                    rx_dropped: now.rx_dropped,
+                    tx_packets: now.tx_packets,
+                    tx_bytes: now.tx_bytes,
+                    tx_dropped: now.tx_dropped,
+                    rx_packets_per_sec: rates.rx_packets,
+                    rx_bytes_per_sec: rates.rx_bytes,
+                    rx_dropped_per_sec: rates.rx_dropped,
+                    tx_packets_per_sec: rates.tx_packets,
+                    tx_bytes_per_sec: rates.tx_bytes,
+                    tx_dropped_per_sec: rates.tx_dropped,
+                }
+            })
+            .collect();
+        Ok(Response::new(NicStats { nics, interval_ms }))
+    }
+
+    async fn get_rx_pool_stats(
+        &self,
+        request: Request<GetRxPoolStatsRequest>,
+    ) -> Result<Response<RxPoolStats>, Status> {
+        let req = request.into_inner();
+        let pools = self
+            .reactors(&req.nic_id, |h| h.rx_pool().snapshot())
+            .await?
+            .into_iter()
+            .map(|(reactor, pool)| ReactorRxPool {
+                reactor,
+                capacity: pool.capacity,
+                max: pool.max,
+                held: pool.held,
+                high_watermark: pool.high_watermark,
+                grow_events: pool.grow_events,
+                exhausted: pool.exhausted,
+            })
+            .collect();
+        Ok(Response::new(RxPoolStats { reactors: pools }))
+    }
+
+    // ========== Huge Pages ==========
+
+    async fn get_huge_page_usage(
+        &self,
+        _request: Request<GetHugePageUsageRequest>,
+    ) -> Result<Response<HugePageUsage>, Status> {
+        let nodes = self
+            .manager
+            .hugepages()
+            .usage()
+            .into_iter()
+            .map(|usage| NumaNodeMemory {
+                node: usage.node.map(|n| n as u32),
+                huge_bytes: usage.huge_bytes,
+                free_bytes: usage.free_bytes,
+                fallback_bytes: usage.fallback_bytes,
+                fallbacks: usage.fallbacks,
+            })
+            .collect();
+        Ok(Response::new(HugePageUsage { nodes }))
+    }
+
+    // ========== Packet Capture ==========
+
+    async fn start_capture(
+        &self,
+        request: Request<StartCaptureRequest>,
+    ) -> Result<Response<CaptureInfo>, Status> {
+        let req = request.into_inner();
+
+        let nic_ids = match (req.nic_id.is_empty(), req.network_id.is_empty()) {
+            (false, true) => vec![self.resolve_nic(&req.nic_id).await?.id],
+            (true, false) => {
+                let network = self.resolve_network_ref(&req.network_id).await?;
+                self.storage
+                    .list_nics_in_network(&network.id)
+                    .map_err(storage_err_to_status)?
+                    .into_iter()
+                    .map(|nic| nic.id)
+                    .collect()
+            }
+            _ => {
+                return Err(Status::invalid_argument(
+                    "Exactly one of nic_id and network_id required",
+                ));
+            }
+        };
+        let direction = match CaptureDirection::try_from(req.direction) {
+            Ok(CaptureDirection::Both) => None,
+            Ok(CaptureDirection::FromGuest) => Some(capture::Direction::FromGuest),
+            Ok(CaptureDirection::ToGuest) => Some(capture::Direction::ToGuest),
+            Err(_) => return Err(Status::invalid_argument("Invalid capture direction")),
+        };
+
+        let mut taps = Vec::new();
+        let mut tapped = Vec::new();
+        for nic_id in &nic_ids {
+            for (id, tap) in self
+                .manager
+                .reactors(Some(nic_id), |h| h.capture().clone())
+                .await
+            {
+                tapped.push(id);
+                taps.push(tap);
+            }
+        }
+        if taps.is_empty() {
+            return Err(Status::failed_precondition(
+                "No NIC to capture has a reactor on this host",
+            ));
+        }
+
+        let defaults = CaptureOptions::default();
+        let options = CaptureOptions {
+            direction,
+            snaplen: if req.snaplen == 0 {
+                defaults.snaplen
+            } else {
+                req.snaplen
+            },
+            sample_every: u64::from(req.sample_every.max(1)),
+            buffer_frames: if req.buffer_frames == 0 {
+                defaults.buffer_frames
+            } else {
+                (req.buffer_frames as usize).min(MAX_CAPTURE_BUFFER_FRAMES)
+            },
+            max_frames: req.max_frames,
+        };
+        let id = self.captures.start(taps, options);
+        info!(capture_id = %id, nics = tapped.len(), "Packet capture started");
+        self.audit.capture_started(&id, &tapped);
+
+        if req.duration_seconds > 0 {
+            let captures = Arc::clone(&self.captures);
+            let audit = Arc::clone(&self.audit);
+            let duration = std::time::Duration::from_secs(req.duration_seconds.into());
+            let id = id.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                if let Some(stats) = captures.stop(&id) {
+                    info!(capture_id = %id, "Packet capture ended after its duration");
+                    audit.capture_stopped(&id, stats.captured, stats.dropped);
+                }
+            });
+        }
+
+        Ok(Response::new(CaptureInfo {
+            id,
+            nic_ids: tapped,
+        }))
+    }
+
+    type StreamCaptureStream = tokio_stream::wrappers::ReceiverStream<Result<CaptureChunk, Status>>;
+
+    async fn stream_capture(
+        &self,
+        request: Request<StreamCaptureRequest>,
+    ) -> Result<Response<Self::StreamCaptureStream>, Status> {
+        let id = request.into_inner().capture_id;
+        let (mut frames, options) = self.captures.take_stream(&id).ok_or_else(|| {
+            Status::not_found(format!("Capture not found or already streaming: {}", id))
+        })?;
+        let limit = match options.max_frames {
+            0 => u64::MAX,
+            max => max,
+        };
+
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        let captures = Arc::clone(&self.captures);
+        let audit = Arc::clone(&self.audit);
+        tokio::spawn(async move {
+            let mut sent = 0u64;
+            let mut chunk = pcap_header(options.snaplen);
+            while sent < limit {
+                // Wait for a frame unless the header is still pending
+                if chunk.is_empty() {
+                    let frame = tokio::select! {
+                        frame = frames.recv() => frame,
+                        _ = tx.closed() => None,
+                    };
+                    let Some(frame) = frame else {
+                        break;
+                    };
+                    pcap_record(&mut chunk, &frame);
+                    sent += 1;
+                }
+                while sent < limit && chunk.len() < CAPTURE_CHUNK_BYTES {
+                    let Ok(frame) = frames.try_recv() else {
+                        break;
+                    };
+                    pcap_record(&mut chunk, &frame);
+                    sent += 1;
+                }
+                let data = std::mem::take(&mut chunk);
+                if tx.send(Ok(CaptureChunk { data })).await.is_err() {
+                    break;
+                }
+            }
+            // Reaching the frame limit or losing the client ends the capture
+            if let Some(stats) = captures.stop(&id) {
+                info!(capture_id = %id, "Packet capture ended with its stream");
+                audit.capture_stopped(&id, stats.captured, stats.dropped);
+            }
+        });
+
+        Ok(Response::new(tokio_stream::wrappers::ReceiverStream::new(
+            rx,
+        )))
+    }
+
+    async fn stop_capture(
+        &self,
+        request: Request<StopCaptureRequest>,
+    ) -> Result<Response<CaptureStats>, Status> {
+        let id = request.into_inner().capture_id;
+        let stats = self
+            .captures
+            .stop(&id)
+            .ok_or_else(|| Status::not_found(format!("Capture not found: {}", id)))?;
+        info!(capture_id = %id, "Packet capture stopped");
+        self.audit
+            .capture_stopped(&id, stats.captured, stats.dropped);
+        Ok(Response::new(CaptureStats {
+            seen: stats.seen,
+            captured: stats.captured,
+            dropped: stats.dropped,
+        }))
     }
 
     // Watch streams: not implemented on this legacy daemon. mvirt-ebpf
@@ -740,4 +2938,52 @@ impl NetService for NetServiceImpl {
             "WatchNetworks not implemented in legacy mvirt-net (use mvirt-ebpf)",
         ))
     }
+
+    // ========== Allocation Export ==========
+
+    type WatchAllocationsStream =
+        tokio_stream::wrappers::ReceiverStream<Result<AllocationEvent, Status>>;
+
+    async fn watch_allocations(
+        &self,
+        request: Request<WatchAllocationsRequest>,
+    ) -> Result<Response<Self::WatchAllocationsStream>, Status> {
+        let req = request.into_inner();
+
+        let network_filter = if req.network_id.is_empty() {
+            None
+        } else {
+            Some(self.resolve_network_ref(&req.network_id).await?.id)
+        };
+
+        // Subscribe before taking the snapshot so no allocation falls in between
+        let rx = self.allocation_events.subscribe();
+
+        let existing = if req.include_existing {
+            match &network_filter {
+                Some(network_id) => self.storage.list_nics_in_network(network_id),
+                None => self.storage.list_nics(),
+            }
+            .map_err(storage_err_to_status)?
+        } else {
+            Vec::new()
+        };
+
+        let now = Utc::now().timestamp();
+        let existing = existing
+            .iter()
+            .map(|nic| AllocationEvent {
+                r#type: AllocationEventType::Added as i32,
+                timestamp: now,
+                allocation: Some(nic_data_to_allocation(nic)),
+            })
+            .collect();
+        Ok(Response::new(watch::watch_stream(
+            "Allocation",
+            existing,
+            rx,
+            network_filter.map(|id| id.to_string()),
+            |ev: &AllocationEvent| ev.allocation.as_ref().map(|a| a.network_id.as_str()),
+        )))
+    }
 }
@@ -1,10 +1,12 @@
 //! SQLite storage layer for networks and NICs.
 
+use crate::socket_access::SocketAccess;
 use chrono::{DateTime, Utc};
-use ipnet::{Ipv4Net, Ipv6Net};
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use mvirt_common::rule_window::RuleSchedule;
 use refinery::embed_migrations;
 use rusqlite::{Connection, OptionalExtension, Row, params};
-use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::Path;
 use std::sync::Mutex;
 use thiserror::Error;
@@ -35,6 +37,45 @@ pub enum StorageError {
 
     #[error("IP address already in use: {0}")]
     IpAddressInUse(String),
+
+    #[error("Route not found: {0}")]
+    RouteNotFound(String),
+
+    #[error("Route already exists: {0}")]
+    RouteExists(String),
+
+    #[error("Lease not found: {0}")]
+    LeaseNotFound(String),
+
+    #[error("Lease already exists: {0}")]
+    LeaseExists(String),
+
+    #[error("Peering not found: {0}")]
+    PeeringNotFound(String),
+
+    #[error("Peering already exists: {0}")]
+    PeeringExists(String),
+
+    #[error("Port forward not found: {0}")]
+    PortForwardNotFound(String),
+
+    #[error("Port already forwarded: {0}")]
+    PortForwardExists(String),
+
+    #[error("Floating IP not found: {0}")]
+    FloatingIpNotFound(String),
+
+    #[error("Floating IP already exists: {0}")]
+    FloatingIpExists(String),
+
+    #[error("NIC already has a floating IP: {0}")]
+    NicHasFloatingIp(String),
+
+    #[error("Security group not found: {0}")]
+    SecurityGroupNotFound(String),
+
+    #[error("Security group name already exists: {0}")]
+    SecurityGroupNameExists(String),
 }
 
 pub type Result<T> = std::result::Result<T, StorageError>;
@@ -81,6 +122,16 @@ pub struct NetworkData {
     pub is_public: bool,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Answer ARP/ND from upstream for the routed prefixes of the
+    /// network's NICs
+    pub proxy_neighbors: bool,
+    /// Upstream DNS servers the gateway forwards names outside the
+    /// network's zone to; empty uses the daemon's
+    pub dns_forwarders: Vec<SocketAddr>,
+    /// TFTP server handed to PXE clients (BOOTP siaddr)
+    pub boot_server: Option<Ipv4Addr>,
+    /// Boot file or UEFI HTTP boot URL; `None` disables network boot
+    pub boot_file: Option<String>,
 }
 
 impl NetworkData {
@@ -116,23 +167,278 @@ pub struct NicData {
     pub state: NicState,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Pod or VM whose deletion cascades to this NIC.
+    pub owner: Option<OwnerRef>,
+    /// Administrative link state the guest sees.
+    pub link_up: bool,
+    /// Prefix handed to the guest over DHCPv6 prefix delegation and routed
+    /// to the NIC.
+    pub delegated_ipv6_prefix: Option<Ipv6Net>,
+    /// Owner, group and mode of the vhost-user socket, over the daemon's
+    /// defaults.
+    pub socket_access: SocketAccess,
+}
+
+/// Reference to the object owning a NIC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerRef {
+    pub kind: String,
+    pub id: String,
 }
 
 impl NicData {
     /// Format MAC address as string.
     pub fn mac_string(&self) -> String {
+        format_mac_address(&self.mac_address)
+    }
+}
+
+/// Static route data stored in the database.
+#[derive(Debug, Clone)]
+pub struct RouteData {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub destination: IpNet,
+    /// NIC address to forward to; `None` routes via the uplink (TUN).
+    pub next_hop: Option<IpAddr>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Static DHCP lease stored in the database: the DHCP server of `nic_id`
+/// answers `mac_address` with `ipv4_address`.
+#[derive(Debug, Clone)]
+pub struct LeaseData {
+    pub id: Uuid,
+    pub network_id: Uuid,
+    pub nic_id: Uuid,
+    pub mac_address: [u8; 6],
+    pub ipv4_address: Ipv4Addr,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LeaseData {
+    /// Format MAC address as string.
+    pub fn mac_string(&self) -> String {
+        format_mac_address(&self.mac_address)
+    }
+}
+
+/// Network peering stored in the database. `network_a_id` is the lower
+/// of the two IDs, so a pair of networks has one peering at most.
+#[derive(Debug, Clone)]
+pub struct PeeringData {
+    pub id: Uuid,
+    pub network_a_id: Uuid,
+    pub network_b_id: Uuid,
+    pub created_at: DateTime<Utc>,
+    /// Routes between the two networks are installed (ConnectNetworks), not
+    /// just allowed by strict isolation
+    pub routed: bool,
+    /// Addresses of either network reachable over the peering; empty for
+    /// all
+    pub allowed_prefixes: Vec<IpNet>,
+}
+
+impl PeeringData {
+    /// A new peering between two networks, in either order.
+    pub fn new(a: Uuid, b: Uuid) -> Self {
+        let (network_a_id, network_b_id) = if a <= b { (a, b) } else { (b, a) };
+        PeeringData {
+            id: Uuid::new_v4(),
+            network_a_id,
+            network_b_id,
+            created_at: Utc::now(),
+            routed: false,
+            allowed_prefixes: Vec::new(),
+        }
+    }
+
+    /// The other network of the peering, `None` if `network_id` isn't one
+    /// of its two.
+    pub fn peer_of(&self, network_id: &Uuid) -> Option<Uuid> {
+        if *network_id == self.network_a_id {
+            Some(self.network_b_id)
+        } else if *network_id == self.network_b_id {
+            Some(self.network_a_id)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `prefix` may be reached over the peering: it lies within
+    /// one of the allowed prefixes, or there is no allow-list.
+    pub fn allows(&self, prefix: &IpNet) -> bool {
+        self.allowed_prefixes.is_empty() || self.allowed_prefixes.iter().any(|p| p.contains(prefix))
+    }
+}
+
+/// Port forward stored in the database: `protocol` traffic for
+/// `host_ip:host_port` goes to `vm_port` of the NIC's IPv4 address.
+#[derive(Debug, Clone)]
+pub struct PortForwardData {
+    pub id: Uuid,
+    pub nic_id: Uuid,
+    /// TCP or UDP
+    pub protocol: RuleProtocol,
+    pub host_ip: Ipv4Addr,
+    pub host_port: u16,
+    pub vm_port: u16,
+    pub created_at: DateTime<Utc>,
+}
+
+impl PortForwardData {
+    /// The forwarded port as `tcp/203.0.113.10:8080`.
+    pub fn host_endpoint(&self) -> String {
         format!(
-            "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
-            self.mac_address[0],
-            self.mac_address[1],
-            self.mac_address[2],
-            self.mac_address[3],
-            self.mac_address[4],
-            self.mac_address[5]
+            "{}/{}:{}",
+            self.protocol.as_str(),
+            self.host_ip,
+            self.host_port
         )
     }
 }
 
+/// Floating IP stored in the database: `address` is translated 1:1 to the
+/// IPv4 address of the NIC, if associated.
+#[derive(Debug, Clone)]
+pub struct FloatingIpData {
+    pub id: Uuid,
+    pub address: Ipv4Addr,
+    pub nic_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Rule direction enum matching proto definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum RuleDirection {
+    Unspecified = 0,
+    Ingress = 1,
+    Egress = 2,
+}
+
+impl From<i32> for RuleDirection {
+    fn from(v: i32) -> Self {
+        match v {
+            1 => RuleDirection::Ingress,
+            2 => RuleDirection::Egress,
+            _ => RuleDirection::Unspecified,
+        }
+    }
+}
+
+impl RuleDirection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleDirection::Ingress => "ingress",
+            RuleDirection::Egress => "egress",
+            RuleDirection::Unspecified => "unspecified",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "ingress" => RuleDirection::Ingress,
+            "egress" => RuleDirection::Egress,
+            _ => RuleDirection::Unspecified,
+        }
+    }
+}
+
+/// Rule protocol enum matching proto definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i32)]
+pub enum RuleProtocol {
+    Unspecified = 0,
+    All = 1,
+    Tcp = 2,
+    Udp = 3,
+    Icmp = 4,
+    Icmpv6 = 5,
+}
+
+impl From<i32> for RuleProtocol {
+    fn from(v: i32) -> Self {
+        match v {
+            1 => RuleProtocol::All,
+            2 => RuleProtocol::Tcp,
+            3 => RuleProtocol::Udp,
+            4 => RuleProtocol::Icmp,
+            5 => RuleProtocol::Icmpv6,
+            _ => RuleProtocol::Unspecified,
+        }
+    }
+}
+
+impl RuleProtocol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RuleProtocol::All => "all",
+            RuleProtocol::Tcp => "tcp",
+            RuleProtocol::Udp => "udp",
+            RuleProtocol::Icmp => "icmp",
+            RuleProtocol::Icmpv6 => "icmpv6",
+            RuleProtocol::Unspecified => "unspecified",
+        }
+    }
+
+    pub fn parse(s: &str) -> Self {
+        match s {
+            "all" => RuleProtocol::All,
+            "tcp" => RuleProtocol::Tcp,
+            "udp" => RuleProtocol::Udp,
+            "icmp" => RuleProtocol::Icmp,
+            "icmpv6" => RuleProtocol::Icmpv6,
+            _ => RuleProtocol::Unspecified,
+        }
+    }
+
+    /// IP protocol number; 0 matches any protocol.
+    pub fn to_ip_protocol(&self) -> u8 {
+        match self {
+            RuleProtocol::Tcp => 6,
+            RuleProtocol::Udp => 17,
+            RuleProtocol::Icmp => 1,
+            RuleProtocol::Icmpv6 => 58,
+            RuleProtocol::All | RuleProtocol::Unspecified => 0,
+        }
+    }
+}
+
+/// Security group data stored in the database.
+#[derive(Debug, Clone)]
+pub struct SecurityGroupData {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    /// Report packets the rules would drop instead of dropping them
+    pub audit: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Security group rule data stored in the database.
+#[derive(Debug, Clone)]
+pub struct SecurityGroupRuleData {
+    pub id: Uuid,
+    pub security_group_id: Uuid,
+    pub direction: RuleDirection,
+    pub protocol: RuleProtocol,
+    pub port_start: Option<u16>,
+    pub port_end: Option<u16>,
+    pub cidr: Option<String>,
+    pub description: Option<String>,
+    /// Rule applies from this time on
+    pub not_before: Option<DateTime<Utc>>,
+    /// Rule expires at this time
+    pub not_after: Option<DateTime<Utc>>,
+    /// Weekly times the rule applies, within its validity window
+    pub schedule: Option<RuleSchedule>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
 /// SQLite storage for networks and NICs.
 pub struct Storage {
     conn: Mutex<Connection>,
@@ -173,10 +479,11 @@ impl Storage {
 
         let dns_json = serde_json::to_string(&network.dns_servers)?;
         let ntp_json = serde_json::to_string(&network.ntp_servers)?;
+        let forwarders_json = serde_json::to_string(&network.dns_forwarders)?;
 
         conn.execute(
-            "INSERT INTO networks (id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            "INSERT INTO networks (id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at, proxy_neighbors, dns_forwarders, boot_server, boot_file)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
             params![
                 network.id.to_string(),
                 network.name,
@@ -189,6 +496,10 @@ impl Storage {
                 network.is_public,
                 network.created_at.to_rfc3339(),
                 network.updated_at.to_rfc3339(),
+                network.proxy_neighbors,
+                forwarders_json,
+                network.boot_server.map(|a| a.to_string()),
+                network.boot_file,
             ],
         ).map_err(|e| {
             if let rusqlite::Error::SqliteFailure(ref err, _) = e
@@ -205,7 +516,7 @@ impl Storage {
     pub fn get_network_by_id(&self, id: &Uuid) -> Result<Option<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at, proxy_neighbors, dns_forwarders, boot_server, boot_file
              FROM networks WHERE id = ?1",
             params![id.to_string()],
             |row| Ok(Self::row_to_network(row)),
@@ -218,7 +529,7 @@ impl Storage {
     pub fn get_network_by_name(&self, name: &str) -> Result<Option<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at, proxy_neighbors, dns_forwarders, boot_server, boot_file
              FROM networks WHERE name = ?1",
             params![name],
             |row| Ok(Self::row_to_network(row)),
@@ -231,7 +542,7 @@ impl Storage {
     pub fn list_networks(&self) -> Result<Vec<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at, proxy_neighbors, dns_forwarders, boot_server, boot_file
              FROM networks ORDER BY created_at",
         )?;
 
@@ -248,7 +559,7 @@ impl Storage {
     pub fn list_public_networks(&self) -> Result<Vec<NetworkData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at
+            "SELECT id, name, ipv4_enabled, ipv4_subnet, ipv6_enabled, ipv6_prefix, dns_servers, ntp_servers, is_public, created_at, updated_at, proxy_neighbors, dns_forwarders, boot_server, boot_file
              FROM networks WHERE is_public = 1 ORDER BY created_at",
         )?;
 
@@ -284,6 +595,44 @@ impl Storage {
         Ok(())
     }
 
+    /// Set whether the uplink answers ARP/ND for the network's routed
+    /// prefixes.
+    pub fn update_network_proxy_neighbors(&self, id: &Uuid, proxy_neighbors: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "UPDATE networks SET proxy_neighbors = ?1, updated_at = ?2 WHERE id = ?3",
+            params![proxy_neighbors, now, id.to_string()],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::NetworkNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Set the upstream DNS servers the network's gateway forwards to.
+    pub fn update_network_dns_forwarders(
+        &self,
+        id: &Uuid,
+        dns_forwarders: &[SocketAddr],
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+        let forwarders_json = serde_json::to_string(dns_forwarders)?;
+
+        let rows = conn.execute(
+            "UPDATE networks SET dns_forwarders = ?1, updated_at = ?2 WHERE id = ?3",
+            params![forwarders_json, now, id.to_string()],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::NetworkNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
     /// Delete a network by ID.
     pub fn delete_network(&self, id: &Uuid) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
@@ -317,6 +666,10 @@ impl Storage {
         let is_public: bool = row.get(8)?;
         let created_at_str: String = row.get(9)?;
         let updated_at_str: String = row.get(10)?;
+        let proxy_neighbors: bool = row.get(11)?;
+        let forwarders_json: String = row.get(12)?;
+        let boot_server_str: Option<String> = row.get(13)?;
+        let boot_file: Option<String> = row.get(14)?;
 
         Ok(NetworkData {
             id: Uuid::parse_str(&id_str).unwrap(),
@@ -334,6 +687,10 @@ impl Storage {
             updated_at: DateTime::parse_from_rfc3339(&updated_at_str)
                 .unwrap()
                 .with_timezone(&Utc),
+            proxy_neighbors,
+            dns_forwarders: serde_json::from_str(&forwarders_json)?,
+            boot_server: boot_server_str.map(|s| s.parse().unwrap()),
+            boot_file,
         })
     }
 
@@ -357,8 +714,8 @@ impl Storage {
         )?;
 
         conn.execute(
-            "INSERT INTO nics (id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            "INSERT INTO nics (id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at, owner_kind, owner_id, link_up, delegated_ipv6_prefix, socket_uid, socket_gid, socket_mode)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)",
             params![
                 nic.id.to_string(),
                 nic.name,
@@ -372,6 +729,13 @@ impl Storage {
                 i32::from(nic.state),
                 nic.created_at.to_rfc3339(),
                 nic.updated_at.to_rfc3339(),
+                nic.owner.as_ref().map(|o| &o.kind),
+                nic.owner.as_ref().map(|o| &o.id),
+                nic.link_up,
+                nic.delegated_ipv6_prefix.map(|p| p.to_string()),
+                nic.socket_access.uid,
+                nic.socket_access.gid,
+                nic.socket_access.mode,
             ],
         )?;
 
@@ -382,7 +746,7 @@ impl Storage {
     pub fn get_nic_by_id(&self, id: &Uuid) -> Result<Option<NicData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at, owner_kind, owner_id, link_up, delegated_ipv6_prefix, socket_uid, socket_gid, socket_mode
              FROM nics WHERE id = ?1",
             params![id.to_string()],
             |row| Ok(Self::row_to_nic(row)),
@@ -395,7 +759,7 @@ impl Storage {
     pub fn get_nic_by_name(&self, name: &str) -> Result<Option<NicData>> {
         let conn = self.conn.lock().unwrap();
         conn.query_row(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at, owner_kind, owner_id, link_up, delegated_ipv6_prefix, socket_uid, socket_gid, socket_mode
              FROM nics WHERE name = ?1",
             params![name],
             |row| Ok(Self::row_to_nic(row)),
@@ -408,7 +772,7 @@ impl Storage {
     pub fn list_nics(&self) -> Result<Vec<NicData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at, owner_kind, owner_id, link_up, delegated_ipv6_prefix, socket_uid, socket_gid, socket_mode
              FROM nics ORDER BY created_at",
         )?;
 
@@ -425,7 +789,7 @@ impl Storage {
     pub fn list_nics_in_network(&self, network_id: &Uuid) -> Result<Vec<NicData>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at
+            "SELECT id, name, network_id, mac_address, ipv4_address, ipv6_address, routed_ipv4_prefixes, routed_ipv6_prefixes, socket_path, state, created_at, updated_at, owner_kind, owner_id, link_up, delegated_ipv6_prefix, socket_uid, socket_gid, socket_mode
              FROM nics WHERE network_id = ?1 ORDER BY created_at",
         )?;
 
@@ -473,6 +837,32 @@ impl Storage {
         Ok(())
     }
 
+    /// Move a NIC to other addresses.
+    pub fn update_nic_addresses(
+        &self,
+        id: &Uuid,
+        ipv4: Option<Ipv4Addr>,
+        ipv6: Option<Ipv6Addr>,
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "UPDATE nics SET ipv4_address = ?1, ipv6_address = ?2, updated_at = ?3 WHERE id = ?4",
+            params![
+                ipv4.map(|a| a.to_string()),
+                ipv6.map(|a| a.to_string()),
+                now,
+                id.to_string()
+            ],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::NicNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
     /// Update NIC state.
     pub fn update_nic_state(&self, id: &Uuid, state: NicState) -> Result<()> {
         let conn = self.conn.lock().unwrap();
@@ -489,6 +879,22 @@ impl Storage {
         Ok(())
     }
 
+    /// Set a NIC's administrative link state.
+    pub fn update_nic_link_up(&self, id: &Uuid, link_up: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "UPDATE nics SET link_up = ?1, updated_at = ?2 WHERE id = ?3",
+            params![link_up, now, id.to_string()],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::NicNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
     /// Delete a NIC by ID.
     pub fn delete_nic(&self, id: &Uuid) -> Result<bool> {
         let conn = self.conn.lock().unwrap();
@@ -518,11 +924,12 @@ impl Storage {
         Ok(count > 0)
     }
 
-    /// Get all used IPv4 addresses in a network.
+    /// Get all used IPv4 addresses in a network, including leased ones.
     pub fn get_used_ipv4_addresses(&self, network_id: &Uuid) -> Result<Vec<Ipv4Addr>> {
         let conn = self.conn.lock().unwrap();
         let mut stmt = conn.prepare(
-            "SELECT ipv4_address FROM nics WHERE network_id = ?1 AND ipv4_address IS NOT NULL",
+            "SELECT ipv4_address FROM nics WHERE network_id = ?1 AND ipv4_address IS NOT NULL
+             UNION SELECT ipv4_address FROM leases WHERE network_id = ?1",
         )?;
 
         let addrs = stmt
@@ -552,6 +959,23 @@ impl Storage {
         Ok(addrs)
     }
 
+    /// Get the IPv6 prefixes delegated to the NICs of a network.
+    pub fn get_delegated_ipv6_prefixes(&self, network_id: &Uuid) -> Result<Vec<Ipv6Net>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT delegated_ipv6_prefix FROM nics WHERE network_id = ?1 AND delegated_ipv6_prefix IS NOT NULL",
+        )?;
+
+        let prefixes = stmt
+            .query_map(params![network_id.to_string()], |row| {
+                let prefix_str: String = row.get(0)?;
+                Ok(prefix_str.parse::<Ipv6Net>().unwrap())
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(prefixes)
+    }
+
     fn row_to_nic(row: &Row) -> Result<NicData> {
         let id_str: String = row.get(0)?;
         let name: Option<String> = row.get(1)?;
@@ -565,6 +989,15 @@ impl Storage {
         let state_int: i32 = row.get(9)?;
         let created_at_str: String = row.get(10)?;
         let updated_at_str: String = row.get(11)?;
+        let owner_kind: Option<String> = row.get(12)?;
+        let owner_id: Option<String> = row.get(13)?;
+        let link_up: bool = row.get(14)?;
+        let delegated_v6_str: Option<String> = row.get(15)?;
+        let socket_access = SocketAccess {
+            uid: row.get(16)?,
+            gid: row.get(17)?,
+            mode: row.get(18)?,
+        };
 
         // Parse MAC address
         let mac_parts: Vec<&str> = mac_str.split(':').collect();
@@ -600,67 +1033,1177 @@ impl Storage {
             updated_at: DateTime::parse_from_rfc3339(&updated_at_str)
                 .unwrap()
                 .with_timezone(&Utc),
+            owner: owner_kind
+                .zip(owner_id)
+                .map(|(kind, id)| OwnerRef { kind, id }),
+            link_up,
+            delegated_ipv6_prefix: delegated_v6_str.map(|s| s.parse().unwrap()),
+            socket_access,
         })
     }
-}
 
-/// Parse MAC address string to bytes.
-pub fn parse_mac_address(s: &str) -> Option<[u8; 6]> {
-    let parts: Vec<&str> = s.split(':').collect();
-    if parts.len() != 6 {
-        return None;
-    }
+    // ========== Route Operations ==========
 
-    let mut mac = [0u8; 6];
-    for (i, part) in parts.iter().enumerate() {
-        mac[i] = u8::from_str_radix(part, 16).ok()?;
-    }
-    Some(mac)
-}
+    /// Create a static route.
+    pub fn create_route(&self, route: &RouteData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
 
-/// Generate a random MAC address with local admin bit set.
-pub fn generate_mac_address() -> [u8; 6] {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    let mut mac = [0u8; 6];
-    rng.fill(&mut mac);
-    // Set locally administered and unicast bits
-    mac[0] = (mac[0] & 0xfe) | 0x02;
-    mac
-}
+        conn.execute(
+            "INSERT INTO routes (id, network_id, destination, next_hop, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                route.id.to_string(),
+                route.network_id.to_string(),
+                route.destination.to_string(),
+                route.next_hop.map(|a| a.to_string()),
+                route.created_at.to_rfc3339(),
+            ],
+        )
+        .map_err(|e| {
+            if let rusqlite::Error::SqliteFailure(ref err, _) = e
+                && err.code == rusqlite::ErrorCode::ConstraintViolation
+            {
+                return StorageError::RouteExists(route.destination.to_string());
+            }
+            StorageError::Database(e)
+        })?;
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        Ok(())
+    }
 
-    #[test]
-    fn test_storage_create_network() {
-        let storage = Storage::in_memory().unwrap();
+    /// Get a static route by ID.
+    pub fn get_route_by_id(&self, id: &Uuid) -> Result<Option<RouteData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, network_id, destination, next_hop, created_at
+             FROM routes WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_route(row)),
+        )
+        .optional()?
+        .transpose()
+    }
 
-        let network = NetworkData {
-            id: Uuid::new_v4(),
-            name: "test-network".to_string(),
-            ipv4_enabled: true,
-            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
-            ipv6_enabled: false,
-            ipv6_prefix: None,
-            dns_servers: vec!["8.8.8.8".parse().unwrap()],
-            ntp_servers: vec![],
-            is_public: true,
-            created_at: Utc::now(),
-            updated_at: Utc::now(),
-        };
+    /// List static routes in a network.
+    pub fn list_routes_in_network(&self, network_id: &Uuid) -> Result<Vec<RouteData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, network_id, destination, next_hop, created_at
+             FROM routes WHERE network_id = ?1 ORDER BY created_at",
+        )?;
 
-        storage.create_network(&network).unwrap();
+        let routes = stmt
+            .query_map(params![network_id.to_string()], |row| {
+                Ok(Self::row_to_route(row))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
 
-        let fetched = storage.get_network_by_id(&network.id).unwrap().unwrap();
-        assert_eq!(fetched.name, "test-network");
-        assert!(fetched.ipv4_enabled);
-        assert_eq!(fetched.ipv4_subnet, Some("10.0.0.0/24".parse().unwrap()));
+        Ok(routes)
     }
 
-    #[test]
-    fn test_storage_create_nic() {
+    /// Delete a static route by ID.
+    pub fn delete_route(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute("DELETE FROM routes WHERE id = ?1", params![id.to_string()])?;
+        Ok(rows > 0)
+    }
+
+    fn row_to_route(row: &Row) -> Result<RouteData> {
+        let id_str: String = row.get(0)?;
+        let network_id_str: String = row.get(1)?;
+        let destination_str: String = row.get(2)?;
+        let next_hop_str: Option<String> = row.get(3)?;
+        let created_at_str: String = row.get(4)?;
+
+        Ok(RouteData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            network_id: Uuid::parse_str(&network_id_str).unwrap(),
+            destination: destination_str.parse().unwrap(),
+            next_hop: next_hop_str.map(|s| s.parse().unwrap()),
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+
+    // ========== Lease Operations ==========
+
+    /// Create a static DHCP lease.
+    pub fn create_lease(&self, lease: &LeaseData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO leases (id, network_id, nic_id, mac_address, ipv4_address, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                lease.id.to_string(),
+                lease.network_id.to_string(),
+                lease.nic_id.to_string(),
+                lease.mac_string(),
+                lease.ipv4_address.to_string(),
+                lease.created_at.to_rfc3339(),
+            ],
+        )
+        .map_err(|e| {
+            if let rusqlite::Error::SqliteFailure(ref err, _) = e
+                && err.code == rusqlite::ErrorCode::ConstraintViolation
+            {
+                return StorageError::LeaseExists(format!(
+                    "{} / {}",
+                    lease.mac_string(),
+                    lease.ipv4_address
+                ));
+            }
+            StorageError::Database(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Get a static DHCP lease by ID.
+    pub fn get_lease_by_id(&self, id: &Uuid) -> Result<Option<LeaseData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, network_id, nic_id, mac_address, ipv4_address, created_at
+             FROM leases WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_lease(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// List static DHCP leases in a network.
+    pub fn list_leases_in_network(&self, network_id: &Uuid) -> Result<Vec<LeaseData>> {
+        self.query_leases(
+            "SELECT id, network_id, nic_id, mac_address, ipv4_address, created_at
+             FROM leases WHERE network_id = ?1 ORDER BY created_at",
+            network_id,
+        )
+    }
+
+    /// List static DHCP leases served by a NIC.
+    pub fn list_leases_for_nic(&self, nic_id: &Uuid) -> Result<Vec<LeaseData>> {
+        self.query_leases(
+            "SELECT id, network_id, nic_id, mac_address, ipv4_address, created_at
+             FROM leases WHERE nic_id = ?1 ORDER BY created_at",
+            nic_id,
+        )
+    }
+
+    /// Move a static DHCP lease to another address.
+    pub fn update_lease_address(&self, id: &Uuid, addr: Ipv4Addr) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE leases SET ipv4_address = ?1 WHERE id = ?2",
+            params![addr.to_string(), id.to_string()],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::LeaseNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Delete a static DHCP lease by ID.
+    pub fn delete_lease(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute("DELETE FROM leases WHERE id = ?1", params![id.to_string()])?;
+        Ok(rows > 0)
+    }
+
+    /// Check if an IPv4 address is leased in a network.
+    pub fn is_ipv4_leased(&self, network_id: &Uuid, addr: Ipv4Addr) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: u32 = conn.query_row(
+            "SELECT COUNT(*) FROM leases WHERE network_id = ?1 AND ipv4_address = ?2",
+            params![network_id.to_string(), addr.to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(count > 0)
+    }
+
+    fn query_leases(&self, sql: &str, id: &Uuid) -> Result<Vec<LeaseData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(sql)?;
+
+        let leases = stmt
+            .query_map(params![id.to_string()], |row| Ok(Self::row_to_lease(row)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(leases)
+    }
+
+    fn row_to_lease(row: &Row) -> Result<LeaseData> {
+        let id_str: String = row.get(0)?;
+        let network_id_str: String = row.get(1)?;
+        let nic_id_str: String = row.get(2)?;
+        let mac_str: String = row.get(3)?;
+        let ipv4_str: String = row.get(4)?;
+        let created_at_str: String = row.get(5)?;
+
+        Ok(LeaseData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            network_id: Uuid::parse_str(&network_id_str).unwrap(),
+            nic_id: Uuid::parse_str(&nic_id_str).unwrap(),
+            mac_address: parse_mac_address(&mac_str).unwrap_or_default(),
+            ipv4_address: ipv4_str.parse().unwrap(),
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+
+    // ========== Peering Operations ==========
+
+    /// Create a network peering.
+    pub fn create_peering(&self, peering: &PeeringData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO peerings (id, network_a_id, network_b_id, created_at, routed, allowed_prefixes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                peering.id.to_string(),
+                peering.network_a_id.to_string(),
+                peering.network_b_id.to_string(),
+                peering.created_at.to_rfc3339(),
+                peering.routed,
+                prefixes_json(&peering.allowed_prefixes)?,
+            ],
+        )
+        .map_err(|e| {
+            if let rusqlite::Error::SqliteFailure(ref err, _) = e
+                && err.code == rusqlite::ErrorCode::ConstraintViolation
+            {
+                return StorageError::PeeringExists(format!(
+                    "{} / {}",
+                    peering.network_a_id, peering.network_b_id
+                ));
+            }
+            StorageError::Database(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Get a network peering by ID.
+    pub fn get_peering_by_id(&self, id: &Uuid) -> Result<Option<PeeringData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, network_a_id, network_b_id, created_at, routed, allowed_prefixes FROM peerings WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_peering(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// List all network peerings.
+    pub fn list_peerings(&self) -> Result<Vec<PeeringData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, network_a_id, network_b_id, created_at, routed, allowed_prefixes FROM peerings ORDER BY created_at",
+        )?;
+
+        let peerings = stmt
+            .query_map([], |row| Ok(Self::row_to_peering(row)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(peerings)
+    }
+
+    /// List the peerings of a network.
+    pub fn list_peerings_for_network(&self, network_id: &Uuid) -> Result<Vec<PeeringData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, network_a_id, network_b_id, created_at, routed, allowed_prefixes FROM peerings
+             WHERE network_a_id = ?1 OR network_b_id = ?1 ORDER BY created_at",
+        )?;
+
+        let peerings = stmt
+            .query_map(params![network_id.to_string()], |row| {
+                Ok(Self::row_to_peering(row))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(peerings)
+    }
+
+    /// Get the peering between two networks, given in either order.
+    pub fn get_peering_between(&self, a: &Uuid, b: &Uuid) -> Result<Option<PeeringData>> {
+        let (a, b) = if a <= b { (a, b) } else { (b, a) };
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, network_a_id, network_b_id, created_at, routed, allowed_prefixes FROM peerings
+             WHERE network_a_id = ?1 AND network_b_id = ?2",
+            params![a.to_string(), b.to_string()],
+            |row| Ok(Self::row_to_peering(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// Set whether a peering routes between its networks, and to which
+    /// prefixes.
+    pub fn update_peering_routes(
+        &self,
+        id: &Uuid,
+        routed: bool,
+        allowed_prefixes: &[IpNet],
+    ) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "UPDATE peerings SET routed = ?1, allowed_prefixes = ?2 WHERE id = ?3",
+            params![routed, prefixes_json(allowed_prefixes)?, id.to_string()],
+        )?;
+        if rows == 0 {
+            return Err(StorageError::PeeringNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Delete a network peering by ID.
+    pub fn delete_peering(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM peerings WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn row_to_peering(row: &Row) -> Result<PeeringData> {
+        let id_str: String = row.get(0)?;
+        let network_a_str: String = row.get(1)?;
+        let network_b_str: String = row.get(2)?;
+        let created_at_str: String = row.get(3)?;
+        let allowed_json: String = row.get(5)?;
+        let allowed: Vec<String> = serde_json::from_str(&allowed_json)?;
+
+        Ok(PeeringData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            network_a_id: Uuid::parse_str(&network_a_str).unwrap(),
+            network_b_id: Uuid::parse_str(&network_b_str).unwrap(),
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+            routed: row.get(4)?,
+            allowed_prefixes: allowed.iter().map(|s| s.parse().unwrap()).collect(),
+        })
+    }
+
+    // ========== Port Forward Operations ==========
+
+    /// Create a port forward.
+    pub fn create_port_forward(&self, forward: &PortForwardData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO port_forwards (id, nic_id, protocol, host_ip, host_port, vm_port, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                forward.id.to_string(),
+                forward.nic_id.to_string(),
+                forward.protocol.as_str(),
+                forward.host_ip.to_string(),
+                forward.host_port,
+                forward.vm_port,
+                forward.created_at.to_rfc3339(),
+            ],
+        )
+        .map_err(|e| {
+            if let rusqlite::Error::SqliteFailure(ref err, _) = e
+                && err.code == rusqlite::ErrorCode::ConstraintViolation
+            {
+                return StorageError::PortForwardExists(forward.host_endpoint());
+            }
+            StorageError::Database(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Get a port forward by ID.
+    pub fn get_port_forward_by_id(&self, id: &Uuid) -> Result<Option<PortForwardData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, nic_id, protocol, host_ip, host_port, vm_port, created_at
+             FROM port_forwards WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_port_forward(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// List all port forwards.
+    pub fn list_port_forwards(&self) -> Result<Vec<PortForwardData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, nic_id, protocol, host_ip, host_port, vm_port, created_at
+             FROM port_forwards ORDER BY created_at",
+        )?;
+
+        let forwards = stmt
+            .query_map([], |row| Ok(Self::row_to_port_forward(row)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(forwards)
+    }
+
+    /// List the port forwards to a NIC.
+    pub fn list_port_forwards_for_nic(&self, nic_id: &Uuid) -> Result<Vec<PortForwardData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, nic_id, protocol, host_ip, host_port, vm_port, created_at
+             FROM port_forwards WHERE nic_id = ?1 ORDER BY created_at",
+        )?;
+
+        let forwards = stmt
+            .query_map(params![nic_id.to_string()], |row| {
+                Ok(Self::row_to_port_forward(row))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(forwards)
+    }
+
+    /// Delete a port forward by ID.
+    pub fn delete_port_forward(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM port_forwards WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn row_to_port_forward(row: &Row) -> Result<PortForwardData> {
+        let id_str: String = row.get(0)?;
+        let nic_id_str: String = row.get(1)?;
+        let protocol_str: String = row.get(2)?;
+        let host_ip_str: String = row.get(3)?;
+        let host_port: u16 = row.get(4)?;
+        let vm_port: u16 = row.get(5)?;
+        let created_at_str: String = row.get(6)?;
+
+        Ok(PortForwardData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            nic_id: Uuid::parse_str(&nic_id_str).unwrap(),
+            protocol: RuleProtocol::parse(&protocol_str),
+            host_ip: host_ip_str.parse().unwrap(),
+            host_port,
+            vm_port,
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+
+    // ========== Floating IP Operations ==========
+
+    /// Create a floating IP.
+    pub fn create_floating_ip(&self, fip: &FloatingIpData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO floating_ips (id, address, nic_id, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                fip.id.to_string(),
+                fip.address.to_string(),
+                fip.nic_id.map(|id| id.to_string()),
+                fip.created_at.to_rfc3339(),
+                fip.updated_at.to_rfc3339(),
+            ],
+        )
+        .map_err(|e| floating_ip_conflict(e, fip.address, fip.nic_id))?;
+
+        Ok(())
+    }
+
+    /// Get a floating IP by ID.
+    pub fn get_floating_ip_by_id(&self, id: &Uuid) -> Result<Option<FloatingIpData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, address, nic_id, created_at, updated_at
+             FROM floating_ips WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_floating_ip(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// Get a floating IP by its address.
+    pub fn get_floating_ip_by_address(&self, address: Ipv4Addr) -> Result<Option<FloatingIpData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, address, nic_id, created_at, updated_at
+             FROM floating_ips WHERE address = ?1",
+            params![address.to_string()],
+            |row| Ok(Self::row_to_floating_ip(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// Get the floating IP associated with a NIC.
+    pub fn get_floating_ip_for_nic(&self, nic_id: &Uuid) -> Result<Option<FloatingIpData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, address, nic_id, created_at, updated_at
+             FROM floating_ips WHERE nic_id = ?1",
+            params![nic_id.to_string()],
+            |row| Ok(Self::row_to_floating_ip(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// List all floating IPs.
+    pub fn list_floating_ips(&self) -> Result<Vec<FloatingIpData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, address, nic_id, created_at, updated_at
+             FROM floating_ips ORDER BY created_at",
+        )?;
+
+        let fips = stmt
+            .query_map([], |row| Ok(Self::row_to_floating_ip(row)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(fips)
+    }
+
+    /// Associate a floating IP with a NIC, or disassociate it with `None`.
+    pub fn set_floating_ip_nic(&self, id: &Uuid, nic_id: Option<Uuid>) -> Result<FloatingIpData> {
+        let fip = self
+            .get_floating_ip_by_id(id)?
+            .ok_or_else(|| StorageError::FloatingIpNotFound(id.to_string()))?;
+
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now();
+        conn.execute(
+            "UPDATE floating_ips SET nic_id = ?1, updated_at = ?2 WHERE id = ?3",
+            params![
+                nic_id.map(|id| id.to_string()),
+                now.to_rfc3339(),
+                id.to_string()
+            ],
+        )
+        .map_err(|e| floating_ip_conflict(e, fip.address, nic_id))?;
+
+        Ok(FloatingIpData {
+            nic_id,
+            updated_at: now,
+            ..fip
+        })
+    }
+
+    /// Delete a floating IP by ID.
+    pub fn delete_floating_ip(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM floating_ips WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn row_to_floating_ip(row: &Row) -> Result<FloatingIpData> {
+        let id_str: String = row.get(0)?;
+        let address_str: String = row.get(1)?;
+        let nic_id_str: Option<String> = row.get(2)?;
+        let created_at_str: String = row.get(3)?;
+        let updated_at_str: String = row.get(4)?;
+
+        Ok(FloatingIpData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            address: address_str.parse().unwrap(),
+            nic_id: nic_id_str.map(|s| Uuid::parse_str(&s).unwrap()),
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+            updated_at: DateTime::parse_from_rfc3339(&updated_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+
+    // ========== Security Group Operations ==========
+
+    /// Create a new security group.
+    pub fn create_security_group(&self, sg: &SecurityGroupData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO security_groups (id, name, description, audit, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                sg.id.to_string(),
+                sg.name,
+                sg.description,
+                sg.audit,
+                sg.created_at.to_rfc3339(),
+                sg.updated_at.to_rfc3339(),
+            ],
+        )
+        .map_err(|e| {
+            if let rusqlite::Error::SqliteFailure(ref err, _) = e
+                && err.code == rusqlite::ErrorCode::ConstraintViolation
+            {
+                return StorageError::SecurityGroupNameExists(sg.name.clone());
+            }
+            StorageError::Database(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Get a security group by ID.
+    pub fn get_security_group_by_id(&self, id: &Uuid) -> Result<Option<SecurityGroupData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, name, description, audit, created_at, updated_at
+             FROM security_groups WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_security_group(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// Get a security group by name.
+    pub fn get_security_group_by_name(&self, name: &str) -> Result<Option<SecurityGroupData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, name, description, audit, created_at, updated_at
+             FROM security_groups WHERE name = ?1",
+            params![name],
+            |row| Ok(Self::row_to_security_group(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// List all security groups.
+    pub fn list_security_groups(&self) -> Result<Vec<SecurityGroupData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, name, description, audit, created_at, updated_at
+             FROM security_groups ORDER BY created_at",
+        )?;
+
+        let groups = stmt
+            .query_map([], |row| Ok(Self::row_to_security_group(row)))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(groups)
+    }
+
+    /// Delete a security group by ID. Its rules and attachments go with it.
+    pub fn delete_security_group(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM security_groups WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// Switch a security group between audit and enforcing mode.
+    pub fn set_security_group_audit(&self, id: &Uuid, audit: bool) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "UPDATE security_groups SET audit = ?1, updated_at = ?2 WHERE id = ?3",
+            params![audit, now, id.to_string()],
+        )?;
+
+        if rows == 0 {
+            return Err(StorageError::SecurityGroupNotFound(id.to_string()));
+        }
+        Ok(())
+    }
+
+    fn row_to_security_group(row: &Row) -> Result<SecurityGroupData> {
+        let id_str: String = row.get(0)?;
+        let name: String = row.get(1)?;
+        let description: Option<String> = row.get(2)?;
+        let audit: bool = row.get(3)?;
+        let created_at_str: String = row.get(4)?;
+        let updated_at_str: String = row.get(5)?;
+
+        Ok(SecurityGroupData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            name,
+            description,
+            audit,
+            created_at: DateTime::parse_from_rfc3339(&created_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+            updated_at: DateTime::parse_from_rfc3339(&updated_at_str)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+
+    /// Create a new security group rule.
+    pub fn create_security_group_rule(&self, rule: &SecurityGroupRuleData) -> Result<()> {
+        let conn = self.conn.lock().unwrap();
+
+        conn.execute(
+            "INSERT INTO security_group_rules (id, security_group_id, direction, protocol, port_start, port_end, cidr, description, not_before, not_after, schedule, created_at, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+            params![
+                rule.id.to_string(),
+                rule.security_group_id.to_string(),
+                rule.direction.as_str(),
+                rule.protocol.as_str(),
+                rule.port_start.map(|p| p as i32),
+                rule.port_end.map(|p| p as i32),
+                rule.cidr,
+                rule.description,
+                rule.not_before.map(|t| t.to_rfc3339()),
+                rule.not_after.map(|t| t.to_rfc3339()),
+                rule.schedule.map(|s| s.to_string()),
+                rule.created_at.to_rfc3339(),
+                rule.updated_at.to_rfc3339(),
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Get a security group rule by ID.
+    pub fn get_security_group_rule_by_id(
+        &self,
+        id: &Uuid,
+    ) -> Result<Option<SecurityGroupRuleData>> {
+        let conn = self.conn.lock().unwrap();
+        conn.query_row(
+            "SELECT id, security_group_id, direction, protocol, port_start, port_end, cidr, description, not_before, not_after, schedule, created_at, updated_at
+             FROM security_group_rules WHERE id = ?1",
+            params![id.to_string()],
+            |row| Ok(Self::row_to_security_group_rule(row)),
+        )
+        .optional()?
+        .transpose()
+    }
+
+    /// List the rules of a security group.
+    pub fn list_rules_for_security_group(
+        &self,
+        sg_id: &Uuid,
+    ) -> Result<Vec<SecurityGroupRuleData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT id, security_group_id, direction, protocol, port_start, port_end, cidr, description, not_before, not_after, schedule, created_at, updated_at
+             FROM security_group_rules WHERE security_group_id = ?1 ORDER BY created_at",
+        )?;
+
+        let rules = stmt
+            .query_map(params![sg_id.to_string()], |row| {
+                Ok(Self::row_to_security_group_rule(row))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(rules)
+    }
+
+    /// Delete a security group rule by ID.
+    pub fn delete_security_group_rule(&self, id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM security_group_rules WHERE id = ?1",
+            params![id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    fn row_to_security_group_rule(row: &Row) -> Result<SecurityGroupRuleData> {
+        let id_str: String = row.get(0)?;
+        let sg_id_str: String = row.get(1)?;
+        let direction_str: String = row.get(2)?;
+        let protocol_str: String = row.get(3)?;
+        let port_start: Option<i32> = row.get(4)?;
+        let port_end: Option<i32> = row.get(5)?;
+        let cidr: Option<String> = row.get(6)?;
+        let description: Option<String> = row.get(7)?;
+        let not_before_str: Option<String> = row.get(8)?;
+        let not_after_str: Option<String> = row.get(9)?;
+        let schedule_str: Option<String> = row.get(10)?;
+        let created_at_str: String = row.get(11)?;
+        let updated_at_str: String = row.get(12)?;
+        let parse_time = |s: String| {
+            DateTime::parse_from_rfc3339(&s)
+                .unwrap()
+                .with_timezone(&Utc)
+        };
+
+        Ok(SecurityGroupRuleData {
+            id: Uuid::parse_str(&id_str).unwrap(),
+            security_group_id: Uuid::parse_str(&sg_id_str).unwrap(),
+            direction: RuleDirection::parse(&direction_str),
+            protocol: RuleProtocol::parse(&protocol_str),
+            port_start: port_start.map(|p| p as u16),
+            port_end: port_end.map(|p| p as u16),
+            cidr,
+            description,
+            not_before: not_before_str.map(parse_time),
+            not_after: not_after_str.map(parse_time),
+            schedule: schedule_str.map(|s| s.parse().unwrap()),
+            created_at: parse_time(created_at_str),
+            updated_at: parse_time(updated_at_str),
+        })
+    }
+
+    /// Attach a security group to a NIC. Returns false if it already was.
+    pub fn attach_security_group(&self, nic_id: &Uuid, sg_id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let now = Utc::now().to_rfc3339();
+
+        let rows = conn.execute(
+            "INSERT OR IGNORE INTO nic_security_groups (nic_id, security_group_id, created_at)
+             VALUES (?1, ?2, ?3)",
+            params![nic_id.to_string(), sg_id.to_string(), now],
+        )?;
+
+        Ok(rows > 0)
+    }
+
+    /// Detach a security group from a NIC.
+    pub fn detach_security_group(&self, nic_id: &Uuid, sg_id: &Uuid) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let rows = conn.execute(
+            "DELETE FROM nic_security_groups WHERE nic_id = ?1 AND security_group_id = ?2",
+            params![nic_id.to_string(), sg_id.to_string()],
+        )?;
+        Ok(rows > 0)
+    }
+
+    /// IDs of the NICs a security group is attached to.
+    pub fn list_nic_ids_in_security_group(&self, sg_id: &Uuid) -> Result<Vec<Uuid>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT nic_id FROM nic_security_groups WHERE security_group_id = ?1 ORDER BY created_at",
+        )?;
+
+        let ids = stmt
+            .query_map(params![sg_id.to_string()], |row| row.get::<_, String>(0))?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .iter()
+            .map(|s| Uuid::parse_str(s).unwrap())
+            .collect();
+
+        Ok(ids)
+    }
+
+    /// List the security groups attached to a NIC.
+    pub fn list_security_groups_for_nic(&self, nic_id: &Uuid) -> Result<Vec<SecurityGroupData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT sg.id, sg.name, sg.description, sg.audit, sg.created_at, sg.updated_at
+             FROM security_groups sg
+             INNER JOIN nic_security_groups nsg ON sg.id = nsg.security_group_id
+             WHERE nsg.nic_id = ?1
+             ORDER BY sg.created_at",
+        )?;
+
+        let groups = stmt
+            .query_map(params![nic_id.to_string()], |row| {
+                Ok(Self::row_to_security_group(row))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(groups)
+    }
+
+    /// All rules of the security groups attached to a NIC.
+    pub fn get_all_rules_for_nic(&self, nic_id: &Uuid) -> Result<Vec<SecurityGroupRuleData>> {
+        let conn = self.conn.lock().unwrap();
+        let mut stmt = conn.prepare(
+            "SELECT r.id, r.security_group_id, r.direction, r.protocol, r.port_start, r.port_end,
+                    r.cidr, r.description, r.not_before, r.not_after, r.schedule, r.created_at,
+                    r.updated_at
+             FROM security_group_rules r
+             INNER JOIN nic_security_groups nsg ON r.security_group_id = nsg.security_group_id
+             WHERE nsg.nic_id = ?1
+             ORDER BY r.security_group_id, r.created_at",
+        )?;
+
+        let rules = stmt
+            .query_map(params![nic_id.to_string()], |row| {
+                Ok(Self::row_to_security_group_rule(row))
+            })?
+            .collect::<std::result::Result<Vec<_>, _>>()?
+            .into_iter()
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+
+        Ok(rules)
+    }
+
+    // ========== Host Migration ==========
+
+    /// Network, NIC, route, lease, peering, port forward and security group
+    /// rows for a state snapshot, as
+    /// `{"table": [{column: value}]}`.
+    pub fn export_state(&self) -> Result<serde_json::Value> {
+        let conn = self.conn.lock().unwrap();
+        let mut state = serde_json::Map::new();
+        for table in STATE_TABLES {
+            state.insert(table.to_string(), export_table(&conn, table)?.into());
+        }
+        Ok(state.into())
+    }
+
+    /// Insert the rows of a state snapshot into an empty store. Returns the
+    /// number of rows.
+    pub fn import_state(&self, state: &serde_json::Value) -> Result<usize> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let mut rows = 0;
+        for table in STATE_TABLES {
+            rows += import_table(&tx, table, snapshot_rows(state, table))?;
+        }
+        tx.commit()?;
+        Ok(rows)
+    }
+
+    /// Whether no network is defined; imports only go into an empty store.
+    pub fn is_empty(&self) -> Result<bool> {
+        let conn = self.conn.lock().unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM networks", [], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+}
+
+/// Format version of [`Storage::export_state`] snapshots.
+pub const STATE_VERSION: u32 = 1;
+
+/// Tables in a state snapshot, parents first.
+const STATE_TABLES: &[&str] = &[
+    "networks",
+    "nics",
+    "routes",
+    "leases",
+    "peerings",
+    "port_forwards",
+    "floating_ips",
+    "security_groups",
+    "security_group_rules",
+    "nic_security_groups",
+];
+
+/// Number of rows in a state snapshot.
+pub fn snapshot_len(state: &serde_json::Value) -> usize {
+    STATE_TABLES
+        .iter()
+        .map(|t| snapshot_rows(state, t).len())
+        .sum()
+}
+
+fn snapshot_rows<'a>(state: &'a serde_json::Value, table: &str) -> &'a [serde_json::Value] {
+    state[table]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get("name"))?
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    Ok(columns)
+}
+
+/// All rows of `table` as JSON objects keyed by column.
+fn export_table(conn: &Connection, table: &str) -> Result<Vec<serde_json::Value>> {
+    let fields = table_columns(conn, table)?
+        .iter()
+        .map(|c| format!("'{0}', \"{0}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let json: String = conn.query_row(
+        &format!(
+            "SELECT json_group_array(json_object({})) FROM {}",
+            fields, table
+        ),
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Insert rows written by [`export_table`]. Columns missing from the rows,
+/// as in an export from an older version, get their defaults.
+fn import_table(conn: &Connection, table: &str, rows: &[serde_json::Value]) -> Result<usize> {
+    let Some(first) = rows.first().and_then(|r| r.as_object()) else {
+        return Ok(0);
+    };
+    let columns: Vec<String> = table_columns(conn, table)?
+        .into_iter()
+        .filter(|c| first.contains_key(c))
+        .collect();
+    let names = columns
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = columns
+        .iter()
+        .map(|c| format!("json_extract(value, '$.\"{}\"')", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let rows = conn.execute(
+        &format!(
+            "INSERT INTO {} ({}) SELECT {} FROM json_each(?1)",
+            table, names, values
+        ),
+        params![serde_json::to_string(rows)?],
+    )?;
+    Ok(rows)
+}
+
+/// Prefixes as the JSON array of strings they are stored as.
+fn prefixes_json(prefixes: &[IpNet]) -> Result<String> {
+    Ok(serde_json::to_string(
+        &prefixes.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+    )?)
+}
+
+/// The error for a unique constraint of `floating_ips` that an insert or
+/// update of `address` and `nic_id` violated.
+fn floating_ip_conflict(
+    e: rusqlite::Error,
+    address: Ipv4Addr,
+    nic_id: Option<Uuid>,
+) -> StorageError {
+    if let rusqlite::Error::SqliteFailure(ref err, ref msg) = e
+        && err.code == rusqlite::ErrorCode::ConstraintViolation
+    {
+        return match (nic_id, msg) {
+            (Some(nic_id), Some(msg)) if msg.contains("floating_ips.nic_id") => {
+                StorageError::NicHasFloatingIp(nic_id.to_string())
+            }
+            _ => StorageError::FloatingIpExists(address.to_string()),
+        };
+    }
+    StorageError::Database(e)
+}
+
+/// Parse MAC address string to bytes.
+pub fn parse_mac_address(s: &str) -> Option<[u8; 6]> {
+    let parts: Vec<&str> = s.split(':').collect();
+    if parts.len() != 6 {
+        return None;
+    }
+
+    let mut mac = [0u8; 6];
+    for (i, part) in parts.iter().enumerate() {
+        mac[i] = u8::from_str_radix(part, 16).ok()?;
+    }
+    Some(mac)
+}
+
+/// Format MAC address bytes as string.
+pub fn format_mac_address(mac: &[u8; 6]) -> String {
+    format!(
+        "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}",
+        mac[0], mac[1], mac[2], mac[3], mac[4], mac[5]
+    )
+}
+
+/// Generate a random MAC address with local admin bit set.
+pub fn generate_mac_address() -> [u8; 6] {
+    use rand::Rng;
+    let mut rng = rand::thread_rng();
+    let mut mac = [0u8; 6];
+    rng.fill(&mut mac);
+    // Set locally administered and unicast bits
+    mac[0] = (mac[0] & 0xfe) | 0x02;
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_storage_create_network() {
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec!["8.8.8.8".parse().unwrap()],
+            ntp_servers: vec![],
+            is_public: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+
+        storage.create_network(&network).unwrap();
+
+        let fetched = storage.get_network_by_id(&network.id).unwrap().unwrap();
+        assert_eq!(fetched.name, "test-network");
+        assert!(fetched.ipv4_enabled);
+        assert_eq!(fetched.ipv4_subnet, Some("10.0.0.0/24".parse().unwrap()));
+        assert!(!fetched.proxy_neighbors);
+
+        storage
+            .update_network_proxy_neighbors(&network.id, true)
+            .unwrap();
+        let fetched = storage.get_network_by_id(&network.id).unwrap().unwrap();
+        assert!(fetched.proxy_neighbors);
+        assert!(matches!(
+            storage.update_network_proxy_neighbors(&Uuid::new_v4(), true),
+            Err(StorageError::NetworkNotFound(_))
+        ));
+
+        assert!(fetched.dns_forwarders.is_empty());
+        let forwarders: Vec<SocketAddr> = vec!["192.0.2.53:53".parse().unwrap()];
+        storage
+            .update_network_dns_forwarders(&network.id, &forwarders)
+            .unwrap();
+        let fetched = storage.get_network_by_id(&network.id).unwrap().unwrap();
+        assert_eq!(fetched.dns_forwarders, forwarders);
+    }
+
+    #[test]
+    fn test_storage_create_nic() {
         let storage = Storage::in_memory().unwrap();
 
         let network = NetworkData {
@@ -675,6 +2218,10 @@ mod tests {
             is_public: false,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
         };
         storage.create_network(&network).unwrap();
 
@@ -691,12 +2238,608 @@ mod tests {
             state: NicState::Created,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            owner: Some(OwnerRef {
+                kind: "pod".to_string(),
+                id: "pod-1".to_string(),
+            }),
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: SocketAccess::default(),
         };
         storage.create_nic(&nic).unwrap();
 
         let fetched = storage.get_nic_by_id(&nic.id).unwrap().unwrap();
         assert_eq!(fetched.name, Some("test-nic".to_string()));
         assert_eq!(fetched.ipv4_address, Some("10.0.0.5".parse().unwrap()));
+        assert_eq!(fetched.owner, nic.owner);
+        assert!(fetched.link_up);
+
+        storage.update_nic_link_up(&nic.id, false).unwrap();
+        assert!(!storage.get_nic_by_id(&nic.id).unwrap().unwrap().link_up);
+    }
+
+    #[test]
+    fn test_storage_routes() {
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        let route = RouteData {
+            id: Uuid::new_v4(),
+            network_id: network.id,
+            destination: "192.168.10.0/24".parse().unwrap(),
+            next_hop: Some("10.0.0.5".parse().unwrap()),
+            created_at: Utc::now(),
+        };
+        storage.create_route(&route).unwrap();
+
+        // Same destination in the same network is rejected
+        let duplicate = RouteData {
+            id: Uuid::new_v4(),
+            next_hop: None,
+            ..route.clone()
+        };
+        assert!(matches!(
+            storage.create_route(&duplicate),
+            Err(StorageError::RouteExists(_))
+        ));
+
+        let routes = storage.list_routes_in_network(&network.id).unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].next_hop, Some("10.0.0.5".parse().unwrap()));
+
+        // Routes are removed together with their network
+        storage.delete_network(&network.id).unwrap();
+        assert!(storage.get_route_by_id(&route.id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_storage_leases() {
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x02],
+            ipv4_address: Some("10.0.0.2".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-lease.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: SocketAccess::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+
+        let lease = LeaseData {
+            id: Uuid::new_v4(),
+            network_id: network.id,
+            nic_id: nic.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x01, 0x01],
+            ipv4_address: "10.0.0.20".parse().unwrap(),
+            created_at: Utc::now(),
+        };
+        storage.create_lease(&lease).unwrap();
+
+        // The same MAC or address can't be leased twice in a network
+        let same_mac = LeaseData {
+            id: Uuid::new_v4(),
+            ipv4_address: "10.0.0.21".parse().unwrap(),
+            ..lease.clone()
+        };
+        assert!(matches!(
+            storage.create_lease(&same_mac),
+            Err(StorageError::LeaseExists(_))
+        ));
+
+        let leases = storage.list_leases_for_nic(&nic.id).unwrap();
+        assert_eq!(leases.len(), 1);
+        assert_eq!(leases[0].mac_address, lease.mac_address);
+        assert!(
+            storage
+                .is_ipv4_leased(&network.id, lease.ipv4_address)
+                .unwrap()
+        );
+
+        // Leased addresses count as used for allocation
+        let used = storage.get_used_ipv4_addresses(&network.id).unwrap();
+        assert!(used.contains(&lease.ipv4_address));
+        assert!(used.contains(&"10.0.0.2".parse().unwrap()));
+
+        // Leases are removed together with their NIC
+        storage.delete_nic(&nic.id).unwrap();
+        assert!(storage.get_lease_by_id(&lease.id).unwrap().is_none());
+        assert!(
+            storage
+                .list_leases_in_network(&network.id)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_storage_peerings() {
+        let storage = Storage::in_memory().unwrap();
+
+        let network = |name: &str, subnet: &str| NetworkData {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some(subnet.parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        let a = network("tenant-a", "10.0.0.0/24");
+        let b = network("tenant-b", "10.0.1.0/24");
+        storage.create_network(&a).unwrap();
+        storage.create_network(&b).unwrap();
+
+        let peering = PeeringData::new(b.id, a.id);
+        assert!(peering.network_a_id < peering.network_b_id);
+        storage.create_peering(&peering).unwrap();
+
+        // One peering per pair, whichever way round it is asked for
+        assert!(matches!(
+            storage.create_peering(&PeeringData::new(a.id, b.id)),
+            Err(StorageError::PeeringExists(_))
+        ));
+
+        assert_eq!(storage.list_peerings().unwrap().len(), 1);
+        assert_eq!(storage.list_peerings_for_network(&a.id).unwrap().len(), 1);
+        assert_eq!(storage.list_peerings_for_network(&b.id).unwrap().len(), 1);
+
+        // Connecting the networks routes the existing peering
+        let allowed: Vec<IpNet> = vec!["10.0.1.16/28".parse().unwrap()];
+        storage
+            .update_peering_routes(&peering.id, true, &allowed)
+            .unwrap();
+        let routed = storage.get_peering_between(&b.id, &a.id).unwrap().unwrap();
+        assert_eq!(routed.id, peering.id);
+        assert!(routed.routed);
+        assert_eq!(routed.allowed_prefixes, allowed);
+        assert!(routed.allows(&"10.0.1.17/32".parse().unwrap()));
+        assert!(!routed.allows(&"10.0.1.2/32".parse().unwrap()));
+
+        // Peerings go with either network
+        storage.delete_network(&b.id).unwrap();
+        assert!(storage.get_peering_by_id(&peering.id).unwrap().is_none());
+        assert!(storage.list_peerings().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_storage_port_forwards() {
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x02],
+            ipv4_address: Some("10.0.0.2".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-forward.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: SocketAccess::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+
+        let forward = PortForwardData {
+            id: Uuid::new_v4(),
+            nic_id: nic.id,
+            protocol: RuleProtocol::Tcp,
+            host_ip: "203.0.113.10".parse().unwrap(),
+            host_port: 8080,
+            vm_port: 80,
+            created_at: Utc::now(),
+        };
+        storage.create_port_forward(&forward).unwrap();
+
+        // A host port is forwarded once per protocol
+        let same_port = PortForwardData {
+            id: Uuid::new_v4(),
+            vm_port: 8080,
+            ..forward.clone()
+        };
+        assert!(matches!(
+            storage.create_port_forward(&same_port),
+            Err(StorageError::PortForwardExists(_))
+        ));
+        let udp = PortForwardData {
+            id: Uuid::new_v4(),
+            protocol: RuleProtocol::Udp,
+            ..forward.clone()
+        };
+        storage.create_port_forward(&udp).unwrap();
+
+        let forwards = storage.list_port_forwards_for_nic(&nic.id).unwrap();
+        assert_eq!(forwards.len(), 2);
+        assert_eq!(forwards[0].protocol, RuleProtocol::Tcp);
+        assert_eq!(forwards[0].host_port, 8080);
+        assert_eq!(forwards[0].vm_port, 80);
+        assert_eq!(forwards[0].host_endpoint(), "tcp/203.0.113.10:8080");
+
+        assert!(storage.delete_port_forward(&udp.id).unwrap());
+        assert!(!storage.delete_port_forward(&udp.id).unwrap());
+
+        // Port forwards are removed together with their NIC
+        storage.delete_nic(&nic.id).unwrap();
+        assert!(
+            storage
+                .get_port_forward_by_id(&forward.id)
+                .unwrap()
+                .is_none()
+        );
+        assert!(storage.list_port_forwards().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_storage_floating_ips() {
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x03],
+            ipv4_address: Some("10.0.0.3".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-fip.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: SocketAccess::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+
+        let fip = FloatingIpData {
+            id: Uuid::new_v4(),
+            address: "203.0.113.20".parse().unwrap(),
+            nic_id: Some(nic.id),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        storage.create_floating_ip(&fip).unwrap();
+
+        // Addresses are unique, and a NIC has one floating IP
+        let same_address = FloatingIpData {
+            id: Uuid::new_v4(),
+            nic_id: None,
+            ..fip.clone()
+        };
+        assert!(matches!(
+            storage.create_floating_ip(&same_address),
+            Err(StorageError::FloatingIpExists(_))
+        ));
+        let other = FloatingIpData {
+            id: Uuid::new_v4(),
+            address: "203.0.113.21".parse().unwrap(),
+            nic_id: None,
+            ..fip.clone()
+        };
+        storage.create_floating_ip(&other).unwrap();
+        assert!(matches!(
+            storage.set_floating_ip_nic(&other.id, Some(nic.id)),
+            Err(StorageError::NicHasFloatingIp(_))
+        ));
+
+        assert_eq!(
+            storage
+                .get_floating_ip_for_nic(&nic.id)
+                .unwrap()
+                .unwrap()
+                .id,
+            fip.id
+        );
+        let moved = storage.set_floating_ip_nic(&fip.id, None).unwrap();
+        assert_eq!(moved.nic_id, None);
+        storage
+            .set_floating_ip_nic(&other.id, Some(nic.id))
+            .unwrap();
+        assert_eq!(
+            storage
+                .get_floating_ip_by_address(other.address)
+                .unwrap()
+                .unwrap()
+                .nic_id,
+            Some(nic.id)
+        );
+
+        // Deleting the NIC keeps its floating IP, unassociated
+        storage.delete_nic(&nic.id).unwrap();
+        let kept = storage.get_floating_ip_by_id(&other.id).unwrap().unwrap();
+        assert_eq!(kept.nic_id, None);
+        assert_eq!(storage.list_floating_ips().unwrap().len(), 2);
+
+        assert!(storage.delete_floating_ip(&fip.id).unwrap());
+        assert!(!storage.delete_floating_ip(&fip.id).unwrap());
+    }
+
+    #[test]
+    fn test_storage_security_groups() {
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x09],
+            ipv4_address: Some("10.0.0.9".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-sg.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: SocketAccess::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+
+        let sg = SecurityGroupData {
+            id: Uuid::new_v4(),
+            name: "web".to_string(),
+            description: None,
+            audit: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        storage.create_security_group(&sg).unwrap();
+        assert!(matches!(
+            storage.create_security_group(&SecurityGroupData {
+                id: Uuid::new_v4(),
+                ..sg.clone()
+            }),
+            Err(StorageError::SecurityGroupNameExists(_))
+        ));
+
+        let rule = SecurityGroupRuleData {
+            id: Uuid::new_v4(),
+            security_group_id: sg.id,
+            direction: RuleDirection::Ingress,
+            protocol: RuleProtocol::Tcp,
+            port_start: Some(22),
+            port_end: Some(22),
+            cidr: Some("192.168.0.0/16".to_string()),
+            description: None,
+            not_before: None,
+            not_after: None,
+            schedule: Some("Mon-Fri 08:00-18:00".parse().unwrap()),
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        };
+        storage.create_security_group_rule(&rule).unwrap();
+
+        assert!(storage.attach_security_group(&nic.id, &sg.id).unwrap());
+        assert!(!storage.attach_security_group(&nic.id, &sg.id).unwrap());
+
+        let groups = storage.list_security_groups_for_nic(&nic.id).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].name, "web");
+        let rules = storage.get_all_rules_for_nic(&nic.id).unwrap();
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].schedule, rule.schedule);
+        assert_eq!(
+            storage.list_nic_ids_in_security_group(&sg.id).unwrap(),
+            vec![nic.id]
+        );
+
+        // Attachments go with the NIC, rules with the group
+        storage.delete_nic(&nic.id).unwrap();
+        assert!(
+            storage
+                .list_nic_ids_in_security_group(&sg.id)
+                .unwrap()
+                .is_empty()
+        );
+        storage.delete_security_group(&sg.id).unwrap();
+        assert!(
+            storage
+                .get_security_group_rule_by_id(&rule.id)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_storage_state_roundtrip() {
+        let source = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-network".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec!["10.0.0.1".parse().unwrap()],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: Some("10.0.0.1".parse().unwrap()),
+            boot_file: Some("pxelinux.0".to_string()),
+        };
+        source.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x07],
+            ipv4_address: Some("10.0.0.7".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec!["192.168.7.0/24".parse().unwrap()],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-7.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: SocketAccess::default(),
+        };
+        source.create_nic(&nic).unwrap();
+
+        let route = RouteData {
+            id: Uuid::new_v4(),
+            network_id: network.id,
+            destination: "172.16.0.0/16".parse().unwrap(),
+            next_hop: Some("10.0.0.7".parse().unwrap()),
+            created_at: Utc::now(),
+        };
+        source.create_route(&route).unwrap();
+
+        let state = source.export_state().unwrap();
+        assert_eq!(snapshot_len(&state), 3);
+
+        let target = Storage::in_memory().unwrap();
+        assert!(target.is_empty().unwrap());
+        assert_eq!(target.import_state(&state).unwrap(), 3);
+        assert!(!target.is_empty().unwrap());
+
+        let restored = target.get_network_by_id(&network.id).unwrap().unwrap();
+        assert_eq!(restored.dns_servers, network.dns_servers);
+        assert_eq!(restored.boot_server, network.boot_server);
+        assert_eq!(restored.boot_file, network.boot_file);
+        let restored = target.get_nic_by_id(&nic.id).unwrap().unwrap();
+        assert_eq!(restored.name, None);
+        assert_eq!(restored.mac_address, nic.mac_address);
+        assert_eq!(restored.ipv4_address, nic.ipv4_address);
+        assert_eq!(restored.routed_ipv4_prefixes, nic.routed_ipv4_prefixes);
+        assert_eq!(restored.socket_path, nic.socket_path);
+        let routes = target.list_routes_in_network(&network.id).unwrap();
+        assert_eq!(routes.len(), 1);
+        assert_eq!(routes[0].next_hop, route.next_hop);
+
+        // An empty snapshot imports nothing
+        let empty = Storage::in_memory().unwrap();
+        assert_eq!(empty.import_state(&serde_json::json!({})).unwrap(), 0);
     }
 
     #[test]
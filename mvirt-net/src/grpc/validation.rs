@@ -1,15 +1,24 @@
 //! Input validation for gRPC requests.
 
-use super::storage::{NetworkData, Storage};
-use ipnet::{Ipv4Net, Ipv6Net};
+use super::storage::{NetworkData, NicData, RuleDirection, RuleProtocol, Storage, StorageError};
+use crate::reactor::{GATEWAY_IPV4_LINK_LOCAL, GATEWAY_IPV6_LINK_LOCAL};
+use crate::socket_access::SocketAccess;
+use chrono::{DateTime, Utc};
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use mvirt_common::naming::{self, NameError};
+use mvirt_common::netboot::NetworkBootError;
+use mvirt_common::rule_window::RuleSchedule;
+use std::collections::HashSet;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use thiserror::Error;
+use tracing::warn;
+use uuid::Uuid;
 
 /// Validation errors.
 #[derive(Debug, Error)]
 pub enum ValidationError {
-    #[error("Network name is required")]
-    NetworkNameRequired,
+    #[error(transparent)]
+    InvalidName(#[from] NameError),
 
     #[error("At least one IP version (IPv4 or IPv6) must be enabled")]
     NoIpVersionEnabled,
@@ -47,6 +56,12 @@ pub enum ValidationError {
     #[error("IPv4 address {0} is already in use")]
     Ipv4AddressInUse(String),
 
+    #[error("IPv4 address {0} is reserved as the {1}")]
+    Ipv4AddressReserved(String, &'static str),
+
+    #[error("IPv6 address {0} is reserved as the {1}")]
+    Ipv6AddressReserved(String, &'static str),
+
     #[error("IPv6 address {0} is already in use")]
     Ipv6AddressInUse(String),
 
@@ -59,11 +74,119 @@ pub enum ValidationError {
     #[error("Invalid NTP server address: {0}")]
     InvalidNtpServer(String),
 
+    #[error(transparent)]
+    InvalidNetworkBoot(#[from] NetworkBootError),
+
     #[error("Invalid routed prefix: {0}")]
     InvalidRoutedPrefix(String),
 
     #[error("Network identifier required")]
     NetworkIdRequired,
+
+    #[error("Invalid route destination: {0}")]
+    InvalidRouteDestination(String),
+
+    #[error("Route destination {0} overlaps network subnet {1}")]
+    RouteOverlapsNetwork(String, String),
+
+    #[error("Exactly one of next_hop or via_uplink must be set")]
+    NextHopRequired,
+
+    #[error("Invalid next hop address: {0}")]
+    InvalidNextHop(String),
+
+    #[error("Next hop {0} is not a NIC address in this network")]
+    NextHopNotInNetwork(String),
+
+    #[error("Next hop {0} does not match the address family of {1}")]
+    NextHopFamilyMismatch(String, String),
+
+    #[error("DHCP leases need a network with IPv4")]
+    LeaseRequiresIpv4,
+
+    #[error("MAC address {0} is the NIC's own; it is served without a lease")]
+    LeaseMacIsNic(String),
+
+    #[error("Port forwards need a NIC with an IPv4 address")]
+    PortForwardRequiresIpv4,
+
+    #[error("Port forwards are TCP or UDP")]
+    InvalidPortForwardProtocol,
+
+    #[error("Invalid host address: {0}")]
+    InvalidHostAddress(String),
+
+    #[error("Host address {0} lies within public network '{1}' ({2})")]
+    HostAddressInNetwork(String, String, String),
+
+    #[error("Host address {0} is a floating IP")]
+    HostAddressIsFloatingIp(String),
+
+    #[error("Floating IPs need a NIC with an IPv4 address")]
+    FloatingIpRequiresIpv4,
+
+    #[error("Address {0} is not in the floating IP pool")]
+    AddressNotInFloatingIpPool(String),
+
+    #[error("Address {0} is the host address of a port forward")]
+    AddressForwarded(String),
+
+    #[error("IPv6 prefix delegation needs a network with IPv6")]
+    DelegationRequiresIpv6,
+
+    #[error("Delegated prefix /{0} must be longer than network prefix {1} and at most /64")]
+    InvalidDelegatedPrefixLen(u32, String),
+
+    #[error("Invalid socket mode {0:o} (must be at most 7777)")]
+    InvalidSocketMode(u32),
+
+    #[error("IPv6 address {0} is within delegated prefix {1}")]
+    Ipv6InDelegatedPrefix(String, String),
+
+    #[error("Uplink routes are only supported on public networks")]
+    UplinkRequiresPublicNetwork,
+
+    #[error("Default route on a public network is implicit via the uplink")]
+    DefaultRouteOnPublicNetwork,
+
+    #[error("Invalid rule direction")]
+    InvalidRuleDirection,
+
+    #[error("Invalid rule protocol")]
+    InvalidRuleProtocol,
+
+    #[error("Invalid port range: start ({0}) > end ({1})")]
+    InvalidPortRange(u32, u32),
+
+    #[error("Port out of range: {0} (must be 0-65535)")]
+    PortOutOfRange(u32),
+
+    #[error("Ports not allowed for protocol {0}")]
+    PortsNotAllowedForProtocol(String),
+
+    #[error("Invalid CIDR: {0}")]
+    InvalidCidr(String),
+
+    #[error("Invalid rule time: {0} (expected RFC 3339, e.g. 2026-01-31T18:00:00Z)")]
+    InvalidRuleTime(String),
+
+    #[error("Rule not_after must be later than not_before")]
+    EmptyRuleWindow,
+
+    #[error("Invalid rule schedule: {0}")]
+    InvalidRuleSchedule(String),
+
+    #[error("A network can't be connected to itself")]
+    ConnectSameNetwork,
+
+    #[error("Networks '{0}' and '{1}' overlap and can't be routed to each other")]
+    ConnectedNetworksOverlap(String, String),
+
+    #[error("Allowed prefix {0} lies outside both networks")]
+    AllowedPrefixOutsideNetworks(String),
+
+    #[error(transparent)]
+    Storage(#[from] StorageError),
 }
 
 pub type Result<T> = std::result::Result<T, ValidationError>;
@@ -98,10 +221,7 @@ pub fn validate_create_network(
     dns_servers: &[String],
     storage: &Storage,
 ) -> Result<(Option<Ipv4Net>, Option<Ipv6Net>, Vec<IpAddr>)> {
-    // Name required
-    if name.trim().is_empty() {
-        return Err(ValidationError::NetworkNameRequired);
-    }
+    naming::validate_name("Network", name)?;
 
     // At least one IP version
     if !ipv4_enabled && !ipv6_enabled {
@@ -228,9 +348,17 @@ pub fn validate_create_nic(
                 "no subnet configured".to_string(),
             ));
         }
+        if let Some(reason) = reserved_ipv4(network, addr) {
+            return Err(ValidationError::Ipv4AddressReserved(
+                addr.to_string(),
+                reason,
+            ));
+        }
 
         // Check if already in use
-        if storage.is_ipv4_in_use(&network.id, addr).unwrap_or(false) {
+        if storage.is_ipv4_in_use(&network.id, addr).unwrap_or(false)
+            || storage.is_ipv4_leased(&network.id, addr).unwrap_or(false)
+        {
             return Err(ValidationError::Ipv4AddressInUse(addr.to_string()));
         }
 
@@ -259,11 +387,26 @@ pub fn validate_create_nic(
                 "no prefix configured".to_string(),
             ));
         }
+        if let Some(reason) = reserved_ipv6(network, addr) {
+            return Err(ValidationError::Ipv6AddressReserved(
+                addr.to_string(),
+                reason,
+            ));
+        }
 
         // Check if already in use
         if storage.is_ipv6_in_use(&network.id, addr).unwrap_or(false) {
             return Err(ValidationError::Ipv6AddressInUse(addr.to_string()));
         }
+        let delegated = storage
+            .get_delegated_ipv6_prefixes(&network.id)
+            .unwrap_or_default();
+        if let Some(prefix) = delegated.iter().find(|p| p.contains(&addr)) {
+            return Err(ValidationError::Ipv6InDelegatedPrefix(
+                addr.to_string(),
+                prefix.to_string(),
+            ));
+        }
 
         Some(addr)
     };
@@ -297,6 +440,311 @@ pub fn validate_create_nic(
     ))
 }
 
+/// Validate a static route request.
+///
+/// Destinations must lie outside the network's own subnets so static routes
+/// never shadow the per-NIC host routes installed by the manager.
+pub fn validate_add_route(
+    network: &NetworkData,
+    destination: &str,
+    next_hop: &str,
+    via_uplink: bool,
+    storage: &Storage,
+) -> Result<(IpNet, Option<IpAddr>)> {
+    let dest: IpNet = destination
+        .parse()
+        .map_err(|_| ValidationError::InvalidRouteDestination(destination.to_string()))?;
+    let dest = dest.trunc();
+
+    match dest {
+        IpNet::V4(net) => {
+            if let Some(subnet) = &network.ipv4_subnet
+                && net.prefix_len() > 0
+                && ipv4_subnets_overlap(&net, subnet)
+            {
+                return Err(ValidationError::RouteOverlapsNetwork(
+                    net.to_string(),
+                    subnet.to_string(),
+                ));
+            }
+        }
+        IpNet::V6(net) => {
+            if let Some(prefix) = &network.ipv6_prefix
+                && net.prefix_len() > 0
+                && ipv6_prefixes_overlap(&net, prefix)
+            {
+                return Err(ValidationError::RouteOverlapsNetwork(
+                    net.to_string(),
+                    prefix.to_string(),
+                ));
+            }
+        }
+    }
+
+    if network.is_public && dest.prefix_len() == 0 {
+        return Err(ValidationError::DefaultRouteOnPublicNetwork);
+    }
+
+    if via_uplink == !next_hop.is_empty() {
+        return Err(ValidationError::NextHopRequired);
+    }
+
+    if via_uplink {
+        if !network.is_public {
+            return Err(ValidationError::UplinkRequiresPublicNetwork);
+        }
+        return Ok((dest, None));
+    }
+
+    let hop: IpAddr = next_hop
+        .parse()
+        .map_err(|_| ValidationError::InvalidNextHop(next_hop.to_string()))?;
+
+    let in_use = match (hop, dest) {
+        (IpAddr::V4(addr), IpNet::V4(_)) => storage.is_ipv4_in_use(&network.id, addr),
+        (IpAddr::V6(addr), IpNet::V6(_)) => storage.is_ipv6_in_use(&network.id, addr),
+        _ => {
+            return Err(ValidationError::NextHopFamilyMismatch(
+                hop.to_string(),
+                dest.to_string(),
+            ));
+        }
+    };
+    if !in_use? {
+        return Err(ValidationError::NextHopNotInNetwork(hop.to_string()));
+    }
+
+    Ok((dest, Some(hop)))
+}
+
+/// Validate a static DHCP lease request for a NIC's DHCP server.
+///
+/// Returns the MAC and, if one was requested, the address; the caller
+/// allocates one otherwise.
+pub fn validate_create_lease(
+    network: &NetworkData,
+    nic: &NicData,
+    mac_address: &str,
+    ipv4_address: &str,
+    storage: &Storage,
+) -> Result<([u8; 6], Option<Ipv4Addr>)> {
+    let subnet = match network.ipv4_subnet {
+        Some(subnet) if network.ipv4_enabled => subnet,
+        _ => return Err(ValidationError::LeaseRequiresIpv4),
+    };
+
+    let mac = parse_mac(mac_address)?;
+    if mac == nic.mac_address {
+        return Err(ValidationError::LeaseMacIsNic(mac_address.to_string()));
+    }
+
+    if ipv4_address.is_empty() {
+        return Ok((mac, None));
+    }
+    let addr: Ipv4Addr = ipv4_address
+        .parse()
+        .map_err(|_| ValidationError::InvalidIpv4Address(ipv4_address.to_string()))?;
+    if !subnet.contains(&addr) {
+        return Err(ValidationError::Ipv4NotInSubnet(
+            addr.to_string(),
+            subnet.to_string(),
+        ));
+    }
+    if let Some(reason) = reserved_ipv4(network, addr) {
+        return Err(ValidationError::Ipv4AddressReserved(
+            addr.to_string(),
+            reason,
+        ));
+    }
+    if storage.is_ipv4_in_use(&network.id, addr).unwrap_or(false)
+        || storage.is_ipv4_leased(&network.id, addr).unwrap_or(false)
+    {
+        return Err(ValidationError::Ipv4AddressInUse(addr.to_string()));
+    }
+
+    Ok((mac, Some(addr)))
+}
+
+/// Validated port forward: protocol, host address, host port and VM port.
+pub type ValidatedPortForward = (RuleProtocol, Ipv4Addr, u16, u16);
+
+/// Validate a port forward to a NIC. A `vm_port` of 0 forwards to the
+/// host port. Whether the host port is still free is left to the store.
+pub fn validate_create_port_forward(
+    nic: &NicData,
+    protocol: i32,
+    host_ip: &str,
+    host_port: u32,
+    vm_port: u32,
+    storage: &Storage,
+) -> Result<ValidatedPortForward> {
+    if nic.ipv4_address.is_none() {
+        return Err(ValidationError::PortForwardRequiresIpv4);
+    }
+
+    let proto = RuleProtocol::from(protocol);
+    if !matches!(proto, RuleProtocol::Tcp | RuleProtocol::Udp) {
+        return Err(ValidationError::InvalidPortForwardProtocol);
+    }
+
+    let addr: Ipv4Addr = host_ip
+        .parse()
+        .map_err(|_| ValidationError::InvalidIpv4Address(host_ip.to_string()))?;
+    if addr.is_unspecified()
+        || addr.is_loopback()
+        || addr.is_link_local()
+        || addr.is_multicast()
+        || addr.is_broadcast()
+    {
+        return Err(ValidationError::InvalidHostAddress(host_ip.to_string()));
+    }
+    check_outside_public_networks(addr, storage)?;
+    // All ports of a floating IP go to its NIC
+    if storage
+        .get_floating_ip_by_address(addr)
+        .ok()
+        .flatten()
+        .is_some()
+    {
+        return Err(ValidationError::HostAddressIsFloatingIp(addr.to_string()));
+    }
+
+    if host_port == 0 || host_port > 65535 {
+        return Err(ValidationError::PortOutOfRange(host_port));
+    }
+    if vm_port > 65535 {
+        return Err(ValidationError::PortOutOfRange(vm_port));
+    }
+    let vm_port = if vm_port == 0 { host_port } else { vm_port };
+
+    Ok((proto, addr, host_port as u16, vm_port as u16))
+}
+
+/// Public subnets are routed into the uplink as a whole, so host addresses
+/// of port forwards and floating IPs must lie outside them.
+fn check_outside_public_networks(addr: Ipv4Addr, storage: &Storage) -> Result<()> {
+    for network in storage.list_public_networks().unwrap_or_default() {
+        if let Some(subnet) = network.ipv4_subnet
+            && subnet.contains(&addr)
+        {
+            return Err(ValidationError::HostAddressInNetwork(
+                addr.to_string(),
+                network.name,
+                subnet.to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Why `addr` can't become a floating IP, if it can't: it must be in the
+/// pool, outside public networks and not forwarded port by port.
+fn floating_ip_conflict(addr: Ipv4Addr, pool: &[Ipv4Net], storage: &Storage) -> Result<()> {
+    if !pool.iter().any(|net| net.contains(&addr)) {
+        return Err(ValidationError::AddressNotInFloatingIpPool(
+            addr.to_string(),
+        ));
+    }
+    check_outside_public_networks(addr, storage)?;
+    let forwarded = storage
+        .list_port_forwards()
+        .unwrap_or_default()
+        .iter()
+        .any(|f| f.host_ip == addr);
+    if forwarded {
+        return Err(ValidationError::AddressForwarded(addr.to_string()));
+    }
+    Ok(())
+}
+
+/// Validate a requested floating IP address.
+pub fn validate_floating_ip_address(
+    address: &str,
+    pool: &[Ipv4Net],
+    storage: &Storage,
+) -> Result<Ipv4Addr> {
+    let addr: Ipv4Addr = address
+        .parse()
+        .map_err(|_| ValidationError::InvalidIpv4Address(address.to_string()))?;
+    floating_ip_conflict(addr, pool, storage)?;
+    Ok(addr)
+}
+
+/// Allocate the next free address of the floating IP pool. The network
+/// and broadcast addresses of pool prefixes shorter than /31 are skipped.
+pub fn allocate_floating_ip(pool: &[Ipv4Net], storage: &Storage) -> Option<Ipv4Addr> {
+    let mut taken: HashSet<Ipv4Addr> = storage
+        .list_floating_ips()
+        .ok()?
+        .into_iter()
+        .map(|f| f.address)
+        .collect();
+    taken.extend(storage.list_port_forwards().ok()?.iter().map(|f| f.host_ip));
+
+    pool.iter()
+        .flat_map(|net| net.hosts())
+        .filter(|addr| !taken.contains(addr))
+        .find(|addr| check_outside_public_networks(*addr, storage).is_ok())
+}
+
+/// Validate that a floating IP can be associated with `nic`.
+pub fn validate_associate_floating_ip(nic: &NicData) -> Result<()> {
+    if nic.ipv4_address.is_none() {
+        return Err(ValidationError::FloatingIpRequiresIpv4);
+    }
+    Ok(())
+}
+
+/// Validate connecting two networks: they must be distinct and must not
+/// overlap, and every allowed prefix must lie in one of them. Returns the
+/// parsed prefixes.
+pub fn validate_connect_networks(
+    a: &NetworkData,
+    b: &NetworkData,
+    allowed_prefixes: &[String],
+) -> Result<Vec<IpNet>> {
+    if a.id == b.id {
+        return Err(ValidationError::ConnectSameNetwork);
+    }
+    let overlap = match (a.ipv4_subnet, b.ipv4_subnet) {
+        (Some(x), Some(y)) => ipv4_subnets_overlap(&x, &y),
+        _ => false,
+    } || match (a.ipv6_prefix, b.ipv6_prefix) {
+        (Some(x), Some(y)) => ipv6_prefixes_overlap(&x, &y),
+        _ => false,
+    };
+    if overlap {
+        return Err(ValidationError::ConnectedNetworksOverlap(
+            a.name.clone(),
+            b.name.clone(),
+        ));
+    }
+
+    let in_network = |prefix: &IpNet, network: &NetworkData| match prefix {
+        IpNet::V4(p) => network
+            .ipv4_subnet
+            .is_some_and(|s| ipv4_subnets_overlap(p, &s)),
+        IpNet::V6(p) => network
+            .ipv6_prefix
+            .is_some_and(|s| ipv6_prefixes_overlap(p, &s)),
+    };
+    allowed_prefixes
+        .iter()
+        .map(|s| {
+            let prefix: IpNet = s
+                .parse()
+                .map_err(|_| ValidationError::InvalidCidr(s.clone()))?;
+            let prefix = prefix.trunc();
+            if !in_network(&prefix, a) && !in_network(&prefix, b) {
+                return Err(ValidationError::AllowedPrefixOutsideNetworks(
+                    prefix.to_string(),
+                ));
+            }
+            Ok(prefix)
+        })
+        .collect()
+}
+
 /// Parse MAC address string.
 fn parse_mac(s: &str) -> Result<[u8; 6]> {
     let parts: Vec<&str> = s.split(':').collect();
@@ -312,22 +760,56 @@ fn parse_mac(s: &str) -> Result<[u8; 6]> {
     Ok(mac)
 }
 
+/// Why an IPv4 address of a network can't go to a NIC or lease, if it
+/// can't: the subnet's network and broadcast addresses, its gateway address
+/// and the link-local gateway the guests actually route through.
+pub fn reserved_ipv4(network: &NetworkData, addr: Ipv4Addr) -> Option<&'static str> {
+    if addr == GATEWAY_IPV4_LINK_LOCAL {
+        return Some("link-local gateway");
+    }
+    let subnet = network.ipv4_subnet?;
+    if addr == subnet.network() {
+        Some("network address")
+    } else if addr == subnet.broadcast() {
+        Some("broadcast address")
+    } else if Some(addr) == network.ipv4_gateway() {
+        Some("gateway address")
+    } else {
+        None
+    }
+}
+
+/// Why an IPv6 address of a network can't go to a NIC, if it can't: the
+/// prefix's subnet-router anycast address, its gateway address (answered by
+/// the reactors) and the link-local gateway.
+pub fn reserved_ipv6(network: &NetworkData, addr: Ipv6Addr) -> Option<&'static str> {
+    if addr == GATEWAY_IPV6_LINK_LOCAL {
+        return Some("link-local gateway");
+    }
+    let prefix = network.ipv6_prefix?;
+    if addr == prefix.network() {
+        Some("network address")
+    } else if Some(addr) == network.ipv6_gateway() {
+        Some("gateway address")
+    } else {
+        None
+    }
+}
+
 /// Allocate the next available IPv4 address in a network.
 ///
-/// Starts at network + 1 (e.g., 10.0.0.1 for 10.0.0.0/24) since the gateway
-/// uses a link-local address (169.254.0.1) instead of an address from the subnet.
+/// Starts at network + 2 (e.g., 10.0.0.2 for 10.0.0.0/24) and skips every
+/// address [`reserved_ipv4`] refuses.
 pub fn allocate_ipv4_address(network: &NetworkData, storage: &Storage) -> Option<Ipv4Addr> {
     let subnet = network.ipv4_subnet?;
     let used = storage.get_used_ipv4_addresses(&network.id).ok()?;
 
-    // Start from network + 1 (skip only network address)
-    // Gateway uses link-local 169.254.0.1, not an address from this subnet
     let network_addr = u32::from(subnet.network());
     let broadcast_addr = u32::from(subnet.broadcast());
 
-    for addr_int in (network_addr + 1)..broadcast_addr {
+    for addr_int in network_addr..=broadcast_addr {
         let addr = Ipv4Addr::from(addr_int);
-        if !used.contains(&addr) {
+        if reserved_ipv4(network, addr).is_none() && !used.contains(&addr) {
             return Some(addr);
         }
     }
@@ -337,20 +819,21 @@ pub fn allocate_ipv4_address(network: &NetworkData, storage: &Storage) -> Option
 
 /// Allocate the next available IPv6 address in a network.
 ///
-/// Starts at prefix + 1 (e.g., 2001:db8::1 for 2001:db8::/64) since the gateway
-/// uses a link-local address (fe80::1) instead of an address from the prefix.
+/// Starts at prefix + 2 (e.g., 2001:db8::2 for 2001:db8::/64) and skips
+/// every address [`reserved_ipv6`] refuses.
 pub fn allocate_ipv6_address(network: &NetworkData, storage: &Storage) -> Option<Ipv6Addr> {
     let prefix = network.ipv6_prefix?;
     let used = storage.get_used_ipv6_addresses(&network.id).ok()?;
 
-    // Start from prefix + 1 (skip only :: network address)
-    // Gateway uses link-local fe80::1, not an address from this prefix
     let network_addr = u128::from(prefix.network());
 
     // Limit search to first 65536 addresses
-    for offset in 1u128..65536 {
+    for offset in 0u128..65536 {
         let addr = Ipv6Addr::from(network_addr + offset);
-        if !used.contains(&addr) {
+        if !prefix.contains(&addr) {
+            break;
+        }
+        if reserved_ipv6(network, addr).is_none() && !used.contains(&addr) {
             return Some(addr);
         }
     }
@@ -358,6 +841,263 @@ pub fn allocate_ipv6_address(network: &NetworkData, storage: &Storage) -> Option
     None
 }
 
+/// A NIC or lease address moved off a reserved address.
+#[derive(Debug, Clone)]
+pub struct Reallocation {
+    /// NIC owning the address, or the NIC a lease is served behind
+    pub nic_id: Uuid,
+    /// Set for lease addresses
+    pub lease_id: Option<Uuid>,
+    pub network_id: Uuid,
+    pub old: IpAddr,
+    /// `None` if the network had no address left; the old one stays
+    pub new: Option<IpAddr>,
+    /// What the old address is reserved as
+    pub reason: &'static str,
+}
+
+/// Move NICs and leases holding reserved addresses (allocated before those
+/// were refused) to free ones. Runs at startup, before NIC reactors come up
+/// with the stored addresses; returns what was moved for the report.
+pub fn reallocate_reserved_addresses(
+    storage: &Storage,
+) -> std::result::Result<Vec<Reallocation>, StorageError> {
+    let mut moved = Vec::new();
+    for network in storage.list_networks()? {
+        for nic in storage.list_nics_in_network(&network.id)? {
+            let mut ipv4 = nic.ipv4_address;
+            let mut ipv6 = nic.ipv6_address;
+            if let Some(old) = nic.ipv4_address
+                && let Some(reason) = reserved_ipv4(&network, old)
+            {
+                let new = allocate_ipv4_address(&network, storage);
+                if new.is_some() {
+                    ipv4 = new;
+                }
+                moved.push(Reallocation {
+                    nic_id: nic.id,
+                    lease_id: None,
+                    network_id: network.id,
+                    old: IpAddr::V4(old),
+                    new: new.map(IpAddr::V4),
+                    reason,
+                });
+            }
+            if let Some(old) = nic.ipv6_address
+                && let Some(reason) = reserved_ipv6(&network, old)
+            {
+                let new = allocate_ipv6_address(&network, storage);
+                if new.is_some() {
+                    ipv6 = new;
+                }
+                moved.push(Reallocation {
+                    nic_id: nic.id,
+                    lease_id: None,
+                    network_id: network.id,
+                    old: IpAddr::V6(old),
+                    new: new.map(IpAddr::V6),
+                    reason,
+                });
+            }
+            if ipv4 != nic.ipv4_address || ipv6 != nic.ipv6_address {
+                storage.update_nic_addresses(&nic.id, ipv4, ipv6)?;
+            }
+        }
+        for lease in storage.list_leases_in_network(&network.id)? {
+            let Some(reason) = reserved_ipv4(&network, lease.ipv4_address) else {
+                continue;
+            };
+            let new = allocate_ipv4_address(&network, storage);
+            if let Some(addr) = new {
+                storage.update_lease_address(&lease.id, addr)?;
+            }
+            moved.push(Reallocation {
+                nic_id: lease.nic_id,
+                lease_id: Some(lease.id),
+                network_id: network.id,
+                old: IpAddr::V4(lease.ipv4_address),
+                new: new.map(IpAddr::V4),
+                reason,
+            });
+        }
+    }
+    for r in &moved {
+        warn!(
+            nic_id = %r.nic_id,
+            lease_id = ?r.lease_id,
+            network_id = %r.network_id,
+            old = %r.old,
+            new = ?r.new,
+            reason = r.reason,
+            "Address reserved by IPAM was allocated"
+        );
+    }
+    Ok(moved)
+}
+
+/// Validate the length of a prefix to delegate to a new NIC; 0 delegates
+/// none. Delegated prefixes come out of the network prefix, so they have to
+/// be longer than it, and no longer than a /64 so the guest can still run
+/// SLAAC behind it.
+pub fn validate_delegated_prefix_len(network: &NetworkData, len: u32) -> Result<Option<u8>> {
+    if len == 0 {
+        return Ok(None);
+    }
+    let prefix = match network.ipv6_prefix {
+        Some(prefix) if network.ipv6_enabled => prefix,
+        _ => return Err(ValidationError::DelegationRequiresIpv6),
+    };
+    if len <= u32::from(prefix.prefix_len()) || len > 64 {
+        return Err(ValidationError::InvalidDelegatedPrefixLen(
+            len,
+            prefix.to_string(),
+        ));
+    }
+    Ok(Some(len as u8))
+}
+
+/// Validate the requested ownership of a NIC's socket.
+pub fn validate_socket_access(access: &SocketAccess) -> Result<()> {
+    match access.mode {
+        Some(mode) if mode > 0o7777 => Err(ValidationError::InvalidSocketMode(mode)),
+        _ => Ok(()),
+    }
+}
+
+/// Allocate the next free prefix of length `len` in a network's IPv6 prefix
+/// for delegation.
+///
+/// The first prefix of that length is never delegated since NIC addresses
+/// are allocated from it; nor are prefixes holding a NIC address or
+/// overlapping another delegation.
+pub fn allocate_delegated_ipv6_prefix(
+    network: &NetworkData,
+    len: u8,
+    storage: &Storage,
+) -> Option<Ipv6Net> {
+    let prefix = network.ipv6_prefix?;
+    let used = storage.get_used_ipv6_addresses(&network.id).ok()?;
+    let delegated = storage.get_delegated_ipv6_prefixes(&network.id).ok()?;
+
+    // Limit search to the first 65536 prefixes
+    prefix
+        .subnets(len)
+        .ok()?
+        .skip(1)
+        .take(65536)
+        .find(|subnet| {
+            !used.iter().any(|addr| subnet.contains(addr))
+                && !delegated.iter().any(|d| ipv6_prefixes_overlap(subnet, d))
+        })
+}
+
+// ========== Security Group Validation ==========
+
+/// Validate security group creation request.
+pub fn validate_create_security_group(name: &str) -> Result<()> {
+    naming::validate_name("Security group", name)?;
+    Ok(())
+}
+
+/// Validated security group rule fields: direction, protocol and port range.
+pub type ValidatedRule = (RuleDirection, RuleProtocol, Option<u16>, Option<u16>);
+
+/// Validate security group rule creation request. Accepts the same rules
+/// as mvirt-ebpf.
+pub fn validate_security_group_rule(
+    direction: i32,
+    protocol: i32,
+    port_start: u32,
+    port_end: u32,
+    cidr: &str,
+) -> Result<ValidatedRule> {
+    let dir = RuleDirection::from(direction);
+    if matches!(dir, RuleDirection::Unspecified) {
+        return Err(ValidationError::InvalidRuleDirection);
+    }
+
+    let proto = RuleProtocol::from(protocol);
+    if matches!(proto, RuleProtocol::Unspecified) {
+        return Err(ValidationError::InvalidRuleProtocol);
+    }
+
+    let (parsed_port_start, parsed_port_end) = if port_start > 0 || port_end > 0 {
+        // Ports are only valid for TCP/UDP
+        if !matches!(
+            proto,
+            RuleProtocol::Tcp | RuleProtocol::Udp | RuleProtocol::All
+        ) {
+            return Err(ValidationError::PortsNotAllowedForProtocol(
+                proto.as_str().to_string(),
+            ));
+        }
+        if port_start > 65535 {
+            return Err(ValidationError::PortOutOfRange(port_start));
+        }
+        if port_end > 65535 {
+            return Err(ValidationError::PortOutOfRange(port_end));
+        }
+
+        let start = if port_start > 0 { port_start as u16 } else { 1 };
+        let end = if port_end > 0 { port_end as u16 } else { start };
+        if start > end {
+            return Err(ValidationError::InvalidPortRange(start as u32, end as u32));
+        }
+        (Some(start), Some(end))
+    } else {
+        (None, None)
+    };
+
+    if !cidr.is_empty() {
+        cidr.parse::<IpNet>()
+            .map_err(|_| ValidationError::InvalidCidr(cidr.to_string()))?;
+    }
+
+    Ok((dir, proto, parsed_port_start, parsed_port_end))
+}
+
+/// Validated rule validity window and schedule.
+pub type ValidatedWindow = (
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    Option<RuleSchedule>,
+);
+
+/// Validate a rule's validity window and schedule; empty strings leave
+/// that part unrestricted.
+pub fn validate_rule_window(
+    not_before: &str,
+    not_after: &str,
+    schedule: &str,
+) -> Result<ValidatedWindow> {
+    let parse_time = |s: &str| {
+        if s.is_empty() {
+            return Ok(None);
+        }
+        DateTime::parse_from_rfc3339(s)
+            .map(|t| Some(t.with_timezone(&Utc)))
+            .map_err(|_| ValidationError::InvalidRuleTime(s.to_string()))
+    };
+    let not_before = parse_time(not_before)?;
+    let not_after = parse_time(not_after)?;
+    if let (Some(from), Some(until)) = (not_before, not_after)
+        && until <= from
+    {
+        return Err(ValidationError::EmptyRuleWindow);
+    }
+
+    let schedule =
+        if schedule.is_empty() {
+            None
+        } else {
+            Some(schedule.parse().map_err(|e| {
+                ValidationError::InvalidRuleSchedule(format!("{}: {}", schedule, e))
+            })?)
+        };
+
+    Ok((not_before, not_after, schedule))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -377,6 +1117,67 @@ mod tests {
         assert!(!ipv4_subnets_overlap(&e, &f));
     }
 
+    #[test]
+    fn test_validate_connect_networks() {
+        use crate::grpc::storage::NetworkData;
+        use chrono::Utc;
+        use uuid::Uuid;
+
+        let network = |name: &str, subnet: &str, prefix: &str| NetworkData {
+            id: Uuid::new_v4(),
+            name: name.to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some(subnet.parse().unwrap()),
+            ipv6_enabled: true,
+            ipv6_prefix: Some(prefix.parse().unwrap()),
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        let a = network("tenant-a", "10.0.0.0/24", "fd00:a::/64");
+        let b = network("tenant-b", "10.0.1.0/24", "fd00:b::/64");
+
+        assert!(validate_connect_networks(&a, &b, &[]).unwrap().is_empty());
+        let allowed = validate_connect_networks(
+            &a,
+            &b,
+            &["10.0.1.17/28".to_string(), "fd00:a::/80".to_string()],
+        )
+        .unwrap();
+        assert_eq!(
+            allowed,
+            vec![
+                "10.0.1.16/28".parse::<IpNet>().unwrap(),
+                "fd00:a::/80".parse().unwrap()
+            ]
+        );
+
+        assert!(matches!(
+            validate_connect_networks(&a, &a, &[]),
+            Err(ValidationError::ConnectSameNetwork)
+        ));
+        assert!(matches!(
+            validate_connect_networks(&a, &b, &["10.0.2.0/24".to_string()]),
+            Err(ValidationError::AllowedPrefixOutsideNetworks(_))
+        ));
+        assert!(matches!(
+            validate_connect_networks(&a, &b, &["10.0.1.0".to_string()]),
+            Err(ValidationError::InvalidCidr(_))
+        ));
+
+        let c = network("tenant-c", "10.0.0.128/25", "fd00:c::/64");
+        assert!(matches!(
+            validate_connect_networks(&a, &c, &[]),
+            Err(ValidationError::ConnectedNetworksOverlap(_, _))
+        ));
+    }
+
     #[test]
     fn test_parse_mac() {
         let mac = parse_mac("02:00:00:00:00:01").unwrap();
@@ -387,6 +1188,391 @@ mod tests {
         assert!(parse_mac("02:00:00:00:00:xx").is_err());
     }
 
+    #[test]
+    fn test_validate_add_route() {
+        use crate::grpc::storage::{NetworkData, NicData, NicState, Storage};
+        use chrono::Utc;
+        use uuid::Uuid;
+
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-routes".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+            ipv4_address: Some("10.0.0.5".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-test.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: Default::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+
+        let (dest, hop) =
+            validate_add_route(&network, "192.168.10.1/24", "10.0.0.5", false, &storage).unwrap();
+        assert_eq!(dest, "192.168.10.0/24".parse::<IpNet>().unwrap());
+        assert_eq!(hop, Some("10.0.0.5".parse().unwrap()));
+
+        // Next hop must belong to a NIC in the network
+        assert!(matches!(
+            validate_add_route(&network, "192.168.10.0/24", "10.0.0.6", false, &storage),
+            Err(ValidationError::NextHopNotInNetwork(_))
+        ));
+
+        // Destination must not shadow the network's own subnet
+        assert!(matches!(
+            validate_add_route(&network, "10.0.0.0/25", "10.0.0.5", false, &storage),
+            Err(ValidationError::RouteOverlapsNetwork(_, _))
+        ));
+
+        // Uplink is reserved for public networks
+        assert!(matches!(
+            validate_add_route(&network, "192.168.10.0/24", "", true, &storage),
+            Err(ValidationError::UplinkRequiresPublicNetwork)
+        ));
+
+        assert!(matches!(
+            validate_add_route(&network, "192.168.10.0/24", "", false, &storage),
+            Err(ValidationError::NextHopRequired)
+        ));
+    }
+
+    #[test]
+    fn test_validate_create_lease() {
+        use crate::grpc::storage::{LeaseData, NetworkData, NicData, NicState, Storage};
+        use chrono::Utc;
+        use uuid::Uuid;
+
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-leases".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+            ipv4_address: Some("10.0.0.5".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-test.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: Default::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+
+        let (mac, addr) =
+            validate_create_lease(&network, &nic, "02:00:00:00:01:01", "10.0.0.20", &storage)
+                .unwrap();
+        assert_eq!(mac, [0x02, 0x00, 0x00, 0x00, 0x01, 0x01]);
+        assert_eq!(addr, Some("10.0.0.20".parse().unwrap()));
+
+        // Without an address the caller allocates one
+        let (_, addr) =
+            validate_create_lease(&network, &nic, "02:00:00:00:01:01", "", &storage).unwrap();
+        assert_eq!(addr, None);
+
+        // The NIC's own MAC is answered without a lease
+        assert!(matches!(
+            validate_create_lease(&network, &nic, "02:00:00:00:00:01", "", &storage),
+            Err(ValidationError::LeaseMacIsNic(_))
+        ));
+
+        assert!(matches!(
+            validate_create_lease(&network, &nic, "02:00:00:00:01:01", "10.0.1.20", &storage),
+            Err(ValidationError::Ipv4NotInSubnet(_, _))
+        ));
+        assert!(matches!(
+            validate_create_lease(&network, &nic, "02:00:00:00:01:01", "10.0.0.5", &storage),
+            Err(ValidationError::Ipv4AddressInUse(_))
+        ));
+
+        // Leased addresses are taken for NICs and other leases
+        storage
+            .create_lease(&LeaseData {
+                id: Uuid::new_v4(),
+                network_id: network.id,
+                nic_id: nic.id,
+                mac_address: [0x02, 0x00, 0x00, 0x00, 0x01, 0x01],
+                ipv4_address: "10.0.0.20".parse().unwrap(),
+                created_at: Utc::now(),
+            })
+            .unwrap();
+        assert!(matches!(
+            validate_create_lease(&network, &nic, "02:00:00:00:01:02", "10.0.0.20", &storage),
+            Err(ValidationError::Ipv4AddressInUse(_))
+        ));
+        assert!(matches!(
+            validate_create_nic(&network, "", "10.0.0.20", "", &[], &[], &storage),
+            Err(ValidationError::Ipv4AddressInUse(_))
+        ));
+    }
+
+    #[test]
+    fn test_validate_create_port_forward() {
+        use crate::grpc::storage::{NetworkData, NicData, NicState, Storage};
+        use chrono::Utc;
+        use uuid::Uuid;
+
+        let storage = Storage::in_memory().unwrap();
+
+        let public = NetworkData {
+            id: Uuid::new_v4(),
+            name: "uplink".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("198.51.100.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&public).unwrap();
+
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: Uuid::new_v4(),
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+            ipv4_address: Some("10.0.0.5".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-test.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: Default::default(),
+        };
+        let tcp = RuleProtocol::Tcp as i32;
+
+        let (proto, addr, host_port, vm_port) =
+            validate_create_port_forward(&nic, tcp, "203.0.113.10", 8080, 80, &storage).unwrap();
+        assert_eq!(proto, RuleProtocol::Tcp);
+        assert_eq!(addr, "203.0.113.10".parse::<Ipv4Addr>().unwrap());
+        assert_eq!((host_port, vm_port), (8080, 80));
+
+        // Without a VM port the host port is kept
+        let (_, _, _, vm_port) =
+            validate_create_port_forward(&nic, tcp, "203.0.113.10", 8080, 0, &storage).unwrap();
+        assert_eq!(vm_port, 8080);
+
+        assert!(matches!(
+            validate_create_port_forward(
+                &nic,
+                RuleProtocol::Icmp as i32,
+                "203.0.113.10",
+                8080,
+                0,
+                &storage
+            ),
+            Err(ValidationError::InvalidPortForwardProtocol)
+        ));
+        assert!(matches!(
+            validate_create_port_forward(&nic, tcp, "127.0.0.1", 8080, 0, &storage),
+            Err(ValidationError::InvalidHostAddress(_))
+        ));
+        assert!(matches!(
+            validate_create_port_forward(&nic, tcp, "198.51.100.20", 8080, 0, &storage),
+            Err(ValidationError::HostAddressInNetwork(_, _, _))
+        ));
+        assert!(matches!(
+            validate_create_port_forward(&nic, tcp, "203.0.113.10", 0, 80, &storage),
+            Err(ValidationError::PortOutOfRange(0))
+        ));
+        assert!(matches!(
+            validate_create_port_forward(&nic, tcp, "203.0.113.10", 8080, 70000, &storage),
+            Err(ValidationError::PortOutOfRange(70000))
+        ));
+
+        let ipv6_only = NicData {
+            ipv4_address: None,
+            ..nic.clone()
+        };
+        assert!(matches!(
+            validate_create_port_forward(&ipv6_only, tcp, "203.0.113.10", 8080, 0, &storage),
+            Err(ValidationError::PortForwardRequiresIpv4)
+        ));
+    }
+
+    #[test]
+    fn test_floating_ips() {
+        use crate::grpc::storage::{
+            FloatingIpData, NetworkData, NicData, NicState, PortForwardData, Storage,
+        };
+        use chrono::Utc;
+        use uuid::Uuid;
+
+        let storage = Storage::in_memory().unwrap();
+
+        let public = NetworkData {
+            id: Uuid::new_v4(),
+            name: "uplink".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("198.51.100.0/24".parse().unwrap()),
+            ipv6_enabled: false,
+            ipv6_prefix: None,
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: true,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&public).unwrap();
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: public.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+            ipv4_address: Some("198.51.100.5".parse().unwrap()),
+            ipv6_address: None,
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-test.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: Default::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+        storage
+            .create_port_forward(&PortForwardData {
+                id: Uuid::new_v4(),
+                nic_id: nic.id,
+                protocol: RuleProtocol::Tcp,
+                host_ip: "203.0.113.17".parse().unwrap(),
+                host_port: 8080,
+                vm_port: 80,
+                created_at: Utc::now(),
+            })
+            .unwrap();
+
+        let pool: Vec<Ipv4Net> = vec![
+            "198.51.100.8/31".parse().unwrap(),
+            "203.0.113.16/30".parse().unwrap(),
+        ];
+
+        assert_eq!(
+            validate_floating_ip_address("203.0.113.18", &pool, &storage).unwrap(),
+            "203.0.113.18".parse::<Ipv4Addr>().unwrap()
+        );
+        assert!(matches!(
+            validate_floating_ip_address("203.0.113.30", &pool, &storage),
+            Err(ValidationError::AddressNotInFloatingIpPool(_))
+        ));
+        assert!(matches!(
+            validate_floating_ip_address("198.51.100.8", &pool, &storage),
+            Err(ValidationError::HostAddressInNetwork(_, _, _))
+        ));
+        assert!(matches!(
+            validate_floating_ip_address("203.0.113.17", &pool, &storage),
+            Err(ValidationError::AddressForwarded(_))
+        ));
+
+        // Past the public network, the network address and the forwarded one
+        let first = allocate_floating_ip(&pool, &storage).unwrap();
+        assert_eq!(first, "203.0.113.18".parse::<Ipv4Addr>().unwrap());
+        storage
+            .create_floating_ip(&FloatingIpData {
+                id: Uuid::new_v4(),
+                address: first,
+                nic_id: None,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .unwrap();
+        assert_eq!(allocate_floating_ip(&pool, &storage), None);
+
+        // Its ports can't be forwarded one by one
+        assert!(matches!(
+            validate_create_port_forward(
+                &nic,
+                RuleProtocol::Tcp as i32,
+                "203.0.113.18",
+                22,
+                0,
+                &storage
+            ),
+            Err(ValidationError::HostAddressIsFloatingIp(_))
+        ));
+
+        assert!(validate_associate_floating_ip(&nic).is_ok());
+        let ipv6_only = NicData {
+            ipv4_address: None,
+            ..nic
+        };
+        assert!(matches!(
+            validate_associate_floating_ip(&ipv6_only),
+            Err(ValidationError::FloatingIpRequiresIpv4)
+        ));
+    }
+
     #[test]
     fn test_allocate_ipv4_starts_at_first_usable() {
         use crate::grpc::storage::{NetworkData, Storage};
@@ -408,16 +1594,19 @@ mod tests {
             is_public: false,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
         };
         storage.create_network(&network).unwrap();
 
-        // First allocated IP should be 10.0.0.1 (only skip .0 network address)
-        // Gateway uses link-local 169.254.0.1, not an address from this subnet
+        // First allocated IP should be 10.0.0.2 (.0 is the network, .1 the gateway)
         let first_ip = allocate_ipv4_address(&network, &storage).unwrap();
         assert_eq!(
             first_ip,
-            Ipv4Addr::new(10, 0, 0, 1),
-            "First IPv4 should be .1 (gateway uses link-local 169.254.0.1)"
+            Ipv4Addr::new(10, 0, 0, 2),
+            "First IPv4 should be .2 (.1 is reserved for the gateway)"
         );
     }
 
@@ -443,16 +1632,203 @@ mod tests {
             is_public: false,
             created_at: Utc::now(),
             updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
         };
         storage.create_network(&network).unwrap();
 
-        // First allocated IP should be 2001:db8::1 (only skip :: network address)
-        // Gateway uses link-local fe80::1, not an address from this prefix
+        // First allocated IP should be 2001:db8::2 (:: is the network, ::1 the gateway)
         let first_ip = allocate_ipv6_address(&network, &storage).unwrap();
         assert_eq!(
             first_ip,
-            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1),
-            "First IPv6 should be ::1 (gateway uses link-local fe80::1)"
+            Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2),
+            "First IPv6 should be ::2 (::1 is reserved for the gateway)"
+        );
+    }
+
+    #[test]
+    fn test_reserved_addresses() {
+        use crate::grpc::storage::{LeaseData, NetworkData, NicData, NicState, Storage};
+        use chrono::Utc;
+
+        let storage = Storage::in_memory().unwrap();
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-reserved".to_string(),
+            ipv4_enabled: true,
+            ipv4_subnet: Some("10.0.0.0/24".parse().unwrap()),
+            ipv6_enabled: true,
+            ipv6_prefix: Some("2001:db8::/64".parse().unwrap()),
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        for addr in ["10.0.0.0", "10.0.0.1", "10.0.0.255"] {
+            assert!(matches!(
+                validate_create_nic(&network, "", addr, "", &[], &[], &storage),
+                Err(ValidationError::Ipv4AddressReserved(_, _))
+            ));
+        }
+        for addr in ["2001:db8::", "2001:db8::1"] {
+            assert!(matches!(
+                validate_create_nic(&network, "", "", addr, &[], &[], &storage),
+                Err(ValidationError::Ipv6AddressReserved(_, _))
+            ));
+        }
+        assert_eq!(
+            reserved_ipv4(&network, GATEWAY_IPV4_LINK_LOCAL),
+            Some("link-local gateway")
         );
+        assert_eq!(reserved_ipv4(&network, "10.0.0.7".parse().unwrap()), None);
+
+        // Addresses handed out before they were reserved are moved
+        let nic = NicData {
+            id: Uuid::new_v4(),
+            name: None,
+            network_id: network.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+            ipv4_address: Some("10.0.0.1".parse().unwrap()),
+            ipv6_address: Some("2001:db8::1".parse().unwrap()),
+            routed_ipv4_prefixes: vec![],
+            routed_ipv6_prefixes: vec![],
+            socket_path: "/run/mvirt/net/nic-reserved.sock".to_string(),
+            state: NicState::Created,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            owner: None,
+            link_up: true,
+            delegated_ipv6_prefix: None,
+            socket_access: Default::default(),
+        };
+        storage.create_nic(&nic).unwrap();
+        let lease = LeaseData {
+            id: Uuid::new_v4(),
+            network_id: network.id,
+            nic_id: nic.id,
+            mac_address: [0x02, 0x00, 0x00, 0x00, 0x01, 0x01],
+            ipv4_address: "10.0.0.255".parse().unwrap(),
+            created_at: Utc::now(),
+        };
+        storage.create_lease(&lease).unwrap();
+
+        let moved = reallocate_reserved_addresses(&storage).unwrap();
+        assert_eq!(moved.len(), 3);
+        let nic = storage.get_nic_by_id(&nic.id).unwrap().unwrap();
+        assert_eq!(nic.ipv4_address, Some("10.0.0.2".parse().unwrap()));
+        assert_eq!(nic.ipv6_address, Some("2001:db8::2".parse().unwrap()));
+        let lease = storage.get_lease_by_id(&lease.id).unwrap().unwrap();
+        assert_eq!(lease.ipv4_address, "10.0.0.3".parse::<Ipv4Addr>().unwrap());
+        assert_eq!(moved[2].lease_id, Some(lease.id));
+        assert_eq!(moved[2].reason, "broadcast address");
+
+        // Nothing left to move
+        assert!(reallocate_reserved_addresses(&storage).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_delegated_prefixes() {
+        use crate::grpc::storage::{NetworkData, NicData, NicState, Storage};
+        use chrono::Utc;
+        use uuid::Uuid;
+
+        let storage = Storage::in_memory().unwrap();
+
+        let network = NetworkData {
+            id: Uuid::new_v4(),
+            name: "test-pd".to_string(),
+            ipv4_enabled: false,
+            ipv4_subnet: None,
+            ipv6_enabled: true,
+            ipv6_prefix: Some("2001:db8::/56".parse().unwrap()),
+            dns_servers: vec![],
+            ntp_servers: vec![],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
+            boot_server: None,
+            boot_file: None,
+        };
+        storage.create_network(&network).unwrap();
+
+        assert_eq!(validate_delegated_prefix_len(&network, 0).unwrap(), None);
+        assert_eq!(
+            validate_delegated_prefix_len(&network, 64).unwrap(),
+            Some(64)
+        );
+        assert!(matches!(
+            validate_delegated_prefix_len(&network, 56),
+            Err(ValidationError::InvalidDelegatedPrefixLen(56, _))
+        ));
+        assert!(matches!(
+            validate_delegated_prefix_len(&network, 80),
+            Err(ValidationError::InvalidDelegatedPrefixLen(80, _))
+        ));
+
+        // The first /64 holds the NIC addresses
+        let first = allocate_delegated_ipv6_prefix(&network, 64, &storage).unwrap();
+        assert_eq!(first, "2001:db8:0:1::/64".parse().unwrap());
+
+        storage
+            .create_nic(&NicData {
+                id: Uuid::new_v4(),
+                name: None,
+                network_id: network.id,
+                mac_address: [0x02, 0x00, 0x00, 0x00, 0x00, 0x01],
+                ipv4_address: None,
+                ipv6_address: Some("2001:db8:0:2::5".parse().unwrap()),
+                routed_ipv4_prefixes: vec![],
+                routed_ipv6_prefixes: vec![],
+                socket_path: "/run/mvirt/net/nic-test.sock".to_string(),
+                state: NicState::Created,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                owner: None,
+                link_up: true,
+                delegated_ipv6_prefix: Some(first),
+                socket_access: Default::default(),
+            })
+            .unwrap();
+
+        // Skips the delegated prefix and the one holding a NIC address
+        let next = allocate_delegated_ipv6_prefix(&network, 60, &storage).unwrap();
+        assert_eq!(next, "2001:db8:0:10::/60".parse().unwrap());
+        let next = allocate_delegated_ipv6_prefix(&network, 64, &storage).unwrap();
+        assert_eq!(next, "2001:db8:0:3::/64".parse().unwrap());
+
+        // Addresses in a delegated prefix are the guest's to hand out
+        assert!(matches!(
+            validate_create_nic(&network, "", "", "2001:db8:0:1::5", &[], &[], &storage),
+            Err(ValidationError::Ipv6InDelegatedPrefix(_, _))
+        ));
+    }
+
+    #[test]
+    fn test_socket_access() {
+        assert!(validate_socket_access(&SocketAccess::default()).is_ok());
+        let access = SocketAccess {
+            uid: Some(107),
+            gid: Some(107),
+            mode: Some(0o660),
+        };
+        assert!(validate_socket_access(&access).is_ok());
+        assert!(matches!(
+            validate_socket_access(&SocketAccess {
+                mode: Some(0o10000),
+                ..access
+            }),
+            Err(ValidationError::InvalidSocketMode(0o10000))
+        ));
     }
 }
@@ -1,9 +1,40 @@
+//! Packet buffer memory.
+//!
+//! Buffers live in huge pages: fewer TLB misses on the hot path and one
+//! contiguous region to register with io_uring. [`HugePageManager`] maps
+//! them ahead of time at daemon start, on the NUMA node of the CPU the
+//! reactor using them is pinned to, and falls back to regular pages when
+//! the system has no huge pages left.
+
 use nix::libc;
+use std::collections::BTreeMap;
+use std::io;
 use std::ptr;
+use std::sync::{Arc, Mutex};
+use tracing::warn;
+
+/// Routers whose buffers the daemon maps at startup: the TUN router and
+/// the first NIC.
+pub const DEFAULT_HUGEPAGE_PREALLOC: usize = 2;
+
+/// Huge page size assumed when prefaulting.
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+/// Regular page size assumed when prefaulting.
+const PAGE_SIZE: usize = 4096;
+
+/// `MPOL_PREFERRED` from `<linux/mempolicy.h>`: allocate on the node if it
+/// has memory left, elsewhere otherwise. `MPOL_BIND` would SIGBUS on a
+/// huge page fault once the node runs out.
+const MPOL_PREFERRED: libc::c_int = 1;
 
 pub struct HugePagePool {
     ptr: *mut u8,
     size: usize,
+    /// Backed by huge pages rather than the regular-page fallback
+    huge: bool,
+    /// NUMA node the memory was placed on
+    node: Option<usize>,
 }
 
 // Safety: The pointer is only accessed through controlled methods
@@ -11,12 +42,34 @@ unsafe impl Send for HugePagePool {}
 
 impl HugePagePool {
     pub fn new(size: usize) -> Option<Self> {
+        Self::map(size, true, None)
+    }
+
+    /// Map `size` bytes of huge pages on `node` and fault them in.
+    pub fn on_node(size: usize, node: Option<usize>) -> Option<Self> {
+        let pool = Self::map(size, true, node)?;
+        pool.prefault();
+        Some(pool)
+    }
+
+    /// Map `size` bytes of regular pages on `node` and fault them in.
+    pub fn regular(size: usize, node: Option<usize>) -> Option<Self> {
+        let pool = Self::map(size, false, node)?;
+        pool.prefault();
+        Some(pool)
+    }
+
+    fn map(size: usize, huge: bool, node: Option<usize>) -> Option<Self> {
+        let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+        if huge {
+            flags |= libc::MAP_HUGETLB;
+        }
         let ptr = unsafe {
             libc::mmap(
                 ptr::null_mut(),
                 size,
                 libc::PROT_READ | libc::PROT_WRITE,
-                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS | libc::MAP_HUGETLB,
+                flags,
                 -1,
                 0,
             )
@@ -26,10 +79,50 @@ impl HugePagePool {
             return None;
         }
 
-        Some(Self {
+        let pool = Self {
             ptr: ptr as *mut u8,
             size,
-        })
+            huge,
+            node,
+        };
+        // Without a policy the pages land on whatever node faults them in
+        if let Some(node) = node
+            && let Err(e) = pool.prefer_node(node)
+        {
+            warn!(node, error = %e, "Failed to set NUMA policy of packet buffers");
+        }
+        Some(pool)
+    }
+
+    /// Set the memory policy of the mapping to prefer `node`.
+    fn prefer_node(&self, node: usize) -> io::Result<()> {
+        let bits = libc::c_ulong::BITS as usize;
+        let mut mask = vec![0 as libc::c_ulong; node / bits + 1];
+        mask[node / bits] |= 1 << (node % bits);
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                self.ptr as *mut libc::c_void,
+                self.size,
+                MPOL_PREFERRED,
+                mask.as_ptr(),
+                mask.len() * bits + 1,
+                0,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Touch every page so it is allocated now, under the memory policy,
+    /// rather than on the reactor's first packet.
+    fn prefault(&self) {
+        let step = if self.huge { HUGE_PAGE_SIZE } else { PAGE_SIZE };
+        for offset in (0..self.size).step_by(step) {
+            unsafe { ptr::write_volatile(self.ptr.add(offset), 0) };
+        }
     }
 
     pub fn ptr(&self) -> *mut u8 {
@@ -39,6 +132,16 @@ impl HugePagePool {
     pub fn size(&self) -> usize {
         self.size
     }
+
+    /// Whether the pool is backed by huge pages.
+    pub fn is_huge(&self) -> bool {
+        self.huge
+    }
+
+    /// NUMA node the pool was placed on.
+    pub fn node(&self) -> Option<usize> {
+        self.node
+    }
 }
 
 impl Drop for HugePagePool {
@@ -48,3 +151,201 @@ impl Drop for HugePagePool {
         }
     }
 }
+
+/// Memory use of one NUMA node, in bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct NodeUsage {
+    /// The node; `None` for memory placed without a node
+    pub node: Option<usize>,
+    /// Huge pages mapped, handed out or not
+    pub huge_bytes: u64,
+    /// Preallocated huge pages not handed out yet
+    pub free_bytes: u64,
+    /// Regular pages handed out because huge pages ran out
+    pub fallback_bytes: u64,
+    /// Allocations that fell back to regular pages
+    pub fallbacks: u64,
+}
+
+#[derive(Default)]
+struct ManagerState {
+    /// Preallocated pools not handed out yet
+    free: Vec<HugePagePool>,
+    usage: BTreeMap<Option<usize>, NodeUsage>,
+}
+
+impl ManagerState {
+    fn usage(&mut self, node: Option<usize>) -> &mut NodeUsage {
+        self.usage.entry(node).or_insert(NodeUsage {
+            node,
+            ..Default::default()
+        })
+    }
+}
+
+/// Hands out packet buffer memory per NUMA node. Clones share the pool.
+///
+/// Handed-out pools are never returned: like the router's own buffers they
+/// stay mapped for zero-copy references held by other reactors.
+#[derive(Clone, Default)]
+pub struct HugePageManager {
+    state: Arc<Mutex<ManagerState>>,
+}
+
+impl HugePageManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Map `count` pools of `size` bytes on `node` ahead of time. Returns
+    /// how many could be mapped.
+    pub fn preallocate(&self, size: usize, count: usize, node: Option<usize>) -> usize {
+        let mut mapped = 0;
+        for _ in 0..count {
+            let Some(pool) = HugePagePool::on_node(size, node) else {
+                break;
+            };
+            let mut state = self.state.lock().unwrap();
+            let usage = state.usage(node);
+            usage.huge_bytes += size as u64;
+            usage.free_bytes += size as u64;
+            state.free.push(pool);
+            mapped += 1;
+        }
+        if mapped < count {
+            warn!(
+                node,
+                size,
+                wanted = count,
+                mapped,
+                "Not enough huge pages to preallocate packet buffers"
+            );
+        }
+        mapped
+    }
+
+    /// A pool of at least `size` bytes on `node`: a preallocated one if
+    /// there is, else freshly mapped huge pages, else regular pages.
+    pub fn allocate(&self, size: usize, node: Option<usize>) -> io::Result<HugePagePool> {
+        {
+            let mut state = self.state.lock().unwrap();
+            let best = state
+                .free
+                .iter()
+                .enumerate()
+                .filter(|(_, pool)| pool.node == node && pool.size >= size)
+                .min_by_key(|(_, pool)| pool.size)
+                .map(|(i, _)| i);
+            if let Some(i) = best {
+                let pool = state.free.swap_remove(i);
+                state.usage(node).free_bytes -= pool.size as u64;
+                return Ok(pool);
+            }
+        }
+
+        if let Some(pool) = HugePagePool::on_node(size, node) {
+            self.state.lock().unwrap().usage(node).huge_bytes += size as u64;
+            return Ok(pool);
+        }
+
+        warn!(
+            node,
+            size,
+            "Out of huge pages, packet buffers use regular pages. \
+             Raise /proc/sys/vm/nr_hugepages for full performance"
+        );
+        let pool = HugePagePool::regular(size, node).ok_or_else(io::Error::last_os_error)?;
+        let mut state = self.state.lock().unwrap();
+        let usage = state.usage(node);
+        usage.fallback_bytes += size as u64;
+        usage.fallbacks += 1;
+        Ok(pool)
+    }
+
+    /// Memory use per node.
+    pub fn usage(&self) -> Vec<NodeUsage> {
+        self.state.lock().unwrap().usage.values().copied().collect()
+    }
+}
+
+/// NUMA node of `cpu`, `None` if the system doesn't report one.
+pub fn numa_node_of_cpu(cpu: usize) -> Option<usize> {
+    let nodes = std::fs::read_dir("/sys/devices/system/node").ok()?;
+    for entry in nodes.flatten() {
+        let name = entry.file_name();
+        let Some(node) = name
+            .to_str()
+            .and_then(|n| n.strip_prefix("node"))
+            .and_then(|n| n.parse().ok())
+        else {
+            continue;
+        };
+        let Ok(cpus) = std::fs::read_to_string(entry.path().join("cpulist")) else {
+            continue;
+        };
+        if parse_cpu_list(&cpus).is_some_and(|cpus| cpus.contains(&cpu)) {
+            return Some(node);
+        }
+    }
+    None
+}
+
+/// Parse a CPU list as the kernel prints it, e.g. `0-3,8,10-11`.
+pub fn parse_cpu_list(list: &str) -> Option<Vec<usize>> {
+    let list = list.trim();
+    if list.is_empty() {
+        return Some(Vec::new());
+    }
+    let mut cpus = Vec::new();
+    for part in list.split(',') {
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let first: usize = first.trim().parse().ok()?;
+                let last: usize = last.trim().parse().ok()?;
+                if first > last {
+                    return None;
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(part.trim().parse().ok()?),
+        }
+    }
+    Some(cpus)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cpu_list() {
+        assert_eq!(
+            parse_cpu_list("0-3,8,10-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(parse_cpu_list("5"), Some(vec![5]));
+        assert_eq!(parse_cpu_list(""), Some(vec![]));
+        assert_eq!(parse_cpu_list("3-1"), None);
+        assert_eq!(parse_cpu_list("a"), None);
+    }
+
+    #[test]
+    fn test_allocate_falls_back_and_reports_usage() {
+        let manager = HugePageManager::new();
+        let size = 4 * PAGE_SIZE;
+
+        // Huge pages if the test host has them, regular pages otherwise
+        let pool = manager.allocate(size, None).unwrap();
+        assert!(pool.size() >= size);
+        let usage = manager.usage();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(usage[0].node, None);
+        if pool.is_huge() {
+            assert_eq!(usage[0].huge_bytes, size as u64);
+        } else {
+            assert_eq!(usage[0].fallback_bytes, size as u64);
+            assert_eq!(usage[0].fallbacks, 1);
+        }
+        assert_eq!(usage[0].free_bytes, 0);
+    }
+}
@@ -1,12 +1,24 @@
 pub mod audit;
+pub mod capture;
+pub mod conflicts;
+pub mod dns_forwarder;
 pub mod grpc;
 pub mod hugepage;
 pub mod inter_reactor;
 pub mod messaging;
+pub mod neighbor_proxy;
+pub mod netns;
+pub mod orphans;
+pub mod overlay;
 pub mod ping;
 pub mod reactor;
+pub mod reactor_supervisor;
+pub mod reload;
 pub mod router;
 pub mod routing;
+pub mod rule_window;
+pub mod security;
+pub mod socket_access;
 pub mod test_util;
 pub mod tun;
 pub mod vhost_user;
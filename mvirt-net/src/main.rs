@@ -1,13 +1,31 @@
+use ipnet::Ipv4Net;
+use mvirt_common::events::EventBus;
+use mvirt_common::limits::{GrpcLimitLayer, LimitConfig};
+use mvirt_common::request_id::GrpcRequestIdLayer;
 use mvirt_net::audit::create_audit_logger;
+use mvirt_net::conflicts::{self, ConflictMonitor, Conflicts};
+use mvirt_net::dns_forwarder::{self, DnsForwarder};
+use mvirt_net::grpc::manager::SOCKET_DIR;
 use mvirt_net::grpc::proto::net_service_server::NetServiceServer;
+use mvirt_net::grpc::validation::reallocate_reserved_addresses;
 use mvirt_net::grpc::{NetServiceImpl, NetworkManager, Storage};
+use mvirt_net::hugepage::{DEFAULT_HUGEPAGE_PREALLOC, parse_cpu_list};
+use mvirt_net::neighbor_proxy::NeighborProxy;
+use mvirt_net::netns::{NetnsConfig, UplinkNamespace};
+use mvirt_net::orphans::{self, OrphanSweeper};
+use mvirt_net::overlay::{Overlay, OverlayConfig, encap::Encap};
+use mvirt_net::reactor_supervisor::{self, ReactorSupervisor};
+use mvirt_net::reload::{self, Reloader, Settings};
+use mvirt_net::rule_window::{self, RuleWindowTimer};
+use mvirt_net::socket_access::SocketAccess;
 use mvirt_net::{ping, router};
-use std::net::Ipv4Addr;
-use std::path::Path;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal::unix::{SignalKind, signal};
 use tonic::transport::Server;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 /// Default gRPC listen address.
 const GRPC_ADDR: &str = "[::1]:50054";
@@ -15,9 +33,6 @@ const GRPC_ADDR: &str = "[::1]:50054";
 /// Default database path.
 const DB_PATH: &str = "/var/lib/mvirt/net/networks.db";
 
-/// Default log service endpoint.
-const LOG_ENDPOINT: &str = "http://[::1]:50052";
-
 /// Default TUN device name.
 const TUN_NAME: &str = "mvirt0";
 
@@ -61,8 +76,224 @@ async fn run_grpc_server() {
         }
     };
 
+    // Settings that can be reloaded: MTU, TX burst, RX pool cap, strict
+    // isolation and log endpoints, from the env file over the environment
+    let env_file = std::env::var("MVIRT_NET_ENV_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(reload::DEFAULT_ENV_FILE));
+    let settings = match Settings::load(&env_file) {
+        Ok(settings) => settings,
+        Err(e) => {
+            error!(error = %e, "Invalid configuration");
+            std::process::exit(1);
+        }
+    };
+
+    // CPUs to pin reactors to, in kernel CPU list syntax (e.g. "2-5,8")
+    let reactor_cpus = match std::env::var("MVIRT_NET_REACTOR_CPUS") {
+        Ok(value) => match parse_cpu_list(&value) {
+            Some(cpus) => cpus,
+            None => {
+                error!(value = %value, "Invalid MVIRT_NET_REACTOR_CPUS");
+                std::process::exit(1);
+            }
+        },
+        Err(_) => Vec::new(),
+    };
+
+    // Routers whose buffers are mapped at startup
+    let hugepage_prealloc = match std::env::var("MVIRT_NET_HUGEPAGE_PREALLOC") {
+        Ok(value) => match value.parse::<usize>() {
+            Ok(routers) => routers,
+            Err(_) => {
+                error!(value = %value, "Invalid MVIRT_NET_HUGEPAGE_PREALLOC");
+                std::process::exit(1);
+            }
+        },
+        Err(_) => DEFAULT_HUGEPAGE_PREALLOC,
+    };
+
+    // Namespace for uplink processing; unset keeps it in the host namespace
+    let uplink = match std::env::var("MVIRT_NET_NETNS") {
+        Ok(name) => {
+            let mut config = NetnsConfig::new(name);
+            if let Ok(value) = std::env::var("MVIRT_NET_NETNS_LINK") {
+                match value.parse() {
+                    Ok(link) => config = config.with_link_v4(link),
+                    Err(_) => {
+                        error!(value = %value, "Invalid MVIRT_NET_NETNS_LINK");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            if let Ok(value) = std::env::var("MVIRT_NET_NETNS_LINK6") {
+                match value.parse() {
+                    Ok(link) => config = config.with_link_v6(link),
+                    Err(_) => {
+                        error!(value = %value, "Invalid MVIRT_NET_NETNS_LINK6");
+                        std::process::exit(1);
+                    }
+                }
+            }
+            match UplinkNamespace::setup(&config).await {
+                Ok(uplink) => Some(uplink),
+                Err(e) => {
+                    error!(netns = %config.name, error = %e, "Failed to set up uplink namespace");
+                    std::process::exit(1);
+                }
+            }
+        }
+        Err(_) => None,
+    };
+
+    // Upstream interface for proxy ARP/ND; unset disables neighbor proxying
+    let neighbor_proxy = match std::env::var("MVIRT_NET_PROXY_INTERFACE") {
+        Ok(interface) => match NeighborProxy::start(&interface) {
+            Ok(proxy) => Some(proxy),
+            Err(e) => {
+                error!(interface = %interface, error = %e, "Failed to start neighbor proxy");
+                std::process::exit(1);
+            }
+        },
+        Err(_) => None,
+    };
+
+    // Where vhost-user sockets go, and who they belong to unless a NIC says
+    // otherwise, for VMMs that don't run as root
+    let socket_dir = std::env::var("MVIRT_NET_SOCKET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(SOCKET_DIR));
+    if let Err(e) = std::fs::create_dir_all(&socket_dir) {
+        error!(error = %e, path = %socket_dir.display(), "Failed to create socket directory");
+        std::process::exit(1);
+    }
+    let socket_access = SocketAccess {
+        uid: parse_socket_var("MVIRT_NET_SOCKET_UID", 10, u32::MAX),
+        gid: parse_socket_var("MVIRT_NET_SOCKET_GID", 10, u32::MAX),
+        mode: parse_socket_var("MVIRT_NET_SOCKET_MODE", 8, 0o7777),
+    };
+
+    // Prefixes floating IPs are allocated from; unset disables floating IPs
+    let floating_ip_pool: Vec<Ipv4Net> = match std::env::var("MVIRT_NET_FLOATING_IP_POOL") {
+        Ok(value) => match value
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(str::parse)
+            .collect()
+        {
+            Ok(pool) => pool,
+            Err(_) => {
+                error!(value = %value, "Invalid MVIRT_NET_FLOATING_IP_POOL");
+                std::process::exit(1);
+            }
+        },
+        Err(_) => Vec::new(),
+    };
+
+    // Set the link of NICs sending from another NIC's address down
+    let conflict_quarantine = match std::env::var("MVIRT_NET_CONFLICT_QUARANTINE") {
+        Ok(value) => match value.as_str() {
+            "1" | "true" => true,
+            "0" | "false" => false,
+            _ => {
+                error!(value = %value, "Invalid MVIRT_NET_CONFLICT_QUARANTINE");
+                std::process::exit(1);
+            }
+        },
+        Err(_) => false,
+    };
+
+    // Encapsulation of the overlay to other hosts and the local address it
+    // tunnels from; unset disables the overlay
+    let overlay = match std::env::var("MVIRT_NET_OVERLAY") {
+        Ok(value) => {
+            let Ok(encap) = value.parse::<Encap>() else {
+                error!(value = %value, "Invalid MVIRT_NET_OVERLAY");
+                std::process::exit(1);
+            };
+            let local: IpAddr = match std::env::var("MVIRT_NET_OVERLAY_LOCAL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+            {
+                Some(local) => local,
+                None => {
+                    error!("MVIRT_NET_OVERLAY requires an address in MVIRT_NET_OVERLAY_LOCAL");
+                    std::process::exit(1);
+                }
+            };
+            let port = match std::env::var("MVIRT_NET_OVERLAY_PORT") {
+                Ok(value) => match value.parse() {
+                    Ok(port) => port,
+                    Err(_) => {
+                        error!(value = %value, "Invalid MVIRT_NET_OVERLAY_PORT");
+                        std::process::exit(1);
+                    }
+                },
+                Err(_) => encap.default_port(),
+            };
+            Some(OverlayConfig { encap, local, port })
+        }
+        Err(_) => None,
+    };
+
+    // Upstream DNS servers the gateway forwards other names to, for
+    // networks without their own; unset disables DNS forwarding
+    let dns_forwarders: Option<Vec<SocketAddr>> = match std::env::var("MVIRT_NET_DNS_FORWARDERS") {
+        Ok(value) => match value
+            .split(',')
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(dns_forwarder::parse_upstream)
+            .collect()
+        {
+            Some(upstreams) => Some(upstreams),
+            None => {
+                error!(value = %value, "Invalid MVIRT_NET_DNS_FORWARDERS");
+                std::process::exit(1);
+            }
+        },
+        Err(_) => None,
+    };
+
     // Initialize network manager
-    let manager = Arc::new(NetworkManager::new(Arc::clone(&storage)));
+    let mut manager = NetworkManager::new(Arc::clone(&storage))
+        .with_mtu(settings.mtu)
+        .with_tx_burst(settings.tx_burst)
+        .with_rx_buffer_max(settings.rx_buffer_max)
+        .with_reactor_cpus(reactor_cpus)
+        .with_strict_isolation(settings.strict_isolation)
+        .with_socket_dir(socket_dir.clone())
+        .with_socket_access(socket_access);
+    if let Some(uplink) = uplink {
+        manager = manager.with_uplink_namespace(uplink);
+    }
+    if let Some(proxy) = neighbor_proxy {
+        manager = manager.with_neighbor_proxy(proxy);
+    }
+    if let Some(config) = overlay {
+        match Overlay::start(config, Arc::clone(manager.registry())) {
+            Ok(overlay) => {
+                info!(encap = %config.encap, local = %config.local, port = config.port, "Overlay started");
+                manager = manager.with_overlay(Arc::new(overlay));
+            }
+            Err(e) => {
+                error!(error = %e, local = %config.local, "Failed to start overlay");
+                std::process::exit(1);
+            }
+        }
+    }
+    if let Some(upstreams) = dns_forwarders {
+        match DnsForwarder::start(upstreams) {
+            Ok(forwarder) => manager = manager.with_dns_forwarder(Arc::new(forwarder)),
+            Err(e) => {
+                error!(error = %e, "Failed to start DNS forwarder");
+                std::process::exit(1);
+            }
+        }
+    }
+    let manager = Arc::new(manager);
+    manager.preallocate_hugepages(hugepage_prealloc);
 
     // Initialize global TUN device
     if let Err(e) = manager.init_tun(TUN_NAME).await {
@@ -71,25 +302,105 @@ async fn run_grpc_server() {
         std::process::exit(1);
     }
 
+    // Move NICs off reserved addresses allocated by older versions, before
+    // their reactors hand them out over DHCP
+    let reallocations = reallocate_reserved_addresses(&storage).unwrap_or_else(|e| {
+        error!(error = %e, "Failed to check for reserved addresses");
+        Vec::new()
+    });
+
     // Recover NIC routers from database
     if let Err(e) = manager.recover_nics().await {
         error!(error = %e, "Failed to recover NIC routers");
         // Continue anyway - NICs can be recreated manually
     }
 
+    // Answer upstream for the routed prefixes recovered NICs own
+    if let Err(e) = manager.sync_neighbor_proxy() {
+        error!(error = %e, "Failed to sync neighbor proxy");
+    }
+
+    // Enable and disable time-limited security rules as their windows pass
+    let rule_windows = RuleWindowTimer::start(
+        Arc::clone(&storage),
+        Arc::clone(&manager),
+        Duration::from_secs(rule_window::DEFAULT_CHECK_INTERVAL_SECS),
+    );
+
     // Create audit logger. mvirt-net is the legacy bridge-based net daemon,
     // superseded by mvirt-ebpf; keep it loopback/plain-h2c-only — operators
     // running it must point at a local mvirt-log.
-    let endpoints: Vec<String> = std::env::var("MVIRT_LOG_ENDPOINTS")
-        .unwrap_or_else(|_| LOG_ENDPOINT.to_string())
-        .split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
-    let audit = create_audit_logger(endpoints, None);
+    let audit = create_audit_logger(settings.log_endpoints.clone(), None);
+    for r in &reallocations {
+        audit.address_reallocated(
+            &r.nic_id.to_string(),
+            &r.old.to_string(),
+            r.new.map(|a| a.to_string()).as_deref(),
+            r.reason,
+        );
+    }
+
+    // Remove sockets of NICs that no longer exist, now and periodically
+    OrphanSweeper::sweep(&socket_dir, &storage, &audit);
+    let orphan_sweeper = OrphanSweeper::start(
+        socket_dir,
+        Arc::clone(&storage),
+        Arc::clone(&audit),
+        Duration::from_secs(orphans::DEFAULT_SWEEP_INTERVAL_SECS),
+    );
+
+    // Restart NIC reactors that crashed
+    let reactor_supervisor = ReactorSupervisor::start(
+        Arc::clone(&manager),
+        Arc::clone(&audit),
+        Duration::from_secs(reactor_supervisor::DEFAULT_CHECK_INTERVAL_SECS),
+    );
+
+    // Report NICs sending from addresses allocated to other NICs
+    let conflicts = Arc::new(Conflicts::default());
+    let conflict_monitor = ConflictMonitor::start(
+        Arc::clone(&storage),
+        Arc::clone(&manager),
+        Arc::clone(&audit),
+        Arc::clone(&conflicts),
+        Duration::from_secs(conflicts::DEFAULT_CHECK_INTERVAL_SECS),
+        conflict_quarantine,
+    );
+
+    // Apply changed settings on SIGHUP and the Reload RPC, keeping the
+    // reactors and their vhost-user sessions
+    let reloader = Arc::new(Reloader::new(
+        env_file,
+        Arc::clone(&manager),
+        Arc::clone(&audit),
+        settings,
+    ));
+    let mut sighup = signal(SignalKind::hangup()).expect("Failed to set up SIGHUP handler");
+    let sighup_task = tokio::spawn({
+        let reloader = Arc::clone(&reloader);
+        async move {
+            while sighup.recv().await.is_some() {
+                info!("Received SIGHUP, reloading configuration...");
+                if let Err(e) = reloader.reload().await {
+                    error!(error = %e, "Failed to reload configuration");
+                }
+            }
+        }
+    });
 
     // Create gRPC service
-    let service = NetServiceImpl::new(Arc::clone(&storage), Arc::clone(&manager), audit);
+    let service = NetServiceImpl::new(Arc::clone(&storage), Arc::clone(&manager), audit)
+        .with_reloader(reloader)
+        .with_floating_ip_pool(floating_ip_pool)
+        .with_conflicts(conflicts);
+    // Deleted NICs are unplugged from VMs by mvirt-vmm
+    let service = match EventBus::new("net") {
+        Ok(bus) => service.with_host_events(bus),
+        Err(e) => {
+            warn!(error = %e, "No host event bus; NIC deletions are not published");
+            service
+        }
+    };
 
     // Parse listen address
     let addr = GRPC_ADDR.parse().expect("Invalid listen address");
@@ -102,6 +413,8 @@ async fn run_grpc_server() {
 
     // Run server with graceful shutdown
     let server = Server::builder()
+        .layer(GrpcRequestIdLayer)
+        .layer(GrpcLimitLayer::new(LimitConfig::from_env()))
         .add_service(NetServiceServer::new(service))
         .serve_with_shutdown(addr, async {
             tokio::select! {
@@ -118,6 +431,12 @@ async fn run_grpc_server() {
         error!(error = %e, "gRPC server error");
     }
 
+    sighup_task.abort();
+    rule_windows.stop();
+    orphan_sweeper.stop();
+    reactor_supervisor.stop();
+    conflict_monitor.stop();
+
     // Shutdown manager
     if let Err(e) = manager.shutdown().await {
         error!(error = %e, "Failed to shutdown network manager");
@@ -126,6 +445,19 @@ async fn run_grpc_server() {
     info!("Server stopped");
 }
 
+/// Optional number in `var`, in `radix`, up to `max`; exits on anything
+/// else.
+fn parse_socket_var(var: &str, radix: u32, max: u32) -> Option<u32> {
+    let value = std::env::var(var).ok()?;
+    match u32::from_str_radix(&value, radix) {
+        Ok(n) if n <= max => Some(n),
+        _ => {
+            error!(value = %value, "Invalid {}", var);
+            std::process::exit(1);
+        }
+    }
+}
+
 async fn run_ping_mode() {
     let local_ip = Ipv4Addr::new(192, 168, 1, 1);
 
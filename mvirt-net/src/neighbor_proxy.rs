@@ -0,0 +1,653 @@
+//! Proxy ARP/NDP for routed prefixes on the upstream interface.
+//!
+//! NICs on public networks can own routed prefixes. Traffic for them
+//! reaches the TUN uplink through the host, but an upstream router that
+//! treats such a prefix as on-link first resolves each address with ARP or
+//! neighbor discovery on the upstream segment, and nothing answers. The
+//! uplink is a layer 3 device and never sees those requests, so the proxy
+//! listens on the host's upstream interface instead and answers for every
+//! address in the proxied prefixes with that interface's MAC.
+//!
+//! Which prefixes are proxied is decided per network; see
+//! `NetworkManager::sync_neighbor_proxy`.
+
+use crate::reactor::icmpv6::compute_icmpv6_checksum;
+use ipnet::{Ipv4Net, Ipv6Net};
+use nix::libc;
+use prefix_trie::PrefixMap;
+use smoltcp::wire::{
+    ArpOperation, ArpPacket, ArpRepr, EthernetAddress, EthernetFrame, EthernetProtocol,
+    EthernetRepr, IpProtocol, Ipv6Address, Ipv6Packet, Ipv6Repr,
+};
+use std::ffi::CString;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Ethernet header size
+const ETHERNET_HEADER_SIZE: usize = 14;
+
+/// ARP packet size (for Ethernet + IPv4)
+const ARP_PACKET_SIZE: usize = 28;
+
+/// IPv6 header size
+const IPV6_HEADER_SIZE: usize = 40;
+
+/// ICMPv6 Neighbor Solicitation type
+const ICMPV6_NEIGHBOR_SOLICIT: u8 = 135;
+
+/// ICMPv6 Neighbor Advertisement type
+const ICMPV6_NEIGHBOR_ADVERT: u8 = 136;
+
+/// How often the proxy thread checks whether it should stop, in ms
+const POLL_INTERVAL_MS: i32 = 200;
+
+/// Classic BPF program passing only ICMPv6 Neighbor Solicitations without
+/// extension headers, so the IPv6 socket doesn't copy all upstream traffic.
+const NS_FILTER: [libc::sock_filter; 6] = [
+    // ldb [20]: IPv6 next header
+    bpf(0x30, 0, 0, (ETHERNET_HEADER_SIZE + 6) as u32),
+    // jeq #58 (ICMPv6), else drop
+    bpf(0x15, 0, 3, 58),
+    // ldb [54]: ICMPv6 type
+    bpf(0x30, 0, 0, (ETHERNET_HEADER_SIZE + IPV6_HEADER_SIZE) as u32),
+    // jeq #135, else drop
+    bpf(0x15, 0, 1, ICMPV6_NEIGHBOR_SOLICIT as u32),
+    // accept
+    bpf(0x06, 0, 0, 0xffff),
+    // drop
+    bpf(0x06, 0, 0, 0),
+];
+
+const fn bpf(code: u16, jt: u8, jf: u8, k: u32) -> libc::sock_filter {
+    libc::sock_filter { code, jt, jf, k }
+}
+
+/// Prefixes the proxy answers for.
+#[derive(Clone, Default)]
+pub struct ProxyPrefixes {
+    ipv4: PrefixMap<Ipv4Net, ()>,
+    ipv6: PrefixMap<Ipv6Net, ()>,
+}
+
+impl ProxyPrefixes {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert_v4(&mut self, prefix: Ipv4Net) {
+        self.ipv4.insert(prefix.trunc(), ());
+    }
+
+    pub fn insert_v6(&mut self, prefix: Ipv6Net) {
+        self.ipv6.insert(prefix.trunc(), ());
+    }
+
+    pub fn contains_v4(&self, addr: Ipv4Addr) -> bool {
+        Ipv4Net::new(addr, 32)
+            .ok()
+            .is_some_and(|net| self.ipv4.get_lpm(&net).is_some())
+    }
+
+    pub fn contains_v6(&self, addr: Ipv6Addr) -> bool {
+        Ipv6Net::new(addr, 128)
+            .ok()
+            .is_some_and(|net| self.ipv6.get_lpm(&net).is_some())
+    }
+
+    /// Number of proxied prefixes.
+    pub fn len(&self) -> usize {
+        self.ipv4.len() + self.ipv6.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Build the reply to an ARP request or Neighbor Solicitation for a
+/// proxied address, announcing `mac` as its link-layer address.
+///
+/// Returns `None` for anything else, including gratuitous ARP and
+/// duplicate address detection, which must stay unanswered.
+pub fn answer(frame: &[u8], mac: [u8; 6], prefixes: &ProxyPrefixes) -> Option<Vec<u8>> {
+    let eth_frame = EthernetFrame::new_checked(frame).ok()?;
+    match eth_frame.ethertype() {
+        EthernetProtocol::Arp => answer_arp(eth_frame.payload(), mac, prefixes),
+        EthernetProtocol::Ipv6 => {
+            answer_neighbor_solicitation(eth_frame.payload(), eth_frame.src_addr(), mac, prefixes)
+        }
+        _ => None,
+    }
+}
+
+/// Reply to an ARP request for a proxied IPv4 address.
+fn answer_arp(payload: &[u8], mac: [u8; 6], prefixes: &ProxyPrefixes) -> Option<Vec<u8>> {
+    let arp_packet = ArpPacket::new_checked(payload).ok()?;
+    let ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Request,
+        source_hardware_addr,
+        source_protocol_addr,
+        target_protocol_addr,
+        ..
+    } = ArpRepr::parse(&arp_packet).ok()?
+    else {
+        return None;
+    };
+
+    let target = Ipv4Addr::from(target_protocol_addr.0);
+    if source_protocol_addr == target_protocol_addr || !prefixes.contains_v4(target) {
+        return None;
+    }
+
+    debug!(
+        src_ip = %source_protocol_addr,
+        target_ip = %target,
+        "Proxying ARP request"
+    );
+
+    let mut reply = vec![0u8; ETHERNET_HEADER_SIZE + ARP_PACKET_SIZE];
+    let own_mac = EthernetAddress(mac);
+    let eth_repr = EthernetRepr {
+        src_addr: own_mac,
+        dst_addr: source_hardware_addr,
+        ethertype: EthernetProtocol::Arp,
+    };
+    let mut eth_frame = EthernetFrame::new_unchecked(&mut reply);
+    eth_repr.emit(&mut eth_frame);
+
+    let arp_repr = ArpRepr::EthernetIpv4 {
+        operation: ArpOperation::Reply,
+        source_hardware_addr: own_mac,
+        source_protocol_addr: target_protocol_addr,
+        target_hardware_addr: source_hardware_addr,
+        target_protocol_addr: source_protocol_addr,
+    };
+    let mut arp_packet = ArpPacket::new_unchecked(eth_frame.payload_mut());
+    arp_repr.emit(&mut arp_packet);
+
+    Some(reply)
+}
+
+/// Reply to a Neighbor Solicitation for a proxied IPv6 address.
+fn answer_neighbor_solicitation(
+    payload: &[u8],
+    src_mac: EthernetAddress,
+    mac: [u8; 6],
+    prefixes: &ProxyPrefixes,
+) -> Option<Vec<u8>> {
+    let ipv6_packet = Ipv6Packet::new_checked(payload).ok()?;
+    // Neighbor discovery from off-link is forged (RFC 4861 7.1.1)
+    if ipv6_packet.next_header() != IpProtocol::Icmpv6 || ipv6_packet.hop_limit() != 255 {
+        return None;
+    }
+
+    // Type, code, checksum, reserved, target address
+    let icmpv6_raw = ipv6_packet.payload();
+    if icmpv6_raw.len() < 24 || icmpv6_raw[0] != ICMPV6_NEIGHBOR_SOLICIT || icmpv6_raw[1] != 0 {
+        return None;
+    }
+
+    let src_addr = ipv6_packet.src_addr();
+    if src_addr.is_unspecified() {
+        // Duplicate address detection
+        return None;
+    }
+
+    let target_bytes: [u8; 16] = icmpv6_raw[8..24].try_into().ok()?;
+    let target = Ipv6Addr::from(target_bytes);
+    if !prefixes.contains_v6(target) {
+        return None;
+    }
+
+    debug!(src = %src_addr, target = %target, "Proxying Neighbor Solicitation");
+
+    // NA: type, code, checksum, flags, target, TLLAO
+    let icmpv6_len = 32;
+    let mut reply = vec![0u8; ETHERNET_HEADER_SIZE + IPV6_HEADER_SIZE + icmpv6_len];
+    let target_addr = Ipv6Address::from_bytes(&target_bytes);
+
+    let eth_repr = EthernetRepr {
+        src_addr: EthernetAddress(mac),
+        dst_addr: src_mac,
+        ethertype: EthernetProtocol::Ipv6,
+    };
+    let mut eth_frame = EthernetFrame::new_unchecked(&mut reply);
+    eth_repr.emit(&mut eth_frame);
+
+    let ipv6_repr = Ipv6Repr {
+        src_addr: target_addr,
+        dst_addr: src_addr,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmpv6_len,
+        hop_limit: 255,
+    };
+    let mut ipv6_packet = Ipv6Packet::new_unchecked(eth_frame.payload_mut());
+    ipv6_repr.emit(&mut ipv6_packet);
+
+    let icmpv6_data = &mut reply[ETHERNET_HEADER_SIZE + IPV6_HEADER_SIZE..];
+    icmpv6_data[0] = ICMPV6_NEIGHBOR_ADVERT;
+    // Router (0x80) | Solicited (0x40); no Override, as proxies must not
+    // replace the entry of a host that answers for itself (RFC 4861 7.2.8)
+    icmpv6_data[4] = 0xc0;
+    icmpv6_data[8..24].copy_from_slice(&target_bytes);
+    // Target Link-Layer Address Option
+    icmpv6_data[24] = 2;
+    icmpv6_data[25] = 1;
+    icmpv6_data[26..32].copy_from_slice(&mac);
+
+    let checksum = compute_icmpv6_checksum(&target_addr, &src_addr, icmpv6_data);
+    icmpv6_data[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    Some(reply)
+}
+
+/// Proxy ARP/NDP responder on one upstream interface.
+///
+/// Runs on its own thread with packet sockets bound to the interface. The
+/// proxied prefixes can be replaced at any time.
+pub struct NeighborProxy {
+    interface: String,
+    prefixes: Arc<RwLock<ProxyPrefixes>>,
+    stop: Arc<AtomicBool>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl NeighborProxy {
+    /// Start answering on `interface`, in the host namespace. Proxies
+    /// nothing until prefixes are set.
+    pub fn start(interface: &str) -> io::Result<Self> {
+        let name = CString::new(interface).map_err(io::Error::other)?;
+        let if_index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if if_index == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mac = read_mac(interface)?;
+
+        let arp = open_socket(if_index, libc::ETH_P_ARP as u16)?;
+        let ndp = open_socket(if_index, libc::ETH_P_IPV6 as u16)?;
+        attach_filter(&ndp, &NS_FILTER)?;
+
+        let prefixes = Arc::new(RwLock::new(ProxyPrefixes::new()));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let thread = {
+            let prefixes = Arc::clone(&prefixes);
+            let stop = Arc::clone(&stop);
+            let interface = interface.to_string();
+            std::thread::Builder::new()
+                .name("neighbor-proxy".to_string())
+                .spawn(move || run(&interface, [arp, ndp], mac, &prefixes, &stop))?
+        };
+
+        info!(interface, if_index, "Neighbor proxy started");
+        Ok(NeighborProxy {
+            interface: interface.to_string(),
+            prefixes,
+            stop,
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Name of the upstream interface.
+    pub fn interface(&self) -> &str {
+        &self.interface
+    }
+
+    /// Replace the proxied prefixes.
+    pub fn set_prefixes(&self, prefixes: ProxyPrefixes) {
+        debug!(
+            interface = %self.interface,
+            prefixes = prefixes.len(),
+            "Neighbor proxy prefixes updated"
+        );
+        *self.prefixes.write().unwrap() = prefixes;
+    }
+
+    /// Stop answering and wait for the proxy thread to exit.
+    pub fn stop(&self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.lock().unwrap().take() {
+            let _ = thread.join();
+            info!(interface = %self.interface, "Neighbor proxy stopped");
+        }
+    }
+}
+
+impl Drop for NeighborProxy {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Proxy thread: answer requests until told to stop.
+fn run(
+    interface: &str,
+    sockets: [OwnedFd; 2],
+    mac: [u8; 6],
+    prefixes: &RwLock<ProxyPrefixes>,
+    stop: &AtomicBool,
+) {
+    let mut fds = sockets.each_ref().map(|socket| libc::pollfd {
+        fd: socket.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    });
+    let mut buf = [0u8; 2048];
+
+    while !stop.load(Ordering::Relaxed) {
+        let ready = unsafe {
+            libc::poll(
+                fds.as_mut_ptr(),
+                fds.len() as libc::nfds_t,
+                POLL_INTERVAL_MS,
+            )
+        };
+        if ready < 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::Interrupted {
+                continue;
+            }
+            warn!(interface, error = %e, "Neighbor proxy poll failed, stopping");
+            return;
+        }
+
+        for pollfd in fds.iter_mut().filter(|p| p.revents & libc::POLLIN != 0) {
+            pollfd.revents = 0;
+            let len = unsafe {
+                libc::recv(
+                    pollfd.fd,
+                    buf.as_mut_ptr() as *mut libc::c_void,
+                    buf.len(),
+                    libc::MSG_DONTWAIT,
+                )
+            };
+            if len <= 0 {
+                continue;
+            }
+
+            let reply = answer(&buf[..len as usize], mac, &prefixes.read().unwrap());
+            if let Some(reply) = reply {
+                let sent = unsafe {
+                    libc::send(
+                        pollfd.fd,
+                        reply.as_ptr() as *const libc::c_void,
+                        reply.len(),
+                        0,
+                    )
+                };
+                if sent < 0 {
+                    warn!(
+                        interface,
+                        error = %io::Error::last_os_error(),
+                        "Failed to send proxy reply"
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Open a packet socket for `protocol` frames received on `if_index`.
+fn open_socket(if_index: u32, protocol: u16) -> io::Result<OwnedFd> {
+    let fd = unsafe {
+        libc::socket(
+            libc::AF_PACKET,
+            libc::SOCK_RAW | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+            protocol.to_be() as libc::c_int,
+        )
+    };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    let mut addr: libc::sockaddr_ll = unsafe { std::mem::zeroed() };
+    addr.sll_family = libc::AF_PACKET as u16;
+    addr.sll_protocol = protocol.to_be();
+    addr.sll_ifindex = if_index as libc::c_int;
+    let ret = unsafe {
+        libc::bind(
+            fd,
+            &addr as *const libc::sockaddr_ll as *const libc::sockaddr,
+            std::mem::size_of::<libc::sockaddr_ll>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // Solicitations go to solicited-node multicast groups nobody on the
+    // host has joined
+    let mreq = libc::packet_mreq {
+        mr_ifindex: if_index as libc::c_int,
+        mr_type: libc::PACKET_MR_ALLMULTI as libc::c_ushort,
+        mr_alen: 0,
+        mr_address: [0; 8],
+    };
+    setsockopt(
+        &socket,
+        libc::SOL_PACKET,
+        libc::PACKET_ADD_MEMBERSHIP,
+        &mreq,
+    )?;
+
+    Ok(socket)
+}
+
+/// Attach a classic BPF filter to `socket`.
+fn attach_filter(socket: &OwnedFd, filter: &[libc::sock_filter]) -> io::Result<()> {
+    let prog = libc::sock_fprog {
+        len: filter.len() as libc::c_ushort,
+        filter: filter.as_ptr() as *mut libc::sock_filter,
+    };
+    setsockopt(socket, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &prog)
+}
+
+fn setsockopt<T>(
+    socket: &OwnedFd,
+    level: libc::c_int,
+    name: libc::c_int,
+    value: &T,
+) -> io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            level,
+            name,
+            value as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// MAC address of `interface`.
+fn read_mac(interface: &str) -> io::Result<[u8; 6]> {
+    let path = format!("/sys/class/net/{}/address", interface);
+    let text = std::fs::read_to_string(&path)?;
+    parse_mac(text.trim()).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has no Ethernet address", interface),
+        )
+    })
+}
+
+fn parse_mac(s: &str) -> Option<[u8; 6]> {
+    let mut mac = [0u8; 6];
+    let mut parts = s.split(':');
+    for byte in &mut mac {
+        *byte = u8::from_str_radix(parts.next()?, 16).ok()?;
+    }
+    parts.next().is_none().then_some(mac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smoltcp::wire::Ipv4Address;
+
+    const UPSTREAM_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0xaa, 0xbb, 0xcc];
+    const OWN_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x11, 0x22, 0x33];
+
+    fn prefixes() -> ProxyPrefixes {
+        let mut prefixes = ProxyPrefixes::new();
+        prefixes.insert_v4("203.0.113.8/29".parse().unwrap());
+        prefixes.insert_v6("2001:db8:1::/64".parse().unwrap());
+        prefixes
+    }
+
+    fn arp_request(sender: Ipv4Address, target: Ipv4Address) -> Vec<u8> {
+        let mut packet = vec![0u8; ETHERNET_HEADER_SIZE + ARP_PACKET_SIZE];
+        let mut eth_frame = EthernetFrame::new_unchecked(&mut packet);
+        EthernetRepr {
+            src_addr: EthernetAddress(UPSTREAM_MAC),
+            dst_addr: EthernetAddress::BROADCAST,
+            ethertype: EthernetProtocol::Arp,
+        }
+        .emit(&mut eth_frame);
+        ArpRepr::EthernetIpv4 {
+            operation: ArpOperation::Request,
+            source_hardware_addr: EthernetAddress(UPSTREAM_MAC),
+            source_protocol_addr: sender,
+            target_hardware_addr: EthernetAddress([0; 6]),
+            target_protocol_addr: target,
+        }
+        .emit(&mut ArpPacket::new_unchecked(eth_frame.payload_mut()));
+        packet
+    }
+
+    fn neighbor_solicitation(src: Ipv6Addr, target: Ipv6Addr, hop_limit: u8) -> Vec<u8> {
+        let icmpv6_len = 24;
+        let mut packet = vec![0u8; ETHERNET_HEADER_SIZE + IPV6_HEADER_SIZE + icmpv6_len];
+        let mut eth_frame = EthernetFrame::new_unchecked(&mut packet);
+        EthernetRepr {
+            src_addr: EthernetAddress(UPSTREAM_MAC),
+            dst_addr: EthernetAddress([0x33, 0x33, 0xff, 0, 0, 0x05]),
+            ethertype: EthernetProtocol::Ipv6,
+        }
+        .emit(&mut eth_frame);
+        Ipv6Repr {
+            src_addr: Ipv6Address::from_bytes(&src.octets()),
+            dst_addr: Ipv6Address::from_bytes(
+                &"ff02::1:ff00:5".parse::<Ipv6Addr>().unwrap().octets(),
+            ),
+            next_header: IpProtocol::Icmpv6,
+            payload_len: icmpv6_len,
+            hop_limit,
+        }
+        .emit(&mut Ipv6Packet::new_unchecked(eth_frame.payload_mut()));
+        let icmpv6 = &mut packet[ETHERNET_HEADER_SIZE + IPV6_HEADER_SIZE..];
+        icmpv6[0] = ICMPV6_NEIGHBOR_SOLICIT;
+        icmpv6[8..24].copy_from_slice(&target.octets());
+        packet
+    }
+
+    #[test]
+    fn test_arp_request_for_routed_prefix() {
+        let request = arp_request(
+            Ipv4Address::new(203, 0, 113, 1),
+            Ipv4Address::new(203, 0, 113, 10),
+        );
+        let reply = answer(&request, OWN_MAC, &prefixes()).expect("should proxy");
+
+        let eth_frame = EthernetFrame::new_checked(&reply[..]).unwrap();
+        assert_eq!(eth_frame.dst_addr(), EthernetAddress(UPSTREAM_MAC));
+        assert_eq!(eth_frame.src_addr(), EthernetAddress(OWN_MAC));
+        let arp = ArpRepr::parse(&ArpPacket::new_checked(eth_frame.payload()).unwrap()).unwrap();
+        assert_eq!(
+            arp,
+            ArpRepr::EthernetIpv4 {
+                operation: ArpOperation::Reply,
+                source_hardware_addr: EthernetAddress(OWN_MAC),
+                source_protocol_addr: Ipv4Address::new(203, 0, 113, 10),
+                target_hardware_addr: EthernetAddress(UPSTREAM_MAC),
+                target_protocol_addr: Ipv4Address::new(203, 0, 113, 1),
+            }
+        );
+    }
+
+    #[test]
+    fn test_arp_ignored_outside_prefixes_and_gratuitous() {
+        let outside = arp_request(
+            Ipv4Address::new(203, 0, 113, 1),
+            Ipv4Address::new(203, 0, 113, 20),
+        );
+        assert!(answer(&outside, OWN_MAC, &prefixes()).is_none());
+
+        let gratuitous = arp_request(
+            Ipv4Address::new(203, 0, 113, 10),
+            Ipv4Address::new(203, 0, 113, 10),
+        );
+        assert!(answer(&gratuitous, OWN_MAC, &prefixes()).is_none());
+
+        let request = arp_request(
+            Ipv4Address::new(203, 0, 113, 1),
+            Ipv4Address::new(203, 0, 113, 10),
+        );
+        assert!(answer(&request, OWN_MAC, &ProxyPrefixes::new()).is_none());
+    }
+
+    #[test]
+    fn test_neighbor_solicitation_for_routed_prefix() {
+        let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let target: Ipv6Addr = "2001:db8:1::5".parse().unwrap();
+        let request = neighbor_solicitation(src, target, 255);
+        let reply = answer(&request, OWN_MAC, &prefixes()).expect("should proxy");
+
+        let eth_frame = EthernetFrame::new_checked(&reply[..]).unwrap();
+        assert_eq!(eth_frame.dst_addr(), EthernetAddress(UPSTREAM_MAC));
+        let ipv6 = Ipv6Packet::new_checked(eth_frame.payload()).unwrap();
+        assert_eq!(Ipv6Addr::from(ipv6.dst_addr().0), src);
+        assert_eq!(ipv6.hop_limit(), 255);
+
+        let icmpv6 = ipv6.payload();
+        assert_eq!(icmpv6[0], ICMPV6_NEIGHBOR_ADVERT);
+        assert_eq!(icmpv6[4], 0xc0);
+        assert_eq!(&icmpv6[8..24], &target.octets());
+        assert_eq!(&icmpv6[26..32], &OWN_MAC);
+
+        // Checksum over the pseudo-header verifies to zero
+        let mut data = icmpv6.to_vec();
+        let checksum = u16::from_be_bytes([data[2], data[3]]);
+        data[2..4].fill(0);
+        assert_eq!(
+            compute_icmpv6_checksum(&ipv6.src_addr(), &ipv6.dst_addr(), &data),
+            checksum
+        );
+    }
+
+    #[test]
+    fn test_neighbor_solicitation_ignored() {
+        let src: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let target: Ipv6Addr = "2001:db8:1::5".parse().unwrap();
+
+        // Outside the proxied prefixes
+        let outside = neighbor_solicitation(src, "2001:db8:2::5".parse().unwrap(), 255);
+        assert!(answer(&outside, OWN_MAC, &prefixes()).is_none());
+
+        // Duplicate address detection
+        let dad = neighbor_solicitation(Ipv6Addr::UNSPECIFIED, target, 255);
+        assert!(answer(&dad, OWN_MAC, &prefixes()).is_none());
+
+        // Routed from off-link
+        let forwarded = neighbor_solicitation(src, target, 64);
+        assert!(answer(&forwarded, OWN_MAC, &prefixes()).is_none());
+    }
+
+    #[test]
+    fn test_parse_mac() {
+        assert_eq!(parse_mac("52:54:00:11:22:33"), Some(OWN_MAC));
+        assert_eq!(parse_mac("52:54:00:11:22"), None);
+        assert_eq!(parse_mac("52:54:00:11:22:33:44"), None);
+        assert_eq!(parse_mac("00:00:00:00:00:zz"), None);
+    }
+}
@@ -0,0 +1,463 @@
+//! Dedicated network namespace for uplink processing.
+//!
+//! With a namespace configured, the routers' TUN devices and the kernel
+//! routes to them live in it instead of the host namespace, so tenant
+//! traffic never passes the host's main routing table or firewall rules. A
+//! veth pair connects the two: the host routes public network subnets to
+//! the namespace end, and the namespace routes everything else back.
+
+use futures::TryStreamExt;
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use nix::libc;
+use rtnetlink::Handle;
+use std::ffi::CString;
+use std::fs::{self, File};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{debug, info};
+
+/// Where named namespaces are mounted, shared with `ip netns`.
+const NETNS_DIR: &str = "/run/netns";
+
+/// `f_type` of an nsfs mount.
+const NSFS_MAGIC: i64 = 0x6e73_6673;
+
+/// Name of the namespace end of the veth pair, inside the namespace.
+const NS_VETH: &str = "uplink0";
+
+/// Default transfer network of the veth pair.
+pub const DEFAULT_LINK_V4: Ipv4Net = Ipv4Net::new_assert(Ipv4Addr::new(169, 254, 200, 0), 30);
+
+/// Default IPv6 transfer network of the veth pair.
+pub const DEFAULT_LINK_V6: Ipv6Net =
+    Ipv6Net::new_assert(Ipv6Addr::new(0xfd6d, 0x7669, 0x7274, 0, 0, 0, 0, 0), 126);
+
+/// Configuration of the uplink namespace.
+#[derive(Debug, Clone)]
+pub struct NetnsConfig {
+    /// Namespace name, as `ip netns` lists it
+    pub name: String,
+    /// Transfer network of the veth pair; the host gets the first address,
+    /// the namespace the second
+    pub link_v4: Ipv4Net,
+    pub link_v6: Ipv6Net,
+}
+
+impl NetnsConfig {
+    pub fn new(name: impl Into<String>) -> Self {
+        NetnsConfig {
+            name: name.into(),
+            link_v4: DEFAULT_LINK_V4,
+            link_v6: DEFAULT_LINK_V6,
+        }
+    }
+
+    pub fn with_link_v4(mut self, link: Ipv4Net) -> Self {
+        self.link_v4 = link;
+        self
+    }
+
+    pub fn with_link_v6(mut self, link: Ipv6Net) -> Self {
+        self.link_v6 = link;
+        self
+    }
+}
+
+/// A named network namespace. Clones refer to the same namespace.
+#[derive(Clone)]
+pub struct Netns {
+    name: String,
+    fd: Arc<OwnedFd>,
+}
+
+impl Netns {
+    /// Open the namespace `name`, creating it like `ip netns add` if it
+    /// doesn't exist.
+    pub fn open_or_create(name: &str) -> io::Result<Self> {
+        let path = Path::new(NETNS_DIR).join(name);
+        if let Ok(file) = File::open(&path)
+            && is_nsfs(&file)
+        {
+            debug!(name, "Using existing network namespace");
+            return Ok(Netns {
+                name: name.to_string(),
+                fd: Arc::new(file.into()),
+            });
+        }
+
+        fs::create_dir_all(NETNS_DIR)?;
+        File::create(&path)?;
+        // A namespace lives as long as something refers to it; bind
+        // mounting it keeps it past this thread
+        let mount_path = path.clone();
+        std::thread::spawn(move || create_mounted(&mount_path))
+            .join()
+            .map_err(|_| io::Error::other("netns thread panicked"))??;
+        info!(name, "Created network namespace");
+
+        let file = File::open(&path)?;
+        Ok(Netns {
+            name: name.to_string(),
+            fd: Arc::new(file.into()),
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Run `f` on a thread inside the namespace. Interfaces, sockets and
+    /// `/proc/sys/net` files it creates or opens belong to the namespace.
+    pub fn run<T: Send>(&self, f: impl FnOnce() -> io::Result<T> + Send) -> io::Result<T> {
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        let fd = self.fd.as_raw_fd();
+        std::thread::scope(|scope| {
+            scope
+                .spawn(|| {
+                    if unsafe { libc::setns(fd, libc::CLONE_NEWNET) } < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    // Netlink sockets register with the runtime on creation
+                    let _guard = runtime.as_ref().map(|rt| rt.enter());
+                    f()
+                })
+                .join()
+                .map_err(|_| io::Error::other("netns thread panicked"))?
+        })
+    }
+}
+
+/// A netlink handle for `netns`, or for the host namespace without one.
+pub fn netlink(netns: Option<&Netns>) -> io::Result<Handle> {
+    let (connection, handle, _) = match netns {
+        Some(netns) => netns.run(rtnetlink::new_connection)?,
+        None => rtnetlink::new_connection()?,
+    };
+    tokio::spawn(connection);
+    Ok(handle)
+}
+
+/// The uplink namespace and the host end of its veth pair.
+pub struct UplinkNamespace {
+    netns: Netns,
+    host_veth: String,
+    host_if_index: u32,
+    ns_v4: Ipv4Addr,
+    ns_v6: Ipv6Addr,
+}
+
+impl UplinkNamespace {
+    /// Create the namespace and its veth pair, or pick up the ones a
+    /// previous run left.
+    pub async fn setup(config: &NetnsConfig) -> io::Result<Self> {
+        let netns = Netns::open_or_create(&config.name)?;
+        let (host_v4, ns_v4) = transfer_addrs_v4(config.link_v4)?;
+        let (host_v6, ns_v6) = transfer_addrs_v6(config.link_v6)?;
+
+        // Interface names are at most 15 characters
+        let short: String = config.name.chars().take(11).collect();
+        let host_veth = format!("mvh-{}", short);
+        let peer_veth = format!("mvn-{}", short);
+
+        let host = netlink(None)?;
+        let host_if_index = match link_index(&host, &host_veth).await? {
+            Some(index) => index,
+            None => {
+                host.link()
+                    .add()
+                    .veth(host_veth.clone(), peer_veth.clone())
+                    .execute()
+                    .await
+                    .map_err(io::Error::other)?;
+                let peer_index = link_index(&host, &peer_veth)
+                    .await?
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "veth peer missing"))?;
+                host.link()
+                    .set(peer_index)
+                    .setns_by_fd(netns.fd.as_raw_fd())
+                    .execute()
+                    .await
+                    .map_err(io::Error::other)?;
+                info!(host = %host_veth, netns = %config.name, "Created uplink veth pair");
+                link_index(&host, &host_veth)
+                    .await?
+                    .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "veth missing"))?
+            }
+        };
+        add_address(
+            &host,
+            host_if_index,
+            host_v4.into(),
+            config.link_v4.prefix_len(),
+        )
+        .await?;
+        add_address(
+            &host,
+            host_if_index,
+            host_v6.into(),
+            config.link_v6.prefix_len(),
+        )
+        .await?;
+        host.link()
+            .set(host_if_index)
+            .up()
+            .execute()
+            .await
+            .map_err(io::Error::other)?;
+
+        // Inside: loopback and our end up, everything not routed to a TUN
+        // goes back to the host
+        let inner = netlink(Some(&netns))?;
+        let lo = link_index(&inner, "lo")
+            .await?
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "lo missing"))?;
+        inner
+            .link()
+            .set(lo)
+            .up()
+            .execute()
+            .await
+            .map_err(io::Error::other)?;
+        let ns_if_index = match link_index(&inner, &peer_veth).await? {
+            Some(index) => {
+                inner
+                    .link()
+                    .set(index)
+                    .name(NS_VETH.to_string())
+                    .execute()
+                    .await
+                    .map_err(io::Error::other)?;
+                index
+            }
+            None => link_index(&inner, NS_VETH)
+                .await?
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "uplink veth missing"))?,
+        };
+        add_address(
+            &inner,
+            ns_if_index,
+            ns_v4.into(),
+            config.link_v4.prefix_len(),
+        )
+        .await?;
+        add_address(
+            &inner,
+            ns_if_index,
+            ns_v6.into(),
+            config.link_v6.prefix_len(),
+        )
+        .await?;
+        inner
+            .link()
+            .set(ns_if_index)
+            .up()
+            .execute()
+            .await
+            .map_err(io::Error::other)?;
+        ignore_exists(inner.route().add().v4().gateway(host_v4).execute().await)?;
+        ignore_exists(inner.route().add().v6().gateway(host_v6).execute().await)?;
+
+        netns.run(|| {
+            fs::write("/proc/sys/net/ipv4/ip_forward", "1")?;
+            fs::write("/proc/sys/net/ipv6/conf/all/forwarding", "1")
+        })?;
+
+        info!(
+            netns = %config.name,
+            host = %host_veth,
+            ns_v4 = %ns_v4,
+            ns_v6 = %ns_v6,
+            "Uplink namespace ready"
+        );
+        Ok(UplinkNamespace {
+            netns,
+            host_veth,
+            host_if_index,
+            ns_v4,
+            ns_v6,
+        })
+    }
+
+    pub fn netns(&self) -> &Netns {
+        &self.netns
+    }
+
+    /// Route `prefix` from the host into the namespace.
+    pub async fn add_host_route(&self, prefix: IpNet) -> io::Result<()> {
+        let host = netlink(None)?;
+        let result = match prefix {
+            IpNet::V4(net) => {
+                host.route()
+                    .add()
+                    .v4()
+                    .destination_prefix(net.addr(), net.prefix_len())
+                    .gateway(self.ns_v4)
+                    .output_interface(self.host_if_index)
+                    .execute()
+                    .await
+            }
+            IpNet::V6(net) => {
+                host.route()
+                    .add()
+                    .v6()
+                    .destination_prefix(net.addr(), net.prefix_len())
+                    .gateway(self.ns_v6)
+                    .output_interface(self.host_if_index)
+                    .execute()
+                    .await
+            }
+        };
+        ignore_exists(result)?;
+        debug!(prefix = %prefix, veth = %self.host_veth, "Host route into uplink namespace added");
+        Ok(())
+    }
+
+    /// Remove the host route of `prefix` into the namespace.
+    pub async fn remove_host_route(&self, prefix: IpNet) -> io::Result<()> {
+        use netlink_packet_route::AddressFamily;
+        use netlink_packet_route::route::{
+            RouteAddress, RouteAttribute, RouteHeader, RouteMessage,
+        };
+
+        let host = netlink(None)?;
+        let mut message = RouteMessage::default();
+        message.header.destination_prefix_length = prefix.prefix_len();
+        message.header.table = RouteHeader::RT_TABLE_MAIN;
+        match prefix {
+            IpNet::V4(net) => {
+                message.header.address_family = AddressFamily::Inet;
+                message
+                    .attributes
+                    .push(RouteAttribute::Destination(RouteAddress::Inet(net.addr())));
+            }
+            IpNet::V6(net) => {
+                message.header.address_family = AddressFamily::Inet6;
+                message
+                    .attributes
+                    .push(RouteAttribute::Destination(RouteAddress::Inet6(net.addr())));
+            }
+        }
+        message
+            .attributes
+            .push(RouteAttribute::Oif(self.host_if_index));
+
+        match host.route().del(message).execute().await {
+            Ok(()) => {
+                debug!(prefix = %prefix, "Host route into uplink namespace removed");
+                Ok(())
+            }
+            Err(rtnetlink::Error::NetlinkError(e)) if e.raw_code() == -libc::ESRCH => Ok(()),
+            Err(e) => Err(io::Error::other(e)),
+        }
+    }
+}
+
+/// Create a network namespace on this thread and bind mount it on `path`.
+fn create_mounted(path: &Path) -> io::Result<()> {
+    if unsafe { libc::unshare(libc::CLONE_NEWNET) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let source = CString::new("/proc/thread-self/ns/net").unwrap();
+    let target = CString::new(path.as_os_str().as_bytes()).map_err(io::Error::other)?;
+    let ret = unsafe {
+        libc::mount(
+            source.as_ptr(),
+            target.as_ptr(),
+            std::ptr::null(),
+            libc::MS_BIND,
+            std::ptr::null(),
+        )
+    };
+    if ret < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn is_nsfs(file: &File) -> bool {
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    unsafe { libc::fstatfs(file.as_raw_fd(), &mut stat) == 0 && stat.f_type as i64 == NSFS_MAGIC }
+}
+
+async fn link_index(handle: &Handle, name: &str) -> io::Result<Option<u32>> {
+    let mut links = handle.link().get().match_name(name.to_string()).execute();
+    match links.try_next().await {
+        Ok(link) => Ok(link.map(|l| l.header.index)),
+        Err(rtnetlink::Error::NetlinkError(e)) if e.raw_code() == -libc::ENODEV => Ok(None),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+async fn add_address(
+    handle: &Handle,
+    if_index: u32,
+    addr: IpAddr,
+    prefix_len: u8,
+) -> io::Result<()> {
+    ignore_exists(
+        handle
+            .address()
+            .add(if_index, addr, prefix_len)
+            .execute()
+            .await,
+    )
+}
+
+/// Treat "already exists" as success, for setup that may run twice.
+fn ignore_exists(result: Result<(), rtnetlink::Error>) -> io::Result<()> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(rtnetlink::Error::NetlinkError(e)) if e.raw_code() == -libc::EEXIST => Ok(()),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+/// Host and namespace addresses of an IPv4 transfer network.
+fn transfer_addrs_v4(link: Ipv4Net) -> io::Result<(Ipv4Addr, Ipv4Addr)> {
+    let mut hosts = link.hosts();
+    match (hosts.next(), hosts.next()) {
+        (Some(host), Some(ns)) => Ok((host, ns)),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("transfer network {} has no room for two addresses", link),
+        )),
+    }
+}
+
+/// Host and namespace addresses of an IPv6 transfer network.
+fn transfer_addrs_v6(link: Ipv6Net) -> io::Result<(Ipv6Addr, Ipv6Addr)> {
+    if link.prefix_len() > 126 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("transfer network {} has no room for two addresses", link),
+        ));
+    }
+    let base = u128::from(link.network());
+    Ok((Ipv6Addr::from(base + 1), Ipv6Addr::from(base + 2)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_transfer_addrs() {
+        assert_eq!(
+            transfer_addrs_v4(DEFAULT_LINK_V4).unwrap(),
+            (
+                Ipv4Addr::new(169, 254, 200, 1),
+                Ipv4Addr::new(169, 254, 200, 2)
+            )
+        );
+        assert!(transfer_addrs_v4("10.0.0.0/31".parse().unwrap()).is_err());
+
+        let (host, ns) = transfer_addrs_v6(DEFAULT_LINK_V6).unwrap();
+        assert_eq!(host, "fd6d:7669:7274::1".parse::<Ipv6Addr>().unwrap());
+        assert_eq!(ns, "fd6d:7669:7274::2".parse::<Ipv6Addr>().unwrap());
+        assert!(transfer_addrs_v6("fd00::/127".parse().unwrap()).is_err());
+    }
+}
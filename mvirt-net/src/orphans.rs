@@ -0,0 +1,220 @@
+//! Cleanup of vhost-user sockets nothing references anymore.
+//!
+//! Each NIC listens on `nic-<uuid>.sock` in the socket directory. When the
+//! daemon dies before a NIC is torn down, or a NIC is deleted from the
+//! database by hand, the socket file stays behind. The sweeper compares the
+//! directory against the NICs in the store and removes sockets no NIC
+//! claims, once at startup and then on an interval. Per-NIC TUN devices
+//! need no sweeping: they are not persistent and go away with the process.
+//!
+//! The startup pass runs before the gRPC server accepts requests, so it
+//! removes what it finds right away. Later passes race with CreateNic, which
+//! stores the NIC before its socket appears, and with DeleteNic, so a socket
+//! is only removed once two passes in a row found it unreferenced.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::audit::NetAuditLogger;
+use crate::grpc::Storage;
+
+/// Default interval between sweeps in seconds
+pub const DEFAULT_SWEEP_INTERVAL_SECS: u64 = 300;
+
+/// Whether `name` is a NIC socket as created by
+/// [`generate_socket_path`](crate::grpc::manager::generate_socket_path).
+fn is_nic_socket(name: &str) -> bool {
+    name.strip_prefix("nic-")
+        .and_then(|s| s.strip_suffix(".sock"))
+        .is_some_and(|id| Uuid::parse_str(id).is_ok())
+}
+
+/// Entries of `existing` not in `referenced`.
+pub fn orphans(existing: Vec<String>, referenced: &HashSet<String>) -> Vec<String> {
+    existing
+        .into_iter()
+        .filter(|path| !referenced.contains(path))
+        .collect()
+}
+
+/// Orphans that were already orphaned in the previous pass; `found`
+/// becomes the new set of suspects.
+pub fn confirmed(suspects: &mut HashSet<String>, found: Vec<String>) -> Vec<String> {
+    let confirmed = found
+        .iter()
+        .filter(|path| suspects.contains(*path))
+        .cloned()
+        .collect();
+    *suspects = found.into_iter().collect();
+    confirmed
+}
+
+/// NIC sockets in `dir`.
+fn list_sockets(dir: &Path) -> std::io::Result<Vec<String>> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+    let mut sockets = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_str().is_some_and(is_nic_socket) {
+            sockets.push(entry.path().to_string_lossy().into_owned());
+        }
+    }
+    Ok(sockets)
+}
+
+/// Unreferenced NIC sockets in `dir`, or `None` if the directory or the
+/// store couldn't be read.
+fn find_orphans(dir: &Path, storage: &Storage) -> Option<Vec<String>> {
+    let sockets = match list_sockets(dir) {
+        Ok(sockets) => sockets,
+        Err(e) => {
+            warn!(dir = %dir.display(), error = %e, "Failed to list NIC sockets");
+            return None;
+        }
+    };
+    let referenced = match storage.list_nics() {
+        Ok(nics) => nics.into_iter().map(|nic| nic.socket_path).collect(),
+        Err(e) => {
+            warn!(error = %e, "Failed to list NICs for orphan sweep");
+            return None;
+        }
+    };
+    Some(orphans(sockets, &referenced))
+}
+
+fn remove(orphans: Vec<String>, audit: &NetAuditLogger) {
+    for path in orphans {
+        match std::fs::remove_file(&path) {
+            Ok(()) => {
+                info!(path = %path, "Removed orphaned vhost-user socket");
+                audit.orphan_removed("vhost-user socket", &path);
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => warn!(path = %path, error = %e, "Failed to remove orphaned socket"),
+        }
+    }
+}
+
+/// Periodic orphan sweeper.
+pub struct OrphanSweeper {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl OrphanSweeper {
+    /// Remove every unreferenced socket in `dir` now. Only safe while no
+    /// NICs are being created or deleted.
+    pub fn sweep(dir: &Path, storage: &Storage, audit: &NetAuditLogger) {
+        if let Some(orphans) = find_orphans(dir, storage) {
+            remove(orphans, audit);
+        }
+    }
+
+    /// Start a new sweeper task for the sockets in `dir`.
+    pub fn start(
+        dir: PathBuf,
+        storage: Arc<Storage>,
+        audit: Arc<NetAuditLogger>,
+        interval: Duration,
+    ) -> Self {
+        info!(dir = %dir.display(), interval = ?interval, "Orphan sweeper started");
+
+        let task = tokio::spawn(sweep_loop(dir, storage, audit, interval));
+        Self { task }
+    }
+
+    /// Stop the sweeper task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Orphan sweeper stopped");
+    }
+}
+
+/// Main sweeper loop.
+async fn sweep_loop(
+    dir: PathBuf,
+    storage: Arc<Storage>,
+    audit: Arc<NetAuditLogger>,
+    interval: Duration,
+) {
+    let mut interval = time::interval(interval);
+    // The startup sweep just ran
+    interval.tick().await;
+    let mut suspects = HashSet::new();
+
+    loop {
+        interval.tick().await;
+
+        let Some(found) = find_orphans(&dir, &storage) else {
+            continue;
+        };
+        debug!(suspects = found.len(), "Orphan sweep");
+        remove(confirmed(&mut suspects, found), &audit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(items: &[&str]) -> HashSet<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn list(items: &[&str]) -> Vec<String> {
+        items.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_is_nic_socket() {
+        assert!(is_nic_socket(
+            "nic-0b1c6a4e-7a3f-4c1e-9d2b-5f6e7a8b9c0d.sock"
+        ));
+        assert!(!is_nic_socket("nic-foo.sock"));
+        assert!(!is_nic_socket(
+            "vm-0b1c6a4e-7a3f-4c1e-9d2b-5f6e7a8b9c0d.sock"
+        ));
+        assert!(!is_nic_socket("nic-0b1c6a4e-7a3f-4c1e-9d2b-5f6e7a8b9c0d"));
+    }
+
+    #[test]
+    fn test_orphans() {
+        let referenced = set(&["/a", "/b"]);
+        assert_eq!(orphans(list(&["/a", "/c"]), &referenced), list(&["/c"]));
+        assert!(orphans(list(&["/a", "/b"]), &referenced).is_empty());
+    }
+
+    #[test]
+    fn test_confirmed_needs_two_passes() {
+        let mut suspects = HashSet::new();
+        assert!(confirmed(&mut suspects, list(&["/a", "/b"])).is_empty());
+        // /b got claimed in between, /c is new
+        assert_eq!(confirmed(&mut suspects, list(&["/a", "/c"])), list(&["/a"]));
+        assert_eq!(suspects, set(&["/a", "/c"]));
+        assert_eq!(confirmed(&mut suspects, list(&["/c"])), list(&["/c"]));
+    }
+
+    #[test]
+    fn test_list_sockets() {
+        let dir = std::env::temp_dir().join(format!("mvirt-orphans-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join(format!("nic-{}.sock", Uuid::new_v4()));
+        std::fs::write(&socket, b"").unwrap();
+        std::fs::write(dir.join("other.sock"), b"").unwrap();
+
+        let sockets = list_sockets(&dir).unwrap();
+        assert_eq!(sockets, vec![socket.to_string_lossy().into_owned()]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(list_sockets(&dir).unwrap().is_empty());
+    }
+}
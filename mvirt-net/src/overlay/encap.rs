@@ -0,0 +1,161 @@
+//! VXLAN (RFC 7348) and Geneve (RFC 8926) headers.
+//!
+//! Both carry an Ethernet frame after an 8-byte header with a 24-bit
+//! virtual network identifier (VNI). Geneve may add options after its
+//! header; they are skipped on receive and never sent.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// IANA port for VXLAN
+pub const VXLAN_PORT: u16 = 4789;
+
+/// IANA port for Geneve
+pub const GENEVE_PORT: u16 = 6081;
+
+/// Size of the header both encapsulations send
+pub const HEADER_LEN: usize = 8;
+
+/// Largest VNI
+pub const MAX_VNI: u32 = 0x00ff_ffff;
+
+/// VXLAN flags: the VNI is valid
+const VXLAN_FLAG_VNI: u8 = 0x08;
+
+/// Geneve protocol type of an Ethernet payload (transparent Ethernet bridging)
+const GENEVE_PROTO_ETHERNET: u16 = 0x6558;
+
+/// Geneve flags: control packet, not to be forwarded
+const GENEVE_FLAG_OAM: u8 = 0x80;
+
+/// Encapsulation of the overlay.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encap {
+    Vxlan,
+    Geneve,
+}
+
+impl Encap {
+    /// UDP port the encapsulation uses unless configured otherwise.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Encap::Vxlan => VXLAN_PORT,
+            Encap::Geneve => GENEVE_PORT,
+        }
+    }
+
+    /// Header for a frame in `vni`.
+    pub fn header(self, vni: u32) -> [u8; HEADER_LEN] {
+        let mut header = [0u8; HEADER_LEN];
+        match self {
+            Encap::Vxlan => header[0] = VXLAN_FLAG_VNI,
+            Encap::Geneve => header[2..4].copy_from_slice(&GENEVE_PROTO_ETHERNET.to_be_bytes()),
+        }
+        header[4..7].copy_from_slice(&vni.to_be_bytes()[1..]);
+        header
+    }
+
+    /// VNI and inner frame of a datagram, `None` for anything else
+    /// (other versions, Geneve control packets, non-Ethernet payloads).
+    pub fn parse(self, datagram: &[u8]) -> Option<(u32, &[u8])> {
+        let header = datagram.get(..HEADER_LEN)?;
+        let vni = u32::from_be_bytes([0, header[4], header[5], header[6]]);
+        let payload = match self {
+            Encap::Vxlan => {
+                if header[0] & VXLAN_FLAG_VNI == 0 {
+                    return None;
+                }
+                &datagram[HEADER_LEN..]
+            }
+            Encap::Geneve => {
+                let version = header[0] >> 6;
+                let options = usize::from(header[0] & 0x3f) * 4;
+                let protocol = u16::from_be_bytes([header[2], header[3]]);
+                if version != 0
+                    || header[1] & GENEVE_FLAG_OAM != 0
+                    || protocol != GENEVE_PROTO_ETHERNET
+                {
+                    return None;
+                }
+                datagram.get(HEADER_LEN + options..)?
+            }
+        };
+        Some((vni, payload))
+    }
+}
+
+impl fmt::Display for Encap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Encap::Vxlan => write!(f, "vxlan"),
+            Encap::Geneve => write!(f, "geneve"),
+        }
+    }
+}
+
+impl FromStr for Encap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vxlan" => Ok(Encap::Vxlan),
+            "geneve" => Ok(Encap::Geneve),
+            _ => Err(format!("unknown overlay encapsulation: {s}")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vxlan_header() {
+        let header = Encap::Vxlan.header(0x123456);
+        assert_eq!(header, [0x08, 0, 0, 0, 0x12, 0x34, 0x56, 0]);
+
+        let mut datagram = header.to_vec();
+        datagram.extend_from_slice(b"frame");
+        assert_eq!(
+            Encap::Vxlan.parse(&datagram),
+            Some((0x123456, &b"frame"[..]))
+        );
+
+        // No VNI flag
+        datagram[0] = 0;
+        assert_eq!(Encap::Vxlan.parse(&datagram), None);
+        assert_eq!(Encap::Vxlan.parse(&[0x08, 0, 0]), None);
+    }
+
+    #[test]
+    fn test_geneve_header() {
+        let header = Encap::Geneve.header(42);
+        assert_eq!(header, [0, 0, 0x65, 0x58, 0, 0, 42, 0]);
+
+        let mut datagram = header.to_vec();
+        datagram.extend_from_slice(b"frame");
+        assert_eq!(Encap::Geneve.parse(&datagram), Some((42, &b"frame"[..])));
+
+        // One 4-byte option word is skipped
+        let mut with_option = datagram.clone();
+        with_option[0] = 1;
+        with_option.splice(HEADER_LEN..HEADER_LEN, [0xaa; 4]);
+        assert_eq!(Encap::Geneve.parse(&with_option), Some((42, &b"frame"[..])));
+
+        // Control packets and other payloads are not delivered
+        let mut oam = datagram.clone();
+        oam[1] = GENEVE_FLAG_OAM;
+        assert_eq!(Encap::Geneve.parse(&oam), None);
+        let mut ipv4 = datagram.clone();
+        ipv4[2..4].copy_from_slice(&0x0800u16.to_be_bytes());
+        assert_eq!(Encap::Geneve.parse(&ipv4), None);
+    }
+
+    #[test]
+    fn test_parse_encap() {
+        assert_eq!("vxlan".parse(), Ok(Encap::Vxlan));
+        assert_eq!("geneve".parse(), Ok(Encap::Geneve));
+        assert!("gre".parse::<Encap>().is_err());
+        assert_eq!(Encap::Geneve.default_port(), GENEVE_PORT);
+    }
+}
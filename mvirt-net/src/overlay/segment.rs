@@ -0,0 +1,277 @@
+//! Guest frames made ready for the overlay.
+//!
+//! Guests hand the NIC frames with offloads pending: an L4 checksum left
+//! for the host to finish (`NEEDS_CSUM`) and TCP segmentation (TSO), up to
+//! 64 KiB per frame. Locally the TUN and the receiving guest finish them,
+//! but an encapsulated frame leaves the host as it is, so the overlay does
+//! both in software first: checksums are completed and TSO frames are cut
+//! into segments of the guest's segment size.
+
+use super::encap::HEADER_LEN;
+
+/// virtio-net header flag: the L4 checksum is left to be completed
+const VIRTIO_NET_HDR_F_NEEDS_CSUM: u8 = 1;
+
+/// virtio_net_hdr gso_type values (the ECN bit masked off)
+const GSO_NONE: u8 = 0;
+const GSO_TCPV4: u8 = 1;
+const GSO_TCPV6: u8 = 4;
+const GSO_ECN: u8 = 0x80;
+
+const ETHERNET_HDR_SIZE: usize = 14;
+const IPV6_HDR_SIZE: usize = 40;
+const IPPROTO_TCP: u8 = 6;
+
+/// TCP flags cleared on all but the first or the last segment
+const TCP_FLAG_FIN: u8 = 0x01;
+const TCP_FLAG_PSH: u8 = 0x08;
+const TCP_FLAG_CWR: u8 = 0x80;
+
+/// The Ethernet frames to send for a guest frame, each after
+/// [`HEADER_LEN`] bytes left free for the encapsulation header. `None` for
+/// frames that can't be finished (malformed, or an offload that isn't
+/// offered to guests).
+pub fn wire_frames(virtio_hdr: &[u8], frame: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let flags = *virtio_hdr.first()?;
+    let gso_type = *virtio_hdr.get(1)? & !GSO_ECN;
+    match gso_type {
+        GSO_NONE => {
+            let mut buf = with_headroom(frame);
+            if flags & VIRTIO_NET_HDR_F_NEEDS_CSUM != 0 {
+                let csum_start = usize::from(read_le16(virtio_hdr, 6)?);
+                let csum_offset = usize::from(read_le16(virtio_hdr, 8)?);
+                complete_checksum(&mut buf[HEADER_LEN..], csum_start, csum_offset)?;
+            }
+            Some(vec![buf])
+        }
+        GSO_TCPV4 | GSO_TCPV6 => {
+            let mss = usize::from(read_le16(virtio_hdr, 4)?);
+            segment_tcp(frame, mss)
+        }
+        _ => None,
+    }
+}
+
+/// Finish a partial checksum: the guest put the pseudo-header sum at
+/// `csum_start + csum_offset`, the rest is summed from `csum_start` on.
+fn complete_checksum(frame: &mut [u8], csum_start: usize, csum_offset: usize) -> Option<()> {
+    let at = csum_start + csum_offset;
+    if at + 2 > frame.len() {
+        return None;
+    }
+    let sum = !fold(sum(&frame[csum_start..], 0));
+    frame[at..at + 2].copy_from_slice(&sum.to_be_bytes());
+    Some(())
+}
+
+/// Cut a TCP frame into segments of at most `mss` payload bytes, with the
+/// IP and TCP headers of each fixed up and the checksums computed.
+fn segment_tcp(frame: &[u8], mss: usize) -> Option<Vec<Vec<u8>>> {
+    if mss == 0 {
+        return None;
+    }
+    let ip = frame.get(ETHERNET_HDR_SIZE..)?;
+    let (ipv4, ip_hdr_len) = match ip.first()? >> 4 {
+        4 => (true, usize::from(ip[0] & 0x0f) * 4),
+        6 => (false, IPV6_HDR_SIZE),
+        _ => return None,
+    };
+    let protocol = if ipv4 { *ip.get(9)? } else { *ip.get(6)? };
+    if protocol != IPPROTO_TCP || ip_hdr_len < 20 {
+        return None;
+    }
+    let tcp = ip.get(ip_hdr_len..)?;
+    let tcp_hdr_len = usize::from(tcp.get(12)? >> 4) * 4;
+    let hdr_len = ETHERNET_HDR_SIZE + ip_hdr_len + tcp_hdr_len;
+    if tcp_hdr_len < 20 || hdr_len > frame.len() {
+        return None;
+    }
+
+    let headers = &frame[..hdr_len];
+    let payload = &frame[hdr_len..];
+    let seq = u32::from_be_bytes(tcp[4..8].try_into().unwrap());
+    let ip_id = u16::from_be_bytes([ip[4], ip[5]]);
+    let chunks: Vec<&[u8]> = if payload.is_empty() {
+        vec![payload]
+    } else {
+        payload.chunks(mss).collect()
+    };
+    let count = chunks.len();
+
+    let mut segments = Vec::with_capacity(count);
+    for (i, chunk) in chunks.into_iter().enumerate() {
+        let mut buf = Vec::with_capacity(HEADER_LEN + hdr_len + chunk.len());
+        buf.resize(HEADER_LEN, 0);
+        buf.extend_from_slice(headers);
+        buf.extend_from_slice(chunk);
+        let seg = &mut buf[HEADER_LEN + ETHERNET_HDR_SIZE..];
+        let l4_len = tcp_hdr_len + chunk.len();
+
+        if ipv4 {
+            let total = (ip_hdr_len + l4_len) as u16;
+            seg[2..4].copy_from_slice(&total.to_be_bytes());
+            seg[4..6].copy_from_slice(&ip_id.wrapping_add(i as u16).to_be_bytes());
+            seg[10..12].fill(0);
+            let check = !fold(sum(&seg[..ip_hdr_len], 0));
+            seg[10..12].copy_from_slice(&check.to_be_bytes());
+        } else {
+            seg[4..6].copy_from_slice(&(l4_len as u16).to_be_bytes());
+        }
+
+        let (ip_hdr, tcp) = seg.split_at_mut(ip_hdr_len);
+        let seg_seq = seq.wrapping_add((i * mss) as u32);
+        tcp[4..8].copy_from_slice(&seg_seq.to_be_bytes());
+        if i + 1 < count {
+            tcp[13] &= !(TCP_FLAG_FIN | TCP_FLAG_PSH);
+        }
+        if i > 0 {
+            tcp[13] &= !TCP_FLAG_CWR;
+        }
+        tcp[16..18].fill(0);
+        let addresses = if ipv4 {
+            &ip_hdr[12..20]
+        } else {
+            &ip_hdr[8..40]
+        };
+        let pseudo = sum(addresses, u32::from(IPPROTO_TCP) + l4_len as u32);
+        let check = !fold(sum(tcp, pseudo));
+        tcp[16..18].copy_from_slice(&check.to_be_bytes());
+
+        segments.push(buf);
+    }
+    Some(segments)
+}
+
+/// A copy of `frame` after [`HEADER_LEN`] free bytes.
+fn with_headroom(frame: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_LEN + frame.len());
+    buf.resize(HEADER_LEN, 0);
+    buf.extend_from_slice(frame);
+    buf
+}
+
+/// One's complement sum of `data` as 16-bit words, added to `initial`.
+fn sum(data: &[u8], initial: u32) -> u32 {
+    let mut sum = initial;
+    let mut words = data.chunks_exact(2);
+    for word in &mut words {
+        sum += u32::from(u16::from_be_bytes([word[0], word[1]]));
+    }
+    if let [last] = words.remainder() {
+        sum += u32::from(*last) << 8;
+    }
+    sum
+}
+
+fn fold(mut sum: u32) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+fn read_le16(buf: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(buf.get(at..at + 2)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smoltcp::wire::{IpAddress, Ipv4Packet, TcpPacket};
+
+    /// An Ethernet frame with an IPv4 TCP packet carrying `payload`, its
+    /// checksums left to the host like a guest with offloads does.
+    fn tcp_frame(payload: &[u8], flags: u8) -> Vec<u8> {
+        let mut frame = vec![0u8; ETHERNET_HDR_SIZE];
+        frame[12..14].copy_from_slice(&0x0800u16.to_be_bytes());
+        let total = (20 + 20 + payload.len()) as u16;
+        let mut ip = [0u8; 20];
+        ip[0] = 0x45;
+        ip[2..4].copy_from_slice(&total.to_be_bytes());
+        ip[4..6].copy_from_slice(&100u16.to_be_bytes());
+        ip[8] = 64;
+        ip[9] = IPPROTO_TCP;
+        ip[12..16].copy_from_slice(&[10, 0, 0, 2]);
+        ip[16..20].copy_from_slice(&[10, 0, 0, 3]);
+        let check = !fold(sum(&ip, 0));
+        ip[10..12].copy_from_slice(&check.to_be_bytes());
+        frame.extend_from_slice(&ip);
+        let mut tcp = [0u8; 20];
+        tcp[0..2].copy_from_slice(&40000u16.to_be_bytes());
+        tcp[2..4].copy_from_slice(&80u16.to_be_bytes());
+        tcp[4..8].copy_from_slice(&1000u32.to_be_bytes());
+        tcp[12] = 5 << 4;
+        tcp[13] = flags;
+        // Pseudo-header sum, as guests leave it
+        let pseudo = fold(sum(&ip[12..20], 0) + 6 + 20 + payload.len() as u32);
+        tcp[16..18].copy_from_slice(&pseudo.to_be_bytes());
+        frame.extend_from_slice(&tcp);
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn virtio_hdr(gso_type: u8, gso_size: u16) -> [u8; 12] {
+        let mut hdr = [0u8; 12];
+        hdr[0] = VIRTIO_NET_HDR_F_NEEDS_CSUM;
+        hdr[1] = gso_type;
+        hdr[2..4].copy_from_slice(&54u16.to_le_bytes());
+        hdr[4..6].copy_from_slice(&gso_size.to_le_bytes());
+        hdr[6..8].copy_from_slice(&34u16.to_le_bytes());
+        hdr[8..10].copy_from_slice(&16u16.to_le_bytes());
+        hdr
+    }
+
+    fn assert_valid(frame: &[u8]) -> (u32, usize, u8) {
+        let ip = Ipv4Packet::new_checked(&frame[ETHERNET_HDR_SIZE..]).unwrap();
+        assert!(ip.verify_checksum());
+        let tcp = TcpPacket::new_checked(ip.payload()).unwrap();
+        assert!(tcp.verify_checksum(
+            &IpAddress::Ipv4(ip.src_addr()),
+            &IpAddress::Ipv4(ip.dst_addr())
+        ));
+        (tcp.seq_number().0 as u32, tcp.payload().len(), frame[47])
+    }
+
+    #[test]
+    fn test_checksum_completed() {
+        let frame = tcp_frame(b"hello", TCP_FLAG_PSH);
+        let frames = wire_frames(&virtio_hdr(GSO_NONE, 0), &frame).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].len(), HEADER_LEN + frame.len());
+        assert_valid(&frames[0][HEADER_LEN..]);
+
+        // Frames without pending offloads are sent as they are
+        let frames = wire_frames(&[0u8; 12], &frame).unwrap();
+        assert_eq!(&frames[0][HEADER_LEN..], &frame[..]);
+    }
+
+    #[test]
+    fn test_tso_segmented() {
+        let payload: Vec<u8> = (0..2500u32).map(|i| i as u8).collect();
+        let frame = tcp_frame(&payload, TCP_FLAG_PSH | TCP_FLAG_FIN);
+        let frames = wire_frames(&virtio_hdr(GSO_TCPV4, 1000), &frame).unwrap();
+        assert_eq!(frames.len(), 3);
+
+        let segments: Vec<_> = frames
+            .iter()
+            .map(|f| assert_valid(&f[HEADER_LEN..]))
+            .collect();
+        assert_eq!(segments[0], (1000, 1000, 0));
+        assert_eq!(segments[1], (2000, 1000, 0));
+        assert_eq!(segments[2], (3000, 500, TCP_FLAG_PSH | TCP_FLAG_FIN));
+        // Each segment has its own IP ID
+        assert_eq!(
+            &frames[1][HEADER_LEN + 18..HEADER_LEN + 20],
+            &101u16.to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn test_unsupported_offloads() {
+        let frame = tcp_frame(b"data", 0);
+        // UDP fragmentation offload isn't offered to guests
+        assert!(wire_frames(&virtio_hdr(3, 1000), &frame).is_none());
+        assert!(wire_frames(&virtio_hdr(GSO_TCPV4, 0), &frame).is_none());
+        assert!(wire_frames(&virtio_hdr(GSO_TCPV4, 1000), &frame[..30]).is_none());
+    }
+}
@@ -139,7 +139,14 @@ mod tests {
             ipv6_address: None,
             ipv6_gateway: None,
             ipv6_prefix_len: 64,
+            ipv6_delegated_prefix: None,
             dns_servers: vec![],
+            boot_server: None,
+            boot_file: None,
+            mtu: 1500,
+            tx_burst: crate::reactor::DEFAULT_TX_BURST,
+            leases: vec![],
+            dns_zone: None,
         }
     }
 
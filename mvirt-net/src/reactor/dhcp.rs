@@ -2,6 +2,16 @@
 //!
 //! This module implements a minimal DHCP server that responds to DISCOVER and REQUEST
 //! messages from VMs with the configured IP address, gateway, and DNS servers.
+//!
+//! The NIC's own MAC gets the NIC's address. Other MACs behind the NIC (nested
+//! VMs or containers bridged in the guest) get the address of their static
+//! lease, if they have one, and no answer otherwise.
+//!
+//! Clients are given the network's DNS zone as their domain, and the
+//! gateway as their resolver if the network has no DNS servers.
+//!
+//! Boot firmware (PXE or UEFI HTTP boot clients) is offered the network's
+//! boot file; the booted OS gets a plain lease.
 
 use super::{GATEWAY_IPV4_LINK_LOCAL, GATEWAY_MAC, NicConfig};
 use dhcproto::v4::{DhcpOption, Flags, Message, MessageType, Opcode, OptionCode};
@@ -112,6 +122,23 @@ pub fn handle_dhcp_packet(
     }
 }
 
+/// The address for the client with hardware address `chaddr`.
+fn client_address(nic_config: &NicConfig, chaddr: &[u8]) -> Option<Ipv4Addr> {
+    let mac: [u8; 6] = chaddr.get(..6)?.try_into().ok()?;
+    if mac == nic_config.mac {
+        return nic_config.ipv4_address;
+    }
+    let leased = nic_config
+        .leases
+        .iter()
+        .find(|(lease_mac, _)| *lease_mac == mac)
+        .map(|(_, addr)| *addr);
+    if leased.is_none() {
+        debug!(client_mac = ?mac, "DHCP: no lease for client MAC");
+    }
+    leased
+}
+
 /// Get the DHCP message type from options.
 fn get_dhcp_message_type(msg: &Message) -> Option<MessageType> {
     msg.opts().get(OptionCode::MessageType).and_then(|opt| {
@@ -130,7 +157,7 @@ fn handle_discover(
     eth_frame: &EthernetFrame<&[u8]>,
     discover: &Message,
 ) -> Option<Vec<u8>> {
-    let ipv4_address = nic_config.ipv4_address?;
+    let ipv4_address = client_address(nic_config, discover.chaddr())?;
 
     debug!(
         offered_ip = %ipv4_address,
@@ -156,7 +183,7 @@ fn handle_request(
     eth_frame: &EthernetFrame<&[u8]>,
     request: &Message,
 ) -> Option<Vec<u8>> {
-    let ipv4_address = nic_config.ipv4_address?;
+    let ipv4_address = client_address(nic_config, request.chaddr())?;
 
     // Check if the requested IP matches what we configured
     // Get the requested IP from options
@@ -265,6 +292,17 @@ fn build_dhcp_response(
         opts.insert(DhcpOption::DomainNameServer(dns_v4));
     }
 
+    // The network's zone, answered by the gateway. Without other servers
+    // the gateway is the resolver, so names of the network resolve.
+    if let Some(zone) = &nic_config.dns_zone {
+        if nic_config.dns_servers.is_empty() {
+            opts.insert(DhcpOption::DomainNameServer(vec![GATEWAY_IPV4_LINK_LOCAL]));
+        }
+        opts.insert(DhcpOption::DomainName(zone.domain().to_string()));
+    }
+
+    add_boot_options(nic_config, request, &mut response);
+
     // Encode the DHCP message
     let mut dhcp_bytes = Vec::new();
     let mut encoder = Encoder::new(&mut dhcp_bytes);
@@ -273,6 +311,35 @@ fn build_dhcp_response(
     build_dhcp_packet(nic_config, virtio_hdr, request, &dhcp_bytes, assigned_ip)
 }
 
+/// Offer the network's boot file to clients that identify as boot
+/// firmware: `http(s)://` URLs to UEFI HTTP boot clients, anything else to
+/// PXE clients, fetched by TFTP from the boot server.
+fn add_boot_options(nic_config: &NicConfig, request: &Message, response: &mut Message) {
+    let Some(boot_file) = &nic_config.boot_file else {
+        return;
+    };
+    let client_class = match request.opts().get(OptionCode::ClassIdentifier) {
+        Some(DhcpOption::ClassIdentifier(class)) => class.as_slice(),
+        _ => &[],
+    };
+    let is_url = boot_file.starts_with("http://") || boot_file.starts_with("https://");
+
+    if is_url && client_class.starts_with(b"HTTPClient") {
+        response
+            .opts_mut()
+            .insert(DhcpOption::ClassIdentifier(b"HTTPClient".to_vec()));
+        response.set_fname(boot_file.as_bytes());
+    } else if !is_url && client_class.starts_with(b"PXEClient") {
+        response
+            .opts_mut()
+            .insert(DhcpOption::ClassIdentifier(b"PXEClient".to_vec()));
+        if let Some(server) = nic_config.boot_server {
+            response.set_siaddr(server);
+        }
+        response.set_fname(boot_file.as_bytes());
+    }
+}
+
 /// Build a DHCP NAK response.
 fn build_dhcp_nak(
     nic_config: &NicConfig,
@@ -447,9 +514,103 @@ fn build_dhcp_packet_broadcast(
 mod tests {
     use super::*;
 
+    use crate::test_util::packets::{create_dhcp_discover, parse_dhcp_response};
+
     #[test]
     fn test_link_local_gateway_constant() {
         // Verify the link-local gateway address
         assert_eq!(GATEWAY_IPV4_LINK_LOCAL, Ipv4Addr::new(169, 254, 0, 1));
     }
+
+    #[test]
+    fn test_offers_by_client_mac() {
+        let nic_mac = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+        let lease_mac = [0x02, 0x00, 0x00, 0x00, 0x01, 0x01];
+        let config = NicConfig {
+            mac: nic_mac,
+            ipv4_address: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            ipv4_gateway: Some(GATEWAY_IPV4_LINK_LOCAL),
+            ipv4_prefix_len: 24,
+            ipv6_address: None,
+            ipv6_gateway: None,
+            ipv6_prefix_len: 0,
+            ipv6_delegated_prefix: None,
+            dns_servers: vec![],
+            boot_server: None,
+            boot_file: None,
+            mtu: 1500,
+            tx_burst: crate::reactor::DEFAULT_TX_BURST,
+            leases: vec![(lease_mac, Ipv4Addr::new(10, 0, 0, 20))],
+            dns_zone: None,
+        };
+        let offer = |mac: [u8; 6]| {
+            let discover = create_dhcp_discover(mac, 1);
+            let (virtio_hdr, frame) = discover.split_at(12);
+            handle_dhcp_packet(&config, virtio_hdr, frame)
+                .and_then(|reply| parse_dhcp_response(&reply))
+                .map(|reply| reply.your_ip)
+        };
+
+        assert_eq!(offer(nic_mac), Some([10, 0, 0, 2]));
+        assert_eq!(offer(lease_mac), Some([10, 0, 0, 20]));
+        assert_eq!(offer([0x02, 0x00, 0x00, 0x00, 0x01, 0x02]), None);
+    }
+
+    #[test]
+    fn test_boot_options_by_client_class() {
+        let config = |server: Option<Ipv4Addr>, file: &str| NicConfig {
+            mac: [0x02, 0x00, 0x00, 0x00, 0x00, 0x02],
+            ipv4_address: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            ipv4_gateway: Some(GATEWAY_IPV4_LINK_LOCAL),
+            ipv4_prefix_len: 24,
+            ipv6_address: None,
+            ipv6_gateway: None,
+            ipv6_prefix_len: 0,
+            ipv6_delegated_prefix: None,
+            dns_servers: vec![],
+            boot_server: server,
+            boot_file: Some(file.to_string()),
+            mtu: 1500,
+            tx_burst: crate::reactor::DEFAULT_TX_BURST,
+            leases: vec![],
+            dns_zone: None,
+        };
+        let reply = |config: &NicConfig, class: Option<&[u8]>| {
+            let mut request = Message::default();
+            if let Some(class) = class {
+                request
+                    .opts_mut()
+                    .insert(DhcpOption::ClassIdentifier(class.to_vec()));
+            }
+            let mut response = Message::default();
+            response.set_siaddr(GATEWAY_IPV4_LINK_LOCAL);
+            add_boot_options(config, &request, &mut response);
+            response
+        };
+        let tftp_server = Ipv4Addr::new(10, 0, 0, 5);
+
+        let pxe = config(Some(tftp_server), "pxelinux.0");
+        let response = reply(&pxe, Some(b"PXEClient:Arch:00000:UNDI:002001"));
+        assert_eq!(response.siaddr(), tftp_server);
+        assert_eq!(response.fname(), Some(&b"pxelinux.0"[..]));
+        assert!(matches!(
+            response.opts().get(OptionCode::ClassIdentifier),
+            Some(DhcpOption::ClassIdentifier(class)) if class == b"PXEClient"
+        ));
+
+        // The booted OS gets a plain lease
+        let response = reply(&pxe, None);
+        assert_eq!(response.siaddr(), GATEWAY_IPV4_LINK_LOCAL);
+        assert_eq!(response.fname(), None);
+
+        let http = config(None, "http://boot.example/ipxe.efi");
+        let response = reply(&http, Some(b"HTTPClient:Arch:00016:UNDI:003001"));
+        assert_eq!(response.fname(), Some(&b"http://boot.example/ipxe.efi"[..]));
+        assert!(matches!(
+            response.opts().get(OptionCode::ClassIdentifier),
+            Some(DhcpOption::ClassIdentifier(class)) if class == b"HTTPClient"
+        ));
+        // A URL means nothing to PXE firmware
+        assert_eq!(reply(&http, Some(b"PXEClient")).fname(), None);
+    }
 }
@@ -2,10 +2,12 @@
 //!
 //! This module implements a minimal DHCPv6 server that responds to SOLICIT and REQUEST
 //! messages from VMs with the configured IPv6 address (/128) and DNS servers.
+//! Clients asking for prefix delegation (IA_PD) get the NIC's delegated
+//! prefix, if it has one.
 
-use super::{GATEWAY_MAC, NicConfig};
+use super::{GATEWAY_IPV6_LINK_LOCAL, GATEWAY_MAC, NicConfig};
 use dhcproto::v6::{
-    DhcpOption, IAAddr, IANA, Message, MessageType, OptionCode, Status, StatusCode,
+    DhcpOption, IAAddr, IANA, IAPD, IAPrefix, Message, MessageType, OptionCode, Status, StatusCode,
 };
 use dhcproto::{Decodable, Decoder, Encodable, Encoder};
 use smoltcp::wire::{
@@ -272,6 +274,10 @@ fn build_dhcpv6_response(
         };
 
         response.opts_mut().insert(DhcpOption::IANA(ia_na));
+
+        if let Some(ia_pd) = delegated_prefix(nic_config, request) {
+            response.opts_mut().insert(DhcpOption::IAPD(ia_pd));
+        }
     }
 
     // Add DNS servers if configured
@@ -287,6 +293,16 @@ fn build_dhcpv6_response(
         response
             .opts_mut()
             .insert(DhcpOption::DomainNameServers(dns_v6));
+    } else if nic_config.dns_servers.is_empty()
+        && nic_config
+            .dns_zone
+            .as_ref()
+            .is_some_and(|zone| zone.forward().is_some())
+    {
+        // The gateway resolves all names when it forwards
+        response
+            .opts_mut()
+            .insert(DhcpOption::DomainNameServers(vec![GATEWAY_IPV6_LINK_LOCAL]));
     }
 
     // Status code: Success
@@ -305,6 +321,35 @@ fn build_dhcpv6_response(
     build_dhcpv6_packet(virtio_hdr, &dhcp_bytes, dst_addr, dst_mac)
 }
 
+/// IA_PD with the NIC's delegated prefix, if the client asked for one.
+/// Like IA_NA, the client's IAID is echoed back.
+fn delegated_prefix(nic_config: &NicConfig, request: &Message) -> Option<IAPD> {
+    let prefix = nic_config.ipv6_delegated_prefix?;
+    let client_iaid = match request.opts().get(OptionCode::IAPD)? {
+        DhcpOption::IAPD(iapd) => iapd.id,
+        _ => return None,
+    };
+
+    let ia_prefix = IAPrefix {
+        preferred_lifetime: PREFERRED_LIFETIME,
+        valid_lifetime: VALID_LIFETIME,
+        prefix_len: prefix.prefix_len(),
+        prefix_ip: prefix.network(),
+        opts: Default::default(),
+    };
+
+    Some(IAPD {
+        id: client_iaid,
+        t1: PREFERRED_LIFETIME / 2,
+        t2: (PREFERRED_LIFETIME * 4) / 5,
+        opts: {
+            let mut opts = dhcproto::v6::DhcpOptions::new();
+            opts.insert(DhcpOption::IAPrefix(ia_prefix));
+            opts
+        },
+    })
+}
+
 /// Build the complete DHCPv6 response packet with Ethernet/IPv6/UDP headers.
 fn build_dhcpv6_packet(
     virtio_hdr: &[u8],
@@ -442,4 +487,78 @@ mod tests {
         let ipv6 = Ipv6Packet::new_checked(eth.payload()).unwrap();
         assert_eq!(ipv6.next_header(), IpProtocol::Udp);
     }
+
+    #[test]
+    fn test_delegates_prefix() {
+        let config = NicConfig {
+            mac: [0x02, 0x00, 0x00, 0x00, 0x00, 0x02],
+            ipv4_address: None,
+            ipv4_gateway: None,
+            ipv4_prefix_len: 0,
+            ipv6_address: Some("2001:db8::2".parse().unwrap()),
+            ipv6_gateway: Some("fe80::1".parse().unwrap()),
+            ipv6_prefix_len: 48,
+            ipv6_delegated_prefix: Some("2001:db8:0:1::/64".parse().unwrap()),
+            dns_servers: vec![],
+            boot_server: None,
+            boot_file: None,
+            mtu: 1500,
+            tx_burst: crate::reactor::DEFAULT_TX_BURST,
+            leases: vec![],
+            dns_zone: None,
+        };
+
+        let solicit = |with_pd: bool| {
+            let mut msg = Message::new(MessageType::Solicit);
+            msg.opts_mut()
+                .insert(DhcpOption::ClientId(vec![0, 3, 0, 1]));
+            msg.opts_mut().insert(DhcpOption::IANA(IANA {
+                id: 1,
+                t1: 0,
+                t2: 0,
+                opts: Default::default(),
+            }));
+            if with_pd {
+                msg.opts_mut().insert(DhcpOption::IAPD(IAPD {
+                    id: 7,
+                    t1: 0,
+                    t2: 0,
+                    opts: Default::default(),
+                }));
+            }
+            msg
+        };
+        let advertise = |request: &Message| {
+            let packet = build_dhcpv6_response(
+                &config,
+                &[0u8; 12],
+                request,
+                MessageType::Advertise,
+                Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 2),
+                EthernetAddress(config.mac),
+                config.ipv6_address,
+            )
+            .unwrap();
+            let payload = &packet[12 + ETHERNET_HEADER_SIZE + IPV6_HEADER_SIZE + UDP_HEADER_SIZE..];
+            Message::decode(&mut Decoder::new(payload)).unwrap()
+        };
+
+        let reply = advertise(&solicit(true));
+        let Some(DhcpOption::IAPD(iapd)) = reply.opts().get(OptionCode::IAPD) else {
+            panic!("no IA_PD in reply");
+        };
+        assert_eq!(iapd.id, 7);
+        let Some(DhcpOption::IAPrefix(prefix)) = iapd.opts.get(OptionCode::IAPrefix) else {
+            panic!("no prefix in IA_PD");
+        };
+        assert_eq!(
+            prefix.prefix_ip,
+            "2001:db8:0:1::".parse::<Ipv6Addr>().unwrap()
+        );
+        assert_eq!(prefix.prefix_len, 64);
+
+        // Only clients asking for a prefix get one
+        let reply = advertise(&solicit(false));
+        assert!(reply.opts().get(OptionCode::IAPD).is_none());
+    }
 }
@@ -0,0 +1,637 @@
+//! DNS responder for the names of a network.
+//!
+//! Queries to the gateway on UDP port 53 are answered in the NIC's reactor,
+//! like DHCP. Every network is a zone `<network>.internal` in which each
+//! named NIC has A and AAAA records `<nic>.<network>.internal` for its
+//! addresses; the manager rebuilds the zone from the NIC store when NICs
+//! come and go. Names outside the zone are resolved by the DNS forwarder
+//! (see [`crate::dns_forwarder`]) if the network has upstream servers: the
+//! query is handed over and the reply comes back to the reactor later.
+//! Without upstream servers they are refused, so a resolver that also
+//! knows other servers moves on to them.
+
+use super::{GATEWAY_IPV4_LINK_LOCAL, GATEWAY_IPV6_LINK_LOCAL, GATEWAY_MAC, NicConfig};
+use crate::dns_forwarder::{DnsForwarder, Query};
+use smoltcp::wire::{
+    EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, IpAddress, IpProtocol,
+    Ipv4Packet, Ipv4Repr, Ipv6Packet, Ipv6Repr, UdpPacket, UdpRepr,
+};
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsRawFd, OwnedFd};
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use tracing::debug;
+
+/// Parent domain of all network zones
+pub const ZONE_SUFFIX: &str = "internal";
+
+/// Ethernet header size
+const ETHERNET_HEADER_SIZE: usize = 14;
+
+/// Minimum IPv4 header size
+const IPV4_HEADER_SIZE: usize = 20;
+
+/// IPv6 header size
+const IPV6_HEADER_SIZE: usize = 40;
+
+/// UDP header size
+const UDP_HEADER_SIZE: usize = 8;
+
+/// DNS server port
+const DNS_PORT: u16 = 53;
+
+/// DNS message header size
+pub(crate) const DNS_HEADER_SIZE: usize = 12;
+
+/// Longest reply without EDNS (RFC 1035); longer ones are truncated
+const MAX_UDP_REPLY: usize = 512;
+
+/// TTL of the records. NICs come and go, so resolvers shouldn't hold on
+/// to them for long.
+const RECORD_TTL: u32 = 30;
+
+const TYPE_A: u16 = 1;
+const TYPE_AAAA: u16 = 28;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+pub(crate) const RCODE_NOERROR: u8 = 0;
+pub(crate) const RCODE_FORMERR: u8 = 1;
+pub(crate) const RCODE_SERVFAIL: u8 = 2;
+pub(crate) const RCODE_NXDOMAIN: u8 = 3;
+const RCODE_NOTIMP: u8 = 4;
+pub(crate) const RCODE_REFUSED: u8 = 5;
+
+/// The names of one network.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DnsZone {
+    /// Domain of the zone, e.g. `mynet.internal`
+    domain: String,
+    /// Addresses by host name relative to the domain
+    hosts: HashMap<String, Vec<IpAddr>>,
+    /// Where names outside the zone are resolved; `None` refuses them
+    forward: Option<Forward>,
+}
+
+/// Upstream servers resolving the names outside a zone.
+#[derive(Debug, Clone)]
+pub struct Forward {
+    pub forwarder: Arc<DnsForwarder>,
+    /// Servers asked, in order
+    pub upstreams: Arc<[SocketAddr]>,
+}
+
+impl PartialEq for Forward {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.forwarder, &other.forwarder) && self.upstreams == other.upstreams
+    }
+}
+
+impl Eq for Forward {}
+
+/// What a zone knows about a name.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Lookup<'a> {
+    /// The name is outside the zone
+    Refused,
+    /// The name is in the zone but doesn't exist
+    NxDomain,
+    /// The name exists (the apex has no addresses)
+    Found(&'a [IpAddr]),
+}
+
+impl DnsZone {
+    /// Empty zone of the network `network_name`.
+    pub fn new(network_name: &str) -> Self {
+        DnsZone {
+            domain: format!("{}.{}", network_name.to_ascii_lowercase(), ZONE_SUFFIX),
+            hosts: HashMap::new(),
+            forward: None,
+        }
+    }
+
+    /// Resolve names outside the zone through `forward`.
+    pub fn set_forward(&mut self, forward: Forward) {
+        self.forward = Some(forward);
+    }
+
+    /// Where names outside the zone are resolved, if anywhere.
+    pub fn forward(&self) -> Option<&Forward> {
+        self.forward.as_ref()
+    }
+
+    /// Domain of the zone.
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    /// Give `name` (relative to the domain) an address. NICs sharing a
+    /// name share the record.
+    pub fn add(&mut self, name: &str, addr: IpAddr) {
+        self.hosts
+            .entry(name.to_ascii_lowercase())
+            .or_default()
+            .push(addr);
+    }
+
+    /// Number of names with addresses.
+    pub fn len(&self) -> usize {
+        self.hosts.len()
+    }
+
+    /// Whether no name has an address.
+    pub fn is_empty(&self) -> bool {
+        self.hosts.is_empty()
+    }
+
+    /// Look up a fully qualified name, with or without the trailing dot.
+    pub fn lookup(&self, name: &str) -> Lookup<'_> {
+        let name = name.trim_end_matches('.').to_ascii_lowercase();
+        if name == self.domain {
+            return Lookup::Found(&[]);
+        }
+        let Some(host) = name
+            .strip_suffix(self.domain.as_str())
+            .and_then(|rest| rest.strip_suffix('.'))
+        else {
+            return Lookup::Refused;
+        };
+        match self.hosts.get(host) {
+            Some(addrs) => Lookup::Found(addrs),
+            None => Lookup::NxDomain,
+        }
+    }
+}
+
+/// What became of a query to the gateway.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Handled {
+    /// Answered from the zone; the reply frame
+    Reply(Vec<u8>),
+    /// Handed to the forwarder, which replies through the reactor later
+    Forwarded,
+}
+
+/// Channel the forwarder hands reply frames back to a reactor on. Each
+/// send wakes the reactor.
+#[derive(Debug, Clone)]
+pub struct ReplySender {
+    tx: Sender<Vec<u8>>,
+    event_fd: Arc<OwnedFd>,
+}
+
+impl ReplySender {
+    /// Channel to a reactor woken through `event_fd`.
+    pub fn new(event_fd: OwnedFd) -> (Self, Receiver<Vec<u8>>) {
+        let (tx, rx) = mpsc::channel();
+        let sender = ReplySender {
+            tx,
+            event_fd: Arc::new(event_fd),
+        };
+        (sender, rx)
+    }
+
+    /// Hand a reply frame to the reactor.
+    pub fn send(&self, frame: Vec<u8>) {
+        if self.tx.send(frame).is_err() {
+            return;
+        }
+        let buf: u64 = 1;
+        unsafe {
+            nix::libc::write(
+                self.event_fd.as_raw_fd(),
+                &buf as *const u64 as *const nix::libc::c_void,
+                8,
+            );
+        }
+    }
+}
+
+/// The guest a forwarded query came from and the way back to it.
+#[derive(Debug, Clone)]
+pub struct Origin {
+    virtio_hdr_len: usize,
+    mac: EthernetAddress,
+    gateway: IpAddress,
+    guest: IpAddress,
+    port: u16,
+    replies: ReplySender,
+}
+
+impl Origin {
+    /// Send the DNS message `reply` to the guest from the gateway address
+    /// it asked.
+    pub fn reply(&self, reply: &[u8]) {
+        let frame = build_dns_packet(
+            &vec![0u8; self.virtio_hdr_len],
+            self.mac,
+            self.gateway,
+            self.guest,
+            self.port,
+            reply,
+        );
+        self.replies.send(frame);
+    }
+}
+
+/// Handle a DNS query from a VM.
+///
+/// Returns what became of it if this is a query to the gateway and the NIC
+/// has a zone. Replies to forwarded queries go to `replies`.
+pub fn handle_dns_packet(
+    nic_config: &NicConfig,
+    virtio_hdr: &[u8],
+    ethernet_frame: &[u8],
+    replies: &ReplySender,
+) -> Option<Handled> {
+    let zone = nic_config.dns_zone.as_deref()?;
+    let eth_frame = EthernetFrame::new_checked(ethernet_frame).ok()?;
+
+    let (src, dst, l4) = match eth_frame.ethertype() {
+        EthernetProtocol::Ipv4 => {
+            let ip = Ipv4Packet::new_checked(eth_frame.payload()).ok()?;
+            let dst = Ipv4Addr::from(ip.dst_addr().0);
+            if ip.next_header() != IpProtocol::Udp
+                || (dst != GATEWAY_IPV4_LINK_LOCAL && Some(dst) != nic_config.ipv4_gateway)
+            {
+                return None;
+            }
+            (
+                IpAddress::Ipv4(ip.src_addr()),
+                IpAddress::Ipv4(ip.dst_addr()),
+                ip.payload(),
+            )
+        }
+        EthernetProtocol::Ipv6 => {
+            let ip = Ipv6Packet::new_checked(eth_frame.payload()).ok()?;
+            let dst = Ipv6Addr::from(ip.dst_addr().0);
+            if ip.next_header() != IpProtocol::Udp
+                || (dst != GATEWAY_IPV6_LINK_LOCAL && Some(dst) != nic_config.ipv6_gateway)
+            {
+                return None;
+            }
+            (
+                IpAddress::Ipv6(ip.src_addr()),
+                IpAddress::Ipv6(ip.dst_addr()),
+                ip.payload(),
+            )
+        }
+        _ => return None,
+    };
+
+    let udp = UdpPacket::new_checked(l4).ok()?;
+    if udp.dst_port() != DNS_PORT {
+        return None;
+    }
+    let reply = answer(zone, udp.payload())?;
+
+    // Refused means the name isn't ours
+    if reply[3] & 0x0f == RCODE_REFUSED
+        && let Some(forward) = &zone.forward
+    {
+        forward.forwarder.submit(Query {
+            message: udp.payload().to_vec(),
+            upstreams: Arc::clone(&forward.upstreams),
+            origin: Origin {
+                virtio_hdr_len: virtio_hdr.len(),
+                mac: eth_frame.src_addr(),
+                gateway: dst,
+                guest: src,
+                port: udp.src_port(),
+                replies: replies.clone(),
+            },
+        });
+        debug!(src = %src, "DNS query forwarded");
+        return Some(Handled::Forwarded);
+    }
+
+    debug!(src = %src, len = reply.len(), "DNS query answered");
+    Some(Handled::Reply(build_dns_packet(
+        virtio_hdr,
+        eth_frame.src_addr(),
+        dst,
+        src,
+        udp.src_port(),
+        &reply,
+    )))
+}
+
+/// The reply to the DNS message `query`, `None` for anything that isn't a
+/// query (including replies, which are dropped).
+pub fn answer(zone: &DnsZone, query: &[u8]) -> Option<Vec<u8>> {
+    if query.len() < DNS_HEADER_SIZE {
+        return None;
+    }
+    let flags = u16::from_be_bytes([query[2], query[3]]);
+    if flags & 0x8000 != 0 {
+        return None;
+    }
+    let opcode = ((flags >> 11) & 0xf) as u8;
+    let qdcount = u16::from_be_bytes([query[4], query[5]]);
+
+    if opcode != 0 {
+        return Some(header_only(query, RCODE_NOTIMP));
+    }
+    if qdcount != 1 {
+        return Some(header_only(query, RCODE_FORMERR));
+    }
+    let Some((name, question_end)) = read_name(query, DNS_HEADER_SIZE) else {
+        return Some(header_only(query, RCODE_FORMERR));
+    };
+    let Some(fixed) = query.get(question_end..question_end + 4) else {
+        return Some(header_only(query, RCODE_FORMERR));
+    };
+    let qtype = u16::from_be_bytes([fixed[0], fixed[1]]);
+    let qclass = u16::from_be_bytes([fixed[2], fixed[3]]);
+    let question = &query[DNS_HEADER_SIZE..question_end + 4];
+
+    let (rcode, addrs) = match zone.lookup(&name) {
+        _ if qclass != CLASS_IN => (RCODE_REFUSED, &[][..]),
+        Lookup::Refused => (RCODE_REFUSED, &[][..]),
+        Lookup::NxDomain => (RCODE_NXDOMAIN, &[][..]),
+        Lookup::Found(addrs) => (RCODE_NOERROR, addrs),
+    };
+    let authoritative = rcode != RCODE_REFUSED;
+
+    let mut reply = Vec::with_capacity(MAX_UDP_REPLY);
+    reply.extend_from_slice(&query[..DNS_HEADER_SIZE]);
+    reply.extend_from_slice(question);
+
+    let mut answers = 0u16;
+    let mut truncated = false;
+    for addr in addrs {
+        let (rtype, rdata) = match (addr, qtype) {
+            (IpAddr::V4(v4), TYPE_A | TYPE_ANY) => (TYPE_A, v4.octets().to_vec()),
+            (IpAddr::V6(v6), TYPE_AAAA | TYPE_ANY) => (TYPE_AAAA, v6.octets().to_vec()),
+            _ => continue,
+        };
+        // Name pointer, type, class, TTL, length, data
+        if reply.len() + 12 + rdata.len() > MAX_UDP_REPLY {
+            truncated = true;
+            break;
+        }
+        reply.extend_from_slice(&(0xc000 | DNS_HEADER_SIZE as u16).to_be_bytes());
+        reply.extend_from_slice(&rtype.to_be_bytes());
+        reply.extend_from_slice(&CLASS_IN.to_be_bytes());
+        reply.extend_from_slice(&RECORD_TTL.to_be_bytes());
+        reply.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        reply.extend_from_slice(&rdata);
+        answers += 1;
+    }
+
+    set_reply_header(&mut reply, flags, rcode, authoritative, truncated);
+    reply[4..6].copy_from_slice(&1u16.to_be_bytes());
+    reply[6..8].copy_from_slice(&answers.to_be_bytes());
+    reply[8..12].fill(0);
+    Some(reply)
+}
+
+/// A reply of just the query's header with `rcode`, for queries that
+/// can't be parsed.
+fn header_only(query: &[u8], rcode: u8) -> Vec<u8> {
+    let mut reply = query[..DNS_HEADER_SIZE].to_vec();
+    let flags = u16::from_be_bytes([query[2], query[3]]);
+    set_reply_header(&mut reply, flags, rcode, false, false);
+    reply[4..12].fill(0);
+    reply
+}
+
+/// Turn the copied query header into a reply header: QR set, opcode and RD
+/// kept, RA clear.
+fn set_reply_header(reply: &mut [u8], query_flags: u16, rcode: u8, aa: bool, tc: bool) {
+    let mut flags = 0x8000 | (query_flags & 0x7900);
+    if aa {
+        flags |= 0x0400;
+    }
+    if tc {
+        flags |= 0x0200;
+    }
+    flags |= rcode as u16;
+    reply[2..4].copy_from_slice(&flags.to_be_bytes());
+}
+
+/// Read the uncompressed name at `offset` as dotted lowercase text.
+/// Returns the name and the offset after it.
+pub(crate) fn read_name(message: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut name = String::new();
+    loop {
+        let len = *message.get(offset)? as usize;
+        offset += 1;
+        if len == 0 {
+            break;
+        }
+        // Pointers and extended labels have no place in a question
+        if len > 63 {
+            return None;
+        }
+        let label = message.get(offset..offset + len)?;
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(&String::from_utf8_lossy(label).to_ascii_lowercase());
+        offset += len;
+        if name.len() > 253 {
+            return None;
+        }
+    }
+    Some((name, offset))
+}
+
+/// Build the reply frame from the gateway to the querying guest.
+fn build_dns_packet(
+    virtio_hdr: &[u8],
+    dst_mac: EthernetAddress,
+    src_addr: IpAddress,
+    dst_addr: IpAddress,
+    dst_port: u16,
+    dns_bytes: &[u8],
+) -> Vec<u8> {
+    let virtio_hdr_size = virtio_hdr.len();
+    let udp_len = UDP_HEADER_SIZE + dns_bytes.len();
+    let (ethertype, ip_header_size) = match src_addr {
+        IpAddress::Ipv4(_) => (EthernetProtocol::Ipv4, IPV4_HEADER_SIZE),
+        IpAddress::Ipv6(_) => (EthernetProtocol::Ipv6, IPV6_HEADER_SIZE),
+    };
+    let total_len = virtio_hdr_size + ETHERNET_HEADER_SIZE + ip_header_size + udp_len;
+
+    // Virtio header (zeroed)
+    let mut packet = vec![0u8; total_len];
+
+    let eth_repr = EthernetRepr {
+        src_addr: EthernetAddress(GATEWAY_MAC),
+        dst_addr: dst_mac,
+        ethertype,
+    };
+    let mut eth_frame = EthernetFrame::new_unchecked(&mut packet[virtio_hdr_size..]);
+    eth_repr.emit(&mut eth_frame);
+
+    let checksums = smoltcp::phy::ChecksumCapabilities::default();
+    let udp_repr = UdpRepr {
+        src_port: DNS_PORT,
+        dst_port,
+    };
+    let emit_payload = |buf: &mut [u8]| buf.copy_from_slice(dns_bytes);
+    match (src_addr, dst_addr) {
+        (IpAddress::Ipv4(src), IpAddress::Ipv4(dst)) => {
+            let ip_repr = Ipv4Repr {
+                src_addr: src,
+                dst_addr: dst,
+                next_header: IpProtocol::Udp,
+                payload_len: udp_len,
+                hop_limit: 64,
+            };
+            let mut ip_packet = Ipv4Packet::new_unchecked(eth_frame.payload_mut());
+            ip_repr.emit(&mut ip_packet, &checksums);
+            let mut udp_packet = UdpPacket::new_unchecked(ip_packet.payload_mut());
+            udp_repr.emit(
+                &mut udp_packet,
+                &src_addr,
+                &dst_addr,
+                dns_bytes.len(),
+                emit_payload,
+                &checksums,
+            );
+        }
+        (IpAddress::Ipv6(src), IpAddress::Ipv6(dst)) => {
+            let ip_repr = Ipv6Repr {
+                src_addr: src,
+                dst_addr: dst,
+                next_header: IpProtocol::Udp,
+                payload_len: udp_len,
+                hop_limit: 64,
+            };
+            let mut ip_packet = Ipv6Packet::new_unchecked(eth_frame.payload_mut());
+            ip_repr.emit(&mut ip_packet);
+            let mut udp_packet = UdpPacket::new_unchecked(ip_packet.payload_mut());
+            udp_repr.emit(
+                &mut udp_packet,
+                &src_addr,
+                &dst_addr,
+                dns_bytes.len(),
+                emit_payload,
+                &checksums,
+            );
+        }
+        _ => unreachable!("reply addresses of one family"),
+    }
+
+    packet
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn zone() -> DnsZone {
+        let mut zone = DnsZone::new("prod");
+        zone.add("web", "10.0.0.5".parse().unwrap());
+        zone.add("web", "fd00::5".parse().unwrap());
+        zone.add("db", "10.0.0.6".parse().unwrap());
+        zone
+    }
+
+    fn query(name: &str, qtype: u16) -> Vec<u8> {
+        // ID 0x1234, RD set, one question
+        let mut q = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        for label in name.split('.') {
+            q.push(label.len() as u8);
+            q.extend_from_slice(label.as_bytes());
+        }
+        q.push(0);
+        q.extend_from_slice(&qtype.to_be_bytes());
+        q.extend_from_slice(&CLASS_IN.to_be_bytes());
+        q
+    }
+
+    fn rcode(reply: &[u8]) -> u8 {
+        reply[3] & 0x0f
+    }
+
+    fn answers(reply: &[u8]) -> u16 {
+        u16::from_be_bytes([reply[6], reply[7]])
+    }
+
+    #[test]
+    fn looks_up_names_in_the_zone() {
+        let zone = zone();
+        assert_eq!(zone.domain(), "prod.internal");
+        assert_eq!(zone.len(), 2);
+        assert!(matches!(zone.lookup("WEB.prod.internal."), Lookup::Found(a) if a.len() == 2));
+        assert_eq!(zone.lookup("prod.internal"), Lookup::Found(&[]));
+        assert_eq!(zone.lookup("mail.prod.internal"), Lookup::NxDomain);
+        assert_eq!(zone.lookup("web.dev.internal"), Lookup::Refused);
+        assert_eq!(zone.lookup("example.com"), Lookup::Refused);
+        assert_eq!(zone.lookup("xprod.internal"), Lookup::Refused);
+    }
+
+    #[test]
+    fn answers_a_and_aaaa_queries() {
+        let zone = zone();
+
+        let reply = answer(&zone, &query("Web.prod.internal", TYPE_A)).unwrap();
+        assert_eq!(&reply[..2], &[0x12, 0x34]);
+        // QR, AA and RD
+        assert_eq!(reply[2], 0x85);
+        assert_eq!(rcode(&reply), RCODE_NOERROR);
+        assert_eq!(answers(&reply), 1);
+        // The question keeps its case
+        assert_eq!(&reply[13..16], b"Web");
+        assert_eq!(&reply[reply.len() - 4..], &[10, 0, 0, 5]);
+
+        let reply = answer(&zone, &query("web.prod.internal", TYPE_AAAA)).unwrap();
+        assert_eq!(answers(&reply), 1);
+        let v6: Ipv6Addr = "fd00::5".parse().unwrap();
+        assert_eq!(&reply[reply.len() - 16..], &v6.octets());
+
+        let reply = answer(&zone, &query("web.prod.internal", TYPE_ANY)).unwrap();
+        assert_eq!(answers(&reply), 2);
+
+        // No AAAA for db: the name exists but has no data
+        let reply = answer(&zone, &query("db.prod.internal", TYPE_AAAA)).unwrap();
+        assert_eq!(rcode(&reply), RCODE_NOERROR);
+        assert_eq!(answers(&reply), 0);
+    }
+
+    #[test]
+    fn refuses_or_denies_other_names() {
+        let zone = zone();
+
+        let reply = answer(&zone, &query("mail.prod.internal", TYPE_A)).unwrap();
+        assert_eq!(rcode(&reply), RCODE_NXDOMAIN);
+        assert_eq!(reply[2] & 0x04, 0x04);
+
+        let reply = answer(&zone, &query("example.com", TYPE_A)).unwrap();
+        assert_eq!(rcode(&reply), RCODE_REFUSED);
+        assert_eq!(reply[2] & 0x04, 0);
+        assert_eq!(answers(&reply), 0);
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        let zone = zone();
+
+        // Replies are ignored
+        let mut reply = query("web.prod.internal", TYPE_A);
+        reply[2] |= 0x80;
+        assert_eq!(answer(&zone, &reply), None);
+        assert_eq!(answer(&zone, &[0u8; 4]), None);
+
+        // A compressed name in the question
+        let mut compressed = query("web.prod.internal", TYPE_A);
+        compressed[12] = 0xc0;
+        assert_eq!(rcode(&answer(&zone, &compressed).unwrap()), RCODE_FORMERR);
+
+        // Truncated question
+        let q = query("web.prod.internal", TYPE_A);
+        assert_eq!(
+            rcode(&answer(&zone, &q[..q.len() - 2]).unwrap()),
+            RCODE_FORMERR
+        );
+
+        // Opcode STATUS
+        let mut status = query("web.prod.internal", TYPE_A);
+        status[2] |= 2 << 3;
+        assert_eq!(rcode(&answer(&zone, &status).unwrap()), RCODE_NOTIMP);
+    }
+}
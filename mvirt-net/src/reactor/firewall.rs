@@ -0,0 +1,450 @@
+//! Security group filtering for vhost-user interfaces.
+//!
+//! Mirrors the eBPF backend's TC programs so a security group behaves the
+//! same on either backend: each reactor keeps a connection table for its
+//! NIC and tracks every flow the VM opens. Packets towards the VM pass if
+//! they answer a tracked flow; otherwise, once a group is attached, they
+//! need an ingress rule matching source address, protocol and destination
+//! port. Egress stays allowed and is only tracked.
+//!
+//! In audit mode the packets the rules would drop are delivered anyway
+//! and each such flow is logged once per idle period.
+//!
+//! The reactor only sees the rules that are active right now; validity
+//! windows and schedules are evaluated by [`crate::rule_window`].
+
+use ipnet::IpNet;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::time::{Duration, Instant};
+use tracing::{debug, info};
+
+/// IP protocol numbers with ports
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+
+/// Idle timeout for TCP flows
+const TCP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Idle timeout for UDP flows
+const UDP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Idle timeout for ICMP and other flows
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Flows tracked per NIC; new flows are not tracked while the table is full
+/// of live entries.
+pub const MAX_TRACKED_FLOWS: usize = 65536;
+
+/// How often expired flows are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// An allow rule in reactor form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecurityRule {
+    /// IP protocol number; 0 matches any
+    pub protocol: u8,
+    /// Inclusive destination port range for TCP and UDP; `None` matches any
+    pub ports: Option<(u16, u16)>,
+    /// Source network; `None` matches any address of either IP version
+    pub cidr: Option<IpNet>,
+}
+
+impl SecurityRule {
+    fn matches(&self, flow: &Flow) -> bool {
+        if self.protocol != 0 && self.protocol != flow.protocol {
+            return false;
+        }
+        if let Some((start, end)) = self.ports
+            && matches!(flow.protocol, PROTO_TCP | PROTO_UDP)
+            && !(start..=end).contains(&flow.dst_port)
+        {
+            return false;
+        }
+        self.cidr.is_none_or(|net| net.contains(&flow.src))
+    }
+}
+
+/// Filter configuration of a NIC with at least one security group.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityPolicy {
+    /// Deliver would-be drops and log them instead
+    pub audit: bool,
+    /// Active ingress rules of all attached groups
+    pub ingress: Vec<SecurityRule>,
+}
+
+/// The 5-tuple of an IP packet. ICMP and protocols without ports have
+/// both ports set to 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Flow {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub protocol: u8,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+impl Flow {
+    /// Parse the IP header (and TCP/UDP ports) at the start of `ip`.
+    pub fn parse(ip: &[u8]) -> Option<Self> {
+        let (src, dst, protocol, l4) = match ip.first()? >> 4 {
+            4 if ip.len() >= 20 => {
+                let ihl = ((ip[0] & 0x0f) as usize) * 4;
+                let fragment_offset = u16::from_be_bytes([ip[6], ip[7]]) & 0x1fff;
+                let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+                let dst = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+                // Later fragments carry no L4 header
+                let l4 = if fragment_offset == 0 {
+                    ip.get(ihl..)
+                } else {
+                    None
+                };
+                (IpAddr::V4(src), IpAddr::V4(dst), ip[9], l4)
+            }
+            6 if ip.len() >= 40 => {
+                let src: [u8; 16] = ip[8..24].try_into().ok()?;
+                let dst: [u8; 16] = ip[24..40].try_into().ok()?;
+                (
+                    IpAddr::V6(Ipv6Addr::from(src)),
+                    IpAddr::V6(Ipv6Addr::from(dst)),
+                    ip[6],
+                    ip.get(40..),
+                )
+            }
+            _ => return None,
+        };
+
+        let (src_port, dst_port) = match (protocol, l4) {
+            (PROTO_TCP | PROTO_UDP, Some(l4)) if l4.len() >= 4 => (
+                u16::from_be_bytes([l4[0], l4[1]]),
+                u16::from_be_bytes([l4[2], l4[3]]),
+            ),
+            _ => (0, 0),
+        };
+
+        Some(Self {
+            src,
+            dst,
+            protocol,
+            src_port,
+            dst_port,
+        })
+    }
+
+    /// The same flow seen from the other end.
+    pub fn reversed(&self) -> Self {
+        Self {
+            src: self.dst,
+            dst: self.src,
+            protocol: self.protocol,
+            src_port: self.dst_port,
+            dst_port: self.src_port,
+        }
+    }
+
+    /// Idle time after which the flow is forgotten
+    pub fn timeout(&self) -> Duration {
+        match self.protocol {
+            PROTO_TCP => TCP_TIMEOUT,
+            PROTO_UDP => UDP_TIMEOUT,
+            _ => DEFAULT_TIMEOUT,
+        }
+    }
+}
+
+/// Flows by last time seen.
+#[derive(Debug)]
+struct FlowTable {
+    flows: HashMap<Flow, Instant>,
+    last_sweep: Instant,
+}
+
+impl FlowTable {
+    fn new(now: Instant) -> Self {
+        Self {
+            flows: HashMap::new(),
+            last_sweep: now,
+        }
+    }
+
+    fn is_live(flow: &Flow, seen: Instant, now: Instant) -> bool {
+        now.saturating_duration_since(seen) < flow.timeout()
+    }
+
+    fn contains(&self, flow: &Flow, now: Instant) -> bool {
+        self.flows
+            .get(flow)
+            .is_some_and(|&seen| Self::is_live(flow, seen, now))
+    }
+
+    fn sweep(&mut self, now: Instant) {
+        self.flows
+            .retain(|flow, &mut seen| Self::is_live(flow, seen, now));
+        self.last_sweep = now;
+    }
+
+    /// Record `flow` as seen at `now`. Returns true if it was not tracked
+    /// before (or had expired).
+    fn touch(&mut self, flow: Flow, now: Instant) -> bool {
+        if now.saturating_duration_since(self.last_sweep) >= SWEEP_INTERVAL {
+            self.sweep(now);
+        }
+        let is_new = !self.contains(&flow, now);
+        if is_new && !self.flows.contains_key(&flow) && self.flows.len() >= MAX_TRACKED_FLOWS {
+            self.sweep(now);
+            if self.flows.len() >= MAX_TRACKED_FLOWS {
+                return true;
+            }
+        }
+        self.flows.insert(flow, now);
+        is_new
+    }
+
+    fn clear(&mut self) {
+        self.flows.clear();
+    }
+}
+
+/// Per-NIC security group filter and connection table.
+#[derive(Debug)]
+pub struct Firewall {
+    policy: Option<SecurityPolicy>,
+    /// Flows opened by the VM, keyed as seen from the VM
+    conntrack: FlowTable,
+    /// Would-be dropped flows already logged in audit mode
+    audited: FlowTable,
+}
+
+impl Default for Firewall {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Firewall {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        Self {
+            policy: None,
+            conntrack: FlowTable::new(now),
+            audited: FlowTable::new(now),
+        }
+    }
+
+    /// Replace the NIC's policy; `None` when no group is attached. Tracked
+    /// connections survive the change.
+    pub fn set_policy(&mut self, policy: Option<SecurityPolicy>) {
+        debug!(
+            enabled = policy.is_some(),
+            audit = policy.as_ref().is_some_and(|p| p.audit),
+            rules = policy.as_ref().map_or(0, |p| p.ingress.len()),
+            "Security policy updated"
+        );
+        self.policy = policy;
+        self.audited.clear();
+    }
+
+    /// Track an IP packet the VM sends.
+    pub fn egress(&mut self, ip: &[u8], now: Instant) {
+        if let Some(flow) = Flow::parse(ip) {
+            self.conntrack.touch(flow, now);
+        }
+    }
+
+    /// Decide whether an IP packet may be delivered to the VM.
+    pub fn ingress(&mut self, ip: &[u8], now: Instant) -> bool {
+        let Some(policy) = &self.policy else {
+            return true;
+        };
+        // Non-IP traffic is not subject to security groups
+        let Some(flow) = Flow::parse(ip) else {
+            return true;
+        };
+
+        if self.conntrack.contains(&flow.reversed(), now)
+            || policy.ingress.iter().any(|rule| rule.matches(&flow))
+        {
+            return true;
+        }
+
+        if policy.audit {
+            if self.audited.touch(flow, now) {
+                info!(
+                    src = %flow.src,
+                    dst = %flow.dst,
+                    protocol = flow.protocol,
+                    src_port = flow.src_port,
+                    dst_port = flow.dst_port,
+                    "Security audit: flow would be dropped"
+                );
+            }
+            return true;
+        }
+
+        debug!(
+            src = %flow.src,
+            dst = %flow.dst,
+            protocol = flow.protocol,
+            dst_port = flow.dst_port,
+            "Security group dropped packet"
+        );
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VM: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 5);
+    const REMOTE: Ipv4Addr = Ipv4Addr::new(192, 0, 2, 10);
+
+    fn ipv4(src: Ipv4Addr, dst: Ipv4Addr, protocol: u8, sport: u16, dport: u16) -> Vec<u8> {
+        let mut p = vec![0u8; 28];
+        p[0] = 0x45;
+        p[9] = protocol;
+        p[12..16].copy_from_slice(&src.octets());
+        p[16..20].copy_from_slice(&dst.octets());
+        p[20..22].copy_from_slice(&sport.to_be_bytes());
+        p[22..24].copy_from_slice(&dport.to_be_bytes());
+        p
+    }
+
+    fn ssh_from(cidr: &str) -> SecurityPolicy {
+        SecurityPolicy {
+            audit: false,
+            ingress: vec![SecurityRule {
+                protocol: PROTO_TCP,
+                ports: Some((22, 22)),
+                cidr: Some(cidr.parse().unwrap()),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_parse_flow() {
+        let flow = Flow::parse(&ipv4(VM, REMOTE, PROTO_UDP, 5353, 53)).unwrap();
+        assert_eq!(flow.src, IpAddr::V4(VM));
+        assert_eq!(flow.dst, IpAddr::V4(REMOTE));
+        assert_eq!(
+            (flow.protocol, flow.src_port, flow.dst_port),
+            (17, 5353, 53)
+        );
+        assert_eq!(flow.reversed().reversed(), flow);
+
+        let mut v6 = vec![0u8; 48];
+        v6[0] = 0x60;
+        v6[6] = PROTO_TCP;
+        v6[23] = 1;
+        v6[39] = 2;
+        v6[42..44].copy_from_slice(&443u16.to_be_bytes());
+        let flow = Flow::parse(&v6).unwrap();
+        assert_eq!(flow.src, "::1".parse::<IpAddr>().unwrap());
+        assert_eq!(flow.dst_port, 443);
+
+        // ICMP has no ports
+        let flow = Flow::parse(&ipv4(REMOTE, VM, 1, 0x0800, 0x1234)).unwrap();
+        assert_eq!((flow.src_port, flow.dst_port), (0, 0));
+
+        assert!(Flow::parse(&[0x45, 0, 0]).is_none());
+        assert!(Flow::parse(&[]).is_none());
+    }
+
+    #[test]
+    fn test_no_policy_passes_everything() {
+        let mut fw = Firewall::new();
+        let now = Instant::now();
+        assert!(fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 40000, 5432), now));
+    }
+
+    #[test]
+    fn test_ingress_rules() {
+        let mut fw = Firewall::new();
+        let now = Instant::now();
+        fw.set_policy(Some(ssh_from("192.0.2.0/24")));
+
+        assert!(fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 40000, 22), now));
+        assert!(!fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 40000, 80), now));
+        assert!(!fw.ingress(&ipv4(REMOTE, VM, PROTO_UDP, 40000, 22), now));
+        let outsider = Ipv4Addr::new(198, 51, 100, 1);
+        assert!(!fw.ingress(&ipv4(outsider, VM, PROTO_TCP, 40000, 22), now));
+
+        // A group without rules drops all unsolicited ingress
+        fw.set_policy(Some(SecurityPolicy::default()));
+        assert!(!fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 40000, 22), now));
+    }
+
+    #[test]
+    fn test_replies_to_tracked_flows_pass() {
+        let mut fw = Firewall::new();
+        let now = Instant::now();
+        fw.set_policy(Some(SecurityPolicy::default()));
+
+        fw.egress(&ipv4(VM, REMOTE, PROTO_TCP, 50000, 443), now);
+        assert!(fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 443, 50000), now));
+        // Other ports of the same peer stay closed
+        assert!(!fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 443, 50001), now));
+
+        // UDP flows idle out sooner than TCP
+        fw.egress(&ipv4(VM, REMOTE, PROTO_UDP, 50000, 53), now);
+        let later = now + UDP_TIMEOUT;
+        assert!(!fw.ingress(&ipv4(REMOTE, VM, PROTO_UDP, 53, 50000), later));
+        assert!(fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 443, 50000), later));
+    }
+
+    #[test]
+    fn test_flows_tracked_before_attach_survive() {
+        let mut fw = Firewall::new();
+        let now = Instant::now();
+        fw.egress(&ipv4(VM, REMOTE, PROTO_TCP, 50000, 443), now);
+
+        fw.set_policy(Some(SecurityPolicy::default()));
+        assert!(fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 443, 50000), now));
+    }
+
+    #[test]
+    fn test_audit_mode_delivers() {
+        let mut fw = Firewall::new();
+        let now = Instant::now();
+        fw.set_policy(Some(SecurityPolicy {
+            audit: true,
+            ..ssh_from("192.0.2.0/24")
+        }));
+
+        assert!(fw.ingress(&ipv4(REMOTE, VM, PROTO_TCP, 40000, 80), now));
+        let flow = Flow::parse(&ipv4(REMOTE, VM, PROTO_TCP, 40000, 80)).unwrap();
+        assert!(fw.audited.contains(&flow, now));
+    }
+
+    #[test]
+    fn test_rule_matching() {
+        let flow = |proto, port, src: &str| Flow {
+            src: src.parse().unwrap(),
+            dst: IpAddr::V4(VM),
+            protocol: proto,
+            src_port: 40000,
+            dst_port: port,
+        };
+
+        let any = SecurityRule {
+            protocol: 0,
+            ports: None,
+            cidr: None,
+        };
+        assert!(any.matches(&flow(PROTO_TCP, 80, "192.0.2.1")));
+        assert!(any.matches(&flow(58, 0, "2001:db8::1")));
+
+        let range = SecurityRule {
+            protocol: 0,
+            ports: Some((8000, 8999)),
+            cidr: Some("2001:db8::/32".parse().unwrap()),
+        };
+        assert!(range.matches(&flow(PROTO_UDP, 8080, "2001:db8::1")));
+        assert!(!range.matches(&flow(PROTO_UDP, 9000, "2001:db8::1")));
+        // Ports don't restrict protocols that have none
+        assert!(range.matches(&flow(58, 0, "2001:db8::1")));
+        // An IPv6 CIDR never matches IPv4 sources
+        assert!(!range.matches(&flow(PROTO_UDP, 8080, "192.0.2.1")));
+    }
+}
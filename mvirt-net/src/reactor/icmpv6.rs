@@ -411,7 +411,11 @@ fn build_router_advertisement(
 }
 
 /// Compute ICMPv6 checksum.
-fn compute_icmpv6_checksum(src: &Ipv6Address, dst: &Ipv6Address, icmpv6_data: &[u8]) -> u16 {
+pub(crate) fn compute_icmpv6_checksum(
+    src: &Ipv6Address,
+    dst: &Ipv6Address,
+    icmpv6_data: &[u8],
+) -> u16 {
     let mut sum: u32 = 0;
 
     // Pseudo-header
@@ -456,7 +460,14 @@ mod tests {
             ipv6_address: Some(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)),
             ipv6_gateway: Some(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)),
             ipv6_prefix_len: 128,
+            ipv6_delegated_prefix: None,
             dns_servers: vec![],
+            boot_server: None,
+            boot_file: None,
+            mtu: 1500,
+            tx_burst: crate::reactor::DEFAULT_TX_BURST,
+            leases: vec![],
+            dns_zone: None,
         }
     }
 
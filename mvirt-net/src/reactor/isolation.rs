@@ -0,0 +1,212 @@
+//! Strict isolation between networks.
+//!
+//! Networks are isolated by their routing tables, but routes can still
+//! join them: two public networks meet behind the uplink, and a static
+//! route may point anywhere. With strict isolation a NIC reactor forwards
+//! nothing between its network and another network of this host unless
+//! the two are peered, whatever the routes say. A peering may be limited
+//! to some prefixes, and then only the addresses within them are reachable
+//! from the other network.
+//!
+//! The check runs last: on the routing decision for what the guest sends,
+//! and on admission of what other reactors deliver to it. Addresses outside
+//! every network (the internet behind the uplink) are not affected.
+
+use ipnet::{IpNet, Ipv4Net, Ipv6Net};
+use prefix_trie::PrefixMap;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Which network every address of this host belongs to, and which
+/// networks may talk to each other. Shared by all NIC reactors.
+#[derive(Clone)]
+pub struct IsolationMap {
+    v4: PrefixMap<Ipv4Net, Uuid>,
+    v6: PrefixMap<Ipv6Net, Uuid>,
+    /// Peered network pairs, lower ID first, with the prefixes they are
+    /// limited to (empty for none)
+    peerings: HashMap<(Uuid, Uuid), Vec<IpNet>>,
+}
+
+impl IsolationMap {
+    pub fn new() -> Self {
+        IsolationMap {
+            v4: PrefixMap::new(),
+            v6: PrefixMap::new(),
+            peerings: HashMap::new(),
+        }
+    }
+
+    /// Claim an IPv4 prefix (a subnet or a NIC's routed prefix) for a network.
+    pub fn add_v4(&mut self, prefix: Ipv4Net, network_id: Uuid) {
+        self.v4.insert(prefix, network_id);
+    }
+
+    /// Claim an IPv6 prefix for a network.
+    pub fn add_v6(&mut self, prefix: Ipv6Net, network_id: Uuid) {
+        self.v6.insert(prefix, network_id);
+    }
+
+    /// Let two networks talk to each other.
+    pub fn add_peering(&mut self, a: Uuid, b: Uuid) {
+        self.add_limited_peering(a, b, Vec::new());
+    }
+
+    /// Let two networks talk to each other, but only to addresses within
+    /// `allowed` on either side; an empty list allows all.
+    pub fn add_limited_peering(&mut self, a: Uuid, b: Uuid, allowed: Vec<IpNet>) {
+        self.peerings.insert(pair(a, b), allowed);
+    }
+
+    /// Network an address belongs to, `None` for addresses outside all of
+    /// them.
+    pub fn network_of(&self, addr: IpAddr) -> Option<Uuid> {
+        match addr {
+            IpAddr::V4(addr) => {
+                let host = Ipv4Net::new(addr, 32).ok()?;
+                self.v4.get_lpm(&host).map(|(_, id)| *id)
+            }
+            IpAddr::V6(addr) => {
+                let host = Ipv6Net::new(addr, 128).ok()?;
+                self.v6.get_lpm(&host).map(|(_, id)| *id)
+            }
+        }
+    }
+
+    /// Whether traffic may pass between `network_id` and `peer`.
+    pub fn allows(&self, network_id: Uuid, peer: IpAddr) -> bool {
+        match self.network_of(peer) {
+            None => true,
+            Some(other) if other == network_id => true,
+            Some(other) => match self.peerings.get(&pair(network_id, other)) {
+                None => false,
+                Some(allowed) => allowed.is_empty() || allowed.iter().any(|p| p.contains(&peer)),
+            },
+        }
+    }
+}
+
+impl Default for IsolationMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Strict isolation as one NIC reactor applies it.
+#[derive(Clone)]
+pub struct Isolation {
+    /// Network of the NIC
+    pub network_id: Uuid,
+    pub map: Arc<IsolationMap>,
+}
+
+impl Isolation {
+    /// Whether the NIC may exchange traffic with `peer`.
+    pub fn allows(&self, peer: IpAddr) -> bool {
+        self.map.allows(self.network_id, peer)
+    }
+}
+
+impl std::fmt::Debug for Isolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Isolation")
+            .field("network_id", &self.network_id)
+            .field("peerings", &self.map.peerings.len())
+            .finish()
+    }
+}
+
+/// Source address of an IPv4 or IPv6 packet.
+pub fn source_address(ip: &[u8]) -> Option<IpAddr> {
+    match ip.first()? >> 4 {
+        4 => {
+            let src: [u8; 4] = ip.get(12..16)?.try_into().ok()?;
+            Some(IpAddr::V4(Ipv4Addr::from(src)))
+        }
+        6 => {
+            let src: [u8; 16] = ip.get(8..24)?.try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(src)))
+        }
+        _ => None,
+    }
+}
+
+fn pair(a: Uuid, b: Uuid) -> (Uuid, Uuid) {
+    if a <= b { (a, b) } else { (b, a) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn two_networks() -> (IsolationMap, Uuid, Uuid) {
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let mut map = IsolationMap::new();
+        map.add_v4("10.0.0.0/24".parse().unwrap(), a);
+        map.add_v6("fd00:a::/64".parse().unwrap(), a);
+        map.add_v4("10.0.1.0/24".parse().unwrap(), b);
+        // A routed prefix of a NIC in network a
+        map.add_v4("192.168.5.0/24".parse().unwrap(), a);
+        (map, a, b)
+    }
+
+    #[test]
+    fn blocks_other_networks_unless_peered() {
+        let (mut map, a, b) = two_networks();
+
+        assert!(map.allows(a, "10.0.0.7".parse().unwrap()));
+        assert!(map.allows(a, "fd00:a::7".parse().unwrap()));
+        assert!(!map.allows(a, "10.0.1.7".parse().unwrap()));
+        assert!(!map.allows(b, "192.168.5.1".parse().unwrap()));
+
+        map.add_peering(b, a);
+        assert!(map.allows(a, "10.0.1.7".parse().unwrap()));
+        assert!(map.allows(b, "192.168.5.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn limits_peerings_to_allowed_prefixes() {
+        let (mut map, a, b) = two_networks();
+        map.add_limited_peering(
+            a,
+            b,
+            vec![
+                "10.0.1.16/28".parse().unwrap(),
+                "10.0.0.0/24".parse().unwrap(),
+            ],
+        );
+
+        assert!(map.allows(a, "10.0.1.17".parse().unwrap()));
+        assert!(!map.allows(a, "10.0.1.7".parse().unwrap()));
+        assert!(map.allows(b, "10.0.0.7".parse().unwrap()));
+        assert!(!map.allows(b, "192.168.5.1".parse().unwrap()));
+        // Within its own network a NIC is never limited
+        assert!(map.allows(b, "10.0.1.7".parse().unwrap()));
+    }
+
+    #[test]
+    fn leaves_outside_addresses_alone() {
+        let (map, a, _) = two_networks();
+        assert!(map.allows(a, "8.8.8.8".parse().unwrap()));
+        assert!(map.allows(a, "2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn reads_source_addresses() {
+        let mut v4 = [0u8; 20];
+        v4[0] = 0x45;
+        v4[12..16].copy_from_slice(&[10, 0, 1, 7]);
+        assert_eq!(source_address(&v4), Some("10.0.1.7".parse().unwrap()));
+
+        let mut v6 = [0u8; 40];
+        v6[0] = 0x60;
+        v6[8..24].copy_from_slice(&"fd00:a::7".parse::<Ipv6Addr>().unwrap().octets());
+        assert_eq!(source_address(&v6), Some("fd00:a::7".parse().unwrap()));
+
+        assert_eq!(source_address(&v4[..10]), None);
+        assert_eq!(source_address(&[]), None);
+    }
+}
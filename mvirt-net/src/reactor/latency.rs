@@ -0,0 +1,192 @@
+//! Per-stage packet latency histograms for the reactor hot path.
+//!
+//! Packets are timed from the moment the reactor takes them off a ring
+//! (guest TX vring or TUN read) through routing and submission until their
+//! buffer is released:
+//!
+//! - `route`: taken off the ring → routing decision
+//! - `submit`: routing decision → queued to io_uring or the target reactor
+//! - `completion`: queued → write completed / target reactor done
+//! - `total`: taken off the ring → completion
+//!
+//! Timing costs a clock read per stage and a lock per sample, so it is only
+//! compiled in with the `latency-histograms` feature. Without it [`Stamp`]
+//! is zero-sized, recording is a no-op and [`ENABLED`] is false.
+
+#[cfg(feature = "latency-histograms")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "latency-histograms")]
+use std::time::Instant;
+
+/// Whether this build records latencies.
+pub const ENABLED: bool = cfg!(feature = "latency-histograms");
+
+/// Longest latency tracked; slower samples are clamped to it.
+#[cfg(feature = "latency-histograms")]
+const MAX_TRACKED_NS: u64 = 10_000_000_000;
+
+/// A packet stage.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Route,
+    Submit,
+    Completion,
+    Total,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [Stage::Route, Stage::Submit, Stage::Completion, Stage::Total];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Route => "route",
+            Stage::Submit => "submit",
+            Stage::Completion => "completion",
+            Stage::Total => "total",
+        }
+    }
+}
+
+/// A point in time a stage starts from.
+#[derive(Debug, Clone, Copy)]
+pub struct Stamp {
+    #[cfg(feature = "latency-histograms")]
+    at: Instant,
+}
+
+impl Stamp {
+    #[inline]
+    pub fn now() -> Self {
+        Stamp {
+            #[cfg(feature = "latency-histograms")]
+            at: Instant::now(),
+        }
+    }
+}
+
+/// Timestamps of a packet waiting for its completion.
+#[derive(Debug, Clone, Copy)]
+pub struct InFlightTiming {
+    pub popped: Stamp,
+    pub submitted: Stamp,
+}
+
+/// Summary of one stage's histogram, in nanoseconds.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StageSummary {
+    pub stage: &'static str,
+    pub count: u64,
+    pub min_ns: u64,
+    pub p50_ns: u64,
+    pub p90_ns: u64,
+    pub p99_ns: u64,
+    pub p999_ns: u64,
+    pub max_ns: u64,
+    pub mean_ns: f64,
+}
+
+/// Histograms of one reactor, shared between the reactor thread and
+/// whoever reads them. Clones record into the same histograms.
+#[derive(Clone, Default)]
+pub struct LatencyRecorder {
+    #[cfg(feature = "latency-histograms")]
+    histograms: Arc<Mutex<Vec<hdrhistogram::Histogram<u64>>>>,
+}
+
+impl LatencyRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the time since `from` for `stage`. Returns the current time,
+    /// where the next stage starts.
+    #[inline]
+    pub fn record(&self, stage: Stage, from: Stamp) -> Stamp {
+        let now = Stamp::now();
+        #[cfg(feature = "latency-histograms")]
+        {
+            let ns = now.at.duration_since(from.at).as_nanos() as u64;
+            let mut histograms = self.histograms.lock().unwrap();
+            if histograms.is_empty() {
+                *histograms = Stage::ALL.iter().map(|_| new_histogram()).collect();
+            }
+            histograms[stage as usize].saturating_record(ns.clamp(1, MAX_TRACKED_NS));
+        }
+        #[cfg(not(feature = "latency-histograms"))]
+        let _ = (stage, from);
+        now
+    }
+
+    /// Record a completed packet: its completion and total latency.
+    #[inline]
+    pub fn complete(&self, timing: &InFlightTiming) {
+        self.record(Stage::Completion, timing.submitted);
+        self.record(Stage::Total, timing.popped);
+    }
+
+    /// Summaries of all stages, empty if nothing was recorded.
+    #[cfg(feature = "latency-histograms")]
+    pub fn summary(&self) -> Vec<StageSummary> {
+        let histograms = self.histograms.lock().unwrap();
+        Stage::ALL
+            .iter()
+            .zip(histograms.iter())
+            .map(|(stage, h)| StageSummary {
+                stage: stage.name(),
+                count: h.len(),
+                min_ns: if h.is_empty() { 0 } else { h.min() },
+                p50_ns: h.value_at_quantile(0.5),
+                p90_ns: h.value_at_quantile(0.9),
+                p99_ns: h.value_at_quantile(0.99),
+                p999_ns: h.value_at_quantile(0.999),
+                max_ns: h.max(),
+                mean_ns: h.mean(),
+            })
+            .collect()
+    }
+
+    /// Summaries of all stages, empty if nothing was recorded.
+    #[cfg(not(feature = "latency-histograms"))]
+    pub fn summary(&self) -> Vec<StageSummary> {
+        Vec::new()
+    }
+
+    /// Clear all histograms.
+    pub fn reset(&self) {
+        #[cfg(feature = "latency-histograms")]
+        for h in self.histograms.lock().unwrap().iter_mut() {
+            h.reset();
+        }
+    }
+}
+
+#[cfg(feature = "latency-histograms")]
+fn new_histogram() -> hdrhistogram::Histogram<u64> {
+    // 3 significant digits: 0.1% resolution from 1ns to MAX_TRACKED_NS
+    hdrhistogram::Histogram::new_with_bounds(1, MAX_TRACKED_NS, 3).expect("valid histogram bounds")
+}
+
+#[cfg(all(test, feature = "latency-histograms"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_summary_reset() {
+        let recorder = LatencyRecorder::new();
+        assert!(recorder.summary().is_empty());
+
+        let popped = Stamp::now();
+        let routed = recorder.record(Stage::Route, popped);
+        let submitted = recorder.record(Stage::Submit, routed);
+        recorder.complete(&InFlightTiming { popped, submitted });
+
+        let summary = recorder.clone().summary();
+        let names: Vec<_> = summary.iter().map(|s| s.stage).collect();
+        assert_eq!(names, ["route", "submit", "completion", "total"]);
+        assert!(summary.iter().all(|s| s.count == 1));
+        assert!(summary[3].max_ns >= summary[0].min_ns);
+
+        recorder.reset();
+        assert!(recorder.summary().iter().all(|s| s.count == 0));
+    }
+}
@@ -1,29 +1,50 @@
 pub mod arp;
 pub mod dhcp;
 pub mod dhcpv6;
+pub mod dns;
+pub mod firewall;
 pub mod icmpv6;
+pub mod isolation;
+pub mod latency;
+pub mod nat;
+pub mod pmtu;
 pub mod registry;
+pub mod rx_pool;
+pub mod sources;
+pub mod traffic;
 
 // Re-export inter-reactor types for convenience
 pub use crate::inter_reactor::{CompletionNotify, PacketId, PacketRef, PacketSource, ReactorId};
 pub use registry::{InterfaceType, ReactorInfo, ReactorRegistry};
 
+use crate::capture::{CaptureTap, Direction};
 use crate::routing::{IpPrefix, LpmTable, RouteTarget, RoutingDecision, RoutingTables};
 use crate::tun::VNET_HDR_SIZE;
 use crate::vhost_user::{GuestMemoryMmapAtomic, VhostHandshake, VringType};
 use crate::virtqueue::{DescriptorChain, RxVirtqueue, TxPacket, TxVirtqueue};
-use io_uring::{IoUring, opcode, types};
+use dns::DnsZone;
+use firewall::{Firewall, SecurityPolicy};
+use io_uring::{IoUring, opcode, squeue, types};
+use ipnet::Ipv6Net;
+use isolation::Isolation;
+use latency::{InFlightTiming, LatencyRecorder, Stage, Stamp};
+use nat::{Egress, FloatingIp, PortForward, PortForwarder};
 use nix::libc;
 use nix::sys::eventfd::{EfdFlags, EventFd};
+use rx_pool::{RxPoolScaler, RxPoolStats};
+use smallvec::SmallVec;
 use smoltcp::wire::{
     EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, Icmpv4Message, Icmpv4Packet,
     Icmpv4Repr, IpProtocol, Ipv4Packet, Ipv4Repr, Ipv6Packet,
 };
+use sources::SourceWatch;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
 use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
 use std::sync::Arc;
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::Instant;
 use tracing::{debug, error, info, warn};
+use traffic::TrafficCounters;
 use uuid::Uuid;
 use vhost_user_backend::VringT;
 use virtio_queue::QueueT;
@@ -57,10 +78,31 @@ pub struct NicConfig {
     pub ipv6_gateway: Option<Ipv6Addr>,
     /// IPv6 subnet prefix length
     pub ipv6_prefix_len: u8,
+    /// Prefix delegated to the VM via DHCPv6 (IA_PD)
+    pub ipv6_delegated_prefix: Option<Ipv6Net>,
     /// DNS servers for DHCP
     pub dns_servers: Vec<IpAddr>,
+    /// TFTP server handed to PXE clients (BOOTP siaddr)
+    pub boot_server: Option<Ipv4Addr>,
+    /// Boot file or UEFI HTTP boot URL offered to boot firmware
+    pub boot_file: Option<String>,
+    /// Largest IP packet routed on from this NIC; bigger ones get an ICMP
+    /// too-big reply (see [`pmtu`])
+    pub mtu: u16,
+    /// Descriptors taken off the guest's TX ring per burst
+    pub tx_burst: usize,
+    /// Static DHCP leases for other MACs behind the NIC (e.g. nested VMs
+    /// bridged in the guest), as (MAC, address)
+    pub leases: Vec<([u8; 6], Ipv4Addr)>,
+    /// Names of the NIC's network, answered on the gateway's DNS port
+    /// (see [`dns`]); `None` until the manager sets them
+    pub dns_zone: Option<Arc<DnsZone>>,
 }
 
+/// Default number of guest TX descriptors handled per burst. Larger bursts
+/// amortize io_uring submissions; smaller ones return buffers sooner.
+pub const DEFAULT_TX_BURST: usize = 64;
+
 const USER_DATA_RX_FLAG: u64 = 1 << 63;
 const USER_DATA_EVENT_FLAG: u64 = 1 << 62;
 const USER_DATA_VHOST_TX_FLAG: u64 = 1 << 61;
@@ -68,7 +110,10 @@ const USER_DATA_INCOMING_TUN_FLAG: u64 = 1 << 60;
 const USER_DATA_TUN_POLL_FLAG: u64 = 1 << 59;
 
 /// virtio-net header size (with VIRTIO_NET_F_MRG_RXBUF)
-const VIRTIO_NET_HDR_SIZE: usize = 12;
+pub(crate) const VIRTIO_NET_HDR_SIZE: usize = 12;
+
+/// virtio-net header flag: the L4 checksum is left to be completed
+const VIRTIO_NET_HDR_F_NEEDS_CSUM: u8 = 1;
 
 /// Ethernet header size
 const ETHERNET_HDR_SIZE: usize = 14;
@@ -77,13 +122,17 @@ const ETHERNET_HDR_SIZE: usize = 14;
 /// Must be large enough for DHCP packets (12 + 14 + 20 + 8 + ~548 = ~600 bytes).
 const PEEK_BUF_SIZE: usize = 600;
 
+/// Bytes of an incoming IP packet read for the security group check:
+/// the longest IPv4 header plus the L4 ports.
+const FIREWALL_PEEK_SIZE: usize = 64;
+
 /// Maximum number of iovec segments for vhost TX packets.
 /// Synchronized with inter_reactor::MAX_PACKET_IOVECS for zero-copy forwarding.
 const MAX_TX_IOVECS: usize = crate::inter_reactor::MAX_PACKET_IOVECS;
 
 /// Copies `dst.len()` bytes starting at `offset` from scattered iovecs into `dst`.
 /// Returns false if insufficient data available.
-fn copy_from_iovecs(
+pub(crate) fn copy_from_iovecs(
     iovecs: &[libc::iovec],
     iovecs_len: usize,
     offset: usize,
@@ -116,6 +165,15 @@ fn copy_from_iovecs(
     written >= len
 }
 
+/// Destination of an IPv4 packet, to pick the MAC it is sent to.
+#[inline]
+pub(crate) fn ipv4_destination(ip_data: &[u8]) -> Option<Ipv4Addr> {
+    match ip_data.get(..20) {
+        Some(hdr) if hdr[0] >> 4 == 4 => Some(Ipv4Addr::new(hdr[16], hdr[17], hdr[18], hdr[19])),
+        _ => None,
+    }
+}
+
 /// Adjusts virtio_net_hdr.csum_start when stripping Ethernet header.
 /// When removing the 14-byte Ethernet header, csum_start must be reduced
 /// by 14 because subsequent offsets shift backward.
@@ -218,6 +276,9 @@ const VHOST_TX_QUEUE: usize = 1;
 struct VhostState {
     mem: GuestMemoryMmapAtomic,
     vrings: Vec<VringType>,
+    /// Keeps guest memory mapped while its packets are in flight (in
+    /// io_uring or inter-reactor channels); cloned into each packet
+    keep_alive: Arc<dyn std::any::Any + Send + Sync>,
 }
 
 /// Tracks an in-flight vhost TX operation for zero-copy I/O.
@@ -231,6 +292,7 @@ struct VhostTxInFlight {
     keep_alive: Option<Arc<dyn std::any::Any + Send + Sync>>,
     /// Local copy of virtio_net_hdr with patched csum_start for TUN transmission
     patched_virtio_hdr: [u8; VIRTIO_NET_HDR_SIZE],
+    timing: InFlightTiming,
 }
 
 /// Tracks an in-flight VM-to-VM packet awaiting completion
@@ -238,6 +300,7 @@ struct VhostTxInFlight {
 struct VhostToVhostInFlight {
     head_index: u16,
     total_len: u32,
+    timing: InFlightTiming,
 }
 
 /// Tracks an in-flight incoming packet being written to TUN
@@ -279,12 +342,57 @@ pub enum ReactorCommand {
     SetDefaultTable {
         id: Uuid,
     },
+    /// Replace the NIC's security group policy (`None` = unfiltered)
+    SetSecurityPolicy {
+        policy: Option<SecurityPolicy>,
+    },
+    /// Set the cap the RX buffer pool may grow to
+    SetRxBufferMax {
+        max: usize,
+    },
+    /// Set the largest IP packet routed on from the NIC
+    SetMtu {
+        mtu: u16,
+    },
+    /// Set how many descriptors are taken off the guest's TX ring per burst
+    SetTxBurst {
+        burst: usize,
+    },
+    /// Administratively bring the NIC link up or down
+    SetLinkUp {
+        up: bool,
+    },
+    /// Replace the static DHCP leases served behind the NIC
+    SetDhcpLeases {
+        leases: Vec<([u8; 6], Ipv4Addr)>,
+    },
+    /// Replace the names the NIC's DNS responder answers for
+    SetDnsZone {
+        zone: Option<Arc<DnsZone>>,
+    },
+    /// Replace the strict isolation of the NIC's network (`None` = off)
+    SetIsolation {
+        isolation: Option<Isolation>,
+    },
+    /// Replace the port forwards of the TUN reactor
+    SetPortForwards {
+        forwards: Vec<PortForward>,
+    },
+    /// Replace the floating IPs of the TUN reactor
+    SetFloatingIps {
+        floating_ips: Vec<FloatingIp>,
+    },
 }
 
 /// Handle for controlling the reactor from outside
 pub struct ReactorHandle {
     event_fd: OwnedFd,
     command_tx: Sender<ReactorCommand>,
+    latency: LatencyRecorder,
+    rx_pool: Arc<RxPoolStats>,
+    capture: CaptureTap,
+    traffic: Arc<TrafficCounters>,
+    sources: Arc<SourceWatch>,
 }
 
 impl ReactorHandle {
@@ -332,6 +440,83 @@ impl ReactorHandle {
         self.send_command(ReactorCommand::SetDefaultTable { id });
     }
 
+    /// Replace the NIC's security group policy
+    pub fn set_security_policy(&self, policy: Option<SecurityPolicy>) {
+        self.send_command(ReactorCommand::SetSecurityPolicy { policy });
+    }
+
+    /// Replace the static DHCP leases the NIC's DHCP server answers for
+    /// other MACs. Packets routed to a leased address go to its MAC.
+    pub fn set_dhcp_leases(&self, leases: Vec<([u8; 6], Ipv4Addr)>) {
+        self.send_command(ReactorCommand::SetDhcpLeases { leases });
+    }
+
+    /// Replace the zone of the NIC's network, shared by all its NICs
+    pub fn set_dns_zone(&self, zone: Option<Arc<DnsZone>>) {
+        self.send_command(ReactorCommand::SetDnsZone { zone });
+    }
+
+    /// Drop traffic between the NIC and networks its own isn't peered with
+    pub fn set_isolation(&self, isolation: Option<Isolation>) {
+        self.send_command(ReactorCommand::SetIsolation { isolation });
+    }
+
+    /// Forward host ports to NICs; only the TUN reactor translates
+    pub fn set_port_forwards(&self, forwards: Vec<PortForward>) {
+        self.send_command(ReactorCommand::SetPortForwards { forwards });
+    }
+
+    /// Translate floating IPs to NICs; only the TUN reactor translates
+    pub fn set_floating_ips(&self, floating_ips: Vec<FloatingIp>) {
+        self.send_command(ReactorCommand::SetFloatingIps { floating_ips });
+    }
+
+    /// Packet latency histograms of the reactor
+    pub fn latency(&self) -> &LatencyRecorder {
+        &self.latency
+    }
+
+    /// Let the RX buffer pool grow up to `max` buffers under load
+    pub fn set_rx_buffer_max(&self, max: usize) {
+        self.send_command(ReactorCommand::SetRxBufferMax { max });
+    }
+
+    /// Set the MTU of traffic routed on from the NIC
+    pub fn set_mtu(&self, mtu: u16) {
+        self.send_command(ReactorCommand::SetMtu { mtu });
+    }
+
+    /// Set the guest TX burst size
+    pub fn set_tx_burst(&self, burst: usize) {
+        self.send_command(ReactorCommand::SetTxBurst { burst });
+    }
+
+    /// Bring the link up or down. A down link drops everything the guest
+    /// sends and everything routed to it.
+    pub fn set_link_up(&self, up: bool) {
+        self.send_command(ReactorCommand::SetLinkUp { up });
+    }
+
+    /// Size and high watermark of the RX buffer pool
+    pub fn rx_pool(&self) -> &Arc<RxPoolStats> {
+        &self.rx_pool
+    }
+
+    /// Where packet captures attach to the reactor
+    pub fn capture(&self) -> &CaptureTap {
+        &self.capture
+    }
+
+    /// Packets, bytes and drops to and from the guest
+    pub fn traffic(&self) -> &Arc<TrafficCounters> {
+        &self.traffic
+    }
+
+    /// Source addresses the guest sent from that aren't the NIC's
+    pub fn sources(&self) -> &Arc<SourceWatch> {
+        &self.sources
+    }
+
     /// Send a command to the reactor and signal via eventfd
     fn send_command(&self, cmd: ReactorCommand) {
         let _ = self.command_tx.send(cmd);
@@ -367,6 +552,30 @@ pub struct Reactor<RX, TX> {
     next_packet_id: u64,
     /// NIC configuration for DHCP/ARP/ND handling (for vhost interfaces)
     nic_config: Option<NicConfig>,
+    /// Security group filter and connection table of the NIC
+    firewall: Firewall,
+    /// Per-stage packet latencies (no-op without `latency-histograms`)
+    latency: LatencyRecorder,
+    /// Guest TX burst being processed, kept to reuse its allocation
+    tx_batch: Vec<VhostTxInFlight>,
+    /// Growth of the TUN RX buffer pool
+    rx_pool: RxPoolScaler,
+    /// Administrative link state; a down link forwards nothing
+    link_up: bool,
+    /// Packet captures of the guest's frames
+    capture: CaptureTap,
+    /// Traffic to and from the guest
+    traffic: Arc<TrafficCounters>,
+    /// Sources of the guest's frames that aren't the NIC's
+    sources: Arc<SourceWatch>,
+    /// Strict isolation of the NIC's network, if enabled
+    isolation: Option<Isolation>,
+    /// Port forwards and their connections (TUN reactor)
+    port_forwards: PortForwarder,
+    /// Where the DNS forwarder sends replies to the guest's queries
+    dns_reply_tx: dns::ReplySender,
+    /// Replies of the DNS forwarder
+    dns_reply_rx: Receiver<Vec<u8>>,
 }
 
 impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
@@ -421,6 +630,15 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
 
         // Create mpsc channel for commands
         let (command_tx, command_rx) = mpsc::channel();
+        // And one for DNS replies, with its own fd to wake the reactor
+        let (dns_reply_tx, dns_reply_rx) =
+            dns::ReplySender::new(unsafe { OwnedFd::from_raw_fd(nix::libc::dup(event_fd_raw)) });
+        let latency = LatencyRecorder::new();
+        let rx_pool = RxPoolScaler::new(rx_queue.capacity());
+        let rx_pool_stats = Arc::clone(rx_pool.stats());
+        let capture = CaptureTap::new();
+        let traffic = Arc::new(TrafficCounters::default());
+        let sources = Arc::new(SourceWatch::default());
 
         let reactor = Reactor {
             rx_queue,
@@ -435,6 +653,18 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
             completion_rx,
             next_packet_id: 0,
             nic_config,
+            firewall: Firewall::new(),
+            latency: latency.clone(),
+            tx_batch: Vec::new(),
+            rx_pool,
+            link_up: true,
+            capture: capture.clone(),
+            traffic: Arc::clone(&traffic),
+            sources: Arc::clone(&sources),
+            isolation: None,
+            port_forwards: PortForwarder::new(),
+            dns_reply_tx,
+            dns_reply_rx,
         };
 
         // Keep the original efd alive by forgetting it (reactor uses the raw fd)
@@ -443,6 +673,11 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
         let handle = ReactorHandle {
             event_fd: efd_dup,
             command_tx,
+            latency,
+            rx_pool: rx_pool_stats,
+            capture,
+            traffic,
+            sources,
         };
 
         (reactor, handle)
@@ -489,6 +724,9 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
             let dst_v4 =
                 std::net::Ipv4Addr::new(dst_addr.0[0], dst_addr.0[1], dst_addr.0[2], dst_addr.0[3]);
             if let Some(target) = table.lookup_v4(dst_v4) {
+                if !self.isolation_allows(IpAddr::V4(dst_v4)) {
+                    return RoutingDecision::Drop;
+                }
                 let decision = Self::route_target_to_decision(target);
                 debug!(?decision, "route_ipv4: route found");
                 return decision;
@@ -537,6 +775,9 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
         if let Some(table) = self.routing_tables.get_default() {
             let dst_v6 = std::net::Ipv6Addr::from(dst_addr.0);
             if let Some(target) = table.lookup_v6(dst_v6) {
+                if !self.isolation_allows(IpAddr::V6(dst_v6)) {
+                    return RoutingDecision::Drop;
+                }
                 let decision = Self::route_target_to_decision(target);
                 debug!(?decision, "route_ipv6: route found");
                 return decision;
@@ -550,6 +791,24 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
         RoutingDecision::Drop
     }
 
+    /// Final check of a routing decision: with strict isolation, routes
+    /// into networks the NIC's network isn't peered with are not taken.
+    fn isolation_allows(&self, dst: IpAddr) -> bool {
+        let Some(isolation) = &self.isolation else {
+            return true;
+        };
+        if isolation.allows(dst) {
+            return true;
+        }
+        debug!(
+            reactor_id = %self.reactor_id,
+            dst = %dst,
+            "Dropping packet to an isolated network"
+        );
+        self.traffic.cross_network_blocked();
+        false
+    }
+
     /// Convert a RouteTarget to a RoutingDecision
     fn route_target_to_decision(target: &RouteTarget) -> RoutingDecision {
         match target {
@@ -633,7 +892,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
 
         // Track in-flight TunRx packets (zero-copy path: packet_id -> chain_id)
         // Buffer is NOT returned to pool until completion is received
-        let mut tun_rx_in_flight: std::collections::HashMap<u64, u64> =
+        let mut tun_rx_in_flight: std::collections::HashMap<u64, (u64, InFlightTiming)> =
             std::collections::HashMap::new();
 
         // Pending TX packets to send
@@ -665,14 +924,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 break;
             };
 
-            let read_e = opcode::ReadFixed::new(
-                tun_fd,
-                chain.buffer.ptr,
-                chain.buffer.len,
-                chain.buffer.buf_index,
-            )
-            .build()
-            .user_data(chain.chain_id | USER_DATA_RX_FLAG);
+            let read_e = rx_read(tun_fd, &chain);
 
             unsafe {
                 if ring.submission().push(&read_e).is_err() {
@@ -782,6 +1034,73 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                                     debug!(%id, "Setting default routing table");
                                     self.routing_tables.set_default(id);
                                 }
+                                ReactorCommand::SetSecurityPolicy { policy } => {
+                                    self.firewall.set_policy(policy);
+                                }
+                                ReactorCommand::SetRxBufferMax { max } => {
+                                    self.rx_pool.set_max(max);
+                                }
+                                ReactorCommand::SetMtu { mtu } => {
+                                    if let Some(nic_config) = &mut self.nic_config {
+                                        nic_config.mtu = mtu;
+                                    }
+                                }
+                                ReactorCommand::SetTxBurst { burst } => {
+                                    if let Some(nic_config) = &mut self.nic_config {
+                                        nic_config.tx_burst = burst;
+                                    }
+                                }
+                                ReactorCommand::SetLinkUp { up } => {
+                                    info!(reactor_id = %self.reactor_id, up, "Link state set");
+                                    self.link_up = up;
+                                }
+                                ReactorCommand::SetDhcpLeases { leases } => {
+                                    debug!(
+                                        reactor_id = %self.reactor_id,
+                                        count = leases.len(),
+                                        "DHCP leases set"
+                                    );
+                                    if let Some(registry) = &self.registry {
+                                        registry.set_lease_macs(&self.reactor_id, &leases);
+                                    }
+                                    if let Some(nic_config) = &mut self.nic_config {
+                                        nic_config.leases = leases;
+                                    }
+                                }
+                                ReactorCommand::SetDnsZone { zone } => {
+                                    debug!(
+                                        reactor_id = %self.reactor_id,
+                                        names = zone.as_ref().map_or(0, |z| z.len()),
+                                        "DNS zone set"
+                                    );
+                                    if let Some(nic_config) = &mut self.nic_config {
+                                        nic_config.dns_zone = zone;
+                                    }
+                                }
+                                ReactorCommand::SetIsolation { isolation } => {
+                                    debug!(
+                                        reactor_id = %self.reactor_id,
+                                        ?isolation,
+                                        "Isolation set"
+                                    );
+                                    self.isolation = isolation;
+                                }
+                                ReactorCommand::SetPortForwards { forwards } => {
+                                    debug!(
+                                        reactor_id = %self.reactor_id,
+                                        count = forwards.len(),
+                                        "Port forwards set"
+                                    );
+                                    self.port_forwards.set_forwards(forwards);
+                                }
+                                ReactorCommand::SetFloatingIps { floating_ips } => {
+                                    debug!(
+                                        reactor_id = %self.reactor_id,
+                                        count = floating_ips.len(),
+                                        "Floating IPs set"
+                                    );
+                                    self.port_forwards.set_floating_ips(floating_ips);
+                                }
                             }
                         }
 
@@ -795,6 +1114,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                                 info!("Received vhost handshake from daemon");
                             }
                             vhost_state = Some(VhostState {
+                                keep_alive: Arc::new(handshake.mem.clone()),
                                 mem: handshake.mem,
                                 vrings: handshake.vrings,
                             });
@@ -816,6 +1136,10 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                         // Process incoming packets from other reactors
                         if let Some(ref state) = vhost_state {
                             self.process_incoming_packets(state, &mut vhost_to_vhost_in_flight);
+                            // And replies to the guest's forwarded DNS queries
+                            while let Ok(reply) = self.dns_reply_rx.try_recv() {
+                                self.inject_to_vhost_rx(state, &reply);
+                            }
                         } else {
                             // TUN-only reactor: write incoming packets to TUN fd
                             self.process_incoming_packets_to_tun(
@@ -851,10 +1175,10 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                                     } => {
                                         if let Some(ref state) = vhost_state {
                                             // Remove from in-flight tracking
-                                            if vhost_to_vhost_in_flight
-                                                .remove(&packet_id.raw())
-                                                .is_some()
+                                            if let Some(in_flight) =
+                                                vhost_to_vhost_in_flight.remove(&packet_id.raw())
                                             {
+                                                self.latency.complete(&in_flight.timing);
                                                 debug!(
                                                     packet_id = %packet_id,
                                                     head_index,
@@ -903,7 +1227,10 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                                         result,
                                     } => {
                                         // Remove from in-flight tracking
-                                        if tun_rx_in_flight.remove(&packet_id.raw()).is_some() {
+                                        if let Some((_, timing)) =
+                                            tun_rx_in_flight.remove(&packet_id.raw())
+                                        {
+                                            self.latency.complete(&timing);
                                             debug!(
                                                 packet_id = %packet_id,
                                                 chain_id,
@@ -916,14 +1243,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
 
                                             // Resubmit RX read
                                             if let Some(new_chain) = self.rx_queue.pop_available() {
-                                                let read_e = opcode::ReadFixed::new(
-                                                    tun_fd,
-                                                    new_chain.buffer.ptr,
-                                                    new_chain.buffer.len,
-                                                    new_chain.buffer.buf_index,
-                                                )
-                                                .build()
-                                                .user_data(new_chain.chain_id | USER_DATA_RX_FLAG);
+                                                let read_e = rx_read(tun_fd, &new_chain);
 
                                                 unsafe {
                                                     if ring.submission().push(&read_e).is_ok() {
@@ -963,6 +1283,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 let is_vhost_tx = (user_data & USER_DATA_VHOST_TX_FLAG) != 0;
                 if is_vhost_tx {
                     if let Some(in_flight) = vhost_tx_in_flight.remove(&user_data) {
+                        self.latency.complete(&in_flight.timing);
                         if result < 0 {
                             error!(error = -result, "vhost TX writev error");
                         }
@@ -1049,15 +1370,8 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 if is_tun_poll {
                     if let Some(chain) = rx_poll_pending.remove(&chain_id) {
                         if result >= 0 {
-                            // Poll succeeded - TUN is ready, resubmit ReadFixed
-                            let read_e = opcode::ReadFixed::new(
-                                tun_fd,
-                                chain.buffer.ptr,
-                                chain.buffer.len,
-                                chain.buffer.buf_index,
-                            )
-                            .build()
-                            .user_data(chain.chain_id | USER_DATA_RX_FLAG);
+                            // Poll succeeded - TUN is ready, resubmit the read
+                            let read_e = rx_read(tun_fd, &chain);
 
                             unsafe {
                                 if ring.submission().push(&read_e).is_ok() {
@@ -1111,23 +1425,33 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                     }
 
                     let len = result as usize;
+                    let popped = Stamp::now();
 
                     // Route L3 packet to appropriate VM
                     if len > VNET_HDR_SIZE {
                         let packet_data =
-                            unsafe { std::slice::from_raw_parts(chain.buffer.ptr, len) };
-                        let ip_data = &packet_data[VNET_HDR_SIZE..];
+                            unsafe { std::slice::from_raw_parts_mut(chain.buffer.ptr, len) };
+                        let partial = packet_data[0] & VIRTIO_NET_HDR_F_NEEDS_CSUM != 0;
+                        let ip_data = &mut packet_data[VNET_HDR_SIZE..];
+
+                        // Forwarded ports and floating IPs go to their NIC
+                        // whatever the routes say
+                        let forwarded =
+                            self.port_forwards.ingress(ip_data, partial, Instant::now());
+                        let ip_data = &*ip_data;
 
                         // Determine IP version and route
                         let ip_version = ip_data.first().map(|b| b >> 4);
-                        let routing_decision = match ip_version {
-                            Some(4) => self.route_ipv4(ip_data),
-                            Some(6) => self.route_ipv6(ip_data),
+                        let routing_decision = match (forwarded, ip_version) {
+                            (Some(reactor_id), _) => RoutingDecision::ToVhost { reactor_id },
+                            (None, Some(4)) => self.route_ipv4(ip_data),
+                            (None, Some(6)) => self.route_ipv6(ip_data),
                             _ => {
                                 debug!(len, "TUN RX: unknown IP version");
                                 RoutingDecision::Drop
                             }
                         };
+                        let routed = self.latency.record(Stage::Route, popped);
 
                         match routing_decision {
                             RoutingDecision::ToVhost {
@@ -1139,7 +1463,10 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                                 if let Some(registry) = registry_opt {
                                     // Get destination MAC from registry
                                     let dst_mac = registry
-                                        .get_mac_for_reactor(&target_reactor)
+                                        .get_mac_for_destination(
+                                            &target_reactor,
+                                            ipv4_destination(ip_data),
+                                        )
                                         .unwrap_or([0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
 
                                     // Determine ethertype from IP version
@@ -1185,7 +1512,12 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                                         // Track reactor for batched signaling
                                         reactors_to_signal.insert(target_reactor);
                                         // Track in-flight - DON'T return buffer yet
-                                        tun_rx_in_flight.insert(packet_id.raw(), chain_id);
+                                        let timing = InFlightTiming {
+                                            popped,
+                                            submitted: self.latency.record(Stage::Submit, routed),
+                                        };
+                                        tun_rx_in_flight
+                                            .insert(packet_id.raw(), (chain_id, timing));
                                         // Continue without returning the buffer
                                         continue;
                                     } else {
@@ -1211,14 +1543,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
 
                     // Resubmit RX read (unless shutting down)
                     if !shutdown_requested && let Some(new_chain) = self.rx_queue.pop_available() {
-                        let read_e = opcode::ReadFixed::new(
-                            tun_fd,
-                            new_chain.buffer.ptr,
-                            new_chain.buffer.len,
-                            new_chain.buffer.buf_index,
-                        )
-                        .build()
-                        .user_data(new_chain.chain_id | USER_DATA_RX_FLAG);
+                        let read_e = rx_read(tun_fd, &new_chain);
 
                         unsafe {
                             if ring.submission().push(&read_e).is_ok() {
@@ -1245,6 +1570,41 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 }
             }
 
+            // Grow the RX pool while nearly all buffers hold packets
+            let capacity = self.rx_queue.capacity();
+            let posted = rx_in_flight.len() + rx_poll_pending.len() + self.rx_queue.available();
+            let grow =
+                self.rx_pool
+                    .observe(capacity, capacity.saturating_sub(posted), Instant::now());
+            if grow > 0 && !shutdown_requested {
+                let added = self.rx_queue.grow(grow);
+                if added == 0 {
+                    warn!(
+                        reactor_id = %self.reactor_id,
+                        capacity,
+                        "Failed to grow RX buffer pool, keeping its size"
+                    );
+                    self.rx_pool.set_max(capacity);
+                } else {
+                    self.rx_pool.grown(capacity + added);
+                    info!(
+                        reactor_id = %self.reactor_id,
+                        capacity = capacity + added,
+                        "Grew RX buffer pool"
+                    );
+                    while let Some(chain) = self.rx_queue.pop_available() {
+                        let read_e = rx_read(tun_fd, &chain);
+                        unsafe {
+                            if ring.submission().push(&read_e).is_err() {
+                                self.rx_queue.push_used(chain.chain_id, 0);
+                                break;
+                            }
+                        }
+                        rx_in_flight.insert(chain.chain_id, chain);
+                    }
+                }
+            }
+
             // Signal all reactors that received packets this batch (batched signaling)
             if let Some(ref registry) = self.registry {
                 for reactor_id in &reactors_to_signal {
@@ -1340,6 +1700,10 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
             iovecs_len,
             keep_alive,
             patched_virtio_hdr: [0u8; VIRTIO_NET_HDR_SIZE],
+            timing: InFlightTiming {
+                popped: Stamp::now(),
+                submitted: Stamp::now(),
+            },
         })
     }
 
@@ -1349,10 +1713,13 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
     /// This function checks for:
     /// - ARP requests (respond for gateway IP)
     /// - DHCP packets (respond with configured IP)
+    /// - DNS queries to the gateway (answer from the network's zone, or
+    ///   hand to the DNS forwarder)
     /// - ICMPv6 NS/RS (respond for gateway)
     /// - DHCPv6 packets (respond with configured IPv6)
     ///
-    /// Returns true if the packet was handled locally and a response was injected.
+    /// Returns true if the packet was handled locally and a response was
+    /// injected or will be.
     fn handle_vhost_ethernet_protocols(&self, state: &VhostState, peek_data: &[u8]) -> bool {
         let nic_config = match &self.nic_config {
             Some(cfg) => cfg,
@@ -1395,7 +1762,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 if let Some(response) =
                     arp::handle_arp_packet(nic_config, virtio_hdr, ethernet_data)
                 {
-                    Self::inject_to_vhost_rx(state, &response);
+                    self.inject_to_vhost_rx(state, &response);
                     return true;
                 }
             }
@@ -1404,7 +1771,19 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 if let Some(response) =
                     dhcp::handle_dhcp_packet(nic_config, virtio_hdr, ethernet_data)
                 {
-                    Self::inject_to_vhost_rx(state, &response);
+                    self.inject_to_vhost_rx(state, &response);
+                    return true;
+                }
+                // Check for DNS (UDP port 53 on the gateway)
+                if let Some(handled) = dns::handle_dns_packet(
+                    nic_config,
+                    virtio_hdr,
+                    ethernet_data,
+                    &self.dns_reply_tx,
+                ) {
+                    if let dns::Handled::Reply(response) = handled {
+                        self.inject_to_vhost_rx(state, &response);
+                    }
                     return true;
                 }
                 // For ICMP echo to link-local gateway, handle it
@@ -1420,7 +1799,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                             dst = %ipv4.dst_addr(),
                             "ICMP Echo Request for gateway"
                         );
-                        Self::handle_vhost_ethernet_icmp_request(
+                        self.handle_vhost_ethernet_icmp_request(
                             state, nic_config, virtio_hdr, &eth_frame, &ipv4, &icmp,
                         );
                         return true;
@@ -1432,14 +1811,25 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 if let Some(response) =
                     icmpv6::handle_icmpv6_packet(nic_config, virtio_hdr, ethernet_data)
                 {
-                    Self::inject_to_vhost_rx(state, &response);
+                    self.inject_to_vhost_rx(state, &response);
                     return true;
                 }
                 // Check for DHCPv6
                 if let Some(response) =
                     dhcpv6::handle_dhcpv6_packet(nic_config, virtio_hdr, ethernet_data)
                 {
-                    Self::inject_to_vhost_rx(state, &response);
+                    self.inject_to_vhost_rx(state, &response);
+                    return true;
+                }
+                if let Some(handled) = dns::handle_dns_packet(
+                    nic_config,
+                    virtio_hdr,
+                    ethernet_data,
+                    &self.dns_reply_tx,
+                ) {
+                    if let dns::Handled::Reply(response) = handled {
+                        self.inject_to_vhost_rx(state, &response);
+                    }
                     return true;
                 }
             }
@@ -1451,6 +1841,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
 
     /// Handle ICMP echo request in Ethernet frame format and inject reply.
     fn handle_vhost_ethernet_icmp_request(
+        &self,
         state: &VhostState,
         _nic_config: &NicConfig,
         virtio_hdr: &[u8],
@@ -1512,7 +1903,29 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
             "ICMP Echo Reply (Ethernet)"
         );
 
-        Self::inject_to_vhost_rx(state, &reply);
+        self.inject_to_vhost_rx(state, &reply);
+    }
+
+    /// Check a routed packet against the NIC's MTU and inject an ICMP
+    /// too-big reply if it doesn't fit. Returns true if the packet was
+    /// rejected and must not be forwarded.
+    fn reject_oversized(&self, state: &VhostState, peek_data: &[u8], total_len: usize) -> bool {
+        let Some(nic_config) = &self.nic_config else {
+            return false;
+        };
+        if peek_data.len() <= VIRTIO_NET_HDR_SIZE {
+            return false;
+        }
+
+        let (virtio_hdr, ethernet_data) = peek_data.split_at(VIRTIO_NET_HDR_SIZE);
+        let frame_len = total_len.saturating_sub(VIRTIO_NET_HDR_SIZE);
+        match pmtu::check_packet_size(nic_config, virtio_hdr, ethernet_data, frame_len) {
+            Some(reply) => {
+                self.inject_to_vhost_rx(state, &reply);
+                true
+            }
+            None => false,
+        }
     }
 
     /// Parse Ethernet frame and route based on IP destination.
@@ -1568,165 +1981,263 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
 
         let mut vring_state = tx_vring.get_mut();
 
+        let burst = self
+            .nic_config
+            .as_ref()
+            .map_or(DEFAULT_TX_BURST, |c| c.tx_burst)
+            .max(1);
+
         loop {
-            let mut descriptors_returned = false;
-
-            // Create keep_alive reference to prevent guest memory from being unmapped
-            // while packets are in flight (in io_uring or inter-reactor channels)
-            let keep_alive: Option<Arc<dyn std::any::Any + Send + Sync>> =
-                Some(Arc::new(state.mem.clone()));
-
-            let queue = vring_state.get_queue_mut();
-            while let Some(desc_chain) = queue.pop_descriptor_chain(&*mem_guard) {
-                let Some(in_flight) =
-                    Self::desc_chain_to_iovecs(&desc_chain, &mem_guard, keep_alive.clone())
-                else {
-                    // Empty chain - return immediately
-                    let _ = queue.add_used(&*mem_guard, desc_chain.head_index(), 0);
-                    descriptors_returned = true;
-                    continue;
-                };
+            // Take the ring in bursts: pop up to `burst` descriptors, route
+            // them, then hand all TUN writes to io_uring at once.
+            loop {
+                let mut descriptors_returned = false;
+                let mut batch = std::mem::take(&mut self.tx_batch);
+                let mut writes: SmallVec<[squeue::Entry; DEFAULT_TX_BURST]> = SmallVec::new();
+
+                let queue = vring_state.get_queue_mut();
+                while batch.len() < burst
+                    && let Some(desc_chain) = queue.pop_descriptor_chain(&*mem_guard)
+                {
+                    match Self::desc_chain_to_iovecs(
+                        &desc_chain,
+                        &mem_guard,
+                        Some(state.keep_alive.clone()),
+                    ) {
+                        Some(in_flight) => batch.push(in_flight),
+                        None => {
+                            // Empty chain - return immediately
+                            let _ = queue.add_used(&*mem_guard, desc_chain.head_index(), 0);
+                            descriptors_returned = true;
+                        }
+                    }
+                }
+                // A short burst means the ring is drained
+                let drained = batch.len() < burst;
 
-                debug!(
-                    len = in_flight.total_len,
-                    iovecs = in_flight.iovecs_len,
-                    "vhost TX processing"
-                );
+                for in_flight in batch.drain(..) {
+                    debug!(
+                        len = in_flight.total_len,
+                        iovecs = in_flight.iovecs_len,
+                        "vhost TX processing"
+                    );
 
-                // Peek at packet headers (stack buffer avoids heap allocation)
-                let mut peek_buf = [0u8; PEEK_BUF_SIZE];
-                let peek_slice = Self::peek_packet_headers(&in_flight, &mut peek_buf);
+                    // A down link drops everything, local protocols included
+                    if !self.link_up {
+                        self.traffic.tx_dropped(1);
+                        let _ = queue.add_used(&*mem_guard, in_flight.head_index, 0);
+                        descriptors_returned = true;
+                        continue;
+                    }
 
-                // First, try to handle protocol packets locally (ARP, DHCP, ICMPv6, DHCPv6)
-                // These need responses injected back to the VM
-                if self.handle_vhost_ethernet_protocols(state, peek_slice) {
-                    // Protocol handler consumed the packet and injected a response
-                    let _ = queue.add_used(&*mem_guard, in_flight.head_index, in_flight.total_len);
-                    descriptors_returned = true;
-                    continue;
-                }
+                    let frame_len =
+                        (in_flight.total_len as usize).saturating_sub(VIRTIO_NET_HDR_SIZE);
+                    self.traffic.tx(frame_len);
+                    if self.capture.is_active() {
+                        self.capture.record(Direction::FromGuest, frame_len, |buf| {
+                            copy_from_iovecs(
+                                &in_flight.iovecs,
+                                in_flight.iovecs_len,
+                                VIRTIO_NET_HDR_SIZE,
+                                buf,
+                            );
+                        });
+                    }
 
-                // Route the packet using Ethernet-aware routing
-                let routing_decision = self.peek_and_route_ethernet(peek_slice);
+                    // Peek at packet headers (stack buffer avoids heap allocation)
+                    let mut peek_buf = [0u8; PEEK_BUF_SIZE];
+                    let peek_slice = Self::peek_packet_headers(&in_flight, &mut peek_buf);
 
-                match routing_decision {
-                    RoutingDecision::ToTun { if_index: _ } => {
-                        // Route to TUN - strip Ethernet header for L3 TUN device
-                        // Box to ensure stable memory address for io_uring
-                        let mut boxed = Box::new(in_flight);
+                    // Note sources that aren't the NIC's for the conflict monitor
+                    if let Some(nic_config) = &self.nic_config
+                        && let Some(frame) = peek_slice.get(VIRTIO_NET_HDR_SIZE..)
+                    {
+                        for source in sources::foreign_sources(nic_config, frame)
+                            .into_iter()
+                            .flatten()
+                        {
+                            self.sources.record(source);
+                        }
+                    }
 
-                        // Save original iovecs before prepare_tun_iovecs modifies them
-                        let src_iovecs = boxed.iovecs;
-                        let src_len = boxed.iovecs_len;
+                    // First, try to handle protocol packets locally (ARP, DHCP, ICMPv6, DHCPv6)
+                    // These need responses injected back to the VM
+                    if self.handle_vhost_ethernet_protocols(state, peek_slice) {
+                        // Protocol handler consumed the packet and injected a response
+                        let _ =
+                            queue.add_used(&*mem_guard, in_flight.head_index, in_flight.total_len);
+                        descriptors_returned = true;
+                        continue;
+                    }
 
-                        // Prepare TUN iovecs: copy+patch virtio header, skip Ethernet
-                        if !prepare_tun_iovecs(&src_iovecs, src_len, &mut boxed) {
-                            warn!("Packet too short for Ethernet stripping");
-                            let _ = queue.add_used(&*mem_guard, boxed.head_index, 0);
-                            descriptors_returned = true;
-                            continue;
-                        }
+                    // Route the packet using Ethernet-aware routing
+                    let routing_decision = self.peek_and_route_ethernet(peek_slice);
+                    let routed = self.latency.record(Stage::Route, in_flight.timing.popped);
+
+                    // Routed packets over the MTU are answered with ICMP too-big
+                    if !matches!(routing_decision, RoutingDecision::Drop)
+                        && self.reject_oversized(state, peek_slice, in_flight.total_len as usize)
+                    {
+                        self.traffic.tx_dropped(1);
+                        let _ = queue.add_used(&*mem_guard, in_flight.head_index, 0);
+                        descriptors_returned = true;
+                        continue;
+                    }
 
-                        let user_data = *vhost_tx_id | USER_DATA_VHOST_TX_FLAG;
-                        *vhost_tx_id = vhost_tx_id.wrapping_add(1);
+                    // Track what the VM sends so replies pass its security groups
+                    if !matches!(routing_decision, RoutingDecision::Drop)
+                        && let Some(ip) = peek_slice.get(VIRTIO_NET_HDR_SIZE + ETHERNET_HDR_SIZE..)
+                    {
+                        self.firewall.egress(ip, Instant::now());
+                    }
 
-                        let writev = opcode::Writev::new(
-                            tun_fd,
-                            boxed.iovecs.as_ptr(),
-                            boxed.iovecs_len as u32,
-                        )
-                        .build()
-                        .user_data(user_data);
+                    match routing_decision {
+                        RoutingDecision::ToTun { if_index: _ } => {
+                            // Route to TUN - strip Ethernet header for L3 TUN device
+                            // Box to ensure stable memory address for io_uring
+                            let mut boxed = Box::new(in_flight);
 
-                        unsafe {
-                            if ring.submission().push(&writev).is_ok() {
-                                debug!(len = boxed.total_len, "vhost TX -> TUN (zero-copy)");
-                                vhost_tx_in_flight.insert(user_data, boxed);
-                            } else {
-                                warn!("SQ full, dropping vhost TX");
+                            // Save original iovecs before prepare_tun_iovecs modifies them
+                            let src_iovecs = boxed.iovecs;
+                            let src_len = boxed.iovecs_len;
+
+                            // Prepare TUN iovecs: copy+patch virtio header, skip Ethernet
+                            if !prepare_tun_iovecs(&src_iovecs, src_len, &mut boxed) {
+                                warn!("Packet too short for Ethernet stripping");
                                 let _ = queue.add_used(&*mem_guard, boxed.head_index, 0);
                                 descriptors_returned = true;
+                                continue;
                             }
-                        }
-                    }
 
-                    RoutingDecision::ToVhost {
-                        reactor_id: target_reactor_id,
-                    } => {
-                        // Route to another VM (or TUN reactor) - send via registry
-                        // Clone registry to avoid borrow conflicts
-                        let registry_opt = self.registry.clone();
-                        if let Some(registry) = registry_opt {
-                            // Lookup destination VM's MAC address for header rewriting
-                            let dst_mac = registry
-                                .get_mac_for_reactor(&target_reactor_id)
-                                .unwrap_or([0xff; 6]); // Fallback: broadcast
-
-                            let packet_id = self.next_packet_id();
-                            let source = PacketSource::VhostToVhost {
-                                head_index: in_flight.head_index,
-                                total_len: in_flight.total_len,
-                                source_reactor: self.reactor_id,
-                                dst_mac,
-                                src_mac: GATEWAY_MAC,
-                            };
-
-                            let packet = PacketRef::new(
-                                packet_id,
-                                in_flight.iovecs, // Direct array copy (stack, not heap)
-                                in_flight.iovecs_len,
-                                source,
-                                in_flight.keep_alive.clone(),
-                            );
+                            let user_data = *vhost_tx_id | USER_DATA_VHOST_TX_FLAG;
+                            *vhost_tx_id = vhost_tx_id.wrapping_add(1);
 
-                            debug!(
-                                packet_id = %packet_id,
-                                len = in_flight.total_len,
-                                src = %self.reactor_id,
-                                dst = %target_reactor_id,
-                                head_idx = in_flight.head_index,
-                                "Sending packet to target reactor"
+                            // Queued with the rest of the burst below
+                            writes.push(
+                                opcode::Writev::new(
+                                    tun_fd,
+                                    boxed.iovecs.as_ptr(),
+                                    boxed.iovecs_len as u32,
+                                )
+                                .build()
+                                .user_data(user_data),
                             );
+                            debug!(len = boxed.total_len, "vhost TX -> TUN (zero-copy)");
+                            boxed.timing.submitted = self.latency.record(Stage::Submit, routed);
+                            vhost_tx_in_flight.insert(user_data, boxed);
+                        }
+
+                        RoutingDecision::ToVhost {
+                            reactor_id: target_reactor_id,
+                        } => {
+                            // Route to another VM (or TUN reactor) - send via registry
+                            // Clone registry to avoid borrow conflicts
+                            let registry_opt = self.registry.clone();
+                            if let Some(registry) = registry_opt {
+                                // Lookup destination VM's MAC address for header rewriting
+                                let dst_ip = peek_slice
+                                    .get(VIRTIO_NET_HDR_SIZE + ETHERNET_HDR_SIZE..)
+                                    .and_then(ipv4_destination);
+                                let dst_mac = registry
+                                    .get_mac_for_destination(&target_reactor_id, dst_ip)
+                                    .unwrap_or([0xff; 6]); // Fallback: broadcast
+
+                                let packet_id = self.next_packet_id();
+                                let source = PacketSource::VhostToVhost {
+                                    head_index: in_flight.head_index,
+                                    total_len: in_flight.total_len,
+                                    source_reactor: self.reactor_id,
+                                    dst_mac,
+                                    src_mac: GATEWAY_MAC,
+                                };
+
+                                let packet = PacketRef::new(
+                                    packet_id,
+                                    in_flight.iovecs, // Direct array copy (stack, not heap)
+                                    in_flight.iovecs_len,
+                                    source,
+                                    in_flight.keep_alive.clone(),
+                                );
 
-                            if registry.send_packet_to_no_signal(&target_reactor_id, packet) {
-                                reactors_to_signal.insert(target_reactor_id);
                                 debug!(
+                                    packet_id = %packet_id,
                                     len = in_flight.total_len,
+                                    src = %self.reactor_id,
                                     dst = %target_reactor_id,
-                                    "vhost TX -> vhost (VM-to-VM)"
-                                );
-                                // Track in-flight - don't return descriptor yet
-                                vhost_to_vhost_in_flight.insert(
-                                    packet_id.raw(),
-                                    VhostToVhostInFlight {
-                                        head_index: in_flight.head_index,
-                                        total_len: in_flight.total_len,
-                                    },
+                                    head_idx = in_flight.head_index,
+                                    "Sending packet to target reactor"
                                 );
+
+                                if registry.send_packet_to_no_signal(&target_reactor_id, packet) {
+                                    reactors_to_signal.insert(target_reactor_id);
+                                    debug!(
+                                        len = in_flight.total_len,
+                                        dst = %target_reactor_id,
+                                        "vhost TX -> vhost (VM-to-VM)"
+                                    );
+                                    // Track in-flight - don't return descriptor yet
+                                    vhost_to_vhost_in_flight.insert(
+                                        packet_id.raw(),
+                                        VhostToVhostInFlight {
+                                            head_index: in_flight.head_index,
+                                            total_len: in_flight.total_len,
+                                            timing: InFlightTiming {
+                                                popped: in_flight.timing.popped,
+                                                submitted: self
+                                                    .latency
+                                                    .record(Stage::Submit, routed),
+                                            },
+                                        },
+                                    );
+                                } else {
+                                    warn!(dst = %target_reactor_id, "Failed to send to target reactor");
+                                    self.traffic.tx_dropped(1);
+                                    let _ = queue.add_used(&*mem_guard, in_flight.head_index, 0);
+                                    descriptors_returned = true;
+                                }
                             } else {
-                                warn!(dst = %target_reactor_id, "Failed to send to target reactor");
+                                warn!("No registry configured for VM-to-VM routing");
                                 let _ = queue.add_used(&*mem_guard, in_flight.head_index, 0);
                                 descriptors_returned = true;
                             }
-                        } else {
-                            warn!("No registry configured for VM-to-VM routing");
+                        }
+
+                        RoutingDecision::Drop => {
+                            // Drop packet - return descriptor immediately
+                            debug!(len = in_flight.total_len, "vhost TX dropped (no route)");
+                            self.traffic.tx_dropped(1);
                             let _ = queue.add_used(&*mem_guard, in_flight.head_index, 0);
                             descriptors_returned = true;
                         }
                     }
-
-                    RoutingDecision::Drop => {
-                        // Drop packet - return descriptor immediately
-                        debug!(len = in_flight.total_len, "vhost TX dropped (no route)");
-                        let _ = queue.add_used(&*mem_guard, in_flight.head_index, 0);
-                        descriptors_returned = true;
+                }
+                self.tx_batch = batch;
+
+                if !writes.is_empty() {
+                    // On a full SQ, submit what is queued and retry once
+                    let queued = unsafe { ring.submission().push_multiple(&writes).is_ok() }
+                        || (ring.submit().is_ok()
+                            && unsafe { ring.submission().push_multiple(&writes).is_ok() });
+                    if !queued {
+                        warn!(count = writes.len(), "SQ full, dropping vhost TX burst");
+                        self.traffic.tx_dropped(writes.len() as u64);
+                        for write in &writes {
+                            if let Some(dropped) = vhost_tx_in_flight.remove(&write.get_user_data())
+                            {
+                                let _ = queue.add_used(&*mem_guard, dropped.head_index, 0);
+                                descriptors_returned = true;
+                            }
+                        }
                     }
                 }
-            }
 
-            // Only signal used queue if we actually returned descriptors
-            if descriptors_returned {
-                let _ = vring_state.signal_used_queue();
+                // Only signal used queue if we actually returned descriptors
+                if descriptors_returned {
+                    let _ = vring_state.signal_used_queue();
+                }
+                if drained {
+                    break;
+                }
             }
 
             // Re-enable notifications for event_idx mode.
@@ -2306,7 +2817,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
     /// copies the data to the local VM's RX queue, and sends CompletionNotify
     /// back to the source reactor.
     fn process_incoming_packets(
-        &self,
+        &mut self,
         state: &VhostState,
         _vhost_to_vhost_in_flight: &mut std::collections::HashMap<u64, VhostToVhostInFlight>,
     ) {
@@ -2326,10 +2837,34 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 "Processing incoming VM-to-VM packet"
             );
 
+            // Filtered packets and packets for a down link are completed
+            // as if delivered
+            if !self.link_up
+                || !Self::admit_incoming(
+                    &mut self.firewall,
+                    self.isolation.as_ref(),
+                    &self.traffic,
+                    &packet,
+                )
+            {
+                self.traffic.rx_dropped();
+                self.send_incoming_completion(&packet, 0);
+                continue;
+            }
+
+            if self.capture.is_active() {
+                Self::capture_incoming(&self.capture, &packet);
+            }
+
             // Copy packet to local RX queue
             let result = Self::copy_to_vhost_rx(state, &packet);
             if result >= 0 {
+                // Count the frame, without the virtio header
+                self.traffic
+                    .rx((result as usize).saturating_sub(VIRTIO_NET_HDR_SIZE));
                 signal_needed = true;
+            } else {
+                self.traffic.rx_dropped();
             }
 
             // Send completion notification back to source reactor
@@ -2378,6 +2913,98 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
         }
     }
 
+    /// Hand a packet from another reactor to the captures as the guest
+    /// receives it: TUN packets with the Ethernet header we inject, VM
+    /// packets with their MACs rewritten.
+    fn capture_incoming(tap: &CaptureTap, packet: &PacketRef) {
+        let payload_len = packet.total_len().saturating_sub(VIRTIO_NET_HDR_SIZE);
+        match packet.source {
+            PacketSource::TunRx {
+                dst_mac, ethertype, ..
+            } => {
+                let mut eth_hdr = [0u8; ETHERNET_HDR_SIZE];
+                eth_hdr[0..6].copy_from_slice(&dst_mac);
+                eth_hdr[6..12].copy_from_slice(&GATEWAY_MAC);
+                eth_hdr[12..14].copy_from_slice(&ethertype.to_be_bytes());
+                let frame_len = ETHERNET_HDR_SIZE + payload_len;
+                tap.record(Direction::ToGuest, frame_len, |buf| {
+                    let hdr_len = buf.len().min(ETHERNET_HDR_SIZE);
+                    buf[..hdr_len].copy_from_slice(&eth_hdr[..hdr_len]);
+                    copy_from_iovecs(
+                        packet.iovecs(),
+                        packet.iovecs_len(),
+                        VIRTIO_NET_HDR_SIZE,
+                        &mut buf[hdr_len..],
+                    );
+                });
+            }
+            PacketSource::VhostToVhost {
+                dst_mac, src_mac, ..
+            } => {
+                tap.record(Direction::ToGuest, payload_len, |buf| {
+                    copy_from_iovecs(
+                        packet.iovecs(),
+                        packet.iovecs_len(),
+                        VIRTIO_NET_HDR_SIZE,
+                        buf,
+                    );
+                    let mut macs = [0u8; 12];
+                    macs[0..6].copy_from_slice(&dst_mac);
+                    macs[6..12].copy_from_slice(&src_mac);
+                    let len = buf.len().min(macs.len());
+                    buf[..len].copy_from_slice(&macs[..len]);
+                });
+            }
+            PacketSource::VhostTx { .. } => {
+                tap.record(Direction::ToGuest, payload_len, |buf| {
+                    copy_from_iovecs(
+                        packet.iovecs(),
+                        packet.iovecs_len(),
+                        VIRTIO_NET_HDR_SIZE,
+                        buf,
+                    );
+                });
+            }
+        }
+    }
+
+    /// Check a packet from another reactor against strict isolation and
+    /// the NIC's security groups.
+    fn admit_incoming(
+        firewall: &mut Firewall,
+        isolation: Option<&Isolation>,
+        traffic: &TrafficCounters,
+        packet: &PacketRef,
+    ) -> bool {
+        // Packets from a TUN carry no Ethernet header
+        let ip_offset = match packet.source {
+            PacketSource::TunRx { .. } => VIRTIO_NET_HDR_SIZE,
+            _ => VIRTIO_NET_HDR_SIZE + ETHERNET_HDR_SIZE,
+        };
+        let mut header = [0u8; FIREWALL_PEEK_SIZE];
+        let len = packet
+            .total_len()
+            .saturating_sub(ip_offset)
+            .min(FIREWALL_PEEK_SIZE);
+        if !copy_from_iovecs(
+            packet.iovecs(),
+            packet.iovecs_len(),
+            ip_offset,
+            &mut header[..len],
+        ) {
+            return true;
+        }
+        if let Some(isolation) = isolation
+            && let Some(src) = isolation::source_address(&header[..len])
+            && !isolation.allows(src)
+        {
+            debug!(src = %src, "Dropping packet from an isolated network");
+            traffic.cross_network_blocked();
+            return false;
+        }
+        firewall.ingress(&header[..len], Instant::now())
+    }
+
     /// Process incoming packets from other reactors for TUN-only reactors.
     ///
     /// For TUN-only reactors (no vhost), packets from other reactors should be
@@ -2432,6 +3059,25 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                 patch_virtio_hdr_for_eth_stripping(hdr_array_ref);
             }
 
+            // Replies to forwarded connections leave with the host address
+            // and NICs with a floating IP from it; NICs in private networks
+            // send nothing else
+            let partial = l3_packet[0] & VIRTIO_NET_HDR_F_NEEDS_CSUM != 0;
+            if self.port_forwards.egress(
+                &mut l3_packet[VIRTIO_NET_HDR_SIZE..],
+                partial,
+                packet.source.source_reactor(),
+                Instant::now(),
+            ) == Egress::Drop
+            {
+                debug!(
+                    src = %packet.source.source_reactor(),
+                    "TUN reactor: dropping packet of a private network"
+                );
+                self.send_incoming_completion(&packet, 0);
+                continue;
+            }
+
             // Generate unique user_data for this write
             let user_data = USER_DATA_INCOMING_TUN_FLAG | *incoming_tun_id;
             *incoming_tun_id = incoming_tun_id.wrapping_add(1);
@@ -2504,7 +3150,14 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
     }
 
     /// Inject a packet into the vhost RX queue (network → guest)
-    fn inject_to_vhost_rx(state: &VhostState, packet: &[u8]) {
+    fn inject_to_vhost_rx(&self, state: &VhostState, packet: &[u8]) {
+        if self.capture.is_active() {
+            let frame = packet.get(VIRTIO_NET_HDR_SIZE..).unwrap_or_default();
+            self.capture.record(Direction::ToGuest, frame.len(), |buf| {
+                buf.copy_from_slice(&frame[..buf.len()]);
+            });
+        }
+
         let mem_guard = state.mem.memory();
         let rx_vring = &state.vrings[VHOST_RX_QUEUE];
 
@@ -2521,6 +3174,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
         // Pop descriptor chains until all packet data is written
         while written < packet_len {
             let Some(desc_chain) = queue.pop_descriptor_chain(&*mem_guard) else {
+                self.traffic.rx_dropped();
                 if chains_used.is_empty() {
                     warn!("No RX buffer available for vhost injection");
                     return;
@@ -2556,6 +3210,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
                         mem_guard.write_slice(&packet[written..written + to_write], desc.addr())
                     {
                         warn!(?e, "Failed to write to vhost RX buffer");
+                        self.traffic.rx_dropped();
                         // Return all chains with len=0
                         for (head_idx, _) in &chains_used {
                             let _ = queue.add_used(&*mem_guard, *head_idx, 0);
@@ -2593,6 +3248,7 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
         if let Err(e) = vring_state.signal_used_queue() {
             warn!(?e, "Failed to signal vhost RX used queue");
         }
+        self.traffic.rx(written.saturating_sub(VIRTIO_NET_HDR_SIZE));
 
         debug!(
             len = written,
@@ -2601,6 +3257,18 @@ impl<RX: RxVirtqueue, TX: TxVirtqueue> Reactor<RX, TX> {
     }
 }
 
+/// Read from the TUN device into an RX buffer. Buffers added to the pool
+/// after io_uring registration are read without the fixed-buffer path.
+fn rx_read(tun_fd: types::Fd, chain: &DescriptorChain) -> squeue::Entry {
+    let buf = &chain.buffer;
+    let read = if buf.fixed {
+        opcode::ReadFixed::new(tun_fd, buf.ptr, buf.len, buf.buf_index).build()
+    } else {
+        opcode::Read::new(tun_fd, buf.ptr, buf.len).build()
+    };
+    read.user_data(chain.chain_id | USER_DATA_RX_FLAG)
+}
+
 /// Patch virtio_net_hdr for Ethernet header injection.
 ///
 /// When injecting a 14-byte Ethernet header into L3 packets from TUN,
@@ -0,0 +1,655 @@
+//! Port forwarding and floating IPs in the TUN reactor.
+//!
+//! A port forward sends what arrives for a TCP or UDP port of a host
+//! address to a port of a NIC's IPv4 address. The host address is routed
+//! into the TUN like a public subnet. The TUN reactor rewrites the
+//! destination of each packet for a forwarded port and hands it straight
+//! to the NIC's reactor instead of routing it, so forwards also reach NICs
+//! in private networks, whose subnets may overlap. The connection is
+//! tracked, and the VM's replies leave through the TUN with the host
+//! address and port as their source.
+//!
+//! A NIC in a private network gets a way out to the TUN for its replies
+//! and nothing else: what it sends there is dropped unless it answers a
+//! forwarded connection.
+//!
+//! A floating IP is translated 1:1 instead: everything for its address
+//! goes to the NIC, whatever the protocol, and everything the NIC sends
+//! out through the TUN leaves from it, without tracking connections. A
+//! NIC with a floating IP may send anything, even from a private network.
+//! Port forwards come first, so connections through them keep their host
+//! address.
+//!
+//! Fragmented packets are not port forwarded. Floating IPs translate them,
+//! with the TCP/UDP checksum updated in the first fragment only.
+
+use super::firewall::{Flow, MAX_TRACKED_FLOWS};
+use crate::inter_reactor::ReactorId;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::{Duration, Instant};
+
+/// IP protocol numbers with ports
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+
+/// Offsets of the addresses in the IPv4 header and of the ports in the
+/// TCP/UDP header
+const SRC_ADDR: usize = 12;
+const DST_ADDR: usize = 16;
+const SRC_PORT: usize = 0;
+const DST_PORT: usize = 2;
+
+/// How often expired connections are swept out
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+/// A port forward in reactor form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortForward {
+    /// IP protocol number, TCP or UDP
+    pub protocol: u8,
+    pub host_ip: Ipv4Addr,
+    pub host_port: u16,
+    /// Address of the NIC
+    pub vm_ip: Ipv4Addr,
+    pub vm_port: u16,
+    /// Reactor of the NIC
+    pub target: ReactorId,
+    /// The NIC is in a private network: only its replies may leave
+    pub reply_only: bool,
+}
+
+/// A floating IP in reactor form.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FloatingIp {
+    pub address: Ipv4Addr,
+    /// Address of the NIC
+    pub vm_ip: Ipv4Addr,
+    /// Reactor of the NIC
+    pub target: ReactorId,
+}
+
+/// What happens to a packet leaving through the TUN.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Egress {
+    /// A reply whose source is now the host address and port, or a packet
+    /// whose source is now the floating IP
+    Translated,
+    /// Not part of a forwarded connection
+    Untouched,
+    /// Sent by a NIC in a private network and not a reply
+    Drop,
+}
+
+/// Host address and port a tracked connection came in on.
+#[derive(Debug, Clone, Copy)]
+struct Translation {
+    host_ip: Ipv4Addr,
+    host_port: u16,
+    seen: Instant,
+}
+
+/// Port forwards, the connections through them, and floating IPs.
+#[derive(Debug)]
+pub struct PortForwarder {
+    /// Forwards by protocol, host address and host port
+    rules: HashMap<(u8, Ipv4Addr, u16), PortForward>,
+    /// Floating IPs by address
+    floating: HashMap<Ipv4Addr, FloatingIp>,
+    /// Floating IPs by the NIC's reactor and address
+    floating_by_nic: HashMap<(ReactorId, Ipv4Addr), Ipv4Addr>,
+    /// Reactors of NICs in private networks
+    reply_only: HashSet<ReactorId>,
+    /// Connections by the NIC's reactor and the flow of its replies
+    conns: HashMap<(ReactorId, Flow), Translation>,
+    last_sweep: Instant,
+}
+
+impl PortForwarder {
+    pub fn new() -> Self {
+        PortForwarder {
+            rules: HashMap::new(),
+            floating: HashMap::new(),
+            floating_by_nic: HashMap::new(),
+            reply_only: HashSet::new(),
+            conns: HashMap::new(),
+            last_sweep: Instant::now(),
+        }
+    }
+
+    /// Replace the forwards. Connections through removed forwards are
+    /// forgotten.
+    pub fn set_forwards(&mut self, forwards: Vec<PortForward>) {
+        self.reply_only = forwards
+            .iter()
+            .filter(|f| f.reply_only)
+            .map(|f| f.target)
+            .collect();
+        self.rules = forwards
+            .into_iter()
+            .map(|f| ((f.protocol, f.host_ip, f.host_port), f))
+            .collect();
+        let rules = &self.rules;
+        self.conns.retain(|(target, flow), t| {
+            rules
+                .get(&(flow.protocol, t.host_ip, t.host_port))
+                .is_some_and(|f| {
+                    f.target == *target
+                        && flow.src == IpAddr::V4(f.vm_ip)
+                        && flow.src_port == f.vm_port
+                })
+        });
+    }
+
+    /// Replace the floating IPs.
+    pub fn set_floating_ips(&mut self, floating_ips: Vec<FloatingIp>) {
+        self.floating_by_nic = floating_ips
+            .iter()
+            .map(|f| ((f.target, f.vm_ip), f.address))
+            .collect();
+        self.floating = floating_ips.into_iter().map(|f| (f.address, f)).collect();
+    }
+
+    /// Number of forwards
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Number of floating IPs
+    pub fn floating_len(&self) -> usize {
+        self.floating.len()
+    }
+
+    /// Translate a packet from the TUN if it is for a forwarded port.
+    /// Returns the reactor of the NIC it goes to. `partial` is set when the
+    /// L4 checksum is left to be completed (`VIRTIO_NET_HDR_F_NEEDS_CSUM`).
+    pub fn ingress(&mut self, ip: &mut [u8], partial: bool, now: Instant) -> Option<ReactorId> {
+        if self.rules.is_empty() && self.floating.is_empty() {
+            return None;
+        }
+        let forwarded = unfragmented_flow(ip).and_then(|flow| {
+            let IpAddr::V4(host_ip) = flow.dst else {
+                return None;
+            };
+            let fwd = self.rules.get(&(flow.protocol, host_ip, flow.dst_port))?;
+            Some((flow, host_ip, fwd.target, fwd.vm_ip, fwd.vm_port))
+        });
+        let Some((flow, host_ip, target, vm_ip, vm_port)) = forwarded else {
+            return self.floating_ingress(ip, partial);
+        };
+
+        if !rewrite(ip, DST_ADDR, vm_ip, DST_PORT, vm_port, partial) {
+            return None;
+        }
+        let reply = Flow {
+            src: IpAddr::V4(vm_ip),
+            dst: flow.src,
+            protocol: flow.protocol,
+            src_port: vm_port,
+            dst_port: flow.src_port,
+        };
+        self.track(target, reply, host_ip, flow.dst_port, now);
+        Some(target)
+    }
+
+    /// Translate a packet for a floating IP to the NIC's address.
+    fn floating_ingress(&self, ip: &mut [u8], partial: bool) -> Option<ReactorId> {
+        if ip.len() < 20 || ip[0] >> 4 != 4 {
+            return None;
+        }
+        let dst = Ipv4Addr::new(ip[16], ip[17], ip[18], ip[19]);
+        let fip = self.floating.get(&dst)?;
+        rewrite_addr(ip, DST_ADDR, fip.vm_ip, partial).then_some(fip.target)
+    }
+
+    /// Translate a packet a NIC's reactor `from` sends out through the TUN
+    /// if it answers a forwarded connection or the NIC has a floating IP.
+    pub fn egress(
+        &mut self,
+        ip: &mut [u8],
+        partial: bool,
+        from: ReactorId,
+        now: Instant,
+    ) -> Egress {
+        let untracked = if self.reply_only.contains(&from) {
+            Egress::Drop
+        } else {
+            Egress::Untouched
+        };
+        if let Some(flow) = unfragmented_flow(ip)
+            && let Some(t) = self.conns.get_mut(&(from, flow))
+            && now.saturating_duration_since(t.seen) < flow.timeout()
+        {
+            t.seen = now;
+            if rewrite(ip, SRC_ADDR, t.host_ip, SRC_PORT, t.host_port, partial) {
+                return Egress::Translated;
+            }
+        }
+        self.floating_egress(ip, partial, from).unwrap_or(untracked)
+    }
+
+    /// Translate a packet of a NIC with a floating IP to leave from it.
+    fn floating_egress(&self, ip: &mut [u8], partial: bool, from: ReactorId) -> Option<Egress> {
+        if self.floating_by_nic.is_empty() || ip.len() < 20 || ip[0] >> 4 != 4 {
+            return None;
+        }
+        let src = Ipv4Addr::new(ip[12], ip[13], ip[14], ip[15]);
+        let address = *self.floating_by_nic.get(&(from, src))?;
+        rewrite_addr(ip, SRC_ADDR, address, partial).then_some(Egress::Translated)
+    }
+
+    fn track(
+        &mut self,
+        target: ReactorId,
+        reply: Flow,
+        host_ip: Ipv4Addr,
+        host_port: u16,
+        now: Instant,
+    ) {
+        if now.saturating_duration_since(self.last_sweep) >= SWEEP_INTERVAL {
+            self.sweep(now);
+        }
+        let key = (target, reply);
+        if !self.conns.contains_key(&key) && self.conns.len() >= MAX_TRACKED_FLOWS {
+            self.sweep(now);
+            // Replies of connections not tracked leave untranslated
+            if self.conns.len() >= MAX_TRACKED_FLOWS {
+                return;
+            }
+        }
+        self.conns.insert(
+            key,
+            Translation {
+                host_ip,
+                host_port,
+                seen: now,
+            },
+        );
+    }
+
+    fn sweep(&mut self, now: Instant) {
+        self.conns
+            .retain(|(_, flow), t| now.saturating_duration_since(t.seen) < flow.timeout());
+        self.last_sweep = now;
+    }
+}
+
+impl Default for PortForwarder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The flow of an IPv4 TCP or UDP packet that is not a fragment.
+fn unfragmented_flow(ip: &[u8]) -> Option<Flow> {
+    if ip.len() < 20 || ip[0] >> 4 != 4 {
+        return None;
+    }
+    // More fragments flag or a fragment offset
+    if u16::from_be_bytes([ip[6], ip[7]]) & 0x3fff != 0 {
+        return None;
+    }
+    let flow = Flow::parse(ip)?;
+    matches!(flow.protocol, PROTO_TCP | PROTO_UDP).then_some(flow)
+}
+
+/// Replace an address in the IPv4 header and a port in the TCP/UDP header
+/// and update both checksums. With `partial` the L4 checksum field only
+/// holds the pseudo header sum, which covers the address but not the port.
+fn rewrite(
+    ip: &mut [u8],
+    addr_offset: usize,
+    addr: Ipv4Addr,
+    port_offset: usize,
+    port: u16,
+    partial: bool,
+) -> bool {
+    let ihl = ((ip[0] & 0x0f) as usize) * 4;
+    let protocol = ip[9];
+    let csum_at = ihl
+        + match protocol {
+            PROTO_TCP => 16,
+            PROTO_UDP => 6,
+            _ => return false,
+        };
+    if ihl < 20 || ip.len() < csum_at + 2 {
+        return false;
+    }
+    let port_at = ihl + port_offset;
+
+    let old_addr: [u8; 4] = ip[addr_offset..addr_offset + 4].try_into().unwrap();
+    let new_addr = addr.octets();
+    let old_port = [ip[port_at], ip[port_at + 1]];
+    let new_port = port.to_be_bytes();
+
+    let check = read_u16(ip, 10);
+    write_u16(ip, 10, update_checksum(check, &old_addr, &new_addr));
+
+    let check = read_u16(ip, csum_at);
+    if partial {
+        write_u16(ip, csum_at, update_sum(check, &old_addr, &new_addr));
+    } else if !(protocol == PROTO_UDP && check == 0) {
+        // A UDP checksum of 0 means none
+        let check = update_checksum(check, &old_addr, &new_addr);
+        let check = update_checksum(check, &old_port, &new_port);
+        let check = if protocol == PROTO_UDP && check == 0 {
+            0xffff
+        } else {
+            check
+        };
+        write_u16(ip, csum_at, check);
+    }
+
+    ip[addr_offset..addr_offset + 4].copy_from_slice(&new_addr);
+    ip[port_at..port_at + 2].copy_from_slice(&new_port);
+    true
+}
+
+/// Replace an address in the IPv4 header and update the IPv4 checksum and,
+/// in the first fragment of a TCP or UDP packet, the L4 checksum, which
+/// covers the address through the pseudo header.
+fn rewrite_addr(ip: &mut [u8], addr_offset: usize, addr: Ipv4Addr, partial: bool) -> bool {
+    let ihl = ((ip[0] & 0x0f) as usize) * 4;
+    if ihl < 20 || ip.len() < ihl {
+        return false;
+    }
+    let old_addr: [u8; 4] = ip[addr_offset..addr_offset + 4].try_into().unwrap();
+    let new_addr = addr.octets();
+
+    let check = read_u16(ip, 10);
+    write_u16(ip, 10, update_checksum(check, &old_addr, &new_addr));
+
+    let first_fragment = read_u16(ip, 6) & 0x1fff == 0;
+    let protocol = ip[9];
+    let csum_at = match protocol {
+        PROTO_TCP => Some(ihl + 16),
+        PROTO_UDP => Some(ihl + 6),
+        _ => None,
+    };
+    if let Some(csum_at) = csum_at
+        && first_fragment
+        && ip.len() >= csum_at + 2
+    {
+        let check = read_u16(ip, csum_at);
+        if partial {
+            write_u16(ip, csum_at, update_sum(check, &old_addr, &new_addr));
+        } else if !(protocol == PROTO_UDP && check == 0) {
+            let check = update_checksum(check, &old_addr, &new_addr);
+            let check = if protocol == PROTO_UDP && check == 0 {
+                0xffff
+            } else {
+                check
+            };
+            write_u16(ip, csum_at, check);
+        }
+    }
+
+    ip[addr_offset..addr_offset + 4].copy_from_slice(&new_addr);
+    true
+}
+
+/// Internet checksum `check` after the 16-bit words `old` became `new`
+/// (RFC 1624).
+fn update_checksum(check: u16, old: &[u8], new: &[u8]) -> u16 {
+    !update_sum(!check, old, new)
+}
+
+/// The same for a ones' complement sum that isn't inverted yet.
+fn update_sum(sum: u16, old: &[u8], new: &[u8]) -> u16 {
+    let mut acc = sum as u32;
+    for word in old.chunks_exact(2) {
+        acc += !u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    for word in new.chunks_exact(2) {
+        acc += u16::from_be_bytes([word[0], word[1]]) as u32;
+    }
+    while acc > 0xffff {
+        acc = (acc & 0xffff) + (acc >> 16);
+    }
+    acc as u16
+}
+
+fn read_u16(buf: &[u8], at: usize) -> u16 {
+    u16::from_be_bytes([buf[at], buf[at + 1]])
+}
+
+fn write_u16(buf: &mut [u8], at: usize, value: u16) {
+    buf[at..at + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: Ipv4Addr = Ipv4Addr::new(203, 0, 113, 10);
+    const REMOTE: Ipv4Addr = Ipv4Addr::new(198, 51, 100, 7);
+    const VM: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 5);
+
+    fn sum(data: &[u8]) -> u32 {
+        data.chunks(2)
+            .map(|w| u16::from_be_bytes([w[0], *w.get(1).unwrap_or(&0)]) as u32)
+            .sum()
+    }
+
+    fn fold(mut acc: u32) -> u16 {
+        while acc > 0xffff {
+            acc = (acc & 0xffff) + (acc >> 16);
+        }
+        acc as u16
+    }
+
+    fn pseudo_header_sum(ip: &[u8]) -> u32 {
+        let l4_len = (ip.len() - 20) as u32;
+        sum(&ip[12..20]) + ip[9] as u32 + l4_len
+    }
+
+    /// TCP packet with valid checksums
+    fn tcp(src: (Ipv4Addr, u16), dst: (Ipv4Addr, u16)) -> Vec<u8> {
+        let mut ip = vec![0u8; 20 + 20 + 4];
+        ip[0] = 0x45;
+        let len = ip.len() as u16;
+        ip[2..4].copy_from_slice(&len.to_be_bytes());
+        ip[8] = 64;
+        ip[9] = PROTO_TCP;
+        ip[12..16].copy_from_slice(&src.0.octets());
+        ip[16..20].copy_from_slice(&dst.0.octets());
+        ip[20..22].copy_from_slice(&src.1.to_be_bytes());
+        ip[22..24].copy_from_slice(&dst.1.to_be_bytes());
+        ip[32] = 0x50;
+        ip[40..44].copy_from_slice(b"ping");
+        let check = !fold(sum(&ip[..20]));
+        write_u16(&mut ip, 10, check);
+        let check = !fold(pseudo_header_sum(&ip) + sum(&ip[20..]));
+        write_u16(&mut ip, 36, check);
+        ip
+    }
+
+    fn checksums_valid(ip: &[u8]) -> bool {
+        fold(sum(&ip[..20])) == 0xffff && fold(pseudo_header_sum(ip) + sum(&ip[20..])) == 0xffff
+    }
+
+    fn forward(target: ReactorId, reply_only: bool) -> PortForward {
+        PortForward {
+            protocol: PROTO_TCP,
+            host_ip: HOST,
+            host_port: 8080,
+            vm_ip: VM,
+            vm_port: 80,
+            target,
+            reply_only,
+        }
+    }
+
+    #[test]
+    fn forwards_and_translates_replies() {
+        let target = ReactorId::new();
+        let now = Instant::now();
+        let mut nat = PortForwarder::new();
+        nat.set_forwards(vec![forward(target, false)]);
+
+        let mut request = tcp((REMOTE, 40000), (HOST, 8080));
+        assert_eq!(nat.ingress(&mut request, false, now), Some(target));
+        assert_eq!(request[16..20], VM.octets());
+        assert_eq!(read_u16(&request, 22), 80);
+        assert!(checksums_valid(&request));
+
+        let mut reply = tcp((VM, 80), (REMOTE, 40000));
+        assert_eq!(
+            nat.egress(&mut reply, false, target, now),
+            Egress::Translated
+        );
+        assert_eq!(reply[12..16], HOST.octets());
+        assert_eq!(read_u16(&reply, 20), 8080);
+        assert!(checksums_valid(&reply));
+
+        // Other ports and other traffic of the VM pass as they are
+        let mut other = tcp((REMOTE, 40000), (HOST, 22));
+        assert_eq!(nat.ingress(&mut other, false, now), None);
+        let mut outgoing = tcp((VM, 50000), (REMOTE, 443));
+        assert_eq!(
+            nat.egress(&mut outgoing, false, target, now),
+            Egress::Untouched
+        );
+        assert_eq!(outgoing, tcp((VM, 50000), (REMOTE, 443)));
+    }
+
+    #[test]
+    fn private_nics_only_send_replies() {
+        let target = ReactorId::new();
+        let now = Instant::now();
+        let mut nat = PortForwarder::new();
+        nat.set_forwards(vec![forward(target, true)]);
+
+        let mut outgoing = tcp((VM, 50000), (REMOTE, 443));
+        assert_eq!(nat.egress(&mut outgoing, false, target, now), Egress::Drop);
+
+        let mut request = tcp((REMOTE, 40000), (HOST, 8080));
+        nat.ingress(&mut request, false, now);
+        let mut reply = tcp((VM, 80), (REMOTE, 40000));
+        assert_eq!(
+            nat.egress(&mut reply, false, target, now),
+            Egress::Translated
+        );
+
+        // Expired connections are no longer answered
+        let later = now + Duration::from_secs(3600);
+        let mut late = tcp((VM, 80), (REMOTE, 40000));
+        assert_eq!(nat.egress(&mut late, false, target, later), Egress::Drop);
+    }
+
+    #[test]
+    fn updates_partial_checksums() {
+        let target = ReactorId::new();
+        let mut nat = PortForwarder::new();
+        nat.set_forwards(vec![forward(target, false)]);
+
+        // Offloaded: the field holds the pseudo header sum only
+        let mut request = tcp((REMOTE, 40000), (HOST, 8080));
+        let partial = fold(pseudo_header_sum(&request));
+        write_u16(&mut request, 36, partial);
+
+        nat.ingress(&mut request, true, Instant::now());
+        assert_eq!(read_u16(&request, 36), fold(pseudo_header_sum(&request)));
+        assert_eq!(fold(sum(&request[..20])), 0xffff);
+    }
+
+    #[test]
+    fn floating_ips_translate_both_ways() {
+        let target = ReactorId::new();
+        let now = Instant::now();
+        let mut nat = PortForwarder::new();
+        nat.set_floating_ips(vec![FloatingIp {
+            address: HOST,
+            vm_ip: VM,
+            target,
+        }]);
+
+        let mut request = tcp((REMOTE, 40000), (HOST, 22));
+        assert_eq!(nat.ingress(&mut request, false, now), Some(target));
+        assert_eq!(request, tcp((REMOTE, 40000), (VM, 22)));
+
+        // Connections the VM opens leave from the floating IP
+        let mut outgoing = tcp((VM, 50000), (REMOTE, 443));
+        assert_eq!(
+            nat.egress(&mut outgoing, false, target, now),
+            Egress::Translated
+        );
+        assert_eq!(outgoing, tcp((HOST, 50000), (REMOTE, 443)));
+
+        // Any protocol, and later fragments too
+        let mut icmp = tcp((REMOTE, 0), (HOST, 0));
+        icmp[9] = 1;
+        icmp[6] = 0x00;
+        icmp[7] = 0x10;
+        let check = !fold(sum(&icmp[..10]) + sum(&icmp[12..20]));
+        write_u16(&mut icmp, 10, check);
+        let payload = icmp[20..].to_vec();
+        assert_eq!(nat.ingress(&mut icmp, false, now), Some(target));
+        assert_eq!(icmp[16..20], VM.octets());
+        assert_eq!(fold(sum(&icmp[..20])), 0xffff);
+        assert_eq!(icmp[20..], payload[..]);
+
+        // Other NICs and addresses are left alone
+        let mut other = tcp((VM, 50000), (REMOTE, 443));
+        assert_eq!(
+            nat.egress(&mut other, false, ReactorId::new(), now),
+            Egress::Untouched
+        );
+        let mut elsewhere = tcp((REMOTE, 40000), (Ipv4Addr::new(203, 0, 113, 11), 22));
+        assert_eq!(nat.ingress(&mut elsewhere, false, now), None);
+    }
+
+    #[test]
+    fn port_forwards_take_precedence_over_floating_ips() {
+        let forwarded = ReactorId::new();
+        let floating = ReactorId::new();
+        let now = Instant::now();
+        let mut nat = PortForwarder::new();
+        nat.set_forwards(vec![forward(forwarded, true)]);
+        nat.set_floating_ips(vec![FloatingIp {
+            address: HOST,
+            vm_ip: Ipv4Addr::new(10, 0, 0, 6),
+            target: floating,
+        }]);
+
+        let mut request = tcp((REMOTE, 40000), (HOST, 8080));
+        assert_eq!(nat.ingress(&mut request, false, now), Some(forwarded));
+        let mut request = tcp((REMOTE, 40000), (HOST, 8081));
+        assert_eq!(nat.ingress(&mut request, false, now), Some(floating));
+
+        // A private NIC with a floating IP may send anything
+        let mut outgoing = tcp((Ipv4Addr::new(10, 0, 0, 6), 50000), (REMOTE, 443));
+        assert_eq!(
+            nat.egress(&mut outgoing, false, floating, now),
+            Egress::Translated
+        );
+        let mut outgoing = tcp((VM, 50000), (REMOTE, 443));
+        assert_eq!(
+            nat.egress(&mut outgoing, false, forwarded, now),
+            Egress::Drop
+        );
+    }
+
+    #[test]
+    fn removing_a_forward_ends_its_connections() {
+        let target = ReactorId::new();
+        let now = Instant::now();
+        let mut nat = PortForwarder::new();
+        nat.set_forwards(vec![forward(target, false)]);
+
+        let mut request = tcp((REMOTE, 40000), (HOST, 8080));
+        nat.ingress(&mut request, false, now);
+        nat.set_forwards(Vec::new());
+        assert!(nat.is_empty());
+
+        let mut reply = tcp((VM, 80), (REMOTE, 40000));
+        assert_eq!(
+            nat.egress(&mut reply, false, target, now),
+            Egress::Untouched
+        );
+    }
+}
@@ -0,0 +1,420 @@
+//! Path MTU handling for packets routed out of vhost-user interfaces.
+//!
+//! The reactor forwards frames as they are and never fragments. When a
+//! routed packet is larger than the NIC's MTU, the gateway answers with
+//! ICMP Fragmentation Needed (IPv4 with DF set) or ICMPv6 Packet Too Big,
+//! so path MTU discovery in the VM settles on a size that fits - e.g. the
+//! smaller MTU of an overlay network. IPv4 packets without DF are forwarded
+//! unchanged and left to the kernel to fragment.
+//!
+//! With TSO the guest hands over super-frames; these are checked by the
+//! size of the segments they will be cut into, not their total length.
+
+use super::icmpv6::compute_icmpv6_checksum;
+use super::{GATEWAY_IPV4_LINK_LOCAL, GATEWAY_IPV6_LINK_LOCAL, GATEWAY_MAC, NicConfig};
+use smoltcp::wire::{
+    EthernetAddress, EthernetFrame, EthernetProtocol, EthernetRepr, Icmpv4DstUnreachable,
+    Icmpv4Message, Icmpv4Packet, Icmpv6Message, IpProtocol, Ipv4Address, Ipv4Packet, Ipv4Repr,
+    Ipv6Address, Ipv6Packet, Ipv6Repr,
+};
+use tracing::debug;
+
+/// Default MTU of routed traffic.
+pub const DEFAULT_MTU: u16 = 1500;
+
+/// Smallest configurable MTU: the IPv6 minimum link MTU (RFC 8200).
+pub const MIN_MTU: u16 = 1280;
+
+/// Ethernet header size
+const ETHERNET_HEADER_SIZE: usize = 14;
+
+/// IPv4 header size (without options)
+const IPV4_HEADER_SIZE: usize = 20;
+
+/// IPv6 header size
+const IPV6_HEADER_SIZE: usize = 40;
+
+/// ICMP/ICMPv6 error header: type, code, checksum, 4 bytes of MTU field
+const ICMP_ERROR_HEADER_SIZE: usize = 8;
+
+/// ICMPv4 errors stay within 576 bytes (RFC 1812 4.3.2.3).
+const ICMPV4_ERROR_MAX_LEN: usize = 576;
+
+/// ICMPv6 errors stay within the minimum IPv6 MTU (RFC 4443 2.4).
+const ICMPV6_ERROR_MAX_LEN: usize = 1280;
+
+/// virtio_net_hdr gso_type for non-GSO packets
+const VIRTIO_NET_HDR_GSO_NONE: u8 = 0;
+
+/// Check a frame from the VM against the NIC's MTU.
+///
+/// `ethernet_frame` holds the leading bytes of the frame (as much as should
+/// be quoted in an error), `frame_len` the full Ethernet frame length.
+/// Returns the ICMP error to inject back into the VM if the packet must not
+/// be forwarded, `None` if it fits or may be forwarded anyway.
+pub fn check_packet_size(
+    nic_config: &NicConfig,
+    virtio_hdr: &[u8],
+    ethernet_frame: &[u8],
+    frame_len: usize,
+) -> Option<Vec<u8>> {
+    let ip_len = wire_frame_len(virtio_hdr, frame_len)?.checked_sub(ETHERNET_HEADER_SIZE)?;
+    if ip_len <= nic_config.mtu as usize {
+        return None;
+    }
+
+    let eth_frame = EthernetFrame::new_checked(ethernet_frame).ok()?;
+    let reply = match eth_frame.ethertype() {
+        EthernetProtocol::Ipv4 => build_frag_needed(
+            virtio_hdr,
+            eth_frame.src_addr(),
+            eth_frame.payload(),
+            nic_config.mtu,
+        ),
+        EthernetProtocol::Ipv6 => build_packet_too_big(
+            virtio_hdr,
+            eth_frame.src_addr(),
+            eth_frame.payload(),
+            nic_config.mtu,
+        ),
+        _ => None,
+    }?;
+
+    debug!(
+        len = ip_len,
+        mtu = nic_config.mtu,
+        ethertype = ?eth_frame.ethertype(),
+        "Packet exceeds MTU, replying with ICMP too big"
+    );
+    Some(reply)
+}
+
+/// Length of the largest frame that goes on the wire for this packet.
+///
+/// For GSO packets this is one segment: the headers (`hdr_len`, which
+/// includes Ethernet) plus `gso_size` bytes of payload.
+fn wire_frame_len(virtio_hdr: &[u8], frame_len: usize) -> Option<usize> {
+    if virtio_hdr.len() < 6 || virtio_hdr[1] == VIRTIO_NET_HDR_GSO_NONE {
+        return Some(frame_len);
+    }
+    let hdr_len = u16::from_le_bytes([virtio_hdr[2], virtio_hdr[3]]) as usize;
+    let gso_size = u16::from_le_bytes([virtio_hdr[4], virtio_hdr[5]]) as usize;
+    if hdr_len == 0 || gso_size == 0 {
+        // Guest didn't fill in the segment layout; can't tell
+        return None;
+    }
+    Some((hdr_len + gso_size).min(frame_len))
+}
+
+/// Build an ICMP Destination Unreachable / Fragmentation Needed reply
+/// (RFC 1191) for an IPv4 packet with DF set.
+fn build_frag_needed(
+    virtio_hdr: &[u8],
+    dst_mac: EthernetAddress,
+    ip_data: &[u8],
+    mtu: u16,
+) -> Option<Vec<u8>> {
+    // Peeked data is usually shorter than the packet, so parse unchecked
+    if ip_data.len() < IPV4_HEADER_SIZE {
+        return None;
+    }
+    let ipv4 = Ipv4Packet::new_unchecked(ip_data);
+    let header_len = ipv4.header_len() as usize;
+    if ipv4.version() != 4 || header_len < IPV4_HEADER_SIZE || ip_data.len() < header_len {
+        return None;
+    }
+    if !ipv4.dont_frag() || ipv4.frag_offset() != 0 {
+        return None;
+    }
+    if ipv4.next_header() == IpProtocol::Icmp && is_icmpv4_error(&ip_data[header_len..]) {
+        // Never answer an ICMP error with another (RFC 1122 3.2.2)
+        return None;
+    }
+
+    let quote_len = ip_data
+        .len()
+        .min(ICMPV4_ERROR_MAX_LEN - IPV4_HEADER_SIZE - ICMP_ERROR_HEADER_SIZE);
+    let icmp_len = ICMP_ERROR_HEADER_SIZE + quote_len;
+    let ip_repr = Ipv4Repr {
+        src_addr: Ipv4Address::from_bytes(&GATEWAY_IPV4_LINK_LOCAL.octets()),
+        dst_addr: ipv4.src_addr(),
+        next_header: IpProtocol::Icmp,
+        payload_len: icmp_len,
+        hop_limit: 64,
+    };
+
+    let virtio_hdr_size = virtio_hdr.len();
+    let total_len = virtio_hdr_size + ETHERNET_HEADER_SIZE + IPV4_HEADER_SIZE + icmp_len;
+    let mut packet = vec![0u8; total_len];
+
+    let mut eth_frame = EthernetFrame::new_unchecked(&mut packet[virtio_hdr_size..]);
+    EthernetRepr {
+        src_addr: EthernetAddress(GATEWAY_MAC),
+        dst_addr: dst_mac,
+        ethertype: EthernetProtocol::Ipv4,
+    }
+    .emit(&mut eth_frame);
+
+    let mut ip_packet = Ipv4Packet::new_unchecked(eth_frame.payload_mut());
+    ip_repr.emit(
+        &mut ip_packet,
+        &smoltcp::phy::ChecksumCapabilities::default(),
+    );
+
+    let icmp_data = ip_packet.payload_mut();
+    icmp_data[0] = Icmpv4Message::DstUnreachable.into();
+    icmp_data[1] = Icmpv4DstUnreachable::FragRequired.into();
+    // Bytes 4-5 unused, 6-7 next-hop MTU
+    icmp_data[6..8].copy_from_slice(&mtu.to_be_bytes());
+    icmp_data[ICMP_ERROR_HEADER_SIZE..].copy_from_slice(&ip_data[..quote_len]);
+    Icmpv4Packet::new_unchecked(icmp_data).fill_checksum();
+
+    Some(packet)
+}
+
+/// Build an ICMPv6 Packet Too Big reply (RFC 4443 3.2).
+fn build_packet_too_big(
+    virtio_hdr: &[u8],
+    dst_mac: EthernetAddress,
+    ip_data: &[u8],
+    mtu: u16,
+) -> Option<Vec<u8>> {
+    if ip_data.len() < IPV6_HEADER_SIZE {
+        return None;
+    }
+    let ipv6 = Ipv6Packet::new_unchecked(ip_data);
+    if ipv6.version() != 6 {
+        return None;
+    }
+    let src_addr = ipv6.src_addr();
+    if src_addr.is_unspecified() || src_addr.is_multicast() {
+        return None;
+    }
+    if ipv6.next_header() == IpProtocol::Icmpv6
+        && ip_data
+            .get(IPV6_HEADER_SIZE)
+            .is_some_and(|msg_type| *msg_type < 128)
+    {
+        // ICMPv6 error messages (types 0-127) never get an error in reply
+        return None;
+    }
+
+    let quote_len = ip_data
+        .len()
+        .min(ICMPV6_ERROR_MAX_LEN - IPV6_HEADER_SIZE - ICMP_ERROR_HEADER_SIZE);
+    let icmp_len = ICMP_ERROR_HEADER_SIZE + quote_len;
+    let gateway_ll = Ipv6Address::from_bytes(&GATEWAY_IPV6_LINK_LOCAL.octets());
+    let ip_repr = Ipv6Repr {
+        src_addr: gateway_ll,
+        dst_addr: src_addr,
+        next_header: IpProtocol::Icmpv6,
+        payload_len: icmp_len,
+        hop_limit: 64,
+    };
+
+    let virtio_hdr_size = virtio_hdr.len();
+    let total_len = virtio_hdr_size + ETHERNET_HEADER_SIZE + IPV6_HEADER_SIZE + icmp_len;
+    let mut packet = vec![0u8; total_len];
+
+    let mut eth_frame = EthernetFrame::new_unchecked(&mut packet[virtio_hdr_size..]);
+    EthernetRepr {
+        src_addr: EthernetAddress(GATEWAY_MAC),
+        dst_addr: dst_mac,
+        ethertype: EthernetProtocol::Ipv6,
+    }
+    .emit(&mut eth_frame);
+
+    let mut ip_packet = Ipv6Packet::new_unchecked(eth_frame.payload_mut());
+    ip_repr.emit(&mut ip_packet);
+
+    let icmp_data = ip_packet.payload_mut();
+    icmp_data[0] = Icmpv6Message::PktTooBig.into();
+    icmp_data[1] = 0;
+    icmp_data[4..8].copy_from_slice(&(mtu as u32).to_be_bytes());
+    icmp_data[ICMP_ERROR_HEADER_SIZE..].copy_from_slice(&ip_data[..quote_len]);
+    let checksum = compute_icmpv6_checksum(&gateway_ll, &src_addr, icmp_data);
+    icmp_data[2..4].copy_from_slice(&checksum.to_be_bytes());
+
+    Some(packet)
+}
+
+/// Whether `icmp_data` starts with an ICMPv4 error message type.
+fn is_icmpv4_error(icmp_data: &[u8]) -> bool {
+    matches!(
+        icmp_data.first().map(|t| Icmpv4Message::from(*t)),
+        Some(
+            Icmpv4Message::DstUnreachable
+                | Icmpv4Message::Redirect
+                | Icmpv4Message::TimeExceeded
+                | Icmpv4Message::ParamProblem
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smoltcp::wire::{Icmpv6Packet, IpAddress};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    const VM_MAC: [u8; 6] = [0x52, 0x54, 0x00, 0x12, 0x34, 0x56];
+
+    fn make_test_config(mtu: u16) -> NicConfig {
+        NicConfig {
+            mac: VM_MAC,
+            ipv4_address: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            ipv4_gateway: Some(Ipv4Addr::new(10, 0, 0, 1)),
+            ipv4_prefix_len: 24,
+            ipv6_address: Some(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)),
+            ipv6_gateway: Some(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 1)),
+            ipv6_prefix_len: 128,
+            ipv6_delegated_prefix: None,
+            dns_servers: vec![],
+            boot_server: None,
+            boot_file: None,
+            mtu,
+            tx_burst: crate::reactor::DEFAULT_TX_BURST,
+            leases: vec![],
+            dns_zone: None,
+        }
+    }
+
+    /// Build an Ethernet frame carrying a UDP/IPv4 packet of `ip_len` bytes.
+    fn ipv4_frame(ip_len: usize, dont_frag: bool) -> Vec<u8> {
+        let mut frame = vec![0u8; ETHERNET_HEADER_SIZE + ip_len];
+        let mut eth = EthernetFrame::new_unchecked(&mut frame);
+        EthernetRepr {
+            src_addr: EthernetAddress(VM_MAC),
+            dst_addr: EthernetAddress(GATEWAY_MAC),
+            ethertype: EthernetProtocol::Ipv4,
+        }
+        .emit(&mut eth);
+        let mut ip = Ipv4Packet::new_unchecked(eth.payload_mut());
+        Ipv4Repr {
+            src_addr: Ipv4Address::new(10, 0, 0, 2),
+            dst_addr: Ipv4Address::new(192, 0, 2, 1),
+            next_header: IpProtocol::Udp,
+            payload_len: ip_len - IPV4_HEADER_SIZE,
+            hop_limit: 64,
+        }
+        .emit(&mut ip, &smoltcp::phy::ChecksumCapabilities::default());
+        ip.set_dont_frag(dont_frag);
+        ip.fill_checksum();
+        frame
+    }
+
+    /// Build an Ethernet frame carrying a UDP/IPv6 packet of `ip_len` bytes.
+    fn ipv6_frame(ip_len: usize) -> Vec<u8> {
+        let mut frame = vec![0u8; ETHERNET_HEADER_SIZE + ip_len];
+        let mut eth = EthernetFrame::new_unchecked(&mut frame);
+        EthernetRepr {
+            src_addr: EthernetAddress(VM_MAC),
+            dst_addr: EthernetAddress(GATEWAY_MAC),
+            ethertype: EthernetProtocol::Ipv6,
+        }
+        .emit(&mut eth);
+        let mut ip = Ipv6Packet::new_unchecked(eth.payload_mut());
+        Ipv6Repr {
+            src_addr: Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2),
+            dst_addr: Ipv6Address::new(0x2001, 0xdb8, 1, 0, 0, 0, 0, 1),
+            next_header: IpProtocol::Udp,
+            payload_len: ip_len - IPV6_HEADER_SIZE,
+            hop_limit: 64,
+        }
+        .emit(&mut ip);
+        frame
+    }
+
+    #[test]
+    fn test_fitting_packet_is_forwarded() {
+        let config = make_test_config(1400);
+        let virtio_hdr = [0u8; 12];
+        let frame = ipv4_frame(1400, true);
+
+        assert!(check_packet_size(&config, &virtio_hdr, &frame, frame.len()).is_none());
+    }
+
+    #[test]
+    fn test_frag_needed_for_df_packet() {
+        let config = make_test_config(1400);
+        let virtio_hdr = [0u8; 12];
+        let frame = ipv4_frame(1500, true);
+
+        // Only the peeked prefix of the frame is available to the check
+        let reply = check_packet_size(&config, &virtio_hdr, &frame[..588], frame.len())
+            .expect("DF packet over the MTU should be rejected");
+
+        let eth = EthernetFrame::new_checked(&reply[12..]).unwrap();
+        assert_eq!(eth.dst_addr(), EthernetAddress(VM_MAC));
+        assert_eq!(eth.src_addr(), EthernetAddress(GATEWAY_MAC));
+
+        let ip = Ipv4Packet::new_checked(eth.payload()).unwrap();
+        assert!(ip.verify_checksum());
+        assert_eq!(ip.src_addr(), Ipv4Address::new(169, 254, 0, 1));
+        assert_eq!(ip.dst_addr(), Ipv4Address::new(10, 0, 0, 2));
+        assert!(ip.total_len() as usize <= ICMPV4_ERROR_MAX_LEN);
+
+        let icmp = Icmpv4Packet::new_checked(ip.payload()).unwrap();
+        assert!(icmp.verify_checksum());
+        assert_eq!(icmp.msg_type(), Icmpv4Message::DstUnreachable);
+        assert_eq!(icmp.msg_code(), 4);
+        assert_eq!(&ip.payload()[6..8], &1400u16.to_be_bytes());
+        // Quotes the start of the original packet
+        let quoted = &ip.payload()[ICMP_ERROR_HEADER_SIZE..];
+        assert_eq!(quoted, &frame[ETHERNET_HEADER_SIZE..][..quoted.len()]);
+    }
+
+    #[test]
+    fn test_packet_without_df_is_forwarded() {
+        let config = make_test_config(1400);
+        let virtio_hdr = [0u8; 12];
+        let frame = ipv4_frame(1500, false);
+
+        assert!(check_packet_size(&config, &virtio_hdr, &frame, frame.len()).is_none());
+    }
+
+    #[test]
+    fn test_packet_too_big_for_ipv6() {
+        let config = make_test_config(1400);
+        let virtio_hdr = [0u8; 12];
+        let frame = ipv6_frame(1500);
+
+        let reply = check_packet_size(&config, &virtio_hdr, &frame[..588], frame.len())
+            .expect("IPv6 packet over the MTU should be rejected");
+
+        let eth = EthernetFrame::new_checked(&reply[12..]).unwrap();
+        assert_eq!(eth.dst_addr(), EthernetAddress(VM_MAC));
+
+        let ip = Ipv6Packet::new_checked(eth.payload()).unwrap();
+        assert_eq!(ip.src_addr(), Ipv6Address::new(0xfe80, 0, 0, 0, 0, 0, 0, 1));
+        assert_eq!(
+            ip.dst_addr(),
+            Ipv6Address::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 2)
+        );
+
+        let icmp = Icmpv6Packet::new_checked(ip.payload()).unwrap();
+        assert_eq!(icmp.msg_type(), Icmpv6Message::PktTooBig);
+        assert_eq!(icmp.pkt_too_big_mtu(), 1400);
+        assert!(icmp.verify_checksum(
+            &IpAddress::Ipv6(ip.src_addr()),
+            &IpAddress::Ipv6(ip.dst_addr())
+        ));
+    }
+
+    #[test]
+    fn test_gso_packet_checked_by_segment_size() {
+        let config = make_test_config(1400);
+        let frame = ipv4_frame(9000, true);
+
+        // TSO super-frame: 54 bytes of headers, 1300 byte segments
+        let mut virtio_hdr = [0u8; 12];
+        virtio_hdr[1] = 1; // VIRTIO_NET_HDR_GSO_TCPV4
+        virtio_hdr[2..4].copy_from_slice(&54u16.to_le_bytes());
+        virtio_hdr[4..6].copy_from_slice(&1300u16.to_le_bytes());
+        assert!(check_packet_size(&config, &virtio_hdr, &frame[..588], frame.len()).is_none());
+
+        // Segments of 1400 bytes don't fit anymore
+        virtio_hdr[4..6].copy_from_slice(&1400u16.to_le_bytes());
+        assert!(check_packet_size(&config, &virtio_hdr, &frame[..588], frame.len()).is_some());
+    }
+}
@@ -5,6 +5,7 @@
 
 use crate::inter_reactor::{CompletionNotify, PacketRef, ReactorId};
 use std::collections::HashMap;
+use std::net::Ipv4Addr;
 use std::os::unix::io::RawFd;
 use std::sync::RwLock;
 use std::sync::mpsc::Sender;
@@ -23,6 +24,8 @@ pub enum InterfaceType {
         /// UUID identifying the vhost device.
         device_id: Uuid,
     },
+    /// Overlay endpoint tunneling packets to other hosts.
+    Overlay,
 }
 
 impl InterfaceType {
@@ -30,14 +33,14 @@ impl InterfaceType {
     pub fn tun_if_index(&self) -> Option<u32> {
         match self {
             InterfaceType::Tun { if_index } => Some(*if_index),
-            InterfaceType::Vhost { .. } => None,
+            InterfaceType::Vhost { .. } | InterfaceType::Overlay => None,
         }
     }
 
     /// Get the vhost device ID if this is a vhost interface.
     pub fn vhost_device_id(&self) -> Option<Uuid> {
         match self {
-            InterfaceType::Tun { .. } => None,
+            InterfaceType::Tun { .. } | InterfaceType::Overlay => None,
             InterfaceType::Vhost { device_id } => Some(*device_id),
         }
     }
@@ -58,6 +61,8 @@ pub struct ReactorInfo {
     pub interface_type: InterfaceType,
     /// MAC address for vhost interfaces (used for Ethernet header construction).
     pub mac_address: Option<[u8; 6]>,
+    /// MACs behind the interface with a static DHCP lease, by leased address.
+    pub lease_macs: HashMap<Ipv4Addr, [u8; 6]>,
 }
 
 impl ReactorInfo {
@@ -76,6 +81,7 @@ impl ReactorInfo {
             completion_tx,
             interface_type,
             mac_address: None,
+            lease_macs: HashMap::new(),
         }
     }
 
@@ -95,6 +101,7 @@ impl ReactorInfo {
             completion_tx,
             interface_type,
             mac_address: Some(mac_address),
+            lease_macs: HashMap::new(),
         }
     }
 
@@ -169,6 +176,7 @@ impl ReactorRegistry {
             InterfaceType::Vhost { device_id } => {
                 self.vhost_index.write().unwrap().insert(*device_id, id);
             }
+            InterfaceType::Overlay => {}
         }
 
         self.reactors.write().unwrap().insert(id, info)
@@ -188,6 +196,7 @@ impl ReactorRegistry {
             InterfaceType::Vhost { device_id } => {
                 self.vhost_index.write().unwrap().remove(device_id);
             }
+            InterfaceType::Overlay => {}
         }
 
         Some(info)
@@ -279,6 +288,28 @@ impl ReactorRegistry {
         reactors.get(reactor_id).and_then(|info| info.mac_address)
     }
 
+    /// Get the MAC address to send a packet for `dst` to through a reactor:
+    /// the MAC holding a static DHCP lease for `dst` behind the reactor's
+    /// interface, or the interface's own.
+    pub fn get_mac_for_destination(
+        &self,
+        reactor_id: &ReactorId,
+        dst: Option<Ipv4Addr>,
+    ) -> Option<[u8; 6]> {
+        let reactors = self.reactors.read().unwrap();
+        let info = reactors.get(reactor_id)?;
+        dst.and_then(|dst| info.lease_macs.get(&dst).copied())
+            .or(info.mac_address)
+    }
+
+    /// Replace the static DHCP leases behind a reactor's interface.
+    pub fn set_lease_macs(&self, reactor_id: &ReactorId, leases: &[([u8; 6], Ipv4Addr)]) {
+        let mut reactors = self.reactors.write().unwrap();
+        if let Some(info) = reactors.get_mut(reactor_id) {
+            info.lease_macs = leases.iter().map(|(mac, ip)| (*ip, *mac)).collect();
+        }
+    }
+
     /// Get the number of registered reactors.
     pub fn len(&self) -> usize {
         self.reactors.read().unwrap().len()
@@ -377,6 +408,45 @@ mod tests {
         assert!(ids.contains(&id2));
     }
 
+    #[test]
+    fn test_registry_lease_macs() {
+        let registry = ReactorRegistry::new();
+        let id = ReactorId::new();
+        let (packet_tx, _packet_rx) = mpsc::channel();
+        let (completion_tx, _completion_rx) = mpsc::channel();
+        let nic_mac = [0x02, 0, 0, 0, 0, 0x01];
+        registry.register(ReactorInfo::with_mac(
+            id,
+            -1,
+            packet_tx,
+            completion_tx,
+            InterfaceType::Vhost {
+                device_id: Uuid::new_v4(),
+            },
+            nic_mac,
+        ));
+
+        let lease_mac = [0x02, 0, 0, 0, 0x01, 0x01];
+        let leased = Ipv4Addr::new(10, 0, 0, 20);
+        registry.set_lease_macs(&id, &[(lease_mac, leased)]);
+
+        assert_eq!(
+            registry.get_mac_for_destination(&id, Some(leased)),
+            Some(lease_mac)
+        );
+        assert_eq!(
+            registry.get_mac_for_destination(&id, Some(Ipv4Addr::new(10, 0, 0, 2))),
+            Some(nic_mac)
+        );
+        assert_eq!(registry.get_mac_for_destination(&id, None), Some(nic_mac));
+
+        registry.set_lease_macs(&id, &[]);
+        assert_eq!(
+            registry.get_mac_for_destination(&id, Some(leased)),
+            Some(nic_mac)
+        );
+    }
+
     #[test]
     fn test_interface_type_accessors() {
         let tun = InterfaceType::Tun { if_index: 5 };
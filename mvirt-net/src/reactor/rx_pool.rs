@@ -0,0 +1,169 @@
+//! Autoscaling of a reactor's TUN RX buffer pool.
+//!
+//! Packets read from the TUN device stay in their RX buffer until the
+//! target reactor has copied them into the guest. Under bursty load all
+//! buffers can be held at once; the reactor then stops reading and the
+//! kernel drops packets at the TUN queue. Instead of sizing every pool for
+//! the worst case, the pool starts small and grows while occupancy stays
+//! high, up to a configured cap. It never shrinks: the memory stays
+//! mapped for zero-copy references held by other reactors.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Default cap of a reactor's RX pool, in buffers.
+pub const DEFAULT_RX_BUFFER_MAX: usize = 1024;
+
+/// Occupancy at which the pool counts as under pressure.
+const HIGH_OCCUPANCY_PERCENT: usize = 90;
+
+/// How long the pool has to stay under pressure before it grows.
+const SUSTAINED: Duration = Duration::from_millis(50);
+
+/// Smallest growth step, in buffers.
+const MIN_GROW_STEP: usize = 32;
+
+/// Pool statistics, readable while the reactor runs.
+#[derive(Debug, Default)]
+pub struct RxPoolStats {
+    capacity: AtomicU64,
+    max: AtomicU64,
+    held: AtomicU64,
+    high_watermark: AtomicU64,
+    grow_events: AtomicU64,
+    exhausted: AtomicU64,
+}
+
+/// A point-in-time copy of [`RxPoolStats`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RxPoolSnapshot {
+    /// Buffers in the pool
+    pub capacity: u64,
+    /// Cap the pool may grow to
+    pub max: u64,
+    /// Buffers currently holding packets
+    pub held: u64,
+    /// Most buffers ever held at once
+    pub high_watermark: u64,
+    /// Times the pool grew
+    pub grow_events: u64,
+    /// Times every buffer was held and reads stalled
+    pub exhausted: u64,
+}
+
+impl RxPoolStats {
+    pub fn snapshot(&self) -> RxPoolSnapshot {
+        RxPoolSnapshot {
+            capacity: self.capacity.load(Ordering::Relaxed),
+            max: self.max.load(Ordering::Relaxed),
+            held: self.held.load(Ordering::Relaxed),
+            high_watermark: self.high_watermark.load(Ordering::Relaxed),
+            grow_events: self.grow_events.load(Ordering::Relaxed),
+            exhausted: self.exhausted.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Decides when and by how much a reactor's RX pool grows.
+pub struct RxPoolScaler {
+    max: usize,
+    /// Since when occupancy has been high
+    pressure_since: Option<Instant>,
+    /// Whether the last observation had every buffer held
+    was_exhausted: bool,
+    stats: Arc<RxPoolStats>,
+}
+
+impl RxPoolScaler {
+    /// A scaler that doesn't grow the pool until a cap is set.
+    pub fn new(capacity: usize) -> Self {
+        let stats = Arc::new(RxPoolStats::default());
+        stats.capacity.store(capacity as u64, Ordering::Relaxed);
+        stats.max.store(capacity as u64, Ordering::Relaxed);
+        Self {
+            max: capacity,
+            pressure_since: None,
+            was_exhausted: false,
+            stats,
+        }
+    }
+
+    pub fn stats(&self) -> &Arc<RxPoolStats> {
+        &self.stats
+    }
+
+    /// Set the cap the pool may grow to.
+    pub fn set_max(&mut self, max: usize) {
+        self.max = max;
+        self.stats.max.store(max as u64, Ordering::Relaxed);
+    }
+
+    /// Record that `held` of `capacity` buffers hold packets. Returns how
+    /// many buffers to add, 0 to leave the pool as it is.
+    pub fn observe(&mut self, capacity: usize, held: usize, now: Instant) -> usize {
+        self.stats.held.store(held as u64, Ordering::Relaxed);
+        self.stats
+            .high_watermark
+            .fetch_max(held as u64, Ordering::Relaxed);
+
+        let exhausted = held >= capacity;
+        if exhausted && !self.was_exhausted {
+            self.stats.exhausted.fetch_add(1, Ordering::Relaxed);
+        }
+        self.was_exhausted = exhausted;
+
+        if held * 100 < capacity * HIGH_OCCUPANCY_PERCENT {
+            self.pressure_since = None;
+            return 0;
+        }
+        let since = *self.pressure_since.get_or_insert(now);
+        if capacity >= self.max || now.duration_since(since) < SUSTAINED {
+            return 0;
+        }
+        self.pressure_since = None;
+        (capacity / 4).max(MIN_GROW_STEP).min(self.max - capacity)
+    }
+
+    /// Record that the pool grew to `capacity` buffers.
+    pub fn grown(&mut self, capacity: usize) {
+        self.stats
+            .capacity
+            .store(capacity as u64, Ordering::Relaxed);
+        self.stats.grow_events.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grows_on_sustained_pressure_up_to_cap() {
+        let mut scaler = RxPoolScaler::new(256);
+        let t0 = Instant::now();
+
+        // No cap set: never grows
+        assert_eq!(scaler.observe(256, 256, t0), 0);
+        assert_eq!(scaler.observe(256, 256, t0 + SUSTAINED), 0);
+
+        scaler.set_max(300);
+        // Pressure has to last
+        assert_eq!(scaler.observe(256, 250, t0), 0);
+        assert_eq!(scaler.observe(256, 250, t0 + SUSTAINED / 2), 0);
+        // A dip resets it
+        assert_eq!(scaler.observe(256, 10, t0 + SUSTAINED / 2), 0);
+        assert_eq!(scaler.observe(256, 250, t0 + SUSTAINED), 0);
+        // Grows by a quarter, capped
+        assert_eq!(scaler.observe(256, 250, t0 + SUSTAINED * 2), 44);
+        scaler.grown(300);
+        assert_eq!(scaler.observe(300, 300, t0 + SUSTAINED * 4), 0);
+
+        let stats = scaler.stats().snapshot();
+        assert_eq!(stats.capacity, 300);
+        assert_eq!(stats.max, 300);
+        assert_eq!(stats.high_watermark, 300);
+        assert_eq!(stats.grow_events, 1);
+        assert_eq!(stats.exhausted, 2);
+    }
+}
@@ -0,0 +1,187 @@
+//! Source addresses a guest sends from that aren't its NIC's.
+//!
+//! A guest with a static address typed in by hand, or a VM cloned with its
+//! template's MAC still configured, sends from a MAC or IP allocated to
+//! another NIC. The reactor doesn't know the other NICs, so it only notes
+//! the source MACs and IPs of its guest's frames (ARP senders included)
+//! that are neither the NIC's own nor one of its static DHCP leases. The
+//! conflict monitor (see [`crate::conflicts`]) takes them off every NIC's
+//! [`SourceWatch`] and matches them against the allocations of all NICs.
+//!
+//! Unspecified and link-local IPv6 sources are left out: the former is
+//! what DHCP and duplicate address detection send from, the latter is
+//! derived from the MAC.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::Mutex;
+
+use super::NicConfig;
+
+/// Distinct sources kept between two takes; more are not counted
+const MAX_SOURCES: usize = 64;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+
+/// A source address seen from the guest that isn't the NIC's.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ForeignSource {
+    Mac([u8; 6]),
+    Ip(IpAddr),
+}
+
+/// Foreign sources of a NIC's guest and how many frames came from each
+/// since they were last taken.
+#[derive(Debug, Default)]
+pub struct SourceWatch {
+    seen: Mutex<HashMap<ForeignSource, u64>>,
+}
+
+impl SourceWatch {
+    /// A frame from `source`
+    pub fn record(&self, source: ForeignSource) {
+        let mut seen = self.seen.lock().unwrap();
+        if let Some(frames) = seen.get_mut(&source) {
+            *frames += 1;
+        } else if seen.len() < MAX_SOURCES {
+            seen.insert(source, 1);
+        }
+    }
+
+    /// The sources seen since the last take
+    pub fn take(&self) -> HashMap<ForeignSource, u64> {
+        std::mem::take(&mut *self.seen.lock().unwrap())
+    }
+}
+
+/// Source MAC and source IP of a guest's Ethernet frame, each only if it
+/// isn't the NIC's.
+pub fn foreign_sources(nic: &NicConfig, frame: &[u8]) -> [Option<ForeignSource>; 2] {
+    let Some(header) = frame.get(..14) else {
+        return [None, None];
+    };
+    let mac: [u8; 6] = header[6..12].try_into().unwrap();
+    let foreign_mac = (mac != nic.mac && !nic.leases.iter().any(|(m, _)| *m == mac))
+        .then_some(ForeignSource::Mac(mac));
+
+    let payload = &frame[14..];
+    let ip = match u16::from_be_bytes([header[12], header[13]]) {
+        ETHERTYPE_IPV4 => payload
+            .get(12..16)
+            .filter(|_| payload[0] >> 4 == 4)
+            .map(|src| IpAddr::from(<[u8; 4]>::try_from(src).unwrap())),
+        ETHERTYPE_IPV6 => payload
+            .get(8..24)
+            .filter(|_| payload[0] >> 4 == 6)
+            .map(|src| IpAddr::from(<[u8; 16]>::try_from(src).unwrap())),
+        // Ethernet/IPv4 ARP: the sender's protocol address
+        ETHERTYPE_ARP => payload
+            .get(14..18)
+            .filter(|_| payload[..6] == [0, 1, 0x08, 0x00, 6, 4])
+            .map(|src| IpAddr::from(<[u8; 4]>::try_from(src).unwrap())),
+        _ => None,
+    };
+    let foreign_ip = ip.filter(|ip| !is_own_ip(nic, ip)).map(ForeignSource::Ip);
+
+    [foreign_mac, foreign_ip]
+}
+
+fn is_own_ip(nic: &NicConfig, ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            *ip == Ipv4Addr::UNSPECIFIED
+                || nic.ipv4_address == Some(*ip)
+                || nic.leases.iter().any(|(_, a)| a == ip)
+        }
+        IpAddr::V6(ip) => {
+            *ip == Ipv6Addr::UNSPECIFIED
+                || ip.is_unicast_link_local()
+                || nic.ipv6_address == Some(*ip)
+                || nic.ipv6_delegated_prefix.is_some_and(|p| p.contains(ip))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reactor::GATEWAY_MAC;
+    use crate::test_util::packets::{
+        create_arp_request, create_dhcp_discover, create_icmp_echo_request,
+    };
+
+    const NIC_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x02];
+    const LEASE_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x01, 0x01];
+    const OTHER_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x03];
+
+    fn make_test_config() -> NicConfig {
+        NicConfig {
+            mac: NIC_MAC,
+            ipv4_address: Some(Ipv4Addr::new(10, 0, 0, 2)),
+            ipv4_gateway: None,
+            ipv4_prefix_len: 24,
+            ipv6_address: None,
+            ipv6_gateway: None,
+            ipv6_prefix_len: 0,
+            ipv6_delegated_prefix: None,
+            dns_servers: vec![],
+            boot_server: None,
+            boot_file: None,
+            mtu: 1500,
+            tx_burst: crate::reactor::DEFAULT_TX_BURST,
+            leases: vec![(LEASE_MAC, Ipv4Addr::new(10, 0, 0, 20))],
+            dns_zone: None,
+        }
+    }
+
+    fn sources(packet: &[u8]) -> [Option<ForeignSource>; 2] {
+        foreign_sources(&make_test_config(), &packet[12..])
+    }
+
+    fn ping(mac: [u8; 6], ip: [u8; 4]) -> Vec<u8> {
+        create_icmp_echo_request(mac, GATEWAY_MAC, ip, [10, 0, 0, 1], 1, 1)
+    }
+
+    #[test]
+    fn test_own_and_leased_sources() {
+        assert_eq!(sources(&ping(NIC_MAC, [10, 0, 0, 2])), [None, None]);
+        assert_eq!(sources(&ping(LEASE_MAC, [10, 0, 0, 20])), [None, None]);
+        assert_eq!(sources(&create_dhcp_discover(NIC_MAC, 1)), [None, None]);
+    }
+
+    #[test]
+    fn test_foreign_sources() {
+        assert_eq!(
+            sources(&ping(NIC_MAC, [10, 0, 0, 7])),
+            [None, Some(ForeignSource::Ip("10.0.0.7".parse().unwrap()))]
+        );
+        assert_eq!(
+            sources(&ping(OTHER_MAC, [10, 0, 0, 2])),
+            [Some(ForeignSource::Mac(OTHER_MAC)), None]
+        );
+        // A gratuitous ARP for another address
+        assert_eq!(
+            sources(&create_arp_request(NIC_MAC, [10, 0, 0, 9], [10, 0, 0, 9])),
+            [None, Some(ForeignSource::Ip("10.0.0.9".parse().unwrap()))]
+        );
+        assert_eq!(sources(&[0u8; 20]), [None, None]);
+    }
+
+    #[test]
+    fn test_watch_counts_and_caps() {
+        let watch = SourceWatch::default();
+        let mac = ForeignSource::Mac(OTHER_MAC);
+        watch.record(mac);
+        watch.record(mac);
+        for i in 0..MAX_SOURCES as u8 {
+            watch.record(ForeignSource::Ip(IpAddr::from([10, 0, 1, i])));
+        }
+
+        let seen = watch.take();
+        assert_eq!(seen.len(), MAX_SOURCES);
+        assert_eq!(seen[&mac], 2);
+        assert!(watch.take().is_empty());
+    }
+}
@@ -0,0 +1,169 @@
+//! Traffic counters of a NIC reactor.
+//!
+//! Counted from the guest's point of view: `rx` is what reaches the guest,
+//! `tx` what it sends. Only the reactor thread writes the counters, so an
+//! update is a relaxed load and store rather than a locked add; other
+//! threads read them through [`TrafficCounters::snapshot`].
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Packet, byte and drop counters, readable while the reactor runs.
+#[derive(Debug, Default)]
+pub struct TrafficCounters {
+    rx_packets: AtomicU64,
+    rx_bytes: AtomicU64,
+    rx_dropped: AtomicU64,
+    tx_packets: AtomicU64,
+    tx_bytes: AtomicU64,
+    tx_dropped: AtomicU64,
+    /// Packets dropped by strict isolation, either way
+    cross_network_blocked: AtomicU64,
+}
+
+/// A point-in-time copy of [`TrafficCounters`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TrafficSnapshot {
+    pub rx_packets: u64,
+    pub rx_bytes: u64,
+    pub rx_dropped: u64,
+    pub tx_packets: u64,
+    pub tx_bytes: u64,
+    pub tx_dropped: u64,
+}
+
+/// Per-second rates between two snapshots.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TrafficRates {
+    pub rx_packets: f64,
+    pub rx_bytes: f64,
+    pub rx_dropped: f64,
+    pub tx_packets: f64,
+    pub tx_bytes: f64,
+    pub tx_dropped: f64,
+}
+
+/// Add to a counter only the reactor thread writes.
+#[inline]
+fn bump(counter: &AtomicU64, n: u64) {
+    counter.store(
+        counter.load(Ordering::Relaxed).wrapping_add(n),
+        Ordering::Relaxed,
+    );
+}
+
+impl TrafficCounters {
+    /// A frame of `bytes` delivered to the guest
+    #[inline]
+    pub fn rx(&self, bytes: usize) {
+        bump(&self.rx_packets, 1);
+        bump(&self.rx_bytes, bytes as u64);
+    }
+
+    /// A frame for the guest that was dropped
+    #[inline]
+    pub fn rx_dropped(&self) {
+        bump(&self.rx_dropped, 1);
+    }
+
+    /// A frame of `bytes` sent by the guest
+    #[inline]
+    pub fn tx(&self, bytes: usize) {
+        bump(&self.tx_packets, 1);
+        bump(&self.tx_bytes, bytes as u64);
+    }
+
+    /// Frames sent by the guest that were dropped
+    #[inline]
+    pub fn tx_dropped(&self, frames: u64) {
+        bump(&self.tx_dropped, frames);
+    }
+
+    /// A packet to or from a network the NIC's network isn't peered with
+    #[inline]
+    pub fn cross_network_blocked(&self) {
+        bump(&self.cross_network_blocked, 1);
+    }
+
+    /// Packets dropped by strict isolation so far
+    pub fn cross_network_blocked_total(&self) -> u64 {
+        self.cross_network_blocked.load(Ordering::Relaxed)
+    }
+
+    pub fn snapshot(&self) -> TrafficSnapshot {
+        TrafficSnapshot {
+            rx_packets: self.rx_packets.load(Ordering::Relaxed),
+            rx_bytes: self.rx_bytes.load(Ordering::Relaxed),
+            rx_dropped: self.rx_dropped.load(Ordering::Relaxed),
+            tx_packets: self.tx_packets.load(Ordering::Relaxed),
+            tx_bytes: self.tx_bytes.load(Ordering::Relaxed),
+            tx_dropped: self.tx_dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl TrafficSnapshot {
+    /// Rates from an earlier snapshot of the same counters to this one.
+    pub fn rates_since(&self, earlier: &TrafficSnapshot, elapsed: Duration) -> TrafficRates {
+        let secs = elapsed.as_secs_f64();
+        if secs <= 0.0 {
+            return TrafficRates::default();
+        }
+        let rate = |now: u64, then: u64| now.wrapping_sub(then) as f64 / secs;
+        TrafficRates {
+            rx_packets: rate(self.rx_packets, earlier.rx_packets),
+            rx_bytes: rate(self.rx_bytes, earlier.rx_bytes),
+            rx_dropped: rate(self.rx_dropped, earlier.rx_dropped),
+            tx_packets: rate(self.tx_packets, earlier.tx_packets),
+            tx_bytes: rate(self.tx_bytes, earlier.tx_bytes),
+            tx_dropped: rate(self.tx_dropped, earlier.tx_dropped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_frames_and_bytes() {
+        let counters = TrafficCounters::default();
+        counters.rx(60);
+        counters.rx(1500);
+        counters.rx_dropped();
+        counters.tx(100);
+        counters.tx_dropped(3);
+        counters.cross_network_blocked();
+
+        assert_eq!(
+            counters.snapshot(),
+            TrafficSnapshot {
+                rx_packets: 2,
+                rx_bytes: 1560,
+                rx_dropped: 1,
+                tx_packets: 1,
+                tx_bytes: 100,
+                tx_dropped: 3,
+            }
+        );
+        assert_eq!(counters.cross_network_blocked_total(), 1);
+    }
+
+    #[test]
+    fn rates_between_snapshots() {
+        let counters = TrafficCounters::default();
+        let before = counters.snapshot();
+        for _ in 0..10 {
+            counters.tx(1000);
+        }
+        let rates = counters
+            .snapshot()
+            .rates_since(&before, Duration::from_millis(500));
+        assert_eq!(rates.tx_packets, 20.0);
+        assert_eq!(rates.tx_bytes, 20_000.0);
+        assert_eq!(rates.rx_packets, 0.0);
+
+        let none = counters.snapshot().rates_since(&before, Duration::ZERO);
+        assert_eq!(none, TrafficRates::default());
+    }
+}
@@ -0,0 +1,251 @@
+//! Restart of crashed NIC reactors.
+//!
+//! Each NIC's reactor runs on its own thread, and a panic there ends only
+//! that thread. The NIC then passes no traffic: its guest stays connected
+//! to the vhost-user socket, but nothing serves its queues. This task looks
+//! for crashed reactors on an interval, puts their NIC into the Error
+//! state and restarts them after a backoff that doubles with each crash in
+//! a row. A restarted reactor takes over the connected guest and gets the
+//! NIC's routing tables, routes and security policy set up again from the
+//! store, as for a new NIC.
+//!
+//! Descriptors the crashed reactor held are lost to the guest, and its
+//! packet buffers stay mapped since other reactors may still reference
+//! them, so a NIC that keeps crashing costs memory until its backoff caps
+//! the restarts.
+
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::time;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use crate::audit::NetAuditLogger;
+use crate::grpc::NetworkManager;
+use crate::grpc::storage::NicState;
+
+/// Default check interval in seconds
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 1;
+
+/// Delay before the first restart after a crash
+const FIRST_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// Longest delay between restarts
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(300);
+
+/// A reactor that ran this long before crashing starts its backoff over
+const STABLE_AFTER: Duration = Duration::from_secs(600);
+
+/// Delay before restarting a reactor that crashed `streak` times in a row.
+pub fn restart_delay(streak: u32) -> Duration {
+    let doublings = streak.saturating_sub(1).min(16);
+    (FIRST_RESTART_DELAY * 2u32.pow(doublings)).min(MAX_RESTART_DELAY)
+}
+
+/// Crash history of a NIC's reactor.
+#[derive(Debug, Clone)]
+pub struct ReactorCrashes {
+    /// Crashes since the NIC's router was created
+    pub count: u32,
+    /// Crashes without the reactor running stable in between
+    pub streak: u32,
+    /// Panic message of the last crash
+    pub last_panic: Option<String>,
+    /// When the reactor was last started
+    pub started_at: Instant,
+    /// When the crashed reactor is due for a restart; `None` while it runs
+    pub restart_at: Option<Instant>,
+    /// NIC state before the crash, restored after the restart
+    pub state_before: NicState,
+}
+
+impl ReactorCrashes {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            count: 0,
+            streak: 0,
+            last_panic: None,
+            started_at: now,
+            restart_at: None,
+            state_before: NicState::Created,
+        }
+    }
+
+    /// Record a crash and schedule the restart. Returns the delay.
+    pub fn crashed(&mut self, panic: String, state: NicState, now: Instant) -> Duration {
+        if now.duration_since(self.started_at) >= STABLE_AFTER {
+            self.streak = 0;
+        }
+        self.count += 1;
+        self.streak += 1;
+        self.last_panic = Some(panic);
+        self.state_before = state;
+        let delay = restart_delay(self.streak);
+        self.restart_at = Some(now + delay);
+        delay
+    }
+
+    /// Whether the crashed reactor is due for a restart.
+    pub fn restart_due(&self, now: Instant) -> bool {
+        self.restart_at.is_some_and(|at| at <= now)
+    }
+
+    /// Record a restart.
+    pub fn restarted(&mut self, now: Instant) {
+        self.started_at = now;
+        self.restart_at = None;
+    }
+
+    /// Record a failed restart and schedule the next attempt.
+    pub fn restart_failed(&mut self, now: Instant) -> Duration {
+        self.streak += 1;
+        let delay = restart_delay(self.streak);
+        self.restart_at = Some(now + delay);
+        delay
+    }
+}
+
+/// What a supervision pass did to a NIC's reactor.
+#[derive(Debug)]
+pub enum ReactorEvent {
+    Crashed {
+        nic_id: Uuid,
+        crashes: u32,
+        panic: String,
+        restart_in: Duration,
+    },
+    Restarted {
+        nic_id: Uuid,
+    },
+    RestartFailed {
+        nic_id: Uuid,
+        error: String,
+        retry_in: Duration,
+    },
+}
+
+/// Reactor supervisor task.
+pub struct ReactorSupervisor {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl ReactorSupervisor {
+    /// Start a new supervisor task.
+    pub fn start(
+        manager: Arc<NetworkManager>,
+        audit: Arc<NetAuditLogger>,
+        interval: Duration,
+    ) -> Self {
+        info!(interval = ?interval, "Reactor supervisor started");
+
+        let task = tokio::spawn(supervise_loop(manager, audit, interval));
+        Self { task }
+    }
+
+    /// Stop the supervisor task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Reactor supervisor stopped");
+    }
+}
+
+/// Main supervisor loop.
+async fn supervise_loop(
+    manager: Arc<NetworkManager>,
+    audit: Arc<NetAuditLogger>,
+    interval: Duration,
+) {
+    let mut interval = time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        for event in manager.supervise_reactors(Instant::now()).await {
+            match event {
+                ReactorEvent::Crashed {
+                    nic_id,
+                    crashes,
+                    panic,
+                    restart_in,
+                } => {
+                    warn!(
+                        nic_id = %nic_id,
+                        crashes,
+                        panic = %panic,
+                        restart_in = ?restart_in,
+                        "NIC reactor crashed"
+                    );
+                    audit.nic_reactor_crashed(&nic_id.to_string(), crashes, &panic);
+                }
+                ReactorEvent::Restarted { nic_id } => {
+                    info!(nic_id = %nic_id, "NIC reactor restarted");
+                    audit.nic_reactor_restarted(&nic_id.to_string());
+                }
+                ReactorEvent::RestartFailed {
+                    nic_id,
+                    error,
+                    retry_in,
+                } => {
+                    warn!(
+                        nic_id = %nic_id,
+                        error = %error,
+                        retry_in = ?retry_in,
+                        "Failed to restart NIC reactor"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restart_delay() {
+        assert_eq!(restart_delay(1), Duration::from_secs(1));
+        assert_eq!(restart_delay(2), Duration::from_secs(2));
+        assert_eq!(restart_delay(5), Duration::from_secs(16));
+        assert_eq!(restart_delay(9), Duration::from_secs(256));
+        assert_eq!(restart_delay(10), MAX_RESTART_DELAY);
+        assert_eq!(restart_delay(u32::MAX), MAX_RESTART_DELAY);
+    }
+
+    #[test]
+    fn test_backoff_resets_after_stable_run() {
+        let start = Instant::now();
+        let mut crashes = ReactorCrashes::new(start);
+
+        let delay = crashes.crashed("boom".into(), NicState::Created, start);
+        assert_eq!(delay, Duration::from_secs(1));
+        assert!(!crashes.restart_due(start));
+        assert!(crashes.restart_due(start + delay));
+
+        // Crashes right after the restart back off further
+        crashes.restarted(start + delay);
+        let delay = crashes.crashed("boom".into(), NicState::Created, start + delay);
+        assert_eq!(delay, Duration::from_secs(2));
+
+        // A long stable run starts over, the count goes on
+        crashes.restarted(start);
+        let later = start + STABLE_AFTER;
+        assert_eq!(
+            crashes.crashed("again".into(), NicState::Created, later),
+            Duration::from_secs(1)
+        );
+        assert_eq!(crashes.count, 3);
+        assert_eq!(crashes.last_panic.as_deref(), Some("again"));
+    }
+
+    #[test]
+    fn test_failed_restart_backs_off() {
+        let now = Instant::now();
+        let mut crashes = ReactorCrashes::new(now);
+        crashes.crashed("boom".into(), NicState::Created, now);
+        assert_eq!(crashes.restart_failed(now), Duration::from_secs(2));
+        assert_eq!(crashes.restart_failed(now), Duration::from_secs(4));
+        assert_eq!(crashes.count, 1);
+    }
+}
@@ -0,0 +1,310 @@
+//! Applying configuration changes without a restart.
+//!
+//! A restart tears down every reactor and with it the vhost-user sessions
+//! of all running VMs. The settings here are read again on SIGHUP and on
+//! the Reload RPC and applied in place: running reactors get the new MTU,
+//! TX burst size, RX pool cap and isolation through their command
+//! channels, audit entries go to the new log endpoints, and NICs found in
+//! the database without a router get one. Everything else (listen address,
+//! TUN device, reactor CPUs, uplink namespace) still needs a restart.
+//!
+//! The environment of a running process can't be changed from outside, so
+//! the reloadable settings are also read from an env file of `KEY=VALUE`
+//! lines (as systemd's `EnvironmentFile=`), whose values take precedence
+//! over the process environment. A missing file is the same as an empty
+//! one.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use thiserror::Error;
+use tokio::sync::Mutex;
+use tracing::{info, warn};
+
+use crate::audit::NetAuditLogger;
+use crate::grpc::NetworkManager;
+use crate::reactor::{DEFAULT_TX_BURST, pmtu, rx_pool};
+
+/// Env file read at startup and on reload, unless `MVIRT_NET_ENV_FILE`
+/// names another
+pub const DEFAULT_ENV_FILE: &str = "/etc/mvirt/net.env";
+
+/// Log endpoint used when `MVIRT_LOG_ENDPOINTS` is unset
+pub const DEFAULT_LOG_ENDPOINT: &str = "http://[::1]:50052";
+
+#[derive(Debug, Error)]
+pub enum SettingsError {
+    #[error("Failed to read {0}: {1}")]
+    Io(String, std::io::Error),
+
+    #[error("Invalid {0}: {1}")]
+    Invalid(&'static str, String),
+}
+
+/// Settings that can change while the daemon runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// MTU of routed traffic (`MVIRT_NET_MTU`)
+    pub mtu: u16,
+    /// Guest TX descriptors per burst (`MVIRT_NET_TX_BURST`)
+    pub tx_burst: usize,
+    /// Cap on each reactor's TUN RX buffer pool (`MVIRT_NET_RX_BUFFER_MAX`)
+    pub rx_buffer_max: usize,
+    /// Drop traffic between networks that aren't peered
+    /// (`MVIRT_NET_STRICT_ISOLATION`)
+    pub strict_isolation: bool,
+    /// mvirt-log endpoints for audit entries (`MVIRT_LOG_ENDPOINTS`)
+    pub log_endpoints: Vec<String>,
+}
+
+impl Settings {
+    /// Read the settings from `env_file` over the process environment.
+    pub fn load(env_file: &Path) -> Result<Self, SettingsError> {
+        let file = match std::fs::read_to_string(env_file) {
+            Ok(content) => parse_env_file(&content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(SettingsError::Io(env_file.display().to_string(), e)),
+        };
+        Self::from_lookup(|key| file.get(key).cloned().or_else(|| std::env::var(key).ok()))
+    }
+
+    /// Parse the settings from whatever `lookup` returns for each key.
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, SettingsError> {
+        let mtu = match lookup("MVIRT_NET_MTU") {
+            Some(value) => match value.parse::<u16>() {
+                Ok(mtu) if mtu >= pmtu::MIN_MTU => mtu,
+                _ => return Err(SettingsError::Invalid("MVIRT_NET_MTU", value)),
+            },
+            None => pmtu::DEFAULT_MTU,
+        };
+        let tx_burst = match lookup("MVIRT_NET_TX_BURST") {
+            Some(value) => match value.parse::<usize>() {
+                Ok(burst) if burst > 0 => burst,
+                _ => return Err(SettingsError::Invalid("MVIRT_NET_TX_BURST", value)),
+            },
+            None => DEFAULT_TX_BURST,
+        };
+        let rx_buffer_max = match lookup("MVIRT_NET_RX_BUFFER_MAX") {
+            Some(value) => match value.parse::<usize>() {
+                Ok(max) if max > 0 => max,
+                _ => return Err(SettingsError::Invalid("MVIRT_NET_RX_BUFFER_MAX", value)),
+            },
+            None => rx_pool::DEFAULT_RX_BUFFER_MAX,
+        };
+        let strict_isolation = match lookup("MVIRT_NET_STRICT_ISOLATION") {
+            Some(value) => match value.as_str() {
+                "1" | "true" => true,
+                "0" | "false" => false,
+                _ => return Err(SettingsError::Invalid("MVIRT_NET_STRICT_ISOLATION", value)),
+            },
+            None => false,
+        };
+        let log_endpoints = lookup("MVIRT_LOG_ENDPOINTS")
+            .unwrap_or_else(|| DEFAULT_LOG_ENDPOINT.to_string())
+            .split(',')
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        Ok(Settings {
+            mtu,
+            tx_burst,
+            rx_buffer_max,
+            strict_isolation,
+            log_endpoints,
+        })
+    }
+
+    /// Names of the settings that differ from `other`.
+    pub fn changes(&self, other: &Settings) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if self.mtu != other.mtu {
+            changed.push("mtu");
+        }
+        if self.tx_burst != other.tx_burst {
+            changed.push("tx_burst");
+        }
+        if self.rx_buffer_max != other.rx_buffer_max {
+            changed.push("rx_buffer_max");
+        }
+        if self.strict_isolation != other.strict_isolation {
+            changed.push("strict_isolation");
+        }
+        if self.log_endpoints != other.log_endpoints {
+            changed.push("log_endpoints");
+        }
+        changed
+    }
+}
+
+/// `KEY=VALUE` lines; blank lines and `#` comments are skipped, and values
+/// may be quoted.
+pub fn parse_env_file(content: &str) -> HashMap<String, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(value);
+            (key.trim().to_string(), value.to_string())
+        })
+        .collect()
+}
+
+/// What a reload changed.
+#[derive(Debug, Clone, Default)]
+pub struct ReloadSummary {
+    /// Settings that took a new value
+    pub changed: Vec<&'static str>,
+    /// NICs that got a router
+    pub nics_started: usize,
+}
+
+/// Re-reads the settings and applies them to the running daemon.
+pub struct Reloader {
+    env_file: PathBuf,
+    manager: Arc<NetworkManager>,
+    audit: Arc<NetAuditLogger>,
+    /// Settings in effect; also serializes reloads
+    current: Mutex<Settings>,
+}
+
+impl Reloader {
+    /// `current` are the settings the daemon was started with.
+    pub fn new(
+        env_file: PathBuf,
+        manager: Arc<NetworkManager>,
+        audit: Arc<NetAuditLogger>,
+        current: Settings,
+    ) -> Self {
+        Reloader {
+            env_file,
+            manager,
+            audit,
+            current: Mutex::new(current),
+        }
+    }
+
+    /// Read the settings again and apply what changed. Invalid settings
+    /// are rejected as a whole and the running ones stay.
+    pub async fn reload(&self) -> Result<ReloadSummary, SettingsError> {
+        let mut current = self.current.lock().await;
+        let settings = Settings::load(&self.env_file)?;
+        let changed = settings.changes(&current);
+
+        if let Err(e) = self
+            .manager
+            .apply_tunables(
+                settings.mtu,
+                settings.tx_burst,
+                settings.rx_buffer_max,
+                settings.strict_isolation,
+            )
+            .await
+        {
+            warn!(error = %e, "Failed to apply tunables");
+        }
+        if settings.log_endpoints != current.log_endpoints {
+            self.audit
+                .set_endpoints(settings.log_endpoints.clone(), None);
+        }
+
+        // Networks and NICs written to the database behind the daemon's back
+        let nics_started = match self.manager.start_missing_nics().await {
+            Ok(started) => started,
+            Err(e) => {
+                warn!(error = %e, "Failed to start NICs from the database");
+                0
+            }
+        };
+        if let Err(e) = self.manager.sync_isolation().await {
+            warn!(error = %e, "Failed to sync isolation");
+        }
+        if let Err(e) = self.manager.sync_neighbor_proxy() {
+            warn!(error = %e, "Failed to sync neighbor proxy");
+        }
+        if let Err(e) = self.manager.sync_nat().await {
+            warn!(error = %e, "Failed to sync port forwards and floating IPs");
+        }
+        if let Err(e) = self.manager.sync_peering_routes().await {
+            warn!(error = %e, "Failed to sync routes between connected networks");
+        }
+
+        info!(?changed, nics_started, "Configuration reloaded");
+        self.audit.config_reloaded(&changed, nics_started);
+        *current = settings;
+        Ok(ReloadSummary {
+            changed,
+            nics_started,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |key| vars.get(key).cloned()
+    }
+
+    #[test]
+    fn parses_env_files() {
+        let vars = parse_env_file(
+            "# tunables\n\nMVIRT_NET_MTU=1400\n MVIRT_LOG_ENDPOINTS = \"http://[::1]:50052\"\nnonsense\n",
+        );
+        assert_eq!(vars.len(), 2);
+        assert_eq!(vars["MVIRT_NET_MTU"], "1400");
+        assert_eq!(vars["MVIRT_LOG_ENDPOINTS"], "http://[::1]:50052");
+    }
+
+    #[test]
+    fn defaults_and_changes() {
+        let defaults = Settings::from_lookup(lookup(&[])).unwrap();
+        assert_eq!(defaults.mtu, pmtu::DEFAULT_MTU);
+        assert_eq!(
+            defaults.log_endpoints,
+            vec![DEFAULT_LOG_ENDPOINT.to_string()]
+        );
+        assert!(!defaults.strict_isolation);
+
+        let changed = Settings::from_lookup(lookup(&[
+            ("MVIRT_NET_MTU", "1400"),
+            ("MVIRT_NET_STRICT_ISOLATION", "true"),
+            ("MVIRT_LOG_ENDPOINTS", "http://a:50052, http://b:50052"),
+        ]))
+        .unwrap();
+        assert_eq!(changed.log_endpoints.len(), 2);
+        assert_eq!(
+            changed.changes(&defaults),
+            vec!["mtu", "strict_isolation", "log_endpoints"]
+        );
+        assert!(defaults.changes(&defaults).is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_values() {
+        assert!(matches!(
+            Settings::from_lookup(lookup(&[("MVIRT_NET_MTU", "100")])),
+            Err(SettingsError::Invalid("MVIRT_NET_MTU", _))
+        ));
+        assert!(matches!(
+            Settings::from_lookup(lookup(&[("MVIRT_NET_TX_BURST", "0")])),
+            Err(SettingsError::Invalid("MVIRT_NET_TX_BURST", _))
+        ));
+        assert!(matches!(
+            Settings::from_lookup(lookup(&[("MVIRT_NET_STRICT_ISOLATION", "yes")])),
+            Err(SettingsError::Invalid("MVIRT_NET_STRICT_ISOLATION", _))
+        ));
+    }
+}
@@ -1,19 +1,25 @@
-use crate::hugepage::HugePagePool;
+use crate::hugepage::{self, HugePageManager, HugePagePool};
 use crate::inter_reactor::{CompletionNotify, PacketRef};
+use crate::netns::Netns;
 use crate::reactor::{
-    InterfaceType, NicConfig, Reactor, ReactorHandle, ReactorId, ReactorInfo, ReactorRegistry,
+    DEFAULT_TX_BURST, InterfaceType, NicConfig, Reactor, ReactorHandle, ReactorId, ReactorInfo,
+    ReactorRegistry, pmtu,
 };
+use crate::socket_access::SocketAccess;
 use crate::tun::TunDevice;
-use crate::vhost_user::{VhostHandshake, VhostUserNetDevice};
+use crate::vhost_user::{ReactorAttachment, VhostHandshake, VhostLink, VhostUserNetDevice};
 use crate::virtqueue::SimpleRxTxQueues;
+use ipnet::Ipv6Net;
+use std::any::Any;
 use std::io;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
-use std::os::unix::io::IntoRawFd;
-use std::sync::Arc;
+use std::os::unix::io::{IntoRawFd, OwnedFd};
+use std::path::Path;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
 use std::thread::{self, JoinHandle};
-use tracing::info;
+use tracing::{info, warn};
 
 /// Buffer size for TUN device reads/writes.
 /// This size is required to hold full 64 KiB GSO packets plus virtio/ethernet headers
@@ -26,10 +32,14 @@ pub const TUN_BUFFER_COUNT: usize = 256;
 pub struct Router {
     reactor_handle: ReactorHandle,
     reactor_thread: JoinHandle<()>,
+    /// Panic message once the reactor thread panicked
+    reactor_panic: Arc<Mutex<Option<String>>>,
     vhost_thread: Option<JoinHandle<io::Result<()>>>,
     tun_name: String,
     /// TUN interface index for kernel route management
     tun_if_index: u32,
+    /// Namespace of the TUN device, `None` for the host's
+    netns: Option<Netns>,
     vhost_socket: Option<String>,
     /// Shared reactor registry for inter-reactor communication
     registry: Arc<ReactorRegistry>,
@@ -37,6 +47,34 @@ pub struct Router {
     reactor_id: ReactorId,
     /// Shutdown flag shared with vhost thread
     shutdown_flag: Arc<AtomicBool>,
+    /// Link state the guest sees, for vhost routers
+    link: Option<VhostLink>,
+    /// Reactor the vhost-user backend hands the guest's queues to
+    attachment: Option<ReactorAttachment>,
+    /// How to start the reactor again after a crash
+    setup: ReactorSetup,
+}
+
+/// Everything needed to start a router's reactor, kept to restart it.
+struct ReactorSetup {
+    buf_size: usize,
+    rx_count: usize,
+    tx_count: usize,
+    cpu: Option<usize>,
+    hugepages: Option<HugePageManager>,
+    /// NIC config of vhost routers
+    nic: Option<NicConfig>,
+}
+
+/// A reactor thread with its TUN device, as started by [`ReactorSetup::start`].
+struct RunningReactor {
+    handle: ReactorHandle,
+    thread: JoinHandle<()>,
+    id: ReactorId,
+    tun_if_index: u32,
+    /// Handshake channel and notify fd for the vhost-user backend
+    vhost: Option<(SyncSender<VhostHandshake>, OwnedFd)>,
+    panic: Arc<Mutex<Option<String>>>,
 }
 
 /// Configuration for a vhost-user device
@@ -58,9 +96,40 @@ pub struct VhostConfig {
     pub ipv6_gateway: Option<Ipv6Addr>,
     /// IPv6 subnet prefix length
     pub ipv6_prefix_len: u8,
+    /// Prefix delegated to the VM via DHCPv6 (IA_PD)
+    pub ipv6_delegated_prefix: Option<Ipv6Net>,
 
     /// DNS servers for DHCP option 6 / DHCPv6 option 23
     pub dns_servers: Vec<IpAddr>,
+
+    /// TFTP server handed to PXE clients (BOOTP siaddr)
+    pub boot_server: Option<Ipv4Addr>,
+    /// Boot file or UEFI HTTP boot URL offered to boot firmware
+    pub boot_file: Option<String>,
+
+    /// MTU of routed traffic; larger packets get an ICMP too-big reply
+    pub mtu: u16,
+
+    /// Guest TX descriptors handled per burst
+    pub tx_burst: usize,
+
+    /// Link state reported to the guest at start
+    pub link_up: bool,
+
+    /// Owner, group and mode the socket gets once created
+    pub socket_access: SocketAccess,
+}
+
+/// Where a router's reactor runs and where its buffers come from.
+#[derive(Clone, Default)]
+pub struct Placement {
+    /// CPU to pin the reactor thread to
+    pub cpu: Option<usize>,
+    /// Pool to take buffers from, on the CPU's NUMA node. Without one
+    /// buffers are mapped ad hoc and creation fails without huge pages.
+    pub hugepages: Option<HugePageManager>,
+    /// Network namespace to create the TUN device in instead of the host's
+    pub netns: Option<Netns>,
 }
 
 impl VhostConfig {
@@ -76,7 +145,14 @@ impl VhostConfig {
             ipv6_address: None,
             ipv6_gateway: None,
             ipv6_prefix_len: 64,
+            ipv6_delegated_prefix: None,
             dns_servers: Vec::new(),
+            boot_server: None,
+            boot_file: None,
+            mtu: pmtu::DEFAULT_MTU,
+            tx_burst: DEFAULT_TX_BURST,
+            link_up: true,
+            socket_access: SocketAccess::default(),
         }
     }
 
@@ -96,12 +172,49 @@ impl VhostConfig {
         self
     }
 
+    /// Set the prefix delegated to the VM via DHCPv6.
+    pub fn with_delegated_prefix(mut self, prefix: Ipv6Net) -> Self {
+        self.ipv6_delegated_prefix = Some(prefix);
+        self
+    }
+
     /// Add DNS servers for DHCP.
     pub fn with_dns(mut self, servers: Vec<IpAddr>) -> Self {
         self.dns_servers = servers;
         self
     }
 
+    /// Set the network boot options for DHCP.
+    pub fn with_boot(mut self, server: Option<Ipv4Addr>, file: Option<String>) -> Self {
+        self.boot_server = server;
+        self.boot_file = file;
+        self
+    }
+
+    /// Set the MTU of routed traffic.
+    pub fn with_mtu(mut self, mtu: u16) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Set how many guest TX descriptors are handled per burst.
+    pub fn with_tx_burst(mut self, tx_burst: usize) -> Self {
+        self.tx_burst = tx_burst;
+        self
+    }
+
+    /// Set the link state reported to the guest at start.
+    pub fn with_link_up(mut self, link_up: bool) -> Self {
+        self.link_up = link_up;
+        self
+    }
+
+    /// Set the owner, group and mode of the socket.
+    pub fn with_socket_access(mut self, access: SocketAccess) -> Self {
+        self.socket_access = access;
+        self
+    }
+
     /// Convert to NicConfig for the reactor.
     pub fn to_nic_config(&self) -> NicConfig {
         NicConfig {
@@ -112,7 +225,14 @@ impl VhostConfig {
             ipv6_address: self.ipv6_address,
             ipv6_gateway: self.ipv6_gateway,
             ipv6_prefix_len: self.ipv6_prefix_len,
+            ipv6_delegated_prefix: self.ipv6_delegated_prefix,
             dns_servers: self.dns_servers.clone(),
+            boot_server: self.boot_server,
+            boot_file: self.boot_file.clone(),
+            mtu: self.mtu,
+            tx_burst: self.tx_burst,
+            leases: Vec::new(),
+            dns_zone: None,
         }
     }
 }
@@ -165,126 +285,90 @@ impl Router {
         vhost_config: Option<VhostConfig>,
         registry: Arc<ReactorRegistry>,
     ) -> io::Result<Self> {
-        // Create TUN device (L3 mode - no IP address, only routes)
-        let tun = TunDevice::create(name).await?;
+        Self::with_placement(
+            name,
+            ip,
+            buf_size,
+            rx_count,
+            tx_count,
+            vhost_config,
+            registry,
+            Placement::default(),
+        )
+        .await
+    }
+
+    /// Create a router whose reactor is pinned to a CPU, with buffers on
+    /// that CPU's NUMA node.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn with_placement(
+        name: &str,
+        ip: Option<(Ipv4Addr, u8)>,
+        buf_size: usize,
+        rx_count: usize,
+        tx_count: usize,
+        vhost_config: Option<VhostConfig>,
+        registry: Arc<ReactorRegistry>,
+        placement: Placement,
+    ) -> io::Result<Self> {
         // Note: No IP address assigned to TUN - use kernel routes instead
         // The ip parameter is kept for compatibility but will be removed
         let _ = ip; // Suppress unused warning
-        tun.set_up().await?;
-
-        // Store if_index before consuming TUN device
-        let tun_if_index = tun.if_index;
-        let tun_file = tun.into_file();
-
-        // Allocate buffers
-        let buffers = HugePagePool::new((rx_count + tx_count) * buf_size).ok_or_else(|| {
-            io::Error::other(
-                "Failed to allocate huge pages. Run: echo 64 | sudo tee /proc/sys/vm/nr_hugepages",
-            )
-        })?;
 
-        // Create queues
-        let queues = SimpleRxTxQueues::new(tun_file, buffers, buf_size, rx_count, tx_count);
-        let (rx_queue, tx_queue) = queues.split();
-
-        // Create inter-reactor communication channels
-        let (packet_tx, packet_rx): (Sender<PacketRef>, Receiver<PacketRef>) = mpsc::channel();
-        let (completion_tx, completion_rx): (Sender<CompletionNotify>, Receiver<CompletionNotify>) =
-            mpsc::channel();
-
-        // Create reactor with optional vhost handshake channel and registry
-        let (reactor, reactor_handle, handshake_tx, reactor_id) =
-            if let Some(ref config) = vhost_config {
-                let (tx, rx) = mpsc::sync_channel::<VhostHandshake>(1);
-                let nic_config = config.to_nic_config();
-                let (reactor, handle) = Reactor::with_registry(
-                    rx_queue,
-                    tx_queue,
-                    Some(rx),
-                    Some(Arc::clone(&registry)),
-                    Some(packet_rx),
-                    Some(completion_rx),
-                    Some(nic_config),
-                    None, // No initial tables (will be populated via commands)
-                );
-                let id = reactor.id();
-                (reactor, handle, Some(tx), id)
-            } else {
-                let (reactor, handle) = Reactor::with_registry(
-                    rx_queue,
-                    tx_queue,
-                    None,
-                    Some(Arc::clone(&registry)),
-                    Some(packet_rx),
-                    Some(completion_rx),
-                    None, // No NIC config for TUN-only reactors
-                    None, // No initial tables (will be populated via commands)
-                );
-                let id = reactor.id();
-                (reactor, handle, None, id)
-            };
-
-        // Get reactor notify fd for vhost daemon (before spawning reactor thread)
-        let reactor_notify = if vhost_config.is_some() {
-            Some(reactor_handle.get_notify_fd())
-        } else {
-            None
-        };
-
-        // Register the reactor in the registry
-        // Use into_raw_fd() to transfer ownership - otherwise the OwnedFd would close
-        // the fd when dropped, leaving ReactorInfo with an invalid fd
-        let notify_raw_fd = reactor_handle.get_notify_fd().into_raw_fd();
-        let reactor_info = if let Some(ref config) = vhost_config {
-            // Vhost interface - register with MAC address for Ethernet header construction
-            let interface_type = InterfaceType::Vhost {
-                device_id: uuid::Uuid::new_v4(), // TODO: Use actual device UUID
-            };
-            ReactorInfo::with_mac(
-                reactor_id,
-                notify_raw_fd,
-                packet_tx,
-                completion_tx,
-                interface_type,
-                config.mac,
-            )
-        } else {
-            // TUN interface - no MAC address needed
-            let interface_type = InterfaceType::Tun {
-                if_index: tun_if_index,
-            };
-            ReactorInfo::new(
-                reactor_id,
-                notify_raw_fd,
-                packet_tx,
-                completion_tx,
-                interface_type,
-            )
+        let setup = ReactorSetup {
+            buf_size,
+            rx_count,
+            tx_count,
+            cpu: placement.cpu,
+            hugepages: placement.hugepages,
+            nic: vhost_config.as_ref().map(VhostConfig::to_nic_config),
         };
-        registry.register(reactor_info);
-        info!(id = %reactor_id, "Registered reactor in registry");
-
-        // Spawn reactor thread
-        let reactor_thread = thread::spawn(move || {
-            reactor.run();
-        });
+        let reactor = setup
+            .start(name, placement.netns.as_ref(), &registry)
+            .await?;
 
         // Create shutdown flag for clean shutdown signaling
         let shutdown_flag = Arc::new(AtomicBool::new(false));
 
         // Optionally spawn vhost-user device
-        let (vhost_thread, vhost_socket) = if let Some(config) = vhost_config {
+        let link = vhost_config.as_ref().map(|c| VhostLink::new(c.link_up));
+        if link.as_ref().is_some_and(|l| !l.is_up()) {
+            reactor.handle.set_link_up(false);
+        }
+        let (vhost_thread, vhost_socket, attachment) = if let Some(config) = vhost_config {
             let socket_path = config.socket_path.clone();
+            let (handshake_tx, reactor_notify) = reactor
+                .vhost
+                .expect("vhost reactor should have a handshake channel");
+            let attachment = ReactorAttachment::new();
+            attachment.attach(handshake_tx, reactor_notify);
             let device = VhostUserNetDevice::with_reactor(
                 &config.socket_path,
                 config.mac,
-                handshake_tx.expect("handshake_tx should be set"),
-                reactor_notify.expect("reactor_notify should be set"),
-            );
+                attachment.clone(),
+            )
+            .with_mtu(config.mtu)
+            .with_link(link.clone().expect("link should be set"));
+            // Bind here so the socket exists, with its owner and mode, when
+            // the router is returned
+            let listener = match device.listen() {
+                Ok(listener) => listener,
+                Err(e) => {
+                    reactor.handle.shutdown();
+                    let _ = reactor.thread.join();
+                    registry.unregister(&reactor.id);
+                    let _ = TunDevice::delete_in(name, placement.netns.as_ref()).await;
+                    return Err(e);
+                }
+            };
+            if let Err(e) = config.socket_access.apply(Path::new(&socket_path)) {
+                warn!(socket = %socket_path, error = %e, "Failed to set socket owner and mode");
+            }
             let shutdown_flag_clone = Arc::clone(&shutdown_flag);
             let handle = thread::spawn(move || {
-                let result =
-                    std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| device.run()));
+                let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                    device.serve(listener)
+                }));
                 match result {
                     Ok(Ok(())) => Ok(()),
                     Ok(Err(e)) => {
@@ -295,39 +379,89 @@ impl Router {
                         Err(e)
                     }
                     Err(panic) => {
-                        let msg = if let Some(s) = panic.downcast_ref::<&str>() {
-                            s.to_string()
-                        } else if let Some(s) = panic.downcast_ref::<String>() {
-                            s.clone()
-                        } else {
-                            "unknown panic".to_string()
-                        };
+                        let msg = panic_message(&*panic);
                         tracing::error!(panic = %msg, "vhost-user device panicked!");
                         Err(io::Error::other(msg))
                     }
                 }
             });
             info!(socket = %socket_path, mac = ?config.mac, "vhost-user device started");
-            (Some(handle), Some(socket_path))
+            (Some(handle), Some(socket_path), Some(attachment))
         } else {
-            (None, None)
+            (None, None, None)
         };
 
-        info!(name, tun_if_index, "Router started (L3 mode)");
+        info!(
+            name,
+            tun_if_index = reactor.tun_if_index,
+            "Router started (L3 mode)"
+        );
 
         Ok(Router {
-            reactor_handle,
-            reactor_thread,
+            reactor_handle: reactor.handle,
+            reactor_thread: reactor.thread,
+            reactor_panic: reactor.panic,
             vhost_thread,
             tun_name: name.to_string(),
-            tun_if_index,
+            tun_if_index: reactor.tun_if_index,
+            netns: placement.netns,
             vhost_socket,
             registry,
-            reactor_id,
+            reactor_id: reactor.id,
             shutdown_flag,
+            link,
+            attachment,
+            setup,
         })
     }
 
+    /// Panic message of the reactor thread, if it panicked. The router's
+    /// NIC passes no traffic until [`Router::restart_reactor`].
+    pub fn crashed(&self) -> Option<String> {
+        self.reactor_panic.lock().unwrap().clone()
+    }
+
+    /// Start a new reactor in place of one that panicked, on a new TUN
+    /// device of the same name. A connected guest stays connected and is
+    /// handed over to the new reactor. The reactor gets a new ID and starts
+    /// without routing tables or security policy, like a new router's.
+    pub async fn restart_reactor(&mut self) -> io::Result<()> {
+        if self.crashed().is_none() {
+            return Err(io::Error::other("Reactor is still running"));
+        }
+
+        self.registry.unregister(&self.reactor_id);
+        // The crashed reactor's TUN fd is never closed, so its device stays
+        // until deleted
+        if let Err(e) = TunDevice::delete_in(&self.tun_name, self.netns.as_ref()).await {
+            warn!(name = %self.tun_name, error = %e, "Failed to delete TUN of crashed reactor");
+        }
+
+        let reactor = self
+            .setup
+            .start(&self.tun_name, self.netns.as_ref(), &self.registry)
+            .await?;
+        // The old thread has ended, joining only reaps it
+        let crashed = std::mem::replace(&mut self.reactor_thread, reactor.thread);
+        let _ = crashed.join();
+
+        self.reactor_handle = reactor.handle;
+        self.reactor_panic = reactor.panic;
+        self.reactor_id = reactor.id;
+        self.tun_if_index = reactor.tun_if_index;
+        if self.link.as_ref().is_some_and(|l| !l.is_up()) {
+            self.reactor_handle.set_link_up(false);
+        }
+        if let (Some(attachment), Some((handshake_tx, reactor_notify))) =
+            (&self.attachment, reactor.vhost)
+        {
+            attachment.attach(handshake_tx, reactor_notify);
+        }
+
+        info!(name = %self.tun_name, id = %self.reactor_id, "Reactor restarted");
+        Ok(())
+    }
+
     /// Get the TUN interface index for kernel route management.
     pub fn tun_if_index(&self) -> u32 {
         self.tun_if_index
@@ -353,6 +487,16 @@ impl Router {
         &self.reactor_handle
     }
 
+    /// Bring the link up or down: the reactor stops forwarding and the
+    /// guest sees the link state change. No-op for routers without a
+    /// vhost-user device.
+    pub fn set_link_up(&self, up: bool) {
+        if let Some(link) = &self.link {
+            self.reactor_handle.set_link_up(up);
+            link.set_up(up);
+        }
+    }
+
     /// Signal that shutdown is imminent.
     ///
     /// Call this before disconnecting vhost-user frontends to suppress
@@ -370,6 +514,7 @@ impl Router {
         self.reactor_thread
             .join()
             .map_err(|_| io::Error::other("Reactor thread panicked"))?;
+        self.registry.unregister(&self.reactor_id);
 
         // Signal clean shutdown to vhost thread before disconnecting
         self.shutdown_flag.store(true, Ordering::Relaxed);
@@ -391,10 +536,160 @@ impl Router {
         }
 
         // Delete TUN device
-        TunDevice::delete(&self.tun_name).await?;
+        TunDevice::delete_in(&self.tun_name, self.netns.as_ref()).await?;
 
         info!(name = %self.tun_name, "Router stopped");
 
         Ok(())
     }
 }
+
+impl ReactorSetup {
+    /// Create the TUN device, register a reactor for it and run the
+    /// reactor on its own thread.
+    async fn start(
+        &self,
+        name: &str,
+        netns: Option<&Netns>,
+        registry: &Arc<ReactorRegistry>,
+    ) -> io::Result<RunningReactor> {
+        // Create TUN device (L3 mode - no IP address, only routes)
+        let tun = TunDevice::create_in(name, netns).await?;
+        tun.set_up().await?;
+
+        // Store if_index before consuming TUN device
+        let tun_if_index = tun.if_index;
+        let tun_file = tun.into_file();
+
+        // Allocate buffers
+        let buffers_size = (self.rx_count + self.tx_count) * self.buf_size;
+        let buffers = match &self.hugepages {
+            Some(hugepages) => {
+                let node = self.cpu.and_then(hugepage::numa_node_of_cpu);
+                hugepages.allocate(buffers_size, node)?
+            }
+            None => HugePagePool::new(buffers_size).ok_or_else(|| {
+                io::Error::other(
+                    "Failed to allocate huge pages. Run: echo 64 | sudo tee /proc/sys/vm/nr_hugepages",
+                )
+            })?,
+        };
+
+        // Create queues
+        let queues = SimpleRxTxQueues::new(
+            tun_file,
+            buffers,
+            self.buf_size,
+            self.rx_count,
+            self.tx_count,
+        );
+        let (rx_queue, tx_queue) = queues.split();
+
+        // Create inter-reactor communication channels
+        let (packet_tx, packet_rx): (Sender<PacketRef>, Receiver<PacketRef>) = mpsc::channel();
+        let (completion_tx, completion_rx): (Sender<CompletionNotify>, Receiver<CompletionNotify>) =
+            mpsc::channel();
+
+        // Create reactor with optional vhost handshake channel and registry
+        let (handshake_tx, handshake_rx) = match self.nic {
+            Some(_) => {
+                let (tx, rx) = mpsc::sync_channel::<VhostHandshake>(1);
+                (Some(tx), Some(rx))
+            }
+            None => (None, None),
+        };
+        let (reactor, handle) = Reactor::with_registry(
+            rx_queue,
+            tx_queue,
+            handshake_rx,
+            Some(Arc::clone(registry)),
+            Some(packet_rx),
+            Some(completion_rx),
+            self.nic.clone(), // No NIC config for TUN-only reactors
+            None,             // No initial tables (will be populated via commands)
+        );
+        let id = reactor.id();
+
+        // Register the reactor in the registry
+        // Use into_raw_fd() to transfer ownership - otherwise the OwnedFd would close
+        // the fd when dropped, leaving ReactorInfo with an invalid fd
+        let notify_raw_fd = handle.get_notify_fd().into_raw_fd();
+        let reactor_info = if let Some(ref nic) = self.nic {
+            // Vhost interface - register with MAC address for Ethernet header construction
+            let interface_type = InterfaceType::Vhost {
+                device_id: uuid::Uuid::new_v4(), // TODO: Use actual device UUID
+            };
+            ReactorInfo::with_mac(
+                id,
+                notify_raw_fd,
+                packet_tx,
+                completion_tx,
+                interface_type,
+                nic.mac,
+            )
+        } else {
+            // TUN interface - no MAC address needed
+            let interface_type = InterfaceType::Tun {
+                if_index: tun_if_index,
+            };
+            ReactorInfo::new(id, notify_raw_fd, packet_tx, completion_tx, interface_type)
+        };
+        registry.register(reactor_info);
+        info!(id = %id, "Registered reactor in registry");
+
+        // Spawn reactor thread. A panic ends only this reactor; it is
+        // recorded for whoever restarts it.
+        let cpu = self.cpu;
+        let panic = Arc::new(Mutex::new(None));
+        let panic_slot = Arc::clone(&panic);
+        let thread = thread::spawn(move || {
+            if let Some(cpu) = cpu {
+                pin_to_cpu(cpu);
+            }
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| reactor.run()));
+            if let Err(panic) = result {
+                let msg = panic_message(&*panic);
+                tracing::error!(id = %id, panic = %msg, "Reactor panicked");
+                *panic_slot.lock().unwrap() = Some(msg);
+            }
+        });
+
+        // Get reactor notify fd for the vhost daemon
+        let vhost = handshake_tx.map(|tx| (tx, handle.get_notify_fd()));
+
+        Ok(RunningReactor {
+            handle,
+            thread,
+            id,
+            tun_if_index,
+            vhost,
+            panic,
+        })
+    }
+}
+
+/// Message of a caught panic.
+fn panic_message(panic: &(dyn Any + Send)) -> String {
+    if let Some(s) = panic.downcast_ref::<&str>() {
+        s.to_string()
+    } else if let Some(s) = panic.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
+
+/// Pin the calling thread to `cpu`. A reactor that can't be pinned still
+/// works, just with cross-CPU and possibly cross-node traffic.
+fn pin_to_cpu(cpu: usize) {
+    use nix::sched::{CpuSet, sched_setaffinity};
+    use nix::unistd::Pid;
+
+    let mut set = CpuSet::new();
+    let result = set
+        .set(cpu)
+        .and_then(|()| sched_setaffinity(Pid::from_raw(0), &set));
+    if let Err(e) = result {
+        warn!(cpu, error = %e, "Failed to pin reactor thread");
+    }
+}
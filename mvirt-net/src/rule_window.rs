@@ -0,0 +1,121 @@
+//! Time-limited and scheduled security group rules.
+//!
+//! A rule may carry a validity window (`not_before`/`not_after`) and a
+//! weekly schedule such as `Mon-Fri 08:00-18:00` (UTC), checked by
+//! [`mvirt_common::rule_window`]. Reactors only get the rules active when
+//! a NIC's policy is compiled, so this task re-evaluates the NICs with
+//! time-limited rules on an interval and pushes a new policy when one of
+//! their rules opens or closes.
+//!
+//! Connections admitted while a rule was active stay tracked and run
+//! until they idle out.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use mvirt_common::rule_window::{RuleSchedule, TimedRule, active_rules};
+use tokio::time;
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::grpc::storage::SecurityGroupRuleData;
+use crate::grpc::{NetworkManager, Storage};
+
+/// Default evaluation interval in seconds
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 10;
+
+impl TimedRule for SecurityGroupRuleData {
+    type Id = Uuid;
+
+    fn rule_id(&self) -> Uuid {
+        self.id
+    }
+
+    fn not_before(&self) -> Option<DateTime<Utc>> {
+        self.not_before
+    }
+
+    fn not_after(&self) -> Option<DateTime<Utc>> {
+        self.not_after
+    }
+
+    fn schedule(&self) -> Option<RuleSchedule> {
+        self.schedule
+    }
+}
+
+/// Rule window timer handle.
+pub struct RuleWindowTimer {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl RuleWindowTimer {
+    /// Start a new timer task.
+    pub fn start(storage: Arc<Storage>, manager: Arc<NetworkManager>, interval: Duration) -> Self {
+        info!(interval = ?interval, "Security rule window timer started");
+
+        let task = tokio::spawn(timer_loop(storage, manager, interval));
+        Self { task }
+    }
+
+    /// Stop the timer task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Security rule window timer stopped");
+    }
+}
+
+/// Main timer loop.
+async fn timer_loop(storage: Arc<Storage>, manager: Arc<NetworkManager>, interval: Duration) {
+    let mut interval = time::interval(interval);
+    // Active rule set per NIC as last pushed by this task
+    let mut programmed: HashMap<Uuid, HashSet<Uuid>> = HashMap::new();
+
+    loop {
+        interval.tick().await;
+
+        let nics = match storage.list_nics() {
+            Ok(nics) => nics,
+            Err(e) => {
+                warn!(error = %e, "Failed to list NICs for rule windows");
+                continue;
+            }
+        };
+        programmed.retain(|nic_id, _| nics.iter().any(|n| n.id == *nic_id));
+
+        for nic in nics {
+            let rules = match storage.get_all_rules_for_nic(&nic.id) {
+                Ok(rules) => rules,
+                Err(e) => {
+                    warn!(nic_id = %nic.id, error = %e, "Failed to load security rules");
+                    continue;
+                }
+            };
+
+            let Some(active) = active_rules(&rules, Utc::now()) else {
+                programmed.remove(&nic.id);
+                continue;
+            };
+            if programmed.get(&nic.id) == Some(&active) {
+                continue;
+            }
+
+            match manager.apply_security(&nic.id).await {
+                Ok(()) => {
+                    debug!(
+                        nic_id = %nic.id,
+                        active = active.len(),
+                        rules = rules.len(),
+                        "Reapplied time-limited security rules"
+                    );
+                    programmed.insert(nic.id, active);
+                }
+                Err(e) => {
+                    warn!(nic_id = %nic.id, error = %e, "Failed to reapply security rules");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,134 @@
+//! Security groups on the vhost-user backend.
+//!
+//! Groups, rules and attachments are stored like in mvirt-ebpf and served
+//! by the same NetService RPCs, so the control plane programs either
+//! backend the same way. Here they are enforced by the reactor of each NIC
+//! (see [`crate::reactor::firewall`]): the rules of all attached groups are
+//! compiled into one [`SecurityPolicy`] and pushed to the reactor whenever
+//! a group, rule or attachment changes.
+//!
+//! Only rules active at the time of compilation are included;
+//! [`crate::rule_window`] recompiles NICs as windows open and close.
+//! Egress rules are stored and reported but, as on the eBPF backend, not
+//! enforced: egress is allowed and tracked.
+
+use chrono::{DateTime, Utc};
+use ipnet::IpNet;
+use mvirt_common::rule_window::TimedRule;
+use tracing::warn;
+
+use crate::grpc::storage::{RuleDirection, SecurityGroupData, SecurityGroupRuleData};
+use crate::reactor::firewall::{SecurityPolicy, SecurityRule};
+
+/// Reactor form of a stored ingress rule; `None` for egress rules and
+/// rules whose CIDR does not parse.
+pub fn reactor_rule(rule: &SecurityGroupRuleData) -> Option<SecurityRule> {
+    if rule.direction != RuleDirection::Ingress {
+        return None;
+    }
+    let cidr = match rule.cidr.as_deref() {
+        None => None,
+        Some(cidr) => match cidr.parse::<IpNet>() {
+            Ok(net) => Some(net.trunc()),
+            Err(_) => {
+                warn!(rule_id = %rule.id, cidr, "Skipping security rule with invalid CIDR");
+                return None;
+            }
+        },
+    };
+    Some(SecurityRule {
+        protocol: rule.protocol.to_ip_protocol(),
+        ports: rule
+            .port_start
+            .map(|start| (start, rule.port_end.unwrap_or(start))),
+        cidr,
+    })
+}
+
+/// Policy for a NIC with the given groups and their rules at `now`;
+/// `None` when no group is attached and the NIC is not filtered.
+pub fn policy(
+    groups: &[SecurityGroupData],
+    rules: &[SecurityGroupRuleData],
+    now: DateTime<Utc>,
+) -> Option<SecurityPolicy> {
+    if groups.is_empty() {
+        return None;
+    }
+    Some(SecurityPolicy {
+        audit: groups.iter().any(|g| g.audit),
+        ingress: rules
+            .iter()
+            .filter(|r| r.is_active_at(now))
+            .filter_map(reactor_rule)
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::storage::RuleProtocol;
+    use uuid::Uuid;
+
+    fn group(audit: bool) -> SecurityGroupData {
+        SecurityGroupData {
+            id: Uuid::new_v4(),
+            name: "web".to_string(),
+            description: None,
+            audit,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    fn rule(direction: RuleDirection, cidr: Option<&str>) -> SecurityGroupRuleData {
+        SecurityGroupRuleData {
+            id: Uuid::new_v4(),
+            security_group_id: Uuid::new_v4(),
+            direction,
+            protocol: RuleProtocol::Tcp,
+            port_start: Some(443),
+            port_end: Some(443),
+            cidr: cidr.map(str::to_string),
+            description: None,
+            not_before: None,
+            not_after: None,
+            schedule: None,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_policy() {
+        let now = Utc::now();
+        assert_eq!(
+            policy(&[], &[rule(RuleDirection::Ingress, None)], now),
+            None
+        );
+
+        let mut expired = rule(RuleDirection::Ingress, None);
+        expired.not_after = Some(now);
+        let rules = [
+            rule(RuleDirection::Ingress, Some("10.1.2.3/16")),
+            rule(RuleDirection::Egress, None),
+            expired,
+        ];
+        let p = policy(&[group(false), group(true)], &rules, now).unwrap();
+        assert!(p.audit);
+        assert_eq!(
+            p.ingress,
+            vec![SecurityRule {
+                protocol: 6,
+                ports: Some((443, 443)),
+                cidr: Some("10.1.0.0/16".parse().unwrap()),
+            }]
+        );
+
+        // A group without rules still filters
+        let p = policy(&[group(false)], &[], now).unwrap();
+        assert!(!p.audit);
+        assert!(p.ingress.is_empty());
+    }
+}
@@ -0,0 +1,242 @@
+//! Ownership and permissions of vhost-user sockets.
+//!
+//! The daemon runs as root, so the sockets it creates belong to root and a
+//! VMM running as another user can't connect to them. A NIC's socket can
+//! be given an owner, group and mode, each falling back to the daemon's
+//! defaults (`MVIRT_NET_SOCKET_UID`, `MVIRT_NET_SOCKET_GID`,
+//! `MVIRT_NET_SOCKET_MODE`). Connecting takes write permission on the
+//! socket and search permission on every directory above it;
+//! [`SocketAccess::access_error`] checks both for the VMM's user, so a NIC
+//! can report why its VMM won't get in rather than the VMM failing with
+//! EACCES.
+
+use std::fs::Permissions;
+use std::io;
+use std::os::unix::fs::{FileTypeExt, MetadataExt, PermissionsExt, chown};
+use std::path::Path;
+
+/// Permission bit for writing, in the "other" position
+const WRITE: u32 = 0o2;
+/// Permission bit for searching a directory, in the "other" position
+const SEARCH: u32 = 0o1;
+
+/// Owner, group and mode of a NIC's socket; unset fields are left as
+/// created. The owner and group are also the user the VMM is expected to
+/// run as.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SocketAccess {
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    /// Permission bits, e.g. `0o660`
+    pub mode: Option<u32>,
+}
+
+impl SocketAccess {
+    /// These fields, and those of `defaults` where unset.
+    pub fn or(self, defaults: SocketAccess) -> SocketAccess {
+        SocketAccess {
+            uid: self.uid.or(defaults.uid),
+            gid: self.gid.or(defaults.gid),
+            mode: self.mode.or(defaults.mode),
+        }
+    }
+
+    /// Whether nothing is set.
+    pub fn is_empty(&self) -> bool {
+        self.uid.is_none() && self.gid.is_none() && self.mode.is_none()
+    }
+
+    /// Give the socket at `path` its owner, group and mode.
+    pub fn apply(&self, path: &Path) -> io::Result<()> {
+        if self.uid.is_some() || self.gid.is_some() {
+            chown(path, self.uid, self.gid)?;
+        }
+        if let Some(mode) = self.mode {
+            std::fs::set_permissions(path, Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    /// Why a VMM running as this owner and group can't connect to the
+    /// socket at `path`, `None` if it can. Without an owner or group the
+    /// VMM is taken to run as root, which only needs the socket to exist.
+    /// Supplementary groups of the VMM's user are not known.
+    pub fn access_error(&self, path: &Path) -> Option<String> {
+        let meta = match std::fs::metadata(path) {
+            Ok(meta) => meta,
+            Err(e) => return Some(format!("Socket {}: {}", path.display(), e)),
+        };
+        if !meta.file_type().is_socket() {
+            return Some(format!("{} is not a socket", path.display()));
+        }
+        if self.uid.is_none() && self.gid.is_none() {
+            return None;
+        }
+
+        if !self.permits(meta.uid(), meta.gid(), meta.mode(), WRITE) {
+            return Some(format!(
+                "{} can't write to socket {} (owner {}:{}, mode {:o})",
+                self.user(),
+                path.display(),
+                meta.uid(),
+                meta.gid(),
+                meta.mode() & 0o7777
+            ));
+        }
+        match path.parent() {
+            Some(dir) => self.directory_error(dir),
+            None => None,
+        }
+    }
+
+    /// Why a VMM running as this owner and group can't reach sockets in
+    /// `dir`, `None` if it can: it needs search permission on `dir` and
+    /// every directory above it.
+    pub fn directory_error(&self, dir: &Path) -> Option<String> {
+        if self.uid.is_none() && self.gid.is_none() {
+            return None;
+        }
+        for dir in dir.ancestors() {
+            if dir.as_os_str().is_empty() {
+                continue;
+            }
+            let meta = match std::fs::metadata(dir) {
+                Ok(meta) => meta,
+                Err(e) => return Some(format!("Directory {}: {}", dir.display(), e)),
+            };
+            if !self.permits(meta.uid(), meta.gid(), meta.mode(), SEARCH) {
+                return Some(format!(
+                    "{} can't search directory {} (owner {}:{}, mode {:o})",
+                    self.user(),
+                    dir.display(),
+                    meta.uid(),
+                    meta.gid(),
+                    meta.mode() & 0o7777
+                ));
+            }
+        }
+        None
+    }
+
+    /// Whether this owner and group get `want` (an "other" permission bit)
+    /// on a file of `owner`, `group` and `mode`.
+    fn permits(&self, owner: u32, group: u32, mode: u32, want: u32) -> bool {
+        if self.uid == Some(0) {
+            return true;
+        }
+        let shift = if self.uid == Some(owner) {
+            6
+        } else if self.gid == Some(group) {
+            3
+        } else {
+            0
+        };
+        mode & (want << shift) != 0
+    }
+
+    /// The VMM's user for messages.
+    fn user(&self) -> String {
+        match (self.uid, self.gid) {
+            (Some(uid), Some(gid)) => format!("User {}:{}", uid, gid),
+            (Some(uid), None) => format!("User {}", uid),
+            (None, Some(gid)) => format!("Group {}", gid),
+            (None, None) => "root".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixListener;
+    use uuid::Uuid;
+
+    #[test]
+    fn falls_back_per_field() {
+        let defaults = SocketAccess {
+            uid: Some(107),
+            gid: Some(107),
+            mode: Some(0o660),
+        };
+        let nic = SocketAccess {
+            gid: Some(200),
+            ..Default::default()
+        };
+        assert_eq!(
+            nic.or(defaults),
+            SocketAccess {
+                uid: Some(107),
+                gid: Some(200),
+                mode: Some(0o660),
+            }
+        );
+        assert!(SocketAccess::default().is_empty());
+        assert!(!nic.is_empty());
+    }
+
+    #[test]
+    fn checks_the_matching_class() {
+        let vmm = SocketAccess {
+            uid: Some(1000),
+            gid: Some(1000),
+            mode: None,
+        };
+        // Owner bits for the owner, even if the group would allow it
+        assert!(vmm.permits(1000, 0, 0o600, WRITE));
+        assert!(!vmm.permits(1000, 1000, 0o060, WRITE));
+        // Group bits for the group
+        assert!(vmm.permits(0, 1000, 0o660, WRITE));
+        assert!(!vmm.permits(0, 1000, 0o640, WRITE));
+        // Other bits for everyone else
+        assert!(vmm.permits(0, 0, 0o666, WRITE));
+        assert!(!vmm.permits(0, 0, 0o755, WRITE));
+        assert!(vmm.permits(0, 0, 0o755, SEARCH));
+        assert!(!vmm.permits(0, 0, 0o750, SEARCH));
+
+        let root = SocketAccess {
+            uid: Some(0),
+            ..Default::default()
+        };
+        assert!(root.permits(1000, 1000, 0o000, WRITE));
+    }
+
+    #[test]
+    fn reports_inaccessible_sockets() {
+        let dir = std::env::temp_dir().join(format!("mvirt-socket-{}", Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, Permissions::from_mode(0o755)).unwrap();
+        let path = dir.join("nic.sock");
+
+        let vmm = SocketAccess {
+            uid: Some(65534),
+            gid: Some(65534),
+            mode: None,
+        };
+        assert!(vmm.access_error(&path).is_some());
+
+        let _listener = UnixListener::bind(&path).unwrap();
+        std::fs::set_permissions(&path, Permissions::from_mode(0o600)).unwrap();
+        let meta = std::fs::metadata(&path).unwrap();
+        // Unless the test runs as the VMM's user, it can't write
+        if meta.uid() != 65534 {
+            assert!(vmm.access_error(&path).unwrap().contains("can't write"));
+        }
+        assert_eq!(SocketAccess::default().access_error(&path), None);
+
+        // Opened up to everyone
+        SocketAccess {
+            mode: Some(0o666),
+            ..Default::default()
+        }
+        .apply(&path)
+        .unwrap();
+        let error = vmm.access_error(&path);
+        assert!(
+            error.as_ref().is_none_or(|e| e.contains("can't search")),
+            "{:?}",
+            error
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
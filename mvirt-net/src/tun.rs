@@ -9,6 +9,8 @@ use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::AsRawFd;
 use tracing::{debug, info, warn};
 
+use crate::netns::{self, Netns};
+
 const TUNSETIFF: nix::libc::Ioctl = 0x400454ca as nix::libc::Ioctl;
 const TUNSETVNETHDRSZ: nix::libc::Ioctl = 0x400454d8 as nix::libc::Ioctl;
 const TUNSETOFFLOAD: nix::libc::Ioctl = 0x400454d0 as nix::libc::Ioctl;
@@ -46,10 +48,44 @@ pub struct TunDevice {
     pub name: String,
     file: ManuallyDrop<File>,
     pub if_index: u32,
+    /// Namespace the device lives in, `None` for the host's
+    netns: Option<Netns>,
 }
 
 impl TunDevice {
     pub async fn create(name: &str) -> io::Result<Self> {
+        Self::create_in(name, None).await
+    }
+
+    /// Create the device inside `netns`, or in the host namespace.
+    pub async fn create_in(name: &str, netns: Option<&Netns>) -> io::Result<Self> {
+        let file = match netns {
+            Some(netns) => netns.run(|| Self::open(name))?,
+            None => Self::open(name)?,
+        };
+
+        let handle = netns::netlink(netns)?;
+
+        let if_index = Self::get_interface_index(&handle, name)
+            .await
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Interface not found"))?;
+
+        info!(
+            name,
+            if_index,
+            netns = netns.map(Netns::name),
+            "TUN device created"
+        );
+
+        Ok(TunDevice {
+            name: name.to_string(),
+            file: ManuallyDrop::new(file),
+            if_index,
+            netns: netns.cloned(),
+        })
+    }
+
+    fn open(name: &str) -> io::Result<File> {
         let file = OpenOptions::new()
             .read(true)
             .write(true)
@@ -91,20 +127,7 @@ impl TunDevice {
             return Err(io::Error::last_os_error());
         }
 
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
-
-        let if_index = Self::get_interface_index(&handle, name)
-            .await
-            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "Interface not found"))?;
-
-        info!(name, if_index, "TUN device created");
-
-        Ok(TunDevice {
-            name: name.to_string(),
-            file: ManuallyDrop::new(file),
-            if_index,
-        })
+        Ok(file)
     }
 
     async fn get_interface_index(handle: &Handle, name: &str) -> Option<u32> {
@@ -116,8 +139,7 @@ impl TunDevice {
     }
 
     pub async fn set_up(&self) -> io::Result<()> {
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
+        let handle = netns::netlink(self.netns.as_ref())?;
 
         handle
             .link()
@@ -131,8 +153,7 @@ impl TunDevice {
     }
 
     pub async fn add_address(&self, addr: Ipv4Addr, prefix_len: u8) -> io::Result<()> {
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
+        let handle = netns::netlink(self.netns.as_ref())?;
 
         handle
             .address()
@@ -150,8 +171,7 @@ impl TunDevice {
     /// This adds a route in the kernel's routing table so that traffic for the
     /// given prefix is delivered to this TUN device.
     pub async fn add_route_v4(&self, prefix: Ipv4Addr, prefix_len: u8) -> io::Result<()> {
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
+        let handle = netns::netlink(self.netns.as_ref())?;
 
         match handle
             .route()
@@ -177,8 +197,7 @@ impl TunDevice {
 
     /// Add an IPv6 kernel route via this TUN device.
     pub async fn add_route_v6(&self, prefix: Ipv6Addr, prefix_len: u8) -> io::Result<()> {
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
+        let handle = netns::netlink(self.netns.as_ref())?;
 
         match handle
             .route()
@@ -211,8 +230,12 @@ impl TunDevice {
     }
 
     pub async fn delete(name: &str) -> io::Result<()> {
-        let (connection, handle, _) = rtnetlink::new_connection().map_err(io::Error::other)?;
-        tokio::spawn(connection);
+        Self::delete_in(name, None).await
+    }
+
+    /// Delete the device `name` from `netns`, or from the host namespace.
+    pub async fn delete_in(name: &str, netns: Option<&Netns>) -> io::Result<()> {
+        let handle = netns::netlink(netns)?;
 
         let if_index = Self::get_interface_index(&handle, name)
             .await
@@ -2,13 +2,15 @@
 
 #![allow(dead_code)]
 
+use crate::reactor::pmtu;
 use std::io;
 use std::os::unix::io::{AsRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::SyncSender;
-use std::sync::{Arc, RwLock};
+use std::sync::{Arc, Mutex, RwLock};
 use tracing::{debug, info, warn};
-use vhost::vhost_user::Listener;
 use vhost::vhost_user::message::{VhostUserProtocolFeatures, VhostUserVirtioFeatures};
+use vhost::vhost_user::{Backend, Listener, VhostUserFrontendReqHandler};
 use vhost_user_backend::{VhostUserBackendMut, VhostUserDaemon, VringMutex, VringT};
 use vm_memory::{ByteValued, GuestMemoryAtomic, GuestMemoryMmap, Le16};
 use vmm_sys_util::epoll::EventSet;
@@ -23,6 +25,7 @@ const VIRTIO_F_RING_EVENT_IDX: u64 = 1 << 29;
 // Virtio-net feature flags
 const VIRTIO_NET_F_CSUM: u64 = 1 << 0;
 const VIRTIO_NET_F_GUEST_CSUM: u64 = 1 << 1;
+const VIRTIO_NET_F_MTU: u64 = 1 << 3;
 const VIRTIO_NET_F_GUEST_TSO4: u64 = 1 << 7;
 const VIRTIO_NET_F_GUEST_TSO6: u64 = 1 << 8;
 const VIRTIO_NET_F_GUEST_ECN: u64 = 1 << 9;
@@ -32,6 +35,10 @@ const VIRTIO_NET_F_HOST_TSO6: u64 = 1 << 12;
 const VIRTIO_NET_F_HOST_ECN: u64 = 1 << 13;
 const VIRTIO_NET_F_HOST_UFO: u64 = 1 << 14;
 const VIRTIO_NET_F_MRG_RXBUF: u64 = 1 << 15;
+const VIRTIO_NET_F_STATUS: u64 = 1 << 16;
+
+/// Link status bit of the config space `status` field
+const VIRTIO_NET_S_LINK_UP: u16 = 1;
 
 /// Number of virtqueues (RX, TX)
 const NUM_QUEUES: usize = 2;
@@ -41,12 +48,15 @@ const QUEUE_SIZE: usize = 256;
 const RX_QUEUE: u16 = 0;
 const TX_QUEUE: u16 = 1;
 
-/// Virtio net config (MAC address + status)
+/// Virtio net config space, up to the MTU
 #[repr(C, packed)]
 #[derive(Clone, Copy, Default)]
 pub struct VirtioNetConfig {
     pub mac: [u8; 6],
     pub status: Le16,
+    /// Only valid with VIRTIO_NET_F_MQ, which we don't offer
+    pub max_virtqueue_pairs: Le16,
+    pub mtu: Le16,
 }
 
 // SAFETY: VirtioNetConfig contains only plain data types
@@ -57,33 +67,175 @@ pub type GuestMemoryMmapAtomic = GuestMemoryAtomic<GuestMemoryMmap>;
 /// Type alias for vring with mutex
 pub type VringType = VringMutex<GuestMemoryMmapAtomic>;
 
-/// Handshake data sent from VhostUserDaemon to Reactor (once)
+/// Handshake data sent from VhostUserDaemon to Reactor (once per
+/// connection and reactor)
+#[derive(Clone)]
 pub struct VhostHandshake {
     pub mem: GuestMemoryMmapAtomic,
     pub vrings: Vec<VringType>,
 }
 
+/// Link state of a vhost-user NIC as the guest sees it. Shared between
+/// whoever sets it and the backend of the current connection, which tells
+/// the guest about changes. Clones share the state.
+#[derive(Clone)]
+pub struct VhostLink {
+    inner: Arc<LinkInner>,
+}
+
+struct LinkInner {
+    up: AtomicBool,
+    /// Backend-initiated message channel of the current connection
+    backend: Mutex<Option<Backend>>,
+}
+
+impl VhostLink {
+    pub fn new(up: bool) -> Self {
+        VhostLink {
+            inner: Arc::new(LinkInner {
+                up: AtomicBool::new(up),
+                backend: Mutex::new(None),
+            }),
+        }
+    }
+
+    pub fn is_up(&self) -> bool {
+        self.inner.up.load(Ordering::Relaxed)
+    }
+
+    /// Set the link state and, if it changed, raise a config change
+    /// interrupt so the guest driver re-reads the status.
+    pub fn set_up(&self, up: bool) {
+        if self.inner.up.swap(up, Ordering::Relaxed) == up {
+            return;
+        }
+        info!(up, "vhost-user link state changed");
+        if let Some(backend) = self.inner.backend.lock().unwrap().as_ref()
+            && let Err(e) = backend.handle_config_change()
+        {
+            warn!(error = ?e, "Failed to notify guest of link state change");
+        }
+    }
+
+    fn set_backend(&self, backend: Option<Backend>) {
+        *self.inner.backend.lock().unwrap() = backend;
+    }
+}
+
+/// The reactor serving a vhost-user NIC's queues. Shared between the
+/// router and the backend of the current connection, so a reactor started
+/// in place of a crashed one can take over a connected guest: attaching it
+/// hands it the connection's handshake again. Clones share the state.
+#[derive(Clone, Default)]
+pub struct ReactorAttachment {
+    inner: Arc<Mutex<Attachment>>,
+}
+
+#[derive(Default)]
+struct Attachment {
+    handshake_tx: Option<SyncSender<VhostHandshake>>,
+    /// Fd to signal the reactor for queue processing
+    notify: Option<OwnedFd>,
+    /// Handshake of the current connection, once it was made
+    handshake: Option<VhostHandshake>,
+}
+
+impl Attachment {
+    fn send(&self, handshake: VhostHandshake) -> bool {
+        let Some(ref handshake_tx) = self.handshake_tx else {
+            return false;
+        };
+        match handshake_tx.try_send(handshake) {
+            Ok(()) => {
+                info!("Handshake sent to reactor");
+                true
+            }
+            Err(e) => {
+                warn!(?e, "Failed to send handshake to reactor");
+                false
+            }
+        }
+    }
+
+    fn signal(&self) {
+        if let Some(ref notify) = self.notify {
+            let buf: u64 = 1;
+            unsafe {
+                nix::libc::write(
+                    notify.as_raw_fd(),
+                    &buf as *const u64 as *const nix::libc::c_void,
+                    8,
+                );
+            }
+            debug!("Signaled reactor for vhost queue processing");
+        }
+    }
+}
+
+impl ReactorAttachment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve the queues by the reactor behind `handshake_tx` and `notify`
+    /// from now on. With a guest connected, the reactor gets its handshake
+    /// right away.
+    pub fn attach(&self, handshake_tx: SyncSender<VhostHandshake>, notify: OwnedFd) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.handshake_tx = Some(handshake_tx);
+        inner.notify = Some(notify);
+        if let Some(handshake) = inner.handshake.clone()
+            && inner.send(handshake)
+        {
+            // Let it pick up whatever the guest queued in the meantime
+            inner.signal();
+        }
+    }
+
+    fn is_attached(&self) -> bool {
+        self.inner.lock().unwrap().handshake_tx.is_some()
+    }
+
+    /// Hand the connection's vrings and memory to the reactor. Returns
+    /// whether it got them.
+    fn handshake(&self, handshake: VhostHandshake) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        inner.handshake = Some(handshake.clone());
+        inner.send(handshake)
+    }
+
+    /// Forget the handshake of a connection that ended.
+    fn disconnected(&self) {
+        self.inner.lock().unwrap().handshake = None;
+    }
+
+    fn signal(&self) {
+        self.inner.lock().unwrap().signal();
+    }
+}
+
 /// The vhost-user net backend
 pub struct VhostUserNetBackend {
     event_idx: bool,
     mem: Option<GuestMemoryMmapAtomic>,
     config: VirtioNetConfig,
     exit_event: Option<(EventConsumer, EventNotifier)>,
-    /// One-shot handshake channel to reactor
-    handshake_tx: Option<SyncSender<VhostHandshake>>,
-    /// Fd to signal reactor for queue processing
-    reactor_notify: Option<OwnedFd>,
+    /// Reactor serving the queues
+    reactor: ReactorAttachment,
     /// Whether handshake has been completed
     handshake_done: bool,
     /// Store vrings for set_event_idx propagation
     vrings: Option<Vec<VringType>>,
+    /// Link state reported in the config space
+    link: VhostLink,
 }
 
 impl VhostUserNetBackend {
     pub fn new(
         mac: [u8; 6],
-        handshake_tx: Option<SyncSender<VhostHandshake>>,
-        reactor_notify: Option<OwnedFd>,
+        mtu: u16,
+        link: VhostLink,
+        reactor: ReactorAttachment,
     ) -> io::Result<Self> {
         let exit_event = new_event_consumer_and_notifier(EventFlag::CLOEXEC).ok();
 
@@ -93,12 +245,14 @@ impl VhostUserNetBackend {
             config: VirtioNetConfig {
                 mac,
                 status: Le16::default(),
+                max_virtqueue_pairs: Le16::from(1),
+                mtu: Le16::from(mtu),
             },
             exit_event,
-            handshake_tx,
-            reactor_notify,
+            reactor,
             handshake_done: false,
             vrings: None,
+            link,
         })
     }
 
@@ -109,9 +263,9 @@ impl VhostUserNetBackend {
             return;
         }
 
-        let Some(ref handshake_tx) = self.handshake_tx else {
+        if !self.reactor.is_attached() {
             return;
-        };
+        }
 
         let Some(ref mem) = self.mem else {
             return;
@@ -133,30 +287,12 @@ impl VhostUserNetBackend {
             vrings: vrings_clone,
         };
 
-        match handshake_tx.try_send(handshake) {
-            Ok(()) => {
-                info!("Handshake sent to reactor");
-                self.handshake_done = true;
-            }
-            Err(e) => {
-                warn!(?e, "Failed to send handshake to reactor");
-            }
-        }
+        self.handshake_done = self.reactor.handshake(handshake);
     }
 
     /// Signal the reactor to process vhost queues
     fn signal_reactor(&self) {
-        if let Some(ref notify) = self.reactor_notify {
-            let buf: u64 = 1;
-            unsafe {
-                nix::libc::write(
-                    notify.as_raw_fd(),
-                    &buf as *const u64 as *const nix::libc::c_void,
-                    8,
-                );
-            }
-            debug!("Signaled reactor for vhost queue processing");
-        }
+        self.reactor.signal();
     }
 }
 
@@ -186,11 +322,22 @@ impl VhostUserBackendMut for VhostUserNetBackend {
             | VIRTIO_NET_F_HOST_ECN
         //    | VIRTIO_NET_F_HOST_UFO
             | VIRTIO_NET_F_MRG_RXBUF
+            | VIRTIO_NET_F_MTU
+            | VIRTIO_NET_F_STATUS
             | VhostUserVirtioFeatures::PROTOCOL_FEATURES.bits()
     }
 
     fn protocol_features(&self) -> VhostUserProtocolFeatures {
+        // MQ only lets the frontend ask for our queue count; VIRTIO_NET_F_MQ
+        // itself needs a control queue, so the guest sees a single pair.
+        // NET_MTU is left out: it lets the frontend push its MTU to us,
+        // while ours comes from the network config via VIRTIO_NET_F_MTU.
+        // BACKEND_REQ carries link change notifications.
         VhostUserProtocolFeatures::CONFIG
+            | VhostUserProtocolFeatures::MQ
+            | VhostUserProtocolFeatures::STATUS
+            | VhostUserProtocolFeatures::BACKEND_REQ
+            | VhostUserProtocolFeatures::REPLY_ACK
     }
 
     fn set_event_idx(&mut self, enabled: bool) {
@@ -252,7 +399,13 @@ impl VhostUserBackendMut for VhostUserNetBackend {
     }
 
     fn get_config(&self, offset: u32, size: u32) -> Vec<u8> {
-        let config_bytes = self.config.as_slice();
+        let mut config = self.config;
+        config.status = Le16::from(if self.link.is_up() {
+            VIRTIO_NET_S_LINK_UP
+        } else {
+            0
+        });
+        let config_bytes = config.as_slice();
         let offset = offset as usize;
         let size = size as usize;
 
@@ -269,6 +422,10 @@ impl VhostUserBackendMut for VhostUserNetBackend {
         Ok(())
     }
 
+    fn set_backend_req_fd(&mut self, backend: Backend) {
+        self.link.set_backend(Some(backend));
+    }
+
     fn exit_event(&self, _thread_index: usize) -> Option<(EventConsumer, EventNotifier)> {
         self.exit_event.as_ref().and_then(|(consumer, notifier)| {
             Some((consumer.try_clone().ok()?, notifier.try_clone().ok()?))
@@ -285,8 +442,9 @@ impl VhostUserBackendMut for VhostUserNetBackend {
 pub struct VhostUserNetDevice {
     socket_path: String,
     mac: [u8; 6],
-    handshake_tx: Option<SyncSender<VhostHandshake>>,
-    reactor_notify: Option<OwnedFd>,
+    mtu: u16,
+    link: VhostLink,
+    reactor: ReactorAttachment,
 }
 
 impl VhostUserNetDevice {
@@ -294,45 +452,65 @@ impl VhostUserNetDevice {
         VhostUserNetDevice {
             socket_path: socket_path.into(),
             mac,
-            handshake_tx: None,
-            reactor_notify: None,
+            mtu: pmtu::DEFAULT_MTU,
+            link: VhostLink::new(true),
+            reactor: ReactorAttachment::new(),
         }
     }
 
-    /// Create with handshake channel and reactor notification
+    /// Create with the reactor serving the queues
     pub fn with_reactor(
         socket_path: impl Into<String>,
         mac: [u8; 6],
-        handshake_tx: SyncSender<VhostHandshake>,
-        reactor_notify: OwnedFd,
+        reactor: ReactorAttachment,
     ) -> Self {
         VhostUserNetDevice {
             socket_path: socket_path.into(),
             mac,
-            handshake_tx: Some(handshake_tx),
-            reactor_notify: Some(reactor_notify),
+            mtu: pmtu::DEFAULT_MTU,
+            link: VhostLink::new(true),
+            reactor,
         }
     }
 
+    /// Set the MTU offered to the guest.
+    pub fn with_mtu(mut self, mtu: u16) -> Self {
+        self.mtu = mtu;
+        self
+    }
+
+    /// Report link state from `link` to the guest.
+    pub fn with_link(mut self, link: VhostLink) -> Self {
+        self.link = link;
+        self
+    }
+
     /// Start the vhost-user daemon (blocking, reconnection loop)
     pub fn run(self) -> io::Result<()> {
-        info!(socket = %self.socket_path, "Creating vhost-user-net backend with reconnection support");
+        let listener = self.listen()?;
+        self.serve(listener)
+    }
 
-        // Create listener once - it will be reused for reconnections
+    /// Create the socket, replacing a stale one. The listener is reused
+    /// for reconnections.
+    pub fn listen(&self) -> io::Result<Listener> {
         info!(socket = %self.socket_path, "Creating listener");
-        let mut listener = Listener::new(&self.socket_path, true)
-            .map_err(|e| io::Error::other(format!("listener failed: {:?}", e)))?;
+        Listener::new(&self.socket_path, true)
+            .map_err(|e| io::Error::other(format!("listener failed: {:?}", e)))
+    }
+
+    /// Serve VMs connecting on `listener` (blocking, reconnection loop)
+    pub fn serve(self, mut listener: Listener) -> io::Result<()> {
+        info!(socket = %self.socket_path, "Creating vhost-user-net backend with reconnection support");
 
         loop {
             // Create fresh backend for each connection
             info!("Creating new backend for connection");
             let backend = Arc::new(RwLock::new(VhostUserNetBackend::new(
                 self.mac,
-                self.handshake_tx.clone(),
-                self.reactor_notify
-                    .as_ref()
-                    .map(|fd| fd.try_clone())
-                    .transpose()?,
+                self.mtu,
+                self.link.clone(),
+                self.reactor.clone(),
             )?));
 
             info!("Creating VhostUserDaemon");
@@ -349,7 +527,11 @@ impl VhostUserNetDevice {
                 .map_err(|e| io::Error::other(format!("daemon start failed: {:?}", e)))?;
 
             info!("VM connected, calling daemon.wait()");
-            match daemon.wait() {
+            let result = daemon.wait();
+            // The channel and handshake belong to the connection that just ended
+            self.link.set_backend(None);
+            self.reactor.disconnected();
+            match result {
                 Ok(()) => {
                     info!("VM disconnected cleanly, waiting for reconnection...");
                 }
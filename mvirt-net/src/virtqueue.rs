@@ -15,6 +15,8 @@ pub struct VirtqueueBuffer {
     pub len: u32,
     /// Index for io_uring fixed buffer registration
     pub buf_index: u16,
+    /// Registered with io_uring; buffers added after registration are not
+    pub fixed: bool,
 }
 
 /// A chain of descriptors from the virtqueue
@@ -43,6 +45,15 @@ pub trait RxVirtqueue: Send {
 
     /// Signal that RX buffers are available.
     fn notify(&self);
+
+    /// Number of buffers in the pool, available or not.
+    fn capacity(&self) -> usize;
+
+    /// Number of buffers ready to be popped.
+    fn available(&self) -> usize;
+
+    /// Add up to `count` buffers to the pool. Returns how many were added.
+    fn grow(&mut self, count: usize) -> usize;
 }
 
 /// Trait for TX virtqueue (sending packets)
@@ -95,6 +106,7 @@ impl SimpleRxTxQueues {
             base_ptr,
             buffer_size,
             buffer_count: rx_count,
+            grown: Vec::new(),
             available: (0..rx_count as u64).collect(),
         };
 
@@ -123,8 +135,10 @@ pub struct SimpleRxQueue {
     fd: RawFd,
     base_ptr: *mut u8,
     buffer_size: usize,
-    #[allow(dead_code)]
+    /// Buffers in the initial, registered pool
     buffer_count: usize,
+    /// Buffers added by `grow`, chain IDs from `buffer_count` on
+    grown: Vec<*mut u8>,
     available: VecDeque<u64>,
 }
 
@@ -138,14 +152,20 @@ impl RxVirtqueue for SimpleRxQueue {
     fn pop_available(&mut self) -> Option<DescriptorChain> {
         let chain_id = self.available.pop_front()?;
         let idx = chain_id as usize;
-        let ptr = unsafe { self.base_ptr.add(idx * self.buffer_size) };
+        let fixed = idx < self.buffer_count;
+        let ptr = if fixed {
+            unsafe { self.base_ptr.add(idx * self.buffer_size) }
+        } else {
+            self.grown[idx - self.buffer_count]
+        };
 
         Some(DescriptorChain {
             chain_id,
             buffer: VirtqueueBuffer {
                 ptr,
                 len: self.buffer_size as u32,
-                buf_index: idx as u16,
+                buf_index: if fixed { idx as u16 } else { 0 },
+                fixed,
             },
         })
     }
@@ -164,6 +184,31 @@ impl RxVirtqueue for SimpleRxQueue {
     }
 
     fn notify(&self) {}
+
+    fn capacity(&self) -> usize {
+        self.buffer_count + self.grown.len()
+    }
+
+    fn available(&self) -> usize {
+        self.available.len()
+    }
+
+    fn grow(&mut self, count: usize) -> usize {
+        if count == 0 {
+            return 0;
+        }
+        let Some(pool) = HugePagePool::new(count * self.buffer_size) else {
+            return 0;
+        };
+        let first = self.capacity() as u64;
+        self.grown
+            .extend((0..count).map(|i| unsafe { pool.ptr().add(i * self.buffer_size) }));
+        self.available.extend(first..first + count as u64);
+        // Never unmapped, like the initial pool: other reactors may still
+        // hold zero-copy references into it
+        std::mem::forget(pool);
+        count
+    }
 }
 
 /// Simple TX queue implementation
@@ -196,6 +241,7 @@ impl TxVirtqueue for SimpleTxQueue {
                 ptr,
                 len: self.buffer_size as u32,
                 buf_index: idx as u16,
+                fixed: true,
             },
         })
     }
@@ -9,44 +9,74 @@
 //! gone). No transition enum, no polling.
 
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
+use mvirt_common::request_id::RequestIdChannel;
 use mvirt_daemon_protos::net::net_service_client::NetServiceClient;
 use mvirt_daemon_protos::net::{WatchNetworksRequest, WatchNicsRequest};
 use mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient;
-use mvirt_daemon_protos::vmm::WatchVmsRequest;
+use mvirt_daemon_protos::vmm::{ListVmsRequest, WatchVmsRequest};
 use mvirt_daemon_protos::zfs::zfs_service_client::ZfsServiceClient;
 use mvirt_daemon_protos::zfs::{WatchTemplatesRequest, WatchVolumesRequest};
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, Mutex};
 use tokio_stream::wrappers::ReceiverStream;
 use tokio_stream::Stream;
 use tokio_stream::StreamExt;
-use tonic::transport::Channel;
 use tonic::{Request, Response, Status};
 use tracing::{debug, info};
 
 use crate::proto::node::node_agent_server::NodeAgent;
 use crate::proto::node_event::Kind as NodeEventKind;
 use crate::proto::{
-    CurrentResourcesRequest, IdentifyRequest, IdentifyResponse, NetworkStateChanged,
-    NicStateChanged, NodeEvent, NodeResources, TemplateStateChanged, VmStateChanged,
-    VolumeStateChanged, WatchEventsRequest,
+    CurrentResourcesRequest, DaemonKind, DaemonVersion, DaemonVersionsRequest,
+    DaemonVersionsResponse, IdentifyRequest, IdentifyResponse, NetworkStateChanged,
+    NicStateChanged, NodeEvent, NodeResources, ProbeTcpRequest, ProbeTcpResponse,
+    QueryUsageHistoryRequest, RestartDaemonRequest, RestartDaemonResponse, SyncSpecsRequest,
+    SyncSpecsResponse, TemplateStateChanged, UsageHistory, VmStateChanged, VolumeStateChanged,
+    WatchEventsRequest,
 };
+use crate::restart::{self, daemon_name, DaemonUnits, Daemons};
+use crate::spec_cache::SpecCache;
+use crate::telemetry::HostResources;
+use crate::usage_history::UsageRecorder;
 
 const EVENT_CHANNEL_CAPACITY: usize = 64;
 const RESUBSCRIBE_DELAY: std::time::Duration = std::time::Duration::from_secs(2);
+const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+const MAX_PROBE_TIMEOUT: Duration = Duration::from_secs(30);
 
 #[derive(Clone)]
 pub struct NodeAgentService {
     pub node_id: String,
     pub name: String,
     pub address: String,
-    pub resources: NodeResources,
+    /// Latest host sample, refreshed in the background.
+    pub resources: HostResources,
+    /// Usage history kept on disk.
+    pub usage: UsageRecorder,
     pub agent_version: String,
     /// Typed gRPC clients for each local daemon. We subscribe to each
     /// daemon's Watch* stream per cplane WatchEvents call.
-    pub vmm: VmServiceClient<Channel>,
-    pub zfs: ZfsServiceClient<Channel>,
-    pub net: NetServiceClient<Channel>,
+    pub vmm: VmServiceClient<RequestIdChannel>,
+    pub zfs: ZfsServiceClient<RequestIdChannel>,
+    pub net: NetServiceClient<RequestIdChannel>,
+    /// systemd units restarted by RestartDaemon.
+    pub units: DaemonUnits,
+    /// Serializes RestartDaemon calls.
+    pub restart_lock: Arc<Mutex<()>>,
+    /// Desired VM state from the api, kept for offline operation.
+    pub specs: SpecCache,
+}
+
+impl NodeAgentService {
+    fn daemons(&self) -> Daemons {
+        Daemons {
+            vmm: self.vmm.clone(),
+            zfs: self.zfs.clone(),
+            net: self.net.clone(),
+        }
+    }
 }
 
 #[tonic::async_trait]
@@ -62,7 +92,7 @@ impl NodeAgent for NodeAgentService {
             node_id: self.node_id.clone(),
             name: self.name.clone(),
             address: self.address.clone(),
-            resources: Some(self.resources),
+            resources: Some(self.resources.current()),
             labels: Default::default(),
             agent_version: self.agent_version.clone(),
         }))
@@ -73,6 +103,7 @@ impl NodeAgent for NodeAgentService {
         _request: Request<WatchEventsRequest>,
     ) -> Result<Response<Self::WatchEventsStream>, Status> {
         let (tx, rx) = mpsc::channel::<Result<NodeEvent, Status>>(EVENT_CHANNEL_CAPACITY);
+        tokio::spawn(replay_vm_states(self.vmm.clone(), tx.clone()));
         tokio::spawn(forward_vm_events(self.vmm.clone(), tx.clone()));
         tokio::spawn(forward_volume_events(self.zfs.clone(), tx.clone()));
         tokio::spawn(forward_template_events(self.zfs.clone(), tx.clone()));
@@ -85,7 +116,154 @@ impl NodeAgent for NodeAgentService {
         &self,
         _request: Request<CurrentResourcesRequest>,
     ) -> Result<Response<NodeResources>, Status> {
-        Ok(Response::new(self.resources))
+        Ok(Response::new(self.resources.current()))
+    }
+
+    async fn daemon_versions(
+        &self,
+        _request: Request<DaemonVersionsRequest>,
+    ) -> Result<Response<DaemonVersionsResponse>, Status> {
+        let daemons = self.daemons();
+        let mut versions = Vec::new();
+        for daemon in [DaemonKind::Vmm, DaemonKind::Zfs, DaemonKind::Net] {
+            let version = daemons.version(daemon).await;
+            versions.push(DaemonVersion {
+                daemon: daemon as i32,
+                reachable: version.is_ok(),
+                version: version.unwrap_or_default(),
+            });
+        }
+        Ok(Response::new(DaemonVersionsResponse {
+            agent_version: self.agent_version.clone(),
+            daemons: versions,
+        }))
+    }
+
+    async fn restart_daemon(
+        &self,
+        request: Request<RestartDaemonRequest>,
+    ) -> Result<Response<RestartDaemonResponse>, Status> {
+        let req = request.into_inner();
+        let daemon = DaemonKind::try_from(req.daemon)
+            .map_err(|_| Status::invalid_argument("unknown daemon"))?;
+        let unit = self
+            .units
+            .unit(daemon)
+            .ok_or_else(|| Status::invalid_argument("daemon not specified"))?;
+        let _guard = self
+            .restart_lock
+            .try_lock()
+            .map_err(|_| Status::aborted("another daemon restart is in progress on this node"))?;
+
+        let daemons = self.daemons();
+        let previous_version = daemons.version(daemon).await.unwrap_or_default();
+
+        let mut undrained_vms = Vec::new();
+        if daemon == DaemonKind::Net {
+            undrained_vms = daemons
+                .drain_vhost(Duration::from_secs(req.drain_timeout_secs.into()))
+                .await
+                .map_err(|e| {
+                    Status::unavailable(format!("cannot list VMs to drain: {}", e.message()))
+                })?;
+            if !undrained_vms.is_empty() && !req.force {
+                return Err(Status::failed_precondition(format!(
+                    "VMs with vhost-user NICs still running: {}",
+                    undrained_vms.join(", ")
+                )));
+            }
+        }
+
+        restart::restart_unit(unit).await?;
+        let version = daemons.wait_ready(daemon).await?;
+        info!(
+            daemon = daemon_name(daemon),
+            previous_version = %previous_version,
+            version = %version,
+            "daemon restarted"
+        );
+        Ok(Response::new(RestartDaemonResponse {
+            previous_version,
+            version,
+            undrained_vms,
+        }))
+    }
+
+    async fn sync_specs(
+        &self,
+        request: Request<SyncSpecsRequest>,
+    ) -> Result<Response<SyncSpecsResponse>, Status> {
+        let vms = request.into_inner().vms;
+        debug!(vms = vms.len(), "caching VM specs");
+        self.specs
+            .replace(vms)
+            .map_err(|e| Status::internal(format!("persist spec cache: {e:#}")))?;
+        Ok(Response::new(SyncSpecsResponse {}))
+    }
+
+    async fn query_usage_history(
+        &self,
+        request: Request<QueryUsageHistoryRequest>,
+    ) -> Result<Response<UsageHistory>, Status> {
+        let req = request.into_inner();
+        Ok(Response::new(self.usage.query(req.days, req.max_points)))
+    }
+
+    async fn probe_tcp(
+        &self,
+        request: Request<ProbeTcpRequest>,
+    ) -> Result<Response<ProbeTcpResponse>, Status> {
+        let req = request.into_inner();
+        let ip: std::net::IpAddr = req
+            .address
+            .parse()
+            .map_err(|_| Status::invalid_argument(format!("invalid address '{}'", req.address)))?;
+        let port = u16::try_from(req.port)
+            .ok()
+            .filter(|&p| p != 0)
+            .ok_or_else(|| Status::invalid_argument(format!("invalid port {}", req.port)))?;
+        let timeout = match req.timeout_ms {
+            0 => DEFAULT_PROBE_TIMEOUT,
+            ms => Duration::from_millis(ms.into()).min(MAX_PROBE_TIMEOUT),
+        };
+        let addr = std::net::SocketAddr::new(ip, port);
+        let error = match tokio::time::timeout(timeout, tokio::net::TcpStream::connect(addr)).await
+        {
+            Ok(Ok(_)) => String::new(),
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => format!("no answer within {}ms", timeout.as_millis()),
+        };
+        Ok(Response::new(ProbeTcpResponse {
+            open: error.is_empty(),
+            error,
+        }))
+    }
+}
+
+/// Send the current state of every VM once, so a freshly connected api
+/// catches up on changes made while the tunnel was down (e.g. VMs the
+/// offline supervisor restarted).
+async fn replay_vm_states(
+    mut vmm: VmServiceClient<RequestIdChannel>,
+    tx: mpsc::Sender<Result<NodeEvent, Status>>,
+) {
+    let vms = match vmm.list_vms(ListVmsRequest {}).await {
+        Ok(resp) => resp.into_inner().vms,
+        Err(s) => {
+            debug!(error = %s, "vmm.ListVms for state replay failed");
+            return;
+        }
+    };
+    for vm in vms {
+        let node_event = NodeEvent {
+            kind: Some(NodeEventKind::VmState(VmStateChanged {
+                vm_id: vm.id.clone(),
+                vm: Some(vm),
+            })),
+        };
+        if tx.send(Ok(node_event)).await.is_err() {
+            return;
+        }
     }
 }
 
@@ -93,7 +271,7 @@ impl NodeAgent for NodeAgentService {
 /// Watch stream and a closure that wraps each event into a NodeEvent,
 /// run the resubscribe-on-error loop.
 async fn forward_vm_events(
-    mut vmm: VmServiceClient<Channel>,
+    mut vmm: VmServiceClient<RequestIdChannel>,
     tx: mpsc::Sender<Result<NodeEvent, Status>>,
 ) {
     use mvirt_daemon_protos::vmm::VmEventType;
@@ -119,9 +297,15 @@ async fn forward_vm_events(
                     break;
                 }
             };
-            let is_deleted = VmEventType::try_from(ev.r#type)
-                .map(|t| t == VmEventType::VmEventDeleted)
-                .unwrap_or(false);
+            let ty = VmEventType::try_from(ev.r#type).ok();
+            // Drift findings don't change the VM's state
+            if matches!(
+                ty,
+                Some(VmEventType::VmEventDriftDetected | VmEventType::VmEventDriftResolved)
+            ) {
+                continue;
+            }
+            let is_deleted = ty == Some(VmEventType::VmEventDeleted);
             let vm = if is_deleted { None } else { ev.vm };
             let node_event = NodeEvent {
                 kind: Some(NodeEventKind::VmState(VmStateChanged {
@@ -138,7 +322,7 @@ async fn forward_vm_events(
 }
 
 async fn forward_volume_events(
-    mut zfs: ZfsServiceClient<Channel>,
+    mut zfs: ZfsServiceClient<RequestIdChannel>,
     tx: mpsc::Sender<Result<NodeEvent, Status>>,
 ) {
     loop {
@@ -178,7 +362,7 @@ async fn forward_volume_events(
 }
 
 async fn forward_template_events(
-    mut zfs: ZfsServiceClient<Channel>,
+    mut zfs: ZfsServiceClient<RequestIdChannel>,
     tx: mpsc::Sender<Result<NodeEvent, Status>>,
 ) {
     loop {
@@ -219,7 +403,7 @@ async fn forward_template_events(
 }
 
 async fn forward_nic_events(
-    mut net: NetServiceClient<Channel>,
+    mut net: NetServiceClient<RequestIdChannel>,
     tx: mpsc::Sender<Result<NodeEvent, Status>>,
 ) {
     loop {
@@ -259,7 +443,7 @@ async fn forward_nic_events(
 }
 
 async fn forward_network_events(
-    mut net: NetServiceClient<Channel>,
+    mut net: NetServiceClient<RequestIdChannel>,
     tx: mpsc::Sender<Result<NodeEvent, Status>>,
 ) {
     loop {
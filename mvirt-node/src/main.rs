@@ -12,19 +12,30 @@ mod agent_impl;
 mod onboarding;
 mod proto;
 mod proxy;
+mod restart;
+mod spec_cache;
+mod telemetry;
 mod tunnel;
+mod usage_history;
 
 use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
 
 use anyhow::{anyhow, Context, Result};
 use clap::Parser;
 use http::Uri;
+use mvirt_common::events::EventBus;
+use mvirt_common::request_id::RequestIdChannel;
 use tracing::{info, warn};
 
 use crate::agent_impl::NodeAgentService;
-use crate::proto::NodeResources;
 use crate::proxy::DaemonProxy;
+use crate::restart::DaemonUnits;
+use crate::spec_cache::SpecCache;
+use crate::telemetry::{HostResources, Overrides};
 use crate::tunnel::ProxyBundle;
+use crate::usage_history::UsageRecorder;
 
 #[derive(Parser, Debug)]
 #[command(name = "mvirt-node", version, about)]
@@ -70,6 +81,18 @@ struct Args {
     #[arg(long, default_value = "http://[::1]:50054")]
     net_endpoint: String,
 
+    /// systemd unit of mvirt-vmm, restarted during upgrades
+    #[arg(long, default_value = "mvirt-vmm.service")]
+    vmm_unit: String,
+
+    /// systemd unit of mvirt-zfs, restarted during upgrades
+    #[arg(long, default_value = "mvirt-zfs.service")]
+    zfs_unit: String,
+
+    /// systemd unit of mvirt-net/ebpf, restarted during upgrades
+    #[arg(long, default_value = "mvirt-net.service")]
+    net_unit: String,
+
     /// CPU cores available on this node (auto-detected if absent)
     #[arg(long)]
     cpu_cores: Option<u32>,
@@ -78,17 +101,14 @@ struct Args {
     #[arg(long)]
     memory_mb: Option<u64>,
 
-    /// Storage in GB available on this node
-    #[arg(long, default_value_t = 0)]
-    storage_gb: u64,
+    /// Storage in GB available on this node (ZFS pool size if absent)
+    #[arg(long)]
+    storage_gb: Option<u64>,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    mvirt_log::tracing_setup::init(
-        "mvirt_node=info,tonic=warn,tower=warn,hyper=warn",
-        &[],
-    );
+    mvirt_log::tracing_setup::init("mvirt_node=info,tonic=warn,tower=warn,hyper=warn", &[]);
 
     let args = Args::parse();
     let agent_version = env!("CARGO_PKG_VERSION");
@@ -143,25 +163,13 @@ async fn main() -> Result<()> {
             .map(|h| h.to_string_lossy().into_owned())
             .unwrap_or_else(|_| "unknown".to_string())
     });
-    let cpu_cores = args.cpu_cores.unwrap_or_else(detect_cpu_cores);
-    let memory_mb = args.memory_mb.unwrap_or_else(detect_memory_mb);
-
     info!(
         node_id = %pki.state.node_id,
         name = %node_name,
         cluster_slug = %pki.state.cluster_slug,
-        cpu_cores, memory_mb, storage_gb = args.storage_gb,
         "starting mvirt-node"
     );
 
-    let resources = NodeResources {
-        cpu_cores,
-        memory_mb,
-        storage_gb: args.storage_gb,
-        available_cpu_cores: cpu_cores,
-        available_memory_mb: memory_mb,
-        available_storage_gb: args.storage_gb,
-    };
     // Typed gRPC clients for the local daemons. mvirt-node subscribes to
     // each daemon's Watch* stream and forwards events upstream to the
     // cplane via NodeAgent.WatchEvents. tonic Channels reconnect lazily,
@@ -178,15 +186,72 @@ async fn main() -> Result<()> {
         parse_uri(&args.net_endpoint, "net_endpoint")?.to_string(),
     )?
     .connect_lazy();
+
+    let resources = HostResources::spawn(
+        mvirt_daemon_protos::zfs::zfs_service_client::ZfsServiceClient::new(zfs_channel.clone()),
+        Overrides {
+            cpu_cores: args.cpu_cores,
+            memory_mb: args.memory_mb,
+            storage_gb: args.storage_gb,
+        },
+        &args.state_dir,
+    )
+    .await;
+    match EventBus::new("node").and_then(|bus| bus.subscribe()) {
+        Ok(subscription) => {
+            tokio::spawn(telemetry::follow_host_events(
+                resources.clone(),
+                subscription,
+            ));
+        }
+        Err(e) => warn!(error = %e, "no host event bus; sampling on the interval only"),
+    }
+    let sample = resources.current();
+    info!(
+        cpu_cores = sample.cpu_cores,
+        memory_mb = sample.memory_mb,
+        storage_gb = sample.storage_gb,
+        "host resources sampled"
+    );
+    // Host, pool and VM usage over the last weeks, for capacity trends
+    let usage = UsageRecorder::spawn(
+        resources.clone(),
+        mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient::new(vmm_channel.clone()),
+        &args.state_dir,
+    );
+    // Desired VM state from the last api push, held up by the supervisor
+    // while the tunnel is down.
+    let specs = SpecCache::load(&args.state_dir);
+    let online = Arc::new(AtomicBool::new(false));
+    tokio::spawn(spec_cache::supervise(
+        specs.clone(),
+        mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient::new(vmm_channel.clone()),
+        online.clone(),
+    ));
+
     let agent = NodeAgentService {
         node_id: pki.state.node_id.clone(),
         name: node_name,
         address: String::new(),
         resources,
+        usage,
         agent_version: agent_version.to_string(),
-        vmm: mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient::new(vmm_channel),
-        zfs: mvirt_daemon_protos::zfs::zfs_service_client::ZfsServiceClient::new(zfs_channel),
-        net: mvirt_daemon_protos::net::net_service_client::NetServiceClient::new(net_channel),
+        vmm: mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient::new(
+            RequestIdChannel::new(vmm_channel),
+        ),
+        zfs: mvirt_daemon_protos::zfs::zfs_service_client::ZfsServiceClient::new(
+            RequestIdChannel::new(zfs_channel),
+        ),
+        net: mvirt_daemon_protos::net::net_service_client::NetServiceClient::new(
+            RequestIdChannel::new(net_channel),
+        ),
+        units: DaemonUnits {
+            vmm: args.vmm_unit.clone(),
+            zfs: args.zfs_unit.clone(),
+            net: args.net_unit.clone(),
+        },
+        restart_lock: Default::default(),
+        specs,
     };
     let proxies = ProxyBundle {
         vmm: DaemonProxy::new(parse_uri(&args.vmm_endpoint, "vmm_endpoint")?),
@@ -194,7 +259,7 @@ async fn main() -> Result<()> {
         net: DaemonProxy::new(parse_uri(&args.net_endpoint, "net_endpoint")?),
     };
 
-    if let Err(e) = tunnel::run(tunnel_endpoint, pki, agent, proxies).await {
+    if let Err(e) = tunnel::run(tunnel_endpoint, pki, agent, proxies, online).await {
         warn!(error = %e, "tunnel loop terminated");
         return Err(e);
     }
@@ -205,25 +270,3 @@ fn parse_uri(s: &str, label: &str) -> Result<Uri> {
     s.parse::<Uri>()
         .with_context(|| format!("invalid {label}: {s}"))
 }
-
-fn detect_cpu_cores() -> u32 {
-    std::fs::read_to_string("/proc/cpuinfo")
-        .map(|s| s.matches("processor\t:").count() as u32)
-        .unwrap_or(1)
-}
-
-fn detect_memory_mb() -> u64 {
-    std::fs::read_to_string("/proc/meminfo")
-        .ok()
-        .and_then(|s| {
-            s.lines()
-                .find(|l| l.starts_with("MemTotal:"))
-                .and_then(|l| {
-                    l.split_whitespace()
-                        .nth(1)
-                        .and_then(|v| v.parse::<u64>().ok())
-                })
-        })
-        .map(|kb| kb / 1024)
-        .unwrap_or(0)
-}
@@ -0,0 +1,166 @@
+//! Local daemon versions and restarts, driven by the cplane's upgrade
+//! controller through NodeAgent.DaemonVersions / RestartDaemon.
+//!
+//! A restart is `systemctl restart <unit>` followed by polling the
+//! daemon's GetVersion until it answers again. The daemons recover their
+//! state on start; the one thing that doesn't survive is a vhost-user NIC
+//! across a net restart (the backend side of the socket goes away under
+//! the running guest), so net restarts wait for those VMs to drain first.
+
+use std::time::Duration;
+
+use mvirt_common::request_id::RequestIdChannel;
+use mvirt_daemon_protos::net::net_service_client::NetServiceClient;
+use mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient;
+use mvirt_daemon_protos::vmm::{ListVmsRequest, VmState};
+use mvirt_daemon_protos::zfs::zfs_service_client::ZfsServiceClient;
+use tokio::process::Command;
+use tokio::time::Instant;
+use tonic::Status;
+use tracing::{info, warn};
+
+use crate::proto::DaemonKind;
+
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const READY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// systemd units of the local daemons.
+#[derive(Clone, Debug)]
+pub struct DaemonUnits {
+    pub vmm: String,
+    pub zfs: String,
+    pub net: String,
+}
+
+impl DaemonUnits {
+    pub fn unit(&self, daemon: DaemonKind) -> Option<&str> {
+        match daemon {
+            DaemonKind::Vmm => Some(&self.vmm),
+            DaemonKind::Zfs => Some(&self.zfs),
+            DaemonKind::Net => Some(&self.net),
+            DaemonKind::Unspecified => None,
+        }
+    }
+}
+
+pub fn daemon_name(daemon: DaemonKind) -> &'static str {
+    match daemon {
+        DaemonKind::Vmm => "vmm",
+        DaemonKind::Zfs => "zfs",
+        DaemonKind::Net => "net",
+        DaemonKind::Unspecified => "unspecified",
+    }
+}
+
+/// Typed clients for the local daemons.
+#[derive(Clone)]
+pub struct Daemons {
+    pub vmm: VmServiceClient<RequestIdChannel>,
+    pub zfs: ZfsServiceClient<RequestIdChannel>,
+    pub net: NetServiceClient<RequestIdChannel>,
+}
+
+impl Daemons {
+    /// Version reported by `daemon`, or the error if it didn't answer.
+    pub async fn version(&self, daemon: DaemonKind) -> Result<String, Status> {
+        match daemon {
+            DaemonKind::Vmm => Ok(self
+                .vmm
+                .clone()
+                .get_version(mvirt_daemon_protos::vmm::GetVersionRequest {})
+                .await?
+                .into_inner()
+                .version),
+            DaemonKind::Zfs => Ok(self
+                .zfs
+                .clone()
+                .get_version(mvirt_daemon_protos::zfs::GetVersionRequest {})
+                .await?
+                .into_inner()
+                .version),
+            DaemonKind::Net => Ok(self
+                .net
+                .clone()
+                .get_version(mvirt_daemon_protos::net::GetVersionRequest {})
+                .await?
+                .into_inner()
+                .version),
+            DaemonKind::Unspecified => Err(Status::invalid_argument("daemon not specified")),
+        }
+    }
+
+    /// Running VMs with a vhost-user NIC; these lose networking when the
+    /// net daemon restarts.
+    pub async fn vhost_vms(&self) -> Result<Vec<String>, Status> {
+        let vms = self
+            .vmm
+            .clone()
+            .list_vms(ListVmsRequest {})
+            .await?
+            .into_inner()
+            .vms;
+        Ok(vms
+            .into_iter()
+            .filter(|vm| vm.state == VmState::Running as i32)
+            .filter(|vm| {
+                vm.config
+                    .as_ref()
+                    .is_some_and(|c| c.nics.iter().any(|n| n.vhost_socket.is_some()))
+            })
+            .map(|vm| vm.name.unwrap_or(vm.id))
+            .collect())
+    }
+
+    /// Wait up to `timeout` for [`Daemons::vhost_vms`] to empty. Returns
+    /// the VMs still running when it didn't.
+    pub async fn drain_vhost(&self, timeout: Duration) -> Result<Vec<String>, Status> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let vms = self.vhost_vms().await?;
+            if vms.is_empty() || Instant::now() >= deadline {
+                return Ok(vms);
+            }
+            info!(vms = ?vms, "waiting for vhost-user VMs to stop before restarting net");
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Poll `daemon` until it reports a version again.
+    pub async fn wait_ready(&self, daemon: DaemonKind) -> Result<String, Status> {
+        let deadline = Instant::now() + READY_TIMEOUT;
+        loop {
+            match self.version(daemon).await {
+                Ok(version) => return Ok(version),
+                Err(e) if Instant::now() >= deadline => {
+                    return Err(Status::deadline_exceeded(format!(
+                        "{} not serving {}s after restart: {}",
+                        daemon_name(daemon),
+                        READY_TIMEOUT.as_secs(),
+                        e.message()
+                    )))
+                }
+                Err(_) => tokio::time::sleep(READY_POLL_INTERVAL).await,
+            }
+        }
+    }
+}
+
+/// `systemctl restart <unit>`.
+pub async fn restart_unit(unit: &str) -> Result<(), Status> {
+    info!(unit, "restarting daemon");
+    let output = Command::new("systemctl")
+        .args(["restart", unit])
+        .output()
+        .await
+        .map_err(|e| Status::internal(format!("failed to run systemctl: {e}")))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        warn!(unit, stderr = %stderr.trim(), "systemctl restart failed");
+        return Err(Status::internal(format!(
+            "systemctl restart {unit} failed: {}",
+            stderr.trim()
+        )));
+    }
+    Ok(())
+}
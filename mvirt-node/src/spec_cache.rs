@@ -0,0 +1,302 @@
+//! Node-local cache of the api's desired VM state, for offline operation.
+//!
+//! The api pushes the desired power state of this node's VMs through
+//! NodeAgent.SyncSpecs; we persist it as `specs.json` in the state dir.
+//! While the tunnel is down — including after a node restart, before the
+//! first connect — [`supervise`] holds the VMs at that state: a VM that
+//! should run but is stopped gets started again, the same thing the api's
+//! VM reconciler would do. Once the tunnel is up the api drives the daemons
+//! itself and the supervisor stands by; WatchEvents replays every VM's
+//! state on connect so the api learns what happened in the meantime.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient;
+use mvirt_daemon_protos::vmm::{ListVmsRequest, StartVmRequest, VmState};
+use serde::{Deserialize, Serialize};
+use tonic::transport::Channel;
+use tracing::{debug, info, warn};
+
+use crate::proto::CachedVmSpec;
+
+const SPEC_FILE: &str = "specs.json";
+const SUPERVISE_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct CachedVm {
+    vm_id: String,
+    name: String,
+    running: bool,
+    run_once: bool,
+}
+
+impl From<CachedVmSpec> for CachedVm {
+    fn from(spec: CachedVmSpec) -> Self {
+        Self {
+            vm_id: spec.vm_id,
+            name: spec.name,
+            running: spec.running,
+            run_once: spec.run_once,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SpecFile {
+    synced_at: String,
+    vms: Vec<CachedVm>,
+}
+
+/// Last desired state received from the api, mirrored on disk.
+#[derive(Clone)]
+pub struct SpecCache {
+    path: PathBuf,
+    vms: Arc<RwLock<Vec<CachedVm>>>,
+}
+
+impl SpecCache {
+    /// Load the cache from `state_dir`. A missing or unreadable file
+    /// starts empty; the api's next push fills it.
+    pub fn load(state_dir: &Path) -> Self {
+        let path = state_dir.join(SPEC_FILE);
+        let file = match std::fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice::<SpecFile>(&bytes).unwrap_or_else(|e| {
+                warn!(path = %path.display(), error = %e, "ignoring corrupt spec cache");
+                SpecFile::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SpecFile::default(),
+            Err(e) => {
+                warn!(path = %path.display(), error = %e, "cannot read spec cache");
+                SpecFile::default()
+            }
+        };
+        if !file.vms.is_empty() {
+            info!(vms = file.vms.len(), synced_at = %file.synced_at, "loaded cached VM specs");
+        }
+        Self {
+            path,
+            vms: Arc::new(RwLock::new(file.vms)),
+        }
+    }
+
+    /// Replace the cache with the api's latest push and persist it. The
+    /// file is swapped in by rename so a crash never leaves half of it.
+    pub fn replace(&self, specs: Vec<CachedVmSpec>) -> Result<()> {
+        let file = SpecFile {
+            synced_at: chrono::Utc::now().to_rfc3339(),
+            vms: specs.into_iter().map(CachedVm::from).collect(),
+        };
+        let body = serde_json::to_vec_pretty(&file).context("serialize spec cache")?;
+        let tmp = self.path.with_extension("json.tmp");
+        std::fs::write(&tmp, body).with_context(|| format!("write {}", tmp.display()))?;
+        std::fs::rename(&tmp, &self.path)
+            .with_context(|| format!("rename to {}", self.path.display()))?;
+        *self.vms.write().unwrap() = file.vms;
+        Ok(())
+    }
+
+    /// VMs to hold running while offline. A run-once VM that stopped
+    /// was powered off by its guest and stays down.
+    fn wanted(&self) -> Vec<CachedVm> {
+        self.vms
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|vm| vm.running && !vm.run_once)
+            .cloned()
+            .collect()
+    }
+}
+
+/// Keep cached VMs at their desired state whenever `online` is false.
+pub async fn supervise(cache: SpecCache, vmm: VmServiceClient<Channel>, online: Arc<AtomicBool>) {
+    let mut tick = tokio::time::interval(SUPERVISE_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tick.tick().await;
+        if online.load(Ordering::Relaxed) {
+            continue;
+        }
+        enforce(&cache, vmm.clone()).await;
+    }
+}
+
+async fn enforce(cache: &SpecCache, mut vmm: VmServiceClient<Channel>) {
+    let wanted = cache.wanted();
+    if wanted.is_empty() {
+        return;
+    }
+    let states: HashMap<String, i32> = match vmm.list_vms(ListVmsRequest {}).await {
+        Ok(resp) => resp
+            .into_inner()
+            .vms
+            .into_iter()
+            .map(|vm| (vm.id, vm.state))
+            .collect(),
+        Err(e) => {
+            debug!(error = %e, "vmm not reachable; cannot hold VMs at desired state");
+            return;
+        }
+    };
+
+    for vm in to_start(wanted, &states) {
+        info!(vm = %vm.vm_id, name = %vm.name, "api unreachable; starting VM per cached spec");
+        if let Err(e) = vmm
+            .start_vm(StartVmRequest {
+                id: vm.vm_id.clone(),
+                prepare_stages: vec![],
+            })
+            .await
+        {
+            warn!(vm = %vm.vm_id, error = %e, "offline start failed");
+        }
+    }
+}
+
+/// The wanted VMs the vmm reports stopped, by vmm state code.
+fn to_start(wanted: Vec<CachedVm>, states: &HashMap<String, i32>) -> Vec<CachedVm> {
+    wanted
+        .into_iter()
+        .filter(
+            |vm| match states.get(&vm.vm_id).map(|s| VmState::try_from(*s)) {
+                Some(Ok(VmState::Stopped)) => true,
+                // Creating a missing VM needs its full config, which only the
+                // api has.
+                None => {
+                    debug!(vm = %vm.vm_id, "cached VM not on this node's vmm");
+                    false
+                }
+                _ => false,
+            },
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mvirt-spec-cache-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn spec(vm_id: &str, running: bool, run_once: bool) -> CachedVmSpec {
+        CachedVmSpec {
+            vm_id: vm_id.to_string(),
+            name: format!("{}-name", vm_id),
+            running,
+            run_once,
+        }
+    }
+
+    fn ids(vms: &[CachedVm]) -> Vec<&str> {
+        vms.iter().map(|vm| vm.vm_id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_load_missing_file_is_empty() {
+        let dir = temp_dir();
+        assert!(SpecCache::load(&dir).wanted().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_replace_persists_across_load() {
+        let dir = temp_dir();
+        let cache = SpecCache::load(&dir);
+        cache
+            .replace(vec![spec("vm-1", true, false), spec("vm-2", true, false)])
+            .unwrap();
+        assert_eq!(ids(&cache.wanted()), ["vm-1", "vm-2"]);
+        assert!(!dir.join("specs.json.tmp").exists());
+
+        let reloaded = SpecCache::load(&dir);
+        assert_eq!(ids(&reloaded.wanted()), ["vm-1", "vm-2"]);
+        assert_eq!(reloaded.wanted()[0].name, "vm-1-name");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_replace_drops_the_previous_push() {
+        let dir = temp_dir();
+        let cache = SpecCache::load(&dir);
+        cache.replace(vec![spec("vm-1", true, false)]).unwrap();
+        cache.replace(vec![spec("vm-2", true, false)]).unwrap();
+        assert_eq!(ids(&cache.wanted()), ["vm-2"]);
+        assert_eq!(ids(&SpecCache::load(&dir).wanted()), ["vm-2"]);
+
+        cache.replace(vec![]).unwrap();
+        assert!(SpecCache::load(&dir).wanted().is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_corrupt_file_is_empty() {
+        let dir = temp_dir();
+        std::fs::write(dir.join(SPEC_FILE), b"{\"synced_at\": \"2024").unwrap();
+        let cache = SpecCache::load(&dir);
+        assert!(cache.wanted().is_empty());
+
+        // The next push overwrites it
+        cache.replace(vec![spec("vm-1", true, false)]).unwrap();
+        assert_eq!(ids(&SpecCache::load(&dir).wanted()), ["vm-1"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_load_ignores_stale_temp_file() {
+        let dir = temp_dir();
+        let cache = SpecCache::load(&dir);
+        cache.replace(vec![spec("vm-1", true, false)]).unwrap();
+        // A crash between write and rename leaves the temp file behind
+        std::fs::write(dir.join("specs.json.tmp"), b"{\"synced_at\"").unwrap();
+        assert_eq!(ids(&SpecCache::load(&dir).wanted()), ["vm-1"]);
+
+        cache.replace(vec![spec("vm-2", true, false)]).unwrap();
+        assert_eq!(ids(&SpecCache::load(&dir).wanted()), ["vm-2"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_wanted_skips_stopped_and_run_once() {
+        let dir = temp_dir();
+        let cache = SpecCache::load(&dir);
+        cache
+            .replace(vec![
+                spec("vm-1", true, false),
+                spec("vm-2", false, false),
+                spec("vm-3", true, true),
+            ])
+            .unwrap();
+        assert_eq!(ids(&cache.wanted()), ["vm-1"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_enforce_starts_only_stopped_vms() {
+        let dir = temp_dir();
+        let cache = SpecCache::load(&dir);
+        cache
+            .replace(vec![
+                spec("stopped", true, false),
+                spec("running", true, false),
+                spec("starting", true, false),
+                spec("missing", true, false),
+            ])
+            .unwrap();
+        let states = HashMap::from([
+            ("stopped".to_string(), VmState::Stopped as i32),
+            ("running".to_string(), VmState::Running as i32),
+            ("starting".to_string(), VmState::Starting as i32),
+        ]);
+        assert_eq!(ids(&to_start(cache.wanted(), &states)), ["stopped"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
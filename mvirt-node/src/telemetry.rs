@@ -0,0 +1,338 @@
+//! Host telemetry behind NodeAgent.CurrentResources.
+//!
+//! A background task samples the host every [`SAMPLE_INTERVAL`]: CPU
+//! topology, load and steal time from /proc, MemAvailable and hugepages
+//! from /proc/meminfo, stalls from /proc/pressure, KVM from /dev/kvm and
+//! pool capacity from mvirt-zfs.
+//! CurrentResources hands out the latest sample, and the cplane pulls it
+//! as the node's heartbeat, so the scheduler places VMs by what the host
+//! actually has free. `--cpu-cores` / `--memory-mb` / `--storage-gb` pin
+//! the totals where detection gets them wrong.
+//!
+//! Each sample is also written to `<state-dir>/resources.json` for the
+//! daemons on this host: mvirt-vmm checks VM resizes against it. A VM
+//! stop or volume resize on the host's event bus is sampled right away.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use mvirt_common::events::{Event, Subscription};
+use mvirt_daemon_protos::zfs::zfs_service_client::ZfsServiceClient;
+use mvirt_daemon_protos::zfs::GetPoolStatsRequest;
+use serde::Serialize;
+use tokio::sync::Notify;
+use tonic::transport::Channel;
+use tracing::{debug, warn};
+
+use crate::proto::{HostTelemetry, NodeResources};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+const GIB: u64 = 1024 * 1024 * 1024;
+const LOCAL_FILE: &str = "resources.json";
+
+/// Totals configured on the command line; `None` means detect.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Overrides {
+    pub cpu_cores: Option<u32>,
+    pub memory_mb: Option<u64>,
+    pub storage_gb: Option<u64>,
+}
+
+/// Latest host sample, shared with the NodeAgent service.
+#[derive(Clone)]
+pub struct HostResources {
+    latest: Arc<RwLock<NodeResources>>,
+    resample: Arc<Notify>,
+}
+
+impl HostResources {
+    /// Take a first sample, then keep refreshing it in the background.
+    /// Every sample is written to `resources.json` in `state_dir`.
+    pub async fn spawn(
+        zfs: ZfsServiceClient<Channel>,
+        overrides: Overrides,
+        state_dir: &Path,
+    ) -> Self {
+        let local = state_dir.join(LOCAL_FILE);
+        // The first steal figure covers the time since boot
+        let mut cpu_times = CpuTimes::default();
+        let first = sample(&mut zfs.clone(), overrides, &mut cpu_times).await;
+        write_local(&local, &first);
+        let latest = Arc::new(RwLock::new(first));
+        let resample = Arc::new(Notify::new());
+        let handle = Self {
+            latest: latest.clone(),
+            resample: resample.clone(),
+        };
+        tokio::spawn(async move {
+            let mut zfs = zfs;
+            let mut tick = tokio::time::interval(SAMPLE_INTERVAL);
+            tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            tick.tick().await;
+            loop {
+                tokio::select! {
+                    _ = tick.tick() => {}
+                    _ = resample.notified() => tick.reset(),
+                }
+                let resources = sample(&mut zfs, overrides, &mut cpu_times).await;
+                write_local(&local, &resources);
+                *latest.write().unwrap() = resources;
+            }
+        });
+        handle
+    }
+
+    pub fn current(&self) -> NodeResources {
+        self.latest.read().unwrap().clone()
+    }
+
+    /// Take the next sample now rather than at the next interval, e.g.
+    /// after a VM stopped and freed its CPUs and memory.
+    pub fn resample(&self) {
+        self.resample.notify_one();
+    }
+}
+
+/// Resample whenever the host's event bus tells of freed or grown
+/// capacity, until the bus fails.
+pub async fn follow_host_events(resources: HostResources, mut subscription: Subscription) {
+    loop {
+        match subscription.recv().await {
+            Ok(envelope) => match envelope.event {
+                Event::VmStopped { .. } | Event::VolumeResized { .. } => {
+                    debug!(source = %envelope.source, event = ?envelope.event, "resampling host after event");
+                    resources.resample();
+                }
+                Event::NicDeleted { .. } => {}
+            },
+            Err(e) => {
+                warn!(error = %e, "host event bus failed; sampling on the interval only");
+                return;
+            }
+        }
+    }
+}
+
+/// The part of a sample the daemons on this host read from
+/// `resources.json`.
+#[derive(Serialize)]
+struct LocalResources {
+    /// Unix seconds
+    sampled_at: i64,
+    cpu_cores: u32,
+    memory_mb: u64,
+    available_cpu_cores: u32,
+    available_memory_mb: u64,
+}
+
+/// Swap in the new sample by rename so readers never see half of it.
+fn write_local(path: &Path, resources: &NodeResources) {
+    let local = LocalResources {
+        sampled_at: chrono::Utc::now().timestamp(),
+        cpu_cores: resources.cpu_cores,
+        memory_mb: resources.memory_mb,
+        available_cpu_cores: resources.available_cpu_cores,
+        available_memory_mb: resources.available_memory_mb,
+    };
+    let tmp = path.with_extension("json.tmp");
+    let result = serde_json::to_vec_pretty(&local)
+        .map_err(std::io::Error::other)
+        .and_then(|body| std::fs::write(&tmp, body))
+        .and_then(|()| std::fs::rename(&tmp, path));
+    if let Err(e) = result {
+        warn!(path = %path.display(), error = %e, "write local resources failed");
+    }
+}
+
+/// Takes the steal time since `cpu_times`, which it updates.
+async fn sample(
+    zfs: &mut ZfsServiceClient<Channel>,
+    overrides: Overrides,
+    cpu_times: &mut CpuTimes,
+) -> NodeResources {
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let meminfo = parse_meminfo(&std::fs::read_to_string("/proc/meminfo").unwrap_or_default());
+    let load = std::fs::read_to_string("/proc/loadavg").unwrap_or_default();
+    let load: Vec<f64> = load
+        .split_whitespace()
+        .take(3)
+        .filter_map(|v| v.parse().ok())
+        .collect();
+    let (load1, load5, load15) = match load[..] {
+        [a, b, c] => (a, b, c),
+        _ => (0.0, 0.0, 0.0),
+    };
+
+    let now = CpuTimes::parse(&std::fs::read_to_string("/proc/stat").unwrap_or_default());
+    let cpu_steal = now.steal_since(cpu_times);
+    *cpu_times = now;
+    let pressure = |resource: &str| {
+        std::fs::read_to_string(format!("/proc/pressure/{}", resource))
+            .ok()
+            .and_then(|text| parse_pressure(&text))
+            .unwrap_or(0.0)
+    };
+
+    let cpu = parse_cpuinfo(&cpuinfo);
+    let cpu_cores = overrides.cpu_cores.unwrap_or(cpu.threads.max(1));
+    let memory_mb = overrides
+        .memory_mb
+        .unwrap_or(meminfo.get("MemTotal").copied().unwrap_or(0) / 1024);
+    let available_memory_mb = meminfo
+        .get("MemAvailable")
+        .map(|kb| kb / 1024)
+        .unwrap_or(memory_mb)
+        .min(memory_mb);
+
+    let pool = match zfs.get_pool_stats(GetPoolStatsRequest {}).await {
+        Ok(resp) => Some(resp.into_inner()),
+        Err(e) => {
+            debug!(error = %e, "zfs GetPoolStats failed; storage not sampled");
+            None
+        }
+    };
+    let storage_gb = overrides
+        .storage_gb
+        .unwrap_or_else(|| pool.as_ref().map(|p| p.total_bytes / GIB).unwrap_or(0));
+    let available_storage_gb = pool
+        .as_ref()
+        .map(|p| p.available_bytes / GIB)
+        .unwrap_or(storage_gb)
+        .min(storage_gb);
+    let (zfs_pool, zfs_pool_health) = pool.map(|p| (p.name, p.health)).unwrap_or_default();
+
+    NodeResources {
+        cpu_cores,
+        memory_mb,
+        storage_gb,
+        available_cpu_cores: cpu_cores.saturating_sub(load5.round() as u32),
+        available_memory_mb,
+        available_storage_gb,
+        host: Some(HostTelemetry {
+            cpu_model: cpu.model,
+            cpu_sockets: cpu.sockets,
+            threads_per_core: cpu.threads_per_core,
+            load1,
+            load5,
+            load15,
+            kernel_version: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default(),
+            kvm: std::path::Path::new("/dev/kvm").exists(),
+            hugepage_size_kb: meminfo.get("Hugepagesize").copied().unwrap_or(0),
+            hugepages_total: meminfo.get("HugePages_Total").copied().unwrap_or(0),
+            hugepages_free: meminfo.get("HugePages_Free").copied().unwrap_or(0),
+            zfs_pool,
+            zfs_pool_health,
+            cpu_pressure: pressure("cpu"),
+            memory_pressure: pressure("memory"),
+            io_pressure: pressure("io"),
+            cpu_steal,
+        }),
+    }
+}
+
+/// The `some avg60` of a /proc/pressure file: percent of the last minute
+/// in which at least one task stalled.
+fn parse_pressure(text: &str) -> Option<f64> {
+    let some = text.lines().find(|line| line.starts_with("some "))?;
+    some.split_whitespace()
+        .find_map(|field| field.strip_prefix("avg60="))?
+        .parse()
+        .ok()
+}
+
+/// Aggregate CPU counters from the `cpu` line of /proc/stat, in ticks.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct CpuTimes {
+    total: u64,
+    steal: u64,
+}
+
+impl CpuTimes {
+    fn parse(text: &str) -> Self {
+        let Some(line) = text.lines().find(|line| line.starts_with("cpu ")) else {
+            return Self::default();
+        };
+        // user nice system idle iowait irq softirq steal guest guest_nice;
+        // guest time is already counted in user and nice
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .skip(1)
+            .take(8)
+            .filter_map(|v| v.parse().ok())
+            .collect();
+        Self {
+            total: fields.iter().sum(),
+            steal: fields.get(7).copied().unwrap_or(0),
+        }
+    }
+
+    /// Percent of the CPU time since `earlier` that was stolen.
+    fn steal_since(&self, earlier: &Self) -> f64 {
+        let total = self.total.saturating_sub(earlier.total);
+        if total == 0 {
+            return 0.0;
+        }
+        self.steal.saturating_sub(earlier.steal) as f64 * 100.0 / total as f64
+    }
+}
+
+/// `Key: value [kB]` lines of /proc/meminfo; values as printed (kB, or a
+/// plain count for the HugePages_* lines).
+fn parse_meminfo(text: &str) -> HashMap<&str, u64> {
+    text.lines()
+        .filter_map(|line| {
+            let (key, rest) = line.split_once(':')?;
+            let value = rest.split_whitespace().next()?.parse().ok()?;
+            Some((key.trim(), value))
+        })
+        .collect()
+}
+
+struct CpuTopology {
+    model: String,
+    threads: u32,
+    sockets: u32,
+    threads_per_core: u32,
+}
+
+fn parse_cpuinfo(text: &str) -> CpuTopology {
+    let field = |line: &str, name: &str| {
+        line.split_once(':')
+            .filter(|(k, _)| k.trim() == name)
+            .map(|(_, v)| v.trim().to_string())
+    };
+    let mut threads = 0;
+    let mut model = String::new();
+    let mut sockets = HashSet::new();
+    let mut siblings = 0u32;
+    let mut cores = 0u32;
+    for line in text.lines() {
+        if field(line, "processor").is_some() {
+            threads += 1;
+        } else if let Some(v) = field(line, "model name") {
+            if model.is_empty() {
+                model = v;
+            }
+        } else if let Some(v) = field(line, "physical id") {
+            sockets.insert(v);
+        } else if let Some(v) = field(line, "siblings") {
+            siblings = v.parse().unwrap_or(0);
+        } else if let Some(v) = field(line, "cpu cores") {
+            cores = v.parse().unwrap_or(0);
+        }
+    }
+    CpuTopology {
+        model,
+        threads,
+        sockets: (sockets.len() as u32).max(1),
+        threads_per_core: if cores > 0 {
+            (siblings / cores).max(1)
+        } else {
+            1
+        },
+    }
+}
@@ -7,11 +7,13 @@
 //! and the cplane authenticates us via our client cert.
 
 use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::task::{Context as StdContext, Poll};
 use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
+use mvirt_common::request_id::GrpcRequestIdLayer;
 use rustls::pki_types::ServerName;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio::net::TcpStream;
@@ -76,18 +78,29 @@ pub struct ProxyBundle {
 
 /// Reconnect loop: dial the api, host gRPC services on the TLS stream until
 /// it breaks, then back off and retry.
+/// `online` is true while a tunnel is up.
 pub async fn run(
     tunnel_endpoint: String,
     pki: NodePki,
     agent: NodeAgentService,
     proxies: ProxyBundle,
+    online: Arc<AtomicBool>,
 ) -> Result<()> {
     install_default_crypto_provider();
     let tls_config = build_client_tls(&pki)?;
     let connector = TlsConnector::from(Arc::new(tls_config));
     let backoff = Duration::from_secs(5);
     loop {
-        match dial_and_serve(&tunnel_endpoint, &connector, agent.clone(), proxies.clone()).await {
+        let result = dial_and_serve(
+            &tunnel_endpoint,
+            &connector,
+            agent.clone(),
+            proxies.clone(),
+            &online,
+        )
+        .await;
+        online.store(false, Ordering::Relaxed);
+        match result {
             Ok(()) => info!("tunnel closed by api, reconnecting..."),
             Err(e) => warn!(error = %e, "tunnel connection failed; retrying"),
         }
@@ -100,6 +113,7 @@ async fn dial_and_serve(
     connector: &TlsConnector,
     agent: NodeAgentService,
     proxies: ProxyBundle,
+    online: &AtomicBool,
 ) -> Result<()> {
     let target = tunnel_endpoint
         .strip_prefix("http://")
@@ -121,13 +135,17 @@ async fn dial_and_serve(
         .await
         .context("TLS handshake to cplane")?;
     info!("tunnel established, serving NodeAgent + daemon proxies");
+    online.store(true, Ordering::Relaxed);
 
     use futures::StreamExt;
     let stream = TunnelStream(tls);
     let incoming = futures::stream::once(async move { Ok::<_, std::io::Error>(stream) })
         .chain(futures::stream::pending());
 
+    // The request ID layer also fills in IDs on proxied calls, so the
+    // daemons log the one the api's reconcile ran under
     Server::builder()
+        .layer(GrpcRequestIdLayer)
         .add_service(NodeAgentServer::new(agent))
         .add_service(VmServiceProxy(proxies.vmm.clone()))
         .add_service(PodServiceProxy(proxies.vmm))
@@ -0,0 +1,317 @@
+//! Usage history behind NodeAgent.QueryUsageHistory, for capacity trends.
+//!
+//! Every [`SAMPLE_INTERVAL`] the latest host sample (load, memory, ZFS pool)
+//! and the running VMs from mvirt-vmm go into the current
+//! [`BUCKET_SECS`] bucket, which keeps the average and the peak of each
+//! measure. Closed buckets form a ring of [`RETENTION_DAYS`], written to
+//! `usage-history.json` in the state dir as each one closes, so the
+//! history survives agent restarts. Queries merge buckets into wider
+//! intervals when the caller wants fewer points, so a 30-day chart does
+//! not have to carry every five minutes.
+
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use mvirt_daemon_protos::vmm::vm_service_client::VmServiceClient;
+use mvirt_daemon_protos::vmm::{ListVmsRequest, VmState};
+use serde::{Deserialize, Serialize};
+use tonic::transport::Channel;
+use tracing::{debug, info, warn};
+
+use crate::proto::{NodeResources, UsageGauge, UsageHistory, UsagePoint};
+use crate::telemetry::HostResources;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(30);
+const BUCKET_SECS: i64 = 300;
+const RETENTION_DAYS: u32 = 30;
+const MAX_BUCKETS: usize = RETENTION_DAYS as usize * 86_400 / BUCKET_SECS as usize;
+const HISTORY_FILE: &str = "usage-history.json";
+
+/// Sum and peak of one measure; the average is the sum over the bucket's
+/// sample count.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+struct Gauge {
+    sum: f64,
+    max: f64,
+}
+
+impl Gauge {
+    fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.max = self.max.max(value);
+    }
+
+    fn merge(&mut self, other: &Gauge) {
+        self.sum += other.sum;
+        self.max = self.max.max(other.max);
+    }
+
+    fn to_proto(self, samples: u32) -> UsageGauge {
+        UsageGauge {
+            avg: if samples == 0 {
+                0.0
+            } else {
+                self.sum / samples as f64
+            },
+            max: self.max,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+struct Bucket {
+    /// Unix seconds, a multiple of the bucket width
+    start: i64,
+    samples: u32,
+    cpu_cores: u32,
+    memory_mb: u64,
+    storage_gb: u64,
+    cpu_load: Gauge,
+    memory_used_mb: Gauge,
+    storage_used_gb: Gauge,
+    vms_running: Gauge,
+    vm_vcpus: Gauge,
+    vm_memory_mb: Gauge,
+}
+
+impl Bucket {
+    fn merge(&mut self, other: &Bucket) {
+        self.samples += other.samples;
+        // Capacity as of the later bucket
+        self.cpu_cores = other.cpu_cores;
+        self.memory_mb = other.memory_mb;
+        self.storage_gb = other.storage_gb;
+        self.cpu_load.merge(&other.cpu_load);
+        self.memory_used_mb.merge(&other.memory_used_mb);
+        self.storage_used_gb.merge(&other.storage_used_gb);
+        self.vms_running.merge(&other.vms_running);
+        self.vm_vcpus.merge(&other.vm_vcpus);
+        self.vm_memory_mb.merge(&other.vm_memory_mb);
+    }
+
+    fn to_proto(&self) -> UsagePoint {
+        UsagePoint {
+            timestamp: self.start,
+            cpu_cores: self.cpu_cores,
+            memory_mb: self.memory_mb,
+            storage_gb: self.storage_gb,
+            cpu_load: Some(self.cpu_load.to_proto(self.samples)),
+            memory_used_mb: Some(self.memory_used_mb.to_proto(self.samples)),
+            storage_used_gb: Some(self.storage_used_gb.to_proto(self.samples)),
+            vms_running: Some(self.vms_running.to_proto(self.samples)),
+            vm_vcpus: Some(self.vm_vcpus.to_proto(self.samples)),
+            vm_memory_mb: Some(self.vm_memory_mb.to_proto(self.samples)),
+        }
+    }
+}
+
+/// One reading of the host and its VMs.
+struct Sample {
+    resources: NodeResources,
+    vms_running: u32,
+    vm_vcpus: u64,
+    vm_memory_mb: u64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct HistoryFile {
+    buckets: VecDeque<Bucket>,
+}
+
+#[derive(Default)]
+struct Ring {
+    /// Closed buckets, oldest first
+    closed: VecDeque<Bucket>,
+    current: Option<Bucket>,
+}
+
+impl Ring {
+    /// Add a sample taken at `now`. Returns true if that closed a bucket.
+    fn add(&mut self, now: i64, sample: &Sample) -> bool {
+        let start = now - now.rem_euclid(BUCKET_SECS);
+        let mut closed = false;
+        if self.current.as_ref().is_some_and(|b| b.start != start) {
+            self.closed.extend(self.current.take());
+            while self.closed.len() > MAX_BUCKETS {
+                self.closed.pop_front();
+            }
+            closed = true;
+        }
+        let bucket = self.current.get_or_insert_with(|| Bucket {
+            start,
+            ..Default::default()
+        });
+
+        let r = &sample.resources;
+        bucket.samples += 1;
+        bucket.cpu_cores = r.cpu_cores;
+        bucket.memory_mb = r.memory_mb;
+        bucket.storage_gb = r.storage_gb;
+        bucket
+            .cpu_load
+            .add(r.host.as_ref().map(|h| h.load1).unwrap_or(0.0));
+        bucket
+            .memory_used_mb
+            .add(r.memory_mb.saturating_sub(r.available_memory_mb) as f64);
+        bucket
+            .storage_used_gb
+            .add(r.storage_gb.saturating_sub(r.available_storage_gb) as f64);
+        bucket.vms_running.add(sample.vms_running as f64);
+        bucket.vm_vcpus.add(sample.vm_vcpus as f64);
+        bucket.vm_memory_mb.add(sample.vm_memory_mb as f64);
+        closed
+    }
+
+    /// Buckets since `since`, the open one included, merged into intervals
+    /// of `interval` seconds.
+    fn query(&self, since: i64, interval: i64) -> Vec<Bucket> {
+        let mut points: Vec<Bucket> = Vec::new();
+        for bucket in self.closed.iter().chain(self.current.iter()) {
+            if bucket.start < since {
+                continue;
+            }
+            let start = bucket.start - bucket.start.rem_euclid(interval);
+            match points.last_mut() {
+                Some(point) if point.start == start => point.merge(bucket),
+                _ => points.push(Bucket {
+                    start,
+                    ..bucket.clone()
+                }),
+            }
+        }
+        points
+    }
+}
+
+/// Usage history of this node, shared with the NodeAgent service.
+#[derive(Clone)]
+pub struct UsageRecorder {
+    ring: Arc<RwLock<Ring>>,
+}
+
+impl UsageRecorder {
+    /// Load the history from `state_dir` and keep adding to it in the
+    /// background. A missing or unreadable file starts empty.
+    pub fn spawn(
+        resources: HostResources,
+        vmm: VmServiceClient<Channel>,
+        state_dir: &Path,
+    ) -> Self {
+        let path = state_dir.join(HISTORY_FILE);
+        let ring = Arc::new(RwLock::new(Ring {
+            closed: load(&path),
+            current: None,
+        }));
+        tokio::spawn(record(resources, vmm, path, ring.clone()));
+        Self { ring }
+    }
+
+    /// The last `days` (all of them for 0), with at most `max_points`
+    /// points (no limit for 0).
+    pub fn query(&self, days: u32, max_points: u32) -> UsageHistory {
+        let days = if days == 0 {
+            RETENTION_DAYS
+        } else {
+            days.min(RETENTION_DAYS)
+        };
+        let now = chrono::Utc::now().timestamp();
+        let since = now - i64::from(days) * 86_400;
+        // Buckets per point, so that the range fits in max_points
+        let buckets = u64::from(days) * 86_400 / BUCKET_SECS as u64 + 1;
+        let per_point = match max_points {
+            0 => 1,
+            max => buckets.div_ceil(u64::from(max)),
+        };
+        let interval = BUCKET_SECS * per_point as i64;
+        let points = self.ring.read().unwrap().query(since, interval);
+        UsageHistory {
+            interval_secs: interval as u32,
+            points: points.iter().map(Bucket::to_proto).collect(),
+            retention_days: RETENTION_DAYS,
+        }
+    }
+}
+
+async fn record(
+    resources: HostResources,
+    mut vmm: VmServiceClient<Channel>,
+    path: PathBuf,
+    ring: Arc<RwLock<Ring>>,
+) {
+    let mut tick = tokio::time::interval(SAMPLE_INTERVAL);
+    tick.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        tick.tick().await;
+        let sample = sample(&resources, &mut vmm).await;
+        let now = chrono::Utc::now().timestamp();
+        let file = {
+            let mut ring = ring.write().unwrap();
+            ring.add(now, &sample).then(|| HistoryFile {
+                buckets: ring.closed.clone(),
+            })
+        };
+        if let Some(file) = file {
+            save(&path, &file);
+        }
+    }
+}
+
+async fn sample(resources: &HostResources, vmm: &mut VmServiceClient<Channel>) -> Sample {
+    let mut sample = Sample {
+        resources: resources.current(),
+        vms_running: 0,
+        vm_vcpus: 0,
+        vm_memory_mb: 0,
+    };
+    match vmm.list_vms(ListVmsRequest {}).await {
+        Ok(resp) => {
+            for vm in resp.into_inner().vms {
+                if vm.state != VmState::Running as i32 {
+                    continue;
+                }
+                let config = vm.config.unwrap_or_default();
+                sample.vms_running += 1;
+                sample.vm_vcpus += u64::from(config.vcpus);
+                sample.vm_memory_mb += config.memory_mb;
+            }
+        }
+        Err(e) => debug!(error = %e, "vmm ListVms failed; VMs not sampled"),
+    }
+    sample
+}
+
+fn load(path: &Path) -> VecDeque<Bucket> {
+    let file = match std::fs::read(path) {
+        Ok(bytes) => serde_json::from_slice::<HistoryFile>(&bytes).unwrap_or_else(|e| {
+            warn!(path = %path.display(), error = %e, "ignoring corrupt usage history");
+            HistoryFile::default()
+        }),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => HistoryFile::default(),
+        Err(e) => {
+            warn!(path = %path.display(), error = %e, "cannot read usage history");
+            HistoryFile::default()
+        }
+    };
+    let mut buckets = file.buckets;
+    while buckets.len() > MAX_BUCKETS {
+        buckets.pop_front();
+    }
+    if !buckets.is_empty() {
+        info!(buckets = buckets.len(), "loaded usage history");
+    }
+    buckets
+}
+
+/// Swap in the new history by rename so a crash never leaves half of it.
+fn save(path: &Path, file: &HistoryFile) {
+    let tmp = path.with_extension("json.tmp");
+    let result = serde_json::to_vec(file)
+        .map_err(std::io::Error::other)
+        .and_then(|body| std::fs::write(&tmp, body))
+        .and_then(|()| std::fs::rename(&tmp, path));
+    if let Err(e) = result {
+        warn!(path = %path.display(), error = %e, "write usage history failed");
+    }
+}
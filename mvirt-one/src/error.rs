@@ -54,8 +54,10 @@ pub enum ContainerError {
 #[derive(Debug)]
 pub enum PodError {
     NotFound(String),
+    ContainerNotFound(String),
     InvalidState { expected: String, actual: String },
     ContainerFailed { container_id: String, error: String },
+    InitFailed { container: String, error: String },
 }
 
 impl fmt::Display for Error {
@@ -119,6 +121,7 @@ impl fmt::Display for PodError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             PodError::NotFound(id) => write!(f, "Pod not found: {id}"),
+            PodError::ContainerNotFound(id) => write!(f, "Container not found: {id}"),
             PodError::InvalidState { expected, actual } => {
                 write!(f, "Invalid pod state: expected {expected}, got {actual}")
             }
@@ -128,6 +131,9 @@ impl fmt::Display for PodError {
             } => {
                 write!(f, "Container {container_id} failed: {error}")
             }
+            PodError::InitFailed { container, error } => {
+                write!(f, "Init container {container} failed: {error}")
+            }
         }
     }
 }
@@ -7,7 +7,7 @@
 //!
 //! - **Pod Service**: High-level pod lifecycle management
 //! - **Task Service**: Low-level OCI runtime (youki) interface
-//! - **Image Service**: OCI image pulling and layer extraction
+//! - **Image Service**: OCI image pulling, archive loading and layer extraction
 //!
 //! ## Dual-Mode Operation
 //!
@@ -15,16 +15,18 @@
 //! - **Local mode**: For development/testing without a VM
 
 pub mod error;
+pub mod logs;
 pub mod proto;
 pub mod services;
 pub mod utils;
+pub mod vsock;
 
-use crate::services::image::{self, Command as ImageCommand};
+use crate::services::image::{self, Command as ImageCommand, PullProgress};
 use crate::services::pod::{Command as PodCommand, PodApiHandler, PodDispatcher};
 use crate::services::task::{Command as TaskCommand, Event as TaskEvent, TaskDispatcher};
 use log::info;
 use std::path::PathBuf;
-use tokio::sync::mpsc;
+use tokio::sync::{broadcast, mpsc};
 
 /// Configuration for one services.
 pub struct Config {
@@ -63,6 +65,7 @@ impl Default for Config {
 /// Service handles for communicating with one services.
 pub struct Services {
     pub image_tx: mpsc::Sender<ImageCommand>,
+    pub pull_progress_tx: broadcast::Sender<PullProgress>,
     pub task_tx: mpsc::Sender<TaskCommand>,
     pub pod_tx: mpsc::Sender<PodCommand>,
     pub shutdown_tx: mpsc::Sender<()>,
@@ -78,7 +81,8 @@ pub async fn initialize_services(config: Config) -> anyhow::Result<Services> {
     tokio::fs::create_dir_all(&config.pods_dir).await?;
 
     // Initialize Image Service
-    let image_tx = image::orchestrator::initialize_image_service(config.images_dir.clone()).await;
+    let (image_tx, pull_progress_tx) =
+        image::orchestrator::initialize_image_service(config.images_dir.clone()).await;
     info!("Image Service initialized");
 
     // Initialize Task Service
@@ -112,6 +116,7 @@ pub async fn initialize_services(config: Config) -> anyhow::Result<Services> {
 
     Ok(Services {
         image_tx,
+        pull_progress_tx,
         task_tx,
         pod_tx,
         shutdown_tx,
@@ -122,7 +127,9 @@ pub async fn initialize_services(config: Config) -> anyhow::Result<Services> {
 /// Create a gRPC service for the Pod API.
 pub fn create_api_handler(
     pod_tx: mpsc::Sender<PodCommand>,
+    image_tx: mpsc::Sender<ImageCommand>,
     shutdown_tx: mpsc::Sender<()>,
+    pull_progress_tx: broadcast::Sender<PullProgress>,
 ) -> PodApiHandler {
-    PodApiHandler::new(pod_tx, shutdown_tx)
+    PodApiHandler::new(pod_tx, image_tx, shutdown_tx, pull_progress_tx)
 }
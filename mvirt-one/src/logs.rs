@@ -0,0 +1,240 @@
+//! Guest log forwarding.
+//!
+//! Container stdout/stderr and kernel messages are collected into one hub
+//! and served to the host on the `logs` vsock channel
+//! ([`crate::vsock::CHANNEL_LOGS`]) as JSON lines, one [`LogRecord`] each.
+//! mvirt-vmm relays them to mvirt-log under the pod's and container's IDs.
+//!
+//! The hub keeps the last [`BACKLOG`] records, so output written before
+//! the host connected (boot messages, a container that crashed right
+//! away) is replayed when it does. Records the host doesn't read fast
+//! enough are dropped rather than blocking the containers.
+
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::{LazyLock, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast;
+
+/// Records kept for replay to a host that connects late.
+const BACKLOG: usize = 1000;
+/// Records buffered for a connected host before it starts losing some.
+const LIVE_BUFFER: usize = 1024;
+/// Longer lines are cut; a runaway line shouldn't become one huge entry.
+const MAX_LINE: usize = 16 * 1024;
+
+/// Where a record came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSource {
+    Stdout,
+    Stderr,
+    Kernel,
+}
+
+/// One line of guest output.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogRecord {
+    /// Guest wall clock when the line was read, nanoseconds since the epoch.
+    pub ts_ns: i64,
+    pub source: LogSource,
+    /// Container the line belongs to; empty for kernel messages.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub container_id: String,
+    /// Syslog priority (0 = emergency .. 7 = debug) for kernel messages.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u8>,
+    pub line: String,
+}
+
+impl LogRecord {
+    fn new(source: LogSource, container_id: &str, line: String) -> Self {
+        let ts_ns = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as i64)
+            .unwrap_or_default();
+        Self {
+            ts_ns,
+            source,
+            container_id: container_id.to_string(),
+            priority: None,
+            line,
+        }
+    }
+}
+
+struct Hub {
+    backlog: Mutex<VecDeque<LogRecord>>,
+    live: broadcast::Sender<LogRecord>,
+}
+
+static HUB: LazyLock<Hub> = LazyLock::new(|| Hub {
+    backlog: Mutex::new(VecDeque::with_capacity(BACKLOG)),
+    live: broadcast::channel(LIVE_BUFFER).0,
+});
+
+/// Add a record to the hub.
+pub fn publish(record: LogRecord) {
+    let mut backlog = HUB.backlog.lock().unwrap();
+    if backlog.len() >= BACKLOG {
+        backlog.pop_front();
+    }
+    backlog.push_back(record.clone());
+    // Sent under the lock so a concurrent subscribe sees each record
+    // either in the backlog or live, never twice.
+    let _ = HUB.live.send(record);
+}
+
+/// The backlog so far plus a receiver for everything after it.
+pub fn subscribe() -> (Vec<LogRecord>, broadcast::Receiver<LogRecord>) {
+    let backlog = HUB.backlog.lock().unwrap();
+    (backlog.iter().cloned().collect(), HUB.live.subscribe())
+}
+
+/// Publish each line read from a container's stdout or stderr until EOF.
+pub fn tail<R>(reader: R, container_id: String, source: LogSource)
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut reader = BufReader::new(reader);
+        let mut buf = Vec::new();
+        loop {
+            buf.clear();
+            match reader.read_until(b'\n', &mut buf).await {
+                Ok(0) => break,
+                Ok(_) => {
+                    let line = trim_line(&buf);
+                    publish(LogRecord::new(source, &container_id, line));
+                }
+                Err(e) => {
+                    debug!("logs: {} {:?} read error: {}", container_id, source, e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// Publish kernel messages from /dev/kmsg, starting with the ring buffer's
+/// current contents. Runs on a blocking thread: each read returns exactly
+/// one record.
+pub fn tail_kmsg() {
+    std::thread::spawn(|| {
+        use std::io::Read;
+        let mut kmsg = match std::fs::File::open("/dev/kmsg") {
+            Ok(f) => f,
+            Err(e) => {
+                warn!("logs: cannot open /dev/kmsg: {}", e);
+                return;
+            }
+        };
+        let mut buf = vec![0u8; 8192];
+        loop {
+            match kmsg.read(&mut buf) {
+                Ok(0) => return,
+                Ok(n) => {
+                    if let Some(record) = parse_kmsg(&buf[..n]) {
+                        publish(record);
+                    }
+                }
+                // Records were overwritten before we got to them; the
+                // next read continues with the oldest one left.
+                Err(e) if e.raw_os_error() == Some(libc::EPIPE) => continue,
+                Err(e) => {
+                    warn!("logs: reading /dev/kmsg failed: {}", e);
+                    return;
+                }
+            }
+        }
+    });
+}
+
+/// Stream the backlog and then live records to the host, until it
+/// disconnects.
+pub async fn serve<S: AsyncWrite + Unpin>(mut stream: S) -> std::io::Result<()> {
+    let (backlog, mut live) = subscribe();
+    for record in &backlog {
+        write_record(&mut stream, record).await?;
+    }
+    stream.flush().await?;
+    loop {
+        match live.recv().await {
+            Ok(record) => {
+                write_record(&mut stream, &record).await?;
+                stream.flush().await?;
+            }
+            Err(broadcast::error::RecvError::Lagged(n)) => {
+                warn!("logs: host fell behind, dropped {} records", n);
+            }
+            Err(broadcast::error::RecvError::Closed) => return Ok(()),
+        }
+    }
+}
+
+async fn write_record<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    record: &LogRecord,
+) -> std::io::Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    stream.write_all(&line).await
+}
+
+fn trim_line(buf: &[u8]) -> String {
+    let buf = buf.strip_suffix(b"\n").unwrap_or(buf);
+    let buf = buf.strip_suffix(b"\r").unwrap_or(buf);
+    String::from_utf8_lossy(&buf[..buf.len().min(MAX_LINE)]).into_owned()
+}
+
+/// Parse one /dev/kmsg record: `<prio+facility>,<seq>,<usec>,<flags>;<text>`
+/// followed by optional ` KEY=value` continuation lines, which are dropped.
+fn parse_kmsg(raw: &[u8]) -> Option<LogRecord> {
+    let raw = String::from_utf8_lossy(raw);
+    let (header, text) = raw.split_once(';')?;
+    let prio: u32 = header.split(',').next()?.parse().ok()?;
+    let text = text.lines().next().unwrap_or_default();
+    let mut record = LogRecord::new(LogSource::Kernel, "", trim_line(text.as_bytes()));
+    record.priority = Some((prio & 7) as u8);
+    Some(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kmsg_records() {
+        let r = parse_kmsg(b"6,339,5140900,-;NET: Registered protocol family 10\n").unwrap();
+        assert_eq!(r.source, LogSource::Kernel);
+        assert_eq!(r.priority, Some(6));
+        assert_eq!(r.line, "NET: Registered protocol family 10");
+
+        // Facility bits are masked off; continuation lines dropped.
+        let r = parse_kmsg(b"27,1,2,c;virtio_net: link down\n SUBSYSTEM=net\n").unwrap();
+        assert_eq!(r.priority, Some(3));
+        assert_eq!(r.line, "virtio_net: link down");
+
+        assert!(parse_kmsg(b"garbage").is_none());
+    }
+
+    #[test]
+    fn record_wire_format() {
+        let mut r = LogRecord::new(LogSource::Stderr, "c-1", "boom".to_string());
+        r.ts_ns = 42;
+        let json = serde_json::to_string(&r).unwrap();
+        assert_eq!(
+            json,
+            r#"{"ts_ns":42,"source":"stderr","container_id":"c-1","line":"boom"}"#
+        );
+        assert_eq!(serde_json::from_str::<LogRecord>(&json).unwrap(), r);
+    }
+
+    #[test]
+    fn long_lines_are_cut() {
+        let long = vec![b'x'; MAX_LINE + 10];
+        assert_eq!(trim_line(&long).len(), MAX_LINE);
+        assert_eq!(trim_line(b"ok\r\n"), "ok");
+    }
+}
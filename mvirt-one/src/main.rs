@@ -1,17 +1,19 @@
 //! mvirt-one - MicroVM Init System for isolated Pods.
 //!
-//! Runs as PID 1 inside MicroVMs or locally for development.
+//! Runs as PID 1 inside MicroVMs, as a guest agent in regular VMs
+//! (`--agent`), or locally for development.
 
 use anyhow::Result;
 use clap::Parser;
-use log::{error, info};
+use log::{error, info, warn};
+use mvirt_one::logs;
 use mvirt_one::proto::one_service_server::OneServiceServer;
 use mvirt_one::utils::{mount, network, signals};
+use mvirt_one::vsock::{CHANNEL_API, CHANNEL_LOGS};
 use mvirt_one::{Config, create_api_handler, initialize_services};
 use nix::sys::prctl;
 use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::time::Duration;
 use tokio::net::TcpListener;
 use tonic::transport::Server;
 
@@ -38,6 +40,11 @@ struct Args {
     /// Port to listen on (default: 50051)
     #[arg(long, default_value = "50051")]
     port: u16,
+
+    /// Run as the guest agent of a regular VM: serve the host over vsock
+    /// and run its provisioning commands on this guest
+    #[arg(long)]
+    agent: bool,
 }
 
 #[tokio::main]
@@ -63,6 +70,7 @@ async fn main() -> Result<()> {
             data_dir: None,
             youki_root: None,
             port: 50051,
+            agent: false,
         }
     } else {
         Args::parse()
@@ -71,6 +79,9 @@ async fn main() -> Result<()> {
     if is_pid1 {
         info!("Running as PID 1 (init mode)");
         run_as_init().await
+    } else if args.agent {
+        info!("Running as guest agent");
+        run_agent().await
     } else {
         info!("Running in local development mode");
         run_local(args).await
@@ -103,9 +114,10 @@ async fn run_as_init() -> Result<()> {
     info!("Phase 1: Mounting filesystems");
     mount::mount_all();
 
-    // Phase 2: Setup signal handling
-    info!("Phase 2: Setting up signal handlers");
-    signals::setup_signal_handlers();
+    // Phase 2: Start reaping children
+    info!("Phase 2: Starting child reaper");
+    let _reaper = signals::spawn_reaper();
+    logs::tail_kmsg();
 
     // Phase 3: Configure network
     info!("Phase 3: Configuring network");
@@ -118,12 +130,17 @@ async fn run_as_init() -> Result<()> {
 
     // Phase 5: Start vsock server
     info!("Phase 5: Starting vsock server");
-    let api_handler = create_api_handler(services.pod_tx, services.shutdown_tx);
+    let api_handler = create_api_handler(
+        services.pod_tx,
+        services.image_tx,
+        services.shutdown_tx,
+        services.pull_progress_tx,
+    );
     let _vsock_handle = start_vsock_server(api_handler).await?;
 
     // Phase 6: Signal ready to host
     info!("Phase 6: Signaling ready to host");
-    if let Err(e) = signal_ready_to_host().await {
+    if let Err(e) = signal_ready_to_host(&[CHANNEL_API, CHANNEL_LOGS]).await {
         error!("Failed to signal ready to host: {} (continuing anyway)", e);
     }
 
@@ -131,28 +148,49 @@ async fn run_as_init() -> Result<()> {
     info!("mvirt-one ready, entering main loop");
     let mut shutdown_rx = services.shutdown_rx;
 
-    loop {
-        tokio::select! {
-            _ = shutdown_rx.recv() => {
-                info!("Shutdown signal received");
-                break;
-            }
-            _ = tokio::time::sleep(Duration::from_secs(1)) => {
-                // Reap zombie children periodically
-                signals::reap_children();
-            }
-        }
-    }
+    let _ = shutdown_rx.recv().await;
+    info!("Shutdown signal received");
 
     info!("mvirt-one shutting down");
     Ok(())
 }
 
+/// Run as the guest agent of a regular VM.
+///
+/// The guest's own init has done the mounting and networking; we only
+/// serve the api channel and run the host's commands on the guest.
+async fn run_agent() -> Result<()> {
+    prctl::set_child_subreaper(true)
+        .map_err(|e| anyhow::anyhow!("Failed to set as child subreaper: {}", e))?;
+    let _reaper = signals::spawn_reaper();
+
+    let services = initialize_services(Config::default()).await?;
+    let api_handler = create_api_handler(
+        services.pod_tx,
+        services.image_tx,
+        services.shutdown_tx,
+        services.pull_progress_tx,
+    )
+    .with_guest_exec();
+    let _vsock_handle = start_vsock_server(api_handler).await?;
+
+    // The host only listens for this while the VM starts, so there's no
+    // point retrying; a restarted agent is found by the next start
+    signal_ready_to_host(&[CHANNEL_API]).await?;
+
+    info!("mvirt-one agent ready");
+    let mut shutdown_rx = services.shutdown_rx;
+    let _ = shutdown_rx.recv().await;
+    info!("mvirt-one agent shutting down");
+    Ok(())
+}
+
 /// Run locally for development/testing.
 async fn run_local(args: Args) -> Result<()> {
     // Set as child subreaper so we can wait for grandchildren
     prctl::set_child_subreaper(true)
         .map_err(|e| anyhow::anyhow!("Failed to set as child subreaper: {}", e))?;
+    let _reaper = signals::spawn_reaper();
 
     // Initialize services
     info!("Initializing services");
@@ -182,7 +220,12 @@ async fn run_local(args: Args) -> Result<()> {
     // Start TCP server for local testing (instead of vsock)
     let addr: SocketAddr = format!("127.0.0.1:{}", args.port).parse()?;
     info!("Starting TCP server on {}", addr);
-    let api_handler = create_api_handler(services.pod_tx, services.shutdown_tx);
+    let api_handler = create_api_handler(
+        services.pod_tx,
+        services.image_tx,
+        services.shutdown_tx,
+        services.pull_progress_tx,
+    );
 
     let listener = TcpListener::bind(addr).await?;
 
@@ -197,28 +240,31 @@ async fn run_local(args: Args) -> Result<()> {
 }
 
 /// Start the vsock server for host communication.
+///
+/// Each connection opens one channel (see [`mvirt_one::vsock`]); api
+/// connections are fed to the gRPC server, logs connections stream the
+/// guest's logs, others are refused.
 async fn start_vsock_server(
     api_handler: mvirt_one::services::pod::PodApiHandler,
 ) -> Result<tokio::task::JoinHandle<()>> {
+    use mvirt_one::vsock::{self, Accepted, CHANNEL_PORT, Prefixed};
     use std::pin::Pin;
     use std::task::{Context, Poll};
     use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio::sync::mpsc;
     use tokio_vsock::{VsockAddr, VsockListener, VsockStream};
     use tonic::transport::server::Connected;
 
     // CID_ANY (u32::MAX) means accept connections from any CID
-    // Port 1024 is our chosen port for the one API
-    const VSOCK_PORT: u32 = 1024;
-
-    let addr = VsockAddr::new(libc::VMADDR_CID_ANY, VSOCK_PORT);
+    let addr = VsockAddr::new(libc::VMADDR_CID_ANY, CHANNEL_PORT);
     let mut listener =
         VsockListener::bind(addr).map_err(|e| anyhow::anyhow!("Failed to bind vsock: {}", e))?;
 
-    info!("vsock server listening on port {}", VSOCK_PORT);
+    info!("vsock server listening on port {}", CHANNEL_PORT);
 
     // Wrapper for VsockStream that implements Connected
     struct VsockConnection {
-        inner: VsockStream,
+        inner: Prefixed<VsockStream>,
         peer_cid: u32,
     }
 
@@ -269,23 +315,73 @@ async fn start_vsock_server(
         }
     }
 
-    let handle = tokio::spawn(async move {
-        // Convert vsock listener to a stream of connections
-        let incoming = async_stream::stream! {
-            loop {
-                match listener.accept().await {
-                    Ok((stream, addr)) => {
-                        info!("vsock connection from CID {}", addr.cid());
-                        let conn = VsockConnection {
-                            inner: stream,
-                            peer_cid: addr.cid(),
-                        };
-                        yield Ok::<_, std::io::Error>(conn);
+    let (api_tx, mut api_rx) = mpsc::channel::<VsockConnection>(16);
+
+    // Route connections by channel. The preamble is read in a task per
+    // connection so a silent peer can't hold up the others.
+    tokio::spawn(async move {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    error!("vsock accept error: {}", e);
+                    continue;
+                }
+            };
+            let peer_cid = addr.cid();
+            let api_tx = api_tx.clone();
+            tokio::spawn(async move {
+                let inner = match vsock::accept_channel(stream).await {
+                    Ok(Accepted::Channel(channel, mut stream)) if channel == CHANNEL_API => {
+                        if let Err(e) = vsock::reply_channel(&mut stream, Ok(())).await {
+                            error!(
+                                "vsock: Failed to accept channel from CID {}: {}",
+                                peer_cid, e
+                            );
+                            return;
+                        }
+                        Prefixed::new(Vec::new(), stream)
+                    }
+                    Ok(Accepted::Channel(channel, mut stream)) if channel == CHANNEL_LOGS => {
+                        if vsock::reply_channel(&mut stream, Ok(())).await.is_err() {
+                            return;
+                        }
+                        info!("vsock logs connection from CID {}", peer_cid);
+                        if let Err(e) = logs::serve(stream).await {
+                            info!("vsock logs connection from CID {} closed: {}", peer_cid, e);
+                        }
+                        return;
+                    }
+                    Ok(Accepted::Channel(channel, mut stream)) => {
+                        warn!(
+                            "vsock: CID {} asked for unknown channel {:?}",
+                            peer_cid, channel
+                        );
+                        let _ = vsock::reply_channel(&mut stream, Err("unknown channel")).await;
+                        return;
+                    }
+                    Ok(Accepted::Legacy(stream)) => {
+                        info!(
+                            "vsock: Unversioned connection from CID {}, serving api",
+                            peer_cid
+                        );
+                        stream
                     }
                     Err(e) => {
-                        error!("vsock accept error: {}", e);
+                        error!("vsock: Bad channel request from CID {}: {}", peer_cid, e);
+                        return;
                     }
-                }
+                };
+                info!("vsock api connection from CID {}", peer_cid);
+                let _ = api_tx.send(VsockConnection { inner, peer_cid }).await;
+            });
+        }
+    });
+
+    let handle = tokio::spawn(async move {
+        let incoming = async_stream::stream! {
+            while let Some(conn) = api_rx.recv().await {
+                yield Ok::<_, std::io::Error>(conn);
             }
         };
 
@@ -303,14 +399,14 @@ async fn start_vsock_server(
 
 /// Signal to the host that mvirt-one is ready.
 ///
-/// Connects to the host (CID 2) on port 1025 to notify that
-/// the guest is ready to receive gRPC calls.
-async fn signal_ready_to_host() -> Result<()> {
+/// Connects to the host (CID 2) on the ready port and sends the hello
+/// with our protocol version and the `channels` we serve.
+async fn signal_ready_to_host(channels: &[&str]) -> Result<()> {
+    use mvirt_one::vsock::{Hello, READY_PORT};
     use tokio::io::AsyncWriteExt;
     use tokio_vsock::{VsockAddr, VsockStream};
 
     const HOST_CID: u32 = 2; // VMADDR_CID_HOST
-    const READY_PORT: u32 = 1025;
 
     let addr = VsockAddr::new(HOST_CID, READY_PORT);
 
@@ -321,10 +417,14 @@ async fn signal_ready_to_host() -> Result<()> {
 
     let mut stream = VsockStream::connect(addr).await?;
 
-    // Send a simple ready message (the connection itself is the signal)
-    stream.write_all(b"READY\n").await?;
+    let hello = Hello::new(channels);
+    stream.write_all(hello.to_line().as_bytes()).await?;
     // Connection will be closed when stream is dropped
 
-    info!("Ready signal sent to host");
+    info!(
+        "Ready signal sent to host (protocol v{}, channels: {})",
+        hello.version,
+        hello.channels.join(",")
+    );
     Ok(())
 }
@@ -0,0 +1,353 @@
+//! OCI Archive Reader - Loads images from OCI image layout tarballs.
+//!
+//! Air-gapped hosts can't let the guest pull from a registry; the host
+//! streams the archive of an `oci-archive:` image instead (see `LoadImage`).
+//! The tar is read while it arrives and yields the same data as a pull.
+
+use super::{PulledImageData, PulledLayer};
+use crate::error::ImageError;
+use log::{info, warn};
+use oci_distribution::manifest;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::io::{self, Read};
+use tar::Archive;
+use tokio::sync::mpsc;
+
+/// Layer media types an archive may hold. Unlike registries, archives
+/// written by `skopeo` or `buildah` often keep layers uncompressed.
+const LAYER_MEDIA_TYPES: [&str; 4] = [
+    manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE,
+    manifest::IMAGE_DOCKER_LAYER_GZIP_MEDIA_TYPE,
+    manifest::IMAGE_LAYER_MEDIA_TYPE,
+    manifest::IMAGE_DOCKER_LAYER_TAR_MEDIA_TYPE,
+];
+
+/// Nested indexes followed before giving up.
+const MAX_INDEX_DEPTH: usize = 4;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Descriptor {
+    #[serde(default)]
+    media_type: String,
+    digest: String,
+    platform: Option<Platform>,
+}
+
+#[derive(Deserialize)]
+struct Platform {
+    architecture: String,
+    os: String,
+}
+
+#[derive(Deserialize)]
+struct Index {
+    manifests: Vec<Descriptor>,
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    config: Descriptor,
+    layers: Vec<Descriptor>,
+}
+
+/// Blocking reader over archive chunks arriving on a channel. A chunk that
+/// is an error fails the read, so a broken stream isn't taken for the end
+/// of the archive.
+pub struct ChunkReader {
+    rx: mpsc::Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+}
+
+impl ChunkReader {
+    pub fn new(rx: mpsc::Receiver<io::Result<Vec<u8>>>) -> Self {
+        Self {
+            rx,
+            chunk: Vec::new(),
+            pos: 0,
+        }
+    }
+}
+
+impl std::fmt::Debug for ChunkReader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ChunkReader").finish_non_exhaustive()
+    }
+}
+
+impl Read for ChunkReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk?;
+                    self.pos = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// Read the image of an OCI archive. Of a multi-platform archive, the image
+/// for this guest's architecture is read.
+pub fn read_oci_archive(reader: impl Read) -> Result<PulledImageData, ImageError> {
+    let mut files = read_layout(reader)?;
+    let index = files.get("index.json").ok_or_else(|| {
+        ImageError::LayerExtraction("archive has no index.json, it is no OCI archive".to_string())
+    })?;
+    let manifest = resolve_manifest(&files, index)?;
+
+    let config = blob(&files, &manifest.config.digest)?.clone();
+    let mut layers = Vec::new();
+    for (i, layer) in manifest.layers.iter().enumerate() {
+        if !LAYER_MEDIA_TYPES.contains(&layer.media_type.as_str()) {
+            warn!(
+                "ImageArchive: Skipping layer with unsupported media type: {}",
+                layer.media_type
+            );
+            continue;
+        }
+        // Identical layers (e.g. empty ones) share a blob
+        let shared = manifest.layers[i + 1..]
+            .iter()
+            .any(|l| l.digest == layer.digest);
+        let data = if shared {
+            blob(&files, &layer.digest)?.clone()
+        } else {
+            files
+                .remove(&blob_path(&layer.digest)?)
+                .ok_or_else(|| missing_blob(&layer.digest))?
+        };
+        info!(
+            "ImageArchive: Read layer {} ({} bytes)",
+            layer.digest,
+            data.len()
+        );
+        layers.push(PulledLayer {
+            media_type: layer.media_type.clone(),
+            data,
+        });
+    }
+
+    if layers.is_empty() {
+        return Err(ImageError::LayerExtraction(
+            "No compatible layers found".to_string(),
+        ));
+    }
+
+    Ok(PulledImageData { config, layers })
+}
+
+/// The files of the image layout: `index.json` and the blobs.
+fn read_layout(reader: impl Read) -> Result<HashMap<String, Vec<u8>>, ImageError> {
+    let read_err = |e: io::Error| ImageError::LayerExtraction(format!("read archive: {}", e));
+    let mut files = HashMap::new();
+    let mut archive = Archive::new(reader);
+    for entry in archive.entries().map_err(read_err)? {
+        let mut entry = entry.map_err(read_err)?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = {
+            let path = entry.path().map_err(read_err)?;
+            path.to_string_lossy().trim_start_matches("./").to_string()
+        };
+        if path != "index.json" && !path.starts_with("blobs/") {
+            continue;
+        }
+        let mut data = Vec::with_capacity(entry.size() as usize);
+        entry.read_to_end(&mut data).map_err(read_err)?;
+        files.insert(path, data);
+    }
+    Ok(files)
+}
+
+/// Follow the index down to the image manifest for this guest.
+fn resolve_manifest(
+    files: &HashMap<String, Vec<u8>>,
+    index: &[u8],
+) -> Result<Manifest, ImageError> {
+    let mut index: Index = parse_json("index.json", index)?;
+    for _ in 0..MAX_INDEX_DEPTH {
+        let descriptor = pick_manifest(index.manifests)?;
+        let data = blob(files, &descriptor.digest)?;
+        match descriptor.media_type.as_str() {
+            manifest::OCI_IMAGE_INDEX_MEDIA_TYPE | manifest::IMAGE_MANIFEST_LIST_MEDIA_TYPE => {
+                index = parse_json(&descriptor.digest, data)?;
+            }
+            _ => return parse_json(&descriptor.digest, data),
+        }
+    }
+    Err(ImageError::LayerExtraction(
+        "image index nested too deep".to_string(),
+    ))
+}
+
+/// The only manifest, or the one for this guest's platform.
+fn pick_manifest(manifests: Vec<Descriptor>) -> Result<Descriptor, ImageError> {
+    if manifests.len() == 1 {
+        return Ok(manifests.into_iter().next().unwrap());
+    }
+    let arch = guest_architecture();
+    let count = manifests.len();
+    manifests
+        .into_iter()
+        .find(|m| {
+            m.platform
+                .as_ref()
+                .is_some_and(|p| p.os == "linux" && p.architecture == arch)
+        })
+        .ok_or_else(|| {
+            ImageError::NotFound(format!(
+                "none of the {} images in the archive is for linux/{}",
+                count, arch
+            ))
+        })
+}
+
+/// This guest's architecture in OCI platform terms.
+fn guest_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        arch => arch,
+    }
+}
+
+fn blob_path(digest: &str) -> Result<String, ImageError> {
+    match digest.split_once(':') {
+        Some((algorithm, hex))
+            if !algorithm.is_empty() && !hex.is_empty() && !digest.contains('/') =>
+        {
+            Ok(format!("blobs/{}/{}", algorithm, hex))
+        }
+        _ => Err(ImageError::InvalidReference(format!(
+            "invalid digest {}",
+            digest
+        ))),
+    }
+}
+
+fn blob<'a>(files: &'a HashMap<String, Vec<u8>>, digest: &str) -> Result<&'a Vec<u8>, ImageError> {
+    files
+        .get(&blob_path(digest)?)
+        .ok_or_else(|| missing_blob(digest))
+}
+
+fn missing_blob(digest: &str) -> ImageError {
+    ImageError::NotFound(format!("blob {} is missing from the archive", digest))
+}
+
+fn parse_json<T: for<'de> Deserialize<'de>>(name: &str, data: &[u8]) -> Result<T, ImageError> {
+    serde_json::from_slice(data)
+        .map_err(|e| ImageError::LayerExtraction(format!("parse {}: {}", name, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tar of the given (path, contents).
+    fn tar(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, path, *data).unwrap();
+        }
+        builder.into_inner().unwrap()
+    }
+
+    fn descriptor(media_type: &str, digest: &str) -> String {
+        format!(
+            r#"{{"mediaType":"{}","digest":"{}","size":1}}"#,
+            media_type, digest
+        )
+    }
+
+    #[test]
+    fn reads_single_image() {
+        let manifest = format!(
+            r#"{{"schemaVersion":2,"config":{},"layers":[{},{},{}]}}"#,
+            descriptor("application/vnd.oci.image.config.v1+json", "sha256:c0"),
+            descriptor(manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE, "sha256:l1"),
+            descriptor(manifest::IMAGE_LAYER_MEDIA_TYPE, "sha256:l2"),
+            descriptor(manifest::IMAGE_LAYER_GZIP_MEDIA_TYPE, "sha256:l1"),
+        );
+        let index = format!(
+            r#"{{"schemaVersion":2,"manifests":[{}]}}"#,
+            descriptor(manifest::OCI_IMAGE_MEDIA_TYPE, "sha256:m0")
+        );
+        let archive = tar(&[
+            ("oci-layout", br#"{"imageLayoutVersion":"1.0.0"}"#),
+            ("./index.json", index.as_bytes()),
+            ("blobs/sha256/m0", manifest.as_bytes()),
+            ("blobs/sha256/c0", b"config"),
+            ("blobs/sha256/l1", b"gzip layer"),
+            ("blobs/sha256/l2", b"tar layer"),
+        ]);
+
+        let image = read_oci_archive(archive.as_slice()).unwrap();
+        assert_eq!(image.config, b"config");
+        let layers: Vec<&[u8]> = image.layers.iter().map(|l| l.data.as_slice()).collect();
+        assert_eq!(layers, [&b"gzip layer"[..], b"tar layer", b"gzip layer"]);
+        assert_eq!(image.layers[1].media_type, manifest::IMAGE_LAYER_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn picks_guest_platform() {
+        let platform = |arch: &str, digest: &str| {
+            format!(
+                r#"{{"mediaType":"{}","digest":"{}","size":1,"platform":{{"architecture":"{}","os":"linux"}}}}"#,
+                manifest::OCI_IMAGE_MEDIA_TYPE,
+                digest,
+                arch
+            )
+        };
+        let index = format!(
+            r#"{{"manifests":[{},{}]}}"#,
+            platform("s390x", "sha256:other"),
+            platform(guest_architecture(), "sha256:ours"),
+        );
+        let manifests: Index = serde_json::from_str(&index).unwrap();
+        assert_eq!(
+            pick_manifest(manifests.manifests).unwrap().digest,
+            "sha256:ours"
+        );
+
+        let index = format!(r#"{{"manifests":[{}]}}"#, platform("s390x", "sha256:other"));
+        let manifests: Index = serde_json::from_str(&index).unwrap();
+        // A single image is used whatever it claims to be
+        assert!(pick_manifest(manifests.manifests).is_ok());
+    }
+
+    #[test]
+    fn rejects_non_oci_archives() {
+        let archive = tar(&[("manifest.json", b"[]")]);
+        assert!(read_oci_archive(archive.as_slice()).is_err());
+        assert!(blob_path("sha256:../../etc").is_err());
+        assert!(blob_path("nodigest").is_err());
+    }
+
+    #[test]
+    fn chunk_reader_fails_on_stream_error() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.try_send(Ok(b"ab".to_vec())).unwrap();
+        tx.try_send(Ok(b"c".to_vec())).unwrap();
+        tx.try_send(Err(io::Error::other("stream reset"))).unwrap();
+        let mut reader = ChunkReader::new(rx);
+        let mut data = Vec::new();
+        let err = reader.read_to_end(&mut data).unwrap_err();
+        assert_eq!(data, b"abc");
+        assert_eq!(err.to_string(), "stream reset");
+    }
+}
@@ -28,6 +28,8 @@ struct ImageMetadata {
     env: Vec<String>,
     #[serde(default)]
     working_dir: String,
+    #[serde(default)]
+    stop_signal: String,
 }
 
 /// Commands for the FileStore actor.
@@ -169,6 +171,7 @@ impl FileStore {
             cmd: image_config.cmd,
             env: image_config.env,
             working_dir: image_config.working_dir,
+            stop_signal: image_config.stop_signal,
         };
         let metadata_json = serde_json::to_string_pretty(&metadata)
             .map_err(|e| ImageError::Storage(std::io::Error::other(e)))?;
@@ -221,6 +224,7 @@ impl FileStore {
                                     cmd: metadata.cmd,
                                     env: metadata.env,
                                     working_dir: metadata.working_dir,
+                                    stop_signal: metadata.stop_signal,
                                 },
                             };
                             store.insert(uuid.to_string(), image_info);
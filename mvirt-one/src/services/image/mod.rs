@@ -1,18 +1,25 @@
 //! Image Service - OCI image pulling and storage.
 //!
 //! Downloads container images from OCI registries and extracts layers to rootfs.
+//! Images of air-gapped hosts arrive as OCI archives streamed by the host.
 //! Based on FeOS image-service pattern.
 
+mod archive;
 mod filestore;
 pub mod orchestrator;
 mod puller;
 
+pub use archive::ChunkReader;
 pub use filestore::FileStore;
 pub use orchestrator::ImageOrchestrator;
 
 use crate::error::ImageError;
 use tokio::sync::oneshot;
 
+/// Prefix of image references the host loads from an OCI archive instead of
+/// a registry, e.g. `oci-archive:/srv/images/app.tar`.
+pub const ARCHIVE_PREFIX: &str = "oci-archive:";
+
 /// Commands that can be sent to the Image Service.
 #[derive(Debug)]
 pub enum Command {
@@ -21,6 +28,13 @@ pub enum Command {
         image_ref: String,
         responder: oneshot::Sender<Result<PullResponse, ImageError>>,
     },
+    /// Load an image from an OCI archive and cache it as `image_ref`,
+    /// replacing what was loaded under that name before.
+    Load {
+        image_ref: String,
+        archive: ChunkReader,
+        responder: oneshot::Sender<Result<PullResponse, ImageError>>,
+    },
     /// Get information about a cached image.
     Get {
         image_id: String,
@@ -52,6 +66,8 @@ pub struct ImageConfig {
     pub cmd: Vec<String>,
     pub env: Vec<String>,
     pub working_dir: String,
+    /// Signal that asks the image's process to exit, empty if unset
+    pub stop_signal: String,
 }
 
 /// Information about a cached image.
@@ -73,6 +89,21 @@ pub enum ImageState {
     Failed,
 }
 
+/// Download progress of an image pull, published per layer as bytes arrive.
+#[derive(Debug, Clone, Default)]
+pub struct PullProgress {
+    pub image_ref: String,
+    pub layer_digest: String,
+    /// 0-based index of the layer being downloaded.
+    pub layer_index: u32,
+    pub layer_count: u32,
+    pub layer_downloaded_bytes: u64,
+    pub layer_total_bytes: u64,
+    /// Across all layers of the image.
+    pub downloaded_bytes: u64,
+    pub total_bytes: u64,
+}
+
 /// Data from a pulled image.
 #[derive(Debug)]
 pub struct PulledImageData {
@@ -106,6 +137,8 @@ pub fn parse_image_config(config_json: &[u8]) -> ImageConfig {
         env: Option<Vec<String>>,
         #[serde(rename = "WorkingDir")]
         working_dir: Option<String>,
+        #[serde(rename = "StopSignal")]
+        stop_signal: Option<String>,
     }
 
     match serde_json::from_slice::<OciImageSpec>(config_json) {
@@ -116,6 +149,7 @@ pub fn parse_image_config(config_json: &[u8]) -> ImageConfig {
                 cmd: config.cmd.unwrap_or_default(),
                 env: config.env.unwrap_or_default(),
                 working_dir: config.working_dir.unwrap_or_default(),
+                stop_signal: config.stop_signal.unwrap_or_default(),
             }
         }
         Err(e) => {
@@ -2,14 +2,18 @@
 //!
 //! Based on FeOS image-service/worker.rs pattern.
 
+use super::archive::read_oci_archive;
 use super::filestore::{FileCommand, FileStore};
 use super::puller::pull_oci_image;
-use super::{Command, ImageConfig, ImageInfo, ImageState, PullResponse, parse_image_config};
+use super::{
+    ARCHIVE_PREFIX, Command, ImageConfig, ImageInfo, ImageState, PullProgress, PullResponse,
+    PulledImageData, parse_image_config,
+};
 use crate::error::ImageError;
 use log::{error, info};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot};
 use uuid::Uuid;
 
 /// Image Orchestrator - coordinates pulling and storage of images.
@@ -17,6 +21,7 @@ pub struct ImageOrchestrator {
     command_rx: mpsc::Receiver<Command>,
     command_tx: mpsc::Sender<Command>,
     filestore_tx: mpsc::Sender<FileCommand>,
+    progress_tx: broadcast::Sender<PullProgress>,
     store: HashMap<String, ImageInfo>,
 }
 
@@ -24,10 +29,12 @@ impl ImageOrchestrator {
     /// Create a new Image Orchestrator.
     pub fn new(filestore_tx: mpsc::Sender<FileCommand>) -> Self {
         let (command_tx, command_rx) = mpsc::channel(32);
+        let (progress_tx, _) = broadcast::channel(64);
         Self {
             command_rx,
             command_tx,
             filestore_tx,
+            progress_tx,
             store: HashMap::new(),
         }
     }
@@ -37,6 +44,12 @@ impl ImageOrchestrator {
         self.command_tx.clone()
     }
 
+    /// Get the channel pull progress is published on. Subscribers don't go
+    /// through the command loop, which is busy for the length of a pull.
+    pub fn get_progress_sender(&self) -> broadcast::Sender<PullProgress> {
+        self.progress_tx.clone()
+    }
+
     /// Run the orchestrator loop.
     pub async fn run(mut self) {
         // Scan for existing images on startup
@@ -80,6 +93,15 @@ impl ImageOrchestrator {
                     }
                 }
 
+                // Nothing to pull from; the host loads these first
+                if image_ref.starts_with(ARCHIVE_PREFIX) {
+                    let _ = responder.send(Err(ImageError::NotFound(format!(
+                        "{} was not loaded from the host",
+                        image_ref
+                    ))));
+                    return;
+                }
+
                 let image_id = Uuid::new_v4().to_string();
                 info!(
                     "ImageOrchestrator: Starting pull for '{}', assigned ID {}",
@@ -99,7 +121,7 @@ impl ImageOrchestrator {
                 );
 
                 // Pull the image
-                let image_data = match pull_oci_image(&image_ref).await {
+                let image_data = match pull_oci_image(&image_ref, &self.progress_tx).await {
                     Ok(data) => data,
                     Err(e) => {
                         error!("ImageOrchestrator: Pull failed for {}: {}", image_ref, e);
@@ -111,65 +133,53 @@ impl ImageOrchestrator {
                     }
                 };
 
-                // Parse image config (Entrypoint, Cmd, Env, etc.)
-                let image_config = parse_image_config(&image_data.config);
-
-                // Update state to extracting and store config
-                if let Some(info) = self.store.get_mut(&image_id) {
-                    info.state = ImageState::Extracting;
-                    info.config = image_config.clone();
-                }
-
-                // Store the image
-                let (store_responder, store_rx) = oneshot::channel();
-                let store_cmd = FileCommand::Store {
-                    image_id: image_id.clone(),
-                    image_ref: image_ref.clone(),
-                    image_data,
-                    responder: store_responder,
-                };
-
-                if self.filestore_tx.send(store_cmd).await.is_err() {
-                    error!("ImageOrchestrator: Failed to send to FileStore");
-                    if let Some(info) = self.store.get_mut(&image_id) {
-                        info.state = ImageState::Failed;
-                    }
-                    let _ = responder.send(Err(ImageError::Storage(std::io::Error::other(
-                        "FileStore channel closed",
-                    ))));
-                    return;
-                }
-
-                match store_rx.await {
-                    Ok(Ok(rootfs_path)) => {
-                        info!("ImageOrchestrator: Image {} stored successfully", image_id);
-                        if let Some(info) = self.store.get_mut(&image_id) {
-                            info.state = ImageState::Ready;
-                            info.rootfs_path = rootfs_path.clone();
-                        }
-                        let _ = responder.send(Ok(PullResponse {
-                            image_id,
-                            rootfs_path,
-                            config: image_config,
-                        }));
-                    }
-                    Ok(Err(e)) => {
-                        error!("ImageOrchestrator: FileStore failed to store image: {}", e);
-                        if let Some(info) = self.store.get_mut(&image_id) {
-                            info.state = ImageState::Failed;
+                let result = self.store_image(image_id, image_ref, image_data).await;
+                let _ = responder.send(result);
+            }
+            Command::Load {
+                image_ref,
+                archive,
+                responder,
+            } => {
+                info!("ImageOrchestrator: Loading '{}' from archive", image_ref);
+                // The archive is read as the host streams it
+                let image_data =
+                    match tokio::task::spawn_blocking(move || read_oci_archive(archive)).await {
+                        Ok(Ok(data)) => data,
+                        Ok(Err(e)) => {
+                            error!("ImageOrchestrator: Load failed for {}: {}", image_ref, e);
+                            let _ = responder.send(Err(e));
+                            return;
                         }
-                        let _ = responder.send(Err(e));
-                    }
-                    Err(_) => {
-                        error!("ImageOrchestrator: FileStore dropped response channel");
-                        if let Some(info) = self.store.get_mut(&image_id) {
-                            info.state = ImageState::Failed;
+                        Err(e) => {
+                            let _ = responder.send(Err(ImageError::LayerExtraction(e.to_string())));
+                            return;
                         }
-                        let _ = responder.send(Err(ImageError::Storage(std::io::Error::other(
-                            "FileStore response channel dropped",
-                        ))));
-                    }
+                    };
+
+                let stale: Vec<String> = self
+                    .store
+                    .iter()
+                    .filter(|(_, info)| info.image_ref == image_ref)
+                    .map(|(id, _)| id.clone())
+                    .collect();
+                for image_id in stale {
+                    self.delete_image(image_id).await;
                 }
+
+                let image_id = Uuid::new_v4().to_string();
+                self.store.insert(
+                    image_id.clone(),
+                    ImageInfo {
+                        image_id: image_id.clone(),
+                        image_ref: image_ref.clone(),
+                        rootfs_path: String::new(),
+                        state: ImageState::Extracting,
+                        config: ImageConfig::default(),
+                    },
+                );
+                let result = self.store_image(image_id, image_ref, image_data).await;
+                let _ = responder.send(result);
             }
             Command::Get {
                 image_id,
@@ -186,29 +196,104 @@ impl ImageOrchestrator {
                 image_id,
                 responder,
             } => {
-                info!("ImageOrchestrator: Deleting image {}", image_id);
-                self.store.remove(&image_id);
+                self.delete_image(image_id).await;
+                let _ = responder.send(Ok(()));
+            }
+        }
+    }
 
-                let (file_resp_tx, file_resp_rx) = oneshot::channel();
-                let file_cmd = FileCommand::Delete {
-                    image_id: image_id.clone(),
-                    responder: file_resp_tx,
-                };
+    /// Extract pulled or loaded image data and mark the image ready.
+    async fn store_image(
+        &mut self,
+        image_id: String,
+        image_ref: String,
+        image_data: PulledImageData,
+    ) -> Result<PullResponse, ImageError> {
+        // Parse image config (Entrypoint, Cmd, Env, etc.)
+        let image_config = parse_image_config(&image_data.config);
 
-                if self.filestore_tx.send(file_cmd).await.is_err() {
-                    error!("ImageOrchestrator: Failed to send delete to FileStore");
-                }
+        // Update state to extracting and store config
+        if let Some(info) = self.store.get_mut(&image_id) {
+            info.state = ImageState::Extracting;
+            info.config = image_config.clone();
+        }
 
-                // Wait for deletion but don't fail if it errors
-                let _ = file_resp_rx.await;
-                let _ = responder.send(Ok(()));
+        // Store the image
+        let (store_responder, store_rx) = oneshot::channel();
+        let store_cmd = FileCommand::Store {
+            image_id: image_id.clone(),
+            image_ref,
+            image_data,
+            responder: store_responder,
+        };
+
+        if self.filestore_tx.send(store_cmd).await.is_err() {
+            error!("ImageOrchestrator: Failed to send to FileStore");
+            if let Some(info) = self.store.get_mut(&image_id) {
+                info.state = ImageState::Failed;
             }
+            return Err(ImageError::Storage(std::io::Error::other(
+                "FileStore channel closed",
+            )));
         }
+
+        match store_rx.await {
+            Ok(Ok(rootfs_path)) => {
+                info!("ImageOrchestrator: Image {} stored successfully", image_id);
+                if let Some(info) = self.store.get_mut(&image_id) {
+                    info.state = ImageState::Ready;
+                    info.rootfs_path = rootfs_path.clone();
+                }
+                Ok(PullResponse {
+                    image_id,
+                    rootfs_path,
+                    config: image_config,
+                })
+            }
+            Ok(Err(e)) => {
+                error!("ImageOrchestrator: FileStore failed to store image: {}", e);
+                if let Some(info) = self.store.get_mut(&image_id) {
+                    info.state = ImageState::Failed;
+                }
+                Err(e)
+            }
+            Err(_) => {
+                error!("ImageOrchestrator: FileStore dropped response channel");
+                if let Some(info) = self.store.get_mut(&image_id) {
+                    info.state = ImageState::Failed;
+                }
+                Err(ImageError::Storage(std::io::Error::other(
+                    "FileStore response channel dropped",
+                )))
+            }
+        }
+    }
+
+    async fn delete_image(&mut self, image_id: String) {
+        info!("ImageOrchestrator: Deleting image {}", image_id);
+        self.store.remove(&image_id);
+
+        let (file_resp_tx, file_resp_rx) = oneshot::channel();
+        let file_cmd = FileCommand::Delete {
+            image_id,
+            responder: file_resp_tx,
+        };
+
+        if self.filestore_tx.send(file_cmd).await.is_err() {
+            error!("ImageOrchestrator: Failed to send delete to FileStore");
+        }
+
+        // Wait for deletion but don't fail if it errors
+        let _ = file_resp_rx.await;
     }
 }
 
 /// Initialize the Image Service.
-pub async fn initialize_image_service(base_dir: PathBuf) -> mpsc::Sender<Command> {
+///
+/// Returns the command sender and the pull progress channel.
+pub async fn initialize_image_service(
+    base_dir: PathBuf,
+) -> (mpsc::Sender<Command>, broadcast::Sender<PullProgress>) {
     let filestore = FileStore::new(base_dir);
     let filestore_tx = filestore.get_command_sender();
     tokio::spawn(async move {
@@ -218,10 +303,11 @@ pub async fn initialize_image_service(base_dir: PathBuf) -> mpsc::Sender<Command
 
     let orchestrator = ImageOrchestrator::new(filestore_tx);
     let orchestrator_tx = orchestrator.get_command_sender();
+    let progress_tx = orchestrator.get_progress_sender();
     tokio::spawn(async move {
         orchestrator.run().await;
     });
     info!("ImageService: Orchestrator started");
 
-    orchestrator_tx
+    (orchestrator_tx, progress_tx)
 }
@@ -2,13 +2,64 @@
 //!
 //! Based on FeOS image-service/worker.rs pattern.
 
-use super::{PulledImageData, PulledLayer};
+use super::{PullProgress, PulledImageData, PulledLayer};
 use crate::error::ImageError;
 use log::{info, warn};
 use oci_distribution::{Client, Reference, client::ClientConfig, manifest, secrets::RegistryAuth};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::AsyncWrite;
+use tokio::sync::broadcast;
 
-/// Pull an OCI image from a registry.
-pub async fn pull_oci_image(image_ref: &str) -> Result<PulledImageData, ImageError> {
+/// Publish progress at most once per this many downloaded bytes.
+const PROGRESS_STEP_BYTES: u64 = 1024 * 1024;
+
+/// Buffers a layer blob and publishes progress as it is written.
+struct ProgressWriter<'a> {
+    data: Vec<u8>,
+    progress: PullProgress,
+    last_published: u64,
+    progress_tx: &'a broadcast::Sender<PullProgress>,
+}
+
+impl ProgressWriter<'_> {
+    fn publish(&mut self) {
+        self.last_published = self.progress.layer_downloaded_bytes;
+        // No subscribers is fine; nobody is watching this pull.
+        let _ = self.progress_tx.send(self.progress.clone());
+    }
+}
+
+impl AsyncWrite for ProgressWriter<'_> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        this.data.extend_from_slice(buf);
+        this.progress.layer_downloaded_bytes += buf.len() as u64;
+        this.progress.downloaded_bytes += buf.len() as u64;
+        if this.progress.layer_downloaded_bytes - this.last_published >= PROGRESS_STEP_BYTES {
+            this.publish();
+        }
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Pull an OCI image from a registry, publishing per-layer progress.
+pub async fn pull_oci_image(
+    image_ref: &str,
+    progress_tx: &broadcast::Sender<PullProgress>,
+) -> Result<PulledImageData, ImageError> {
     info!("ImagePuller: Fetching image: {}", image_ref);
 
     let reference = Reference::try_from(image_ref.to_string())
@@ -41,34 +92,56 @@ pub async fn pull_oci_image(image_ref: &str) -> Result<PulledImageData, ImageErr
         config_data.len()
     );
 
-    let mut layers = Vec::new();
-    for layer in manifest.layers {
-        if !accepted_media_types.contains(&layer.media_type.as_str()) {
-            warn!(
-                "ImagePuller: Skipping layer with unsupported media type: {}",
-                layer.media_type
-            );
-            continue;
-        }
+    let (pulled, skipped): (Vec<_>, Vec<_>) = manifest
+        .layers
+        .into_iter()
+        .partition(|layer| accepted_media_types.contains(&layer.media_type.as_str()));
+    for layer in &skipped {
+        warn!(
+            "ImagePuller: Skipping layer with unsupported media type: {}",
+            layer.media_type
+        );
+    }
 
+    let total_bytes: u64 = pulled.iter().map(|l| l.size.max(0) as u64).sum();
+    let mut downloaded_bytes = 0;
+    let mut layers = Vec::new();
+    for (index, layer) in pulled.iter().enumerate() {
         info!(
             "ImagePuller: Pulling layer {} ({})",
             layer.digest, layer.media_type
         );
 
-        let mut layer_data = Vec::new();
+        let mut writer = ProgressWriter {
+            data: Vec::new(),
+            progress: PullProgress {
+                image_ref: image_ref.to_string(),
+                layer_digest: layer.digest.clone(),
+                layer_index: index as u32,
+                layer_count: pulled.len() as u32,
+                layer_downloaded_bytes: 0,
+                layer_total_bytes: layer.size.max(0) as u64,
+                downloaded_bytes,
+                total_bytes,
+            },
+            last_published: 0,
+            progress_tx,
+        };
+        writer.publish();
         client
-            .pull_blob(&reference, &layer, &mut layer_data)
+            .pull_blob(&reference, layer, &mut writer)
             .await
             .map_err(|e| ImageError::Registry(e.to_string()))?;
+        writer.publish();
+        downloaded_bytes = writer.progress.downloaded_bytes;
         info!(
             "ImagePuller: Pulled layer blob ({} bytes)",
-            layer_data.len()
+            writer.data.len()
         );
 
         layers.push(PulledLayer {
             media_type: layer.media_type.clone(),
-            data: layer_data,
+            data: writer.data,
         });
     }
 
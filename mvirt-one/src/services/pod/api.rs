@@ -1,39 +1,81 @@
 //! Pod API Handler - gRPC/vsock API implementation.
 
 use super::Command;
-use crate::error::PodError;
+use crate::error::{ImageError, PodError};
 use crate::proto::{
-    CreatePodRequest, DeletePodRequest, Empty, ExecInput, ExecOutput, GetPodRequest,
-    HealthResponse, InterfaceInfo, ListPodsResponse, LogsRequest, LogsResponse, NetworkInfo, Pod,
-    ShutdownRequest, StartPodRequest, StopPodRequest, one_service_server::OneService,
+    ContainerState, ContainerStats, CreatePodRequest, DeletePodRequest, Empty, ExecInput,
+    ExecOutput, GetPodRequest, HealthResponse, InterfaceInfo, ListPodsResponse, LoadImageChunk,
+    LoadImageResponse, LogsRequest, LogsResponse, NetworkInfo, Pod, PodStats, PullProgress,
+    RunCommandRequest, RunCommandResponse, ShutdownRequest, StartPodRequest, StopPodRequest,
+    one_service_server::OneService,
 };
+use crate::services::image::{
+    self, ChunkReader, Command as ImageCommand, PullProgress as ImagePullProgress,
+};
+use crate::services::task::{self, cgroup};
 use crate::utils::network;
-use log::info;
-use tokio::sync::{mpsc, oneshot};
+use log::{debug, info};
+use std::time::Duration;
+use tokio::sync::{broadcast, mpsc, oneshot};
 use tokio_stream::wrappers::ReceiverStream;
 use tonic::{Request, Response, Status, Streaming};
 
 /// Pod API Handler for gRPC/vsock requests.
 pub struct PodApiHandler {
     command_tx: mpsc::Sender<Command>,
+    image_tx: mpsc::Sender<ImageCommand>,
     shutdown_tx: Option<mpsc::Sender<()>>,
+    pull_progress_tx: broadcast::Sender<ImagePullProgress>,
+    /// RunCommand without a pod runs on the guest itself.
+    guest_exec: bool,
 }
 
 impl PodApiHandler {
     /// Create a new Pod API handler.
-    pub fn new(command_tx: mpsc::Sender<Command>, shutdown_tx: mpsc::Sender<()>) -> Self {
+    pub fn new(
+        command_tx: mpsc::Sender<Command>,
+        image_tx: mpsc::Sender<ImageCommand>,
+        shutdown_tx: mpsc::Sender<()>,
+        pull_progress_tx: broadcast::Sender<ImagePullProgress>,
+    ) -> Self {
         Self {
             command_tx,
+            image_tx,
             shutdown_tx: Some(shutdown_tx),
+            pull_progress_tx,
+            guest_exec: false,
         }
     }
+
+    /// Serve RunCommand without a pod by running the command on the guest
+    /// itself. For the guest agent in regular VMs, whose host runs
+    /// provisioning hooks in it; MicroVMs only run commands in containers.
+    pub fn with_guest_exec(mut self) -> Self {
+        self.guest_exec = true;
+        self
+    }
 }
 
+/// Timeout of RunCommand calls that don't set one.
+const DEFAULT_RUN_COMMAND_TIMEOUT: Duration = Duration::from_secs(30);
+
 fn pod_error_to_status(e: PodError) -> Status {
     match e {
-        PodError::NotFound(_) => Status::not_found(e.to_string()),
+        PodError::NotFound(_) | PodError::ContainerNotFound(_) => Status::not_found(e.to_string()),
         PodError::InvalidState { .. } => Status::failed_precondition(e.to_string()),
         PodError::ContainerFailed { .. } => Status::internal(e.to_string()),
+        PodError::InitFailed { .. } => Status::aborted(e.to_string()),
+    }
+}
+
+fn image_error_to_status(e: ImageError) -> Status {
+    match e {
+        ImageError::InvalidReference(_) | ImageError::LayerExtraction(_) => {
+            Status::invalid_argument(e.to_string())
+        }
+        ImageError::NotFound(_) => Status::not_found(e.to_string()),
+        ImageError::Registry(_) => Status::unavailable(e.to_string()),
+        ImageError::Storage(_) => Status::internal(e.to_string()),
     }
 }
 
@@ -51,6 +93,7 @@ impl OneService for PodApiHandler {
             id: req.id,
             name: req.name,
             containers: req.containers,
+            init_containers: req.init_containers,
             responder,
         };
 
@@ -188,6 +231,159 @@ impl OneService for PodApiHandler {
         Ok(Response::new(ListPodsResponse { pods }))
     }
 
+    async fn get_pod_stats(
+        &self,
+        request: Request<GetPodRequest>,
+    ) -> Result<Response<PodStats>, Status> {
+        let req = request.into_inner();
+        debug!("API: GetPodStats id={}", req.id);
+
+        let (responder, rx) = oneshot::channel();
+        let cmd = Command::Get {
+            id: req.id,
+            responder,
+        };
+
+        self.command_tx
+            .send(cmd)
+            .await
+            .map_err(|_| Status::unavailable("Service unavailable"))?;
+
+        let pod = rx
+            .await
+            .map_err(|_| Status::internal("Service error"))?
+            .map_err(pod_error_to_status)?;
+
+        // Read cgroups here rather than in the dispatcher so stats polling
+        // never holds up lifecycle commands.
+        let timestamp_usec = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_micros() as i64)
+            .unwrap_or(0);
+        let mut containers = Vec::new();
+        for c in pod
+            .containers
+            .iter()
+            .filter(|c| c.state == ContainerState::Running as i32)
+        {
+            if let Some(stats) = cgroup::read_stats(&c.id).await {
+                containers.push(ContainerStats {
+                    container_id: c.id.clone(),
+                    name: c.name.clone(),
+                    cpu_usage_usec: stats.cpu_usage_usec,
+                    memory_bytes: stats.memory_bytes,
+                    memory_limit_bytes: stats.memory_limit_bytes.unwrap_or(0),
+                    pids: stats.pids,
+                });
+            }
+        }
+
+        Ok(Response::new(PodStats {
+            pod_id: pod.id,
+            timestamp_usec,
+            containers,
+        }))
+    }
+
+    type WatchPullProgressStream = ReceiverStream<Result<PullProgress, Status>>;
+
+    async fn watch_pull_progress(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::WatchPullProgressStream>, Status> {
+        debug!("API: WatchPullProgress");
+
+        let mut progress_rx = self.pull_progress_tx.subscribe();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let p = match progress_rx.recv().await {
+                    Ok(p) => p,
+                    // Progress is superseded by the next update anyway
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                let msg = PullProgress {
+                    image_ref: p.image_ref,
+                    layer_digest: p.layer_digest,
+                    layer_index: p.layer_index,
+                    layer_count: p.layer_count,
+                    layer_downloaded_bytes: p.layer_downloaded_bytes,
+                    layer_total_bytes: p.layer_total_bytes,
+                    downloaded_bytes: p.downloaded_bytes,
+                    total_bytes: p.total_bytes,
+                };
+                if tx.send(Ok(msg)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn load_image(
+        &self,
+        request: Request<Streaming<LoadImageChunk>>,
+    ) -> Result<Response<LoadImageResponse>, Status> {
+        let mut stream = request.into_inner();
+        let first = stream
+            .message()
+            .await?
+            .ok_or_else(|| Status::invalid_argument("Image stream is empty"))?;
+        if !first.image_ref.starts_with(image::ARCHIVE_PREFIX) {
+            return Err(Status::invalid_argument(format!(
+                "Only {} images can be loaded",
+                image::ARCHIVE_PREFIX
+            )));
+        }
+        info!("API: LoadImage {}", first.image_ref);
+
+        let (chunk_tx, chunk_rx) = mpsc::channel(16);
+        let (responder, rx) = oneshot::channel();
+        let cmd = ImageCommand::Load {
+            image_ref: first.image_ref,
+            archive: ChunkReader::new(chunk_rx),
+            responder,
+        };
+        self.image_tx
+            .send(cmd)
+            .await
+            .map_err(|_| Status::unavailable("Image service unavailable"))?;
+
+        // Feed the reader until the stream ends or the reader is done with
+        // the archive (or gave up on it)
+        let mut received = first.data.len() as u64;
+        if chunk_tx.send(Ok(first.data)).await.is_ok() {
+            loop {
+                let chunk = match stream.message().await {
+                    Ok(Some(chunk)) => chunk,
+                    Ok(None) => break,
+                    Err(status) => {
+                        let _ = chunk_tx
+                            .send(Err(std::io::Error::other(status.message().to_string())))
+                            .await;
+                        break;
+                    }
+                };
+                received += chunk.data.len() as u64;
+                if chunk_tx.send(Ok(chunk.data)).await.is_err() {
+                    break;
+                }
+            }
+        }
+        drop(chunk_tx);
+
+        let loaded = rx
+            .await
+            .map_err(|_| Status::internal("Service error"))?
+            .map_err(image_error_to_status)?;
+        Ok(Response::new(LoadImageResponse {
+            image_id: loaded.image_id,
+            archive_bytes: received,
+        }))
+    }
+
     type LogsStream = ReceiverStream<Result<LogsResponse, Status>>;
 
     async fn logs(
@@ -208,6 +404,66 @@ impl OneService for PodApiHandler {
         Err(Status::unimplemented("Exec not yet implemented"))
     }
 
+    async fn run_command(
+        &self,
+        request: Request<RunCommandRequest>,
+    ) -> Result<Response<RunCommandResponse>, Status> {
+        let req = request.into_inner();
+        info!(
+            "API: RunCommand pod={} container={}",
+            req.pod_id, req.container_id
+        );
+
+        if req.command.is_empty() {
+            return Err(Status::invalid_argument("command is empty"));
+        }
+        let timeout = match req.timeout_seconds {
+            0 => DEFAULT_RUN_COMMAND_TIMEOUT,
+            secs => Duration::from_secs(secs.into()),
+        };
+
+        if req.pod_id.is_empty() {
+            if !self.guest_exec {
+                return Err(Status::invalid_argument("pod_id is required"));
+            }
+            let result = task::run_on_guest(&req.command, timeout)
+                .await
+                .map_err(|e| Status::internal(format!("Failed to run command: {e}")))?;
+            return Ok(Response::new(RunCommandResponse {
+                exit_code: result.exit_code,
+                stdout: result.stdout,
+                stderr: result.stderr,
+                timed_out: result.timed_out,
+            }));
+        }
+
+        let (responder, rx) = oneshot::channel();
+        let cmd = Command::Exec {
+            id: req.pod_id,
+            container: req.container_id,
+            command: req.command,
+            timeout,
+            responder,
+        };
+
+        self.command_tx
+            .send(cmd)
+            .await
+            .map_err(|_| Status::unavailable("Service unavailable"))?;
+
+        let result = rx
+            .await
+            .map_err(|_| Status::internal("Service error"))?
+            .map_err(pod_error_to_status)?;
+
+        Ok(Response::new(RunCommandResponse {
+            exit_code: result.exit_code,
+            stdout: result.stdout,
+            stderr: result.stderr,
+            timed_out: result.timed_out,
+        }))
+    }
+
     async fn shutdown(&self, request: Request<ShutdownRequest>) -> Result<Response<Empty>, Status> {
         let req = request.into_inner();
         info!("API: Shutdown timeout={}s", req.timeout_seconds);
@@ -3,13 +3,13 @@
 use super::worker;
 use super::{Command, PodData};
 use crate::error::PodError;
-use crate::proto::PodState;
+use crate::proto::{Pod, PodState};
 use crate::services::image::Command as ImageCommand;
 use crate::services::task::{Command as TaskCommand, Event as TaskEvent};
 use log::{error, info};
 use std::collections::HashMap;
 use std::path::PathBuf;
-use tokio::sync::mpsc;
+use tokio::sync::{mpsc, oneshot};
 
 /// Pod Service Dispatcher.
 pub struct PodDispatcher {
@@ -18,6 +18,9 @@ pub struct PodDispatcher {
     image_tx: mpsc::Sender<ImageCommand>,
     task_tx: mpsc::Sender<TaskCommand>,
     pods: HashMap<String, PodData>,
+    /// Start requests of initializing pods, answered once their containers
+    /// run or an init container fails.
+    starting: HashMap<String, oneshot::Sender<Result<Pod, PodError>>>,
     pods_dir: PathBuf,
 }
 
@@ -36,6 +39,7 @@ impl PodDispatcher {
             image_tx,
             task_tx,
             pods: HashMap::new(),
+            starting: HashMap::new(),
             pods_dir,
         }
     }
@@ -50,7 +54,7 @@ impl PodDispatcher {
                     self.handle_command(cmd).await;
                 }
                 Some(event) = self.task_event_rx.recv() => {
-                    self.handle_task_event(event);
+                    self.handle_task_event(event).await;
                 }
                 else => {
                     break;
@@ -67,6 +71,7 @@ impl PodDispatcher {
                 id,
                 name,
                 containers,
+                init_containers,
                 responder,
             } => {
                 info!("PodDispatcher: Create pod {}", name);
@@ -83,6 +88,7 @@ impl PodDispatcher {
                     id.clone(),
                     name,
                     containers,
+                    init_containers,
                     &self.image_tx,
                     &self.pods_dir,
                 )
@@ -118,11 +124,14 @@ impl PodDispatcher {
                     return;
                 }
 
-                match worker::start_pod(pod, &self.task_tx).await {
-                    Ok(()) => {
+                match worker::begin_start(pod, &self.task_tx).await {
+                    Ok(true) => {
                         let response = pod.clone().into();
                         let _ = responder.send(Ok(response));
                     }
+                    Ok(false) => {
+                        self.starting.insert(id, responder);
+                    }
                     Err(e) => {
                         error!("PodDispatcher: Failed to start pod: {}", e);
                         let _ = responder.send(Err(e));
@@ -144,13 +153,14 @@ impl PodDispatcher {
                     }
                 };
 
-                if pod.state != PodState::Running {
+                if !matches!(pod.state, PodState::Running | PodState::Initializing) {
                     let _ = responder.send(Err(PodError::InvalidState {
                         expected: "running".to_string(),
                         actual: format!("{:?}", pod.state),
                     }));
                     return;
                 }
+                abandon_start(&mut self.starting, &id, "stopped");
 
                 match worker::stop_pod(pod, &self.task_tx, timeout_seconds).await {
                     Ok(()) => {
@@ -178,16 +188,18 @@ impl PodDispatcher {
                     }
                 };
 
-                if pod.state == PodState::Running && !force {
+                let running = matches!(pod.state, PodState::Running | PodState::Initializing);
+                if running && !force {
                     let _ = responder.send(Err(PodError::InvalidState {
                         expected: "stopped".to_string(),
-                        actual: "running".to_string(),
+                        actual: format!("{:?}", pod.state),
                     }));
                     return;
                 }
+                abandon_start(&mut self.starting, &id, "deleted");
 
                 // Stop first if running
-                if pod.state == PodState::Running {
+                if running {
                     let _ = worker::stop_pod(pod, &self.task_tx, 10).await;
                 }
 
@@ -214,10 +226,78 @@ impl PodDispatcher {
                 let pods: Vec<_> = self.pods.values().cloned().map(|p| p.into()).collect();
                 let _ = responder.send(pods);
             }
+            Command::Exec {
+                id,
+                container,
+                command,
+                timeout,
+                responder,
+            } => {
+                info!("PodDispatcher: Exec in pod {}", id);
+
+                let pod = match self.pods.get(&id) {
+                    Some(pod) => pod,
+                    None => {
+                        let _ = responder.send(Err(PodError::NotFound(id)));
+                        return;
+                    }
+                };
+
+                if pod.state != PodState::Running {
+                    let _ = responder.send(Err(PodError::InvalidState {
+                        expected: "running".to_string(),
+                        actual: format!("{:?}", pod.state),
+                    }));
+                    return;
+                }
+
+                let target = if container.is_empty() {
+                    pod.containers.first()
+                } else {
+                    pod.containers
+                        .iter()
+                        .find(|c| c.id == container || c.name == container)
+                };
+                let Some(container_id) = target.map(|c| c.id.clone()) else {
+                    let _ = responder.send(Err(PodError::ContainerNotFound(container)));
+                    return;
+                };
+
+                let (task_responder, rx) = oneshot::channel();
+                let cmd = TaskCommand::Exec {
+                    container_id: container_id.clone(),
+                    command,
+                    timeout,
+                    responder: task_responder,
+                };
+                if self.task_tx.send(cmd).await.is_err() {
+                    let _ = responder.send(Err(PodError::ContainerFailed {
+                        container_id,
+                        error: "task service unavailable".to_string(),
+                    }));
+                    return;
+                }
+
+                // Answer once the command is done, without holding up the
+                // commands queued behind it
+                tokio::spawn(async move {
+                    let result = match rx.await {
+                        Ok(result) => result.map_err(|e| PodError::ContainerFailed {
+                            container_id,
+                            error: e.to_string(),
+                        }),
+                        Err(_) => Err(PodError::ContainerFailed {
+                            container_id,
+                            error: "task service error".to_string(),
+                        }),
+                    };
+                    let _ = responder.send(result);
+                });
+            }
         }
     }
 
-    fn handle_task_event(&mut self, event: TaskEvent) {
+    async fn handle_task_event(&mut self, event: TaskEvent) {
         // Find the pod that contains this container
         let container_id = match &event {
             TaskEvent::ContainerStopped { id, .. } => id,
@@ -228,11 +308,45 @@ impl PodDispatcher {
             TaskEvent::ContainerDeleted { id } => id,
         };
 
-        for pod in self.pods.values_mut() {
-            if pod.containers.iter().any(|c| c.id == *container_id) {
-                worker::handle_container_event(pod, &event);
-                break;
+        let Some(pod) = self.pods.values_mut().find(|pod| {
+            pod.containers
+                .iter()
+                .chain(&pod.init_containers)
+                .any(|c| c.id == *container_id)
+        }) else {
+            return;
+        };
+        worker::handle_container_event(pod, &event);
+
+        // An init container exiting starts the next one, or the containers
+        if !self.starting.contains_key(&pod.id) {
+            return;
+        }
+        let result = match worker::continue_start(pod, &self.task_tx).await {
+            Ok(false) => return,
+            Ok(true) => Ok(pod.clone().into()),
+            Err(e) => {
+                error!("PodDispatcher: Failed to start pod: {}", e);
+                Err(e)
             }
+        };
+        if let Some(responder) = self.starting.remove(&pod.id) {
+            let _ = responder.send(result);
         }
     }
 }
+
+/// Answer the start request of a pod that stops initializing because it is
+/// stopped or deleted.
+fn abandon_start(
+    starting: &mut HashMap<String, oneshot::Sender<Result<Pod, PodError>>>,
+    id: &str,
+    now: &str,
+) {
+    if let Some(responder) = starting.remove(id) {
+        let _ = responder.send(Err(PodError::InvalidState {
+            expected: "initializing".to_string(),
+            actual: now.to_string(),
+        }));
+    }
+}
@@ -12,7 +12,9 @@ pub use api::PodApiHandler;
 pub use dispatcher::PodDispatcher;
 
 use crate::error::PodError;
-use crate::proto::{Container, ContainerSpec, ContainerState, Pod, PodState};
+use crate::proto::{Container, ContainerSpec, ContainerState, Pod, PodState, StopPath};
+use crate::services::task::ExecResponse;
+use std::time::Duration;
 use tokio::sync::oneshot;
 
 /// Commands that can be sent to the Pod Service.
@@ -22,8 +24,10 @@ pub enum Command {
         id: String,
         name: String,
         containers: Vec<ContainerSpec>,
+        init_containers: Vec<ContainerSpec>,
         responder: oneshot::Sender<Result<Pod, PodError>>,
     },
+    /// Answered once the containers run, after the init containers.
     Start {
         id: String,
         responder: oneshot::Sender<Result<Pod, PodError>>,
@@ -45,6 +49,14 @@ pub enum Command {
     List {
         responder: oneshot::Sender<Vec<Pod>>,
     },
+    Exec {
+        id: String,
+        /// Container ID or name; empty for the pod's first container
+        container: String,
+        command: Vec<String>,
+        timeout: Duration,
+        responder: oneshot::Sender<Result<ExecResponse, PodError>>,
+    },
 }
 
 /// Internal pod state.
@@ -54,6 +66,8 @@ pub struct PodData {
     pub name: String,
     pub state: PodState,
     pub containers: Vec<ContainerData>,
+    /// Run to completion, in order, before `containers` start.
+    pub init_containers: Vec<ContainerData>,
     pub ip_address: String,
     pub error_message: String,
 }
@@ -66,6 +80,13 @@ pub struct ContainerData {
     pub image: String,
     pub state: ContainerState,
     pub exit_code: i32,
+    /// Signal that killed the container, if any.
+    pub exit_signal: Option<i32>,
+    pub oom_killed: bool,
+    /// Sent first when the pod is stopped.
+    pub stop_signal: i32,
+    /// How the last stop ended the container.
+    pub stop_path: StopPath,
     pub bundle_path: String,
     pub pid: Option<i32>,
     pub error_message: String,
@@ -81,6 +102,7 @@ impl From<PodData> for Pod {
             containers: data.containers.into_iter().map(|c| c.into()).collect(),
             ip_address: data.ip_address,
             error_message: data.error_message,
+            init_containers: data.init_containers.into_iter().map(|c| c.into()).collect(),
         }
     }
 }
@@ -93,6 +115,9 @@ impl From<ContainerData> for Container {
             state: data.state.into(),
             exit_code: data.exit_code,
             image: data.image,
+            exit_signal: data.exit_signal.unwrap_or(0),
+            oom_killed: data.oom_killed,
+            stop_path: data.stop_path.into(),
             error_message: data.error_message,
         }
     }
@@ -2,6 +2,7 @@
 
 use crate::proto::ContainerSpec;
 use crate::services::image::ImageConfig;
+use crate::services::task::cgroup_path;
 use log::info;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
@@ -85,6 +86,8 @@ struct Mount {
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Linux {
+    /// Fixed so the task service can find the container's memory.events.
+    cgroups_path: String,
     namespaces: Vec<Namespace>,
 }
 
@@ -233,6 +236,7 @@ impl OciSpec {
                 },
             ],
             linux: Linux {
+                cgroups_path: cgroup_path(&container_spec.id),
                 // Create only pid and mount namespaces.
                 // Network namespace is NOT listed, so the container inherits
                 // the parent's network namespace (per OCI runtime spec).
@@ -3,141 +3,300 @@
 use super::spec::generate_oci_spec;
 use super::{ContainerData, PodData};
 use crate::error::PodError;
-use crate::proto::{ContainerSpec, ContainerState, PodState};
-use crate::services::image::{Command as ImageCommand, PullResponse};
+use crate::proto::{ContainerSpec, ContainerState, PodState, StopPath};
+use crate::services::image::{Command as ImageCommand, ImageConfig, PullResponse};
 use crate::services::task::{Command as TaskCommand, CreateResponse, Event as TaskEvent};
+use crate::utils::signals;
 use log::{info, warn};
 use std::path::Path;
+use std::time::Duration;
 use tokio::sync::{mpsc, oneshot};
+use tokio::time::Instant;
+
+const SIGKILL: i32 = 9;
+const SIGTERM: i32 = 15;
+
+/// How long a killed container gets to be gone.
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The signal that asks a container to stop: its spec's, else its image's
+/// StopSignal, else SIGTERM.
+fn stop_signal(spec: &ContainerSpec, image: &ImageConfig) -> Result<i32, PodError> {
+    if !spec.stop_signal.is_empty() {
+        return signals::parse_signal(&spec.stop_signal).map_err(|error| {
+            PodError::ContainerFailed {
+                container_id: spec.id.clone(),
+                error,
+            }
+        });
+    }
+    if !image.stop_signal.is_empty() {
+        match signals::parse_signal(&image.stop_signal) {
+            Ok(signal) => return Ok(signal),
+            Err(e) => warn!("Worker: Ignoring StopSignal of image {}: {}", spec.image, e),
+        }
+    }
+    Ok(SIGTERM)
+}
 
 /// Create a pod by pulling images and preparing bundles.
 pub async fn create_pod(
     id: String,
     name: String,
     containers: Vec<ContainerSpec>,
+    init_containers: Vec<ContainerSpec>,
     image_tx: &mpsc::Sender<ImageCommand>,
     pods_dir: &Path,
 ) -> Result<PodData, PodError> {
     info!("Worker: Creating pod {} ({})", name, id);
 
-    let mut container_data = Vec::new();
     let pod_dir = pods_dir.join(&id);
-
+    let mut init_data = Vec::new();
+    for spec in init_containers {
+        init_data.push(prepare_container(spec, image_tx, &pod_dir).await?);
+    }
+    let mut container_data = Vec::new();
     for spec in containers {
-        info!(
-            "Worker: Processing container {} with image {}",
-            spec.name, spec.image
-        );
+        container_data.push(prepare_container(spec, image_tx, &pod_dir).await?);
+    }
 
-        // Pull the image
-        let (responder, rx) = oneshot::channel();
-        let cmd = ImageCommand::Pull {
-            image_ref: spec.image.clone(),
-            responder,
-        };
+    Ok(PodData {
+        id,
+        name,
+        state: PodState::Created,
+        containers: container_data,
+        init_containers: init_data,
+        ip_address: String::new(),
+        error_message: String::new(),
+    })
+}
 
-        if image_tx.send(cmd).await.is_err() {
-            return Err(PodError::ContainerFailed {
-                container_id: spec.id.clone(),
-                error: "Image service unavailable".to_string(),
-            });
-        }
+/// Pull a container's image and write its bundle.
+async fn prepare_container(
+    spec: ContainerSpec,
+    image_tx: &mpsc::Sender<ImageCommand>,
+    pod_dir: &Path,
+) -> Result<ContainerData, PodError> {
+    info!(
+        "Worker: Processing container {} with image {}",
+        spec.name, spec.image
+    );
 
-        let pull_result: Result<PullResponse, _> =
-            rx.await.map_err(|_| PodError::ContainerFailed {
-                container_id: spec.id.clone(),
-                error: "Image service channel closed".to_string(),
-            })?;
+    // Pull the image
+    let (responder, rx) = oneshot::channel();
+    let cmd = ImageCommand::Pull {
+        image_ref: spec.image.clone(),
+        responder,
+    };
 
-        let pull_response = pull_result.map_err(|e| PodError::ContainerFailed {
+    if image_tx.send(cmd).await.is_err() {
+        return Err(PodError::ContainerFailed {
             container_id: spec.id.clone(),
-            error: format!("Image pull failed: {}", e),
-        })?;
+            error: "Image service unavailable".to_string(),
+        });
+    }
 
-        info!(
-            "Worker: Image pulled for container {}: {}",
-            spec.name, pull_response.rootfs_path
-        );
+    let pull_result: Result<PullResponse, _> = rx.await.map_err(|_| PodError::ContainerFailed {
+        container_id: spec.id.clone(),
+        error: "Image service channel closed".to_string(),
+    })?;
 
-        // Create bundle directory
-        let bundle_path = pod_dir.join(&spec.id);
-        tokio::fs::create_dir_all(&bundle_path)
-            .await
-            .map_err(|e| PodError::ContainerFailed {
-                container_id: spec.id.clone(),
-                error: format!("Failed to create bundle directory: {}", e),
-            })?;
-
-        // Generate OCI spec (use image config for Entrypoint/Cmd if not specified)
-        generate_oci_spec(
-            &spec,
-            &pull_response.rootfs_path,
-            &bundle_path,
-            &pull_response.config,
-        )
+    let pull_response = pull_result.map_err(|e| PodError::ContainerFailed {
+        container_id: spec.id.clone(),
+        error: format!("Image pull failed: {}", e),
+    })?;
+
+    info!(
+        "Worker: Image pulled for container {}: {}",
+        spec.name, pull_response.rootfs_path
+    );
+
+    // Create bundle directory
+    let bundle_path = pod_dir.join(&spec.id);
+    tokio::fs::create_dir_all(&bundle_path)
         .await
         .map_err(|e| PodError::ContainerFailed {
             container_id: spec.id.clone(),
-            error: format!("Failed to generate OCI spec: {}", e),
+            error: format!("Failed to create bundle directory: {}", e),
         })?;
 
-        container_data.push(ContainerData {
-            id: spec.id.clone(),
-            name: spec.name.clone(),
-            image: spec.image.clone(),
-            state: ContainerState::Created,
-            exit_code: 0,
-            bundle_path: bundle_path.to_string_lossy().to_string(),
-            pid: None,
-            error_message: String::new(),
-            spec,
-        });
-    }
-
-    Ok(PodData {
-        id,
-        name,
-        state: PodState::Created,
-        containers: container_data,
-        ip_address: String::new(),
+    // Generate OCI spec (use image config for Entrypoint/Cmd if not specified)
+    generate_oci_spec(
+        &spec,
+        &pull_response.rootfs_path,
+        &bundle_path,
+        &pull_response.config,
+    )
+    .await
+    .map_err(|e| PodError::ContainerFailed {
+        container_id: spec.id.clone(),
+        error: format!("Failed to generate OCI spec: {}", e),
+    })?;
+
+    let stop_signal = stop_signal(&spec, &pull_response.config)?;
+
+    Ok(ContainerData {
+        id: spec.id.clone(),
+        name: spec.name.clone(),
+        image: spec.image.clone(),
+        state: ContainerState::Created,
+        exit_code: 0,
+        exit_signal: None,
+        oom_killed: false,
+        stop_signal,
+        stop_path: StopPath::Unspecified,
+        bundle_path: bundle_path.to_string_lossy().to_string(),
+        pid: None,
         error_message: String::new(),
+        spec,
     })
 }
 
-/// Start a pod by creating and starting all containers.
-pub async fn start_pod(
+/// Begin starting a pod. A pod with init containers starts the first of
+/// them and becomes Initializing; [`continue_start`] takes it from there as
+/// they exit. Returns whether the pod's containers are started.
+pub async fn begin_start(
     pod: &mut PodData,
     task_tx: &mpsc::Sender<TaskCommand>,
-) -> Result<(), PodError> {
+) -> Result<bool, PodError> {
+    if pod.init_containers.is_empty() {
+        start_pod(pod, task_tx).await?;
+        return Ok(true);
+    }
+    info!("Worker: Initializing pod {} ({})", pod.name, pod.id);
+    // Init containers run again on every start
+    for container in &mut pod.init_containers {
+        container.state = ContainerState::Created;
+        container.exit_code = 0;
+        container.exit_signal = None;
+        container.oom_killed = false;
+        container.pid = None;
+        container.error_message.clear();
+    }
+    pod.state = PodState::Initializing;
+    pod.error_message.clear();
+    continue_start(pod, task_tx).await
+}
+
+/// Start the next init container of an initializing pod once the previous
+/// one has exited 0, and the pod's containers after the last one. An init
+/// container that exits non-zero or doesn't start fails the pod. Returns
+/// whether the pod's containers are started.
+pub async fn continue_start(
+    pod: &mut PodData,
+    task_tx: &mpsc::Sender<TaskCommand>,
+) -> Result<bool, PodError> {
+    for container in &mut pod.init_containers {
+        let error = match container.state {
+            ContainerState::Stopped if container.exit_code == 0 => continue,
+            ContainerState::Running => return Ok(false),
+            ContainerState::Stopped => format!("exited with code {}", container.exit_code),
+            ContainerState::Failed => container.error_message.clone(),
+            _ => match run_container(container, task_tx).await {
+                Ok(()) => return Ok(false),
+                Err(PodError::ContainerFailed { error, .. }) => error,
+                Err(e) => e.to_string(),
+            },
+        };
+        let label = if container.name.is_empty() {
+            container.id.clone()
+        } else {
+            container.name.clone()
+        };
+        warn!("Worker: Init container {} failed: {}", label, error);
+        pod.state = PodState::Failed;
+        pod.error_message = format!("Init container {} failed: {}", label, error);
+        return Err(PodError::InitFailed {
+            container: label,
+            error,
+        });
+    }
+    start_pod(pod, task_tx).await?;
+    Ok(true)
+}
+
+/// Start a pod's containers.
+async fn start_pod(pod: &mut PodData, task_tx: &mpsc::Sender<TaskCommand>) -> Result<(), PodError> {
     info!("Worker: Starting pod {} ({})", pod.name, pod.id);
 
     for container in &mut pod.containers {
-        info!("Worker: Creating container {}", container.name);
+        run_container(container, task_tx).await?;
+    }
 
-        // Create container
-        let (responder, rx) = oneshot::channel();
-        let cmd = TaskCommand::Create {
+    pod.state = PodState::Running;
+    info!("Worker: Pod {} started successfully", pod.name);
+    Ok(())
+}
+
+/// Create and start a container.
+async fn run_container(
+    container: &mut ContainerData,
+    task_tx: &mpsc::Sender<TaskCommand>,
+) -> Result<(), PodError> {
+    info!("Worker: Creating container {}", container.name);
+    container.stop_path = StopPath::Unspecified;
+
+    // Create container
+    let (responder, rx) = oneshot::channel();
+    let cmd = TaskCommand::Create {
+        container_id: container.id.clone(),
+        bundle_path: container.bundle_path.clone(),
+        responder,
+    };
+
+    if task_tx.send(cmd).await.is_err() {
+        container.state = ContainerState::Failed;
+        container.error_message = "Task service unavailable".to_string();
+        return Err(PodError::ContainerFailed {
             container_id: container.id.clone(),
-            bundle_path: container.bundle_path.clone(),
-            responder,
-        };
+            error: container.error_message.clone(),
+        });
+    }
 
-        if task_tx.send(cmd).await.is_err() {
-            container.state = ContainerState::Failed;
-            container.error_message = "Task service unavailable".to_string();
-            return Err(PodError::ContainerFailed {
-                container_id: container.id.clone(),
-                error: container.error_message.clone(),
-            });
+    let create_result: Result<CreateResponse, _> =
+        rx.await.map_err(|_| PodError::ContainerFailed {
+            container_id: container.id.clone(),
+            error: "Task service channel closed".to_string(),
+        })?;
+
+    let create_response = create_result.map_err(|e| {
+        container.state = ContainerState::Failed;
+        container.error_message = e.to_string();
+        PodError::ContainerFailed {
+            container_id: container.id.clone(),
+            error: e.to_string(),
         }
+    })?;
 
-        let create_result: Result<CreateResponse, _> =
-            rx.await.map_err(|_| PodError::ContainerFailed {
-                container_id: container.id.clone(),
-                error: "Task service channel closed".to_string(),
-            })?;
+    container.pid = Some(create_response.pid);
+    info!(
+        "Worker: Container {} created with PID {}",
+        container.name, create_response.pid
+    );
+
+    // Start container
+    let (responder, rx) = oneshot::channel();
+    let cmd = TaskCommand::Start {
+        container_id: container.id.clone(),
+        pid: create_response.pid,
+        responder,
+    };
+
+    if task_tx.send(cmd).await.is_err() {
+        container.state = ContainerState::Failed;
+        container.error_message = "Task service unavailable".to_string();
+        return Err(PodError::ContainerFailed {
+            container_id: container.id.clone(),
+            error: container.error_message.clone(),
+        });
+    }
 
-        let create_response = create_result.map_err(|e| {
+    rx.await
+        .map_err(|_| PodError::ContainerFailed {
+            container_id: container.id.clone(),
+            error: "Task service channel closed".to_string(),
+        })?
+        .map_err(|e| {
             container.state = ContainerState::Failed;
             container.error_message = e.to_string();
             PodError::ContainerFailed {
@@ -146,53 +305,13 @@ pub async fn start_pod(
             }
         })?;
 
-        container.pid = Some(create_response.pid);
-        info!(
-            "Worker: Container {} created with PID {}",
-            container.name, create_response.pid
-        );
-
-        // Start container
-        let (responder, rx) = oneshot::channel();
-        let cmd = TaskCommand::Start {
-            container_id: container.id.clone(),
-            pid: create_response.pid,
-            responder,
-        };
-
-        if task_tx.send(cmd).await.is_err() {
-            container.state = ContainerState::Failed;
-            container.error_message = "Task service unavailable".to_string();
-            return Err(PodError::ContainerFailed {
-                container_id: container.id.clone(),
-                error: container.error_message.clone(),
-            });
-        }
-
-        rx.await
-            .map_err(|_| PodError::ContainerFailed {
-                container_id: container.id.clone(),
-                error: "Task service channel closed".to_string(),
-            })?
-            .map_err(|e| {
-                container.state = ContainerState::Failed;
-                container.error_message = e.to_string();
-                PodError::ContainerFailed {
-                    container_id: container.id.clone(),
-                    error: e.to_string(),
-                }
-            })?;
-
-        container.state = ContainerState::Running;
-        info!("Worker: Container {} started", container.name);
-    }
-
-    pod.state = PodState::Running;
-    info!("Worker: Pod {} started successfully", pod.name);
+    container.state = ContainerState::Running;
+    info!("Worker: Container {} started", container.name);
     Ok(())
 }
 
-/// Stop a pod by killing all containers.
+/// Stop a pod: send each running container its stop signal, give it its
+/// grace period (or `timeout_seconds`) to exit, then kill it.
 pub async fn stop_pod(
     pod: &mut PodData,
     task_tx: &mpsc::Sender<TaskCommand>,
@@ -203,43 +322,61 @@ pub async fn stop_pod(
         pod.name, pod.id, timeout_seconds
     );
 
-    // Send SIGTERM to all containers
-    for container in &mut pod.containers {
+    // Signal all containers first so their grace periods run in parallel.
+    // An initializing pod has an init container running instead.
+    let mut containers: Vec<&mut ContainerData> = pod
+        .init_containers
+        .iter_mut()
+        .chain(pod.containers.iter_mut())
+        .collect();
+    let signalled_at = Instant::now();
+    let mut signalled = Vec::new();
+    for (i, container) in containers.iter_mut().enumerate() {
         if container.state != ContainerState::Running {
+            if matches!(
+                container.state,
+                ContainerState::Stopped | ContainerState::Failed
+            ) {
+                container.stop_path = StopPath::Exited;
+            }
             continue;
         }
-
-        let (responder, rx) = oneshot::channel();
-        let cmd = TaskCommand::Kill {
-            container_id: container.id.clone(),
-            signal: 15, // SIGTERM
-            responder,
-        };
-
-        if task_tx.send(cmd).await.is_err() {
-            warn!("Worker: Failed to send kill to container {}", container.id);
+        let Some(pid) = container.pid else {
             continue;
+        };
+        if let Err(e) = kill_container(task_tx, &container.id, container.stop_signal).await {
+            warn!(
+                "Worker: Failed to send signal {} to container {}: {}",
+                container.stop_signal, container.id, e
+            );
         }
-
-        let _ = rx.await;
+        let grace = match container.spec.stop_grace_seconds {
+            0 => timeout_seconds,
+            grace => grace,
+        };
+        signalled.push((i, pid, signalled_at + Duration::from_secs(grace.into())));
     }
 
-    // Wait for containers to stop (simplified - in practice would poll state)
-    tokio::time::sleep(tokio::time::Duration::from_secs(timeout_seconds as u64)).await;
-
-    // Force kill any remaining
-    for container in &mut pod.containers {
-        if container.state == ContainerState::Running {
-            let (responder, rx) = oneshot::channel();
-            let cmd = TaskCommand::Kill {
-                container_id: container.id.clone(),
-                signal: 9, // SIGKILL
-                responder,
-            };
-
-            if task_tx.send(cmd).await.is_ok() {
-                let _ = rx.await;
+    for (i, pid, deadline) in signalled {
+        let container = &mut *containers[i];
+        if wait_exited(pid, deadline).await {
+            info!(
+                "Worker: Container {} exited on signal {}",
+                container.name, container.stop_signal
+            );
+            container.stop_path = StopPath::Signal;
+        } else {
+            warn!(
+                "Worker: Container {} still running after its grace period, killing it",
+                container.name
+            );
+            if let Err(e) = kill_container(task_tx, &container.id, SIGKILL).await {
+                warn!("Worker: Failed to kill container {}: {}", container.id, e);
+            }
+            if !wait_exited(pid, Instant::now() + KILL_TIMEOUT).await {
+                warn!("Worker: Container {} survived SIGKILL", container.name);
             }
+            container.stop_path = StopPath::Killed;
         }
         container.state = ContainerState::Stopped;
     }
@@ -249,6 +386,42 @@ pub async fn stop_pod(
     Ok(())
 }
 
+/// Send `signal` to a container's init process.
+async fn kill_container(
+    task_tx: &mpsc::Sender<TaskCommand>,
+    container_id: &str,
+    signal: i32,
+) -> Result<(), String> {
+    let (responder, rx) = oneshot::channel();
+    let cmd = TaskCommand::Kill {
+        container_id: container_id.to_string(),
+        signal,
+        responder,
+    };
+    task_tx
+        .send(cmd)
+        .await
+        .map_err(|_| "task service unavailable".to_string())?;
+    rx.await
+        .map_err(|_| "task service channel closed".to_string())?
+        .map_err(|e| e.to_string())
+}
+
+/// Wait until process `pid` is gone (exited and reaped), at most until
+/// `deadline`. Returns whether it is.
+async fn wait_exited(pid: i32, deadline: Instant) -> bool {
+    loop {
+        let alive = nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), None);
+        if alive == Err(nix::errno::Errno::ESRCH) {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+}
+
 /// Delete a pod by removing all containers and cleaning up.
 pub async fn delete_pod(
     pod: &mut PodData,
@@ -258,7 +431,7 @@ pub async fn delete_pod(
     info!("Worker: Deleting pod {} ({})", pod.name, pod.id);
 
     // Delete all containers
-    for container in &pod.containers {
+    for container in pod.init_containers.iter().chain(&pod.containers) {
         let (responder, rx) = oneshot::channel();
         let cmd = TaskCommand::Delete {
             container_id: container.id.clone(),
@@ -285,27 +458,52 @@ pub async fn delete_pod(
 /// Handle container events from the Task Service.
 pub fn handle_container_event(pod: &mut PodData, event: &TaskEvent) {
     match event {
-        TaskEvent::ContainerStopped { id, exit_code } => {
-            if let Some(container) = pod.containers.iter_mut().find(|c| c.id == *id) {
+        TaskEvent::ContainerStopped {
+            id,
+            exit_code,
+            signal,
+            oom_killed,
+        } => {
+            let init = pod.init_containers.iter().any(|c| c.id == *id);
+            if let Some(container) = pod
+                .containers
+                .iter_mut()
+                .chain(pod.init_containers.iter_mut())
+                .find(|c| c.id == *id)
+            {
                 container.state = ContainerState::Stopped;
                 container.exit_code = *exit_code;
+                container.exit_signal = *signal;
+                container.oom_killed = *oom_killed;
+                if *oom_killed {
+                    container.error_message = "OOMKilled".to_string();
+                }
                 info!(
-                    "Container {} stopped with exit code {}",
-                    container.name, exit_code
+                    "Container {} stopped with exit code {}{}",
+                    container.name,
+                    exit_code,
+                    if *oom_killed { " (OOMKilled)" } else { "" }
                 );
 
-                // Check if all containers are stopped
-                let all_stopped = pod
-                    .containers
-                    .iter()
-                    .all(|c| c.state == ContainerState::Stopped);
+                // Check if all containers are stopped; an init container
+                // exiting moves the pod on instead
+                let all_stopped = !init
+                    && pod
+                        .containers
+                        .iter()
+                        .all(|c| c.state == ContainerState::Stopped);
                 if all_stopped {
                     pod.state = PodState::Stopped;
                 }
             }
         }
         TaskEvent::ContainerStartFailed { id, error } => {
-            if let Some(container) = pod.containers.iter_mut().find(|c| c.id == *id) {
+            if let Some(container) = pod
+                .containers
+                .iter_mut()
+                .chain(pod.init_containers.iter_mut())
+                .find(|c| c.id == *id)
+            {
                 container.state = ContainerState::Failed;
                 container.error_message = error.clone();
                 pod.state = PodState::Failed;
@@ -0,0 +1,100 @@
+//! Container cgroups (v2).
+//!
+//! Every container's OCI spec pins its cgroupsPath under [`CGROUP_PARENT`],
+//! so the task service can read OOM events and resource usage without
+//! asking youki where the container ended up.
+
+use log::debug;
+use std::path::PathBuf;
+
+const CGROUP_ROOT: &str = "/sys/fs/cgroup";
+
+/// cgroup (relative to the cgroup2 root) that container cgroups live under.
+const CGROUP_PARENT: &str = "/mvirt";
+
+/// Controllers enabled for containers: OOM accounting and usage stats.
+const CONTROLLERS: &str = "+cpu +memory +pids";
+
+/// cgroupsPath of a container, as put in its OCI spec.
+pub(crate) fn cgroup_path(container_id: &str) -> String {
+    format!("{}/{}", CGROUP_PARENT, container_id)
+}
+
+fn cgroup_dir(container_id: &str) -> PathBuf {
+    PathBuf::from(CGROUP_ROOT).join(cgroup_path(container_id).trim_start_matches('/'))
+}
+
+/// Enable the cpu, memory and pids controllers down to the containers'
+/// parent cgroup so each container gets its own stats. Best effort.
+pub(crate) async fn enable_controllers() {
+    let parent = PathBuf::from(CGROUP_ROOT).join(CGROUP_PARENT.trim_start_matches('/'));
+    if let Err(e) = tokio::fs::create_dir_all(&parent).await {
+        debug!("cgroup: Cannot create {}: {}", parent.display(), e);
+        return;
+    }
+    for dir in [PathBuf::from(CGROUP_ROOT), parent] {
+        if let Err(e) = tokio::fs::write(dir.join("cgroup.subtree_control"), CONTROLLERS).await {
+            debug!(
+                "cgroup: Cannot enable controllers in {}: {}",
+                dir.display(),
+                e
+            );
+        }
+    }
+}
+
+/// `oom_kill` counter from the container cgroup's memory.events; 0 if the
+/// memory controller isn't enabled there.
+pub(crate) async fn oom_kill_count(container_id: &str) -> u64 {
+    let events = cgroup_dir(container_id).join("memory.events");
+    let Ok(content) = tokio::fs::read_to_string(&events).await else {
+        return 0;
+    };
+    keyed_value(&content, "oom_kill").unwrap_or(0)
+}
+
+/// Point-in-time resource usage of a container.
+#[derive(Debug, Clone, Default)]
+pub struct CgroupStats {
+    /// Cumulative CPU time in microseconds.
+    pub cpu_usage_usec: u64,
+    pub memory_bytes: u64,
+    /// `None` if unlimited.
+    pub memory_limit_bytes: Option<u64>,
+    pub pids: u64,
+}
+
+/// Read a container's usage. Returns `None` if its cgroup doesn't exist
+/// (not created yet, or already deleted).
+pub(crate) async fn read_stats(container_id: &str) -> Option<CgroupStats> {
+    let dir = cgroup_dir(container_id);
+    let cpu_stat = tokio::fs::read_to_string(dir.join("cpu.stat")).await.ok()?;
+    let read_u64 = |file: &'static str| {
+        let path = dir.join(file);
+        async move {
+            tokio::fs::read_to_string(path)
+                .await
+                .ok()
+                .and_then(|s| s.trim().parse::<u64>().ok())
+        }
+    };
+    Some(CgroupStats {
+        cpu_usage_usec: keyed_value(&cpu_stat, "usage_usec").unwrap_or(0),
+        memory_bytes: read_u64("memory.current").await.unwrap_or(0),
+        // memory.max is "max" when unlimited, which fails to parse
+        memory_limit_bytes: read_u64("memory.max").await,
+        pids: read_u64("pids.current").await.unwrap_or(0),
+    })
+}
+
+/// Value of a `key value` line in a flat-keyed cgroup file.
+fn keyed_value(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let (k, v) = line.split_once(' ')?;
+        if k == key {
+            v.trim().parse().ok()
+        } else {
+            None
+        }
+    })
+}
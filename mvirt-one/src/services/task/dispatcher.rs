@@ -116,6 +116,23 @@ impl TaskDispatcher {
                 )
                 .await;
             }
+            Command::Exec {
+                container_id,
+                command,
+                timeout,
+                responder,
+            } => {
+                info!("TaskDispatcher: Exec in container {}", container_id);
+                // Runs as long as the command; don't hold up other commands
+                tokio::spawn(worker::handle_exec(
+                    container_id,
+                    command,
+                    timeout,
+                    responder,
+                    self.youki_path.clone(),
+                    self.youki_root.clone(),
+                ));
+            }
         }
     }
 }
@@ -3,12 +3,16 @@
 //! Wraps youki commands and manages container lifecycle at the runtime level.
 //! Based on FeOS task-service pattern.
 
+pub(crate) mod cgroup;
 mod dispatcher;
 mod worker;
 
+pub(crate) use cgroup::cgroup_path;
 pub use dispatcher::TaskDispatcher;
+pub(crate) use worker::run_on_guest;
 
 use crate::error::ContainerError;
+use std::time::Duration;
 use tokio::sync::oneshot;
 
 /// Commands that can be sent to the Task Service.
@@ -33,6 +37,12 @@ pub enum Command {
         container_id: String,
         responder: oneshot::Sender<Result<(), ContainerError>>,
     },
+    Exec {
+        container_id: String,
+        command: Vec<String>,
+        timeout: Duration,
+        responder: oneshot::Sender<Result<ExecResponse, ContainerError>>,
+    },
 }
 
 /// Response from container creation.
@@ -41,13 +51,45 @@ pub struct CreateResponse {
     pub pid: i32,
 }
 
+/// Response from a command run in a container.
+#[derive(Debug)]
+pub struct ExecResponse {
+    /// Exit status, or 128 + signal number if killed by a signal.
+    pub exit_code: i32,
+    /// Last few KiB of output.
+    pub stdout: String,
+    pub stderr: String,
+    /// The command was killed after its timeout.
+    pub timed_out: bool,
+}
+
 /// Events emitted by the Task Service.
 #[derive(Debug, Clone)]
 pub enum Event {
-    ContainerCreated { id: String, pid: i32 },
-    ContainerCreateFailed { id: String, error: String },
-    ContainerStarted { id: String },
-    ContainerStartFailed { id: String, error: String },
-    ContainerStopped { id: String, exit_code: i32 },
-    ContainerDeleted { id: String },
+    ContainerCreated {
+        id: String,
+        pid: i32,
+    },
+    ContainerCreateFailed {
+        id: String,
+        error: String,
+    },
+    ContainerStarted {
+        id: String,
+    },
+    ContainerStartFailed {
+        id: String,
+        error: String,
+    },
+    ContainerStopped {
+        id: String,
+        /// Exit status, or 128 + signal number if killed by a signal.
+        exit_code: i32,
+        signal: Option<i32>,
+        /// The cgroup recorded an OOM kill.
+        oom_killed: bool,
+    },
+    ContainerDeleted {
+        id: String,
+    },
 }
@@ -2,14 +2,16 @@
 //!
 //! Based on FeOS task-service/worker.rs pattern.
 
-use super::{CreateResponse, Event};
+use super::{CreateResponse, Event, ExecResponse, cgroup};
 use crate::error::ContainerError;
+use crate::logs::{self, LogSource};
+use crate::utils::signals;
 use log::{debug, error, info, warn};
-use nix::sys::wait::{WaitStatus, waitpid};
-use nix::unistd::Pid;
 use std::path::PathBuf;
 use std::process::Stdio;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
 use tokio::process::Command;
 use tokio::sync::{mpsc, oneshot};
 
@@ -36,27 +38,48 @@ async fn run_youki_command(
         full_args.join(" ")
     );
 
-    let output = Command::new(youki_path)
-        .args(&full_args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .output()
+    // The exit status comes from the reaper; see utils::signals.
+    let (mut child, exit) = signals::spawn_watched(
+        Command::new(youki_path)
+            .args(&full_args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )
+    .map_err(|e| ContainerError::YoukiCommand(format!("Failed to execute youki: {e}")))?;
+
+    let mut stdout_pipe = child.stdout.take();
+    let mut stderr_pipe = child.stderr.take();
+    let (mut stdout_buf, mut stderr_buf) = (Vec::new(), Vec::new());
+    let _ = tokio::join!(
+        async {
+            if let Some(p) = stdout_pipe.as_mut() {
+                let _ = p.read_to_end(&mut stdout_buf).await;
+            }
+        },
+        async {
+            if let Some(p) = stderr_pipe.as_mut() {
+                let _ = p.read_to_end(&mut stderr_buf).await;
+            }
+        },
+    );
+    let status = exit
         .await
-        .map_err(|e| ContainerError::YoukiCommand(format!("Failed to execute youki: {e}")))?;
+        .map_err(|_| ContainerError::YoukiCommand("Lost youki exit status".to_string()))?;
+    signals::release(child);
 
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        let stdout = String::from_utf8_lossy(&output.stdout);
+    if !status.success() {
+        let stderr = String::from_utf8_lossy(&stderr_buf);
+        let stdout = String::from_utf8_lossy(&stdout_buf);
         let err_msg = format!(
-            "youki exited with code {}: stderr='{}', stdout='{}'",
-            output.status, stderr, stdout
+            "youki exited with {}: stderr='{}', stdout='{}'",
+            status, stderr, stdout
         );
         error!("Worker: {err_msg}");
         return Err(ContainerError::YoukiCommand(err_msg));
     }
 
     debug!("Worker: youki command successful");
-    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    let stdout = String::from_utf8_lossy(&stdout_buf).to_string();
     Ok(stdout)
 }
 
@@ -76,6 +99,8 @@ pub async fn handle_create(
     let id = container_id.clone();
     let pid_file = format!("{}/container.pid", bundle_path);
 
+    cgroup::enable_controllers().await;
+
     // Build args: optionally prepend --root <path>
     let mut args: Vec<String> = Vec::new();
     if let Some(ref root) = youki_root {
@@ -98,13 +123,13 @@ pub async fn handle_create(
     );
 
     // Spawn youki create - it becomes the container init process and doesn't exit
-    let mut child = match Command::new(youki_path.as_ref())
-        .args(&args)
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-    {
-        Ok(child) => child,
+    let (mut child, mut exit) = match signals::spawn_watched(
+        Command::new(youki_path.as_ref())
+            .args(&args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    ) {
+        Ok(spawned) => spawned,
         Err(e) => {
             let err = ContainerError::YoukiCommand(format!("Failed to spawn youki create: {e}"));
             let _ = event_tx
@@ -126,12 +151,11 @@ pub async fn handle_create(
 
     loop {
         // Check if process exited with error
-        match child.try_wait() {
-            Ok(Some(status)) if !status.success() => {
+        match exit.try_recv() {
+            Ok(status) if !status.success() => {
                 // Process exited with error - read stderr
                 let mut stderr_buf = Vec::new();
                 if let Some(mut stderr) = child.stderr.take() {
-                    use tokio::io::AsyncReadExt;
                     let _ = stderr.read_to_end(&mut stderr_buf).await;
                 }
                 let stderr = String::from_utf8_lossy(&stderr_buf);
@@ -149,24 +173,12 @@ pub async fn handle_create(
                 let _ = responder.send(Err(err));
                 return;
             }
-            Ok(Some(_)) => {
-                // Process exited successfully - PID file should exist
-            }
-            Ok(None) => {
+            // Exited successfully (Closed: status already consumed on an
+            // earlier pass) - PID file should exist
+            Ok(_) | Err(oneshot::error::TryRecvError::Closed) => {}
+            Err(oneshot::error::TryRecvError::Empty) => {
                 // Process still running - this is expected, it becomes the init
             }
-            Err(e) => {
-                let err =
-                    ContainerError::YoukiCommand(format!("Failed to check youki status: {e}"));
-                let _ = event_tx
-                    .send(Event::ContainerCreateFailed {
-                        id,
-                        error: err.to_string(),
-                    })
-                    .await;
-                let _ = responder.send(Err(err));
-                return;
-            }
         }
 
         // Check if PID file exists
@@ -185,14 +197,28 @@ pub async fn handle_create(
                 })
                 .await;
             let _ = responder.send(Err(err));
-            // Kill the process
-            let _ = child.kill().await;
+            // Kill the process; the reaper collects it
+            let _ = child.start_kill();
+            signals::release(child);
             return;
         }
 
         tokio::time::sleep(std::time::Duration::from_millis(100)).await;
     }
 
+    // The container init inherits youki's stdio, so these pipes now carry
+    // the container's output.
+    if let Some(stdout) = child.stdout.take() {
+        logs::tail(stdout, id.clone(), LogSource::Stdout);
+    }
+    if let Some(stderr) = child.stderr.take() {
+        logs::tail(stderr, id.clone(), LogSource::Stderr);
+    }
+
+    // Still running as (or on the way to becoming) the container init;
+    // its exit is picked up via the pid file's pid in handle_start.
+    signals::release(child);
+
     // Read PID from file
     let result: Result<i32, ContainerError> = async {
         let pid_str = tokio::fs::read_to_string(&pid_file)
@@ -315,46 +341,204 @@ pub async fn handle_delete(
     let _ = responder.send(Ok(()));
 }
 
-/// Wait for a container process to exit and send an event.
-async fn wait_for_process_exit(id: String, pid: i32, event_tx: mpsc::Sender<Event>) {
-    info!("Worker: Waiting for container {} (PID {}) to exit", id, pid);
-    let pid_obj = Pid::from_raw(pid);
+/// Output kept per stream of a command run with `handle_exec`.
+const EXEC_OUTPUT_LIMIT: usize = 4096;
+
+/// The last `EXEC_OUTPUT_LIMIT` bytes of `output`.
+fn output_tail(output: &[u8]) -> String {
+    let start = output.len().saturating_sub(EXEC_OUTPUT_LIMIT);
+    String::from_utf8_lossy(&output[start..]).into_owned()
+}
+
+/// Handle a command run in a running container (`youki exec`).
+///
+/// On timeout youki is killed, which doesn't reach processes the command
+/// started in the container.
+pub async fn handle_exec(
+    container_id: String,
+    command: Vec<String>,
+    timeout: Duration,
+    responder: oneshot::Sender<Result<ExecResponse, ContainerError>>,
+    youki_path: Arc<PathBuf>,
+    youki_root: Option<Arc<PathBuf>>,
+) {
+    let mut args: Vec<String> = Vec::new();
+    if let Some(root) = youki_root {
+        args.push("--root".to_string());
+        args.push(root.to_string_lossy().to_string());
+    }
+    args.push("exec".to_string());
+    args.push(container_id.clone());
+    args.extend(command.iter().cloned());
 
-    // Use spawn_blocking since waitpid is a blocking syscall
-    let wait_result = tokio::task::spawn_blocking(move || waitpid(pid_obj, None))
+    info!(
+        "Worker: Exec in container {}: {}",
+        container_id,
+        command.join(" ")
+    );
+
+    let result = run_to_completion(Command::new(youki_path.as_ref()).args(&args), timeout)
         .await
-        .unwrap_or(Err(nix::errno::Errno::ECHILD));
+        .map_err(|e| ContainerError::YoukiCommand(format!("Failed to execute youki: {e}")));
+    match &result {
+        Ok(response) if response.timed_out => warn!(
+            "Worker: Exec in {} timed out after {:?}, killed it",
+            container_id, timeout
+        ),
+        Ok(response) => debug!(
+            "Worker: Exec in {} exited with {}",
+            container_id, response.exit_code
+        ),
+        Err(_) => {}
+    }
+    let _ = responder.send(result);
+}
 
-    let exit_code = match wait_result {
-        Ok(WaitStatus::Exited(_, code)) => {
-            info!("Worker: Container {} exited with code {}", id, code);
-            code
-        }
-        Ok(WaitStatus::Signaled(_, signal, _)) => {
-            info!(
-                "Worker: Container {} was terminated by signal {:?}",
-                id, signal
-            );
-            128 + (signal as i32)
-        }
-        Ok(status) => {
-            warn!(
-                "Worker: Container {} ended with unexpected status: {:?}",
-                id, status
-            );
-            255
-        }
-        Err(e) => {
-            error!("Worker: waitpid failed for container {}: {}", id, e);
-            255
+/// Run a command on the guest itself, outside any container. Only the
+/// guest agent mode serves this; see `PodApiHandler::with_guest_exec`.
+pub async fn run_on_guest(command: &[String], timeout: Duration) -> std::io::Result<ExecResponse> {
+    let Some((program, args)) = command.split_first() else {
+        return Err(std::io::Error::other("command is empty"));
+    };
+    info!("Worker: Running on the guest: {}", command.join(" "));
+    run_to_completion(Command::new(program).args(args), timeout).await
+}
+
+/// Run a command with its output captured, killing it after `timeout`.
+///
+/// On timeout only the command itself is killed, not what it started.
+async fn run_to_completion(
+    command: &mut Command,
+    timeout: Duration,
+) -> std::io::Result<ExecResponse> {
+    let (mut child, exit) = signals::spawn_watched(
+        command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped()),
+    )?;
+
+    let mut stdout_pipe = child.stdout.take();
+    let mut stderr_pipe = child.stderr.take();
+    let finished = async {
+        let (mut stdout_buf, mut stderr_buf) = (Vec::new(), Vec::new());
+        let _ = tokio::join!(
+            async {
+                if let Some(p) = stdout_pipe.as_mut() {
+                    let _ = p.read_to_end(&mut stdout_buf).await;
+                }
+            },
+            async {
+                if let Some(p) = stderr_pipe.as_mut() {
+                    let _ = p.read_to_end(&mut stderr_buf).await;
+                }
+            },
+        );
+        (exit.await, stdout_buf, stderr_buf)
+    };
+
+    let result = match tokio::time::timeout(timeout, finished).await {
+        Ok((Ok(status), stdout, stderr)) => Ok(ExecResponse {
+            exit_code: status.code(),
+            stdout: output_tail(&stdout),
+            stderr: output_tail(&stderr),
+            timed_out: false,
+        }),
+        Ok((Err(_), _, _)) => Err(std::io::Error::other("lost the exit status")),
+        Err(_) => {
+            let _ = child.start_kill();
+            Ok(ExecResponse {
+                exit_code: -1,
+                stdout: String::new(),
+                stderr: String::new(),
+                timed_out: true,
+            })
         }
     };
+    signals::release(child);
+    result
+}
+
+/// Wait for a container process to exit and send an event.
+async fn wait_for_process_exit(id: String, pid: i32, event_tx: mpsc::Sender<Event>) {
+    info!("Worker: Waiting for container {} (PID {}) to exit", id, pid);
+
+    let Ok(status) = signals::watch(pid).await else {
+        error!("Worker: Lost exit status of container {} (PID {})", id, pid);
+        return;
+    };
+    let oom_killed = cgroup::oom_kill_count(&id).await > 0;
+    if oom_killed {
+        warn!(
+            "Worker: Container {} was OOM killed ({}), exit code {}",
+            id,
+            status,
+            status.code()
+        );
+    } else {
+        info!(
+            "Worker: Container {} ended with {}, exit code {}",
+            id,
+            status,
+            status.code()
+        );
+    }
 
     if event_tx
-        .send(Event::ContainerStopped { id, exit_code })
+        .send(Event::ContainerStopped {
+            id,
+            exit_code: status.code(),
+            signal: status.signal(),
+            oom_killed,
+        })
         .await
         .is_err()
     {
         error!("Worker: Failed to send ContainerStopped event");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".into(), "-c".into(), script.into()]
+    }
+
+    #[tokio::test]
+    async fn runs_commands_on_the_guest() {
+        let _reaper = signals::spawn_reaper();
+
+        let done = run_on_guest(
+            &sh("echo out; echo err >&2; exit 3"),
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert_eq!(done.exit_code, 3);
+        assert_eq!(done.stdout, "out\n");
+        assert_eq!(done.stderr, "err\n");
+        assert!(!done.timed_out);
+
+        let slow = run_on_guest(&sh("sleep 10"), Duration::from_millis(100))
+            .await
+            .unwrap();
+        assert!(slow.timed_out);
+
+        assert!(run_on_guest(&[], Duration::from_secs(1)).await.is_err());
+        assert!(
+            run_on_guest(&["/nonexistent".to_string()], Duration::from_secs(1))
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn output_is_cut_to_its_tail() {
+        let long = [b"x".repeat(EXEC_OUTPUT_LIMIT), b"end".to_vec()].concat();
+        let tail = output_tail(&long);
+        assert_eq!(tail.len(), EXEC_OUTPUT_LIMIT);
+        assert!(tail.ends_with("end"));
+    }
+}
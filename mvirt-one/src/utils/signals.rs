@@ -1,33 +1,176 @@
-//! Signal handling utilities for one.
+//! Signal handling and child reaping for one.
 //! Ported from pideisn.
+//!
+//! As PID 1 (and child subreaper in local mode) we inherit every orphaned
+//! process, so a single reaper owns `waitpid(-1)`. Anything that needs a
+//! child's exit status subscribes here instead of calling `waitpid` itself:
+//! two waiters racing for the same pid is how exit codes got lost before.
 
-use log::debug;
-use nix::sys::signal::{SigHandler, Signal, signal};
+use log::{debug, warn};
 use nix::sys::wait::{WaitPidFlag, WaitStatus, waitpid};
 use nix::unistd::Pid;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+use tokio::signal::unix::{SignalKind, signal};
+use tokio::sync::oneshot;
 
-/// Setup signal handlers for PID 1 operation.
-pub fn setup_signal_handlers() {
-    unsafe {
-        // We handle SIGCHLD manually in reap_children()
-        let _ = signal(Signal::SIGCHLD, SigHandler::Handler(sigchld_handler));
+/// Exits nobody was watching yet, kept so a late `watch()` still gets them.
+const MAX_UNCLAIMED: usize = 64;
+
+/// How a child process ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExitStatus {
+    Exited(i32),
+    Signaled { signal: i32, core_dumped: bool },
+}
+
+impl ExitStatus {
+    /// Shell-style exit code: the status, or 128 + signal number.
+    pub fn code(&self) -> i32 {
+        match self {
+            ExitStatus::Exited(code) => *code,
+            ExitStatus::Signaled { signal, .. } => 128 + signal,
+        }
+    }
+
+    pub fn signal(&self) -> Option<i32> {
+        match self {
+            ExitStatus::Exited(_) => None,
+            ExitStatus::Signaled { signal, .. } => Some(*signal),
+        }
+    }
+
+    pub fn success(&self) -> bool {
+        *self == ExitStatus::Exited(0)
     }
 }
 
-extern "C" fn sigchld_handler(_: i32) {
-    // Just wake up the main loop - actual reaping happens in reap_children()
+impl std::fmt::Display for ExitStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExitStatus::Exited(code) => write!(f, "exit code {}", code),
+            ExitStatus::Signaled {
+                signal,
+                core_dumped,
+            } => {
+                write!(f, "signal {}", signal)?;
+                if *core_dumped {
+                    write!(f, " (core dumped)")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Reaper {
+    watchers: HashMap<i32, Vec<oneshot::Sender<ExitStatus>>>,
+    unclaimed: VecDeque<(i32, ExitStatus)>,
 }
 
-/// Reap zombie children.
-/// Call this periodically in the main loop.
+impl Reaper {
+    fn dispatch(&mut self, pid: i32, status: ExitStatus) {
+        match self.watchers.remove(&pid) {
+            Some(watchers) => {
+                for tx in watchers {
+                    let _ = tx.send(status);
+                }
+            }
+            None => {
+                debug!("Child {} exited with {} (unwatched)", pid, status);
+                if self.unclaimed.len() >= MAX_UNCLAIMED {
+                    self.unclaimed.pop_front();
+                }
+                self.unclaimed.push_back((pid, status));
+            }
+        }
+    }
+
+    fn subscribe(&mut self, pid: i32) -> oneshot::Receiver<ExitStatus> {
+        let (tx, rx) = oneshot::channel();
+        if let Some(pos) = self.unclaimed.iter().position(|(p, _)| *p == pid) {
+            let (_, status) = self.unclaimed.remove(pos).expect("position is valid");
+            let _ = tx.send(status);
+        } else {
+            self.watchers.entry(pid).or_default().push(tx);
+        }
+        rx
+    }
+}
+
+static REAPER: LazyLock<Mutex<Reaper>> = LazyLock::new(Mutex::default);
+
+/// Spawn a command and subscribe to its exit. The reaper lock is held
+/// across spawn, so the child can't be reaped before we're watching.
+///
+/// Don't `wait()` on the returned `Child`; await the receiver instead.
+pub fn spawn_watched(
+    cmd: &mut tokio::process::Command,
+) -> std::io::Result<(tokio::process::Child, oneshot::Receiver<ExitStatus>)> {
+    let mut reaper = REAPER.lock().unwrap();
+    let child = cmd.spawn()?;
+    let pid = child.id().expect("freshly spawned child has a pid") as i32;
+    let rx = reaper.subscribe(pid);
+    Ok((child, rx))
+}
+
+/// Let go of a watched child that may still be running.
+///
+/// Dropping a running tokio `Child` queues it as an orphan that tokio
+/// reaps itself, which would steal the exit status from our watchers.
+pub fn release(mut child: tokio::process::Child) {
+    drop(child.stdin.take());
+    drop(child.stdout.take());
+    drop(child.stderr.take());
+    std::mem::forget(child);
+}
+
+/// Subscribe to the exit of a process we didn't spawn directly but will
+/// inherit (e.g. a container init re-parented to us).
+pub fn watch(pid: i32) -> oneshot::Receiver<ExitStatus> {
+    REAPER.lock().unwrap().subscribe(pid)
+}
+
+/// Parse a signal the way images and specs name it: `SIGTERM`, `TERM` or
+/// `15`.
+pub fn parse_signal(s: &str) -> Result<i32, String> {
+    use nix::sys::signal::Signal;
+
+    let invalid = || format!("invalid signal '{}'", s);
+    if let Ok(number) = s.parse::<i32>() {
+        return Signal::try_from(number)
+            .map(|signal| signal as i32)
+            .map_err(|_| invalid());
+    }
+    let name = s.to_ascii_uppercase();
+    let name = if name.starts_with("SIG") {
+        name
+    } else {
+        format!("SIG{}", name)
+    };
+    name.parse::<Signal>()
+        .map(|signal| signal as i32)
+        .map_err(|_| invalid())
+}
+
+/// Reap all exited children and hand their status to watchers.
 pub fn reap_children() {
+    let mut reaper = REAPER.lock().unwrap();
     loop {
         match waitpid(Pid::from_raw(-1), Some(WaitPidFlag::WNOHANG)) {
-            Ok(WaitStatus::Exited(pid, status)) => {
-                debug!("Child {} exited with status {}", pid, status);
+            Ok(WaitStatus::Exited(pid, code)) => {
+                reaper.dispatch(pid.as_raw(), ExitStatus::Exited(code));
             }
-            Ok(WaitStatus::Signaled(pid, signal, _)) => {
-                debug!("Child {} killed by signal {:?}", pid, signal);
+            Ok(WaitStatus::Signaled(pid, signal, core_dumped)) => {
+                reaper.dispatch(
+                    pid.as_raw(),
+                    ExitStatus::Signaled {
+                        signal: signal as i32,
+                        core_dumped,
+                    },
+                );
             }
             Ok(WaitStatus::StillAlive) => {
                 // No more children to reap
@@ -47,24 +190,45 @@ pub fn reap_children() {
     }
 }
 
-/// Information about a child process exit.
-#[derive(Debug, Clone)]
-pub struct ChildExit {
-    pub pid: i32,
-    pub exit_code: i32,
+/// Run the reaper: on every SIGCHLD, plus a periodic sweep in case
+/// signals were coalesced while we were busy.
+pub fn spawn_reaper() -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut sigchld = match signal(SignalKind::child()) {
+            Ok(s) => Some(s),
+            Err(e) => {
+                warn!("Failed to listen for SIGCHLD, polling only: {}", e);
+                None
+            }
+        };
+        let mut tick = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            match sigchld.as_mut() {
+                Some(s) => {
+                    tokio::select! {
+                        _ = s.recv() => {}
+                        _ = tick.tick() => {}
+                    }
+                }
+                None => {
+                    tick.tick().await;
+                }
+            }
+            reap_children();
+        }
+    })
 }
 
-/// Wait for a specific child process to exit.
-/// This is a blocking call and should be spawned in a separate task.
-pub fn wait_for_child(pid: i32) -> ChildExit {
-    let pid_obj = Pid::from_raw(pid);
-    let status = waitpid(pid_obj, None);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    let exit_code = match status {
-        Ok(WaitStatus::Exited(_, code)) => code,
-        Ok(WaitStatus::Signaled(_, sig, _)) => 128 + (sig as i32),
-        _ => 255,
-    };
-
-    ChildExit { pid, exit_code }
+    #[test]
+    fn parses_signal_names_and_numbers() {
+        assert_eq!(parse_signal("SIGTERM"), Ok(15));
+        assert_eq!(parse_signal("int"), Ok(2));
+        assert_eq!(parse_signal("9"), Ok(9));
+        assert!(parse_signal("SIGNOPE").is_err());
+        assert!(parse_signal("99").is_err());
+    }
 }
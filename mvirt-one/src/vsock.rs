@@ -0,0 +1,397 @@
+//! Host ↔ guest vsock protocol.
+//!
+//! Shared by mvirt-one (guest) and mvirt-vmm (host), so both sides agree on
+//! ports and framing:
+//!
+//! 1. On boot the guest connects to the host on [`READY_PORT`] and sends a
+//!    hello line: `READY <version> <channel,channel,...>`. Guests predating
+//!    versioning send a bare `READY`, which parses as version 0.
+//! 2. The host opens channels by connecting to [`CHANNEL_PORT`] and sending
+//!    `OPEN <channel>`. The guest answers `OK <version>` and hands the rest of
+//!    the connection to that channel's service, or answers `ERR <reason>`.
+//!    Each channel is its own vsock connection, so services never share a
+//!    byte stream and new ones only need a name, not a port.
+//!
+//! Version 0 guests speak gRPC on [`CHANNEL_PORT`] straight away; the host
+//! must not send `OPEN` to them.
+
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, ReadBuf};
+
+/// Protocol version spoken by this build.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Guest port the host opens channels on.
+pub const CHANNEL_PORT: u32 = 1024;
+
+/// Host port the guest sends its hello to.
+pub const READY_PORT: u32 = 1025;
+
+/// The OneService gRPC API.
+pub const CHANNEL_API: &str = "api";
+
+/// Guest logs as JSON lines; see [`crate::logs`].
+pub const CHANNEL_LOGS: &str = "logs";
+
+/// Longest control line either side accepts.
+const MAX_LINE: usize = 256;
+
+/// What a guest announced in its ready signal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hello {
+    pub version: u32,
+    /// Channels the guest serves.
+    pub channels: Vec<String>,
+}
+
+impl Hello {
+    /// Hello of this build, serving `channels`.
+    pub fn new(channels: &[&str]) -> Self {
+        Self {
+            version: PROTOCOL_VERSION,
+            channels: channels.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// Hello of a guest that predates versioning: gRPC only, no `OPEN`.
+    pub fn legacy() -> Self {
+        Self {
+            version: 0,
+            channels: vec![CHANNEL_API.to_string()],
+        }
+    }
+
+    pub fn supports(&self, channel: &str) -> bool {
+        self.channels.iter().any(|c| c == channel)
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("READY {} {}\n", self.version, self.channels.join(","))
+    }
+
+    /// Parse a hello line; anything unrecognized is treated as a legacy
+    /// guest since it got far enough to connect.
+    pub fn parse(line: &str) -> Self {
+        let mut parts = line.split_whitespace();
+        if parts.next() != Some("READY") {
+            return Self::legacy();
+        }
+        let Some(version) = parts.next().and_then(|v| v.parse().ok()) else {
+            return Self::legacy();
+        };
+        let channels = parts
+            .next()
+            .map(|c| {
+                c.split(',')
+                    .filter(|c| !c.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self { version, channels }
+    }
+}
+
+/// Read one `\n`-terminated control line without reading past it, so the
+/// bytes that follow stay in the stream for the channel's service.
+pub async fn read_line<S: AsyncRead + Unpin>(stream: &mut S) -> std::io::Result<String> {
+    let mut line = Vec::new();
+    loop {
+        let byte = stream.read_u8().await?;
+        if byte == b'\n' {
+            break;
+        }
+        if line.len() >= MAX_LINE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "control line too long",
+            ));
+        }
+        line.push(byte);
+    }
+    String::from_utf8(line)
+        .map(|l| l.trim_end_matches('\r').to_string())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+/// Host side: ask the guest for `channel` on a freshly connected stream.
+/// Returns the guest's protocol version.
+pub async fn open_channel<S: AsyncRead + AsyncWrite + Unpin>(
+    stream: &mut S,
+    channel: &str,
+) -> std::io::Result<u32> {
+    stream
+        .write_all(format!("OPEN {}\n", channel).as_bytes())
+        .await?;
+    let reply = read_line(stream).await?;
+    match reply.split_once(' ') {
+        Some(("OK", version)) => version
+            .trim()
+            .parse()
+            .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "bad OK reply")),
+        Some(("ERR", reason)) => Err(std::io::Error::other(format!(
+            "guest refused channel {}: {}",
+            channel, reason
+        ))),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("unexpected channel reply: {}", reply),
+        )),
+    }
+}
+
+/// How a guest-side connection on [`CHANNEL_PORT`] wants to be served.
+pub enum Accepted<S> {
+    /// `OPEN <channel>` was received; the stream is positioned after it.
+    Channel(String, S),
+    /// A version 0 host talking gRPC directly. The stream replays the bytes
+    /// read while looking for `OPEN`.
+    Legacy(Prefixed<S>),
+}
+
+/// Guest side: read the channel request of an incoming connection.
+pub async fn accept_channel<S: AsyncRead + Unpin>(mut stream: S) -> std::io::Result<Accepted<S>> {
+    // "OPEN " can't be confused with the HTTP/2 preface ("PRI * ...")
+    let mut head = [0u8; 5];
+    stream.read_exact(&mut head).await?;
+    if &head != b"OPEN " {
+        return Ok(Accepted::Legacy(Prefixed::new(head.to_vec(), stream)));
+    }
+    let channel = read_line(&mut stream).await?;
+    Ok(Accepted::Channel(channel.trim().to_string(), stream))
+}
+
+/// Guest side: accept or refuse a requested channel.
+pub async fn reply_channel<S: AsyncWrite + Unpin>(
+    stream: &mut S,
+    result: Result<(), &str>,
+) -> std::io::Result<()> {
+    let line = match result {
+        Ok(()) => format!("OK {}\n", PROTOCOL_VERSION),
+        Err(reason) => format!("ERR {}\n", reason),
+    };
+    stream.write_all(line.as_bytes()).await?;
+    stream.flush().await
+}
+
+/// A stream that yields `prefix` before reading from the inner stream.
+pub struct Prefixed<S> {
+    prefix: Vec<u8>,
+    pos: usize,
+    inner: S,
+}
+
+impl<S> Prefixed<S> {
+    pub fn new(prefix: Vec<u8>, inner: S) -> Self {
+        Self {
+            prefix,
+            pos: 0,
+            inner,
+        }
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Prefixed<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<std::io::Result<()>> {
+        if self.pos < self.prefix.len() {
+            let n = buf.remaining().min(self.prefix.len() - self.pos);
+            let start = self.pos;
+            buf.put_slice(&self.prefix[start..start + n]);
+            self.pos += n;
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Prefixed<S> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::duplex;
+
+    #[test]
+    fn hello_round_trips() {
+        let hello = Hello::new(&[CHANNEL_API, CHANNEL_LOGS]);
+        assert_eq!(hello.to_line(), "READY 1 api,logs\n");
+        assert_eq!(Hello::parse(&hello.to_line()), hello);
+        assert!(hello.supports(CHANNEL_LOGS));
+        assert!(!hello.supports("shell"));
+
+        // No channels at all is still a versioned guest
+        assert_eq!(
+            Hello::parse("READY 2"),
+            Hello {
+                version: 2,
+                channels: vec![]
+            }
+        );
+        assert_eq!(Hello::parse("READY 1 api,,logs").channels, ["api", "logs"]);
+    }
+
+    #[test]
+    fn legacy_and_malformed_hellos_parse_as_legacy() {
+        // Guests predating versioning
+        assert_eq!(Hello::parse("READY"), Hello::legacy());
+        assert_eq!(Hello::parse("READY\r"), Hello::legacy());
+        assert_eq!(Hello::parse(""), Hello::legacy());
+        // Garbage is treated the same: the guest did connect
+        assert_eq!(Hello::parse("READY x api"), Hello::legacy());
+        assert_eq!(Hello::parse("HELLO 1 api"), Hello::legacy());
+        assert_eq!(Hello::parse("READY -1 api"), Hello::legacy());
+        assert!(Hello::legacy().supports(CHANNEL_API));
+        assert!(!Hello::legacy().supports(CHANNEL_LOGS));
+    }
+
+    #[tokio::test]
+    async fn read_line_leaves_the_rest_in_the_stream() {
+        let (mut host, mut guest) = duplex(1024);
+        host.write_all(b"READY 1 api\r\nPRI * HTTP/2.0")
+            .await
+            .unwrap();
+        drop(host);
+
+        assert_eq!(read_line(&mut guest).await.unwrap(), "READY 1 api");
+        let mut rest = Vec::new();
+        guest.read_to_end(&mut rest).await.unwrap();
+        assert_eq!(rest, b"PRI * HTTP/2.0");
+    }
+
+    #[tokio::test]
+    async fn read_line_rejects_long_invalid_and_cut_lines() {
+        // Too long
+        let (mut host, mut guest) = duplex(1024);
+        host.write_all(&[b'a'; MAX_LINE + 1]).await.unwrap();
+        let err = read_line(&mut guest).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // Not UTF-8
+        let (mut host, mut guest) = duplex(1024);
+        host.write_all(b"READY \xff\n").await.unwrap();
+        let err = read_line(&mut guest).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+        // Connection closed mid-line
+        let (mut host, mut guest) = duplex(1024);
+        host.write_all(b"READY 1").await.unwrap();
+        drop(host);
+        let err = read_line(&mut guest).await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[tokio::test]
+    async fn opens_a_channel() {
+        let (mut host, guest) = duplex(1024);
+        let guest = tokio::spawn(async move {
+            let Accepted::Channel(channel, mut stream) = accept_channel(guest).await.unwrap()
+            else {
+                panic!("expected a channel request");
+            };
+            reply_channel(&mut stream, Ok(())).await.unwrap();
+            let mut payload = [0u8; 5];
+            stream.read_exact(&mut payload).await.unwrap();
+            (channel, payload)
+        });
+
+        assert_eq!(
+            open_channel(&mut host, CHANNEL_LOGS).await.unwrap(),
+            PROTOCOL_VERSION
+        );
+        host.write_all(b"hello").await.unwrap();
+        let (channel, payload) = guest.await.unwrap();
+        assert_eq!(channel, CHANNEL_LOGS);
+        assert_eq!(&payload, b"hello");
+    }
+
+    #[tokio::test]
+    async fn refused_channel_is_an_error() {
+        let (mut host, guest) = duplex(1024);
+        tokio::spawn(async move {
+            if let Accepted::Channel(_, mut stream) = accept_channel(guest).await.unwrap() {
+                reply_channel(&mut stream, Err("unknown channel"))
+                    .await
+                    .unwrap();
+            }
+        });
+        let err = open_channel(&mut host, "shell").await.unwrap_err();
+        assert!(err.to_string().contains("unknown channel"), "{err}");
+    }
+
+    #[tokio::test]
+    async fn malformed_channel_reply_is_invalid_data() {
+        for reply in ["OK one\n", "HELLO\n"] {
+            let (mut host, mut guest) = duplex(1024);
+            guest.write_all(reply.as_bytes()).await.unwrap();
+            let err = open_channel(&mut host, CHANNEL_API).await.unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidData, "{reply}");
+        }
+    }
+
+    #[tokio::test]
+    async fn legacy_host_gets_its_bytes_replayed() {
+        // A version 0 host starts with the HTTP/2 preface
+        let preface = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+        let (mut host, guest) = duplex(1024);
+        host.write_all(preface).await.unwrap();
+        drop(host);
+
+        let Accepted::Legacy(mut stream) = accept_channel(guest).await.unwrap() else {
+            panic!("expected a legacy connection");
+        };
+        let mut replayed = Vec::new();
+        stream.read_to_end(&mut replayed).await.unwrap();
+        assert_eq!(replayed, preface);
+    }
+
+    #[tokio::test]
+    async fn short_connection_fails_to_accept() {
+        let (mut host, guest) = duplex(1024);
+        host.write_all(b"OPE").await.unwrap();
+        drop(host);
+        assert!(accept_channel(guest).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn prefixed_serves_prefix_then_inner() {
+        let (mut host, guest) = duplex(1024);
+        let mut stream = Prefixed::new(b"abc".to_vec(), guest);
+        host.write_all(b"def").await.unwrap();
+
+        // The prefix comes out in pieces as small as the reader asks for
+        let mut one = [0u8; 2];
+        stream.read_exact(&mut one).await.unwrap();
+        assert_eq!(&one, b"ab");
+        let mut rest = [0u8; 4];
+        stream.read_exact(&mut rest).await.unwrap();
+        assert_eq!(&rest, b"cdef");
+
+        // Writes go straight through
+        stream.write_all(b"reply").await.unwrap();
+        stream.flush().await.unwrap();
+        let mut reply = [0u8; 5];
+        host.read_exact(&mut reply).await.unwrap();
+        assert_eq!(&reply, b"reply");
+    }
+}
@@ -13,8 +13,8 @@ mod common;
 
 use common::{TestServer, check_port};
 use mvirt_one::proto::{
-    ContainerSpec, CreatePodRequest, DeletePodRequest, Empty, GetPodRequest, PodState,
-    StartPodRequest, StopPodRequest, one_service_client::OneServiceClient,
+    ContainerSpec, ContainerState, CreatePodRequest, DeletePodRequest, Empty, GetPodRequest,
+    PodState, StartPodRequest, StopPodRequest, one_service_client::OneServiceClient,
 };
 
 /// Test: Health Check - verify server is running and responds.
@@ -55,7 +55,10 @@ async fn test_pod_lifecycle() {
                 args: vec!["30".into()],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
+            init_containers: vec![],
         })
         .await
         .expect("CreatePod failed")
@@ -117,6 +120,139 @@ async fn test_pod_lifecycle() {
     assert!(result.is_err(), "Pod should not exist after deletion");
 }
 
+/// Test: Exit status - a container's own exit code is reported exactly
+/// rather than as a generic failure.
+#[tokio::test]
+#[ignore]
+async fn test_container_exit_status() {
+    let server = TestServer::start().await.expect("Failed to start server");
+    let mut client = OneServiceClient::connect(server.addr.clone())
+        .await
+        .expect("Failed to connect to server");
+
+    client
+        .create_pod(CreatePodRequest {
+            id: "test-pod-exit".into(),
+            name: "exit-test".into(),
+            containers: vec![ContainerSpec {
+                id: "exits-3".into(),
+                name: "exits".into(),
+                image: "docker.io/library/alpine:latest".into(),
+                command: vec!["sh".into(), "-c".into(), "exit 3".into()],
+                args: vec![],
+                env: vec![],
+                working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
+            }],
+            init_containers: vec![],
+        })
+        .await
+        .expect("CreatePod failed");
+
+    client
+        .start_pod(StartPodRequest {
+            id: "test-pod-exit".into(),
+        })
+        .await
+        .expect("StartPod failed");
+
+    // Wait for the container to be reported as exited
+    let mut pod = None;
+    for _ in 0..30 {
+        let p = client
+            .get_pod(GetPodRequest {
+                id: "test-pod-exit".into(),
+            })
+            .await
+            .expect("GetPod failed")
+            .into_inner();
+        if p.state == PodState::Stopped as i32 {
+            pod = Some(p);
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+    let pod = pod.expect("Pod did not stop");
+
+    let exits = pod.containers.iter().find(|c| c.id == "exits-3").unwrap();
+    assert_eq!(exits.exit_code, 3);
+    assert_eq!(exits.exit_signal, 0);
+    assert!(!exits.oom_killed);
+
+    client
+        .delete_pod(DeletePodRequest {
+            id: "test-pod-exit".into(),
+            force: true,
+        })
+        .await
+        .expect("DeletePod failed");
+}
+
+/// Test: Init containers run in order before the containers, and a failing
+/// one fails the pod.
+#[tokio::test]
+#[ignore]
+async fn test_init_containers() {
+    let server = TestServer::start().await.expect("Failed to start server");
+    let mut client = OneServiceClient::connect(server.addr.clone())
+        .await
+        .expect("Failed to connect to server");
+
+    let alpine = |id: &str, script: &str| ContainerSpec {
+        id: id.into(),
+        name: id.into(),
+        image: "docker.io/library/alpine:latest".into(),
+        command: vec!["sh".into(), "-c".into(), script.into()],
+        args: vec![],
+        env: vec![],
+        working_dir: String::new(),
+        stop_signal: String::new(),
+        stop_grace_seconds: 0,
+    };
+
+    client
+        .create_pod(CreatePodRequest {
+            id: "test-pod-init".into(),
+            name: "init-test".into(),
+            containers: vec![alpine("main", "sleep 30")],
+            init_containers: vec![alpine("init-ok", "true"), alpine("init-fails", "exit 4")],
+        })
+        .await
+        .expect("CreatePod failed");
+
+    let err = client
+        .start_pod(StartPodRequest {
+            id: "test-pod-init".into(),
+        })
+        .await
+        .expect_err("StartPod should fail on the init container");
+    assert_eq!(err.code(), tonic::Code::Aborted);
+    assert!(err.message().contains("init-fails"));
+
+    let pod = client
+        .get_pod(GetPodRequest {
+            id: "test-pod-init".into(),
+        })
+        .await
+        .expect("GetPod failed")
+        .into_inner();
+    assert_eq!(pod.state, PodState::Failed as i32);
+    assert!(pod.error_message.contains("exited with code 4"));
+    assert_eq!(pod.init_containers[0].exit_code, 0);
+    assert_eq!(pod.init_containers[1].exit_code, 4);
+    // The containers never started
+    assert_eq!(pod.containers[0].state, ContainerState::Created as i32);
+
+    client
+        .delete_pod(DeletePodRequest {
+            id: "test-pod-init".into(),
+            force: true,
+        })
+        .await
+        .expect("DeletePod failed");
+}
+
 /// Test: HTTP Server with Port Binding.
 ///
 /// Uses busybox httpd to test port binding since it's simpler than nginx
@@ -150,7 +286,10 @@ async fn test_httpd_port_binding() {
                 ],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
+            init_containers: vec![],
         })
         .await
         .expect("CreatePod failed");
@@ -224,7 +363,10 @@ async fn test_list_pods() {
                 args: vec![],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
+            init_containers: vec![],
         })
         .await
         .expect("CreatePod failed");
@@ -290,7 +432,10 @@ async fn test_nginx_http_response() {
                 args: vec![],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
+            init_containers: vec![],
         })
         .await
         .expect("CreatePod failed")
@@ -396,7 +541,10 @@ async fn test_busybox_http_response() {
                 ],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
+            init_containers: vec![],
         })
         .await
         .expect("CreatePod failed")
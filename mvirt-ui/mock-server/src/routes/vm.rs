@@ -251,9 +251,10 @@ pub async fn console_ws(ws: WebSocketUpgrade) -> impl IntoResponse {
 
         // Echo server for mock console
         while let Some(Ok(msg)) = socket.recv().await {
-            if let Message::Text(text) = msg {
+            // Console bytes come as binary frames, like from mvirt-cplane
+            if let Message::Binary(data) = msg {
                 // Echo back the input
-                if socket.send(Message::Text(text)).await.is_err() {
+                if socket.send(Message::Binary(data)).await.is_err() {
                     break;
                 }
             }
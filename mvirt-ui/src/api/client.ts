@@ -87,15 +87,18 @@ export async function del<T>(path: string): Promise<T> {
   return handleResponse<T>(response)
 }
 
-// Stream endpoints stay unauthenticated for now — neither EventSource nor the
-// browser WebSocket constructor accept custom headers, so a Bearer header isn't
-// possible. Once the backend lands a JWT-aware fallback (e.g. `?access_token=`
-// query param read from a one-time cookie), wire it up here.
+// Neither EventSource nor the browser WebSocket constructor accept custom
+// headers, so a Bearer header isn't possible. Event streams stay
+// unauthenticated for now; WebSocket upgrades take the token as an
+// `?access_token=` query parameter instead.
 export function createEventSource(path: string): EventSource {
   return new EventSource(`${API_BASE}${path}`)
 }
 
-export function createWebSocket(path: string): WebSocket {
-  const protocol = window.location.protocol === 'https:' ? 'wss:' : 'ws:'
-  return new WebSocket(`${protocol}//${window.location.host}${API_BASE}${path}`)
+export async function createWebSocket(path: string): Promise<WebSocket> {
+  const url = new URL(`${API_BASE}${path}`, window.location.href)
+  url.protocol = url.protocol === 'https:' ? 'wss:' : 'ws:'
+  const token = await getAccessToken()
+  if (token) url.searchParams.set('access_token', token)
+  return new WebSocket(url)
 }
@@ -13,6 +13,10 @@ function formatMb(mb: number): string {
   return `${mb} MB`
 }
 
+function formatPercent(value: number): string {
+  return `${value.toFixed(1)}%`
+}
+
 /// Map node status → badge variant. Without this, every non-Online status
 /// hits the "else" branch and renders red, which is wrong for
 /// onboarding/offline (those aren't errors, just transient states).
@@ -215,6 +219,37 @@ export function NodeDetailPage() {
             </CardContent>
           </Card>
 
+          {r.host && (
+            <Card>
+              <CardHeader>
+                <CardTitle>Pressure</CardTitle>
+              </CardHeader>
+              <CardContent>
+                <dl className="grid grid-cols-2 gap-4 text-sm">
+                  <div>
+                    <dt className="text-muted-foreground">CPU Pressure</dt>
+                    <dd className="font-mono">{formatPercent(r.host.cpu_pressure)}</dd>
+                  </div>
+                  <div>
+                    <dt className="text-muted-foreground">Memory Pressure</dt>
+                    <dd className="font-mono">{formatPercent(r.host.memory_pressure)}</dd>
+                  </div>
+                  <div>
+                    <dt className="text-muted-foreground">IO Pressure</dt>
+                    <dd className="font-mono">{formatPercent(r.host.io_pressure)}</dd>
+                  </div>
+                  <div>
+                    <dt className="text-muted-foreground">CPU Steal</dt>
+                    <dd className="font-mono">{formatPercent(r.host.cpu_steal)}</dd>
+                  </div>
+                </dl>
+                <p className="mt-4 text-xs text-muted-foreground">
+                  Share of the last minute tasks stalled waiting for a resource
+                </p>
+              </CardContent>
+            </Card>
+          )}
+
           <Card>
             <CardHeader>
               <CardTitle>Storage</CardTitle>
@@ -44,34 +44,47 @@ export function VmConsole({ vmId }: VmConsoleProps) {
     termRef.current = term
     fitAddonRef.current = fitAddon
 
-    // Connect to WebSocket
-    const ws = createWebSocket(`/vms/${vmId}/console`)
-    wsRef.current = ws
-
-    ws.onopen = () => {
-      setConnected(true)
-      term.writeln('\x1b[32mConnected to console\x1b[0m')
-      term.writeln('Press Ctrl+a t to detach\n')
-    }
+    // Connect to WebSocket. Binary frames are console bytes; text frames
+    // are JSON session status from the server.
+    let disposed = false
+    const encoder = new TextEncoder()
+    createWebSocket(`/vms/${vmId}/console?scrollback=4096`).then((ws) => {
+      if (disposed) {
+        ws.close()
+        return
+      }
+      wsRef.current = ws
+      ws.binaryType = 'arraybuffer'
 
-    ws.onmessage = (event) => {
-      term.write(event.data)
-    }
+      ws.onopen = () => {
+        setConnected(true)
+        term.writeln('\x1b[32mConnected to console\x1b[0m')
+      }
 
-    ws.onclose = () => {
-      setConnected(false)
-      term.writeln('\n\x1b[31mDisconnected\x1b[0m')
-    }
+      ws.onmessage = (event) => {
+        if (typeof event.data === 'string') {
+          const status = JSON.parse(event.data) as { notice?: string }
+          if (status.notice) term.writeln(`\r\n\x1b[33m[${status.notice}]\x1b[0m`)
+          return
+        }
+        term.write(new Uint8Array(event.data as ArrayBuffer))
+      }
 
-    ws.onerror = () => {
-      setConnected(false)
-      term.writeln('\n\x1b[31mConnection error\x1b[0m')
-    }
+      ws.onclose = () => {
+        setConnected(false)
+        term.writeln('\n\x1b[31mDisconnected\x1b[0m')
+      }
 
-    term.onData((data) => {
-      if (ws.readyState === WebSocket.OPEN) {
-        ws.send(data)
+      ws.onerror = () => {
+        setConnected(false)
+        term.writeln('\n\x1b[31mConnection error\x1b[0m')
       }
+
+      term.onData((data) => {
+        if (ws.readyState === WebSocket.OPEN) {
+          ws.send(encoder.encode(data))
+        }
+      })
     })
 
     // Handle resize
@@ -81,8 +94,9 @@ export function VmConsole({ vmId }: VmConsoleProps) {
     resizeObserver.observe(terminalRef.current)
 
     return () => {
+      disposed = true
       resizeObserver.disconnect()
-      ws.close()
+      wsRef.current?.close()
       term.dispose()
     }
   }, [vmId])
@@ -6,6 +6,21 @@ export enum NodeStatus {
   UNKNOWN = 'unknown',
 }
 
+/** Live host details reported by the node agent. */
+export interface HostTelemetry {
+  cpu_model: string
+  load1: number
+  load5: number
+  load15: number
+  kvm: boolean
+  /** Percent of the last minute some task stalled (PSI avg60). */
+  cpu_pressure: number
+  memory_pressure: number
+  io_pressure: number
+  /** Percent of CPU time stolen by the hypervisor below the host. */
+  cpu_steal: number
+}
+
 export interface NodeResources {
   cpu_cores: number
   memory_mb: number
@@ -13,6 +28,7 @@ export interface NodeResources {
   available_cpu_cores: number
   available_memory_mb: number
   available_storage_gb: number
+  host?: HostTelemetry
 }
 
 /** Wire shape from /v1/nodes (camelCase). */
@@ -1,5 +1,17 @@
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("cargo:rerun-if-changed=proto/mvirt.proto");
     tonic_prost_build::compile_protos("proto/mvirt.proto")?;
+
+    // Clients for cascading deletes to owned volumes and NICs
+    let protos = [
+        "../mvirt-zfs/proto/zfs.proto",
+        "../mvirt-net/proto/net.proto",
+    ];
+    for p in &protos {
+        println!("cargo:rerun-if-changed={p}");
+    }
+    tonic_prost_build::configure()
+        .build_server(false)
+        .compile_protos(&protos, &["../mvirt-zfs/proto", "../mvirt-net/proto"])?;
     Ok(())
 }
@@ -0,0 +1,12 @@
+-- Stage timings of VM and pod starts: the last one on the VM, and a
+-- history of successful starts for percentiles
+ALTER TABLE vms ADD COLUMN start_timing_json TEXT;
+
+CREATE TABLE IF NOT EXISTS start_timings (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    vm_id TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    timing_json TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_start_timings_kind ON start_timings(kind, id);
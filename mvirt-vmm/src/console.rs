@@ -0,0 +1,1059 @@
+//! Console session hub: one backend connection per VM, fanned out to any
+//! number of gRPC viewers.
+//!
+//! cloud-hypervisor's serial socket serves a single client, so viewers
+//! can't each open their own connection. The hub owns that connection (or,
+//! for kernel boot, tails the serial log), broadcasts output to every
+//! session and forwards input only from sessions allowed to write.
+//!
+//! Write access:
+//! - `Shared` sessions type freely unless someone holds exclusive access.
+//! - `Exclusive` sessions become the sole writer if nobody else is;
+//!   otherwise they attach read-only.
+//! - `Takeover` (on attach, or sent later by any session) grabs exclusive
+//!   access, demoting the previous holder to read-only.
+//!
+//! Every attach, detach, takeover and forced disconnect is audit-logged,
+//! naming the session by its caller's uid (see [`crate::peer`]) and the
+//! client name it reported for itself.
+//!
+//! History: the hub starts capturing a VM's console as soon as it boots,
+//! whether or not anyone is attached, and keeps the most recent output in
+//! a per-VM ring buffer. Sessions can replay it on attach and
+//! `GetConsoleBuffer` serves it directly, so the boot log of a VM nobody
+//! watched is still available. The buffer outlives the VM process (for
+//! post-mortems) until the VM is deleted. With spooling enabled, output is
+//! also appended to `<data_dir>/console/<vm_id>.log` (one rotated `.1`
+//! file), which survives daemon restarts.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use mvirt_log::{AuditLogger, LogLevel};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::{Mutex, broadcast, mpsc};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+use crate::hypervisor::Hypervisor;
+use crate::proto::{ConsoleAccess, ConsoleSession, VmEvent, VmEventType};
+
+/// Output chunks buffered per viewer before it starts losing data.
+const OUTPUT_BUFFER: usize = 256;
+
+/// cloud-hypervisor creates the serial socket shortly after spawning, so
+/// capture right after start retries the connect for a little while.
+const CONNECT_ATTEMPTS: u32 = 20;
+const CONNECT_RETRY: Duration = Duration::from_millis(100);
+
+#[derive(Clone, Debug)]
+pub struct ConsoleBufferConfig {
+    /// Bytes of recent output kept in memory per VM.
+    pub capacity: usize,
+    /// Directory for on-disk spool files; `None` disables spooling.
+    pub spool_dir: Option<PathBuf>,
+    /// Size at which a spool file is rotated.
+    pub spool_limit: u64,
+}
+
+impl Default for ConsoleBufferConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 256 * 1024,
+            spool_dir: None,
+            spool_limit: 16 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub enum ConsoleEvent {
+    Data(Vec<u8>),
+    /// Backend hit EOF (VM stopped) or failed; sessions should end.
+    Closed,
+}
+
+/// Per-session notification from the hub.
+pub enum SessionControl {
+    /// Write access changed; `notice` says why.
+    Access { writable: bool, notice: String },
+    /// Session was forcibly disconnected.
+    Kicked { reason: String },
+}
+
+/// Ring buffer of the most recent console output.
+struct History {
+    buf: VecDeque<u8>,
+    capacity: usize,
+    /// Older output has been dropped to stay within capacity.
+    truncated: bool,
+}
+
+impl History {
+    fn new(capacity: usize) -> Self {
+        Self {
+            buf: VecDeque::with_capacity(capacity),
+            capacity,
+            truncated: false,
+        }
+    }
+
+    fn push(&mut self, data: &[u8]) {
+        self.buf.extend(data);
+        let excess = self.buf.len().saturating_sub(self.capacity);
+        if excess > 0 {
+            self.buf.drain(..excess);
+            self.truncated = true;
+        }
+    }
+
+    /// The last `max` bytes (everything if `max` is 0) and whether older
+    /// output exists that isn't included.
+    fn tail(&self, max: usize) -> (Vec<u8>, bool) {
+        let skip = match max {
+            0 => 0,
+            max => self.buf.len().saturating_sub(max),
+        };
+        (
+            self.buf.iter().skip(skip).copied().collect(),
+            self.truncated || skip > 0,
+        )
+    }
+}
+
+/// Append-only console log on disk with a single rotation.
+struct Spool {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    limit: u64,
+}
+
+impl Spool {
+    async fn open(path: PathBuf, limit: u64) -> std::io::Result<Self> {
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .await?;
+        let written = file.metadata().await?.len();
+        Ok(Self {
+            path,
+            file,
+            written,
+            limit,
+        })
+    }
+
+    async fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.file.write_all(data).await?;
+        self.written += data.len() as u64;
+        if self.written >= self.limit {
+            tokio::fs::rename(&self.path, rotated(&self.path)).await?;
+            *self = Self::open(self.path.clone(), self.limit).await?;
+        }
+        Ok(())
+    }
+}
+
+fn rotated(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".1");
+    PathBuf::from(name)
+}
+
+/// Last `max` bytes of a spooled console log, including the rotated file
+/// if the current one is shorter. Returns `None` if nothing was spooled.
+async fn read_spool(path: &Path, max: usize) -> Option<(Vec<u8>, bool)> {
+    async fn tail(path: &Path, max: usize) -> std::io::Result<(Vec<u8>, bool)> {
+        let mut file = File::open(path).await?;
+        let len = file.metadata().await?.len();
+        let start = len.saturating_sub(max as u64);
+        file.seek(std::io::SeekFrom::Start(start)).await?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data).await?;
+        Ok((data, start > 0))
+    }
+
+    let (mut data, truncated) = tail(path, max).await.ok()?;
+    if truncated || data.len() >= max {
+        return Some((data, truncated));
+    }
+    match tail(&rotated(path), max - data.len()).await {
+        Ok((mut older, truncated)) => {
+            older.append(&mut data);
+            Some((older, truncated))
+        }
+        Err(_) => Some((data, false)),
+    }
+}
+
+/// Shared by a console's reader task: records output in the history and
+/// spool and fans it out to sessions.
+struct Recorder {
+    output: broadcast::Sender<ConsoleEvent>,
+    history: Arc<std::sync::Mutex<History>>,
+    spool: Option<Spool>,
+    closed: Arc<AtomicBool>,
+}
+
+impl Recorder {
+    async fn record(&mut self, data: &[u8]) {
+        {
+            // History and broadcast under one lock, so attach() can take a
+            // snapshot and subscribe without gaps or duplicates.
+            let mut history = self.history.lock().unwrap();
+            history.push(data);
+            let _ = self.output.send(ConsoleEvent::Data(data.to_vec()));
+        }
+        let failed = match &mut self.spool {
+            Some(spool) => match spool.write(data).await {
+                Ok(()) => false,
+                Err(e) => {
+                    warn!(path = %spool.path.display(), error = %e, "Console spool failed, disabling");
+                    true
+                }
+            },
+            None => false,
+        };
+        if failed {
+            self.spool = None;
+        }
+    }
+
+    fn finish(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        let _ = self.output.send(ConsoleEvent::Closed);
+    }
+}
+
+struct SessionState {
+    client: String,
+    access: ConsoleAccess,
+    attached_at: i64,
+    control: mpsc::UnboundedSender<SessionControl>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    sessions: HashMap<String, SessionState>,
+    /// Session holding exclusive write access, if any.
+    exclusive: Option<String>,
+}
+
+impl Sessions {
+    fn writable(&self, backend_writable: bool, session_id: &str) -> bool {
+        if !backend_writable {
+            return false;
+        }
+        match &self.exclusive {
+            Some(holder) => holder == session_id,
+            None => self
+                .sessions
+                .get(session_id)
+                .is_some_and(|s| s.access != ConsoleAccess::ReadOnly),
+        }
+    }
+
+    /// Tell every session its (possibly changed) write access.
+    fn notify_all(&self, backend_writable: bool, notice: &str) {
+        for (id, s) in &self.sessions {
+            let _ = s.control.send(SessionControl::Access {
+                writable: self.writable(backend_writable, id),
+                notice: notice.to_string(),
+            });
+        }
+    }
+}
+
+/// A VM's console backend, its output history and attached sessions.
+pub struct VmConsole {
+    vm_id: String,
+    output: broadcast::Sender<ConsoleEvent>,
+    /// `None` for read-only (file-tailed) consoles.
+    input: Option<mpsc::Sender<Vec<u8>>>,
+    history: Arc<std::sync::Mutex<History>>,
+    sessions: Mutex<Sessions>,
+    closed: Arc<AtomicBool>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl Drop for VmConsole {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
+}
+
+impl VmConsole {
+    /// Connect to the VM's console. `history` carries over output from a
+    /// previous run of the same VM.
+    async fn open(
+        vm_id: &str,
+        path: &Path,
+        history: History,
+        spool: Option<Spool>,
+    ) -> std::io::Result<Self> {
+        let (output, _) = broadcast::channel(OUTPUT_BUFFER);
+        let closed = Arc::new(AtomicBool::new(false));
+        let capacity = history.capacity;
+        let history = Arc::new(std::sync::Mutex::new(history));
+        let mut recorder = Recorder {
+            output: output.clone(),
+            history: history.clone(),
+            spool,
+            closed: closed.clone(),
+        };
+        let mut tasks = Vec::new();
+
+        let is_socket = path.extension().is_some_and(|e| e == "sock");
+        let input = if is_socket {
+            // Socket-based console (Disk Boot) - bidirectional
+            let socket = connect(path).await?;
+            let (mut socket_read, mut socket_write) = socket.into_split();
+            let (input_tx, mut input_rx) = mpsc::channel::<Vec<u8>>(64);
+
+            tasks.push(tokio::spawn(async move {
+                while let Some(data) = input_rx.recv().await {
+                    if socket_write.write_all(&data).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+
+            tasks.push(tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                loop {
+                    match socket_read.read(&mut buf).await {
+                        Ok(0) => break, // EOF
+                        Ok(n) => recorder.record(&buf[..n]).await,
+                        Err(e) => {
+                            error!(error = %e, "Error reading from console socket");
+                            break;
+                        }
+                    }
+                }
+                recorder.finish();
+            }));
+            Some(input_tx)
+        } else {
+            // File-based console (Kernel Boot) - read-only, tail -f style.
+            // Start one buffer's worth from the end so history covers what
+            // the VM printed before we started watching.
+            let mut console_file = File::open(path).await?;
+            let len = console_file.metadata().await?.len();
+            let _ = console_file
+                .seek(std::io::SeekFrom::Start(
+                    len.saturating_sub(capacity as u64),
+                ))
+                .await;
+
+            tasks.push(tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                loop {
+                    match console_file.read(&mut buf).await {
+                        Ok(0) => {
+                            // No new data, wait briefly and try again (tail -f behavior)
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                        }
+                        Ok(n) => recorder.record(&buf[..n]).await,
+                        Err(e) => {
+                            error!(error = %e, "Error reading from console log");
+                            break;
+                        }
+                    }
+                }
+                recorder.finish();
+            }));
+            None
+        };
+
+        Ok(Self {
+            vm_id: vm_id.to_string(),
+            output,
+            input,
+            history,
+            sessions: Mutex::new(Sessions::default()),
+            closed,
+            tasks,
+        })
+    }
+
+    fn backend_writable(&self) -> bool {
+        self.input.is_some()
+    }
+
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    /// Stop reading from the backend; the history stays available.
+    fn close(&self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+        if !self.closed.swap(true, Ordering::SeqCst) {
+            let _ = self.output.send(ConsoleEvent::Closed);
+        }
+    }
+
+    fn take_history(&self) -> History {
+        let mut history = self.history.lock().unwrap();
+        let capacity = history.capacity;
+        std::mem::replace(&mut *history, History::new(capacity))
+    }
+}
+
+async fn connect(path: &Path) -> std::io::Result<UnixStream> {
+    let mut attempt = 1;
+    loop {
+        match UnixStream::connect(path).await {
+            Ok(s) => return Ok(s),
+            Err(_) if attempt < CONNECT_ATTEMPTS => {
+                attempt += 1;
+                tokio::time::sleep(CONNECT_RETRY).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// How a session is named in audit logs and listings: who the caller is,
+/// and the client name it gave itself, which anyone can make up.
+pub fn client_label(caller: &str, claimed: &str) -> String {
+    match claimed.trim() {
+        "" => caller.to_string(),
+        claimed => format!("{} (self-reported: {})", caller, claimed),
+    }
+}
+
+/// An attached viewer. Dropping it does not detach; call
+/// [`ConsoleHub::detach`] once the gRPC stream ends.
+pub struct ConsoleSessionHandle {
+    pub id: String,
+    pub writable: bool,
+    /// Recent output to replay before the live stream.
+    pub history: Vec<u8>,
+    pub output: broadcast::Receiver<ConsoleEvent>,
+    pub control: mpsc::UnboundedReceiver<SessionControl>,
+    pub console: Arc<VmConsole>,
+}
+
+pub struct ConsoleHub {
+    consoles: Mutex<HashMap<String, Arc<VmConsole>>>,
+    audit: Arc<AuditLogger>,
+    config: ConsoleBufferConfig,
+}
+
+impl ConsoleHub {
+    pub fn new(audit: Arc<AuditLogger>, config: ConsoleBufferConfig) -> Self {
+        Self {
+            consoles: Mutex::new(HashMap::new()),
+            audit,
+            config,
+        }
+    }
+
+    fn spool_path(&self, vm_id: &str) -> Option<PathBuf> {
+        self.config
+            .spool_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.log", vm_id)))
+    }
+
+    /// The VM's live console, (re)connecting if there is none or the
+    /// previous one has closed. History from a closed console carries over.
+    async fn open(&self, vm_id: &str, path: &Path) -> std::io::Result<Arc<VmConsole>> {
+        let mut consoles = self.consoles.lock().await;
+        let history = match consoles.get(vm_id) {
+            Some(c) if !c.is_closed() => return Ok(c.clone()),
+            Some(c) => c.take_history(),
+            None => History::new(self.config.capacity),
+        };
+        let spool = match self.spool_path(vm_id) {
+            Some(p) => match Spool::open(p.clone(), self.config.spool_limit).await {
+                Ok(s) => Some(s),
+                Err(e) => {
+                    warn!(path = %p.display(), error = %e, "Failed to open console spool");
+                    None
+                }
+            },
+            None => None,
+        };
+        let console = Arc::new(VmConsole::open(vm_id, path, history, spool).await?);
+        consoles.insert(vm_id.to_string(), console.clone());
+        Ok(console)
+    }
+
+    /// Start recording a VM's console without attaching a session.
+    pub async fn capture(&self, vm_id: &str, path: &Path) {
+        match self.open(vm_id, path).await {
+            Ok(_) => info!(vm_id, "Capturing console output"),
+            Err(e) => warn!(vm_id, error = %e, "Failed to capture console"),
+        }
+    }
+
+    /// VM stopped: disconnect sessions but keep its history.
+    pub async fn close(&self, vm_id: &str) {
+        if let Some(console) = self.consoles.lock().await.get(vm_id) {
+            console.close();
+        }
+    }
+
+    /// VM deleted: drop its history and spool.
+    pub async fn forget(&self, vm_id: &str) {
+        self.consoles.lock().await.remove(vm_id);
+        if let Some(path) = self.spool_path(vm_id) {
+            let _ = tokio::fs::remove_file(rotated(&path)).await;
+            let _ = tokio::fs::remove_file(&path).await;
+        }
+    }
+
+    /// Recent console output of a VM: the in-memory history if the hub
+    /// has seen the VM since startup, else the spool file. Returns the data
+    /// and whether older output was cut off, or `None` if nothing is known.
+    pub async fn buffer(&self, vm_id: &str, max_bytes: usize) -> Option<(Vec<u8>, bool)> {
+        let console = self.consoles.lock().await.get(vm_id).cloned();
+        if let Some(console) = console {
+            return Some(console.history.lock().unwrap().tail(max_bytes));
+        }
+        let path = self.spool_path(vm_id)?;
+        let max = match max_bytes {
+            0 => self.config.capacity,
+            max => max,
+        };
+        read_spool(&path, max).await
+    }
+
+    /// Follow VM lifecycle events: capture consoles of started VMs, close
+    /// them on stop and drop their history on delete.
+    pub fn spawn_event_listener(
+        self: Arc<Self>,
+        hypervisor: Arc<Hypervisor>,
+        mut events: broadcast::Receiver<VmEvent>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                let event = match events.recv().await {
+                    Ok(event) => event,
+                    Err(broadcast::error::RecvError::Lagged(n)) => {
+                        warn!(missed = n, "Console hub lagged behind VM events");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                };
+                match VmEventType::try_from(event.r#type) {
+                    Ok(VmEventType::VmEventStarted) => {
+                        if let Some(path) = hypervisor.console_path(&event.vm_id).await {
+                            self.capture(&event.vm_id, &path).await;
+                        }
+                    }
+                    Ok(VmEventType::VmEventStopped) => self.close(&event.vm_id).await,
+                    Ok(VmEventType::VmEventDeleted) => self.forget(&event.vm_id).await,
+                    _ => {}
+                }
+            }
+        })
+    }
+
+    /// Attach a session, opening the VM's console backend if it isn't
+    /// being captured yet. Up to `history_bytes` of recent output are
+    /// returned for replay.
+    pub async fn attach(
+        &self,
+        vm_id: &str,
+        path: &Path,
+        access: ConsoleAccess,
+        client: &str,
+        history_bytes: usize,
+    ) -> std::io::Result<ConsoleSessionHandle> {
+        let console = self.open(vm_id, path).await?;
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let access = match access {
+            ConsoleAccess::Unspecified => ConsoleAccess::Shared,
+            a => a,
+        };
+        let (control_tx, control_rx) = mpsc::unbounded_channel();
+        // Snapshot and subscribe under the history lock: the reader records
+        // and broadcasts under it too, so replay and live output line up.
+        let (history, output) = {
+            let h = console.history.lock().unwrap();
+            let history = match history_bytes {
+                0 => Vec::new(),
+                n => h.tail(n).0,
+            };
+            (history, console.output.subscribe())
+        };
+
+        let (writable, took_over) = {
+            let mut s = console.sessions.lock().await;
+            s.sessions.insert(
+                id.clone(),
+                SessionState {
+                    client: client.to_string(),
+                    access,
+                    attached_at: chrono::Utc::now().timestamp(),
+                    control: control_tx,
+                },
+            );
+            let took_over = match access {
+                ConsoleAccess::Exclusive if s.exclusive.is_none() => {
+                    s.exclusive = Some(id.clone());
+                    true
+                }
+                ConsoleAccess::Takeover => {
+                    s.exclusive = Some(id.clone());
+                    true
+                }
+                _ => false,
+            };
+            if took_over && console.backend_writable() {
+                s.notify_all(true, &format!("{} has exclusive write access", client));
+            }
+            (s.writable(console.backend_writable(), &id), took_over)
+        };
+
+        info!(vm_id, session = %id, client, ?access, "Console session attached");
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "Console attached: VM {} by {} ({}{})",
+                    vm_id,
+                    client,
+                    if writable { "read-write" } else { "read-only" },
+                    if took_over { ", exclusive" } else { "" }
+                ),
+                vec![vm_id.to_string(), id.clone()],
+            )
+            .await;
+
+        Ok(ConsoleSessionHandle {
+            id,
+            writable,
+            history,
+            output,
+            control: control_rx,
+            console,
+        })
+    }
+
+    /// Forward input from `session_id`. Returns false (and drops the data)
+    /// if the session currently has no write access.
+    pub async fn write(&self, console: &VmConsole, session_id: &str, data: Vec<u8>) -> bool {
+        let Some(input) = &console.input else {
+            return false;
+        };
+        if !console.sessions.lock().await.writable(true, session_id) {
+            return false;
+        }
+        input.send(data).await.is_ok()
+    }
+
+    /// Give `session_id` exclusive write access, demoting the current holder.
+    pub async fn takeover(&self, console: &VmConsole, session_id: &str) {
+        let (client, previous) = {
+            let mut s = console.sessions.lock().await;
+            let Some(client) = s.sessions.get(session_id).map(|s| s.client.clone()) else {
+                return;
+            };
+            if s.exclusive.as_deref() == Some(session_id) {
+                return;
+            }
+            let previous = s.exclusive.replace(session_id.to_string());
+            s.notify_all(
+                console.backend_writable(),
+                &format!("{} took over write access", client),
+            );
+            (client, previous)
+        };
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "Console takeover: VM {} by {}{}",
+                    console.vm_id,
+                    client,
+                    previous
+                        .map(|p| format!(" (from session {})", p))
+                        .unwrap_or_default()
+                ),
+                vec![console.vm_id.clone(), session_id.to_string()],
+            )
+            .await;
+    }
+
+    /// Remove a session. The backend stays open to keep recording history.
+    pub async fn detach(&self, console: &VmConsole, session_id: &str) {
+        let client = {
+            let mut s = console.sessions.lock().await;
+            let Some(state) = s.sessions.remove(session_id) else {
+                return;
+            };
+            if s.exclusive.as_deref() == Some(session_id) {
+                s.exclusive = None;
+                s.notify_all(
+                    console.backend_writable(),
+                    "exclusive write access released",
+                );
+            }
+            state.client
+        };
+
+        info!(vm_id = %console.vm_id, session = %session_id, client, "Console session detached");
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!("Console detached: VM {} by {}", console.vm_id, client),
+                vec![console.vm_id.clone(), session_id.to_string()],
+            )
+            .await;
+    }
+
+    /// Sessions attached to a VM's console, or to all VMs if `vm_id` is empty.
+    pub async fn list(&self, vm_id: &str) -> Vec<ConsoleSession> {
+        let consoles: Vec<Arc<VmConsole>> = {
+            let consoles = self.consoles.lock().await;
+            consoles
+                .values()
+                .filter(|c| vm_id.is_empty() || c.vm_id == vm_id)
+                .cloned()
+                .collect()
+        };
+        let mut out = Vec::new();
+        for console in consoles {
+            let s = console.sessions.lock().await;
+            for (id, state) in &s.sessions {
+                out.push(ConsoleSession {
+                    id: id.clone(),
+                    vm_id: console.vm_id.clone(),
+                    client: state.client.clone(),
+                    access: state.access as i32,
+                    writable: s.writable(console.backend_writable(), id),
+                    attached_at: state.attached_at,
+                });
+            }
+        }
+        out.sort_by_key(|s| s.attached_at);
+        out
+    }
+
+    /// Forcibly end a session. Returns false if no such session exists.
+    pub async fn disconnect(&self, vm_id: &str, session_id: &str, reason: &str) -> bool {
+        let Some(console) = self.consoles.lock().await.get(vm_id).cloned() else {
+            return false;
+        };
+        let kicked = {
+            let s = console.sessions.lock().await;
+            match s.sessions.get(session_id) {
+                Some(state) => {
+                    let _ = state.control.send(SessionControl::Kicked {
+                        reason: reason.to_string(),
+                    });
+                    Some(state.client.clone())
+                }
+                None => None,
+            }
+        };
+        let Some(client) = kicked else {
+            return false;
+        };
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "Console session disconnected by admin: VM {} session of {} ({})",
+                    vm_id, client, reason
+                ),
+                vec![vm_id.to_string(), session_id.to_string()],
+            )
+            .await;
+        // The session's stream task observes the kick and calls detach().
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::UnixListener;
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mvirt-console-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn hub() -> ConsoleHub {
+        ConsoleHub::new(
+            Arc::new(AuditLogger::new_noop()),
+            ConsoleBufferConfig::default(),
+        )
+    }
+
+    /// A serial socket like cloud-hypervisor's, for the hub to connect to.
+    fn serial(dir: &Path) -> (PathBuf, UnixListener) {
+        let path = dir.join("serial.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        (path, listener)
+    }
+
+    fn session(access: ConsoleAccess) -> (SessionState, mpsc::UnboundedReceiver<SessionControl>) {
+        let (control, rx) = mpsc::unbounded_channel();
+        let state = SessionState {
+            client: "test".to_string(),
+            access,
+            attached_at: 0,
+            control,
+        };
+        (state, rx)
+    }
+
+    /// The write access last announced to a session, if any.
+    fn announced(control: &mut mpsc::UnboundedReceiver<SessionControl>) -> Option<bool> {
+        let mut last = None;
+        while let Ok(msg) = control.try_recv() {
+            if let SessionControl::Access { writable, .. } = msg {
+                last = Some(writable);
+            }
+        }
+        last
+    }
+
+    #[test]
+    fn test_sessions_writable() {
+        let mut s = Sessions::default();
+        let (shared, _shared_rx) = session(ConsoleAccess::Shared);
+        let (read_only, _read_only_rx) = session(ConsoleAccess::ReadOnly);
+        s.sessions.insert("a".to_string(), shared);
+        s.sessions.insert("b".to_string(), read_only);
+
+        assert!(s.writable(true, "a"));
+        assert!(!s.writable(true, "b"));
+        assert!(!s.writable(true, "unknown"));
+        // A file-tailed console takes no input at all
+        assert!(!s.writable(false, "a"));
+
+        // An exclusive holder is the only writer, even if read-only before
+        s.exclusive = Some("b".to_string());
+        assert!(!s.writable(true, "a"));
+        assert!(s.writable(true, "b"));
+    }
+
+    #[test]
+    fn test_notify_all() {
+        let mut s = Sessions::default();
+        let (a, mut a_rx) = session(ConsoleAccess::Shared);
+        let (b, mut b_rx) = session(ConsoleAccess::Shared);
+        s.sessions.insert("a".to_string(), a);
+        s.sessions.insert("b".to_string(), b);
+        s.exclusive = Some("b".to_string());
+
+        s.notify_all(true, "b has exclusive write access");
+        assert_eq!(announced(&mut a_rx), Some(false));
+        assert_eq!(announced(&mut b_rx), Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_exclusive_attach_and_takeover() {
+        let dir = temp_dir();
+        let (path, listener) = serial(&dir);
+        let hub = hub();
+
+        let mut a = hub
+            .attach("vm-1", &path, ConsoleAccess::Unspecified, "a", 0)
+            .await
+            .unwrap();
+        let (mut guest, _) = listener.accept().await.unwrap();
+        assert!(a.writable);
+
+        // Exclusive access demotes the shared writer
+        let mut b = hub
+            .attach("vm-1", &path, ConsoleAccess::Exclusive, "b", 0)
+            .await
+            .unwrap();
+        assert!(b.writable);
+        assert_eq!(announced(&mut a.control), Some(false));
+        assert!(!hub.write(&a.console, &a.id, b"ls\n".to_vec()).await);
+
+        // Nobody else gets it while it's held
+        let c = hub
+            .attach("vm-1", &path, ConsoleAccess::Exclusive, "c", 0)
+            .await
+            .unwrap();
+        assert!(!c.writable);
+
+        // Takeover moves it
+        hub.takeover(&a.console, &a.id).await;
+        assert_eq!(announced(&mut a.control), Some(true));
+        assert_eq!(announced(&mut b.control), Some(false));
+        assert!(!hub.write(&b.console, &b.id, b"rm\n".to_vec()).await);
+        assert!(hub.write(&a.console, &a.id, b"ls\n".to_vec()).await);
+        let mut input = [0u8; 3];
+        guest.read_exact(&mut input).await.unwrap();
+        assert_eq!(&input, b"ls\n");
+
+        let sessions = hub.list("vm-1").await;
+        assert_eq!(sessions.len(), 3);
+        let writers: Vec<&str> = sessions
+            .iter()
+            .filter(|s| s.writable)
+            .map(|s| s.client.as_str())
+            .collect();
+        assert_eq!(writers, ["a"]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_detach_releases_exclusive_access() {
+        let dir = temp_dir();
+        let (path, _listener) = serial(&dir);
+        let hub = hub();
+
+        let mut a = hub
+            .attach("vm-1", &path, ConsoleAccess::Shared, "a", 0)
+            .await
+            .unwrap();
+        let b = hub
+            .attach("vm-1", &path, ConsoleAccess::Takeover, "b", 0)
+            .await
+            .unwrap();
+        assert_eq!(announced(&mut a.control), Some(false));
+
+        hub.detach(&b.console, &b.id).await;
+        assert_eq!(announced(&mut a.control), Some(true));
+        assert!(hub.write(&a.console, &a.id, b"ls\n".to_vec()).await);
+        let sessions = hub.list("vm-1").await;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].id, a.id);
+
+        // Detaching twice, or a session that never was, is a no-op
+        hub.detach(&b.console, &b.id).await;
+        hub.detach(&a.console, "unknown").await;
+        assert_eq!(hub.list("vm-1").await.len(), 1);
+
+        hub.detach(&a.console, &a.id).await;
+        assert!(hub.list("").await.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_history_ring_bound() {
+        let mut history = History::new(8);
+        history.push(b"hello");
+        assert_eq!(history.tail(0), (b"hello".to_vec(), false));
+        assert_eq!(history.tail(3), (b"llo".to_vec(), true));
+        assert_eq!(history.tail(100), (b"hello".to_vec(), false));
+
+        // Older output is dropped to stay within capacity
+        history.push(b" world");
+        assert_eq!(history.buf.len(), 8);
+        assert_eq!(history.tail(0), (b"lo world".to_vec(), true));
+
+        // A single push larger than the ring keeps its end
+        history.push(b"0123456789abcdef");
+        assert_eq!(history.tail(0), (b"89abcdef".to_vec(), true));
+    }
+
+    #[tokio::test]
+    async fn test_spool_rotates_at_the_limit() {
+        let dir = temp_dir();
+        let path = dir.join("vm-1.log");
+        let mut spool = Spool::open(path.clone(), 10).await.unwrap();
+        spool.write(b"12345").await.unwrap();
+        assert!(!rotated(&path).exists());
+
+        // Reaching the limit moves the file aside and starts a new one
+        spool.write(b"67890").await.unwrap();
+        assert_eq!(std::fs::read(rotated(&path)).unwrap(), b"1234567890");
+        assert_eq!(std::fs::read(&path).unwrap(), b"");
+        spool.write(b"abc").await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"abc");
+
+        // A reopened spool counts what's already there
+        drop(spool);
+        let mut spool = Spool::open(path.clone(), 10).await.unwrap();
+        assert_eq!(spool.written, 3);
+        spool.write(b"defghij").await.unwrap();
+        assert_eq!(std::fs::read(rotated(&path)).unwrap(), b"abcdefghij");
+
+        // Only one rotated file is kept
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_read_spool_across_rotation() {
+        let dir = temp_dir();
+        let path = dir.join("vm-1.log");
+        assert_eq!(read_spool(&path, 10).await, None);
+
+        std::fs::write(rotated(&path), b"0123456789").unwrap();
+        std::fs::write(&path, b"abcd").unwrap();
+        // The current file alone
+        assert_eq!(read_spool(&path, 4).await, Some((b"abcd".to_vec(), false)));
+        assert_eq!(read_spool(&path, 2).await, Some((b"cd".to_vec(), true)));
+        // Topped up from the rotated file
+        assert_eq!(
+            read_spool(&path, 7).await,
+            Some((b"789abcd".to_vec(), true))
+        );
+        assert_eq!(
+            read_spool(&path, 100).await,
+            Some((b"0123456789abcd".to_vec(), false))
+        );
+
+        // Without a rotated file, what there is
+        std::fs::remove_file(rotated(&path)).unwrap();
+        assert_eq!(
+            read_spool(&path, 100).await,
+            Some((b"abcd".to_vec(), false))
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_buffer_falls_back_to_the_spool() {
+        let dir = temp_dir();
+        let hub = ConsoleHub::new(
+            Arc::new(AuditLogger::new_noop()),
+            ConsoleBufferConfig {
+                capacity: 16,
+                spool_dir: Some(dir.clone()),
+                spool_limit: 1024,
+            },
+        );
+        assert_eq!(hub.buffer("vm-1", 0).await, None);
+
+        // A VM the hub hasn't seen since a restart is served from disk
+        std::fs::write(dir.join("vm-1.log"), b"boot log of vm-1").unwrap();
+        assert_eq!(
+            hub.buffer("vm-1", 0).await,
+            Some((b"boot log of vm-1".to_vec(), false))
+        );
+        assert_eq!(hub.buffer("vm-1", 5).await, Some((b" vm-1".to_vec(), true)));
+
+        hub.forget("vm-1").await;
+        assert_eq!(hub.buffer("vm-1", 0).await, None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_client_label() {
+        assert_eq!(client_label("uid 1000", ""), "uid 1000");
+        assert_eq!(
+            client_label("uid 0", "alice via mvirt-api"),
+            "uid 0 (self-reported: alice via mvirt-api)"
+        );
+    }
+}
@@ -0,0 +1,336 @@
+//! Volumes and NICs owned by a VM or pod.
+//!
+//! mvirt-zfs and mvirt-net store an owner reference on volumes and NICs
+//! created for a VM or pod. Deleting the owner either deletes them too or
+//! is refused while they exist, as chosen by the request's
+//! [`DeletePropagation`].
+
+use std::fmt;
+
+use tonic::Status;
+use tonic::transport::Channel;
+use tracing::warn;
+
+use crate::net_proto::net_service_client::NetServiceClient;
+use crate::net_proto::{DeleteNicRequest, ListNicsRequest, Nic};
+use crate::proto::DeletePropagation;
+use crate::zfs_proto::zfs_service_client::ZfsServiceClient;
+use crate::zfs_proto::{DeleteVolumeRequest, ListVolumesRequest, Volume};
+
+/// Owner kind of VMs in owner references.
+pub const OWNER_KIND_VM: &str = "vm";
+/// Owner kind of pods in owner references.
+pub const OWNER_KIND_POD: &str = "pod";
+
+/// A resource in another daemon owned by a VM or pod.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Dependent {
+    /// mvirt-zfs deletes volumes by name.
+    Volume {
+        name: String,
+    },
+    Nic {
+        id: String,
+        name: String,
+    },
+}
+
+impl fmt::Display for Dependent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Dependent::Volume { name } => write!(f, "volume {}", name),
+            Dependent::Nic { id, name } if name.is_empty() => write!(f, "nic {}", id),
+            Dependent::Nic { name, .. } => write!(f, "nic {}", name),
+        }
+    }
+}
+
+/// Clients of the daemons holding owned resources. A daemon without a
+/// client has no dependents.
+#[derive(Clone, Default)]
+pub struct Dependents {
+    zfs: Option<ZfsServiceClient<Channel>>,
+    net: Option<NetServiceClient<Channel>>,
+}
+
+impl Dependents {
+    pub fn new(zfs: Option<Channel>, net: Option<Channel>) -> Self {
+        Self {
+            zfs: zfs.map(ZfsServiceClient::new),
+            net: net.map(NetServiceClient::new),
+        }
+    }
+
+    /// Volumes and NICs owned by `kind`/`id`.
+    pub async fn list(&self, kind: &str, id: &str) -> Result<Vec<Dependent>, Status> {
+        let mut dependents = Vec::new();
+
+        if let Some(zfs) = &self.zfs {
+            let volumes = zfs
+                .clone()
+                .list_volumes(ListVolumesRequest {})
+                .await?
+                .into_inner()
+                .volumes;
+            dependents.extend(owned_volumes(volumes, kind, id));
+        }
+
+        if let Some(net) = &self.net {
+            let nics = net
+                .clone()
+                .list_nics(ListNicsRequest {
+                    network_id: String::new(),
+                })
+                .await?
+                .into_inner()
+                .nics;
+            dependents.extend(owned_nics(nics, kind, id));
+        }
+
+        Ok(dependents)
+    }
+
+    /// Apply `propagation` ahead of deleting `kind`/`id`. Blocking fails
+    /// with the dependents listed if there are any; cascading returns them
+    /// for [`Dependents::delete`] once the owner is gone.
+    ///
+    /// A cascade doesn't hold up the owner's deletion when the other
+    /// daemons can't be asked; their resources are then left behind.
+    pub async fn check(
+        &self,
+        kind: &str,
+        id: &str,
+        propagation: DeletePropagation,
+    ) -> Result<Vec<Dependent>, Status> {
+        decide(kind, id, propagation, self.list(kind, id).await)
+    }
+
+    /// Delete `dependents`, returning those that were deleted. Failures
+    /// are logged and skipped.
+    pub async fn delete(&self, dependents: &[Dependent]) -> Vec<Dependent> {
+        let mut deleted = Vec::new();
+        for dependent in dependents {
+            let result = match dependent {
+                Dependent::Volume { name } => match &self.zfs {
+                    Some(zfs) => zfs
+                        .clone()
+                        .delete_volume(DeleteVolumeRequest { name: name.clone() })
+                        .await
+                        .map(|_| ()),
+                    None => continue,
+                },
+                Dependent::Nic { id, .. } => match &self.net {
+                    Some(net) => net
+                        .clone()
+                        .delete_nic(DeleteNicRequest { id: id.clone() })
+                        .await
+                        .map(|_| ()),
+                    None => continue,
+                },
+            };
+            match result {
+                Ok(()) => deleted.push(dependent.clone()),
+                Err(e) => {
+                    warn!(dependent = %dependent, error = %e.message(), "Failed to delete dependent")
+                }
+            }
+        }
+        deleted
+    }
+}
+
+fn owned_volumes(volumes: Vec<Volume>, kind: &str, id: &str) -> impl Iterator<Item = Dependent> {
+    volumes
+        .into_iter()
+        .filter(move |v| {
+            v.owner
+                .as_ref()
+                .is_some_and(|o| o.kind == kind && o.id == id)
+        })
+        .map(|v| Dependent::Volume { name: v.name })
+}
+
+fn owned_nics(nics: Vec<Nic>, kind: &str, id: &str) -> impl Iterator<Item = Dependent> {
+    nics.into_iter()
+        .filter(move |n| {
+            n.owner
+                .as_ref()
+                .is_some_and(|o| o.kind == kind && o.id == id)
+        })
+        .map(|n| Dependent::Nic {
+            id: n.id,
+            name: n.name,
+        })
+}
+
+/// [`Dependents::check`] given the result of listing the dependents.
+fn decide(
+    kind: &str,
+    id: &str,
+    propagation: DeletePropagation,
+    listed: Result<Vec<Dependent>, Status>,
+) -> Result<Vec<Dependent>, Status> {
+    match propagation {
+        DeletePropagation::Block => {
+            let dependents = listed.map_err(|e| {
+                Status::unavailable(format!("Cannot list dependents: {}", e.message()))
+            })?;
+            if dependents.is_empty() {
+                Ok(vec![])
+            } else {
+                Err(Status::failed_precondition(format!(
+                    "{} {} still owns {}",
+                    kind,
+                    id,
+                    join(&dependents)
+                )))
+            }
+        }
+        DeletePropagation::Cascade => match listed {
+            Ok(dependents) => Ok(dependents),
+            Err(e) => {
+                warn!(owner = %id, error = %e.message(), "Cannot list dependents; not cascading");
+                Ok(vec![])
+            }
+        },
+    }
+}
+
+/// `volume a, nic b` for messages.
+pub fn join(dependents: &[Dependent]) -> String {
+    dependents
+        .iter()
+        .map(|d| d.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::net_proto::OwnerReference as NicOwner;
+    use crate::zfs_proto::OwnerReference as VolumeOwner;
+
+    fn volume(name: &str, owner: Option<(&str, &str)>) -> Volume {
+        Volume {
+            name: name.to_string(),
+            owner: owner.map(|(kind, id)| VolumeOwner {
+                kind: kind.to_string(),
+                id: id.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn nic(id: &str, name: &str, owner: Option<(&str, &str)>) -> Nic {
+        Nic {
+            id: id.to_string(),
+            name: name.to_string(),
+            owner: owner.map(|(kind, id)| NicOwner {
+                kind: kind.to_string(),
+                id: id.to_string(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_owned_filters_by_kind_and_id() {
+        let volumes = vec![
+            volume("root", Some((OWNER_KIND_POD, "p1"))),
+            volume("other-pod", Some((OWNER_KIND_POD, "p2"))),
+            volume("vm-disk", Some((OWNER_KIND_VM, "p1"))),
+            volume("unowned", None),
+        ];
+        assert_eq!(
+            owned_volumes(volumes, OWNER_KIND_POD, "p1").collect::<Vec<_>>(),
+            [Dependent::Volume {
+                name: "root".to_string()
+            }]
+        );
+
+        let nics = vec![
+            nic("n1", "", Some((OWNER_KIND_VM, "v1"))),
+            nic("n2", "eth", Some((OWNER_KIND_VM, "v1"))),
+            nic("n3", "", None),
+        ];
+        let owned: Vec<_> = owned_nics(nics, OWNER_KIND_VM, "v1").collect();
+        assert_eq!(join(&owned), "nic n1, nic eth");
+    }
+
+    #[test]
+    fn test_block_refuses_while_dependents_exist() {
+        let owned = vec![Dependent::Volume {
+            name: "root".to_string(),
+        }];
+        let err = decide(
+            OWNER_KIND_POD,
+            "p1",
+            DeletePropagation::Block,
+            Ok(owned.clone()),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+        assert_eq!(err.message(), "pod p1 still owns volume root");
+
+        assert_eq!(
+            decide(OWNER_KIND_POD, "p1", DeletePropagation::Block, Ok(vec![])).unwrap(),
+            []
+        );
+        assert_eq!(
+            decide(
+                OWNER_KIND_POD,
+                "p1",
+                DeletePropagation::Cascade,
+                Ok(owned.clone())
+            )
+            .unwrap(),
+            owned
+        );
+    }
+
+    #[test]
+    fn test_unreachable_daemons() {
+        // Blocking can't tell whether there are dependents, so refuses
+        let err = decide(
+            OWNER_KIND_VM,
+            "v1",
+            DeletePropagation::Block,
+            Err(Status::unavailable("connection refused")),
+        )
+        .unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        // Cascading deletes the owner and leaves its dependents behind
+        assert_eq!(
+            decide(
+                OWNER_KIND_VM,
+                "v1",
+                DeletePropagation::Cascade,
+                Err(Status::unavailable("connection refused")),
+            )
+            .unwrap(),
+            []
+        );
+    }
+
+    #[test]
+    fn test_unset_propagation_blocks() {
+        assert_eq!(DeletePropagation::default(), DeletePropagation::Block);
+        assert_eq!(
+            DeletePropagation::try_from(0).unwrap(),
+            DeletePropagation::Block
+        );
+    }
+
+    #[tokio::test]
+    async fn test_no_daemons_no_dependents() {
+        let dependents = Dependents::default();
+        assert_eq!(dependents.list(OWNER_KIND_POD, "p1").await.unwrap(), []);
+        let stray = [Dependent::Nic {
+            id: "n1".to_string(),
+            name: String::new(),
+        }];
+        assert_eq!(dependents.delete(&stray).await, []);
+    }
+}
@@ -0,0 +1,477 @@
+//! Drift between the store and the running cloud-hypervisor processes.
+//!
+//! The store is what mvirt-vmm believes runs; the processes are what does.
+//! The two part ways when a process is started or killed behind the
+//! daemon's back, when a VM is reconfigured directly over its API socket,
+//! or when a store snapshot is imported on a host whose processes don't
+//! match it. The checker compares them on an interval: each stored VM
+//! against its process and the configuration cloud-hypervisor reports for
+//! it (`vm.info`), and each VM directory with a live API socket against the
+//! store. Its findings are served by GetDrift, and a finding appearing,
+//! changing or going away is published on the VM event bus and audit
+//! logged.
+//!
+//! The checker only reports. Whether the store or the process is right is
+//! for the operator, or the cplane's reconciler, to decide.
+//!
+//! VMs being started or stopped are skipped, and so are pod microVMs,
+//! whose pauses the pod service tracks. A VM paused for a memory dump
+//! shows as drifted until it is resumed.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use mvirt_log::{AuditLogger, LogLevel};
+use serde_json::Value;
+use tokio::time;
+use tracing::{debug, info, warn};
+
+use crate::hypervisor::Hypervisor;
+use crate::proto::{DriftFinding, DriftKind, VmConfig, VmEvent, VmEventType, VmState};
+use crate::store::{VmEntry, VmStore};
+
+/// Default check interval in seconds
+pub const DEFAULT_CHECK_INTERVAL_SECS: u64 = 60;
+
+/// How long a VM's API socket gets to answer `vm.info`
+const API_TIMEOUT: Duration = Duration::from_secs(5);
+
+const MIB: u64 = 1024 * 1024;
+
+/// Differences between a VM's stored config and the config in its
+/// `vm.info`, one line each.
+pub fn config_diffs(config: &VmConfig, info: &Value) -> Vec<String> {
+    let running = &info["config"];
+    let mut diffs = Vec::new();
+
+    let vcpus = running["cpus"]["boot_vcpus"].as_u64().unwrap_or(0);
+    if vcpus != u64::from(config.vcpus) {
+        diffs.push(format!("vcpus: stored {}, running {}", config.vcpus, vcpus));
+    }
+
+    // virtio-mem resizes change the hotplugged size, not the boot size
+    let memory = running["memory"]["size"].as_u64().unwrap_or(0)
+        + running["memory"]["hotplugged_size"].as_u64().unwrap_or(0);
+    if memory != config.memory_mb * MIB {
+        diffs.push(format!(
+            "memory: stored {} MiB, running {} MiB",
+            config.memory_mb,
+            memory / MIB
+        ));
+    }
+
+    // The cloud-init ISO is attached as one more disk
+    let disks = config.disks.len() + usize::from(config.user_data.is_some());
+    let running_disks = running["disks"].as_array().map_or(0, Vec::len);
+    if disks != running_disks {
+        diffs.push(format!(
+            "disks: stored {}, running {}",
+            disks, running_disks
+        ));
+    }
+
+    // NICs with neither a TAP nor a socket aren't passed to cloud-hypervisor
+    let nics: Vec<_> = config
+        .nics
+        .iter()
+        .filter(|nic| nic.tap.is_some() || nic.vhost_socket.is_some())
+        .collect();
+    let running_nics = running["net"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+    if nics.len() != running_nics.len() {
+        diffs.push(format!(
+            "nics: stored {}, running {}",
+            nics.len(),
+            running_nics.len()
+        ));
+    }
+    let macs: HashSet<String> = running_nics
+        .iter()
+        .filter_map(|nic| nic["mac"].as_str())
+        .map(str::to_ascii_lowercase)
+        .collect();
+    for mac in nics.iter().filter_map(|nic| nic.mac.as_deref()) {
+        if !macs.contains(&mac.to_ascii_lowercase()) {
+            diffs.push(format!("nic {}: not running", mac));
+        }
+    }
+
+    diffs
+}
+
+/// What runs on the host for a VM.
+#[derive(Debug, Default)]
+pub struct Observed {
+    /// A cloud-hypervisor process of the VM is alive
+    pub alive: bool,
+    /// `vm.info` from its API socket; `None` if nothing answered
+    pub info: Option<Value>,
+}
+
+/// The drift of a stored VM, if any.
+pub fn check_vm(entry: &VmEntry, observed: &Observed) -> Option<(DriftKind, String)> {
+    match entry.state {
+        VmState::Running => {
+            let Some(info) = &observed.info else {
+                return Some(if observed.alive {
+                    (
+                        DriftKind::Unresponsive,
+                        "cloud-hypervisor doesn't answer on its API socket".to_string(),
+                    )
+                } else {
+                    (
+                        DriftKind::ProcessMissing,
+                        "stored as running, but no cloud-hypervisor process runs".to_string(),
+                    )
+                });
+            };
+            let state = info["state"].as_str().unwrap_or("unknown");
+            if state != "Running" {
+                return Some((
+                    DriftKind::StateMismatch,
+                    format!("stored as running, cloud-hypervisor reports {}", state),
+                ));
+            }
+            let diffs = config_diffs(&entry.config, info);
+            (!diffs.is_empty()).then(|| (DriftKind::ConfigMismatch, diffs.join("; ")))
+        }
+        VmState::Stopped if observed.alive || observed.info.is_some() => Some((
+            DriftKind::UnexpectedProcess,
+            "stored as stopped, but cloud-hypervisor runs".to_string(),
+        )),
+        _ => None,
+    }
+}
+
+/// Replace `findings` with a check's `found`. A finding that persists with
+/// the same kind and detail keeps the time it was first seen. Returns the
+/// findings that are new or changed, and those that went away.
+pub fn merge(
+    findings: &mut HashMap<String, DriftFinding>,
+    found: Vec<DriftFinding>,
+) -> (Vec<DriftFinding>, Vec<DriftFinding>) {
+    let mut previous = std::mem::take(findings);
+    let mut detected = Vec::new();
+    for mut finding in found {
+        match previous.remove(&finding.vm_id) {
+            Some(old) if old.kind == finding.kind && old.detail == finding.detail => {
+                finding.since = old.since;
+            }
+            _ => detected.push(finding.clone()),
+        }
+        findings.insert(finding.vm_id.clone(), finding);
+    }
+    (detected, previous.into_values().collect())
+}
+
+#[derive(Default)]
+struct Findings {
+    by_vm: HashMap<String, DriftFinding>,
+    checked_at: i64,
+}
+
+/// Compares the store against the running processes and keeps the last
+/// check's findings.
+pub struct DriftChecker {
+    store: Arc<VmStore>,
+    hypervisor: Arc<Hypervisor>,
+    audit: Arc<AuditLogger>,
+    /// Held for a whole check, so checks don't overlap
+    checking: tokio::sync::Mutex<()>,
+    findings: Mutex<Findings>,
+}
+
+impl DriftChecker {
+    pub fn new(store: Arc<VmStore>, hypervisor: Arc<Hypervisor>, audit: Arc<AuditLogger>) -> Self {
+        Self {
+            store,
+            hypervisor,
+            audit,
+            checking: tokio::sync::Mutex::new(()),
+            findings: Mutex::new(Findings::default()),
+        }
+    }
+
+    /// The last check's findings, ordered by VM, and when it ran (0 before
+    /// the first check).
+    pub fn findings(&self) -> (Vec<DriftFinding>, i64) {
+        let findings = self.findings.lock().unwrap();
+        (sorted(&findings.by_vm), findings.checked_at)
+    }
+
+    /// Check now; returns the findings and the time of the check.
+    pub async fn check(&self) -> Result<(Vec<DriftFinding>, i64)> {
+        let _checking = self.checking.lock().await;
+        let now = chrono::Utc::now().timestamp();
+
+        let vms = self.store.list().await?;
+        let stored: HashSet<String> = self
+            .store
+            .list_all()
+            .await?
+            .into_iter()
+            .map(|vm| vm.id)
+            .collect();
+
+        let mut found = Vec::new();
+        for vm in &vms {
+            if let Some((kind, detail)) = check_vm(vm, &self.observe(&vm.id).await) {
+                found.push(finding(&vm.id, kind, detail, now));
+            }
+        }
+        for id in self.hypervisor.vm_dir_ids().await {
+            if !stored.contains(&id) && self.observe(&id).await.info.is_some() {
+                let detail = "cloud-hypervisor runs for a VM that isn't stored".to_string();
+                found.push(finding(&id, DriftKind::UnknownProcess, detail, now));
+            }
+        }
+        debug!(vms = vms.len(), findings = found.len(), "Drift check");
+
+        let (detected, resolved, current) = {
+            let mut findings = self.findings.lock().unwrap();
+            let (detected, resolved) = merge(&mut findings.by_vm, found);
+            findings.checked_at = now;
+            (detected, resolved, sorted(&findings.by_vm))
+        };
+        let entries: HashMap<&str, &VmEntry> = vms.iter().map(|vm| (vm.id.as_str(), vm)).collect();
+        for finding in detected {
+            warn!(vm_id = %finding.vm_id, kind = ?finding.kind(), detail = %finding.detail, "VM drifted from the store");
+            self.audit
+                .log(
+                    LogLevel::Warn,
+                    format!("VM drifted from the store: {}", finding.detail),
+                    vec![finding.vm_id.clone()],
+                )
+                .await;
+            self.publish(VmEventType::VmEventDriftDetected, finding, &entries);
+        }
+        for finding in resolved {
+            info!(vm_id = %finding.vm_id, kind = ?finding.kind(), "VM drift resolved");
+            self.audit
+                .log(
+                    LogLevel::Info,
+                    format!("VM drift resolved: {}", finding.detail),
+                    vec![finding.vm_id.clone()],
+                )
+                .await;
+            self.publish(VmEventType::VmEventDriftResolved, finding, &entries);
+        }
+
+        Ok((current, now))
+    }
+
+    async fn observe(&self, vm_id: &str) -> Observed {
+        Observed {
+            alive: self.hypervisor.is_running(vm_id).await,
+            info: time::timeout(API_TIMEOUT, self.hypervisor.vm_info(vm_id))
+                .await
+                .ok()
+                .and_then(Result::ok),
+        }
+    }
+
+    fn publish(&self, ty: VmEventType, finding: DriftFinding, entries: &HashMap<&str, &VmEntry>) {
+        let _ = self.hypervisor.events_tx().send(VmEvent {
+            vm_id: finding.vm_id.clone(),
+            r#type: ty as i32,
+            timestamp: chrono::Utc::now().timestamp(),
+            vm: entries.get(finding.vm_id.as_str()).map(|vm| vm.to_proto()),
+            drift: Some(finding),
+        });
+    }
+}
+
+fn finding(vm_id: &str, kind: DriftKind, detail: String, now: i64) -> DriftFinding {
+    DriftFinding {
+        vm_id: vm_id.to_string(),
+        kind: kind as i32,
+        detail,
+        since: now,
+    }
+}
+
+fn sorted(findings: &HashMap<String, DriftFinding>) -> Vec<DriftFinding> {
+    let mut findings: Vec<_> = findings.values().cloned().collect();
+    findings.sort_by(|a, b| a.vm_id.cmp(&b.vm_id));
+    findings
+}
+
+/// Periodic drift check.
+pub struct DriftMonitor {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl DriftMonitor {
+    /// Start a new monitor task.
+    pub fn start(checker: Arc<DriftChecker>, interval: Duration) -> Self {
+        info!(interval = ?interval, "Drift monitor started");
+
+        let task = tokio::spawn(check_loop(checker, interval));
+        Self { task }
+    }
+
+    /// Stop the monitor task.
+    pub fn stop(self) {
+        self.task.abort();
+        info!("Drift monitor stopped");
+    }
+}
+
+/// Main monitor loop.
+async fn check_loop(checker: Arc<DriftChecker>, interval: Duration) {
+    let mut interval = time::interval(interval);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = checker.check().await {
+            warn!(error = %e, "Drift check failed");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::{DiskConfig, NicConfig};
+    use serde_json::json;
+
+    fn entry(state: VmState) -> VmEntry {
+        VmEntry {
+            id: "vm-1".to_string(),
+            name: None,
+            state,
+            config: VmConfig {
+                vcpus: 2,
+                memory_mb: 1024,
+                disks: vec![DiskConfig {
+                    path: "/dev/zvol/pool/vm-1".to_string(),
+                    ..Default::default()
+                }],
+                nics: vec![NicConfig {
+                    tap: Some("tap_1234567".to_string()),
+                    mac: Some("52:54:00:AB:CD:EF".to_string()),
+                    ..Default::default()
+                }],
+                user_data: Some("#cloud-config".to_string()),
+                ..Default::default()
+            },
+            created_at: 0,
+            started_at: None,
+            start_timing: None,
+        }
+    }
+
+    fn info(state: &str, vcpus: u64) -> Value {
+        json!({
+            "state": state,
+            "config": {
+                "cpus": {"boot_vcpus": vcpus},
+                "memory": {"size": 1024 * MIB},
+                "disks": [{"path": "/dev/zvol/pool/vm-1"}, {"path": "cloudinit.iso"}],
+                "net": [{"tap": "tap_1234567", "mac": "52:54:00:ab:cd:ef"}]
+            }
+        })
+    }
+
+    fn observed(alive: bool, info: Option<Value>) -> Observed {
+        Observed { alive, info }
+    }
+
+    fn kind(entry: &VmEntry, observed: &Observed) -> Option<DriftKind> {
+        check_vm(entry, observed).map(|(kind, _)| kind)
+    }
+
+    #[test]
+    fn test_matching_vm_has_no_drift() {
+        let running = entry(VmState::Running);
+        assert_eq!(
+            kind(&running, &observed(true, Some(info("Running", 2)))),
+            None
+        );
+        assert_eq!(kind(&entry(VmState::Stopped), &observed(false, None)), None);
+        // Mid-transition VMs aren't judged
+        assert_eq!(
+            kind(&entry(VmState::Stopping), &observed(false, None)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_process_drift() {
+        let running = entry(VmState::Running);
+        assert_eq!(
+            kind(&running, &observed(false, None)),
+            Some(DriftKind::ProcessMissing)
+        );
+        assert_eq!(
+            kind(&running, &observed(true, None)),
+            Some(DriftKind::Unresponsive)
+        );
+        assert_eq!(
+            kind(&running, &observed(true, Some(info("Paused", 2)))),
+            Some(DriftKind::StateMismatch)
+        );
+        assert_eq!(
+            kind(
+                &entry(VmState::Stopped),
+                &observed(false, Some(info("Running", 2)))
+            ),
+            Some(DriftKind::UnexpectedProcess)
+        );
+    }
+
+    #[test]
+    fn test_config_drift() {
+        let running = entry(VmState::Running);
+        let mut reported = info("Running", 4);
+        reported["config"]["net"][0]["mac"] = json!("52:54:00:00:00:01");
+        let (kind, detail) = check_vm(&running, &observed(true, Some(reported))).unwrap();
+        assert_eq!(kind, DriftKind::ConfigMismatch);
+        assert_eq!(
+            detail,
+            "vcpus: stored 2, running 4; nic 52:54:00:AB:CD:EF: not running"
+        );
+
+        let mut config = running.config.clone();
+        config.user_data = None;
+        assert_eq!(
+            config_diffs(&config, &info("Running", 2)),
+            vec!["disks: stored 1, running 2"]
+        );
+
+        // Grown with virtio-mem
+        let mut config = running.config.clone();
+        config.memory_mb = 1536;
+        let mut reported = info("Running", 2);
+        reported["config"]["memory"]["hotplugged_size"] = json!(512 * MIB);
+        assert!(config_diffs(&config, &reported).is_empty());
+    }
+
+    #[test]
+    fn test_merge_keeps_first_seen() {
+        let mut findings = HashMap::new();
+        let missing = |since| finding("vm-1", DriftKind::ProcessMissing, "gone".into(), since);
+
+        let (detected, resolved) = merge(&mut findings, vec![missing(10)]);
+        assert_eq!(detected, vec![missing(10)]);
+        assert!(resolved.is_empty());
+
+        // Seen again: not new, and still dated from the first check
+        let (detected, _) = merge(&mut findings, vec![missing(20)]);
+        assert!(detected.is_empty());
+        assert_eq!(findings["vm-1"].since, 10);
+
+        // A different finding for the VM counts as new
+        let paused = finding("vm-1", DriftKind::StateMismatch, "paused".into(), 30);
+        let (detected, resolved) = merge(&mut findings, vec![paused.clone()]);
+        assert_eq!(detected, vec![paused]);
+        assert!(resolved.is_empty());
+
+        let (detected, resolved) = merge(&mut findings, Vec::new());
+        assert!(detected.is_empty());
+        assert_eq!(resolved.len(), 1);
+        assert!(findings.is_empty());
+    }
+}
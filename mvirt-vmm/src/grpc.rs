@@ -1,20 +1,53 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Duration;
 
+use anyhow::anyhow;
+use mvirt_common::jobs::{JobHandle, JobRegistry};
+use mvirt_common::naming;
 use mvirt_log::{AuditLogger, LogLevel};
-use tokio::fs::File;
-use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
-use tokio::net::UnixStream;
-use tokio::sync::mpsc;
+use mvirt_one::proto::{
+    RunCommandRequest as OneRunCommandRequest, one_service_client::OneServiceClient,
+};
+use tokio::net::UnixListener;
+use tokio::sync::{broadcast, mpsc};
 use tokio_stream::StreamExt;
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Request, Response, Status};
-use tracing::{error, info};
-
-use crate::hypervisor::Hypervisor;
+use tonic::transport::Channel;
+use tonic::{Code, Request, Response, Status};
+use tracing::{error, info, warn};
+
+use crate::console::{self, ConsoleEvent, ConsoleHub, SessionControl};
+use crate::dependents::{self, Dependents, OWNER_KIND_VM};
+use crate::drift::DriftChecker;
+use crate::guest_agent::GuestAgents;
+use crate::hypervisor::{self, Hypervisor};
+use crate::memory_dump;
+use crate::migration;
+use crate::peer;
 use crate::proto::vm_service_server::VmService;
 use crate::proto::*;
-use crate::store::VmStore;
+use crate::remote_disk::{self, RemoteTarget};
+use crate::resize::{self, NodeCapacity};
+use crate::snapshot::{self, SnapshotMeta, SnapshotStore};
+use crate::start_timing::{self, StartTimer};
+use crate::store::{self, STATE_VERSION, VmStore};
+use crate::zfs_proto::zfs_service_client::ZfsServiceClient;
+use crate::zfs_proto::{
+    CreateSnapshotRequest, DeleteSnapshotRequest, ListVolumesRequest, RollbackSnapshotRequest,
+};
+
+/// Job kind of a VM start; DeleteVm looks for it to cancel the start.
+pub const VM_START_JOB: &str = "vm-start";
+
+/// Job kinds of the two sides of a live migration.
+const MIGRATION_SEND_JOB: &str = "migration-send";
+const MIGRATION_RECEIVE_JOB: &str = "migration-receive";
+
+/// Job kind of a VM snapshot. Restores run as VM starts.
+const VM_SNAPSHOT_JOB: &str = "vm-snapshot";
 
 pub struct VmServiceImpl {
     store: Arc<VmStore>,
@@ -24,6 +57,30 @@ pub struct VmServiceImpl {
     /// Mutator paths (create/start/stop/delete) and the hypervisor's
     /// child-watch task publish here.
     events: tokio::sync::broadcast::Sender<VmEvent>,
+    /// Shared console connections; one per VM, fanned out to all viewers.
+    console: Arc<ConsoleHub>,
+    /// Volumes and NICs owned by VMs, for cascading deletes.
+    dependents: Dependents,
+    /// Serve guest memory dumps and introspection.
+    allow_memory_dump: bool,
+    /// Local users allowed to use them.
+    memory_dump_uids: Vec<u32>,
+    /// Drift between the store and the processes; GetDrift is unavailable
+    /// without it.
+    drift: Option<Arc<DriftChecker>>,
+    /// Long-running operations, served by ListJobs/GetJob/CancelJob
+    jobs: JobRegistry,
+    /// Memory and device state of VM snapshots.
+    snapshots: SnapshotStore,
+    /// mvirt-zfs, for the volume snapshots of VM snapshots; VMs with
+    /// writable disks can't be snapshotted without it.
+    zfs: Option<ZfsServiceClient<Channel>>,
+    /// mvirt-node's samples of the host, for checking resizes; unchecked
+    /// without it.
+    node_resources: Option<PathBuf>,
+    /// Agents of VMs started with `guest_agent`, for GuestReady and
+    /// RunGuestCommand.
+    guest_agents: GuestAgents,
 }
 
 impl VmServiceImpl {
@@ -32,25 +89,403 @@ impl VmServiceImpl {
         hypervisor: Arc<Hypervisor>,
         audit: Arc<AuditLogger>,
         events: tokio::sync::broadcast::Sender<VmEvent>,
+        console: Arc<ConsoleHub>,
+        dependents: Dependents,
     ) -> Self {
+        let snapshots = SnapshotStore::new(hypervisor.data_dir());
         Self {
             store,
             hypervisor,
+            console,
             audit,
             events,
+            dependents,
+            allow_memory_dump: false,
+            memory_dump_uids: Vec::new(),
+            drift: None,
+            jobs: JobRegistry::default(),
+            snapshots,
+            zfs: None,
+            node_resources: None,
+            guest_agents: GuestAgents::default(),
+        }
+    }
+
+    /// Snapshot VM volumes through the mvirt-zfs at `zfs`.
+    pub fn with_volumes(mut self, zfs: Option<Channel>) -> Self {
+        self.zfs = zfs.map(ZfsServiceClient::new);
+        self
+    }
+
+    /// Check VM resizes against the host resources mvirt-node writes to
+    /// `path`.
+    pub fn with_node_resources(mut self, path: Option<PathBuf>) -> Self {
+        self.node_resources = path;
+        self
+    }
+
+    /// Serve DumpGuestMemory and GetGuestMemoryInfo to callers running on
+    /// this host as one of `uids`. Off by default: a dump holds everything
+    /// in the guest, keys and passwords included.
+    pub fn with_memory_dump(mut self, allowed: bool, uids: Vec<u32>) -> Self {
+        self.allow_memory_dump = allowed;
+        self.memory_dump_uids = uids;
+        self
+    }
+
+    /// Jobs of this service, to share with the pod service so pod starts
+    /// are listed and cancelled alongside VM starts.
+    pub fn jobs(&self) -> &JobRegistry {
+        &self.jobs
+    }
+
+    /// Serve GetDrift from `checker`.
+    pub fn with_drift(mut self, checker: Arc<DriftChecker>) -> Self {
+        self.drift = Some(checker);
+        self
+    }
+
+    /// A VM as reported to clients, with its place in the start queue and
+    /// whether its guest agent is ready.
+    fn vm_status(&self, entry: &store::VmEntry) -> Vm {
+        Vm {
+            start_queue_position: self.hypervisor.start_queue_position(&entry.id),
+            guest_ready: entry.state == VmState::Running && self.guest_agents.is_ready(&entry.id),
+            ..entry.to_proto()
+        }
+    }
+
+    /// A running VM, for the memory debugging RPCs of `caller` (see
+    /// [`peer::caller_uid`]).
+    async fn memory_debug_target(
+        &self,
+        caller: Option<u32>,
+        vm_id: &str,
+    ) -> Result<store::VmEntry, Status> {
+        if !self.allow_memory_dump {
+            return Err(Status::permission_denied(
+                "Guest memory access is disabled; start mvirt-vmm with --allow-memory-dump",
+            ));
+        }
+        if !memory_dump::may_access(&self.memory_dump_uids, caller) {
+            warn!(vm_id = %vm_id, caller = ?caller, "Refused guest memory access");
+            return Err(Status::permission_denied(match caller {
+                Some(uid) => format!("uid {} may not access guest memory", uid),
+                None => "Guest memory access needs a caller on this host".to_string(),
+            }));
+        }
+        let entry = self
+            .store
+            .get(vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", vm_id)))?;
+        if entry.state != VmState::Running {
+            return Err(Status::failed_precondition("VM is not running"));
         }
+        Ok(entry)
     }
 
     /// Publish a lifecycle event. Errors are intentionally ignored — a
     /// no-subscriber broadcast just discards.
     fn publish_vm_event(&self, vm_id: &str, ty: VmEventType, vm: Option<Vm>) {
-        let _ = self.events.send(VmEvent {
+        let _ = self.events.send(vm_event(vm_id, ty, vm));
+    }
+
+    fn zfs(&self) -> anyhow::Result<ZfsServiceClient<Channel>> {
+        self.zfs
+            .clone()
+            .ok_or_else(|| anyhow!("mvirt-zfs is not configured (--zfs-server)"))
+    }
+
+    /// mvirt-zfs's volumes as (name, device path); none without mvirt-zfs.
+    async fn zfs_volumes(&self) -> Result<Vec<(String, String)>, Status> {
+        let Some(zfs) = &self.zfs else {
+            return Ok(Vec::new());
+        };
+        let volumes = zfs
+            .clone()
+            .list_volumes(ListVolumesRequest {})
+            .await
+            .map_err(|s| Status::unavailable(format!("mvirt-zfs: {}", s.message())))?
+            .into_inner()
+            .volumes;
+        Ok(volumes.into_iter().map(|v| (v.name, v.path)).collect())
+    }
+
+    /// Pause the VM, write its state, snapshot its volumes and let it run
+    /// again. Volume snapshots already taken are deleted on error.
+    async fn take_snapshot(
+        &self,
+        vm_id: &str,
+        name: &str,
+        volumes: &[String],
+        job: &JobHandle,
+    ) -> anyhow::Result<SnapshotMeta> {
+        let was_paused = self.hypervisor.is_paused(vm_id).await?;
+        if !was_paused {
+            job.set_phase("pausing");
+            self.hypervisor.pause(vm_id).await?;
+        }
+        let result = async {
+            job.set_phase("writing state");
+            self.hypervisor
+                .snapshot(vm_id, &self.snapshots.state_dir(vm_id, name))
+                .await?;
+            job.set_phase("snapshotting volumes");
+            for (i, volume) in volumes.iter().enumerate() {
+                let request = CreateSnapshotRequest {
+                    volume_name: volume.clone(),
+                    snapshot_name: name.to_string(),
+                };
+                let created = match self.zfs() {
+                    Ok(mut zfs) => zfs
+                        .create_snapshot(request)
+                        .await
+                        .map_err(|s| anyhow!("snapshot of volume {}: {}", volume, s.message())),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = created {
+                    self.delete_volume_snapshots(&volumes[..i], name).await;
+                    return Err(e);
+                }
+            }
+            Ok(())
+        }
+        .await;
+        if !was_paused && let Err(e) = self.hypervisor.resume(vm_id).await {
+            error!(vm_id = %vm_id, error = %e, "Failed to resume VM after snapshot");
+        }
+        result?;
+        Ok(SnapshotMeta {
+            name: name.to_string(),
+            created_at: chrono::Utc::now().timestamp(),
+            volumes: volumes.to_vec(),
+        })
+    }
+
+    /// Delete the volume snapshots `name` of `volumes`, as far as they exist.
+    async fn delete_volume_snapshots(&self, volumes: &[String], name: &str) {
+        let Ok(zfs) = self.zfs() else {
+            return;
+        };
+        for volume in volumes {
+            let request = DeleteSnapshotRequest {
+                volume_name: volume.clone(),
+                snapshot_name: name.to_string(),
+            };
+            match zfs.clone().delete_snapshot(request).await {
+                Err(s) if s.code() != Code::NotFound => {
+                    warn!(volume = %volume, snapshot = %name, error = %s.message(), "Failed to delete volume snapshot");
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Delete a VM snapshot with its volume snapshots.
+    async fn drop_snapshot(&self, vm_id: &str, meta: &SnapshotMeta) -> anyhow::Result<()> {
+        self.delete_volume_snapshots(&meta.volumes, &meta.name)
+            .await;
+        self.snapshots.remove(vm_id, &meta.name).await
+    }
+
+    /// Roll the volumes back to the snapshot and bring the VM up from its
+    /// state.
+    async fn restore_snapshot(
+        &self,
+        entry: &store::VmEntry,
+        meta: &SnapshotMeta,
+        job: &JobHandle,
+    ) -> anyhow::Result<()> {
+        job.set_phase("rolling back volumes");
+        for volume in &meta.volumes {
+            self.zfs()?
+                .rollback_snapshot(RollbackSnapshotRequest {
+                    volume_name: volume.clone(),
+                    snapshot_name: meta.name.clone(),
+                })
+                .await
+                .map_err(|s| anyhow!("rollback of volume {}: {}", volume, s.message()))?;
+        }
+        job.set_phase("restoring");
+        self.hypervisor
+            .spawn_empty(&entry.id, entry.name.as_deref(), &entry.config)
+            .await?;
+        self.hypervisor
+            .restore(
+                &entry.id,
+                &entry.config,
+                &self.snapshots.state_dir(&entry.id, &meta.name),
+            )
+            .await
+    }
+
+    /// A VM whose devices can change: running, or stopped.
+    async fn hotplug_target(&self, vm_id: &str) -> Result<store::VmEntry, Status> {
+        let entry = self
+            .store
+            .get(vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", vm_id)))?;
+        if !matches!(entry.state, VmState::Running | VmState::Stopped) {
+            return Err(Status::failed_precondition(
+                "VM is starting or stopping; change it once it is running or stopped",
+            ));
+        }
+        // Both carry the config over as it was when they began
+        if self.jobs.find_running(VM_SNAPSHOT_JOB, &entry.id).is_some()
+            || self
+                .jobs
+                .find_running(MIGRATION_SEND_JOB, &entry.id)
+                .is_some()
+        {
+            return Err(Status::failed_precondition(
+                "VM is being snapshotted or migrated",
+            ));
+        }
+        Ok(entry)
+    }
+
+    /// Store the config of a hot-plug and report it.
+    async fn save_hotplug(
+        &self,
+        entry: &store::VmEntry,
+        config: VmConfig,
+        what: String,
+    ) -> Result<Vm, Status> {
+        let updated = self
+            .store
+            .update_config(&entry.id, &config)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", entry.id)))?;
+        info!(id = %entry.id, "{}", what);
+        let vm = self.vm_status(&updated);
+        self.publish_vm_event(&entry.id, VmEventType::VmEventUpdated, Some(vm.clone()));
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "VM {}, {}",
+                    entry.name.as_deref().unwrap_or(&entry.id),
+                    what
+                ),
+                vec![entry.id.clone()],
+            )
+            .await;
+        Ok(vm)
+    }
+
+    /// Remove the NIC at `index` of a hot-plug target's config, unplugging
+    /// it first if the VM runs.
+    async fn unplug_nic(
+        &self,
+        entry: &store::VmEntry,
+        index: usize,
+        what: &str,
+    ) -> Result<Vm, Status> {
+        let mut config = entry.config.clone();
+        let removed = config.nics.remove(index);
+
+        if entry.state == VmState::Running {
+            self.hypervisor
+                .remove_nic(&entry.id, &removed)
+                .await
+                .map_err(|e| Status::internal(format!("Failed to unplug NIC: {}", e)))?;
+        }
+
+        self.save_hotplug(entry, config, format!("{}: {}", what, nic_label(&removed)))
+            .await
+    }
+
+    /// Detach a NIC its network daemon deleted from every VM that has it,
+    /// so running guests lose the dead device and stopped VMs don't fail to
+    /// boot on the missing backend. `socket` is the backend in the form of
+    /// [`NicConfig::vhost_socket`].
+    pub async fn detach_deleted_nic(&self, mac: &str, socket: &str) {
+        let deleted = NicConfig {
+            tap: None,
+            mac: (!mac.is_empty()).then(|| mac.to_string()),
+            vhost_socket: (!socket.is_empty()).then(|| socket.to_string()),
+        };
+        let entries = match self.store.list_all().await {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!(error = %e, "Failed to list VMs for deleted NIC");
+                return;
+            }
+        };
+        for entry in entries {
+            if !entry
+                .config
+                .nics
+                .iter()
+                .any(|n| hypervisor::same_nic(n, &deleted))
+            {
+                continue;
+            }
+            // Re-read under the hot-plug rules; a VM that is starting or
+            // stopping keeps the NIC until the cplane detaches it
+            let result = match self.hotplug_target(&entry.id).await {
+                Ok(entry) => match entry
+                    .config
+                    .nics
+                    .iter()
+                    .position(|n| hypervisor::same_nic(n, &deleted))
+                {
+                    Some(index) => self
+                        .unplug_nic(&entry, index, "deleted NIC detached")
+                        .await
+                        .map(|_| ()),
+                    None => Ok(()),
+                },
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                warn!(id = %entry.id, mac = %mac, socket = %socket, error = %e.message(), "Failed to detach deleted NIC");
+            }
+        }
+    }
+
+    async fn snapshot_to_proto(&self, vm_id: &str, meta: SnapshotMeta) -> VmSnapshot {
+        VmSnapshot {
             vm_id: vm_id.to_string(),
-            r#type: ty as i32,
-            timestamp: chrono::Utc::now().timestamp(),
-            vm,
-        });
+            state_bytes: self.snapshots.size(vm_id, &meta.name).await,
+            name: meta.name,
+            created_at: meta.created_at,
+            volumes: meta.volumes,
+        }
+    }
+}
+
+fn vm_event(vm_id: &str, ty: VmEventType, vm: Option<Vm>) -> VmEvent {
+    VmEvent {
+        vm_id: vm_id.to_string(),
+        r#type: ty as i32,
+        timestamp: chrono::Utc::now().timestamp(),
+        vm,
+        drift: None,
+    }
+}
+
+/// How a NIC shows up in logs: its MAC, else its TAP or socket.
+fn nic_label(nic: &NicConfig) -> &str {
+    nic.mac
+        .as_deref()
+        .or(nic.tap.as_deref())
+        .or(nic.vhost_socket.as_deref())
+        .unwrap_or("-")
+}
+
+/// Remove what a failed incoming migration left: the process, the
+/// connected disks and the VM itself.
+async fn abandon_migration(hypervisor: &Hypervisor, store: &VmStore, vm_id: &str) {
+    if let Err(e) = hypervisor.kill(vm_id).await {
+        warn!(vm_id = %vm_id, error = %e, "Failed to clean up after a migration");
     }
+    let _ = store.delete(vm_id).await;
 }
 
 #[tonic::async_trait]
@@ -145,6 +580,19 @@ impl VmService for VmServiceImpl {
                     ));
                 }
             }
+            BootMode::Network => {
+                if config.nics.is_empty() {
+                    return Err(Status::invalid_argument(
+                        "Network boot mode requires at least one NIC",
+                    ));
+                }
+            }
+        }
+
+        naming::validate_optional_name("VM", req.name.as_deref())?;
+        validate_labels(&config.labels).map_err(Status::invalid_argument)?;
+        for disk in &config.disks {
+            RemoteTarget::parse(&disk.path).map_err(|e| Status::invalid_argument(e.to_string()))?;
         }
 
         info!(id = ?req.id, name = ?req.name, vcpus = config.vcpus, memory_mb = config.memory_mb, boot_mode = ?boot_mode, "Creating VM");
@@ -183,7 +631,7 @@ impl VmService for VmServiceImpl {
             .await
             .map_err(|e| Status::internal(e.to_string()))?
             .ok_or_else(|| Status::not_found(format!("VM {} not found", req.id)))?;
-        Ok(Response::new(entry.to_proto()))
+        Ok(Response::new(self.vm_status(&entry)))
     }
 
     async fn list_vms(
@@ -195,7 +643,7 @@ impl VmService for VmServiceImpl {
             .list()
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
-        let vms = entries.into_iter().map(|e| e.to_proto()).collect();
+        let vms = entries.iter().map(|e| self.vm_status(e)).collect();
         Ok(Response::new(ListVmsResponse { vms }))
     }
 
@@ -204,7 +652,8 @@ impl VmService for VmServiceImpl {
         request: Request<DeleteVmRequest>,
     ) -> Result<Response<DeleteVmResponse>, Status> {
         let req = request.into_inner();
-        info!(id = %req.id, "Deleting VM");
+        let propagation = DeletePropagation::try_from(req.propagation).unwrap_or_default();
+        info!(id = %req.id, force = req.force, ?propagation, "Deleting VM");
 
         let entry = self
             .store
@@ -213,24 +662,59 @@ impl VmService for VmServiceImpl {
             .map_err(|e| Status::internal(e.to_string()))?
             .ok_or_else(|| Status::not_found(format!("VM {} not found", req.id)))?;
 
-        if entry.state == VmState::Running {
+        let starting = self.jobs.find_running(VM_START_JOB, &req.id);
+        if starting.is_some() && !req.force {
+            return Err(Status::failed_precondition(
+                "VM is starting. Use force=true to cancel the start and delete it",
+            ));
+        }
+        if entry.state == VmState::Running && !req.force {
             return Err(Status::failed_precondition("Cannot delete running VM"));
         }
 
+        let owned = self
+            .dependents
+            .check(OWNER_KIND_VM, &req.id, propagation)
+            .await?;
+
+        // The start rolls itself back; only then is the VM safe to remove
+        if let Some(start) = starting {
+            info!(id = %req.id, job = %start.id, "Cancelling VM start");
+            let _ = self.jobs.cancel(&start.id);
+            self.jobs.wait_finished(&start.id).await;
+        }
+        if self.hypervisor.is_running(&req.id).await
+            && let Err(e) = self.hypervisor.kill(&req.id).await
+        {
+            error!(id = %req.id, error = %e, "Failed to kill VM before deleting it");
+            return Err(Status::internal(format!("Failed to kill VM: {}", e)));
+        }
+
         self.store
             .delete(&req.id)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
+        self.guest_agents.forget(&req.id);
+
+        // Snapshots can't be restored without their VM
+        for meta in self.snapshots.list(&req.id).await.unwrap_or_default() {
+            let _ = self.drop_snapshot(&req.id, &meta).await;
+        }
+        if let Err(e) = self.snapshots.remove_all(&req.id).await {
+            warn!(id = %req.id, error = %e, "Failed to remove VM snapshots");
+        }
+
+        let deleted = self.dependents.delete(&owned).await;
 
-        info!(id = %req.id, "VM deleted");
+        info!(id = %req.id, dependents = deleted.len(), "VM deleted");
         self.publish_vm_event(&req.id, VmEventType::VmEventDeleted, None);
-        self.audit
-            .log(
-                LogLevel::Audit,
-                format!("VM deleted: {}", entry.name.as_deref().unwrap_or(&req.id)),
-                vec![req.id],
-            )
-            .await;
+        let name = entry.name.as_deref().unwrap_or(&req.id);
+        let message = if deleted.is_empty() {
+            format!("VM deleted: {}", name)
+        } else {
+            format!("VM deleted: {} (with {})", name, dependents::join(&deleted))
+        };
+        self.audit.log(LogLevel::Audit, message, vec![req.id]).await;
         Ok(Response::new(DeleteVmResponse {}))
     }
 
@@ -239,6 +723,7 @@ impl VmService for VmServiceImpl {
     async fn start_vm(&self, request: Request<StartVmRequest>) -> Result<Response<Vm>, Status> {
         let req = request.into_inner();
         info!(id = %req.id, "Starting VM");
+        let mut timer = StartTimer::new(req.prepare_stages);
 
         let entry = self
             .store
@@ -251,20 +736,62 @@ impl VmService for VmServiceImpl {
             return Err(Status::failed_precondition("VM is already running"));
         }
 
+        if self.jobs.find_running(VM_START_JOB, &req.id).is_some() {
+            return Err(Status::failed_precondition("VM is already starting"));
+        }
+        let job = self.jobs.start(VM_START_JOB, &req.id, true);
+
         // Update state to starting
         self.store
             .update_state(&req.id, VmState::Starting)
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
 
-        // Start the VM via hypervisor (normal VMs don't use vsock)
+        // Only VMs with a guest agent get vsock; its ready signal is
+        // listened for before the VM can send it
+        let agent = if entry.config.guest_agent {
+            match self.guest_agents.prepare(&self.hypervisor, &req.id).await {
+                Ok(agent) => Some(agent),
+                Err(e) => {
+                    let _ = self.store.update_state(&req.id, VmState::Stopped).await;
+                    job.fail(&e);
+                    return Err(Status::internal(format!(
+                        "Failed to listen for the guest agent: {}",
+                        e
+                    )));
+                }
+            }
+        } else {
+            None
+        };
+
+        // Start the VM via hypervisor
         if let Err(e) = self
             .hypervisor
-            .start(&req.id, entry.name.as_deref(), &entry.config, None)
+            .start(
+                &req.id,
+                entry.name.as_deref(),
+                &entry.config,
+                agent.as_ref().map(|a| a.cid),
+                &job,
+                &mut timer,
+            )
             .await
         {
             // Revert state on failure
             let _ = self.store.update_state(&req.id, VmState::Stopped).await;
+            if job.is_cancelled() {
+                job.finish_cancelled();
+                self.audit
+                    .log(
+                        LogLevel::Audit,
+                        "VM start cancelled".to_string(),
+                        vec![req.id.clone()],
+                    )
+                    .await;
+                return Err(Status::cancelled("VM start cancelled"));
+            }
+            job.fail(&e);
             self.audit
                 .log(
                     LogLevel::Error,
@@ -275,6 +802,15 @@ impl VmService for VmServiceImpl {
             return Err(Status::internal(format!("Failed to start VM: {}", e)));
         }
 
+        let timing = timer.finish();
+        if let Err(e) = self
+            .store
+            .record_start(&req.id, start_timing::KIND_VM, &timing)
+            .await
+        {
+            warn!(id = %req.id, error = %e, "Failed to record VM start timing");
+        }
+
         // Update state to running
         let entry = self
             .store
@@ -282,6 +818,10 @@ impl VmService for VmServiceImpl {
             .await
             .map_err(|e| Status::internal(e.to_string()))?
             .ok_or_else(|| Status::internal("Failed to update VM state"))?;
+        job.complete();
+        if let Some(agent) = agent {
+            self.guest_agents.wait_for(&req.id, agent);
+        }
 
         info!(id = %req.id, "VM started");
         let proto = entry.to_proto();
@@ -319,6 +859,8 @@ impl VmService for VmServiceImpl {
             .map_err(|e| Status::internal(e.to_string()))?
             .ok_or_else(|| Status::internal("Failed to update VM state"))?;
 
+        self.guest_agents.forget(&req.id);
+
         // Spawn background task to stop the VM
         let hypervisor = Arc::clone(&self.hypervisor);
         let store = Arc::clone(&self.store);
@@ -372,6 +914,8 @@ impl VmService for VmServiceImpl {
             entry
         };
 
+        self.guest_agents.forget(&req.id);
+
         // Spawn background task to kill the VM
         let hypervisor = Arc::clone(&self.hypervisor);
         let store = Arc::clone(&self.store);
@@ -398,182 +942,1243 @@ impl VmService for VmServiceImpl {
         Ok(Response::new(entry.to_proto()))
     }
 
-    // Hot-plug (Phase 2 - stubs)
-
-    async fn attach_disk(
-        &self,
-        _request: Request<AttachDiskRequest>,
-    ) -> Result<Response<Vm>, Status> {
-        Err(Status::unimplemented("AttachDisk not yet implemented"))
-    }
-
-    async fn detach_disk(
-        &self,
-        _request: Request<DetachDiskRequest>,
-    ) -> Result<Response<Vm>, Status> {
-        Err(Status::unimplemented("DetachDisk not yet implemented"))
-    }
-
-    async fn attach_nic(
-        &self,
-        _request: Request<AttachNicRequest>,
-    ) -> Result<Response<Vm>, Status> {
-        Err(Status::unimplemented("AttachNic not yet implemented"))
-    }
+    // Snapshots
 
-    async fn detach_nic(
+    async fn snapshot_vm(
         &self,
-        _request: Request<DetachNicRequest>,
-    ) -> Result<Response<Vm>, Status> {
-        Err(Status::unimplemented("DetachNic not yet implemented"))
-    }
-
-    // Console
-
-    type ConsoleStream = ReceiverStream<Result<ConsoleOutput, Status>>;
+        request: Request<SnapshotVmRequest>,
+    ) -> Result<Response<VmSnapshot>, Status> {
+        let req = request.into_inner();
+        naming::validate_name("Snapshot", &req.name)?;
+        info!(id = %req.vm_id, snapshot = %req.name, "Snapshotting VM");
 
-    async fn console(
-        &self,
-        request: Request<tonic::Streaming<ConsoleInput>>,
-    ) -> Result<Response<Self::ConsoleStream>, Status> {
-        let mut input_stream = request.into_inner();
+        let entry = self
+            .store
+            .get(&req.vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", req.vm_id)))?;
+        if entry.state != VmState::Running {
+            return Err(Status::failed_precondition("VM is not running"));
+        }
+        if self.jobs.find_running(VM_SNAPSHOT_JOB, &entry.id).is_some()
+            || self
+                .jobs
+                .find_running(MIGRATION_SEND_JOB, &entry.id)
+                .is_some()
+        {
+            return Err(Status::failed_precondition(
+                "VM is being snapshotted or migrated",
+            ));
+        }
+        if self
+            .snapshots
+            .get(&entry.id, &req.name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .is_some()
+        {
+            return Err(Status::already_exists(format!(
+                "Snapshot {} of VM {} already exists",
+                req.name, entry.id
+            )));
+        }
+        let volumes = snapshot::disk_volumes(&entry.config, &self.zfs_volumes().await?)
+            .map_err(Status::failed_precondition)?;
 
-        // Get VM ID from first message
-        let first_msg = input_stream
-            .next()
+        // Left over from a snapshot that didn't finish
+        let _ = self.snapshots.remove(&entry.id, &req.name).await;
+        self.snapshots
+            .create(&entry.id, &req.name)
             .await
-            .ok_or_else(|| Status::invalid_argument("No input received"))?
             .map_err(|e| Status::internal(e.to_string()))?;
 
-        let vm_id = first_msg.vm_id;
-        if vm_id.is_empty() {
-            return Err(Status::invalid_argument(
-                "vm_id is required in first message",
-            ));
+        let job = self.jobs.start(VM_SNAPSHOT_JOB, &entry.id, false);
+        let vm_name = entry.name.as_deref().unwrap_or(&entry.id);
+        let result = async {
+            let meta = self
+                .take_snapshot(&entry.id, &req.name, &volumes, &job)
+                .await?;
+            if let Err(e) = self.snapshots.save(&entry.id, &meta).await {
+                self.delete_volume_snapshots(&volumes, &req.name).await;
+                return Err(e);
+            }
+            Ok(meta)
         }
+        .await;
+        let meta = match result {
+            Ok(meta) => meta,
+            Err(e) => {
+                let _ = self.snapshots.remove(&entry.id, &req.name).await;
+                job.fail(&e);
+                self.audit
+                    .log(
+                        LogLevel::Error,
+                        format!("VM snapshot failed: {}: {}", vm_name, e),
+                        vec![entry.id.clone()],
+                    )
+                    .await;
+                return Err(Status::internal(format!("Failed to snapshot VM: {}", e)));
+            }
+        };
+        job.complete();
+
+        info!(id = %entry.id, snapshot = %req.name, volumes = volumes.len(), "VM snapshotted");
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!("VM snapshotted: {} ({})", vm_name, req.name),
+                vec![entry.id.clone()],
+            )
+            .await;
+        Ok(Response::new(self.snapshot_to_proto(&entry.id, meta).await))
+    }
 
-        info!(vm_id = %vm_id, "Console connection requested");
+    async fn restore_vm(&self, request: Request<RestoreVmRequest>) -> Result<Response<Vm>, Status> {
+        let req = request.into_inner();
+        info!(id = %req.vm_id, snapshot = %req.name, "Restoring VM");
 
-        // Check VM exists and is running
         let entry = self
             .store
-            .get(&vm_id)
+            .get(&req.vm_id)
             .await
             .map_err(|e| Status::internal(e.to_string()))?
-            .ok_or_else(|| Status::not_found(format!("VM {} not found", vm_id)))?;
-
-        if entry.state != VmState::Running {
-            return Err(Status::failed_precondition("VM is not running"));
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", req.vm_id)))?;
+        if entry.state != VmState::Stopped {
+            return Err(Status::failed_precondition(
+                "VM must be stopped to restore a snapshot",
+            ));
         }
-
-        // Get console path (socket or file depending on boot mode)
-        let console_path = self
-            .hypervisor
-            .console_path(&vm_id)
+        if self.jobs.find_running(VM_START_JOB, &entry.id).is_some() {
+            return Err(Status::failed_precondition("VM is already starting"));
+        }
+        let meta = self
+            .snapshots
+            .get(&entry.id, &req.name)
             .await
-            .ok_or_else(|| Status::unavailable("Console not available"))?;
-
-        if !console_path.exists() {
-            return Err(Status::unavailable("Console not available"));
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "Snapshot {} of VM {} not found",
+                    req.name, entry.id
+                ))
+            })?;
+        let missing = missing_paths(&entry.id, &entry.config, true);
+        if !missing.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "Missing on this host: {}",
+                missing.join(", ")
+            )));
         }
 
-        // Channel for output to client
-        let (tx, rx) = mpsc::channel::<Result<ConsoleOutput, Status>>(32);
-
-        // Check if this is a socket (bidirectional) or file (read-only)
-        let is_socket = console_path
-            .extension()
-            .map(|e| e == "sock")
-            .unwrap_or(false);
+        // A restore is a start, so StartVm and DeleteVm see it as one
+        let job = self.jobs.start(VM_START_JOB, &entry.id, false);
+        self.store
+            .update_state(&entry.id, VmState::Starting)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
 
-        if is_socket {
-            // Socket-based console (Disk Boot) - bidirectional
-            let socket = UnixStream::connect(&console_path)
-                .await
-                .map_err(|e| Status::internal(format!("Failed to connect to console: {}", e)))?;
+        let vm_name = entry.name.as_deref().unwrap_or(&entry.id);
+        if let Err(e) = self.restore_snapshot(&entry, &meta, &job).await {
+            if let Err(e) = self.hypervisor.kill(&entry.id).await {
+                warn!(id = %entry.id, error = %e, "Failed to clean up after a restore");
+            }
+            let _ = self.store.update_state(&entry.id, VmState::Stopped).await;
+            job.fail(&e);
+            self.audit
+                .log(
+                    LogLevel::Error,
+                    format!("VM restore failed: {}: {}", vm_name, e),
+                    vec![entry.id.clone()],
+                )
+                .await;
+            return Err(Status::internal(format!("Failed to restore VM: {}", e)));
+        }
 
-            let (mut socket_read, mut socket_write) = socket.into_split();
+        let running = self
+            .store
+            .update_state(&entry.id, VmState::Running)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::internal("Failed to update VM state"))?;
+        job.complete();
+
+        // The rollback destroyed the volume snapshots of later VM snapshots
+        let snapshots = self.snapshots.list(&entry.id).await.unwrap_or_default();
+        for later in snapshots.iter().skip_while(|s| s.name != meta.name).skip(1) {
+            info!(id = %entry.id, snapshot = %later.name, "Deleting snapshot taken after the restored one");
+            if let Err(e) = self.drop_snapshot(&entry.id, later).await {
+                warn!(id = %entry.id, snapshot = %later.name, error = %e, "Failed to delete VM snapshot");
+            }
+        }
 
-            // Task: Forward input from client -> socket
-            tokio::spawn(async move {
-                while let Some(Ok(msg)) = input_stream.next().await {
-                    if !msg.data.is_empty() && socket_write.write_all(&msg.data).await.is_err() {
-                        break;
-                    }
-                }
-            });
+        info!(id = %entry.id, snapshot = %meta.name, "VM restored");
+        let proto = running.to_proto();
+        self.publish_vm_event(&entry.id, VmEventType::VmEventStarted, Some(proto.clone()));
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!("VM restored: {} ({})", vm_name, meta.name),
+                vec![entry.id.clone()],
+            )
+            .await;
+        Ok(Response::new(proto))
+    }
+
+    async fn list_vm_snapshots(
+        &self,
+        request: Request<ListVmSnapshotsRequest>,
+    ) -> Result<Response<ListVmSnapshotsResponse>, Status> {
+        let req = request.into_inner();
+        self.store
+            .get(&req.vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", req.vm_id)))?;
+        let metas = self
+            .snapshots
+            .list(&req.vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let mut snapshots = Vec::with_capacity(metas.len());
+        for meta in metas {
+            snapshots.push(self.snapshot_to_proto(&req.vm_id, meta).await);
+        }
+        Ok(Response::new(ListVmSnapshotsResponse { snapshots }))
+    }
+
+    async fn delete_vm_snapshot(
+        &self,
+        request: Request<DeleteVmSnapshotRequest>,
+    ) -> Result<Response<DeleteVmSnapshotResponse>, Status> {
+        let req = request.into_inner();
+        info!(id = %req.vm_id, snapshot = %req.name, "Deleting VM snapshot");
+        let entry = self
+            .store
+            .get(&req.vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", req.vm_id)))?;
+        let meta = self
+            .snapshots
+            .get(&entry.id, &req.name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| {
+                Status::not_found(format!(
+                    "Snapshot {} of VM {} not found",
+                    req.name, entry.id
+                ))
+            })?;
+        // Restoring reads the state; a start or restore of the VM holds it
+        if self.jobs.find_running(VM_START_JOB, &entry.id).is_some() {
+            return Err(Status::failed_precondition("VM is starting"));
+        }
+        self.drop_snapshot(&entry.id, &meta)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "VM snapshot deleted: {} ({})",
+                    entry.name.as_deref().unwrap_or(&entry.id),
+                    meta.name
+                ),
+                vec![entry.id.clone()],
+            )
+            .await;
+        Ok(Response::new(DeleteVmSnapshotResponse {}))
+    }
+
+    // Hot-plug
+
+    async fn attach_disk(
+        &self,
+        request: Request<AttachDiskRequest>,
+    ) -> Result<Response<Vm>, Status> {
+        let req = request.into_inner();
+        let disk = req
+            .disk
+            .ok_or_else(|| Status::invalid_argument("disk is required"))?;
+        info!(id = %req.vm_id, path = %disk.path, "Attaching disk");
+
+        let entry = self.hotplug_target(&req.vm_id).await?;
+        if entry.config.disks.iter().any(|d| d.path == disk.path) {
+            return Err(Status::already_exists(format!(
+                "VM {} already has disk {}",
+                entry.id, disk.path
+            )));
+        }
+        RemoteTarget::parse(&disk.path).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let added = VmConfig {
+            disks: vec![disk.clone()],
+            ..Default::default()
+        };
+        let missing = missing_paths(&entry.id, &added, false);
+        if !missing.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "Missing on this host: {}",
+                missing.join(", ")
+            )));
+        }
+
+        if entry.state == VmState::Running {
+            let path = remote_disk::connect_all(&added.disks)
+                .await
+                .map_err(|e| Status::failed_precondition(e.to_string()))?
+                .remove(0);
+            if let Err(e) = self
+                .hypervisor
+                .add_disk(&entry.id, &path, disk.readonly)
+                .await
+            {
+                remote_disk::disconnect_all(&added.disks).await;
+                return Err(Status::internal(format!("Failed to hot-plug disk: {}", e)));
+            }
+        }
+
+        let mut config = entry.config.clone();
+        config.disks.push(disk);
+        let vm = self
+            .save_hotplug(
+                &entry,
+                config,
+                format!("disk attached: {}", added.disks[0].path),
+            )
+            .await?;
+        Ok(Response::new(vm))
+    }
+
+    async fn detach_disk(
+        &self,
+        request: Request<DetachDiskRequest>,
+    ) -> Result<Response<Vm>, Status> {
+        let req = request.into_inner();
+        info!(id = %req.vm_id, path = %req.path, "Detaching disk");
+
+        let entry = self.hotplug_target(&req.vm_id).await?;
+        let index = entry
+            .config
+            .disks
+            .iter()
+            .position(|d| d.path == req.path)
+            .ok_or_else(|| {
+                Status::not_found(format!("VM {} has no disk {}", entry.id, req.path))
+            })?;
+        let mut config = entry.config.clone();
+        let removed = config.disks.remove(index);
+
+        if entry.state == VmState::Running {
+            // A connected remote disk just reports its device
+            let removed = std::slice::from_ref(&removed);
+            let path = remote_disk::connect_all(removed)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+                .remove(0);
+            self.hypervisor
+                .remove_disk(&entry.id, &path)
+                .await
+                .map_err(|e| Status::internal(format!("Failed to unplug disk: {}", e)))?;
+            remote_disk::disconnect_all(removed).await;
+        }
+
+        let vm = self
+            .save_hotplug(&entry, config, format!("disk detached: {}", req.path))
+            .await?;
+        Ok(Response::new(vm))
+    }
+
+    async fn attach_nic(&self, request: Request<AttachNicRequest>) -> Result<Response<Vm>, Status> {
+        let req = request.into_inner();
+        let nic = req
+            .nic
+            .ok_or_else(|| Status::invalid_argument("nic is required"))?;
+        if nic.tap.is_none() && nic.vhost_socket.is_none() {
+            return Err(Status::invalid_argument(
+                "NIC needs a TAP or a vhost-user socket",
+            ));
+        }
+        info!(id = %req.vm_id, nic = ?nic, "Attaching NIC");
+
+        let entry = self.hotplug_target(&req.vm_id).await?;
+        if entry
+            .config
+            .nics
+            .iter()
+            .any(|n| hypervisor::same_nic(n, &nic))
+        {
+            return Err(Status::already_exists(format!(
+                "VM {} already has this NIC",
+                entry.id
+            )));
+        }
+        let added = VmConfig {
+            nics: vec![nic.clone()],
+            ..Default::default()
+        };
+        let missing = missing_paths(&entry.id, &added, true);
+        if !missing.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "Missing on this host: {}",
+                missing.join(", ")
+            )));
+        }
+
+        if entry.state == VmState::Running {
+            self.hypervisor
+                .add_nic(&entry.id, &nic)
+                .await
+                .map_err(|e| Status::internal(format!("Failed to hot-plug NIC: {}", e)))?;
+        }
+
+        let what = nic_label(&nic);
+        let mut config = entry.config.clone();
+        config.nics.push(nic);
+        let vm = self
+            .save_hotplug(&entry, config, format!("NIC attached: {}", what))
+            .await?;
+        Ok(Response::new(vm))
+    }
+
+    async fn detach_nic(&self, request: Request<DetachNicRequest>) -> Result<Response<Vm>, Status> {
+        let req = request.into_inner();
+        if req.mac.is_empty() && req.socket.is_empty() {
+            return Err(Status::invalid_argument("mac or socket is required"));
+        }
+        info!(id = %req.vm_id, mac = %req.mac, socket = %req.socket, "Detaching NIC");
+
+        let entry = self.hotplug_target(&req.vm_id).await?;
+        let (tap, vhost_socket) = match req.socket.strip_prefix("tap:") {
+            Some(tap) => (Some(tap.to_string()), None),
+            None => (None, (!req.socket.is_empty()).then_some(req.socket)),
+        };
+        let wanted = NicConfig {
+            tap,
+            mac: (!req.mac.is_empty()).then_some(req.mac),
+            vhost_socket,
+        };
+        let index = entry
+            .config
+            .nics
+            .iter()
+            .position(|n| hypervisor::same_nic(n, &wanted))
+            .ok_or_else(|| Status::not_found(format!("VM {} has no such NIC", entry.id)))?;
+        let vm = self.unplug_nic(&entry, index, "NIC detached").await?;
+        Ok(Response::new(vm))
+    }
+
+    async fn update_vm_resources(
+        &self,
+        request: Request<UpdateVmResourcesRequest>,
+    ) -> Result<Response<Vm>, Status> {
+        let req = request.into_inner();
+        if req.vcpus.is_none() && req.memory_mb.is_none() {
+            return Err(Status::invalid_argument("vcpus or memory_mb is required"));
+        }
+        if req.vcpus == Some(0) || req.memory_mb == Some(0) {
+            return Err(Status::invalid_argument(
+                "vcpus and memory_mb must be at least 1",
+            ));
+        }
+        info!(id = %req.vm_id, vcpus = ?req.vcpus, memory_mb = ?req.memory_mb, "Resizing VM");
+
+        let entry = self.hotplug_target(&req.vm_id).await?;
+        let running = entry.state == VmState::Running;
+        resize::check_limits(&entry.config, running, req.vcpus, req.memory_mb)
+            .map_err(Status::failed_precondition)?;
+        if let Some(path) = &self.node_resources
+            && let Some(node) = NodeCapacity::read(path).await
+        {
+            resize::check_capacity(&node, &entry.config, running, req.vcpus, req.memory_mb)
+                .map_err(Status::resource_exhausted)?;
+        }
+
+        if running {
+            self.hypervisor
+                .resize(&entry.id, req.vcpus, req.memory_mb)
+                .await
+                .map_err(|e| Status::internal(format!("Failed to resize VM: {}", e)))?;
+        }
+
+        let mut config = entry.config.clone();
+        config.vcpus = req.vcpus.unwrap_or(config.vcpus);
+        config.memory_mb = req.memory_mb.unwrap_or(config.memory_mb);
+        let what = format!("resized to {} vCPUs, {} MB", config.vcpus, config.memory_mb);
+        let vm = self.save_hotplug(&entry, config, what).await?;
+        Ok(Response::new(vm))
+    }
+
+    // Console
+
+    type ConsoleStream = ReceiverStream<Result<ConsoleOutput, Status>>;
+
+    async fn console(
+        &self,
+        request: Request<tonic::Streaming<ConsoleInput>>,
+    ) -> Result<Response<Self::ConsoleStream>, Status> {
+        let caller = peer::describe(&request);
+        let mut input_stream = request.into_inner();
+
+        // Get VM ID from first message
+        let first_msg = input_stream
+            .next()
+            .await
+            .ok_or_else(|| Status::invalid_argument("No input received"))?
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let vm_id = first_msg.vm_id;
+        if vm_id.is_empty() {
+            return Err(Status::invalid_argument(
+                "vm_id is required in first message",
+            ));
+        }
+        let client = console::client_label(&caller, &first_msg.client);
+
+        info!(vm_id = %vm_id, client = %client, "Console connection requested");
+
+        // Check VM exists and is running
+        let entry = self
+            .store
+            .get(&vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", vm_id)))?;
+
+        if entry.state != VmState::Running {
+            return Err(Status::failed_precondition("VM is not running"));
+        }
+
+        // Get console path (socket or file depending on boot mode)
+        let console_path = self
+            .hypervisor
+            .console_path(&vm_id)
+            .await
+            .ok_or_else(|| Status::unavailable("Console not available"))?;
+
+        if !console_path.exists() {
+            return Err(Status::unavailable("Console not available"));
+        }
 
-            // Task: Read from socket -> send to client
-            let tx_clone = tx.clone();
-            tokio::spawn(async move {
-                let mut buf = [0u8; 1024];
-                loop {
-                    match socket_read.read(&mut buf).await {
-                        Ok(0) => break, // EOF
-                        Ok(n) => {
+        let access = ConsoleAccess::try_from(first_msg.access).unwrap_or_default();
+        let mut session = self
+            .console
+            .attach(
+                &vm_id,
+                &console_path,
+                access,
+                &client,
+                first_msg.history_bytes as usize,
+            )
+            .await
+            .map_err(|e| Status::internal(format!("Failed to connect to console: {}", e)))?;
+
+        // Channel for output to client
+        let (tx, rx) = mpsc::channel::<Result<ConsoleOutput, Status>>(32);
+
+        // First message tells the client its session and write access.
+        let _ = tx
+            .send(Ok(ConsoleOutput {
+                session_id: session.id.clone(),
+                writable: session.writable,
+                notice: if session.writable {
+                    String::new()
+                } else {
+                    "console is read-only for this session".to_string()
+                },
+                ..Default::default()
+            }))
+            .await;
+
+        if !session.history.is_empty() {
+            let _ = tx
+                .send(Ok(ConsoleOutput {
+                    data: std::mem::take(&mut session.history),
+                    ..Default::default()
+                }))
+                .await;
+        }
+
+        let hub = self.console.clone();
+        tokio::spawn(async move {
+            if !first_msg.data.is_empty() {
+                hub.write(&session.console, &session.id, first_msg.data)
+                    .await;
+            }
+            loop {
+                tokio::select! {
+                    msg = input_stream.next() => {
+                        let Some(Ok(msg)) = msg else { break };
+                        if msg.access == ConsoleAccess::Takeover as i32 {
+                            hub.takeover(&session.console, &session.id).await;
+                        }
+                        // Input from sessions without write access is dropped.
+                        if !msg.data.is_empty() {
+                            hub.write(&session.console, &session.id, msg.data).await;
+                        }
+                    }
+                    event = session.output.recv() => match event {
+                        Ok(ConsoleEvent::Data(data)) => {
                             let output = ConsoleOutput {
-                                data: buf[..n].to_vec(),
+                                data,
+                                ..Default::default()
                             };
-                            if tx_clone.send(Ok(output)).await.is_err() {
+                            if tx.send(Ok(output)).await.is_err() {
                                 break;
                             }
                         }
-                        Err(e) => {
-                            error!(error = %e, "Error reading from console socket");
+                        // A slow viewer misses output rather than stalling the others.
+                        Err(broadcast::error::RecvError::Lagged(_)) => {}
+                        Ok(ConsoleEvent::Closed) | Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                    control = session.control.recv() => match control {
+                        Some(SessionControl::Access { writable, notice }) => {
+                            let output = ConsoleOutput {
+                                session_id: session.id.clone(),
+                                writable,
+                                notice,
+                                ..Default::default()
+                            };
+                            if tx.send(Ok(output)).await.is_err() {
+                                break;
+                            }
+                        }
+                        Some(SessionControl::Kicked { reason }) => {
+                            let _ = tx
+                                .send(Err(Status::aborted(format!(
+                                    "Console session disconnected: {}",
+                                    reason
+                                ))))
+                                .await;
                             break;
                         }
-                    }
+                        None => break,
+                    },
                 }
-            });
+            }
+            hub.detach(&session.console, &session.id).await;
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn list_console_sessions(
+        &self,
+        request: Request<ListConsoleSessionsRequest>,
+    ) -> Result<Response<ListConsoleSessionsResponse>, Status> {
+        let req = request.into_inner();
+        let sessions = self.console.list(&req.vm_id).await;
+        Ok(Response::new(ListConsoleSessionsResponse { sessions }))
+    }
+
+    async fn get_console_buffer(
+        &self,
+        request: Request<GetConsoleBufferRequest>,
+    ) -> Result<Response<GetConsoleBufferResponse>, Status> {
+        let req = request.into_inner();
+        let (data, truncated) = self
+            .console
+            .buffer(&req.vm_id, req.max_bytes as usize)
+            .await
+            .ok_or_else(|| {
+                Status::not_found(format!("No console output recorded for VM {}", req.vm_id))
+            })?;
+        Ok(Response::new(GetConsoleBufferResponse { data, truncated }))
+    }
+
+    async fn disconnect_console_session(
+        &self,
+        request: Request<DisconnectConsoleSessionRequest>,
+    ) -> Result<Response<DisconnectConsoleSessionResponse>, Status> {
+        let req = request.into_inner();
+        if req.vm_id.is_empty() || req.session_id.is_empty() {
+            return Err(Status::invalid_argument(
+                "vm_id and session_id are required",
+            ));
+        }
+        let reason = if req.reason.is_empty() {
+            "disconnected by administrator".to_string()
         } else {
-            // File-based console (Kernel Boot) - read-only, tail -f style
-            let mut console_file = File::open(&console_path)
-                .await
-                .map_err(|e| Status::internal(format!("Failed to open console log: {}", e)))?;
+            req.reason
+        };
+        if !self
+            .console
+            .disconnect(&req.vm_id, &req.session_id, &reason)
+            .await
+        {
+            return Err(Status::not_found(format!(
+                "Console session {} not found on VM {}",
+                req.session_id, req.vm_id
+            )));
+        }
+        Ok(Response::new(DisconnectConsoleSessionResponse {}))
+    }
 
-            // Seek to end to only show new output (like tail -f)
-            let _ = console_file.seek(std::io::SeekFrom::End(0)).await;
+    // Host migration
 
-            // Note: Input from client is ignored for file-based console (read-only)
-            // Just drain the input stream to prevent backpressure
-            tokio::spawn(async move {
-                while input_stream.next().await.is_some() {
-                    // Discard input - serial file is read-only
+    async fn export_state(
+        &self,
+        _request: Request<ExportStateRequest>,
+    ) -> Result<Response<StateSnapshot>, Status> {
+        let state = self
+            .store
+            .export_state()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let data = serde_json::to_vec(&state).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(StateSnapshot {
+            version: STATE_VERSION,
+            data,
+        }))
+    }
+
+    async fn import_state(
+        &self,
+        request: Request<ImportStateRequest>,
+    ) -> Result<Response<ImportStateResponse>, Status> {
+        let req = request.into_inner();
+        let snapshot = req
+            .snapshot
+            .ok_or_else(|| Status::invalid_argument("snapshot is required"))?;
+        if snapshot.version != STATE_VERSION {
+            return Err(Status::invalid_argument(format!(
+                "Unsupported state snapshot version {} (expected {})",
+                snapshot.version, STATE_VERSION
+            )));
+        }
+        let state: serde_json::Value = serde_json::from_slice(&snapshot.data)
+            .map_err(|e| Status::invalid_argument(format!("Invalid state snapshot: {}", e)))?;
+        let configs = store::snapshot_configs(&state)
+            .map_err(|e| Status::invalid_argument(format!("Invalid state snapshot: {}", e)))?;
+
+        if !self
+            .store
+            .is_empty()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+        {
+            return Err(Status::failed_precondition(
+                "VMs are already defined on this host",
+            ));
+        }
+
+        // NIC sockets and TAPs are created by the network daemon's own
+        // import, which a dry run hasn't done yet.
+        let missing: Vec<String> = configs
+            .iter()
+            .flat_map(|(id, config)| missing_paths(id, config, !req.dry_run))
+            .collect();
+
+        if req.dry_run {
+            return Ok(Response::new(ImportStateResponse {
+                records: configs.len() as u32,
+                missing,
+            }));
+        }
+
+        let records = self
+            .store
+            .import_state(&state)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        info!(vms = records, missing = missing.len(), "Imported VM state");
+        for (id, _) in &configs {
+            if let Ok(Some(entry)) = self.store.get(id).await {
+                self.publish_vm_event(id, VmEventType::VmEventCreated, Some(entry.to_proto()));
+            }
+        }
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!("Host state imported: {} VMs", records),
+                configs.into_iter().map(|(id, _)| id).collect(),
+            )
+            .await;
+        Ok(Response::new(ImportStateResponse {
+            records: records as u32,
+            missing,
+        }))
+    }
+
+    // Live migration
+
+    type MigrateVmStream = ReceiverStream<Result<MigrationMessage, Status>>;
+
+    async fn migrate_vm(
+        &self,
+        request: Request<tonic::Streaming<MigrationMessage>>,
+    ) -> Result<Response<Self::MigrateVmStream>, Status> {
+        let mut inbound = request.into_inner();
+        let header = migration::next_header(&mut inbound).await?;
+        let entry = self
+            .store
+            .get(&header.vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", header.vm_id)))?;
+        if entry.state != VmState::Running {
+            return Err(Status::failed_precondition("VM is not running"));
+        }
+        if self
+            .jobs
+            .find_running(MIGRATION_SEND_JOB, &entry.id)
+            .is_some()
+        {
+            return Err(Status::failed_precondition("VM is already migrating"));
+        }
+
+        // The block devices the disk URLs were connected as, for the target
+        // to check it gets the same ones; the cloud-init ISO comes last
+        let info = self
+            .hypervisor
+            .vm_info(&entry.id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let disk_paths: Vec<String> = info["config"]["disks"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .take(entry.config.disks.len())
+            .filter_map(|disk| disk["path"].as_str().map(String::from))
+            .collect();
+
+        info!(id = %entry.id, "Migrating VM");
+        let job = self.jobs.start(MIGRATION_SEND_JOB, &entry.id, true);
+        job.set_phase("waiting for target");
+        let (tx, rx) = mpsc::channel(16);
+        let _ = tx
+            .send(Ok(migration::header_message(MigrationHeader {
+                vm_id: entry.id.clone(),
+                name: entry.name.clone(),
+                config: Some(entry.config.clone()),
+                disk_paths,
+            })))
+            .await;
+
+        let hypervisor = self.hypervisor.clone();
+        let store = self.store.clone();
+        let audit = self.audit.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let id = entry.id.as_str();
+            let name = entry.name.as_deref().unwrap_or(id);
+            let result: anyhow::Result<()> = async {
+                // The target answers once it waits for the VM
+                tokio::select! {
+                    header = migration::next_header(&mut inbound) => {
+                        header.map_err(|s| anyhow!("target: {}", s.message()))?;
+                    }
+                    _ = job.wait_cancelled() => return Err(anyhow!("migration cancelled")),
                 }
-            });
+                job.set_phase("migrating");
+                let socket = hypervisor.migration_socket(id);
+                let _ = tokio::fs::remove_file(&socket).await;
+                let listener = UnixListener::bind(&socket)?;
+                let send = hypervisor.send_migration(id, &socket);
+                tokio::pin!(send);
+                let conn = tokio::select! {
+                    accepted = listener.accept() => accepted?.0,
+                    result = &mut send => {
+                        result?;
+                        return Err(anyhow!("migration ended before it connected"));
+                    }
+                    _ = job.wait_cancelled() => return Err(anyhow!("migration cancelled")),
+                };
+                migration::relay(conn, &mut inbound, &tx, &job, send).await
+            }
+            .await;
 
-            // Task: Read from console file -> send to client (tail -f style)
-            let tx_clone = tx.clone();
-            tokio::spawn(async move {
-                let mut buf = [0u8; 1024];
-                loop {
-                    match console_file.read(&mut buf).await {
-                        Ok(0) => {
-                            // No new data, wait briefly and try again (tail -f behavior)
-                            tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-                        }
-                        Ok(n) => {
-                            let output = ConsoleOutput {
-                                data: buf[..n].to_vec(),
-                            };
-                            if tx_clone.send(Ok(output)).await.is_err() {
-                                break;
-                            }
-                        }
-                        Err(e) => {
-                            error!(error = %e, "Error reading from console log");
-                            break;
-                        }
+            match result {
+                Ok(()) => {
+                    // The VM runs on the target now. Volumes and NICs it
+                    // owns stay here for the caller to remove.
+                    if let Err(e) = hypervisor.kill(id).await {
+                        warn!(vm_id = %id, error = %e, "Failed to clean up a migrated VM");
                     }
+                    let _ = store.delete(id).await;
+                    job.complete();
+                    info!(id = %id, "VM migrated");
+                    let _ = events.send(vm_event(id, VmEventType::VmEventDeleted, None));
+                    audit
+                        .log(
+                            LogLevel::Audit,
+                            format!("VM migrated to another host: {}", name),
+                            vec![id.to_string()],
+                        )
+                        .await;
                 }
-            });
+                Err(e) => {
+                    // The VM keeps running here
+                    error!(vm_id = %id, error = %e, "VM migration failed");
+                    let _ = tx
+                        .send(Err(Status::aborted(format!("Migration failed: {}", e))))
+                        .await;
+                    if job.is_cancelled() {
+                        job.finish_cancelled();
+                    } else {
+                        job.fail(&e);
+                    }
+                    audit
+                        .log(
+                            LogLevel::Error,
+                            format!("VM migration failed: {}: {}", name, e),
+                            vec![id.to_string()],
+                        )
+                        .await;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    type ReceiveMigrationStream = ReceiverStream<Result<MigrationMessage, Status>>;
+
+    async fn receive_migration(
+        &self,
+        request: Request<tonic::Streaming<MigrationMessage>>,
+    ) -> Result<Response<Self::ReceiveMigrationStream>, Status> {
+        let mut inbound = request.into_inner();
+        let header = migration::next_header(&mut inbound).await?;
+        let config = header
+            .config
+            .ok_or_else(|| Status::invalid_argument("config is required"))?;
+        let id = header.vm_id;
+        naming::validate_optional_name("VM", header.name.as_deref())?;
+        if self
+            .store
+            .get(&id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .is_some()
+        {
+            return Err(Status::already_exists(format!(
+                "VM {} already exists on this host",
+                id
+            )));
+        }
+        migration::check_disk_locations(&config, &header.disk_paths)
+            .map_err(Status::failed_precondition)?;
+        let missing = missing_paths(&id, &config, true);
+        if !missing.is_empty() {
+            return Err(Status::failed_precondition(format!(
+                "Missing on this host: {}",
+                missing.join(", ")
+            )));
+        }
+
+        info!(id = %id, "Receiving VM migration");
+        let job = self.jobs.start(MIGRATION_RECEIVE_JOB, &id, true);
+        job.set_phase("connecting disks");
+        if let Err(e) = migration::connect_disks(&config, &header.disk_paths).await {
+            job.fail(&e);
+            return Err(Status::failed_precondition(e));
         }
+        let disks = config.disks.clone();
+        let entry = match self.store.create_with_id(&id, header.name, config).await {
+            Ok(entry) => entry,
+            Err(e) => {
+                remote_disk::disconnect_all(&disks).await;
+                job.fail(&e);
+                return Err(Status::internal(e.to_string()));
+            }
+        };
+        let _ = self.store.update_state(&id, VmState::Starting).await;
+        job.set_phase("spawning");
+        if let Err(e) = self
+            .hypervisor
+            .spawn_empty(&id, entry.name.as_deref(), &entry.config)
+            .await
+        {
+            abandon_migration(&self.hypervisor, &self.store, &id).await;
+            job.fail(&e);
+            return Err(Status::internal(format!(
+                "Failed to start cloud-hypervisor: {}",
+                e
+            )));
+        }
+
+        job.set_phase("waiting for source");
+        let (tx, rx) = mpsc::channel(16);
+        let _ = tx
+            .send(Ok(migration::header_message(MigrationHeader {
+                vm_id: id.clone(),
+                ..Default::default()
+            })))
+            .await;
+
+        let hypervisor = self.hypervisor.clone();
+        let store = self.store.clone();
+        let audit = self.audit.clone();
+        let events = self.events.clone();
+        tokio::spawn(async move {
+            let name = entry.name.as_deref().unwrap_or(&id);
+            let result: anyhow::Result<()> = async {
+                let socket = hypervisor.migration_socket(&id);
+                let receive = hypervisor.receive_migration(&id, &entry.config, &socket);
+                tokio::pin!(receive);
+                let conn = tokio::select! {
+                    conn = migration::connect(&socket) => conn?,
+                    result = &mut receive => {
+                        result?;
+                        return Err(anyhow!("migration ended before it connected"));
+                    }
+                    _ = job.wait_cancelled() => return Err(anyhow!("migration cancelled")),
+                };
+                job.set_phase("migrating");
+                migration::relay(conn, &mut inbound, &tx, &job, receive).await
+            }
+            .await;
 
+            match result {
+                Ok(()) => {
+                    let vm = store
+                        .update_state(&id, VmState::Running)
+                        .await
+                        .ok()
+                        .flatten()
+                        .map(|e| e.to_proto());
+                    job.complete();
+                    info!(id = %id, "VM migrated in");
+                    let _ = events.send(vm_event(&id, VmEventType::VmEventCreated, vm.clone()));
+                    let _ = events.send(vm_event(&id, VmEventType::VmEventStarted, vm));
+                    audit
+                        .log(
+                            LogLevel::Audit,
+                            format!("VM migrated from another host: {}", name),
+                            vec![id.clone()],
+                        )
+                        .await;
+                }
+                Err(e) => {
+                    error!(vm_id = %id, error = %e, "Incoming VM migration failed");
+                    let _ = tx
+                        .send(Err(Status::aborted(format!("Migration failed: {}", e))))
+                        .await;
+                    abandon_migration(&hypervisor, &store, &id).await;
+                    if job.is_cancelled() {
+                        job.finish_cancelled();
+                    } else {
+                        job.fail(&e);
+                    }
+                    audit
+                        .log(
+                            LogLevel::Error,
+                            format!("Incoming VM migration failed: {}: {}", name, e),
+                            vec![id.clone()],
+                        )
+                        .await;
+                }
+            }
+        });
         Ok(Response::new(ReceiverStream::new(rx)))
     }
 
     // Events (Phase 2 - stub)
 
+    // Debugging
+
+    type DumpGuestMemoryStream = ReceiverStream<Result<GuestMemoryChunk, Status>>;
+
+    async fn dump_guest_memory(
+        &self,
+        request: Request<DumpGuestMemoryRequest>,
+    ) -> Result<Response<Self::DumpGuestMemoryStream>, Status> {
+        let caller = peer::caller_uid(&request);
+        let req = request.into_inner();
+        let entry = self.memory_debug_target(caller, &req.vm_id).await?;
+        let range = (req.length > 0).then_some((req.start, req.length));
+        let name = entry.name.clone().unwrap_or_else(|| entry.id.clone());
+
+        let job = Arc::new(self.jobs.start("memory-dump", &name, true));
+        job.set_phase("dumping");
+        let path = self.hypervisor.coredump_path(&req.vm_id);
+        let what = match range {
+            Some((start, length)) => format!("{:#x}+{:#x}", start, length),
+            None => "all".to_string(),
+        };
+        let who = caller.map_or("unknown".to_string(), |uid| uid.to_string());
+
+        let (tx, rx) = mpsc::channel(4);
+        let Some(range) = range else {
+            // A full dump is the file itself, streamed once it's complete
+            if let Err(e) = self.hypervisor.coredump(&req.vm_id, &path).await {
+                let _ = tokio::fs::remove_file(&path).await;
+                let message = format!("Memory dump failed: {}", e);
+                job.fail(&message);
+                return Err(Status::internal(message));
+            }
+            self.audit
+                .log(
+                    LogLevel::Audit,
+                    format!("Guest memory dumped ({}) by uid {}: {}", what, who, name),
+                    vec![entry.id.clone()],
+                )
+                .await;
+            let total = std::fs::metadata(&path).ok().map(|m| m.len());
+            job.set_phase("streaming");
+            job.set_progress(0, total);
+            tokio::task::spawn_blocking(move || {
+                let mut sent = 0u64;
+                let result = memory_dump::read_chunks(&path, None, |chunk| {
+                    sent += chunk.data.len() as u64;
+                    job.set_progress(sent, total);
+                    !job.is_cancelled() && tx.blocking_send(Ok(chunk)).is_ok()
+                });
+                finish_memory_dump(&job, &tx, result);
+                let _ = std::fs::remove_file(&path);
+            });
+            return Ok(Response::new(ReceiverStream::new(rx)));
+        };
+
+        // A range is read while the dump is written, the rest dropped
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!("Guest memory dumped ({}) by uid {}: {}", what, who, name),
+                vec![entry.id.clone()],
+            )
+            .await;
+        job.set_progress(0, Some(range.1));
+        let hypervisor = Arc::clone(&self.hypervisor);
+        let vm_id = req.vm_id.clone();
+        tokio::spawn(async move {
+            let written = Arc::new(AtomicBool::new(false));
+            let follower = tokio::task::spawn_blocking({
+                let (path, written, job, tx) =
+                    (path.clone(), written.clone(), job.clone(), tx.clone());
+                move || {
+                    let mut sent = 0u64;
+                    memory_dump::follow_range(
+                        &path,
+                        range,
+                        || written.load(Ordering::Acquire),
+                        |chunk| {
+                            sent += chunk.data.len() as u64;
+                            job.set_progress(sent, Some(range.1));
+                            !job.is_cancelled() && tx.blocking_send(Ok(chunk)).is_ok()
+                        },
+                    )
+                }
+            });
+            let dumped = hypervisor.coredump(&vm_id, &path).await;
+            written.store(true, Ordering::Release);
+            let followed = follower
+                .await
+                .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+            let _ = tokio::fs::remove_file(&path).await;
+            match dumped {
+                Err(e) => {
+                    let message = format!("Memory dump failed: {}", e);
+                    let _ = tx.send(Err(Status::internal(message.clone()))).await;
+                    job.fail(message);
+                }
+                Ok(()) => {
+                    let _ = tokio::task::spawn_blocking(move || {
+                        finish_memory_dump(&job, &tx, followed)
+                    })
+                    .await;
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn get_guest_memory_info(
+        &self,
+        request: Request<GetGuestMemoryInfoRequest>,
+    ) -> Result<Response<GuestMemoryInfo>, Status> {
+        let caller = peer::caller_uid(&request);
+        let req = request.into_inner();
+        self.memory_debug_target(caller, &req.vm_id).await?;
+        let info = self
+            .hypervisor
+            .vm_info(&req.vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(memory_dump::memory_info(&info)))
+    }
+
+    async fn get_drift(
+        &self,
+        request: Request<GetDriftRequest>,
+    ) -> Result<Response<GetDriftResponse>, Status> {
+        let checker = self
+            .drift
+            .as_ref()
+            .ok_or_else(|| Status::unavailable("Drift detection is disabled"))?;
+        let (findings, checked_at) = if request.into_inner().check_now {
+            checker
+                .check()
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+        } else {
+            checker.findings()
+        };
+        Ok(Response::new(GetDriftResponse {
+            findings,
+            checked_at,
+        }))
+    }
+
+    async fn run_guest_command(
+        &self,
+        request: Request<RunGuestCommandRequest>,
+    ) -> Result<Response<RunGuestCommandResponse>, Status> {
+        let req = request.into_inner();
+        info!(vm_id = %req.vm_id, "Running command in guest");
+
+        let entry = self
+            .store
+            .get(&req.vm_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("VM {} not found", req.vm_id)))?;
+        if !entry.config.guest_agent {
+            return Err(Status::failed_precondition("VM has no guest agent"));
+        }
+        if entry.state != VmState::Running {
+            return Err(Status::failed_precondition("VM is not running"));
+        }
+        let channel = self
+            .guest_agents
+            .channel(&req.vm_id)
+            .ok_or_else(|| Status::unavailable("Guest agent is not ready"))?;
+        let mut agent = OneServiceClient::new(channel);
+
+        self.audit
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "Running command in guest {}: {}",
+                    entry.name.as_deref().unwrap_or(&entry.id),
+                    req.command.join(" ")
+                ),
+                vec![req.vm_id.clone()],
+            )
+            .await;
+
+        // The agent enforces the timeout; the call only fails on errors.
+        // An empty pod runs the command on the guest itself.
+        let response = agent
+            .run_command(OneRunCommandRequest {
+                pod_id: String::new(),
+                container_id: String::new(),
+                command: req.command,
+                timeout_seconds: req.timeout_seconds,
+            })
+            .await?
+            .into_inner();
+
+        Ok(Response::new(RunGuestCommandResponse {
+            exit_code: response.exit_code,
+            stdout: response.stdout,
+            stderr: response.stderr,
+            timed_out: response.timed_out,
+        }))
+    }
+
     type WatchVmsStream = ReceiverStream<Result<VmEvent, Status>>;
 
     async fn watch_vms(
@@ -600,4 +2205,175 @@ impl VmService for VmServiceImpl {
         });
         Ok(Response::new(ReceiverStream::new(out_rx)))
     }
+
+    // Jobs
+
+    async fn list_jobs(
+        &self,
+        request: Request<ListJobsRequest>,
+    ) -> Result<Response<ListJobsResponse>, Status> {
+        let req = request.into_inner();
+        let jobs = self
+            .jobs
+            .list(req.include_finished)
+            .into_iter()
+            .map(job_to_proto)
+            .collect();
+        Ok(Response::new(ListJobsResponse { jobs }))
+    }
+
+    async fn get_job(&self, request: Request<GetJobRequest>) -> Result<Response<Job>, Status> {
+        let req = request.into_inner();
+        let job = self
+            .jobs
+            .get(&req.id)
+            .ok_or_else(|| Status::not_found(format!("Job {} not found", req.id)))?;
+        Ok(Response::new(job_to_proto(job)))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<CancelJobRequest>,
+    ) -> Result<Response<Job>, Status> {
+        let req = request.into_inner();
+        let job = self.jobs.cancel(&req.id)?;
+        info!(job_id = %job.id, kind = %job.kind, "Job cancellation requested");
+        Ok(Response::new(job_to_proto(job)))
+    }
+
+    // Start latency
+
+    async fn get_start_stats(
+        &self,
+        request: Request<GetStartStatsRequest>,
+    ) -> Result<Response<StartStats>, Status> {
+        let req = request.into_inner();
+        let kind = match StartKind::try_from(req.kind).unwrap_or_default() {
+            StartKind::Unspecified => None,
+            StartKind::Vm => Some(start_timing::KIND_VM),
+            StartKind::Pod => Some(start_timing::KIND_POD),
+        };
+        let timings = self
+            .store
+            .start_timings(kind, req.limit)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(start_timing::stats(&timings)))
+    }
+}
+
+/// Settle a memory dump job once its chunks have been streamed.
+fn finish_memory_dump(
+    job: &JobHandle,
+    tx: &mpsc::Sender<Result<GuestMemoryChunk, Status>>,
+    result: std::io::Result<()>,
+) {
+    match result {
+        Err(e) => {
+            error!(error = %e, "Failed to read memory dump");
+            let _ = tx.blocking_send(Err(Status::internal(format!(
+                "Failed to read memory dump: {}",
+                e
+            ))));
+            job.fail(e);
+        }
+        Ok(()) if job.is_cancelled() => {
+            let _ = tx.blocking_send(Err(Status::cancelled("Memory dump cancelled")));
+            job.finish_cancelled();
+        }
+        Ok(()) if tx.is_closed() => job.fail("client disconnected"),
+        Ok(()) => job.complete(),
+    }
+}
+
+fn job_to_proto(job: mvirt_common::jobs::Job) -> Job {
+    use mvirt_common::jobs::JobState as State;
+
+    let state = match job.state {
+        State::Running => JobState::Running,
+        State::Completed => JobState::Completed,
+        State::Failed => JobState::Failed,
+        State::Cancelled => JobState::Cancelled,
+    };
+    let rfc3339 = |t: std::time::SystemTime| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339();
+    Job {
+        id: job.id,
+        kind: job.kind,
+        target: job.target,
+        state: state.into(),
+        phase: job.phase,
+        done: job.done,
+        total: job.total.unwrap_or(0),
+        error: job.error,
+        cancellable: job.cancellable,
+        cancel_requested: job.cancel_requested,
+        started_at: rfc3339(job.started_at),
+        finished_at: job.finished_at.map(rfc3339),
+    }
+}
+
+/// Host paths `config` of VM `id` refers to that don't exist here.
+/// NIC sockets and TAPs are only checked with `nics`.
+pub(crate) fn missing_paths(id: &str, config: &VmConfig, nics: bool) -> Vec<String> {
+    let mut missing = Vec::new();
+    let files = config
+        .disks
+        .iter()
+        .filter(|d| !matches!(RemoteTarget::parse(&d.path), Ok(Some(_))))
+        .map(|d| ("disk", d.path.as_str()))
+        .chain(config.kernel.as_deref().map(|k| ("kernel", k)))
+        .chain(config.initramfs.as_deref().map(|i| ("initramfs", i)));
+    for (what, path) in files {
+        if !std::path::Path::new(path).exists() {
+            missing.push(format!("VM {}: {} {}", id, what, path));
+        }
+    }
+    if nics {
+        for nic in &config.nics {
+            if let Some(socket) = &nic.vhost_socket
+                && !std::path::Path::new(socket).exists()
+            {
+                missing.push(format!("VM {}: NIC socket {}", id, socket));
+            }
+            if let Some(tap) = &nic.tap
+                && !std::path::Path::new("/sys/class/net").join(tap).exists()
+            {
+                missing.push(format!("VM {}: TAP {}", id, tap));
+            }
+        }
+    }
+    missing
+}
+
+const MAX_LABEL_KEY: usize = 63;
+const MAX_LABEL_VALUE: usize = 255;
+
+/// Label keys are short identifiers (`owner`, `app.kubernetes.io/name`)
+/// so they can be used as column names and in filters; values are free
+/// text.
+pub(crate) fn validate_labels(labels: &HashMap<String, String>) -> Result<(), String> {
+    for (key, value) in labels {
+        if key.is_empty() || key.len() > MAX_LABEL_KEY {
+            return Err(format!(
+                "label key '{}' must be 1-{} characters",
+                key, MAX_LABEL_KEY
+            ));
+        }
+        if !key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/'))
+        {
+            return Err(format!(
+                "label key '{}' may only contain letters, digits, '-', '_', '.' and '/'",
+                key
+            ));
+        }
+        if value.len() > MAX_LABEL_VALUE {
+            return Err(format!(
+                "label '{}' value exceeds {} bytes",
+                key, MAX_LABEL_VALUE
+            ));
+        }
+    }
+    Ok(())
 }
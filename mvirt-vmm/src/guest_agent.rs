@@ -0,0 +1,161 @@
+//! Guest agents of regular VMs.
+//!
+//! A VM created with `guest_agent` set gets a vsock device like a pod's
+//! MicroVM. Its own init starts `mvirt-one --agent`, which signals ready on
+//! the vsock ready port and then serves RunCommand on the guest. Until the
+//! agent has signalled, the VM reports `guest_ready = false` and guest
+//! commands are refused.
+//!
+//! Agents are only found while a VM starts: a VM whose agent comes up
+//! later, or that was running when mvirt-vmm restarted, stays not ready
+//! until its next start.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::task::JoinHandle;
+use tonic::transport::Channel;
+use tracing::{info, warn};
+
+use crate::hypervisor::Hypervisor;
+use crate::ready_listener::ReadySignalListener;
+use crate::vsock_client::{OneClient, vm_id_to_cid, vsock_socket_path};
+
+/// How long after a start the guest's agent may take to signal ready.
+/// Covers a full OS boot and cloud-init, not just a MicroVM's init.
+pub const GUEST_AGENT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// A VM prepared for its guest agent; pass it to [`GuestAgents::wait_for`]
+/// once the VM has started.
+pub struct PendingAgent {
+    /// vsock CID to start the VM with.
+    pub cid: u32,
+    vsock_socket: PathBuf,
+    listener: ReadySignalListener,
+}
+
+/// The guest agents that have signalled ready, by VM id.
+#[derive(Clone, Default)]
+pub struct GuestAgents {
+    ready: Arc<Mutex<HashMap<String, Channel>>>,
+    waiting: Arc<Mutex<HashMap<String, JoinHandle<()>>>>,
+}
+
+impl GuestAgents {
+    /// Listen for the agent of `vm_id`. Call this before starting the VM
+    /// so the ready signal can't be missed.
+    pub async fn prepare(
+        &self,
+        hypervisor: &Hypervisor,
+        vm_id: &str,
+    ) -> anyhow::Result<PendingAgent> {
+        self.forget(vm_id);
+        hypervisor.prepare_vm_dir(vm_id).await?;
+        let vsock_socket = vsock_socket_path(hypervisor.data_dir(), vm_id);
+        let listener = ReadySignalListener::new(&vsock_socket).await?;
+        Ok(PendingAgent {
+            cid: vm_id_to_cid(vm_id),
+            vsock_socket,
+            listener,
+        })
+    }
+
+    /// Wait for the agent of a started VM in the background and connect
+    /// to it once it has signalled ready.
+    pub fn wait_for(&self, vm_id: &str, pending: PendingAgent) {
+        let ready = Arc::clone(&self.ready);
+        let id = vm_id.to_string();
+        let task = tokio::spawn(async move {
+            let hello = match pending.listener.wait(GUEST_AGENT_TIMEOUT).await {
+                Ok(hello) => hello,
+                Err(e) => {
+                    warn!(vm_id = %id, error = %e, "Guest agent did not signal ready");
+                    return;
+                }
+            };
+            match OneClient::connect(&pending.vsock_socket, hello).await {
+                Ok(client) => {
+                    info!(vm_id = %id, "Guest agent ready");
+                    ready.lock().unwrap().insert(id, client.channel());
+                }
+                Err(e) => warn!(vm_id = %id, error = %e, "Failed to connect to guest agent"),
+            }
+        });
+        if let Some(previous) = self.waiting.lock().unwrap().insert(vm_id.to_string(), task) {
+            previous.abort();
+        }
+    }
+
+    /// Whether the agent of `vm_id` has signalled ready since it started.
+    pub fn is_ready(&self, vm_id: &str) -> bool {
+        self.ready.lock().unwrap().contains_key(vm_id)
+    }
+
+    /// Connection to the agent of `vm_id`, once it is ready.
+    pub fn channel(&self, vm_id: &str) -> Option<Channel> {
+        self.ready.lock().unwrap().get(vm_id).cloned()
+    }
+
+    /// Drop the agent of a VM that stopped or is starting again.
+    pub fn forget(&self, vm_id: &str) {
+        if let Some(task) = self.waiting.lock().unwrap().remove(vm_id) {
+            task.abort();
+        }
+        self.ready.lock().unwrap().remove(vm_id);
+    }
+
+    #[cfg(test)]
+    fn mark_ready(&self, vm_id: &str, channel: Channel) {
+        self.ready
+            .lock()
+            .unwrap()
+            .insert(vm_id.to_string(), channel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::transport::Endpoint;
+
+    fn channel() -> Channel {
+        Endpoint::from_static("http://vsock.local").connect_lazy()
+    }
+
+    #[tokio::test]
+    async fn test_ready_until_forgotten() {
+        let agents = GuestAgents::default();
+        assert!(!agents.is_ready("vm-1"));
+        assert!(agents.channel("vm-1").is_none());
+
+        agents.mark_ready("vm-1", channel());
+        assert!(agents.is_ready("vm-1"));
+        assert!(agents.channel("vm-1").is_some());
+        assert!(!agents.is_ready("vm-2"));
+
+        agents.forget("vm-1");
+        assert!(!agents.is_ready("vm-1"));
+    }
+
+    #[tokio::test]
+    async fn test_forget_stops_waiting() {
+        let agents = GuestAgents::default();
+        let dir = std::env::temp_dir().join(format!("mvirt-agent-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let vsock_socket = dir.join("vsock.sock");
+        let pending = PendingAgent {
+            cid: 3,
+            listener: ReadySignalListener::new(&vsock_socket).await.unwrap(),
+            vsock_socket,
+        };
+        agents.wait_for("vm-1", pending);
+        assert!(agents.waiting.lock().unwrap().contains_key("vm-1"));
+
+        agents.forget("vm-1");
+        assert!(agents.waiting.lock().unwrap().is_empty());
+        assert!(!agents.is_ready("vm-1"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,137 @@
+//! Guest images for pod MicroVMs.
+//!
+//! A guest image bundles the kernel, optional initramfs and mvirt-one rootfs
+//! template a pod boots from. Several versions can be installed side by side
+//! so pods can be moved to a new guest stack one at a time:
+//!
+//! ```text
+//! <base>/bzImage, rootfs.raw, [initramfs.img], [cmdline]   "default"
+//! <base>/images/<name>/bzImage, rootfs.raw, [initramfs.img], [cmdline]
+//! ```
+//!
+//! The registry reads the directory on every lookup, so newly installed
+//! images are usable without restarting the VMM.
+
+use crate::proto::GuestImage as GuestImageInfo;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+/// Name of the guest image installed directly in the base directory.
+pub const DEFAULT_GUEST_IMAGE: &str = "default";
+
+const KERNEL_FILE: &str = "bzImage";
+const ROOTFS_FILE: &str = "rootfs.raw";
+const INITRAMFS_FILE: &str = "initramfs.img";
+const CMDLINE_FILE: &str = "cmdline";
+
+/// Kernel command line for images without a cmdline file (disk boot).
+const DEFAULT_CMDLINE: &str = "console=ttyS0 quiet root=/dev/vda rw init=/init";
+
+/// A resolved guest image.
+#[derive(Debug, Clone)]
+pub struct GuestImage {
+    pub name: String,
+    pub kernel: PathBuf,
+    pub initramfs: Option<PathBuf>,
+    pub rootfs: PathBuf,
+    pub cmdline: String,
+}
+
+impl GuestImage {
+    /// Load the image in `dir`. Returns `None` if the kernel or rootfs is
+    /// missing.
+    fn load(name: &str, dir: &Path) -> Option<Self> {
+        let kernel = dir.join(KERNEL_FILE);
+        let rootfs = dir.join(ROOTFS_FILE);
+        if !kernel.is_file() || !rootfs.is_file() {
+            return None;
+        }
+        let initramfs = Some(dir.join(INITRAMFS_FILE)).filter(|p| p.is_file());
+        let cmdline = std::fs::read_to_string(dir.join(CMDLINE_FILE))
+            .map(|s| s.trim().to_string())
+            .unwrap_or_else(|_| DEFAULT_CMDLINE.to_string());
+        Some(Self {
+            name: name.to_string(),
+            kernel,
+            initramfs,
+            rootfs,
+            cmdline,
+        })
+    }
+}
+
+impl From<&GuestImage> for GuestImageInfo {
+    fn from(image: &GuestImage) -> Self {
+        GuestImageInfo {
+            name: image.name.clone(),
+            kernel: image.kernel.display().to_string(),
+            initramfs: image.initramfs.as_ref().map(|p| p.display().to_string()),
+            rootfs: image.rootfs.display().to_string(),
+            cmdline: image.cmdline.clone(),
+        }
+    }
+}
+
+/// Registry of installed guest images.
+#[derive(Debug, Clone)]
+pub struct GuestImageRegistry {
+    base_dir: PathBuf,
+    default_name: String,
+}
+
+impl GuestImageRegistry {
+    /// Create a registry over `base_dir`. `default_name` is used for pods
+    /// that don't ask for a specific guest image.
+    pub fn new(base_dir: PathBuf, default_name: String) -> Self {
+        Self {
+            base_dir,
+            default_name,
+        }
+    }
+
+    /// Name of the image pods get when they don't choose one.
+    pub fn default_name(&self) -> &str {
+        &self.default_name
+    }
+
+    /// Look up an image by name, or the default image for `None`.
+    pub fn resolve(&self, name: Option<&str>) -> Option<GuestImage> {
+        let name = name.filter(|n| !n.is_empty()).unwrap_or(&self.default_name);
+        if name == DEFAULT_GUEST_IMAGE {
+            return GuestImage::load(name, &self.base_dir);
+        }
+        // Names are directory names; keep them from escaping images/
+        if name.contains('/') || name == "." || name == ".." {
+            return None;
+        }
+        GuestImage::load(name, &self.base_dir.join("images").join(name))
+    }
+
+    /// All installed images, sorted by name.
+    pub fn list(&self) -> Vec<GuestImage> {
+        let mut images: Vec<GuestImage> = GuestImage::load(DEFAULT_GUEST_IMAGE, &self.base_dir)
+            .into_iter()
+            .collect();
+        let images_dir = self.base_dir.join("images");
+        match std::fs::read_dir(&images_dir) {
+            Ok(entries) => {
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if name == DEFAULT_GUEST_IMAGE {
+                        warn!(dir = %entry.path().display(), "Ignoring guest image shadowing the default");
+                        continue;
+                    }
+                    if let Some(image) = GuestImage::load(&name, &entry.path()) {
+                        images.push(image);
+                    }
+                }
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => {
+                warn!(dir = %images_dir.display(), error = %e, "Failed to read guest images");
+            }
+        }
+        images.sort_by(|a, b| a.name.cmp(&b.name));
+        images
+    }
+}
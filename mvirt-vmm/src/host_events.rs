@@ -0,0 +1,70 @@
+//! mvirt-vmm on the host's event bus (see [`mvirt_common::events`]).
+//!
+//! VM stops are published for the other daemons, and NICs that mvirt-net
+//! or mvirt-ebpf delete are detached from the VMs still using them, without
+//! waiting for the cplane to notice. The subscription only yields events
+//! of root or this daemon's user, so other local users can't detach NICs.
+
+use std::sync::Arc;
+
+use mvirt_common::events::{Event, EventBus, Subscription};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+use crate::VmEvent;
+use crate::grpc::VmServiceImpl;
+use crate::proto::VmEventType;
+
+/// Publish the stops among the VM lifecycle `events` on `bus`.
+pub fn spawn_publisher(bus: EventBus, mut events: broadcast::Receiver<VmEvent>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    warn!(missed = n, "Host event publisher lagged behind VM events");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            if event.r#type == VmEventType::VmEventStopped as i32 {
+                bus.publish(Event::VmStopped { vm_id: event.vm_id });
+            }
+        }
+    })
+}
+
+/// Detach the NICs deleted by the network daemon that `subscription`
+/// hears of.
+pub fn spawn_listener(
+    service: Arc<VmServiceImpl>,
+    mut subscription: Subscription,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let envelope = match subscription.recv().await {
+                Ok(envelope) => envelope,
+                Err(e) => {
+                    warn!(error = %e, "Host event bus failed; no longer following it");
+                    return;
+                }
+            };
+            match envelope.event {
+                Event::NicDeleted {
+                    nic_id,
+                    mac,
+                    socket,
+                } => {
+                    let Some(sender) = envelope.sender else {
+                        warn!(nic_id = %nic_id, "Ignoring NIC deletion of an unknown sender");
+                        continue;
+                    };
+                    info!(nic_id = %nic_id, source = %envelope.source, pid = sender.pid, "NIC deleted; detaching it from VMs");
+                    service.detach_deleted_nic(&mac, &socket).await;
+                }
+                event => debug!(?event, "Ignoring host event"),
+            }
+        }
+    })
+}
@@ -5,11 +5,15 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Result, anyhow};
+use mvirt_common::jobs::JobHandle;
 use tokio::process::{Child, Command};
 use tokio::sync::{RwLock, mpsc};
 use tracing::{debug, error, info, warn};
 
-use crate::proto::{BootMode, VmConfig};
+use crate::proto::{BootMode, NicConfig, VmConfig};
+use crate::remote_disk;
+use crate::start_queue::{DEFAULT_MAX_PARALLEL_STARTS, DEFAULT_START_SETTLE, StartQueue};
+use crate::start_timing::{self, StartTimer};
 use crate::store::VmStore;
 
 fn firmware_path_default() -> String {
@@ -27,6 +31,11 @@ pub struct Hypervisor {
     /// a cloud-hypervisor process exits unexpectedly. Recv-end can lag — that
     /// is acceptable, the cplane's 30s resync is the safety net.
     events: tokio::sync::broadcast::Sender<crate::proto::VmEvent>,
+    /// Limits how many VMs boot at once.
+    start_queue: StartQueue,
+    /// One memory dump per VM at a time, so a finished dump's resume can't
+    /// unpause the VM while another is still being written.
+    dump_locks: std::sync::Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
 }
 
 impl Hypervisor {
@@ -40,9 +49,23 @@ impl Hypervisor {
             processes: Arc::new(RwLock::new(HashMap::new())),
             store,
             events,
+            start_queue: StartQueue::new(DEFAULT_MAX_PARALLEL_STARTS, DEFAULT_START_SETTLE),
+            dump_locks: Default::default(),
         })
     }
 
+    /// Boot at most `max_parallel` VMs at once (0 = no limit), each keeping
+    /// its slot for `settle` after its process came up.
+    pub fn with_start_limit(mut self, max_parallel: usize, settle: Duration) -> Self {
+        self.start_queue = StartQueue::new(max_parallel, settle);
+        self
+    }
+
+    /// Place of a VM waiting for a boot slot, 1 being next.
+    pub fn start_queue_position(&self, vm_id: &str) -> Option<u32> {
+        self.start_queue.position(vm_id)
+    }
+
     pub fn events_tx(&self) -> tokio::sync::broadcast::Sender<crate::proto::VmEvent> {
         self.events.clone()
     }
@@ -71,6 +94,21 @@ impl Hypervisor {
         &self.data_dir
     }
 
+    /// IDs of the VMs that have a directory: those running, and any whose
+    /// process was left behind.
+    pub async fn vm_dir_ids(&self) -> Vec<String> {
+        let mut ids = Vec::new();
+        let Ok(mut entries) = tokio::fs::read_dir(self.data_dir.join("vm")).await else {
+            return ids;
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Some(id) = entry.file_name().to_str() {
+                ids.push(id.to_string());
+            }
+        }
+        ids
+    }
+
     fn api_socket(&self, vm_id: &str) -> PathBuf {
         self.vm_dir(vm_id).join("api.sock")
     }
@@ -79,6 +117,20 @@ impl Hypervisor {
         self.vm_dir(vm_id).join("cloudinit.iso")
     }
 
+    /// Where the serial console of the VM goes: a socket, or a log file for
+    /// kernel boots.
+    fn serial_path(&self, vm_id: &str, config: &VmConfig) -> PathBuf {
+        match BootMode::try_from(config.boot_mode) {
+            Ok(BootMode::Kernel) => self.vm_dir(vm_id).join("console.log"),
+            _ => self.vm_dir(vm_id).join("serial.sock"),
+        }
+    }
+
+    /// Socket a live migration of the VM is relayed through.
+    pub fn migration_socket(&self, vm_id: &str) -> PathBuf {
+        self.vm_dir(vm_id).join("migration.sock")
+    }
+
     fn firmware_path(&self) -> PathBuf {
         PathBuf::from(firmware_path_default())
     }
@@ -150,12 +202,76 @@ ethernets:
         Ok(())
     }
 
+    /// Boot a VM. Waits for a slot in the start queue first, so starting
+    /// many VMs at once (host boot, batch starts) doesn't swamp the pool.
+    ///
+    /// Cancelling `job` abandons the wait or the disk connects, or kills
+    /// the VM if it was already spawned; either way nothing of the start
+    /// is left behind and the start fails. The queue wait, disk connects
+    /// and spawn are noted as stages on `timer`.
     pub async fn start(
         &self,
         vm_id: &str,
         vm_name: Option<&str>,
         config: &VmConfig,
         vsock_cid: Option<u32>,
+        job: &JobHandle,
+        timer: &mut StartTimer,
+    ) -> Result<()> {
+        if let Some(position) = self.start_queue.position(vm_id) {
+            return Err(anyhow!(
+                "VM {} is already waiting to start (position {})",
+                vm_id,
+                position
+            ));
+        }
+        job.set_phase("queued");
+        timer.stage(start_timing::QUEUED);
+        let slot = tokio::select! {
+            slot = self.start_queue.acquire(vm_id) => slot,
+            _ = job.wait_cancelled() => return Err(anyhow!("start cancelled")),
+        };
+
+        // Remote disks are connected first, their devices replace the URLs
+        job.set_phase("connecting disks");
+        timer.stage(start_timing::DISK_CONNECT);
+        let disk_paths = tokio::select! {
+            paths = remote_disk::connect_all(&config.disks) => paths?,
+            _ = job.wait_cancelled() => {
+                remote_disk::disconnect_all(&config.disks).await;
+                return Err(anyhow!("start cancelled"));
+            }
+        };
+        job.set_phase("spawning");
+        timer.stage(start_timing::SPAWN);
+        let result = self
+            .spawn(vm_id, vm_name, config, vsock_cid, &disk_paths)
+            .await;
+        match result {
+            Ok(()) if job.is_cancelled() => {
+                if let Err(e) = self.kill(vm_id).await {
+                    warn!(vm_id = %vm_id, error = %e, "Failed to kill VM of a cancelled start");
+                }
+                Err(anyhow!("start cancelled"))
+            }
+            Ok(()) => {
+                slot.release_after_settle();
+                Ok(())
+            }
+            Err(e) => {
+                remote_disk::disconnect_all(&config.disks).await;
+                Err(e)
+            }
+        }
+    }
+
+    async fn spawn(
+        &self,
+        vm_id: &str,
+        vm_name: Option<&str>,
+        config: &VmConfig,
+        vsock_cid: Option<u32>,
+        disk_paths: &[String],
     ) -> Result<()> {
         let vm_dir = self.vm_dir(vm_id);
         let api_socket = self.api_socket(vm_id);
@@ -179,10 +295,12 @@ ethernets:
 
         // Boot configuration and serial output based on boot_mode
         let serial_path = match BootMode::try_from(config.boot_mode).unwrap_or(BootMode::Disk) {
-            BootMode::Disk | BootMode::Unspecified => {
-                // Disk boot: use rust-hypervisor-firmware + serial socket (bidirectional)
+            BootMode::Disk | BootMode::Unspecified | BootMode::Network => {
+                // Disk boot: use rust-hypervisor-firmware + serial socket (bidirectional).
+                // Network boot relies on the EDK2 build (CLOUDHV.fd), which falls back
+                // to PXE / HTTP boot over virtio-net when no bootable disk is attached.
                 cmd.arg("--kernel").arg(self.firmware_path());
-                let serial_socket = vm_dir.join("serial.sock");
+                let serial_socket = self.serial_path(vm_id, config);
                 cmd.arg("--serial")
                     .arg(format!("socket={}", serial_socket.display()));
                 serial_socket
@@ -194,7 +312,7 @@ ethernets:
                 } else {
                     return Err(anyhow!("Kernel boot mode requires kernel path"));
                 }
-                let console_log = vm_dir.join("console.log");
+                let console_log = self.serial_path(vm_id, config);
                 cmd.arg("--serial")
                     .arg(format!("file={}", console_log.display()));
                 console_log
@@ -202,35 +320,20 @@ ethernets:
         };
         cmd.arg("--console").arg("off");
 
-        let cpus_arg = if config.nested_virt {
-            format!("boot={},nested=on", config.vcpus)
-        } else {
-            format!("boot={}", config.vcpus)
-        };
-        cmd.arg("--cpus").arg(cpus_arg);
-
-        // Check if any NIC uses vhost-user (requires shared memory)
-        let uses_vhost_user = config.nics.iter().any(|nic| nic.vhost_socket.is_some());
+        cmd.arg("--cpus").arg(cpus_arg(config));
 
-        let memory_arg = if Self::hugepages_available(config.memory_mb) {
+        // Hot-plugged memory comes from the same pool as the boot memory
+        let hugepages = Self::hugepages_available(config.max_memory_mb.max(config.memory_mb));
+        if hugepages {
             info!("Using hugepages for VM memory ({}MB)", config.memory_mb);
-            if uses_vhost_user {
-                format!("size={}M,hugepages=on,shared=on", config.memory_mb)
-            } else {
-                format!("size={}M,hugepages=on", config.memory_mb)
-            }
-        } else if uses_vhost_user {
-            format!("size={}M,shared=on", config.memory_mb)
-        } else {
-            format!("size={}M", config.memory_mb)
-        };
-        cmd.arg("--memory").arg(memory_arg);
+        }
+        cmd.arg("--memory").arg(memory_arg(config, hugepages));
 
         // Collect all disks
         let mut disk_args: Vec<String> = Vec::new();
 
-        for disk in &config.disks {
-            let mut disk_arg = format!("path={}", disk.path);
+        for (disk, path) in config.disks.iter().zip(disk_paths) {
+            let mut disk_arg = format!("path={}", path);
             if disk.readonly {
                 disk_arg.push_str(",readonly=on");
             }
@@ -263,42 +366,21 @@ ethernets:
 
         // Add network interfaces
         for nic in &config.nics {
-            // Check for direct TAP device first
-            if let Some(ref tap_name) = nic.tap {
-                let mut net_arg = format!("tap={}", tap_name);
-                if let Some(ref mac) = nic.mac {
-                    net_arg.push_str(&format!(",mac={}", mac));
+            let mut net_arg = match net_backend(nic) {
+                Some(NetBackend::Tap(tap_name)) => {
+                    info!(vm_id = %vm_id, tap = %tap_name, "Using TAP network");
+                    format!("tap={}", tap_name)
                 }
-                cmd.arg("--net").arg(net_arg);
-                info!(vm_id = %vm_id, tap = %tap_name, "Using TAP network");
-            } else if let Some(ref nic_spec) = nic.vhost_socket {
-                if let Some(socket_path) = nic_spec.strip_prefix("vhost-user:") {
-                    // vhost-user (mvirt-net)
-                    let mut net_arg =
-                        format!("vhost_user=true,socket={},num_queues=2", socket_path);
-                    if let Some(ref mac) = nic.mac {
-                        net_arg.push_str(&format!(",mac={}", mac));
-                    }
-                    cmd.arg("--net").arg(net_arg);
+                Some(NetBackend::VhostUser(socket_path)) => {
                     info!(vm_id = %vm_id, socket = %socket_path, "Using vhost-user network");
-                } else if let Some(tap_name) = nic_spec.strip_prefix("tap:") {
-                    // TAP device (mvirt-ebpf) - legacy format in vhost_socket
-                    let mut net_arg = format!("tap={}", tap_name);
-                    if let Some(ref mac) = nic.mac {
-                        net_arg.push_str(&format!(",mac={}", mac));
-                    }
-                    cmd.arg("--net").arg(net_arg);
-                    info!(vm_id = %vm_id, tap = %tap_name, "Using TAP network");
-                } else {
-                    // Fallback: assume vhost-user without prefix (backwards compatibility)
-                    let mut net_arg = format!("vhost_user=true,socket={},num_queues=2", nic_spec);
-                    if let Some(ref mac) = nic.mac {
-                        net_arg.push_str(&format!(",mac={}", mac));
-                    }
-                    cmd.arg("--net").arg(net_arg);
-                    warn!(vm_id = %vm_id, socket = %nic_spec, "Using vhost-user (no prefix)");
+                    format!("vhost_user=true,socket={},num_queues=2", socket_path)
                 }
+                None => continue,
+            };
+            if let Some(ref mac) = nic.mac {
+                net_arg.push_str(&format!(",mac={}", mac));
             }
+            cmd.arg("--net").arg(net_arg);
         }
 
         // Add vsock device for MicroVMs (host<->guest communication)
@@ -436,6 +518,7 @@ ethernets:
     async fn cleanup(&self, vm_id: &str) -> Result<()> {
         // Remove from tracked processes
         self.processes.write().await.remove(vm_id);
+        self.disconnect_remote_disks(vm_id).await;
 
         // Clear runtime from DB
         self.store.clear_runtime(vm_id).await?;
@@ -449,7 +532,8 @@ ethernets:
         Ok(())
     }
 
-    async fn is_running(&self, vm_id: &str) -> bool {
+    /// Whether a cloud-hypervisor process of the VM is alive.
+    pub async fn is_running(&self, vm_id: &str) -> bool {
         let processes = self.processes.read().await;
         if let Some(child) = processes.get(vm_id) {
             // Check if process is still running
@@ -467,23 +551,379 @@ ethernets:
     }
 
     async fn send_shutdown(&self, api_socket: &Path) -> Result<()> {
-        use hyper::body::Bytes;
-        use hyper::{Method, Request};
-        use hyper_util::client::legacy::Client;
-        use hyper_util::rt::TokioExecutor;
+        self.send_vm_action(api_socket, "vm.shutdown").await
+    }
+
+    /// PUT a body-less VM action (e.g. `vm.pause`) to the cloud-hypervisor API.
+    async fn send_vm_action(&self, api_socket: &Path, action: &str) -> Result<()> {
+        api_request(api_socket, hyper::Method::PUT, action, None).await?;
+        Ok(())
+    }
+
+    /// cloud-hypervisor's `vm.info` of a running VM: its config, state and
+    /// actual memory size.
+    pub async fn vm_info(&self, vm_id: &str) -> Result<serde_json::Value> {
+        let api_socket = self.api_socket(vm_id);
+        if !api_socket.exists() {
+            return Err(anyhow!("VM {} is not running", vm_id));
+        }
+        let body = api_request(&api_socket, hyper::Method::GET, "vm.info", None).await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    /// A path for a new memory dump of the VM, unique to the dump.
+    pub fn coredump_path(&self, vm_id: &str) -> PathBuf {
+        self.vm_dir(vm_id)
+            .join(format!("memory-{}.core", uuid::Uuid::new_v4()))
+    }
+
+    /// Write the memory of a running VM to an ELF core file at `path`
+    /// (see [`Self::coredump_path`]). The VM is paused while it is written;
+    /// dumps of the same VM wait for each other.
+    pub async fn coredump(&self, vm_id: &str, path: &Path) -> Result<()> {
+        let lock = self
+            .dump_locks
+            .lock()
+            .unwrap()
+            .entry(vm_id.to_string())
+            .or_default()
+            .clone();
+        let guard = lock.lock().await;
+        let result = self.coredump_locked(vm_id, path).await;
+        drop(guard);
+
+        let mut locks = self.dump_locks.lock().unwrap();
+        // Only the map's and ours: nobody else is waiting
+        if Arc::strong_count(&lock) == 2 {
+            locks.remove(vm_id);
+        }
+        result
+    }
+
+    async fn coredump_locked(&self, vm_id: &str, path: &Path) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        let was_paused = self.vm_info(vm_id).await?["state"] == "Paused";
+
+        if !was_paused {
+            self.send_vm_action(&api_socket, "vm.pause").await?;
+        }
+        info!(vm_id = %vm_id, path = %path.display(), "Dumping guest memory");
+        let body = serde_json::json!({
+            "destination_url": format!("file://{}", path.display()),
+        });
+        let result = api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.coredump",
+            Some(body.to_string()),
+        )
+        .await;
+        if !was_paused && let Err(e) = self.send_vm_action(&api_socket, "vm.resume").await {
+            error!(vm_id = %vm_id, error = %e, "Failed to resume VM after memory dump");
+        }
+        result?;
+        Ok(())
+    }
+
+    /// Freeze the VM's vCPUs. Its process and devices stay up.
+    pub async fn pause(&self, vm_id: &str) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        if !api_socket.exists() {
+            return Err(anyhow!("VM {} is not running", vm_id));
+        }
+        info!(vm_id = %vm_id, "Pausing VM");
+        self.send_vm_action(&api_socket, "vm.pause").await
+    }
+
+    /// Let a paused VM's vCPUs run again.
+    pub async fn resume(&self, vm_id: &str) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        if !api_socket.exists() {
+            return Err(anyhow!("VM {} is not running", vm_id));
+        }
+        info!(vm_id = %vm_id, "Resuming VM");
+        self.send_vm_action(&api_socket, "vm.resume").await
+    }
+
+    /// Migrate a running VM to another host over `destination`, a socket
+    /// the caller listens on. Returns once the VM runs on the other side;
+    /// the local process is done then and exits.
+    pub async fn send_migration(&self, vm_id: &str, destination: &Path) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        if !api_socket.exists() {
+            return Err(anyhow!("VM {} is not running", vm_id));
+        }
+        info!(vm_id = %vm_id, destination = %destination.display(), "Sending VM migration");
+        let body = serde_json::json!({
+            "destination_url": format!("unix:{}", destination.display()),
+        });
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.send-migration",
+            Some(body.to_string()),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Spawn a cloud-hypervisor process without a VM, for a VM migrated in
+    /// by [`Self::receive_migration`] or restored by [`Self::restore`]. The
+    /// devices come with the migrated or snapshotted config, so the VM
+    /// directory has to be at the same path as before; the cloud-init ISO
+    /// is recreated in it.
+    pub async fn spawn_empty(
+        &self,
+        vm_id: &str,
+        vm_name: Option<&str>,
+        config: &VmConfig,
+    ) -> Result<()> {
+        let vm_dir = self.vm_dir(vm_id);
+        let api_socket = self.api_socket(vm_id);
+        tokio::fs::create_dir_all(&vm_dir).await?;
+        let _ = tokio::fs::remove_file(&api_socket).await;
+        let _ = tokio::fs::remove_file(self.migration_socket(vm_id)).await;
+
+        if let Some(user_data) = &config.user_data {
+            self.create_cloudinit_iso(vm_id, vm_name, user_data).await?;
+        }
+
+        let mut cmd = Command::new("cloud-hypervisor");
+        cmd.arg("--api-socket")
+            .arg(format!("path={}", api_socket.display()));
+        cmd.stdout(std::fs::File::create(
+            vm_dir.join("cloud-hypervisor.stdout"),
+        )?);
+        cmd.stderr(std::fs::File::create(
+            vm_dir.join("cloud-hypervisor.stderr"),
+        )?);
+
+        info!(vm_id = %vm_id, cmd = ?cmd.as_std(), "Spawning cloud-hypervisor without a VM");
+        let child = cmd.spawn()?;
+        self.processes
+            .write()
+            .await
+            .insert(vm_id.to_string(), child);
 
-        let connector = hyperlocal::UnixConnector;
-        let client: Client<_, http_body_util::Empty<Bytes>> =
-            Client::builder(TokioExecutor::new()).build(connector);
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(5);
+        while !api_socket.exists() {
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow!("cloud-hypervisor did not open its API socket"));
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        Ok(())
+    }
+
+    /// Let the process of [`Self::spawn_empty`] take the VM in over
+    /// `receiver`, a socket it listens on until the caller connects.
+    /// Returns once the VM runs here.
+    pub async fn receive_migration(
+        &self,
+        vm_id: &str,
+        config: &VmConfig,
+        receiver: &Path,
+    ) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        info!(vm_id = %vm_id, receiver = %receiver.display(), "Receiving VM migration");
+        let body = serde_json::json!({
+            "receiver_url": format!("unix:{}", receiver.display()),
+        });
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.receive-migration",
+            Some(body.to_string()),
+        )
+        .await?;
+        self.record_runtime(vm_id, config).await
+    }
+
+    /// Grow a running VM to `vcpus` and `memory_mb`, up to the maximums it
+    /// booted with. vCPUs are hot-plugged, memory is added with virtio-mem.
+    pub async fn resize(
+        &self,
+        vm_id: &str,
+        vcpus: Option<u32>,
+        memory_mb: Option<u64>,
+    ) -> Result<()> {
+        let api_socket = self.running_api_socket(vm_id)?;
+        let mut body = serde_json::json!({});
+        if let Some(vcpus) = vcpus {
+            body["desired_vcpus"] = vcpus.into();
+        }
+        if let Some(memory_mb) = memory_mb {
+            body["desired_ram"] = (memory_mb * 1024 * 1024).into();
+        }
+        info!(vm_id = %vm_id, resize = %body, "Resizing VM");
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.resize",
+            Some(body.to_string()),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Hot-plug a disk into a running VM. `path` is the host path, the
+    /// device of a network-attached disk.
+    pub async fn add_disk(&self, vm_id: &str, path: &str, readonly: bool) -> Result<()> {
+        let api_socket = self.running_api_socket(vm_id)?;
+        info!(vm_id = %vm_id, path = %path, "Hot-plugging disk");
+        let body = serde_json::json!({ "path": path, "readonly": readonly });
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.add-disk",
+            Some(body.to_string()),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Hot-plug a NIC into a running VM.
+    pub async fn add_nic(&self, vm_id: &str, nic: &NicConfig) -> Result<()> {
+        let api_socket = self.running_api_socket(vm_id)?;
+        let mut body = match net_backend(nic) {
+            Some(NetBackend::Tap(tap)) => serde_json::json!({ "tap": tap }),
+            Some(NetBackend::VhostUser(socket)) => {
+                // The backend maps guest memory, which is only shared if the
+                // VM booted with a vhost-user NIC
+                let info = self.vm_info(vm_id).await?;
+                if info["config"]["memory"]["shared"] != true {
+                    return Err(anyhow!(
+                        "VM {} has no shared memory for a vhost-user NIC; \
+                         it needs one at boot",
+                        vm_id
+                    ));
+                }
+                serde_json::json!({
+                    "vhost_user": true,
+                    "vhost_socket": socket,
+                    "num_queues": 2,
+                })
+            }
+            None => return Err(anyhow!("NIC has neither a TAP nor a vhost-user socket")),
+        };
+        if let Some(mac) = &nic.mac {
+            body["mac"] = mac.as_str().into();
+        }
+        info!(vm_id = %vm_id, nic = %body, "Hot-plugging NIC");
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.add-net",
+            Some(body.to_string()),
+        )
+        .await?;
+        Ok(())
+    }
 
-        let uri = hyperlocal::Uri::new(api_socket, "/api/v1/vm.shutdown");
+    /// Unplug the disk at host path `path` from a running VM.
+    pub async fn remove_disk(&self, vm_id: &str, path: &str) -> Result<()> {
+        let info = self.vm_info(vm_id).await?;
+        let id = disk_device_id(&info, path)
+            .ok_or_else(|| anyhow!("VM {} has no disk {}", vm_id, path))?;
+        self.remove_device(vm_id, &id).await
+    }
 
-        let req = Request::builder()
-            .method(Method::PUT)
-            .uri(uri)
-            .body(http_body_util::Empty::new())?;
+    /// Unplug a NIC from a running VM.
+    pub async fn remove_nic(&self, vm_id: &str, nic: &NicConfig) -> Result<()> {
+        let info = self.vm_info(vm_id).await?;
+        let id =
+            nic_device_id(&info, nic).ok_or_else(|| anyhow!("VM {} has no such NIC", vm_id))?;
+        self.remove_device(vm_id, &id).await
+    }
 
-        let _resp = client.request(req).await?;
+    /// Ask the guest to release device `id`; cloud-hypervisor removes it
+    /// once the guest has.
+    async fn remove_device(&self, vm_id: &str, id: &str) -> Result<()> {
+        let api_socket = self.running_api_socket(vm_id)?;
+        info!(vm_id = %vm_id, device = %id, "Unplugging device");
+        let body = serde_json::json!({ "id": id });
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.remove-device",
+            Some(body.to_string()),
+        )
+        .await?;
+        Ok(())
+    }
+
+    fn running_api_socket(&self, vm_id: &str) -> Result<PathBuf> {
+        let api_socket = self.api_socket(vm_id);
+        if !api_socket.exists() {
+            return Err(anyhow!("VM {} is not running", vm_id));
+        }
+        Ok(api_socket)
+    }
+
+    /// Whether the vCPUs of a running VM are paused.
+    pub async fn is_paused(&self, vm_id: &str) -> Result<bool> {
+        Ok(self.vm_info(vm_id).await?["state"] == "Paused")
+    }
+
+    /// Write the memory and device state of a paused VM to `dir`.
+    pub async fn snapshot(&self, vm_id: &str, dir: &Path) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        if !api_socket.exists() {
+            return Err(anyhow!("VM {} is not running", vm_id));
+        }
+        info!(vm_id = %vm_id, dir = %dir.display(), "Snapshotting VM");
+        let body = serde_json::json!({
+            "destination_url": format!("file://{}", dir.display()),
+        });
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.snapshot",
+            Some(body.to_string()),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Let the process of [`Self::spawn_empty`] load the VM from the state
+    /// in `dir` and run it.
+    pub async fn restore(&self, vm_id: &str, config: &VmConfig, dir: &Path) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        info!(vm_id = %vm_id, dir = %dir.display(), "Restoring VM");
+        let body = serde_json::json!({
+            "source_url": format!("file://{}", dir.display()),
+        });
+        api_request(
+            &api_socket,
+            hyper::Method::PUT,
+            "vm.restore",
+            Some(body.to_string()),
+        )
+        .await?;
+        self.record_runtime(vm_id, config).await?;
+        // A restored VM comes up paused, as it was snapshotted
+        self.send_vm_action(&api_socket, "vm.resume").await
+    }
+
+    /// Store the runtime of a VM that came up in the process of
+    /// [`Self::spawn_empty`].
+    async fn record_runtime(&self, vm_id: &str, config: &VmConfig) -> Result<()> {
+        let api_socket = self.api_socket(vm_id);
+        let pid = self
+            .processes
+            .read()
+            .await
+            .get(vm_id)
+            .and_then(|child| child.id())
+            .ok_or_else(|| anyhow!("cloud-hypervisor of VM {} exited", vm_id))?;
+        self.store
+            .set_runtime(
+                vm_id,
+                pid,
+                api_socket.to_str().unwrap(),
+                self.serial_path(vm_id, config).to_str().unwrap(),
+            )
+            .await?;
         Ok(())
     }
 
@@ -568,6 +1008,7 @@ ethernets:
         use crate::proto::{VmEvent, VmEventType, VmState};
 
         self.processes.write().await.remove(vm_id);
+        self.disconnect_remote_disks(vm_id).await;
         self.store.clear_runtime(vm_id).await?;
         self.store.update_state(vm_id, VmState::Stopped).await?;
 
@@ -586,6 +1027,7 @@ ethernets:
             r#type: VmEventType::VmEventStopped as i32,
             timestamp: chrono::Utc::now().timestamp(),
             vm,
+            drift: None,
         });
 
         // Cleanup socket dir
@@ -597,6 +1039,13 @@ ethernets:
         Ok(())
     }
 
+    /// Disconnect the network-attached disks of a stopped VM.
+    async fn disconnect_remote_disks(&self, vm_id: &str) {
+        if let Ok(Some(vm)) = self.store.get(vm_id).await {
+            remote_disk::disconnect_all(&vm.config.disks).await;
+        }
+    }
+
     /// Get the console path (PTY) for the VM from runtime info
     pub async fn console_path(&self, vm_id: &str) -> Option<PathBuf> {
         if let Ok(Some(runtime)) = self.store.get_runtime(vm_id).await {
@@ -668,6 +1117,7 @@ ethernets:
                 }
 
                 // Clean up
+                remote_disk::disconnect_all(&vm.config.disks).await;
                 self.store.update_state(&vm.id, VmState::Stopped).await?;
                 self.store.clear_runtime(&vm.id).await?;
 
@@ -684,6 +1134,149 @@ ethernets:
     }
 }
 
+/// Send a request to a cloud-hypervisor API socket; returns the response
+/// body.
+async fn api_request(
+    api_socket: &Path,
+    method: hyper::Method,
+    action: &str,
+    body: Option<String>,
+) -> Result<hyper::body::Bytes> {
+    use http_body_util::{BodyExt, Full};
+    use hyper::Request;
+    use hyper::body::Bytes;
+    use hyper_util::client::legacy::Client;
+    use hyper_util::rt::TokioExecutor;
+
+    let connector = hyperlocal::UnixConnector;
+    let client: Client<_, Full<Bytes>> = Client::builder(TokioExecutor::new()).build(connector);
+
+    let uri = hyperlocal::Uri::new(api_socket, &format!("/api/v1/{}", action));
+
+    let mut req = Request::builder().method(method).uri(uri);
+    if body.is_some() {
+        req = req.header("Content-Type", "application/json");
+    }
+    let req = req.body(Full::new(Bytes::from(body.unwrap_or_default())))?;
+
+    let resp = client.request(req).await?;
+    let status = resp.status();
+    let body = resp.into_body().collect().await?.to_bytes();
+    if !status.is_success() {
+        return Err(anyhow!(
+            "{} failed with HTTP {}: {}",
+            action,
+            status,
+            String::from_utf8_lossy(&body).trim()
+        ));
+    }
+    Ok(body)
+}
+
+/// `--cpus` of a VM: vCPUs beyond the boot count up to `max_vcpus` can
+/// be hot-plugged.
+fn cpus_arg(config: &VmConfig) -> String {
+    let mut arg = format!("boot={}", config.vcpus);
+    if config.max_vcpus > config.vcpus {
+        arg.push_str(&format!(",max={}", config.max_vcpus));
+    }
+    if config.nested_virt {
+        arg.push_str(",nested=on");
+    }
+    arg
+}
+
+/// `--memory` of a VM. Memory beyond the boot size up to `max_memory_mb`
+/// is a virtio-mem region the VM grows into.
+fn memory_arg(config: &VmConfig, hugepages: bool) -> String {
+    let mut arg = format!("size={}M", config.memory_mb);
+    if hugepages {
+        arg.push_str(",hugepages=on");
+    }
+    // vhost-user backends map guest memory
+    if config.nics.iter().any(|nic| nic.vhost_socket.is_some()) {
+        arg.push_str(",shared=on");
+    }
+    if config.max_memory_mb > config.memory_mb {
+        arg.push_str(&format!(
+            ",hotplug_method=virtio-mem,hotplug_size={}M",
+            config.max_memory_mb - config.memory_mb
+        ));
+    }
+    arg
+}
+
+/// What a NIC is connected to on the host.
+#[derive(Debug, PartialEq, Eq)]
+enum NetBackend<'a> {
+    /// TAP device (manual or mvirt-ebpf)
+    Tap(&'a str),
+    /// vhost-user socket (mvirt-net)
+    VhostUser(&'a str),
+}
+
+/// The backend of a NIC. `vhost_socket` takes `vhost-user:<path>`,
+/// `tap:<name>` (the legacy mvirt-ebpf format) or a bare socket path.
+fn net_backend(nic: &NicConfig) -> Option<NetBackend<'_>> {
+    if let Some(tap) = &nic.tap {
+        return Some(NetBackend::Tap(tap));
+    }
+    let spec = nic.vhost_socket.as_deref()?;
+    Some(if let Some(path) = spec.strip_prefix("vhost-user:") {
+        NetBackend::VhostUser(path)
+    } else if let Some(tap) = spec.strip_prefix("tap:") {
+        NetBackend::Tap(tap)
+    } else {
+        NetBackend::VhostUser(spec)
+    })
+}
+
+/// Whether `a` and `b` are attached to the same host device: the same MAC
+/// if both have one, else the same TAP or socket.
+pub fn same_nic(a: &NicConfig, b: &NicConfig) -> bool {
+    match (&a.mac, &b.mac) {
+        (Some(x), Some(y)) => x.eq_ignore_ascii_case(y),
+        _ => net_backend(a).is_some() && net_backend(a) == net_backend(b),
+    }
+}
+
+/// cloud-hypervisor's ID of the disk at host path `path`, from `vm.info`.
+fn disk_device_id(info: &serde_json::Value, path: &str) -> Option<String> {
+    info["config"]["disks"]
+        .as_array()?
+        .iter()
+        .find(|d| d["path"] == path)
+        .and_then(|d| d["id"].as_str())
+        .map(String::from)
+}
+
+/// cloud-hypervisor's ID of `nic`, from `vm.info`.
+fn nic_device_id(info: &serde_json::Value, nic: &NicConfig) -> Option<String> {
+    info["config"]["net"]
+        .as_array()?
+        .iter()
+        .find(|n| {
+            let attached = NicConfig {
+                tap: n["tap"].as_str().map(String::from),
+                mac: n["mac"].as_str().map(String::from),
+                vhost_socket: n["vhost_socket"].as_str().map(String::from),
+            };
+            // cloud-hypervisor makes up a MAC for NICs configured without one
+            match &nic.mac {
+                Some(_) => same_nic(nic, &attached),
+                None => same_nic(
+                    nic,
+                    &NicConfig {
+                        mac: None,
+                        ..attached
+                    },
+                ),
+            }
+        })
+        .and_then(|n| n["id"].as_str())
+        .map(String::from)
+}
+
 fn is_process_alive(pid: u32) -> bool {
     nix::sys::signal::kill(
         nix::unistd::Pid::from_raw(pid as i32),
@@ -691,3 +1284,90 @@ fn is_process_alive(pid: u32) -> bool {
     )
     .is_ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nic(tap: Option<&str>, mac: Option<&str>, vhost_socket: Option<&str>) -> NicConfig {
+        NicConfig {
+            tap: tap.map(String::from),
+            mac: mac.map(String::from),
+            vhost_socket: vhost_socket.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_resource_args() {
+        let mut config = VmConfig {
+            vcpus: 2,
+            memory_mb: 1024,
+            nested_virt: true,
+            ..Default::default()
+        };
+        assert_eq!(cpus_arg(&config), "boot=2,nested=on");
+        assert_eq!(memory_arg(&config, false), "size=1024M");
+
+        config.max_vcpus = 8;
+        config.max_memory_mb = 4096;
+        config.nics = vec![nic(None, None, Some("/run/mvirt/net/a.sock"))];
+        assert_eq!(cpus_arg(&config), "boot=2,max=8,nested=on");
+        assert_eq!(
+            memory_arg(&config, true),
+            "size=1024M,hugepages=on,shared=on,hotplug_method=virtio-mem,hotplug_size=3072M"
+        );
+    }
+
+    #[test]
+    fn test_net_backend() {
+        assert_eq!(
+            net_backend(&nic(Some("tap0"), None, None)),
+            Some(NetBackend::Tap("tap0"))
+        );
+        assert_eq!(
+            net_backend(&nic(None, None, Some("vhost-user:/run/mvirt/net/a.sock"))),
+            Some(NetBackend::VhostUser("/run/mvirt/net/a.sock"))
+        );
+        assert_eq!(
+            net_backend(&nic(None, None, Some("tap:tap_abc"))),
+            Some(NetBackend::Tap("tap_abc"))
+        );
+        assert_eq!(
+            net_backend(&nic(None, None, Some("/run/b.sock"))),
+            Some(NetBackend::VhostUser("/run/b.sock"))
+        );
+        assert_eq!(
+            net_backend(&nic(None, Some("02:00:00:00:00:01"), None)),
+            None
+        );
+    }
+
+    #[test]
+    fn test_device_ids() {
+        let info = serde_json::json!({
+            "config": {
+                "disks": [
+                    {"path": "/dev/zvol/mvirt/1", "id": "_disk0"},
+                    {"path": "/dev/nvme1n1", "id": "_disk1"},
+                ],
+                "net": [
+                    {"tap": "tap_abc", "mac": "02:00:00:00:00:01", "id": "_net2"},
+                    {"vhost_socket": "/run/b.sock", "mac": "52:54:00:12:34:56", "id": "_net3"},
+                ],
+            }
+        });
+        assert_eq!(
+            disk_device_id(&info, "/dev/nvme1n1").as_deref(),
+            Some("_disk1")
+        );
+        assert_eq!(disk_device_id(&info, "/dev/sdz"), None);
+
+        let by_mac = nic(None, Some("02:00:00:00:00:01"), Some("tap:tap_abc"));
+        assert_eq!(nic_device_id(&info, &by_mac).as_deref(), Some("_net2"));
+        // A made-up MAC doesn't hide a NIC configured without one
+        let by_socket = nic(None, None, Some("vhost-user:/run/b.sock"));
+        assert_eq!(nic_device_id(&info, &by_socket).as_deref(), Some("_net3"));
+        let other = nic(None, Some("02:00:00:00:00:09"), None);
+        assert_eq!(nic_device_id(&info, &other), None);
+    }
+}
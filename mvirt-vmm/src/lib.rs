@@ -2,10 +2,25 @@
 //!
 //! This module exposes the VMM components for integration testing.
 
+pub mod console;
+pub mod dependents;
+pub mod drift;
 pub mod grpc;
+pub mod guest_agent;
+pub mod guest_image;
+pub mod host_events;
 pub mod hypervisor;
+pub mod memory_dump;
+pub mod migration;
+pub mod peer;
+pub mod pod_logs;
 pub mod pod_service;
 pub mod ready_listener;
+pub mod remote_disk;
+pub mod resize;
+pub mod snapshot;
+pub mod start_queue;
+pub mod start_timing;
 pub mod store;
 pub mod system_info;
 pub mod vsock_client;
@@ -14,4 +29,14 @@ pub mod proto {
     tonic::include_proto!("mvirt");
 }
 
+/// mvirt-zfs client, for volumes owned by VMs and pods.
+pub mod zfs_proto {
+    tonic::include_proto!("mvirt.zfs");
+}
+
+/// mvirt-net / mvirt-ebpf client, for NICs owned by VMs and pods.
+pub mod net_proto {
+    tonic::include_proto!("mvirt.net");
+}
+
 pub use proto::VmEvent;
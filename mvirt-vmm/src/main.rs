@@ -1,15 +1,25 @@
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::Parser;
+use mvirt_common::events::EventBus;
+use mvirt_common::limits::{GrpcLimitLayer, LimitConfig};
+use mvirt_common::request_id::GrpcRequestIdLayer;
 use mvirt_log::{create_audit_logger, tls_config_from_paths};
+use mvirt_vmm::console::{ConsoleBufferConfig, ConsoleHub};
+use mvirt_vmm::dependents::Dependents;
+use mvirt_vmm::drift::{DriftChecker, DriftMonitor};
 use mvirt_vmm::grpc::VmServiceImpl;
+use mvirt_vmm::guest_image::{DEFAULT_GUEST_IMAGE, GuestImageRegistry};
+use mvirt_vmm::host_events;
 use mvirt_vmm::hypervisor::Hypervisor;
 use mvirt_vmm::pod_service::PodServiceImpl;
 use mvirt_vmm::proto::pod_service_server::PodServiceServer;
 use mvirt_vmm::proto::vm_service_server::VmServiceServer;
+use mvirt_vmm::resize::DEFAULT_NODE_RESOURCES;
 use mvirt_vmm::store::VmStore;
-use tonic::transport::Server;
+use tonic::transport::{Endpoint, Server};
 use tracing::{info, warn};
 
 #[derive(Parser)]
@@ -62,6 +72,66 @@ struct Args {
     /// Disable mTLS to mvirt-log (talk plain h2c). Dev/loopback only.
     #[arg(long, env = "MVIRT_LOG_INSECURE")]
     log_insecure: bool,
+
+    /// Recent console output kept in memory per VM, in KiB
+    #[arg(long, default_value = "256")]
+    console_buffer_kb: usize,
+
+    /// Also spool console output to <data-dir>/console, rotating each
+    /// VM's log at this size in MiB (0 = no spooling)
+    #[arg(long, default_value = "0")]
+    console_spool_mb: u64,
+
+    /// Directory holding pod guest images (kernel, initramfs, mvirt-one
+    /// rootfs); named versions live in its images/ subdirectory
+    #[arg(long, default_value = "/usr/share/mvirt/one")]
+    guest_image_dir: PathBuf,
+
+    /// Guest image for pods that don't request one
+    #[arg(long, default_value = DEFAULT_GUEST_IMAGE)]
+    default_guest_image: String,
+
+    /// mvirt-zfs address, for deleting volumes owned by deleted VMs and
+    /// pods and for snapshotting VM volumes (empty = don't cascade to
+    /// volumes; only VMs without writable disks can be snapshotted)
+    #[arg(long, default_value = "http://[::1]:50053")]
+    zfs_server: String,
+
+    /// mvirt-ebpf / mvirt-net address, for deleting NICs owned by deleted
+    /// VMs and pods (empty = don't cascade to NICs)
+    #[arg(long, default_value = "http://[::1]:50054")]
+    net_server: String,
+
+    /// Serve guest memory dumps and introspection (DumpGuestMemory,
+    /// GetGuestMemoryInfo) for debugging
+    #[arg(long)]
+    allow_memory_dump: bool,
+
+    /// Local users (by uid) that may use them; repeat or comma-separate
+    #[arg(long, value_delimiter = ',', default_value = "0")]
+    memory_dump_uid: Vec<u32>,
+
+    /// VMs booting at the same time; further starts wait their turn
+    /// (0 = no limit)
+    #[arg(long, default_value = "4")]
+    max_parallel_starts: usize,
+
+    /// Seconds a started VM counts as booting towards --max-parallel-starts
+    #[arg(long, default_value = "10")]
+    start_settle_secs: u64,
+
+    /// Seconds between checks of the store against the running
+    /// cloud-hypervisor processes (0 = only check on GetDrift requests)
+    #[arg(long, default_value = "60")]
+    drift_check_secs: u64,
+
+    /// Host resources sampled by mvirt-node, for checking VM resizes
+    /// against (empty = don't check)
+    #[arg(long, default_value = DEFAULT_NODE_RESOURCES)]
+    node_resources: String,
+
+    #[command(flatten)]
+    limits: LimitConfig,
 }
 
 #[tokio::main]
@@ -86,7 +156,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Initialize hypervisor
     let hypervisor = Arc::new(
-        Hypervisor::new(args.data_dir.clone(), store.clone(), vm_events_tx.clone()).await?,
+        Hypervisor::new(args.data_dir.clone(), store.clone(), vm_events_tx.clone())
+            .await?
+            .with_start_limit(
+                args.max_parallel_starts,
+                Duration::from_secs(args.start_settle_secs),
+            ),
     );
 
     // Recover VMs from previous run
@@ -109,20 +184,107 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     };
     let audit = create_audit_logger(args.log_endpoint.clone(), "vmm", tls);
 
-    // Create gRPC services
-    let vm_service = VmServiceImpl::new(
+    // Console hub: records every running VM's console from boot so history
+    // is available without anyone attached.
+    let console = Arc::new(ConsoleHub::new(
+        audit.clone(),
+        ConsoleBufferConfig {
+            capacity: args.console_buffer_kb * 1024,
+            spool_dir: (args.console_spool_mb > 0).then(|| args.data_dir.join("console")),
+            spool_limit: args.console_spool_mb * 1024 * 1024,
+        },
+    ));
+    let _console_listener = console
+        .clone()
+        .spawn_event_listener(hypervisor.clone(), vm_events_tx.subscribe());
+    for vm in store.list_all().await? {
+        if vm.state == mvirt_vmm::proto::VmState::Running
+            && let Some(path) = hypervisor.console_path(&vm.id).await
+        {
+            console.capture(&vm.id, &path).await;
+        }
+    }
+
+    // Owned volumes and NICs, and volume snapshots; the daemons are dialed
+    // on first use
+    let zfs_channel = match args.zfs_server.as_str() {
+        "" => None,
+        url => Some(Endpoint::from_shared(url.to_string())?.connect_lazy()),
+    };
+    let net_channel = match args.net_server.as_str() {
+        "" => None,
+        url => Some(Endpoint::from_shared(url.to_string())?.connect_lazy()),
+    };
+    let dependents = Dependents::new(zfs_channel.clone(), net_channel);
+    let node_resources = match args.node_resources.as_str() {
+        "" => None,
+        path => Some(PathBuf::from(path)),
+    };
+
+    // Drift between the store and the cloud-hypervisor processes
+    let drift = Arc::new(DriftChecker::new(
         store.clone(),
         hypervisor.clone(),
         audit.clone(),
-        vm_events_tx,
+    ));
+    let _drift_monitor = (args.drift_check_secs > 0)
+        .then(|| DriftMonitor::start(drift.clone(), Duration::from_secs(args.drift_check_secs)));
+
+    // Create gRPC services
+    let host_events = match EventBus::new("vmm") {
+        Ok(bus) => Some(bus),
+        Err(e) => {
+            warn!(error = %e, "No host event bus; VM stops are not published");
+            None
+        }
+    };
+    let _host_event_publisher = host_events
+        .clone()
+        .map(|bus| host_events::spawn_publisher(bus, vm_events_tx.subscribe()));
+    let vm_service = Arc::new(
+        VmServiceImpl::new(
+            store.clone(),
+            hypervisor.clone(),
+            audit.clone(),
+            vm_events_tx,
+            console,
+            dependents.clone(),
+        )
+        .with_memory_dump(args.allow_memory_dump, args.memory_dump_uid)
+        .with_drift(drift)
+        .with_volumes(zfs_channel)
+        .with_node_resources(node_resources),
     );
-    let pod_service = PodServiceImpl::new(store, hypervisor, audit);
+    // Detach NICs the network daemon deleted
+    let _host_event_listener = match host_events.as_ref().map(EventBus::subscribe) {
+        Some(Ok(subscription)) => Some(host_events::spawn_listener(
+            vm_service.clone(),
+            subscription,
+        )),
+        Some(Err(e)) => {
+            warn!(error = %e, "Failed to subscribe to host events");
+            None
+        }
+        None => None,
+    };
+    let guest_images = GuestImageRegistry::new(args.guest_image_dir, args.default_guest_image);
+    if guest_images.resolve(None).is_none() {
+        warn!(
+            default = %guest_images.default_name(),
+            "Default pod guest image is not installed; pods must name one"
+        );
+    }
+    // Pod starts are jobs of mvirt-vmm like VM starts
+    let pod_service = PodServiceImpl::new(store, hypervisor, audit, guest_images, dependents)
+        .with_jobs(vm_service.jobs().clone());
 
     let addr = args.listen.parse()?;
     info!(addr = %addr, "Starting gRPC server");
 
     Server::builder()
-        .add_service(VmServiceServer::new(vm_service))
+        .layer(GrpcRequestIdLayer)
+        .layer(GrpcLimitLayer::new(args.limits))
+        .add_service(VmServiceServer::from_arc(vm_service))
         .add_service(PodServiceServer::new(pod_service))
         .serve(addr)
         .await?;
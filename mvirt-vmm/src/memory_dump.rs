@@ -0,0 +1,454 @@
+//! Guest memory dumps and memory introspection for debugging.
+//!
+//! cloud-hypervisor writes guest memory as an ELF core file
+//! (`vm.coredump`, with the VM paused meanwhile). A full dump streams that
+//! file unchanged, ready for `crash` or `gdb`; it lives in the VM
+//! directory while it is transferred. A range dump streams only the bytes
+//! of a guest-physical range, read from the file's PT_LOAD segments while
+//! cloud-hypervisor is still writing it. Everything already read or
+//! outside the range is punched out of the file right away, so a range
+//! dump doesn't take the guest's whole memory in disk space.
+//!
+//! Both expose everything in the guest, so mvirt-vmm only serves them when
+//! started with `--allow-memory-dump`, and only to callers on this host
+//! running as one of the users given with `--memory-dump-uid` (root by
+//! default).
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::time::Duration;
+
+use nix::fcntl::{FallocateFlags, fallocate};
+use serde_json::Value;
+
+use crate::proto::{GuestMemoryChunk, GuestMemoryInfo, GuestMemoryRegion};
+
+/// Bytes per streamed chunk.
+pub const CHUNK_SIZE: u64 = 1024 * 1024;
+
+const PT_LOAD: u32 = 1;
+
+/// How often a core file that is still being written is checked for more.
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Whether `caller`, a local uid as found by [`crate::peer::caller_uid`],
+/// may dump guest memory. Callers of unknown identity never may.
+pub fn may_access(allowed_uids: &[u32], caller: Option<u32>) -> bool {
+    caller.is_some_and(|uid| allowed_uids.contains(&uid))
+}
+
+/// A PT_LOAD segment: guest memory at `addr` stored at `offset` in the
+/// core file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Segment {
+    pub addr: u64,
+    pub offset: u64,
+    pub size: u64,
+}
+
+fn u16_at(buf: &[u8], at: usize) -> u16 {
+    u16::from_le_bytes(buf[at..at + 2].try_into().unwrap())
+}
+
+fn u32_at(buf: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(buf[at..at + 4].try_into().unwrap())
+}
+
+fn u64_at(buf: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(buf[at..at + 8].try_into().unwrap())
+}
+
+/// Memory segments of a little-endian ELF64 core file.
+pub fn load_segments<F: Read + Seek>(file: &mut F) -> io::Result<Vec<Segment>> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut header = [0u8; 64];
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut header)?;
+    if &header[..4] != b"\x7fELF" || header[4] != 2 || header[5] != 1 {
+        return Err(invalid("not a little-endian ELF64 core file"));
+    }
+    let phoff = u64_at(&header, 0x20);
+    let phentsize = u16_at(&header, 0x36) as usize;
+    let phnum = u16_at(&header, 0x38) as usize;
+    if phentsize < 56 {
+        return Err(invalid("ELF program headers too short"));
+    }
+
+    let mut table = vec![0u8; phentsize * phnum];
+    file.seek(SeekFrom::Start(phoff))?;
+    file.read_exact(&mut table)?;
+    Ok(table
+        .chunks_exact(phentsize)
+        .filter(|ph| u32_at(ph, 0) == PT_LOAD)
+        .map(|ph| Segment {
+            addr: u64_at(ph, 24),
+            offset: u64_at(ph, 8),
+            size: u64_at(ph, 32),
+        })
+        .filter(|s| s.size > 0)
+        .collect())
+}
+
+/// A chunk of a range dump: `len` bytes of guest memory at `addr`, found
+/// at `offset` in the core file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Piece {
+    pub addr: u64,
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// The chunks covering `[start, start + length)`, in address order.
+/// Addresses no segment covers are left out.
+pub fn plan(segments: &[Segment], start: u64, length: u64) -> Vec<Piece> {
+    let end = start.saturating_add(length);
+    let mut segments = segments.to_vec();
+    segments.sort_by_key(|s| s.addr);
+
+    let mut pieces = Vec::new();
+    for seg in segments {
+        let from = start.max(seg.addr);
+        let to = end.min(seg.addr.saturating_add(seg.size));
+        let mut addr = from;
+        while addr < to {
+            let len = (to - addr).min(CHUNK_SIZE);
+            pieces.push(Piece {
+                addr,
+                offset: seg.offset + (addr - seg.addr),
+                len,
+            });
+            addr += len;
+        }
+    }
+    pieces
+}
+
+/// Read a core file as dump chunks: all of it, or the memory in the
+/// guest-physical `range` (start, length). Stops early once `emit`
+/// returns false.
+pub fn read_chunks(
+    path: &Path,
+    range: Option<(u64, u64)>,
+    mut emit: impl FnMut(GuestMemoryChunk) -> bool,
+) -> io::Result<()> {
+    let mut file = File::open(path)?;
+    let pieces = match range {
+        Some((start, length)) => plan(&load_segments(&mut file)?, start, length),
+        None => {
+            let size = file.metadata()?.len();
+            (0..size)
+                .step_by(CHUNK_SIZE as usize)
+                .map(|offset| Piece {
+                    addr: offset,
+                    offset,
+                    len: (size - offset).min(CHUNK_SIZE),
+                })
+                .collect()
+        }
+    };
+    for piece in pieces {
+        let mut data = vec![0u8; piece.len as usize];
+        file.seek(SeekFrom::Start(piece.offset))?;
+        file.read_exact(&mut data)?;
+        if !emit(GuestMemoryChunk {
+            offset: piece.addr,
+            data,
+        }) {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Read the memory in the guest-physical `range` (start, length) from a
+/// core file while it is being written, as dump chunks in file order.
+/// `written` turns true once the writer is done, successful or not.
+///
+/// cloud-hypervisor writes the headers first and memory after them in
+/// order, so a byte can be read once the file has grown past it. Read and
+/// unneeded parts are punched out as the file grows: it never holds more
+/// than what was written between two checks. Once `emit` returns false
+/// nothing more is read, but the file is still punched until the writer
+/// is done.
+pub fn follow_range(
+    path: &Path,
+    (start, length): (u64, u64),
+    written: impl Fn() -> bool,
+    mut emit: impl FnMut(GuestMemoryChunk) -> bool,
+) -> io::Result<()> {
+    let mut file = loop {
+        let done = written();
+        match OpenOptions::new().read(true).write(true).open(path) {
+            Ok(file) => break file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound && !done => {
+                std::thread::sleep(FOLLOW_INTERVAL)
+            }
+            Err(e) => return Err(e),
+        }
+    };
+    let segments = loop {
+        let done = written();
+        match load_segments(&mut file) {
+            Ok(segments) => break segments,
+            Err(_) if !done => std::thread::sleep(FOLLOW_INTERVAL),
+            Err(e) => return Err(e),
+        }
+    };
+    let mut pieces = plan(&segments, start, length);
+    pieces.sort_by_key(|p| p.offset);
+
+    let mut punched = 0;
+    let mut emitting = true;
+    for piece in pieces {
+        let end = piece.offset + piece.len;
+        loop {
+            let done = written();
+            let size = file.metadata()?.len();
+            punch(&file, &mut punched, size.min(piece.offset))?;
+            if size >= end {
+                break;
+            }
+            if done {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "core file ended before the requested range",
+                ));
+            }
+            std::thread::sleep(FOLLOW_INTERVAL);
+        }
+        if emitting {
+            let mut data = vec![0u8; piece.len as usize];
+            file.seek(SeekFrom::Start(piece.offset))?;
+            file.read_exact(&mut data)?;
+            emitting = emit(GuestMemoryChunk {
+                offset: piece.addr,
+                data,
+            });
+        }
+        punch(&file, &mut punched, end)?;
+    }
+    // The rest of the guest is still on its way
+    loop {
+        let done = written();
+        punch(&file, &mut punched, file.metadata()?.len())?;
+        if done {
+            return Ok(());
+        }
+        std::thread::sleep(FOLLOW_INTERVAL);
+    }
+}
+
+/// Free the disk space of `file` from `*punched` up to `to`.
+fn punch(file: &File, punched: &mut u64, to: u64) -> io::Result<()> {
+    if to <= *punched {
+        return Ok(());
+    }
+    fallocate(
+        file.as_raw_fd(),
+        FallocateFlags::FALLOC_FL_PUNCH_HOLE | FallocateFlags::FALLOC_FL_KEEP_SIZE,
+        *punched as i64,
+        (to - *punched) as i64,
+    )?;
+    *punched = to;
+    Ok(())
+}
+
+/// Memory layout and balloon state from cloud-hypervisor's `vm.info`.
+pub fn memory_info(info: &Value) -> GuestMemoryInfo {
+    let memory = &info["config"]["memory"];
+    let zones = memory["zones"].as_array().filter(|z| !z.is_empty());
+    let region = |id: &str, zone: &Value| GuestMemoryRegion {
+        id: id.to_string(),
+        size_bytes: zone["size"].as_u64().unwrap_or(0),
+        hotplugged_bytes: zone["hotplugged_size"].as_u64().unwrap_or(0),
+        host_numa_node: zone["host_numa_node"].as_u64().map(|n| n as u32),
+        hugepages: zone["hugepages"].as_bool().unwrap_or(false),
+        shared: zone["shared"].as_bool().unwrap_or(false),
+    };
+    let regions: Vec<GuestMemoryRegion> = match zones {
+        Some(zones) => zones
+            .iter()
+            .map(|z| region(z["id"].as_str().unwrap_or_default(), z))
+            .collect(),
+        None => vec![region("default", memory)],
+    };
+    let configured_bytes = regions
+        .iter()
+        .map(|r| r.size_bytes + r.hotplugged_bytes)
+        .sum();
+
+    let balloon = &info["config"]["balloon"];
+    GuestMemoryInfo {
+        configured_bytes,
+        actual_bytes: info["memory_actual_size"]
+            .as_u64()
+            .unwrap_or(configured_bytes),
+        regions,
+        balloon_bytes: balloon["size"].as_u64(),
+        balloon_deflate_on_oom: balloon["deflate_on_oom"].as_bool().unwrap_or(false),
+        balloon_free_page_reporting: balloon["free_page_reporting"].as_bool().unwrap_or(false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn core_file(segments: &[Segment]) -> Vec<u8> {
+        let mut elf = vec![0u8; 64];
+        elf[..6].copy_from_slice(b"\x7fELF\x02\x01");
+        elf[0x20..0x28].copy_from_slice(&64u64.to_le_bytes());
+        elf[0x36..0x38].copy_from_slice(&56u16.to_le_bytes());
+        elf[0x38..0x3a].copy_from_slice(&(segments.len() as u16 + 1).to_le_bytes());
+        // A PT_NOTE first, as in real core files
+        let mut note = [0u8; 56];
+        note[..4].copy_from_slice(&4u32.to_le_bytes());
+        elf.extend_from_slice(&note);
+        for s in segments {
+            let mut ph = [0u8; 56];
+            ph[..4].copy_from_slice(&PT_LOAD.to_le_bytes());
+            ph[8..16].copy_from_slice(&s.offset.to_le_bytes());
+            ph[24..32].copy_from_slice(&s.addr.to_le_bytes());
+            ph[32..40].copy_from_slice(&s.size.to_le_bytes());
+            elf.extend_from_slice(&ph);
+        }
+        elf
+    }
+
+    #[test]
+    fn segments_and_ranges() {
+        // Low memory below the PCI hole, high memory from 4 GiB
+        let low = Segment {
+            addr: 0,
+            offset: 0x1000,
+            size: 0xc000_0000,
+        };
+        let high = Segment {
+            addr: 0x1_0000_0000,
+            offset: 0xc000_1000,
+            size: 0x4000_0000,
+        };
+        let segments = load_segments(&mut Cursor::new(core_file(&[high, low]))).unwrap();
+        assert_eq!(segments, vec![high, low]);
+
+        // A range across the hole skips it
+        let pieces = plan(&segments, 0xbfff_f000, 0x4000_2000);
+        assert_eq!(
+            pieces,
+            vec![
+                Piece {
+                    addr: 0xbfff_f000,
+                    offset: 0xc000_0000,
+                    len: 0x1000,
+                },
+                Piece {
+                    addr: 0x1_0000_0000,
+                    offset: 0xc000_1000,
+                    len: 0x1000,
+                },
+            ]
+        );
+
+        // Large ranges are split into chunks
+        let pieces = plan(&segments, 0, 3 * CHUNK_SIZE + 1);
+        assert_eq!(pieces.len(), 4);
+        assert_eq!(pieces[3].len, 1);
+    }
+
+    #[test]
+    fn follow_range_while_written() {
+        use std::io::Write;
+        use std::os::unix::fs::MetadataExt;
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let dir = std::env::temp_dir().join(format!("mvirt-dump-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("memory.core");
+        let seg = Segment {
+            addr: 0x10_0000,
+            offset: 0x1000,
+            size: 4 * CHUNK_SIZE,
+        };
+        let mut header = core_file(&[seg]);
+        header.resize(seg.offset as usize, 0);
+        let memory: Vec<u8> = (0..seg.size).map(|i| (i / 4096) as u8).collect();
+
+        let written = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (path, memory, written) = (path.clone(), memory.clone(), written.clone());
+            std::thread::spawn(move || {
+                std::thread::sleep(FOLLOW_INTERVAL);
+                let mut file = File::create(&path).unwrap();
+                file.write_all(&header).unwrap();
+                for chunk in memory.chunks(CHUNK_SIZE as usize / 4) {
+                    file.write_all(chunk).unwrap();
+                    std::thread::sleep(Duration::from_millis(5));
+                }
+                written.store(true, Ordering::Release);
+            })
+        };
+
+        let start = seg.addr + CHUNK_SIZE + 100;
+        let mut got = Vec::new();
+        follow_range(
+            &path,
+            (start, 2 * CHUNK_SIZE),
+            || written.load(Ordering::Acquire),
+            |chunk| {
+                assert_eq!(chunk.offset, start + got.len() as u64);
+                got.extend(chunk.data);
+                true
+            },
+        )
+        .unwrap();
+        writer.join().unwrap();
+
+        let from = (CHUNK_SIZE + 100) as usize;
+        assert_eq!(got, memory[from..from + 2 * CHUNK_SIZE as usize]);
+        // Nothing of the guest is left on disk
+        let meta = std::fs::metadata(&path).unwrap();
+        assert_eq!(meta.len(), seg.offset + seg.size);
+        assert!(meta.blocks() * 512 < CHUNK_SIZE, "{} blocks", meta.blocks());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn follow_range_fails_without_a_dump() {
+        let path = std::env::temp_dir().join(format!("mvirt-dump-{}", uuid::Uuid::new_v4()));
+        let err = follow_range(&path, (0, 1), || true, |_| true).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn memory_info_from_vm_info() {
+        let info = serde_json::json!({
+            "config": {
+                "memory": {"size": 2147483648u64, "hotplugged_size": 0, "shared": true},
+                "balloon": {"size": 536870912u64, "deflate_on_oom": true}
+            },
+            "memory_actual_size": 1610612736u64
+        });
+        let m = memory_info(&info);
+        assert_eq!(m.configured_bytes, 2147483648);
+        assert_eq!(m.actual_bytes, 1610612736);
+        assert_eq!(m.regions.len(), 1);
+        assert_eq!(m.regions[0].id, "default");
+        assert!(m.regions[0].shared);
+        assert_eq!(m.balloon_bytes, Some(536870912));
+        assert!(m.balloon_deflate_on_oom);
+    }
+
+    #[test]
+    fn test_may_access() {
+        assert!(may_access(&[0], Some(0)));
+        assert!(may_access(&[0, 1000], Some(1000)));
+        assert!(!may_access(&[0], Some(1000)));
+        assert!(!may_access(&[0], None));
+        assert!(!may_access(&[], Some(0)));
+    }
+}
@@ -0,0 +1,267 @@
+//! Live migration of running VMs between hosts.
+//!
+//! cloud-hypervisor migrates over a connection between the source and the
+//! target process. Hosts don't talk to each other directly, so each
+//! mvirt-vmm hands its cloud-hypervisor a Unix socket and relays that
+//! connection over a gRPC stream; the caller (the CLI) passes the messages
+//! of the source's MigrateVm on to the target's ReceiveMigration and back.
+//!
+//! Only memory and device state move. The disks have to be reachable from
+//! both hosts under the same path: network-attached disks, which the target
+//! connects before the migration, or files on shared storage. NICs are
+//! re-attached on the target to the vhost-user sockets or TAPs of the
+//! migrated config, so they must exist there before the migration starts.
+
+use std::future::Future;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use mvirt_common::jobs::JobHandle;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::sync::mpsc;
+use tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+
+use crate::proto::migration_message::Message;
+use crate::proto::{MigrationHeader, MigrationMessage, VmConfig};
+use crate::remote_disk::{self, RemoteTarget};
+
+/// Bytes per relayed message.
+const CHUNK_SIZE: usize = 256 * 1024;
+
+/// How long cloud-hypervisor gets to open its end of the connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the rest of the connection may take once the migration is
+/// complete on this side.
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn header_message(header: MigrationHeader) -> MigrationMessage {
+    MigrationMessage {
+        message: Some(Message::Header(header)),
+    }
+}
+
+/// The header that opens each direction of a migration.
+pub async fn next_header<S>(inbound: &mut S) -> Result<MigrationHeader, Status>
+where
+    S: Stream<Item = Result<MigrationMessage, Status>> + Unpin,
+{
+    match inbound.next().await {
+        Some(Ok(MigrationMessage {
+            message: Some(Message::Header(header)),
+        })) => Ok(header),
+        Some(Ok(_)) => Err(Status::invalid_argument(
+            "Migration must start with a header",
+        )),
+        Some(Err(status)) => Err(status),
+        None => Err(Status::cancelled("Migration ended before it started")),
+    }
+}
+
+/// Check that the disks of a migrating VM can be reached from this host:
+/// cloud-hypervisor reopens them here by the paths the source uses. Whether
+/// local paths exist is left to the caller's check of the whole config.
+pub fn check_disk_locations(config: &VmConfig, source_paths: &[String]) -> Result<(), String> {
+    if source_paths.len() != config.disks.len() {
+        return Err(format!(
+            "source reported {} disk paths for {} disks",
+            source_paths.len(),
+            config.disks.len()
+        ));
+    }
+    for disk in &config.disks {
+        let remote = RemoteTarget::parse(&disk.path).map_err(|e| e.to_string())?;
+        if remote.is_none() && disk.path.starts_with("/dev/zvol/") {
+            return Err(format!(
+                "disk {} is a local volume of the source; live migration needs \
+                 network-attached disks",
+                disk.path
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Connect the network-attached disks of a migrating VM and check they show
+/// up under the device paths the source uses. Nothing stays connected on
+/// error.
+pub async fn connect_disks(config: &VmConfig, source_paths: &[String]) -> Result<(), String> {
+    let paths = remote_disk::connect_all(&config.disks)
+        .await
+        .map_err(|e| e.to_string())?;
+    let moved = config
+        .disks
+        .iter()
+        .zip(&paths)
+        .zip(source_paths)
+        .find(|((_, here), there)| here != there);
+    if let Some(((disk, here), there)) = moved {
+        remote_disk::disconnect_all(&config.disks).await;
+        return Err(format!(
+            "disk {} is {} on this host but {} on the source",
+            disk.path, here, there
+        ));
+    }
+    Ok(())
+}
+
+/// Wait for cloud-hypervisor to listen on `path` and connect to it.
+pub async fn connect(path: &Path) -> Result<UnixStream> {
+    let deadline = tokio::time::Instant::now() + CONNECT_TIMEOUT;
+    loop {
+        match UnixStream::connect(path).await {
+            Ok(conn) => return Ok(conn),
+            Err(e) if tokio::time::Instant::now() >= deadline => {
+                return Err(anyhow!("connect to {}: {}", path.display(), e));
+            }
+            Err(_) => tokio::time::sleep(Duration::from_millis(50)).await,
+        }
+    }
+}
+
+/// Relay cloud-hypervisor's migration connection `conn` over the gRPC
+/// streams until `done` (the migration API call) resolves, then pass on
+/// what is left of the connection. Progress is the number of bytes
+/// received from the other side.
+pub async fn relay<S, F>(
+    conn: UnixStream,
+    inbound: &mut S,
+    outbound: &mpsc::Sender<Result<MigrationMessage, Status>>,
+    job: &JobHandle,
+    done: F,
+) -> Result<()>
+where
+    S: Stream<Item = Result<MigrationMessage, Status>> + Unpin,
+    F: Future<Output = Result<()>>,
+{
+    let (mut read, mut write) = conn.into_split();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    let mut received = 0u64;
+    let mut conn_open = true;
+    let mut inbound_open = true;
+    tokio::pin!(done);
+
+    loop {
+        tokio::select! {
+            result = &mut done => {
+                result?;
+                break;
+            }
+            n = read.read(&mut buf), if conn_open => {
+                let n = n?;
+                if n == 0 {
+                    conn_open = false;
+                } else if outbound.send(Ok(data_message(&buf[..n]))).await.is_err() {
+                    return Err(anyhow!("caller went away"));
+                }
+            }
+            message = inbound.next(), if inbound_open => match message {
+                Some(Ok(MigrationMessage { message: Some(Message::Data(data)) })) => {
+                    received += data.len() as u64;
+                    job.set_progress(received, None);
+                    write.write_all(&data).await?;
+                }
+                Some(Ok(_)) => return Err(anyhow!("unexpected header during migration")),
+                Some(Err(status)) => return Err(anyhow!("{}", status.message())),
+                None => inbound_open = false,
+            },
+            _ = job.wait_cancelled() => return Err(anyhow!("migration cancelled")),
+        }
+    }
+
+    // The last response may still be in the socket
+    let drain = async {
+        if !conn_open {
+            return Ok(());
+        }
+        loop {
+            let n = read.read(&mut buf).await?;
+            if n == 0 {
+                break;
+            }
+            if outbound.send(Ok(data_message(&buf[..n]))).await.is_err() {
+                break;
+            }
+        }
+        Ok::<(), std::io::Error>(())
+    };
+    let _ = tokio::time::timeout(DRAIN_TIMEOUT, drain).await;
+    Ok(())
+}
+
+fn data_message(data: &[u8]) -> MigrationMessage {
+    MigrationMessage {
+        message: Some(Message::Data(data.to_vec())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::DiskConfig;
+    use mvirt_common::jobs::JobRegistry;
+
+    fn config(paths: &[&str]) -> VmConfig {
+        VmConfig {
+            disks: paths
+                .iter()
+                .map(|p| DiskConfig {
+                    path: p.to_string(),
+                    readonly: false,
+                    secret: None,
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_disk_locations() {
+        let remote = "iscsi://10.0.0.6/iqn.2024-01.io.example:storage/1";
+        let config_remote = config(&[remote]);
+        assert!(check_disk_locations(&config_remote, &["/dev/sdb".into()]).is_ok());
+        assert!(check_disk_locations(&config_remote, &[]).is_err());
+
+        let local = config(&["/dev/zvol/mvirt/vm-root"]);
+        let err = check_disk_locations(&local, &["/dev/zvol/mvirt/vm-root".into()]).unwrap_err();
+        assert!(err.contains("local volume"));
+
+        let shared = config(&["/srv/shared/vm-root.raw"]);
+        assert!(check_disk_locations(&shared, &["/srv/shared/vm-root.raw".into()]).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_relay_passes_both_directions() {
+        let (conn, mut hypervisor) = UnixStream::pair().unwrap();
+        let mut inbound = tokio_stream::iter(vec![Ok(data_message(b"from target"))]);
+        let (tx, mut rx) = mpsc::channel(8);
+        let registry = JobRegistry::default();
+        let job = registry.start("migration", "vm-1", true);
+
+        // Plays cloud-hypervisor: sends a request, reads the answer, and
+        // completes the migration
+        let (done_tx, done_rx) = tokio::sync::oneshot::channel();
+        let peer = tokio::spawn(async move {
+            hypervisor.write_all(b"from source").await.unwrap();
+            let mut answer = vec![0u8; 11];
+            hypervisor.read_exact(&mut answer).await.unwrap();
+            done_tx.send(()).unwrap();
+            answer
+        });
+        let done = async {
+            done_rx.await.unwrap();
+            Ok(())
+        };
+        relay(conn, &mut inbound, &tx, &job, done).await.unwrap();
+
+        assert_eq!(peer.await.unwrap(), b"from target");
+        match rx.recv().await.unwrap().unwrap().message {
+            Some(Message::Data(data)) => assert_eq!(data, b"from source"),
+            other => panic!("unexpected message {:?}", other),
+        }
+        assert_eq!(registry.get(job.id()).unwrap().done, 11);
+    }
+}
@@ -0,0 +1,130 @@
+//! Who is calling: the user owning the other end of a local connection.
+//!
+//! mvirt-vmm serves plain TCP on loopback, so there is no TLS identity and
+//! no SO_PEERCRED. The kernel still knows who owns the peer's socket: for
+//! a connection from this host, the peer's end is listed in
+//! `/proc/net/tcp` (or `tcp6`) with its owner's uid, the same lookup
+//! identd does. Remote peers have no entry and no identity.
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use tonic::Request;
+
+/// The uid owning the peer's end of the connection `request` came in on,
+/// if the peer is on this host.
+pub fn caller_uid<T>(request: &Request<T>) -> Option<u32> {
+    let local = request.local_addr()?;
+    let remote = request.remote_addr()?;
+    ["/proc/net/tcp6", "/proc/net/tcp"]
+        .iter()
+        .find_map(|table| {
+            let table = fs::read_to_string(table).ok()?;
+            socket_owner(&table, remote, local)
+        })
+}
+
+/// The caller as audit logs name it: `uid N` for a caller on this host,
+/// else its address.
+pub fn describe<T>(request: &Request<T>) -> String {
+    match (caller_uid(request), request.remote_addr()) {
+        (Some(uid), _) => format!("uid {}", uid),
+        (None, Some(addr)) => addr.to_string(),
+        (None, None) => "unknown".to_string(),
+    }
+}
+
+/// Owner of the socket bound to `local` and connected to `remote` in a
+/// `/proc/net/tcp{,6}` table.
+fn socket_owner(table: &str, local: SocketAddr, remote: SocketAddr) -> Option<u32> {
+    let (local, remote) = (canonical(local), canonical(remote));
+    table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (entry_local, entry_remote, uid) = (fields.get(1)?, fields.get(2)?, fields.get(7)?);
+        (parse_addr(entry_local)? == local && parse_addr(entry_remote)? == remote)
+            .then(|| uid.parse().ok())
+            .flatten()
+    })
+}
+
+/// IPv4-mapped IPv6 addresses as plain IPv4, as in the v4 table.
+fn canonical(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(addr.ip().to_canonical(), addr.port())
+}
+
+/// An address as the kernel prints it: the address in 32-bit words of
+/// native byte order, then the port, in hex.
+fn parse_addr(field: &str) -> Option<SocketAddr> {
+    let (ip, port) = field.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let word = |i: usize| -> Option<[u8; 4]> {
+        let hex = ip.get(i * 8..i * 8 + 8)?;
+        Some(u32::from_str_radix(hex, 16).ok()?.to_ne_bytes())
+    };
+    let ip = match ip.len() {
+        8 => IpAddr::V4(Ipv4Addr::from(word(0)?)),
+        32 => {
+            let mut bytes = [0u8; 16];
+            for i in 0..4 {
+                bytes[i * 4..i * 4 + 4].copy_from_slice(&word(i)?);
+            }
+            IpAddr::V6(Ipv6Addr::from(bytes)).to_canonical()
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(all(test, target_endian = "little"))]
+mod tests {
+    use super::*;
+
+    const TCP: &str = "\
+  sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 0100007F:C383 00000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 1 1 0 100 0 0 10 0
+   1: 0100007F:D431 0100007F:C383 01 00000000:00000000 00:00000000 00000000  1000        0 2 1 0 20 4 30 10 -1
+";
+
+    const TCP6: &str = "\
+  sl  local_address                         remote_address                        st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+   0: 00000000000000000000000001000000:C383 00000000000000000000000000000000:0000 0A 00000000:00000000 00:00000000 00000000     0        0 3 1 0 100 0 0 10 0
+   1: 00000000000000000000000001000000:E2A6 00000000000000000000000001000000:C383 01 00000000:00000000 00:00000000 00000000   107        0 4 1 0 20 4 30 10 -1
+";
+
+    #[test]
+    fn test_parse_addr() {
+        assert_eq!(
+            parse_addr("0100007F:C383"),
+            Some("127.0.0.1:50051".parse().unwrap())
+        );
+        assert_eq!(
+            parse_addr("00000000000000000000000001000000:C383"),
+            Some("[::1]:50051".parse().unwrap())
+        );
+        // IPv4-mapped, as a dual-stack listener sees v4 peers
+        assert_eq!(
+            parse_addr("0000000000000000FFFF00000100007F:C383"),
+            Some("127.0.0.1:50051".parse().unwrap())
+        );
+        assert_eq!(parse_addr("garbage"), None);
+    }
+
+    #[test]
+    fn test_socket_owner() {
+        let server: SocketAddr = "127.0.0.1:50051".parse().unwrap();
+        let client: SocketAddr = "127.0.0.1:54321".parse().unwrap();
+        assert_eq!(socket_owner(TCP, client, server), Some(1000));
+        // The server's own end doesn't count
+        assert_eq!(socket_owner(TCP, server, client), None);
+        // A v4 peer seen through a dual-stack listener
+        let mapped: SocketAddr = "[::ffff:127.0.0.1]:54321".parse().unwrap();
+        assert_eq!(socket_owner(TCP, mapped, server), Some(1000));
+
+        let server: SocketAddr = "[::1]:50051".parse().unwrap();
+        let client: SocketAddr = "[::1]:58022".parse().unwrap();
+        assert_eq!(socket_owner(TCP6, client, server), Some(107));
+        // Not from this host
+        let remote: SocketAddr = "[2001:db8::1]:58022".parse().unwrap();
+        assert_eq!(socket_owner(TCP6, remote, server), None);
+    }
+}
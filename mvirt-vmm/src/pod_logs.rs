@@ -0,0 +1,161 @@
+//! Relay of pod logs from mvirt-one to mvirt-log.
+//!
+//! mvirt-one serves its containers' stdout/stderr and the guest kernel's
+//! messages on the `logs` vsock channel (see [`mvirt_one::logs`]). While a
+//! pod runs, one forwarder reads that stream and writes every line to
+//! mvirt-log under the pod's, the MicroVM's and the container's IDs, so a
+//! query for any of them finds the output. The forwarder ends with the
+//! MicroVM, when the guest closes the channel.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use mvirt_log::{AuditLogger, LogLevel};
+use mvirt_one::logs::{LogRecord, LogSource};
+use mvirt_one::vsock::CHANNEL_LOGS;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::{debug, info, warn};
+
+use crate::vsock_client::OneClient;
+
+/// Component name the entries are stored under.
+pub const COMPONENT: &str = "pod";
+
+/// Start forwarding a pod's logs. `containers` maps container IDs to
+/// names for the message prefix. Guests that don't serve the logs channel
+/// are skipped.
+pub async fn spawn(
+    one: &OneClient,
+    audit: Arc<AuditLogger>,
+    pod_id: String,
+    vm_id: String,
+    containers: HashMap<String, String>,
+) {
+    if !one.hello().supports(CHANNEL_LOGS) {
+        debug!(pod_id = %pod_id, "Guest does not ship logs");
+        return;
+    }
+    let stream = match one.open_channel(CHANNEL_LOGS).await {
+        Ok(stream) => stream,
+        Err(e) => {
+            warn!(pod_id = %pod_id, error = %e, "Failed to open pod log channel");
+            return;
+        }
+    };
+    info!(pod_id = %pod_id, "Forwarding pod logs to mvirt-log");
+
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(stream).lines();
+        loop {
+            let line = match lines.next_line().await {
+                Ok(Some(line)) => line,
+                Ok(None) => break,
+                Err(e) => {
+                    debug!(pod_id = %pod_id, error = %e, "Pod log channel failed");
+                    break;
+                }
+            };
+            let record: LogRecord = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(e) => {
+                    debug!(pod_id = %pod_id, error = %e, "Skipping malformed log record");
+                    continue;
+                }
+            };
+            let (level, message, ids) = entry(&record, &pod_id, &vm_id, &containers);
+            audit
+                .forward(COMPONENT, level, record.ts_ns, message, ids)
+                .await;
+        }
+        debug!(pod_id = %pod_id, "Pod log channel closed");
+    });
+}
+
+/// Level, message and object IDs of the mvirt-log entry for a record.
+fn entry(
+    record: &LogRecord,
+    pod_id: &str,
+    vm_id: &str,
+    containers: &HashMap<String, String>,
+) -> (LogLevel, String, Vec<String>) {
+    let mut ids = vec![pod_id.to_string(), vm_id.to_string()];
+    let name = || {
+        containers
+            .get(&record.container_id)
+            .unwrap_or(&record.container_id)
+            .clone()
+    };
+    match record.source {
+        LogSource::Kernel => {
+            let level = match record.priority.unwrap_or(6) {
+                0 => LogLevel::Emergency,
+                1 => LogLevel::Alert,
+                2 => LogLevel::Critical,
+                3 => LogLevel::Error,
+                4 => LogLevel::Warn,
+                5 => LogLevel::Notice,
+                6 => LogLevel::Info,
+                _ => LogLevel::Debug,
+            };
+            (level, format!("[kernel] {}", record.line), ids)
+        }
+        LogSource::Stdout => {
+            ids.push(record.container_id.clone());
+            (LogLevel::Info, format!("[{}] {}", name(), record.line), ids)
+        }
+        LogSource::Stderr => {
+            ids.push(record.container_id.clone());
+            let message = format!("[{}/stderr] {}", name(), record.line);
+            (LogLevel::Info, message, ids)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(source: LogSource, container_id: &str, priority: Option<u8>) -> LogRecord {
+        LogRecord {
+            ts_ns: 1,
+            source,
+            container_id: container_id.to_string(),
+            priority,
+            line: "hello".to_string(),
+        }
+    }
+
+    #[test]
+    fn entries_are_tagged_with_pod_vm_and_container() {
+        let containers = HashMap::from([("c-1".to_string(), "web".to_string())]);
+
+        let (level, message, ids) = entry(
+            &record(LogSource::Stderr, "c-1", None),
+            "pod-1",
+            "vm-1",
+            &containers,
+        );
+        assert_eq!(level, LogLevel::Info);
+        assert_eq!(message, "[web/stderr] hello");
+        assert_eq!(ids, ["pod-1", "vm-1", "c-1"]);
+
+        // Unknown containers fall back to the ID.
+        let (_, message, _) = entry(
+            &record(LogSource::Stdout, "c-2", None),
+            "pod-1",
+            "vm-1",
+            &containers,
+        );
+        assert_eq!(message, "[c-2] hello");
+
+        let (level, message, ids) = entry(
+            &record(LogSource::Kernel, "", Some(3)),
+            "pod-1",
+            "vm-1",
+            &containers,
+        );
+        assert_eq!(level, LogLevel::Error);
+        assert_eq!(message, "[kernel] hello");
+        assert_eq!(ids, ["pod-1", "vm-1"]);
+    }
+}
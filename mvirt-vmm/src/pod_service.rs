@@ -1,45 +1,61 @@
 //! Pod Service - gRPC service for managing container pods in MicroVMs.
 
+use crate::dependents::{self, Dependents, OWNER_KIND_POD};
+use crate::guest_image::GuestImageRegistry;
 use crate::hypervisor::Hypervisor;
+use crate::pod_logs;
 use crate::proto::{
-    BootMode, Container, ContainerSpec, ContainerState, CreatePodRequest, DeletePodRequest,
-    DeletePodResponse, DiskConfig, GetPodNetworkInfoRequest, GetPodRequest, ListPodsRequest,
-    ListPodsResponse, LogChunk, NicConfig, Pod, PodExecInput, PodExecOutput, PodInterfaceInfo,
-    PodLogsRequest, PodNetworkInfo, PodResources, PodState, StartPodRequest, StopPodRequest,
-    VmConfig, pod_service_server::PodService,
+    BootMode, Container, ContainerSpec, ContainerState, ContainerStats, CreatePodRequest,
+    DeletePodRequest, DeletePodResponse, DeletePropagation, DiskConfig, GetPodNetworkInfoRequest,
+    GetPodRequest, GetPodStatsRequest, ListGuestImagesRequest, ListGuestImagesResponse,
+    ListPodsRequest, ListPodsResponse, LogChunk, NicConfig, OwnerReference, PausePodRequest, Pod,
+    PodExecInput, PodExecOutput, PodInterfaceInfo, PodLogsRequest, PodNetworkInfo, PodResources,
+    PodState, PodStats, PullProgress, ResumePodRequest, RunPodCommandRequest,
+    RunPodCommandResponse, StartPodRequest, StartTiming, StopPodRequest, VmConfig,
+    pod_service_server::PodService,
 };
 use crate::ready_listener::ReadySignalListener;
+use crate::start_timing::{self, StartTimer};
 use crate::store::VmStore;
 use crate::vsock_client::{OneClient, vm_id_to_cid, vsock_socket_path};
+use mvirt_common::jobs::{JobHandle, JobRegistry};
+use mvirt_common::naming;
 use mvirt_log::AuditLogger;
 use mvirt_one::proto::{
-    ContainerSpec as OneContainerSpec, CreatePodRequest as OneCreatePodRequest, Empty as OneEmpty,
+    Container as OneContainer, ContainerSpec as OneContainerSpec,
+    CreatePodRequest as OneCreatePodRequest, Empty as OneEmpty, GetPodRequest as OneGetPodRequest,
+    LoadImageChunk, PodStats as OnePodStats, RunCommandRequest as OneRunCommandRequest,
     StartPodRequest as OneStartPodRequest, StopPodRequest as OneStopPodRequest,
     one_service_client::OneServiceClient,
 };
+use mvirt_one::services::image::ARCHIVE_PREFIX;
 use std::collections::HashMap;
+use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::RwLock;
+use tokio::io::AsyncReadExt;
+use tokio::sync::{RwLock, mpsc};
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Request, Response, Status, Streaming};
+use tonic::{Code, Request, Response, Status, Streaming};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-/// Kernel path for MicroVM boot.
-const ONE_KERNEL_PATH: &str = "/usr/share/mvirt/one/bzImage";
-/// Rootfs template path for pod volumes.
-const ONE_ROOTFS_TEMPLATE: &str = "/usr/share/mvirt/one/rootfs.raw";
-/// Cmdline file path.
-const ONE_CMDLINE_PATH: &str = "/usr/share/mvirt/one/cmdline";
-/// Default kernel command line for MicroVM (disk boot).
-const ONE_DISK_CMDLINE: &str = "console=ttyS0 quiet root=/dev/vda rw init=/init";
 /// Default memory for pod MicroVMs (MB).
 const POD_DEFAULT_MEMORY_MB: u64 = 256;
 /// Default vCPUs for pod MicroVMs.
 const POD_DEFAULT_VCPUS: u32 = 1;
 /// Timeout for waiting for mvirt-one to boot.
 const ONE_BOOT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Timeout for fetching container status from mvirt-one.
+const ONE_STATUS_TIMEOUT: Duration = Duration::from_secs(2);
+/// A previous stats sample older than this isn't used for CPU rates.
+const STATS_MAX_SAMPLE_AGE: Duration = Duration::from_secs(60);
+/// Gap between the two samples taken when there's no usable previous one.
+const STATS_SAMPLE_INTERVAL: Duration = Duration::from_millis(500);
+/// Bytes per message when streaming an image archive to mvirt-one.
+const ARCHIVE_CHUNK_SIZE: usize = 256 * 1024;
+/// Job kind of a pod start; DeletePod looks for it to cancel the start.
+pub const POD_START_JOB: &str = "pod-start";
 
 /// Internal pod data stored by the service.
 #[derive(Debug, Clone)]
@@ -49,6 +65,8 @@ struct PodData {
     state: PodState,
     vm_id: Option<String>,
     containers: Vec<ContainerSpec>,
+    /// Run to completion before `containers` on every start.
+    init_containers: Vec<ContainerSpec>,
     resources: Option<PodResources>,
     /// Path to root disk volume (ZFS volume created by CLI, rootfs written by VMM).
     root_disk_path: Option<String>,
@@ -60,13 +78,20 @@ struct PodData {
     created_at: i64,
     started_at: Option<i64>,
     error_message: Option<String>,
-}
-
-/// Read kernel cmdline from file, falling back to default.
-fn read_one_cmdline() -> String {
-    std::fs::read_to_string(ONE_CMDLINE_PATH)
-        .map(|s| s.trim().to_string())
-        .unwrap_or_else(|_| ONE_DISK_CMDLINE.to_string())
+    /// Last container status reported by mvirt-one, keyed by container ID.
+    container_status: HashMap<String, Container>,
+    /// Last cgroup stats sample from mvirt-one, the baseline for CPU rates.
+    last_stats: Option<OnePodStats>,
+    /// Image download progress while the pod is pulling.
+    pull_progress: Option<PullProgress>,
+    /// Guest image (kernel/initramfs/rootfs) the MicroVM boots.
+    guest_image: String,
+    paused_at: Option<i64>,
+    /// Object this pod belongs to (e.g. a cplane pod spec).
+    owner: Option<OwnerReference>,
+    labels: HashMap<String, String>,
+    /// Stages of the last successful start.
+    start_timing: Option<StartTiming>,
 }
 
 impl From<PodData> for Pod {
@@ -78,24 +103,41 @@ impl From<PodData> for Pod {
             vm_id: data.vm_id.unwrap_or_default(),
             containers: data
                 .containers
-                .into_iter()
-                .map(|spec| Container {
-                    id: spec.id.clone(),
-                    name: spec.name.clone(),
-                    state: ContainerState::Unspecified.into(),
-                    image: spec.image.clone(),
-                    exit_code: 0,
-                    error_message: None,
-                })
+                .iter()
+                .map(|spec| container_status(&data.container_status, spec))
+                .collect(),
+            init_containers: data
+                .init_containers
+                .iter()
+                .map(|spec| container_status(&data.container_status, spec))
                 .collect(),
             ip_address: data.ip_address,
             created_at: data.created_at,
             started_at: data.started_at,
             error_message: data.error_message,
+            pull_progress: data.pull_progress,
+            guest_image: data.guest_image,
+            paused_at: data.paused_at,
+            owner: data.owner,
+            labels: data.labels,
+            nic_mac_address: data.nic_mac_address.unwrap_or_default(),
+            start_timing: data.start_timing,
         }
     }
 }
 
+/// Last status of a container, or an unknown state before mvirt-one
+/// reported one.
+fn container_status(status: &HashMap<String, Container>, spec: &ContainerSpec) -> Container {
+    status.get(&spec.id).cloned().unwrap_or_else(|| Container {
+        id: spec.id.clone(),
+        name: spec.name.clone(),
+        state: ContainerState::Unspecified.into(),
+        image: spec.image.clone(),
+        ..Default::default()
+    })
+}
+
 /// gRPC implementation of the Pod Service.
 pub struct PodServiceImpl {
     #[allow(dead_code)]
@@ -105,18 +147,362 @@ pub struct PodServiceImpl {
     pods: Arc<RwLock<HashMap<String, PodData>>>,
     /// Map of pod_id -> OneClient for communicating with MicroVMs
     one_clients: Arc<RwLock<HashMap<String, OneClient>>>,
+    guest_images: GuestImageRegistry,
+    /// Volumes and NICs owned by pods, for cascading deletes.
+    dependents: Dependents,
+    /// In-flight pod starts, cancelled by a forced DeletePod
+    jobs: JobRegistry,
 }
 
 impl PodServiceImpl {
     /// Create a new Pod Service.
-    pub fn new(store: Arc<VmStore>, hypervisor: Arc<Hypervisor>, audit: Arc<AuditLogger>) -> Self {
+    pub fn new(
+        store: Arc<VmStore>,
+        hypervisor: Arc<Hypervisor>,
+        audit: Arc<AuditLogger>,
+        guest_images: GuestImageRegistry,
+        dependents: Dependents,
+    ) -> Self {
         Self {
             store,
             hypervisor,
             audit,
+            guest_images,
+            dependents,
             pods: Arc::new(RwLock::new(HashMap::new())),
             one_clients: Arc::new(RwLock::new(HashMap::new())),
+            jobs: JobRegistry::default(),
+        }
+    }
+
+    /// Register pod starts in `jobs`, e.g. the VM service's, so they are
+    /// listed and can be cancelled with the other jobs of mvirt-vmm.
+    pub fn with_jobs(mut self, jobs: JobRegistry) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// Roll back a start cancelled through its job: kill the MicroVM if it
+    /// was spawned and leave the pod stopped, so it can be started again
+    /// or deleted.
+    async fn abort_start(&self, pod_id: &str, vm_id: Option<&str>, job: &JobHandle) -> Status {
+        info!(pod_id = %pod_id, "Pod start cancelled, rolling back");
+        self.one_clients.write().await.remove(pod_id);
+        if let Some(vm_id) = vm_id
+            && let Err(e) = self.hypervisor.kill(vm_id).await
+        {
+            warn!(vm_id = %vm_id, error = %e, "Failed to kill MicroVM of cancelled pod start");
+        }
+        let _ = self
+            .store
+            .update_state(pod_id, crate::proto::VmState::Stopped)
+            .await;
+        let name = {
+            let mut pods = self.pods.write().await;
+            pods.get_mut(pod_id).map(|pod| {
+                pod.state = PodState::Stopped;
+                pod.pull_progress = None;
+                pod.error_message = Some("Start cancelled".to_string());
+                pod.name.clone()
+            })
+        };
+        job.finish_cancelled();
+        self.audit
+            .log(
+                mvirt_log::LogLevel::Audit,
+                &format!(
+                    "Pod {} ({}) start cancelled",
+                    name.as_deref().unwrap_or(pod_id),
+                    pod_id
+                ),
+                vec![pod_id.to_string()],
+            )
+            .await;
+        Status::cancelled("Pod start cancelled")
+    }
+
+    /// Pull container status (states, exit codes, OOM kills) from
+    /// mvirt-one for the given pods. Pods without a connection or that
+    /// don't answer in time keep their last known status.
+    async fn refresh_container_status(&self, pod_ids: &[String]) {
+        for pod_id in pod_ids {
+            // mvirt-one doesn't answer pod queries while it pulls images,
+            // and a frozen guest doesn't answer at all
+            let unreachable = self
+                .pods
+                .read()
+                .await
+                .get(pod_id)
+                .is_some_and(|p| matches!(p.state, PodState::Pulling | PodState::Paused));
+            if unreachable {
+                continue;
+            }
+            let channel = match self.one_clients.read().await.get(pod_id) {
+                Some(c) => c.channel(),
+                None => continue,
+            };
+            let mut one = OneServiceClient::new(channel);
+            let request = OneGetPodRequest { id: pod_id.clone() };
+            let one_pod = match tokio::time::timeout(ONE_STATUS_TIMEOUT, one.get_pod(request)).await
+            {
+                Ok(Ok(resp)) => resp.into_inner(),
+                Ok(Err(e)) => {
+                    debug!(pod_id = %pod_id, error = %e, "Failed to get pod status from mvirt-one");
+                    continue;
+                }
+                Err(_) => {
+                    debug!(pod_id = %pod_id, "Timed out getting pod status from mvirt-one");
+                    continue;
+                }
+            };
+
+            let mut pods = self.pods.write().await;
+            let Some(pod) = pods.get_mut(pod_id) else {
+                continue;
+            };
+            for c in one_pod
+                .containers
+                .into_iter()
+                .chain(one_pod.init_containers)
+            {
+                pod.container_status
+                    .insert(c.id.clone(), container_from_one(c));
+            }
+        }
+    }
+
+    /// Fetch a raw cgroup stats sample from mvirt-one.
+    async fn fetch_one_stats(&self, pod_id: &str) -> Result<OnePodStats, Status> {
+        let channel = self
+            .one_clients
+            .read()
+            .await
+            .get(pod_id)
+            .map(|c| c.channel())
+            .ok_or_else(|| Status::unavailable("No connection to pod"))?;
+        let mut one = OneServiceClient::new(channel);
+        let request = OneGetPodRequest {
+            id: pod_id.to_string(),
+        };
+        match tokio::time::timeout(ONE_STATUS_TIMEOUT, one.get_pod_stats(request)).await {
+            Ok(Ok(resp)) => Ok(resp.into_inner()),
+            Ok(Err(e)) => Err(Status::internal(format!("Failed to get pod stats: {}", e))),
+            Err(_) => Err(Status::deadline_exceeded(
+                "Timed out getting pod stats from mvirt-one",
+            )),
+        }
+    }
+}
+
+/// Copy image pull progress reported by mvirt-one into the pod until the
+/// stream ends or the task is aborted.
+async fn watch_pull_progress(
+    mut one: OneServiceClient<tonic::transport::Channel>,
+    pods: Arc<RwLock<HashMap<String, PodData>>>,
+    audit: Arc<AuditLogger>,
+    pod_id: String,
+) {
+    let mut stream = match one.watch_pull_progress(OneEmpty {}).await {
+        Ok(resp) => resp.into_inner(),
+        Err(e) => {
+            debug!(pod_id = %pod_id, error = %e, "Pull progress not available from mvirt-one");
+            return;
+        }
+    };
+
+    while let Ok(Some(p)) = stream.message().await {
+        let first = p.layer_index == 0 && p.layer_downloaded_bytes == 0;
+        let last =
+            p.layer_index + 1 == p.layer_count && p.layer_downloaded_bytes >= p.layer_total_bytes;
+        if first || last {
+            let message = if first {
+                format!("Pod {} pulling image {}", pod_id, p.image_ref)
+            } else {
+                format!(
+                    "Pod {} pulled image {} ({} bytes)",
+                    pod_id, p.image_ref, p.downloaded_bytes
+                )
+            };
+            audit
+                .log(mvirt_log::LogLevel::Info, &message, vec![pod_id.clone()])
+                .await;
         }
+
+        let mut pods = pods.write().await;
+        let Some(pod) = pods.get_mut(&pod_id) else {
+            return;
+        };
+        pod.pull_progress = Some(PullProgress {
+            image: p.image_ref,
+            layer_index: p.layer_index,
+            layer_count: p.layer_count,
+            downloaded_bytes: p.downloaded_bytes,
+            total_bytes: p.total_bytes,
+        });
+    }
+}
+
+/// Stream the `oci-archive:` images of a pod's containers from this host to
+/// mvirt-one, which caches them under their reference for the pod's pull to
+/// find. The transfer shows as the pod's pull progress.
+async fn load_image_archives(
+    one: &mut OneServiceClient<tonic::transport::Channel>,
+    containers: &[ContainerSpec],
+    pods: &RwLock<HashMap<String, PodData>>,
+    pod_id: &str,
+    job: &JobHandle,
+) -> Result<(), String> {
+    let mut images: Vec<&str> = containers
+        .iter()
+        .map(|c| c.image.as_str())
+        .filter(|image| image.starts_with(ARCHIVE_PREFIX))
+        .collect();
+    images.sort();
+    images.dedup();
+
+    for image in images {
+        job.set_phase("loading image");
+        let path = &image[ARCHIVE_PREFIX.len()..];
+        let mut file = tokio::fs::File::open(path)
+            .await
+            .map_err(|e| format!("{}: {}", path, e))?;
+        let total = file
+            .metadata()
+            .await
+            .map_err(|e| format!("{}: {}", path, e))?
+            .len();
+
+        // The first message names the image, the rest carry the archive
+        let (tx, rx) = mpsc::channel(4);
+        let _ = tx
+            .send(LoadImageChunk {
+                image_ref: image.to_string(),
+                data: Vec::new(),
+            })
+            .await;
+        let send = async move {
+            let mut buf = vec![0u8; ARCHIVE_CHUNK_SIZE];
+            let mut sent = 0u64;
+            loop {
+                let n = file
+                    .read(&mut buf)
+                    .await
+                    .map_err(|e| format!("{}: {}", path, e))?;
+                if n == 0 {
+                    return Ok(());
+                }
+                let chunk = LoadImageChunk {
+                    image_ref: String::new(),
+                    data: buf[..n].to_vec(),
+                };
+                if tx.send(chunk).await.is_err() {
+                    // mvirt-one stopped reading; its answer says why
+                    return Ok(());
+                }
+                sent += n as u64;
+                job.set_progress(sent, Some(total));
+                if let Some(pod) = pods.write().await.get_mut(pod_id) {
+                    pod.pull_progress = Some(PullProgress {
+                        image: image.to_string(),
+                        layer_index: 0,
+                        layer_count: 1,
+                        downloaded_bytes: sent,
+                        total_bytes: total,
+                    });
+                }
+            }
+        };
+        let (sent, loaded) = tokio::join!(send, one.load_image(ReceiverStream::new(rx)));
+        sent?;
+        let loaded = loaded.map_err(|s| s.message().to_string())?.into_inner();
+        info!(
+            pod_id = %pod_id,
+            image = %image,
+            bytes = loaded.archive_bytes,
+            "Loaded image archive into mvirt-one"
+        );
+    }
+    Ok(())
+}
+
+/// A container's status as mvirt-one reports it.
+fn container_from_one(c: OneContainer) -> Container {
+    Container {
+        id: c.id,
+        name: c.name,
+        // mvirt-one's ContainerState and StopPath numbering matches ours
+        state: c.state,
+        image: c.image,
+        exit_code: c.exit_code,
+        error_message: (!c.error_message.is_empty()).then_some(c.error_message),
+        exit_signal: c.exit_signal,
+        oom_killed: c.oom_killed,
+        stop_path: c.stop_path,
+    }
+}
+
+fn one_container_spec(c: &ContainerSpec) -> OneContainerSpec {
+    OneContainerSpec {
+        id: c.id.clone(),
+        name: c.name.clone(),
+        image: c.image.clone(),
+        command: c.command.clone(),
+        args: c.args.clone(),
+        env: c.env.clone(),
+        working_dir: c.working_dir.clone(),
+        stop_signal: c.stop_signal.clone(),
+        stop_grace_seconds: c.stop_grace_seconds,
+    }
+}
+
+/// Build pod stats from a sample, with CPU rates against `prev`.
+fn pod_stats(pod: &PodData, prev: &OnePodStats, sample: &OnePodStats) -> PodStats {
+    let elapsed_usec = sample.timestamp_usec - prev.timestamp_usec;
+    let containers: Vec<ContainerStats> = sample
+        .containers
+        .iter()
+        .map(|c| {
+            let cpu_percent = prev
+                .containers
+                .iter()
+                .find(|pc| pc.container_id == c.container_id)
+                .filter(|_| elapsed_usec > 0)
+                .map(|pc| {
+                    c.cpu_usage_usec.saturating_sub(pc.cpu_usage_usec) as f64 * 100.0
+                        / elapsed_usec as f64
+                })
+                .unwrap_or(0.0);
+            ContainerStats {
+                id: c.container_id.clone(),
+                name: c.name.clone(),
+                cpu_percent,
+                cpu_usage_usec: c.cpu_usage_usec,
+                memory_bytes: c.memory_bytes,
+                memory_limit_bytes: c.memory_limit_bytes,
+                pids: c.pids,
+            }
+        })
+        .collect();
+    let resources = pod.resources.unwrap_or_default();
+    let vcpus = if resources.vcpus > 0 {
+        resources.vcpus
+    } else {
+        POD_DEFAULT_VCPUS
+    };
+    let memory_mb = if resources.memory_mb > 0 {
+        resources.memory_mb
+    } else {
+        POD_DEFAULT_MEMORY_MB
+    };
+
+    PodStats {
+        pod_id: pod.id.clone(),
+        timestamp: sample.timestamp_usec / 1_000_000,
+        cpu_percent: containers.iter().map(|c| c.cpu_percent).sum(),
+        memory_bytes: containers.iter().map(|c| c.memory_bytes).sum(),
+        vcpus,
+        memory_limit_bytes: memory_mb * 1024 * 1024,
+        containers,
     }
 }
 
@@ -127,7 +513,16 @@ impl PodService for PodServiceImpl {
         request: Request<CreatePodRequest>,
     ) -> Result<Response<Pod>, Status> {
         let req = request.into_inner();
-        let pod_id = Uuid::new_v4().to_string();
+        // A caller-supplied id lets volumes and NICs created ahead of the
+        // pod reference it as their owner. It doubles as the MicroVM's id.
+        let pod_id = match req.id.as_deref().filter(|s| !s.is_empty()) {
+            Some(id) => Uuid::parse_str(id)
+                .map_err(|_| Status::invalid_argument(format!("Invalid pod ID: {}", id)))?
+                .to_string(),
+            None => Uuid::new_v4().to_string(),
+        };
+        naming::validate_optional_name("Pod", req.name.as_deref())?;
+        crate::grpc::validate_labels(&req.labels).map_err(Status::invalid_argument)?;
         let name = req.name.unwrap_or_else(|| format!("pod-{}", &pod_id[..8]));
 
         info!(pod_id = %pod_id, name = %name, "Creating pod");
@@ -138,18 +533,46 @@ impl PodService for PodServiceImpl {
                 "At least one container is required",
             ));
         }
+        // Archives are read from this host whenever the pod starts
+        for path in req
+            .containers
+            .iter()
+            .chain(&req.init_containers)
+            .filter_map(|c| c.image.strip_prefix(ARCHIVE_PREFIX))
+        {
+            if !Path::new(path).is_absolute() || !Path::new(path).is_file() {
+                return Err(Status::invalid_argument(format!(
+                    "Image archive {} is not a file on this host (the path must be absolute)",
+                    path
+                )));
+            }
+        }
+
+        // Pin the guest image now so a later change of the host default
+        // doesn't switch the pod's guest stack underneath it.
+        let guest_image = self
+            .guest_images
+            .resolve(req.guest_image.as_deref())
+            .ok_or_else(|| {
+                Status::invalid_argument(format!(
+                    "Guest image '{}' is not installed",
+                    req.guest_image
+                        .as_deref()
+                        .unwrap_or(self.guest_images.default_name())
+                ))
+            })?
+            .name;
 
         // Assign IDs to containers if not provided
-        let containers: Vec<ContainerSpec> = req
-            .containers
-            .into_iter()
-            .map(|mut spec| {
-                if spec.id.is_empty() {
-                    spec.id = Uuid::new_v4().to_string();
-                }
-                spec
-            })
-            .collect();
+        let assign_id = |mut spec: ContainerSpec| {
+            if spec.id.is_empty() {
+                spec.id = Uuid::new_v4().to_string();
+            }
+            spec
+        };
+        let containers: Vec<ContainerSpec> = req.containers.into_iter().map(assign_id).collect();
+        let init_containers: Vec<ContainerSpec> =
+            req.init_containers.into_iter().map(assign_id).collect();
 
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -162,6 +585,7 @@ impl PodService for PodServiceImpl {
             state: PodState::Created,
             vm_id: None,
             containers,
+            init_containers,
             resources: req.resources,
             root_disk_path: req.root_disk_path,
             nic_socket_path: req.nic_socket_path,
@@ -170,11 +594,25 @@ impl PodService for PodServiceImpl {
             created_at: now,
             started_at: None,
             error_message: None,
+            container_status: HashMap::new(),
+            last_stats: None,
+            pull_progress: None,
+            guest_image,
+            paused_at: None,
+            owner: req.owner,
+            labels: req.labels,
+            start_timing: None,
         };
 
         // Store pod
         {
             let mut pods = self.pods.write().await;
+            if pods.contains_key(&pod_id) {
+                return Err(Status::already_exists(format!(
+                    "Pod {} already exists",
+                    pod_id
+                )));
+            }
             pods.insert(pod_id.clone(), pod_data.clone());
         }
 
@@ -191,6 +629,8 @@ impl PodService for PodServiceImpl {
 
     async fn get_pod(&self, request: Request<GetPodRequest>) -> Result<Response<Pod>, Status> {
         let req = request.into_inner();
+        self.refresh_container_status(std::slice::from_ref(&req.id))
+            .await;
         let pods = self.pods.read().await;
 
         let pod = pods
@@ -204,6 +644,8 @@ impl PodService for PodServiceImpl {
         &self,
         _request: Request<ListPodsRequest>,
     ) -> Result<Response<ListPodsResponse>, Status> {
+        let ids: Vec<String> = self.one_clients.read().await.keys().cloned().collect();
+        self.refresh_container_status(&ids).await;
         let pods = self.pods.read().await;
         let pod_list: Vec<Pod> = pods.values().cloned().map(|p| p.into()).collect();
 
@@ -215,20 +657,48 @@ impl PodService for PodServiceImpl {
         request: Request<DeletePodRequest>,
     ) -> Result<Response<DeletePodResponse>, Status> {
         let req = request.into_inner();
-        info!(pod_id = %req.id, force = req.force, "Deleting pod");
+        let propagation = DeletePropagation::try_from(req.propagation).unwrap_or_default();
+        info!(pod_id = %req.id, force = req.force, ?propagation, "Deleting pod");
+
+        let starting = {
+            let pods = self.pods.read().await;
+            let pod = pods
+                .get(&req.id)
+                .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.id)))?;
+
+            // Check state
+            if matches!(pod.state, PodState::Running | PodState::Paused) && !req.force {
+                return Err(Status::failed_precondition(
+                    "Pod is running. Use force=true to delete anyway",
+                ));
+            }
+            let starting = self.jobs.find_running(POD_START_JOB, &req.id);
+            if starting.is_some() && !req.force {
+                return Err(Status::failed_precondition(
+                    "Pod is starting. Use force=true to cancel the start and delete it",
+                ));
+            }
+            starting
+        };
+
+        let owned = self
+            .dependents
+            .check(OWNER_KIND_POD, &req.id, propagation)
+            .await?;
+
+        // The start kills its MicroVM on the way out; wait for that so
+        // nothing of it outlives the pod
+        if let Some(start) = starting {
+            info!(pod_id = %req.id, job = %start.id, "Cancelling pod start");
+            let _ = self.jobs.cancel(&start.id);
+            self.jobs.wait_finished(&start.id).await;
+        }
 
         let mut pods = self.pods.write().await;
         let pod = pods
             .get(&req.id)
             .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.id)))?;
 
-        // Check state
-        if pod.state == PodState::Running && !req.force {
-            return Err(Status::failed_precondition(
-                "Pod is running. Use force=true to delete anyway",
-            ));
-        }
-
         // Stop the MicroVM if running
         if let Some(vm_id) = &pod.vm_id {
             // Remove one client
@@ -246,17 +716,25 @@ impl PodService for PodServiceImpl {
             warn!(pod_id = %req.id, error = %e, "Failed to delete VM entry for pod");
         }
 
-        // Note: ZFS volume cleanup is the CLI's responsibility
-
         let pod_name = pod.name.clone();
         pods.remove(&req.id);
+        drop(pods);
 
-        self.audit
-            .log(
-                mvirt_log::LogLevel::Audit,
-                &format!("Pod {} ({}) deleted", pod_name, req.id),
-                vec![req.id.clone()],
+        // The MicroVM is gone, so its root volume and NIC are free
+        let deleted = self.dependents.delete(&owned).await;
+
+        let message = if deleted.is_empty() {
+            format!("Pod {} ({}) deleted", pod_name, req.id)
+        } else {
+            format!(
+                "Pod {} ({}) deleted with {}",
+                pod_name,
+                req.id,
+                dependents::join(&deleted)
             )
+        };
+        self.audit
+            .log(mvirt_log::LogLevel::Audit, &message, vec![req.id.clone()])
             .await;
 
         Ok(Response::new(DeletePodResponse {}))
@@ -265,16 +743,20 @@ impl PodService for PodServiceImpl {
     async fn start_pod(&self, request: Request<StartPodRequest>) -> Result<Response<Pod>, Status> {
         let req = request.into_inner();
         info!(pod_id = %req.id, "Starting pod");
+        let mut timer = StartTimer::new(req.prepare_stages);
 
         // Get pod data (we need to clone to avoid holding the lock)
         let (
             pod_id,
             pod_name,
             _containers,
+            init_containers,
             resources,
             root_disk_path,
             nic_socket_path,
             nic_mac_address,
+            guest_image_name,
+            job,
         ) = {
             let mut pods = self.pods.write().await;
             let pod = pods
@@ -285,19 +767,49 @@ impl PodService for PodServiceImpl {
             if pod.state == PodState::Running {
                 return Err(Status::failed_precondition("Pod is already running"));
             }
+            if matches!(pod.state, PodState::Starting | PodState::Pulling) {
+                return Err(Status::failed_precondition("Pod is already starting"));
+            }
+            if pod.state == PodState::Paused {
+                return Err(Status::failed_precondition("Pod is paused"));
+            }
 
             pod.state = PodState::Starting;
+            // Registered under the lock, so a DeletePod that sees the pod
+            // starting also finds the job to cancel
+            let job = self.jobs.start(POD_START_JOB, &pod.id, true);
+            job.set_phase("writing rootfs");
+            timer.stage(start_timing::ROOTFS_WRITE);
             (
                 pod.id.clone(),
                 pod.name.clone(),
                 pod.containers.clone(),
+                pod.init_containers.clone(),
                 pod.resources,
                 pod.root_disk_path.clone(),
                 pod.nic_socket_path.clone(),
                 pod.nic_mac_address.clone(),
+                pod.guest_image.clone(),
+                job,
             )
         };
 
+        let Some(guest_image) = self.guest_images.resolve(Some(&guest_image_name)) else {
+            error!(pod_id = %pod_id, guest_image = %guest_image_name, "Guest image not installed");
+            let mut pods = self.pods.write().await;
+            if let Some(pod) = pods.get_mut(&pod_id) {
+                pod.state = PodState::Failed;
+                pod.error_message = Some(format!(
+                    "Guest image '{}' is no longer installed",
+                    guest_image_name
+                ));
+            }
+            return Err(Status::failed_precondition(format!(
+                "Guest image '{}' is no longer installed",
+                guest_image_name
+            )));
+        };
+
         // Root disk is required (created by CLI via mvirt-zfs)
         let root_disk_path = match root_disk_path {
             Some(path) => path,
@@ -317,17 +829,21 @@ impl PodService for PodServiceImpl {
 
         // Write rootfs template to volume
         // Use conv=notrunc to preserve the volume size (important for raw files in /tmp for tests)
-        info!(pod_id = %pod_id, volume = %root_disk_path, "Writing rootfs template to volume");
-        let dd_status = std::process::Command::new("dd")
-            .args([
-                &format!("if={}", ONE_ROOTFS_TEMPLATE),
-                &format!("of={}", root_disk_path),
-                "bs=4M",
-                "conv=fsync,notrunc",
-            ])
-            .stdout(std::process::Stdio::null())
-            .stderr(std::process::Stdio::null())
-            .status();
+        info!(pod_id = %pod_id, volume = %root_disk_path, guest_image = %guest_image.name, "Writing rootfs template to volume");
+        let mut dd = tokio::process::Command::new("dd");
+        dd.args([
+            &format!("if={}", guest_image.rootfs.display()),
+            &format!("of={}", root_disk_path),
+            "bs=4M",
+            "conv=fsync,notrunc",
+        ])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true);
+        let dd_status = tokio::select! {
+            status = dd.status() => status,
+            _ = job.wait_cancelled() => return Err(self.abort_start(&pod_id, None, &job).await),
+        };
 
         match dd_status {
             Ok(status) if status.success() => {}
@@ -380,6 +896,9 @@ impl PodService for PodServiceImpl {
             }
             _ => {}
         }
+        if job.is_cancelled() {
+            return Err(self.abort_start(&pod_id, None, &job).await);
+        }
 
         // Determine resource values
         let vcpus = resources
@@ -403,9 +922,6 @@ impl PodService for PodServiceImpl {
             })
             .unwrap_or(POD_DEFAULT_MEMORY_MB);
 
-        // Read kernel cmdline
-        let cmdline = read_one_cmdline();
-
         // Build NIC config if socket path provided
         let nics = if let Some(socket_path) = nic_socket_path {
             vec![NicConfig {
@@ -422,16 +938,25 @@ impl PodService for PodServiceImpl {
             vcpus,
             memory_mb,
             boot_mode: BootMode::Kernel.into(),
-            kernel: Some(ONE_KERNEL_PATH.to_string()),
-            initramfs: None, // No initramfs - boot from disk
-            cmdline: Some(cmdline),
+            kernel: Some(guest_image.kernel.display().to_string()),
+            initramfs: guest_image
+                .initramfs
+                .as_ref()
+                .map(|p| p.display().to_string()),
+            cmdline: Some(guest_image.cmdline.clone()),
             disks: vec![DiskConfig {
                 path: root_disk_path.clone(),
                 readonly: false,
+                secret: None,
             }],
             nics,
             user_data: None,
             nested_virt: false,
+            labels: Default::default(),
+            max_vcpus: 0,
+            max_memory_mb: 0,
+            // The pod start path talks to mvirt-one itself
+            guest_agent: false,
         };
 
         // Create a VM entry in the database (so console works via standard VM API)
@@ -507,9 +1032,19 @@ impl PodService for PodServiceImpl {
         info!(vm_id = %vm_id, pod_id = %pod_id, cid = cid, "Starting MicroVM for pod");
         if let Err(e) = self
             .hypervisor
-            .start(&vm_id, Some(&pod_name), &vm_config, Some(cid))
+            .start(
+                &vm_id,
+                Some(&pod_name),
+                &vm_config,
+                Some(cid),
+                &job,
+                &mut timer,
+            )
             .await
         {
+            if job.is_cancelled() {
+                return Err(self.abort_start(&pod_id, None, &job).await);
+            }
             error!(vm_id = %vm_id, error = %e, "Failed to start MicroVM");
             // Revert VM state and clean up
             let _ = self
@@ -533,9 +1068,24 @@ impl PodService for PodServiceImpl {
         debug!(vm_id = %vm_id, pod_id = %pod_id, "MicroVM started");
 
         // Wait for mvirt-one to signal it's ready via vsock
-        match ready_listener.wait(ONE_BOOT_TIMEOUT).await {
-            Ok(()) => {
-                info!(pod_id = %pod_id, cid = cid, "Received ready signal from mvirt-one");
+        job.set_phase("booting");
+        timer.stage(start_timing::GUEST_READY);
+        let ready = tokio::select! {
+            ready = ready_listener.wait(ONE_BOOT_TIMEOUT) => ready,
+            _ = job.wait_cancelled() => {
+                return Err(self.abort_start(&pod_id, Some(&vm_id), &job).await);
+            }
+        };
+        let hello = match ready {
+            Ok(hello) => {
+                info!(
+                    pod_id = %pod_id,
+                    cid = cid,
+                    protocol_version = hello.version,
+                    channels = %hello.channels.join(","),
+                    "Received ready signal from mvirt-one"
+                );
+                hello
             }
             Err(e) => {
                 error!(pod_id = %pod_id, cid = cid, error = %e, "Failed waiting for ready signal");
@@ -551,10 +1101,11 @@ impl PodService for PodServiceImpl {
                 }
                 return Err(Status::internal(format!("Ready signal failed: {}", e)));
             }
-        }
+        };
+        timer.stage(start_timing::POD_RUNNING);
 
         // Now connect to mvirt-one via vsock
-        let one_client = match OneClient::connect(&vsock_socket).await {
+        let one_client = match OneClient::connect(&vsock_socket, hello).await {
             Ok(client) => {
                 info!(pod_id = %pod_id, "Connected to mvirt-one via vsock");
                 client
@@ -575,6 +1126,22 @@ impl PodService for PodServiceImpl {
             }
         };
 
+        // Ship container and kernel output to mvirt-log from the start, so
+        // image pulls and early crashes are in there too
+        let container_names = _containers
+            .iter()
+            .chain(&init_containers)
+            .map(|c| (c.id.clone(), c.name.clone()))
+            .collect();
+        pod_logs::spawn(
+            &one_client,
+            self.audit.clone(),
+            pod_id.clone(),
+            vm_id.clone(),
+            container_names,
+        )
+        .await;
+
         // Store the one client for later use
         self.one_clients
             .write()
@@ -591,28 +1158,74 @@ impl PodService for PodServiceImpl {
         if let Some(channel) = channel {
             let mut one = OneServiceClient::new(channel);
 
-            // Convert container specs to mvirt-one format
-            let one_containers: Vec<OneContainerSpec> = _containers
-                .iter()
-                .map(|c| OneContainerSpec {
-                    id: c.id.clone(),
-                    name: c.name.clone(),
-                    image: c.image.clone(),
-                    command: c.command.clone(),
-                    args: c.args.clone(),
-                    env: c.env.clone(),
-                    working_dir: c.working_dir.clone(),
-                })
-                .collect();
-
             // Create pod in mvirt-one
             let create_req = OneCreatePodRequest {
                 id: pod_id.clone(),
                 name: pod_name.clone(),
-                containers: one_containers,
+                containers: _containers.iter().map(one_container_spec).collect(),
+                init_containers: init_containers.iter().map(one_container_spec).collect(),
             };
 
-            match one.create_pod(create_req).await {
+            // Creating the pod pulls its images; mirror the progress
+            if let Some(pod) = self.pods.write().await.get_mut(&pod_id) {
+                pod.state = PodState::Pulling;
+            }
+
+            // Images from archives have to be in mvirt-one before it pulls
+            let all_containers: Vec<ContainerSpec> = _containers
+                .iter()
+                .chain(&init_containers)
+                .cloned()
+                .collect();
+            let loaded = tokio::select! {
+                result = load_image_archives(&mut one, &all_containers, &self.pods, &pod_id, &job) => result,
+                _ = job.wait_cancelled() => {
+                    return Err(self.abort_start(&pod_id, Some(&vm_id), &job).await);
+                }
+            };
+            if let Err(e) = loaded {
+                error!(pod_id = %pod_id, error = %e, "Failed to load image archive");
+                self.one_clients.write().await.remove(&pod_id);
+                let _ = self.hypervisor.kill(&vm_id).await;
+                let _ = self
+                    .store
+                    .update_state(&vm_id, crate::proto::VmState::Stopped)
+                    .await;
+                let mut pods = self.pods.write().await;
+                if let Some(pod) = pods.get_mut(&pod_id) {
+                    pod.state = PodState::Failed;
+                    pod.pull_progress = None;
+                    pod.error_message = Some(format!("Image load failed: {}", e));
+                }
+                job.fail(&e);
+                return Err(Status::failed_precondition(format!(
+                    "Image load failed: {}",
+                    e
+                )));
+            }
+
+            job.set_phase("pulling");
+            let progress_watcher = tokio::spawn(watch_pull_progress(
+                one.clone(),
+                self.pods.clone(),
+                self.audit.clone(),
+                pod_id.clone(),
+            ));
+
+            let create_result = tokio::select! {
+                result = one.create_pod(create_req) => result,
+                _ = job.wait_cancelled() => {
+                    progress_watcher.abort();
+                    return Err(self.abort_start(&pod_id, Some(&vm_id), &job).await);
+                }
+            };
+            progress_watcher.abort();
+            if let Some(pod) = self.pods.write().await.get_mut(&pod_id) {
+                pod.state = PodState::Starting;
+                pod.pull_progress = None;
+            }
+
+            match create_result {
                 Ok(_) => {
                     debug!(pod_id = %pod_id, "Pod created in mvirt-one");
                 }
@@ -621,13 +1234,42 @@ impl PodService for PodServiceImpl {
                 }
             }
 
-            // Start pod in mvirt-one
+            // Start pod in mvirt-one; it answers once the init containers
+            // have run
             let start_req = OneStartPodRequest { id: pod_id.clone() };
+            if !init_containers.is_empty() {
+                job.set_phase("initializing");
+            }
+            let start_result = tokio::select! {
+                result = one.start_pod(start_req) => result,
+                _ = job.wait_cancelled() => {
+                    return Err(self.abort_start(&pod_id, Some(&vm_id), &job).await);
+                }
+            };
 
-            match one.start_pod(start_req).await {
+            match start_result {
                 Ok(_) => {
                     debug!(pod_id = %pod_id, "Pod started in mvirt-one");
                 }
+                // An init container failed, so the containers never started
+                Err(e) if e.code() == Code::Aborted => {
+                    error!(pod_id = %pod_id, error = %e.message(), "Pod initialization failed");
+                    self.refresh_container_status(std::slice::from_ref(&pod_id))
+                        .await;
+                    self.one_clients.write().await.remove(&pod_id);
+                    let _ = self.hypervisor.kill(&vm_id).await;
+                    let _ = self
+                        .store
+                        .update_state(&vm_id, crate::proto::VmState::Stopped)
+                        .await;
+                    let mut pods = self.pods.write().await;
+                    if let Some(pod) = pods.get_mut(&pod_id) {
+                        pod.state = PodState::Failed;
+                        pod.error_message = Some(e.message().to_string());
+                    }
+                    job.fail(e.message());
+                    return Err(Status::failed_precondition(e.message()));
+                }
                 Err(e) => {
                     warn!(pod_id = %pod_id, error = %e, "Failed to start pod in mvirt-one");
                     // Set error_message but don't fail - the VM is still running
@@ -639,6 +1281,19 @@ impl PodService for PodServiceImpl {
             }
         }
 
+        if job.is_cancelled() {
+            return Err(self.abort_start(&pod_id, Some(&vm_id), &job).await);
+        }
+
+        let timing = timer.finish();
+        if let Err(e) = self
+            .store
+            .record_start(&vm_id, start_timing::KIND_POD, &timing)
+            .await
+        {
+            warn!(pod_id = %pod_id, error = %e, "Failed to record pod start timing");
+        }
+
         // Mark pod as running
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -652,8 +1307,10 @@ impl PodService for PodServiceImpl {
                 pod.vm_id = Some(vm_id.clone());
                 pod.started_at = Some(now);
                 pod.error_message = None;
+                pod.start_timing = Some(timing);
             }
         }
+        job.complete();
 
         self.audit
             .log(
@@ -673,28 +1330,45 @@ impl PodService for PodServiceImpl {
         info!(pod_id = %req.id, "Stopping pod");
 
         // Get pod data and client (clone to avoid holding locks)
-        let (pod_id, pod_name, vm_id) = {
+        let (pod_id, pod_name, vm_id, was_paused) = {
             let mut pods = self.pods.write().await;
             let pod = pods
                 .get_mut(&req.id)
                 .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.id)))?;
 
             // Check state
-            if pod.state != PodState::Running {
+            if !matches!(pod.state, PodState::Running | PodState::Paused) {
                 return Err(Status::failed_precondition("Pod is not running"));
             }
 
+            let was_paused = pod.state == PodState::Paused;
             pod.state = PodState::Stopping;
-            (pod.id.clone(), pod.name.clone(), pod.vm_id.clone())
+            pod.paused_at = None;
+            (
+                pod.id.clone(),
+                pod.name.clone(),
+                pod.vm_id.clone(),
+                was_paused,
+            )
         };
 
+        // A frozen guest can't shut its containers down; thaw it first
+        if was_paused
+            && let Some(ref vm_id) = vm_id
+            && let Err(e) = self.hypervisor.resume(vm_id).await
+        {
+            warn!(vm_id = %vm_id, error = %e, "Failed to resume paused pod before stop");
+        }
+
         let timeout_secs = if req.timeout_seconds > 0 {
             req.timeout_seconds
         } else {
             10 // Default timeout
         };
 
-        // Try to gracefully stop the pod via one
+        // Try to gracefully stop the pod via one: each container gets its
+        // stop signal and grace period before it is killed
+        let mut stopped_containers = Vec::new();
         if let Some(ref vm_id) = vm_id {
             let mut clients = self.one_clients.write().await;
             if let Some(one_client) = clients.remove(&pod_id) {
@@ -705,8 +1379,11 @@ impl PodService for PodServiceImpl {
                 };
 
                 debug!(pod_id = %pod_id, "Sending stop request to one");
-                if let Err(e) = one.stop_pod(stop_req).await {
-                    warn!(pod_id = %pod_id, error = %e, "Failed to stop pod via one, will kill VM");
+                match one.stop_pod(stop_req).await {
+                    Ok(resp) => stopped_containers = resp.into_inner().containers,
+                    Err(e) => {
+                        warn!(pod_id = %pod_id, error = %e, "Failed to stop pod via one, will kill VM")
+                    }
                 }
             }
 
@@ -729,6 +1406,10 @@ impl PodService for PodServiceImpl {
             if let Some(pod) = pods.get_mut(&pod_id) {
                 pod.state = PodState::Stopped;
                 pod.vm_id = None;
+                for c in stopped_containers {
+                    pod.container_status
+                        .insert(c.id.clone(), container_from_one(c));
+                }
             }
         }
 
@@ -745,6 +1426,96 @@ impl PodService for PodServiceImpl {
         Ok(Response::new(pod.into()))
     }
 
+    async fn pause_pod(&self, request: Request<PausePodRequest>) -> Result<Response<Pod>, Status> {
+        let req = request.into_inner();
+        info!(pod_id = %req.id, "Pausing pod");
+
+        // Hold the lock across the API call so the pod can't be stopped
+        // or resumed halfway through
+        let mut pods = self.pods.write().await;
+        let pod = pods
+            .get_mut(&req.id)
+            .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.id)))?;
+        if pod.state != PodState::Running {
+            return Err(Status::failed_precondition("Pod is not running"));
+        }
+        let vm_id = pod
+            .vm_id
+            .clone()
+            .ok_or_else(|| Status::failed_precondition("Pod has no MicroVM"))?;
+
+        self.hypervisor
+            .pause(&vm_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to pause MicroVM: {}", e)))?;
+
+        pod.state = PodState::Paused;
+        pod.paused_at = Some(chrono::Utc::now().timestamp());
+        let response: Pod = pod.clone().into();
+        let pod_name = pod.name.clone();
+        drop(pods);
+
+        self.audit
+            .log(
+                mvirt_log::LogLevel::Audit,
+                &format!("Pod {} ({}) paused", pod_name, req.id),
+                vec![req.id.clone(), vm_id],
+            )
+            .await;
+
+        Ok(Response::new(response))
+    }
+
+    async fn resume_pod(
+        &self,
+        request: Request<ResumePodRequest>,
+    ) -> Result<Response<Pod>, Status> {
+        let req = request.into_inner();
+        info!(pod_id = %req.id, "Resuming pod");
+
+        let mut pods = self.pods.write().await;
+        let pod = pods
+            .get_mut(&req.id)
+            .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.id)))?;
+        if pod.state != PodState::Paused {
+            return Err(Status::failed_precondition("Pod is not paused"));
+        }
+        let vm_id = pod
+            .vm_id
+            .clone()
+            .ok_or_else(|| Status::failed_precondition("Pod has no MicroVM"))?;
+
+        self.hypervisor
+            .resume(&vm_id)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to resume MicroVM: {}", e)))?;
+
+        let paused_secs = pod
+            .paused_at
+            .take()
+            .map(|t| chrono::Utc::now().timestamp() - t)
+            .unwrap_or(0);
+        pod.state = PodState::Running;
+        // The pre-pause sample would smear the pause into the next CPU rate
+        pod.last_stats = None;
+        let response: Pod = pod.clone().into();
+        let pod_name = pod.name.clone();
+        drop(pods);
+
+        self.audit
+            .log(
+                mvirt_log::LogLevel::Audit,
+                &format!(
+                    "Pod {} ({}) resumed after {}s",
+                    pod_name, req.id, paused_secs
+                ),
+                vec![req.id.clone(), vm_id],
+            )
+            .await;
+
+        Ok(Response::new(response))
+    }
+
     type PodLogsStream = ReceiverStream<Result<LogChunk, Status>>;
 
     async fn pod_logs(
@@ -763,6 +1534,65 @@ impl PodService for PodServiceImpl {
         Err(Status::unimplemented("Pod exec not yet implemented"))
     }
 
+    async fn run_pod_command(
+        &self,
+        request: Request<RunPodCommandRequest>,
+    ) -> Result<Response<RunPodCommandResponse>, Status> {
+        let req = request.into_inner();
+        info!(pod_id = %req.pod_id, container = %req.container, "Running command in pod");
+
+        let pod_name = {
+            let pods = self.pods.read().await;
+            let pod = pods
+                .get(&req.pod_id)
+                .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.pod_id)))?;
+            if pod.state != PodState::Running {
+                return Err(Status::failed_precondition("Pod is not running"));
+            }
+            pod.name.clone()
+        };
+
+        let channel = self
+            .one_clients
+            .read()
+            .await
+            .get(&req.pod_id)
+            .map(|c| c.channel())
+            .ok_or_else(|| Status::unavailable("No connection to pod"))?;
+        let mut one = OneServiceClient::new(channel);
+
+        self.audit
+            .log(
+                mvirt_log::LogLevel::Audit,
+                &format!(
+                    "Running command in pod {} ({}): {}",
+                    pod_name,
+                    req.pod_id,
+                    req.command.join(" ")
+                ),
+                vec![req.pod_id.clone()],
+            )
+            .await;
+
+        // mvirt-one enforces the timeout; the call only fails on errors
+        let response = one
+            .run_command(OneRunCommandRequest {
+                pod_id: req.pod_id,
+                container_id: req.container,
+                command: req.command,
+                timeout_seconds: req.timeout_seconds,
+            })
+            .await?
+            .into_inner();
+
+        Ok(Response::new(RunPodCommandResponse {
+            exit_code: response.exit_code,
+            stdout: response.stdout,
+            stderr: response.stderr,
+            timed_out: response.timed_out,
+        }))
+    }
+
     async fn get_pod_network_info(
         &self,
         request: Request<GetPodNetworkInfoRequest>,
@@ -819,4 +1649,55 @@ impl PodService for PodServiceImpl {
 
         Ok(Response::new(PodNetworkInfo { interfaces }))
     }
+
+    async fn list_guest_images(
+        &self,
+        _request: Request<ListGuestImagesRequest>,
+    ) -> Result<Response<ListGuestImagesResponse>, Status> {
+        let images = self.guest_images.list().iter().map(Into::into).collect();
+        Ok(Response::new(ListGuestImagesResponse {
+            images,
+            default_image: self.guest_images.default_name().to_string(),
+        }))
+    }
+
+    async fn get_pod_stats(
+        &self,
+        request: Request<GetPodStatsRequest>,
+    ) -> Result<Response<PodStats>, Status> {
+        let req = request.into_inner();
+        debug!(pod_id = %req.pod_id, "Getting pod stats");
+
+        let prev = {
+            let pods = self.pods.read().await;
+            let pod = pods
+                .get(&req.pod_id)
+                .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.pod_id)))?;
+            if pod.state != PodState::Running {
+                return Err(Status::failed_precondition("Pod is not running"));
+            }
+            pod.last_stats.clone()
+        };
+
+        // CPU rates need two samples. Reuse the previous poll's sample when
+        // it's recent, otherwise take a fresh pair.
+        let max_age_usec = STATS_MAX_SAMPLE_AGE.as_micros() as i64;
+        let mut sample = self.fetch_one_stats(&req.pod_id).await?;
+        let prev = match prev {
+            Some(p) if sample.timestamp_usec - p.timestamp_usec <= max_age_usec => p,
+            _ => {
+                tokio::time::sleep(STATS_SAMPLE_INTERVAL).await;
+                std::mem::replace(&mut sample, self.fetch_one_stats(&req.pod_id).await?)
+            }
+        };
+
+        let mut pods = self.pods.write().await;
+        let pod = pods
+            .get_mut(&req.pod_id)
+            .ok_or_else(|| Status::not_found(format!("Pod {} not found", req.pod_id)))?;
+        let stats = pod_stats(pod, &prev, &sample);
+        pod.last_stats = Some(sample);
+
+        Ok(Response::new(stats))
+    }
 }
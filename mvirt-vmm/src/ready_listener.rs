@@ -2,16 +2,17 @@
 //!
 //! Guests (mvirt-one) connect to CID 2 (host) on port 1025 to signal they're ready.
 //! Cloud-hypervisor proxies this as a connection to `<vsock_socket>_1025`.
+//! The guest's hello line carries its protocol version and channels.
 
 use std::path::{Path, PathBuf};
 use std::time::Duration;
 
-use tokio::io::AsyncReadExt;
+use mvirt_one::vsock::{self, Hello, READY_PORT};
 use tokio::net::UnixListener;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-/// Port on which guests signal ready (used in socket path suffix).
-const READY_SIGNAL_PORT: u32 = 1025;
+/// How long to wait for the hello line once the guest has connected.
+const HELLO_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// A prepared ready signal listener.
 ///
@@ -26,8 +27,7 @@ impl ReadySignalListener {
     ///
     /// Call this BEFORE starting the VM, then call `wait()` after.
     pub async fn new(vsock_socket: &Path) -> anyhow::Result<Self> {
-        let socket_path =
-            PathBuf::from(format!("{}_{}", vsock_socket.display(), READY_SIGNAL_PORT));
+        let socket_path = PathBuf::from(format!("{}_{}", vsock_socket.display(), READY_PORT));
 
         debug!(path = %socket_path.display(), "Creating ready signal listener");
 
@@ -44,20 +44,33 @@ impl ReadySignalListener {
         })
     }
 
-    /// Wait for the guest to signal ready.
+    /// Wait for the guest to signal ready and return its hello.
     ///
     /// Consumes the listener and cleans up the socket file.
-    pub async fn wait(self, timeout: Duration) -> anyhow::Result<()> {
+    pub async fn wait(self, timeout: Duration) -> anyhow::Result<Hello> {
         let result = tokio::time::timeout(timeout, async {
             match self.listener.accept().await {
                 Ok((mut stream, _)) => {
                     info!(path = %self.socket_path.display(), "Guest connected to ready signal socket");
 
-                    // Read any data the guest sends (e.g., "READY\n")
-                    let mut buf = [0u8; 64];
-                    let _ = stream.read(&mut buf).await;
-
-                    Ok(())
+                    // The connection itself is the ready signal; a guest
+                    // that doesn't send a readable hello is treated as
+                    // unversioned.
+                    let hello =
+                        match tokio::time::timeout(HELLO_TIMEOUT, vsock::read_line(&mut stream))
+                            .await
+                        {
+                            Ok(Ok(line)) => Hello::parse(&line),
+                            Ok(Err(e)) => {
+                                warn!(error = %e, "Failed to read guest hello");
+                                Hello::legacy()
+                            }
+                            Err(_) => {
+                                warn!("Timeout reading guest hello");
+                                Hello::legacy()
+                            }
+                        };
+                    Ok(hello)
                 }
                 Err(e) => Err(anyhow::anyhow!("Failed to accept: {}", e)),
             }
@@ -68,7 +81,7 @@ impl ReadySignalListener {
         let _ = tokio::fs::remove_file(&self.socket_path).await;
 
         match result {
-            Ok(Ok(())) => Ok(()),
+            Ok(Ok(hello)) => Ok(hello),
             Ok(Err(e)) => Err(e),
             Err(_) => Err(anyhow::anyhow!("Timeout waiting for ready signal")),
         }
@@ -0,0 +1,377 @@
+//! Disks on network-attached storage.
+//!
+//! A disk whose path is a target URL instead of a host path is connected
+//! before the VM boots and disconnected once it stops:
+//!
+//! ```text
+//! nvme+tcp://10.0.0.5[:4420]/nqn.2024-01.io.example:vm-root
+//! iscsi://10.0.0.6[:3260]/iqn.2024-01.io.example:storage/1    (LUN, default 0)
+//! ```
+//!
+//! Credentials never go into the VM config. A disk names a secret, a file
+//! of `key=value` lines under the secrets directory (`/etc/mvirt/secrets`
+//! unless `MVIRT_SECRETS_DIR` says otherwise):
+//!
+//! ```text
+//! # iSCSI CHAP
+//! username=vm-root
+//! password=...
+//! # NVMe-oF in-band authentication
+//! dhchap_secret=DHHC-1:00:...
+//! dhchap_ctrl_secret=DHHC-1:00:...
+//! ```
+//!
+//! Connecting uses `nvme` (nvme-cli) and `iscsiadm` (open-iscsi).
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Result, anyhow};
+use tokio::process::Command;
+use tracing::{info, warn};
+
+use crate::proto::DiskConfig;
+
+const NVME_DEFAULT_PORT: u16 = 4420;
+const ISCSI_DEFAULT_PORT: u16 = 3260;
+
+/// How long to wait for the block device after connecting.
+const DEVICE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn secrets_dir() -> PathBuf {
+    std::env::var("MVIRT_SECRETS_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from("/etc/mvirt/secrets"))
+}
+
+/// A remote block device a disk path can refer to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteTarget {
+    NvmeTcp {
+        host: String,
+        port: u16,
+        nqn: String,
+    },
+    Iscsi {
+        host: String,
+        port: u16,
+        iqn: String,
+        lun: u32,
+    },
+}
+
+impl fmt::Display for RemoteTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NvmeTcp { host, port, nqn } => write!(f, "nvme+tcp://{}:{}/{}", host, port, nqn),
+            Self::Iscsi {
+                host,
+                port,
+                iqn,
+                lun,
+            } => write!(f, "iscsi://{}:{}/{}/{}", host, port, iqn, lun),
+        }
+    }
+}
+
+/// Split `host[:port]`, with IPv6 hosts in brackets.
+fn host_port(authority: &str, default_port: u16) -> Option<(String, u16)> {
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(rest) => {
+            let (host, tail) = rest.split_once(']')?;
+            (host, tail.strip_prefix(':'))
+        }
+        None => match authority.rsplit_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    if host.is_empty() {
+        return None;
+    }
+    let port = match port {
+        Some(p) => p.parse().ok()?,
+        None => default_port,
+    };
+    Some((host.to_string(), port))
+}
+
+impl RemoteTarget {
+    /// Parse a disk path. `Ok(None)` for a plain host path.
+    pub fn parse(path: &str) -> Result<Option<Self>> {
+        let Some((scheme, rest)) = path.split_once("://") else {
+            return Ok(None);
+        };
+        let invalid = || anyhow!("invalid {} disk URL: {}", scheme, path);
+        let (authority, target) = rest.split_once('/').ok_or_else(invalid)?;
+        match scheme {
+            "nvme+tcp" => {
+                let (host, port) = host_port(authority, NVME_DEFAULT_PORT).ok_or_else(invalid)?;
+                if target.is_empty() || target.contains('/') {
+                    return Err(invalid());
+                }
+                Ok(Some(Self::NvmeTcp {
+                    host,
+                    port,
+                    nqn: target.to_string(),
+                }))
+            }
+            "iscsi" => {
+                let (host, port) = host_port(authority, ISCSI_DEFAULT_PORT).ok_or_else(invalid)?;
+                let (iqn, lun) = match target.split_once('/') {
+                    Some((iqn, lun)) => (iqn, lun.parse().map_err(|_| invalid())?),
+                    None => (target, 0),
+                };
+                if iqn.is_empty() {
+                    return Err(invalid());
+                }
+                Ok(Some(Self::Iscsi {
+                    host,
+                    port,
+                    iqn: iqn.to_string(),
+                    lun,
+                }))
+            }
+            _ => Err(anyhow!("unsupported disk URL scheme: {}", scheme)),
+        }
+    }
+
+    /// The local block device of the target, if connected.
+    async fn device(&self) -> Option<PathBuf> {
+        match self {
+            Self::NvmeTcp { nqn, .. } => nvme_device(nqn).await,
+            Self::Iscsi {
+                host,
+                port,
+                iqn,
+                lun,
+            } => {
+                let host = if host.contains(':') {
+                    format!("[{}]", host)
+                } else {
+                    host.clone()
+                };
+                let path = PathBuf::from(format!(
+                    "/dev/disk/by-path/ip-{}:{}-iscsi-{}-lun-{}",
+                    host, port, iqn, lun
+                ));
+                path.exists().then_some(path)
+            }
+        }
+    }
+
+    /// Connect the target and return its block device.
+    pub async fn connect(&self, secret: Option<&str>) -> Result<PathBuf> {
+        if let Some(device) = self.device().await {
+            return Ok(device);
+        }
+        let secret = match secret {
+            Some(name) => read_secret(&secrets_dir(), name)?,
+            None => HashMap::new(),
+        };
+
+        match self {
+            Self::NvmeTcp { host, port, nqn } => {
+                let mut args = vec![
+                    "connect".to_string(),
+                    "--transport=tcp".to_string(),
+                    format!("--traddr={}", host),
+                    format!("--trsvcid={}", port),
+                    format!("--nqn={}", nqn),
+                ];
+                if let Some(key) = secret.get("dhchap_secret") {
+                    args.push(format!("--dhchap-secret={}", key));
+                }
+                if let Some(key) = secret.get("dhchap_ctrl_secret") {
+                    args.push(format!("--dhchap-ctrl-secret={}", key));
+                }
+                run("nvme", &args).await?;
+            }
+            Self::Iscsi {
+                host, port, iqn, ..
+            } => {
+                let portal = format!("{}:{}", host, port);
+                let node = ["-m", "node", "-T", iqn.as_str(), "-p", &portal];
+                run("iscsiadm", &[&node[..], &["-o", "new"]].concat()).await?;
+                if let (Some(user), Some(password)) =
+                    (secret.get("username"), secret.get("password"))
+                {
+                    for (key, value) in [
+                        ("node.session.auth.authmethod", "CHAP"),
+                        ("node.session.auth.username", user.as_str()),
+                        ("node.session.auth.password", password.as_str()),
+                    ] {
+                        run(
+                            "iscsiadm",
+                            &[&node[..], &["-o", "update", "-n", key, "-v", value]].concat(),
+                        )
+                        .await?;
+                    }
+                }
+                run("iscsiadm", &[&node[..], &["--login"]].concat()).await?;
+            }
+        }
+
+        let deadline = tokio::time::Instant::now() + DEVICE_TIMEOUT;
+        loop {
+            if let Some(device) = self.device().await {
+                info!(target = %self, device = %device.display(), "Remote disk connected");
+                return Ok(device);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                self.disconnect().await;
+                return Err(anyhow!("no block device appeared for {}", self));
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    /// Disconnect the target if it is connected.
+    pub async fn disconnect(&self) {
+        let result = match self {
+            Self::NvmeTcp { nqn, .. } => {
+                if nvme_device(nqn).await.is_none() {
+                    return;
+                }
+                run("nvme", &["disconnect", "--nqn", nqn.as_str()]).await
+            }
+            Self::Iscsi {
+                host, port, iqn, ..
+            } => {
+                if self.device().await.is_none() {
+                    return;
+                }
+                let portal = format!("{}:{}", host, port);
+                run(
+                    "iscsiadm",
+                    &["-m", "node", "-T", iqn.as_str(), "-p", &portal, "--logout"],
+                )
+                .await
+            }
+        };
+        match result {
+            Ok(()) => info!(target = %self, "Remote disk disconnected"),
+            Err(e) => warn!(target = %self, error = %e, "Failed to disconnect remote disk"),
+        }
+    }
+}
+
+/// Connect the remote disks of a VM. Returns the path cloud-hypervisor
+/// gets for each disk, in order; on error the disks connected so far are
+/// disconnected again.
+pub async fn connect_all(disks: &[DiskConfig]) -> Result<Vec<String>> {
+    let mut paths = Vec::with_capacity(disks.len());
+    for (i, disk) in disks.iter().enumerate() {
+        let path = match RemoteTarget::parse(&disk.path) {
+            Ok(None) => disk.path.clone(),
+            Ok(Some(target)) => match target.connect(disk.secret.as_deref()).await {
+                Ok(device) => device.display().to_string(),
+                Err(e) => {
+                    disconnect_all(&disks[..i]).await;
+                    return Err(e);
+                }
+            },
+            Err(e) => {
+                disconnect_all(&disks[..i]).await;
+                return Err(e);
+            }
+        };
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// Disconnect the remote disks of a VM.
+pub async fn disconnect_all(disks: &[DiskConfig]) {
+    for disk in disks {
+        if let Ok(Some(target)) = RemoteTarget::parse(&disk.path) {
+            target.disconnect().await;
+        }
+    }
+}
+
+/// Block device of the connected NVMe subsystem `nqn`: its first namespace.
+async fn nvme_device(nqn: &str) -> Option<PathBuf> {
+    let mut subsystems = tokio::fs::read_dir("/sys/class/nvme-subsystem")
+        .await
+        .ok()?;
+    while let Ok(Some(subsys)) = subsystems.next_entry().await {
+        let dir = subsys.path();
+        match tokio::fs::read_to_string(dir.join("subsysnqn")).await {
+            Ok(found) if found.trim() == nqn => {}
+            _ => continue,
+        }
+        let mut entries = tokio::fs::read_dir(&dir).await.ok()?;
+        let mut namespaces = Vec::new();
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Namespaces (nvme0n1) next to controllers (nvme0)
+            if name.starts_with("nvme") && name[4..].contains('n') {
+                namespaces.push(name);
+            }
+        }
+        namespaces.sort();
+        return namespaces
+            .into_iter()
+            .next()
+            .map(|name| Path::new("/dev").join(name));
+    }
+    None
+}
+
+/// Read the `key=value` lines of secret `name` in `dir`.
+fn read_secret(dir: &Path, name: &str) -> Result<HashMap<String, String>> {
+    if name.is_empty() || name.contains('/') || name.starts_with('.') {
+        return Err(anyhow!("invalid secret name: {}", name));
+    }
+    let path = dir.join(name);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| anyhow!("read secret {}: {}", path.display(), e))?;
+    Ok(content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+        .collect())
+}
+
+async fn run<S: AsRef<std::ffi::OsStr>>(program: &str, args: &[S]) -> Result<()> {
+    let output = Command::new(program).args(args).output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(anyhow!("{} failed: {}", program, stderr.trim()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_targets() {
+        assert_eq!(RemoteTarget::parse("/dev/zvol/mvirt/vm-1").unwrap(), None);
+        assert_eq!(
+            RemoteTarget::parse("nvme+tcp://10.0.0.5/nqn.2024-01.io.example:vm").unwrap(),
+            Some(RemoteTarget::NvmeTcp {
+                host: "10.0.0.5".to_string(),
+                port: NVME_DEFAULT_PORT,
+                nqn: "nqn.2024-01.io.example:vm".to_string(),
+            })
+        );
+        assert_eq!(
+            RemoteTarget::parse("iscsi://[fd00::6]:3261/iqn.2024-01.io.example:s/2").unwrap(),
+            Some(RemoteTarget::Iscsi {
+                host: "fd00::6".to_string(),
+                port: 3261,
+                iqn: "iqn.2024-01.io.example:s".to_string(),
+                lun: 2,
+            })
+        );
+        assert!(RemoteTarget::parse("iscsi://10.0.0.6/").is_err());
+        assert!(RemoteTarget::parse("nbd://10.0.0.6/export").is_err());
+    }
+}
@@ -0,0 +1,200 @@
+//! Resizing VMs with UpdateVmResources.
+//!
+//! A running VM grows in place: cloud-hypervisor hot-plugs vCPUs up to the
+//! `max_vcpus` it booted with and adds memory up to `max_memory_mb` through
+//! virtio-mem. It doesn't shrink; that, and growing past the maximums,
+//! takes a stop. A stopped VM takes any size for its next boot.
+//!
+//! Growth is checked against what the host has, as sampled by mvirt-node
+//! into `resources.json` in its state directory. Without a recent sample
+//! (no mvirt-node on the host, or it stopped) the check is skipped.
+
+use std::path::Path;
+
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::proto::VmConfig;
+
+/// Where mvirt-node writes its samples by default.
+pub const DEFAULT_NODE_RESOURCES: &str = "/var/lib/mvirt-node/resources.json";
+
+/// Older samples don't say what the host has now; mvirt-node samples every
+/// few seconds.
+const MAX_SAMPLE_AGE_SECS: i64 = 60;
+
+/// The host capacity from mvirt-node's `resources.json`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct NodeCapacity {
+    /// Unix seconds
+    pub sampled_at: i64,
+    pub cpu_cores: u32,
+    pub memory_mb: u64,
+    pub available_memory_mb: u64,
+}
+
+impl NodeCapacity {
+    /// The latest sample at `path`, unless it is missing or stale.
+    pub async fn read(path: &Path) -> Option<Self> {
+        let data = match tokio::fs::read(path).await {
+            Ok(data) => data,
+            Err(e) => {
+                debug!(path = %path.display(), error = %e, "No node resources");
+                return None;
+            }
+        };
+        let capacity: Self = match serde_json::from_slice(&data) {
+            Ok(capacity) => capacity,
+            Err(e) => {
+                debug!(path = %path.display(), error = %e, "Unreadable node resources");
+                return None;
+            }
+        };
+        let age = chrono::Utc::now().timestamp() - capacity.sampled_at;
+        if age > MAX_SAMPLE_AGE_SECS {
+            debug!(path = %path.display(), age, "Stale node resources");
+            return None;
+        }
+        Some(capacity)
+    }
+}
+
+/// Check a resize of a VM with `config` against the VM itself: a running
+/// VM only grows, and only up to its maximums.
+pub fn check_limits(
+    config: &VmConfig,
+    running: bool,
+    vcpus: Option<u32>,
+    memory_mb: Option<u64>,
+) -> Result<(), String> {
+    if !running {
+        return Ok(());
+    }
+    if let Some(vcpus) = vcpus {
+        if vcpus < config.vcpus {
+            return Err(format!(
+                "a running VM can't shrink from {} to {} vCPUs; stop it first",
+                config.vcpus, vcpus
+            ));
+        }
+        let max = config.max_vcpus.max(config.vcpus);
+        if vcpus > max {
+            return Err(format!(
+                "the VM booted with room for {} vCPUs; stop it to go beyond",
+                max
+            ));
+        }
+    }
+    if let Some(memory_mb) = memory_mb {
+        if memory_mb < config.memory_mb {
+            return Err(format!(
+                "a running VM can't shrink from {} to {} MB; stop it first",
+                config.memory_mb, memory_mb
+            ));
+        }
+        let max = config.max_memory_mb.max(config.memory_mb);
+        if memory_mb > max {
+            return Err(format!(
+                "the VM booted with room for {} MB; stop it to go beyond",
+                max
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Check a resize of a VM with `config` against the host. A running VM
+/// takes the memory it grows by from what is available now; a stopped VM
+/// has to fit the host at all.
+pub fn check_capacity(
+    node: &NodeCapacity,
+    config: &VmConfig,
+    running: bool,
+    vcpus: Option<u32>,
+    memory_mb: Option<u64>,
+) -> Result<(), String> {
+    if let Some(vcpus) = vcpus
+        && vcpus > node.cpu_cores
+    {
+        return Err(format!(
+            "{} vCPUs requested, the host has {} CPU cores",
+            vcpus, node.cpu_cores
+        ));
+    }
+    if let Some(memory_mb) = memory_mb {
+        if running {
+            let growth = memory_mb.saturating_sub(config.memory_mb);
+            if growth > node.available_memory_mb {
+                return Err(format!(
+                    "{} MB more memory requested, the host has {} MB available",
+                    growth, node.available_memory_mb
+                ));
+            }
+        } else if memory_mb > node.memory_mb {
+            return Err(format!(
+                "{} MB memory requested, the host has {} MB",
+                memory_mb, node.memory_mb
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> VmConfig {
+        VmConfig {
+            vcpus: 2,
+            memory_mb: 1024,
+            max_vcpus: 4,
+            max_memory_mb: 4096,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let config = config();
+        assert!(check_limits(&config, true, Some(4), Some(4096)).is_ok());
+        assert!(check_limits(&config, true, None, Some(2048)).is_ok());
+
+        let err = check_limits(&config, true, Some(1), None).unwrap_err();
+        assert!(err.contains("shrink"));
+        let err = check_limits(&config, true, Some(5), None).unwrap_err();
+        assert!(err.contains("room for 4 vCPUs"));
+        let err = check_limits(&config, true, None, Some(8192)).unwrap_err();
+        assert!(err.contains("room for 4096 MB"));
+
+        // Without headroom the running VM stays at its boot size
+        let fixed = VmConfig {
+            max_vcpus: 0,
+            max_memory_mb: 0,
+            ..config.clone()
+        };
+        assert!(check_limits(&fixed, true, Some(2), Some(1024)).is_ok());
+        assert!(check_limits(&fixed, true, Some(3), None).is_err());
+
+        // A stopped VM takes any size
+        assert!(check_limits(&config, false, Some(1), Some(8192)).is_ok());
+    }
+
+    #[test]
+    fn test_check_capacity() {
+        let node = NodeCapacity {
+            sampled_at: 0,
+            cpu_cores: 8,
+            memory_mb: 16384,
+            available_memory_mb: 2048,
+        };
+        let config = config();
+        assert!(check_capacity(&node, &config, true, Some(8), Some(3072)).is_ok());
+        assert!(check_capacity(&node, &config, true, Some(9), None).is_err());
+        let err = check_capacity(&node, &config, true, None, Some(4096)).unwrap_err();
+        assert!(err.contains("3072 MB more"));
+
+        assert!(check_capacity(&node, &config, false, None, Some(16384)).is_ok());
+        assert!(check_capacity(&node, &config, false, None, Some(32768)).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+//! VM snapshots: the memory and device state cloud-hypervisor writes for a
+//! paused VM, plus ZFS snapshots of the VM's volumes taken before it runs
+//! again, so that disks and memory match when the VM is restored.
+//!
+//! A snapshot is the directory `<data_dir>/snapshots/<vm id>/<name>/`, with
+//! cloud-hypervisor's files under `state/` and a [`SnapshotMeta`] in
+//! `snapshot.json`. The VM directory can't hold them since it goes away
+//! whenever the VM stops. The volume snapshots in mvirt-zfs carry the name
+//! of the VM snapshot.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+use crate::proto::VmConfig;
+use crate::remote_disk::RemoteTarget;
+
+const META_FILE: &str = "snapshot.json";
+const STATE_DIR: &str = "state";
+
+/// What mvirt knows about a snapshot beyond cloud-hypervisor's state.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SnapshotMeta {
+    pub name: String,
+    /// Unix seconds
+    pub created_at: i64,
+    /// mvirt-zfs volumes snapshotted with the VM
+    pub volumes: Vec<String>,
+}
+
+pub struct SnapshotStore {
+    dir: PathBuf,
+}
+
+impl SnapshotStore {
+    pub fn new(data_dir: &Path) -> Self {
+        Self {
+            dir: data_dir.join("snapshots"),
+        }
+    }
+
+    fn vm_dir(&self, vm_id: &str) -> PathBuf {
+        self.dir.join(vm_id)
+    }
+
+    fn snapshot_dir(&self, vm_id: &str, name: &str) -> PathBuf {
+        self.vm_dir(vm_id).join(name)
+    }
+
+    /// Where cloud-hypervisor writes and reads the state of a snapshot.
+    pub fn state_dir(&self, vm_id: &str, name: &str) -> PathBuf {
+        self.snapshot_dir(vm_id, name).join(STATE_DIR)
+    }
+
+    /// Create the directories of a new snapshot. Fails if the snapshot
+    /// exists, finished or not.
+    pub async fn create(&self, vm_id: &str, name: &str) -> Result<()> {
+        tokio::fs::create_dir_all(self.vm_dir(vm_id)).await?;
+        match tokio::fs::create_dir(self.snapshot_dir(vm_id, name)).await {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                return Err(anyhow!("snapshot {} of VM {} exists", name, vm_id));
+            }
+            result => result?,
+        }
+        tokio::fs::create_dir(self.state_dir(vm_id, name)).await?;
+        Ok(())
+    }
+
+    /// Mark a snapshot complete by writing its metadata.
+    pub async fn save(&self, vm_id: &str, meta: &SnapshotMeta) -> Result<()> {
+        let path = self.snapshot_dir(vm_id, &meta.name).join(META_FILE);
+        tokio::fs::write(path, serde_json::to_vec_pretty(meta)?).await?;
+        Ok(())
+    }
+
+    /// A complete snapshot of the VM.
+    pub async fn get(&self, vm_id: &str, name: &str) -> Result<Option<SnapshotMeta>> {
+        let path = self.snapshot_dir(vm_id, name).join(META_FILE);
+        match tokio::fs::read(&path).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// The complete snapshots of the VM, oldest first.
+    pub async fn list(&self, vm_id: &str) -> Result<Vec<SnapshotMeta>> {
+        let mut snapshots = Vec::new();
+        let mut entries = match tokio::fs::read_dir(self.vm_dir(vm_id)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(snapshots),
+            Err(e) => return Err(e.into()),
+        };
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str()
+                && let Some(meta) = self.get(vm_id, name).await?
+            {
+                snapshots.push(meta);
+            }
+        }
+        snapshots.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.name.cmp(&b.name)));
+        Ok(snapshots)
+    }
+
+    /// Bytes of memory and device state a snapshot takes on disk.
+    pub async fn size(&self, vm_id: &str, name: &str) -> u64 {
+        let mut size = 0;
+        if let Ok(mut entries) = tokio::fs::read_dir(self.state_dir(vm_id, name)).await {
+            while let Ok(Some(entry)) = entries.next_entry().await {
+                if let Ok(metadata) = entry.metadata().await {
+                    size += metadata.len();
+                }
+            }
+        }
+        size
+    }
+
+    pub async fn remove(&self, vm_id: &str, name: &str) -> Result<()> {
+        match tokio::fs::remove_dir_all(self.snapshot_dir(vm_id, name)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove every snapshot of a deleted VM.
+    pub async fn remove_all(&self, vm_id: &str) -> Result<()> {
+        match tokio::fs::remove_dir_all(self.vm_dir(vm_id)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// The mvirt-zfs volumes to snapshot with a VM, given the (name, device
+/// path) of every volume. Read-only local disks don't change and may be
+/// anything; a writable disk that isn't a volume can't be snapshotted.
+/// Network-attached disks may come back under another device path, which
+/// the state wouldn't match.
+pub fn disk_volumes(
+    config: &VmConfig,
+    volumes: &[(String, String)],
+) -> Result<Vec<String>, String> {
+    let mut names = Vec::new();
+    for disk in &config.disks {
+        if !matches!(RemoteTarget::parse(&disk.path), Ok(None)) {
+            return Err(format!(
+                "disk {} is network-attached and can't be snapshotted",
+                disk.path
+            ));
+        }
+        match volumes.iter().find(|(_, path)| *path == disk.path) {
+            Some((name, _)) => names.push(name.clone()),
+            None if disk.readonly => {}
+            None => {
+                return Err(format!(
+                    "disk {} is not an mvirt-zfs volume and can't be snapshotted",
+                    disk.path
+                ));
+            }
+        }
+    }
+    Ok(names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::DiskConfig;
+
+    fn disk(path: &str, readonly: bool) -> DiskConfig {
+        DiskConfig {
+            path: path.to_string(),
+            readonly,
+            secret: None,
+        }
+    }
+
+    #[test]
+    fn test_disk_volumes() {
+        let volumes = vec![
+            ("root".to_string(), "/dev/zvol/mvirt/1".to_string()),
+            ("data".to_string(), "/dev/zvol/mvirt/2".to_string()),
+        ];
+        let config = VmConfig {
+            disks: vec![
+                disk("/dev/zvol/mvirt/2", false),
+                disk("/srv/images/tools.iso", true),
+                disk("/dev/zvol/mvirt/1", true),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(disk_volumes(&config, &volumes).unwrap(), ["data", "root"]);
+
+        let config = VmConfig {
+            disks: vec![disk("/srv/images/scratch.raw", false)],
+            ..Default::default()
+        };
+        let err = disk_volumes(&config, &volumes).unwrap_err();
+        assert!(err.contains("/srv/images/scratch.raw"));
+
+        let config = VmConfig {
+            disks: vec![disk(
+                "iscsi://10.0.0.6/iqn.2024-01.io.example:storage/1",
+                true,
+            )],
+            ..Default::default()
+        };
+        assert!(disk_volumes(&config, &volumes).is_err());
+    }
+}
@@ -0,0 +1,174 @@
+//! Boot concurrency limit.
+//!
+//! A booting VM reads its disk hard for its first seconds. When dozens
+//! start at once, as after a host reboot or a batch of StartVm/StartPod
+//! calls, they saturate the ZFS pool and every one of them boots slowly.
+//! Starts therefore take a slot from a [`StartQueue`] and keep it until
+//! the VM has had a while to settle. Waiting starts are served in arrival
+//! order, so each one can report its place in the queue.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Simultaneous boots allowed unless configured otherwise.
+pub const DEFAULT_MAX_PARALLEL_STARTS: usize = 4;
+
+/// How long a started VM keeps its slot unless configured otherwise.
+pub const DEFAULT_START_SETTLE: Duration = Duration::from_secs(10);
+
+pub struct StartQueue {
+    /// `None` when starts are not limited.
+    slots: Option<Arc<Semaphore>>,
+    settle: Duration,
+    /// IDs of the VMs waiting for a slot, first in line first.
+    waiting: Mutex<VecDeque<String>>,
+}
+
+/// A boot slot. Dropping it frees the slot right away, which is what a
+/// failed start wants; a started VM calls [`StartSlot::release_after_settle`].
+pub struct StartSlot {
+    permit: Option<OwnedSemaphorePermit>,
+    settle: Duration,
+}
+
+impl StartSlot {
+    /// Free the slot once the VM has had the settle time to boot.
+    pub fn release_after_settle(self) {
+        if let Some(permit) = self.permit {
+            let settle = self.settle;
+            tokio::spawn(async move {
+                tokio::time::sleep(settle).await;
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Takes a VM out of the waiting list however its wait ends, including
+/// the start request being dropped.
+struct Waiting<'a> {
+    queue: &'a Mutex<VecDeque<String>>,
+    vm_id: &'a str,
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(i) = queue.iter().position(|id| id == self.vm_id) {
+            queue.remove(i);
+        }
+    }
+}
+
+impl StartQueue {
+    /// Allow `max_parallel` boots at once, each holding its slot for
+    /// `settle` after the VM started. 0 means no limit.
+    pub fn new(max_parallel: usize, settle: Duration) -> Self {
+        Self {
+            slots: (max_parallel > 0).then(|| Arc::new(Semaphore::new(max_parallel))),
+            settle,
+            waiting: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Wait for a boot slot for `vm_id`.
+    pub async fn acquire(&self, vm_id: &str) -> StartSlot {
+        let Some(slots) = &self.slots else {
+            return StartSlot {
+                permit: None,
+                settle: self.settle,
+            };
+        };
+        self.waiting.lock().unwrap().push_back(vm_id.to_string());
+        let _waiting = Waiting {
+            queue: &self.waiting,
+            vm_id,
+        };
+        // The semaphore is fair, so slots are handed out in the same order
+        // as the waiting list.
+        let permit = slots
+            .clone()
+            .acquire_owned()
+            .await
+            .expect("start queue semaphore is never closed");
+        StartSlot {
+            permit: Some(permit),
+            settle: self.settle,
+        }
+    }
+
+    /// Place of a waiting VM in the queue, 1 being next; `None` if the VM
+    /// isn't waiting.
+    pub fn position(&self, vm_id: &str) -> Option<u32> {
+        self.waiting
+            .lock()
+            .unwrap()
+            .iter()
+            .position(|id| id == vm_id)
+            .map(|i| i as u32 + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn settle_tasks() {
+        for _ in 0..10 {
+            tokio::task::yield_now().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_queue_positions() {
+        let queue = Arc::new(StartQueue::new(1, Duration::ZERO));
+        let first = queue.acquire("a").await;
+        assert_eq!(queue.position("a"), None);
+
+        let mut waiters = Vec::new();
+        for id in ["b", "c"] {
+            let queue = queue.clone();
+            waiters.push(tokio::spawn(async move { queue.acquire(id).await }));
+            settle_tasks().await;
+        }
+        assert_eq!(queue.position("b"), Some(1));
+        assert_eq!(queue.position("c"), Some(2));
+
+        // b gets the slot and keeps it, c moves up.
+        drop(first);
+        let b = waiters.remove(0).await.unwrap();
+        assert_eq!(queue.position("b"), None);
+        assert_eq!(queue.position("c"), Some(1));
+
+        drop(b);
+        let _c = waiters.remove(0).await.unwrap();
+        assert_eq!(queue.position("c"), None);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_wait_leaves_queue() {
+        let queue = Arc::new(StartQueue::new(1, Duration::ZERO));
+        let _slot = queue.acquire("a").await;
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move { queue.acquire("b").await })
+        };
+        settle_tasks().await;
+        assert_eq!(queue.position("b"), Some(1));
+
+        waiter.abort();
+        let _ = waiter.await;
+        assert_eq!(queue.position("b"), None);
+    }
+
+    #[tokio::test]
+    async fn test_unlimited() {
+        let queue = StartQueue::new(0, Duration::ZERO);
+        let _a = queue.acquire("a").await;
+        let _b = queue.acquire("b").await;
+        assert_eq!(queue.position("b"), None);
+    }
+}
@@ -0,0 +1,218 @@
+//! Start latency per stage.
+//!
+//! A cold start is a chain of stages. The caller runs the first ones
+//! before StartVm/StartPod (cloning or creating the volume, creating the
+//! NIC) and passes them along; the rest run here: writing a pod's rootfs,
+//! waiting for a boot slot, connecting remote disks, spawning
+//! cloud-hypervisor and, for pods, waiting for mvirt-one's READY signal
+//! and getting the containers running. A [`StartTimer`] notes when each
+//! stage began. The timing of every successful start goes to the store,
+//! so a slow start shows the stage it lost its time in, and [`stats`]
+//! turns the recent ones into percentiles per stage.
+
+use std::collections::HashMap;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::proto::{StageStats, StartStage, StartStats, StartTiming};
+
+/// Writing the guest image's rootfs to a pod's volume.
+pub const ROOTFS_WRITE: &str = "rootfs_write";
+/// Waiting for a boot slot of the start queue.
+pub const QUEUED: &str = "queued";
+/// Connecting remote disks.
+pub const DISK_CONNECT: &str = "disk_connect";
+/// Spawning cloud-hypervisor until the VM runs.
+pub const SPAWN: &str = "spawn";
+/// Waiting for mvirt-one's READY signal.
+pub const GUEST_READY: &str = "guest_ready";
+/// Pulling images and starting the containers until the pod is Running.
+pub const POD_RUNNING: &str = "pod_running";
+
+/// Kinds of start, as the store keeps them.
+pub const KIND_VM: &str = "vm";
+pub const KIND_POD: &str = "pod";
+
+/// Stages of one start as they happen.
+pub struct StartTimer {
+    stages: Vec<StartStage>,
+    /// Stage running now and when it began
+    current: Option<(StartStage, Instant)>,
+}
+
+impl StartTimer {
+    /// A start beginning now, after the stages the caller already ran.
+    pub fn new(mut prepared: Vec<StartStage>) -> Self {
+        prepared.sort_by_key(|s| s.started_at_ms);
+        StartTimer {
+            stages: prepared,
+            current: None,
+        }
+    }
+
+    /// End the running stage and begin `name`.
+    pub fn stage(&mut self, name: &str) {
+        self.end_current();
+        let stage = StartStage {
+            name: name.to_string(),
+            started_at_ms: now_ms(),
+            duration_ms: 0,
+        };
+        self.current = Some((stage, Instant::now()));
+    }
+
+    /// End the running stage; the timing of the whole start.
+    pub fn finish(mut self) -> StartTiming {
+        self.end_current();
+        let end_ms = now_ms();
+        let started_at_ms = self
+            .stages
+            .first()
+            .map(|s| s.started_at_ms)
+            .unwrap_or(end_ms);
+        StartTiming {
+            started_at_ms,
+            total_ms: (end_ms - started_at_ms).max(0) as u64,
+            stages: self.stages,
+        }
+    }
+
+    fn end_current(&mut self) {
+        if let Some((mut stage, began)) = self.current.take() {
+            stage.duration_ms = began.elapsed().as_millis() as u64;
+            self.stages.push(stage);
+        }
+    }
+}
+
+/// Percentiles of every stage and of the total over `timings`. Stages are
+/// listed in the order they first appear.
+pub fn stats(timings: &[StartTiming]) -> StartStats {
+    let mut order: Vec<&str> = Vec::new();
+    let mut durations: HashMap<&str, Vec<u64>> = HashMap::new();
+    for timing in timings {
+        for stage in &timing.stages {
+            let samples = durations.entry(stage.name.as_str()).or_insert_with(|| {
+                order.push(stage.name.as_str());
+                Vec::new()
+            });
+            samples.push(stage.duration_ms);
+        }
+    }
+
+    StartStats {
+        count: timings.len() as u32,
+        stages: order
+            .into_iter()
+            .map(|name| stage_stats(name, durations.remove(name).unwrap_or_default()))
+            .collect(),
+        total: Some(stage_stats(
+            "total",
+            timings.iter().map(|t| t.total_ms).collect(),
+        )),
+    }
+}
+
+fn stage_stats(name: &str, mut samples: Vec<u64>) -> StageStats {
+    samples.sort_unstable();
+    StageStats {
+        name: name.to_string(),
+        count: samples.len() as u32,
+        p50_ms: percentile(&samples, 50),
+        p90_ms: percentile(&samples, 90),
+        p99_ms: percentile(&samples, 99),
+        max_ms: samples.last().copied().unwrap_or(0),
+    }
+}
+
+/// Nearest-rank percentile of sorted samples, 0 without any.
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage(name: &str, started_at_ms: i64, duration_ms: u64) -> StartStage {
+        StartStage {
+            name: name.to_string(),
+            started_at_ms,
+            duration_ms,
+        }
+    }
+
+    #[test]
+    fn records_stages_after_the_prepared_ones() {
+        let before = now_ms();
+        let mut timer = StartTimer::new(vec![
+            stage("nic_create", before - 300, 100),
+            stage("volume_clone", before - 1000, 700),
+        ]);
+        timer.stage(QUEUED);
+        timer.stage(SPAWN);
+        let timing = timer.finish();
+
+        let names: Vec<&str> = timing.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec!["volume_clone", "nic_create", QUEUED, SPAWN]);
+        assert_eq!(timing.started_at_ms, before - 1000);
+        assert!(timing.total_ms >= 1000);
+        assert!(timing.stages[2].started_at_ms >= before);
+    }
+
+    #[test]
+    fn an_empty_start_takes_no_time() {
+        let timing = StartTimer::new(Vec::new()).finish();
+        assert!(timing.stages.is_empty());
+        assert_eq!(timing.total_ms, 0);
+    }
+
+    #[test]
+    fn nearest_rank_percentiles() {
+        let samples: Vec<u64> = (1..=100).collect();
+        assert_eq!(percentile(&samples, 50), 50);
+        assert_eq!(percentile(&samples, 90), 90);
+        assert_eq!(percentile(&samples, 99), 99);
+        assert_eq!(percentile(&[7], 99), 7);
+        assert_eq!(percentile(&[1, 2, 3], 50), 2);
+        assert_eq!(percentile(&[], 50), 0);
+    }
+
+    #[test]
+    fn groups_stats_by_stage() {
+        let vm = StartTiming {
+            started_at_ms: 0,
+            total_ms: 300,
+            stages: vec![stage(QUEUED, 0, 100), stage(SPAWN, 100, 200)],
+        };
+        let pod = StartTiming {
+            started_at_ms: 0,
+            total_ms: 1000,
+            stages: vec![
+                stage(ROOTFS_WRITE, 0, 400),
+                stage(QUEUED, 400, 0),
+                stage(SPAWN, 400, 300),
+                stage(GUEST_READY, 700, 300),
+            ],
+        };
+        let stats = stats(&[vm, pod]);
+
+        assert_eq!(stats.count, 2);
+        let names: Vec<&str> = stats.stages.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec![QUEUED, SPAWN, ROOTFS_WRITE, GUEST_READY]);
+        let spawn = &stats.stages[1];
+        assert_eq!((spawn.count, spawn.p50_ms, spawn.max_ms), (2, 200, 300));
+        let total = stats.total.unwrap();
+        assert_eq!((total.count, total.p50_ms, total.p99_ms), (2, 300, 1000));
+    }
+}
@@ -1,12 +1,19 @@
+use std::collections::HashMap;
 use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use anyhow::Result;
 use sqlx::Row;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::sqlite::{SqliteConnection, SqlitePool, SqlitePoolOptions};
 use uuid::Uuid;
 
-use crate::proto::{Vm, VmConfig, VmState};
+use crate::proto::{StartStage, StartTiming, Vm, VmConfig, VmState};
+
+/// Format version of [`VmStore::export_state`] snapshots.
+pub const STATE_VERSION: u32 = 1;
+
+/// Start timings kept for [`VmStore::start_timings`]; older ones are dropped.
+pub const MAX_START_TIMINGS: i64 = 10_000;
 
 pub struct VmStore {
     pool: SqlitePool,
@@ -90,13 +97,14 @@ impl VmStore {
             config,
             created_at: now,
             started_at: None,
+            start_timing: None,
         })
     }
 
     pub async fn get(&self, id: &str) -> Result<Option<VmEntry>> {
         let row = sqlx::query(
             r#"
-            SELECT id, name, state, config_json, created_at, started_at
+            SELECT id, name, state, config_json, created_at, started_at, start_timing_json
             FROM vms WHERE id = ?
             "#,
         )
@@ -114,7 +122,7 @@ impl VmStore {
     pub async fn list(&self) -> Result<Vec<VmEntry>> {
         let rows = sqlx::query(
             r#"
-            SELECT id, name, state, config_json, created_at, started_at
+            SELECT id, name, state, config_json, created_at, started_at, start_timing_json
             FROM vms WHERE microvm = FALSE ORDER BY created_at DESC
             "#,
         )
@@ -128,7 +136,7 @@ impl VmStore {
     pub async fn list_all(&self) -> Result<Vec<VmEntry>> {
         let rows = sqlx::query(
             r#"
-            SELECT id, name, state, config_json, created_at, started_at
+            SELECT id, name, state, config_json, created_at, started_at, start_timing_json
             FROM vms ORDER BY created_at DESC
             "#,
         )
@@ -179,6 +187,70 @@ impl VmStore {
         self.get(id).await
     }
 
+    /// Replace the config of a VM, e.g. after hot-plugging a device.
+    pub async fn update_config(&self, id: &str, config: &VmConfig) -> Result<Option<VmEntry>> {
+        let config_json = serde_json::to_string(&ProtoConfig::from(config.clone()))?;
+        let result = sqlx::query("UPDATE vms SET config_json = ? WHERE id = ?")
+            .bind(&config_json)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        self.get(id).await
+    }
+
+    // Start timings
+
+    /// Record a successful start: the VM's last start timing, and one more
+    /// sample for [`VmStore::start_timings`].
+    pub async fn record_start(&self, vm_id: &str, kind: &str, timing: &StartTiming) -> Result<()> {
+        let timing_json = serde_json::to_string(&ProtoTiming::from(timing.clone()))?;
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("UPDATE vms SET start_timing_json = ? WHERE id = ?")
+            .bind(&timing_json)
+            .bind(vm_id)
+            .execute(&mut *tx)
+            .await?;
+        let result =
+            sqlx::query("INSERT INTO start_timings (vm_id, kind, timing_json) VALUES (?, ?, ?)")
+                .bind(vm_id)
+                .bind(kind)
+                .bind(&timing_json)
+                .execute(&mut *tx)
+                .await?;
+        sqlx::query("DELETE FROM start_timings WHERE id <= ?")
+            .bind(result.last_insert_rowid() - MAX_START_TIMINGS)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Timings of the most recent successful starts of `kind` (of all kinds
+    /// if `None`), newest first. A `limit` of 0 returns all kept.
+    pub async fn start_timings(&self, kind: Option<&str>, limit: u32) -> Result<Vec<StartTiming>> {
+        let limit = if limit == 0 { -1 } else { limit as i64 };
+        let rows: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT timing_json FROM start_timings
+            WHERE ?1 IS NULL OR kind = ?1
+            ORDER BY id DESC LIMIT ?2
+            "#,
+        )
+        .bind(kind)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.iter()
+            .map(|json| Ok(serde_json::from_str::<ProtoTiming>(json)?.into()))
+            .collect()
+    }
+
     // Runtime management
 
     pub async fn set_runtime(
@@ -230,6 +302,122 @@ impl VmStore {
 
         Ok(())
     }
+
+    /// VM rows for a state snapshot, as `{"vms": [{column: value}]}`.
+    /// Pod MicroVMs and runtime rows only mean something on this host
+    /// and are left out.
+    pub async fn export_state(&self) -> Result<serde_json::Value> {
+        let mut conn = self.pool.acquire().await?;
+        let vms = export_table(&mut *conn, "vms", "NOT microvm").await?;
+        Ok(serde_json::json!({ "vms": vms }))
+    }
+
+    /// Insert the VM rows of a state snapshot. Imported VMs are stopped.
+    /// Returns the number of rows.
+    pub async fn import_state(&self, state: &serde_json::Value) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let rows = import_table(&mut *tx, "vms", snapshot_rows(state, "vms")).await?;
+        sqlx::query(
+            "UPDATE vms SET state = 'stopped', started_at = NULL, start_timing_json = NULL",
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok(rows)
+    }
+
+    /// Whether any VM is defined; imports only go into an empty store.
+    pub async fn is_empty(&self) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM vms WHERE NOT microvm")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(count == 0)
+    }
+}
+
+/// Id and config of each VM in a state snapshot.
+pub fn snapshot_configs(state: &serde_json::Value) -> Result<Vec<(String, VmConfig)>> {
+    snapshot_rows(state, "vms")
+        .iter()
+        .map(|row| {
+            let id = row["id"].as_str().unwrap_or_default().to_string();
+            let config_json = row["config_json"]
+                .as_str()
+                .ok_or_else(|| anyhow::anyhow!("VM {} has no config", id))?;
+            let config: ProtoConfig = serde_json::from_str(config_json)?;
+            Ok((id, config.into()))
+        })
+        .collect()
+}
+
+fn snapshot_rows<'a>(state: &'a serde_json::Value, table: &str) -> &'a [serde_json::Value] {
+    state[table]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+async fn table_columns(conn: &mut SqliteConnection, table: &str) -> Result<Vec<String>> {
+    let rows = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rows.iter().map(|r| r.get("name")).collect())
+}
+
+/// All rows of `table` matching `filter` as JSON objects keyed by column.
+async fn export_table(
+    conn: &mut SqliteConnection,
+    table: &str,
+    filter: &str,
+) -> Result<Vec<serde_json::Value>> {
+    let fields = table_columns(conn, table)
+        .await?
+        .iter()
+        .map(|c| format!("'{0}', \"{0}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let json: String = sqlx::query_scalar(&format!(
+        "SELECT json_group_array(json_object({})) FROM {} WHERE {}",
+        fields, table, filter
+    ))
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Insert rows written by [`export_table`]. Columns this schema has but
+/// the rows lack (an export from an older version) get their defaults.
+async fn import_table(
+    conn: &mut SqliteConnection,
+    table: &str,
+    rows: &[serde_json::Value],
+) -> Result<u64> {
+    let Some(first) = rows.first().and_then(|r| r.as_object()) else {
+        return Ok(0);
+    };
+    let columns: Vec<String> = table_columns(conn, table)
+        .await?
+        .into_iter()
+        .filter(|c| first.contains_key(c))
+        .collect();
+    let names = columns
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = columns
+        .iter()
+        .map(|c| format!("json_extract(value, '$.\"{}\"')", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let result = sqlx::query(&format!(
+        "INSERT INTO {} ({}) SELECT {} FROM json_each(?)",
+        table, names, values
+    ))
+    .bind(serde_json::to_string(rows)?)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected())
 }
 
 // Helper types
@@ -242,6 +430,8 @@ pub struct VmEntry {
     pub config: VmConfig,
     pub created_at: i64,
     pub started_at: Option<i64>,
+    /// Stages of the last successful start
+    pub start_timing: Option<StartTiming>,
 }
 
 impl VmEntry {
@@ -253,6 +443,9 @@ impl VmEntry {
             config: Some(self.config.clone()),
             created_at: self.created_at,
             started_at: self.started_at,
+            start_queue_position: None,
+            start_timing: self.start_timing.clone(),
+            guest_ready: false,
         }
     }
 }
@@ -283,12 +476,22 @@ struct ProtoConfig {
     user_data: Option<String>,
     #[serde(default)]
     nested_virt: bool,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    labels: HashMap<String, String>,
+    #[serde(default)]
+    max_vcpus: u32,
+    #[serde(default)]
+    max_memory_mb: u64,
+    #[serde(default)]
+    guest_agent: bool,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
 struct ProtoDisk {
     path: String,
     readonly: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize)]
@@ -314,6 +517,7 @@ impl From<VmConfig> for ProtoConfig {
                 .map(|d| ProtoDisk {
                     path: d.path,
                     readonly: d.readonly,
+                    secret: d.secret,
                 })
                 .collect(),
             nics: c
@@ -327,6 +531,10 @@ impl From<VmConfig> for ProtoConfig {
                 .collect(),
             user_data: c.user_data,
             nested_virt: c.nested_virt,
+            labels: c.labels,
+            max_vcpus: c.max_vcpus,
+            max_memory_mb: c.max_memory_mb,
+            guest_agent: c.guest_agent,
         }
     }
 }
@@ -347,6 +555,7 @@ impl From<ProtoConfig> for VmConfig {
                 .map(|d| DiskConfig {
                     path: d.path,
                     readonly: d.readonly,
+                    secret: d.secret,
                 })
                 .collect(),
             nics: c
@@ -360,6 +569,62 @@ impl From<ProtoConfig> for VmConfig {
                 .collect(),
             user_data: c.user_data,
             nested_virt: c.nested_virt,
+            labels: c.labels,
+            max_vcpus: c.max_vcpus,
+            max_memory_mb: c.max_memory_mb,
+            guest_agent: c.guest_agent,
+        }
+    }
+}
+
+// Serialization helpers for StartTiming
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ProtoTiming {
+    started_at_ms: i64,
+    total_ms: u64,
+    stages: Vec<ProtoStage>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct ProtoStage {
+    name: String,
+    started_at_ms: i64,
+    duration_ms: u64,
+}
+
+impl From<StartTiming> for ProtoTiming {
+    fn from(t: StartTiming) -> Self {
+        Self {
+            started_at_ms: t.started_at_ms,
+            total_ms: t.total_ms,
+            stages: t
+                .stages
+                .into_iter()
+                .map(|s| ProtoStage {
+                    name: s.name,
+                    started_at_ms: s.started_at_ms,
+                    duration_ms: s.duration_ms,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<ProtoTiming> for StartTiming {
+    fn from(t: ProtoTiming) -> Self {
+        Self {
+            started_at_ms: t.started_at_ms,
+            total_ms: t.total_ms,
+            stages: t
+                .stages
+                .into_iter()
+                .map(|s| StartStage {
+                    name: s.name,
+                    started_at_ms: s.started_at_ms,
+                    duration_ms: s.duration_ms,
+                })
+                .collect(),
         }
     }
 }
@@ -367,6 +632,10 @@ impl From<ProtoConfig> for VmConfig {
 fn row_to_entry(row: sqlx::sqlite::SqliteRow) -> Result<VmEntry> {
     let config_json: String = row.get("config_json");
     let proto_config: ProtoConfig = serde_json::from_str(&config_json)?;
+    let start_timing = match row.get::<Option<String>, _>("start_timing_json") {
+        Some(json) => Some(serde_json::from_str::<ProtoTiming>(&json)?.into()),
+        None => None,
+    };
 
     Ok(VmEntry {
         id: row.get("id"),
@@ -375,6 +644,7 @@ fn row_to_entry(row: sqlx::sqlite::SqliteRow) -> Result<VmEntry> {
         config: proto_config.into(),
         created_at: row.get("created_at"),
         started_at: row.get("started_at"),
+        start_timing,
     })
 }
 
@@ -5,9 +5,14 @@
 //! 2. Send "CONNECT <port>\n"
 //! 3. Receive "OK <cid>\n"
 //! 4. Stream is now connected to the guest's vsock port
+//!
+//! Guests speaking protocol version 1 or later then expect an `OPEN <channel>`
+//! line selecting the service (see [`mvirt_one::vsock`]); version 0 guests
+//! serve gRPC right away.
 
 use anyhow::{Result, anyhow};
 use hyper_util::rt::TokioIo;
+use mvirt_one::vsock::{self, CHANNEL_API, CHANNEL_PORT, Hello};
 use std::path::{Path, PathBuf};
 use std::time::{Duration, Instant};
 use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
@@ -16,28 +21,61 @@ use tonic::transport::{Channel, Endpoint, Uri};
 use tower::service_fn;
 use tracing::{debug, info, warn};
 
-/// Port used by one for its API.
-const ONE_VSOCK_PORT: u32 = 1024;
-
 /// Client for communicating with one running inside a MicroVM.
 pub struct OneClient {
     channel: Channel,
+    vsock_socket: PathBuf,
+    hello: Hello,
 }
 
 impl OneClient {
     /// Connect to one in a MicroVM via vsock Unix socket proxy.
-    pub async fn connect(vsock_socket: &Path) -> Result<Self> {
-        info!(socket = %vsock_socket.display(), "Connecting to one via vsock");
-
-        let channel = create_vsock_channel(vsock_socket, ONE_VSOCK_PORT).await?;
+    ///
+    /// `hello` is what the guest announced in its ready signal and decides
+    /// whether channels are opened explicitly.
+    pub async fn connect(vsock_socket: &Path, hello: Hello) -> Result<Self> {
+        info!(
+            socket = %vsock_socket.display(),
+            protocol_version = hello.version,
+            "Connecting to one via vsock"
+        );
+
+        if !hello.supports(CHANNEL_API) {
+            return Err(anyhow!("Guest does not serve the {} channel", CHANNEL_API));
+        }
+        let channel = create_vsock_channel(vsock_socket, hello.version).await?;
 
-        Ok(Self { channel })
+        Ok(Self {
+            channel,
+            vsock_socket: vsock_socket.to_path_buf(),
+            hello,
+        })
     }
 
     /// Get the underlying gRPC channel.
     pub fn channel(&self) -> Channel {
         self.channel.clone()
     }
+
+    /// Protocol version and channels the guest announced.
+    pub fn hello(&self) -> &Hello {
+        &self.hello
+    }
+
+    /// Open a raw stream to one of the guest's channels other than the
+    /// gRPC API (e.g. logs, metrics, exec).
+    pub async fn open_channel(&self, name: &str) -> Result<UnixStream> {
+        if self.hello.version == 0 || !self.hello.supports(name) {
+            return Err(anyhow!(
+                "Guest (protocol v{}) does not serve the {} channel",
+                self.hello.version,
+                name
+            ));
+        }
+        connect_channel(&self.vsock_socket, self.hello.version, name)
+            .await
+            .map_err(|e| anyhow!("Failed to open channel {}: {}", name, e))
+    }
 }
 
 /// Perform the vsock CONNECT handshake over a Unix stream.
@@ -59,8 +97,28 @@ async fn vsock_connect_handshake(stream: &mut UnixStream, port: u32) -> Result<(
     }
 }
 
+/// Connect to the guest's channel port and open `channel`. Version 0 guests
+/// have no channels; the stream goes straight to their gRPC server.
+async fn connect_channel(
+    vsock_socket: &Path,
+    protocol_version: u32,
+    channel: &str,
+) -> std::io::Result<UnixStream> {
+    let mut stream = UnixStream::connect(vsock_socket).await?;
+
+    vsock_connect_handshake(&mut stream, CHANNEL_PORT)
+        .await
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+
+    if protocol_version > 0 {
+        let version = vsock::open_channel(&mut stream, channel).await?;
+        debug!(channel, version, "Opened guest channel");
+    }
+    Ok(stream)
+}
+
 /// Create a tonic channel over vsock Unix socket proxy.
-async fn create_vsock_channel(vsock_socket: &Path, port: u32) -> Result<Channel> {
+async fn create_vsock_channel(vsock_socket: &Path, protocol_version: u32) -> Result<Channel> {
     // Use a dummy URI - the actual connection is made via Unix socket
     let uri = Uri::builder()
         .scheme("http")
@@ -76,15 +134,9 @@ async fn create_vsock_channel(vsock_socket: &Path, port: u32) -> Result<Channel>
         .connect_with_connector(service_fn(move |_: Uri| {
             let socket_path = socket_path.clone();
             async move {
-                debug!(socket = %socket_path.display(), port = port, "Connecting to vsock socket");
-
-                // Connect to Unix socket
-                let mut stream = UnixStream::connect(&socket_path).await?;
+                debug!(socket = %socket_path.display(), "Connecting to vsock socket");
 
-                // Perform vsock CONNECT handshake
-                vsock_connect_handshake(&mut stream, port)
-                    .await
-                    .map_err(|e| std::io::Error::other(e.to_string()))?;
+                let stream = connect_channel(&socket_path, protocol_version, CHANNEL_API).await?;
 
                 // Wrap with TokioIo for hyper compatibility
                 Ok::<_, std::io::Error>(TokioIo::new(stream))
@@ -93,7 +145,7 @@ async fn create_vsock_channel(vsock_socket: &Path, port: u32) -> Result<Channel>
         .await
         .map_err(|e| anyhow!("Failed to connect via vsock: {}", e))?;
 
-    info!(socket = %vsock_socket.display(), port = port, "Connected to one via vsock");
+    info!(socket = %vsock_socket.display(), port = CHANNEL_PORT, "Connected to one via vsock");
     Ok(channel)
 }
 
@@ -118,7 +170,9 @@ pub fn vsock_socket_path(data_dir: &Path, vm_id: &str) -> PathBuf {
 /// Wait for one to become available on a MicroVM via vsock.
 ///
 /// This function retries the connection until the timeout is reached.
-/// Use this after starting a MicroVM to wait for the guest to boot.
+/// Use this after starting a MicroVM to wait for the guest to boot. Without
+/// a ready signal the guest's version is unknown, so it connects the
+/// unversioned way, which every guest accepts.
 pub async fn wait_for_one(vsock_socket: &Path, timeout: Duration) -> Result<OneClient> {
     let start = Instant::now();
     let retry_interval = Duration::from_millis(200);
@@ -130,7 +184,7 @@ pub async fn wait_for_one(vsock_socket: &Path, timeout: Duration) -> Result<OneC
     );
 
     while start.elapsed() < timeout {
-        match OneClient::connect(vsock_socket).await {
+        match OneClient::connect(vsock_socket, Hello::legacy()).await {
             Ok(client) => {
                 info!(
                     socket = %vsock_socket.display(),
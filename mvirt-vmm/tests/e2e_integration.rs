@@ -19,8 +19,8 @@ use mvirt_net::grpc::proto::{
     GetNetworkRequest, get_network_request, net_service_client::NetServiceClient,
 };
 use mvirt_vmm::proto::{
-    ContainerSpec, CreatePodRequest, DeletePodRequest, GetPodNetworkInfoRequest, PodResources,
-    StartPodRequest, pod_service_client::PodServiceClient,
+    ContainerSpec, CreatePodRequest, DeletePodRequest, DeletePropagation, GetPodNetworkInfoRequest,
+    PodResources, StartPodRequest, pod_service_client::PodServiceClient,
 };
 use tokio::time::sleep;
 
@@ -137,6 +137,12 @@ async fn test_e2e_nginx() {
             ntp_servers: vec![],
             is_public: true, // Enable internet access for pulling nginx image
             id: String::new(),
+            boot_server: String::new(),
+            boot_file: String::new(),
+            flow_logs: false,
+            flow_sample_rate: 0,
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
         })
         .await
         .expect("Failed to create network")
@@ -156,6 +162,9 @@ async fn test_e2e_nginx() {
             ipv6_address: String::new(),
             routed_ipv4_prefixes: vec![],
             routed_ipv6_prefixes: vec![],
+            owner: None,
+            delegated_ipv6_prefix_len: 0,
+            socket_access: None,
         })
         .await
         .expect("Failed to create NIC")
@@ -185,6 +194,8 @@ async fn test_e2e_nginx() {
                 args: vec!["-g".into(), "daemon off;".into()],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
             resources: Some(PodResources {
                 vcpus: 1,
@@ -194,6 +205,11 @@ async fn test_e2e_nginx() {
             root_disk_path: Some(test_rootfs.to_string_lossy().into()),
             nic_socket_path: Some(nic_socket),
             nic_mac_address: Some(nic_mac),
+            guest_image: None,
+            id: None,
+            owner: None,
+            labels: Default::default(),
+            init_containers: vec![],
         })
         .await
         .expect("Failed to create pod")
@@ -204,7 +220,10 @@ async fn test_e2e_nginx() {
     // 4. Start pod
     println!("Step 4: Starting pod...");
     let pod = pod_client
-        .start_pod(StartPodRequest { id: pod_id.clone() })
+        .start_pod(StartPodRequest {
+            id: pod_id.clone(),
+            prepare_stages: vec![],
+        })
         .await
         .expect("Failed to start pod")
         .into_inner();
@@ -244,6 +263,7 @@ async fn test_e2e_nginx() {
         .delete_pod(DeletePodRequest {
             id: pod_id,
             force: true,
+            propagation: DeletePropagation::Cascade as i32,
         })
         .await
         .expect("Failed to delete pod");
@@ -323,6 +343,12 @@ async fn test_dhcp_network_info() {
             ntp_servers: vec![],
             is_public: false, // No internet needed for this test
             id: String::new(),
+            boot_server: String::new(),
+            boot_file: String::new(),
+            flow_logs: false,
+            flow_sample_rate: 0,
+            proxy_neighbors: false,
+            dns_forwarders: vec![],
         })
         .await
         .expect("Failed to create network")
@@ -342,6 +368,9 @@ async fn test_dhcp_network_info() {
             ipv6_address: String::new(),
             routed_ipv4_prefixes: vec![],
             routed_ipv6_prefixes: vec![],
+            owner: None,
+            delegated_ipv6_prefix_len: 0,
+            socket_access: None,
         })
         .await
         .expect("Failed to create NIC")
@@ -368,6 +397,8 @@ async fn test_dhcp_network_info() {
                 args: vec![],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
             resources: Some(PodResources {
                 vcpus: 1,
@@ -377,6 +408,11 @@ async fn test_dhcp_network_info() {
             root_disk_path: Some(test_rootfs.to_string_lossy().into()),
             nic_socket_path: Some(nic_socket),
             nic_mac_address: Some(nic_mac),
+            guest_image: None,
+            id: None,
+            owner: None,
+            labels: Default::default(),
+            init_containers: vec![],
         })
         .await
         .expect("Failed to create pod")
@@ -387,7 +423,10 @@ async fn test_dhcp_network_info() {
     // 4. Start pod
     println!("Step 4: Starting pod...");
     let pod = pod_client
-        .start_pod(StartPodRequest { id: pod_id.clone() })
+        .start_pod(StartPodRequest {
+            id: pod_id.clone(),
+            prepare_stages: vec![],
+        })
         .await
         .expect("Failed to start pod")
         .into_inner();
@@ -446,6 +485,7 @@ async fn test_dhcp_network_info() {
         .delete_pod(DeletePodRequest {
             id: pod_id,
             force: true,
+            propagation: DeletePropagation::Cascade as i32,
         })
         .await
         .expect("Failed to delete pod");
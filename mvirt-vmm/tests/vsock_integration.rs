@@ -6,7 +6,7 @@ use std::path::Path;
 use std::time::Duration;
 
 use mvirt_vmm::proto::{
-    ContainerSpec, CreatePodRequest, DeletePodRequest, StartPodRequest,
+    ContainerSpec, CreatePodRequest, DeletePodRequest, DeletePropagation, StartPodRequest,
     pod_service_client::PodServiceClient,
 };
 use mvirt_vmm::vsock_client::{vm_id_to_cid, vsock_socket_path};
@@ -40,11 +40,18 @@ async fn test_vsock_health_ping() {
                 args: vec![],
                 env: vec![],
                 working_dir: String::new(),
+                stop_signal: String::new(),
+                stop_grace_seconds: 0,
             }],
             resources: None,
             root_disk_path: Some(TEST_ROOTFS.to_string()),
             nic_socket_path: None,
             nic_mac_address: None,
+            guest_image: None,
+            id: None,
+            owner: None,
+            labels: Default::default(),
+            init_containers: vec![],
         })
         .await
         .expect("Failed to create pod")
@@ -56,7 +63,10 @@ async fn test_vsock_health_ping() {
     // Start pod
     println!("Starting pod...");
     let pod = pod_client
-        .start_pod(StartPodRequest { id: pod_id.clone() })
+        .start_pod(StartPodRequest {
+            id: pod_id.clone(),
+            prepare_stages: vec![],
+        })
         .await
         .expect("Failed to start pod")
         .into_inner();
@@ -86,6 +96,7 @@ async fn test_vsock_health_ping() {
         .delete_pod(DeletePodRequest {
             id: pod_id,
             force: true,
+            propagation: DeletePropagation::Cascade as i32,
         })
         .await
         .expect("Failed to delete pod");
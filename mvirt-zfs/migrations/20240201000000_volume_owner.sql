@@ -0,0 +1,3 @@
+-- Owner reference: the pod or VM whose deletion takes the volume with it
+ALTER TABLE volumes ADD COLUMN owner_kind TEXT;
+ALTER TABLE volumes ADD COLUMN owner_id TEXT;
@@ -0,0 +1,6 @@
+-- Maintenance mode: at most one row while the pool is read-only
+CREATE TABLE maintenance (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    reason TEXT NOT NULL,
+    since INTEGER NOT NULL
+);
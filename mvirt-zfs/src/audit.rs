@@ -5,7 +5,7 @@
 use std::sync::Arc;
 
 use mvirt_log::{AuditLogger, LogLevel};
-use tonic::transport::ClientTlsConfig;
+use tonic::transport::{Channel, ClientTlsConfig};
 
 /// ZFS audit logger with domain-specific methods
 pub struct ZfsAuditLogger {
@@ -24,6 +24,13 @@ impl ZfsAuditLogger {
         }
     }
 
+    /// Create an audit logger on an existing mvirt-log channel
+    pub fn with_channel(channel: Channel) -> Self {
+        Self {
+            inner: Arc::new(AuditLogger::with_channel(channel, "zfs")),
+        }
+    }
+
     /// Create a noop audit logger (for testing)
     pub fn new_noop() -> Self {
         Self {
@@ -63,6 +70,19 @@ impl ZfsAuditLogger {
             .await;
     }
 
+    pub async fn volume_received(&self, volume_id: &str, volume_name: &str, bytes: u64) {
+        self.inner
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "Volume '{}' received from another host ({} bytes streamed)",
+                    volume_name, bytes
+                ),
+                vec![volume_id.to_string()],
+            )
+            .await;
+    }
+
     // === Import Events ===
 
     pub async fn import_started(&self, job_id: &str, volume_name: &str, source: &str) {
@@ -138,6 +158,25 @@ impl ZfsAuditLogger {
             .await;
     }
 
+    pub async fn volume_rebased(
+        &self,
+        volume_id: &str,
+        volume_name: &str,
+        source_name: &str,
+        template_name: &str,
+    ) {
+        self.inner
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "Volume '{}' rebased from '{}' onto template '{}'",
+                    volume_name, source_name, template_name
+                ),
+                vec![volume_id.to_string()],
+            )
+            .await;
+    }
+
     // === Snapshot Events ===
 
     pub async fn snapshot_created(&self, volume_id: &str, snapshot_name: &str) {
@@ -169,6 +208,39 @@ impl ZfsAuditLogger {
             )
             .await;
     }
+
+    // === Host Migration ===
+
+    pub async fn state_imported(&self, records: u64) {
+        self.inner
+            .log(
+                LogLevel::Audit,
+                format!(
+                    "Host state imported: {} volume, snapshot and template records",
+                    records
+                ),
+                vec![],
+            )
+            .await;
+    }
+
+    // === Maintenance ===
+
+    pub async fn maintenance_enabled(&self, reason: &str) {
+        self.inner
+            .log(
+                LogLevel::Audit,
+                format!("Maintenance mode enabled: {}", reason),
+                vec![],
+            )
+            .await;
+    }
+
+    pub async fn maintenance_disabled(&self) {
+        self.inner
+            .log(LogLevel::Audit, "Maintenance mode disabled", vec![])
+            .await;
+    }
 }
 
 /// Create a shared ZFS audit logger
@@ -1,21 +1,32 @@
 use std::sync::Arc;
 
-use tokio::sync::{broadcast, mpsc};
+use mvirt_common::events::{Event, EventBus};
+use mvirt_common::jobs::JobRegistry;
+use mvirt_common::naming;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{RwLock, RwLockReadGuard, broadcast, mpsc};
 use tokio_stream::wrappers::ReceiverStream;
-use tonic::{Request, Response, Status};
-use tracing::info;
+use tonic::{Request, Response, Status, Streaming};
+use tracing::{info, warn};
 
 use crate::audit::ZfsAuditLogger;
 use crate::import::{ImportManager, ImportSource};
 use crate::proto::zfs_service_server::ZfsService;
 use crate::proto::*;
-use crate::store::{SnapshotEntry, Store, TemplateEntry, VolumeEntry};
-use crate::zfs::ZfsManager;
+use crate::rebase;
+use crate::store::{
+    self, MaintenanceEntry, OwnerRef, STATE_VERSION, SnapshotEntry, Store, TemplateEntry,
+    VolumeEntry,
+};
+use crate::transfer::{self, ReceiveStream};
+use crate::zfs::{VolumeInfo, ZfsManager};
 
 pub struct ZfsServiceImpl {
     store: Arc<Store>,
     zfs: Arc<ZfsManager>,
     import: Arc<ImportManager>,
+    /// Long-running operations, imports included
+    jobs: JobRegistry,
     audit: Arc<ZfsAuditLogger>,
     /// Broadcast bus for volume lifecycle. Mutator paths publish snapshots
     /// of the current Volume (or None on delete). WatchVolumes subscribers
@@ -23,6 +34,11 @@ pub struct ZfsServiceImpl {
     volume_events: broadcast::Sender<VolumeEvent>,
     /// Same for templates + import jobs.
     template_events: broadcast::Sender<TemplateEvent>,
+    /// Set while the pool is in maintenance; mutating RPCs are refused.
+    /// Kept in the store too, so a restart doesn't lift it.
+    maintenance: RwLock<Option<MaintenanceStatus>>,
+    /// Tells the other daemons of the host about resized volumes
+    host_events: Option<EventBus>,
 }
 
 impl ZfsServiceImpl {
@@ -34,13 +50,51 @@ impl ZfsServiceImpl {
     ) -> Self {
         let (volume_events, _) = broadcast::channel(64);
         let (template_events, _) = broadcast::channel(64);
+        let jobs = import.jobs().clone();
         Self {
             store,
             zfs,
             import,
+            jobs,
             audit,
             volume_events,
             template_events,
+            maintenance: RwLock::new(None),
+            host_events: None,
+        }
+    }
+
+    /// Publish volume resizes on the host's event bus.
+    pub fn with_host_events(mut self, bus: EventBus) -> Self {
+        self.host_events = Some(bus);
+        self
+    }
+
+    /// Resume the maintenance mode a previous run left in the store.
+    pub fn with_maintenance(mut self, entry: Option<MaintenanceEntry>) -> Self {
+        self.maintenance = RwLock::new(entry.map(|m| MaintenanceStatus {
+            enabled: true,
+            reason: m.reason,
+            since: m.since,
+        }));
+        self
+    }
+
+    /// Refuse a mutating RPC while maintenance mode is on.
+    async fn ensure_writable(&self) -> Result<(), Status> {
+        self.writable().await.map(|_| ())
+    }
+
+    /// Like [`Self::ensure_writable`], but maintenance can't start until
+    /// the returned guard is dropped. Held while an import job is started.
+    async fn writable(&self) -> Result<RwLockReadGuard<'_, Option<MaintenanceStatus>>, Status> {
+        let maintenance = self.maintenance.read().await;
+        match maintenance.as_ref() {
+            Some(m) => Err(Status::failed_precondition(format!(
+                "mvirt-zfs is read-only for maintenance: {}",
+                m.reason
+            ))),
+            None => Ok(maintenance),
         }
     }
 
@@ -66,6 +120,108 @@ impl ZfsServiceImpl {
         });
     }
 
+    /// Compare a snapshot of a volume with its template and the new one,
+    /// and unless this is a dry run or blocks conflict, replay the changes
+    /// onto a new clone of the new template.
+    async fn rebase_snapshot(
+        &self,
+        req: &RebaseVolumeRequest,
+        snapshot_path: &str,
+        origin: &TemplateEntry,
+        target: &TemplateEntry,
+        source: &VolumeInfo,
+    ) -> Result<RebaseVolumeResponse, Status> {
+        let (scratch_path, scratch_device) = self
+            .zfs
+            .clone_to_scratch(snapshot_path)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let result = self
+            .replay_scratch(req, &scratch_device, origin, target, source)
+            .await;
+        if let Err(e) = self.zfs.destroy(&scratch_path).await {
+            warn!(path = %scratch_path, error = %e, "Failed to remove rebase scratch clone");
+        }
+        result
+    }
+
+    async fn replay_scratch(
+        &self,
+        req: &RebaseVolumeRequest,
+        scratch_device: &str,
+        origin: &TemplateEntry,
+        target: &TemplateEntry,
+        source: &VolumeInfo,
+    ) -> Result<RebaseVolumeResponse, Status> {
+        let base = self.zfs.template_device_path(&origin.id);
+        let new_base = self.zfs.template_device_path(&target.id);
+        let volume = scratch_device.to_string();
+        let block_size = source.volblocksize.max(512) as usize;
+        let deltas = tokio::task::spawn_blocking(move || {
+            rebase::compare_devices(&base, &volume, &new_base, block_size)
+        })
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(|e| Status::internal(format!("Failed to compare blocks: {}", e)))?;
+
+        let mut resp = RebaseVolumeResponse {
+            feasible: deltas.conflict_bytes == 0,
+            changed_bytes: deltas.changed_bytes(),
+            conflict_bytes: deltas.conflict_bytes,
+            ..Default::default()
+        };
+        if !resp.feasible {
+            resp.reason = format!(
+                "the volume and template '{}' changed the same blocks",
+                target.name
+            );
+            return Ok(resp);
+        }
+        if req.dry_run {
+            return Ok(resp);
+        }
+
+        let volume_id = uuid::Uuid::new_v4().to_string();
+        let mut vol = self
+            .zfs
+            .clone_template_to_volume(&target.id, &volume_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let written = async {
+            if source.volsize_bytes > vol.volsize_bytes {
+                vol = self
+                    .zfs
+                    .resize_volume(&volume_id, source.volsize_bytes)
+                    .await?;
+            }
+            let volume = scratch_device.to_string();
+            let dest = vol.device_path.clone();
+            tokio::task::spawn_blocking(move || rebase::replay_devices(&volume, &dest, &deltas))
+                .await??;
+            anyhow::Ok(())
+        }
+        .await;
+        if let Err(e) = written {
+            let _ = self.zfs.delete_volume_recursive(&volume_id).await;
+            return Err(Status::internal(format!("Failed to replay changes: {}", e)));
+        }
+
+        let entry = VolumeEntry::new(
+            volume_id.clone(),
+            req.new_volume_name.clone(),
+            self.zfs.volume_zfs_path(&volume_id),
+            vol.device_path.clone(),
+            vol.volsize_bytes,
+            Some(target.id.clone()),
+        );
+        if let Err(e) = self.store.create_volume(&entry).await {
+            let _ = self.zfs.delete_volume_recursive(&volume_id).await;
+            return Err(Status::internal(e.to_string()));
+        }
+        resp.volume = Some(volume_to_proto(&entry, &vol));
+        Ok(resp)
+    }
+
     /// Sync DB snapshot entries with actual ZFS snapshots after rollback.
     /// Removes any DB entries for snapshots that no longer exist in ZFS.
     async fn sync_snapshots_after_rollback(&self, volume_id: &str) {
@@ -133,6 +289,7 @@ impl ZfsService for ZfsServiceImpl {
             used_bytes: stats.used_bytes,
             provisioned_bytes: stats.provisioned_bytes,
             compression_ratio: stats.compression_ratio,
+            health: stats.health,
         }))
     }
 
@@ -142,11 +299,10 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<CreateVolumeRequest>,
     ) -> Result<Response<Volume>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
 
-        if req.name.is_empty() {
-            return Err(Status::invalid_argument("name is required"));
-        }
+        naming::validate_name("Volume", &req.name)?;
         if req.size_bytes == 0 {
             return Err(Status::invalid_argument("size_bytes must be > 0"));
         }
@@ -176,7 +332,7 @@ impl ZfsService for ZfsServiceImpl {
             .map_err(|e| Status::internal(e.to_string()))?;
 
         // Store in database with name as label
-        let entry = VolumeEntry::new(
+        let mut entry = VolumeEntry::new(
             volume_id.clone(),
             req.name.clone(),
             self.zfs.volume_zfs_path(&volume_id),
@@ -184,6 +340,10 @@ impl ZfsService for ZfsServiceImpl {
             req.size_bytes,
             None, // No origin template for empty volumes
         );
+        entry.owner = req.owner.map(|o| OwnerRef {
+            kind: o.kind,
+            id: o.id,
+        });
 
         self.store
             .create_volume(&entry)
@@ -299,6 +459,7 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<DeleteVolumeRequest>,
     ) -> Result<Response<DeleteVolumeResponse>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
 
         // Get from database to get ID
@@ -337,6 +498,7 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<ResizeVolumeRequest>,
     ) -> Result<Response<Volume>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
 
         // Get from database
@@ -366,6 +528,14 @@ impl ZfsService for ZfsServiceImpl {
         self.audit
             .volume_resized(&entry.id, &entry.name, req.new_size_bytes)
             .await;
+        if let Some(bus) = &self.host_events {
+            bus.publish(Event::VolumeResized {
+                volume_id: entry.id.clone(),
+                name: entry.name.clone(),
+                path: entry.device_path.clone(),
+                size_bytes: req.new_size_bytes,
+            });
+        }
 
         Ok(Response::new(volume_to_proto(&entry, &vol)))
     }
@@ -376,11 +546,11 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<ImportTemplateRequest>,
     ) -> Result<Response<ImportJob>, Status> {
+        // Until the job is registered, so maintenance sees it
+        let _writable = self.writable().await?;
         let req = request.into_inner();
 
-        if req.name.is_empty() {
-            return Err(Status::invalid_argument("name is required"));
-        }
+        naming::validate_name("Template", &req.name)?;
         if req.source.is_empty() {
             return Err(Status::invalid_argument("source is required"));
         }
@@ -457,6 +627,7 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<CancelImportJobRequest>,
     ) -> Result<Response<CancelImportJobResponse>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
 
         let cancelled = self
@@ -474,7 +645,9 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<CreateSnapshotRequest>,
     ) -> Result<Response<Snapshot>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
+        naming::validate_name("Snapshot", &req.snapshot_name)?;
 
         // Verify volume exists
         let vol_entry = self
@@ -484,6 +657,20 @@ impl ZfsService for ZfsServiceImpl {
             .map_err(|e| Status::internal(e.to_string()))?
             .ok_or_else(|| Status::not_found(format!("Volume '{}' not found", req.volume_name)))?;
 
+        // Snapshot names are unique per volume
+        if self
+            .store
+            .get_snapshot(&vol_entry.id, &req.snapshot_name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .is_some()
+        {
+            return Err(Status::already_exists(format!(
+                "Snapshot '{}' already exists on volume '{}'",
+                req.snapshot_name, req.volume_name
+            )));
+        }
+
         // Generate UUIDs
         let zfs_name = uuid::Uuid::new_v4().to_string();
         let snapshot_id = uuid::Uuid::new_v4().to_string();
@@ -561,6 +748,7 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<DeleteSnapshotRequest>,
     ) -> Result<Response<DeleteSnapshotResponse>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
 
         // Get volume from database
@@ -605,6 +793,7 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<RollbackSnapshotRequest>,
     ) -> Result<Response<Volume>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
 
         // Get volume from database
@@ -649,7 +838,9 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<PromoteSnapshotRequest>,
     ) -> Result<Response<Template>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
+        naming::validate_name("Template", &req.template_name)?;
 
         // Check if template name already exists
         if self
@@ -761,16 +952,20 @@ impl ZfsService for ZfsServiceImpl {
             .await
             .map_err(|e| Status::internal(e.to_string()))?;
 
-        // TODO: Count clones for each template
-        let templates: Vec<Template> = templates.iter().map(|t| template_to_proto(t, 0)).collect();
+        let mut protos = Vec::with_capacity(templates.len());
+        for t in &templates {
+            let clones = self.store.count_volumes_by_origin(&t.id).await.unwrap_or(0);
+            protos.push(template_to_proto(t, clones as u32));
+        }
 
-        Ok(Response::new(ListTemplatesResponse { templates }))
+        Ok(Response::new(ListTemplatesResponse { templates: protos }))
     }
 
     async fn delete_template(
         &self,
         request: Request<DeleteTemplateRequest>,
     ) -> Result<Response<DeleteTemplateResponse>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
 
         // Get template from database
@@ -837,7 +1032,22 @@ impl ZfsService for ZfsServiceImpl {
         &self,
         request: Request<CloneFromTemplateRequest>,
     ) -> Result<Response<Volume>, Status> {
+        self.ensure_writable().await?;
         let req = request.into_inner();
+        naming::validate_name("Volume", &req.new_volume_name)?;
+
+        if self
+            .store
+            .get_volume_by_name(&req.new_volume_name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .is_some()
+        {
+            return Err(Status::already_exists(format!(
+                "Volume '{}' already exists",
+                req.new_volume_name
+            )));
+        }
 
         // Get template from database
         let template = self
@@ -884,7 +1094,7 @@ impl ZfsService for ZfsServiceImpl {
         }
 
         // Store new volume in database with origin template
-        let entry = VolumeEntry::new(
+        let mut entry = VolumeEntry::new(
             volume_id.clone(),
             req.new_volume_name.clone(),
             self.zfs.volume_zfs_path(&volume_id),
@@ -892,6 +1102,10 @@ impl ZfsService for ZfsServiceImpl {
             volume_size,
             Some(template.id.clone()), // origin_template_id
         );
+        entry.owner = req.owner.map(|o| OwnerRef {
+            kind: o.kind,
+            id: o.id,
+        });
 
         self.store
             .create_volume(&entry)
@@ -916,6 +1130,568 @@ impl ZfsService for ZfsServiceImpl {
         Ok(Response::new(proto))
     }
 
+    async fn prewarm_template(
+        &self,
+        request: Request<PrewarmTemplateRequest>,
+    ) -> Result<Response<PrewarmTemplateResponse>, Status> {
+        let req = request.into_inner();
+
+        let template = self
+            .store
+            .get_template(&req.template_name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| {
+                Status::not_found(format!("Template '{}' not found", req.template_name))
+            })?;
+
+        let started = std::time::Instant::now();
+        let bytes_read = self
+            .zfs
+            .prewarm_template(&template.id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let duration_ms = started.elapsed().as_millis() as u64;
+
+        let referenced_bytes = self
+            .zfs
+            .referenced_bytes(&self.zfs.template_zfs_path(&template.id))
+            .await
+            .unwrap_or(0);
+        let arc_max_bytes = crate::zfs::arc_max_bytes().unwrap_or(0);
+        if arc_max_bytes > 0 && referenced_bytes > arc_max_bytes {
+            warn!(
+                template = %req.template_name,
+                referenced_bytes,
+                arc_max_bytes,
+                "Template is larger than the ARC; prewarming only keeps part of it cached"
+            );
+        }
+
+        info!(
+            template = %req.template_name,
+            template_id = %template.id,
+            bytes_read,
+            duration_ms,
+            "Template prewarmed"
+        );
+
+        Ok(Response::new(PrewarmTemplateResponse {
+            bytes_read,
+            referenced_bytes,
+            arc_max_bytes,
+            duration_ms,
+        }))
+    }
+
+    async fn list_template_clones(
+        &self,
+        _request: Request<ListTemplateClonesRequest>,
+    ) -> Result<Response<ListTemplateClonesResponse>, Status> {
+        let templates = self
+            .store
+            .list_templates()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let volumes = self
+            .store
+            .list_volumes()
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        let mut groups: Vec<TemplateClones> = templates
+            .into_iter()
+            .map(|t| TemplateClones {
+                template_id: t.id,
+                template_name: t.name,
+                ..Default::default()
+            })
+            .collect();
+        for entry in volumes {
+            let Some(origin) = entry.origin_template_id else {
+                continue;
+            };
+            let used_bytes = match self.zfs.get_volume(&entry.id).await {
+                Ok(vol) => vol.used_bytes,
+                Err(_) => continue,
+            };
+            let i = match groups.iter().position(|g| g.template_id == origin) {
+                Some(i) => i,
+                None => {
+                    groups.push(TemplateClones {
+                        template_id: origin,
+                        deleted: true,
+                        ..Default::default()
+                    });
+                    groups.len() - 1
+                }
+            };
+            groups[i].used_bytes += used_bytes;
+            groups[i].clones.push(TemplateClone {
+                volume_id: entry.id,
+                volume_name: entry.name,
+                used_bytes,
+            });
+        }
+        groups.sort_by(|a, b| {
+            (a.deleted, &a.template_name, &a.template_id).cmp(&(
+                b.deleted,
+                &b.template_name,
+                &b.template_id,
+            ))
+        });
+
+        Ok(Response::new(ListTemplateClonesResponse {
+            templates: groups,
+        }))
+    }
+
+    async fn rebase_volume(
+        &self,
+        request: Request<RebaseVolumeRequest>,
+    ) -> Result<Response<RebaseVolumeResponse>, Status> {
+        self.ensure_writable().await?;
+        let req = request.into_inner();
+        if !req.dry_run {
+            naming::validate_name("Volume", &req.new_volume_name)?;
+            if self
+                .store
+                .get_volume_by_name(&req.new_volume_name)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?
+                .is_some()
+            {
+                return Err(Status::already_exists(format!(
+                    "Volume '{}' already exists",
+                    req.new_volume_name
+                )));
+            }
+        }
+
+        let entry = self
+            .store
+            .get_volume_by_name(&req.volume_name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("Volume '{}' not found", req.volume_name)))?;
+        let target = self
+            .store
+            .get_template(&req.template_name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| {
+                Status::not_found(format!("Template '{}' not found", req.template_name))
+            })?;
+
+        let infeasible = |reason: &str| -> Result<Response<RebaseVolumeResponse>, Status> {
+            Ok(Response::new(RebaseVolumeResponse {
+                reason: reason.to_string(),
+                ..Default::default()
+            }))
+        };
+        let Some(origin_id) = entry.origin_template_id.clone() else {
+            return infeasible("volume was not cloned from a template");
+        };
+        if origin_id == target.id {
+            return Err(Status::invalid_argument(format!(
+                "Volume '{}' is already based on template '{}'",
+                req.volume_name, req.template_name
+            )));
+        }
+        let Some(origin) = self
+            .store
+            .get_template_by_id(&origin_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+        else {
+            return infeasible("the template the volume was cloned from was deleted");
+        };
+        let source = self
+            .zfs
+            .get_volume(&entry.id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        // Compare a snapshot, so the volume can stay in use meanwhile
+        let snapshot = format!(
+            "{}{}",
+            rebase::SNAPSHOT_PREFIX,
+            uuid::Uuid::new_v4().simple()
+        );
+        self.zfs
+            .create_snapshot(&entry.id, &snapshot)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let snapshot_path = format!("{}@{}", entry.zfs_path, snapshot);
+        let result = self
+            .rebase_snapshot(&req, &snapshot_path, &origin, &target, &source)
+            .await;
+        if let Err(e) = self.zfs.destroy(&snapshot_path).await {
+            warn!(snapshot = %snapshot_path, error = %e, "Failed to remove rebase snapshot");
+        }
+        let resp = result?;
+
+        if let Some(volume) = &resp.volume {
+            info!(
+                source = %req.volume_name,
+                name = %volume.name,
+                template = %req.template_name,
+                changed_bytes = resp.changed_bytes,
+                "Volume rebased"
+            );
+            self.audit
+                .volume_rebased(
+                    &volume.id,
+                    &volume.name,
+                    &req.volume_name,
+                    &req.template_name,
+                )
+                .await;
+            self.publish_volume(&volume.id, Some(volume.clone()));
+        }
+        Ok(Response::new(resp))
+    }
+
+    // === Host migration ===
+
+    async fn export_state(
+        &self,
+        _request: Request<ExportStateRequest>,
+    ) -> Result<Response<StateSnapshot>, Status> {
+        let state = self
+            .store
+            .export_state()
+            .await
+            .map_err(|e| Status::internal(format!("Failed to export state: {}", e)))?;
+        let data = serde_json::to_vec(&state).map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::new(StateSnapshot {
+            version: STATE_VERSION,
+            data,
+        }))
+    }
+
+    async fn import_state(
+        &self,
+        request: Request<ImportStateRequest>,
+    ) -> Result<Response<ImportStateResponse>, Status> {
+        self.ensure_writable().await?;
+        let req = request.into_inner();
+        let snapshot = req
+            .snapshot
+            .ok_or_else(|| Status::invalid_argument("snapshot is required"))?;
+        if snapshot.version != STATE_VERSION {
+            return Err(Status::invalid_argument(format!(
+                "Unsupported state snapshot version {} (expected {})",
+                snapshot.version, STATE_VERSION
+            )));
+        }
+        let state: serde_json::Value = serde_json::from_slice(&snapshot.data)
+            .map_err(|e| Status::invalid_argument(format!("Invalid state snapshot: {}", e)))?;
+
+        if !self
+            .store
+            .is_empty()
+            .await
+            .map_err(|e| Status::internal(format!("Database error: {}", e)))?
+        {
+            return Err(Status::failed_precondition(
+                "Volumes or templates already exist on this host",
+            ));
+        }
+
+        // The zvols themselves move with zfs send/recv; only their
+        // metadata is in the snapshot.
+        let mut missing = Vec::new();
+        for (label, dataset) in store::snapshot_datasets(&state) {
+            let exists = self
+                .zfs
+                .exists(&dataset)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+            if !exists {
+                missing.push(format!("{}: {}", label, dataset));
+            }
+        }
+
+        if req.dry_run {
+            return Ok(Response::new(ImportStateResponse {
+                records: store::snapshot_len(&state) as u32,
+                missing,
+            }));
+        }
+
+        let records = self
+            .store
+            .import_state(&state)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to import state: {}", e)))?;
+
+        info!(records, missing = missing.len(), "Imported volume state");
+        self.audit.state_imported(records).await;
+        Ok(Response::new(ImportStateResponse {
+            records: records as u32,
+            missing,
+        }))
+    }
+
+    // === Volume transfer ===
+
+    type SendVolumeStream = ReceiverStream<Result<VolumeStreamChunk, Status>>;
+
+    async fn send_volume(
+        &self,
+        request: Request<SendVolumeRequest>,
+    ) -> Result<Response<Self::SendVolumeStream>, Status> {
+        self.ensure_writable().await?;
+        let req = request.into_inner();
+        let entry = self
+            .store
+            .get_volume_by_name(&req.name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .ok_or_else(|| Status::not_found(format!("Volume '{}' not found", req.name)))?;
+
+        // Send a snapshot so the guest can keep writing during the copy
+        let snapshot = format!(
+            "{}{}",
+            transfer::SNAPSHOT_PREFIX,
+            uuid::Uuid::new_v4().simple()
+        );
+        self.zfs
+            .create_snapshot(&entry.id, &snapshot)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let snapshot_path = format!("{}@{}", entry.zfs_path, snapshot);
+        let child = match self.zfs.spawn_send(&snapshot_path) {
+            Ok(child) => child,
+            Err(e) => {
+                let _ = self.zfs.destroy(&snapshot_path).await;
+                return Err(Status::internal(e.to_string()));
+            }
+        };
+
+        info!(name = %entry.name, id = %entry.id, snapshot = %snapshot, "Sending volume");
+        let header = VolumeStreamHeader {
+            name: entry.name.clone(),
+            id: String::new(),
+            size_bytes: entry.size_bytes,
+            snapshot,
+        };
+        let zfs = Arc::clone(&self.zfs);
+        let job = self.jobs.start("volume-send", &entry.name, true);
+        job.set_phase("sending");
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            match transfer::send_stream(child, header, &tx, &job).await {
+                Ok(bytes) => {
+                    info!(name = %entry.name, bytes, "Volume sent");
+                    job.complete();
+                }
+                Err(_) if job.is_cancelled() => {
+                    info!(name = %entry.name, "Volume send cancelled");
+                    let _ = tx
+                        .send(Err(Status::cancelled("Volume send cancelled")))
+                        .await;
+                    job.finish_cancelled();
+                }
+                Err(e) => {
+                    warn!(name = %entry.name, error = %e, "Volume send failed");
+                    let _ = tx.send(Err(Status::internal(e.to_string()))).await;
+                    job.fail(e);
+                }
+            }
+            if let Err(e) = zfs.destroy(&snapshot_path).await {
+                warn!(snapshot = %snapshot_path, error = %e, "Failed to remove transfer snapshot");
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
+    async fn receive_volume(
+        &self,
+        request: Request<Streaming<VolumeStreamChunk>>,
+    ) -> Result<Response<Volume>, Status> {
+        self.ensure_writable().await?;
+        let mut stream = request.into_inner();
+        let (mut incoming, header) = ReceiveStream::start(stream.message().await?)?;
+
+        if self
+            .store
+            .get_volume_by_name(&header.name)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .is_some()
+        {
+            return Err(Status::already_exists(format!(
+                "Volume '{}' already exists",
+                header.name
+            )));
+        }
+
+        // Use caller-supplied id when provided (see create_volume).
+        let volume_id = if header.id.is_empty() {
+            uuid::Uuid::new_v4().to_string()
+        } else {
+            uuid::Uuid::parse_str(&header.id)
+                .map_err(|_| Status::invalid_argument(format!("invalid volume id: {}", header.id)))?
+                .to_string()
+        };
+
+        let mut child = self
+            .zfs
+            .spawn_receive(&volume_id)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let mut stdin = child
+            .stdin
+            .take()
+            .ok_or_else(|| Status::internal("zfs receive has no stdin"))?;
+
+        let job = self.jobs.start("volume-receive", &header.name, true);
+        job.set_phase("receiving");
+        let mut bytes = 0u64;
+        let streamed: Result<(), Status> = async {
+            while let Some(msg) = stream.message().await? {
+                if job.is_cancelled() {
+                    return Err(Status::cancelled("Volume receive cancelled"));
+                }
+                if let Some(data) = incoming.next(&msg)? {
+                    stdin
+                        .write_all(data)
+                        .await
+                        .map_err(|e| Status::internal(format!("zfs receive: {}", e)))?;
+                    bytes += data.len() as u64;
+                    job.set_progress(bytes, None);
+                }
+            }
+            incoming.finish()
+        }
+        .await;
+        if let Err(status) = streamed {
+            // zfs receive drops a stream it did not see the end of
+            let _ = child.kill().await;
+            warn!(name = %header.name, error = %status.message(), "Volume receive aborted");
+            if job.is_cancelled() {
+                job.finish_cancelled();
+            } else {
+                job.fail(status.message());
+            }
+            return Err(status);
+        }
+        job.set_phase("registering");
+
+        drop(stdin);
+        let output = child
+            .wait_with_output()
+            .await
+            .map_err(|e| Status::internal(format!("zfs receive: {}", e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Status::internal(format!("zfs receive failed: {}", stderr)));
+        }
+
+        let zfs_path = self.zfs.volume_zfs_path(&volume_id);
+        let snapshot_path = format!("{}@{}", zfs_path, header.snapshot);
+        if let Err(e) = self.zfs.destroy(&snapshot_path).await {
+            warn!(snapshot = %snapshot_path, error = %e, "Failed to remove transfer snapshot");
+        }
+
+        let vol = self
+            .zfs
+            .wait_for_volume(&volume_id)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        let entry = VolumeEntry::new(
+            volume_id.clone(),
+            header.name.clone(),
+            zfs_path,
+            vol.device_path.clone(),
+            vol.volsize_bytes,
+            None,
+        );
+        self.store
+            .create_volume(&entry)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        info!(name = %header.name, id = %volume_id, bytes, "Volume received");
+        job.complete();
+        self.audit
+            .volume_received(&volume_id, &header.name, bytes)
+            .await;
+
+        let proto = volume_to_proto(&entry, &vol);
+        self.publish_volume(&entry.id, Some(proto.clone()));
+        Ok(Response::new(proto))
+    }
+
+    // === Maintenance ===
+
+    async fn set_maintenance(
+        &self,
+        request: Request<SetMaintenanceRequest>,
+    ) -> Result<Response<MaintenanceStatus>, Status> {
+        let req = request.into_inner();
+        if req.enabled && req.reason.trim().is_empty() {
+            return Err(Status::invalid_argument(
+                "reason is required to enable maintenance",
+            ));
+        }
+
+        let status = {
+            // Waits for imports that are being started
+            let mut maintenance = self.maintenance.write().await;
+            let next = match (req.enabled, maintenance.as_ref()) {
+                // Already locked: keep the original start time, update the reason
+                (true, Some(current)) => Some(MaintenanceStatus {
+                    reason: req.reason.clone(),
+                    ..current.clone()
+                }),
+                (true, None) => {
+                    let imports = running_imports(&self.jobs);
+                    if !imports.is_empty() {
+                        return Err(Status::failed_precondition(format!(
+                            "Import jobs still running: {}; wait for or cancel them first",
+                            imports.join(", ")
+                        )));
+                    }
+                    Some(MaintenanceStatus {
+                        enabled: true,
+                        reason: req.reason.clone(),
+                        since: chrono::Utc::now().timestamp(),
+                    })
+                }
+                (false, _) => None,
+            };
+            let entry = next.as_ref().map(|m| MaintenanceEntry {
+                reason: m.reason.clone(),
+                since: m.since,
+            });
+            self.store
+                .set_maintenance(entry.as_ref())
+                .await
+                .map_err(|e| Status::internal(format!("Failed to store maintenance: {}", e)))?;
+            *maintenance = next;
+            maintenance.clone().unwrap_or_default()
+        };
+
+        if req.enabled {
+            info!(reason = %req.reason, "Maintenance mode enabled");
+            self.audit.maintenance_enabled(&req.reason).await;
+        } else {
+            info!("Maintenance mode disabled");
+            self.audit.maintenance_disabled().await;
+        }
+        Ok(Response::new(status))
+    }
+
+    async fn get_maintenance(
+        &self,
+        _request: Request<GetMaintenanceRequest>,
+    ) -> Result<Response<MaintenanceStatus>, Status> {
+        let status = self.maintenance.read().await.clone();
+        Ok(Response::new(status.unwrap_or_default()))
+    }
+
     type WatchVolumesStream = ReceiverStream<Result<VolumeEvent, Status>>;
 
     async fn watch_volumes(
@@ -963,6 +1739,48 @@ impl ZfsService for ZfsServiceImpl {
         });
         Ok(Response::new(ReceiverStream::new(out_rx)))
     }
+
+    // === Jobs ===
+
+    async fn list_jobs(
+        &self,
+        request: Request<ListJobsRequest>,
+    ) -> Result<Response<ListJobsResponse>, Status> {
+        let req = request.into_inner();
+        let jobs = self
+            .jobs
+            .list(req.include_finished)
+            .into_iter()
+            .map(job_to_proto)
+            .collect();
+        Ok(Response::new(ListJobsResponse { jobs }))
+    }
+
+    async fn get_job(&self, request: Request<GetJobRequest>) -> Result<Response<Job>, Status> {
+        let req = request.into_inner();
+        let job = self
+            .jobs
+            .get(&req.id)
+            .ok_or_else(|| Status::not_found(format!("Job '{}' not found", req.id)))?;
+        Ok(Response::new(job_to_proto(job)))
+    }
+
+    async fn cancel_job(
+        &self,
+        request: Request<CancelJobRequest>,
+    ) -> Result<Response<Job>, Status> {
+        self.ensure_writable().await?;
+        let req = request.into_inner();
+        let job = self.jobs.cancel(&req.id)?;
+        if job.kind == "import" {
+            self.import
+                .record_cancelled(&job.id)
+                .await
+                .map_err(|e| Status::internal(e.to_string()))?;
+        }
+        info!(job_id = %job.id, kind = %job.kind, "Job cancellation requested");
+        Ok(Response::new(job_to_proto(job)))
+    }
 }
 
 // === Helper functions ===
@@ -978,6 +1796,10 @@ fn volume_to_proto(entry: &VolumeEntry, vol: &crate::zfs::VolumeInfo) -> Volume
         compression_ratio: vol.compression_ratio,
         created_at: entry.created_at.clone(),
         snapshots: vec![], // Populated separately if needed
+        owner: entry.owner.as_ref().map(|o| OwnerReference {
+            kind: o.kind.clone(),
+            id: o.id.clone(),
+        }),
     }
 }
 
@@ -1035,3 +1857,150 @@ fn import_job_to_proto(
         completed_at: entry.completed_at.clone(),
     }
 }
+
+/// IDs of the import jobs that are still running.
+fn running_imports(jobs: &JobRegistry) -> Vec<String> {
+    jobs.list(false)
+        .into_iter()
+        .filter(|job| job.kind == "import")
+        .map(|job| job.id)
+        .collect()
+}
+
+fn job_to_proto(job: mvirt_common::jobs::Job) -> Job {
+    use mvirt_common::jobs::JobState as State;
+
+    let state = match job.state {
+        State::Running => JobState::Running,
+        State::Completed => JobState::Completed,
+        State::Failed => JobState::Failed,
+        State::Cancelled => JobState::Cancelled,
+    };
+    let rfc3339 = |t: std::time::SystemTime| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339();
+    Job {
+        id: job.id,
+        kind: job.kind,
+        target: job.target,
+        state: state.into(),
+        phase: job.phase,
+        done: job.done,
+        total: job.total.unwrap_or(0),
+        error: job.error,
+        cancellable: job.cancellable,
+        cancel_requested: job.cancel_requested,
+        started_at: rfc3339(job.started_at),
+        finished_at: job.finished_at.map(rfc3339),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::{Path, PathBuf};
+    use tonic::Code;
+
+    fn state_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mvirt-zfs-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// A service as main builds it, on the store in `dir`.
+    async fn service(dir: &Path) -> ZfsServiceImpl {
+        let store = Arc::new(Store::new(dir.to_str().unwrap()).await.unwrap());
+        let zfs = Arc::new(ZfsManager::new("test".to_string()));
+        let audit = Arc::new(ZfsAuditLogger::new_noop());
+        let import = Arc::new(ImportManager::new(
+            "test".to_string(),
+            dir.display().to_string(),
+            Arc::clone(&store),
+            Arc::clone(&zfs),
+            Arc::clone(&audit),
+        ));
+        let maintenance = store.get_maintenance().await.unwrap();
+        ZfsServiceImpl::new(store, zfs, import, audit).with_maintenance(maintenance)
+    }
+
+    async fn set(service: &ZfsServiceImpl, enabled: bool) -> Result<MaintenanceStatus, Status> {
+        let req = SetMaintenanceRequest {
+            enabled,
+            reason: if enabled {
+                "pool scrub".to_string()
+            } else {
+                String::new()
+            },
+        };
+        Ok(service
+            .set_maintenance(Request::new(req))
+            .await?
+            .into_inner())
+    }
+
+    async fn get(service: &ZfsServiceImpl) -> MaintenanceStatus {
+        service
+            .get_maintenance(Request::new(GetMaintenanceRequest {}))
+            .await
+            .unwrap()
+            .into_inner()
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_survives_restart() {
+        let dir = state_dir();
+        let status = set(&service(&dir).await, true).await.unwrap();
+        assert!(status.enabled);
+
+        let restarted = service(&dir).await;
+        assert_eq!(get(&restarted).await, status);
+        let err = restarted
+            .create_volume(Request::new(CreateVolumeRequest::default()))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+        assert!(err.message().contains("pool scrub"));
+
+        // Lifting it sticks as well
+        set(&restarted, false).await.unwrap();
+        assert!(!get(&service(&dir).await).await.enabled);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_maintenance_refused_while_importing() {
+        let dir = state_dir();
+        let service = service(&dir).await;
+        let import = service.jobs.start("import", "debian", true);
+        // Other jobs don't hold it up
+        let _send = service.jobs.start("volume-send", "data", true);
+
+        let err = set(&service, true).await.unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+        assert!(err.message().contains(import.id()));
+        assert!(!get(&service).await.enabled);
+        assert!(service.store.get_maintenance().await.unwrap().is_none());
+
+        import.complete();
+        assert!(set(&service, true).await.unwrap().enabled);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_import_refused_in_maintenance() {
+        let dir = state_dir();
+        let service = service(&dir).await;
+        set(&service, true).await.unwrap();
+
+        let req = ImportTemplateRequest {
+            name: "debian".to_string(),
+            source: "/nonexistent.img".to_string(),
+            ..Default::default()
+        };
+        let err = service
+            .import_template(Request::new(req))
+            .await
+            .unwrap_err();
+        assert_eq!(err.code(), Code::FailedPrecondition);
+        assert!(running_imports(&service.jobs).is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -2,13 +2,12 @@
 //!
 //! Handles importing raw and qcow2 images from local files and HTTP(S) URLs.
 
-use std::collections::HashMap;
 use std::sync::Arc;
 
 use anyhow::{Context, Result, anyhow};
+use mvirt_common::jobs::{JobHandle, JobRegistry};
 use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::sync::{RwLock, oneshot};
 use tracing::{error, info, warn};
 use uuid::Uuid;
 
@@ -47,9 +46,26 @@ impl ImportSource {
     }
 }
 
-/// State of an in-memory import job
-struct RunningJob {
-    cancel_tx: Option<oneshot::Sender<()>>,
+/// How an import that didn't fail ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Completed,
+    Cancelled,
+}
+
+/// Record a non-final import state in the store and on the job.
+async fn report(
+    store: &Store,
+    job: &JobHandle,
+    state: &str,
+    bytes: u64,
+    total: Option<u64>,
+) -> Result<()> {
+    job.set_phase(state);
+    job.set_progress(bytes, total);
+    store
+        .update_import_job(job.id(), state, bytes, total, None)
+        .await
 }
 
 /// Import manager handles async import operations
@@ -60,7 +76,8 @@ pub struct ImportManager {
     store: Arc<Store>,
     zfs: Arc<ZfsManager>,
     audit: Arc<ZfsAuditLogger>,
-    running_jobs: Arc<RwLock<HashMap<String, RunningJob>>>,
+    /// Running imports, for progress and cancellation
+    jobs: JobRegistry,
 }
 
 impl ImportManager {
@@ -77,10 +94,16 @@ impl ImportManager {
             store,
             zfs,
             audit,
-            running_jobs: Arc::new(RwLock::new(HashMap::new())),
+            jobs: JobRegistry::default(),
         }
     }
 
+    /// The registry imports report to. The daemon's other long-running
+    /// operations share it.
+    pub fn jobs(&self) -> &JobRegistry {
+        &self.jobs
+    }
+
     /// Detect image format from file header
     pub async fn detect_format_from_file(path: &str) -> Result<ImageFormat> {
         let mut file = File::open(path).await.context("Failed to open file")?;
@@ -151,31 +174,23 @@ impl ImportManager {
             .import_started(&job_entry.id, &template_name, source.as_str())
             .await;
 
-        // Create cancel channel
-        let (cancel_tx, cancel_rx) = oneshot::channel();
-
         // Register running job
-        {
-            let mut jobs = self.running_jobs.write().await;
-            jobs.insert(
-                job_entry.id.clone(),
-                RunningJob {
-                    cancel_tx: Some(cancel_tx),
-                },
-            );
-        }
+        let job = self
+            .jobs
+            .start_with_id(job_entry.id.clone(), "import", &template_name, true);
+        job.set_phase("pending");
+        job.set_progress(0, size_bytes);
 
         // Spawn background task
-        let job_id = job_entry.id.clone();
         let store = Arc::clone(&self.store);
         let zfs = Arc::clone(&self.zfs);
         let audit = Arc::clone(&self.audit);
         let state_dir = self.state_dir.clone();
-        let running_jobs = Arc::clone(&self.running_jobs);
 
         tokio::spawn(async move {
+            let job_id = job.id().to_string();
             let result = Self::run_import(
-                &job_id,
+                job,
                 &template_name,
                 source,
                 format,
@@ -184,16 +199,9 @@ impl ImportManager {
                 &zfs,
                 &audit,
                 &state_dir,
-                cancel_rx,
             )
             .await;
 
-            // Clean up running job entry
-            {
-                let mut jobs = running_jobs.write().await;
-                jobs.remove(&job_id);
-            }
-
             if let Err(e) = result {
                 error!(job_id = %job_id, error = %e, "Import job failed");
             }
@@ -214,26 +222,33 @@ impl ImportManager {
 
     /// Cancel a running job
     pub async fn cancel_job(&self, job_id: &str) -> Result<bool> {
-        let mut jobs = self.running_jobs.write().await;
-        if let Some(mut job) = jobs.remove(job_id)
-            && let Some(tx) = job.cancel_tx.take()
+        if self
+            .jobs
+            .get(job_id)
+            .is_some_and(|job| job.kind == "import")
+            && self.jobs.cancel(job_id).is_ok()
         {
-            let _ = tx.send(());
-            // Update job state in database
-            self.store
-                .update_import_job(job_id, "cancelled", 0, None, None)
-                .await?;
-            info!(job_id = %job_id, "Import job cancelled");
+            self.record_cancelled(job_id).await?;
             return Ok(true);
         }
         Ok(false)
     }
 
+    /// Mark an import cancelled in the database once its job was asked to
+    /// stop, without waiting for the import to notice.
+    pub async fn record_cancelled(&self, job_id: &str) -> Result<()> {
+        self.store
+            .update_import_job(job_id, "cancelled", 0, None, None)
+            .await?;
+        info!(job_id = %job_id, "Import job cancelled");
+        Ok(())
+    }
+
     /// Run the actual import with centralized error handling
     /// Returns the template_id on success for cleanup purposes
     #[allow(clippy::too_many_arguments)]
     async fn run_import(
-        job_id: &str,
+        job: JobHandle,
         template_name: &str,
         source: ImportSource,
         format: Option<ImageFormat>,
@@ -242,13 +257,13 @@ impl ImportManager {
         zfs: &ZfsManager,
         audit: &ZfsAuditLogger,
         state_dir: &str,
-        mut cancel_rx: oneshot::Receiver<()>,
     ) -> Result<()> {
+        let job_id = &job.id().to_string();
         // Generate template UUID upfront for cleanup tracking
         let template_id = Uuid::new_v4().to_string();
 
         let result = Self::run_import_inner(
-            job_id,
+            &job,
             &template_id,
             template_name,
             source,
@@ -257,57 +272,67 @@ impl ImportManager {
             store,
             zfs,
             state_dir,
-            &mut cancel_rx,
         )
         .await;
 
-        // Handle success: log completion
-        if result.is_ok() {
-            audit
-                .import_completed(job_id, &template_id, template_name)
-                .await;
-        }
+        let e = match result {
+            // Handle success: log completion
+            Ok(Outcome::Completed) => {
+                audit
+                    .import_completed(job_id, &template_id, template_name)
+                    .await;
+                job.set_phase("completed");
+                job.complete();
+                return Ok(());
+            }
+            Ok(Outcome::Cancelled) => {
+                job.set_phase("cancelled");
+                job.finish_cancelled();
+                return Ok(());
+            }
+            Err(e) => e,
+        };
 
         // Handle errors centrally: update job state and cleanup
-        if let Err(ref e) = result {
-            let error_msg = e.to_string();
-            error!(job_id = %job_id, error = %error_msg, "Import failed");
-
-            // Audit log: import failed
-            audit.import_failed(job_id, template_name, &error_msg).await;
-
-            // Try to clean up the template ZVOL if it was created
-            let zfs_path = zfs.template_zfs_path(&template_id);
-            if let Err(cleanup_err) = zfs.destroy_recursive(&zfs_path).await {
-                // Template ZVOL might not exist yet, that's fine
-                warn!(
-                    job_id = %job_id,
-                    template_id = %template_id,
-                    error = %cleanup_err,
-                    "Failed to cleanup template ZVOL after import error (may not exist)"
-                );
-            }
+        let error_msg = e.to_string();
+        error!(job_id = %job_id, error = %error_msg, "Import failed");
+
+        // Audit log: import failed
+        audit.import_failed(job_id, template_name, &error_msg).await;
+
+        // Try to clean up the template ZVOL if it was created
+        let zfs_path = zfs.template_zfs_path(&template_id);
+        if let Err(cleanup_err) = zfs.destroy_recursive(&zfs_path).await {
+            // Template ZVOL might not exist yet, that's fine
+            warn!(
+                job_id = %job_id,
+                template_id = %template_id,
+                error = %cleanup_err,
+                "Failed to cleanup template ZVOL after import error (may not exist)"
+            );
+        }
 
-            // Update job state to failed
-            if let Err(db_err) = store
-                .update_import_job(job_id, "failed", 0, None, Some(&error_msg))
-                .await
-            {
-                error!(
-                    job_id = %job_id,
-                    error = %db_err,
-                    "Failed to update job state to failed"
-                );
-            }
+        // Update job state to failed
+        if let Err(db_err) = store
+            .update_import_job(job_id, "failed", 0, None, Some(&error_msg))
+            .await
+        {
+            error!(
+                job_id = %job_id,
+                error = %db_err,
+                "Failed to update job state to failed"
+            );
         }
+        job.set_phase("failed");
+        job.fail(&error_msg);
 
-        result
+        Err(e)
     }
 
     /// Inner import logic - errors are handled by run_import wrapper
     #[allow(clippy::too_many_arguments)]
     async fn run_import_inner(
-        job_id: &str,
+        job: &JobHandle,
         template_id: &str,
         template_name: &str,
         source: ImportSource,
@@ -316,39 +341,28 @@ impl ImportManager {
         store: &Store,
         zfs: &ZfsManager,
         state_dir: &str,
-        cancel_rx: &mut oneshot::Receiver<()>,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
         match (format, source) {
             // Local files: format is known
             (Some(ImageFormat::Raw), ImportSource::LocalFile(path)) => {
                 Self::import_raw_file(
-                    job_id,
+                    job,
                     template_id,
                     template_name,
                     &path,
                     size_bytes,
                     store,
                     zfs,
-                    cancel_rx,
                 )
                 .await
             }
             (Some(ImageFormat::Qcow2), ImportSource::LocalFile(path)) => {
-                Self::import_qcow2_file(
-                    job_id,
-                    template_id,
-                    template_name,
-                    &path,
-                    store,
-                    zfs,
-                    cancel_rx,
-                )
-                .await
+                Self::import_qcow2_file(job, template_id, template_name, &path, store, zfs).await
             }
             // HTTP URLs: detect format from first bytes during download
             (None, ImportSource::HttpUrl(url)) => {
                 Self::import_from_url(
-                    job_id,
+                    job,
                     template_id,
                     template_name,
                     &url,
@@ -356,7 +370,6 @@ impl ImportManager {
                     store,
                     zfs,
                     state_dir,
-                    cancel_rx,
                 )
                 .await
             }
@@ -373,19 +386,15 @@ impl ImportManager {
     /// Import raw file to template
     #[allow(clippy::too_many_arguments)]
     async fn import_raw_file(
-        job_id: &str,
+        job: &JobHandle,
         template_id: &str,
         template_name: &str,
         path: &str,
         size_bytes: Option<u64>,
         store: &Store,
         zfs: &ZfsManager,
-        cancel_rx: &mut oneshot::Receiver<()>,
-    ) -> Result<()> {
-        // Update state to writing
-        store
-            .update_import_job(job_id, "writing", 0, None, None)
-            .await?;
+    ) -> Result<Outcome> {
+        let job_id = job.id();
 
         // Get file size
         let metadata = tokio::fs::metadata(path)
@@ -393,6 +402,9 @@ impl ImportManager {
             .context("Failed to get file metadata")?;
         let file_size = size_bytes.unwrap_or(metadata.len());
 
+        // Update state to writing
+        report(store, job, "writing", 0, Some(file_size)).await?;
+
         // Create ZVOL for template
         let device_path = zfs.create_template_zvol(template_id, file_size).await?;
 
@@ -415,7 +427,7 @@ impl ImportManager {
 
         loop {
             // Check for cancellation
-            if cancel_rx.try_recv().is_ok() {
+            if job.is_cancelled() {
                 // Clean up: delete the template ZVOL
                 let _ = zfs
                     .destroy_recursive(&zfs.template_zfs_path(template_id))
@@ -423,7 +435,7 @@ impl ImportManager {
                 store
                     .update_import_job(job_id, "cancelled", bytes_written, None, None)
                     .await?;
-                return Ok(());
+                return Ok(Outcome::Cancelled);
             }
 
             let n = src_file.read(&mut buffer).await?;
@@ -436,9 +448,7 @@ impl ImportManager {
 
             // Update progress every second
             if last_update.elapsed().as_secs() >= 1 {
-                store
-                    .update_import_job(job_id, "writing", bytes_written, None, None)
-                    .await?;
+                report(store, job, "writing", bytes_written, Some(file_size)).await?;
                 last_update = std::time::Instant::now();
             }
         }
@@ -472,25 +482,25 @@ impl ImportManager {
             "Raw file import completed"
         );
 
-        Ok(())
+        Ok(Outcome::Completed)
     }
 
     /// Import qcow2 file using qemu-img convert to template
     async fn import_qcow2_file(
-        job_id: &str,
+        job: &JobHandle,
         template_id: &str,
         template_name: &str,
         path: &str,
         store: &Store,
         zfs: &ZfsManager,
-        _cancel_rx: &mut oneshot::Receiver<()>,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
+        use std::process::Stdio;
         use tokio::process::Command;
 
+        let job_id = job.id();
+
         // Update state to converting
-        store
-            .update_import_job(job_id, "converting", 0, None, None)
-            .await?;
+        report(store, job, "converting", 0, None).await?;
 
         // Get virtual size from qcow2 using qemu-img info
         let output = Command::new("qemu-img")
@@ -521,9 +531,7 @@ impl ImportManager {
         let device_path = zfs.create_template_zvol(template_id, virtual_size).await?;
 
         // Update state to writing
-        store
-            .update_import_job(job_id, "writing", 0, None, None)
-            .await?;
+        report(store, job, "writing", 0, Some(virtual_size)).await?;
 
         // Convert qcow2 to raw directly to base ZVOL using qemu-img convert
         info!(
@@ -533,23 +541,66 @@ impl ImportManager {
             "Converting qcow2 to base ZVOL"
         );
 
-        let output = Command::new("qemu-img")
+        let mut child = Command::new("qemu-img")
             .args([
                 "convert",
                 "-f",
                 "qcow2",
                 "-O",
                 "raw",
-                "-p", // Show progress (goes to stderr)
+                "-p", // Show progress (goes to stdout)
                 path,
                 &device_path,
             ])
-            .output()
-            .await
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
             .context("Failed to run qemu-img convert")?;
+        let mut progress = child.stdout.take().context("qemu-img stdout")?;
+        let mut stderr = child.stderr.take().context("qemu-img stderr")?;
+        let stderr = tokio::spawn(async move {
+            let mut text = String::new();
+            let _ = stderr.read_to_string(&mut text).await;
+            text
+        });
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
+        let mut buffer = [0u8; 512];
+        let mut last_update = std::time::Instant::now();
+        loop {
+            tokio::select! {
+                n = progress.read(&mut buffer) => {
+                    let n = n.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    if let Some(percent) = qemu_img_progress(&buffer[..n])
+                        && last_update.elapsed().as_secs() >= 1
+                    {
+                        let written = (virtual_size as f64 * percent / 100.0) as u64;
+                        report(store, job, "writing", written, Some(virtual_size)).await?;
+                        last_update = std::time::Instant::now();
+                    }
+                }
+                _ = job.wait_cancelled() => {
+                    let _ = child.kill().await;
+                    let _ = zfs
+                        .destroy_recursive(&zfs.template_zfs_path(template_id))
+                        .await;
+                    store
+                        .update_import_job(job_id, "cancelled", 0, None, None)
+                        .await?;
+                    return Ok(Outcome::Cancelled);
+                }
+            }
+        }
+
+        let status = child
+            .wait()
+            .await
+            .context("Failed to run qemu-img convert")?;
+        if !status.success() {
+            let stderr = stderr.await.unwrap_or_default();
             return Err(anyhow!("qemu-img convert failed: {}", stderr));
         }
 
@@ -579,14 +630,14 @@ impl ImportManager {
             "qcow2 import completed"
         );
 
-        Ok(())
+        Ok(Outcome::Completed)
     }
 
     /// Import from URL with auto-detection of format from first bytes
     /// Downloads to temp file, detects format, then processes accordingly
     #[allow(clippy::too_many_arguments)]
     async fn import_from_url(
-        job_id: &str,
+        job: &JobHandle,
         template_id: &str,
         template_name: &str,
         url: &str,
@@ -594,14 +645,13 @@ impl ImportManager {
         store: &Store,
         zfs: &ZfsManager,
         state_dir: &str,
-        cancel_rx: &mut oneshot::Receiver<()>,
-    ) -> Result<()> {
+    ) -> Result<Outcome> {
         use futures_util::StreamExt;
 
+        let job_id = job.id();
+
         // Update state to downloading
-        store
-            .update_import_job(job_id, "downloading", 0, None, None)
-            .await?;
+        report(store, job, "downloading", 0, None).await?;
 
         // Create temp directory and file
         let tmp_dir = format!("{}/tmp", state_dir);
@@ -636,12 +686,12 @@ impl ImportManager {
         let mut magic_captured = false;
 
         while let Some(chunk_result) = stream.next().await {
-            if cancel_rx.try_recv().is_ok() {
+            if job.is_cancelled() {
                 let _ = tokio::fs::remove_file(&tmp_file).await;
                 store
                     .update_import_job(job_id, "cancelled", bytes_downloaded, None, None)
                     .await?;
-                return Ok(());
+                return Ok(Outcome::Cancelled);
             }
 
             let chunk = chunk_result.context("Failed to read HTTP chunk")?;
@@ -656,15 +706,7 @@ impl ImportManager {
             bytes_downloaded += chunk.len() as u64;
 
             if last_update.elapsed().as_secs() >= 1 {
-                store
-                    .update_import_job(
-                        job_id,
-                        "downloading",
-                        bytes_downloaded,
-                        content_length,
-                        None,
-                    )
-                    .await?;
+                report(store, job, "downloading", bytes_downloaded, content_length).await?;
                 last_update = std::time::Instant::now();
             }
         }
@@ -685,24 +727,14 @@ impl ImportManager {
         // Process based on detected format
         let result = match format {
             ImageFormat::Qcow2 => {
-                Self::import_qcow2_file(
-                    job_id,
-                    template_id,
-                    template_name,
-                    &tmp_file,
-                    store,
-                    zfs,
-                    cancel_rx,
-                )
-                .await
+                Self::import_qcow2_file(job, template_id, template_name, &tmp_file, store, zfs)
+                    .await
             }
             ImageFormat::Raw => {
                 // For raw, copy temp file to ZVOL
                 let file_size = size_bytes.or(content_length).unwrap_or(bytes_downloaded);
 
-                store
-                    .update_import_job(job_id, "writing", 0, None, None)
-                    .await?;
+                report(store, job, "writing", 0, Some(file_size)).await?;
 
                 let device_path = zfs.create_template_zvol(template_id, file_size).await?;
 
@@ -717,14 +749,31 @@ impl ImportManager {
 
                 let mut buffer = vec![0u8; 1024 * 1024];
                 let mut bytes_written: u64 = 0;
+                let mut last_update = std::time::Instant::now();
 
                 loop {
+                    if job.is_cancelled() {
+                        drop(zvol);
+                        let _ = tokio::fs::remove_file(&tmp_file).await;
+                        let _ = zfs
+                            .destroy_recursive(&zfs.template_zfs_path(template_id))
+                            .await;
+                        store
+                            .update_import_job(job_id, "cancelled", bytes_written, None, None)
+                            .await?;
+                        return Ok(Outcome::Cancelled);
+                    }
                     let n = src.read(&mut buffer).await?;
                     if n == 0 {
                         break;
                     }
                     zvol.write_all(&buffer[..n]).await?;
                     bytes_written += n as u64;
+
+                    if last_update.elapsed().as_secs() >= 1 {
+                        report(store, job, "writing", bytes_written, Some(file_size)).await?;
+                        last_update = std::time::Instant::now();
+                    }
                 }
 
                 zvol.flush().await?;
@@ -751,7 +800,7 @@ impl ImportManager {
                     "Raw URL import completed"
                 );
 
-                Ok(())
+                Ok(Outcome::Completed)
             }
         };
 
@@ -761,3 +810,27 @@ impl ImportManager {
         result
     }
 }
+
+/// Percentage from a chunk of `qemu-img -p` output, e.g. `    (42.17/100%)\r`.
+fn qemu_img_progress(output: &[u8]) -> Option<f64> {
+    let text = String::from_utf8_lossy(output);
+    let end = text.rfind("/100%)")?;
+    let start = text[..end].rfind('(')?;
+    text[start + 1..end].trim().parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_qemu_img_progress() {
+        assert_eq!(qemu_img_progress(b"    (0.00/100%)\r"), Some(0.0));
+        assert_eq!(
+            qemu_img_progress(b"    (12.50/100%)\r    (42.17/100%)\r"),
+            Some(42.17)
+        );
+        assert_eq!(qemu_img_progress(b"/100%)\r    (4"), None);
+        assert_eq!(qemu_img_progress(b""), None);
+    }
+}
@@ -5,7 +5,9 @@
 pub mod audit;
 pub mod grpc;
 pub mod import;
+pub mod rebase;
 pub mod store;
+pub mod transfer;
 pub mod zfs;
 
 pub mod proto {
@@ -6,6 +6,9 @@ use tokio::signal;
 use tonic::transport::Server;
 use tracing::{info, warn};
 
+use mvirt_common::events::EventBus;
+use mvirt_common::limits::{GrpcLimitLayer, LimitConfig};
+use mvirt_common::request_id::GrpcRequestIdLayer;
 use mvirt_log::tls_config_from_paths;
 use mvirt_zfs::audit::create_audit_logger;
 use mvirt_zfs::grpc::ZfsServiceImpl;
@@ -65,6 +68,9 @@ struct Args {
     /// Disable mTLS to mvirt-log (talk plain h2c). Dev/loopback only.
     #[arg(long, env = "MVIRT_LOG_INSECURE")]
     log_insecure: bool,
+
+    #[command(flatten)]
+    limits: LimitConfig,
 }
 
 #[tokio::main]
@@ -113,13 +119,24 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     ));
 
     // Create gRPC service
-    let service = ZfsServiceImpl::new(store, Arc::clone(&zfs_manager), import_manager, audit);
+    let maintenance = store.get_maintenance().await?;
+    if let Some(m) = &maintenance {
+        warn!(reason = %m.reason, "Still in maintenance mode; mutating RPCs are refused");
+    }
+    let mut service = ZfsServiceImpl::new(store, Arc::clone(&zfs_manager), import_manager, audit)
+        .with_maintenance(maintenance);
+    match EventBus::new("zfs") {
+        Ok(bus) => service = service.with_host_events(bus),
+        Err(e) => warn!(error = %e, "No host event bus; volume resizes are not published"),
+    }
 
     let addr = args.listen.parse()?;
     info!(addr = %addr, pool = %args.pool, "Starting gRPC server");
 
     // Run server with graceful shutdown on SIGTERM/SIGINT
     Server::builder()
+        .layer(GrpcRequestIdLayer)
+        .layer(GrpcLimitLayer::new(args.limits))
         .add_service(ZfsServiceServer::new(service))
         .serve_with_shutdown(addr, async {
             let ctrl_c = signal::ctrl_c();
@@ -0,0 +1,182 @@
+//! Rebasing a volume onto another template
+//!
+//! A clone stores only the blocks written since it was cloned and reads
+//! everything else from its template. Rebasing replays those blocks onto a
+//! clone of another template, typically a newer build of the same image,
+//! so the old template can go without a promoted clone holding its data.
+//!
+//! ZFS can't move a clone to another origin, and the volume's blocks only
+//! make sense on top of the image they were written against. The replay is
+//! therefore only done if no block was changed both by the volume and,
+//! differently, by the new template. Filesystem images that were mounted
+//! on both sides usually conflict (superblocks, allocation bitmaps); those
+//! volumes stay where they are and the caller learns how much conflicts.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, Read, Seek, SeekFrom, Write};
+
+/// Prefix of the snapshot RebaseVolume takes of the source volume
+pub const SNAPSHOT_PREFIX: &str = "rebase-";
+
+/// Read buffer per device while comparing
+const READ_BUFFER: usize = 1024 * 1024;
+
+/// Blocks a volume changed relative to the template it was cloned from
+#[derive(Debug, PartialEq)]
+pub struct Deltas {
+    pub block_size: u64,
+    /// Offsets of the blocks the volume changed, ascending
+    pub changed: Vec<u64>,
+    /// Bytes of those blocks the new template changed as well, to
+    /// something else
+    pub conflict_bytes: u64,
+}
+
+impl Deltas {
+    pub fn changed_bytes(&self) -> u64 {
+        self.changed.len() as u64 * self.block_size
+    }
+}
+
+/// Fill `buf` from `r`, zeroing what is past its end. Returns the bytes read.
+fn read_block(r: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut n = 0;
+    while n < buf.len() {
+        match r.read(&mut buf[n..]) {
+            Ok(0) => break,
+            Ok(k) => n += k,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    buf[n..].fill(0);
+    Ok(n)
+}
+
+/// Compare `volume` and the new template `target` block by block against
+/// `base`, the template the volume was cloned from. Runs to the end of
+/// `volume`; `base` and `target` read as zeros past their end.
+pub fn compare(
+    mut base: impl Read,
+    mut volume: impl Read,
+    mut target: impl Read,
+    block_size: usize,
+) -> io::Result<Deltas> {
+    let mut b = vec![0u8; block_size];
+    let mut v = vec![0u8; block_size];
+    let mut t = vec![0u8; block_size];
+    let mut deltas = Deltas {
+        block_size: block_size as u64,
+        changed: Vec::new(),
+        conflict_bytes: 0,
+    };
+    let mut offset = 0;
+    while read_block(&mut volume, &mut v)? > 0 {
+        read_block(&mut base, &mut b)?;
+        read_block(&mut target, &mut t)?;
+        if v != b {
+            deltas.changed.push(offset);
+            if t != b && t != v {
+                deltas.conflict_bytes += block_size as u64;
+            }
+        }
+        offset += block_size as u64;
+    }
+    Ok(deltas)
+}
+
+/// Write the blocks the volume changed to the same offsets of `dest`.
+pub fn replay(
+    mut volume: impl Read + Seek,
+    mut dest: impl Write + Seek,
+    deltas: &Deltas,
+) -> io::Result<()> {
+    let mut buf = vec![0u8; deltas.block_size as usize];
+    for &offset in &deltas.changed {
+        volume.seek(SeekFrom::Start(offset))?;
+        let n = read_block(&mut volume, &mut buf)?;
+        dest.seek(SeekFrom::Start(offset))?;
+        dest.write_all(&buf[..n])?;
+    }
+    dest.flush()
+}
+
+/// [`compare`] on device nodes
+pub fn compare_devices(
+    base: &str,
+    volume: &str,
+    target: &str,
+    block_size: usize,
+) -> io::Result<Deltas> {
+    let open = |path: &str| File::open(path).map(|f| BufReader::with_capacity(READ_BUFFER, f));
+    compare(open(base)?, open(volume)?, open(target)?, block_size)
+}
+
+/// [`replay`] between device nodes; the destination is synced before
+/// returning.
+pub fn replay_devices(volume: &str, dest: &str, deltas: &Deltas) -> io::Result<()> {
+    let mut dest = OpenOptions::new().write(true).open(dest)?;
+    replay(File::open(volume)?, &mut dest, deltas)?;
+    dest.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const BS: usize = 4;
+
+    /// A four-block image with `blocks` overwritten
+    fn image(blocks: &[(usize, u8)]) -> Vec<u8> {
+        let mut data = vec![0u8; 4 * BS];
+        for &(i, byte) in blocks {
+            data[i * BS..(i + 1) * BS].fill(byte);
+        }
+        data
+    }
+
+    fn deltas(base: &[u8], volume: &[u8], target: &[u8]) -> Deltas {
+        compare(base, volume, target, BS).unwrap()
+    }
+
+    #[test]
+    fn test_disjoint_changes() {
+        let base = image(&[(0, 1)]);
+        let volume = image(&[(0, 1), (1, 7)]);
+        let target = image(&[(0, 1), (2, 9)]);
+        let d = deltas(&base, &volume, &target);
+        assert_eq!(d.changed, vec![BS as u64]);
+        assert_eq!(d.conflict_bytes, 0);
+
+        let mut dest = Cursor::new(target.clone());
+        replay(Cursor::new(&volume), &mut dest, &d).unwrap();
+        assert_eq!(dest.into_inner(), image(&[(0, 1), (1, 7), (2, 9)]));
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let base = image(&[]);
+        let volume = image(&[(1, 7), (2, 5)]);
+        // Block 1 changed differently, block 2 the same way
+        let target = image(&[(1, 8), (2, 5)]);
+        let d = deltas(&base, &volume, &target);
+        assert_eq!(d.changed, vec![BS as u64, 2 * BS as u64]);
+        assert_eq!(d.conflict_bytes, BS as u64);
+    }
+
+    #[test]
+    fn test_grown_volume() {
+        // The volume was resized past the template and written at the end
+        let base = image(&[]);
+        let mut volume = image(&[]);
+        volume.extend_from_slice(&[0, 0, 0, 0, 3, 3]);
+        let d = deltas(&base, &volume, &base);
+        assert_eq!(d.changed, vec![5 * BS as u64]);
+        assert_eq!(d.changed_bytes(), BS as u64);
+
+        let mut dest = Cursor::new(vec![0u8; volume.len()]);
+        replay(Cursor::new(&volume), &mut dest, &d).unwrap();
+        assert_eq!(dest.into_inner(), volume);
+    }
+}
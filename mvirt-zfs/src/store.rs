@@ -1,7 +1,7 @@
 use anyhow::Result;
 use chrono::Utc;
 use sqlx::Row;
-use sqlx::sqlite::{SqlitePool, SqlitePoolOptions};
+use sqlx::sqlite::{SqliteConnection, SqlitePool, SqlitePoolOptions};
 use uuid::Uuid;
 
 /// SQLite-backed metadata store for ZFS volumes
@@ -30,8 +30,8 @@ impl Store {
     pub async fn create_volume(&self, entry: &VolumeEntry) -> Result<()> {
         sqlx::query(
             r#"
-            INSERT INTO volumes (id, name, zfs_path, device_path, size_bytes, origin_template_id, created_at, updated_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO volumes (id, name, zfs_path, device_path, size_bytes, origin_template_id, owner_kind, owner_id, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
         .bind(&entry.id)
@@ -40,6 +40,8 @@ impl Store {
         .bind(&entry.device_path)
         .bind(entry.size_bytes as i64)
         .bind(&entry.origin_template_id)
+        .bind(entry.owner.as_ref().map(|o| &o.kind))
+        .bind(entry.owner.as_ref().map(|o| &o.id))
         .bind(&entry.created_at)
         .bind(&entry.updated_at)
         .execute(&self.pool)
@@ -51,7 +53,7 @@ impl Store {
     pub async fn get_volume(&self, id: &str) -> Result<Option<VolumeEntry>> {
         let row = sqlx::query(
             r#"
-            SELECT id, name, zfs_path, device_path, size_bytes, origin_template_id, created_at, updated_at
+            SELECT id, name, zfs_path, device_path, size_bytes, origin_template_id, owner_kind, owner_id, created_at, updated_at
             FROM volumes WHERE id = ?
             "#,
         )
@@ -66,6 +68,7 @@ impl Store {
             device_path: r.get("device_path"),
             size_bytes: r.get::<i64, _>("size_bytes") as u64,
             origin_template_id: r.get("origin_template_id"),
+            owner: owner_from_row(&r),
             created_at: r.get("created_at"),
             updated_at: r.get("updated_at"),
         }))
@@ -74,7 +77,7 @@ impl Store {
     pub async fn get_volume_by_name(&self, name: &str) -> Result<Option<VolumeEntry>> {
         let row = sqlx::query(
             r#"
-            SELECT id, name, zfs_path, device_path, size_bytes, origin_template_id, created_at, updated_at
+            SELECT id, name, zfs_path, device_path, size_bytes, origin_template_id, owner_kind, owner_id, created_at, updated_at
             FROM volumes WHERE name = ?
             "#,
         )
@@ -89,6 +92,7 @@ impl Store {
             device_path: r.get("device_path"),
             size_bytes: r.get::<i64, _>("size_bytes") as u64,
             origin_template_id: r.get("origin_template_id"),
+            owner: owner_from_row(&r),
             created_at: r.get("created_at"),
             updated_at: r.get("updated_at"),
         }))
@@ -97,7 +101,7 @@ impl Store {
     pub async fn list_volumes(&self) -> Result<Vec<VolumeEntry>> {
         let rows = sqlx::query(
             r#"
-            SELECT id, name, zfs_path, device_path, size_bytes, origin_template_id, created_at, updated_at
+            SELECT id, name, zfs_path, device_path, size_bytes, origin_template_id, owner_kind, owner_id, created_at, updated_at
             FROM volumes ORDER BY created_at DESC
             "#,
         )
@@ -113,6 +117,7 @@ impl Store {
                 device_path: r.get("device_path"),
                 size_bytes: r.get::<i64, _>("size_bytes") as u64,
                 origin_template_id: r.get("origin_template_id"),
+                owner: owner_from_row(&r),
                 created_at: r.get("created_at"),
                 updated_at: r.get("updated_at"),
             })
@@ -467,6 +472,188 @@ impl Store {
 
         Ok(row.is_some())
     }
+
+    // === Maintenance ===
+
+    /// The maintenance mode in effect, if any.
+    pub async fn get_maintenance(&self) -> Result<Option<MaintenanceEntry>> {
+        let row = sqlx::query("SELECT reason, since FROM maintenance WHERE id = 1")
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|r| MaintenanceEntry {
+            reason: r.get("reason"),
+            since: r.get("since"),
+        }))
+    }
+
+    /// Enter (or update) maintenance mode, or leave it with `None`.
+    pub async fn set_maintenance(&self, entry: Option<&MaintenanceEntry>) -> Result<()> {
+        match entry {
+            Some(entry) => {
+                sqlx::query(
+                    "INSERT INTO maintenance (id, reason, since) VALUES (1, ?, ?) ON CONFLICT(id) DO UPDATE SET reason = excluded.reason, since = excluded.since",
+                )
+                .bind(&entry.reason)
+                .bind(entry.since)
+                .execute(&self.pool)
+                .await?;
+            }
+            None => {
+                sqlx::query("DELETE FROM maintenance")
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    // === Host migration ===
+
+    /// Volume, snapshot and template rows for a state snapshot, as
+    /// `{"table": [{column: value}]}`. Import jobs stay behind.
+    pub async fn export_state(&self) -> Result<serde_json::Value> {
+        let mut conn = self.pool.acquire().await?;
+        let mut state = serde_json::Map::new();
+        for table in STATE_TABLES {
+            let rows = export_table(&mut *conn, table).await?;
+            state.insert(table.to_string(), serde_json::Value::Array(rows));
+        }
+        Ok(serde_json::Value::Object(state))
+    }
+
+    /// Insert the rows of a state snapshot. Returns the number of rows.
+    pub async fn import_state(&self, state: &serde_json::Value) -> Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let mut rows = 0;
+        for table in STATE_TABLES {
+            rows += import_table(&mut *tx, table, snapshot_rows(state, table)).await?;
+        }
+        tx.commit().await?;
+        Ok(rows)
+    }
+
+    /// Whether there are no volumes or templates; imports only go into an
+    /// empty store.
+    pub async fn is_empty(&self) -> Result<bool> {
+        let count: i64 = sqlx::query_scalar(
+            "SELECT (SELECT COUNT(*) FROM volumes) + (SELECT COUNT(*) FROM templates)",
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count == 0)
+    }
+}
+
+/// Format version of [`Store::export_state`] snapshots.
+pub const STATE_VERSION: u32 = 1;
+
+/// Tables in a state snapshot, parents first.
+const STATE_TABLES: [&str; 3] = ["volumes", "snapshots", "templates"];
+
+/// Number of rows in a state snapshot.
+pub fn snapshot_len(state: &serde_json::Value) -> usize {
+    STATE_TABLES
+        .iter()
+        .map(|t| snapshot_rows(state, t).len())
+        .sum()
+}
+
+/// ZFS datasets and snapshots a state snapshot refers to, with a label
+/// for messages.
+pub fn snapshot_datasets(state: &serde_json::Value) -> Vec<(String, String)> {
+    let mut datasets = Vec::new();
+    for row in snapshot_rows(state, "volumes") {
+        if let Some(path) = row["zfs_path"].as_str() {
+            datasets.push((
+                format!("volume {}", row["name"].as_str().unwrap_or_default()),
+                path.to_string(),
+            ));
+        }
+    }
+    for row in snapshot_rows(state, "snapshots") {
+        if let Some(name) = row["zfs_name"].as_str() {
+            datasets.push((
+                format!("snapshot {}", row["name"].as_str().unwrap_or_default()),
+                name.to_string(),
+            ));
+        }
+    }
+    for row in snapshot_rows(state, "templates") {
+        let label = format!("template {}", row["name"].as_str().unwrap_or_default());
+        for column in ["base_zvol_path", "snapshot_path"] {
+            if let Some(path) = row[column].as_str().filter(|p| !p.is_empty()) {
+                datasets.push((label.clone(), path.to_string()));
+            }
+        }
+    }
+    datasets
+}
+
+fn snapshot_rows<'a>(state: &'a serde_json::Value, table: &str) -> &'a [serde_json::Value] {
+    state[table]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default()
+}
+
+async fn table_columns(conn: &mut SqliteConnection, table: &str) -> Result<Vec<String>> {
+    let rows = sqlx::query(&format!("PRAGMA table_info({})", table))
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rows.iter().map(|r| r.get("name")).collect())
+}
+
+/// All rows of `table` as JSON objects keyed by column.
+async fn export_table(conn: &mut SqliteConnection, table: &str) -> Result<Vec<serde_json::Value>> {
+    let fields = table_columns(conn, table)
+        .await?
+        .iter()
+        .map(|c| format!("'{0}', \"{0}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let json: String = sqlx::query_scalar(&format!(
+        "SELECT json_group_array(json_object({})) FROM {}",
+        fields, table
+    ))
+    .fetch_one(&mut *conn)
+    .await?;
+    Ok(serde_json::from_str(&json)?)
+}
+
+/// Insert rows written by [`export_table`]. Columns missing from the rows,
+/// as in an export from an older version, get their defaults.
+async fn import_table(
+    conn: &mut SqliteConnection,
+    table: &str,
+    rows: &[serde_json::Value],
+) -> Result<u64> {
+    let Some(first) = rows.first().and_then(|r| r.as_object()) else {
+        return Ok(0);
+    };
+    let columns: Vec<String> = table_columns(conn, table)
+        .await?
+        .into_iter()
+        .filter(|c| first.contains_key(c))
+        .collect();
+    let names = columns
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let values = columns
+        .iter()
+        .map(|c| format!("json_extract(value, '$.\"{}\"')", c))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let result = sqlx::query(&format!(
+        "INSERT INTO {} ({}) SELECT {} FROM json_each(?)",
+        table, names, values
+    ))
+    .bind(serde_json::to_string(rows)?)
+    .execute(&mut *conn)
+    .await?;
+    Ok(result.rows_affected())
 }
 
 // === Entry types ===
@@ -479,10 +666,27 @@ pub struct VolumeEntry {
     pub device_path: String,
     pub size_bytes: u64,
     pub origin_template_id: Option<String>,
+    pub owner: Option<OwnerRef>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+/// Pod or VM a volume belongs to; deleting it cascades to the volume.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnerRef {
+    pub kind: String,
+    pub id: String,
+}
+
+fn owner_from_row(r: &sqlx::sqlite::SqliteRow) -> Option<OwnerRef> {
+    let kind: Option<String> = r.get("owner_kind");
+    let id: Option<String> = r.get("owner_id");
+    Some(OwnerRef {
+        kind: kind?,
+        id: id?,
+    })
+}
+
 impl VolumeEntry {
     pub fn new(
         id: String,
@@ -500,6 +704,7 @@ impl VolumeEntry {
             device_path,
             size_bytes,
             origin_template_id,
+            owner: None,
             created_at: now.clone(),
             updated_at: now,
         }
@@ -576,6 +781,14 @@ impl ImportJobEntry {
     }
 }
 
+/// Maintenance mode: the pool is read-only until it is lifted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MaintenanceEntry {
+    pub reason: String,
+    /// Unix timestamp
+    pub since: i64,
+}
+
 /// Snapshot entry (directly contains ZFS snapshot name)
 #[derive(Debug, Clone)]
 pub struct SnapshotEntry {
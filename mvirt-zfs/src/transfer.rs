@@ -0,0 +1,246 @@
+//! Volume transfer between hosts
+//!
+//! A volume moves as a `zfs send` stream cut into [`VolumeStreamChunk`]s:
+//! one header, the data, then a SHA-256 over the data. The sending side
+//! pumps the stream in [`send_stream`]; the receiving side checks the
+//! order and the checksum with [`ReceiveStream`] before it lets
+//! `zfs receive` finish.
+
+use anyhow::{Result, anyhow};
+use mvirt_common::jobs::JobHandle;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
+use tokio::process::Child;
+use tokio::sync::mpsc;
+use tonic::Status;
+
+use crate::proto::volume_stream_chunk::Chunk;
+use crate::proto::{VolumeStreamChunk, VolumeStreamHeader};
+
+/// Data bytes per chunk, well below tonic's 4 MiB message limit
+pub const CHUNK_SIZE: usize = 256 * 1024;
+
+/// Prefix of the snapshot SendVolume takes for a transfer
+pub const SNAPSHOT_PREFIX: &str = "xfer-";
+
+/// Running SHA-256 over the data bytes of a transfer
+#[derive(Default)]
+struct Checksum {
+    hasher: Sha256,
+    bytes: u64,
+}
+
+impl Checksum {
+    fn update(&mut self, data: &[u8]) {
+        self.hasher.update(data);
+        self.bytes += data.len() as u64;
+    }
+
+    fn hex(self) -> String {
+        self.hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect()
+    }
+}
+
+/// Stream a running `zfs send` to `tx`: the header, its stdout in chunks,
+/// and the checksum once the process exited cleanly. Returns the number
+/// of data bytes sent. Progress goes to `job`, which can cancel the send.
+pub async fn send_stream(
+    mut child: Child,
+    header: VolumeStreamHeader,
+    tx: &mpsc::Sender<Result<VolumeStreamChunk, Status>>,
+    job: &JobHandle,
+) -> Result<u64> {
+    let mut stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| anyhow!("zfs send has no stdout"))?;
+
+    let closed = || anyhow!("receiver closed the stream");
+    tx.send(Ok(chunk(Chunk::Header(header))))
+        .await
+        .map_err(|_| closed())?;
+
+    let mut checksum = Checksum::default();
+    let mut buf = vec![0u8; CHUNK_SIZE];
+    loop {
+        if job.is_cancelled() {
+            let _ = child.kill().await;
+            return Err(anyhow!("cancelled"));
+        }
+        let n = stdout.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        checksum.update(&buf[..n]);
+        job.set_progress(checksum.bytes, None);
+        if tx
+            .send(Ok(chunk(Chunk::Data(buf[..n].to_vec()))))
+            .await
+            .is_err()
+        {
+            let _ = child.kill().await;
+            return Err(closed());
+        }
+    }
+
+    // Only a complete stream gets a checksum; without it the receiver
+    // discards what it got so far.
+    let status = child.wait().await?;
+    if !status.success() {
+        return Err(anyhow!("zfs send exited with {:?}", status.code()));
+    }
+    let bytes = checksum.bytes;
+    tx.send(Ok(chunk(Chunk::Sha256(checksum.hex()))))
+        .await
+        .map_err(|_| closed())?;
+    Ok(bytes)
+}
+
+fn chunk(chunk: Chunk) -> VolumeStreamChunk {
+    VolumeStreamChunk { chunk: Some(chunk) }
+}
+
+/// Receiving end of a transfer: validates message order and the checksum.
+pub struct ReceiveStream {
+    checksum: Option<Checksum>,
+    verified: bool,
+}
+
+impl ReceiveStream {
+    /// Start from the first message, which must be the header.
+    pub fn start(first: Option<VolumeStreamChunk>) -> Result<(Self, VolumeStreamHeader), Status> {
+        let header = match first.and_then(|c| c.chunk) {
+            Some(Chunk::Header(header)) => header,
+            _ => return Err(Status::invalid_argument("stream must start with a header")),
+        };
+        if header.name.is_empty() {
+            return Err(Status::invalid_argument("header name is required"));
+        }
+        // The snapshot name ends up in `zfs destroy`; `%` or `,` there
+        // would select more than one snapshot.
+        if header.snapshot.is_empty()
+            || !header
+                .snapshot
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(Status::invalid_argument(format!(
+                "invalid transfer snapshot name '{}'",
+                header.snapshot
+            )));
+        }
+        let stream = Self {
+            checksum: Some(Checksum::default()),
+            verified: false,
+        };
+        Ok((stream, header))
+    }
+
+    /// Feed the next message. Returns the data to hand to `zfs receive`.
+    pub fn next<'a>(&mut self, msg: &'a VolumeStreamChunk) -> Result<Option<&'a [u8]>, Status> {
+        let Some(checksum) = self.checksum.as_mut() else {
+            return Err(Status::invalid_argument("data after the checksum"));
+        };
+        match &msg.chunk {
+            Some(Chunk::Data(data)) => {
+                checksum.update(data);
+                Ok(Some(data))
+            }
+            Some(Chunk::Sha256(expected)) => {
+                let actual = self.checksum.take().map(Checksum::hex).unwrap_or_default();
+                if !actual.eq_ignore_ascii_case(expected) {
+                    return Err(Status::data_loss(format!(
+                        "checksum mismatch: expected {}, got {}",
+                        expected, actual
+                    )));
+                }
+                self.verified = true;
+                Ok(None)
+            }
+            Some(Chunk::Header(_)) => Err(Status::invalid_argument(
+                "header in the middle of the stream",
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// The stream ended; only a verified checksum makes it complete.
+    pub fn finish(&self) -> Result<(), Status> {
+        if self.verified {
+            Ok(())
+        } else {
+            Err(Status::aborted("stream ended before the checksum"))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::Code;
+
+    fn header() -> VolumeStreamChunk {
+        chunk(Chunk::Header(VolumeStreamHeader {
+            name: "data".to_string(),
+            id: String::new(),
+            size_bytes: 1 << 30,
+            snapshot: "xfer-1234".to_string(),
+        }))
+    }
+
+    fn digest(parts: &[&[u8]]) -> String {
+        let mut checksum = Checksum::default();
+        for part in parts {
+            checksum.update(part);
+        }
+        checksum.hex()
+    }
+
+    #[test]
+    fn receive_verifies_checksum() {
+        let (mut stream, header) = ReceiveStream::start(Some(header())).unwrap();
+        assert_eq!(header.name, "data");
+
+        let a = chunk(Chunk::Data(b"hello ".to_vec()));
+        let b = chunk(Chunk::Data(b"world".to_vec()));
+        assert_eq!(stream.next(&a).unwrap(), Some(&b"hello "[..]));
+        assert_eq!(stream.next(&b).unwrap(), Some(&b"world"[..]));
+        assert_eq!(stream.finish().unwrap_err().code(), Code::Aborted);
+
+        let sum = chunk(Chunk::Sha256(digest(&[b"hello world"])));
+        assert_eq!(stream.next(&sum).unwrap(), None);
+        stream.finish().unwrap();
+
+        // Nothing may follow the checksum
+        assert_eq!(stream.next(&a).unwrap_err().code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn receive_rejects_bad_streams() {
+        // No header first
+        let data = chunk(Chunk::Data(vec![1, 2, 3]));
+        assert!(ReceiveStream::start(Some(data.clone())).is_err());
+        assert!(ReceiveStream::start(None).is_err());
+
+        // Snapshot names that would widen `zfs destroy`
+        for snapshot in ["", "a%b", "a,b", "a@b"] {
+            let mut h = header();
+            if let Some(Chunk::Header(h)) = h.chunk.as_mut() {
+                h.snapshot = snapshot.to_string();
+            }
+            assert!(ReceiveStream::start(Some(h)).is_err(), "{snapshot}");
+        }
+
+        // Second header and wrong checksum
+        let (mut stream, _) = ReceiveStream::start(Some(header())).unwrap();
+        assert!(stream.next(&header()).is_err());
+        stream.next(&data).unwrap();
+        let wrong = chunk(Chunk::Sha256(digest(&[&[1, 2, 4]])));
+        assert_eq!(stream.next(&wrong).unwrap_err().code(), Code::DataLoss);
+        assert!(stream.finish().is_err());
+    }
+}
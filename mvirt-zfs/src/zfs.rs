@@ -2,12 +2,19 @@
 //!
 //! Uses libzfs for reading pool/dataset info and shell commands for write operations.
 
+use std::process::Stdio;
 use std::time::Duration;
 
 use anyhow::{Context, Result, anyhow};
-use tokio::process::Command;
+use tokio::process::{Child, Command};
 use tracing::info;
 
+/// Kernel statistics of the ZFS ARC.
+const ARCSTATS_PATH: &str = "/proc/spl/kstat/zfs/arcstats";
+
+/// Read size when prewarming a template.
+const PREWARM_CHUNK: usize = 1024 * 1024;
+
 /// Manager for ZFS pool and volume operations
 pub struct ZfsManager {
     pool_name: String,
@@ -160,7 +167,13 @@ impl ZfsManager {
     pub async fn get_pool_stats(&self) -> Result<PoolStats> {
         // Use zpool list for basic stats
         let output = Command::new("zpool")
-            .args(["list", "-Hp", "-o", "name,size,alloc,free", &self.pool_name])
+            .args([
+                "list",
+                "-Hp",
+                "-o",
+                "name,size,alloc,free,health",
+                &self.pool_name,
+            ])
             .output()
             .await
             .context("Failed to run zpool list")?;
@@ -173,13 +186,14 @@ impl ZfsManager {
         let stdout = String::from_utf8_lossy(&output.stdout);
         let parts: Vec<&str> = stdout.trim().split('\t').collect();
 
-        if parts.len() < 4 {
+        if parts.len() < 5 {
             return Err(anyhow!("Unexpected zpool list output: {}", stdout));
         }
 
         let total_bytes: u64 = parts[1].parse().unwrap_or(0);
         let used_bytes: u64 = parts[2].parse().unwrap_or(0);
         let available_bytes: u64 = parts[3].parse().unwrap_or(0);
+        let health = parts[4].to_string();
 
         // Get provisioned bytes (sum of all volsize)
         let provisioned_bytes = self.get_total_provisioned().await.unwrap_or(0);
@@ -194,6 +208,7 @@ impl ZfsManager {
             used_bytes,
             provisioned_bytes,
             compression_ratio,
+            health,
         })
     }
 
@@ -518,6 +533,54 @@ impl ZfsManager {
         Ok(vol)
     }
 
+    /// Read a template's ZVOL end to end, discarding the data, so its blocks
+    /// are in the ARC. Clones share the template's blocks, so VMs booting
+    /// from fresh clones then read from memory. Returns the bytes read.
+    pub async fn prewarm_template(&self, uuid: &str) -> Result<u64> {
+        use std::io::Read;
+
+        let device_path = self.template_device_path(uuid);
+        info!(uuid = %uuid, device = %device_path, "Prewarming template");
+
+        tokio::task::spawn_blocking(move || {
+            let mut device = std::fs::File::open(&device_path)
+                .with_context(|| format!("Failed to open {}", device_path))?;
+            let mut buf = vec![0u8; PREWARM_CHUNK];
+            let mut total = 0u64;
+            loop {
+                match device.read(&mut buf) {
+                    Ok(0) => return Ok(total),
+                    Ok(n) => total += n as u64,
+                    Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                    Err(e) => {
+                        return Err(anyhow!("Failed to read {}: {}", device_path, e));
+                    }
+                }
+            }
+        })
+        .await?
+    }
+
+    /// Space a dataset references, i.e. the data it holds
+    pub async fn referenced_bytes(&self, zfs_path: &str) -> Result<u64> {
+        let output = Command::new("zfs")
+            .args(["get", "-Hp", "-o", "value", "referenced", zfs_path])
+            .output()
+            .await
+            .context("Failed to run zfs get")?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(anyhow!("zfs get referenced failed: {}", stderr));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        stdout
+            .trim()
+            .parse()
+            .map_err(|_| anyhow!("Unexpected zfs get output: {}", stdout))
+    }
+
     /// Copy a snapshot to a new independent dataset using zfs send/receive
     /// This creates a full independent copy, not a clone.
     pub async fn copy_snapshot_to_dataset(
@@ -564,6 +627,40 @@ impl ZfsManager {
         Ok(())
     }
 
+    /// Start `zfs send` of a snapshot with its stream on stdout
+    pub fn spawn_send(&self, snapshot_path: &str) -> Result<Child> {
+        info!(snapshot = %snapshot_path, "Starting zfs send");
+        Command::new("zfs")
+            .args(["send", snapshot_path])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to spawn zfs send")
+    }
+
+    /// Start `zfs receive` into a new volume, reading the stream from stdin.
+    /// Killing the process before stdin is closed discards the stream.
+    pub fn spawn_receive(&self, uuid: &str) -> Result<Child> {
+        let zfs_path = self.volume_zfs_path(uuid);
+        info!(target = %zfs_path, "Starting zfs receive");
+        Command::new("zfs")
+            .args(["receive", &zfs_path])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to spawn zfs receive")
+    }
+
+    /// Get a volume that was just received and wait for its device node
+    pub async fn wait_for_volume(&self, uuid: &str) -> Result<VolumeInfo> {
+        let vol = self.get_volume(uuid).await?;
+        Self::wait_for_device(&vol.device_path).await?;
+        Ok(vol)
+    }
+
     // === Snapshot Operations ===
 
     /// Create a snapshot
@@ -722,6 +819,19 @@ impl ZfsManager {
         Ok(())
     }
 
+    /// Clone a snapshot to a scratch ZVOL under .tmp, to read it through
+    /// a device node. Returns the clone's ZFS path and device path.
+    pub async fn clone_to_scratch(&self, snapshot_path: &str) -> Result<(String, String)> {
+        let zfs_path = format!("{}/.tmp/{}", self.pool_name, uuid::Uuid::new_v4());
+        self.clone_snapshot(snapshot_path, &zfs_path).await?;
+        let device_path = format!("/dev/zvol/{}", zfs_path);
+        if let Err(e) = Self::wait_for_device(&device_path).await {
+            let _ = self.destroy(&zfs_path).await;
+            return Err(e);
+        }
+        Ok((zfs_path, device_path))
+    }
+
     /// Promote a clone to become the origin (reverses parent-child relationship)
     /// After promote, the original dataset becomes a clone of this one.
     pub async fn promote(&self, clone_path: &str) -> Result<()> {
@@ -813,6 +923,17 @@ impl ZfsManager {
         Ok(())
     }
 
+    /// Check whether a dataset, zvol or snapshot exists
+    pub async fn exists(&self, name: &str) -> Result<bool> {
+        let output = Command::new("zfs")
+            .args(["list", "-H", "-t", "all", "-o", "name", name])
+            .output()
+            .await
+            .context("Failed to run zfs list")?;
+
+        Ok(output.status.success())
+    }
+
     // === Helper Methods ===
 
     fn parse_volume_line(&self, line: &str) -> Option<VolumeInfo> {
@@ -876,6 +997,23 @@ impl ZfsManager {
     }
 }
 
+/// Upper size limit of the ARC, if the ZFS module exposes it
+pub fn arc_max_bytes() -> Option<u64> {
+    let stats = std::fs::read_to_string(ARCSTATS_PATH).ok()?;
+    parse_arcstat(&stats, "c_max")
+}
+
+/// A value from arcstats, whose lines are `<name> <type> <value>`
+fn parse_arcstat(stats: &str, name: &str) -> Option<u64> {
+    stats.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != name {
+            return None;
+        }
+        fields.nth(1)?.parse().ok()
+    })
+}
+
 // === Data Types ===
 
 #[derive(Debug, Clone)]
@@ -886,6 +1024,7 @@ pub struct PoolStats {
     pub used_bytes: u64,
     pub provisioned_bytes: u64,
     pub compression_ratio: f64,
+    pub health: String,
 }
 
 #[derive(Debug, Clone)]
@@ -912,3 +1051,24 @@ pub struct SnapshotInfo {
     pub used_bytes: u64,
     pub creation_timestamp: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_arcstat() {
+        let stats = "\
+13 1 0x01 147 39984 5211356843 1018215541185
+name                            type data
+hits                            4    2147
+c_min                           4    1054867456
+c_max                           4    16877879296
+size                            4    421822400
+";
+        assert_eq!(parse_arcstat(stats, "c_max"), Some(16877879296));
+        assert_eq!(parse_arcstat(stats, "size"), Some(421822400));
+        assert_eq!(parse_arcstat(stats, "c"), None);
+        assert_eq!(parse_arcstat(stats, "name"), None);
+    }
+}
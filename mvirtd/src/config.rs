@@ -0,0 +1,320 @@
+//! mvirtd configuration file.
+//!
+//! One TOML file for all embedded daemons. Every key is optional; the
+//! defaults match the standalone daemons, so a host can switch from five
+//! units to mvirtd without moving state or reconfiguring clients:
+//!
+//! ```toml
+//! data_dir = "/var/lib/mvirt"
+//!
+//! [vmm]
+//! listen = "[::1]:50051"
+//! guest_image_dir = "/usr/share/mvirt/one"
+//!
+//! [net]
+//! backend = "ebpf"   # "net" for the legacy TUN-based daemon, "auto" to pick by host
+//! flow_ipfix_collector = "[2001:db8::1]:4739"
+//!
+//! [zfs]
+//! pool = "mvirt"
+//!
+//! [log]
+//! listen = "[::1]:50052"
+//!
+//! [log.storage]      # default: embedded store under data_dir
+//! backend = "clickhouse"
+//! clickhouse_url = "http://clickhouse:8123"
+//!
+//! [limits]           # per daemon: per-client rate, global in-flight cap
+//! requests_per_sec = 100
+//! max_concurrent = 64
+//!
+//! [dns]              # A/AAAA records for VMs and pods labelled dns-name
+//! enabled = true
+//! zone = "vms.example.com"
+//! server = "ns1.example.com"
+//! key_file = "/etc/mvirt/dns.key"
+//! ```
+
+use anyhow::{Context, Result};
+use mvirt_common::limits::LimitConfig;
+use mvirt_log::storage::BackendConfig;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Parent of each service's state directory (`vmm/`, `zfs/`, ...).
+    pub data_dir: PathBuf,
+    pub vmm: VmmConfig,
+    pub net: NetConfig,
+    pub zfs: ZfsConfig,
+    pub log: LogConfig,
+    /// Admission limits, applied to each embedded daemon's gRPC server
+    pub limits: LimitConfig,
+    pub dns: DnsConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("/var/lib/mvirt"),
+            vmm: VmmConfig::default(),
+            net: NetConfig::default(),
+            zfs: ZfsConfig::default(),
+            log: LogConfig::default(),
+            limits: LimitConfig::default(),
+            dns: DnsConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Load `path`, or the defaults if it doesn't exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => {
+                toml::from_str(&content).with_context(|| format!("parse {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                info!(path = %path.display(), "No config file, using defaults");
+                Ok(Self::default())
+            }
+            Err(e) => Err(e).with_context(|| format!("read {}", path.display())),
+        }
+    }
+
+    /// State directory of one service.
+    pub fn service_dir(&self, service: &str) -> PathBuf {
+        self.data_dir.join(service)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VmmConfig {
+    pub enabled: bool,
+    pub listen: String,
+    /// Recent console output kept in memory per VM, in KiB
+    pub console_buffer_kb: usize,
+    /// Spool console output to disk, rotating at this size in MiB (0 = off)
+    pub console_spool_mb: u64,
+    pub guest_image_dir: PathBuf,
+    pub default_guest_image: String,
+    /// Serve guest memory dumps for debugging
+    pub allow_memory_dump: bool,
+    /// VMs booting at the same time (0 = no limit)
+    pub max_parallel_starts: usize,
+    /// Seconds a started VM counts as booting
+    pub start_settle_secs: u64,
+    /// Seconds between drift checks of the store against the running
+    /// processes (0 = only on GetDrift requests)
+    pub drift_check_interval_secs: u64,
+}
+
+impl Default for VmmConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            listen: "[::1]:50051".to_string(),
+            console_buffer_kb: 256,
+            console_spool_mb: 0,
+            guest_image_dir: PathBuf::from("/usr/share/mvirt/one"),
+            default_guest_image: mvirt_vmm::guest_image::DEFAULT_GUEST_IMAGE.to_string(),
+            allow_memory_dump: false,
+            max_parallel_starts: mvirt_vmm::start_queue::DEFAULT_MAX_PARALLEL_STARTS,
+            start_settle_secs: mvirt_vmm::start_queue::DEFAULT_START_SETTLE.as_secs(),
+            drift_check_interval_secs: mvirt_vmm::drift::DEFAULT_CHECK_INTERVAL_SECS,
+        }
+    }
+}
+
+/// Which network daemon to embed. Both serve the same NetService API.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetBackend {
+    Ebpf,
+    Net,
+    /// Ebpf if the host can run it, net otherwise
+    Auto,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetConfig {
+    pub enabled: bool,
+    pub backend: NetBackend,
+    pub listen: String,
+    /// TUN device of the legacy `net` backend
+    pub tun_name: String,
+    /// MTU of routed VM traffic (legacy `net` backend); VMs get ICMP
+    /// too-big replies for larger packets
+    pub mtu: u16,
+    /// Guest TX descriptors handled per burst (legacy `net` backend)
+    pub tx_burst: usize,
+    /// Buffers each reactor's TUN RX pool may grow to under sustained load
+    /// (legacy `net` backend)
+    pub rx_buffer_max: usize,
+    /// CPUs to pin reactors to, one per router in turn; buffers go on the
+    /// CPU's NUMA node (legacy `net` backend)
+    pub reactor_cpus: Vec<usize>,
+    /// Routers whose huge page buffers are mapped at startup (legacy `net`
+    /// backend)
+    pub hugepage_prealloc: usize,
+    /// Network namespace to run uplink processing in, connected to the host
+    /// by a veth pair; unset keeps it in the host namespace (legacy `net`
+    /// backend)
+    pub netns: Option<String>,
+    /// IPv4 transfer network of the namespace's veth pair, e.g.
+    /// "169.254.200.0/30"; unset uses that default
+    pub netns_link: Option<String>,
+    /// IPv6 transfer network of the namespace's veth pair
+    pub netns_link6: Option<String>,
+    /// Upstream interface to answer ARP/ND on for the routed prefixes of
+    /// networks with neighbor proxying; unset disables it (legacy `net`
+    /// backend)
+    pub proxy_interface: Option<String>,
+    /// Drop traffic between networks that aren't peered, even where routes
+    /// connect them (legacy `net` backend)
+    pub strict_isolation: bool,
+    /// How often the ebpf backend exports flow logs, in seconds
+    pub flow_export_interval_secs: u64,
+    /// IPFIX collector for flow logs (ebpf backend); unset sends them to
+    /// mvirt-log only
+    pub flow_ipfix_collector: Option<SocketAddr>,
+    /// How often the ebpf backend reports what security groups in audit
+    /// mode would have dropped, in seconds
+    pub security_audit_interval_secs: u64,
+    /// ARP/DHCP/NDP responses per second and NIC (ebpf backend); 0
+    /// disables the limit
+    pub proto_responses_per_sec: u32,
+    /// Open DHCP transactions per NIC (ebpf backend)
+    pub dhcp_max_pending: usize,
+    /// How often orphaned vhost-user sockets (net) or TAP devices (ebpf)
+    /// are looked for and removed, in seconds
+    pub orphan_sweep_interval_secs: u64,
+    /// This host's /80 overlay prefix (ebpf backend); unset disables
+    /// tunnelling to NICs on other hosts
+    pub overlay_prefix: Option<String>,
+    /// Other hosts to route to, as `<overlay prefix>=<endpoint>` of their
+    /// net daemons (ebpf backend)
+    pub overlay_peers: Vec<String>,
+}
+
+impl Default for NetConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            backend: NetBackend::Ebpf,
+            listen: "[::1]:50054".to_string(),
+            tun_name: "mvirt0".to_string(),
+            mtu: mvirt_net::reactor::pmtu::DEFAULT_MTU,
+            tx_burst: mvirt_net::reactor::DEFAULT_TX_BURST,
+            rx_buffer_max: mvirt_net::reactor::rx_pool::DEFAULT_RX_BUFFER_MAX,
+            reactor_cpus: Vec::new(),
+            hugepage_prealloc: mvirt_net::hugepage::DEFAULT_HUGEPAGE_PREALLOC,
+            netns: None,
+            netns_link: None,
+            netns_link6: None,
+            proxy_interface: None,
+            strict_isolation: false,
+            flow_export_interval_secs: mvirt_ebpf::flowlog::DEFAULT_EXPORT_INTERVAL_SECS,
+            flow_ipfix_collector: None,
+            security_audit_interval_secs: mvirt_ebpf::security_audit::DEFAULT_REPORT_INTERVAL_SECS,
+            proto_responses_per_sec: mvirt_ebpf::proto_limits::DEFAULT_RESPONSES_PER_SEC,
+            dhcp_max_pending: mvirt_ebpf::proto_limits::DEFAULT_MAX_PENDING_DHCP,
+            orphan_sweep_interval_secs: mvirt_ebpf::orphans::DEFAULT_SWEEP_INTERVAL_SECS,
+            overlay_prefix: None,
+            overlay_peers: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ZfsConfig {
+    pub enabled: bool,
+    pub pool: String,
+    pub listen: String,
+}
+
+impl Default for ZfsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            pool: "mvirt".to_string(),
+            listen: "[::1]:50053".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub enabled: bool,
+    /// Address for log queries (CLI, UI). Embedded services log in-process.
+    pub listen: String,
+    /// Raft listen address of the single-node log cluster
+    pub raft_listen: String,
+    /// Where entries are stored
+    pub storage: BackendConfig,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            listen: "[::1]:50052".to_string(),
+            raft_listen: "127.0.0.1:7001".to_string(),
+            storage: BackendConfig::default(),
+        }
+    }
+}
+
+/// DNS server API the records are maintained through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DnsProvider {
+    /// Dynamic updates (RFC 2136) sent with `nsupdate`
+    Rfc2136,
+}
+
+/// External DNS records for VMs and pods. Needs the vmm and net services.
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DnsConfig {
+    pub enabled: bool,
+    /// Label holding the name a VM or pod is published under
+    pub label: String,
+    /// Zone the names must lie in; others are ignored
+    pub zone: String,
+    pub ttl: u32,
+    /// Marks the records of this host in their ownership TXT record; the
+    /// hostname if empty. Must differ between hosts sharing a zone.
+    pub owner_id: String,
+    pub provider: DnsProvider,
+    /// Primary server of the zone
+    pub server: String,
+    pub port: u16,
+    /// TSIG key file passed to `nsupdate -k`
+    pub key_file: Option<PathBuf>,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            label: "dns-name".to_string(),
+            zone: String::new(),
+            ttl: 60,
+            owner_id: String::new(),
+            provider: DnsProvider::Rfc2136,
+            server: "127.0.0.1".to_string(),
+            port: 53,
+            key_file: None,
+        }
+    }
+}
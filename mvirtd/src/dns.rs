@@ -0,0 +1,446 @@
+//! External DNS records for VMs and pods.
+//!
+//! A VM or pod labelled `dns-name=web.vms.example.com` gets A/AAAA records
+//! for the addresses of its NICs. Addresses come from the net service's
+//! `WatchAllocations` stream and are matched to VMs and pods by MAC.
+//!
+//! Each published name also carries a TXT record naming this host
+//! (`heritage=mvirt,owner=<id>`). Records are only changed or removed
+//! where that marker is present, so names managed by hand or by another
+//! host are left alone. The names this host published are kept in
+//! `<data_dir>/dns/published.json`, so records of VMs deleted while mvirtd
+//! was down are still cleaned up.
+//!
+//! Providers implement [`Provider`]; RFC 2136 dynamic updates are built
+//! in.
+
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::future::Future;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tonic::transport::Channel;
+use tracing::{debug, info, warn};
+
+use mvirt_vmm::net_proto::net_service_client::NetServiceClient;
+use mvirt_vmm::net_proto::{Allocation, AllocationEventType, WatchAllocationsRequest};
+use mvirt_vmm::proto::pod_service_client::PodServiceClient;
+use mvirt_vmm::proto::vm_service_client::VmServiceClient;
+use mvirt_vmm::proto::{ListPodsRequest, ListVmsRequest};
+
+use crate::config::{DnsConfig, DnsProvider};
+
+/// Labels are re-read this often even without allocation changes.
+const RESYNC_INTERVAL: Duration = Duration::from_secs(30);
+/// Allocation changes are batched for this long before a sync.
+const SETTLE_INTERVAL: Duration = Duration::from_secs(2);
+/// Delay before reconnecting a dropped allocation stream.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Published addresses per fully qualified name (no trailing dot).
+type Records = BTreeMap<String, BTreeSet<IpAddr>>;
+
+/// A DNS server API records are maintained through.
+pub trait Provider: Send + Sync + 'static {
+    /// Point `name` at exactly `addrs`, claiming it with the `owner` TXT
+    /// record. Fails if the name exists without that record.
+    fn upsert(
+        &self,
+        name: &str,
+        addrs: &BTreeSet<IpAddr>,
+        owner: &str,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Remove the A, AAAA and TXT records of `name` if `owner` holds it.
+    fn delete(&self, name: &str, owner: &str) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// RFC 2136 dynamic updates, sent with `nsupdate`. Ownership is checked
+/// with update prerequisites, so it holds even against concurrent writers.
+pub struct Rfc2136 {
+    server: String,
+    port: u16,
+    zone: String,
+    ttl: u32,
+    key_file: Option<PathBuf>,
+}
+
+/// Outcome of one `nsupdate` run whose prerequisites may not hold.
+enum Update {
+    Done,
+    PrereqFailed,
+}
+
+impl Rfc2136 {
+    fn script(&self, body: &str) -> String {
+        format!(
+            "server {} {}\nzone {}\n{}send\n",
+            self.server, self.port, self.zone, body
+        )
+    }
+
+    fn records(&self, name: &str, addrs: &BTreeSet<IpAddr>, owner: &str) -> String {
+        let mut body = String::new();
+        for addr in addrs {
+            let rtype = if addr.is_ipv4() { "A" } else { "AAAA" };
+            body.push_str(&format!(
+                "update add {}. {} {} {}\n",
+                name, self.ttl, rtype, addr
+            ));
+        }
+        body.push_str(&format!(
+            "update add {}. {} TXT \"{}\"\n",
+            name, self.ttl, owner
+        ));
+        body
+    }
+
+    async fn run(&self, script: String) -> Result<Update> {
+        let mut cmd = tokio::process::Command::new("nsupdate");
+        if let Some(key) = &self.key_file {
+            cmd.arg("-k").arg(key);
+        }
+        let mut child = cmd
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .context("spawn nsupdate")?;
+        let mut stdin = child.stdin.take().context("nsupdate stdin")?;
+        stdin.write_all(script.as_bytes()).await?;
+        drop(stdin);
+        let output = child.wait_with_output().await?;
+        if output.status.success() {
+            return Ok(Update::Done);
+        }
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // Rcodes of unmet prerequisites (RFC 2136, 2.2)
+        if ["NXRRSET", "YXDOMAIN", "YXRRSET", "NXDOMAIN"]
+            .iter()
+            .any(|rcode| stderr.contains(rcode))
+        {
+            return Ok(Update::PrereqFailed);
+        }
+        bail!("nsupdate failed: {}", stderr.trim())
+    }
+}
+
+impl Provider for Rfc2136 {
+    async fn upsert(&self, name: &str, addrs: &BTreeSet<IpAddr>, owner: &str) -> Result<()> {
+        // Ours already: replace the addresses
+        let update = format!(
+            "prereq yxrrset {name}. TXT \"{owner}\"\n\
+             update delete {name}. A\n\
+             update delete {name}. AAAA\n\
+             {}",
+            self.records(name, addrs, owner)
+        );
+        if let Update::Done = self.run(self.script(&update)).await? {
+            return Ok(());
+        }
+        // Unused: claim it
+        let claim = format!(
+            "prereq nxdomain {name}.\n{}",
+            self.records(name, addrs, owner)
+        );
+        match self.run(self.script(&claim)).await? {
+            Update::Done => Ok(()),
+            Update::PrereqFailed => bail!("{} exists and is not owned by this host", name),
+        }
+    }
+
+    async fn delete(&self, name: &str, owner: &str) -> Result<()> {
+        let update = format!(
+            "prereq yxrrset {name}. TXT \"{owner}\"\n\
+             update delete {name}. A\n\
+             update delete {name}. AAAA\n\
+             update delete {name}. TXT\n"
+        );
+        match self.run(self.script(&update)).await? {
+            Update::Done => Ok(()),
+            // Gone already, or taken over by someone else
+            Update::PrereqFailed => {
+                debug!(name = %name, "DNS record not owned by this host; left alone");
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Start the DNS controller against the local vmm and net services.
+pub fn start(
+    config: &DnsConfig,
+    data_dir: &Path,
+    vmm: Channel,
+    net: Channel,
+    shutdown: watch::Receiver<bool>,
+) -> Result<JoinHandle<()>> {
+    let zone = normalize(&config.zone);
+    if zone.is_empty() {
+        bail!("dns.zone must be set");
+    }
+    let owner_id = if config.owner_id.is_empty() {
+        std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .context("read hostname for dns.owner_id")?
+            .trim()
+            .to_string()
+    } else {
+        config.owner_id.clone()
+    };
+    let provider = match config.provider {
+        DnsProvider::Rfc2136 => Rfc2136 {
+            server: config.server.clone(),
+            port: config.port,
+            zone: zone.clone(),
+            ttl: config.ttl,
+            key_file: config.key_file.clone(),
+        },
+    };
+    std::fs::create_dir_all(data_dir)?;
+    let state_path = data_dir.join("published.json");
+    let published = load_published(&state_path)?;
+    info!(
+        zone = %zone,
+        owner = %owner_id,
+        published = published.len(),
+        "Starting DNS controller"
+    );
+
+    let controller = Controller {
+        provider,
+        label: config.label.clone(),
+        zone,
+        owner: format!("heritage=mvirt,owner={}", owner_id),
+        state_path,
+        published,
+        allocations: HashMap::new(),
+        vmm: VmServiceClient::new(vmm.clone()),
+        pods: PodServiceClient::new(vmm),
+        net: NetServiceClient::new(net),
+    };
+    Ok(tokio::spawn(controller.run(shutdown)))
+}
+
+struct Controller<P> {
+    provider: P,
+    label: String,
+    zone: String,
+    /// Content of the ownership TXT record
+    owner: String,
+    state_path: PathBuf,
+    /// Records as last written to the DNS server
+    published: Records,
+    /// Current allocations by NIC id
+    allocations: HashMap<String, Allocation>,
+    vmm: VmServiceClient<Channel>,
+    pods: PodServiceClient<Channel>,
+    net: NetServiceClient<Channel>,
+}
+
+impl<P: Provider> Controller<P> {
+    async fn run(mut self, mut shutdown: watch::Receiver<bool>) {
+        loop {
+            tokio::select! {
+                _ = self.watch() => {}
+                _ = shutdown.wait_for(|stop| *stop) => break,
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(RECONNECT_DELAY) => {}
+                _ = shutdown.wait_for(|stop| *stop) => break,
+            }
+        }
+        info!("DNS controller stopped");
+    }
+
+    /// Follow the allocation stream until it fails, syncing records along
+    /// the way.
+    async fn watch(&mut self) {
+        let request = WatchAllocationsRequest {
+            network_id: String::new(),
+            include_existing: true,
+        };
+        let mut stream = match self.net.watch_allocations(request).await {
+            Ok(resp) => resp.into_inner(),
+            Err(e) => {
+                warn!(error = %e, "DNS: allocation stream unavailable");
+                return;
+            }
+        };
+        // The stream replays every allocation first
+        self.allocations.clear();
+        let mut dirty = true;
+        let mut settle = tokio::time::interval(SETTLE_INTERVAL);
+        let mut resync = tokio::time::interval(RESYNC_INTERVAL);
+        resync.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                msg = stream.message() => match msg {
+                    Ok(Some(event)) => {
+                        let Some(alloc) = event.allocation else { continue };
+                        if event.r#type == AllocationEventType::Removed as i32 {
+                            self.allocations.remove(&alloc.nic_id);
+                        } else {
+                            self.allocations.insert(alloc.nic_id.clone(), alloc);
+                        }
+                        dirty = true;
+                    }
+                    Ok(None) => {
+                        warn!("DNS: allocation stream closed");
+                        return;
+                    }
+                    Err(e) => {
+                        warn!(error = %e, "DNS: allocation stream failed");
+                        return;
+                    }
+                },
+                _ = settle.tick(), if dirty => {
+                    dirty = false;
+                    self.sync().await;
+                }
+                _ = resync.tick() => self.sync().await,
+            }
+        }
+    }
+
+    /// Bring the DNS server in line with the labels and allocations.
+    async fn sync(&mut self) {
+        let desired = match self.desired().await {
+            Ok(records) => records,
+            Err(e) => {
+                // Without the VM list every record would look stale
+                warn!(error = %e, "DNS: listing VMs and pods failed");
+                return;
+            }
+        };
+        let mut changed = false;
+        for (name, addrs) in &desired {
+            if self.published.get(name) == Some(addrs) {
+                continue;
+            }
+            match self.provider.upsert(name, addrs, &self.owner).await {
+                Ok(()) => {
+                    info!(name = %name, addrs = ?addrs, "DNS record published");
+                    self.published.insert(name.clone(), addrs.clone());
+                    changed = true;
+                }
+                Err(e) => warn!(name = %name, error = %e, "DNS: publishing record failed"),
+            }
+        }
+        let stale: Vec<String> = self
+            .published
+            .keys()
+            .filter(|name| !desired.contains_key(*name))
+            .cloned()
+            .collect();
+        for name in stale {
+            match self.provider.delete(&name, &self.owner).await {
+                Ok(()) => {
+                    info!(name = %name, "DNS record removed");
+                    self.published.remove(&name);
+                    changed = true;
+                }
+                Err(e) => warn!(name = %name, error = %e, "DNS: removing record failed"),
+            }
+        }
+        if changed && let Err(e) = save_published(&self.state_path, &self.published) {
+            warn!(error = %e, "DNS: saving published records failed");
+        }
+    }
+
+    /// Records wanted for the labelled VMs and pods.
+    async fn desired(&mut self) -> Result<Records> {
+        let vms = self.vmm.list_vms(ListVmsRequest {}).await?.into_inner().vms;
+        let pods = self
+            .pods
+            .list_pods(ListPodsRequest {})
+            .await?
+            .into_inner()
+            .pods;
+
+        // MACs of each name
+        let mut names: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        let labelled = vms
+            .iter()
+            .filter_map(|vm| {
+                let config = vm.config.as_ref()?;
+                let macs = config
+                    .nics
+                    .iter()
+                    .filter_map(|nic| nic.mac.clone())
+                    .collect::<Vec<_>>();
+                Some((config.labels.get(&self.label)?, macs))
+            })
+            .chain(pods.iter().filter_map(|pod| {
+                let macs = Some(pod.nic_mac_address.clone())
+                    .filter(|mac| !mac.is_empty())
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                Some((pod.labels.get(&self.label)?, macs))
+            }));
+        for (value, macs) in labelled {
+            let name = normalize(value);
+            if !in_zone(&name, &self.zone) {
+                debug!(name = %name, zone = %self.zone, "DNS name outside the zone; skipped");
+                continue;
+            }
+            names
+                .entry(name)
+                .or_default()
+                .extend(macs.into_iter().map(|m| m.to_ascii_lowercase()));
+        }
+
+        let mut records = Records::new();
+        for (name, macs) in names {
+            let addrs: BTreeSet<IpAddr> = self
+                .allocations
+                .values()
+                .filter(|a| macs.contains(&a.mac_address.to_ascii_lowercase()))
+                .flat_map(|a| [&a.ipv4_address, &a.ipv6_address])
+                .filter_map(|addr| addr.parse().ok())
+                .collect();
+            if !addrs.is_empty() {
+                records.insert(name, addrs);
+            }
+        }
+        Ok(records)
+    }
+}
+
+/// Lowercase, without the trailing dot.
+fn normalize(name: &str) -> String {
+    name.trim().trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Whether `name` is a valid host name strictly inside `zone`.
+fn in_zone(name: &str, zone: &str) -> bool {
+    let valid = name.split('.').all(|l| {
+        !l.is_empty()
+            && l.len() <= 63
+            && !l.starts_with('-')
+            && !l.ends_with('-')
+            && l.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+    });
+    valid && name.len() <= 253 && name.ends_with(&format!(".{}", zone))
+}
+
+fn load_published(path: &Path) -> Result<Records> {
+    match std::fs::read(path) {
+        Ok(data) => {
+            serde_json::from_slice(&data).map_err(|e| anyhow!("parse {}: {}", path.display(), e))
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Records::new()),
+        Err(e) => Err(e).with_context(|| format!("read {}", path.display())),
+    }
+}
+
+fn save_published(path: &Path, records: &Records) -> Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    std::fs::write(&tmp, serde_json::to_vec_pretty(records)?)?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+//! In-process gRPC transport.
+//!
+//! Serves a tonic router over in-memory pipes and hands out a client
+//! channel to it, so embedded services talk to each other without a
+//! socket, TLS or a listen address.
+
+use hyper_util::rt::TokioIo;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, DuplexStream, ReadBuf};
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::server::{Connected, Router};
+use tonic::transport::{Channel, Endpoint, Uri};
+use tower::service_fn;
+use tracing::error;
+
+/// Per-direction buffer of each in-memory connection.
+const PIPE_BUFFER: usize = 64 * 1024;
+
+/// Server end of an in-memory connection.
+struct InProcessStream(DuplexStream);
+
+impl Connected for InProcessStream {
+    type ConnectInfo = ();
+
+    fn connect_info(&self) -> Self::ConnectInfo {}
+}
+
+impl AsyncRead for InProcessStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for InProcessStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.0).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.0).poll_shutdown(cx)
+    }
+}
+
+/// Serve `router` in the background and return a channel connected to it.
+/// The channel connects lazily and reconnects like a network one would.
+pub fn serve(name: &'static str, router: Router) -> Channel {
+    let (conn_tx, conn_rx) = mpsc::channel::<io::Result<InProcessStream>>(16);
+
+    tokio::spawn(async move {
+        if let Err(e) = router
+            .serve_with_incoming(ReceiverStream::new(conn_rx))
+            .await
+        {
+            error!(service = name, error = %e, "In-process gRPC server failed");
+        }
+    });
+
+    Endpoint::from_static("http://in-process.local").connect_with_connector_lazy(service_fn(
+        move |_: Uri| {
+            let conn_tx = conn_tx.clone();
+            async move {
+                let (client, server) = tokio::io::duplex(PIPE_BUFFER);
+                conn_tx
+                    .send(Ok(InProcessStream(server)))
+                    .await
+                    .map_err(|_| io::Error::other(format!("{} server stopped", name)))?;
+                Ok::<_, io::Error>(TokioIo::new(client))
+            }
+        },
+    ))
+}
@@ -0,0 +1,81 @@
+//! mvirtd - all-in-one mvirt host daemon.
+//!
+//! Runs mvirt-vmm, mvirt-ebpf (or the legacy mvirt-net), mvirt-zfs and
+//! mvirt-log in one process for single-host installs. Each service keeps
+//! its usual gRPC address so the CLI and UI work unchanged; audit logs go
+//! to the embedded log service over an in-process channel.
+
+mod config;
+mod dns;
+mod in_process;
+mod net_backend;
+mod services;
+
+use clap::Parser;
+use config::Config;
+use std::path::PathBuf;
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{error, info};
+
+#[derive(Parser)]
+#[command(name = "mvirtd")]
+#[command(about = "mvirt all-in-one host daemon (vmm, net, zfs, log)")]
+struct Args {
+    /// Config file (TOML); defaults are used if it doesn't exist
+    #[arg(short, long, default_value = "/etc/mvirt/mvirtd.toml")]
+    config: PathBuf,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    mvirt_log::tracing_setup::init("info", &["h2=warn"]);
+
+    let args = Args::parse();
+    let config = Config::load(&args.config)?;
+
+    info!(data_dir = %config.data_dir.display(), "mvirtd starting");
+    std::fs::create_dir_all(&config.data_dir)?;
+
+    let (shutdown_tx, shutdown_rx) = tokio::sync::watch::channel(false);
+    let mut tasks = Vec::new();
+
+    // Log first, so the others audit to it from their first event
+    let log = if config.log.enabled {
+        let (channel, task) = services::start_log(&config, shutdown_rx.clone()).await?;
+        tasks.push(task);
+        Some(channel)
+    } else {
+        None
+    };
+    if config.zfs.enabled {
+        tasks.push(services::start_zfs(&config, log.clone(), shutdown_rx.clone()).await?);
+    }
+    if config.net.enabled {
+        tasks.push(services::start_net(&config, log.clone(), shutdown_rx.clone()).await?);
+    }
+    if config.vmm.enabled {
+        tasks.push(services::start_vmm(&config, log, shutdown_rx.clone()).await?);
+    }
+    if config.dns.enabled {
+        tasks.push(services::start_dns(&config, shutdown_rx)?);
+    }
+
+    info!(services = tasks.len(), "mvirtd ready");
+
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = sigint.recv() => info!("Received SIGINT"),
+        _ = sigterm.recv() => info!("Received SIGTERM"),
+    }
+
+    let _ = shutdown_tx.send(true);
+    for task in tasks {
+        if let Err(e) = task.await {
+            error!(error = %e, "Service task failed");
+        }
+    }
+
+    info!("mvirtd stopped");
+    Ok(())
+}
@@ -0,0 +1,233 @@
+//! Choosing between the two network backends.
+//!
+//! mvirt-ebpf (TAP devices, TC programs) and the legacy mvirt-net
+//! (vhost-user, TUN) serve the same NetService API but keep separate
+//! stores. With `net.backend = "auto"` the backend is picked when mvirtd
+//! starts: ebpf where its programs are installed and the kernel loads
+//! them, net otherwise.
+//!
+//! When the chosen backend's store is empty and the other one has
+//! networks, they are copied over before the service starts, so a host
+//! keeps its networks, NICs and security groups across a switch. The old
+//! store is renamed to `networks.db.migrated` and no longer used. NICs
+//! keep their IDs, MACs and addresses but get the new backend's socket or
+//! TAP; VMs pick it up on their next start. Static routes, DHCP leases,
+//! peerings and floating IPs exist only in mvirt-net; a store holding any
+//! of them isn't migrated to ebpf, and with `auto` the host stays on net.
+//!
+//! The backend is chosen per host, not per network: both daemons would
+//! have to run side by side behind one NetService that routes every call
+//! by network, and NICs of the two datapaths couldn't reach each other on
+//! the same network.
+
+use anyhow::{Result, anyhow};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::config::{Config, NetBackend};
+
+const DB_FILE: &str = "networks.db";
+
+/// The backend to run for the configured one.
+pub fn resolve(configured: NetBackend) -> NetBackend {
+    match configured {
+        NetBackend::Auto => {
+            if mvirt_ebpf::ebpf_loader::EbpfManager::probe() {
+                info!("eBPF datapath available, using the ebpf network backend");
+                NetBackend::Ebpf
+            } else {
+                info!("eBPF datapath unavailable, using the net network backend");
+                NetBackend::Net
+            }
+        }
+        backend => backend,
+    }
+}
+
+/// State directory of a resolved backend.
+pub fn state_dir(config: &Config, backend: NetBackend) -> PathBuf {
+    match backend {
+        NetBackend::Net => config.service_dir("net"),
+        NetBackend::Ebpf | NetBackend::Auto => config.service_dir("ebpf"),
+    }
+}
+
+/// Copy the other backend's networks into `to`'s store if that is empty.
+/// Returns the number of rows copied.
+pub fn migrate(config: &Config, to: NetBackend) -> Result<usize> {
+    let from = match to {
+        NetBackend::Net => NetBackend::Ebpf,
+        NetBackend::Ebpf | NetBackend::Auto => NetBackend::Net,
+    };
+    let source = state_dir(config, from).join(DB_FILE);
+    if !source.exists() {
+        return Ok(0);
+    }
+    let target_dir = state_dir(config, to);
+    std::fs::create_dir_all(&target_dir)?;
+    let target = target_dir.join(DB_FILE);
+
+    let state = match from {
+        NetBackend::Net => mvirt_net::grpc::Storage::new(&source)
+            .and_then(|s| s.export_state())
+            .map_err(|e| anyhow!("read {}: {}", source.display(), e))?,
+        NetBackend::Ebpf | NetBackend::Auto => mvirt_ebpf::grpc::Storage::new(&source)
+            .and_then(|s| s.export_state())
+            .map_err(|e| anyhow!("read {}: {}", source.display(), e))?,
+    };
+    let state = convert(state, to)?;
+
+    let rows = match to {
+        NetBackend::Net => {
+            let storage = mvirt_net::grpc::Storage::new(&target)
+                .map_err(|e| anyhow!("open {}: {}", target.display(), e))?;
+            let existing = storage
+                .export_state()
+                .map_err(|e| anyhow!("read {}: {}", target.display(), e))?;
+            if mvirt_net::grpc::storage::snapshot_len(&existing) > 0 {
+                return Ok(0);
+            }
+            storage
+                .import_state(&state)
+                .map_err(|e| anyhow!("import into {}: {}", target.display(), e))?
+        }
+        NetBackend::Ebpf | NetBackend::Auto => {
+            let storage = mvirt_ebpf::grpc::Storage::new(&target)
+                .map_err(|e| anyhow!("open {}: {}", target.display(), e))?;
+            let existing = storage
+                .export_state()
+                .map_err(|e| anyhow!("read {}: {}", target.display(), e))?;
+            if mvirt_ebpf::grpc::storage::snapshot_len(&existing) > 0 {
+                return Ok(0);
+            }
+            storage
+                .import_state(&state)
+                .map_err(|e| anyhow!("import into {}: {}", target.display(), e))?
+        }
+    };
+
+    let mut retired = source.into_os_string();
+    retired.push(".migrated");
+    std::fs::rename(state_dir(config, from).join(DB_FILE), &retired)?;
+    info!(
+        from = ?from,
+        to = ?to,
+        rows,
+        "Migrated network definitions between backends"
+    );
+    Ok(rows)
+}
+
+/// Tables only mvirt-net has.
+const NET_ONLY_TABLES: &[&str] = &["routes", "leases", "peerings", "floating_ips"];
+
+/// Rewrite a snapshot of one backend's store for the other: NICs get the
+/// socket or TAP of `to`. Columns `to` doesn't have are dropped on import.
+/// Fails if the snapshot has rows of tables `to` doesn't have.
+fn convert(mut state: Value, to: NetBackend) -> Result<Value> {
+    if to != NetBackend::Net {
+        let unsupported: Vec<String> = NET_ONLY_TABLES
+            .iter()
+            .filter_map(|table| {
+                let rows = state[*table].as_array().map_or(0, Vec::len);
+                (rows > 0).then(|| format!("{} {}", rows, table.replace('_', " ")))
+            })
+            .collect();
+        if !unsupported.is_empty() {
+            return Err(anyhow!(
+                "the ebpf backend has no {}; remove them or keep net.backend = \"net\"",
+                unsupported.join(", ")
+            ));
+        }
+    }
+
+    if let Some(nics) = state["nics"].as_array_mut() {
+        for nic in nics.iter_mut().filter_map(Value::as_object_mut) {
+            let Some(id) = nic
+                .get("id")
+                .and_then(Value::as_str)
+                .and_then(|id| id.parse().ok())
+            else {
+                continue;
+            };
+            match to {
+                NetBackend::Net => {
+                    nic.remove("tap_name");
+                    nic.insert(
+                        "socket_path".to_string(),
+                        mvirt_net::grpc::manager::generate_socket_path(
+                            Path::new(mvirt_net::grpc::manager::SOCKET_DIR),
+                            &id,
+                        )
+                        .into(),
+                    );
+                }
+                NetBackend::Ebpf | NetBackend::Auto => {
+                    nic.remove("socket_path");
+                    nic.insert(
+                        "tap_name".to_string(),
+                        mvirt_ebpf::tap::tap_name_from_nic_id(&id).into(),
+                    );
+                }
+            }
+        }
+    }
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const NIC_ID: &str = "0b5c1a2e-6f1d-4c3b-9a8e-2d7f4e5a6b7c";
+
+    #[test]
+    fn test_convert_nics_between_backends() {
+        let net = json!({
+            "networks": [{"id": "n1"}],
+            "nics": [{"id": NIC_ID, "network_id": "n1", "socket_path": "/old.sock"}],
+            "routes": [],
+        });
+        let ebpf = convert(net, NetBackend::Ebpf).unwrap();
+        let nic = &ebpf["nics"][0];
+        assert_eq!(nic["tap_name"], "tap_0b5c1a2");
+        assert!(nic.get("socket_path").is_none());
+        assert_eq!(nic["network_id"], "n1");
+
+        let net = convert(ebpf, NetBackend::Net).unwrap();
+        let nic = &net["nics"][0];
+        assert_eq!(
+            nic["socket_path"],
+            format!(
+                "{}/nic-{}.sock",
+                mvirt_net::grpc::manager::SOCKET_DIR,
+                NIC_ID
+            )
+        );
+        assert!(nic.get("tap_name").is_none());
+    }
+
+    #[test]
+    fn test_convert_refuses_net_only_rows() {
+        let net = json!({
+            "networks": [{"id": "n1"}, {"id": "n2"}],
+            "routes": [{"id": "r1"}, {"id": "r2"}],
+            "leases": [],
+            "peerings": [{"id": "p1"}],
+            "floating_ips": [{"id": "f1"}],
+        });
+        let err = convert(net.clone(), NetBackend::Ebpf)
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("2 routes, 1 peerings, 1 floating ips"),
+            "{err}"
+        );
+        assert!(!err.contains("leases"), "{err}");
+
+        // mvirt-net keeps them
+        assert_eq!(convert(net.clone(), NetBackend::Net).unwrap(), net);
+    }
+}
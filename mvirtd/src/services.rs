@@ -0,0 +1,617 @@
+//! Startup of the embedded daemons.
+//!
+//! Mirrors each daemon's own `main`, with state under the shared data dir,
+//! audit logs going to the in-process log service, and one shutdown signal
+//! for all servers. Each `start_*` returns a task that runs the gRPC server
+//! and then the daemon's cleanup.
+
+use anyhow::{Context, Result, anyhow};
+use mraft::{NodeConfig, StorageBackend};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tonic::transport::{Channel, Endpoint, Server};
+use tracing::{error, info, warn};
+
+use mvirt_common::events::EventBus;
+use mvirt_common::limits::GrpcLimitLayer;
+use mvirt_common::request_id::GrpcRequestIdLayer;
+
+use crate::config::{Config, NetBackend};
+use crate::dns;
+use crate::in_process;
+use crate::net_backend;
+
+/// Resolves once shutdown is requested.
+async fn shutdown_requested(mut rx: watch::Receiver<bool>) {
+    let _ = rx.wait_for(|stop| *stop).await;
+}
+
+fn parse_addr(service: &str, addr: &str) -> Result<SocketAddr> {
+    addr.parse()
+        .with_context(|| format!("invalid {} listen address {}", service, addr))
+}
+
+/// Lazy client channel to an embedded service's gRPC address.
+fn local_channel(service: &str, listen: &str) -> Result<Channel> {
+    Ok(Endpoint::from_shared(format!("http://{}", listen))
+        .with_context(|| format!("invalid {} listen address {}", service, listen))?
+        .connect_lazy())
+}
+
+/// The host's event bus for an embedded service; the services run
+/// without it if it can't be set up.
+fn host_events(service: &str) -> Option<EventBus> {
+    EventBus::new(service)
+        .inspect_err(|e| warn!(service, error = %e, "No host event bus"))
+        .ok()
+}
+
+/// Start the single-node log service. Returns the in-process channel the
+/// other services audit to, and the task serving queries on `log.listen`.
+pub async fn start_log(
+    config: &Config,
+    shutdown: watch::Receiver<bool>,
+) -> Result<(Channel, JoinHandle<()>)> {
+    use mvirt_log::LogServiceServer;
+    use mvirt_log::server::{LogServer, start_node};
+    use mvirt_log::storage::{init_log_manager, open_backend};
+
+    let data_dir = config.service_dir("log");
+    std::fs::create_dir_all(&data_dir)?;
+    info!(data_dir = %data_dir.display(), "Initializing log service");
+
+    let backend = open_backend(&config.log.storage, &data_dir)
+        .await
+        .map_err(|e| anyhow!("open log storage: {}", e))?;
+    init_log_manager(backend);
+
+    let raft_db = data_dir.join("raft.db");
+    let bootstrap = !raft_db.exists();
+    let node_config = NodeConfig {
+        id: 1,
+        listen_addr: config.log.raft_listen.clone(),
+        peers: BTreeMap::new(),
+        storage: StorageBackend::Persistent { path: raft_db },
+        raft_config: None,
+    };
+    let store = start_node(node_config, bootstrap)
+        .await
+        .map_err(|e| anyhow!("start log raft node: {}", e))?;
+    let service = Arc::new(LogServer::new(store));
+
+    let channel = in_process::serve(
+        "log",
+        Server::builder().add_service(LogServiceServer::from_arc(service.clone())),
+    );
+
+    let addr = parse_addr("log", &config.log.listen)?;
+    info!(addr = %addr, "Starting log gRPC server (plain h2c)");
+    let handle = tokio::spawn(async move {
+        if let Err(e) = Server::builder()
+            .add_service(LogServiceServer::from_arc(service))
+            .serve_with_shutdown(addr, shutdown_requested(shutdown))
+            .await
+        {
+            error!(error = %e, "Log gRPC server error");
+        }
+    });
+
+    Ok((channel, handle))
+}
+
+pub async fn start_zfs(
+    config: &Config,
+    log: Option<Channel>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<JoinHandle<()>> {
+    use mvirt_zfs::audit::ZfsAuditLogger;
+    use mvirt_zfs::grpc::ZfsServiceImpl;
+    use mvirt_zfs::import::ImportManager;
+    use mvirt_zfs::proto::zfs_service_server::ZfsServiceServer;
+    use mvirt_zfs::store::Store;
+    use mvirt_zfs::zfs::ZfsManager;
+
+    let pool = config.zfs.pool.clone();
+    let state_dir = config.service_dir("zfs").display().to_string();
+    tokio::fs::create_dir_all(&state_dir).await?;
+    info!(pool = %pool, state_dir = %state_dir, "Initializing zfs service");
+
+    let store = Arc::new(Store::new(&state_dir).await?);
+    let zfs_manager = Arc::new(ZfsManager::new(pool.clone()));
+    zfs_manager
+        .ensure_pool_structure(&format!("{}/tmp", state_dir))
+        .await?;
+
+    let audit = Arc::new(match log {
+        Some(channel) => ZfsAuditLogger::with_channel(channel),
+        None => ZfsAuditLogger::new_noop(),
+    });
+    let import_manager = Arc::new(ImportManager::new(
+        pool,
+        state_dir,
+        Arc::clone(&store),
+        Arc::clone(&zfs_manager),
+        Arc::clone(&audit),
+    ));
+    let mut service = ZfsServiceImpl::new(store, Arc::clone(&zfs_manager), import_manager, audit);
+    if let Some(bus) = host_events("zfs") {
+        service = service.with_host_events(bus);
+    }
+
+    let addr = parse_addr("zfs", &config.zfs.listen)?;
+    info!(addr = %addr, "Starting zfs gRPC server");
+    let limits = GrpcLimitLayer::new(config.limits);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = Server::builder()
+            .layer(GrpcRequestIdLayer)
+            .layer(limits)
+            .add_service(ZfsServiceServer::new(service))
+            .serve_with_shutdown(addr, shutdown_requested(shutdown))
+            .await
+        {
+            error!(error = %e, "zfs gRPC server error");
+        }
+        zfs_manager.destroy_tmp_dataset().await;
+    }))
+}
+
+pub async fn start_net(
+    config: &Config,
+    log: Option<Channel>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<JoinHandle<()>> {
+    let addr = parse_addr("net", &config.net.listen)?;
+    let mut backend = net_backend::resolve(config.net.backend);
+    if let Err(e) = net_backend::migrate(config, backend) {
+        // Starting ebpf on an empty store would lose the networks; with
+        // auto, stay on net, which has them.
+        if config.net.backend != NetBackend::Auto || backend == NetBackend::Net {
+            return Err(e.context("migrate network definitions"));
+        }
+        warn!(error = %e, "Not migrating network definitions, staying on the net backend");
+        backend = NetBackend::Net;
+    }
+    match backend {
+        NetBackend::Ebpf | NetBackend::Auto => start_ebpf(config, addr, log, shutdown).await,
+        NetBackend::Net => start_legacy_net(config, addr, log, shutdown).await,
+    }
+}
+
+async fn start_ebpf(
+    config: &Config,
+    addr: SocketAddr,
+    log: Option<Channel>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<JoinHandle<()>> {
+    use mvirt_ebpf::audit::EbpfAuditLogger;
+    use mvirt_ebpf::ebpf_loader::EbpfManager;
+    use mvirt_ebpf::flowlog::{FlowLogConfig, FlowLogExporter};
+    use mvirt_ebpf::grpc::proto::net_service_server::NetServiceServer;
+    use mvirt_ebpf::grpc::{EbpfNetServiceImpl, Storage};
+    use mvirt_ebpf::nat;
+    use mvirt_ebpf::orphans::OrphanSweeper;
+    use mvirt_ebpf::proto_handler::ProtocolHandler;
+    use mvirt_ebpf::proto_limits::ProtoLimits;
+    use mvirt_ebpf::route_sync::{OverlayPrefix, Peer, RouteSync};
+    use mvirt_ebpf::rule_window::{DEFAULT_CHECK_INTERVAL_SECS, RuleWindowTimer};
+    use mvirt_ebpf::security_audit::{SecurityAuditConfig, SecurityAuditReporter};
+
+    let state_dir = net_backend::state_dir(config, NetBackend::Ebpf);
+    std::fs::create_dir_all(&state_dir)?;
+    info!(state_dir = %state_dir.display(), "Initializing net service (ebpf)");
+
+    let storage = Arc::new(
+        Storage::new(&state_dir.join("networks.db")).map_err(|e| anyhow!("net storage: {}", e))?,
+    );
+    nat::init_nftables().map_err(|e| anyhow!("init nftables: {}", e))?;
+    let ebpf = Arc::new(EbpfManager::load().map_err(|e| anyhow!("load eBPF programs: {}", e))?);
+
+    let audit = Arc::new(match log {
+        Some(channel) => EbpfAuditLogger::with_channel(channel),
+        None => EbpfAuditLogger::new_noop(),
+    });
+    let flow_log = FlowLogExporter::start(
+        Arc::clone(&ebpf),
+        Arc::clone(&audit),
+        FlowLogConfig {
+            interval: Duration::from_secs(config.net.flow_export_interval_secs.max(1)),
+            ipfix_collector: config.net.flow_ipfix_collector,
+            ..FlowLogConfig::default()
+        },
+    )
+    .await
+    .map_err(|e| anyhow!("start flow log exporter: {}", e))?;
+    let security_audit = SecurityAuditReporter::start(
+        Arc::clone(&ebpf),
+        Arc::clone(&audit),
+        SecurityAuditConfig {
+            interval: Duration::from_secs(config.net.security_audit_interval_secs.max(1)),
+            ..SecurityAuditConfig::default()
+        },
+    );
+    let rule_windows = RuleWindowTimer::start(
+        Arc::clone(&storage),
+        Arc::clone(&ebpf),
+        Duration::from_secs(DEFAULT_CHECK_INTERVAL_SECS),
+    );
+    let proto_handler = ProtocolHandler::new().with_limits(ProtoLimits {
+        responses_per_sec: config.net.proto_responses_per_sec,
+        max_pending_dhcp: config.net.dhcp_max_pending.max(1),
+        ..ProtoLimits::default()
+    });
+    let mut service = EbpfNetServiceImpl::new(
+        Arc::clone(&storage),
+        Arc::clone(&ebpf),
+        Arc::new(proto_handler),
+        Arc::clone(&audit),
+    )
+    .with_flow_logs(flow_log.nics())
+    .with_security_audit(security_audit.nics())
+    .with_rule_windows(rule_windows.nics());
+    if let Some(bus) = host_events("net") {
+        service = service.with_host_events(bus);
+    }
+    let mut route_sync = None;
+    if let Some(prefix) = &config.net.overlay_prefix {
+        let prefix: OverlayPrefix = prefix
+            .parse()
+            .map_err(|e| anyhow!("net.overlay_prefix: {}", e))?;
+        let peers = config
+            .net
+            .overlay_peers
+            .iter()
+            .map(|peer| peer.parse::<Peer>())
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow!("net.overlay_peers: {}", e))?;
+        service = service.with_overlay_prefix(prefix);
+        if !peers.is_empty() {
+            route_sync = Some(RouteSync::start(Arc::clone(&ebpf), peers, None));
+        }
+    }
+    if let Err(e) = service.recover_nics().await {
+        error!(error = %e, "Failed to recover NICs");
+    }
+    OrphanSweeper::sweep(&storage, &audit).await;
+    let orphan_sweeper = OrphanSweeper::start(
+        storage,
+        audit,
+        Duration::from_secs(config.net.orphan_sweep_interval_secs.max(1)),
+    );
+
+    info!(addr = %addr, "Starting net gRPC server");
+    let limits = GrpcLimitLayer::new(config.limits);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = Server::builder()
+            .layer(GrpcRequestIdLayer)
+            .layer(limits)
+            .add_service(NetServiceServer::new(service))
+            .serve_with_shutdown(addr, shutdown_requested(shutdown))
+            .await
+        {
+            error!(error = %e, "net gRPC server error");
+        }
+        flow_log.stop();
+        security_audit.stop();
+        rule_windows.stop();
+        orphan_sweeper.stop();
+        if let Some(route_sync) = route_sync {
+            route_sync.stop();
+        }
+        if let Err(e) = nat::cleanup_nftables() {
+            error!(error = %e, "Failed to cleanup nftables");
+        }
+    }))
+}
+
+async fn start_legacy_net(
+    config: &Config,
+    addr: SocketAddr,
+    log: Option<Channel>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<JoinHandle<()>> {
+    use mvirt_net::audit::NetAuditLogger;
+    use mvirt_net::grpc::proto::net_service_server::NetServiceServer;
+    use mvirt_net::grpc::validation::reallocate_reserved_addresses;
+    use mvirt_net::grpc::{NetServiceImpl, NetworkManager, Storage};
+    use mvirt_net::neighbor_proxy::NeighborProxy;
+    use mvirt_net::netns::{NetnsConfig, UplinkNamespace};
+    use mvirt_net::orphans::OrphanSweeper;
+    use mvirt_net::reactor::pmtu::MIN_MTU;
+    use mvirt_net::reactor_supervisor::{self, ReactorSupervisor};
+
+    let state_dir = net_backend::state_dir(config, NetBackend::Net);
+    std::fs::create_dir_all(&state_dir)?;
+    info!(state_dir = %state_dir.display(), "Initializing net service (legacy)");
+
+    let storage = Arc::new(
+        Storage::new(&state_dir.join("networks.db")).map_err(|e| anyhow!("net storage: {}", e))?,
+    );
+    if config.net.mtu < MIN_MTU {
+        return Err(anyhow!(
+            "net.mtu {} is below the minimum of {}",
+            config.net.mtu,
+            MIN_MTU
+        ));
+    }
+    if config.net.tx_burst == 0 {
+        return Err(anyhow!("net.tx_burst must be at least 1"));
+    }
+    let mut manager = NetworkManager::new(Arc::clone(&storage))
+        .with_mtu(config.net.mtu)
+        .with_tx_burst(config.net.tx_burst)
+        .with_rx_buffer_max(config.net.rx_buffer_max)
+        .with_reactor_cpus(config.net.reactor_cpus.clone())
+        .with_strict_isolation(config.net.strict_isolation);
+    if let Some(name) = &config.net.netns {
+        let mut netns = NetnsConfig::new(name.clone());
+        if let Some(link) = &config.net.netns_link {
+            netns = netns.with_link_v4(
+                link.parse()
+                    .map_err(|_| anyhow!("net.netns_link {} is not an IPv4 network", link))?,
+            );
+        }
+        if let Some(link) = &config.net.netns_link6 {
+            netns = netns.with_link_v6(
+                link.parse()
+                    .map_err(|_| anyhow!("net.netns_link6 {} is not an IPv6 network", link))?,
+            );
+        }
+        let uplink = UplinkNamespace::setup(&netns)
+            .await
+            .map_err(|e| anyhow!("set up uplink namespace {}: {}", name, e))?;
+        manager = manager.with_uplink_namespace(uplink);
+    }
+    if let Some(interface) = &config.net.proxy_interface {
+        let proxy = NeighborProxy::start(interface)
+            .map_err(|e| anyhow!("start neighbor proxy on {}: {}", interface, e))?;
+        manager = manager.with_neighbor_proxy(proxy);
+    }
+    let manager = Arc::new(manager);
+    manager.preallocate_hugepages(config.net.hugepage_prealloc);
+    manager
+        .init_tun(&config.net.tun_name)
+        .await
+        .map_err(|e| anyhow!("init TUN device {}: {}", config.net.tun_name, e))?;
+    let reallocations = reallocate_reserved_addresses(&storage).unwrap_or_else(|e| {
+        error!(error = %e, "Failed to check for reserved addresses");
+        Vec::new()
+    });
+    if let Err(e) = manager.recover_nics().await {
+        error!(error = %e, "Failed to recover NIC routers");
+    }
+    if let Err(e) = manager.sync_neighbor_proxy() {
+        error!(error = %e, "Failed to sync neighbor proxy");
+    }
+
+    let audit = Arc::new(match log {
+        Some(channel) => NetAuditLogger::with_channel(channel),
+        None => NetAuditLogger::new_noop(),
+    });
+    for r in &reallocations {
+        audit.address_reallocated(
+            &r.nic_id.to_string(),
+            &r.old.to_string(),
+            r.new.map(|a| a.to_string()).as_deref(),
+            r.reason,
+        );
+    }
+    OrphanSweeper::sweep(&storage, &audit);
+    let orphan_sweeper = OrphanSweeper::start(
+        Arc::clone(&storage),
+        Arc::clone(&audit),
+        Duration::from_secs(config.net.orphan_sweep_interval_secs.max(1)),
+    );
+    let reactor_supervisor = ReactorSupervisor::start(
+        Arc::clone(&manager),
+        Arc::clone(&audit),
+        Duration::from_secs(reactor_supervisor::DEFAULT_CHECK_INTERVAL_SECS),
+    );
+    let mut service = NetServiceImpl::new(storage, Arc::clone(&manager), audit);
+    if let Some(bus) = host_events("net") {
+        service = service.with_host_events(bus);
+    }
+
+    info!(addr = %addr, "Starting net gRPC server");
+    let limits = GrpcLimitLayer::new(config.limits);
+    Ok(tokio::spawn(async move {
+        if let Err(e) = Server::builder()
+            .layer(GrpcRequestIdLayer)
+            .layer(limits)
+            .add_service(NetServiceServer::new(service))
+            .serve_with_shutdown(addr, shutdown_requested(shutdown))
+            .await
+        {
+            error!(error = %e, "net gRPC server error");
+        }
+        orphan_sweeper.stop();
+        reactor_supervisor.stop();
+        if let Err(e) = manager.shutdown().await {
+            error!(error = %e, "Failed to shutdown network manager");
+        }
+    }))
+}
+
+pub async fn start_vmm(
+    config: &Config,
+    log: Option<Channel>,
+    shutdown: watch::Receiver<bool>,
+) -> Result<JoinHandle<()>> {
+    use mvirt_log::AuditLogger;
+    use mvirt_vmm::console::{ConsoleBufferConfig, ConsoleHub};
+    use mvirt_vmm::dependents::Dependents;
+    use mvirt_vmm::drift::{DriftChecker, DriftMonitor};
+    use mvirt_vmm::grpc::VmServiceImpl;
+    use mvirt_vmm::guest_image::GuestImageRegistry;
+    use mvirt_vmm::hypervisor::Hypervisor;
+    use mvirt_vmm::pod_service::PodServiceImpl;
+    use mvirt_vmm::proto::pod_service_server::PodServiceServer;
+    use mvirt_vmm::proto::vm_service_server::VmServiceServer;
+    use mvirt_vmm::store::VmStore;
+
+    let vmm = &config.vmm;
+    let data_dir = config.service_dir("vmm");
+    tokio::fs::create_dir_all(&data_dir).await?;
+    info!(data_dir = %data_dir.display(), "Initializing vmm service");
+
+    let store = Arc::new(
+        VmStore::new(&data_dir)
+            .await
+            .map_err(|e| anyhow!("vmm store: {}", e))?,
+    );
+    let (vm_events_tx, _) = tokio::sync::broadcast::channel::<mvirt_vmm::VmEvent>(64);
+    let hypervisor = Arc::new(
+        Hypervisor::new(data_dir.clone(), store.clone(), vm_events_tx.clone())
+            .await
+            .map_err(|e| anyhow!("hypervisor: {}", e))?
+            .with_start_limit(
+                vmm.max_parallel_starts,
+                Duration::from_secs(vmm.start_settle_secs),
+            ),
+    );
+    hypervisor
+        .recover_vms()
+        .await
+        .map_err(|e| anyhow!("recover VMs: {}", e))?;
+    let watcher_shutdown = hypervisor.clone().spawn_watcher();
+
+    let audit = Arc::new(match log {
+        Some(channel) => AuditLogger::with_channel(channel, "vmm"),
+        None => AuditLogger::new_noop(),
+    });
+
+    let console = Arc::new(ConsoleHub::new(
+        audit.clone(),
+        ConsoleBufferConfig {
+            capacity: vmm.console_buffer_kb * 1024,
+            spool_dir: (vmm.console_spool_mb > 0).then(|| data_dir.join("console")),
+            spool_limit: vmm.console_spool_mb * 1024 * 1024,
+        },
+    ));
+    let console_listener = console
+        .clone()
+        .spawn_event_listener(hypervisor.clone(), vm_events_tx.subscribe());
+    for vm in store
+        .list_all()
+        .await
+        .map_err(|e| anyhow!("list VMs: {}", e))?
+    {
+        if vm.state == mvirt_vmm::proto::VmState::Running
+            && let Some(path) = hypervisor.console_path(&vm.id).await
+        {
+            console.capture(&vm.id, &path).await;
+        }
+    }
+
+    // Cascading deletes and volume snapshots go to whichever of zfs and
+    // net run here
+    let zfs_channel = config
+        .zfs
+        .enabled
+        .then(|| local_channel("zfs", &config.zfs.listen))
+        .transpose()?;
+    let dependents = Dependents::new(
+        zfs_channel.clone(),
+        config
+            .net
+            .enabled
+            .then(|| local_channel("net", &config.net.listen))
+            .transpose()?,
+    );
+
+    let drift = Arc::new(DriftChecker::new(
+        store.clone(),
+        hypervisor.clone(),
+        audit.clone(),
+    ));
+    let drift_monitor = (vmm.drift_check_interval_secs > 0).then(|| {
+        DriftMonitor::start(
+            Arc::clone(&drift),
+            Duration::from_secs(vmm.drift_check_interval_secs),
+        )
+    });
+
+    // VM stops go out on the host's event bus, and NICs the net service
+    // deletes are detached from VMs
+    let host_events = host_events("vmm");
+    let host_event_publisher = host_events
+        .clone()
+        .map(|bus| mvirt_vmm::host_events::spawn_publisher(bus, vm_events_tx.subscribe()));
+    let vm_service = Arc::new(
+        VmServiceImpl::new(
+            store.clone(),
+            hypervisor.clone(),
+            audit.clone(),
+            vm_events_tx,
+            console,
+            dependents.clone(),
+        )
+        .with_memory_dump(vmm.allow_memory_dump)
+        .with_drift(drift)
+        .with_volumes(zfs_channel),
+    );
+    let host_event_listener = match host_events.as_ref().map(EventBus::subscribe) {
+        Some(Ok(subscription)) => Some(mvirt_vmm::host_events::spawn_listener(
+            vm_service.clone(),
+            subscription,
+        )),
+        Some(Err(e)) => {
+            warn!(error = %e, "Failed to subscribe to host events");
+            None
+        }
+        None => None,
+    };
+    let guest_images =
+        GuestImageRegistry::new(vmm.guest_image_dir.clone(), vmm.default_guest_image.clone());
+    if guest_images.resolve(None).is_none() {
+        warn!(
+            default = %guest_images.default_name(),
+            "Default pod guest image is not installed; pods must name one"
+        );
+    }
+    // Pod starts are jobs of mvirt-vmm like VM starts
+    let pod_service = PodServiceImpl::new(store, hypervisor, audit, guest_images, dependents)
+        .with_jobs(vm_service.jobs().clone());
+
+    let addr = parse_addr("vmm", &vmm.listen)?;
+    info!(addr = %addr, "Starting vmm gRPC server");
+    let limits = GrpcLimitLayer::new(config.limits);
+    Ok(tokio::spawn(async move {
+        // Held until the server stops
+        let _watcher_shutdown = watcher_shutdown;
+        let _console_listener = console_listener;
+        let _host_event_publisher = host_event_publisher;
+        let _host_event_listener = host_event_listener;
+        if let Err(e) = Server::builder()
+            .layer(GrpcRequestIdLayer)
+            .layer(limits)
+            .add_service(VmServiceServer::from_arc(vm_service))
+            .add_service(PodServiceServer::new(pod_service))
+            .serve_with_shutdown(addr, shutdown_requested(shutdown))
+            .await
+        {
+            error!(error = %e, "vmm gRPC server error");
+        }
+        if let Some(drift_monitor) = drift_monitor {
+            drift_monitor.stop();
+        }
+    }))
+}
+
+/// Start the external DNS controller. It talks to the vmm and net services
+/// over their gRPC addresses like any other client.
+pub fn start_dns(config: &Config, shutdown: watch::Receiver<bool>) -> Result<JoinHandle<()>> {
+    if !config.vmm.enabled || !config.net.enabled {
+        return Err(anyhow!("dns needs the vmm and net services enabled"));
+    }
+    dns::start(
+        &config.dns,
+        &config.service_dir("dns"),
+        local_channel("vmm", &config.vmm.listen)?,
+        local_channel("net", &config.net.listen)?,
+        shutdown,
+    )
+}